@@ -0,0 +1,131 @@
+//! Address discovery windows of UTXO accounts
+//!
+//! Each account (a `wallet_xpubs` row) remembers how many address indexes
+//! its last sync scanned and the highest index that had activity, so the next
+//! sync starts at that size instead of growing the window from the default
+//! gap limit again. Rows go away with their xpub.
+
+use rusqlite::{Connection, OptionalExtension};
+use crate::errors::{DatabaseError, Result};
+use crate::types::AccountScanState;
+use crate::Database;
+
+const SCAN_STATE_QUERY: &str =
+    "SELECT x.id, x.device_id, x.wallet_fingerprint, x.path, x.caip,
+            COALESCE(s.window_size, 0), s.high_water, COALESCE(s.ceiling_reached, 0), s.updated_at
+     FROM wallet_xpubs x LEFT JOIN account_scan_state s ON s.xpub_id = x.id";
+
+/// UTXO accounts only; account-based chains have no address gap
+const UTXO_FILTER: &str = "x.caip LIKE 'bip122:%'";
+
+fn scan_state_from_row(row: &rusqlite::Row) -> rusqlite::Result<AccountScanState> {
+    Ok(AccountScanState {
+        account_id: row.get(0)?,
+        device_id: row.get(1)?,
+        wallet_fingerprint: row.get(2)?,
+        path: row.get(3)?,
+        caip: row.get(4)?,
+        window_size: row.get(5)?,
+        high_water: row.get(6)?,
+        ceiling_reached: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+fn read_scan_state(conn: &Connection, account_id: i64) -> rusqlite::Result<Option<AccountScanState>> {
+    conn.query_row(
+        &format!("{} WHERE x.id = ?1 AND {}", SCAN_STATE_QUERY, UTXO_FILTER),
+        [account_id],
+        scan_state_from_row,
+    ).optional()
+}
+
+impl Database {
+    /// Scan state of every UTXO account of a device, across its wallets
+    pub async fn get_account_scan_states(&self, device_id: &str) -> Result<Vec<AccountScanState>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "{} WHERE x.device_id = ?1 AND {} ORDER BY x.wallet_fingerprint, x.path, x.caip",
+                SCAN_STATE_QUERY, UTXO_FILTER
+            ))?;
+            let states = stmt
+                .query_map([device_id], scan_state_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(states)
+        }).await
+    }
+
+    /// Scan state of one UTXO account, or None if there is no such account
+    pub async fn get_account_scan_state(&self, account_id: i64) -> Result<Option<AccountScanState>> {
+        self.with_connection(|conn| Ok(read_scan_state(conn, account_id)?)).await
+    }
+
+    /// Store an account's scanned window. The high-water mark only moves up.
+    pub async fn save_account_scan_state(
+        &self,
+        account_id: i64,
+        window_size: u32,
+        high_water: Option<u32>,
+        ceiling_reached: bool,
+    ) -> Result<AccountScanState> {
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            let Some(current) = read_scan_state(conn, account_id)? else {
+                return Err(DatabaseError::Validation(format!("No UTXO account with id {}", account_id)));
+            };
+            let high_water = current.high_water.max(high_water);
+            conn.execute(
+                "INSERT INTO account_scan_state (xpub_id, window_size, high_water, ceiling_reached, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(xpub_id) DO UPDATE SET
+                    window_size = excluded.window_size, high_water = excluded.high_water,
+                    ceiling_reached = excluded.ceiling_reached, updated_at = excluded.updated_at",
+                rusqlite::params![account_id, window_size, high_water, ceiling_reached, now],
+            )?;
+            Ok(AccountScanState { window_size, high_water, ceiling_reached, updated_at: Some(now), ..current })
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::WalletXpubInput;
+
+    async fn seed_account(db: &Database, caip: &str, path: &str) -> i64 {
+        db.save_wallet_xpub(&WalletXpubInput {
+            device_id: "dev1".to_string(),
+            wallet_fingerprint: "fp1".to_string(),
+            path: path.to_string(),
+            label: "Account".to_string(),
+            caip: caip.to_string(),
+            pubkey: format!("xpub-{}", path),
+        }).await.unwrap();
+        db.get_wallet_xpubs("dev1", "fp1").await.unwrap().into_iter().find(|x| x.path == path).unwrap().id
+    }
+
+    #[tokio::test]
+    async fn test_scan_state_keeps_high_water() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.with_connection(|conn| {
+            conn.execute("INSERT INTO devices (device_id, first_seen, last_seen) VALUES ('dev1', 1, 1)", [])?;
+            Ok(())
+        }).await.unwrap();
+        let btc = seed_account(&db, "bip122:000000000019d6689c085ae165831e93/slip44:0", "m/84'/0'/0'").await;
+        let eth = seed_account(&db, "eip155:1/slip44:60", "m/44'/60'/0'").await;
+
+        let states = db.get_account_scan_states("dev1").await.unwrap();
+        assert_eq!(states.len(), 1);
+        assert_eq!((states[0].account_id, states[0].window_size, states[0].high_water), (btc, 0, None));
+
+        db.save_account_scan_state(btc, 40, None, false).await.unwrap();
+        assert_eq!(db.get_account_scan_state(btc).await.unwrap().unwrap().high_water, None);
+        db.save_account_scan_state(btc, 60, Some(57), false).await.unwrap();
+        // A later sync that saw less activity does not lower the mark
+        let state = db.save_account_scan_state(btc, 60, Some(12), true).await.unwrap();
+        assert_eq!((state.window_size, state.high_water, state.ceiling_reached), (60, Some(57), true));
+
+        assert!(db.save_account_scan_state(eth, 20, None, false).await.is_err());
+        assert!(db.get_account_scan_state(eth).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,45 @@
+use crate::errors::Result;
+use crate::types::ActivityEntry;
+use crate::Database;
+
+impl Database {
+    /// Append an entry to the activity log
+    pub async fn log_activity(&self, category: &str, message: &str, details: Option<&serde_json::Value>) -> Result<()> {
+        let now = Self::current_timestamp();
+        let details_json = details.map(serde_json::to_string).transpose()?;
+
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO activity_log (timestamp, category, message, details_json) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![now, category, message, details_json],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    /// Most recent activity entries, optionally for a single category
+    pub async fn get_activity_log(&self, limit: usize, category: Option<&str>) -> Result<Vec<ActivityEntry>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, timestamp, category, message, details_json
+                 FROM activity_log
+                 WHERE ?1 IS NULL OR category = ?1
+                 ORDER BY timestamp DESC, id DESC
+                 LIMIT ?2"
+            )?;
+
+            let entries = stmt.query_map(rusqlite::params![category, limit as i64], |row| {
+                Ok(ActivityEntry {
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    category: row.get(2)?,
+                    message: row.get(3)?,
+                    details_json: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            Ok(entries)
+        }).await
+    }
+}
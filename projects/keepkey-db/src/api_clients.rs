@@ -0,0 +1,155 @@
+//! REST API clients paired through a one-time code
+//!
+//! Each client holds one bearer token; only its hash is stored, so the token
+//! cannot be recovered from the database and a lost token means pairing
+//! again. Revoked and expired clients stay in the table so a request with
+//! their token can be told why it was refused.
+
+use rusqlite::{Connection, OptionalExtension, Row};
+use crate::errors::{DatabaseError, Result};
+use crate::types::{ApiClient, ApiScope};
+use crate::Database;
+
+const CLIENT_COLUMNS: &str = "id, name, scope, created_at, last_used_at, expires_at, revoked_at";
+
+fn client_from_row(row: &Row) -> rusqlite::Result<ApiClient> {
+    Ok(ApiClient {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        scope: ApiScope::from_db(&row.get::<_, String>(2)?),
+        created_at: row.get(3)?,
+        last_used_at: row.get(4)?,
+        expires_at: row.get(5)?,
+        revoked_at: row.get(6)?,
+    })
+}
+
+fn load_client(conn: &Connection, id: i64) -> rusqlite::Result<Option<ApiClient>> {
+    conn.query_row(
+        &format!("SELECT {} FROM api_clients WHERE id = ?1", CLIENT_COLUMNS),
+        [id],
+        client_from_row,
+    ).optional()
+}
+
+impl Database {
+    /// Store a newly paired client; `token_hash` is the hex hash of its token
+    pub async fn insert_api_client(
+        &self,
+        name: &str,
+        token_hash: &str,
+        scope: ApiScope,
+        expires_at: i64,
+    ) -> Result<ApiClient> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(DatabaseError::Validation("Client name cannot be empty".to_string()));
+        }
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO api_clients (name, token_hash, scope, created_at, expires_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![name, token_hash, scope.as_str(), now, expires_at],
+            )?;
+            Ok(conn.query_row(
+                &format!("SELECT {} FROM api_clients WHERE id = ?1", CLIENT_COLUMNS),
+                [conn.last_insert_rowid()],
+                client_from_row,
+            )?)
+        }).await
+    }
+
+    /// The client holding a token, including revoked and expired ones
+    pub async fn find_api_client_by_token_hash(&self, token_hash: &str) -> Result<Option<ApiClient>> {
+        self.with_connection(|conn| {
+            Ok(conn.query_row(
+                &format!("SELECT {} FROM api_clients WHERE token_hash = ?1", CLIENT_COLUMNS),
+                [token_hash],
+                client_from_row,
+            ).optional()?)
+        }).await
+    }
+
+    pub async fn get_api_client(&self, id: i64) -> Result<Option<ApiClient>> {
+        self.with_connection(|conn| Ok(load_client(conn, id)?)).await
+    }
+
+    /// Record that a client made an authenticated request
+    pub async fn touch_api_client(&self, id: i64) -> Result<()> {
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            conn.execute("UPDATE api_clients SET last_used_at = ?1 WHERE id = ?2", rusqlite::params![now, id])?;
+            Ok(())
+        }).await
+    }
+
+    /// All paired clients, newest first
+    pub async fn list_api_clients(&self) -> Result<Vec<ApiClient>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                &format!("SELECT {} FROM api_clients ORDER BY created_at DESC, id DESC", CLIENT_COLUMNS)
+            )?;
+            let clients = stmt.query_map([], client_from_row)?.collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(clients)
+        }).await
+    }
+
+    /// Revoke a client's token; false when the client does not exist or was already revoked
+    pub async fn revoke_api_client(&self, id: i64) -> Result<bool> {
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            let changed = conn.execute(
+                "UPDATE api_clients SET revoked_at = ?1 WHERE id = ?2 AND revoked_at IS NULL",
+                rusqlite::params![now, id],
+            )?;
+            Ok(changed > 0)
+        }).await
+    }
+
+    /// Change a live client's scope; false when it does not exist or was revoked
+    pub async fn set_api_client_scope(&self, id: i64, scope: ApiScope) -> Result<bool> {
+        self.with_connection(|conn| {
+            let changed = conn.execute(
+                "UPDATE api_clients SET scope = ?1 WHERE id = ?2 AND revoked_at IS NULL",
+                rusqlite::params![scope.as_str(), id],
+            )?;
+            Ok(changed > 0)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pair_touch_and_revoke() {
+        let db = Database::new_in_memory().await.unwrap();
+        assert!(db.insert_api_client("  ", "hash0", ApiScope::Read, 0).await.is_err());
+
+        let client = db.insert_api_client("portfolio-cli", "hash1", ApiScope::Read, 2_000_000_000).await.unwrap();
+        assert_eq!(client.name, "portfolio-cli");
+        assert_eq!(client.scope, ApiScope::Read);
+        assert_eq!(client.last_used_at, None);
+        // Token hashes are unique
+        assert!(db.insert_api_client("other", "hash1", ApiScope::Read, 0).await.is_err());
+
+        let found = db.find_api_client_by_token_hash("hash1").await.unwrap().unwrap();
+        assert_eq!(found.id, client.id);
+        assert!(db.find_api_client_by_token_hash("nope").await.unwrap().is_none());
+
+        db.touch_api_client(client.id).await.unwrap();
+        assert!(db.get_api_client(client.id).await.unwrap().unwrap().last_used_at.is_some());
+
+        assert!(db.set_api_client_scope(client.id, ApiScope::Sign).await.unwrap());
+        assert_eq!(db.list_api_clients().await.unwrap()[0].scope, ApiScope::Sign);
+
+        assert!(db.revoke_api_client(client.id).await.unwrap());
+        assert!(!db.revoke_api_client(client.id).await.unwrap());
+        assert!(!db.set_api_client_scope(client.id, ApiScope::Read).await.unwrap());
+        // Revoked clients are still found, so the caller can say why a token was refused
+        let revoked = db.find_api_client_by_token_hash("hash1").await.unwrap().unwrap();
+        assert!(revoked.revoked_at.is_some());
+        assert!(!db.revoke_api_client(999).await.unwrap());
+    }
+}
@@ -1,7 +1,117 @@
+use crate::custom_tokens::USER_ASSET_SOURCE;
+use crate::errors::Result;
+use crate::types::{Asset, AssetInput, NetworkEndpoints, NetworkFinality, NetworkReserve};
 use crate::Database;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Deserialize;
+
+/// Source of registry rows that discovery and the bundled seed may refresh;
+/// rows with any other source are left as they are
+pub const DISCOVERY_ASSET_SOURCE: &str = "pioneer-discovery";
+
+/// Native assets shipped with the app, seeded on first run
+const DEFAULT_ASSETS: &str = include_str!("../data/default_assets.json");
+
+/// meta key holding the version of the last bundle seeded
+const ASSET_SEED_VERSION_KEY: &str = "asset_seed_version";
+
+#[derive(Deserialize)]
+struct AssetBundle {
+    version: u32,
+    assets: Vec<AssetInput>,
+}
+
+/// Columns read by `asset_from_row`, in order
+const ASSET_COLUMNS: &str =
+    "id, caip, network_id, chain_id, symbol, name, asset_type, is_native, contract_address, token_id, icon,
+     color, decimals, precision, network_name, native_asset_caip, explorer, explorer_address_link,
+     explorer_tx_link, coin_gecko_id, chain_reference, tags, source, is_verified, created_at, last_updated";
+
+fn asset_from_row(row: &rusqlite::Row) -> rusqlite::Result<Asset> {
+    Ok(Asset {
+        id: row.get(0)?,
+        caip: row.get(1)?,
+        network_id: row.get(2)?,
+        chain_id: row.get(3)?,
+        symbol: row.get(4)?,
+        name: row.get(5)?,
+        asset_type: row.get(6)?,
+        is_native: row.get::<_, Option<bool>>(7)?.unwrap_or(false),
+        contract_address: row.get(8)?,
+        token_id: row.get(9)?,
+        icon: row.get(10)?,
+        color: row.get(11)?,
+        decimals: row.get(12)?,
+        precision: row.get(13)?,
+        network_name: row.get(14)?,
+        native_asset_caip: row.get(15)?,
+        explorer: row.get(16)?,
+        explorer_address_link: row.get(17)?,
+        explorer_tx_link: row.get(18)?,
+        coin_gecko_id: row.get(19)?,
+        chain_reference: row.get(20)?,
+        tags: row.get(21)?,
+        source: row.get::<_, Option<String>>(22)?.unwrap_or_else(|| DISCOVERY_ASSET_SOURCE.to_string()),
+        is_verified: row.get::<_, Option<bool>>(23)?.unwrap_or(false),
+        created_at: row.get(24)?,
+        last_updated: row.get(25)?,
+    })
+}
+
+/// Write assets without overwriting rows from another source; returns the
+/// number of rows inserted or refreshed
+fn upsert_assets_in(conn: &Connection, assets: &[AssetInput], now: i64) -> rusqlite::Result<usize> {
+    let mut stmt = conn.prepare(
+        "INSERT INTO assets (caip, network_id, chain_id, symbol, name, asset_type, is_native, contract_address,
+                             icon, color, decimals, precision, network_name, native_asset_caip, explorer,
+                             explorer_address_link, explorer_tx_link, coin_gecko_id, source, is_verified,
+                             created_at, last_updated)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?21)
+         ON CONFLICT(caip) DO UPDATE SET
+             network_id = excluded.network_id, chain_id = excluded.chain_id, symbol = excluded.symbol,
+             name = excluded.name, asset_type = excluded.asset_type, is_native = excluded.is_native,
+             contract_address = excluded.contract_address, icon = excluded.icon, color = excluded.color,
+             decimals = excluded.decimals, precision = excluded.precision, network_name = excluded.network_name,
+             native_asset_caip = excluded.native_asset_caip, explorer = excluded.explorer,
+             explorer_address_link = excluded.explorer_address_link, explorer_tx_link = excluded.explorer_tx_link,
+             coin_gecko_id = excluded.coin_gecko_id, source = excluded.source,
+             is_verified = excluded.is_verified, last_updated = excluded.last_updated
+         WHERE COALESCE(assets.source, ?22) = ?22"
+    )?;
+    let mut written = 0;
+    for asset in assets {
+        let source = asset.source.as_deref().unwrap_or(DISCOVERY_ASSET_SOURCE);
+        written += stmt.execute(rusqlite::params![
+            asset.caip,
+            asset.network_id,
+            asset.chain_id,
+            asset.symbol,
+            asset.name,
+            asset.asset_type,
+            asset.asset_type == "native",
+            asset.contract_address,
+            asset.icon,
+            asset.color,
+            asset.decimals,
+            asset.precision,
+            asset.network_name,
+            asset.native_asset_caip,
+            asset.explorer,
+            asset.explorer_address_link,
+            asset.explorer_tx_link,
+            asset.coin_gecko_id,
+            source,
+            source != USER_ASSET_SOURCE,
+            now,
+            DISCOVERY_ASSET_SOURCE,
+        ])?;
+    }
+    Ok(written)
+}
 
 /// Assets manager - handles asset metadata and network information
 pub struct Assets {
+    #[allow(dead_code)]
     db: Database,
 }
 
@@ -11,4 +121,206 @@ impl Assets {
     }
 
     // Asset methods can be added here
-} 
\ No newline at end of file
+}
+
+impl Database {
+    /// Insert or refresh registry assets in one transaction. Existing rows are
+    /// only refreshed while their source is 'pioneer-discovery', so assets the
+    /// user added keep their values. Returns the number of rows written.
+    pub async fn upsert_assets(&self, assets: Vec<AssetInput>) -> Result<usize> {
+        let now = Self::current_timestamp();
+        self.transaction(move |tx| Ok(upsert_assets_in(tx, &assets, now)?)).await
+    }
+
+    /// A registry asset by CAIP
+    pub async fn get_asset_by_caip(&self, caip: &str) -> Result<Option<Asset>> {
+        self.with_connection(|conn| {
+            Ok(conn.query_row(
+                &format!("SELECT {} FROM assets WHERE caip = ?1", ASSET_COLUMNS),
+                [caip],
+                asset_from_row,
+            ).optional()?)
+        }).await
+    }
+
+    /// Seed the bundled native assets when the bundle is newer than the last
+    /// one seeded. Safe to run on every launch; returns the rows written,
+    /// 0 when the registry is already up to date.
+    pub async fn seed_default_assets(&self) -> Result<usize> {
+        let bundle: AssetBundle = serde_json::from_str(DEFAULT_ASSETS)?;
+        let now = Self::current_timestamp();
+        self.transaction(move |tx| {
+            let seeded: Option<u32> = tx.query_row(
+                "SELECT val FROM meta WHERE key = ?1",
+                [ASSET_SEED_VERSION_KEY],
+                |row| row.get::<_, Option<String>>(0),
+            ).optional()?.flatten().and_then(|v| v.parse().ok());
+            if seeded.is_some_and(|version| version >= bundle.version) {
+                return Ok(0);
+            }
+
+            let written = upsert_assets_in(tx, &bundle.assets, now)?;
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, val) VALUES (?1, ?2)",
+                rusqlite::params![ASSET_SEED_VERSION_KEY, bundle.version.to_string()],
+            )?;
+            log::info!("Seeded {} default assets (bundle v{})", written, bundle.version);
+            Ok(written)
+        }).await
+    }
+
+    /// Get decimals for an asset, if it is in the registry
+    pub async fn get_asset_decimals(&self, caip: &str) -> Result<Option<u32>> {
+        self.with_connection(|conn| {
+            let decimals = conn.query_row(
+                "SELECT decimals FROM assets WHERE caip = ?1",
+                [caip],
+                |row| row.get::<_, Option<u32>>(0),
+            ).optional()?;
+            Ok(decimals.flatten())
+        }).await
+    }
+
+    /// Get reserve requirements configured for a network
+    pub async fn get_network_reserve(&self, network_id: &str) -> Result<Option<NetworkReserve>> {
+        self.with_connection(|conn| {
+            let reserve = conn.query_row(
+                "SELECT base_reserve, reserve_per_item, recommended_gas_buffer FROM networks WHERE network_id = ?1",
+                [network_id],
+                |row| Ok(NetworkReserve {
+                    base_reserve: row.get(0)?,
+                    reserve_per_item: row.get(1)?,
+                    recommended_gas_buffer: row.get(2)?,
+                }),
+            ).optional()?;
+            Ok(reserve)
+        }).await
+    }
+
+    /// RPC and explorer endpoints configured for a network
+    pub async fn get_network_endpoints(&self, network_id: &str) -> Result<Option<NetworkEndpoints>> {
+        self.with_connection(|conn| {
+            let endpoints = conn.query_row(
+                "SELECT rpc_urls, explorer_api_url, supports_eip1559 FROM networks WHERE network_id = ?1",
+                [network_id],
+                |row| {
+                    let rpc_urls: Option<String> = row.get(0)?;
+                    Ok(NetworkEndpoints {
+                        // Stored as a JSON array of URLs
+                        rpc_urls: rpc_urls
+                            .and_then(|urls| serde_json::from_str(&urls).ok())
+                            .unwrap_or_default(),
+                        explorer_api_url: row.get(1)?,
+                        supports_eip1559: row.get(2)?,
+                    })
+                },
+            ).optional()?;
+            Ok(endpoints)
+        }).await
+    }
+
+    /// Network type and configured finality depth
+    pub async fn get_network_finality(&self, network_id: &str) -> Result<Option<NetworkFinality>> {
+        self.with_connection(|conn| {
+            let finality = conn.query_row(
+                "SELECT network_type, finality_depth FROM networks WHERE network_id = ?1",
+                [network_id],
+                |row| Ok(NetworkFinality {
+                    network_type: row.get(0)?,
+                    finality_depth: row.get(1)?,
+                }),
+            ).optional()?;
+            Ok(finality)
+        }).await
+    }
+
+    /// Update reserve requirements for a known network. Returns false if the network is not registered.
+    pub async fn set_network_reserve(&self, network_id: &str, reserve: &NetworkReserve) -> Result<bool> {
+        self.with_connection(|conn| {
+            let updated = conn.execute(
+                "UPDATE networks SET base_reserve = ?1, reserve_per_item = ?2, recommended_gas_buffer = ?3
+                 WHERE network_id = ?4",
+                rusqlite::params![
+                    reserve.base_reserve,
+                    reserve.reserve_per_item,
+                    reserve.recommended_gas_buffer,
+                    network_id
+                ],
+            )?;
+            Ok(updated > 0)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(caip: &str, symbol: &str, name: &str, source: Option<&str>) -> AssetInput {
+        AssetInput {
+            caip: caip.to_string(),
+            network_id: "eip155:1".to_string(),
+            chain_id: Some("1".to_string()),
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+            asset_type: "token".to_string(),
+            contract_address: None,
+            icon: None,
+            color: None,
+            decimals: Some(18),
+            precision: None,
+            network_name: None,
+            native_asset_caip: None,
+            explorer: None,
+            explorer_address_link: None,
+            explorer_tx_link: None,
+            coin_gecko_id: None,
+            source: source.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upsert_refreshes_discovered_assets_but_not_user_ones() {
+        let db = Database::new_in_memory().await.unwrap();
+        let discovered = "eip155:1/erc20:0xaaaa";
+        let custom = "eip155:1/erc20:0xbbbb";
+        db.upsert_assets(vec![
+            input(discovered, "AAA", "Token A", None),
+            input(custom, "BBB", "My Token", Some(USER_ASSET_SOURCE)),
+        ]).await.unwrap();
+
+        let written = db.upsert_assets(vec![
+            input(discovered, "AAA", "Token A v2", None),
+            input(custom, "BBB", "Discovered B", None),
+        ]).await.unwrap();
+        assert_eq!(written, 1);
+
+        let a = db.get_asset_by_caip(discovered).await.unwrap().unwrap();
+        assert_eq!((a.name.as_str(), a.source.as_str(), a.is_native), ("Token A v2", DISCOVERY_ASSET_SOURCE, false));
+        let b = db.get_asset_by_caip(custom).await.unwrap().unwrap();
+        assert_eq!((b.name.as_str(), b.source.as_str(), b.is_verified), ("My Token", USER_ASSET_SOURCE, false));
+        assert!(db.get_asset_by_caip("eip155:1/erc20:0xcccc").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_seed_runs_once_per_bundle_version() {
+        let db = Database::new_in_memory().await.unwrap();
+        let bundle: AssetBundle = serde_json::from_str(DEFAULT_ASSETS).unwrap();
+        assert_eq!(db.seed_default_assets().await.unwrap(), bundle.assets.len());
+        assert_eq!(db.seed_default_assets().await.unwrap(), 0);
+
+        let btc = db.get_asset_by_caip("bip122:000000000019d6689c085ae165831e93/slip44:0").await.unwrap().unwrap();
+        assert_eq!((btc.symbol.as_str(), btc.decimals, btc.is_native), ("BTC", Some(8), true));
+        let ethereum = db.search_assets("eth", Some("eip155:1"), true, 10).await.unwrap();
+        assert_eq!(ethereum.iter().map(|a| a.caip.as_str()).collect::<Vec<_>>(), ["eip155:1/slip44:60"]);
+
+        // An older recorded version re-seeds, leaving user rows alone
+        db.with_connection(|conn| {
+            conn.execute("UPDATE meta SET val = '0' WHERE key = ?1", [ASSET_SEED_VERSION_KEY])?;
+            conn.execute("UPDATE assets SET name = 'Mine', source = 'user' WHERE symbol = 'BTC'", [])?;
+            Ok(())
+        }).await.unwrap();
+        assert_eq!(db.seed_default_assets().await.unwrap(), bundle.assets.len() - 1);
+        assert_eq!(db.get_asset_by_caip(&btc.caip).await.unwrap().unwrap().name, "Mine");
+    }
+}
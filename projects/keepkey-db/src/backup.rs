@@ -0,0 +1,473 @@
+use crate::errors::{DatabaseError, Result};
+use crate::migration_guard::PRE_MIGRATION_PREFIX;
+use crate::migrations::{apply_migrations, SCHEMA_VERSION};
+use crate::Database;
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use rusqlite::{Connection, DatabaseName, OpenFlags, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Default local time of the nightly backup
+pub const DEFAULT_BACKUP_TIME: &str = "03:00";
+/// Default number of most recent daily backups to keep
+pub const DEFAULT_KEEP_DAILY: usize = 7;
+/// Default number of months that keep one backup each
+pub const DEFAULT_KEEP_MONTHLY: usize = 6;
+
+const BACKUP_PREFIX: &str = "keepkey-";
+const BACKUP_EXTENSION: &str = ".db";
+const PRE_RESTORE_PREFIX: &str = "keepkey-pre-restore-";
+
+/// Default directory for automatic backups (`backups` in the data directory)
+pub fn default_backup_dir() -> PathBuf {
+    crate::data_dir::resolve_data_dir().path.join("backups")
+}
+
+/// When backups run and how many are kept
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct BackupPolicy {
+    pub enabled: bool,
+    /// Local time of day as "HH:MM"
+    pub time_of_day: String,
+    pub keep_daily: usize,
+    pub keep_monthly: usize,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            time_of_day: DEFAULT_BACKUP_TIME.to_string(),
+            keep_daily: DEFAULT_KEEP_DAILY,
+            keep_monthly: DEFAULT_KEEP_MONTHLY,
+        }
+    }
+}
+
+impl BackupPolicy {
+    /// Parsed `time_of_day`
+    pub fn scheduled_time(&self) -> Result<NaiveTime> {
+        NaiveTime::parse_from_str(&self.time_of_day, "%H:%M")
+            .map_err(|_| DatabaseError::Validation(format!("Invalid backup time '{}', expected HH:MM", self.time_of_day)))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.scheduled_time()?;
+        if self.keep_daily == 0 {
+            return Err(DatabaseError::Validation("At least one daily backup must be kept".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// A backup file on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct BackupInfo {
+    pub filename: String,
+    /// Day the backup was taken; None for pre-restore and pre-migration snapshots
+    pub date: Option<NaiveDate>,
+    pub size_bytes: u64,
+    /// Taken automatically before a restore or a migration without dry run
+    pub is_pre_restore: bool,
+}
+
+/// What a scheduled backup run did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BackupOutcome {
+    /// Nothing was written since the last backup
+    Unchanged,
+    Created {
+        filename: String,
+        size_bytes: u64,
+        pruned: Vec<String>,
+    },
+}
+
+/// File name of the backup taken on `date`
+pub fn backup_file_name(date: NaiveDate) -> String {
+    format!("{}{}{}", BACKUP_PREFIX, date.format("%Y-%m-%d"), BACKUP_EXTENSION)
+}
+
+/// Date of a rotated backup file, None for anything else
+pub fn parse_backup_date(filename: &str) -> Option<NaiveDate> {
+    let date = filename.strip_prefix(BACKUP_PREFIX)?.strip_suffix(BACKUP_EXTENSION)?;
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// Backups in `dir`, newest first
+pub fn list_backups(dir: &Path) -> Result<Vec<BackupInfo>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let filename = entry.file_name().to_string_lossy().into_owned();
+        let is_pre_restore = (filename.starts_with(PRE_RESTORE_PREFIX) || filename.starts_with(PRE_MIGRATION_PREFIX))
+            && filename.ends_with(BACKUP_EXTENSION);
+        let date = parse_backup_date(&filename);
+        if date.is_none() && !is_pre_restore {
+            continue;
+        }
+        backups.push(BackupInfo {
+            filename,
+            date,
+            size_bytes: entry.metadata()?.len(),
+            is_pre_restore,
+        });
+    }
+
+    // Pre-restore names embed a sortable timestamp, so name order is age order
+    backups.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| b.filename.cmp(&a.filename)));
+    Ok(backups)
+}
+
+/// Backups outside the retention policy: everything except the newest
+/// `keep_daily` plus the earliest backup of each of the `keep_monthly` most
+/// recent months.
+pub fn backups_to_prune(dates: &[NaiveDate], policy: &BackupPolicy) -> Vec<NaiveDate> {
+    let mut sorted: Vec<NaiveDate> = dates.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    sorted.dedup();
+
+    let mut keep: BTreeSet<NaiveDate> = sorted.iter().take(policy.keep_daily).copied().collect();
+
+    let mut months: Vec<(i32, u32)> = sorted.iter().map(|d| (d.year(), d.month())).collect();
+    months.dedup();
+    for (year, month) in months.into_iter().take(policy.keep_monthly) {
+        if let Some(first) = sorted.iter().rev().find(|d| d.year() == year && d.month() == month) {
+            keep.insert(*first);
+        }
+    }
+
+    sorted.into_iter().filter(|d| !keep.contains(d)).collect()
+}
+
+/// Whether the scheduled backup should run now.
+///
+/// Runs once per day: at the configured time, or straight away on the first
+/// check after launch if today has no backup yet.
+pub fn is_backup_due(policy: &BackupPolicy, now: NaiveDateTime, last_run: Option<NaiveDate>, first_check: bool) -> bool {
+    if !policy.enabled || last_run == Some(now.date()) {
+        return false;
+    }
+    if first_check {
+        return true;
+    }
+    policy.scheduled_time().map(|t| now.time() >= t).unwrap_or(false)
+}
+
+/// Checks a file before it may replace the live database: it must be a
+/// readable SQLite database that passes integrity_check and carries a
+/// KeepKey schema no newer than this build understands.
+pub fn validate_backup_file(path: &Path) -> Result<()> {
+    if !path.is_file() {
+        return Err(DatabaseError::Validation(format!("Backup not found: {}", path.display())));
+    }
+
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| DatabaseError::Validation(format!("Not a database file: {}", e)))?;
+
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| DatabaseError::Validation(format!("Not a database file: {}", e)))?;
+    if integrity != "ok" {
+        return Err(DatabaseError::Validation(format!("Backup failed integrity check: {}", integrity)));
+    }
+
+    let version: Option<String> = conn
+        .query_row("SELECT val FROM meta WHERE key = 'db_version'", [], |row| row.get(0))
+        .optional()
+        .map_err(|_| DatabaseError::Validation("Backup is not a KeepKey database".to_string()))?;
+    let version: u32 = version
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| DatabaseError::Validation("Backup has no schema version".to_string()))?;
+    if version > SCHEMA_VERSION {
+        return Err(DatabaseError::Validation(format!(
+            "Backup schema version {} is newer than supported version {}",
+            version, SCHEMA_VERSION
+        )));
+    }
+    Ok(())
+}
+
+/// Runs scheduled backups, remembering what the last one captured
+pub struct BackupScheduler {
+    dir: PathBuf,
+    last_token: Option<String>,
+    last_run: Option<NaiveDate>,
+    checked: bool,
+}
+
+impl BackupScheduler {
+    /// Scheduler writing into `dir`; a backup already there for today counts as today's run
+    pub fn new(dir: PathBuf) -> Self {
+        let last_run = list_backups(&dir)
+            .ok()
+            .and_then(|backups| backups.into_iter().find_map(|b| b.date));
+        Self { dir, last_token: None, last_run, checked: false }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Run the backup if it is due. Returns None when it was not due.
+    pub async fn tick(&mut self, db: &Database, policy: &BackupPolicy, now: NaiveDateTime) -> Result<Option<BackupOutcome>> {
+        let first_check = !self.checked;
+        self.checked = true;
+        if !is_backup_due(policy, now, self.last_run, first_check) {
+            return Ok(None);
+        }
+
+        let token = db.change_token().await?;
+        if self.last_token.as_deref() == Some(token.as_str()) {
+            log::info!("Skipping scheduled backup: database unchanged since last backup");
+            self.last_run = Some(now.date());
+            return Ok(Some(BackupOutcome::Unchanged));
+        }
+
+        let outcome = db.run_backup(&self.dir, policy, now.date()).await?;
+        self.last_run = Some(now.date());
+        // Taken after the activity entry so the backup's own bookkeeping doesn't count as a change
+        self.last_token = Some(db.change_token().await?);
+        Ok(Some(outcome))
+    }
+}
+
+impl Database {
+    /// Load the backup policy from preferences, falling back to defaults
+    pub async fn get_backup_policy(&self) -> Result<BackupPolicy> {
+        let defaults = BackupPolicy::default();
+        let enabled = self.get_preference("backup_enabled").await?;
+        let time_of_day = self.get_preference("backup_time").await?;
+        let keep_daily = self.get_preference("backup_keep_daily").await?;
+        let keep_monthly = self.get_preference("backup_keep_monthly").await?;
+
+        Ok(BackupPolicy {
+            enabled: enabled.and_then(|v| v.parse().ok()).unwrap_or(defaults.enabled),
+            time_of_day: time_of_day.unwrap_or(defaults.time_of_day),
+            keep_daily: keep_daily.and_then(|v| v.parse().ok()).unwrap_or(defaults.keep_daily),
+            keep_monthly: keep_monthly.and_then(|v| v.parse().ok()).unwrap_or(defaults.keep_monthly),
+        })
+    }
+
+    /// Validate and store the backup policy
+    pub async fn set_backup_policy(&self, policy: &BackupPolicy) -> Result<()> {
+        policy.validate()?;
+        self.set_preference("backup_enabled", &policy.enabled.to_string()).await?;
+        self.set_preference("backup_time", &policy.time_of_day).await?;
+        self.set_preference("backup_keep_daily", &policy.keep_daily.to_string()).await?;
+        self.set_preference("backup_keep_monthly", &policy.keep_monthly.to_string()).await?;
+        Ok(())
+    }
+
+    /// Opaque value that changes whenever the database is written.
+    ///
+    /// data_version only moves for commits made by other connections, so it is
+    /// paired with total_changes() for writes made through this one.
+    pub async fn change_token(&self) -> Result<String> {
+        self.with_connection(|conn| {
+            let data_version: i64 = conn.query_row("PRAGMA data_version", [], |row| row.get(0))?;
+            let total_changes: i64 = conn.query_row("SELECT total_changes()", [], |row| row.get(0))?;
+            Ok(format!("{}:{}", data_version, total_changes))
+        }).await
+    }
+
+    /// Write a consistent snapshot of the database to `dest`.
+    ///
+    /// VACUUM INTO reads inside a single transaction, and the file is written
+    /// under a temporary name so a failed run (e.g. disk full) never leaves a
+    /// truncated backup behind.
+    pub async fn export_snapshot(&self, dest: &Path) -> Result<u64> {
+        let mut tmp = dest.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        if tmp.exists() {
+            std::fs::remove_file(&tmp)?;
+        }
+
+        let tmp_str = tmp.to_string_lossy().into_owned();
+        let result = self.with_connection(|conn| {
+            conn.execute("VACUUM INTO ?1", [&tmp_str])?;
+            Ok(())
+        }).await;
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e);
+        }
+
+        std::fs::rename(&tmp, dest)?;
+        Ok(std::fs::metadata(dest)?.len())
+    }
+
+    /// Snapshot the database into `dir` for `date` and prune old backups
+    pub async fn run_backup(&self, dir: &Path, policy: &BackupPolicy, date: NaiveDate) -> Result<BackupOutcome> {
+        std::fs::create_dir_all(dir)?;
+        let filename = backup_file_name(date);
+        let size_bytes = self.export_snapshot(&dir.join(&filename)).await?;
+
+        let dates: Vec<NaiveDate> = list_backups(dir)?.into_iter().filter_map(|b| b.date).collect();
+        let mut pruned = Vec::new();
+        for old in backups_to_prune(&dates, policy) {
+            let name = backup_file_name(old);
+            match std::fs::remove_file(dir.join(&name)) {
+                Ok(()) => pruned.push(name),
+                Err(e) => log::warn!("Failed to prune backup {}: {}", name, e),
+            }
+        }
+
+        log::info!("Created database backup {} ({} bytes, pruned {})", filename, size_bytes, pruned.len());
+        self.log_activity(
+            "backup",
+            &format!("Created backup {}", filename),
+            Some(&serde_json::json!({ "filename": filename, "size_bytes": size_bytes, "pruned": pruned })),
+        ).await?;
+
+        Ok(BackupOutcome::Created { filename, size_bytes, pruned })
+    }
+
+    /// Replace the live database with a validated backup.
+    ///
+    /// The current contents are first saved to `backup_dir` as a pre-restore
+    /// snapshot, whose file name is returned. The restored data is migrated to
+    /// the current schema before anything else can use the connection.
+    pub async fn restore_from_backup(&self, source: &Path, backup_dir: &Path) -> Result<String> {
+        validate_backup_file(source)?;
+
+        std::fs::create_dir_all(backup_dir)?;
+        let pre_restore = format!(
+            "{}{}{}",
+            PRE_RESTORE_PREFIX,
+            Local::now().format("%Y%m%d-%H%M%S"),
+            BACKUP_EXTENSION
+        );
+        self.export_snapshot(&backup_dir.join(&pre_restore)).await?;
+
+        self.with_connection_mut(|conn| {
+            // A database left read-only by a blocked migration can still be restored
+            conn.pragma_update(None, "query_only", false)?;
+            conn.restore(DatabaseName::Main, source, None::<fn(rusqlite::backup::Progress)>)?;
+            apply_migrations(conn)
+        }).await?;
+        self.clear_migration_block();
+
+        let source_name = source.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        log::info!("Restored database from {} (previous data saved as {})", source_name, pre_restore);
+        self.log_activity(
+            "backup",
+            &format!("Restored database from {}", source_name),
+            Some(&serde_json::json!({ "source": source_name, "pre_restore_snapshot": pre_restore })),
+        ).await?;
+
+        Ok(pre_restore)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn at(day: &str, time: &str) -> NaiveDateTime {
+        date(day).and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+    }
+
+    #[test]
+    fn test_rotation_keeps_daily_and_monthly() {
+        let start = date("2026-01-01");
+        let dates: Vec<NaiveDate> = (0..60).map(|i| start + chrono::Duration::days(i)).collect();
+
+        let pruned = backups_to_prune(&dates, &BackupPolicy::default());
+        let kept: Vec<NaiveDate> = dates.iter().filter(|d| !pruned.contains(d)).copied().collect();
+
+        // Newest seven (Feb 23 - Mar 1) plus the first backup of January and February
+        assert_eq!(kept.len(), 9);
+        assert!(kept.contains(&date("2026-01-01")));
+        assert!(kept.contains(&date("2026-02-01")));
+        assert!(kept.contains(&date("2026-02-23")));
+        assert!(!kept.contains(&date("2026-02-22")));
+
+        // Only the six most recent months keep a monthly backup
+        let monthly: Vec<NaiveDate> = (1..=9).map(|m| NaiveDate::from_ymd_opt(2025, m, 15).unwrap()).collect();
+        let policy = BackupPolicy { keep_daily: 1, ..BackupPolicy::default() };
+        let pruned = backups_to_prune(&monthly, &policy);
+        assert_eq!(pruned, vec![date("2025-03-15"), date("2025-02-15"), date("2025-01-15")]);
+    }
+
+    #[test]
+    fn test_backup_due_schedule() {
+        let policy = BackupPolicy::default();
+        // First launch of the day runs straight away, even before the scheduled time
+        assert!(is_backup_due(&policy, at("2026-03-02", "01:00"), Some(date("2026-03-01")), true));
+        assert!(!is_backup_due(&policy, at("2026-03-02", "01:00"), Some(date("2026-03-01")), false));
+        assert!(is_backup_due(&policy, at("2026-03-02", "03:00"), Some(date("2026-03-01")), false));
+        assert!(!is_backup_due(&policy, at("2026-03-02", "23:00"), Some(date("2026-03-02")), true));
+
+        let disabled = BackupPolicy { enabled: false, ..policy };
+        assert!(!is_backup_due(&disabled, at("2026-03-02", "04:00"), None, true));
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_database_is_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::open_at_path(temp_dir.path().join("keepkey.db")).await.unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+        let policy = BackupPolicy::default();
+        let mut scheduler = BackupScheduler::new(backup_dir.clone());
+
+        let first = scheduler.tick(&db, &policy, at("2026-03-01", "03:00")).await.unwrap();
+        assert!(matches!(first, Some(BackupOutcome::Created { .. })));
+        // Already ran today
+        assert!(scheduler.tick(&db, &policy, at("2026-03-01", "04:00")).await.unwrap().is_none());
+
+        let second = scheduler.tick(&db, &policy, at("2026-03-02", "03:00")).await.unwrap();
+        assert!(matches!(second, Some(BackupOutcome::Unchanged)));
+
+        db.set_preference("theme", "dark").await.unwrap();
+        let third = scheduler.tick(&db, &policy, at("2026-03-03", "03:00")).await.unwrap();
+        assert!(matches!(third, Some(BackupOutcome::Created { .. })));
+
+        let backups = list_backups(&backup_dir).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(backups[0].filename, "keepkey-2026-03-03.db");
+        assert_eq!(db.get_activity_log(10, Some("backup")).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_restore_validates_and_replaces_data() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::open_at_path(temp_dir.path().join("keepkey.db")).await.unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+
+        db.set_preference("theme", "dark").await.unwrap();
+        db.set_device_note("dev1", "office unit").await.unwrap();
+        db.run_backup(&backup_dir, &BackupPolicy::default(), date("2026-03-01")).await.unwrap();
+        db.set_preference("theme", "light").await.unwrap();
+        db.set_device_note("dev1", "").await.unwrap();
+
+        let garbage = temp_dir.path().join("garbage.db");
+        std::fs::write(&garbage, b"not a database").unwrap();
+        assert!(db.restore_from_backup(&garbage, &backup_dir).await.is_err());
+        assert_eq!(db.get_preference("theme").await.unwrap().as_deref(), Some("light"));
+
+        let pre_restore = db
+            .restore_from_backup(&backup_dir.join("keepkey-2026-03-01.db"), &backup_dir)
+            .await
+            .unwrap();
+        assert_eq!(db.get_preference("theme").await.unwrap().as_deref(), Some("dark"));
+        assert_eq!(db.get_device_note("dev1").await.unwrap().unwrap().note_markdown, "office unit");
+
+        let backups = list_backups(&backup_dir).unwrap();
+        assert!(backups.iter().any(|b| b.filename == pre_restore && b.is_pre_restore));
+    }
+}
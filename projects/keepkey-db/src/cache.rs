@@ -1,7 +1,17 @@
+use crate::errors::{DatabaseError, Result};
+use crate::types::{CacheMetadata, CacheStats, DeviceCacheStats, FeeRateCache};
 use crate::Database;
+use rusqlite::OptionalExtension;
+
+/// Cached xpubs not used for this many days are evicted at startup
+pub const PUBKEY_CACHE_MAX_IDLE_DAYS: u32 = 90;
+
+/// Statuses cache_metadata accepts for a device's frontload
+const FRONTLOAD_STATUSES: [&str; 4] = ["pending", "in_progress", "completed", "failed"];
 
 /// Cache manager - handles frontloading and cached data
 pub struct Cache {
+    #[allow(dead_code)]
     db: Database,
 }
 
@@ -11,4 +21,380 @@ impl Cache {
     }
 
     // Cache methods can be added here
-} 
\ No newline at end of file
+}
+
+impl Database {
+    /// Cached fee estimates for a network, if any have been fetched
+    pub async fn get_fee_rates(&self, caip: &str) -> Result<Option<FeeRateCache>> {
+        self.with_connection(|conn| {
+            let rates = conn.query_row(
+                "SELECT caip, fastest, fast, average, histogram, last_updated FROM fee_rate_cache WHERE caip = ?1",
+                [caip],
+                |row| Ok(FeeRateCache {
+                    caip: row.get(0)?,
+                    fastest: row.get(1)?,
+                    fast: row.get(2)?,
+                    average: row.get(3)?,
+                    histogram: row.get(4)?,
+                    last_updated: row.get(5)?,
+                }),
+            ).optional()?;
+            Ok(rates)
+        }).await
+    }
+
+    /// Replace the cached fee estimates for a network
+    pub async fn cache_fee_rates(&self, rates: &FeeRateCache) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO fee_rate_cache (caip, fastest, fast, average, histogram, last_updated)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT(caip) DO UPDATE SET
+                    fastest = excluded.fastest, fast = excluded.fast, average = excluded.average,
+                    histogram = excluded.histogram, last_updated = excluded.last_updated",
+                rusqlite::params![rates.caip, rates.fastest, rates.fast, rates.average, rates.histogram, rates.last_updated],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    /// Cached xpub for a path on a wallet, refreshing its last_used time.
+    /// `script_type` None matches rows stored without one.
+    pub async fn get_cached_pubkey(
+        &self,
+        device_id: &str,
+        wallet_fingerprint: &str,
+        derivation_path: &str,
+        coin_name: &str,
+        script_type: Option<&str>,
+    ) -> Result<Option<String>> {
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            let xpub: Option<Option<String>> = conn.query_row(
+                "UPDATE cached_pubkeys SET last_used = ?6
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2 AND derivation_path = ?3
+                   AND coin_name = ?4 AND script_type IS ?5 AND xpub IS NOT NULL
+                 RETURNING xpub",
+                rusqlite::params![device_id, wallet_fingerprint, derivation_path, coin_name, script_type, now],
+                |row| row.get(0),
+            ).optional()?;
+            Ok(xpub.flatten())
+        }).await
+    }
+
+    /// Store an xpub read from the device, replacing any cached for the same key
+    pub async fn insert_cached_pubkey(
+        &self,
+        device_id: &str,
+        wallet_fingerprint: &str,
+        derivation_path: &str,
+        coin_name: &str,
+        script_type: Option<&str>,
+        xpub: &str,
+    ) -> Result<()> {
+        let now = Self::current_timestamp();
+        self.transaction(|tx| {
+            // NULL script types never collide in the UNIQUE key, so replace by hand
+            tx.execute(
+                "DELETE FROM cached_pubkeys
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2 AND derivation_path = ?3
+                   AND coin_name = ?4 AND script_type IS ?5",
+                rusqlite::params![device_id, wallet_fingerprint, derivation_path, coin_name, script_type],
+            )?;
+            tx.execute(
+                "INSERT INTO cached_pubkeys
+                    (device_id, wallet_fingerprint, derivation_path, coin_name, script_type, xpub, cached_at, last_used)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                rusqlite::params![device_id, wallet_fingerprint, derivation_path, coin_name, script_type, xpub, now],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    /// Cached address for a full address path on a wallet
+    pub async fn get_cached_address(
+        &self,
+        device_id: &str,
+        wallet_fingerprint: &str,
+        derivation_path: &str,
+        coin_name: &str,
+        script_type: Option<&str>,
+    ) -> Result<Option<String>> {
+        self.with_connection(|conn| {
+            let address: Option<Option<String>> = conn.query_row(
+                "SELECT address FROM cached_pubkeys
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2 AND derivation_path = ?3
+                   AND coin_name = ?4 AND script_type IS ?5",
+                rusqlite::params![device_id, wallet_fingerprint, derivation_path, coin_name, script_type],
+                |row| row.get(0),
+            ).optional()?;
+            Ok(address.flatten())
+        }).await
+    }
+
+    /// Store an address the device derived, keeping any xpub cached for the same key
+    pub async fn cache_address(
+        &self,
+        device_id: &str,
+        wallet_fingerprint: &str,
+        derivation_path: &str,
+        coin_name: &str,
+        script_type: Option<&str>,
+        address: &str,
+    ) -> Result<()> {
+        let now = Self::current_timestamp();
+        self.transaction(|tx| {
+            let updated = tx.execute(
+                "UPDATE cached_pubkeys SET address = ?6, last_used = ?7
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2 AND derivation_path = ?3
+                   AND coin_name = ?4 AND script_type IS ?5",
+                rusqlite::params![device_id, wallet_fingerprint, derivation_path, coin_name, script_type, address, now],
+            )?;
+            if updated == 0 {
+                tx.execute(
+                    "INSERT INTO cached_pubkeys
+                        (device_id, wallet_fingerprint, derivation_path, coin_name, script_type, address, cached_at, last_used)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                    rusqlite::params![device_id, wallet_fingerprint, derivation_path, coin_name, script_type, address, now],
+                )?;
+            }
+            Ok(())
+        }).await
+    }
+
+    /// Mark a cached xpub as used now; false if there is no such row
+    pub async fn touch_pubkey(&self, id: i64) -> Result<bool> {
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            // Touching twice within a second leaves last_used as is, which
+            // the update_last_used_timestamp trigger sets to the current time anyway
+            let updated = conn.execute(
+                "UPDATE cached_pubkeys SET last_used = ?1 WHERE id = ?2",
+                rusqlite::params![now, id],
+            )?;
+            Ok(updated > 0)
+        }).await
+    }
+
+    /// Delete cached xpubs, of every device, unused for `older_than_days`;
+    /// returns the number removed
+    pub async fn evict_unused_pubkeys(&self, older_than_days: u32) -> Result<usize> {
+        let cutoff = Self::current_timestamp() - i64::from(older_than_days) * 86_400;
+        self.with_connection(|conn| {
+            let evicted = conn.execute("DELETE FROM cached_pubkeys WHERE last_used < ?1", [cutoff])?;
+            if evicted > 0 {
+                log::info!("Evicted {} cached pubkeys unused for {} days", evicted, older_than_days);
+            }
+            Ok(evicted)
+        }).await
+    }
+
+    /// Record where a device's frontload stands: `status` is 'pending',
+    /// 'in_progress', 'completed' or 'failed' and `progress` a percentage.
+    /// Completing stamps last_frontload; a status without an error clears it.
+    pub async fn set_frontload_status(
+        &self,
+        device_id: &str,
+        status: &str,
+        progress: i32,
+        error: Option<&str>,
+    ) -> Result<()> {
+        if !FRONTLOAD_STATUSES.contains(&status) {
+            return Err(DatabaseError::Validation(format!("Unknown frontload status: {}", status)));
+        }
+        if !(0..=100).contains(&progress) {
+            return Err(DatabaseError::Validation(format!("Frontload progress {} is not a percentage", progress)));
+        }
+        let completed_at = (status == "completed").then(Self::current_timestamp);
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO cache_metadata (device_id, frontload_status, frontload_progress, last_frontload, error_message)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(device_id) DO UPDATE SET
+                    frontload_status = excluded.frontload_status,
+                    frontload_progress = excluded.frontload_progress,
+                    last_frontload = COALESCE(excluded.last_frontload, last_frontload),
+                    error_message = excluded.error_message",
+                rusqlite::params![device_id, status, progress, completed_at, error],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    /// A device's cache metadata, None if nothing was recorded for it
+    pub async fn get_frontload_status(&self, device_id: &str) -> Result<Option<CacheMetadata>> {
+        self.with_connection(|conn| {
+            Ok(conn.query_row(
+                "SELECT device_id, label, firmware_version, initialized, frontload_status,
+                        COALESCE(frontload_progress, 0), last_frontload, error_message
+                 FROM cache_metadata WHERE device_id = ?1",
+                [device_id],
+                |row| Ok(CacheMetadata {
+                    device_id: row.get(0)?,
+                    label: row.get(1)?,
+                    firmware_version: row.get(2)?,
+                    initialized: row.get(3)?,
+                    frontload_status: row.get(4)?,
+                    frontload_progress: row.get(5)?,
+                    last_frontload: row.get(6)?,
+                    error_message: row.get(7)?,
+                }),
+            ).optional()?)
+        }).await
+    }
+
+    /// Row counts of the cache tables, and per device its cached keys and
+    /// last frontload
+    pub async fn get_cache_stats(&self) -> Result<CacheStats> {
+        self.with_connection(|conn| {
+            let count = |table: &str| -> rusqlite::Result<i64> {
+                conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+            };
+            let mut stmt = conn.prepare(
+                "SELECT d.device_id,
+                        (SELECT COUNT(*) FROM cached_pubkeys p WHERE p.device_id = d.device_id),
+                        (SELECT COUNT(*) FROM wallet_xpubs x WHERE x.device_id = d.device_id),
+                        m.frontload_status, COALESCE(m.frontload_progress, 0), m.last_frontload, m.error_message
+                 FROM (SELECT device_id FROM cache_metadata
+                       UNION SELECT device_id FROM cached_pubkeys
+                       UNION SELECT device_id FROM wallet_xpubs) d
+                 LEFT JOIN cache_metadata m ON m.device_id = d.device_id
+                 ORDER BY d.device_id"
+            )?;
+            let devices = stmt
+                .query_map([], |row| Ok(DeviceCacheStats {
+                    device_id: row.get(0)?,
+                    cached_pubkeys: row.get(1)?,
+                    wallet_xpubs: row.get(2)?,
+                    frontload_status: row.get(3)?,
+                    frontload_progress: row.get(4)?,
+                    last_frontload: row.get(5)?,
+                    error_message: row.get(6)?,
+                }))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(CacheStats {
+                cached_pubkeys: count("cached_pubkeys")?,
+                wallet_xpubs: count("wallet_xpubs")?,
+                portfolio_balances: count("portfolio_balances")?,
+                transaction_cache: count("transaction_cache")?,
+                fee_rate_cache: count("fee_rate_cache")?,
+                devices,
+            })
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fee_rates_round_trip() {
+        let db = Database::new_in_memory().await.unwrap();
+        let caip = "bip122:000000000019d6689c085ae165831e93";
+        assert!(db.get_fee_rates(caip).await.unwrap().is_none());
+
+        let mut rates = FeeRateCache {
+            caip: caip.to_string(),
+            fastest: 30,
+            fast: 20,
+            average: 10,
+            histogram: Some("[[30.0,1000]]".to_string()),
+            last_updated: 1_700_000_000,
+        };
+        db.cache_fee_rates(&rates).await.unwrap();
+        assert_eq!(db.get_fee_rates(caip).await.unwrap(), Some(rates.clone()));
+
+        // A tiers-only refresh clears the stale histogram
+        rates.histogram = None;
+        rates.fastest = 12;
+        db.cache_fee_rates(&rates).await.unwrap();
+        assert_eq!(db.get_fee_rates(caip).await.unwrap(), Some(rates));
+    }
+
+    #[tokio::test]
+    async fn test_cached_pubkeys() {
+        let db = Database::new_in_memory().await.unwrap();
+        let path = "m/84'/0'/0'";
+        assert_eq!(db.get_cached_pubkey("dev1", "fp1", path, "Bitcoin", Some("p2wpkh")).await.unwrap(), None);
+
+        db.insert_cached_pubkey("dev1", "fp1", path, "Bitcoin", Some("p2wpkh"), "zpub-a").await.unwrap();
+        db.insert_cached_pubkey("dev1", "fp1", "m/44'/0'/0'", "Bitcoin", None, "xpub-b").await.unwrap();
+        assert_eq!(db.get_cached_pubkey("dev1", "fp1", path, "Bitcoin", Some("p2wpkh")).await.unwrap().as_deref(), Some("zpub-a"));
+        assert_eq!(db.get_cached_pubkey("dev1", "fp1", "m/44'/0'/0'", "Bitcoin", None).await.unwrap().as_deref(), Some("xpub-b"));
+
+        // Every part of the key counts, including the wallet
+        assert_eq!(db.get_cached_pubkey("dev1", "fp2", path, "Bitcoin", Some("p2wpkh")).await.unwrap(), None);
+        assert_eq!(db.get_cached_pubkey("dev1", "fp1", path, "Testnet", Some("p2wpkh")).await.unwrap(), None);
+        assert_eq!(db.get_cached_pubkey("dev1", "fp1", path, "Bitcoin", None).await.unwrap(), None);
+        assert_eq!(db.get_cached_pubkey("dev2", "fp1", path, "Bitcoin", Some("p2wpkh")).await.unwrap(), None);
+
+        // Re-inserting replaces, also without a script type
+        db.insert_cached_pubkey("dev1", "fp1", path, "Bitcoin", Some("p2wpkh"), "zpub-c").await.unwrap();
+        db.insert_cached_pubkey("dev1", "fp1", "m/44'/0'/0'", "Bitcoin", None, "xpub-d").await.unwrap();
+        assert_eq!(db.get_cached_pubkey("dev1", "fp1", path, "Bitcoin", Some("p2wpkh")).await.unwrap().as_deref(), Some("zpub-c"));
+        assert_eq!(db.get_cached_pubkey("dev1", "fp1", "m/44'/0'/0'", "Bitcoin", None).await.unwrap().as_deref(), Some("xpub-d"));
+        let rows: i64 = db.with_connection(|conn| {
+            Ok(conn.query_row("SELECT COUNT(*) FROM cached_pubkeys", [], |row| row.get(0))?)
+        }).await.unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[tokio::test]
+    async fn test_cached_addresses() {
+        let db = Database::new_in_memory().await.unwrap();
+        let path = "m/84'/0'/0'/0/0";
+        assert_eq!(db.get_cached_address("dev1", "fp1", path, "Bitcoin", Some("p2wpkh")).await.unwrap(), None);
+
+        db.insert_cached_pubkey("dev1", "fp1", path, "Bitcoin", Some("p2wpkh"), "zpub-a").await.unwrap();
+        db.cache_address("dev1", "fp1", path, "Bitcoin", Some("p2wpkh"), "bc1qold").await.unwrap();
+        db.cache_address("dev1", "fp1", path, "Bitcoin", Some("p2wpkh"), "bc1qnew").await.unwrap();
+        db.cache_address("dev1", "fp1", "m/44'/60'/0'/0/0", "Ethereum", None, "0xabc").await.unwrap();
+
+        assert_eq!(db.get_cached_address("dev1", "fp1", path, "Bitcoin", Some("p2wpkh")).await.unwrap().as_deref(), Some("bc1qnew"));
+        assert_eq!(db.get_cached_pubkey("dev1", "fp1", path, "Bitcoin", Some("p2wpkh")).await.unwrap().as_deref(), Some("zpub-a"));
+        assert_eq!(db.get_cached_address("dev1", "fp1", "m/44'/60'/0'/0/0", "Ethereum", None).await.unwrap().as_deref(), Some("0xabc"));
+        assert_eq!(db.get_cached_address("dev1", "fp2", path, "Bitcoin", Some("p2wpkh")).await.unwrap(), None);
+        // An address-only row has no xpub to serve
+        assert_eq!(db.get_cached_pubkey("dev1", "fp1", "m/44'/60'/0'/0/0", "Ethereum", None).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_pubkey_eviction_and_frontload_status() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.insert_cached_pubkey("dev1", "fp1", "m/44'/0'/0'", "Bitcoin", None, "xpub-old").await.unwrap();
+        db.insert_cached_pubkey("dev2", "fp2", "m/84'/0'/0'", "Bitcoin", Some("p2wpkh"), "zpub-new").await.unwrap();
+        let old_day = Database::current_timestamp() - 100 * 86_400;
+        db.with_connection(move |conn| {
+            conn.execute("UPDATE cached_pubkeys SET last_used = ?1", [old_day])?;
+            Ok(())
+        }).await.unwrap();
+        let dev2_id: i64 = db.with_connection(|conn| {
+            Ok(conn.query_row("SELECT id FROM cached_pubkeys WHERE device_id = 'dev2'", [], |row| row.get(0))?)
+        }).await.unwrap();
+        assert!(db.touch_pubkey(dev2_id).await.unwrap());
+        assert!(!db.touch_pubkey(dev2_id + 100).await.unwrap());
+
+        assert_eq!(db.evict_unused_pubkeys(PUBKEY_CACHE_MAX_IDLE_DAYS).await.unwrap(), 1);
+        assert_eq!(db.get_cached_pubkey("dev1", "fp1", "m/44'/0'/0'", "Bitcoin", None).await.unwrap(), None);
+        assert!(db.get_cached_pubkey("dev2", "fp2", "m/84'/0'/0'", "Bitcoin", Some("p2wpkh")).await.unwrap().is_some());
+
+        assert_eq!(db.get_frontload_status("dev2").await.unwrap(), None);
+        db.set_frontload_status("dev2", "in_progress", 25, None).await.unwrap();
+        db.set_frontload_status("dev2", "completed", 100, None).await.unwrap();
+        db.set_frontload_status("dev2", "failed", 50, Some("Device disconnected")).await.unwrap();
+        assert!(db.set_frontload_status("dev2", "done", 100, None).await.is_err());
+        assert!(db.set_frontload_status("dev2", "completed", 101, None).await.is_err());
+        let status = db.get_frontload_status("dev2").await.unwrap().unwrap();
+        assert_eq!((status.frontload_status.as_deref(), status.frontload_progress), (Some("failed"), 50));
+        assert_eq!(status.error_message.as_deref(), Some("Device disconnected"));
+        // The last completed run is still known
+        assert!(status.last_frontload.is_some());
+
+        let stats = db.get_cache_stats().await.unwrap();
+        assert_eq!(stats.cached_pubkeys, 1);
+        assert_eq!(stats.devices.len(), 1);
+        assert_eq!((stats.devices[0].device_id.as_str(), stats.devices[0].cached_pubkeys), ("dev2", 1));
+        assert_eq!(stats.devices[0].last_frontload, status.last_frontload);
+    }
+}
@@ -0,0 +1,312 @@
+//! Tokens the user added by contract address
+//!
+//! Custom tokens live in the `assets` registry with source = 'user' and
+//! is_verified = 0, so balance sync and the send flow treat them like any
+//! other token. `upsert_assets` never overwrites these rows, so they
+//! survive a registry reseed. Each token is mapped to the derivation paths
+//! that serve its network.
+
+use rusqlite::{Connection, OptionalExtension};
+use crate::errors::{DatabaseError, Result};
+use crate::types::CustomToken;
+use crate::Database;
+
+/// Source value marking user-added registry rows
+pub const USER_ASSET_SOURCE: &str = "user";
+
+/// Largest decimals value accepted for a token
+pub const MAX_TOKEN_DECIMALS: u32 = 36;
+
+/// CAIP-19 id of an ERC-20 token
+pub fn erc20_caip(network_id: &str, contract_address: &str) -> String {
+    format!("{}/erc20:{}", network_id, contract_address.to_lowercase())
+}
+
+/// Whether a derivation path's network entry covers `network_id`;
+/// `eip155:*` covers every EVM chain
+fn path_serves(path_network: &str, network_id: &str) -> bool {
+    match path_network.strip_suffix('*') {
+        Some(prefix) => network_id.starts_with(prefix),
+        None => path_network == network_id,
+    }
+}
+
+fn paths_for_network(conn: &Connection, network_id: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT path_id, networks FROM derivation_paths ORDER BY path_id")?;
+    let paths = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(paths
+        .into_iter()
+        .filter(|(_, networks)| {
+            // Stored as a JSON array of network ids
+            serde_json::from_str::<Vec<String>>(networks)
+                .unwrap_or_default()
+                .iter()
+                .any(|n| path_serves(n, network_id))
+        })
+        .map(|(path_id, _)| path_id)
+        .collect())
+}
+
+fn mapped_paths(conn: &Connection, caip: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT path_id FROM path_asset_mapping WHERE caip = ?1 ORDER BY path_id")?;
+    let paths = stmt.query_map([caip], |row| row.get(0))?.collect();
+    paths
+}
+
+fn read_custom_token(conn: &Connection, caip: &str) -> rusqlite::Result<Option<CustomToken>> {
+    let token = conn.query_row(
+        "SELECT caip, network_id, contract_address, symbol, name, decimals, created_at
+         FROM assets WHERE caip = ?1 AND source = 'user'",
+        [caip],
+        |row| Ok(CustomToken {
+            caip: row.get(0)?,
+            network_id: row.get(1)?,
+            contract_address: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            symbol: row.get(3)?,
+            name: row.get(4)?,
+            decimals: row.get::<_, Option<u32>>(5)?.unwrap_or(0),
+            path_ids: Vec::new(),
+            created_at: row.get(6)?,
+        }),
+    ).optional()?;
+    token
+        .map(|token| Ok(CustomToken { path_ids: mapped_paths(conn, &token.caip)?, ..token }))
+        .transpose()
+}
+
+/// A cached balance that is not clearly zero; unparseable values count as held
+fn is_nonzero_balance(balance: &str) -> bool {
+    balance.trim().parse::<f64>().map(|b| b != 0.0).unwrap_or(true)
+}
+
+impl Database {
+    /// Add an ERC-20 token to the registry as a user asset and map it to the
+    /// derivation paths serving its network. The contract must already have
+    /// been checked against the chain; this only stores it.
+    pub async fn add_custom_token(
+        &self,
+        network_id: &str,
+        contract_address: &str,
+        symbol: &str,
+        name: &str,
+        decimals: u32,
+    ) -> Result<CustomToken> {
+        let symbol = symbol.trim();
+        let name = name.trim();
+        if symbol.is_empty() || name.is_empty() {
+            return Err(DatabaseError::Validation("Token symbol and name are required".to_string()));
+        }
+        if decimals > MAX_TOKEN_DECIMALS {
+            return Err(DatabaseError::Validation(format!(
+                "Token decimals must be at most {}, got {}", MAX_TOKEN_DECIMALS, decimals
+            )));
+        }
+        let contract_address = contract_address.to_lowercase();
+        let caip = erc20_caip(network_id, &contract_address);
+        let now = Self::current_timestamp();
+
+        self.transaction(|conn| {
+            let chain_id: Option<Option<String>> = conn.query_row(
+                "SELECT chain_id FROM networks WHERE network_id = ?1",
+                [network_id],
+                |row| row.get(0),
+            ).optional()?;
+            let Some(chain_id) = chain_id else {
+                return Err(DatabaseError::Validation(format!("Unknown network {}", network_id)));
+            };
+
+            let existing: Option<Option<String>> = conn.query_row(
+                "SELECT source FROM assets WHERE caip = ?1",
+                [&caip],
+                |row| row.get(0),
+            ).optional()?;
+            match existing.as_ref().map(|s| s.as_deref()) {
+                Some(Some(USER_ASSET_SOURCE)) => {
+                    return Err(DatabaseError::Validation(format!("{} has already been added", caip)));
+                }
+                Some(_) => {
+                    return Err(DatabaseError::Validation(format!("{} is already in the asset registry", caip)));
+                }
+                None => {}
+            }
+
+            conn.execute(
+                "INSERT INTO assets (caip, network_id, chain_id, symbol, name, asset_type, is_native,
+                                     contract_address, decimals, source, is_verified, created_at, last_updated)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 'token', 0, ?6, ?7, ?8, 0, ?9, ?9)",
+                rusqlite::params![caip, network_id, chain_id, symbol, name, contract_address, decimals, USER_ASSET_SOURCE, now],
+            )?;
+            for path_id in paths_for_network(conn, network_id)? {
+                conn.execute(
+                    "INSERT OR IGNORE INTO path_asset_mapping (path_id, caip, network_id, is_primary, created_at)
+                     VALUES (?1, ?2, ?3, 0, ?4)",
+                    rusqlite::params![path_id, caip, network_id, now],
+                )?;
+            }
+
+            read_custom_token(conn, &caip)?
+                .ok_or_else(|| DatabaseError::Validation(format!("{} was not stored", caip)))
+        }).await
+    }
+
+    /// Every user-added token, by network then symbol
+    pub async fn list_custom_tokens(&self) -> Result<Vec<CustomToken>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT caip FROM assets WHERE source = 'user' ORDER BY network_id, symbol COLLATE NOCASE, caip"
+            )?;
+            let caips = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let mut tokens = Vec::with_capacity(caips.len());
+            for caip in caips {
+                tokens.extend(read_custom_token(conn, &caip)?);
+            }
+            Ok(tokens)
+        }).await
+    }
+
+    /// Remove a user-added token with its path mappings and cached balances.
+    ///
+    /// Refused while any wallet holds a nonzero cached balance of it, unless
+    /// `force`. Registry assets that did not come from the user cannot be
+    /// removed. Returns false if no such token exists.
+    pub async fn remove_custom_token(&self, caip: &str, force: bool) -> Result<bool> {
+        self.transaction(|conn| {
+            let source: Option<Option<String>> = conn.query_row(
+                "SELECT source FROM assets WHERE caip = ?1",
+                [caip],
+                |row| row.get(0),
+            ).optional()?;
+            match source {
+                None => return Ok(false),
+                Some(Some(ref s)) if s == USER_ASSET_SOURCE => {}
+                Some(_) => {
+                    return Err(DatabaseError::Validation(format!(
+                        "{} is a registry asset, not a custom token", caip
+                    )));
+                }
+            }
+
+            if !force {
+                let mut stmt = conn.prepare(
+                    "SELECT balance FROM portfolio_balances WHERE caip = ?1"
+                )?;
+                let held = stmt
+                    .query_map([caip], |row| row.get::<_, String>(0))?
+                    .collect::<rusqlite::Result<Vec<_>>>()?
+                    .iter()
+                    .any(|b| is_nonzero_balance(b));
+                if held {
+                    return Err(DatabaseError::Validation(format!(
+                        "{} still has a balance; remove it with force to hide it anyway", caip
+                    )));
+                }
+            }
+
+            conn.execute("DELETE FROM portfolio_balances WHERE caip = ?1", [caip])?;
+            conn.execute("DELETE FROM path_asset_mapping WHERE caip = ?1", [caip])?;
+            conn.execute("DELETE FROM assets WHERE caip = ?1 AND source = 'user'", [caip])?;
+            Ok(true)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::AssetInput;
+
+    const ETH: &str = "eip155:1";
+    const TOKEN: &str = "0x6B175474E89094C44Da98b954EedeAC495271d0F";
+
+    async fn seed(db: &Database) {
+        db.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO assets (caip, network_id, symbol, name, is_native) VALUES ('eip155:1/slip44:60', ?1, 'ETH', 'Ethereum', 1)",
+                [ETH],
+            )?;
+            conn.execute(
+                "INSERT INTO networks (network_id, name, chain_id, native_asset_caip, native_symbol)
+                 VALUES (?1, 'Ethereum', '1', 'eip155:1/slip44:60', 'ETH')",
+                [ETH],
+            )?;
+            for (path_id, networks) in [("ethereum_44", "[\"eip155:*\"]"), ("bitcoin_84", "[\"bip122:000000000019d6689c085ae165831e93\"]")] {
+                conn.execute(
+                    "INSERT INTO derivation_paths (path_id, blockchain, symbol, networks, address_n_list, address_n_list_master)
+                     VALUES (?1, 'chain', 'SYM', ?2, '[]', '[]')",
+                    [path_id, networks],
+                )?;
+            }
+            Ok(())
+        }).await.unwrap();
+    }
+
+    fn catalog_entry(caip: &str, symbol: &str) -> AssetInput {
+        serde_json::from_value(serde_json::json!({
+            "caip": caip,
+            "network_id": ETH,
+            "chain_id": "1",
+            "symbol": symbol,
+            "name": format!("{} from the catalog", symbol),
+            "asset_type": "token",
+            "contract_address": caip.split(':').next_back(),
+            "decimals": 6,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_custom_token_survives_reseed() {
+        let db = Database::new_in_memory().await.unwrap();
+        seed(&db).await;
+
+        let token = db.add_custom_token(ETH, TOKEN, "DAI", "Dai Stablecoin", 18).await.unwrap();
+        assert_eq!(token.caip, "eip155:1/erc20:0x6b175474e89094c44da98b954eedeac495271d0f");
+        assert_eq!(token.path_ids, vec!["ethereum_44".to_string()]);
+
+        // A reseed listing the same CAIP, plus a catalog token of its own
+        let usdc = "eip155:1/erc20:0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+        let written = db.upsert_assets(vec![catalog_entry(&token.caip, "FAKE"), catalog_entry(usdc, "USDC")]).await.unwrap();
+        assert_eq!(written, 1);
+
+        assert_eq!(db.list_custom_tokens().await.unwrap(), vec![token.clone()]);
+        assert_eq!(db.get_asset_decimals(&token.caip).await.unwrap(), Some(18));
+        let (source, verified): (String, bool) = db.with_connection(|conn| {
+            Ok(conn.query_row("SELECT source, is_verified FROM assets WHERE caip = ?1", [&token.caip], |row| Ok((row.get(0)?, row.get(1)?)))?)
+        }).await.unwrap();
+        assert_eq!((source.as_str(), verified), (USER_ASSET_SOURCE, false));
+
+        // Catalog rows are not custom tokens and cannot be added or removed as one
+        assert!(db.add_custom_token(ETH, "0xA0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", "USDC", "USD Coin", 6).await.is_err());
+        assert!(db.remove_custom_token(usdc, true).await.is_err());
+        assert!(db.add_custom_token("eip155:10", TOKEN, "DAI", "Dai", 18).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remove_is_blocked_by_a_balance_unless_forced() {
+        let db = Database::new_in_memory().await.unwrap();
+        seed(&db).await;
+        let token = db.add_custom_token(ETH, TOKEN, "DAI", "Dai Stablecoin", 18).await.unwrap();
+
+        db.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO portfolio_balances (device_id, pubkey, caip, network_id, ticker, balance, balance_usd, price_usd, type, last_updated)
+                 VALUES ('dev1', 'xpub', ?1, ?2, 'DAI', '12.5', '12.5', '1', 'balance', 1)",
+                [&token.caip, ETH],
+            )?;
+            Ok(())
+        }).await.unwrap();
+
+        let err = db.remove_custom_token(&token.caip, false).await.unwrap_err();
+        assert!(err.to_string().contains("balance"));
+        assert_eq!(db.list_custom_tokens().await.unwrap().len(), 1);
+
+        assert!(db.remove_custom_token(&token.caip, true).await.unwrap());
+        assert!(db.list_custom_tokens().await.unwrap().is_empty());
+        assert!(db.get_asset_balances("dev1", "", &token.caip).await.unwrap().is_empty());
+        assert!(!db.remove_custom_token(&token.caip, false).await.unwrap());
+    }
+}
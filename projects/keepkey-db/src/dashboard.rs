@@ -0,0 +1,310 @@
+//! Portfolio dashboard aggregation
+//!
+//! `portfolio_dashboard` caches what the dashboard shows: the USD total of a
+//! wallet's spendable balances and its breakdown by network and by asset, with
+//! each entry's share of the total. The combined dashboard does the same over
+//! every device and is stored under `COMBINED_DASHBOARD_ID`. Staking,
+//! delegation and reward rows are left out, as in the drift audit.
+
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::{json, Value};
+use crate::errors::Result;
+use crate::types::PortfolioDashboard;
+use crate::Database;
+
+/// Device id of the combined dashboard row, as in v_combined_portfolio
+pub const COMBINED_DASHBOARD_ID: &str = "combined";
+
+/// Spendable balances of one device wallet, or of every device when both
+/// parameters are NULL
+const SCOPE_FILTER: &str =
+    "(?1 IS NULL OR b.device_id = ?1) AND (?2 IS NULL OR b.wallet_fingerprint = ?2)
+     AND COALESCE(b.type, 'balance') = 'balance'";
+
+/// Columns read by `dashboard_from_row`, in order
+const DASHBOARD_COLUMNS: &str =
+    "id, device_id, wallet_fingerprint, total_value_usd, networks_json, assets_json, total_assets,
+     total_networks, last_24h_change_usd, last_24h_change_percent, is_combined, included_devices, last_updated";
+
+fn dashboard_from_row(row: &rusqlite::Row) -> rusqlite::Result<PortfolioDashboard> {
+    Ok(PortfolioDashboard {
+        id: row.get(0)?,
+        device_id: row.get(1)?,
+        wallet_fingerprint: row.get(2)?,
+        total_value_usd: row.get(3)?,
+        networks_json: row.get(4)?,
+        assets_json: row.get(5)?,
+        total_assets: row.get::<_, Option<i32>>(6)?.unwrap_or(0),
+        total_networks: row.get::<_, Option<i32>>(7)?.unwrap_or(0),
+        last_24h_change_usd: row.get(8)?,
+        last_24h_change_percent: row.get(9)?,
+        is_combined: row.get::<_, Option<bool>>(10)?.unwrap_or(false),
+        included_devices: row.get(11)?,
+        last_updated: row.get(12)?,
+    })
+}
+
+/// Share of `total`, 0 for an empty portfolio
+fn percentage(value: f64, total: f64) -> f64 {
+    if total > 0.0 { value / total * 100.0 } else { 0.0 }
+}
+
+/// Totals and breakdowns of the balances in scope
+struct Aggregate {
+    total_usd: f64,
+    networks: Vec<Value>,
+    assets: Vec<Value>,
+}
+
+fn aggregate(conn: &Connection, device_id: Option<&str>, wallet_fingerprint: Option<&str>) -> rusqlite::Result<Aggregate> {
+    let params = rusqlite::params![device_id, wallet_fingerprint];
+    let total_usd: f64 = conn.query_row(
+        &format!("SELECT COALESCE(SUM(CAST(b.balance_usd AS REAL)), 0) FROM portfolio_balances b WHERE {}", SCOPE_FILTER),
+        params,
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT b.network_id, COALESCE(n.name, b.network_id), SUM(CAST(b.balance_usd AS REAL))
+         FROM portfolio_balances b LEFT JOIN networks n ON n.network_id = b.network_id
+         WHERE {}
+         GROUP BY b.network_id ORDER BY 3 DESC, 1",
+        SCOPE_FILTER
+    ))?;
+    let networks = stmt
+        .query_map(params, |row| {
+            let value: f64 = row.get(2)?;
+            Ok(json!({
+                "networkId": row.get::<_, String>(0)?,
+                "name": row.get::<_, String>(1)?,
+                "valueUsd": format!("{:.2}", value),
+                "percentage": percentage(value, total_usd),
+            }))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT b.ticker, COALESCE(MAX(b.name), b.ticker), SUM(CAST(b.balance_usd AS REAL)), SUM(CAST(b.balance AS REAL))
+         FROM portfolio_balances b
+         WHERE {}
+         GROUP BY b.ticker ORDER BY 3 DESC, 1",
+        SCOPE_FILTER
+    ))?;
+    let assets = stmt
+        .query_map(params, |row| {
+            let value: f64 = row.get(2)?;
+            Ok(json!({
+                "ticker": row.get::<_, String>(0)?,
+                "name": row.get::<_, String>(1)?,
+                "valueUsd": format!("{:.2}", value),
+                "balance": row.get::<_, f64>(3)?.to_string(),
+                "percentage": percentage(value, total_usd),
+            }))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(Aggregate { total_usd, networks, assets })
+}
+
+fn write_dashboard(
+    conn: &Connection,
+    device_id: &str,
+    wallet_fingerprint: &str,
+    aggregate: &Aggregate,
+    included_devices: Option<String>,
+    now: i64,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO portfolio_dashboard (device_id, wallet_fingerprint, total_value_usd, networks_json, assets_json,
+                                          total_assets, total_networks, is_combined, included_devices, last_updated)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+         ON CONFLICT(device_id, wallet_fingerprint) DO UPDATE SET
+            total_value_usd = excluded.total_value_usd,
+            networks_json = excluded.networks_json,
+            assets_json = excluded.assets_json,
+            total_assets = excluded.total_assets,
+            total_networks = excluded.total_networks,
+            is_combined = excluded.is_combined,
+            included_devices = excluded.included_devices,
+            last_updated = excluded.last_updated",
+        rusqlite::params![
+            device_id,
+            wallet_fingerprint,
+            format!("{:.2}", aggregate.total_usd),
+            Value::Array(aggregate.networks.clone()).to_string(),
+            Value::Array(aggregate.assets.clone()).to_string(),
+            aggregate.assets.len() as i64,
+            aggregate.networks.len() as i64,
+            included_devices.is_some(),
+            included_devices,
+            now,
+        ],
+    )?;
+    Ok(())
+}
+
+fn read_dashboard(conn: &Connection, device_id: &str, wallet_fingerprint: &str) -> rusqlite::Result<Option<PortfolioDashboard>> {
+    conn.query_row(
+        &format!("SELECT {} FROM portfolio_dashboard WHERE device_id = ?1 AND wallet_fingerprint = ?2", DASHBOARD_COLUMNS),
+        [device_id, wallet_fingerprint],
+        dashboard_from_row,
+    ).optional()
+}
+
+impl Database {
+    /// Rebuild one wallet's dashboard row from its balances
+    pub async fn compute_dashboard(&self, device_id: &str, wallet_fingerprint: &str) -> Result<PortfolioDashboard> {
+        let now = Self::current_timestamp();
+        self.transaction(|tx| {
+            let aggregate = aggregate(tx, Some(device_id), Some(wallet_fingerprint))?;
+            write_dashboard(tx, device_id, wallet_fingerprint, &aggregate, None, now)?;
+            Ok(read_dashboard(tx, device_id, wallet_fingerprint)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?)
+        }).await
+    }
+
+    /// Rebuild the dashboard merging every device's balances; its
+    /// `included_devices` is a JSON array of their ids
+    pub async fn compute_combined_dashboard(&self) -> Result<PortfolioDashboard> {
+        let now = Self::current_timestamp();
+        self.transaction(|tx| {
+            let aggregate = aggregate(tx, None, None)?;
+            let mut stmt = tx.prepare(&format!(
+                "SELECT DISTINCT b.device_id FROM portfolio_balances b WHERE {} ORDER BY 1",
+                SCOPE_FILTER
+            ))?;
+            let devices = stmt
+                .query_map(rusqlite::params![None::<&str>, None::<&str>], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let included = serde_json::to_string(&devices)?;
+            write_dashboard(tx, COMBINED_DASHBOARD_ID, "", &aggregate, Some(included), now)?;
+            Ok(read_dashboard(tx, COMBINED_DASHBOARD_ID, "")?.ok_or(rusqlite::Error::QueryReturnedNoRows)?)
+        }).await
+    }
+
+    /// The cached dashboard of a device wallet, or the combined one without a
+    /// device, if no balance in it was written since it was computed
+    pub async fn get_fresh_dashboard(&self, device_id: Option<&str>, wallet_fingerprint: &str) -> Result<Option<PortfolioDashboard>> {
+        self.with_connection(|conn| {
+            let (row_id, scope) = match device_id {
+                Some(device_id) => (device_id, (Some(device_id), Some(wallet_fingerprint))),
+                None => (COMBINED_DASHBOARD_ID, (None, None)),
+            };
+            let row_fingerprint = if device_id.is_some() { wallet_fingerprint } else { "" };
+            let Some(dashboard) = read_dashboard(conn, row_id, row_fingerprint)? else {
+                return Ok(None);
+            };
+            // Timestamps are in seconds, so a write in the same second counts as newer
+            let newest: Option<i64> = conn.query_row(
+                &format!("SELECT MAX(b.last_updated) FROM portfolio_balances b WHERE {}", SCOPE_FILTER),
+                rusqlite::params![scope.0, scope.1],
+                |row| row.get(0),
+            )?;
+            Ok(match newest {
+                Some(newest) if newest >= dashboard.last_updated => None,
+                _ => Some(dashboard),
+            })
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PortfolioBalanceInput;
+
+    fn balance(device_id: &str, ticker: &str, network_id: &str, usd: &str, balance_type: &str) -> PortfolioBalanceInput {
+        PortfolioBalanceInput {
+            device_id: device_id.to_string(),
+            pubkey: format!("{}-{}", device_id, ticker),
+            caip: format!("{}/slip44:{}", network_id, ticker),
+            network_id: network_id.to_string(),
+            ticker: ticker.to_string(),
+            address: None,
+            balance: "1".to_string(),
+            balance_usd: usd.to_string(),
+            price_usd: usd.to_string(),
+            balance_type: balance_type.to_string(),
+            name: None,
+            icon: None,
+            precision: None,
+            contract: None,
+            validator: None,
+            unbonding_end: None,
+            rewards_available: None,
+        }
+    }
+
+    fn percentages(json: &str) -> Vec<f64> {
+        let entries: Vec<Value> = serde_json::from_str(json).unwrap();
+        entries.iter().map(|e| e["percentage"].as_f64().unwrap()).collect()
+    }
+
+    /// Make every stored balance older than any dashboard computed from now on
+    async fn age_balances(db: &Database) {
+        db.with_connection(|conn| {
+            conn.execute("UPDATE portfolio_balances SET last_updated = last_updated - 10", [])?;
+            Ok(())
+        }).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_percentages() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.upsert_portfolio_balances("dev1", "aaaa0001", vec![
+            balance("dev1", "BTC", "bip122:000000000019d6689c085ae165831e93", "333.33", "balance"),
+            balance("dev1", "ETH", "eip155:1", "333.33", "balance"),
+            balance("dev1", "USDC", "eip155:1", "333.34", "balance"),
+            balance("dev1", "ATOM", "cosmos:cosmoshub-4", "500", "delegation"),
+        ]).await.unwrap();
+        db.upsert_portfolio_balances("dev2", "", vec![balance("dev2", "BTC", "bip122:000000000019d6689c085ae165831e93", "1000", "balance")])
+            .await.unwrap();
+
+        let dashboard = db.compute_dashboard("dev1", "aaaa0001").await.unwrap();
+        assert_eq!(dashboard.total_value_usd, "1000.00");
+        assert_eq!((dashboard.total_assets, dashboard.total_networks), (3, 2));
+        for json in [&dashboard.networks_json, &dashboard.assets_json] {
+            let sum: f64 = percentages(json).iter().sum();
+            assert!((sum - 100.0).abs() < 1e-9, "{}", sum);
+        }
+        assert!((percentages(&dashboard.networks_json)[0] - 66.667).abs() < 1e-9);
+
+        let combined = db.compute_combined_dashboard().await.unwrap();
+        assert!(combined.is_combined);
+        assert_eq!(combined.total_value_usd, "2000.00");
+        assert_eq!(combined.included_devices.as_deref(), Some(r#"["dev1","dev2"]"#));
+        let assets: Vec<Value> = serde_json::from_str(&combined.assets_json).unwrap();
+        assert_eq!(assets[0]["ticker"], "BTC");
+        assert!((assets[0]["percentage"].as_f64().unwrap() - 66.6665).abs() < 1e-9);
+
+        // The combined row is not a wallet dashboard and is not audited as one
+        assert_eq!(db.audit_portfolio_integrity().await.unwrap().dashboards_checked, 1);
+    }
+
+    #[tokio::test]
+    async fn test_empty_portfolio_and_staleness() {
+        let db = Database::new_in_memory().await.unwrap();
+        assert!(db.get_fresh_dashboard(Some("dev1"), "").await.unwrap().is_none());
+
+        let empty = db.compute_dashboard("dev1", "").await.unwrap();
+        assert_eq!((empty.total_value_usd.as_str(), empty.total_assets), ("0.00", 0));
+        assert_eq!(empty.networks_json, "[]");
+        assert!(db.get_fresh_dashboard(Some("dev1"), "").await.unwrap().is_some());
+
+        // Zero-value balances count as assets but share nothing
+        db.upsert_portfolio_balances("dev1", "", vec![balance("dev1", "DOGE", "bip122:1a91e3dace36e2be3bf030a65679fe82", "0", "balance")])
+            .await.unwrap();
+        assert!(db.get_fresh_dashboard(Some("dev1"), "").await.unwrap().is_none());
+        age_balances(&db).await;
+        let zero = db.compute_dashboard("dev1", "").await.unwrap();
+        assert_eq!((zero.total_value_usd.as_str(), zero.total_assets), ("0.00", 1));
+        assert_eq!(percentages(&zero.assets_json), [0.0]);
+        assert_eq!(db.get_fresh_dashboard(Some("dev1"), "").await.unwrap().unwrap().id, zero.id);
+
+        db.compute_combined_dashboard().await.unwrap();
+        assert!(db.get_fresh_dashboard(None, "").await.unwrap().is_some());
+        db.upsert_portfolio_balances("dev2", "", vec![balance("dev2", "ETH", "eip155:1", "5", "balance")]).await.unwrap();
+        assert!(db.get_fresh_dashboard(None, "").await.unwrap().is_none());
+        // Another device's write leaves this wallet's dashboard fresh
+        assert!(db.get_fresh_dashboard(Some("dev1"), "").await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,446 @@
+//! Location of the vault data directory (database, backups, logs, caches)
+//!
+//! Resolution order: the `KEEPKEY_DATA_DIR` environment variable, then the
+//! pointer file written by a relocation, then `~/.keepkey`. The pointer file,
+//! the pending-relocation marker and the instance lock always stay in
+//! `~/.keepkey` so every process can find them.
+
+use crate::errors::{DatabaseError, Result};
+use serde::Serialize;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const DATA_DIR_ENV: &str = "KEEPKEY_DATA_DIR";
+pub const DATABASE_FILE: &str = "keepkey.db";
+const POINTER_FILE: &str = "data-location";
+const PENDING_FILE: &str = "data-location.pending";
+
+/// Files that are never relocated
+const PINNED_FILES: [&str; 3] = [POINTER_FILE, PENDING_FILE, "vault.lock"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum DataDirSource {
+    Env,
+    Pointer,
+    Default,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct DataDirLocation {
+    pub path: PathBuf,
+    pub source: DataDirSource,
+}
+
+/// `~/.keepkey`, where the pointer file lives
+pub fn default_data_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".keepkey")
+}
+
+/// The data directory this process should use
+pub fn resolve_data_dir() -> DataDirLocation {
+    resolve_data_dir_from(std::env::var_os(DATA_DIR_ENV), &default_data_dir())
+}
+
+pub fn resolve_data_dir_from(env: Option<OsString>, default_dir: &Path) -> DataDirLocation {
+    if let Some(dir) = env.filter(|v| !v.is_empty()) {
+        return DataDirLocation { path: PathBuf::from(dir), source: DataDirSource::Env };
+    }
+    match read_pointer(default_dir) {
+        Some(path) => DataDirLocation { path, source: DataDirSource::Pointer },
+        None => DataDirLocation { path: default_dir.to_path_buf(), source: DataDirSource::Default },
+    }
+}
+
+/// Relocated directory recorded in the pointer file; ignored unless absolute
+fn read_pointer(default_dir: &Path) -> Option<PathBuf> {
+    let contents = std::fs::read_to_string(default_dir.join(POINTER_FILE)).ok()?;
+    let path = PathBuf::from(contents.trim());
+    if path.as_os_str().is_empty() || !path.is_absolute() {
+        log::warn!("Ignoring invalid data directory pointer: {:?}", contents.trim());
+        return None;
+    }
+    Some(path)
+}
+
+/// Write `contents` next to `path` and rename it into place
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Record `target` as the data directory; pointing back at the default removes the pointer
+pub fn write_pointer(default_dir: &Path, target: &Path) -> io::Result<()> {
+    if target == default_dir {
+        return match std::fs::remove_file(default_dir.join(POINTER_FILE)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    write_atomically(&default_dir.join(POINTER_FILE), &target.to_string_lossy())
+}
+
+/// Relocation to perform on the next launch, before the database is opened
+pub fn pending_relocation(default_dir: &Path) -> Option<PathBuf> {
+    let contents = std::fs::read_to_string(default_dir.join(PENDING_FILE)).ok()?;
+    Some(PathBuf::from(contents.trim())).filter(|p| p.is_absolute())
+}
+
+pub fn schedule_relocation(default_dir: &Path, target: &Path) -> io::Result<()> {
+    write_atomically(&default_dir.join(PENDING_FILE), &target.to_string_lossy())
+}
+
+pub fn clear_pending_relocation(default_dir: &Path) -> io::Result<()> {
+    match std::fs::remove_file(default_dir.join(PENDING_FILE)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Whether `name` (a top-level entry of the data directory) is a database file
+pub fn is_database_file(name: &str) -> bool {
+    name.starts_with(DATABASE_FILE)
+}
+
+/// Relocatable files under `dir`: (path relative to `dir`, size), sorted.
+/// Database files are included only when `include_database` is set.
+pub fn relocatable_files(dir: &Path, include_database: bool) -> io::Result<Vec<(PathBuf, u64)>> {
+    fn walk(root: &Path, rel: &Path, out: &mut Vec<(PathBuf, u64)>) -> io::Result<()> {
+        for entry in std::fs::read_dir(root.join(rel))? {
+            let entry = entry?;
+            let rel_path = rel.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                walk(root, &rel_path, out)?;
+            } else if file_type.is_file() {
+                out.push((rel_path, entry.metadata()?.len()));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(dir, Path::new(""), &mut files)?;
+    files.retain(|(rel, _)| {
+        let top = rel.components().next().map(|c| c.as_os_str().to_string_lossy().into_owned()).unwrap_or_default();
+        let single = rel.components().count() == 1;
+        !(single && PINNED_FILES.contains(&top.as_str())) && (include_database || !(single && is_database_file(&top)))
+    });
+    files.sort();
+    Ok(files)
+}
+
+/// Nearest existing ancestor, canonicalized, with the not-yet-existing remainder appended
+fn normalize(path: &Path) -> PathBuf {
+    let mut existing = path.to_path_buf();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.file_name().map(|n| n.to_owned()), existing.parent()) {
+            (Some(name), Some(parent)) => {
+                rest.push(name);
+                existing = parent.to_path_buf();
+            }
+            _ => break,
+        }
+    }
+    let mut normalized = existing.canonicalize().unwrap_or(existing);
+    for name in rest.into_iter().rev() {
+        normalized.push(name);
+    }
+    normalized
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`
+#[cfg(unix)]
+pub(crate) fn available_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is NUL-terminated and stat is a valid out-pointer
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Bytes available to the current user on the volume holding `path`
+#[cfg(windows)]
+pub(crate) fn available_space(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut available: u64 = 0;
+    // SAFETY: wide is NUL-terminated and the out-pointers are valid or null
+    if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut()) } == 0 {
+        return None;
+    }
+    Some(available)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Check that `target` can take over from `current`, which needs `required_bytes`.
+///
+/// The target must be absolute, outside the current directory (and not one of
+/// its parents), empty or not yet created, writable and have room to spare.
+/// It may hold pinned files, so moving back to the default directory works.
+pub fn validate_relocation_target(current: &Path, target: &Path, required_bytes: u64) -> Result<()> {
+    if !target.is_absolute() {
+        return Err(DatabaseError::Validation("The new data directory must be an absolute path".to_string()));
+    }
+    let current_norm = normalize(current);
+    let target_norm = normalize(target);
+    if target_norm == current_norm {
+        return Err(DatabaseError::Validation("The new data directory is the current one".to_string()));
+    }
+    if target_norm.starts_with(&current_norm) {
+        return Err(DatabaseError::Validation("The new data directory cannot be inside the current one".to_string()));
+    }
+    if current_norm.starts_with(&target_norm) {
+        return Err(DatabaseError::Validation("The new data directory cannot contain the current one".to_string()));
+    }
+
+    if target.exists() {
+        if !target.is_dir() {
+            return Err(DatabaseError::Validation(format!("{} is not a directory", target.display())));
+        }
+        // Pinned files are allowed so the vault can be moved back to ~/.keepkey
+        let occupied = std::fs::read_dir(target)?
+            .filter_map(|entry| entry.ok())
+            .any(|entry| !PINNED_FILES.iter().any(|pinned| entry.file_name() == *pinned));
+        if occupied {
+            return Err(DatabaseError::Validation(format!("{} is not empty", target.display())));
+        }
+    }
+
+    // Probe writability where the data will actually go
+    let created = !target.exists();
+    std::fs::create_dir_all(target)
+        .map_err(|e| DatabaseError::Validation(format!("Cannot create {}: {}", target.display(), e)))?;
+    let probe = target.join(".keepkey-write-test");
+    let writable = std::fs::write(&probe, b"ok").and_then(|_| std::fs::remove_file(&probe));
+    let space = available_space(target);
+    if created {
+        let _ = std::fs::remove_dir(target);
+    }
+    writable.map_err(|e| DatabaseError::Validation(format!("{} is not writable: {}", target.display(), e)))?;
+
+    // Leave headroom for the database to keep growing after the move
+    let needed = required_bytes + required_bytes / 10;
+    if let Some(available) = space {
+        if available < needed {
+            return Err(DatabaseError::Validation(format!(
+                "Not enough free space on the target drive: {} bytes needed, {} available",
+                needed, available
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Copy `files` (from `relocatable_files`) from `from` to `to`, reporting
+/// (bytes copied, total bytes) after each file
+pub fn copy_files<F>(from: &Path, to: &Path, files: &[(PathBuf, u64)], mut progress: F) -> io::Result<u64>
+where
+    F: FnMut(u64, u64),
+{
+    let total: u64 = files.iter().map(|(_, size)| size).sum();
+    let mut copied = 0;
+    for (rel, _) in files {
+        let dest = to.join(rel);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        copied += std::fs::copy(from.join(rel), &dest)?;
+        progress(copied, total);
+    }
+    Ok(copied)
+}
+
+/// Whether a copy failed because another process holds the file open
+pub fn is_file_busy(err: &io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION / ERROR_LOCK_VIOLATION
+    #[cfg(windows)]
+    if matches!(err.raw_os_error(), Some(32) | Some(33)) {
+        return true;
+    }
+    err.kind() == io::ErrorKind::ResourceBusy
+}
+
+/// Open a copied database read-only and run SQLite's quick check on it
+pub fn verify_database_file(path: &Path) -> Result<()> {
+    let conn = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let status: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if status != "ok" {
+        return Err(DatabaseError::Validation(format!("Copied database failed its check: {}", status)));
+    }
+    Ok(())
+}
+
+/// Remove everything a failed relocation wrote into `target`
+pub fn discard_partial_copy(target: &Path, target_existed: bool) {
+    let result = if target_existed {
+        std::fs::read_dir(target).and_then(|entries| {
+            for entry in entries.flatten() {
+                if PINNED_FILES.iter().any(|pinned| entry.file_name() == *pinned) {
+                    continue;
+                }
+                let path = entry.path();
+                if path.is_dir() {
+                    std::fs::remove_dir_all(&path)?;
+                } else {
+                    std::fs::remove_file(&path)?;
+                }
+            }
+            Ok(())
+        })
+    } else {
+        std::fs::remove_dir_all(target)
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to clean up partial copy in {}: {}", target.display(), e);
+    }
+}
+
+/// Finish a relocation scheduled by an earlier run. Call before the database
+/// is opened; nothing in the data directory is in use yet. On failure the
+/// original directory stays in use and the pending marker is dropped.
+pub fn apply_pending_relocation(default_dir: &Path) -> Option<PathBuf> {
+    let target = pending_relocation(default_dir)?;
+    let current = resolve_data_dir_from(None, default_dir).path;
+    let _ = clear_pending_relocation(default_dir);
+
+    let target_existed = target.exists();
+    let result = relocatable_files(&current, true)
+        .map_err(DatabaseError::from)
+        .and_then(|files| {
+            let size: u64 = files.iter().map(|(_, s)| s).sum();
+            validate_relocation_target(&current, &target, size)?;
+            copy_files(&current, &target, &files, |_, _| {})?;
+            verify_database_file(&target.join(DATABASE_FILE))?;
+            write_pointer(default_dir, &target)?;
+            Ok(())
+        });
+
+    match result {
+        Ok(()) => {
+            log::info!("Moved vault data from {} to {}", current.display(), target.display());
+            Some(target)
+        }
+        Err(e) => {
+            log::error!("Scheduled data directory move to {} failed: {}", target.display(), e);
+            discard_partial_copy(&target, target_existed);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolution_order() {
+        let home = TempDir::new().unwrap();
+        let default_dir = home.path().join(".keepkey");
+        let moved = home.path().join("moved");
+
+        let location = resolve_data_dir_from(None, &default_dir);
+        assert_eq!(location, DataDirLocation { path: default_dir.clone(), source: DataDirSource::Default });
+
+        write_pointer(&default_dir, &moved).unwrap();
+        assert_eq!(resolve_data_dir_from(None, &default_dir).path, moved);
+        assert_eq!(resolve_data_dir_from(None, &default_dir).source, DataDirSource::Pointer);
+
+        // The environment override wins over the pointer file
+        let env = resolve_data_dir_from(Some(OsString::from("/srv/keepkey")), &default_dir);
+        assert_eq!(env, DataDirLocation { path: PathBuf::from("/srv/keepkey"), source: DataDirSource::Env });
+        assert_eq!(resolve_data_dir_from(Some(OsString::new()), &default_dir).source, DataDirSource::Pointer);
+
+        // Relative or garbage pointers are ignored; pointing back home removes it
+        std::fs::write(default_dir.join(POINTER_FILE), "relative/dir").unwrap();
+        assert_eq!(resolve_data_dir_from(None, &default_dir).source, DataDirSource::Default);
+        write_pointer(&default_dir, &moved).unwrap();
+        write_pointer(&default_dir, &default_dir).unwrap();
+        assert_eq!(resolve_data_dir_from(None, &default_dir).source, DataDirSource::Default);
+    }
+
+    #[test]
+    fn test_validation_failures() {
+        let root = TempDir::new().unwrap();
+        let current = root.path().join("current");
+        std::fs::create_dir_all(&current).unwrap();
+
+        let rejects = |target: &Path, required: u64, needle: &str| {
+            let err = validate_relocation_target(&current, target, required).unwrap_err().to_string();
+            assert!(err.contains(needle), "{} should mention {:?}", err, needle);
+        };
+        rejects(Path::new("relative"), 0, "absolute");
+        rejects(&current, 0, "current one");
+        rejects(&current.join("nested"), 0, "inside");
+        rejects(root.path(), 0, "contain");
+
+        let occupied = root.path().join("occupied");
+        std::fs::create_dir_all(&occupied).unwrap();
+        std::fs::write(occupied.join("file"), b"x").unwrap();
+        rejects(&occupied, 0, "not empty");
+
+        let home = root.path().join("home");
+        std::fs::create_dir_all(&home).unwrap();
+        write_pointer(&home, &current).unwrap();
+        validate_relocation_target(&current, &home, 0).unwrap();
+
+        let file_target = root.path().join("a-file");
+        std::fs::write(&file_target, b"x").unwrap();
+        rejects(&file_target, 0, "not a directory");
+
+        #[cfg(unix)]
+        rejects(&root.path().join("huge"), u64::MAX / 2, "free space");
+
+        // A fresh directory passes and is not left behind by the probe
+        let fresh = root.path().join("fresh");
+        validate_relocation_target(&current, &fresh, 1024).unwrap();
+        assert!(!fresh.exists());
+    }
+
+    #[tokio::test]
+    async fn test_pending_relocation_copies_and_switches() {
+        let home = TempDir::new().unwrap();
+        let default_dir = home.path().join(".keepkey");
+        let db = crate::Database::open_at_path(default_dir.join(DATABASE_FILE)).await.unwrap();
+        db.set_preference("theme", "dark").await.unwrap();
+        drop(db);
+        std::fs::create_dir_all(default_dir.join("backups")).unwrap();
+        std::fs::write(default_dir.join("backups").join("keepkey-2026-01-01.db"), b"backup").unwrap();
+        std::fs::write(default_dir.join("vault.lock"), b"123").unwrap();
+
+        let target = home.path().join("big-disk").join("keepkey");
+        schedule_relocation(&default_dir, &target).unwrap();
+        assert_eq!(apply_pending_relocation(&default_dir), Some(target.clone()));
+
+        assert!(pending_relocation(&default_dir).is_none());
+        assert_eq!(resolve_data_dir_from(None, &default_dir).path, target);
+        assert!(target.join("backups").join("keepkey-2026-01-01.db").exists());
+        assert!(!target.join("vault.lock").exists());
+        // The original is left untouched
+        assert!(default_dir.join(DATABASE_FILE).exists());
+
+        let moved = crate::Database::open_at_path(target.join(DATABASE_FILE)).await.unwrap();
+        assert_eq!(moved.get_preference("theme").await.unwrap().as_deref(), Some("dark"));
+    }
+}
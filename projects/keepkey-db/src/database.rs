@@ -1,21 +1,63 @@
-use crate::errors::Result;
-use crate::migrations::apply_migrations;
+use crate::errors::{DatabaseError, Result};
+use crate::migration_guard::{migrate_guarded, GuardOptions};
+use crate::migrations::{apply_migrations, pending_migrations};
+use crate::preferences::PREFERENCE_CHANGES_CAPACITY;
+use crate::types::{MigrationReport, PendingMigration, Preference};
 use rusqlite::{Connection, OpenFlags, OptionalExtension};
-use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::sync::{broadcast, Mutex};
 
 /// Main database manager
 pub struct Database {
     connection: Arc<Mutex<Connection>>,
-    path: PathBuf,
+    /// Changes only when the database is relocated
+    path: RwLock<PathBuf>,
+    /// Outcome of the startup (or last requested) schema migration
+    migration: RwLock<MigrationReport>,
+    /// Every preference written through `set_preference`
+    preference_changes: broadcast::Sender<Preference>,
 }
 
+/// Per-device tables cleared by `forget_device` (signing_audit is deliberately kept)
+const FORGET_DEVICE_TABLES: [&str; 14] = [
+    "device_connections",
+    "wallet_xpubs",
+    "cached_pubkeys",
+    "cache_metadata",
+    "frontload_progress",
+    "portfolio_balances",
+    "portfolio_dashboard",
+    "portfolio_history",
+    "transaction_cache",
+    "tax_lots",
+    "disposals",
+    "device_wallets",
+    "device_fault_logs",
+    "device_notes",
+];
+
+/// Per-device tables derived from the seed, cleared by `clear_wiped_device`
+const WIPED_DEVICE_TABLES: [&str; 5] = [
+    "wallet_xpubs",
+    "cached_pubkeys",
+    "frontload_progress",
+    "portfolio_balances",
+    "portfolio_dashboard",
+];
+
 impl Database {
     /// Create a new database instance
     pub async fn new() -> Result<Self> {
         let path = crate::get_database_path();
         let db = Self::open_at_path(path).await?;
+
+        // Earlier versions kept their data in ~/.keepkey whatever the data directory
+        if !db.is_read_only() {
+            if let Err(e) = db.migrate_legacy_data(&crate::data_dir::default_data_dir()).await {
+                log::warn!("Legacy data migration failed: {}", e);
+            }
+        }
         Ok(db)
     }
 
@@ -34,15 +76,23 @@ impl Database {
             OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
         )?;
 
-        // Apply migrations
-        if let Err(e) = apply_migrations(&conn) {
-            log::error!("Failed to apply migrations: {}", e);
-            return Err(e);
+        // Apply migrations, behind a dry run when the file needs upgrading
+        let report = match Self::migrate_file(&conn, &path, false, true) {
+            Ok(report) => report,
+            Err(e) => {
+                log::error!("Failed to apply migrations: {}", e);
+                return Err(e);
+            }
+        };
+        if report.is_read_only() {
+            log::warn!("Database opened read-only on its previous schema ({:?})", report.status);
         }
 
         let db = Database {
             connection: Arc::new(Mutex::new(conn)),
-            path,
+            path: RwLock::new(path),
+            migration: RwLock::new(report),
+            preference_changes: broadcast::channel(PREFERENCE_CHANGES_CAPACITY).0,
         };
 
         log::info!("Database initialized successfully");
@@ -64,16 +114,164 @@ impl Database {
 
         let db = Database {
             connection: Arc::new(Mutex::new(conn)),
-            path: PathBuf::from(":memory:"),
+            path: RwLock::new(PathBuf::from(":memory:")),
+            migration: RwLock::new(MigrationReport::up_to_date()),
+            preference_changes: broadcast::channel(PREFERENCE_CHANGES_CAPACITY).0,
         };
 
         log::info!("In-memory database initialized successfully");
         Ok(db)
     }
 
+    /// Preferences as they are written, for announcing changes to the app
+    pub fn subscribe_preference_changes(&self) -> broadcast::Receiver<Preference> {
+        self.preference_changes.subscribe()
+    }
+
+    pub(crate) fn notify_preference_change(&self, change: Preference) {
+        // No subscribers is normal (tests, the CLI)
+        let _ = self.preference_changes.send(change);
+    }
+
     /// Get the database path
-    pub fn path(&self) -> &PathBuf {
-        &self.path
+    pub fn path(&self) -> PathBuf {
+        self.path.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn migrate_file(conn: &Connection, path: &Path, force: bool, respect_deferral: bool) -> Result<MigrationReport> {
+        // Backups live next to the database file (see backup::default_backup_dir)
+        let backup_dir = path.parent().map(|p| p.join("backups")).unwrap_or_else(|| PathBuf::from("backups"));
+        let available_space = path.parent().and_then(crate::data_dir::available_space);
+        let options = GuardOptions {
+            db_path: path,
+            backup_dir: &backup_dir,
+            available_space,
+            force,
+            respect_deferral,
+        };
+        migrate_guarded(conn, &options, apply_migrations)
+    }
+
+    /// Outcome of the last schema migration attempt
+    pub fn migration_report(&self) -> MigrationReport {
+        self.migration.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Whether a blocked or deferred migration left the database read-only
+    pub fn is_read_only(&self) -> bool {
+        self.migration.read().unwrap_or_else(|e| e.into_inner()).is_read_only()
+    }
+
+    /// Forget a blocked migration once the schema was brought up to date another way
+    pub(crate) fn clear_migration_block(&self) {
+        *self.migration.write().unwrap_or_else(|e| e.into_inner()) = MigrationReport::up_to_date();
+    }
+
+    /// Schema changes still to be applied to the open database
+    pub async fn get_pending_migrations(&self) -> Result<Vec<PendingMigration>> {
+        self.with_connection(pending_migrations).await
+    }
+
+    /// Apply pending migrations now, ignoring the deferral preference.
+    ///
+    /// Runs the same dry run as startup unless `force` is set, in which case
+    /// the file is backed up and migrated directly. A blocked attempt leaves
+    /// the database read-only again.
+    pub async fn apply_migrations_now(&self, force: bool) -> Result<MigrationReport> {
+        let path = self.path();
+        let report = self.with_connection(|conn| {
+            conn.pragma_update(None, "query_only", false)?;
+            if path == Path::new(":memory:") {
+                apply_migrations(conn)?;
+                return Ok(MigrationReport::up_to_date());
+            }
+            Self::migrate_file(conn, &path, force, false)
+        }).await?;
+
+        log::info!("Schema migration requested: {:?}", report.status);
+        *self.migration.write().unwrap_or_else(|e| e.into_inner()) = report.clone();
+        Ok(report)
+    }
+
+    /// Move the database file to `new_path` and switch to it without reopening
+    /// the `Database`. The copy is written with `VACUUM INTO`, checked and
+    /// migrated before the live connection is swapped; the connection lock is
+    /// held throughout so no writes are lost. The old file is left in place.
+    ///
+    /// `commit` runs after the copy is verified and before the switch (e.g. to
+    /// record the new location); if it fails the copy is removed and nothing changes.
+    pub async fn relocate_to<F>(&self, new_path: &Path, commit: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()> + Send,
+    {
+        if new_path.exists() {
+            return Err(DatabaseError::Validation(format!("{} already exists", new_path.display())));
+        }
+        if let Some(parent) = new_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut staging = new_path.as_os_str().to_owned();
+        staging.push(".relocating");
+        let staging = PathBuf::from(staging);
+
+        self.with_connection_mut(|conn| {
+            let result = (|| -> Result<Connection> {
+                conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+                conn.execute("VACUUM INTO ?1", [staging.to_string_lossy()])?;
+                std::fs::rename(&staging, new_path)?;
+                crate::data_dir::verify_database_file(new_path)?;
+
+                let new_conn = Connection::open_with_flags(
+                    new_path,
+                    OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+                )?;
+                apply_migrations(&new_conn)?;
+                commit()?;
+                Ok(new_conn)
+            })();
+
+            match result {
+                Ok(new_conn) => {
+                    *conn = new_conn;
+                    Ok(())
+                }
+                Err(e) => {
+                    for leftover in [&staging, new_path] {
+                        let _ = std::fs::remove_file(leftover);
+                    }
+                    Err(e)
+                }
+            }
+        })
+        .await?;
+
+        log::info!("Database moved to {:?}", new_path);
+        *self.path.write().unwrap_or_else(|e| e.into_inner()) = new_path.to_path_buf();
+        Ok(())
+    }
+
+    /// Undo `relocate_to`: switch back to the file at `previous`, which the
+    /// move left in place. `commit` runs before the switch, as in `relocate_to`.
+    /// The relocated file is left for the caller to remove.
+    pub async fn return_to<F>(&self, previous: &Path, commit: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()> + Send,
+    {
+        if !previous.exists() {
+            return Err(DatabaseError::Validation(format!("{} no longer exists", previous.display())));
+        }
+        self.with_connection_mut(|conn| {
+            let old_conn = Connection::open_with_flags(previous, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+            apply_migrations(&old_conn)?;
+            commit()?;
+            *conn = old_conn;
+            Ok(())
+        })
+        .await?;
+
+        log::info!("Database moved back to {:?}", previous);
+        *self.path.write().unwrap_or_else(|e| e.into_inner()) = previous.to_path_buf();
+        Ok(())
     }
 
     /// Health check - ensure database is accessible
@@ -95,7 +293,17 @@ impl Database {
         R: Send,
     {
         let conn = self.connection.lock().await;
-        f(&*conn)
+        f(&conn)
+    }
+
+    /// Execute a closure that needs exclusive access to the connection (e.g. online restore)
+    pub(crate) async fn with_connection_mut<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Connection) -> Result<R> + Send,
+        R: Send,
+    {
+        let mut conn = self.connection.lock().await;
+        f(&mut conn)
     }
 
     /// Execute a transaction
@@ -155,18 +363,32 @@ impl Database {
                     (None, None, None, None, None, false, false, false, false)
                 };
             
+            // Re-registering refreshes what the device reports; setup progress,
+            // first_seen and any archive record are kept
             conn.execute(
-                "INSERT OR REPLACE INTO devices (
+                "INSERT INTO devices (
                     device_id, first_seen, last_seen, features, serial_number,
                     vendor, model, label, firmware_variant, firmware_version,
                     bootloader_mode, initialized, pin_protection, passphrase_protection,
                     setup_complete, setup_step_completed
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, FALSE, 0)
+                ON CONFLICT(device_id) DO UPDATE SET
+                    last_seen = excluded.last_seen,
+                    features = excluded.features,
+                    serial_number = excluded.serial_number,
+                    vendor = excluded.vendor,
+                    model = excluded.model,
+                    label = excluded.label,
+                    firmware_variant = excluded.firmware_variant,
+                    firmware_version = excluded.firmware_version,
+                    bootloader_mode = excluded.bootloader_mode,
+                    initialized = excluded.initialized,
+                    pin_protection = excluded.pin_protection,
+                    passphrase_protection = excluded.passphrase_protection",
                 rusqlite::params![
                     device_id, now, now, features, serial_number,
                     vendor, model, label, firmware_variant, firmware_version,
                     bootloader_mode, initialized, pin_protection, passphrase_protection,
-                    false, 0
                 ],
             )?;
             
@@ -333,6 +555,40 @@ impl Database {
         }).await
     }
 
+    /// Store a label the device accepted, in the column and in the stored features
+    pub async fn set_device_label(&self, device_id: &str, label: &str) -> Result<()> {
+        self.with_connection(|conn| {
+            let updated = conn.execute(
+                "UPDATE devices SET
+                    label = ?1,
+                    features = CASE WHEN json_valid(features) THEN json_set(features, '$.label', ?1) ELSE features END
+                 WHERE device_id = ?2",
+                [label, device_id],
+            )?;
+            if updated == 0 {
+                return Err(crate::errors::DatabaseError::DeviceNotFound(device_id.to_string()));
+            }
+            Ok(())
+        }).await
+    }
+
+    /// Store a PIN change the device confirmed, in the column and in the stored features
+    pub async fn set_device_pin_protection(&self, device_id: &str, pin_protection: bool) -> Result<()> {
+        self.with_connection(|conn| {
+            let updated = conn.execute(
+                "UPDATE devices SET
+                    pin_protection = ?1,
+                    features = CASE WHEN json_valid(features) THEN json_set(features, '$.pinProtection', json(?2)) ELSE features END
+                 WHERE device_id = ?3",
+                rusqlite::params![pin_protection, if pin_protection { "true" } else { "false" }, device_id],
+            )?;
+            if updated == 0 {
+                return Err(crate::errors::DatabaseError::DeviceNotFound(device_id.to_string()));
+            }
+            Ok(())
+        }).await
+    }
+
     /// Get device registry (all devices)
     pub async fn get_device_registry(&self) -> Result<Vec<serde_json::Value>> {
         self.with_connection(|conn| {
@@ -419,13 +675,38 @@ impl Database {
         self.with_connection(|conn| {
             let mut stmt = conn.prepare("SELECT eth_address FROM devices WHERE device_id = ?1")?;
             let address = stmt.query_row([device_id], |row| {
-                Ok(row.get::<_, Option<String>>(0)?)
+                row.get::<_, Option<String>>(0)
             }).optional()?;
             
             Ok(address.flatten())
         }).await
     }
 
+    /// Remove a device and everything cached for it (xpubs, balances, history,
+    /// transactions, wallets). The signing audit trail is kept.
+    ///
+    /// Returns false if the device was not registered.
+    pub async fn forget_device(&self, device_id: &str) -> Result<bool> {
+        self.transaction(|tx| {
+            for table in FORGET_DEVICE_TABLES {
+                tx.execute(&format!("DELETE FROM {} WHERE device_id = ?1", table), [device_id])?;
+            }
+            let removed = tx.execute("DELETE FROM devices WHERE device_id = ?1", [device_id])?;
+            Ok(removed > 0)
+        }).await
+    }
+
+    /// Drop what was derived from a wiped device's old seed (xpubs, cached
+    /// keys, balances). The device stays registered and its history is kept.
+    pub async fn clear_wiped_device(&self, device_id: &str) -> Result<()> {
+        self.transaction(|tx| {
+            for table in WIPED_DEVICE_TABLES {
+                tx.execute(&format!("DELETE FROM {} WHERE device_id = ?1", table), [device_id])?;
+            }
+            Ok(())
+        }).await
+    }
+
     // ========== Onboarding/Preferences Methods ==========
 
     /// Check if user has completed onboarding
@@ -457,27 +738,30 @@ impl Database {
         }).await
     }
 
-    /// Set user preference
-    pub async fn set_preference(&self, key: &str, value: &str) -> Result<()> {
-        let pref_key = format!("pref_{}", key);
-        
+    /// Argon2 hash of the app unlock PIN. Stored outside the `pref_` namespace
+    /// so `set_preference` cannot overwrite it.
+    pub async fn get_app_pin_hash(&self) -> Result<Option<String>> {
         self.with_connection(|conn| {
-            conn.execute(
-                "INSERT OR REPLACE INTO meta (key, val) VALUES (?1, ?2)",
-                rusqlite::params![pref_key, value],
-            )?;
-            Ok(())
+            let hash = conn.query_row(
+                "SELECT val FROM meta WHERE key = 'app_pin_hash'",
+                [],
+                |row| row.get::<_, String>(0),
+            ).optional()?;
+            Ok(hash)
         }).await
     }
 
-    /// Get user preference
-    pub async fn get_preference(&self, key: &str) -> Result<Option<String>> {
-        let pref_key = format!("pref_{}", key);
-        
+    /// Store the app PIN hash, or remove it with None
+    pub async fn set_app_pin_hash(&self, hash: Option<&str>) -> Result<()> {
         self.with_connection(|conn| {
-            let mut stmt = conn.prepare("SELECT val FROM meta WHERE key = ?1")?;
-            let result: Option<String> = stmt.query_row([pref_key], |row| row.get(0)).ok();
-            Ok(result)
+            match hash {
+                Some(hash) => conn.execute(
+                    "INSERT OR REPLACE INTO meta (key, val) VALUES ('app_pin_hash', ?1)",
+                    [hash],
+                )?,
+                None => conn.execute("DELETE FROM meta WHERE key = 'app_pin_hash'", [])?,
+            };
+            Ok(())
         }).await
     }
 
@@ -497,6 +781,7 @@ impl Database {
 mod tests {
     use super::*;
     use tempfile::TempDir;
+    use crate::types::WalletXpubInput;
 
     #[tokio::test]
     async fn test_database_creation() {
@@ -530,5 +815,138 @@ mod tests {
         // Check ETH address
         let eth_addr = db.get_device_eth_address("test_device").await.unwrap();
         assert_eq!(eth_addr, Some("0x1234".to_string()));
+
+        // Reconnecting refreshes the features without sending it through setup again
+        db.register_device("test_device", Some("12345"), Some(r#"{"label":"Renamed"}"#)).await.unwrap();
+        assert!(!db.device_needs_setup("test_device").await.unwrap());
+        let device = db.get_device_by_id("test_device").await.unwrap().unwrap();
+        assert_eq!(device["label"], "Renamed");
+        assert_eq!(device["eth_address"], "0x1234");
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_forget_device_clears_cached_data() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.register_device("dev1", Some("12345"), Some("{}")).await.unwrap();
+        db.register_device("dev2", None, Some("{}")).await.unwrap();
+        db.record_wallet_session("dev1", "aaaa0001", false).await.unwrap();
+        db.record_wallet_session("dev2", "bbbb0002", false).await.unwrap();
+
+        assert!(db.forget_device("dev1").await.unwrap());
+        assert!(db.get_device_by_id("dev1").await.unwrap().is_none());
+        assert!(db.list_known_wallets("dev1").await.unwrap().is_empty());
+        assert_eq!(db.list_known_wallets("dev2").await.unwrap().len(), 1);
+        assert!(!db.forget_device("dev1").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_set_device_label() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.register_device("dev1", None, Some(r#"{"label":"Old","version":"7.10.0"}"#)).await.unwrap();
+
+        db.set_device_label("dev1", "Savings").await.unwrap();
+        let device = db.get_device_by_id("dev1").await.unwrap().unwrap();
+        assert_eq!(device["label"], "Savings");
+        let features: serde_json::Value = serde_json::from_str(device["features"].as_str().unwrap()).unwrap();
+        assert_eq!((features["label"].as_str(), features["version"].as_str()), (Some("Savings"), Some("7.10.0")));
+
+        assert!(db.set_device_label("missing", "Savings").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_device_pin_protection() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.register_device("dev1", None, Some(r#"{"pinProtection":true,"version":"7.10.0"}"#)).await.unwrap();
+
+        db.set_device_pin_protection("dev1", false).await.unwrap();
+        let device = db.get_device_by_id("dev1").await.unwrap().unwrap();
+        assert_eq!(device["pin_protection"], false);
+        let features: serde_json::Value = serde_json::from_str(device["features"].as_str().unwrap()).unwrap();
+        assert_eq!((features["pinProtection"].as_bool(), features["version"].as_str()), (Some(false), Some("7.10.0")));
+
+        assert!(db.set_device_pin_protection("missing", true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_clear_wiped_device_keeps_registration() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.register_device("dev1", None, None).await.unwrap();
+        db.register_device("dev2", None, None).await.unwrap();
+        for device_id in ["dev1", "dev2"] {
+            db.save_wallet_xpub(&WalletXpubInput {
+                device_id: device_id.to_string(),
+                wallet_fingerprint: "aaaa0001".to_string(),
+                path: "m/84'/0'/0'".to_string(),
+                label: "Bitcoin Native Segwit".to_string(),
+                caip: "bip122:000000000019d6689c085ae165831e93/slip44:0".to_string(),
+                pubkey: "zpub".to_string(),
+            }).await.unwrap();
+            db.insert_cached_pubkey(device_id, "aaaa0001", "m/84'/0'/0'", "Bitcoin", Some("p2wpkh"), "zpub").await.unwrap();
+        }
+
+        db.clear_wiped_device("dev1").await.unwrap();
+        assert!(db.get_wallet_xpubs("dev1", "aaaa0001").await.unwrap().is_empty());
+        assert_eq!(db.get_cached_pubkey("dev1", "aaaa0001", "m/84'/0'/0'", "Bitcoin", Some("p2wpkh")).await.unwrap(), None);
+        assert!(db.get_device_by_id("dev1").await.unwrap().is_some());
+        assert_eq!(db.get_wallet_xpubs("dev2", "aaaa0001").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_app_pin_hash_is_not_a_preference() {
+        let db = Database::new_in_memory().await.unwrap();
+        assert_eq!(db.get_app_pin_hash().await.unwrap(), None);
+        db.set_app_pin_hash(Some("$argon2id$hash")).await.unwrap();
+        db.set_preference("app_pin_hash", "overwritten").await.unwrap();
+        assert_eq!(db.get_app_pin_hash().await.unwrap().as_deref(), Some("$argon2id$hash"));
+        db.set_app_pin_hash(None).await.unwrap();
+        assert_eq!(db.get_app_pin_hash().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_relocate_keeps_data_and_original() {
+        let temp_dir = TempDir::new().unwrap();
+        let old_path = temp_dir.path().join("old").join("keepkey.db");
+        let new_path = temp_dir.path().join("new").join("keepkey.db");
+        let db = Database::open_at_path(old_path.clone()).await.unwrap();
+        db.set_preference("theme", "dark").await.unwrap();
+
+        db.relocate_to(&new_path, || Ok(())).await.unwrap();
+        assert_eq!(db.path(), new_path);
+        db.set_preference("relocated", "yes").await.unwrap();
+        assert_eq!(db.get_preference("theme").await.unwrap().as_deref(), Some("dark"));
+        assert!(old_path.exists());
+
+        // Writes after the move land in the new file only
+        drop(db);
+        let old = Database::open_at_path(old_path).await.unwrap();
+        assert!(old.get_preference("relocated").await.unwrap().is_none());
+
+        // An occupied destination is refused, and a failed commit leaves no copy behind
+        assert!(old.relocate_to(&new_path, || Ok(())).await.is_err());
+        let other_path = temp_dir.path().join("other").join("keepkey.db");
+        let refused = old.relocate_to(&other_path, || Err(DatabaseError::Validation("no".to_string()))).await;
+        assert!(refused.is_err());
+        assert!(!other_path.exists());
+        assert_eq!(old.path(), temp_dir.path().join("old").join("keepkey.db"));
+    }
+
+    #[tokio::test]
+    async fn test_return_to_switches_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let old_path = temp_dir.path().join("old").join("keepkey.db");
+        let new_path = temp_dir.path().join("new").join("keepkey.db");
+        let db = Database::open_at_path(old_path.clone()).await.unwrap();
+        db.set_preference("theme", "dark").await.unwrap();
+        db.relocate_to(&new_path, || Ok(())).await.unwrap();
+
+        db.return_to(&old_path, || Ok(())).await.unwrap();
+        assert_eq!(db.path(), old_path);
+        // The relocated file is no longer in use and can be removed
+        std::fs::remove_file(&new_path).unwrap();
+        db.set_preference("returned", "yes").await.unwrap();
+        assert_eq!(db.get_preference("theme").await.unwrap().as_deref(), Some("dark"));
+
+        assert!(db.return_to(&temp_dir.path().join("gone.db"), || Ok(())).await.is_err());
+        assert_eq!(db.path(), old_path);
+    }
+}
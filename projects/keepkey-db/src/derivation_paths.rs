@@ -0,0 +1,253 @@
+//! Derivation path catalog
+//!
+//! Paths are loaded from JSON in the default-paths.json format into
+//! `derivation_paths`, and each path is mapped in `path_asset_mapping` to the
+//! native asset of every network it serves that the asset registry knows, so
+//! load the assets first. Loading the same `path_id` again updates it.
+
+use serde::Deserialize;
+use crate::errors::{DatabaseError, Result};
+use crate::types::DerivationPathInfo;
+use crate::Database;
+
+/// Paths frontloaded after device setup, bundled with the app
+const DEFAULT_PATHS: &str = include_str!("../data/default_paths.json");
+
+/// Deepest BIP32 path accepted
+const MAX_PATH_DEPTH: usize = 10;
+
+const HARDENED: u32 = 0x8000_0000;
+
+/// Columns read by `derivation_path_from_row`, in order
+pub(crate) const DERIVATION_PATH_COLUMNS: &str =
+    "path_id, note, blockchain, symbol, networks, script_type, address_n_list, COALESCE(is_default, 0)";
+
+pub(crate) fn derivation_path_from_row(row: &rusqlite::Row) -> rusqlite::Result<DerivationPathInfo> {
+    let networks: String = row.get(4)?;
+    let address_n: String = row.get(6)?;
+    Ok(DerivationPathInfo {
+        path_id: row.get(0)?,
+        note: row.get(1)?,
+        blockchain: row.get(2)?,
+        symbol: row.get(3)?,
+        // Stored as JSON arrays
+        networks: serde_json::from_str(&networks).unwrap_or_default(),
+        script_type: row.get(5)?,
+        address_n: serde_json::from_str(&address_n).unwrap_or_default(),
+        is_default: row.get(7)?,
+    })
+}
+
+#[derive(Deserialize)]
+struct PathFile {
+    paths: Vec<PathEntry>,
+}
+
+/// One entry of default-paths.json; the snake_case names are accepted too
+#[derive(Deserialize)]
+struct PathEntry {
+    #[serde(alias = "path_id")]
+    id: String,
+    #[serde(default)]
+    note: Option<String>,
+    blockchain: String,
+    symbol: String,
+    #[serde(default)]
+    networks: Vec<String>,
+    #[serde(default)]
+    script_type: Option<String>,
+    #[serde(rename = "addressNList", alias = "address_n_list")]
+    address_n_list: Vec<u32>,
+    #[serde(default, rename = "addressNListMaster", alias = "address_n_list_master")]
+    address_n_list_master: Option<Vec<u32>>,
+    #[serde(default)]
+    curve: Option<String>,
+    #[serde(default, rename = "showDisplay", alias = "show_display")]
+    show_display: bool,
+    #[serde(default, rename = "isDefault", alias = "is_default")]
+    is_default: bool,
+}
+
+impl PathEntry {
+    /// Account and address paths, checked; the address path defaults to the
+    /// first receive address under the account
+    fn address_lists(&self) -> Result<(Vec<u32>, Vec<u32>)> {
+        let invalid = |reason: &str| DatabaseError::Validation(format!("Path {}: {}", self.id, reason));
+        if self.id.trim().is_empty() {
+            return Err(DatabaseError::Validation("Path id cannot be empty".to_string()));
+        }
+        let account = &self.address_n_list;
+        if account.is_empty() || account.len() > MAX_PATH_DEPTH {
+            return Err(invalid("addressNList must have 1 to 10 levels"));
+        }
+        if account[0] & HARDENED == 0 {
+            return Err(invalid("the purpose level must be hardened"));
+        }
+        let master = match &self.address_n_list_master {
+            Some(master) => master.clone(),
+            None => [account.as_slice(), &[0, 0]].concat(),
+        };
+        if master.len() > MAX_PATH_DEPTH || !master.starts_with(account) {
+            return Err(invalid("addressNListMaster must extend addressNList"));
+        }
+        Ok((account.clone(), master))
+    }
+}
+
+impl Database {
+    /// Load paths from JSON in the default-paths.json format, in one
+    /// transaction: either every path is stored or none. A path_id already
+    /// stored, or repeated in the file, is updated. Returns the number of
+    /// paths written.
+    pub async fn load_derivation_paths(&self, json: &str) -> Result<usize> {
+        let file: PathFile = serde_json::from_str(json)?;
+        let entries = file
+            .paths
+            .into_iter()
+            .map(|entry| entry.address_lists().map(|lists| (entry, lists)))
+            .collect::<Result<Vec<_>>>()?;
+        let now = Self::current_timestamp();
+
+        self.transaction(move |tx| {
+            for (entry, (account, master)) in &entries {
+                tx.execute(
+                    "INSERT INTO derivation_paths (path_id, note, blockchain, symbol, networks, script_type, address_n_list,
+                                                   address_n_list_master, curve, show_display, is_default,
+                                                   created_at, last_updated)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?12)
+                     ON CONFLICT(path_id) DO UPDATE SET
+                         note = excluded.note, blockchain = excluded.blockchain, symbol = excluded.symbol,
+                         networks = excluded.networks, script_type = excluded.script_type,
+                         address_n_list = excluded.address_n_list, address_n_list_master = excluded.address_n_list_master,
+                         curve = excluded.curve, show_display = excluded.show_display, is_default = excluded.is_default,
+                         last_updated = excluded.last_updated",
+                    rusqlite::params![
+                        entry.id,
+                        entry.note,
+                        entry.blockchain,
+                        entry.symbol,
+                        serde_json::to_string(&entry.networks)?,
+                        entry.script_type,
+                        serde_json::to_string(account)?,
+                        serde_json::to_string(master)?,
+                        entry.curve.as_deref().unwrap_or("secp256k1"),
+                        entry.show_display,
+                        entry.is_default,
+                        now,
+                    ],
+                )?;
+
+                tx.execute("DELETE FROM path_asset_mapping WHERE path_id = ?1", [&entry.id])?;
+                for network_id in &entry.networks {
+                    tx.execute(
+                        "INSERT OR IGNORE INTO path_asset_mapping (path_id, caip, network_id, is_primary)
+                         SELECT ?1, caip, network_id, ?3 FROM assets WHERE network_id = ?2 AND is_native = 1",
+                        rusqlite::params![entry.id, network_id, entry.is_default],
+                    )?;
+                }
+            }
+            log::info!("Loaded {} derivation paths", entries.len());
+            Ok(entries.len())
+        }).await
+    }
+
+    /// Load the bundled frontload paths; run after `seed_default_assets`
+    pub async fn load_default_derivation_paths(&self) -> Result<usize> {
+        self.load_derivation_paths(DEFAULT_PATHS).await
+    }
+
+    /// Paths that serve `network_id`, the default first
+    pub async fn get_paths_for_network(&self, network_id: &str) -> Result<Vec<DerivationPathInfo>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM derivation_paths
+                 WHERE EXISTS (SELECT 1 FROM json_each(derivation_paths.networks) WHERE value = ?1)
+                 ORDER BY is_default DESC, id",
+                DERIVATION_PATH_COLUMNS
+            ))?;
+            let paths = stmt
+                .query_map([network_id], derivation_path_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(paths)
+        }).await
+    }
+
+    /// The path to use for `blockchain`: its default path, or with a
+    /// `script_type` the default among paths of that script type, falling
+    /// back to the first loaded
+    pub async fn get_default_path(&self, blockchain: &str, script_type: Option<&str>) -> Result<Option<DerivationPathInfo>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM derivation_paths
+                 WHERE blockchain = ?1 COLLATE NOCASE AND (?2 IS NULL OR script_type = ?2)
+                 ORDER BY is_default DESC, id
+                 LIMIT 1",
+                DERIVATION_PATH_COLUMNS
+            ))?;
+            let mut paths = stmt
+                .query_map(rusqlite::params![blockchain, script_type], derivation_path_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(paths.pop())
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93";
+
+    #[tokio::test]
+    async fn test_default_paths_load_and_map_to_native_assets() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.seed_default_assets().await.unwrap();
+        assert_eq!(db.load_default_derivation_paths().await.unwrap(), 5);
+        assert_eq!(db.load_default_derivation_paths().await.unwrap(), 5);
+
+        let btc_paths = db.get_paths_for_network(BTC).await.unwrap();
+        assert_eq!(btc_paths.len(), 3);
+        assert_eq!(btc_paths[0].path_id, "bitcoin_native_segwit_account_0");
+        assert_eq!(
+            db.get_default_path("bitcoin", Some("p2pkh")).await.unwrap().unwrap().address_n,
+            [HARDENED | 44, HARDENED, HARDENED]
+        );
+        assert_eq!(db.get_default_path("ethereum", None).await.unwrap().unwrap().symbol, "ETH");
+        assert!(db.get_default_path("bitcoin", Some("p2tr")).await.unwrap().is_none());
+
+        let mappings: Vec<(String, String, bool)> = db.with_connection(|conn| {
+            let mut stmt = conn.prepare("SELECT path_id, caip, is_primary FROM path_asset_mapping ORDER BY path_id")?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(rows)
+        }).await.unwrap();
+        assert_eq!(mappings.len(), 5);
+        assert!(mappings.contains(&("ethereum_account_0".to_string(), "eip155:1/slip44:60".to_string(), true)));
+        assert!(mappings.contains(&("ripple_account_0".to_string(), "ripple:4109c6f2045fc7eff4cde8f9905d19c2/slip44:144".to_string(), true)));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_paths_store_nothing_and_duplicates_update() {
+        let db = Database::new_in_memory().await.unwrap();
+        let file = |entries: &str| format!(r#"{{"paths": [{}]}}"#, entries);
+        let path = |id: &str, note: &str, address_n: &str| format!(
+            r#"{{"path_id": "{}", "note": "{}", "blockchain": "bitcoin", "symbol": "BTC", "networks": ["{}"],
+                "script_type": "p2wpkh", "address_n_list": {}}}"#,
+            id, note, BTC, address_n
+        );
+        let good = path("btc_84", "first", "[2147483732, 2147483648, 2147483648]");
+
+        for bad in ["[]", "[84, 0, 0]"] {
+            let json = file(&[good.clone(), path("btc_bad", "bad", bad)].join(","));
+            assert!(matches!(db.load_derivation_paths(&json).await, Err(DatabaseError::Validation(_))), "{}", bad);
+        }
+        assert!(db.get_paths_for_network(BTC).await.unwrap().is_empty());
+
+        let renamed = path("btc_84", "second", "[2147483732, 2147483648, 2147483648]");
+        assert_eq!(db.load_derivation_paths(&file(&[good, renamed].join(","))).await.unwrap(), 2);
+        let paths = db.get_paths_for_network(BTC).await.unwrap();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].note.as_deref(), Some("second"));
+    }
+}
@@ -0,0 +1,123 @@
+//! Device connection history
+//!
+//! Each time a device is plugged in a `device_connections` row is opened, and
+//! it is closed when the device leaves. A device that drops off during a flow
+//! and comes back within the grace period keeps its row open. Rows left open
+//! by an app that was killed are closed at the next launch with the launch
+//! time, so a connection never appears to last across restarts.
+
+use crate::errors::Result;
+use crate::types::DeviceConnection;
+use crate::Database;
+
+impl Database {
+    /// Record that `device_id` is connected, adding the device if it is new.
+    /// Returns false if a connection was already open for it.
+    pub async fn record_connection(&self, device_id: &str) -> Result<bool> {
+        let now = Self::current_timestamp();
+        self.transaction(|tx| {
+            tx.execute(
+                "INSERT INTO devices (device_id, first_seen, last_seen) VALUES (?1, ?2, ?2)
+                 ON CONFLICT(device_id) DO UPDATE SET last_seen = excluded.last_seen",
+                rusqlite::params![device_id, now],
+            )?;
+            let opened = tx.execute(
+                "INSERT INTO device_connections (device_id, connected_at)
+                 SELECT ?1, ?2
+                 WHERE NOT EXISTS (
+                     SELECT 1 FROM device_connections WHERE device_id = ?1 AND disconnected_at IS NULL
+                 )",
+                rusqlite::params![device_id, now],
+            )?;
+            Ok(opened > 0)
+        }).await
+    }
+
+    /// Close the open connection of `device_id` now; returns the rows closed
+    pub async fn record_disconnection(&self, device_id: &str) -> Result<usize> {
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            let closed = conn.execute(
+                "UPDATE device_connections SET disconnected_at = ?1
+                 WHERE device_id = ?2 AND disconnected_at IS NULL",
+                rusqlite::params![now, device_id],
+            )?;
+            Ok(closed)
+        }).await
+    }
+
+    /// Close every connection opened at or before `at` that is still open,
+    /// stamping it with `at`; run at launch before devices are watched
+    pub async fn close_open_connections(&self, at: i64) -> Result<usize> {
+        self.with_connection(|conn| {
+            let closed = conn.execute(
+                "UPDATE device_connections SET disconnected_at = ?1
+                 WHERE disconnected_at IS NULL AND connected_at <= ?1",
+                [at],
+            )?;
+            if closed > 0 {
+                log::info!("Closed {} device connections left open by the last run", closed);
+            }
+            Ok(closed)
+        }).await
+    }
+
+    /// The last `limit` connections of a device, newest first
+    pub async fn get_connection_history(&self, device_id: &str, limit: u32) -> Result<Vec<DeviceConnection>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, device_id, connected_at, disconnected_at, session_data FROM device_connections
+                 WHERE device_id = ?1
+                 ORDER BY connected_at DESC, id DESC
+                 LIMIT ?2"
+            )?;
+            let history = stmt
+                .query_map(rusqlite::params![device_id, limit], |row| {
+                    let connected_at: i64 = row.get(2)?;
+                    let disconnected_at: Option<i64> = row.get(3)?;
+                    Ok(DeviceConnection {
+                        id: row.get(0)?,
+                        device_id: row.get(1)?,
+                        connected_at,
+                        disconnected_at,
+                        duration_secs: disconnected_at.map(|at| at - connected_at),
+                        session_data: row.get(4)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(history)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connections_open_once_and_stale_ones_close_at_launch() {
+        let db = Database::new_in_memory().await.unwrap();
+        assert!(db.record_connection("kk1").await.unwrap());
+        // A reconnect within a flow's grace period keeps the open row
+        assert!(!db.record_connection("kk1").await.unwrap());
+        assert_eq!(db.record_disconnection("kk1").await.unwrap(), 1);
+        assert_eq!(db.record_disconnection("kk1").await.unwrap(), 0);
+
+        // Left open by a run that was killed
+        db.record_connection("kk1").await.unwrap();
+        db.with_connection(|conn| {
+            conn.execute("UPDATE device_connections SET connected_at = connected_at - 3600 WHERE disconnected_at IS NULL", [])?;
+            Ok(())
+        }).await.unwrap();
+        let launched = Database::current_timestamp();
+        assert_eq!(db.close_open_connections(launched).await.unwrap(), 1);
+
+        let history = db.get_connection_history("kk1", 10).await.unwrap();
+        assert_eq!(history.len(), 2);
+        // Newest first: the backdated connection sorts last
+        assert!(history[0].duration_secs.is_some_and(|secs| secs >= 0));
+        assert_eq!((history[1].disconnected_at, history[1].duration_secs), (Some(launched), Some(3600)));
+        assert_eq!(db.get_connection_history("kk1", 1).await.unwrap().len(), 1);
+        assert!(db.get_connection_history("kk2", 10).await.unwrap().is_empty());
+    }
+}
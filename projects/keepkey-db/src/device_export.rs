@@ -0,0 +1,150 @@
+//! Read side of the per-device data export
+//!
+//! Gathers what the vault stores about one device that is safe to hand to
+//! other wallet software: public keys, cached addresses, wallet nicknames,
+//! transaction history and the device note. vault-core turns it into the
+//! versioned export document.
+
+use rusqlite::OptionalExtension;
+use crate::device_notes::read_note;
+use crate::errors::Result;
+use crate::portfolio::{transaction_from_row, TRANSACTION_COLUMNS};
+use crate::types::{CachedAddress, DeviceExportData};
+use crate::wallets::{known_wallet_from_row, wallet_xpub_from_row, KNOWN_WALLET_COLUMNS, WALLET_XPUB_COLUMNS};
+use crate::Database;
+
+impl Database {
+    /// Export data for a device, or None when it is not registered
+    pub async fn get_device_export_data(&self, device_id: &str) -> Result<Option<DeviceExportData>> {
+        // One transaction so the sections agree with each other
+        self.transaction(|tx| {
+            let Some((label, model, firmware_version)) = tx.query_row(
+                "SELECT label, model, firmware_version FROM devices WHERE device_id = ?1",
+                [device_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            ).optional()? else {
+                return Ok(None);
+            };
+
+            let wallets = tx
+                .prepare(&format!(
+                    "SELECT {} FROM device_wallets WHERE device_id = ?1 ORDER BY is_hidden, first_seen",
+                    KNOWN_WALLET_COLUMNS
+                ))?
+                .query_map([device_id], known_wallet_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let xpubs = tx
+                .prepare(&format!(
+                    "SELECT {} FROM wallet_xpubs WHERE device_id = ?1 ORDER BY wallet_fingerprint, path, caip",
+                    WALLET_XPUB_COLUMNS
+                ))?
+                .query_map([device_id], wallet_xpub_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let addresses = tx
+                .prepare(
+                    "SELECT wallet_fingerprint, derivation_path, coin_name, script_type, address FROM cached_pubkeys
+                     WHERE device_id = ?1 AND address IS NOT NULL AND address != ''
+                     ORDER BY wallet_fingerprint, coin_name, derivation_path",
+                )?
+                .query_map([device_id], |row| {
+                    Ok(CachedAddress {
+                        wallet_fingerprint: row.get(0)?,
+                        derivation_path: row.get(1)?,
+                        coin_name: row.get(2)?,
+                        script_type: row.get(3)?,
+                        address: row.get(4)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let transactions = tx
+                .prepare(&format!(
+                    "SELECT {} FROM transaction_cache WHERE device_id = ?1 ORDER BY timestamp, id",
+                    TRANSACTION_COLUMNS
+                ))?
+                .query_map([device_id], transaction_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            Ok(Some(DeviceExportData {
+                device_id: device_id.to_string(),
+                label,
+                model,
+                firmware_version,
+                wallets,
+                xpubs,
+                addresses,
+                transactions,
+                note: read_note(tx, device_id)?,
+            }))
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{TransactionCache, WalletXpubInput};
+
+    #[tokio::test]
+    async fn test_export_data_is_scoped_to_the_device() {
+        let db = Database::new_in_memory().await.unwrap();
+        assert!(db.get_device_export_data("dev1").await.unwrap().is_none());
+
+        for device_id in ["dev1", "dev2"] {
+            db.with_connection(move |conn| {
+                conn.execute(
+                    "INSERT INTO devices (device_id, label, model, firmware_version, first_seen, last_seen)
+                     VALUES (?1, 'Office', 'KeepKey', '7.10.0', 1, 1)",
+                    [device_id],
+                )?;
+                conn.execute(
+                    "INSERT INTO cached_pubkeys (device_id, derivation_path, coin_name, script_type, address, cached_at, last_used, wallet_fingerprint)
+                     VALUES (?1, 'm/84''/0''/0''/0/0', 'Bitcoin', 'p2wpkh', ?1 || '-addr', 1, 1, 'aabbccdd')",
+                    [device_id],
+                )?;
+                Ok(())
+            }).await.unwrap();
+            db.record_wallet_session(device_id, "aabbccdd", false).await.unwrap();
+            db.save_wallet_xpub(&WalletXpubInput {
+                device_id: device_id.to_string(),
+                wallet_fingerprint: "aabbccdd".to_string(),
+                path: "m/84'/0'/0'".to_string(),
+                label: "Bitcoin".to_string(),
+                caip: "bip122:000000000019d6689c085ae165831e93/slip44:0".to_string(),
+                pubkey: format!("xpub-{}", device_id),
+            }).await.unwrap();
+            db.upsert_transaction(&TransactionCache {
+                id: 0,
+                device_id: device_id.to_string(),
+                txid: format!("tx-{}", device_id),
+                caip: "bip122:000000000019d6689c085ae165831e93/slip44:0".to_string(),
+                transaction_type: "receive".to_string(),
+                amount: "0.1".to_string(),
+                amount_usd: None,
+                fee: None,
+                fee_usd: None,
+                from_address: None,
+                to_address: Some(format!("{}-addr", device_id)),
+                timestamp: 100,
+                block_height: Some(800_000),
+                status: Some("confirmed".to_string()),
+                metadata_json: None,
+                wallet_fingerprint: "aabbccdd".to_string(),
+                origin: None,
+            }).await.unwrap();
+        }
+        db.set_device_note("dev1", "office unit").await.unwrap();
+
+        let data = db.get_device_export_data("dev1").await.unwrap().unwrap();
+        assert_eq!(data.label.as_deref(), Some("Office"));
+        assert_eq!(data.firmware_version.as_deref(), Some("7.10.0"));
+        assert_eq!(data.wallets.len(), 1);
+        assert_eq!(data.xpubs.iter().map(|x| x.pubkey.as_str()).collect::<Vec<_>>(), vec!["xpub-dev1"]);
+        assert_eq!(data.addresses.iter().map(|a| a.address.as_str()).collect::<Vec<_>>(), vec!["dev1-addr"]);
+        assert_eq!(data.transactions.iter().map(|t| t.txid.as_str()).collect::<Vec<_>>(), vec!["tx-dev1"]);
+        assert_eq!(data.note.unwrap().note_markdown, "office unit");
+        assert!(db.get_device_export_data("dev2").await.unwrap().unwrap().note.is_none());
+    }
+}
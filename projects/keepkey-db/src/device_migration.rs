@@ -0,0 +1,266 @@
+//! Moving a replaced device's data to its successor
+//!
+//! A replacement KeepKey restored from the same seed gets a new device_id, so
+//! xpubs, portfolio rows, history and wallet nicknames would otherwise stay
+//! behind under the old one. Migration only proceeds when both devices'
+//! standard wallets have the same root fingerprint. The old device's note is
+//! appended to the new device's.
+
+use rusqlite::{Connection, OptionalExtension};
+use crate::device_notes::merge_device_notes;
+use crate::errors::{DatabaseError, Result};
+use crate::types::{DeviceMigrationSummary, TableMigration};
+use crate::Database;
+
+/// Tables moved to the new device, in order. Device-specific records
+/// (connections, fault logs, frontload state) and the signing audit stay with
+/// the old device.
+pub const MIGRATED_TABLES: &[&str] = &[
+    "device_wallets",
+    "wallet_xpubs",
+    "cached_pubkeys",
+    "portfolio_balances",
+    "portfolio_dashboard",
+    "portfolio_history",
+    "transaction_cache",
+    "tax_lots",
+    "disposals",
+];
+
+/// Root fingerprint of a device's standard (no passphrase) wallet
+fn standard_fingerprint(conn: &Connection, device_id: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT wallet_fingerprint FROM device_wallets WHERE device_id = ?1 AND is_hidden = 0
+         ORDER BY last_used DESC LIMIT 1",
+        [device_id],
+        |row| row.get(0),
+    ).optional()
+}
+
+fn device_exists(conn: &Connection, device_id: &str) -> rusqlite::Result<bool> {
+    conn.query_row("SELECT COUNT(*) FROM devices WHERE device_id = ?1", [device_id], |row| row.get::<_, i64>(0))
+        .map(|n| n > 0)
+}
+
+/// Re-key one table. Rows that collide with a UNIQUE key on the new device
+/// (e.g. xpubs the new device already frontloaded) keep the new device's copy.
+fn migrate_table(conn: &Connection, table: &str, old_device_id: &str, new_device_id: &str) -> rusqlite::Result<TableMigration> {
+    let moved = conn.execute(
+        &format!("UPDATE OR IGNORE {} SET device_id = ?2 WHERE device_id = ?1", table),
+        [old_device_id, new_device_id],
+    )?;
+    let merged = conn.execute(&format!("DELETE FROM {} WHERE device_id = ?1", table), [old_device_id])?;
+    Ok(TableMigration { table: table.to_string(), moved, merged })
+}
+
+impl Database {
+    /// Standard wallet fingerprint last recorded for a device
+    pub async fn get_standard_wallet_fingerprint(&self, device_id: &str) -> Result<Option<String>> {
+        self.with_connection(|conn| Ok(standard_fingerprint(conn, device_id)?)).await
+    }
+
+    /// Migrate everything stored for `old_device_id` to `new_device_id` in one
+    /// transaction and archive the old device.
+    ///
+    /// `new_fingerprint` is the standard wallet fingerprint read from the new
+    /// device; it must match the one recorded for the old device.
+    /// `progress` is called after each table with (step, total, table result).
+    pub async fn migrate_device_data<F>(
+        &self,
+        old_device_id: &str,
+        new_device_id: &str,
+        new_fingerprint: &str,
+        mut progress: F,
+    ) -> Result<DeviceMigrationSummary>
+    where
+        F: FnMut(usize, usize, &TableMigration) + Send,
+    {
+        if old_device_id == new_device_id {
+            return Err(DatabaseError::Validation("A device cannot be migrated to itself".to_string()));
+        }
+        let now = Self::current_timestamp();
+
+        self.transaction(|tx| {
+            for device_id in [old_device_id, new_device_id] {
+                if !device_exists(tx, device_id)? {
+                    return Err(DatabaseError::DeviceNotFound(device_id.to_string()));
+                }
+            }
+            let old_fingerprint = standard_fingerprint(tx, old_device_id)?.ok_or_else(|| {
+                DatabaseError::Validation(format!(
+                    "No wallet fingerprint is recorded for {}; it cannot be matched to the new device",
+                    old_device_id
+                ))
+            })?;
+            if !old_fingerprint.eq_ignore_ascii_case(new_fingerprint) {
+                return Err(DatabaseError::Validation(format!(
+                    "The new device holds a different seed (wallet {} vs {}); restore the same recovery phrase before migrating",
+                    new_fingerprint, old_fingerprint
+                )));
+            }
+
+            // Nicknames of wallets both devices know would be dropped with the merged row
+            tx.execute(
+                "UPDATE device_wallets SET
+                    nickname = COALESCE(nickname, (SELECT o.nickname FROM device_wallets o
+                        WHERE o.device_id = ?1 AND o.wallet_fingerprint = device_wallets.wallet_fingerprint)),
+                    first_seen = MIN(first_seen, COALESCE((SELECT o.first_seen FROM device_wallets o
+                        WHERE o.device_id = ?1 AND o.wallet_fingerprint = device_wallets.wallet_fingerprint), first_seen))
+                 WHERE device_id = ?2",
+                [old_device_id, new_device_id],
+            )?;
+
+            let mut tables = Vec::with_capacity(MIGRATED_TABLES.len());
+            for (i, table) in MIGRATED_TABLES.iter().enumerate() {
+                let result = migrate_table(tx, table, old_device_id, new_device_id)?;
+                progress(i + 1, MIGRATED_TABLES.len(), &result);
+                tables.push(result);
+            }
+
+            let label_copied = tx.execute(
+                "UPDATE devices SET label = (SELECT label FROM devices WHERE device_id = ?1)
+                 WHERE device_id = ?2 AND COALESCE(label, '') = ''
+                   AND (SELECT COALESCE(label, '') FROM devices WHERE device_id = ?1) != ''",
+                [old_device_id, new_device_id],
+            )? > 0;
+
+            let note_merged = merge_device_notes(tx, old_device_id, new_device_id, now)?;
+
+            tx.execute(
+                "UPDATE devices SET archived_at = ?2, migrated_to = ?3 WHERE device_id = ?1",
+                rusqlite::params![old_device_id, now, new_device_id],
+            )?;
+
+            Ok(DeviceMigrationSummary {
+                old_device_id: old_device_id.to_string(),
+                new_device_id: new_device_id.to_string(),
+                wallet_fingerprint: old_fingerprint,
+                tables,
+                label_copied,
+                note_merged,
+            })
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::WalletXpubInput;
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+    const FP: &str = "aaaa0001";
+
+    async fn xpub(db: &Database, device_id: &str, path: &str, pubkey: &str) {
+        db.save_wallet_xpub(&WalletXpubInput {
+            device_id: device_id.to_string(),
+            wallet_fingerprint: FP.to_string(),
+            path: path.to_string(),
+            label: "Bitcoin".to_string(),
+            caip: BTC.to_string(),
+            pubkey: pubkey.to_string(),
+        }).await.unwrap();
+    }
+
+    async fn setup(db: &Database, new_fingerprint: &str) {
+        db.register_device("old", None, Some(r#"{"label":"Main"}"#)).await.unwrap();
+        db.register_device("new", None, None).await.unwrap();
+        db.record_wallet_session("old", FP, false).await.unwrap();
+        db.record_wallet_session("new", new_fingerprint, false).await.unwrap();
+        db.set_wallet_nickname("old", FP, Some("Savings")).await.unwrap();
+        xpub(db, "old", "m/84'/0'/0'", "xpubSegwit").await;
+        xpub(db, "old", "m/44'/0'/0'", "xpubLegacy").await;
+        db.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO transaction_cache (device_id, wallet_fingerprint, txid, caip, type, amount, timestamp, status)
+                 VALUES ('old', ?1, 'tx1', ?2, 'receive', '0.5', 1, 'confirmed')",
+                [FP, BTC],
+            )?;
+            Ok(())
+        }).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_migrates_matching_seed() {
+        let db = Database::new_in_memory().await.unwrap();
+        setup(&db, FP).await;
+
+        let mut steps = Vec::new();
+        let summary = db.migrate_device_data("old", "new", FP, |step, total, _| steps.push((step, total))).await.unwrap();
+        assert_eq!(steps.len(), MIGRATED_TABLES.len());
+        assert!(summary.label_copied);
+        assert!(!summary.note_merged);
+
+        let xpubs = db.get_wallet_xpubs("new", FP).await.unwrap();
+        assert_eq!(xpubs.len(), 2);
+        assert!(db.get_wallet_xpubs("old", FP).await.unwrap().is_empty());
+        assert!(db.get_transaction("new", FP, "tx1", BTC).await.unwrap().is_some());
+        assert_eq!(db.list_known_wallets("new").await.unwrap()[0].nickname.as_deref(), Some("Savings"));
+
+        let (archived_at, migrated_to, label): (Option<i64>, Option<String>, Option<String>) = db.with_connection(|conn| {
+            Ok(conn.query_row(
+                "SELECT o.archived_at, o.migrated_to, n.label FROM devices o, devices n
+                 WHERE o.device_id = 'old' AND n.device_id = 'new'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?)
+        }).await.unwrap();
+        assert!(archived_at.is_some());
+        assert_eq!(migrated_to.as_deref(), Some("new"));
+        assert_eq!(label.as_deref(), Some("Main"));
+
+        // Reconnecting the old device keeps it archived
+        db.register_device("old", None, None).await.unwrap();
+        let still_archived: Option<String> = db.with_connection(|conn| {
+            Ok(conn.query_row("SELECT migrated_to FROM devices WHERE device_id = 'old'", [], |row| row.get(0))?)
+        }).await.unwrap();
+        assert_eq!(still_archived.as_deref(), Some("new"));
+    }
+
+    #[tokio::test]
+    async fn test_migration_merges_device_notes() {
+        let db = Database::new_in_memory().await.unwrap();
+        setup(&db, FP).await;
+        db.set_device_note("old", "office unit, bought 2021").await.unwrap();
+        db.set_device_note("new", "replacement from support").await.unwrap();
+
+        let summary = db.migrate_device_data("old", "new", FP, |_, _, _| {}).await.unwrap();
+        assert!(summary.note_merged);
+        let note = db.get_device_note("new").await.unwrap().unwrap();
+        assert_eq!(
+            note.note_markdown,
+            format!("replacement from support{}office unit, bought 2021", crate::device_notes::NOTE_MERGE_SEPARATOR)
+        );
+        assert!(db.get_device_note("old").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_refuses_different_seed() {
+        let db = Database::new_in_memory().await.unwrap();
+        setup(&db, "bbbb0002").await;
+
+        let err = db.migrate_device_data("old", "new", "bbbb0002", |_, _, _| {}).await.unwrap_err();
+        assert!(err.to_string().contains("different seed"));
+        // Nothing moved
+        assert_eq!(db.get_wallet_xpubs("old", FP).await.unwrap().len(), 2);
+        assert!(db.get_wallet_xpubs("new", FP).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_merges_paths_already_on_new_device() {
+        let db = Database::new_in_memory().await.unwrap();
+        setup(&db, FP).await;
+        // The new device frontloaded one of the same paths before migration
+        xpub(&db, "new", "m/84'/0'/0'", "xpubSegwit").await;
+
+        let summary = db.migrate_device_data("old", "new", FP, |_, _, _| {}).await.unwrap();
+        let xpubs = summary.tables.iter().find(|t| t.table == "wallet_xpubs").unwrap();
+        assert_eq!((xpubs.moved, xpubs.merged), (1, 1));
+        let wallets = summary.tables.iter().find(|t| t.table == "device_wallets").unwrap();
+        assert_eq!((wallets.moved, wallets.merged), (0, 1));
+
+        let paths: Vec<String> = db.get_wallet_xpubs("new", FP).await.unwrap().into_iter().map(|x| x.path).collect();
+        assert_eq!(paths, vec!["m/44'/0'/0'".to_string(), "m/84'/0'/0'".to_string()]);
+        assert_eq!(db.list_known_wallets("new").await.unwrap()[0].nickname.as_deref(), Some("Savings"));
+    }
+}
@@ -0,0 +1,162 @@
+//! Freeform notes attached to devices
+//!
+//! Notes are user text ("office unit, bought 2021, flaky left button") shown
+//! on the device detail page. They are stored as markdown, capped at
+//! `MAX_NOTE_BYTES`, and follow a device's data when it is migrated to a
+//! replacement: the old note is appended to the new device's note.
+
+use rusqlite::{Connection, OptionalExtension};
+use crate::errors::{DatabaseError, Result};
+use crate::types::DeviceNote;
+use crate::Database;
+
+/// Largest note accepted from the user, in bytes after sanitizing
+pub const MAX_NOTE_BYTES: usize = 16 * 1024;
+
+/// Placed between two notes combined by a device migration
+pub const NOTE_MERGE_SEPARATOR: &str = "\n\n---\n\n";
+
+/// Normalize line endings, drop control characters other than newlines and
+/// tabs, and trim surrounding whitespace. Fails if the result is over the cap.
+pub fn sanitize_note(note: &str) -> Result<String> {
+    let cleaned: String = note
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+        .collect();
+    let cleaned = cleaned.trim().to_string();
+
+    if cleaned.len() > MAX_NOTE_BYTES {
+        return Err(DatabaseError::Validation(format!(
+            "Device note is {} bytes; the limit is {} bytes",
+            cleaned.len(),
+            MAX_NOTE_BYTES
+        )));
+    }
+    Ok(cleaned)
+}
+
+pub(crate) fn read_note(conn: &Connection, device_id: &str) -> rusqlite::Result<Option<DeviceNote>> {
+    conn.query_row(
+        "SELECT device_id, note_markdown, updated_at FROM device_notes WHERE device_id = ?1",
+        [device_id],
+        |row| Ok(DeviceNote { device_id: row.get(0)?, note_markdown: row.get(1)?, updated_at: row.get(2)? }),
+    ).optional()
+}
+
+/// Append the old device's note to the new device's and drop the old one.
+///
+/// The merged note may exceed `MAX_NOTE_BYTES`; nothing the user wrote is cut.
+/// Returns whether the old device had a note.
+pub(crate) fn merge_device_notes(conn: &Connection, old_device_id: &str, new_device_id: &str, now: i64) -> rusqlite::Result<bool> {
+    let Some(old) = read_note(conn, old_device_id)? else {
+        return Ok(false);
+    };
+    let merged = match read_note(conn, new_device_id)? {
+        Some(new) if new.note_markdown != old.note_markdown => {
+            format!("{}{}{}", new.note_markdown, NOTE_MERGE_SEPARATOR, old.note_markdown)
+        }
+        _ => old.note_markdown,
+    };
+    conn.execute(
+        "INSERT INTO device_notes (device_id, note_markdown, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(device_id) DO UPDATE SET note_markdown = excluded.note_markdown, updated_at = excluded.updated_at",
+        rusqlite::params![new_device_id, merged, now],
+    )?;
+    conn.execute("DELETE FROM device_notes WHERE device_id = ?1", [old_device_id])?;
+    Ok(true)
+}
+
+impl Database {
+    /// Note attached to a device, if any
+    pub async fn get_device_note(&self, device_id: &str) -> Result<Option<DeviceNote>> {
+        self.with_connection(|conn| Ok(read_note(conn, device_id)?)).await
+    }
+
+    /// Notes of every device, for callers that opted in to sharing them
+    pub async fn get_device_notes(&self) -> Result<Vec<DeviceNote>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT device_id, note_markdown, updated_at FROM device_notes ORDER BY device_id"
+            )?;
+            let notes = stmt
+                .query_map([], |row| {
+                    Ok(DeviceNote { device_id: row.get(0)?, note_markdown: row.get(1)?, updated_at: row.get(2)? })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(notes)
+        }).await
+    }
+
+    /// Replace a device's note. A note that is empty after sanitizing removes
+    /// it and returns None.
+    pub async fn set_device_note(&self, device_id: &str, note: &str) -> Result<Option<DeviceNote>> {
+        let note = sanitize_note(note)?;
+        let now = Self::current_timestamp();
+
+        self.with_connection(|conn| {
+            if note.is_empty() {
+                conn.execute("DELETE FROM device_notes WHERE device_id = ?1", [device_id])?;
+                return Ok(None);
+            }
+            conn.execute(
+                "INSERT INTO device_notes (device_id, note_markdown, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(device_id) DO UPDATE SET note_markdown = excluded.note_markdown, updated_at = excluded.updated_at",
+                rusqlite::params![device_id, note, now],
+            )?;
+            Ok(Some(DeviceNote { device_id: device_id.to_string(), note_markdown: note, updated_at: now }))
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_note_is_sanitized_and_capped() {
+        let db = Database::new_in_memory().await.unwrap();
+
+        let saved = db.set_device_note("dev1", "  office unit\r\nflaky\u{0007} left button\t!  ").await.unwrap().unwrap();
+        assert_eq!(saved.note_markdown, "office unit\nflaky left button\t!");
+        assert_eq!(db.get_device_note("dev1").await.unwrap(), Some(saved));
+
+        let at_cap = "a".repeat(MAX_NOTE_BYTES);
+        assert!(db.set_device_note("dev1", &at_cap).await.is_ok());
+        let err = db.set_device_note("dev1", &format!("{}b", at_cap)).await.unwrap_err();
+        assert!(err.to_string().contains("limit"));
+        // A rejected note leaves the stored one alone
+        assert_eq!(db.get_device_note("dev1").await.unwrap().unwrap().note_markdown, at_cap);
+
+        assert_eq!(db.set_device_note("dev1", " \n ").await.unwrap(), None);
+        assert!(db.get_device_note("dev1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_merge_concatenates_notes() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.set_device_note("old", "bought 2021").await.unwrap();
+        db.set_device_note("new", "replacement unit").await.unwrap();
+
+        let merged = db.with_connection(|conn| Ok(merge_device_notes(conn, "old", "new", 42)?)).await.unwrap();
+        assert!(merged);
+        let note = db.get_device_note("new").await.unwrap().unwrap();
+        assert_eq!(note.note_markdown, format!("replacement unit{}bought 2021", NOTE_MERGE_SEPARATOR));
+        assert_eq!(note.updated_at, 42);
+        assert!(db.get_device_note("old").await.unwrap().is_none());
+
+        // Nothing to merge the second time
+        assert!(!db.with_connection(|conn| Ok(merge_device_notes(conn, "old", "new", 43)?)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_merge_moves_note_to_device_without_one() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.set_device_note("old", "bought 2021").await.unwrap();
+
+        db.with_connection(|conn| Ok(merge_device_notes(conn, "old", "new", 42)?)).await.unwrap();
+        assert_eq!(db.get_device_note("new").await.unwrap().unwrap().note_markdown, "bought 2021");
+        assert_eq!(db.get_device_notes().await.unwrap().len(), 1);
+    }
+}
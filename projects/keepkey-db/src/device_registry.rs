@@ -2,6 +2,7 @@ use crate::Database;
 
 /// Device Registry manager - handles device setup flow and tracking
 pub struct DeviceRegistry {
+    #[allow(dead_code)]
     db: Database,
 }
 
@@ -0,0 +1,138 @@
+//! Outbox for critical events
+//!
+//! Critical events are written here before they are emitted and stay
+//! unacknowledged until the frontend calls `ack_event`, so a backend crash
+//! between emission and handling does not lose them; they are re-emitted on
+//! the next start.
+
+use crate::errors::Result;
+use crate::types::OutboxEvent;
+use crate::Database;
+
+/// Acknowledged rows older than this are pruned
+pub const OUTBOX_RETENTION_SECS: i64 = 7 * 24 * 60 * 60;
+
+fn row_to_event(row: &rusqlite::Row) -> rusqlite::Result<OutboxEvent> {
+    Ok(OutboxEvent {
+        sequence: row.get(0)?,
+        event_name: row.get(1)?,
+        payload_json: row.get(2)?,
+        created_at: row.get(3)?,
+        delivery_attempts: row.get(4)?,
+        acked_at: row.get(5)?,
+    })
+}
+
+impl Database {
+    /// Persist an event before emission; returns its outbox sequence
+    pub async fn enqueue_outbox_event(&self, event_name: &str, payload_json: &str) -> Result<i64> {
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO event_outbox (event_name, payload_json, created_at) VALUES (?1, ?2, ?3)",
+                rusqlite::params![event_name, payload_json, now],
+            )?;
+            Ok(conn.last_insert_rowid())
+        }).await
+    }
+
+    /// Count one emission attempt
+    pub async fn mark_outbox_attempt(&self, sequence: i64) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "UPDATE event_outbox SET delivery_attempts = delivery_attempts + 1 WHERE sequence = ?1",
+                [sequence],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    /// Mark an event delivered; returns false if it was unknown or already acknowledged
+    pub async fn ack_outbox_event(&self, sequence: i64) -> Result<bool> {
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            let updated = conn.execute(
+                "UPDATE event_outbox SET acked_at = ?2 WHERE sequence = ?1 AND acked_at IS NULL",
+                rusqlite::params![sequence, now],
+            )?;
+            Ok(updated > 0)
+        }).await
+    }
+
+    /// Events not yet acknowledged, oldest first
+    pub async fn get_unacked_outbox_events(&self) -> Result<Vec<OutboxEvent>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT sequence, event_name, payload_json, created_at, delivery_attempts, acked_at
+                 FROM event_outbox
+                 WHERE acked_at IS NULL
+                 ORDER BY sequence"
+            )?;
+            let events = stmt.query_map([], row_to_event)?.collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(events)
+        }).await
+    }
+
+    /// Delete acknowledged events older than the retention period
+    pub async fn prune_outbox(&self) -> Result<usize> {
+        let cutoff = Self::current_timestamp() - OUTBOX_RETENTION_SECS;
+        self.with_connection(|conn| {
+            Ok(conn.execute("DELETE FROM event_outbox WHERE acked_at IS NOT NULL AND acked_at < ?1", [cutoff])?)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_unacked_events_survive_restart() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("outbox.db");
+
+        // Written, then the backend dies before the frontend saw it
+        {
+            let db = Database::open_at_path(path.clone()).await.unwrap();
+            db.enqueue_outbox_event("security:xpub-mismatch", r#"{"device_id":"dev1"}"#).await.unwrap();
+            let delivered = db.enqueue_outbox_event("backup:failed", "{}").await.unwrap();
+            db.mark_outbox_attempt(delivered).await.unwrap();
+            assert!(db.ack_outbox_event(delivered).await.unwrap());
+        }
+
+        let db = Database::open_at_path(path).await.unwrap();
+        let pending = db.get_unacked_outbox_events().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].event_name, "security:xpub-mismatch");
+        assert_eq!(pending[0].delivery_attempts, 0);
+
+        assert!(db.ack_outbox_event(pending[0].sequence).await.unwrap());
+        assert!(!db.ack_outbox_event(pending[0].sequence).await.unwrap());
+        assert!(db.get_unacked_outbox_events().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_unacked_and_recent() {
+        let db = Database::new_in_memory().await.unwrap();
+        let old = db.enqueue_outbox_event("backup:failed", "{}").await.unwrap();
+        let recent = db.enqueue_outbox_event("backup:failed", "{}").await.unwrap();
+        db.enqueue_outbox_event("device:fault-detected", "{}").await.unwrap();
+        db.ack_outbox_event(old).await.unwrap();
+        db.ack_outbox_event(recent).await.unwrap();
+        db.with_connection(|conn| {
+            conn.execute(
+                "UPDATE event_outbox SET acked_at = acked_at - ?2 WHERE sequence = ?1",
+                rusqlite::params![old, OUTBOX_RETENTION_SECS + 1],
+            )?;
+            Ok(())
+        }).await.unwrap();
+
+        assert_eq!(db.prune_outbox().await.unwrap(), 1);
+        let remaining: i64 = db.with_connection(|conn| {
+            Ok(conn.query_row("SELECT COUNT(*) FROM event_outbox", [], |row| row.get(0))?)
+        }).await.unwrap();
+        assert_eq!(remaining, 2);
+        assert_eq!(db.get_unacked_outbox_events().await.unwrap().len(), 1);
+    }
+}
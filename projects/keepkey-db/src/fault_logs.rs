@@ -0,0 +1,78 @@
+use crate::errors::Result;
+use crate::types::DeviceFaultLog;
+use crate::Database;
+
+impl Database {
+    /// Store a fault record read from a device.
+    ///
+    /// Returns false if this exact record was already stored, so callers can
+    /// tell a new crash apart from re-reading an old one.
+    pub async fn record_device_fault(
+        &self,
+        device_id: &str,
+        fault_type: &str,
+        summary: &str,
+        record_json: &str,
+    ) -> Result<bool> {
+        let now = Self::current_timestamp();
+
+        self.with_connection(|conn| {
+            let inserted = conn.execute(
+                "INSERT OR IGNORE INTO device_fault_logs (device_id, recorded_at, fault_type, summary, record_json)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![device_id, now, fault_type, summary, record_json],
+            )?;
+
+            if inserted > 0 {
+                log::warn!("Recorded firmware fault for device {}: {}", device_id, summary);
+            }
+            Ok(inserted > 0)
+        }).await
+    }
+
+    /// Most recent fault records, optionally for a single device
+    pub async fn get_device_fault_logs(&self, device_id: Option<&str>, limit: usize) -> Result<Vec<DeviceFaultLog>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, device_id, recorded_at, fault_type, summary, record_json
+                 FROM device_fault_logs
+                 WHERE ?1 IS NULL OR device_id = ?1
+                 ORDER BY recorded_at DESC, id DESC
+                 LIMIT ?2"
+            )?;
+
+            let logs = stmt.query_map(rusqlite::params![device_id, limit as i64], |row| {
+                Ok(DeviceFaultLog {
+                    id: row.get(0)?,
+                    device_id: row.get(1)?,
+                    recorded_at: row.get(2)?,
+                    fault_type: row.get(3)?,
+                    summary: row.get(4)?,
+                    record_json: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            Ok(logs)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fault_records_are_deduplicated() {
+        let db = Database::new_in_memory().await.unwrap();
+
+        assert!(db.record_device_fault("dev1", "hard_fault", "hard_fault in usb_task", "{\"pc\":1}").await.unwrap());
+        assert!(!db.record_device_fault("dev1", "hard_fault", "hard_fault in usb_task", "{\"pc\":1}").await.unwrap());
+        assert!(db.record_device_fault("dev2", "watchdog", "watchdog in main", "{\"pc\":2}").await.unwrap());
+
+        assert_eq!(db.get_device_fault_logs(None, 10).await.unwrap().len(), 2);
+        let dev1 = db.get_device_fault_logs(Some("dev1"), 10).await.unwrap();
+        assert_eq!(dev1.len(), 1);
+        assert_eq!(dev1[0].fault_type, "hard_fault");
+    }
+}
@@ -0,0 +1,104 @@
+//! Firmware and bootloader update history per device
+
+use crate::errors::{DatabaseError, Result};
+use crate::types::FirmwareUpdateRecord;
+use crate::Database;
+
+const KINDS: &[&str] = &["firmware", "bootloader"];
+
+fn row_to_update(row: &rusqlite::Row) -> rusqlite::Result<FirmwareUpdateRecord> {
+    Ok(FirmwareUpdateRecord {
+        id: row.get(0)?,
+        device_id: row.get(1)?,
+        kind: row.get(2)?,
+        from_version: row.get(3)?,
+        to_version: row.get(4)?,
+        downgrade: row.get(5)?,
+        succeeded: row.get(6)?,
+        error: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+impl Database {
+    /// Store the outcome of a flash ('firmware' or 'bootloader'); `error` is
+    /// set when it failed. Returns the stored row.
+    pub async fn record_firmware_update(
+        &self,
+        device_id: &str,
+        kind: &str,
+        from_version: Option<&str>,
+        to_version: &str,
+        downgrade: bool,
+        error: Option<&str>,
+    ) -> Result<FirmwareUpdateRecord> {
+        if !KINDS.contains(&kind) {
+            return Err(DatabaseError::Validation(format!("Unknown update kind '{}'", kind)));
+        }
+        let now = Self::current_timestamp();
+        let succeeded = error.is_none();
+
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO firmware_updates (device_id, kind, from_version, to_version, downgrade, succeeded, error, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                rusqlite::params![device_id, kind, from_version, to_version, downgrade, succeeded, error, now],
+            )?;
+            Ok(FirmwareUpdateRecord {
+                id: conn.last_insert_rowid(),
+                device_id: device_id.to_string(),
+                kind: kind.to_string(),
+                from_version: from_version.map(str::to_string),
+                to_version: to_version.to_string(),
+                downgrade,
+                succeeded,
+                error: error.map(str::to_string),
+                updated_at: now,
+            })
+        }).await
+    }
+
+    /// Most recent flashes, newest first, optionally for a single device
+    pub async fn get_firmware_updates(&self, device_id: Option<&str>, limit: usize) -> Result<Vec<FirmwareUpdateRecord>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, device_id, kind, from_version, to_version, downgrade, succeeded, error, updated_at
+                 FROM firmware_updates
+                 WHERE ?1 IS NULL OR device_id = ?1
+                 ORDER BY updated_at DESC, id DESC
+                 LIMIT ?2"
+            )?;
+            let updates = stmt
+                .query_map(rusqlite::params![device_id, limit as i64], row_to_update)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(updates)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_update_history_per_device() {
+        let db = Database::new_in_memory().await.unwrap();
+        assert!(db.get_firmware_updates(Some("dev1"), 10).await.unwrap().is_empty());
+
+        db.record_firmware_update("dev1", "bootloader", Some("1.0.3"), "2.1.4", false, None).await.unwrap();
+        db.record_firmware_update("dev1", "firmware", Some("7.10.0"), "7.9.2", true, None).await.unwrap();
+        db.record_firmware_update("dev2", "firmware", None, "7.10.0", false, Some("erase failed")).await.unwrap();
+        assert!(db.record_firmware_update("dev1", "updater", None, "2.1.4", false, None).await.is_err());
+
+        let history = db.get_firmware_updates(Some("dev1"), 10).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].kind, "firmware");
+        assert!(history[0].downgrade && history[0].succeeded);
+        assert_eq!(history[0].from_version.as_deref(), Some("7.10.0"));
+
+        let failed = db.get_firmware_updates(Some("dev2"), 10).await.unwrap().remove(0);
+        assert!(!failed.succeeded);
+        assert_eq!(failed.error.as_deref(), Some("erase failed"));
+        assert_eq!(db.get_firmware_updates(None, 10).await.unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,201 @@
+//! Progress of frontloading a device's xpubs
+//!
+//! After setup the vault reads the xpubs of the standard accounts into
+//! `wallet_xpubs`, one network at a time. Each network's row counts the paths
+//! stored so far, so the UI can show progress and a run cut short by an unplug
+//! can pick up where it stopped.
+
+use rusqlite::{Connection, OptionalExtension};
+use crate::errors::{DatabaseError, Result};
+use crate::types::FrontloadProgress;
+use crate::Database;
+
+const PROGRESS_QUERY: &str =
+    "SELECT device_id, network_id, paths_total, paths_completed, last_path, status,
+            error_message, started_at, completed_at
+     FROM frontload_progress";
+
+fn progress_from_row(row: &rusqlite::Row) -> rusqlite::Result<FrontloadProgress> {
+    Ok(FrontloadProgress {
+        device_id: row.get(0)?,
+        network_id: row.get(1)?,
+        paths_total: row.get(2)?,
+        paths_completed: row.get(3)?,
+        last_path: row.get(4)?,
+        status: row.get::<_, Option<String>>(5)?.unwrap_or_else(|| "pending".to_string()),
+        error_message: row.get(6)?,
+        started_at: row.get(7)?,
+        completed_at: row.get(8)?,
+    })
+}
+
+fn read_progress(conn: &Connection, device_id: &str, network_id: &str) -> rusqlite::Result<Option<FrontloadProgress>> {
+    conn.query_row(
+        &format!("{} WHERE device_id = ?1 AND network_id = ?2", PROGRESS_QUERY),
+        [device_id, network_id],
+        progress_from_row,
+    ).optional()
+}
+
+impl Database {
+    /// Start or resume frontloading a network with `paths_completed` of its
+    /// `paths_total` paths already stored. A network with nothing left to do
+    /// is marked completed straight away.
+    pub async fn begin_frontload(
+        &self,
+        device_id: &str,
+        network_id: &str,
+        paths_total: u32,
+        paths_completed: u32,
+    ) -> Result<FrontloadProgress> {
+        if paths_completed > paths_total {
+            return Err(DatabaseError::Validation(format!(
+                "{} of {} paths completed", paths_completed, paths_total
+            )));
+        }
+        let now = Self::current_timestamp();
+        let done = paths_completed == paths_total;
+        self.with_connection(|conn| {
+            // A resumed run keeps its start time; a new run after a completed
+            // one starts over
+            conn.execute(
+                "INSERT INTO frontload_progress
+                    (device_id, network_id, paths_total, paths_completed, status, started_at, completed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT(device_id, network_id) DO UPDATE SET
+                    paths_total = excluded.paths_total,
+                    paths_completed = excluded.paths_completed,
+                    last_path = CASE WHEN excluded.paths_completed = 0 THEN NULL ELSE last_path END,
+                    status = excluded.status,
+                    error_message = NULL,
+                    started_at = CASE WHEN status = 'completed' OR started_at IS NULL
+                                      THEN excluded.started_at ELSE started_at END,
+                    completed_at = excluded.completed_at",
+                rusqlite::params![
+                    device_id,
+                    network_id,
+                    paths_total,
+                    paths_completed,
+                    if done { "completed" } else { "in_progress" },
+                    now,
+                    done.then_some(now),
+                ],
+            )?;
+            read_progress(conn, device_id, network_id)?
+                .ok_or_else(|| DatabaseError::Validation("Frontload progress was not saved".to_string()))
+        }).await
+    }
+
+    /// Count `path` as stored; the network completes with its last path
+    pub async fn record_frontload_path(
+        &self,
+        device_id: &str,
+        network_id: &str,
+        path: &str,
+    ) -> Result<FrontloadProgress> {
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            let updated = conn.execute(
+                "UPDATE frontload_progress SET
+                    paths_completed = MIN(paths_completed + 1, paths_total),
+                    last_path = ?3,
+                    status = CASE WHEN paths_completed + 1 >= paths_total THEN 'completed' ELSE 'in_progress' END,
+                    completed_at = CASE WHEN paths_completed + 1 >= paths_total THEN ?4 ELSE NULL END
+                 WHERE device_id = ?1 AND network_id = ?2",
+                rusqlite::params![device_id, network_id, path, now],
+            )?;
+            if updated == 0 {
+                return Err(DatabaseError::Validation(format!(
+                    "No frontload started for {} on {}", network_id, device_id
+                )));
+            }
+            read_progress(conn, device_id, network_id)?
+                .ok_or_else(|| DatabaseError::Validation("Frontload progress was not saved".to_string()))
+        }).await
+    }
+
+    /// Mark a network's frontload failed, keeping the paths already stored
+    pub async fn fail_frontload(&self, device_id: &str, network_id: &str, error: &str) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "UPDATE frontload_progress SET status = 'failed', error_message = ?3
+                 WHERE device_id = ?1 AND network_id = ?2",
+                [device_id, network_id, error],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    /// Frontload progress of every network of a device
+    pub async fn get_frontload_progress(&self, device_id: &str) -> Result<Vec<FrontloadProgress>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!("{} WHERE device_id = ?1 ORDER BY network_id", PROGRESS_QUERY))?;
+            let progress = stmt
+                .query_map([device_id], progress_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(progress)
+        }).await
+    }
+
+    /// Whether a frontload of the device was started and not finished, e.g.
+    /// because the device was unplugged part way
+    pub async fn has_unfinished_frontload(&self, device_id: &str) -> Result<bool> {
+        self.with_connection(|conn| {
+            let unfinished: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM frontload_progress WHERE device_id = ?1 AND status != 'completed')",
+                [device_id],
+                |row| row.get(0),
+            )?;
+            Ok(unfinished)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93";
+
+    #[tokio::test]
+    async fn test_frontload_progress_resumes_and_restarts() {
+        let db = Database::new_in_memory().await.unwrap();
+        assert!(db.record_frontload_path("kk1", BTC, "m/44'/0'/0'").await.is_err());
+        assert!(!db.has_unfinished_frontload("kk1").await.unwrap());
+
+        let progress = db.begin_frontload("kk1", BTC, 3, 0).await.unwrap();
+        assert_eq!((progress.paths_completed, progress.status.as_str()), (0, "in_progress"));
+        let started_at = progress.started_at;
+
+        let progress = db.record_frontload_path("kk1", BTC, "m/44'/0'/0'").await.unwrap();
+        assert_eq!(progress.paths_completed, 1);
+        assert_eq!(progress.last_path.as_deref(), Some("m/44'/0'/0'"));
+
+        // Unplugged on the second path
+        db.fail_frontload("kk1", BTC, "Device disconnected").await.unwrap();
+        assert!(db.has_unfinished_frontload("kk1").await.unwrap());
+        let progress = &db.get_frontload_progress("kk1").await.unwrap()[0];
+        assert_eq!((progress.paths_completed, progress.status.as_str()), (1, "failed"));
+        assert_eq!(progress.error_message.as_deref(), Some("Device disconnected"));
+
+        // Resumed after reconnecting
+        let progress = db.begin_frontload("kk1", BTC, 3, 1).await.unwrap();
+        assert_eq!((progress.paths_completed, progress.status.as_str()), (1, "in_progress"));
+        assert_eq!(progress.last_path.as_deref(), Some("m/44'/0'/0'"));
+        assert_eq!((progress.error_message, progress.started_at), (None, started_at));
+
+        db.record_frontload_path("kk1", BTC, "m/49'/0'/0'").await.unwrap();
+        let progress = db.record_frontload_path("kk1", BTC, "m/84'/0'/0'").await.unwrap();
+        assert_eq!((progress.paths_completed, progress.status.as_str()), (3, "completed"));
+        assert!(progress.completed_at.is_some());
+        assert!(!db.has_unfinished_frontload("kk1").await.unwrap());
+
+        // A later run starts over; one with nothing to read completes at once
+        let progress = db.begin_frontload("kk1", BTC, 3, 0).await.unwrap();
+        assert_eq!((progress.last_path, progress.completed_at), (None, None));
+        let progress = db.begin_frontload("kk1", "eip155:1", 1, 1).await.unwrap();
+        assert_eq!(progress.status, "completed");
+        assert!(db.begin_frontload("kk1", "eip155:1", 1, 2).await.is_err());
+        assert_eq!(db.get_frontload_progress("kk1").await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,100 @@
+//! Hardware self-test history per device
+
+use crate::errors::{DatabaseError, Result};
+use crate::types::DeviceHealthCheck;
+use crate::Database;
+
+const OUTCOMES: &[&str] = &["passed", "failed", "cancelled"];
+
+fn row_to_check(row: &rusqlite::Row) -> rusqlite::Result<DeviceHealthCheck> {
+    Ok(DeviceHealthCheck {
+        id: row.get(0)?,
+        device_id: row.get(1)?,
+        checked_at: row.get(2)?,
+        outcome: row.get(3)?,
+        display_ok: row.get(4)?,
+        storage_ok: row.get(5)?,
+        rng_ok: row.get(6)?,
+        details_json: row.get(7)?,
+    })
+}
+
+impl Database {
+    /// Store a self-test outcome ('passed', 'failed' or 'cancelled'); returns the stored row
+    pub async fn record_health_check(
+        &self,
+        device_id: &str,
+        outcome: &str,
+        display_ok: Option<bool>,
+        storage_ok: Option<bool>,
+        rng_ok: Option<bool>,
+        details_json: &str,
+    ) -> Result<DeviceHealthCheck> {
+        if !OUTCOMES.contains(&outcome) {
+            return Err(DatabaseError::Validation(format!("Unknown self-test outcome '{}'", outcome)));
+        }
+        let now = Self::current_timestamp();
+
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO device_health_checks (device_id, checked_at, outcome, display_ok, storage_ok, rng_ok, details_json)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![device_id, now, outcome, display_ok, storage_ok, rng_ok, details_json],
+            )?;
+            Ok(DeviceHealthCheck {
+                id: conn.last_insert_rowid(),
+                device_id: device_id.to_string(),
+                checked_at: now,
+                outcome: outcome.to_string(),
+                display_ok,
+                storage_ok,
+                rng_ok,
+                details_json: details_json.to_string(),
+            })
+        }).await
+    }
+
+    /// Most recent self-test results, optionally for a single device
+    pub async fn get_health_checks(&self, device_id: Option<&str>, limit: usize) -> Result<Vec<DeviceHealthCheck>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, device_id, checked_at, outcome, display_ok, storage_ok, rng_ok, details_json
+                 FROM device_health_checks
+                 WHERE ?1 IS NULL OR device_id = ?1
+                 ORDER BY checked_at DESC, id DESC
+                 LIMIT ?2"
+            )?;
+            let checks = stmt
+                .query_map(rusqlite::params![device_id, limit as i64], row_to_check)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(checks)
+        }).await
+    }
+
+    /// Latest self-test result for a device, if it was ever tested
+    pub async fn latest_health_check(&self, device_id: &str) -> Result<Option<DeviceHealthCheck>> {
+        Ok(self.get_health_checks(Some(device_id), 1).await?.pop())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_latest_health_check_per_device() {
+        let db = Database::new_in_memory().await.unwrap();
+        assert!(db.latest_health_check("dev1").await.unwrap().is_none());
+
+        db.record_health_check("dev1", "passed", Some(true), Some(true), Some(true), "{}").await.unwrap();
+        db.record_health_check("dev1", "cancelled", None, None, None, "{}").await.unwrap();
+        db.record_health_check("dev2", "failed", Some(true), Some(false), Some(true), "{}").await.unwrap();
+        assert!(db.record_health_check("dev1", "exploded", None, None, None, "{}").await.is_err());
+
+        let latest = db.latest_health_check("dev1").await.unwrap().unwrap();
+        assert_eq!(latest.outcome, "cancelled");
+        assert_eq!(latest.display_ok, None);
+        assert_eq!(db.latest_health_check("dev2").await.unwrap().unwrap().storage_ok, Some(false));
+        assert_eq!(db.get_health_checks(None, 10).await.unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,225 @@
+//! Watch-only wallets imported from other hardware wallets
+//!
+//! Each import is an `imported_wallets` row with its accounts in `accounts`
+//! (kind 'digital'). Portfolio rows for an import are stored under the
+//! synthetic device id `import:<fingerprint>`, which no device queue accepts,
+//! so they can never be used for signing.
+
+use rusqlite::{Connection, OptionalExtension};
+use crate::errors::{DatabaseError, Result};
+use crate::types::{ImportedAccount, ImportedWallet, ImportedWalletInput};
+use crate::Database;
+
+/// Prefix of the device id portfolio rows of an import are stored under
+pub const IMPORTED_DEVICE_PREFIX: &str = "import:";
+
+/// Portfolio tables cleared when an import is removed
+const IMPORT_PORTFOLIO_TABLES: &[&str] = &[
+    "portfolio_balances",
+    "portfolio_dashboard",
+    "portfolio_history",
+    "transaction_cache",
+    "tax_lots",
+    "disposals",
+];
+
+pub fn imported_device_id(wallet_fingerprint: &str) -> String {
+    format!("{}{}", IMPORTED_DEVICE_PREFIX, wallet_fingerprint)
+}
+
+pub fn is_imported_device(device_id: &str) -> bool {
+    device_id.starts_with(IMPORTED_DEVICE_PREFIX)
+}
+
+fn load_accounts(conn: &Connection, import_id: i64) -> rusqlite::Result<Vec<ImportedAccount>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, label, path, caip, xpub FROM accounts WHERE import_id = ?1 ORDER BY id"
+    )?;
+    let accounts = stmt.query_map([import_id], |row| {
+        Ok(ImportedAccount {
+            id: row.get(0)?,
+            label: row.get(1)?,
+            path: row.get(2)?,
+            caip: row.get(3)?,
+            xpub: row.get(4)?,
+        })
+    })?
+    .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(accounts)
+}
+
+fn load_wallets(conn: &Connection, id: Option<i64>) -> rusqlite::Result<Vec<ImportedWallet>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, wallet_fingerprint, source_format, source_name, label, imported_at
+         FROM imported_wallets
+         WHERE ?1 IS NULL OR id = ?1
+         ORDER BY imported_at, id"
+    )?;
+    let rows = stmt.query_map([id], |row| {
+        let wallet_fingerprint: String = row.get(1)?;
+        Ok(ImportedWallet {
+            id: row.get(0)?,
+            device_id: imported_device_id(&wallet_fingerprint),
+            wallet_fingerprint,
+            source_format: row.get(2)?,
+            source_name: row.get(3)?,
+            label: row.get(4)?,
+            imported_at: row.get(5)?,
+            accounts: Vec::new(),
+        })
+    })?
+    .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    rows.into_iter()
+        .map(|mut wallet| {
+            wallet.accounts = load_accounts(conn, wallet.id)?;
+            Ok(wallet)
+        })
+        .collect()
+}
+
+impl Database {
+    /// Store an imported wallet and its accounts. Importing the same set of
+    /// xpubs twice (same synthetic fingerprint) is refused.
+    pub async fn save_imported_wallet(&self, input: &ImportedWalletInput) -> Result<ImportedWallet> {
+        if input.accounts.is_empty() {
+            return Err(DatabaseError::Validation("An imported wallet needs at least one account".to_string()));
+        }
+        let now = Self::current_timestamp();
+
+        self.transaction(|tx| {
+            let existing: Option<i64> = tx.query_row(
+                "SELECT id FROM imported_wallets WHERE wallet_fingerprint = ?1",
+                [&input.wallet_fingerprint],
+                |row| row.get(0),
+            ).optional()?;
+            if existing.is_some() {
+                return Err(DatabaseError::Validation(format!(
+                    "These accounts were already imported (wallet {})",
+                    input.wallet_fingerprint
+                )));
+            }
+
+            tx.execute(
+                "INSERT INTO imported_wallets (wallet_fingerprint, source_format, source_name, label, imported_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![input.wallet_fingerprint, input.source_format, input.source_name, input.label, now],
+            )?;
+            let import_id = tx.last_insert_rowid();
+
+            for account in &input.accounts {
+                tx.execute(
+                    "INSERT OR IGNORE INTO accounts (wallet_fp, kind, xpub, label, added_ts, import_id, path, caip)
+                     VALUES (?1, 'digital', ?2, ?3, ?4, ?5, ?6, ?7)",
+                    rusqlite::params![
+                        input.wallet_fingerprint, account.xpub, account.label, now, import_id, account.path, account.caip
+                    ],
+                )?;
+            }
+
+            let mut wallets = load_wallets(tx, Some(import_id))?;
+            wallets.pop().ok_or_else(|| DatabaseError::Validation("Imported wallet disappeared".to_string()))
+        }).await
+    }
+
+    /// All imported wallets with their accounts, oldest first
+    pub async fn list_imported_wallets(&self) -> Result<Vec<ImportedWallet>> {
+        self.with_connection(|conn| Ok(load_wallets(conn, None)?)).await
+    }
+
+    /// Remove an imported wallet, its accounts (and their addresses and txs by
+    /// cascade) and its portfolio rows. Returns false if it did not exist.
+    pub async fn remove_imported_wallet(&self, id: i64) -> Result<bool> {
+        self.transaction(|tx| {
+            let fingerprint: Option<String> = tx.query_row(
+                "SELECT wallet_fingerprint FROM imported_wallets WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            ).optional()?;
+            let Some(fingerprint) = fingerprint else {
+                return Ok(false);
+            };
+
+            let device_id = imported_device_id(&fingerprint);
+            for table in IMPORT_PORTFOLIO_TABLES {
+                tx.execute(&format!("DELETE FROM {} WHERE device_id = ?1", table), [&device_id])?;
+            }
+            tx.execute("DELETE FROM imported_wallets WHERE id = ?1", [id])?;
+            Ok(true)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ImportedAccountInput;
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+
+    fn input(fingerprint: &str) -> ImportedWalletInput {
+        ImportedWalletInput {
+            wallet_fingerprint: fingerprint.to_string(),
+            source_format: "descriptors".to_string(),
+            source_name: Some("wallet.txt".to_string()),
+            label: None,
+            accounts: vec![
+                ImportedAccountInput {
+                    label: "Bitcoin #1".to_string(),
+                    path: "m/84'/0'/0'".to_string(),
+                    caip: BTC.to_string(),
+                    xpub: "zpubImported1".to_string(),
+                },
+                ImportedAccountInput {
+                    label: "Bitcoin Legacy #1".to_string(),
+                    path: "m/44'/0'/0'".to_string(),
+                    caip: BTC.to_string(),
+                    xpub: "xpubImported2".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_list_and_remove() {
+        let db = Database::new_in_memory().await.unwrap();
+        let wallet = db.save_imported_wallet(&input("e1e1e1e1")).await.unwrap();
+        assert_eq!(wallet.device_id, "import:e1e1e1e1");
+        assert_eq!(wallet.accounts.len(), 2);
+        assert!(db.save_imported_wallet(&input("e1e1e1e1")).await.is_err());
+
+        // A synced balance for the import is not an orphan
+        db.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO portfolio_balances (device_id, wallet_fingerprint, pubkey, caip, network_id, ticker, balance, balance_usd, price_usd, type, last_updated)
+                 VALUES ('import:e1e1e1e1', 'e1e1e1e1', 'zpubImported1', ?1, 'bip122:000000000019d6689c085ae165831e93', 'BTC', '0.1', '6000', '60000', 'balance', 1)",
+                [BTC],
+            )?;
+            conn.execute(
+                "INSERT INTO addresses (account_id, address, deriv_path) VALUES (?1, 'bc1qimported', 'm/84''/0''/0''/0/0')",
+                [wallet.accounts[0].id],
+            )?;
+            Ok(())
+        }).await.unwrap();
+        assert_eq!(db.audit_portfolio_integrity().await.unwrap().orphaned_rows, 0);
+
+        assert!(db.remove_imported_wallet(wallet.id).await.unwrap());
+        assert!(!db.remove_imported_wallet(wallet.id).await.unwrap());
+        assert!(db.list_imported_wallets().await.unwrap().is_empty());
+        let leftovers: i64 = db.with_connection(|conn| {
+            Ok(conn.query_row(
+                "SELECT (SELECT COUNT(*) FROM accounts) + (SELECT COUNT(*) FROM addresses)
+                      + (SELECT COUNT(*) FROM portfolio_balances)",
+                [],
+                |row| row.get(0),
+            )?)
+        }).await.unwrap();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn test_imported_device_ids() {
+        assert!(is_imported_device(&imported_device_id("e1e1e1e1")));
+        assert!(!is_imported_device("343737340F4736331F003B00"));
+    }
+}
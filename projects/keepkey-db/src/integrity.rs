@@ -0,0 +1,166 @@
+//! Portfolio consistency checks
+//!
+//! Partial sync failures can leave `portfolio_dashboard` totals that no longer
+//! match the `portfolio_balances` rows they were built from, or balance rows for
+//! pubkeys that were since removed from `wallet_xpubs`. The audit is a couple of
+//! aggregate queries so it is cheap enough to run on a schedule.
+
+use crate::errors::Result;
+use crate::types::{DashboardDrift, IntegrityReport};
+use crate::Database;
+
+/// Relative drift tolerated before a dashboard is rebuilt
+pub const DRIFT_TOLERANCE_RATIO: f64 = 0.005;
+
+/// Absolute drift (USD) always tolerated, so small totals do not flap
+pub const DRIFT_TOLERANCE_MIN_USD: f64 = 1.0;
+
+/// Largest delta accepted for a dashboard total: 0.5% or $1, whichever is larger
+pub fn drift_tolerance(total_usd: f64) -> f64 {
+    (total_usd.abs() * DRIFT_TOLERANCE_RATIO).max(DRIFT_TOLERANCE_MIN_USD)
+}
+
+/// Balance rows whose pubkey is not stored for the same device (or, for
+/// watch-only imports, the same imported wallet)
+const ORPHAN_FILTER: &str =
+    "NOT EXISTS (SELECT 1 FROM wallet_xpubs x WHERE x.device_id = b.device_id AND x.pubkey = b.pubkey)
+     AND NOT EXISTS (SELECT 1 FROM accounts a JOIN imported_wallets w ON w.id = a.import_id
+                     WHERE b.device_id = 'import:' || w.wallet_fingerprint AND a.xpub = b.pubkey)";
+
+impl Database {
+    /// Compare every per-wallet dashboard total with the sum of its balances
+    /// and count orphaned balance rows. Read-only.
+    pub async fn audit_portfolio_integrity(&self) -> Result<IntegrityReport> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT d.device_id, d.wallet_fingerprint, CAST(d.total_value_usd AS REAL),
+                        COALESCE((SELECT SUM(CAST(b.balance_usd AS REAL)) FROM portfolio_balances b
+                                  WHERE b.device_id = d.device_id AND b.wallet_fingerprint = d.wallet_fingerprint
+                                    AND COALESCE(b.type, 'balance') = 'balance'), 0)
+                 FROM portfolio_dashboard d
+                 WHERE d.is_combined = 0"
+            )?;
+            let rows = stmt
+                .query_map([], |row| Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                )))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let dashboards_checked = rows.len();
+            let drifted = rows
+                .into_iter()
+                .filter(|(_, _, stored, computed)| (stored - computed).abs() > drift_tolerance(stored.max(*computed)))
+                .map(|(device_id, wallet_fingerprint, stored, computed)| DashboardDrift {
+                    device_id,
+                    wallet_fingerprint,
+                    stored_total_usd: stored,
+                    computed_total_usd: computed,
+                    delta_usd: computed - stored,
+                })
+                .collect();
+
+            let orphaned_rows: i64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM portfolio_balances b WHERE {}", ORPHAN_FILTER),
+                [],
+                |row| row.get(0),
+            )?;
+
+            Ok(IntegrityReport { dashboards_checked, drifted, orphaned_rows: orphaned_rows as usize })
+        }).await
+    }
+
+    /// Delete balance rows whose pubkey is no longer in wallet_xpubs; returns the number removed
+    pub async fn cleanup_orphaned_portfolio_rows(&self) -> Result<usize> {
+        self.with_connection(|conn| {
+            Ok(conn.execute(&format!("DELETE FROM portfolio_balances AS b WHERE {}", ORPHAN_FILTER), [])?)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::WalletXpubInput;
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+
+    async fn seed(db: &Database) {
+        db.register_device("dev1", None, None).await.unwrap();
+        db.save_wallet_xpub(&WalletXpubInput {
+            device_id: "dev1".to_string(),
+            wallet_fingerprint: "aaaa0001".to_string(),
+            path: "m/84'/0'/0'".to_string(),
+            label: "Bitcoin".to_string(),
+            caip: BTC.to_string(),
+            pubkey: "xpubKept".to_string(),
+        }).await.unwrap();
+
+        db.with_connection(|conn| {
+            for (pubkey, usd) in [("xpubKept", "600.00"), ("xpubKept2", "400.00")] {
+                conn.execute(
+                    "INSERT INTO portfolio_balances (device_id, wallet_fingerprint, pubkey, caip, network_id, ticker, address,
+                                                     balance, balance_usd, price_usd, type, last_updated)
+                     VALUES ('dev1', 'aaaa0001', ?1, ?2, 'bip122:000000000019d6689c085ae165831e93', 'BTC', ?1, '0.01', ?3, '60000', 'balance', 100)",
+                    rusqlite::params![pubkey, BTC, usd],
+                )?;
+            }
+            Ok(())
+        }).await.unwrap();
+    }
+
+    async fn set_stored_total(db: &Database, total: &str) {
+        let total = total.to_string();
+        db.with_connection(move |conn| {
+            conn.execute(
+                "INSERT INTO portfolio_dashboard (device_id, wallet_fingerprint, total_value_usd, networks_json, assets_json, last_updated)
+                 VALUES ('dev1', 'aaaa0001', ?1, '[]', '[]', 1)
+                 ON CONFLICT(device_id, wallet_fingerprint) DO UPDATE SET total_value_usd = excluded.total_value_usd",
+                [total],
+            )?;
+            Ok(())
+        }).await.unwrap();
+    }
+
+    #[test]
+    fn test_drift_tolerance() {
+        assert_eq!(drift_tolerance(50.0), 1.0);
+        assert_eq!(drift_tolerance(10_000.0), 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_detects_and_heals_dashboard_drift() {
+        let db = Database::new_in_memory().await.unwrap();
+        seed(&db).await;
+
+        // Within tolerance ($1000 total, $1 off)
+        set_stored_total(&db, "1001.00").await;
+        let report = db.audit_portfolio_integrity().await.unwrap();
+        assert_eq!(report.dashboards_checked, 1);
+        assert!(report.drifted.is_empty());
+
+        // A sync that failed halfway left the old total behind
+        set_stored_total(&db, "600.00").await;
+        let report = db.audit_portfolio_integrity().await.unwrap();
+        assert_eq!(report.drifted.len(), 1);
+        assert_eq!(report.drifted[0].delta_usd, 400.0);
+
+        db.compute_dashboard("dev1", "aaaa0001").await.unwrap();
+        assert_eq!(db.get_dashboard_total_usd("dev1", "aaaa0001").await.unwrap().as_deref(), Some("1000.00"));
+        assert!(db.audit_portfolio_integrity().await.unwrap().drifted.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_orphaned_rows_are_flagged_and_cleaned() {
+        let db = Database::new_in_memory().await.unwrap();
+        seed(&db).await;
+
+        // xpubKept2 was never (or is no longer) in wallet_xpubs
+        assert_eq!(db.audit_portfolio_integrity().await.unwrap().orphaned_rows, 1);
+        assert_eq!(db.cleanup_orphaned_portfolio_rows().await.unwrap(), 1);
+        assert_eq!(db.audit_portfolio_integrity().await.unwrap().orphaned_rows, 0);
+        assert_eq!(db.get_asset_balances("dev1", "aaaa0001", BTC).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,311 @@
+//! One-time import of data kept by earlier vault versions
+//!
+//! Before keepkey.db, onboarding state and preferences lived in
+//! `~/.keepkey/keepkey.json` and devices, xpubs and balances in
+//! `~/.keepkey/index.db`. A fresh keepkey.db copies them over once so an
+//! upgrade does not send everyone through onboarding again. Legacy files that
+//! are missing, partial or corrupt are logged and skipped; the import never
+//! stops the app from starting.
+
+use crate::errors::Result;
+use crate::Database;
+use rusqlite::types::Value;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use serde::Serialize;
+use std::path::Path;
+
+pub const LEGACY_CONFIG_FILE: &str = "keepkey.json";
+pub const LEGACY_INDEX_DB_FILE: &str = "index.db";
+/// Meta key set once the import has run, whatever it found
+const COMPLETED_KEY: &str = "legacy_migration_completed";
+
+/// index.db tables copied as-is, parents before children
+const LEGACY_TABLES: [&str; 3] = ["devices", "wallet_xpubs", "portfolio_cache"];
+
+/// What the legacy import copied
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct LegacyMigration {
+    /// The import had already run, or keepkey.db was not fresh
+    pub skipped: bool,
+    pub onboarding_completed: bool,
+    pub preferences: u32,
+    pub devices: u32,
+    pub wallet_xpubs: u32,
+    pub portfolio_cache: u32,
+}
+
+impl LegacyMigration {
+    fn count_mut(&mut self, table: &str) -> &mut u32 {
+        match table {
+            "devices" => &mut self.devices,
+            "wallet_xpubs" => &mut self.wallet_xpubs,
+            _ => &mut self.portfolio_cache,
+        }
+    }
+}
+
+impl Database {
+    /// Import keepkey.json and index.db from `legacy_dir` into a fresh
+    /// database, once. The completion marker is written even when there was
+    /// nothing to import, so later starts do not look again.
+    pub async fn migrate_legacy_data(&self, legacy_dir: &Path) -> Result<LegacyMigration> {
+        let config_path = legacy_dir.join(LEGACY_CONFIG_FILE);
+        let index_path = legacy_dir.join(LEGACY_INDEX_DB_FILE);
+
+        self.transaction(|conn| {
+            let mut migration = LegacyMigration::default();
+            let completed: Option<String> = conn
+                .query_row("SELECT val FROM meta WHERE key = ?1", [COMPLETED_KEY], |row| row.get(0))
+                .optional()?;
+            if completed.is_some() {
+                migration.skipped = true;
+                return Ok(migration);
+            }
+
+            if is_fresh(conn)? {
+                if index_path.exists() {
+                    import_index_db(conn, &index_path, &mut migration);
+                }
+                if config_path.exists() {
+                    import_config(conn, &config_path, &mut migration);
+                }
+            } else {
+                migration.skipped = true;
+            }
+
+            conn.execute(
+                "INSERT OR REPLACE INTO meta (key, val) VALUES (?1, ?2)",
+                rusqlite::params![COMPLETED_KEY, Database::current_timestamp().to_string()],
+            )?;
+            Ok(migration)
+        })
+        .await
+    }
+}
+
+/// No devices, no xpubs and no finished onboarding
+fn is_fresh(conn: &Connection) -> Result<bool> {
+    let devices: i64 = conn.query_row("SELECT COUNT(*) FROM devices", [], |row| row.get(0))?;
+    let xpubs: i64 = conn.query_row("SELECT COUNT(*) FROM wallet_xpubs", [], |row| row.get(0))?;
+    let onboarded: Option<String> = conn
+        .query_row("SELECT val FROM meta WHERE key = 'onboarding_completed'", [], |row| row.get(0))
+        .optional()?;
+    Ok(devices == 0 && xpubs == 0 && onboarded.as_deref() != Some("true"))
+}
+
+fn import_index_db(conn: &Connection, path: &Path, migration: &mut LegacyMigration) {
+    // Read-write without create: index.db is in WAL mode, and a read-only
+    // connection cannot recover a WAL left behind by a crash. Nothing is written.
+    let legacy = match Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE) {
+        Ok(legacy) => legacy,
+        Err(e) => {
+            log::warn!("Skipping legacy {:?}: {}", path, e);
+            return;
+        }
+    };
+
+    match import_meta(&legacy, conn) {
+        Ok((onboarded, preferences)) => {
+            migration.onboarding_completed |= onboarded;
+            migration.preferences += preferences;
+        }
+        Err(e) => log::warn!("Skipping settings from legacy {:?}: {}", path, e),
+    }
+    for table in LEGACY_TABLES {
+        match copy_table(&legacy, conn, table) {
+            Ok(copied) => *migration.count_mut(table) += copied,
+            Err(e) => log::warn!("Skipping {} from legacy {:?}: {}", table, path, e),
+        }
+    }
+    log::info!("Imported legacy {:?}: {:?}", path, migration);
+}
+
+/// Onboarding state and `pref_` rows; returns (onboarded, preferences copied)
+fn import_meta(legacy: &Connection, conn: &Connection) -> Result<(bool, u32)> {
+    let mut stmt = legacy.prepare(
+        "SELECT key, val FROM meta
+         WHERE key IN ('onboarding_completed', 'onboarding_timestamp') OR key LIKE 'pref\\_%' ESCAPE '\\'",
+    )?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut onboarded = false;
+    let mut preferences = 0;
+    for (key, val) in rows {
+        let Some(val) = val else { continue };
+        if key == "onboarding_completed" {
+            onboarded = val == "true";
+        } else if key.starts_with("pref_") {
+            preferences += 1;
+        }
+        conn.execute("INSERT OR REPLACE INTO meta (key, val) VALUES (?1, ?2)", rusqlite::params![key, val])?;
+    }
+    Ok((onboarded, preferences))
+}
+
+/// Copy the columns `table` has in both databases, skipping row ids (they are
+/// reassigned) and rows that clash with what is there or lost their parent
+fn copy_table(legacy: &Connection, conn: &Connection, table: &str) -> Result<u32> {
+    let target_columns = crate::migrations::column_names(conn, table)?;
+    let columns: Vec<String> = crate::migrations::column_names(legacy, table)?
+        .into_iter()
+        .filter(|column| column != "id" && target_columns.contains(column))
+        .collect();
+    if columns.is_empty() {
+        return Ok(0);
+    }
+
+    let list = columns.join(", ");
+    let placeholders = (1..=columns.len()).map(|i| format!("?{}", i)).collect::<Vec<_>>().join(", ");
+    let mut select = legacy.prepare(&format!("SELECT {} FROM {}", list, table))?;
+    let mut insert = conn.prepare(&format!("INSERT OR IGNORE INTO {} ({}) VALUES ({})", table, list, placeholders))?;
+
+    let mut copied = 0;
+    let mut rows = select.query([])?;
+    while let Some(row) = rows.next()? {
+        let values = (0..columns.len())
+            .map(|i| row.get::<_, Value>(i))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        match insert.execute(rusqlite::params_from_iter(values)) {
+            Ok(inserted) => copied += inserted as u32,
+            Err(e) => log::warn!("Skipping a legacy {} row: {}", table, e),
+        }
+    }
+    Ok(copied)
+}
+
+/// keepkey.json: `isOnboarded` plus flat preferences ("language", "theme", ...)
+fn import_config(conn: &Connection, path: &Path, migration: &mut LegacyMigration) {
+    let config = match std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&text).map_err(|e| e.to_string()))
+    {
+        Ok(config) => config,
+        Err(e) => {
+            log::warn!("Skipping legacy {:?}: {}", path, e);
+            return;
+        }
+    };
+
+    for (key, value) in config {
+        let result = if key == "isOnboarded" {
+            if value.as_bool() != Some(true) {
+                continue;
+            }
+            migration.onboarding_completed = true;
+            conn.execute("INSERT OR REPLACE INTO meta (key, val) VALUES ('onboarding_completed', 'true')", [])
+        } else {
+            let value = match value {
+                serde_json::Value::String(s) => s,
+                serde_json::Value::Bool(_) | serde_json::Value::Number(_) => value.to_string(),
+                _ => continue,
+            };
+            migration.preferences += 1;
+            conn.execute(
+                "INSERT OR REPLACE INTO meta (key, val) VALUES (?1, ?2)",
+                rusqlite::params![format!("pref_{}", key), value],
+            )
+        };
+        if let Err(e) = result {
+            log::warn!("Skipping legacy setting {}: {}", key, e);
+        }
+    }
+    log::info!("Imported legacy {:?}", path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INDEX_DB_V1: &str = include_str!("../tests/fixtures/index_db_v1.sql");
+
+    fn write_index_db(dir: &Path, extra_sql: &str) {
+        let conn = Connection::open(dir.join(LEGACY_INDEX_DB_FILE)).unwrap();
+        conn.execute_batch(INDEX_DB_V1).unwrap();
+        conn.execute_batch(extra_sql).unwrap();
+    }
+
+    async fn meta(db: &Database, key: &str) -> Option<String> {
+        let key = key.to_string();
+        db.with_connection(move |conn| {
+            Ok(conn.query_row("SELECT val FROM meta WHERE key = ?1", [key], |row| row.get(0)).optional()?)
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_legacy_files_are_imported_once() {
+        let legacy = tempfile::tempdir().unwrap();
+        write_index_db(legacy.path(), r#"
+            UPDATE meta SET val = 'true' WHERE key = 'onboarding_completed';
+            UPDATE meta SET val = 'EUR' WHERE key = 'pref_currency';
+            INSERT INTO portfolio_cache (pubkey, caip, balance, balance_usd, price_usd, symbol, last_updated)
+            VALUES ('zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs',
+                    'bip122:000000000019d6689c085ae165831e93/slip44:0', '0.5', '30000', '60000', 'BTC', 1700000000);
+        "#);
+        std::fs::write(
+            legacy.path().join(LEGACY_CONFIG_FILE),
+            r#"{ "language": "de", "isOnboarded": true, "theme": "dark", "notifications": false }"#,
+        ).unwrap();
+
+        let db = Database::new_in_memory().await.unwrap();
+        let migration = db.migrate_legacy_data(legacy.path()).await.unwrap();
+        assert_eq!(migration, LegacyMigration {
+            skipped: false,
+            onboarding_completed: true,
+            preferences: 8,
+            devices: 1,
+            wallet_xpubs: 2,
+            portfolio_cache: 1,
+        });
+
+        assert!(db.is_onboarded().await.unwrap());
+        assert_eq!(db.get_preference("currency").await.unwrap().as_deref(), Some("EUR"));
+        // keepkey.json is applied after index.db
+        assert_eq!(db.get_preference("language").await.unwrap().as_deref(), Some("de"));
+        assert_eq!(db.get_preference("notifications").await.unwrap().as_deref(), Some("false"));
+        let (label, xpubs) = db.with_connection(|conn| {
+            let label: String = conn.query_row(
+                "SELECT label FROM devices WHERE device_id = '343737340F4736331F003B00'", [], |row| row.get(0),
+            )?;
+            let xpubs: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM wallet_xpubs WHERE wallet_fingerprint = ''", [], |row| row.get(0),
+            )?;
+            Ok((label, xpubs))
+        }).await.unwrap();
+        assert_eq!((label.as_str(), xpubs), ("My KeepKey", 2));
+        assert!(meta(&db, COMPLETED_KEY).await.is_some());
+
+        // The marker stops a second import
+        db.set_preference("language", "fr").await.unwrap();
+        assert!(db.migrate_legacy_data(legacy.path()).await.unwrap().skipped);
+        assert_eq!(db.get_preference("language").await.unwrap().as_deref(), Some("fr"));
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_legacy_files_do_not_block_startup() {
+        let legacy = tempfile::tempdir().unwrap();
+        std::fs::write(legacy.path().join(LEGACY_INDEX_DB_FILE), b"not a sqlite database").unwrap();
+        std::fs::write(legacy.path().join(LEGACY_CONFIG_FILE), b"{ \"isOnboarded\": tr").unwrap();
+
+        let db = Database::new_in_memory().await.unwrap();
+        let migration = db.migrate_legacy_data(legacy.path()).await.unwrap();
+        assert_eq!(migration, LegacyMigration::default());
+        assert!(!db.is_onboarded().await.unwrap());
+        assert!(meta(&db, COMPLETED_KEY).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_database_in_use_is_not_overwritten() {
+        let legacy = tempfile::tempdir().unwrap();
+        std::fs::write(legacy.path().join(LEGACY_CONFIG_FILE), r#"{ "language": "de" }"#).unwrap();
+
+        let db = Database::new_in_memory().await.unwrap();
+        db.set_onboarding_completed().await.unwrap();
+        assert!(db.migrate_legacy_data(legacy.path()).await.unwrap().skipped);
+        assert_eq!(db.get_preference("language").await.unwrap().as_deref(), Some("en"));
+    }
+}
@@ -1,11 +1,42 @@
 pub mod database;
+pub mod account_scan;
 pub mod device_registry;
+pub mod device_migration;
+pub mod custom_tokens;
+pub mod device_notes;
+pub mod device_connections;
+pub mod secure_notes;
+pub mod device_export;
 pub mod portfolio;
+pub mod dashboard;
+pub mod portfolio_history;
+pub mod integrity;
 pub mod assets;
 pub mod cache;
+pub mod frontload;
+pub mod storage;
+pub mod fault_logs;
+pub mod health_checks;
+pub mod firmware_updates;
+pub mod event_outbox;
+pub mod wallets;
+pub mod imported_wallets;
+pub mod activity;
+pub mod backup;
+pub mod signing_audit;
+pub mod token_approvals;
+pub mod tx_tracking;
+pub mod networks;
+pub mod derivation_paths;
+pub mod tax_lots;
+pub mod api_clients;
 pub mod migrations;
+pub mod migration_guard;
+pub mod legacy_migration;
+pub mod preferences;
 pub mod types;
 pub mod errors;
+pub mod data_dir;
 
 // Re-export main types and the database
 pub use database::Database;
@@ -21,12 +52,9 @@ pub async fn init_database() -> anyhow::Result<Database> {
     Database::new().await.map_err(Into::into)
 }
 
-/// Get the default database path
+/// Get the database path inside the resolved data directory
 pub fn get_database_path() -> PathBuf {
-    dirs::home_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join(".keepkey")
-        .join("keepkey.db")
+    data_dir::resolve_data_dir().path.join(data_dir::DATABASE_FILE)
 }
 
 /// Check if the database file exists
@@ -0,0 +1,413 @@
+//! Dry-run guard around schema migrations
+//!
+//! Before an existing database is upgraded, the pending migrations run against
+//! a copy made with SQLite's online backup API. The copy must keep every row,
+//! gain no foreign key violations, pass `quick_check` and show each pending
+//! migration in effect; only then is the real file migrated. A failed dry run
+//! leaves the real file untouched and the connection in `query_only` mode, so
+//! the app keeps reading the old schema until the user retries.
+//!
+//! When the disk cannot hold the copy, the guard falls back to migrating the
+//! real file directly after writing a compacted pre-migration backup.
+
+use crate::errors::Result;
+use crate::migrations::{pending_migrations, table_names};
+use crate::types::{MigrationKind, MigrationReport, MigrationStatus, PendingMigration};
+use chrono::Local;
+use rusqlite::{Connection, DatabaseName, OptionalExtension};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Preference that postpones startup migrations until `apply_migrations_now`
+pub const DEFER_MIGRATIONS_PREF: &str = "defer_schema_migrations";
+/// File name prefix of the backup taken before a migration without dry run
+pub(crate) const PRE_MIGRATION_PREFIX: &str = "keepkey-pre-migration-";
+/// The dry-run copy plus the growth of a rebuilt table need about twice the file size
+const DRY_RUN_SPACE_FACTOR: u64 = 2;
+
+pub(crate) struct GuardOptions<'a> {
+    pub db_path: &'a Path,
+    /// Where the pre-migration backup goes when the dry run is skipped
+    pub backup_dir: &'a Path,
+    /// Free bytes on the database's filesystem, if known
+    pub available_space: Option<u64>,
+    /// Skip the dry run (a backup is still taken)
+    pub force: bool,
+    /// Honour `DEFER_MIGRATIONS_PREF`; false when the user asked to migrate now
+    pub respect_deferral: bool,
+}
+
+/// Run `migrate` on `conn` behind a dry run on a copy of the database
+pub(crate) fn migrate_guarded<F>(conn: &Connection, options: &GuardOptions, migrate: F) -> Result<MigrationReport>
+where
+    F: Fn(&Connection) -> Result<()>,
+{
+    let started = Instant::now();
+    let pending = pending_migrations(conn)?;
+    // Fresh installs and up-to-date files have nothing to protect; migrate
+    // still runs for its pragmas and idempotent indexes
+    if pending.is_empty() || table_names(conn)?.is_empty() {
+        migrate(conn)?;
+        return Ok(MigrationReport::up_to_date());
+    }
+
+    let mut report = MigrationReport {
+        status: MigrationStatus::Applied,
+        pending,
+        failures: Vec::new(),
+        backup_file: None,
+        warning: None,
+        duration_ms: 0,
+    };
+    log::info!("{} pending schema migration(s)", report.pending.len());
+
+    if options.respect_deferral && migrations_deferred(conn) {
+        log::warn!("Schema migrations deferred by preference; database opened read-only");
+        report.status = MigrationStatus::Deferred;
+        return finish(conn, report, started);
+    }
+
+    let required = database_size(conn)?.saturating_mul(DRY_RUN_SPACE_FACTOR);
+    let skip_reason = if options.force {
+        Some("Dry run skipped on request".to_string())
+    } else {
+        match options.available_space {
+            Some(free) if free < required => Some(format!(
+                "Only {} bytes free, {} needed for a dry run; migrated directly after a backup",
+                free, required
+            )),
+            _ => None,
+        }
+    };
+
+    if let Some(reason) = skip_reason {
+        report.warning = Some(reason);
+        return migrate_directly(conn, options, report, started, migrate);
+    }
+
+    let scratch = scratch_path(options.db_path);
+    let failures = match dry_run(conn, &scratch, &report.pending, &migrate) {
+        Ok(failures) => failures,
+        Err(e) => {
+            // Usually a full disk while copying; the copy tells us nothing
+            remove_scratch(&scratch);
+            report.warning = Some(format!("Could not make a dry-run copy ({}); migrated directly after a backup", e));
+            return migrate_directly(conn, options, report, started, migrate);
+        }
+    };
+    remove_scratch(&scratch);
+
+    if !failures.is_empty() {
+        log::error!("Migration dry run failed: {}", failures.join("; "));
+        report.status = MigrationStatus::Blocked;
+        report.failures = failures;
+        return finish(conn, report, started);
+    }
+
+    if let Err(e) = migrate(conn) {
+        log::error!("Migration failed after a successful dry run: {}", e);
+        report.status = MigrationStatus::Blocked;
+        report.failures.push(format!("Migration failed: {}", e));
+    }
+    finish(conn, report, started)
+}
+
+fn migrate_directly<F>(
+    conn: &Connection,
+    options: &GuardOptions,
+    mut report: MigrationReport,
+    started: Instant,
+    migrate: F,
+) -> Result<MigrationReport>
+where
+    F: Fn(&Connection) -> Result<()>,
+{
+    if let Some(warning) = &report.warning {
+        log::warn!("{}", warning);
+    }
+    match write_pre_migration_backup(conn, options.backup_dir) {
+        Ok(filename) => report.backup_file = Some(filename),
+        Err(e) => {
+            report.status = MigrationStatus::Blocked;
+            report.failures.push(format!("Pre-migration backup failed: {}", e));
+            return finish(conn, report, started);
+        }
+    }
+
+    report.status = MigrationStatus::AppliedWithoutDryRun;
+    if let Err(e) = migrate(conn) {
+        log::error!("Direct migration failed: {}", e);
+        report.status = MigrationStatus::Blocked;
+        report.failures.push(format!("Migration failed: {}", e));
+    }
+    finish(conn, report, started)
+}
+
+/// Put a blocked or deferred connection into read-only mode and stamp the duration
+fn finish(conn: &Connection, mut report: MigrationReport, started: Instant) -> Result<MigrationReport> {
+    if report.is_read_only() {
+        conn.pragma_update(None, "query_only", true)?;
+    }
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    Ok(report)
+}
+
+fn migrations_deferred(conn: &Connection) -> bool {
+    conn.query_row(
+        "SELECT val FROM meta WHERE key = ?1",
+        [format!("pref_{}", DEFER_MIGRATIONS_PREF)],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+    .is_some_and(|val| val == "true")
+}
+
+fn database_size(conn: &Connection) -> Result<u64> {
+    let page_count: i64 = conn.pragma_query_value(None, "page_count", |row| row.get(0))?;
+    let page_size: i64 = conn.pragma_query_value(None, "page_size", |row| row.get(0))?;
+    Ok((page_count * page_size).max(0) as u64)
+}
+
+fn scratch_path(db_path: &Path) -> PathBuf {
+    let mut scratch = db_path.as_os_str().to_owned();
+    scratch.push(".migration-check");
+    PathBuf::from(scratch)
+}
+
+fn remove_scratch(scratch: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = scratch.as_os_str().to_owned();
+        file.push(suffix);
+        let _ = std::fs::remove_file(PathBuf::from(file));
+    }
+}
+
+/// What the dry run compares before and after migrating the copy
+struct Snapshot {
+    row_counts: Vec<(String, i64)>,
+    foreign_key_violations: usize,
+}
+
+fn snapshot(conn: &Connection) -> Result<Snapshot> {
+    let mut row_counts = Vec::new();
+    for table in table_names(conn)? {
+        let count: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", table), [], |row| row.get(0))?;
+        row_counts.push((table, count));
+    }
+    Ok(Snapshot { row_counts, foreign_key_violations: foreign_key_violations(conn)? })
+}
+
+fn foreign_key_violations(conn: &Connection) -> Result<usize> {
+    let mut stmt = conn.prepare("PRAGMA foreign_key_check")?;
+    let count = stmt.query_map([], |_| Ok(()))?.count();
+    Ok(count)
+}
+
+/// Copy the database to `scratch`, migrate the copy and return what went wrong.
+///
+/// An `Err` means the copy itself could not be made or inspected.
+fn dry_run<F>(conn: &Connection, scratch: &Path, pending: &[PendingMigration], migrate: &F) -> Result<Vec<String>>
+where
+    F: Fn(&Connection) -> Result<()>,
+{
+    remove_scratch(scratch);
+    conn.backup(DatabaseName::Main, scratch, None)?;
+    let copy = Connection::open(scratch)?;
+
+    let before = snapshot(&copy)?;
+    if let Err(e) = migrate(&copy) {
+        return Ok(vec![format!("Migration failed: {}", e)]);
+    }
+    let after = snapshot(&copy)?;
+
+    let mut failures = Vec::new();
+    for (table, count) in &before.row_counts {
+        match after.row_counts.iter().find(|(name, _)| name == table) {
+            None => failures.push(format!("Table {} was dropped", table)),
+            Some((_, migrated)) if migrated < count => {
+                failures.push(format!("Table {} lost {} of {} rows", table, count - migrated, count))
+            }
+            Some(_) => {}
+        }
+    }
+    if after.foreign_key_violations > before.foreign_key_violations {
+        failures.push(format!(
+            "Foreign key violations rose from {} to {}",
+            before.foreign_key_violations, after.foreign_key_violations
+        ));
+    }
+
+    let quick_check: String = copy.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if quick_check != "ok" {
+        failures.push(format!("Integrity check failed: {}", quick_check));
+    }
+
+    let remaining = pending_migrations(&copy)?;
+    for migration in pending {
+        if remaining.iter().any(|m| m.id == migration.id) {
+            failures.push(format!("{} did not take effect", migration.id));
+        } else if let Err(e) = spot_check(&copy, migration) {
+            failures.push(format!("{}: {}", migration.id, e));
+        }
+    }
+    Ok(failures)
+}
+
+/// Query what a migration touched, to catch a table or column that exists but cannot be read
+fn spot_check(conn: &Connection, migration: &PendingMigration) -> rusqlite::Result<()> {
+    let sql = match (migration.kind, &migration.column) {
+        (MigrationKind::AddColumn, Some(column)) => {
+            format!("SELECT \"{}\" FROM \"{}\" LIMIT 1", column, migration.table)
+        }
+        _ => format!("SELECT * FROM \"{}\" LIMIT 1", migration.table),
+    };
+    conn.prepare(&sql)?.query([])?.next()?;
+    Ok(())
+}
+
+/// Compacted copy of the database in `dir`; returns the file name
+fn write_pre_migration_backup(conn: &Connection, dir: &Path) -> Result<String> {
+    std::fs::create_dir_all(dir)?;
+    let filename = format!("{}{}.db", PRE_MIGRATION_PREFIX, Local::now().format("%Y%m%d-%H%M%S"));
+    let dest = dir.join(&filename);
+    let tmp = dir.join(format!("{}.tmp", filename));
+    let _ = std::fs::remove_file(&tmp);
+
+    if let Err(e) = conn.execute("VACUUM INTO ?1", [tmp.to_string_lossy()]) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    std::fs::rename(&tmp, &dest)?;
+    log::info!("Wrote pre-migration backup {}", filename);
+    Ok(filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::DatabaseError;
+    use crate::migrations::apply_migrations;
+
+    /// A database from before networks.finality_depth and the wallet-scoped xpub key
+    fn old_database(path: &Path) -> Connection {
+        let conn = Connection::open(path).unwrap();
+        apply_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "UPDATE meta SET val = '6' WHERE key = 'db_version';
+             ALTER TABLE networks DROP COLUMN finality_depth;
+             DROP TABLE api_clients;
+             DROP TABLE wallet_xpubs;
+             CREATE TABLE wallet_xpubs (id INTEGER PRIMARY KEY AUTOINCREMENT, device_id TEXT NOT NULL, path TEXT NOT NULL,
+                label TEXT NOT NULL, caip TEXT NOT NULL, pubkey TEXT NOT NULL,
+                created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')), UNIQUE(device_id, path, caip));
+             INSERT INTO devices (device_id, first_seen, last_seen) VALUES ('dev1', 0, 0);
+             INSERT INTO wallet_xpubs (device_id, path, label, caip, pubkey) VALUES ('dev1', 'm/84''/0''/0''', 'BTC', 'btc', 'xpubA');"
+        ).unwrap();
+        conn
+    }
+
+    fn options<'a>(db_path: &'a Path, backup_dir: &'a Path, available_space: Option<u64>) -> GuardOptions<'a> {
+        GuardOptions { db_path, backup_dir, available_space, force: false, respect_deferral: true }
+    }
+
+    fn count(conn: &Connection, table: &str) -> i64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_dry_run_then_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("keepkey.db");
+        let conn = old_database(&db_path);
+
+        let pending = pending_migrations(&conn).unwrap();
+        assert!(pending.iter().any(|m| m.id == "add_column:networks.finality_depth"));
+        assert!(pending.iter().any(|m| m.id == "rebuild_table:wallet_xpubs"));
+        assert!(pending.iter().any(|m| m.kind == MigrationKind::CreateTable && m.table == "api_clients"));
+
+        let report = migrate_guarded(&conn, &options(&db_path, &dir.path().join("backups"), None), apply_migrations).unwrap();
+        assert_eq!(report.status, MigrationStatus::Applied, "{:?}", report.failures);
+        assert!(pending_migrations(&conn).unwrap().is_empty());
+        assert_eq!(count(&conn, "wallet_xpubs"), 1);
+        assert!(!scratch_path(&db_path).exists());
+        assert!(!dir.path().join("backups").exists());
+
+        // A second open has nothing to do
+        let again = migrate_guarded(&conn, &options(&db_path, dir.path(), None), apply_migrations).unwrap();
+        assert_eq!(again.status, MigrationStatus::UpToDate);
+    }
+
+    #[test]
+    fn test_failing_migration_is_blocked() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("keepkey.db");
+        let conn = old_database(&db_path);
+        let backup_dir = dir.path().join("backups");
+
+        // Migrates, but loses the user's xpubs on the way
+        let lossy = |conn: &Connection| -> Result<()> {
+            apply_migrations(conn)?;
+            conn.execute("DELETE FROM wallet_xpubs", [])?;
+            Ok(())
+        };
+        let report = migrate_guarded(&conn, &options(&db_path, &backup_dir, None), lossy).unwrap();
+        assert_eq!(report.status, MigrationStatus::Blocked);
+        assert!(report.failures.iter().any(|f| f.contains("wallet_xpubs lost 1 of 1 rows")), "{:?}", report.failures);
+        assert!(report.is_read_only());
+
+        // The real file still has the old schema and its data, and refuses writes
+        assert!(!pending_migrations(&conn).unwrap().is_empty());
+        assert_eq!(count(&conn, "wallet_xpubs"), 1);
+        assert!(conn.execute("DELETE FROM networks", []).is_err());
+        assert!(!scratch_path(&db_path).exists());
+
+        // A migration that errors out is blocked the same way
+        conn.pragma_update(None, "query_only", false).unwrap();
+        let broken = |_: &Connection| -> Result<()> { Err(DatabaseError::Validation("boom".to_string())) };
+        let report = migrate_guarded(&conn, &options(&db_path, &backup_dir, None), broken).unwrap();
+        assert_eq!(report.status, MigrationStatus::Blocked);
+        assert_eq!(report.failures, vec!["Migration failed: Validation error: boom".to_string()]);
+    }
+
+    #[test]
+    fn test_low_disk_space_migrates_directly_after_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("keepkey.db");
+        let conn = old_database(&db_path);
+        let backup_dir = dir.path().join("backups");
+
+        let report = migrate_guarded(&conn, &options(&db_path, &backup_dir, Some(1)), apply_migrations).unwrap();
+        assert_eq!(report.status, MigrationStatus::AppliedWithoutDryRun);
+        assert!(report.warning.as_deref().unwrap().contains("bytes free"));
+        let backup_file = report.backup_file.unwrap();
+        assert!(backup_file.starts_with(PRE_MIGRATION_PREFIX));
+
+        // The backup holds the pre-migration schema
+        let backup = Connection::open(backup_dir.join(&backup_file)).unwrap();
+        assert!(!pending_migrations(&backup).unwrap().is_empty());
+        assert_eq!(count(&backup, "wallet_xpubs"), 1);
+        assert!(pending_migrations(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_deferred_migrations_open_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("keepkey.db");
+        let conn = old_database(&db_path);
+        conn.execute(
+            "INSERT INTO meta (key, val) VALUES (?1, 'true')",
+            [format!("pref_{}", DEFER_MIGRATIONS_PREF)],
+        ).unwrap();
+
+        let report = migrate_guarded(&conn, &options(&db_path, dir.path(), None), apply_migrations).unwrap();
+        assert_eq!(report.status, MigrationStatus::Deferred);
+        assert!(!report.pending.is_empty());
+
+        conn.pragma_update(None, "query_only", false).unwrap();
+        let mut now = options(&db_path, dir.path(), None);
+        now.respect_deferral = false;
+        assert_eq!(migrate_guarded(&conn, &now, apply_migrations).unwrap().status, MigrationStatus::Applied);
+    }
+}
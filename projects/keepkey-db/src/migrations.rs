@@ -1,26 +1,306 @@
 use crate::errors::Result;
-use rusqlite::Connection;
+use crate::types::{MigrationKind, PendingMigration};
+use rusqlite::{Connection, OptionalExtension};
 
-/// Initialize the database schema
+/// Schema version this build migrates databases to, kept in meta.db_version
+pub const SCHEMA_VERSION: u32 = 8;
+
+/// One step of the schema history; `up` runs in a transaction with the
+/// version stamped after it, so a failed step leaves the previous version
+struct Migration {
+    version: u32,
+    name: &'static str,
+    up: fn(&Connection) -> Result<()>,
+}
+
+// Steps in version order. Version 1 is the index.db schema of keepkey-usb;
+// versions 2 to 5 never shipped on this file. New tables, indexes and views
+// only need to go in FULL_SCHEMA, which is applied on every open; a change to
+// an existing table needs a step here.
+const MIGRATIONS: &[Migration] = &[
+    Migration { version: 6, name: "v6 device setup columns", up: migrate_to_v6 },
+    Migration { version: 7, name: "wallet-scoped keys and later columns", up: migrate_to_v7 },
+    Migration { version: 8, name: "standard wallet backfill", up: migrate_to_v8 },
+];
+
+/// Initialize the database schema, or upgrade it from the version it records
 pub fn apply_migrations(conn: &Connection) -> Result<()> {
     // Enable WAL mode and foreign keys
     conn.pragma_update(None, "journal_mode", "WAL")?;
     conn.pragma_update(None, "foreign_keys", "ON")?;
-    
-    log::info!("Creating database schema...");
-    
-    // Create all tables at once
+
+    let version = schema_version(conn)?;
+    if version > SCHEMA_VERSION {
+        log::warn!("Database schema version {} is newer than this build's {}", version, SCHEMA_VERSION);
+    } else if version == 0 {
+        log::info!("Creating database schema...");
+    } else if version < SCHEMA_VERSION {
+        log::info!("Upgrading database schema from version {} to {}", version, SCHEMA_VERSION);
+    }
+
+    // A fresh database gets FULL_SCHEMA as is; the steps only alter tables
+    // that already exist, so none of them has anything to do there
+    for migration in MIGRATIONS.iter().filter(|m| version > 0 && m.version > version) {
+        log::info!("Applying migration {} ({})", migration.version, migration.name);
+        // Rebuilt tables are copied as-is, and orphaned device ids must not
+        // abort the upgrade; foreign_keys cannot change inside a transaction
+        conn.pragma_update(None, "foreign_keys", "OFF")?;
+        let result = (|| -> Result<()> {
+            let tx = conn.unchecked_transaction()?;
+            (migration.up)(&tx)?;
+            set_schema_version(&tx, migration.version)?;
+            tx.commit()?;
+            Ok(())
+        })();
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        result?;
+    }
+
     conn.execute_batch(FULL_SCHEMA)?;
-    
-    log::info!("Database schema created successfully");
+    conn.execute_batch(POST_UPGRADE_SCHEMA)?;
+    if version < SCHEMA_VERSION {
+        set_schema_version(conn, SCHEMA_VERSION)?;
+    }
+    Ok(())
+}
+
+/// Version in meta.db_version; 0 for an empty database. Tables without a
+/// version are taken for an index.db from before it recorded one.
+///
+/// Databases created by the former all-at-once schema are stamped 6 whatever
+/// columns they gained since, so they always run step 7, which only adds
+/// what is missing.
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    let tables = table_names(conn)?;
+    if tables.is_empty() {
+        return Ok(0);
+    }
+    if !tables.iter().any(|table| table == "meta") {
+        return Ok(1);
+    }
+    let version: Option<String> = conn.query_row(
+        "SELECT val FROM meta WHERE key = 'db_version'",
+        [],
+        |row| row.get(0),
+    ).optional()?;
+    Ok(version.and_then(|v| v.parse().ok()).unwrap_or(1))
+}
+
+fn set_schema_version(conn: &Connection, version: u32) -> Result<()> {
+    // The earliest index.db files have no meta table yet
+    conn.execute("CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, val TEXT)", [])?;
+    conn.execute(
+        "INSERT INTO meta (key, val) VALUES ('db_version', ?1)
+         ON CONFLICT(key) DO UPDATE SET val = excluded.val",
+        [version.to_string()],
+    )?;
+    Ok(())
+}
+
+/// index.db (v1) devices gain the setup flow columns
+fn migrate_to_v6(conn: &Connection) -> Result<()> {
+    for (table, column, definition) in V6_COLUMNS {
+        ensure_column(conn, table, column, definition)?;
+    }
+    Ok(())
+}
+
+/// Columns added to the v6 schema, and the UNIQUE keys scoped by wallet
+fn migrate_to_v7(conn: &Connection) -> Result<()> {
+    for (table, column, definition) in COLUMN_UPGRADES {
+        ensure_column(conn, table, column, definition)?;
+    }
+    for table in WALLET_SCOPED_UNIQUE_TABLES {
+        rebuild_for_wallet_scope(conn, table)?;
+    }
+    Ok(())
+}
+
+/// Rows written before wallet scoping go to the standard wallet of their
+/// device, where exactly one is known. Devices never identified keep them
+/// until their standard wallet is first opened.
+fn migrate_to_v8(conn: &Connection) -> Result<()> {
+    if table_sql(conn, "device_wallets")?.is_none() {
+        return Ok(());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT device_id, MIN(wallet_fingerprint) FROM device_wallets
+         WHERE is_hidden = 0 GROUP BY device_id HAVING COUNT(*) = 1"
+    )?;
+    let standard_wallets = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (device_id, wallet_fingerprint) in standard_wallets {
+        crate::wallets::claim_unscoped_rows(conn, &device_id, &wallet_fingerprint)?;
+    }
+    Ok(())
+}
+
+/// Add a column to an existing table if it is missing; a table that does
+/// not exist is left for FULL_SCHEMA to create
+pub fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let columns = column_names(conn, table)?;
+    if !columns.is_empty() && !columns.iter().any(|name| name == column) {
+        log::info!("Adding column {}.{}", table, column);
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
+pub(crate) fn column_names(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(names)
+}
+
+fn table_sql(conn: &Connection, table: &str) -> Result<Option<String>> {
+    Ok(conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get(0),
+    ).optional()?)
+}
+
+fn needs_wallet_scope_rebuild(sql: &str) -> bool {
+    !sql.contains("UNIQUE(device_id, wallet_fingerprint")
+}
+
+/// Tables in the database, excluding SQLite's own
+pub(crate) fn table_names(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+    )?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(names)
+}
+
+/// Schema changes `apply_migrations` would make to this database.
+///
+/// Empty for an up-to-date database. A database with no tables at all lists
+/// every table; callers treat that as a fresh install rather than an upgrade.
+pub fn pending_migrations(conn: &Connection) -> Result<Vec<PendingMigration>> {
+    let existing = table_names(conn)?;
+    let mut pending = Vec::new();
+
+    let schema = Connection::open_in_memory()?;
+    schema.execute_batch(FULL_SCHEMA)?;
+    for table in table_names(&schema)? {
+        if !existing.contains(&table) {
+            pending.push(PendingMigration {
+                id: format!("create_table:{}", table),
+                kind: MigrationKind::CreateTable,
+                table,
+                column: None,
+            });
+        }
+    }
+
+    for (table, column, _) in V6_COLUMNS.iter().chain(COLUMN_UPGRADES) {
+        let table = table.to_string();
+        if existing.contains(&table) && !column_names(conn, &table)?.iter().any(|name| name == column) {
+            pending.push(PendingMigration {
+                id: format!("add_column:{}.{}", table, column),
+                kind: MigrationKind::AddColumn,
+                table,
+                column: Some(column.to_string()),
+            });
+        }
+    }
+
+    for table in WALLET_SCOPED_UNIQUE_TABLES {
+        if let Some(sql) = table_sql(conn, table)? {
+            if needs_wallet_scope_rebuild(&sql) {
+                pending.push(PendingMigration {
+                    id: format!("rebuild_table:{}", table),
+                    kind: MigrationKind::RebuildTable,
+                    table: table.to_string(),
+                    column: None,
+                });
+            }
+        }
+    }
+    Ok(pending)
+}
+
+// Columns the v6 schema added to index.db's devices table
+const V6_COLUMNS: &[(&str, &str, &str)] = &[
+    ("devices", "serial_number", "TEXT"),
+    ("devices", "setup_complete", "BOOLEAN DEFAULT FALSE"),
+    ("devices", "setup_step_completed", "INTEGER DEFAULT 0"),
+    ("devices", "eth_address", "TEXT"),
+    ("devices", "setup_started_at", "INTEGER"),
+    ("devices", "setup_completed_at", "INTEGER"),
+];
+
+// Columns added after the initial v6 schema: (table, column, definition).
+// New installs get them from FULL_SCHEMA; existing databases via ALTER TABLE.
+const COLUMN_UPGRADES: &[(&str, &str, &str)] = &[
+    ("networks", "base_reserve", "TEXT"),
+    ("networks", "reserve_per_item", "TEXT"),
+    ("networks", "recommended_gas_buffer", "TEXT"),
+    ("wallet_xpubs", "wallet_fingerprint", "TEXT NOT NULL DEFAULT ''"),
+    ("portfolio_balances", "wallet_fingerprint", "TEXT NOT NULL DEFAULT ''"),
+    ("portfolio_dashboard", "wallet_fingerprint", "TEXT NOT NULL DEFAULT ''"),
+    ("portfolio_history", "wallet_fingerprint", "TEXT NOT NULL DEFAULT ''"),
+    ("transaction_cache", "wallet_fingerprint", "TEXT NOT NULL DEFAULT ''"),
+    ("cached_pubkeys", "wallet_fingerprint", "TEXT NOT NULL DEFAULT ''"),
+    ("transaction_cache", "origin", "TEXT"),
+    ("fee_rate_cache", "histogram", "TEXT"),
+    ("devices", "archived_at", "INTEGER"),
+    ("devices", "migrated_to", "TEXT"),
+    ("accounts", "import_id", "INTEGER REFERENCES imported_wallets(id) ON DELETE CASCADE"),
+    ("accounts", "path", "TEXT"),
+    ("accounts", "caip", "TEXT"),
+    ("networks", "finality_depth", "INTEGER"),
+    ("transaction_cache", "finalized_at", "INTEGER"),
+];
+
+// Tables whose UNIQUE key gained wallet_fingerprint. SQLite cannot alter a
+// constraint, so older copies are rebuilt from FULL_SCHEMA.
+const WALLET_SCOPED_UNIQUE_TABLES: &[&str] = &[
+    "wallet_xpubs",
+    "portfolio_dashboard",
+    "transaction_cache",
+    "cached_pubkeys",
+];
+
+// Indexes on upgraded columns; run after COLUMN_UPGRADES so old tables have them
+const POST_UPGRADE_SCHEMA: &str = r#"
+CREATE INDEX IF NOT EXISTS idx_wallet_xpubs_wallet ON wallet_xpubs(device_id, wallet_fingerprint);
+CREATE INDEX IF NOT EXISTS idx_portfolio_balances_wallet ON portfolio_balances(device_id, wallet_fingerprint);
+CREATE INDEX IF NOT EXISTS idx_portfolio_history_wallet ON portfolio_history(device_id, wallet_fingerprint, timestamp);
+CREATE INDEX IF NOT EXISTS idx_transaction_cache_wallet ON transaction_cache(device_id, wallet_fingerprint, timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_accounts_import ON accounts(import_id);
+"#;
+
+/// Rebuild a table created before its UNIQUE key included wallet_fingerprint
+fn rebuild_for_wallet_scope(conn: &Connection, table: &str) -> Result<()> {
+    match table_sql(conn, table)? {
+        Some(sql) if needs_wallet_scope_rebuild(&sql) => {}
+        _ => return Ok(()),
+    }
+
+    log::info!("Rebuilding {} for wallet-scoped keys", table);
+    let old = format!("{}_pre_wallet_scope", table);
+    let columns = column_names(conn, table)?.join(", ");
+
+    conn.execute_batch(&format!("ALTER TABLE {} RENAME TO {};", table, old))?;
+    conn.execute_batch(FULL_SCHEMA)?;
+    // Rows are copied as-is; apply_migrations has foreign keys off meanwhile
+    conn.execute_batch(&format!(
+        "INSERT INTO {table} ({columns}) SELECT {columns} FROM {old}; DROP TABLE {old};",
+        table = table, columns = columns, old = old
+    ))?;
     Ok(())
 }
 
 // Complete database schema - all tables, indexes, views, and triggers
 const FULL_SCHEMA: &str = r#"
--- KeepKey Database Schema v6
-PRAGMA journal_mode=WAL;
-PRAGMA foreign_keys = ON;
+-- KeepKey Database Schema (version in SCHEMA_VERSION; pragmas set by apply_migrations)
 
 -- Core accounts table for wallet information
 CREATE TABLE IF NOT EXISTS accounts (
@@ -29,7 +309,20 @@ CREATE TABLE IF NOT EXISTS accounts (
     kind         TEXT NOT NULL,      -- 'keepkey' | 'digital'
     xpub         TEXT NOT NULL,
     label        TEXT,
-    added_ts     INTEGER NOT NULL    -- epoch seconds
+    added_ts     INTEGER NOT NULL,   -- epoch seconds
+    import_id    INTEGER REFERENCES imported_wallets(id) ON DELETE CASCADE, -- set for watch-only imports
+    path         TEXT,               -- account path, e.g. "m/84'/0'/0'"
+    caip         TEXT
+);
+
+-- Watch-only wallets imported from another hardware wallet's export file
+CREATE TABLE IF NOT EXISTS imported_wallets (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_fingerprint TEXT NOT NULL UNIQUE, -- synthetic, derived from the imported xpubs
+    source_format TEXT NOT NULL,     -- 'trezor_suite' | 'ledger_live' | 'descriptors'
+    source_name  TEXT,               -- file name, if imported from a file
+    label        TEXT,
+    imported_at  INTEGER NOT NULL    -- epoch seconds
 );
 
 -- Addresses table for derived addresses
@@ -74,7 +367,9 @@ CREATE TABLE IF NOT EXISTS devices (
     setup_step_completed INTEGER DEFAULT 0, -- Last completed setup step (0-4)
     eth_address TEXT,                -- Cached Ethereum address after setup
     setup_started_at INTEGER,        -- Timestamp when setup began
-    setup_completed_at INTEGER       -- Timestamp when setup finished
+    setup_completed_at INTEGER,      -- Timestamp when setup finished
+    archived_at  INTEGER,            -- set when the device was replaced by another
+    migrated_to  TEXT                -- device_id its data was migrated to
 );
 
 -- Device connections table for tracking connection history
@@ -95,7 +390,8 @@ CREATE TABLE IF NOT EXISTS wallet_xpubs (
     caip         TEXT NOT NULL,      -- "bip122:000000000019d6689c085ae165831e93/slip44:0"
     pubkey       TEXT NOT NULL,      -- xpub string
     created_at   INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
-    UNIQUE(device_id, path, caip),
+    wallet_fingerprint TEXT NOT NULL DEFAULT '', -- root fingerprint of the (possibly hidden) wallet
+    UNIQUE(device_id, wallet_fingerprint, path, caip),
     FOREIGN KEY (device_id) REFERENCES devices(device_id) ON DELETE CASCADE
 );
 
@@ -141,6 +437,7 @@ CREATE TABLE IF NOT EXISTS portfolio_balances (
     last_updated INTEGER NOT NULL,
     last_block_height INTEGER,
     is_verified BOOLEAN DEFAULT 0,
+    wallet_fingerprint TEXT NOT NULL DEFAULT '',
     
     UNIQUE(device_id, pubkey, caip, address, type, validator)
 );
@@ -166,7 +463,8 @@ CREATE TABLE IF NOT EXISTS portfolio_dashboard (
     included_devices TEXT,            -- JSON array of device_ids if combined
     
     last_updated INTEGER NOT NULL,
-    UNIQUE(device_id)
+    wallet_fingerprint TEXT NOT NULL DEFAULT '',
+    UNIQUE(device_id, wallet_fingerprint)
 );
 
 -- Portfolio history for tracking value over time
@@ -175,7 +473,8 @@ CREATE TABLE IF NOT EXISTS portfolio_history (
     device_id TEXT NOT NULL,
     timestamp INTEGER NOT NULL,
     total_value_usd TEXT NOT NULL,
-    snapshot_json TEXT              -- Full portfolio snapshot as JSON
+    snapshot_json TEXT,             -- Full portfolio snapshot as JSON
+    wallet_fingerprint TEXT NOT NULL DEFAULT ''
 );
 
 -- Asset registry table - stores all known assets
@@ -304,6 +603,14 @@ CREATE TABLE IF NOT EXISTS networks (
     fee_asset_caip TEXT,                   -- Asset used for fees (usually same as native)
     min_fee TEXT,                          -- Minimum fee in native units
     
+    -- Reserve requirements (native units, as strings)
+    base_reserve TEXT,                     -- Minimum balance an account must keep (e.g. XRP base reserve)
+    reserve_per_item TEXT,                 -- Extra reserve per owned object (trust lines, offers)
+    recommended_gas_buffer TEXT,           -- Balance to leave for future fees (Cosmos chains)
+    
+    -- Confirmation tracking
+    finality_depth INTEGER,                -- Blocks after which a confirmed tx is no longer rechecked for reorgs
+    
     -- Additional metadata
     tags TEXT,                             -- JSON array of tags
     is_testnet BOOLEAN DEFAULT 0,
@@ -331,9 +638,186 @@ CREATE TABLE IF NOT EXISTS transaction_cache (
     to_address TEXT,
     timestamp INTEGER NOT NULL,
     block_height INTEGER,
-    status TEXT,                     -- 'pending', 'confirmed', 'failed'
+    status TEXT,                     -- 'pending', 'confirmed', 'failed', 'reorged'
     metadata_json TEXT,              -- Additional transaction-specific data
-    UNIQUE(device_id, txid, caip)
+    wallet_fingerprint TEXT NOT NULL DEFAULT '',
+    origin TEXT,                     -- who requested the signature ('main_window', 'rest_api:<token>', ...)
+    finalized_at INTEGER,            -- set once the tx is deeper than the network's finality depth
+    UNIQUE(device_id, wallet_fingerprint, txid, caip)
+);
+
+-- Every signing request and who asked for it
+CREATE TABLE IF NOT EXISTS signing_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    origin TEXT NOT NULL,
+    device_id TEXT NOT NULL,
+    wallet_fingerprint TEXT NOT NULL DEFAULT '',
+    caip TEXT NOT NULL,
+    txid TEXT,
+    value_usd REAL,                  -- NULL when no price was known
+    status TEXT NOT NULL CHECK(status IN ('requested', 'signed', 'broadcast', 'rejected', 'failed'))
+);
+
+-- Soft daily spending limits per signing origin (USD)
+CREATE TABLE IF NOT EXISTS signing_origin_limits (
+    origin TEXT PRIMARY KEY,
+    daily_limit_usd REAL NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+-- Firmware fault/crash records read from devices
+CREATE TABLE IF NOT EXISTS device_fault_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id TEXT NOT NULL,
+    recorded_at INTEGER NOT NULL,    -- epoch seconds when read from the device
+    fault_type TEXT NOT NULL,        -- 'hard_fault', 'watchdog', ...
+    summary TEXT NOT NULL,           -- one-line description for lists
+    record_json TEXT NOT NULL,       -- parsed record (registers/task only, never raw flash)
+    UNIQUE(device_id, record_json)
+);
+
+-- Freeform user notes per device, kept across device migrations
+CREATE TABLE IF NOT EXISTS device_notes (
+    device_id TEXT PRIMARY KEY,
+    note_markdown TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+-- Outcomes of the hardware self-test, newest result is the device's health
+CREATE TABLE IF NOT EXISTS device_health_checks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id TEXT NOT NULL,
+    checked_at INTEGER NOT NULL,     -- epoch seconds when the test finished
+    outcome TEXT NOT NULL CHECK(outcome IN ('passed', 'failed', 'cancelled')),
+    display_ok BOOLEAN,              -- NULL when the check did not run
+    storage_ok BOOLEAN,
+    rng_ok BOOLEAN,
+    details_json TEXT NOT NULL DEFAULT '{}'
+);
+
+-- Firmware and bootloader flashes started from the vault
+CREATE TABLE IF NOT EXISTS firmware_updates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK(kind IN ('firmware', 'bootloader')),
+    from_version TEXT,               -- NULL when the installed version was unknown
+    to_version TEXT NOT NULL,
+    downgrade BOOLEAN NOT NULL DEFAULT FALSE, -- an older version flashed under override
+    succeeded BOOLEAN NOT NULL,
+    error TEXT,
+    updated_at INTEGER NOT NULL      -- epoch seconds when the flash finished
+);
+
+-- Address discovery window per UTXO account (a wallet_xpubs row)
+CREATE TABLE IF NOT EXISTS account_scan_state (
+    xpub_id INTEGER PRIMARY KEY REFERENCES wallet_xpubs(id) ON DELETE CASCADE,
+    window_size INTEGER NOT NULL,    -- address indexes 0..window_size are scanned
+    high_water INTEGER,              -- highest index seen with activity
+    ceiling_reached BOOLEAN NOT NULL DEFAULT 0, -- activity near the edge at the scan ceiling
+    updated_at INTEGER NOT NULL
+);
+
+-- Secrets encrypted on the device with CipherKeyValue; the plaintext is never stored
+CREATE TABLE IF NOT EXISTS secure_notes (
+    id INTEGER PRIMARY KEY,          -- random, also selects the note's key path
+    wallet_fingerprint TEXT NOT NULL, -- seed the note decrypts with, on any device
+    title TEXT NOT NULL,             -- part of the cipher key; shown on the device when reading
+    ciphertext BLOB NOT NULL,
+    plaintext_bytes INTEGER NOT NULL,
+    created_on_device TEXT,
+    created_at INTEGER NOT NULL
+);
+
+-- Current ERC-20 allowances granted by EVM accounts, replaced on each refresh
+CREATE TABLE IF NOT EXISTS token_approvals (
+    network_id TEXT NOT NULL,        -- e.g. "eip155:1"
+    owner TEXT NOT NULL,             -- lowercase 0x account address
+    token_contract TEXT NOT NULL,    -- lowercase 0x token address
+    spender TEXT NOT NULL,           -- lowercase 0x spender address
+    allowance TEXT NOT NULL,         -- raw uint256 as 0x-prefixed hex
+    is_unlimited BOOLEAN NOT NULL DEFAULT 0,
+    token_symbol TEXT,
+    token_decimals INTEGER,
+    spender_label TEXT,              -- known protocol name, NULL if unrecognized
+    approved_block INTEGER,          -- block of the latest Approval event
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY(network_id, owner, token_contract, spender)
+);
+
+-- When each account's allowances were last scanned; an empty scan still counts
+CREATE TABLE IF NOT EXISTS token_approval_scans (
+    network_id TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    scanned_at INTEGER NOT NULL,
+    PRIMARY KEY(network_id, owner)
+);
+
+-- Cost-basis lots replayed from transaction_cache (tax_lots.rs); derived data,
+-- rebuilt per asset whenever a transaction is recorded or the method changes
+CREATE TABLE IF NOT EXISTS tax_lots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id TEXT NOT NULL,
+    wallet_fingerprint TEXT NOT NULL DEFAULT '',
+    caip TEXT NOT NULL,
+    txid TEXT NOT NULL,              -- acquiring transaction, or the self-transfer that moved the lot here
+    acquired_at INTEGER NOT NULL,    -- original acquisition time, kept across self-transfers
+    amount REAL NOT NULL,
+    remaining REAL NOT NULL,
+    cost_per_unit_usd REAL           -- NULL when the acquisition was not priced
+);
+CREATE INDEX IF NOT EXISTS idx_tax_lots_caip ON tax_lots(caip);
+
+-- Lot consumption by outgoing transactions, one row per (transaction, lot) slice
+CREATE TABLE IF NOT EXISTS disposals (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    lot_id INTEGER,                  -- NULL for amounts not covered by any known lot
+    device_id TEXT NOT NULL,
+    wallet_fingerprint TEXT NOT NULL DEFAULT '',
+    caip TEXT NOT NULL,
+    txid TEXT NOT NULL,
+    disposed_at INTEGER NOT NULL,
+    acquired_at INTEGER,
+    amount REAL NOT NULL,
+    proceeds_usd REAL,
+    cost_basis_usd REAL,
+    gain_usd REAL,                   -- NULL unless both proceeds and cost basis are known
+    method TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_disposals_time ON disposals(disposed_at);
+
+-- REST API clients paired with a one-time code; only a hash of each token is kept
+CREATE TABLE IF NOT EXISTS api_clients (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE, -- hex SHA-256 of the bearer token
+    scope TEXT NOT NULL DEFAULT 'read' CHECK(scope IN ('read', 'sign')),
+    created_at INTEGER NOT NULL,
+    last_used_at INTEGER,
+    expires_at INTEGER NOT NULL,
+    revoked_at INTEGER
+);
+
+-- Critical events persisted before emission, until the frontend acknowledges them
+CREATE TABLE IF NOT EXISTS event_outbox (
+    sequence INTEGER PRIMARY KEY AUTOINCREMENT,
+    event_name TEXT NOT NULL,
+    payload_json TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    delivery_attempts INTEGER NOT NULL DEFAULT 0,
+    acked_at INTEGER                 -- NULL until ack_event
+);
+
+-- Wallets seen per device: the standard wallet plus any passphrase (hidden) wallets.
+-- Only the root fingerprint and a user nickname are stored, never the passphrase.
+CREATE TABLE IF NOT EXISTS device_wallets (
+    device_id TEXT NOT NULL,
+    wallet_fingerprint TEXT NOT NULL,
+    is_hidden BOOLEAN NOT NULL DEFAULT 0,
+    nickname TEXT,
+    first_seen INTEGER NOT NULL,
+    last_used INTEGER NOT NULL,
+    PRIMARY KEY(device_id, wallet_fingerprint)
 );
 
 -- Cached public keys and addresses
@@ -349,7 +833,8 @@ CREATE TABLE IF NOT EXISTS cached_pubkeys (
     public_key BLOB,
     cached_at INTEGER NOT NULL,
     last_used INTEGER NOT NULL,
-    UNIQUE(device_id, derivation_path, coin_name, script_type)
+    wallet_fingerprint TEXT NOT NULL DEFAULT '',
+    UNIQUE(device_id, wallet_fingerprint, derivation_path, coin_name, script_type)
 );
 
 -- Device cache metadata
@@ -386,9 +871,19 @@ CREATE TABLE IF NOT EXISTS fee_rate_cache (
     fastest      INTEGER NOT NULL,    -- sat/vbyte
     fast         INTEGER NOT NULL,    -- sat/vbyte
     average      INTEGER NOT NULL,    -- sat/vbyte
+    histogram    TEXT,                -- JSON [[sat/vbyte, vbytes], ...] from the mempool endpoint
     last_updated INTEGER NOT NULL     -- epoch seconds
 );
 
+-- User-visible record of background work (backups, maintenance, ...)
+CREATE TABLE IF NOT EXISTS activity_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,      -- epoch seconds
+    category TEXT NOT NULL,          -- 'backup', 'maintenance', ...
+    message TEXT NOT NULL,
+    details_json TEXT
+);
+
 -- Meta table for key-value storage (including onboarding state)
 CREATE TABLE IF NOT EXISTS meta (
     key TEXT PRIMARY KEY,
@@ -401,6 +896,8 @@ CREATE TABLE IF NOT EXISTS meta (
 CREATE UNIQUE INDEX IF NOT EXISTS idx_accounts_fp_xpub ON accounts(wallet_fp, xpub);
 CREATE INDEX IF NOT EXISTS idx_addresses_account ON addresses(account_id);
 CREATE INDEX IF NOT EXISTS idx_txs_account_block ON txs(account_id, block_height);
+CREATE INDEX IF NOT EXISTS idx_activity_log_time ON activity_log(timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_signing_audit_origin ON signing_audit(origin, timestamp);
 
 -- Device indexes for performance
 CREATE INDEX IF NOT EXISTS idx_devices_setup_incomplete 
@@ -439,6 +936,13 @@ CREATE INDEX IF NOT EXISTS idx_paths_symbol ON derivation_paths(symbol);
 CREATE INDEX IF NOT EXISTS idx_transaction_cache_device ON transaction_cache(device_id, timestamp DESC);
 CREATE INDEX IF NOT EXISTS idx_transaction_cache_status ON transaction_cache(status, timestamp DESC);
 
+-- Fault log indexes
+CREATE INDEX IF NOT EXISTS idx_device_fault_logs_device ON device_fault_logs(device_id, recorded_at DESC);
+CREATE INDEX IF NOT EXISTS idx_device_health_checks_device ON device_health_checks(device_id, checked_at DESC);
+CREATE INDEX IF NOT EXISTS idx_event_outbox_acked ON event_outbox(acked_at);
+CREATE INDEX IF NOT EXISTS idx_firmware_updates_device ON firmware_updates(device_id, updated_at DESC);
+CREATE INDEX IF NOT EXISTS idx_secure_notes_wallet ON secure_notes(wallet_fingerprint, created_at);
+
 -- Fee cache indexes
 CREATE INDEX IF NOT EXISTS idx_fee_cache_updated ON fee_rate_cache(last_updated);
 
@@ -495,6 +999,14 @@ FROM derivation_paths dp
 LEFT JOIN path_asset_mapping pam ON dp.path_id = pam.path_id AND pam.is_primary = 1
 LEFT JOIN assets a ON pam.caip = a.caip;
 
+-- Networks switched off by the user (pref_network_enabled:<network_id> = 'false'),
+-- or inactive in the registry when there is no preference
+CREATE VIEW IF NOT EXISTS v_disabled_networks AS
+SELECT n.network_id
+FROM networks n
+LEFT JOIN meta m ON m.key = 'pref_network_enabled:' || n.network_id
+WHERE COALESCE(m.val, CASE WHEN COALESCE(n.is_active, 1) = 0 THEN 'false' ELSE 'true' END) = 'false';
+
 -- ========== TRIGGERS ==========
 
 -- Trigger to update last_used timestamp on access
@@ -534,7 +1046,6 @@ END;
 
 -- Default onboarding data
 INSERT OR IGNORE INTO meta (key, val) VALUES 
-    ('db_version', '6'),
     ('onboarding_completed', 'false'),
     ('first_install_timestamp', CAST(strftime('%s', 'now') AS TEXT));
 
@@ -545,4 +1056,138 @@ INSERT OR IGNORE INTO meta (key, val) VALUES
     ('pref_currency', 'USD'),
     ('pref_units', 'metric'),
     ('pref_analytics_enabled', 'false');
-"#; 
\ No newline at end of file
+"#; 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MigrationStatus;
+    use crate::Database;
+
+    const INDEX_DB_V1: &str = include_str!("../tests/fixtures/index_db_v1.sql");
+    const V1_DEVICE: &str = "343737340F4736331F003B00";
+
+    #[tokio::test]
+    async fn test_index_db_v1_migrates_to_the_current_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keepkey.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(INDEX_DB_V1).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+        drop(conn);
+
+        let db = Database::open_at_path(path).await.unwrap();
+        assert_eq!(db.migration_report().status, MigrationStatus::Applied);
+        assert!(db.get_pending_migrations().await.unwrap().is_empty());
+
+        // The setup columns index.db lacked are usable
+        assert!(db.device_needs_setup(V1_DEVICE).await.unwrap());
+        db.mark_device_setup_complete(V1_DEVICE, Some("0x1234")).await.unwrap();
+        assert!(!db.device_needs_setup(V1_DEVICE).await.unwrap());
+
+        let (version, xpubs, connections, onboarding) = db.with_connection(|conn| {
+            let xpubs: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM wallet_xpubs WHERE wallet_fingerprint = ''", [], |r| r.get(0),
+            )?;
+            let connections: i64 = conn.query_row("SELECT COUNT(*) FROM device_connections", [], |r| r.get(0))?;
+            let onboarding: String = conn.query_row(
+                "SELECT val FROM meta WHERE key = 'onboarding_completed'", [], |r| r.get(0),
+            )?;
+            Ok((schema_version(conn)?, xpubs, connections, onboarding))
+        }).await.unwrap();
+        assert_eq!((version, xpubs, connections, onboarding.as_str()), (SCHEMA_VERSION, 2, 1, "false"));
+    }
+
+    #[test]
+    fn test_legacy_v6_stamp_gains_later_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        apply_migrations(&conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+
+        // As the all-at-once schema left it before finality_depth existed
+        conn.execute_batch(
+            "ALTER TABLE networks DROP COLUMN finality_depth; UPDATE meta SET val = '6' WHERE key = 'db_version';"
+        ).unwrap();
+        assert_eq!(pending_migrations(&conn).unwrap().len(), 1);
+
+        apply_migrations(&conn).unwrap();
+        assert!(pending_migrations(&conn).unwrap().is_empty());
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_v8_backfills_known_standard_wallets() {
+        let conn = Connection::open_in_memory().unwrap();
+        apply_migrations(&conn).unwrap();
+        // A v7 database: dev1's standard wallet is known, dev2 was never identified
+        conn.execute_batch(
+            "INSERT INTO devices (device_id, first_seen, last_seen) VALUES ('dev1', 0, 0), ('dev2', 0, 0);
+             INSERT INTO device_wallets (device_id, wallet_fingerprint, is_hidden, first_seen, last_used)
+                 VALUES ('dev1', 'aaaa0001', 0, 0, 0), ('dev1', 'bbbb0002', 1, 0, 0);
+             INSERT INTO wallet_xpubs (device_id, wallet_fingerprint, path, label, caip, pubkey) VALUES
+                 ('dev1', '', 'm/84''/0''/0''', 'BTC', 'btc', 'xpubOld'),
+                 ('dev1', '', 'm/44''/60''/0''', 'ETH', 'eth', 'xpubEth'),
+                 ('dev1', 'aaaa0001', 'm/84''/0''/0''', 'BTC', 'btc', 'xpubNew'),
+                 ('dev2', '', 'm/84''/0''/0''', 'BTC', 'btc', 'xpubDev2');
+             UPDATE meta SET val = '7' WHERE key = 'db_version';"
+        ).unwrap();
+
+        apply_migrations(&conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        let rows: Vec<(String, String, String)> = conn
+            .prepare("SELECT device_id, wallet_fingerprint, pubkey FROM wallet_xpubs ORDER BY device_id, pubkey").unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?))).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        let rows: Vec<(&str, &str, &str)> = rows.iter().map(|(d, f, p)| (d.as_str(), f.as_str(), p.as_str())).collect();
+        // The scoped twin of the old BTC row wins; dev2 waits for its device
+        assert_eq!(rows, [
+            ("dev1", "aaaa0001", "xpubEth"),
+            ("dev1", "aaaa0001", "xpubNew"),
+            ("dev2", "", "xpubDev2"),
+        ]);
+    }
+
+    #[test]
+    fn test_upgrade_adds_missing_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        // A networks table from before the reserve columns existed
+        conn.execute_batch(
+            "CREATE TABLE networks (id INTEGER PRIMARY KEY, network_id TEXT NOT NULL UNIQUE, name TEXT NOT NULL,
+                native_asset_caip TEXT NOT NULL, native_symbol TEXT NOT NULL);"
+        ).unwrap();
+
+        apply_migrations(&conn).unwrap();
+        // Running twice must be a no-op
+        apply_migrations(&conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO networks (network_id, name, native_asset_caip, native_symbol, base_reserve) VALUES ('x', 'X', 'x/slip44:0', 'X', '10')",
+            [],
+        ).unwrap();
+    }
+
+    #[test]
+    fn test_upgrade_rebuilds_wallet_scoped_tables() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE wallet_xpubs (id INTEGER PRIMARY KEY AUTOINCREMENT, device_id TEXT NOT NULL, path TEXT NOT NULL,
+                label TEXT NOT NULL, caip TEXT NOT NULL, pubkey TEXT NOT NULL,
+                created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')), UNIQUE(device_id, path, caip));
+             INSERT INTO wallet_xpubs (device_id, path, label, caip, pubkey) VALUES ('dev1', 'm/44''/0''/0''', 'BTC', 'btc', 'xpubA');"
+        ).unwrap();
+        apply_migrations(&conn).unwrap();
+        apply_migrations(&conn).unwrap();
+
+        // Existing row kept with the unscoped fingerprint, same path now allowed for another wallet
+        let fp: String = conn.query_row("SELECT wallet_fingerprint FROM wallet_xpubs WHERE pubkey = 'xpubA'", [], |r| r.get(0)).unwrap();
+        assert_eq!(fp, "");
+        conn.execute("INSERT INTO devices (device_id, first_seen, last_seen) VALUES ('dev1', 0, 0)", []).unwrap();
+        conn.execute(
+            "INSERT INTO wallet_xpubs (device_id, path, label, caip, pubkey, wallet_fingerprint) VALUES ('dev1', 'm/44''/0''/0''', 'BTC', 'btc', 'xpubB', 'deadbeef')",
+            [],
+        ).unwrap();
+        let leftovers: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name LIKE '%_pre_wallet_scope'", [], |r| r.get(0),
+        ).unwrap();
+        assert_eq!(leftovers, 0);
+    }
+}
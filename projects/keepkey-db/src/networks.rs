@@ -0,0 +1,574 @@
+//! Which networks the user has switched on
+//!
+//! The choice is a `network_enabled:<network_id>` preference so it survives the
+//! networks table being reseeded from the asset catalog. Networks without a
+//! preference follow the registry's `is_active` flag. Disabling a network hides
+//! its data from the default queries (portfolio assets, derivation paths, asset
+//! search, transaction tracking) without deleting anything; re-enabling brings
+//! it all back.
+//!
+//! On first run the enabled set is derived from the networks the user's devices
+//! have frontloaded xpubs for, once there are any.
+//!
+//! The registry itself is seeded from a bundled JSON of mainnets, written again
+//! only when the bundle version increases. A network's native asset must be in
+//! the asset registry first, so the assets are seeded before the networks.
+
+use std::collections::BTreeSet;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Deserialize;
+use crate::derivation_paths::{derivation_path_from_row, DERIVATION_PATH_COLUMNS};
+use crate::errors::{DatabaseError, Result};
+use crate::types::{AssetSearchResult, DerivationPathInfo, Network, NetworkInput, NetworkStatus};
+use crate::Database;
+
+/// Preference key prefix (stored in meta as `pref_network_enabled:<network_id>`)
+pub const NETWORK_ENABLED_PREF_PREFIX: &str = "network_enabled:";
+
+/// Set once the first-run network defaults have been written
+const DEFAULTS_INITIALIZED_KEY: &str = "network_defaults_initialized";
+
+/// Row filter for a `network_id` column that leaves out disabled networks
+pub(crate) const ENABLED_NETWORK_FILTER: &str = "network_id NOT IN (SELECT network_id FROM v_disabled_networks)";
+
+/// Mainnets shipped with the app
+const DEFAULT_NETWORKS: &str = include_str!("../data/default_networks.json");
+
+/// meta key holding the version of the last network bundle seeded
+const NETWORK_SEED_VERSION_KEY: &str = "network_seed_version";
+
+#[derive(Deserialize)]
+struct NetworkBundle {
+    version: u32,
+    networks: Vec<NetworkInput>,
+}
+
+/// Columns read by `network_from_row`, in order
+const NETWORK_COLUMNS: &str =
+    "id, network_id, name, short_name, chain_id, network_type, native_asset_caip, native_symbol, rpc_urls,
+     ws_urls, explorer_url, explorer_api_url, COALESCE(explorer_api_key_required, 0), COALESCE(supports_eip1559, 0),
+     COALESCE(supports_memo, 0), COALESCE(supports_tokens, 0), fee_asset_caip, min_fee, base_reserve,
+     reserve_per_item, recommended_gas_buffer, tags, COALESCE(is_testnet, 0), COALESCE(is_active, 1),
+     created_at, last_updated";
+
+/// Network part of a CAIP-19 asset id
+fn network_of(caip: &str) -> &str {
+    caip.split('/').next().unwrap_or(caip)
+}
+
+fn network_from_row(row: &rusqlite::Row) -> rusqlite::Result<Network> {
+    Ok(Network {
+        id: row.get(0)?,
+        network_id: row.get(1)?,
+        name: row.get(2)?,
+        short_name: row.get(3)?,
+        chain_id: row.get(4)?,
+        network_type: row.get(5)?,
+        native_asset_caip: row.get(6)?,
+        native_symbol: row.get(7)?,
+        rpc_urls: row.get(8)?,
+        ws_urls: row.get(9)?,
+        explorer_url: row.get(10)?,
+        explorer_api_url: row.get(11)?,
+        explorer_api_key_required: row.get(12)?,
+        supports_eip1559: row.get(13)?,
+        supports_memo: row.get(14)?,
+        supports_tokens: row.get(15)?,
+        fee_asset_caip: row.get(16)?,
+        min_fee: row.get(17)?,
+        base_reserve: row.get(18)?,
+        reserve_per_item: row.get(19)?,
+        recommended_gas_buffer: row.get(20)?,
+        tags: row.get(21)?,
+        is_testnet: row.get(22)?,
+        is_active: row.get(23)?,
+        created_at: row.get(24)?,
+        last_updated: row.get(25)?,
+    })
+}
+
+/// Insert or refresh a registry network. Reserves, finality and the active
+/// flag are not part of the input and are kept on refresh.
+fn upsert_network_in(conn: &Connection, network: &NetworkInput, now: i64) -> Result<()> {
+    if network.network_id.trim().is_empty() {
+        return Err(DatabaseError::Validation("network_id cannot be empty".to_string()));
+    }
+    let native_known = conn.query_row(
+        "SELECT 1 FROM assets WHERE caip = ?1",
+        [&network.native_asset_caip],
+        |_| Ok(()),
+    ).optional()?;
+    if native_known.is_none() {
+        return Err(DatabaseError::Validation(format!(
+            "Native asset {} of network {} is not in the asset registry; add it with upsert_assets first",
+            network.native_asset_caip, network.network_id
+        )));
+    }
+    let json_urls = |urls: &[String]| (!urls.is_empty()).then(|| serde_json::to_string(urls)).transpose();
+    conn.execute(
+        "INSERT INTO networks (network_id, name, short_name, chain_id, network_type, native_asset_caip, native_symbol,
+                               rpc_urls, ws_urls, explorer_url, explorer_api_url, explorer_api_key_required,
+                               supports_eip1559, supports_memo, supports_tokens, fee_asset_caip, min_fee, is_testnet,
+                               created_at, last_updated)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?19)
+         ON CONFLICT(network_id) DO UPDATE SET
+             name = excluded.name, short_name = excluded.short_name, chain_id = excluded.chain_id,
+             network_type = excluded.network_type, native_asset_caip = excluded.native_asset_caip,
+             native_symbol = excluded.native_symbol, rpc_urls = excluded.rpc_urls, ws_urls = excluded.ws_urls,
+             explorer_url = excluded.explorer_url, explorer_api_url = excluded.explorer_api_url,
+             explorer_api_key_required = excluded.explorer_api_key_required,
+             supports_eip1559 = excluded.supports_eip1559, supports_memo = excluded.supports_memo,
+             supports_tokens = excluded.supports_tokens, fee_asset_caip = excluded.fee_asset_caip,
+             min_fee = excluded.min_fee, is_testnet = excluded.is_testnet, last_updated = excluded.last_updated",
+        rusqlite::params![
+            network.network_id,
+            network.name,
+            network.short_name,
+            network.chain_id,
+            network.network_type,
+            network.native_asset_caip,
+            network.native_symbol,
+            json_urls(&network.rpc_urls)?,
+            json_urls(&network.ws_urls)?,
+            network.explorer_url,
+            network.explorer_api_url,
+            network.explorer_api_key_required,
+            network.supports_eip1559,
+            network.supports_memo,
+            network.supports_tokens,
+            network.fee_asset_caip.as_deref().unwrap_or(&network.native_asset_caip),
+            network.min_fee,
+            network.is_testnet,
+            now,
+        ],
+    )?;
+    Ok(())
+}
+
+impl Database {
+    /// Register a network or refresh its registry fields. Fails with a
+    /// validation error when its native asset is not in the asset registry.
+    pub async fn upsert_network(&self, network: NetworkInput) -> Result<()> {
+        let now = Self::current_timestamp();
+        self.with_connection(move |conn| upsert_network_in(conn, &network, now)).await
+    }
+
+    /// A registry network by CAIP-2 id
+    pub async fn get_network(&self, network_id: &str) -> Result<Option<Network>> {
+        self.with_connection(|conn| {
+            Ok(conn.query_row(
+                &format!("SELECT {} FROM networks WHERE network_id = ?1", NETWORK_COLUMNS),
+                [network_id],
+                network_from_row,
+            ).optional()?)
+        }).await
+    }
+
+    /// Registry networks by name, testnets only if `include_testnets`
+    pub async fn list_networks(&self, include_testnets: bool) -> Result<Vec<Network>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM networks WHERE ?1 OR COALESCE(is_testnet, 0) = 0 ORDER BY is_testnet, name",
+                NETWORK_COLUMNS
+            ))?;
+            let networks = stmt
+                .query_map([include_testnets], network_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(networks)
+        }).await
+    }
+
+    /// Set a network's registry `is_active` flag, which decides whether it is
+    /// enabled while the user has made no choice of their own (see
+    /// `set_network_enabled`). Returns false if the network is not registered.
+    pub async fn set_network_active(&self, network_id: &str, active: bool) -> Result<bool> {
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            let updated = conn.execute(
+                "UPDATE networks SET is_active = ?1, last_updated = ?2 WHERE network_id = ?3",
+                rusqlite::params![active, now, network_id],
+            )?;
+            Ok(updated > 0)
+        }).await
+    }
+
+    /// Register the bundled mainnets when the bundle is newer than the last
+    /// one seeded; returns the number written, 0 when already up to date.
+    /// Run after `seed_default_assets`, which provides their native assets.
+    pub async fn seed_default_networks(&self) -> Result<usize> {
+        let bundle: NetworkBundle = serde_json::from_str(DEFAULT_NETWORKS)?;
+        let now = Self::current_timestamp();
+        self.transaction(move |tx| {
+            let seeded: Option<u32> = tx.query_row(
+                "SELECT val FROM meta WHERE key = ?1",
+                [NETWORK_SEED_VERSION_KEY],
+                |row| row.get::<_, Option<String>>(0),
+            ).optional()?.flatten().and_then(|v| v.parse().ok());
+            if seeded.is_some_and(|version| version >= bundle.version) {
+                return Ok(0);
+            }
+
+            for network in &bundle.networks {
+                upsert_network_in(tx, network, now)?;
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, val) VALUES (?1, ?2)",
+                rusqlite::params![NETWORK_SEED_VERSION_KEY, bundle.version.to_string()],
+            )?;
+            log::info!("Seeded {} default networks (bundle v{})", bundle.networks.len(), bundle.version);
+            Ok(bundle.networks.len())
+        }).await
+    }
+
+    /// Every registered network with its enabled flag
+    pub async fn list_network_statuses(&self) -> Result<Vec<NetworkStatus>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT n.network_id, n.name, n.short_name, n.network_type, n.native_asset_caip, n.native_symbol,
+                        COALESCE(n.is_testnet, 0),
+                        n.network_id NOT IN (SELECT network_id FROM v_disabled_networks)
+                 FROM networks n
+                 ORDER BY n.is_testnet, n.name"
+            )?;
+            let networks = stmt
+                .query_map([], |row| Ok(NetworkStatus {
+                    network_id: row.get(0)?,
+                    name: row.get(1)?,
+                    short_name: row.get(2)?,
+                    network_type: row.get(3)?,
+                    native_asset_caip: row.get(4)?,
+                    native_symbol: row.get(5)?,
+                    is_testnet: row.get(6)?,
+                    enabled: row.get(7)?,
+                }))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(networks)
+        }).await
+    }
+
+    /// Switch a network on or off. Returns false if the network is not registered.
+    pub async fn set_network_enabled(&self, network_id: &str, enabled: bool) -> Result<bool> {
+        let key = format!("pref_{}{}", NETWORK_ENABLED_PREF_PREFIX, network_id);
+        self.with_connection(|conn| {
+            let known = conn.query_row(
+                "SELECT 1 FROM networks WHERE network_id = ?1",
+                [network_id],
+                |_| Ok(()),
+            ).optional()?;
+            if known.is_none() {
+                return Ok(false);
+            }
+            conn.execute(
+                "INSERT OR REPLACE INTO meta (key, val) VALUES (?1, ?2)",
+                rusqlite::params![key, enabled.to_string()],
+            )?;
+            Ok(true)
+        }).await
+    }
+
+    /// Networks switched off by the user, or inactive in the registry
+    pub async fn get_disabled_networks(&self) -> Result<Vec<String>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare("SELECT network_id FROM v_disabled_networks ORDER BY network_id")?;
+            let networks = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(networks)
+        }).await
+    }
+
+    /// Write the first-run enabled set: networks some device has xpubs for are
+    /// enabled, every other network without a preference is disabled. Does
+    /// nothing once done, or while no xpubs have been frontloaded yet. Returns
+    /// the enabled networks when the defaults were written.
+    pub async fn initialize_network_defaults(&self) -> Result<Option<Vec<String>>> {
+        self.transaction(|conn| {
+            let done = conn.query_row(
+                "SELECT 1 FROM meta WHERE key = ?1",
+                [DEFAULTS_INITIALIZED_KEY],
+                |_| Ok(()),
+            ).optional()?;
+            if done.is_some() {
+                return Ok(None);
+            }
+
+            let mut stmt = conn.prepare("SELECT DISTINCT caip FROM wallet_xpubs")?;
+            let with_xpubs: BTreeSet<String> = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+                .iter()
+                .map(|caip| network_of(caip).to_string())
+                .collect();
+            if with_xpubs.is_empty() {
+                return Ok(None);
+            }
+
+            let mut stmt = conn.prepare(
+                "SELECT network_id FROM networks
+                 WHERE NOT EXISTS (SELECT 1 FROM meta WHERE key = 'pref_' || ?1 || networks.network_id)"
+            )?;
+            let undecided = stmt
+                .query_map([NETWORK_ENABLED_PREF_PREFIX], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for network_id in &undecided {
+                conn.execute(
+                    "INSERT INTO meta (key, val) VALUES (?1, ?2)",
+                    rusqlite::params![
+                        format!("pref_{}{}", NETWORK_ENABLED_PREF_PREFIX, network_id),
+                        with_xpubs.contains(network_id).to_string()
+                    ],
+                )?;
+            }
+            conn.execute(
+                "INSERT INTO meta (key, val) VALUES (?1, ?2)",
+                rusqlite::params![DEFAULTS_INITIALIZED_KEY, Database::current_timestamp().to_string()],
+            )?;
+
+            let mut stmt = conn.prepare(&format!(
+                "SELECT network_id FROM networks WHERE {} ORDER BY network_id",
+                ENABLED_NETWORK_FILTER
+            ))?;
+            let enabled = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(Some(enabled))
+        }).await
+    }
+
+    /// Derivation paths from the catalog. Unless `include_disabled`, paths whose
+    /// networks are all disabled are left out.
+    pub async fn get_derivation_paths(&self, include_disabled: bool) -> Result<Vec<DerivationPathInfo>> {
+        let disabled: BTreeSet<String> = if include_disabled {
+            BTreeSet::new()
+        } else {
+            self.get_disabled_networks().await?.into_iter().collect()
+        };
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM derivation_paths ORDER BY path_id",
+                DERIVATION_PATH_COLUMNS
+            ))?;
+            let paths = stmt
+                .query_map([], derivation_path_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(paths
+                .into_iter()
+                .filter(|p| p.networks.is_empty() || p.networks.iter().any(|n| !disabled.contains(n)))
+                .collect())
+        }).await
+    }
+
+    /// Assets whose symbol or name contains `query` (case-insensitive), on
+    /// `network_id` if given and on enabled networks unless `include_disabled`.
+    /// An exact symbol match ranks first, then symbols starting with `query`.
+    pub async fn search_assets(
+        &self,
+        query: &str,
+        network_id: Option<&str>,
+        include_disabled: bool,
+        limit: u32,
+    ) -> Result<Vec<AssetSearchResult>> {
+        let pattern = query.trim().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let network_filter = if include_disabled { "1" } else { ENABLED_NETWORK_FILTER };
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT caip, network_id, symbol, name, icon, decimals, COALESCE(is_native, 0)
+                 FROM assets
+                 WHERE (symbol LIKE '%' || ?1 || '%' ESCAPE '\\' OR name LIKE '%' || ?1 || '%' ESCAPE '\\')
+                   AND (?4 IS NULL OR network_id = ?4) AND {}
+                 ORDER BY symbol = ?2 COLLATE NOCASE DESC, symbol LIKE ?1 || '%' ESCAPE '\\' DESC,
+                          is_native DESC, symbol, caip
+                 LIMIT ?3",
+                network_filter
+            ))?;
+            let assets = stmt
+                .query_map(rusqlite::params![pattern, query.trim(), limit, network_id], |row| Ok(AssetSearchResult {
+                    caip: row.get(0)?,
+                    network_id: row.get(1)?,
+                    symbol: row.get(2)?,
+                    name: row.get(3)?,
+                    icon: row.get(4)?,
+                    decimals: row.get(5)?,
+                    is_native: row.get(6)?,
+                }))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(assets)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{TransactionCache, WalletXpubInput};
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93";
+    const ETH: &str = "eip155:1";
+    const AVAX: &str = "eip155:43114";
+
+    async fn seed(db: &Database) {
+        db.with_connection(|conn| {
+            for (network_id, symbol, name) in [(BTC, "BTC", "Bitcoin"), (ETH, "ETH", "Ethereum"), (AVAX, "AVAX", "Avalanche")] {
+                let caip = format!("{}/slip44:0", network_id);
+                conn.execute(
+                    "INSERT INTO assets (caip, network_id, symbol, name, is_native) VALUES (?1, ?2, ?3, ?4, 1)",
+                    rusqlite::params![caip, network_id, symbol, name],
+                )?;
+                conn.execute(
+                    "INSERT INTO networks (network_id, name, native_asset_caip, native_symbol) VALUES (?1, ?2, ?3, ?4)",
+                    rusqlite::params![network_id, name, caip, symbol],
+                )?;
+                conn.execute(
+                    "INSERT INTO derivation_paths (path_id, blockchain, symbol, networks, address_n_list, address_n_list_master)
+                     VALUES (?1, ?2, ?3, ?4, '[2147483692]', '[2147483692,0,0]')",
+                    rusqlite::params![format!("{}_44", symbol.to_lowercase()), name.to_lowercase(), symbol, format!("[\"{}\"]", network_id)],
+                )?;
+                conn.execute(
+                    "INSERT INTO portfolio_balances (device_id, wallet_fingerprint, pubkey, caip, network_id, ticker,
+                                                     balance, balance_usd, price_usd, type, last_updated)
+                     VALUES ('dev1', '', 'xpub', ?1, ?2, ?3, '1', '1', '1', 'balance', 1)",
+                    rusqlite::params![caip, network_id, symbol],
+                )?;
+            }
+            Ok(())
+        }).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disable_and_reenable_cascade() {
+        let db = Database::new_in_memory().await.unwrap();
+        seed(&db).await;
+        let avax_asset = format!("{}/slip44:0", AVAX);
+        db.upsert_transaction(&TransactionCache {
+            id: 0,
+            device_id: "dev1".to_string(),
+            txid: "t1".to_string(),
+            caip: avax_asset.clone(),
+            transaction_type: "send".to_string(),
+            amount: "1".to_string(),
+            amount_usd: None,
+            fee: None,
+            fee_usd: None,
+            from_address: None,
+            to_address: None,
+            timestamp: 1,
+            block_height: None,
+            status: Some("pending".to_string()),
+            metadata_json: None,
+            wallet_fingerprint: String::new(),
+            origin: None,
+        }).await.unwrap();
+        assert!(db.list_network_statuses().await.unwrap().iter().all(|n| n.enabled));
+
+        assert!(db.set_network_enabled(AVAX, false).await.unwrap());
+        assert!(!db.set_network_enabled("eip155:999999", false).await.unwrap());
+        assert_eq!(db.get_disabled_networks().await.unwrap(), vec![AVAX]);
+        assert!(!db.list_network_statuses().await.unwrap().iter().find(|n| n.network_id == AVAX).unwrap().enabled);
+        assert!(!db.get_portfolio_caips("dev1", "").await.unwrap().contains(&avax_asset));
+        assert!(db.get_derivation_paths(false).await.unwrap().iter().all(|p| p.path_id != "avax_44"));
+        assert!(db.get_derivation_paths(true).await.unwrap().iter().any(|p| p.path_id == "avax_44"));
+        assert!(db.search_assets("ava", None, false, 10).await.unwrap().is_empty());
+        assert_eq!(db.search_assets("ava", None, true, 10).await.unwrap().len(), 1);
+        assert!(db.get_tracked_networks().await.unwrap().is_empty());
+        // Hidden, not deleted
+        assert_eq!(db.get_asset_balances("dev1", "", &avax_asset).await.unwrap(), vec!["1".to_string()]);
+
+        assert!(db.set_network_enabled(AVAX, true).await.unwrap());
+        assert!(db.get_disabled_networks().await.unwrap().is_empty());
+        assert!(db.get_portfolio_caips("dev1", "").await.unwrap().contains(&avax_asset));
+        assert!(db.get_derivation_paths(false).await.unwrap().iter().any(|p| p.path_id == "avax_44"));
+        assert_eq!(db.search_assets("ava", None, false, 10).await.unwrap()[0].caip, avax_asset);
+        assert_eq!(db.get_tracked_networks().await.unwrap(), vec![AVAX]);
+    }
+
+    #[tokio::test]
+    async fn test_registry_inactive_networks_are_disabled_until_enabled() {
+        let db = Database::new_in_memory().await.unwrap();
+        seed(&db).await;
+        assert!(db.set_network_active(ETH, false).await.unwrap());
+        assert!(!db.set_network_active("eip155:999999", false).await.unwrap());
+        assert!(!db.get_network(ETH).await.unwrap().unwrap().is_active);
+        assert_eq!(db.get_disabled_networks().await.unwrap(), vec![ETH]);
+        db.set_network_enabled(ETH, true).await.unwrap();
+        assert!(db.get_disabled_networks().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_first_run_defaults_follow_frontloaded_xpubs() {
+        let db = Database::new_in_memory().await.unwrap();
+        seed(&db).await;
+
+        // Nothing frontloaded yet: leave everything as is and try again later
+        assert_eq!(db.initialize_network_defaults().await.unwrap(), None);
+        assert!(db.get_disabled_networks().await.unwrap().is_empty());
+
+        db.register_device("dev1", None, None).await.unwrap();
+        db.save_wallet_xpub(&WalletXpubInput {
+            device_id: "dev1".to_string(),
+            wallet_fingerprint: String::new(),
+            path: "m/84'/0'/0'".to_string(),
+            label: "Bitcoin".to_string(),
+            caip: format!("{}/slip44:0", BTC),
+            pubkey: "xpub".to_string(),
+        }).await.unwrap();
+        // A choice made before the defaults are written is kept
+        db.set_network_enabled(AVAX, true).await.unwrap();
+
+        assert_eq!(db.initialize_network_defaults().await.unwrap(), Some(vec![BTC.to_string(), AVAX.to_string()]));
+        assert_eq!(db.get_disabled_networks().await.unwrap(), vec![ETH]);
+
+        // Runs once
+        db.set_network_enabled(ETH, true).await.unwrap();
+        assert_eq!(db.initialize_network_defaults().await.unwrap(), None);
+        assert!(db.get_disabled_networks().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_default_networks_need_their_native_assets() {
+        let db = Database::new_in_memory().await.unwrap();
+        let err = db.seed_default_networks().await.unwrap_err();
+        assert!(matches!(err, DatabaseError::Validation(ref m) if m.contains("not in the asset registry")), "{}", err);
+        assert!(db.list_networks(true).await.unwrap().is_empty());
+
+        db.seed_default_assets().await.unwrap();
+        assert_eq!(db.seed_default_networks().await.unwrap(), 5);
+        assert_eq!(db.seed_default_networks().await.unwrap(), 0);
+        let eth = db.get_network(ETH).await.unwrap().unwrap();
+        assert_eq!(eth.fee_asset_caip.as_deref(), Some("eip155:1/slip44:60"));
+        assert!(eth.supports_eip1559 && eth.is_active);
+        assert_eq!(db.get_network_endpoints(ETH).await.unwrap().unwrap().rpc_urls.len(), 2);
+
+        let sepolia_eth = "eip155:11155111/slip44:60";
+        let sepolia = NetworkInput {
+            network_id: "eip155:11155111".to_string(),
+            name: "Sepolia".to_string(),
+            short_name: None,
+            chain_id: Some("11155111".to_string()),
+            network_type: Some("evm".to_string()),
+            native_asset_caip: sepolia_eth.to_string(),
+            native_symbol: "ETH".to_string(),
+            rpc_urls: vec![],
+            ws_urls: vec![],
+            explorer_url: None,
+            explorer_api_url: None,
+            explorer_api_key_required: false,
+            supports_eip1559: true,
+            supports_memo: false,
+            supports_tokens: true,
+            fee_asset_caip: None,
+            min_fee: None,
+            is_testnet: true,
+        };
+        assert!(db.upsert_network(sepolia.clone()).await.is_err());
+        db.with_connection(move |conn| {
+            conn.execute(
+                "INSERT INTO assets (caip, network_id, symbol, name, is_native) VALUES (?1, 'eip155:11155111', 'ETH', 'Sepolia Ether', 1)",
+                [sepolia_eth],
+            )?;
+            Ok(())
+        }).await.unwrap();
+        db.upsert_network(sepolia).await.unwrap();
+        assert_eq!(db.list_networks(false).await.unwrap().len(), 5);
+        assert_eq!(db.list_networks(true).await.unwrap().last().unwrap().name, "Sepolia");
+    }
+}
@@ -1,7 +1,12 @@
+use crate::errors::{DatabaseError, Result};
+use crate::networks::ENABLED_NETWORK_FILTER;
+use crate::types::{CombinedPortfolioEntry, PendingOutgoing, PortfolioBalance, PortfolioBalanceInput, TransactionCache};
 use crate::Database;
+use rusqlite::OptionalExtension;
 
 /// Portfolio manager - handles portfolio data and caching
 pub struct Portfolio {
+    #[allow(dead_code)]
     db: Database,
 }
 
@@ -11,4 +16,531 @@ impl Portfolio {
     }
 
     // Portfolio methods can be added here
-} 
\ No newline at end of file
+}
+
+/// Statuses a cached transaction can have; 'signed' is not broadcast yet
+pub const TRANSACTION_STATUSES: [&str; 5] = ["signed", "pending", "confirmed", "failed", "reorged"];
+
+/// Columns read by `transaction_from_row`, in order
+pub(crate) const TRANSACTION_COLUMNS: &str =
+    "id, device_id, txid, caip, type, amount, amount_usd, fee, fee_usd, from_address,
+     to_address, timestamp, block_height, status, metadata_json, wallet_fingerprint, origin";
+
+pub(crate) fn transaction_from_row(row: &rusqlite::Row) -> rusqlite::Result<TransactionCache> {
+    Ok(TransactionCache {
+        id: row.get(0)?,
+        device_id: row.get(1)?,
+        txid: row.get(2)?,
+        caip: row.get(3)?,
+        transaction_type: row.get(4)?,
+        amount: row.get(5)?,
+        amount_usd: row.get(6)?,
+        fee: row.get(7)?,
+        fee_usd: row.get(8)?,
+        from_address: row.get(9)?,
+        to_address: row.get(10)?,
+        timestamp: row.get(11)?,
+        block_height: row.get(12)?,
+        status: row.get(13)?,
+        metadata_json: row.get(14)?,
+        wallet_fingerprint: row.get(15)?,
+        origin: row.get(16)?,
+    })
+}
+
+/// Columns read by `balance_from_row`, in order
+const BALANCE_COLUMNS: &str =
+    "id, device_id, wallet_fingerprint, pubkey, caip, network_id, ticker, address, balance, balance_usd,
+     price_usd, type, name, icon, precision, contract, validator, unbonding_end, rewards_available,
+     last_updated, last_block_height, is_verified";
+
+/// Matches a row on the table's UNIQUE key. address, type and validator are
+/// nullable and NULLs never conflict, so they are compared with IS instead of
+/// relying on ON CONFLICT.
+const BALANCE_KEY: &str =
+    "device_id = ?1 AND pubkey = ?2 AND caip = ?3 AND address IS ?4 AND type IS ?5 AND validator IS ?6";
+
+fn balance_from_row(row: &rusqlite::Row) -> rusqlite::Result<PortfolioBalance> {
+    Ok(PortfolioBalance {
+        id: row.get(0)?,
+        device_id: row.get(1)?,
+        wallet_fingerprint: row.get(2)?,
+        pubkey: row.get(3)?,
+        caip: row.get(4)?,
+        network_id: row.get(5)?,
+        ticker: row.get(6)?,
+        address: row.get(7)?,
+        balance: row.get(8)?,
+        balance_usd: row.get(9)?,
+        price_usd: row.get(10)?,
+        balance_type: row.get::<_, Option<String>>(11)?.unwrap_or_else(|| "balance".to_string()),
+        name: row.get(12)?,
+        icon: row.get(13)?,
+        precision: row.get(14)?,
+        contract: row.get(15)?,
+        validator: row.get(16)?,
+        unbonding_end: row.get(17)?,
+        rewards_available: row.get(18)?,
+        last_updated: row.get(19)?,
+        last_block_height: row.get(20)?,
+        is_verified: row.get::<_, Option<bool>>(21)?.unwrap_or(false),
+    })
+}
+
+/// A plain decimal such as "0.00012345"; amounts are stored as given so no
+/// precision is lost
+fn is_decimal_amount(amount: &str) -> bool {
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    !whole.is_empty()
+        && whole.bytes().all(|b| b.is_ascii_digit())
+        && fraction.bytes().all(|b| b.is_ascii_digit())
+        && !(amount.contains('.') && fraction.is_empty())
+}
+
+impl Database {
+    /// Store balances fetched for a device wallet in one transaction. A row
+    /// with the same device, pubkey, asset, address, type and validator is
+    /// updated in place. Returns the number of balances written.
+    pub async fn upsert_portfolio_balances(
+        &self,
+        device_id: &str,
+        wallet_fingerprint: &str,
+        balances: Vec<PortfolioBalanceInput>,
+    ) -> Result<usize> {
+        for balance in &balances {
+            if balance.device_id != device_id {
+                return Err(DatabaseError::Validation(format!(
+                    "Balance for {} belongs to device {}, not {}", balance.caip, balance.device_id, device_id
+                )));
+            }
+            if !is_decimal_amount(&balance.balance) {
+                return Err(DatabaseError::Validation(format!(
+                    "Invalid {} balance {:?}", balance.caip, balance.balance
+                )));
+            }
+        }
+        let now = Self::current_timestamp();
+        let wallet_fingerprint = wallet_fingerprint.to_string();
+        self.transaction(move |conn| {
+            let mut update = conn.prepare(&format!(
+                "UPDATE portfolio_balances SET
+                    wallet_fingerprint = ?7, network_id = ?8, ticker = ?9, balance = ?10, balance_usd = ?11,
+                    price_usd = ?12, name = ?13, icon = ?14, precision = ?15, contract = ?16,
+                    unbonding_end = ?17, rewards_available = ?18, last_updated = ?19
+                 WHERE {}",
+                BALANCE_KEY
+            ))?;
+            let mut insert = conn.prepare(
+                "INSERT INTO portfolio_balances
+                    (device_id, pubkey, caip, address, type, validator, wallet_fingerprint, network_id, ticker,
+                     balance, balance_usd, price_usd, name, icon, precision, contract, unbonding_end,
+                     rewards_available, last_updated)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)"
+            )?;
+            for b in &balances {
+                let params = rusqlite::params![
+                    b.device_id, b.pubkey, b.caip, b.address, b.balance_type, b.validator,
+                    wallet_fingerprint, b.network_id, b.ticker, b.balance, b.balance_usd, b.price_usd,
+                    b.name, b.icon, b.precision, b.contract, b.unbonding_end, b.rewards_available, now,
+                ];
+                if update.execute(params)? == 0 {
+                    insert.execute(params)?;
+                }
+            }
+            Ok(balances.len())
+        }).await
+    }
+
+    /// Every cached balance row of a device wallet
+    pub async fn get_portfolio_balances(&self, device_id: &str, wallet_fingerprint: &str) -> Result<Vec<PortfolioBalance>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM portfolio_balances
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2
+                 ORDER BY caip, type, address, validator",
+                BALANCE_COLUMNS
+            ))?;
+            let balances = stmt
+                .query_map([device_id, wallet_fingerprint], balance_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(balances)
+        }).await
+    }
+
+    /// Spendable balances of each asset summed across all devices
+    pub async fn get_combined_portfolio(&self) -> Result<Vec<CombinedPortfolioEntry>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT caip, network_id, ticker, total_balance, total_value_usd, price_usd, last_updated
+                 FROM v_combined_portfolio
+                 ORDER BY total_value_usd DESC, caip"
+            )?;
+            let entries = stmt
+                .query_map([], |row| Ok(CombinedPortfolioEntry {
+                    caip: row.get(0)?,
+                    network_id: row.get(1)?,
+                    ticker: row.get(2)?,
+                    total_balance: row.get::<_, Option<f64>>(3)?.unwrap_or(0.0),
+                    total_value_usd: row.get::<_, Option<f64>>(4)?.unwrap_or(0.0),
+                    price_usd: row.get(5)?,
+                    last_updated: row.get(6)?,
+                }))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(entries)
+        }).await
+    }
+
+    /// Remove a device's balances last written before `older_than` (unix
+    /// seconds), e.g. assets missing from a completed sync. Returns the
+    /// number of rows removed.
+    pub async fn delete_stale_balances(&self, device_id: &str, older_than: i64) -> Result<usize> {
+        self.with_connection(|conn| {
+            Ok(conn.execute(
+                "DELETE FROM portfolio_balances WHERE device_id = ?1 AND last_updated < ?2",
+                rusqlite::params![device_id, older_than],
+            )?)
+        }).await
+    }
+
+    /// Raw cached balances (one per address/xpub) for an asset in a device wallet
+    pub async fn get_asset_balances(&self, device_id: &str, wallet_fingerprint: &str, caip: &str) -> Result<Vec<String>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT balance FROM portfolio_balances
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2 AND caip = ?3
+                   AND COALESCE(type, 'balance') = 'balance'"
+            )?;
+            let balances = stmt
+                .query_map([device_id, wallet_fingerprint, caip], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(balances)
+        }).await
+    }
+
+    /// Outgoing transactions still pending for an asset in a device wallet
+    pub async fn get_pending_outgoing(&self, device_id: &str, wallet_fingerprint: &str, caip: &str) -> Result<Vec<PendingOutgoing>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT txid, amount, fee FROM transaction_cache
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2 AND caip = ?3
+                   AND type = 'send' AND status = 'pending'"
+            )?;
+            let pending = stmt
+                .query_map([device_id, wallet_fingerprint, caip], |row| Ok(PendingOutgoing {
+                    txid: row.get(0)?,
+                    amount: row.get(1)?,
+                    fee: row.get(2)?,
+                }))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(pending)
+        }).await
+    }
+
+    /// Assets with a cached balance in a device wallet, on enabled networks
+    pub async fn get_portfolio_caips(&self, device_id: &str, wallet_fingerprint: &str) -> Result<Vec<String>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                &format!(
+                    "SELECT DISTINCT caip FROM portfolio_balances
+                     WHERE device_id = ?1 AND wallet_fingerprint = ?2 AND {}
+                     ORDER BY caip",
+                    ENABLED_NETWORK_FILTER
+                )
+            )?;
+            let caips = stmt
+                .query_map([device_id, wallet_fingerprint], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(caips)
+        }).await
+    }
+
+    /// Latest cached USD price for an asset, if any balance has been fetched
+    pub async fn get_asset_price_usd(&self, caip: &str) -> Result<Option<f64>> {
+        self.with_connection(|conn| {
+            let price: Option<String> = conn.query_row(
+                "SELECT price_usd FROM portfolio_balances WHERE caip = ?1 ORDER BY last_updated DESC LIMIT 1",
+                [caip],
+                |row| row.get(0),
+            ).optional()?;
+            Ok(price.and_then(|p| p.parse().ok()))
+        }).await
+    }
+
+    /// Cached dashboard total (USD) for one wallet, if the dashboard has been built
+    pub async fn get_dashboard_total_usd(&self, device_id: &str, wallet_fingerprint: &str) -> Result<Option<String>> {
+        self.with_connection(|conn| {
+            Ok(conn.query_row(
+                "SELECT total_value_usd FROM portfolio_dashboard
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2 AND is_combined = 0",
+                [device_id, wallet_fingerprint],
+                |row| row.get(0),
+            ).optional()?)
+        }).await
+    }
+
+    /// Insert or update a transaction in the activity cache (id is ignored).
+    /// With lot tracking on, the asset's lots are replayed to include it.
+    pub async fn upsert_transaction(&self, tx: &TransactionCache) -> Result<()> {
+        let tx = tx.clone();
+        self.transaction(move |conn| {
+            conn.execute(
+                "INSERT INTO transaction_cache
+                    (device_id, txid, caip, type, amount, amount_usd, fee, fee_usd, from_address,
+                     to_address, timestamp, block_height, status, metadata_json, wallet_fingerprint, origin)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+                 ON CONFLICT(device_id, wallet_fingerprint, txid, caip) DO UPDATE SET
+                    status = excluded.status,
+                    origin = COALESCE(origin, excluded.origin),
+                    block_height = COALESCE(excluded.block_height, block_height),
+                    metadata_json = COALESCE(excluded.metadata_json, metadata_json)",
+                rusqlite::params![
+                    tx.device_id, tx.txid, tx.caip, tx.transaction_type, tx.amount, tx.amount_usd,
+                    tx.fee, tx.fee_usd, tx.from_address, tx.to_address, tx.timestamp,
+                    tx.block_height, tx.status, tx.metadata_json, tx.wallet_fingerprint, tx.origin,
+                ],
+            )?;
+            if crate::tax_lots::tracking_enabled(conn)? {
+                let method = crate::tax_lots::lot_method(conn)?;
+                crate::tax_lots::rebuild_asset(conn, &tx.caip, method)?;
+            }
+            Ok(())
+        }).await
+    }
+
+    /// Get a cached transaction from a device wallet
+    pub async fn get_transaction(&self, device_id: &str, wallet_fingerprint: &str, txid: &str, caip: &str) -> Result<Option<TransactionCache>> {
+        self.with_connection(|conn| {
+            Ok(conn.query_row(
+                &format!("SELECT {} FROM transaction_cache
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2 AND txid = ?3 AND caip = ?4", TRANSACTION_COLUMNS),
+                [device_id, wallet_fingerprint, txid, caip],
+                transaction_from_row,
+            ).optional()?)
+        }).await
+    }
+
+    /// Set the status of every row of a transaction in a device wallet (one
+    /// per asset it moved). The block height is kept when None is given.
+    /// Returns the number of rows updated.
+    pub async fn update_transaction_status(
+        &self,
+        device_id: &str,
+        wallet_fingerprint: &str,
+        txid: &str,
+        status: &str,
+        block_height: Option<i64>,
+    ) -> Result<usize> {
+        if !TRANSACTION_STATUSES.contains(&status) {
+            return Err(DatabaseError::Validation(format!("Unknown transaction status: {}", status)));
+        }
+        self.with_connection(|conn| {
+            Ok(conn.execute(
+                "UPDATE transaction_cache SET status = ?1, block_height = COALESCE(?2, block_height)
+                 WHERE device_id = ?3 AND wallet_fingerprint = ?4 AND txid = ?5",
+                rusqlite::params![status, block_height, device_id, wallet_fingerprint, txid],
+            )?)
+        }).await
+    }
+
+    /// A page of a device wallet's transactions, newest first
+    pub async fn get_recent_transactions(
+        &self,
+        device_id: &str,
+        wallet_fingerprint: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<TransactionCache>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM transaction_cache
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2
+                 ORDER BY timestamp DESC, id DESC
+                 LIMIT ?3 OFFSET ?4",
+                TRANSACTION_COLUMNS
+            ))?;
+            let txs = stmt
+                .query_map(rusqlite::params![device_id, wallet_fingerprint, limit, offset], transaction_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(txs)
+        }).await
+    }
+
+    /// Pending transactions of every device and wallet, oldest first
+    pub async fn get_pending_transactions(&self) -> Result<Vec<TransactionCache>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM transaction_cache WHERE status = 'pending' ORDER BY timestamp, id",
+                TRANSACTION_COLUMNS
+            ))?;
+            let txs = stmt
+                .query_map([], transaction_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(txs)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pending_outgoing_only_counts_pending_sends() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.with_connection(|conn| {
+            conn.execute_batch(
+                "INSERT INTO transaction_cache (device_id, txid, caip, type, amount, fee, timestamp, status) VALUES
+                    ('dev1', 'a', 'cosmos:cosmoshub-4/slip44:118', 'send', '1.5', '0.005', 1, 'pending'),
+                    ('dev1', 'b', 'cosmos:cosmoshub-4/slip44:118', 'send', '2', '0.005', 2, 'confirmed'),
+                    ('dev1', 'c', 'cosmos:cosmoshub-4/slip44:118', 'receive', '3', NULL, 3, 'pending');"
+            )?;
+            Ok(())
+        }).await.unwrap();
+
+        let pending = db.get_pending_outgoing("dev1", "", "cosmos:cosmoshub-4/slip44:118").await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].txid, "a");
+    }
+
+    fn atom_balance(device_id: &str, balance: &str, balance_type: &str, validator: Option<&str>) -> PortfolioBalanceInput {
+        PortfolioBalanceInput {
+            device_id: device_id.to_string(),
+            pubkey: "cosmos1delegator".to_string(),
+            caip: "cosmos:cosmoshub-4/slip44:118".to_string(),
+            network_id: "cosmos:cosmoshub-4".to_string(),
+            ticker: "ATOM".to_string(),
+            address: None,
+            balance: balance.to_string(),
+            balance_usd: "10.5".to_string(),
+            price_usd: "7".to_string(),
+            balance_type: balance_type.to_string(),
+            name: Some("Cosmos Hub".to_string()),
+            icon: None,
+            precision: Some(6),
+            contract: None,
+            validator: validator.map(str::to_string),
+            unbonding_end: None,
+            rewards_available: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upsert_portfolio_balances() {
+        let db = Database::new_in_memory().await.unwrap();
+        let precise = "12345678901234567890.123456789012345678";
+        let batch = vec![
+            atom_balance("dev1", "1.5", "balance", None),
+            atom_balance("dev1", "2", "delegation", Some("cosmosvaloper1a")),
+            atom_balance("dev1", "3", "delegation", Some("cosmosvaloper1b")),
+        ];
+        assert_eq!(db.upsert_portfolio_balances("dev1", "0badf00d", batch).await.unwrap(), 3);
+
+        // Same keys again, with a NULL address: updated, not duplicated
+        let batch = vec![
+            atom_balance("dev1", precise, "balance", None),
+            atom_balance("dev1", "4", "delegation", Some("cosmosvaloper1a")),
+        ];
+        db.upsert_portfolio_balances("dev1", "0badf00d", batch).await.unwrap();
+        let stored = db.get_portfolio_balances("dev1", "0badf00d").await.unwrap();
+        let rows: Vec<_> = stored.iter().map(|b| (b.balance_type.as_str(), b.validator.as_deref(), b.balance.as_str())).collect();
+        assert_eq!(rows, [
+            ("balance", None, precise),
+            ("delegation", Some("cosmosvaloper1a"), "4"),
+            ("delegation", Some("cosmosvaloper1b"), "3"),
+        ]);
+        assert!(db.get_portfolio_balances("dev1", "").await.unwrap().is_empty());
+
+        // A bad row rejects the whole batch
+        for (device_id, balance) in [("dev2", "1"), ("dev1", "1e6"), ("dev1", "-1"), ("dev1", "1.")] {
+            let batch = vec![atom_balance("dev1", "9", "balance", None), atom_balance(device_id, balance, "balance", None)];
+            assert!(db.upsert_portfolio_balances("dev1", "0badf00d", batch).await.is_err(), "{} {}", device_id, balance);
+        }
+        assert_eq!(db.get_portfolio_balances("dev1", "0badf00d").await.unwrap()[0].balance, precise);
+
+        db.upsert_portfolio_balances("dev2", "", vec![atom_balance("dev2", "0.5", "balance", None)]).await.unwrap();
+        let combined = db.get_combined_portfolio().await.unwrap();
+        assert_eq!(combined.len(), 1);
+        assert_eq!((combined[0].ticker.as_str(), combined[0].total_value_usd), ("ATOM", 21.0));
+
+        assert_eq!(db.delete_stale_balances("dev1", Database::current_timestamp() - 60).await.unwrap(), 0);
+        assert_eq!(db.delete_stale_balances("dev1", Database::current_timestamp() + 1).await.unwrap(), 3);
+        assert_eq!(db.get_combined_portfolio().await.unwrap()[0].total_balance, 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_upsert_transaction_keeps_batch_metadata() {
+        let db = Database::new_in_memory().await.unwrap();
+        let mut tx = TransactionCache {
+            id: 0,
+            device_id: "dev1".to_string(),
+            txid: "abc".to_string(),
+            caip: "bip122:000000000019d6689c085ae165831e93/slip44:0".to_string(),
+            transaction_type: "send".to_string(),
+            amount: "0.0003".to_string(),
+            amount_usd: None,
+            fee: Some("0.00001".to_string()),
+            fee_usd: None,
+            from_address: None,
+            to_address: None,
+            timestamp: 1,
+            block_height: None,
+            status: Some("pending".to_string()),
+            metadata_json: Some(r#"{"batch":true,"outputs":[1,2,3]}"#.to_string()),
+            wallet_fingerprint: "0badf00d".to_string(),
+            origin: Some("main_window".to_string()),
+        };
+        db.upsert_transaction(&tx).await.unwrap();
+
+        tx.status = Some("confirmed".to_string());
+        tx.metadata_json = None;
+        tx.origin = Some("rest_api:other".to_string());
+        db.upsert_transaction(&tx).await.unwrap();
+
+        let stored = db.get_transaction("dev1", "0badf00d", "abc", &tx.caip).await.unwrap().unwrap();
+        assert_eq!(stored.status.as_deref(), Some("confirmed"));
+        assert!(stored.metadata_json.unwrap().contains("\"batch\":true"));
+        // The requesting origin is fixed when the transaction is first recorded
+        assert_eq!(stored.origin.as_deref(), Some("main_window"));
+    }
+
+    #[tokio::test]
+    async fn test_pending_transaction_confirms_in_place() {
+        let db = Database::new_in_memory().await.unwrap();
+        let tx = |txid: &str, timestamp: i64, status: &str, block_height: Option<i64>| TransactionCache {
+            id: 0,
+            device_id: "dev1".to_string(),
+            txid: txid.to_string(),
+            caip: "eip155:1/slip44:60".to_string(),
+            transaction_type: "send".to_string(),
+            amount: "0.1".to_string(),
+            amount_usd: None,
+            fee: None,
+            fee_usd: None,
+            from_address: None,
+            to_address: None,
+            timestamp,
+            block_height,
+            status: Some(status.to_string()),
+            metadata_json: None,
+            wallet_fingerprint: "fp1".to_string(),
+            origin: None,
+        };
+        db.upsert_transaction(&tx("old", 1, "confirmed", Some(10))).await.unwrap();
+        db.upsert_transaction(&tx("new", 3, "pending", None)).await.unwrap();
+        db.upsert_transaction(&tx("mid", 2, "pending", None)).await.unwrap();
+        assert_eq!(db.get_pending_transactions().await.unwrap().iter().map(|t| t.txid.as_str()).collect::<Vec<_>>(), ["mid", "new"]);
+
+        // Recorded again once it is mined: same row, now confirmed
+        db.upsert_transaction(&tx("new", 3, "confirmed", Some(12))).await.unwrap();
+        assert_eq!(db.update_transaction_status("dev1", "fp1", "mid", "confirmed", Some(11)).await.unwrap(), 1);
+        assert_eq!(db.update_transaction_status("dev1", "fp1", "mid", "failed", None).await.unwrap(), 1);
+        assert!(db.update_transaction_status("dev1", "fp1", "mid", "mined", None).await.is_err());
+        assert!(db.get_pending_transactions().await.unwrap().is_empty());
+
+        let recent = db.get_recent_transactions("dev1", "fp1", 10, 0).await.unwrap();
+        let rows: Vec<_> = recent.iter().map(|t| (t.txid.as_str(), t.status.as_deref(), t.block_height)).collect();
+        assert_eq!(rows, [("new", Some("confirmed"), Some(12)), ("mid", Some("failed"), Some(11)), ("old", Some("confirmed"), Some(10))]);
+        assert_eq!(db.get_recent_transactions("dev1", "fp1", 1, 1).await.unwrap()[0].txid, "mid");
+        assert!(db.get_recent_transactions("dev1", "fp2", 10, 0).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+//! Portfolio value history
+//!
+//! A snapshot of a wallet's total value is recorded each time its balances are
+//! refreshed. Charts read it downsampled: the range is split into equal
+//! buckets and the last snapshot of each bucket is kept. Snapshots older than
+//! `RAW_HISTORY_DAYS` are thinned to the last one of each day; the storage
+//! policy's retention window still removes everything past it. Large
+//! snapshot JSON is stored zstd-compressed, see `storage::encode_snapshot`.
+
+use crate::errors::{DatabaseError, Result};
+use crate::storage::{decode_snapshot, encode_snapshot};
+use crate::types::PortfolioHistoryPoint;
+use rusqlite::OptionalExtension;
+use crate::Database;
+
+/// Days of snapshots kept at full resolution
+pub const RAW_HISTORY_DAYS: i64 = 90;
+
+const DAY_SECS: i64 = 86_400;
+
+impl Database {
+    /// Record the total value of a device wallet now
+    pub async fn record_portfolio_snapshot(
+        &self,
+        device_id: &str,
+        wallet_fingerprint: &str,
+        total_value_usd: &str,
+        snapshot_json: Option<&str>,
+    ) -> Result<()> {
+        let now = Self::current_timestamp();
+        let snapshot_json = snapshot_json.map(encode_snapshot).transpose()?;
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO portfolio_history (device_id, wallet_fingerprint, timestamp, total_value_usd, snapshot_json)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![device_id, wallet_fingerprint, now, total_value_usd, snapshot_json],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    /// Full snapshot JSON last recorded for a device wallet, if it had one
+    pub async fn get_latest_portfolio_snapshot(&self, device_id: &str, wallet_fingerprint: &str) -> Result<Option<String>> {
+        let stored = self.with_connection(|conn| {
+            Ok(conn.query_row(
+                "SELECT snapshot_json FROM portfolio_history
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2
+                 ORDER BY timestamp DESC, id DESC LIMIT 1",
+                [device_id, wallet_fingerprint],
+                |row| row.get::<_, rusqlite::types::Value>(0),
+            ).optional()?)
+        }).await?;
+        match stored {
+            Some(value) => decode_snapshot(value),
+            None => Ok(None),
+        }
+    }
+
+    /// Snapshots of a device wallet between `from_ts` and `to_ts` (inclusive,
+    /// unix seconds), at most `max_points` of them: the range is cut into that
+    /// many buckets and the last snapshot in each is returned, oldest first
+    pub async fn get_portfolio_history(
+        &self,
+        device_id: &str,
+        wallet_fingerprint: &str,
+        from_ts: i64,
+        to_ts: i64,
+        max_points: u32,
+    ) -> Result<Vec<PortfolioHistoryPoint>> {
+        if to_ts < from_ts || max_points == 0 {
+            return Err(DatabaseError::Validation(format!(
+                "Invalid history range {}..{} with {} points", from_ts, to_ts, max_points
+            )));
+        }
+        // Ceiling division, so the last bucket still ends at to_ts
+        let span = to_ts - from_ts + 1;
+        let bucket_secs = (span + i64::from(max_points) - 1) / i64::from(max_points);
+
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT timestamp, total_value_usd FROM (
+                    SELECT timestamp, total_value_usd, ROW_NUMBER() OVER (
+                        PARTITION BY (timestamp - ?3) / ?5 ORDER BY timestamp DESC, id DESC
+                    ) AS rn
+                    FROM portfolio_history
+                    WHERE device_id = ?1 AND wallet_fingerprint = ?2 AND timestamp BETWEEN ?3 AND ?4
+                 ) WHERE rn = 1
+                 ORDER BY timestamp"
+            )?;
+            let points = stmt
+                .query_map(
+                    rusqlite::params![device_id, wallet_fingerprint, from_ts, to_ts, bucket_secs],
+                    |row| Ok(PortfolioHistoryPoint { timestamp: row.get(0)?, total_value_usd: row.get(1)? }),
+                )?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(points)
+        }).await
+    }
+
+    /// Thin snapshots older than RAW_HISTORY_DAYS to the last one per wallet
+    /// and day; returns the number removed
+    pub async fn prune_portfolio_history(&self) -> Result<usize> {
+        let cutoff = Self::current_timestamp() - RAW_HISTORY_DAYS * DAY_SECS;
+        self.with_connection(|conn| {
+            let pruned = conn.execute(
+                "DELETE FROM portfolio_history WHERE id IN (
+                    SELECT id FROM (
+                        SELECT id, ROW_NUMBER() OVER (
+                            PARTITION BY device_id, wallet_fingerprint, timestamp / ?2
+                            ORDER BY timestamp DESC, id DESC
+                        ) AS rn
+                        FROM portfolio_history
+                        WHERE timestamp < ?1
+                    ) WHERE rn > 1
+                )",
+                [cutoff, DAY_SECS],
+            )?;
+            if pruned > 0 {
+                log::info!("Thinned {} portfolio snapshots older than {} days", pruned, RAW_HISTORY_DAYS);
+            }
+            Ok(pruned)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn insert_snapshots(db: &Database, snapshots: &[(&str, i64, &str)]) {
+        let snapshots: Vec<_> = snapshots.iter().map(|(d, t, v)| (d.to_string(), *t, v.to_string())).collect();
+        db.with_connection(move |conn| {
+            for (device_id, timestamp, value) in &snapshots {
+                conn.execute(
+                    "INSERT INTO portfolio_history (device_id, timestamp, total_value_usd) VALUES (?1, ?2, ?3)",
+                    rusqlite::params![device_id, timestamp, value],
+                )?;
+            }
+            Ok(())
+        }).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_history_is_downsampled_to_the_last_snapshot_per_bucket() {
+        let db = Database::new_in_memory().await.unwrap();
+        // Every 10 minutes for 4 hours, the value counting up
+        let snapshots: Vec<(&str, i64, String)> = (0..24).map(|i| ("dev1", i * 600, i.to_string())).collect();
+        let snapshots: Vec<_> = snapshots.iter().map(|(d, t, v)| (*d, *t, v.as_str())).collect();
+        insert_snapshots(&db, &snapshots).await;
+        insert_snapshots(&db, &[("dev2", 600, "999")]).await;
+
+        let hourly = db.get_portfolio_history("dev1", "", 0, 4 * 3600 - 1, 4).await.unwrap();
+        let values: Vec<_> = hourly.iter().map(|p| (p.timestamp, p.total_value_usd.as_str())).collect();
+        assert_eq!(values, [(3000, "5"), (6600, "11"), (10200, "17"), (13800, "23")]);
+
+        let all = db.get_portfolio_history("dev1", "", 0, 4 * 3600, 1000).await.unwrap();
+        assert_eq!(all.len(), 24);
+        assert_eq!(db.get_portfolio_history("dev1", "", 0, 4 * 3600, 1).await.unwrap()[0].total_value_usd, "23");
+        assert!(db.get_portfolio_history("dev1", "", 10, 5, 4).await.is_err());
+        assert!(db.get_portfolio_history("dev1", "", 0, 5, 0).await.is_err());
+
+        db.record_portfolio_snapshot("dev1", "aaaa0001", "1234.56", Some("[]")).await.unwrap();
+        let now = Database::current_timestamp();
+        let recorded = db.get_portfolio_history("dev1", "aaaa0001", now - 60, now, 10).await.unwrap();
+        assert_eq!(recorded, [PortfolioHistoryPoint { timestamp: recorded[0].timestamp, total_value_usd: "1234.56".to_string() }]);
+    }
+
+    #[tokio::test]
+    async fn test_large_snapshots_are_stored_compressed() {
+        let db = Database::new_in_memory().await.unwrap();
+        assert_eq!(db.get_latest_portfolio_snapshot("dev1", "fp1").await.unwrap(), None);
+
+        let small = r#"[{"caip":"eip155:1/slip44:60","balance":"1"}]"#;
+        db.record_portfolio_snapshot("dev1", "fp1", "1", Some(small)).await.unwrap();
+        assert_eq!(db.get_latest_portfolio_snapshot("dev1", "fp1").await.unwrap().as_deref(), Some(small));
+
+        let entries: Vec<String> = (0..500)
+            .map(|i| format!(r#"{{"caip":"eip155:1/erc20:0x{:040x}","balance":"{}"}}"#, i, i))
+            .collect();
+        let large = format!("[{}]", entries.join(","));
+        db.record_portfolio_snapshot("dev1", "fp1", "2", Some(&large)).await.unwrap();
+        assert_eq!(db.get_latest_portfolio_snapshot("dev1", "fp1").await.unwrap(), Some(large.clone()));
+
+        let stored: Vec<(String, i64)> = db.with_connection(|conn| {
+            let mut stmt = conn.prepare("SELECT typeof(snapshot_json), LENGTH(snapshot_json) FROM portfolio_history ORDER BY id")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(rows)
+        }).await.unwrap();
+        assert_eq!(stored[0].0, "text");
+        assert_eq!(stored[1].0, "blob");
+        assert!((stored[1].1 as usize) < large.len() / 4);
+
+        // Without snapshot JSON there is nothing to read back
+        db.record_portfolio_snapshot("dev1", "fp1", "3", None).await.unwrap();
+        assert_eq!(db.get_latest_portfolio_snapshot("dev1", "fp1").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_one_snapshot_per_day_past_the_raw_window() {
+        let db = Database::new_in_memory().await.unwrap();
+        let now = Database::current_timestamp();
+        let old_day = (now / DAY_SECS - RAW_HISTORY_DAYS - 5) * DAY_SECS;
+        let recent = now - 3600;
+        insert_snapshots(&db, &[
+            ("dev1", old_day + 100, "1"),
+            ("dev1", old_day + 200, "2"),
+            ("dev1", old_day + 300, "3"),
+            ("dev1", old_day + DAY_SECS + 10, "4"),
+            ("dev2", old_day + 50, "5"),
+            ("dev1", recent, "6"),
+            ("dev1", recent + 1, "7"),
+        ]).await;
+
+        assert_eq!(db.prune_portfolio_history().await.unwrap(), 2);
+        assert_eq!(db.prune_portfolio_history().await.unwrap(), 0);
+        let kept: Vec<String> = db.with_connection(|conn| {
+            let mut stmt = conn.prepare("SELECT total_value_usd FROM portfolio_history ORDER BY timestamp")?;
+            let values = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(values)
+        }).await.unwrap();
+        assert_eq!(kept, ["5", "3", "4", "6", "7"]);
+    }
+}
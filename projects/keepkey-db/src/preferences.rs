@@ -0,0 +1,264 @@
+//! User preferences
+//!
+//! Preferences are strings stored in `meta` as `pref_<key>`. Keys listed in
+//! `KNOWN_PREFERENCES` are checked when written; anything else is stored as
+//! given and reported as unknown by `get_all_preferences`, so features can
+//! keep adding their own keys (`network_enabled:<id>`, ...) without a rule.
+//!
+//! Every write through `set_preference` is announced to
+//! `Database::subscribe_preference_changes`.
+
+use rusqlite::OptionalExtension;
+use crate::errors::{DatabaseError, Result};
+use crate::types::Preference;
+use crate::Database;
+
+/// Changes buffered for a slow subscriber before it starts missing them
+pub(crate) const PREFERENCE_CHANGES_CAPACITY: usize = 64;
+
+/// Values a known preference accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreferenceRule {
+    /// "true" or "false"
+    Bool,
+    /// A base-10 integer no smaller than the bound
+    Int { min: i64 },
+    OneOf(&'static [&'static str]),
+    /// ISO 4217 code, e.g. "USD"
+    Currency,
+    /// BCP 47 language tag, e.g. "en" or "pt-BR"
+    Language,
+}
+
+/// Preference keys the app itself reads, with the values they accept
+pub const KNOWN_PREFERENCES: &[(&str, PreferenceRule)] = &[
+    ("language", PreferenceRule::Language),
+    ("theme", PreferenceRule::OneOf(&["light", "dark", "system"])),
+    ("currency", PreferenceRule::Currency),
+    ("units", PreferenceRule::OneOf(&["metric", "imperial"])),
+    ("analytics_enabled", PreferenceRule::Bool),
+    ("developer_mode", PreferenceRule::Bool),
+    ("show_tray", PreferenceRule::Bool),
+    ("power_saver", PreferenceRule::OneOf(&["always", "never", "auto"])),
+    ("app_lock_minutes", PreferenceRule::Int { min: 0 }),
+    ("tax_lot_tracking", PreferenceRule::Bool),
+    ("tax_lot_method", PreferenceRule::OneOf(&["fifo", "hifo"])),
+];
+
+/// The rule for `key`, or None if the key is not a known preference
+pub fn preference_rule(key: &str) -> Option<PreferenceRule> {
+    KNOWN_PREFERENCES.iter().find(|(known, _)| *known == key).map(|(_, rule)| *rule)
+}
+
+/// Check `value` against the rule for `key`; unknown keys accept anything
+pub fn validate_preference(key: &str, value: &str) -> Result<()> {
+    let Some(rule) = preference_rule(key) else {
+        return Ok(());
+    };
+    let valid = match rule {
+        PreferenceRule::Bool => value == "true" || value == "false",
+        PreferenceRule::Int { min } => value.parse::<i64>().is_ok_and(|n| n >= min),
+        PreferenceRule::OneOf(allowed) => allowed.contains(&value),
+        PreferenceRule::Currency => is_currency_code(value),
+        PreferenceRule::Language => is_language_tag(value),
+    };
+    if valid {
+        return Ok(());
+    }
+    let expected = match rule {
+        PreferenceRule::Bool => "true or false".to_string(),
+        PreferenceRule::Int { min } => format!("an integer of at least {}", min),
+        PreferenceRule::OneOf(allowed) => format!("one of {}", allowed.join(", ")),
+        PreferenceRule::Currency => "an ISO 4217 currency code such as USD".to_string(),
+        PreferenceRule::Language => "a BCP 47 language tag such as en or pt-BR".to_string(),
+    };
+    Err(DatabaseError::Validation(format!(
+        "Invalid value {:?} for preference {}: expected {}",
+        value, key, expected
+    )))
+}
+
+fn is_currency_code(value: &str) -> bool {
+    value.len() == 3 && value.bytes().all(|b| b.is_ascii_uppercase())
+}
+
+/// Structural check only: a 2-3 or 5-8 letter language subtag followed by
+/// 1-8 character alphanumeric subtags
+fn is_language_tag(value: &str) -> bool {
+    let mut subtags = value.split('-');
+    let language = subtags.next().unwrap_or_default();
+    let language_ok = matches!(language.len(), 2 | 3 | 5..=8) && language.bytes().all(|b| b.is_ascii_alphabetic());
+    language_ok && subtags.all(|s| (1..=8).contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphanumeric()))
+}
+
+impl Database {
+    /// Store a preference and announce the change. Known keys are validated
+    /// first; unknown keys are stored as given.
+    pub async fn set_preference(&self, key: &str, value: &str) -> Result<()> {
+        validate_preference(key, value)?;
+        let known = preference_rule(key).is_some();
+        if !known {
+            log::debug!("Storing unknown preference {}", key);
+        }
+
+        let pref_key = format!("pref_{}", key);
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO meta (key, val) VALUES (?1, ?2)",
+                rusqlite::params![pref_key, value],
+            )?;
+            Ok(())
+        }).await?;
+
+        self.notify_preference_change(Preference { key: key.to_string(), value: value.to_string(), known });
+        Ok(())
+    }
+
+    /// Get user preference
+    pub async fn get_preference(&self, key: &str) -> Result<Option<String>> {
+        let pref_key = format!("pref_{}", key);
+
+        self.with_connection(|conn| {
+            let value = conn.query_row(
+                "SELECT val FROM meta WHERE key = ?1",
+                [pref_key],
+                |row| row.get::<_, String>(0),
+            ).optional()?;
+            Ok(value)
+        }).await
+    }
+
+    /// Every stored preference, sorted by key
+    pub async fn get_all_preferences(&self) -> Result<Vec<Preference>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT substr(key, 6), val FROM meta WHERE key LIKE 'pref\\_%' ESCAPE '\\' ORDER BY key",
+            )?;
+            let preferences = stmt
+                .query_map([], |row| {
+                    let key: String = row.get(0)?;
+                    Ok(Preference { known: preference_rule(&key).is_some(), key, value: row.get(1)? })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(preferences)
+        }).await
+    }
+
+    /// A preference as a string, or `default` when unset
+    pub async fn get_pref_string(&self, key: &str, default: &str) -> Result<String> {
+        Ok(self.get_preference(key).await?.unwrap_or_else(|| default.to_string()))
+    }
+
+    /// A "true"/"false" preference, or `default` when unset or unreadable
+    pub async fn get_pref_bool(&self, key: &str, default: bool) -> Result<bool> {
+        self.get_pref_parsed(key, default).await
+    }
+
+    /// An integer preference, or `default` when unset or unreadable
+    pub async fn get_pref_int(&self, key: &str, default: i64) -> Result<i64> {
+        self.get_pref_parsed(key, default).await
+    }
+
+    async fn get_pref_parsed<T: std::str::FromStr>(&self, key: &str, default: T) -> Result<T> {
+        match self.get_preference(key).await? {
+            None => Ok(default),
+            Some(value) => Ok(value.parse().unwrap_or_else(|_| {
+                log::warn!("Ignoring unreadable value {:?} for preference {}", value, key);
+                default
+            })),
+        }
+    }
+
+    pub async fn set_pref_string(&self, key: &str, value: &str) -> Result<()> {
+        self.set_preference(key, value).await
+    }
+
+    pub async fn set_pref_bool(&self, key: &str, value: bool) -> Result<()> {
+        self.set_preference(key, if value { "true" } else { "false" }).await
+    }
+
+    pub async fn set_pref_int(&self, key: &str, value: i64) -> Result<()> {
+        self.set_preference(key, &value.to_string()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_preferences_are_validated() {
+        for (key, value) in [
+            ("theme", "dark"),
+            ("currency", "EUR"),
+            ("language", "en"),
+            ("language", "pt-BR"),
+            ("language", "zh-Hant-TW"),
+            ("developer_mode", "false"),
+            ("app_lock_minutes", "0"),
+            ("network_enabled:eip155:1", "anything"),
+        ] {
+            assert!(validate_preference(key, value).is_ok(), "{} = {}", key, value);
+        }
+        for (key, value) in [
+            ("theme", "blue"),
+            ("currency", "usd"),
+            ("currency", "DOLLAR"),
+            ("language", ""),
+            ("language", "english_us"),
+            ("language", "en-"),
+            ("developer_mode", "yes"),
+            ("app_lock_minutes", "-5"),
+        ] {
+            assert!(validate_preference(key, value).is_err(), "{} = {}", key, value);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_typed_accessors_fall_back_to_defaults() {
+        let db = Database::new_in_memory().await.unwrap();
+
+        assert!(!db.get_pref_bool("developer_mode", false).await.unwrap());
+        db.set_pref_bool("developer_mode", true).await.unwrap();
+        assert!(db.get_pref_bool("developer_mode", false).await.unwrap());
+
+        assert_eq!(db.get_pref_int("app_lock_minutes", 15).await.unwrap(), 15);
+        db.set_pref_int("app_lock_minutes", 5).await.unwrap();
+        assert_eq!(db.get_pref_int("app_lock_minutes", 15).await.unwrap(), 5);
+        assert!(db.set_pref_int("app_lock_minutes", -1).await.is_err());
+        assert_eq!(db.get_pref_int("app_lock_minutes", 15).await.unwrap(), 5);
+
+        // Unknown keys are stored unchecked; an unreadable value reads as the default
+        db.set_pref_string("event_history_size", "lots").await.unwrap();
+        assert_eq!(db.get_pref_int("event_history_size", 200).await.unwrap(), 200);
+        assert_eq!(db.get_pref_string("theme", "system").await.unwrap(), "system");
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_announced_and_listed() {
+        let db = Database::new_in_memory().await.unwrap();
+        let mut changes = db.subscribe_preference_changes();
+
+        db.set_preference("theme", "dark").await.unwrap();
+        assert!(db.set_preference("theme", "neon").await.is_err());
+        db.set_preference("custom_flag", "on").await.unwrap();
+
+        assert_eq!(
+            changes.recv().await.unwrap(),
+            Preference { key: "theme".to_string(), value: "dark".to_string(), known: true }
+        );
+        assert_eq!(
+            changes.recv().await.unwrap(),
+            Preference { key: "custom_flag".to_string(), value: "on".to_string(), known: false }
+        );
+        assert!(changes.try_recv().is_err());
+
+        let all = db.get_all_preferences().await.unwrap();
+        let theme = all.iter().find(|p| p.key == "theme").unwrap();
+        assert_eq!(theme.value, "dark");
+        assert!(theme.known);
+        assert!(!all.iter().find(|p| p.key == "custom_flag").unwrap().known);
+        // Schema defaults are listed too
+        assert!(all.iter().any(|p| p.key == "currency" && p.value == "USD"));
+    }
+}
@@ -0,0 +1,139 @@
+//! Device-encrypted secure notes
+//!
+//! Only ciphertext produced by the device's CipherKeyValue and the metadata
+//! needed to decrypt it again are stored here; encrypting and decrypting is
+//! done in `vault_core::secure_notes`. Notes belong to a wallet fingerprint
+//! rather than a device, so the same seed on a replacement device reads them.
+
+use rusqlite::OptionalExtension;
+use crate::errors::{DatabaseError, Result};
+use crate::types::SecureNote;
+use crate::Database;
+
+/// Longest title; it is shown on the device screen when the note is read
+pub const MAX_TITLE_CHARS: usize = 48;
+
+const NOTE_COLUMNS: &str = "id, wallet_fingerprint, title, plaintext_bytes, created_on_device, created_at";
+
+fn note_from_row(row: &rusqlite::Row) -> rusqlite::Result<SecureNote> {
+    Ok(SecureNote {
+        id: row.get(0)?,
+        wallet_fingerprint: row.get(1)?,
+        title: row.get(2)?,
+        plaintext_bytes: row.get(3)?,
+        created_on_device: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+/// Trim a title and check it fits the device screen
+pub fn validate_title(title: &str) -> Result<String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err(DatabaseError::Validation("A secure note needs a title".to_string()));
+    }
+    if title.chars().count() > MAX_TITLE_CHARS || title.chars().any(|c| c.is_control()) {
+        return Err(DatabaseError::Validation(format!(
+            "Note titles are single lines of at most {} characters",
+            MAX_TITLE_CHARS
+        )));
+    }
+    Ok(title.to_string())
+}
+
+impl Database {
+    /// Store an encrypted note under `id`, which the caller chose at random
+    /// and used for the key path. Fails if the id is taken.
+    pub async fn add_secure_note(
+        &self,
+        id: u32,
+        wallet_fingerprint: &str,
+        title: &str,
+        ciphertext: &[u8],
+        plaintext_bytes: u32,
+        created_on_device: Option<&str>,
+    ) -> Result<SecureNote> {
+        let title = validate_title(title)?;
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            let inserted = conn.execute(
+                "INSERT OR IGNORE INTO secure_notes
+                    (id, wallet_fingerprint, title, ciphertext, plaintext_bytes, created_on_device, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![id, wallet_fingerprint, title, ciphertext, plaintext_bytes, created_on_device, now],
+            )?;
+            if inserted == 0 {
+                return Err(DatabaseError::Validation(format!("Secure note id {} is already in use", id)));
+            }
+            Ok(SecureNote {
+                id,
+                wallet_fingerprint: wallet_fingerprint.to_string(),
+                title,
+                plaintext_bytes,
+                created_on_device: created_on_device.map(str::to_string),
+                created_at: now,
+            })
+        }).await
+    }
+
+    /// Note metadata, newest first, optionally for one wallet
+    pub async fn list_secure_notes(&self, wallet_fingerprint: Option<&str>) -> Result<Vec<SecureNote>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM secure_notes WHERE ?1 IS NULL OR wallet_fingerprint = ?1
+                 ORDER BY created_at DESC, id",
+                NOTE_COLUMNS
+            ))?;
+            let notes = stmt
+                .query_map([wallet_fingerprint], note_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(notes)
+        }).await
+    }
+
+    /// A note's metadata and ciphertext, for decryption on the device
+    pub async fn get_secure_note_ciphertext(&self, id: u32) -> Result<Option<(SecureNote, Vec<u8>)>> {
+        self.with_connection(|conn| {
+            Ok(conn.query_row(
+                &format!("SELECT {}, ciphertext FROM secure_notes WHERE id = ?1", NOTE_COLUMNS),
+                [id],
+                |row| Ok((note_from_row(row)?, row.get(6)?)),
+            ).optional()?)
+        }).await
+    }
+
+    /// Delete a note; returns false if there was none
+    pub async fn delete_secure_note(&self, id: u32) -> Result<bool> {
+        self.with_connection(|conn| {
+            Ok(conn.execute("DELETE FROM secure_notes WHERE id = ?1", [id])? > 0)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_secure_note_metadata() {
+        let db = Database::new_in_memory().await.unwrap();
+        let ciphertext = vec![0x5a; 32];
+        let note = db.add_secure_note(7, "fp1", "  Exchange 2FA codes ", &ciphertext, 20, Some("dev1")).await.unwrap();
+        assert_eq!(note.title, "Exchange 2FA codes");
+        db.add_secure_note(8, "fp2", "Passphrase hint", &ciphertext, 9, None).await.unwrap();
+
+        assert!(db.add_secure_note(7, "fp1", "Duplicate", &ciphertext, 1, None).await.is_err());
+        assert!(db.add_secure_note(9, "fp1", "   ", &ciphertext, 1, None).await.is_err());
+        assert!(db.add_secure_note(9, "fp1", "two\nlines", &ciphertext, 1, None).await.is_err());
+
+        assert_eq!(db.list_secure_notes(Some("fp1")).await.unwrap(), vec![note.clone()]);
+        assert_eq!(db.list_secure_notes(None).await.unwrap().len(), 2);
+
+        let (stored, stored_ciphertext) = db.get_secure_note_ciphertext(7).await.unwrap().unwrap();
+        assert_eq!((stored, stored_ciphertext), (note, ciphertext));
+
+        assert!(db.delete_secure_note(7).await.unwrap());
+        assert!(!db.delete_secure_note(7).await.unwrap());
+        assert!(db.get_secure_note_ciphertext(7).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,205 @@
+use crate::errors::{DatabaseError, Result};
+use crate::types::{OriginSpending, OriginSpendingLimit, SigningAuditInput};
+use crate::Database;
+use rusqlite::OptionalExtension;
+
+/// Audit statuses whose value counts as spent
+const SPENT_STATUSES: &str = "('signed', 'broadcast')";
+/// Statuses counted against a daily limit: requests still waiting on the
+/// device count too, so concurrent requests cannot each pass on their own
+const LIMIT_STATUSES: &str = "('requested', 'signed', 'broadcast')";
+
+impl Database {
+    /// Record a signing request before the device is asked; returns the audit id
+    pub async fn record_signing_request(&self, request: &SigningAuditInput) -> Result<i64> {
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO signing_audit (timestamp, origin, device_id, wallet_fingerprint, caip, value_usd, status)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'requested')",
+                rusqlite::params![
+                    now, request.origin, request.device_id, request.wallet_fingerprint, request.caip, request.value_usd,
+                ],
+            )?;
+            Ok(conn.last_insert_rowid())
+        }).await
+    }
+
+    /// Move an audit entry to 'signed', 'broadcast', 'rejected' or 'failed'
+    pub async fn update_signing_status(&self, audit_id: i64, status: &str, txid: Option<&str>) -> Result<()> {
+        self.with_connection(|conn| {
+            let updated = conn.execute(
+                "UPDATE signing_audit SET status = ?2, txid = COALESCE(?3, txid) WHERE id = ?1",
+                rusqlite::params![audit_id, status, txid],
+            )?;
+            if updated == 0 {
+                return Err(DatabaseError::InvalidData(format!("Unknown signing audit entry {}", audit_id)));
+            }
+            Ok(())
+        }).await
+    }
+
+    /// Signed and broadcast value per origin over the last `window_days`
+    pub async fn get_spending_by_origin(&self, window_days: i64) -> Result<Vec<OriginSpending>> {
+        let since = Self::current_timestamp() - window_days * 86_400;
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT origin, COALESCE(SUM(value_usd), 0), COUNT(*), COUNT(*) - COUNT(value_usd)
+                 FROM signing_audit
+                 WHERE timestamp >= ?1 AND status IN {}
+                 GROUP BY origin ORDER BY 2 DESC",
+                SPENT_STATUSES
+            ))?;
+            let spending = stmt
+                .query_map([since], |row| Ok(OriginSpending {
+                    origin: row.get(0)?,
+                    total_usd: row.get(1)?,
+                    signed_count: row.get(2)?,
+                    unpriced_count: row.get(3)?,
+                }))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(spending)
+        }).await
+    }
+
+    /// Value requested or signed for one origin since `since` (epoch seconds),
+    /// leaving out the audit entry `excluding` (the request being checked)
+    pub async fn get_origin_spent_since(&self, origin: &str, since: i64, excluding: Option<i64>) -> Result<f64> {
+        self.with_connection(|conn| {
+            Ok(conn.query_row(
+                &format!(
+                    "SELECT COALESCE(SUM(value_usd), 0) FROM signing_audit
+                     WHERE origin = ?1 AND timestamp >= ?2 AND id IS NOT ?3 AND status IN {}",
+                    LIMIT_STATUSES
+                ),
+                rusqlite::params![origin, since, excluding],
+                |row| row.get(0),
+            )?)
+        }).await
+    }
+
+    /// Move the signed audit entries for `txid` to 'broadcast'; returns how many
+    pub async fn mark_signing_broadcast(&self, txid: &str) -> Result<usize> {
+        self.with_connection(|conn| {
+            Ok(conn.execute(
+                "UPDATE signing_audit SET status = 'broadcast' WHERE lower(txid) = lower(?1) AND status = 'signed'",
+                [txid],
+            )?)
+        }).await
+    }
+
+    /// Set (or with None, remove) an origin's soft daily limit
+    pub async fn set_origin_spending_limit(&self, origin: &str, daily_limit_usd: Option<f64>) -> Result<()> {
+        if daily_limit_usd.is_some_and(|limit| !limit.is_finite() || limit < 0.0) {
+            return Err(DatabaseError::Validation("Daily limit must be a non-negative amount".to_string()));
+        }
+        let now = Self::current_timestamp();
+        self.with_connection(|conn| {
+            match daily_limit_usd {
+                Some(limit) => conn.execute(
+                    "INSERT INTO signing_origin_limits (origin, daily_limit_usd, updated_at) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(origin) DO UPDATE SET daily_limit_usd = excluded.daily_limit_usd, updated_at = excluded.updated_at",
+                    rusqlite::params![origin, limit, now],
+                )?,
+                None => conn.execute("DELETE FROM signing_origin_limits WHERE origin = ?1", [origin])?,
+            };
+            Ok(())
+        }).await
+    }
+
+    /// Limit configured for an origin, if any
+    pub async fn get_origin_spending_limit(&self, origin: &str) -> Result<Option<f64>> {
+        self.with_connection(|conn| {
+            Ok(conn.query_row(
+                "SELECT daily_limit_usd FROM signing_origin_limits WHERE origin = ?1",
+                [origin],
+                |row| row.get(0),
+            ).optional()?)
+        }).await
+    }
+
+    /// All configured origin limits
+    pub async fn list_origin_spending_limits(&self) -> Result<Vec<OriginSpendingLimit>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT origin, daily_limit_usd, updated_at FROM signing_origin_limits ORDER BY origin"
+            )?;
+            let limits = stmt
+                .query_map([], |row| Ok(OriginSpendingLimit {
+                    origin: row.get(0)?,
+                    daily_limit_usd: row.get(1)?,
+                    updated_at: row.get(2)?,
+                }))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(limits)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(origin: &str, value_usd: Option<f64>) -> SigningAuditInput {
+        SigningAuditInput {
+            origin: origin.to_string(),
+            device_id: "dev1".to_string(),
+            wallet_fingerprint: "aaaa0001".to_string(),
+            caip: "eip155:1/slip44:60".to_string(),
+            value_usd,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spending_aggregates_signed_value_per_origin() {
+        let db = Database::new_in_memory().await.unwrap();
+
+        for (origin, value, status) in [
+            ("main_window", Some(100.0), "signed"),
+            ("rest_api:tok1", Some(40.0), "signed"),
+            ("rest_api:tok1", Some(60.0), "broadcast"),
+            ("rest_api:tok1", None, "signed"),
+            ("rest_api:tok1", Some(1000.0), "rejected"),
+        ] {
+            let id = db.record_signing_request(&request(origin, value)).await.unwrap();
+            db.update_signing_status(id, status, Some("0xabc")).await.unwrap();
+        }
+        // Still waiting on the device: not spent yet, but held against the limit
+        let waiting = db.record_signing_request(&request("rest_api:tok1", Some(5.0))).await.unwrap();
+
+        let spending = db.get_spending_by_origin(1).await.unwrap();
+        assert_eq!(spending.len(), 2);
+        let api = spending.iter().find(|s| s.origin == "rest_api:tok1").unwrap();
+        assert_eq!(api.total_usd, 100.0);
+        assert_eq!(api.signed_count, 3);
+        assert_eq!(api.unpriced_count, 1);
+        assert_eq!(db.get_origin_spent_since("rest_api:tok1", 0, None).await.unwrap(), 105.0);
+        assert_eq!(db.get_origin_spent_since("rest_api:tok1", 0, Some(waiting)).await.unwrap(), 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_marks_signed_entries() {
+        let db = Database::new_in_memory().await.unwrap();
+        let signed = db.record_signing_request(&request("main_window", Some(10.0))).await.unwrap();
+        db.update_signing_status(signed, "signed", Some("0xABC")).await.unwrap();
+        let failed = db.record_signing_request(&request("main_window", Some(10.0))).await.unwrap();
+        db.update_signing_status(failed, "failed", Some("0xabc")).await.unwrap();
+
+        assert_eq!(db.mark_signing_broadcast("0xabc").await.unwrap(), 1);
+        assert_eq!(db.mark_signing_broadcast("0xabc").await.unwrap(), 0);
+        assert_eq!(db.get_spending_by_origin(1).await.unwrap()[0].total_usd, 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_origin_limits_roundtrip() {
+        let db = Database::new_in_memory().await.unwrap();
+        assert!(db.set_origin_spending_limit("rest_api:tok1", Some(-1.0)).await.is_err());
+
+        db.set_origin_spending_limit("rest_api:tok1", Some(250.0)).await.unwrap();
+        assert_eq!(db.get_origin_spending_limit("rest_api:tok1").await.unwrap(), Some(250.0));
+        assert_eq!(db.list_origin_spending_limits().await.unwrap().len(), 1);
+
+        db.set_origin_spending_limit("rest_api:tok1", None).await.unwrap();
+        assert_eq!(db.get_origin_spending_limit("rest_api:tok1").await.unwrap(), None);
+    }
+}
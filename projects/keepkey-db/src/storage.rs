@@ -0,0 +1,388 @@
+use crate::errors::{DatabaseError, Result};
+use crate::Database;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+/// Default number of days of portfolio_history to keep
+pub const DEFAULT_HISTORY_RETENTION_DAYS: i64 = 365;
+/// Default number of transaction_cache rows kept per device
+pub const DEFAULT_MAX_TRANSACTIONS_PER_DEVICE: i64 = 5000;
+/// snapshot_json payloads larger than this are stored zstd-compressed
+pub const SNAPSHOT_COMPRESSION_THRESHOLD: usize = 4 * 1024;
+
+const SNAPSHOT_ZSTD_LEVEL: i32 = 3;
+
+/// Size information for a single table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct TableStats {
+    pub name: String,
+    pub row_count: i64,
+    /// Approximate on-disk size including indexes (None when dbstat is unavailable)
+    pub size_bytes: Option<i64>,
+}
+
+/// Overall database storage statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct DatabaseStats {
+    pub path: String,
+    pub file_size_bytes: u64,
+    pub wal_size_bytes: u64,
+    pub page_size: i64,
+    pub page_count: i64,
+    pub freelist_count: i64,
+    /// Bytes that a VACUUM could return to the filesystem
+    pub reclaimable_bytes: i64,
+    pub tables: Vec<TableStats>,
+}
+
+/// Retention policies applied before compaction and at startup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct StoragePolicy {
+    pub history_retention_days: i64,
+    pub max_transactions_per_device: i64,
+}
+
+impl Default for StoragePolicy {
+    fn default() -> Self {
+        Self {
+            history_retention_days: DEFAULT_HISTORY_RETENTION_DAYS,
+            max_transactions_per_device: DEFAULT_MAX_TRANSACTIONS_PER_DEVICE,
+        }
+    }
+}
+
+/// Rows removed by retention policies
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct PruneReport {
+    pub history_rows_pruned: usize,
+    pub transactions_pruned: usize,
+}
+
+/// Compaction phases reported to the progress callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompactionPhase {
+    Pruning,
+    Checkpointing,
+    Vacuuming,
+    Finished,
+}
+
+/// Result of a compaction run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CompactionReport {
+    pub size_before_bytes: u64,
+    pub size_after_bytes: u64,
+    pub pruned: PruneReport,
+}
+
+impl Database {
+    /// Load the storage policy from preferences, falling back to defaults
+    pub async fn get_storage_policy(&self) -> Result<StoragePolicy> {
+        let defaults = StoragePolicy::default();
+        let retention = self.get_preference("history_retention_days").await?;
+        let max_txs = self.get_preference("max_transactions_per_device").await?;
+
+        Ok(StoragePolicy {
+            history_retention_days: retention
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.history_retention_days),
+            max_transactions_per_device: max_txs
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.max_transactions_per_device),
+        })
+    }
+
+    /// Per-table row counts and approximate sizes
+    pub async fn get_database_stats(&self) -> Result<DatabaseStats> {
+        let path = self.path();
+
+        let mut stats = self.with_connection(|conn| {
+            let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+            let page_count: i64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+            let freelist_count: i64 = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+
+            let mut stmt = conn.prepare(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+            )?;
+            let names = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let sizes = table_sizes(conn);
+            let mut tables = Vec::with_capacity(names.len());
+            for name in names {
+                let row_count: i64 = conn.query_row(
+                    &format!("SELECT COUNT(*) FROM \"{}\"", name.replace('"', "\"\"")),
+                    [],
+                    |row| row.get(0),
+                )?;
+                let size_bytes = sizes.as_ref().map(|s| {
+                    s.iter().filter(|(table, _)| table == &name).map(|(_, size)| *size).sum()
+                });
+                tables.push(TableStats { name, row_count, size_bytes });
+            }
+
+            Ok(DatabaseStats {
+                path: path.display().to_string(),
+                file_size_bytes: 0,
+                wal_size_bytes: 0,
+                page_size,
+                page_count,
+                freelist_count,
+                reclaimable_bytes: page_size * freelist_count,
+                tables,
+            })
+        }).await?;
+
+        stats.file_size_bytes = file_size(&path);
+        stats.wal_size_bytes = file_size(&wal_path(&path));
+        Ok(stats)
+    }
+
+    /// Prune portfolio_history beyond the retention window and cap transaction_cache per device
+    pub async fn apply_storage_policy(&self, policy: &StoragePolicy) -> Result<PruneReport> {
+        let cutoff = Self::current_timestamp() - policy.history_retention_days * 86_400;
+        let max_txs = policy.max_transactions_per_device;
+
+        self.transaction(|conn| {
+            let history_rows_pruned = conn.execute(
+                "DELETE FROM portfolio_history WHERE timestamp < ?1",
+                [cutoff],
+            )?;
+
+            // Keep the newest N rows per device. Tax lots are replayed from the
+            // full history, so nothing is pruned while lot tracking is on.
+            let transactions_pruned = if crate::tax_lots::tracking_enabled(conn)? {
+                0
+            } else {
+                conn.execute(
+                    "DELETE FROM transaction_cache WHERE id IN (
+                        SELECT id FROM (
+                            SELECT id, ROW_NUMBER() OVER (
+                                PARTITION BY device_id ORDER BY timestamp DESC, id DESC
+                            ) AS rn
+                            FROM transaction_cache
+                        ) WHERE rn > ?1
+                    )",
+                    [max_txs],
+                )?
+            };
+
+            if history_rows_pruned > 0 || transactions_pruned > 0 {
+                log::info!(
+                    "Storage policy pruned {} history rows and {} cached transactions",
+                    history_rows_pruned, transactions_pruned
+                );
+            }
+
+            Ok(PruneReport { history_rows_pruned, transactions_pruned })
+        }).await
+    }
+
+    /// Prune, checkpoint the WAL and VACUUM the database.
+    ///
+    /// VACUUM rebuilds into a temporary file and commits through the journal,
+    /// so a crash mid-way leaves the original database intact. The connection
+    /// lock is held throughout, which pauses every other database user.
+    pub async fn compact_database<F>(&self, policy: &StoragePolicy, mut progress: F) -> Result<CompactionReport>
+    where
+        F: FnMut(CompactionPhase) + Send,
+    {
+        let path = self.path();
+        let size_before_bytes = file_size(&path) + file_size(&wal_path(&path));
+
+        progress(CompactionPhase::Pruning);
+        let pruned = self.apply_storage_policy(policy).await?;
+
+        self.with_connection(|conn| {
+            progress(CompactionPhase::Checkpointing);
+            checkpoint(conn)?;
+
+            progress(CompactionPhase::Vacuuming);
+            conn.execute_batch("VACUUM")?;
+
+            // VACUUM in WAL mode writes the rebuilt pages through the WAL
+            checkpoint(conn)?;
+            Ok(())
+        }).await?;
+
+        progress(CompactionPhase::Finished);
+
+        let size_after_bytes = file_size(&path) + file_size(&wal_path(&path));
+        log::info!("Compacted database: {} -> {} bytes", size_before_bytes, size_after_bytes);
+
+        Ok(CompactionReport { size_before_bytes, size_after_bytes, pruned })
+    }
+}
+
+/// Value stored in snapshot_json: small payloads as TEXT, larger ones as a
+/// zstd-compressed BLOB
+pub(crate) fn encode_snapshot(json: &str) -> Result<Value> {
+    if json.len() <= SNAPSHOT_COMPRESSION_THRESHOLD {
+        return Ok(Value::Text(json.to_string()));
+    }
+    Ok(Value::Blob(zstd::encode_all(json.as_bytes(), SNAPSHOT_ZSTD_LEVEL)?))
+}
+
+/// Read back a snapshot_json value written by `encode_snapshot`; rows from
+/// before compression are plain TEXT
+pub(crate) fn decode_snapshot(value: Value) -> Result<Option<String>> {
+    let bytes = match value {
+        Value::Null => return Ok(None),
+        Value::Text(text) => return Ok(Some(text)),
+        Value::Blob(blob) => zstd::decode_all(blob.as_slice())?,
+        other => {
+            return Err(DatabaseError::InvalidData(format!("snapshot_json holds a {:?}", other.data_type())))
+        }
+    };
+    String::from_utf8(bytes)
+        .map(Some)
+        .map_err(|e| DatabaseError::InvalidData(format!("Compressed snapshot is not UTF-8: {}", e)))
+}
+
+/// Flush the WAL into the main file and truncate it
+fn checkpoint(conn: &Connection) -> Result<()> {
+    // Returns (busy, log frames, checkpointed frames); in-memory databases report -1
+    let (busy, _, _): (i64, i64, i64) =
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?;
+    if busy != 0 {
+        log::warn!("WAL checkpoint could not complete: database busy");
+    }
+    Ok(())
+}
+
+/// Sizes per table (indexes attributed to their table) via the dbstat virtual table
+fn table_sizes(conn: &Connection) -> Option<Vec<(String, i64)>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT COALESCE(m.tbl_name, s.name), SUM(s.pgsize)
+             FROM dbstat s LEFT JOIN sqlite_master m ON m.name = s.name
+             GROUP BY COALESCE(m.tbl_name, s.name)",
+        )
+        .ok()?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+        .ok()?;
+    rows.collect::<rusqlite::Result<Vec<_>>>().ok()
+}
+
+fn wal_path(path: &std::path::Path) -> std::path::PathBuf {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    wal.into()
+}
+
+fn file_size(path: &std::path::Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_stats_report_tables() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.register_device("dev1", None, None).await.unwrap();
+
+        let stats = db.get_database_stats().await.unwrap();
+        let devices = stats.tables.iter().find(|t| t.name == "devices").unwrap();
+        assert_eq!(devices.row_count, 1);
+        assert!(stats.page_count > 0);
+        assert!(devices.size_bytes.unwrap_or(0) > 0);
+    }
+
+    #[tokio::test]
+    async fn test_policy_prunes_history_and_caps_transactions() {
+        let db = Database::new_in_memory().await.unwrap();
+        let now = Database::current_timestamp();
+
+        db.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO portfolio_history (device_id, timestamp, total_value_usd) VALUES ('dev1', ?1, '1'), ('dev1', ?2, '2')",
+                [now - 400 * 86_400, now],
+            )?;
+            for i in 0..10 {
+                conn.execute(
+                    "INSERT INTO transaction_cache (device_id, txid, caip, type, amount, timestamp) VALUES ('dev1', ?1, 'eip155:1/slip44:60', 'send', '1', ?2)",
+                    rusqlite::params![format!("tx{}", i), now - i],
+                )?;
+            }
+            Ok(())
+        }).await.unwrap();
+
+        let policy = StoragePolicy { history_retention_days: 365, max_transactions_per_device: 4 };
+        let report = db.apply_storage_policy(&policy).await.unwrap();
+        assert_eq!(report.history_rows_pruned, 1);
+        assert_eq!(report.transactions_pruned, 6);
+
+        // The newest transactions survive
+        let newest: String = db.with_connection(|conn| {
+            Ok(conn.query_row("SELECT txid FROM transaction_cache ORDER BY timestamp DESC LIMIT 1", [], |row| row.get(0))?)
+        }).await.unwrap();
+        assert_eq!(newest, "tx0");
+    }
+
+    #[tokio::test]
+    async fn test_compaction_shrinks_and_stays_readable() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Database::open_at_path(db_path.clone()).await.unwrap();
+        let now = Database::current_timestamp();
+
+        // Seed ~50MB of snapshots, most of them older than the retention window
+        let blob = "x".repeat(64 * 1024);
+        db.transaction(|conn| {
+            for i in 0..800i64 {
+                let ts = if i % 10 == 0 { now - i } else { now - 1000 * 86_400 };
+                conn.execute(
+                    "INSERT INTO portfolio_history (device_id, timestamp, total_value_usd, snapshot_json) VALUES ('dev1', ?1, ?2, ?3)",
+                    rusqlite::params![ts, i.to_string(), blob],
+                )?;
+            }
+            Ok(())
+        }).await.unwrap();
+        db.with_connection(checkpoint).await.unwrap();
+
+        let before = db.get_database_stats().await.unwrap();
+        assert!(before.file_size_bytes > 45 * 1024 * 1024);
+
+        let mut phases = Vec::new();
+        let report = db
+            .compact_database(&StoragePolicy::default(), |phase| phases.push(phase))
+            .await
+            .unwrap();
+
+        assert_eq!(report.pruned.history_rows_pruned, 720);
+        assert!(report.size_after_bytes < report.size_before_bytes / 5);
+        assert_eq!(phases.last(), Some(&CompactionPhase::Finished));
+
+        // Reopen from disk and make sure every surviving snapshot is intact
+        drop(db);
+        let db = Database::open_at_path(db_path).await.unwrap();
+        let (count, intact): (i64, i64) = db.with_connection(|conn| {
+            Ok(conn.query_row(
+                "SELECT COUNT(*), SUM(LENGTH(snapshot_json) = 65536) FROM portfolio_history",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?)
+        }).await.unwrap();
+        assert_eq!(count, 80);
+        assert_eq!(intact, 80);
+
+        let integrity: String = db.with_connection(|conn| {
+            Ok(conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?)
+        }).await.unwrap();
+        assert_eq!(integrity, "ok");
+    }
+}
@@ -0,0 +1,622 @@
+//! Cost-basis lot tracking for received and spent assets
+//!
+//! Bookkeeping only, not tax advice: figures depend on the cached history
+//! and on the USD value recorded with each transaction, and nothing here
+//! knows any jurisdiction's rules.
+//!
+//! Lots are derived data. When tracking is enabled, every recorded
+//! transaction replays its asset's history from `transaction_cache` in time
+//! order: receives open acquisition lots priced from `amount_usd`, sends
+//! consume lots in the configured order (FIFO by default, HIFO optional) and
+//! store one disposal per lot slice with its realized gain. A transaction
+//! that appears as a send in one of the user's wallets and a receive in
+//! another is a self-transfer; it moves the consumed lots, keeping their
+//! acquisition time and cost, instead of realizing a gain. Only `send` and
+//! `receive` rows are counted, and network fees are not treated as
+//! disposals.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use rusqlite::{Connection, OptionalExtension};
+use crate::errors::Result;
+use crate::types::{Disposal, LotMethod, RealizedGains, TaxLotRebuild, TransactionCache};
+use crate::Database;
+
+/// Preference key: "true" turns lot tracking on
+pub const TAX_LOT_TRACKING_PREF: &str = "tax_lot_tracking";
+
+/// Preference key: "fifo" (default) or "hifo"
+pub const TAX_LOT_METHOD_PREF: &str = "tax_lot_method";
+
+/// Amounts below this are treated as fully consumed
+const EPSILON: f64 = 1e-12;
+
+/// Transactions that never happened on chain
+const VOID_STATUSES: &str = "('failed', 'reorged')";
+
+const DISPOSAL_COLUMNS: &str =
+    "lot_id, device_id, wallet_fingerprint, caip, txid, disposed_at, acquired_at, amount,
+     proceeds_usd, cost_basis_usd, gain_usd, method";
+
+fn disposal_from_row(row: &rusqlite::Row) -> rusqlite::Result<Disposal> {
+    Ok(Disposal {
+        lot_id: row.get(0)?,
+        device_id: row.get(1)?,
+        wallet_fingerprint: row.get(2)?,
+        caip: row.get(3)?,
+        txid: row.get(4)?,
+        disposed_at: row.get(5)?,
+        acquired_at: row.get(6)?,
+        amount: row.get(7)?,
+        proceeds_usd: row.get(8)?,
+        cost_basis_usd: row.get(9)?,
+        gain_usd: row.get(10)?,
+        method: LotMethod::from_pref(row.get::<_, Option<String>>(11)?.as_deref()),
+    })
+}
+
+fn read_pref(conn: &Connection, key: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT val FROM meta WHERE key = ?1", [format!("pref_{}", key)], |row| row.get(0))
+        .optional()
+}
+
+pub(crate) fn tracking_enabled(conn: &Connection) -> rusqlite::Result<bool> {
+    Ok(read_pref(conn, TAX_LOT_TRACKING_PREF)?.as_deref() == Some("true"))
+}
+
+pub(crate) fn lot_method(conn: &Connection) -> rusqlite::Result<LotMethod> {
+    Ok(LotMethod::from_pref(read_pref(conn, TAX_LOT_METHOD_PREF)?.as_deref()))
+}
+
+/// A send or receive row as the replay sees it
+#[derive(Debug, Clone)]
+struct LotTx {
+    device_id: String,
+    wallet_fingerprint: String,
+    txid: String,
+    is_send: bool,
+    amount: f64,
+    amount_usd: Option<f64>,
+    timestamp: i64,
+}
+
+impl LotTx {
+    fn price_per_unit(&self) -> Option<f64> {
+        self.amount_usd.filter(|_| self.amount > EPSILON).map(|usd| usd / self.amount)
+    }
+
+    fn in_scope(&self, lot: &Lot) -> bool {
+        lot.device_id == self.device_id && lot.wallet_fingerprint == self.wallet_fingerprint
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Lot {
+    device_id: String,
+    wallet_fingerprint: String,
+    txid: String,
+    acquired_at: i64,
+    amount: f64,
+    remaining: f64,
+    cost_per_unit_usd: Option<f64>,
+}
+
+/// Part of a lot taken by one outgoing transaction; `lot` is None when the
+/// known lots did not cover the amount
+#[derive(Debug, Clone)]
+struct Slice {
+    lot: Option<usize>,
+    acquired_at: Option<i64>,
+    amount: f64,
+    cost_per_unit_usd: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+struct PendingDisposal {
+    tx: usize,
+    slice: Slice,
+}
+
+#[derive(Debug, Default)]
+struct Replay {
+    lots: Vec<Lot>,
+    disposals: Vec<PendingDisposal>,
+    self_transfers: i64,
+}
+
+impl Replay {
+    /// Take `amount` from the scope of `tx` in `method` order
+    fn consume(&mut self, tx: &LotTx, amount: f64, method: LotMethod) -> Vec<Slice> {
+        let mut candidates: Vec<usize> = (0..self.lots.len())
+            .filter(|&i| tx.in_scope(&self.lots[i]) && self.lots[i].remaining > EPSILON)
+            .collect();
+        candidates.sort_by(|&a, &b| {
+            let (a_lot, b_lot) = (&self.lots[a], &self.lots[b]);
+            let by_cost = match method {
+                LotMethod::Fifo => Ordering::Equal,
+                // Unpriced lots go last
+                LotMethod::Hifo => b_lot.cost_per_unit_usd.unwrap_or(f64::NEG_INFINITY)
+                    .total_cmp(&a_lot.cost_per_unit_usd.unwrap_or(f64::NEG_INFINITY)),
+            };
+            by_cost.then(a_lot.acquired_at.cmp(&b_lot.acquired_at)).then(a.cmp(&b))
+        });
+
+        let mut needed = amount;
+        let mut slices = Vec::new();
+        for i in candidates {
+            if needed <= EPSILON {
+                break;
+            }
+            let lot = &mut self.lots[i];
+            let taken = lot.remaining.min(needed);
+            lot.remaining -= taken;
+            needed -= taken;
+            slices.push(Slice { lot: Some(i), acquired_at: Some(lot.acquired_at), amount: taken, cost_per_unit_usd: lot.cost_per_unit_usd });
+        }
+        if needed > EPSILON {
+            slices.push(Slice { lot: None, acquired_at: None, amount: needed, cost_per_unit_usd: None });
+        }
+        slices
+    }
+
+    fn open_lot(&mut self, tx: &LotTx, acquired_at: i64, amount: f64, cost_per_unit_usd: Option<f64>) {
+        self.lots.push(Lot {
+            device_id: tx.device_id.clone(),
+            wallet_fingerprint: tx.wallet_fingerprint.clone(),
+            txid: tx.txid.clone(),
+            acquired_at,
+            amount,
+            remaining: amount,
+            cost_per_unit_usd,
+        });
+    }
+
+    /// Move lots from the sending wallets to the receiving ones. Anything sent
+    /// beyond what the user's wallets received left the wallet and is disposed;
+    /// anything received beyond what was sent is a new acquisition.
+    fn transfer(&mut self, txs: &[LotTx], group: &[usize], method: LotMethod) {
+        let mut pool: Vec<(usize, Slice)> = Vec::new();
+        for &i in group.iter().filter(|&&i| txs[i].is_send) {
+            for slice in self.consume(&txs[i], txs[i].amount, method) {
+                pool.push((i, slice));
+            }
+        }
+
+        let mut pool = pool.into_iter().peekable();
+        for &i in group.iter().filter(|&&i| !txs[i].is_send) {
+            let receive = &txs[i];
+            let mut needed = receive.amount;
+            while needed > EPSILON {
+                let Some((_, slice)) = pool.peek_mut() else { break };
+                let moved = slice.amount.min(needed);
+                self.open_lot(receive, slice.acquired_at.unwrap_or(receive.timestamp), moved, slice.cost_per_unit_usd);
+                slice.amount -= moved;
+                needed -= moved;
+                if slice.amount <= EPSILON {
+                    pool.next();
+                }
+            }
+            if needed > EPSILON {
+                self.open_lot(receive, receive.timestamp, needed, receive.price_per_unit());
+            }
+        }
+        for (tx, slice) in pool.filter(|(_, slice)| slice.amount > EPSILON) {
+            self.disposals.push(PendingDisposal { tx, slice });
+        }
+        self.self_transfers += 1;
+    }
+}
+
+/// Replay one asset's history. Receives sort before sends at the same
+/// timestamp so funds received and spent in the same block are available.
+fn replay(txs: &[LotTx], method: LotMethod) -> Replay {
+    let mut order: Vec<usize> = (0..txs.len()).collect();
+    order.sort_by(|&a, &b| {
+        (txs[a].timestamp, txs[a].is_send, &txs[a].txid).cmp(&(txs[b].timestamp, txs[b].is_send, &txs[b].txid))
+    });
+
+    let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+    for &i in &order {
+        groups.entry(txs[i].txid.as_str()).or_default().push(i);
+    }
+    let is_self_transfer = |txid: &str| {
+        let group = &groups[txid];
+        group.iter().any(|&i| txs[i].is_send) && group.iter().any(|&i| !txs[i].is_send)
+    };
+
+    let mut replay = Replay::default();
+    let mut transferred: HashSet<&str> = HashSet::new();
+    for &i in &order {
+        let tx = &txs[i];
+        if is_self_transfer(&tx.txid) {
+            if transferred.insert(tx.txid.as_str()) {
+                replay.transfer(txs, &groups[tx.txid.as_str()], method);
+            }
+        } else if tx.is_send {
+            for slice in replay.consume(tx, tx.amount, method) {
+                replay.disposals.push(PendingDisposal { tx: i, slice });
+            }
+        } else {
+            replay.open_lot(tx, tx.timestamp, tx.amount, tx.price_per_unit());
+        }
+    }
+    replay
+}
+
+fn load_lot_txs(conn: &Connection, caip: &str) -> rusqlite::Result<Vec<LotTx>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT device_id, wallet_fingerprint, txid, type, amount, amount_usd, timestamp
+         FROM transaction_cache
+         WHERE caip = ?1 AND type IN ('send', 'receive') AND COALESCE(status, '') NOT IN {}",
+        VOID_STATUSES
+    ))?;
+    let rows = stmt.query_map([caip], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, String>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, i64>(6)?,
+        ))
+    })?;
+
+    let mut txs = Vec::new();
+    for row in rows {
+        let (device_id, wallet_fingerprint, txid, kind, amount, amount_usd, timestamp) = row?;
+        let Some(amount) = amount.trim().parse::<f64>().ok().filter(|a| a.is_finite() && *a > 0.0) else {
+            continue;
+        };
+        txs.push(LotTx {
+            device_id,
+            wallet_fingerprint,
+            txid,
+            is_send: kind == "send",
+            amount,
+            amount_usd: amount_usd.and_then(|usd| usd.trim().parse().ok()),
+            timestamp,
+        });
+    }
+    Ok(txs)
+}
+
+/// Replace one asset's lots and disposals with a fresh replay; returns
+/// (lots, disposals, self-transfers)
+pub(crate) fn rebuild_asset(conn: &Connection, caip: &str, method: LotMethod) -> rusqlite::Result<(i64, i64, i64)> {
+    conn.execute("DELETE FROM disposals WHERE caip = ?1", [caip])?;
+    conn.execute("DELETE FROM tax_lots WHERE caip = ?1", [caip])?;
+
+    let txs = load_lot_txs(conn, caip)?;
+    let replay = replay(&txs, method);
+
+    let mut lot_ids = Vec::with_capacity(replay.lots.len());
+    for lot in &replay.lots {
+        conn.execute(
+            "INSERT INTO tax_lots (device_id, wallet_fingerprint, caip, txid, acquired_at, amount, remaining, cost_per_unit_usd)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            rusqlite::params![
+                lot.device_id, lot.wallet_fingerprint, caip, lot.txid, lot.acquired_at,
+                lot.amount, lot.remaining.max(0.0), lot.cost_per_unit_usd,
+            ],
+        )?;
+        lot_ids.push(conn.last_insert_rowid());
+    }
+
+    for disposal in &replay.disposals {
+        let tx = &txs[disposal.tx];
+        let slice = &disposal.slice;
+        let proceeds = tx.price_per_unit().map(|price| price * slice.amount);
+        let cost_basis = slice.cost_per_unit_usd.map(|cost| cost * slice.amount);
+        let gain = proceeds.zip(cost_basis).map(|(p, c)| p - c);
+        conn.execute(
+            "INSERT INTO disposals (lot_id, device_id, wallet_fingerprint, caip, txid, disposed_at, acquired_at,
+                                    amount, proceeds_usd, cost_basis_usd, gain_usd, method)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            rusqlite::params![
+                slice.lot.map(|i| lot_ids[i]), tx.device_id, tx.wallet_fingerprint, caip, tx.txid, tx.timestamp,
+                slice.acquired_at, slice.amount, proceeds, cost_basis, gain, method.as_str(),
+            ],
+        )?;
+    }
+
+    Ok((replay.lots.len() as i64, replay.disposals.len() as i64, replay.self_transfers))
+}
+
+fn rebuild_all(conn: &Connection) -> rusqlite::Result<TaxLotRebuild> {
+    let method = lot_method(conn)?;
+    conn.execute("DELETE FROM disposals", [])?;
+    conn.execute("DELETE FROM tax_lots", [])?;
+
+    let caips: Vec<String> = conn
+        .prepare("SELECT DISTINCT caip FROM transaction_cache WHERE type IN ('send', 'receive') ORDER BY caip")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut summary = TaxLotRebuild { method, assets: caips.len() as i64, lots: 0, disposals: 0, self_transfers: 0 };
+    for caip in &caips {
+        let (lots, disposals, self_transfers) = rebuild_asset(conn, caip, method)?;
+        summary.lots += lots;
+        summary.disposals += disposals;
+        summary.self_transfers += self_transfers;
+    }
+    Ok(summary)
+}
+
+impl Database {
+    pub async fn is_tax_lot_tracking_enabled(&self) -> Result<bool> {
+        self.with_connection(|conn| Ok(tracking_enabled(conn)?)).await
+    }
+
+    pub async fn get_tax_lot_method(&self) -> Result<LotMethod> {
+        self.with_connection(|conn| Ok(lot_method(conn)?)).await
+    }
+
+    /// Turn lot tracking on (replaying the whole history) or off (clearing
+    /// lots and disposals). Returns the replay summary when enabled.
+    pub async fn set_tax_lot_tracking(&self, enabled: bool) -> Result<Option<TaxLotRebuild>> {
+        self.transaction(move |tx| {
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, val) VALUES (?1, ?2)",
+                rusqlite::params![format!("pref_{}", TAX_LOT_TRACKING_PREF), if enabled { "true" } else { "false" }],
+            )?;
+            if enabled {
+                return Ok(Some(rebuild_all(tx)?));
+            }
+            tx.execute("DELETE FROM disposals", [])?;
+            tx.execute("DELETE FROM tax_lots", [])?;
+            Ok(None)
+        }).await
+    }
+
+    /// Change the lot consumption order and recompute every disposal with it
+    pub async fn set_tax_lot_method(&self, method: LotMethod) -> Result<TaxLotRebuild> {
+        self.transaction(move |tx| {
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, val) VALUES (?1, ?2)",
+                rusqlite::params![format!("pref_{}", TAX_LOT_METHOD_PREF), method.as_str()],
+            )?;
+            Ok(rebuild_all(tx)?)
+        }).await
+    }
+
+    /// Recompute all lots and disposals from the cached history with the
+    /// configured method
+    pub async fn rebuild_tax_lots(&self) -> Result<TaxLotRebuild> {
+        self.transaction(|tx| Ok(rebuild_all(tx)?)).await
+    }
+
+    /// Disposals dated in `year` (UTC) with their totals; bookkeeping figures,
+    /// not tax advice
+    pub async fn get_realized_gains(&self, year: i32) -> Result<RealizedGains> {
+        self.with_connection(move |conn| {
+            let method = lot_method(conn)?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM disposals
+                 WHERE strftime('%Y', disposed_at, 'unixepoch') = ?1
+                 ORDER BY disposed_at, id",
+                DISPOSAL_COLUMNS
+            ))?;
+            let disposals = stmt
+                .query_map([format!("{:04}", year)], disposal_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let mut gains = RealizedGains {
+                year,
+                method,
+                disposals: Vec::new(),
+                total_proceeds_usd: 0.0,
+                total_cost_basis_usd: 0.0,
+                total_gain_usd: 0.0,
+                incomplete_count: 0,
+            };
+            for disposal in &disposals {
+                match (disposal.proceeds_usd, disposal.cost_basis_usd, disposal.gain_usd) {
+                    (Some(proceeds), Some(cost), Some(gain)) => {
+                        gains.total_proceeds_usd += proceeds;
+                        gains.total_cost_basis_usd += cost;
+                        gains.total_gain_usd += gain;
+                    }
+                    _ => gains.incomplete_count += 1,
+                }
+            }
+            gains.disposals = disposals;
+            Ok(gains)
+        }).await
+    }
+
+    /// Cached transactions for the export, oldest first, optionally limited
+    /// to one year (UTC). Sends that consumed lots appear once per disposal;
+    /// everything else once with no disposal.
+    pub async fn get_transaction_export_rows(&self, year: Option<i32>) -> Result<Vec<(TransactionCache, Option<Disposal>)>> {
+        self.with_connection(move |conn| {
+            let year = year.map(|y| format!("{:04}", y));
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM transaction_cache
+                 WHERE ?1 IS NULL OR strftime('%Y', timestamp, 'unixepoch') = ?1
+                 ORDER BY timestamp, id",
+                crate::portfolio::TRANSACTION_COLUMNS
+            ))?;
+            let transactions = stmt
+                .query_map([&year], crate::portfolio::transaction_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let mut disposal_stmt = conn.prepare(&format!(
+                "SELECT {} FROM disposals
+                 WHERE device_id = ?1 AND wallet_fingerprint = ?2 AND txid = ?3 AND caip = ?4
+                 ORDER BY id",
+                DISPOSAL_COLUMNS
+            ))?;
+            let mut rows = Vec::with_capacity(transactions.len());
+            for tx in transactions {
+                let disposals = disposal_stmt
+                    .query_map([&tx.device_id, &tx.wallet_fingerprint, &tx.txid, &tx.caip], disposal_from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                if disposals.is_empty() {
+                    rows.push((tx, None));
+                } else {
+                    rows.extend(disposals.into_iter().map(|d| (tx.clone(), Some(d))));
+                }
+            }
+            Ok(rows)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+
+    fn lot_tx(txid: &str, is_send: bool, amount: f64, usd: f64, timestamp: i64) -> LotTx {
+        LotTx {
+            device_id: "dev1".to_string(),
+            wallet_fingerprint: "fp1".to_string(),
+            txid: txid.to_string(),
+            is_send,
+            amount,
+            amount_usd: Some(usd),
+            timestamp,
+        }
+    }
+
+    /// Three buys at 10k, 30k and 20k, then two sales at 40k; the first sale
+    /// splits a lot under either method
+    fn fixture() -> Vec<LotTx> {
+        vec![
+            lot_tx("buy1", false, 1.0, 10_000.0, 100),
+            lot_tx("buy2", false, 1.0, 30_000.0, 200),
+            lot_tx("buy3", false, 1.0, 20_000.0, 300),
+            lot_tx("sell1", true, 1.5, 60_000.0, 400),
+            lot_tx("sell2", true, 1.0, 40_000.0, 500),
+        ]
+    }
+
+    fn cost_of(disposal: &PendingDisposal) -> f64 {
+        disposal.slice.cost_per_unit_usd.unwrap() * disposal.slice.amount
+    }
+
+    #[test]
+    fn test_fifo_consumes_oldest_lots_with_partial_disposal() {
+        let txs = fixture();
+        let replay = replay(&txs, LotMethod::Fifo);
+
+        // sell1: all of buy1, half of buy2; sell2: rest of buy2, half of buy3
+        let slices: Vec<(&str, Option<&str>, f64)> = replay.disposals.iter()
+            .map(|d| (txs[d.tx].txid.as_str(), d.slice.lot.map(|i| replay.lots[i].txid.as_str()), d.slice.amount))
+            .collect();
+        assert_eq!(slices, vec![
+            ("sell1", Some("buy1"), 1.0),
+            ("sell1", Some("buy2"), 0.5),
+            ("sell2", Some("buy2"), 0.5),
+            ("sell2", Some("buy3"), 0.5),
+        ]);
+        let cost: f64 = replay.disposals.iter().map(cost_of).sum();
+        assert_eq!(cost, 10_000.0 + 15_000.0 + 15_000.0 + 10_000.0);
+        assert_eq!(replay.lots[2].remaining, 0.5);
+    }
+
+    #[test]
+    fn test_hifo_consumes_most_expensive_lots_first() {
+        let txs = fixture();
+        let replay = replay(&txs, LotMethod::Hifo);
+
+        // sell1: all of buy2 (30k), half of buy3 (20k); sell2: rest of buy3, half of buy1
+        let slices: Vec<(&str, Option<&str>, f64)> = replay.disposals.iter()
+            .map(|d| (txs[d.tx].txid.as_str(), d.slice.lot.map(|i| replay.lots[i].txid.as_str()), d.slice.amount))
+            .collect();
+        assert_eq!(slices, vec![
+            ("sell1", Some("buy2"), 1.0),
+            ("sell1", Some("buy3"), 0.5),
+            ("sell2", Some("buy3"), 0.5),
+            ("sell2", Some("buy1"), 0.5),
+        ]);
+        assert_eq!(replay.lots[0].remaining, 0.5);
+    }
+
+    #[test]
+    fn test_uncovered_sale_has_unknown_cost_basis() {
+        let txs = vec![
+            lot_tx("buy1", false, 1.0, 10_000.0, 100),
+            lot_tx("sell1", true, 1.25, 50_000.0, 200),
+        ];
+        let replay = replay(&txs, LotMethod::Fifo);
+        assert_eq!(replay.disposals.len(), 2);
+        assert_eq!(replay.disposals[1].slice.lot, None);
+        assert_eq!(replay.disposals[1].slice.amount, 0.25);
+    }
+
+    #[test]
+    fn test_self_transfer_moves_lots_without_realizing() {
+        let mut receive = lot_tx("move1", false, 1.0, 50_000.0, 300);
+        receive.wallet_fingerprint = "fp2".to_string();
+        let mut sale = lot_tx("sell1", true, 0.5, 30_000.0, 400);
+        sale.wallet_fingerprint = "fp2".to_string();
+        let txs = vec![
+            lot_tx("buy1", false, 1.0, 10_000.0, 100),
+            lot_tx("move1", true, 1.0, 50_000.0, 300),
+            receive,
+            sale,
+        ];
+        let replay = replay(&txs, LotMethod::Fifo);
+
+        assert_eq!(replay.self_transfers, 1);
+        assert_eq!(replay.disposals.len(), 1);
+        let moved = &replay.lots[1];
+        assert_eq!(moved.wallet_fingerprint, "fp2");
+        assert_eq!(moved.acquired_at, 100);
+        assert_eq!(moved.cost_per_unit_usd, Some(10_000.0));
+        // The sale from the second wallet uses the original cost
+        assert_eq!(cost_of(&replay.disposals[0]), 5_000.0);
+    }
+
+    #[tokio::test]
+    async fn test_realized_gains_follow_method_changes() {
+        let db = Database::new_in_memory().await.unwrap();
+        // 2024-01-01T00:00:00Z onwards
+        let base = 1_704_067_200;
+        for (i, tx) in fixture().into_iter().enumerate() {
+            db.upsert_transaction(&TransactionCache {
+                id: 0,
+                device_id: tx.device_id,
+                txid: tx.txid,
+                caip: BTC.to_string(),
+                transaction_type: if tx.is_send { "send" } else { "receive" }.to_string(),
+                amount: tx.amount.to_string(),
+                amount_usd: tx.amount_usd.map(|usd| usd.to_string()),
+                fee: None,
+                fee_usd: None,
+                from_address: None,
+                to_address: None,
+                timestamp: base + i as i64 * 86_400,
+                block_height: None,
+                status: Some("confirmed".to_string()),
+                metadata_json: None,
+                wallet_fingerprint: tx.wallet_fingerprint,
+                origin: None,
+            }).await.unwrap();
+        }
+        // Nothing is tracked until enabled
+        assert!(db.get_realized_gains(2024).await.unwrap().disposals.is_empty());
+
+        let summary = db.set_tax_lot_tracking(true).await.unwrap().unwrap();
+        assert_eq!((summary.lots, summary.disposals), (3, 4));
+        let fifo = db.get_realized_gains(2024).await.unwrap();
+        assert_eq!(fifo.method, LotMethod::Fifo);
+        assert_eq!(fifo.total_proceeds_usd, 100_000.0);
+        assert_eq!(fifo.total_gain_usd, 100_000.0 - 50_000.0);
+
+        db.set_tax_lot_method(LotMethod::Hifo).await.unwrap();
+        let hifo = db.get_realized_gains(2024).await.unwrap();
+        assert_eq!(hifo.total_gain_usd, 100_000.0 - 30_000.0 - 10_000.0 - 10_000.0 - 5_000.0);
+        assert!(db.get_realized_gains(2023).await.unwrap().disposals.is_empty());
+
+        let rows = db.get_transaction_export_rows(Some(2024)).await.unwrap();
+        assert_eq!(rows.len(), 3 + 4);
+        assert!(rows.iter().filter(|(tx, _)| tx.transaction_type == "send").all(|(_, d)| d.is_some()));
+
+        db.set_tax_lot_tracking(false).await.unwrap();
+        assert!(db.get_realized_gains(2024).await.unwrap().disposals.is_empty());
+    }
+}
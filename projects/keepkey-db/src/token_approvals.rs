@@ -0,0 +1,182 @@
+//! Cached ERC-20 allowances per EVM account
+//!
+//! A refresh replaces every row for the scanned owners on a network, so an
+//! allowance that was revoked or spent down to zero disappears from the cache.
+//! The scan time is kept per owner so an account with no approvals is not
+//! mistaken for one that was never scanned.
+
+use rusqlite::{Connection, OptionalExtension};
+use crate::errors::{DatabaseError, Result};
+use crate::types::TokenApproval;
+use crate::Database;
+
+fn load_approvals(conn: &Connection, network_id: &str, owner: &str) -> rusqlite::Result<Vec<TokenApproval>> {
+    let mut stmt = conn.prepare(
+        "SELECT network_id, owner, token_contract, spender, allowance, is_unlimited,
+                token_symbol, token_decimals, spender_label, approved_block, updated_at
+         FROM token_approvals
+         WHERE network_id = ?1 AND owner = ?2
+         ORDER BY token_contract, spender"
+    )?;
+    let approvals = stmt.query_map([network_id, owner], |row| {
+        Ok(TokenApproval {
+            network_id: row.get(0)?,
+            owner: row.get(1)?,
+            token_contract: row.get(2)?,
+            spender: row.get(3)?,
+            allowance: row.get(4)?,
+            is_unlimited: row.get(5)?,
+            token_symbol: row.get(6)?,
+            token_decimals: row.get(7)?,
+            spender_label: row.get(8)?,
+            approved_block: row.get(9)?,
+            updated_at: row.get(10)?,
+        })
+    })?
+    .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(approvals)
+}
+
+impl Database {
+    /// Replace the cached allowances of `owners` on a network with `approvals`,
+    /// scanned at `scanned_at`. Addresses are stored lowercase; every approval
+    /// must belong to one of the owners.
+    pub async fn replace_token_approvals(
+        &self,
+        network_id: &str,
+        owners: &[String],
+        approvals: &[TokenApproval],
+        scanned_at: i64,
+    ) -> Result<()> {
+        let owners: Vec<String> = owners.iter().map(|o| o.to_lowercase()).collect();
+        for approval in approvals {
+            if approval.network_id != network_id || !owners.contains(&approval.owner.to_lowercase()) {
+                return Err(DatabaseError::Validation(format!(
+                    "Approval for {} on {} is outside the refreshed accounts",
+                    approval.owner, approval.network_id
+                )));
+            }
+        }
+
+        self.transaction(|tx| {
+            for owner in &owners {
+                tx.execute(
+                    "DELETE FROM token_approvals WHERE network_id = ?1 AND owner = ?2",
+                    [network_id, owner.as_str()],
+                )?;
+                tx.execute(
+                    "INSERT OR REPLACE INTO token_approval_scans (network_id, owner, scanned_at) VALUES (?1, ?2, ?3)",
+                    rusqlite::params![network_id, owner, scanned_at],
+                )?;
+            }
+            for approval in approvals {
+                tx.execute(
+                    "INSERT OR REPLACE INTO token_approvals
+                     (network_id, owner, token_contract, spender, allowance, is_unlimited,
+                      token_symbol, token_decimals, spender_label, approved_block, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    rusqlite::params![
+                        network_id,
+                        approval.owner.to_lowercase(),
+                        approval.token_contract.to_lowercase(),
+                        approval.spender.to_lowercase(),
+                        approval.allowance,
+                        approval.is_unlimited,
+                        approval.token_symbol,
+                        approval.token_decimals,
+                        approval.spender_label,
+                        approval.approved_block,
+                        approval.updated_at,
+                    ],
+                )?;
+            }
+            Ok(())
+        }).await
+    }
+
+    /// Cached allowances of `owners` on a network
+    pub async fn get_token_approvals(&self, network_id: &str, owners: &[String]) -> Result<Vec<TokenApproval>> {
+        self.with_connection(|conn| {
+            let mut approvals = Vec::new();
+            for owner in owners {
+                approvals.extend(load_approvals(conn, network_id, &owner.to_lowercase())?);
+            }
+            Ok(approvals)
+        }).await
+    }
+
+    /// Oldest scan time among `owners`, or None if any of them was never scanned
+    pub async fn token_approvals_scanned_at(&self, network_id: &str, owners: &[String]) -> Result<Option<i64>> {
+        self.with_connection(|conn| {
+            let mut oldest: Option<i64> = None;
+            for owner in owners {
+                let scanned_at = conn.query_row(
+                    "SELECT scanned_at FROM token_approval_scans WHERE network_id = ?1 AND owner = ?2",
+                    [network_id, owner.to_lowercase().as_str()],
+                    |row| row.get::<_, i64>(0),
+                ).optional()?;
+                match scanned_at {
+                    Some(at) => oldest = Some(oldest.map_or(at, |o| o.min(at))),
+                    None => return Ok(None),
+                }
+            }
+            Ok(oldest)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approval(owner: &str, token: &str, spender: &str) -> TokenApproval {
+        TokenApproval {
+            network_id: "eip155:1".to_string(),
+            owner: owner.to_string(),
+            token_contract: token.to_string(),
+            spender: spender.to_string(),
+            allowance: format!("0x{}", "f".repeat(64)),
+            is_unlimited: true,
+            token_symbol: Some("USDC".to_string()),
+            token_decimals: Some(6),
+            spender_label: None,
+            approved_block: Some(100),
+            updated_at: 1_700_000_000,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_replaces_owner_rows() {
+        let db = Database::new_in_memory().await.unwrap();
+        let owners = vec!["0xAAAA".to_string(), "0xbbbb".to_string()];
+        assert_eq!(db.token_approvals_scanned_at("eip155:1", &owners).await.unwrap(), None);
+        db.replace_token_approvals("eip155:1", &owners, &[
+            approval("0xAAAA", "0xToken1", "0xSpender1"),
+            approval("0xaaaa", "0xtoken2", "0xspender1"),
+            approval("0xbbbb", "0xtoken1", "0xspender2"),
+        ], 100).await.unwrap();
+
+        let cached = db.get_token_approvals("eip155:1", &owners).await.unwrap();
+        assert_eq!(cached.len(), 3);
+        assert_eq!(cached[0].owner, "0xaaaa");
+        assert_eq!(cached[0].token_contract, "0xtoken1");
+        assert!(db.get_token_approvals("eip155:137", &owners).await.unwrap().is_empty());
+
+        // Refreshing one owner drops its revoked approvals and keeps the other owner's
+        db.replace_token_approvals("eip155:1", &owners[..1], &[approval("0xaaaa", "0xtoken2", "0xspender1")], 200)
+            .await
+            .unwrap();
+        let cached = db.get_token_approvals("eip155:1", &owners).await.unwrap();
+        assert_eq!(cached.len(), 2);
+        assert_eq!(cached[1].owner, "0xbbbb");
+        assert_eq!(db.token_approvals_scanned_at("eip155:1", &owners).await.unwrap(), Some(100));
+        assert_eq!(db.token_approvals_scanned_at("eip155:1", &owners[..1]).await.unwrap(), Some(200));
+
+        // An empty scan is still a scan
+        let fresh = vec!["0xcccc".to_string()];
+        db.replace_token_approvals("eip155:1", &fresh, &[], 300).await.unwrap();
+        assert_eq!(db.token_approvals_scanned_at("eip155:1", &fresh).await.unwrap(), Some(300));
+
+        assert!(db.replace_token_approvals("eip155:1", &owners[..1], &[approval("0xcccc", "0xt", "0xs")], 0).await.is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Transactions still being followed on chain
+//!
+//! Pending transactions are watched until they confirm, and confirmed ones
+//! until they are deeper than their network's finality depth, when
+//! `finalized_at` is set and they drop out of tracking. A transaction reorged
+//! out of the chain keeps its row with status 'reorged'.
+
+use crate::errors::Result;
+use crate::portfolio::{transaction_from_row, TRANSACTION_COLUMNS};
+use crate::types::TransactionCache;
+use crate::Database;
+
+/// Row filter for transactions that are not settled yet
+const TRACKED: &str = "finalized_at IS NULL AND status IN ('pending', 'confirmed') AND instr(caip, '/') > 0";
+
+impl Database {
+    /// Enabled networks (the CAIP-2 part of the asset id) with transactions still tracked
+    pub async fn get_tracked_networks(&self) -> Result<Vec<String>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT DISTINCT substr(caip, 1, instr(caip, '/') - 1) AS network
+                 FROM transaction_cache
+                 WHERE {} AND substr(caip, 1, instr(caip, '/') - 1) NOT IN (SELECT network_id FROM v_disabled_networks)
+                 ORDER BY network",
+                TRACKED
+            ))?;
+            let networks = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(networks)
+        }).await
+    }
+
+    /// Tracked transactions on a network, across all devices and wallets
+    pub async fn get_tracked_transactions(&self, network_id: &str) -> Result<Vec<TransactionCache>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM transaction_cache
+                 WHERE {} AND substr(caip, 1, instr(caip, '/') - 1) = ?1
+                 ORDER BY block_height, id",
+                TRANSACTION_COLUMNS, TRACKED
+            ))?;
+            let txs = stmt
+                .query_map([network_id], transaction_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(txs)
+        }).await
+    }
+
+    /// Record a transaction's status and block height (None when it left the chain)
+    pub async fn set_transaction_confirmation(&self, id: i64, status: &str, block_height: Option<i64>) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "UPDATE transaction_cache SET status = ?1, block_height = ?2 WHERE id = ?3",
+                rusqlite::params![status, block_height, id],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    /// Stop tracking a transaction that is past its network's finality depth
+    pub async fn mark_transaction_finalized(&self, id: i64, finalized_at: i64) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "UPDATE transaction_cache SET finalized_at = ?1 WHERE id = ?2",
+                rusqlite::params![finalized_at, id],
+            )?;
+            Ok(())
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(txid: &str, caip: &str, status: &str, block_height: Option<i64>) -> TransactionCache {
+        TransactionCache {
+            id: 0,
+            device_id: "dev1".to_string(),
+            txid: txid.to_string(),
+            caip: caip.to_string(),
+            transaction_type: "send".to_string(),
+            amount: "1".to_string(),
+            amount_usd: None,
+            fee: None,
+            fee_usd: None,
+            from_address: None,
+            to_address: None,
+            timestamp: 1,
+            block_height,
+            status: Some(status.to_string()),
+            metadata_json: None,
+            wallet_fingerprint: String::new(),
+            origin: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tracked_transactions() {
+        let db = Database::new_in_memory().await.unwrap();
+        let btc = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+        db.upsert_transaction(&tx("a", btc, "pending", None)).await.unwrap();
+        db.upsert_transaction(&tx("b", btc, "confirmed", Some(100))).await.unwrap();
+        db.upsert_transaction(&tx("c", btc, "failed", Some(90))).await.unwrap();
+        db.upsert_transaction(&tx("d", "eip155:1/slip44:60", "confirmed", Some(5))).await.unwrap();
+
+        assert_eq!(db.get_tracked_networks().await.unwrap(), vec!["bip122:000000000019d6689c085ae165831e93", "eip155:1"]);
+        let tracked = db.get_tracked_transactions("bip122:000000000019d6689c085ae165831e93").await.unwrap();
+        assert_eq!(tracked.iter().map(|t| t.txid.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+
+        // Back to the mempool clears the height; finalized rows stop being tracked
+        db.set_transaction_confirmation(tracked[1].id, "pending", None).await.unwrap();
+        let stored = db.get_transaction("dev1", "", "b", btc).await.unwrap().unwrap();
+        assert_eq!((stored.status.as_deref(), stored.block_height), (Some("pending"), None));
+
+        db.mark_transaction_finalized(tracked[0].id, 1000).await.unwrap();
+        db.set_transaction_confirmation(tracked[1].id, "reorged", None).await.unwrap();
+        assert!(db.get_tracked_transactions("bip122:000000000019d6689c085ae165831e93").await.unwrap().is_empty());
+        assert_eq!(db.get_tracked_networks().await.unwrap(), vec!["eip155:1"]);
+    }
+}
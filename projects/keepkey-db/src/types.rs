@@ -27,21 +27,165 @@ pub struct DeviceRecord {
     pub setup_completed_at: Option<i64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// One stretch of a device being plugged in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
 pub struct DeviceConnection {
     pub id: i64,
     pub device_id: String,
     pub connected_at: i64,
+    /// None while the device is still connected
     pub disconnected_at: Option<i64>,
+    pub duration_secs: Option<i64>,
     pub session_data: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceFaultLog {
+    pub id: i64,
+    pub device_id: String,
+    pub recorded_at: i64,
+    pub fault_type: String,
+    pub summary: String,
+    pub record_json: String,
+}
+
+/// One hardware self-test result; a flag is None when its check did not run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct DeviceHealthCheck {
+    pub id: i64,
+    pub device_id: String,
+    pub checked_at: i64,
+    pub outcome: String,
+    pub display_ok: Option<bool>,
+    pub storage_ok: Option<bool>,
+    pub rng_ok: Option<bool>,
+    pub details_json: String,
+}
+
+/// One firmware or bootloader flash in a device's update history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct FirmwareUpdateRecord {
+    pub id: i64,
+    pub device_id: String,
+    /// 'firmware' or 'bootloader'
+    pub kind: String,
+    pub from_version: Option<String>,
+    pub to_version: String,
+    /// An older version was flashed under the downgrade override
+    pub downgrade: bool,
+    pub succeeded: bool,
+    pub error: Option<String>,
+    pub updated_at: i64,
+}
+
+/// A cached ERC-20 allowance from `owner` to `spender`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct TokenApproval {
+    pub network_id: String,
+    pub owner: String,
+    pub token_contract: String,
+    pub spender: String,
+    /// Raw uint256 as 0x-prefixed hex
+    pub allowance: String,
+    pub is_unlimited: bool,
+    pub token_symbol: Option<String>,
+    pub token_decimals: Option<u32>,
+    pub spender_label: Option<String>,
+    pub approved_block: Option<i64>,
+    pub updated_at: i64,
+}
+
+/// A critical event held in the outbox until the frontend acknowledges it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct OutboxEvent {
+    pub sequence: i64,
+    pub event_name: String,
+    pub payload_json: String,
+    pub created_at: i64,
+    pub delivery_attempts: i64,
+    pub acked_at: Option<i64>,
+}
+
+/// One watch-only account parsed from an export file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportedAccountInput {
+    pub label: String,
+    pub path: String,
+    pub caip: String,
+    pub xpub: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedWalletInput {
+    /// Synthetic fingerprint derived from the imported xpubs
+    pub wallet_fingerprint: String,
+    pub source_format: String,
+    pub source_name: Option<String>,
+    pub label: Option<String>,
+    pub accounts: Vec<ImportedAccountInput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ImportedAccount {
+    pub id: i64,
+    pub label: Option<String>,
+    pub path: Option<String>,
+    pub caip: Option<String>,
+    pub xpub: String,
+}
+
+/// A watch-only wallet imported from another hardware wallet. Its portfolio
+/// rows are stored under `device_id` (`import:<fingerprint>`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ImportedWallet {
+    pub id: i64,
+    pub wallet_fingerprint: String,
+    pub device_id: String,
+    pub source_format: String,
+    pub source_name: Option<String>,
+    pub label: Option<String>,
+    pub imported_at: i64,
+    pub accounts: Vec<ImportedAccount>,
+}
+
+/// A wallet seen on a device; hidden wallets come from passphrase sessions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct KnownWallet {
+    pub device_id: String,
+    pub wallet_fingerprint: String,
+    pub is_hidden: bool,
+    pub nickname: Option<String>,
+    pub first_seen: i64,
+    pub last_used: i64,
+}
+
+/// An entry in the activity log shown to the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ActivityEntry {
+    pub id: i64,
+    pub timestamp: i64,
+    pub category: String,
+    pub message: String,
+    pub details_json: Option<String>,
+}
+
 // ========== Portfolio Types ==========
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
 pub struct PortfolioBalance {
     pub id: i64,
     pub device_id: String,
+    pub wallet_fingerprint: String,
     pub pubkey: String,
     pub caip: String,
     pub network_id: String,
@@ -64,6 +208,7 @@ pub struct PortfolioBalance {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
 pub struct PortfolioBalanceInput {
     pub device_id: String,
     pub pubkey: String,
@@ -84,10 +229,33 @@ pub struct PortfolioBalanceInput {
     pub rewards_available: Option<String>,
 }
 
+/// One asset totalled across every device, from v_combined_portfolio
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CombinedPortfolioEntry {
+    pub caip: String,
+    pub network_id: String,
+    pub ticker: String,
+    pub total_balance: f64,
+    pub total_value_usd: f64,
+    pub price_usd: String,
+    pub last_updated: i64,
+}
+
+/// Portfolio value at one point in time, for the value-over-time chart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct PortfolioHistoryPoint {
+    pub timestamp: i64,
+    pub total_value_usd: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
 pub struct PortfolioDashboard {
     pub id: i64,
     pub device_id: String,
+    pub wallet_fingerprint: String,
     pub total_value_usd: String,
     pub networks_json: String,
     pub assets_json: String,
@@ -100,9 +268,102 @@ pub struct PortfolioDashboard {
     pub last_updated: i64,
 }
 
+/// A dashboard whose stored total no longer matches its balances
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct DashboardDrift {
+    pub device_id: String,
+    pub wallet_fingerprint: String,
+    pub stored_total_usd: f64,
+    pub computed_total_usd: f64,
+    pub delta_usd: f64,
+}
+
+/// Result of one portfolio integrity audit
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct IntegrityReport {
+    pub dashboards_checked: usize,
+    pub drifted: Vec<DashboardDrift>,
+    /// portfolio_balances rows whose pubkey is not in wallet_xpubs
+    pub orphaned_rows: usize,
+}
+
+/// Rows moved for one table by a device migration; `merged` rows already
+/// existed on the new device and were kept from it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct TableMigration {
+    pub table: String,
+    pub moved: usize,
+    pub merged: usize,
+}
+
+/// Result of migrating a replaced device's data to its successor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct DeviceMigrationSummary {
+    pub old_device_id: String,
+    pub new_device_id: String,
+    pub wallet_fingerprint: String,
+    pub tables: Vec<TableMigration>,
+    pub label_copied: bool,
+    /// The old device's note was appended to the new device's
+    pub note_merged: bool,
+}
+
+/// Freeform note a user attached to a device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct DeviceNote {
+    pub device_id: String,
+    pub note_markdown: String,
+    pub updated_at: i64,
+}
+
+/// Metadata of a device-encrypted secure note; the body is never included
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SecureNote {
+    pub id: u32,
+    pub wallet_fingerprint: String,
+    pub title: String,
+    /// Length of the body before encryption
+    pub plaintext_bytes: u32,
+    pub created_on_device: Option<String>,
+    pub created_at: i64,
+}
+
+/// An address the vault derived and cached for a device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedAddress {
+    pub wallet_fingerprint: String,
+    pub derivation_path: String,
+    pub coin_name: String,
+    pub script_type: Option<String>,
+    pub address: String,
+}
+
+/// Everything non-secret stored about one device, read in one snapshot for
+/// a device data export
+#[derive(Debug, Clone, Default)]
+pub struct DeviceExportData {
+    pub device_id: String,
+    pub label: Option<String>,
+    pub model: Option<String>,
+    pub firmware_version: Option<String>,
+    pub wallets: Vec<KnownWallet>,
+    pub xpubs: Vec<WalletXpub>,
+    pub addresses: Vec<CachedAddress>,
+    /// Oldest first
+    pub transactions: Vec<TransactionCache>,
+    pub note: Option<DeviceNote>,
+}
+
 // ========== Asset Types ==========
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
 pub struct Asset {
     pub id: i64,
     pub caip: String,
@@ -132,7 +393,48 @@ pub struct Asset {
     pub last_updated: i64,
 }
 
+/// A registry asset to store with `upsert_assets`; optional fields may be
+/// left out of JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct AssetInput {
+    pub caip: String,
+    pub network_id: String,
+    #[serde(default)]
+    pub chain_id: Option<String>,
+    pub symbol: String,
+    pub name: String,
+    /// 'native', 'token' or 'nft'
+    pub asset_type: String,
+    #[serde(default)]
+    pub contract_address: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub decimals: Option<i32>,
+    #[serde(default)]
+    pub precision: Option<i32>,
+    #[serde(default)]
+    pub network_name: Option<String>,
+    #[serde(default)]
+    pub native_asset_caip: Option<String>,
+    #[serde(default)]
+    pub explorer: Option<String>,
+    #[serde(default)]
+    pub explorer_address_link: Option<String>,
+    #[serde(default)]
+    pub explorer_tx_link: Option<String>,
+    #[serde(default)]
+    pub coin_gecko_id: Option<String>,
+    /// Defaults to 'pioneer-discovery'
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
 pub struct Network {
     pub id: i64,
     pub network_id: String,
@@ -152,6 +454,9 @@ pub struct Network {
     pub supports_tokens: bool,
     pub fee_asset_caip: Option<String>,
     pub min_fee: Option<String>,
+    pub base_reserve: Option<String>,
+    pub reserve_per_item: Option<String>,
+    pub recommended_gas_buffer: Option<String>,
     pub tags: Option<String>,
     pub is_testnet: bool,
     pub is_active: bool,
@@ -159,6 +464,150 @@ pub struct Network {
     pub last_updated: i64,
 }
 
+/// A network to register with `upsert_network`. Its native asset must
+/// already be in the asset registry. Optional fields may be left out of JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct NetworkInput {
+    pub network_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub short_name: Option<String>,
+    #[serde(default)]
+    pub chain_id: Option<String>,
+    /// 'evm', 'utxo', 'cosmos' or 'other'
+    #[serde(default)]
+    pub network_type: Option<String>,
+    pub native_asset_caip: String,
+    pub native_symbol: String,
+    #[serde(default)]
+    pub rpc_urls: Vec<String>,
+    #[serde(default)]
+    pub ws_urls: Vec<String>,
+    #[serde(default)]
+    pub explorer_url: Option<String>,
+    #[serde(default)]
+    pub explorer_api_url: Option<String>,
+    #[serde(default)]
+    pub explorer_api_key_required: bool,
+    #[serde(default)]
+    pub supports_eip1559: bool,
+    #[serde(default)]
+    pub supports_memo: bool,
+    #[serde(default)]
+    pub supports_tokens: bool,
+    /// Defaults to the native asset
+    #[serde(default)]
+    pub fee_asset_caip: Option<String>,
+    #[serde(default)]
+    pub min_fee: Option<String>,
+    #[serde(default)]
+    pub is_testnet: bool,
+}
+
+/// Reserve requirements for a network, in native units
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NetworkReserve {
+    pub base_reserve: Option<String>,
+    pub reserve_per_item: Option<String>,
+    pub recommended_gas_buffer: Option<String>,
+}
+
+/// Where to reach a network: JSON-RPC endpoints and an Etherscan-style API
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkEndpoints {
+    pub rpc_urls: Vec<String>,
+    pub explorer_api_url: Option<String>,
+    pub supports_eip1559: bool,
+}
+
+/// A registered network and whether the user has it switched on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct NetworkStatus {
+    pub network_id: String,
+    pub name: String,
+    pub short_name: Option<String>,
+    pub network_type: Option<String>,
+    pub native_asset_caip: String,
+    pub native_symbol: String,
+    pub is_testnet: bool,
+    pub enabled: bool,
+}
+
+/// A catalog derivation path (see `derivation_paths`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct DerivationPathInfo {
+    pub path_id: String,
+    pub note: Option<String>,
+    pub blockchain: String,
+    pub symbol: String,
+    pub networks: Vec<String>,
+    pub script_type: Option<String>,
+    /// Account-level path
+    pub address_n: Vec<u32>,
+    pub is_default: bool,
+}
+
+/// An asset registry entry matched by `search_assets`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct AssetSearchResult {
+    pub caip: String,
+    pub network_id: String,
+    pub symbol: String,
+    pub name: String,
+    pub icon: Option<String>,
+    pub decimals: Option<u32>,
+    pub is_native: bool,
+}
+
+/// A token the user added to the registry by contract address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CustomToken {
+    pub caip: String,
+    pub network_id: String,
+    /// Lowercase 0x address
+    pub contract_address: String,
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u32,
+    /// Derivation paths whose accounts hold the token
+    pub path_ids: Vec<String>,
+    pub created_at: i64,
+}
+
+/// How a network's transactions are tracked to finality
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkFinality {
+    /// 'evm', 'utxo', 'cosmos' or 'other'
+    pub network_type: Option<String>,
+    /// Configured finality depth in blocks; None uses the network type's default
+    pub finality_depth: Option<i64>,
+}
+
+/// Cached fee estimates for a network (sat/vB)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeRateCache {
+    pub caip: String,
+    pub fastest: i64,
+    pub fast: i64,
+    pub average: i64,
+    /// Mempool fee histogram as JSON, when the endpoint provides one
+    pub histogram: Option<String>,
+    pub last_updated: i64,
+}
+
+/// An outgoing transaction that has not confirmed yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingOutgoing {
+    pub txid: String,
+    pub amount: String,
+    pub fee: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DerivationPath {
     pub id: i64,
@@ -185,6 +634,7 @@ pub struct DerivationPath {
 pub struct WalletXpub {
     pub id: i64,
     pub device_id: String,
+    pub wallet_fingerprint: String,
     pub path: String,
     pub label: String,
     pub caip: String,
@@ -192,9 +642,29 @@ pub struct WalletXpub {
     pub created_at: i64,
 }
 
+/// Address discovery window of a UTXO account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct AccountScanState {
+    /// `wallet_xpubs` row id
+    pub account_id: i64,
+    pub device_id: String,
+    pub wallet_fingerprint: String,
+    pub path: String,
+    pub caip: String,
+    /// Address indexes 0..window_size are scanned; 0 if never synced
+    pub window_size: u32,
+    /// Highest address index seen with activity
+    pub high_water: Option<u32>,
+    /// The last sync stopped at the scan ceiling with activity near the edge
+    pub ceiling_reached: bool,
+    pub updated_at: Option<i64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletXpubInput {
     pub device_id: String,
+    pub wallet_fingerprint: String,
     pub path: String,
     pub label: String,
     pub caip: String,
@@ -218,18 +688,65 @@ pub struct CachedPubkey {
     pub last_used: i64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
 pub struct CacheMetadata {
     pub device_id: String,
     pub label: Option<String>,
     pub firmware_version: Option<String>,
     pub initialized: Option<bool>,
+    /// 'pending', 'in_progress', 'completed' or 'failed'
     pub frontload_status: Option<String>,
+    /// Percent of the device's frontload paths stored
     pub frontload_progress: i32,
+    /// When a frontload last completed
     pub last_frontload: Option<i64>,
     pub error_message: Option<String>,
 }
 
+/// Cached data held for one device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct DeviceCacheStats {
+    pub device_id: String,
+    pub cached_pubkeys: i64,
+    pub wallet_xpubs: i64,
+    pub frontload_status: Option<String>,
+    pub frontload_progress: i32,
+    pub last_frontload: Option<i64>,
+    pub error_message: Option<String>,
+}
+
+/// Row counts of the cache tables and what each device has cached
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CacheStats {
+    pub cached_pubkeys: i64,
+    pub wallet_xpubs: i64,
+    pub portfolio_balances: i64,
+    pub transaction_cache: i64,
+    pub fee_rate_cache: i64,
+    pub devices: Vec<DeviceCacheStats>,
+}
+
+/// How far frontloading a device's xpubs for one network has come
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct FrontloadProgress {
+    pub device_id: String,
+    /// CAIP-2 chain id, e.g. `eip155:1`
+    pub network_id: String,
+    pub paths_total: u32,
+    pub paths_completed: u32,
+    /// Last path whose xpub was stored
+    pub last_path: Option<String>,
+    /// pending, in_progress, completed or failed
+    pub status: String,
+    pub error_message: Option<String>,
+    pub started_at: Option<i64>,
+    pub completed_at: Option<i64>,
+}
+
 // ========== Transaction Types ==========
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -249,14 +766,265 @@ pub struct TransactionCache {
     pub block_height: Option<i64>,
     pub status: Option<String>,
     pub metadata_json: Option<String>,
+    /// Wallet the transaction belongs to ('' before the standard wallet was identified)
+    pub wallet_fingerprint: String,
+    /// Who requested the signature; None for received or pre-attribution transactions
+    pub origin: Option<String>,
+}
+
+/// A signing request to record in the audit trail
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningAuditInput {
+    pub origin: String,
+    pub device_id: String,
+    pub wallet_fingerprint: String,
+    pub caip: String,
+    pub value_usd: Option<f64>,
+}
+
+/// Signed value per origin over a time window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct OriginSpending {
+    pub origin: String,
+    pub total_usd: f64,
+    pub signed_count: i64,
+    /// Signed requests whose value was unknown (not included in total_usd)
+    pub unpriced_count: i64,
+}
+
+/// Configured soft daily limit for an origin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct OriginSpendingLimit {
+    pub origin: String,
+    pub daily_limit_usd: f64,
+    pub updated_at: i64,
+}
+
+// ========== Tax Lot Types ==========
+
+/// Order in which lots are consumed by a disposal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum LotMethod {
+    /// Oldest lot first
+    Fifo,
+    /// Highest cost per unit first
+    Hifo,
+}
+
+impl LotMethod {
+    /// Unknown or missing values mean FIFO
+    pub fn from_pref(value: Option<&str>) -> Self {
+        match value.map(str::trim) {
+            Some("hifo") => LotMethod::Hifo,
+            _ => LotMethod::Fifo,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LotMethod::Fifo => "fifo",
+            LotMethod::Hifo => "hifo",
+        }
+    }
+}
+
+/// An acquisition lot and how much of it is left
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct TaxLot {
+    pub id: i64,
+    pub device_id: String,
+    pub wallet_fingerprint: String,
+    pub caip: String,
+    pub txid: String,
+    pub acquired_at: i64,
+    pub amount: f64,
+    pub remaining: f64,
+    pub cost_per_unit_usd: Option<f64>,
+}
+
+/// The part of an outgoing transaction taken from one lot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct Disposal {
+    /// None for amounts not covered by any recorded acquisition
+    pub lot_id: Option<i64>,
+    pub device_id: String,
+    pub wallet_fingerprint: String,
+    pub caip: String,
+    pub txid: String,
+    pub disposed_at: i64,
+    pub acquired_at: Option<i64>,
+    pub amount: f64,
+    pub proceeds_usd: Option<f64>,
+    pub cost_basis_usd: Option<f64>,
+    /// None unless both proceeds and cost basis are known
+    pub gain_usd: Option<f64>,
+    pub method: LotMethod,
+}
+
+/// Disposals in a calendar year (UTC) and their totals
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct RealizedGains {
+    pub year: i32,
+    pub method: LotMethod,
+    pub disposals: Vec<Disposal>,
+    /// Sums over disposals with a known gain
+    pub total_proceeds_usd: f64,
+    pub total_cost_basis_usd: f64,
+    pub total_gain_usd: f64,
+    /// Disposals left out of the totals because proceeds or cost basis is unknown
+    pub incomplete_count: i64,
+}
+
+/// Result of replaying the activity cache into lots
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct TaxLotRebuild {
+    pub method: LotMethod,
+    pub assets: i64,
+    pub lots: i64,
+    pub disposals: i64,
+    /// Transactions between the user's own wallets that moved lots
+    pub self_transfers: i64,
 }
 
 // ========== Meta/Preferences Types ==========
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A stored preference, as listed by `get_all_preferences` and announced
+/// when it changes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
 pub struct Preference {
     pub key: String,
     pub value: String,
+    /// The key is one of `preferences::KNOWN_PREFERENCES`; unknown keys are
+    /// stored unchecked
+    pub known: bool,
+}
+
+// ========== API Client Types ==========
+
+/// What a paired REST API client may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    /// Balances, addresses and history
+    Read,
+    /// Read plus signing requests
+    Sign,
+}
+
+impl ApiScope {
+    /// Unknown values fall back to read-only
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "sign" => ApiScope::Sign,
+            _ => ApiScope::Read,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiScope::Read => "read",
+            ApiScope::Sign => "sign",
+        }
+    }
+
+    /// Whether a client with this scope may make a request needing `required`
+    pub fn allows(self, required: ApiScope) -> bool {
+        self == ApiScope::Sign || required == ApiScope::Read
+    }
+}
+
+/// A paired API client; the token itself is never stored or returned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ApiClient {
+    pub id: i64,
+    pub name: String,
+    pub scope: ApiScope,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    pub expires_at: i64,
+    pub revoked_at: Option<i64>,
+}
+
+// ========== Schema Migration Types ==========
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationKind {
+    CreateTable,
+    AddColumn,
+    /// Table copied into a new definition (SQLite cannot alter constraints)
+    RebuildTable,
+}
+
+/// A schema change this build would make to the open database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct PendingMigration {
+    /// e.g. "add_column:networks.finality_depth"
+    pub id: String,
+    pub kind: MigrationKind,
+    pub table: String,
+    pub column: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationStatus {
+    UpToDate,
+    /// Passed a dry run on a copy, then applied
+    Applied,
+    /// Applied without a dry run (no room for the copy, or forced) after a backup
+    AppliedWithoutDryRun,
+    /// Not applied; the database is open read-only on its old schema
+    Blocked,
+    /// Postponed by preference until `apply_migrations_now`; open read-only
+    Deferred,
+}
+
+/// Outcome of the last migration attempt on the open database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct MigrationReport {
+    pub status: MigrationStatus,
+    /// Migrations that were pending when the attempt started
+    pub pending: Vec<PendingMigration>,
+    /// Post-condition or migration failures that blocked the upgrade
+    pub failures: Vec<String>,
+    /// Snapshot written before a migration without dry run
+    pub backup_file: Option<String>,
+    pub warning: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl MigrationReport {
+    pub fn up_to_date() -> Self {
+        Self {
+            status: MigrationStatus::UpToDate,
+            pending: Vec::new(),
+            failures: Vec::new(),
+            backup_file: None,
+            warning: None,
+            duration_ms: 0,
+        }
+    }
+
+    /// The database was left on its old schema and is read-only
+    pub fn is_read_only(&self) -> bool {
+        matches!(self.status, MigrationStatus::Blocked | MigrationStatus::Deferred)
+    }
 }
 
 // ========== Setup Flow Types ==========
@@ -0,0 +1,225 @@
+use rusqlite::Connection;
+use crate::errors::Result;
+use crate::migrations::column_names;
+use crate::types::{KnownWallet, WalletXpub, WalletXpubInput};
+use crate::Database;
+
+/// Tables whose rows belong to a single wallet on a device
+const WALLET_SCOPED_TABLES: &[&str] = &[
+    "wallet_xpubs",
+    "portfolio_balances",
+    "portfolio_dashboard",
+    "portfolio_history",
+    "transaction_cache",
+    "tax_lots",
+    "disposals",
+    "cached_pubkeys",
+];
+
+pub(crate) const KNOWN_WALLET_COLUMNS: &str = "device_id, wallet_fingerprint, is_hidden, nickname, first_seen, last_used";
+
+pub(crate) fn known_wallet_from_row(row: &rusqlite::Row) -> rusqlite::Result<KnownWallet> {
+    Ok(KnownWallet {
+        device_id: row.get(0)?,
+        wallet_fingerprint: row.get(1)?,
+        is_hidden: row.get(2)?,
+        nickname: row.get(3)?,
+        first_seen: row.get(4)?,
+        last_used: row.get(5)?,
+    })
+}
+
+pub(crate) const WALLET_XPUB_COLUMNS: &str = "id, device_id, wallet_fingerprint, path, label, caip, pubkey, created_at";
+
+pub(crate) fn wallet_xpub_from_row(row: &rusqlite::Row) -> rusqlite::Result<WalletXpub> {
+    Ok(WalletXpub {
+        id: row.get(0)?,
+        device_id: row.get(1)?,
+        wallet_fingerprint: row.get(2)?,
+        path: row.get(3)?,
+        label: row.get(4)?,
+        caip: row.get(5)?,
+        pubkey: row.get(6)?,
+        created_at: row.get(7)?,
+    })
+}
+
+/// Give a device's rows without a wallet (written before wallet scoping) to
+/// its standard wallet. A row whose wallet-scoped twin already exists is
+/// dropped rather than breaking the table's UNIQUE key.
+pub(crate) fn claim_unscoped_rows(conn: &Connection, device_id: &str, wallet_fingerprint: &str) -> Result<()> {
+    for table in WALLET_SCOPED_TABLES {
+        if !column_names(conn, table)?.iter().any(|column| column == "wallet_fingerprint") {
+            continue;
+        }
+        let claimed = conn.execute(
+            &format!("UPDATE OR IGNORE {} SET wallet_fingerprint = ?1 WHERE device_id = ?2 AND wallet_fingerprint = ''", table),
+            [wallet_fingerprint, device_id],
+        )?;
+        let duplicates = conn.execute(
+            &format!("DELETE FROM {} WHERE device_id = ?1 AND wallet_fingerprint = ''", table),
+            [device_id],
+        )?;
+        if claimed + duplicates > 0 {
+            log::info!(
+                "Backfilled {} {} rows for standard wallet of {} ({} duplicates dropped)",
+                claimed, table, device_id, duplicates
+            );
+        }
+    }
+    Ok(())
+}
+
+impl Database {
+    /// Record that a wallet session is active on a device.
+    ///
+    /// The schema upgrade gives rows written before wallet scoping to the
+    /// standard wallets it already knows; those of a device first identified
+    /// afterwards are claimed when its standard (no passphrase) wallet is.
+    pub async fn record_wallet_session(&self, device_id: &str, wallet_fingerprint: &str, is_hidden: bool) -> Result<()> {
+        let now = Self::current_timestamp();
+        self.transaction(|tx| {
+            tx.execute(
+                "INSERT INTO device_wallets (device_id, wallet_fingerprint, is_hidden, first_seen, last_used)
+                 VALUES (?1, ?2, ?3, ?4, ?4)
+                 ON CONFLICT(device_id, wallet_fingerprint) DO UPDATE SET last_used = excluded.last_used",
+                rusqlite::params![device_id, wallet_fingerprint, is_hidden, now],
+            )?;
+
+            if !is_hidden {
+                claim_unscoped_rows(tx, device_id, wallet_fingerprint)?;
+            }
+            Ok(())
+        }).await
+    }
+
+    /// Wallets previously seen on a device, standard wallet first
+    pub async fn list_known_wallets(&self, device_id: &str) -> Result<Vec<KnownWallet>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                &format!("SELECT {} FROM device_wallets WHERE device_id = ?1 ORDER BY is_hidden, first_seen", KNOWN_WALLET_COLUMNS)
+            )?;
+            let wallets = stmt
+                .query_map([device_id], known_wallet_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(wallets)
+        }).await
+    }
+
+    /// Set (or clear) the user-assigned nickname of a wallet
+    pub async fn set_wallet_nickname(&self, device_id: &str, wallet_fingerprint: &str, nickname: Option<&str>) -> Result<bool> {
+        self.with_connection(|conn| {
+            let updated = conn.execute(
+                "UPDATE device_wallets SET nickname = ?3 WHERE device_id = ?1 AND wallet_fingerprint = ?2",
+                rusqlite::params![device_id, wallet_fingerprint, nickname],
+            )?;
+            Ok(updated > 0)
+        }).await
+    }
+
+    /// Store an xpub for a wallet
+    pub async fn save_wallet_xpub(&self, xpub: &WalletXpubInput) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO wallet_xpubs (device_id, wallet_fingerprint, path, label, caip, pubkey)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT(device_id, wallet_fingerprint, path, caip) DO UPDATE SET
+                    label = excluded.label, pubkey = excluded.pubkey",
+                rusqlite::params![xpub.device_id, xpub.wallet_fingerprint, xpub.path, xpub.label, xpub.caip, xpub.pubkey],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    /// Xpubs stored for a wallet
+    pub async fn get_wallet_xpubs(&self, device_id: &str, wallet_fingerprint: &str) -> Result<Vec<WalletXpub>> {
+        self.with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                &format!("SELECT {} FROM wallet_xpubs WHERE device_id = ?1 AND wallet_fingerprint = ?2 ORDER BY path", WALLET_XPUB_COLUMNS)
+            )?;
+            let xpubs = stmt
+                .query_map([device_id, wallet_fingerprint], wallet_xpub_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(xpubs)
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+
+    async fn seed_wallet(db: &Database, fp: &str, pubkey: &str, balance: &str) {
+        db.save_wallet_xpub(&WalletXpubInput {
+            device_id: "dev1".to_string(),
+            wallet_fingerprint: fp.to_string(),
+            path: "m/84'/0'/0'".to_string(),
+            label: "Bitcoin".to_string(),
+            caip: BTC.to_string(),
+            pubkey: pubkey.to_string(),
+        }).await.unwrap();
+
+        let (fp, pubkey, balance) = (fp.to_string(), pubkey.to_string(), balance.to_string());
+        db.with_connection(move |conn| {
+            conn.execute(
+                "INSERT INTO portfolio_balances (device_id, wallet_fingerprint, pubkey, caip, network_id, ticker, balance, balance_usd, price_usd, type, last_updated)
+                 VALUES ('dev1', ?1, ?2, ?3, 'bip122:000000000019d6689c085ae165831e93', 'BTC', ?4, '0', '0', 'balance', 1)",
+                rusqlite::params![fp, pubkey, BTC, balance],
+            )?;
+            conn.execute(
+                "INSERT INTO transaction_cache (device_id, wallet_fingerprint, txid, caip, type, amount, timestamp, status)
+                 VALUES ('dev1', ?1, 'shared-txid', ?2, 'send', ?3, 1, 'pending')",
+                rusqlite::params![fp, BTC, balance],
+            )?;
+            Ok(())
+        }).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_passphrase_sessions_are_isolated() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.register_device("dev1", None, None).await.unwrap();
+        db.record_wallet_session("dev1", "aaaa0001", false).await.unwrap();
+        db.record_wallet_session("dev1", "bbbb0002", true).await.unwrap();
+        seed_wallet(&db, "aaaa0001", "xpubStandard", "1.0").await;
+        seed_wallet(&db, "bbbb0002", "xpubHidden", "0.25").await;
+
+        for (fp, pubkey, balance) in [("aaaa0001", "xpubStandard", "1.0"), ("bbbb0002", "xpubHidden", "0.25")] {
+            let xpubs = db.get_wallet_xpubs("dev1", fp).await.unwrap();
+            assert_eq!(xpubs.len(), 1);
+            assert_eq!(xpubs[0].pubkey, pubkey);
+            assert_eq!(db.get_asset_balances("dev1", fp, BTC).await.unwrap(), vec![balance.to_string()]);
+
+            let pending = db.get_pending_outgoing("dev1", fp, BTC).await.unwrap();
+            assert_eq!(pending.len(), 1);
+            assert_eq!(pending[0].amount, balance);
+            assert_eq!(db.get_transaction("dev1", fp, "shared-txid", BTC).await.unwrap().unwrap().amount, balance);
+        }
+
+        let wallets = db.list_known_wallets("dev1").await.unwrap();
+        assert_eq!(wallets.len(), 2);
+        assert!(!wallets[0].is_hidden);
+        assert!(wallets[1].is_hidden);
+
+        assert!(db.set_wallet_nickname("dev1", "bbbb0002", Some("Travel")).await.unwrap());
+        assert_eq!(db.list_known_wallets("dev1").await.unwrap()[1].nickname.as_deref(), Some("Travel"));
+    }
+
+    #[tokio::test]
+    async fn test_standard_session_backfills_unscoped_rows() {
+        let db = Database::new_in_memory().await.unwrap();
+        db.register_device("dev1", None, None).await.unwrap();
+        seed_wallet(&db, "", "xpubLegacy", "2.0").await;
+
+        // A hidden session must not claim legacy rows
+        db.record_wallet_session("dev1", "bbbb0002", true).await.unwrap();
+        assert!(db.get_wallet_xpubs("dev1", "bbbb0002").await.unwrap().is_empty());
+
+        db.record_wallet_session("dev1", "aaaa0001", false).await.unwrap();
+        assert_eq!(db.get_wallet_xpubs("dev1", "aaaa0001").await.unwrap()[0].pubkey, "xpubLegacy");
+        assert_eq!(db.get_asset_balances("dev1", "aaaa0001", BTC).await.unwrap(), vec!["2.0".to_string()]);
+        assert!(db.get_wallet_xpubs("dev1", "").await.unwrap().is_empty());
+    }
+}
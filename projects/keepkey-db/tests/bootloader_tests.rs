@@ -1,3 +1,5 @@
+#![allow(clippy::expect_fun_call, clippy::items_after_test_module, clippy::single_component_path_imports)]
+
 #[cfg(test)]
 mod bootloader_tests {
     use keepkey_db::Database;
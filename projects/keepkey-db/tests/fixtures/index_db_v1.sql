@@ -0,0 +1,152 @@
+-- index.db as created by keepkey-usb (db_version 1), with a device and its keys
+PRAGMA journal_mode=WAL;
+PRAGMA foreign_keys = ON;
+
+-- Accounts table for wallet information
+CREATE TABLE IF NOT EXISTS accounts (
+    id           INTEGER PRIMARY KEY,
+    wallet_fp    TEXT NOT NULL,      -- 4-byte fingerprint (hex)
+    kind         TEXT NOT NULL,      -- 'keepkey' | 'digital'
+    xpub         TEXT NOT NULL,
+    label        TEXT,
+    added_ts     INTEGER NOT NULL    -- epoch seconds
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_accounts_fp_xpub ON accounts(wallet_fp, xpub);
+
+-- Addresses table for derived addresses
+CREATE TABLE IF NOT EXISTS addresses (
+    id           INTEGER PRIMARY KEY,
+    account_id   INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    address      TEXT NOT NULL UNIQUE,
+    deriv_path   TEXT NOT NULL,      -- "m/84'/0'/0'/0/15"
+    first_seen   INTEGER             -- block height
+);
+
+CREATE INDEX IF NOT EXISTS idx_addresses_account ON addresses(account_id);
+
+-- Transactions table
+CREATE TABLE IF NOT EXISTS txs (
+    txid         TEXT PRIMARY KEY,
+    account_id   INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    block_height INTEGER,
+    direction    INTEGER NOT NULL,   -- +sats (recv) / -sats (send)
+    amount       INTEGER NOT NULL,   -- satoshis (always positive)
+    fee          INTEGER,            -- satoshis
+    timestamp    INTEGER             -- tx time per node
+);
+
+CREATE INDEX IF NOT EXISTS idx_txs_account_block ON txs(account_id, block_height);
+
+-- Devices table for tracking all connected devices
+CREATE TABLE IF NOT EXISTS devices (
+    device_id    TEXT PRIMARY KEY,   -- Unique device ID
+    vendor       TEXT,
+    model        TEXT,
+    label        TEXT,
+    firmware_variant TEXT,
+    firmware_version TEXT,
+    bootloader_mode BOOLEAN,
+    initialized  BOOLEAN,
+    pin_protection BOOLEAN,
+    passphrase_protection BOOLEAN,
+    first_seen   INTEGER NOT NULL,   -- epoch seconds
+    last_seen    INTEGER NOT NULL,   -- epoch seconds
+    features     TEXT                -- JSON blob of full features
+);
+
+-- Device connections table for tracking connection history
+CREATE TABLE IF NOT EXISTS device_connections (
+    id           INTEGER PRIMARY KEY,
+    device_id    TEXT NOT NULL REFERENCES devices(device_id),
+    connected_at INTEGER NOT NULL,   -- epoch seconds
+    disconnected_at INTEGER,         -- epoch seconds, NULL if still connected
+    session_data TEXT                -- JSON blob of session-specific data
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_connections_device ON device_connections(device_id);
+CREATE INDEX IF NOT EXISTS idx_device_connections_time ON device_connections(connected_at, disconnected_at);
+
+-- Wallet Context Tables (vault-v2 pattern)
+
+-- Wallet XPUBs table for device-derived public keys
+CREATE TABLE IF NOT EXISTS wallet_xpubs (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id    TEXT NOT NULL,
+    path         TEXT NOT NULL,      -- "m/44'/0'/0'"
+    label        TEXT NOT NULL,      -- "Bitcoin Legacy"
+    caip         TEXT NOT NULL,      -- "bip122:000000000019d6689c085ae165831e93/slip44:0"
+    pubkey       TEXT NOT NULL,      -- xpub string
+    created_at   INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    UNIQUE(device_id, path, caip),
+    FOREIGN KEY (device_id) REFERENCES devices(device_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_wallet_xpubs_device_id ON wallet_xpubs(device_id);
+CREATE INDEX IF NOT EXISTS idx_wallet_xpubs_lookup ON wallet_xpubs(device_id, path, caip);
+
+-- Portfolio cache table for balance data from external APIs
+CREATE TABLE IF NOT EXISTS portfolio_cache (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    pubkey       TEXT NOT NULL,      -- xpub from wallet_xpubs
+    caip         TEXT NOT NULL,      -- matching caip from wallet_xpubs
+    balance      TEXT NOT NULL,      -- balance as string (to preserve precision)
+    balance_usd  TEXT NOT NULL,      -- USD value as string
+    price_usd    TEXT NOT NULL,      -- price per unit in USD
+    symbol       TEXT,               -- BTC, etc.
+    last_updated INTEGER NOT NULL,   -- epoch seconds
+    UNIQUE(pubkey, caip)
+);
+
+CREATE INDEX IF NOT EXISTS idx_portfolio_cache_updated ON portfolio_cache(last_updated);
+
+-- Fee rate cache table for network fee estimates
+CREATE TABLE IF NOT EXISTS fee_rate_cache (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    caip         TEXT NOT NULL UNIQUE, -- network identifier
+    fastest      INTEGER NOT NULL,    -- sat/vbyte
+    fast         INTEGER NOT NULL,    -- sat/vbyte
+    average      INTEGER NOT NULL,    -- sat/vbyte
+    last_updated INTEGER NOT NULL     -- epoch seconds
+);
+
+CREATE INDEX IF NOT EXISTS idx_fee_cache_updated ON fee_rate_cache(last_updated);
+
+-- Meta table for key-value storage (including onboarding state)
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    val TEXT
+);
+
+-- Default onboarding data
+-- This will be inserted only if the table is empty (first time install)
+INSERT OR IGNORE INTO meta (key, val) VALUES 
+    ('db_version', '1'),
+    ('onboarding_completed', 'false'),
+    ('first_install_timestamp', CAST(strftime('%s', 'now') AS TEXT));
+
+-- User preferences with defaults
+INSERT OR IGNORE INTO meta (key, val) VALUES 
+    ('pref_language', 'en'),
+    ('pref_theme', 'system'),
+    ('pref_currency', 'USD'),
+    ('pref_units', 'metric'),
+    ('pref_analytics_enabled', 'false');
+
+INSERT INTO devices (device_id, vendor, model, label, firmware_version, initialized, first_seen, last_seen)
+VALUES ('343737340F4736331F003B00', 'KeepKey', 'K1-14AM', 'My KeepKey', '7.7.0', 1, 1700000000, 1700000500);
+
+INSERT INTO device_connections (device_id, connected_at, disconnected_at)
+VALUES ('343737340F4736331F003B00', 1700000000, 1700000500);
+
+INSERT INTO wallet_xpubs (device_id, path, label, caip, pubkey) VALUES
+    ('343737340F4736331F003B00', 'm/84''/0''/0''', 'Bitcoin Native Segwit',
+     'bip122:000000000019d6689c085ae165831e93/slip44:0', 'zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs'),
+    ('343737340F4736331F003B00', 'm/44''/60''/0''', 'Ethereum',
+     'eip155:1/slip44:60', 'xpub6D1AabNHCupeiLM65ZR9UStMhJ1vCpyV4XbZdyhMZBiJXALQtmn9p42VTQckoHVn8WNqS7dqnJokZHAHcHGoaQgmv8D45oNUKx6DZMNZBCd');
+
+INSERT INTO accounts (wallet_fp, kind, xpub, label, added_ts)
+VALUES ('a1b2c3d4', 'keepkey', 'zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs', 'BTC', 1700000000);
+
+INSERT INTO fee_rate_cache (caip, fastest, fast, average, last_updated)
+VALUES ('bip122:000000000019d6689c085ae165831e93/slip44:0', 20, 12, 5, 1700000000);
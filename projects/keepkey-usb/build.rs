@@ -1,5 +1,140 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::env;
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorSet};
+
+/// Proto type name of a field: the scalar name, or the message/enum name
+fn field_type(field: &FieldDescriptorProto) -> String {
+    let scalar = match field.r#type() {
+        Type::Double => "double",
+        Type::Float => "float",
+        Type::Int64 => "int64",
+        Type::Uint64 => "uint64",
+        Type::Int32 => "int32",
+        Type::Fixed64 => "fixed64",
+        Type::Fixed32 => "fixed32",
+        Type::Bool => "bool",
+        Type::String => "string",
+        Type::Bytes => "bytes",
+        Type::Uint32 => "uint32",
+        Type::Sfixed32 => "sfixed32",
+        Type::Sfixed64 => "sfixed64",
+        Type::Sint32 => "sint32",
+        Type::Sint64 => "sint64",
+        Type::Group | Type::Message | Type::Enum => {
+            return field.type_name().trim_start_matches('.').to_string();
+        }
+    };
+    scalar.to_string()
+}
+
+/// Whether the field refers to an enum (encoded as its i32 value in JSON)
+fn field_kind(field: &FieldDescriptorProto) -> &'static str {
+    match field.r#type() {
+        Type::Message | Type::Group => "message",
+        Type::Enum => "enum",
+        _ => "scalar",
+    }
+}
+
+/// snake_case to the camelCase the generated serde attributes produce
+fn camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn describe_message(
+    message: &DescriptorProto,
+    prefix: &str,
+    file: &str,
+    wire_ids: &BTreeMap<String, i32>,
+    out: &mut Vec<serde_json::Value>,
+) {
+    let name = format!("{}{}", prefix, message.name());
+    let fields: Vec<serde_json::Value> = message.field.iter().map(|field| serde_json::json!({
+        "name": field.name(),
+        "json_name": camel_case(field.name()),
+        "number": field.number(),
+        "type": field_type(field),
+        "kind": field_kind(field),
+        "label": match field.label() {
+            Label::Optional => "optional",
+            Label::Required => "required",
+            Label::Repeated => "repeated",
+        },
+    })).collect();
+    out.push(serde_json::json!({
+        "name": name,
+        "file": file,
+        "wire_id": wire_ids.get(&name),
+        "fields": fields,
+    }));
+    for nested in &message.nested_type {
+        describe_message(nested, &format!("{}.", name), file, wire_ids, out);
+    }
+}
+
+/// Commit of the device-protocol checkout, when it is a git checkout
+fn protocol_commit(proto_dir: &Path) -> Option<String> {
+    let output = std::process::Command::new("git")
+        .arg("-C").arg(proto_dir)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !commit.is_empty()).then_some(commit)
+}
+
+/// Version from device-protocol's package.json
+fn protocol_version(proto_dir: &Path) -> Option<String> {
+    let package = std::fs::read_to_string(proto_dir.join("package.json")).ok()?;
+    let package: serde_json::Value = serde_json::from_str(&package).ok()?;
+    package.get("version")?.as_str().map(str::to_string)
+}
+
+/// Turn the descriptor set protoc wrote into the JSON embedded by
+/// messages/descriptor.rs: every message with its fields and wire id
+fn write_descriptor_json(descriptor_path: &Path, proto_dir: &Path, json_path: &Path) -> std::io::Result<()> {
+    let bytes = std::fs::read(descriptor_path)?;
+    let set = FileDescriptorSet::decode(bytes.as_slice())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    // prost strips the enum name from variants, so MessageType_Features is Features
+    let mut wire_ids = BTreeMap::new();
+    for file in &set.file {
+        for value in file.enum_type.iter().filter(|e| e.name() == "MessageType").flat_map(|e| &e.value) {
+            wire_ids.insert(value.name().trim_start_matches("MessageType_").to_string(), value.number());
+        }
+    }
+
+    // types.proto imports descriptor.proto for its custom options; skip those
+    let mut messages = Vec::new();
+    for file in set.file.iter().filter(|f| f.package() != "google.protobuf") {
+        for message in &file.message_type {
+            describe_message(message, "", file.name(), &wire_ids, &mut messages);
+        }
+    }
+
+    let descriptor = serde_json::json!({
+        "version": protocol_version(proto_dir),
+        "commit": protocol_commit(proto_dir),
+        "messages": messages,
+    });
+    std::fs::write(json_path, serde_json::to_vec_pretty(&descriptor)?)
+}
 
 fn main() -> std::io::Result<()> {
     // Get the current directory and build the absolute path to device-protocol
@@ -50,6 +185,11 @@ fn main() -> std::io::Result<()> {
     );
     config.btree_map(["."]);
 
+    // Keep the descriptor set so the message schema can be embedded as JSON
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR is set by cargo"));
+    let descriptor_path = out_dir.join("protocol_descriptor.bin");
+    config.file_descriptor_set_path(&descriptor_path);
+
     // Compile all protocol files including chain-specific ones
     config.compile_protos(
         &[
@@ -66,8 +206,10 @@ fn main() -> std::io::Result<()> {
             proto_dir.join("messages-tendermint.proto"),
             proto_dir.join("messages-thorchain.proto"),
         ],
-        &[proto_dir],
+        &[&proto_dir],
     )?;
 
+    write_descriptor_json(&descriptor_path, &proto_dir, &out_dir.join("protocol_descriptor.json"))?;
+
     Ok(())
 }
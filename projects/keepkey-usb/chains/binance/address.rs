@@ -0,0 +1,47 @@
+//! Binance Chain address generation and validation
+
+use std::str::FromStr;
+use cosmrs::AccountId;
+use anyhow::{Result, anyhow, bail};
+use crate::device_queue::DeviceQueueHandle;
+
+/// Bech32 prefix of Binance Chain mainnet accounts
+pub const BINANCE_HRP: &str = "bnb";
+
+/// Check that `address` is a bech32 `bnb` account address
+pub fn validate_binance_address(address: &str) -> Result<()> {
+    let account = AccountId::from_str(address)
+        .map_err(|e| anyhow!("{} is not a valid address: {}", address, e))?;
+    if account.prefix() != BINANCE_HRP {
+        bail!("{} is not a {} address", address, BINANCE_HRP);
+    }
+    Ok(())
+}
+
+/// Get a Binance Chain address from the device, shown on its screen with
+/// `display`
+pub async fn get_binance_address(
+    device_queue: &DeviceQueueHandle,
+    path: &[u32],
+    display: bool,
+) -> Result<String> {
+    let msg = crate::messages::BinanceGetAddress {
+        address_n: path.to_vec(),
+        show_display: Some(display),
+    };
+
+    let response = device_queue
+        .send_raw(crate::messages::Message::BinanceGetAddress(msg), display)
+        .await?;
+
+    match response {
+        crate::messages::Message::BinanceAddress(addr) => {
+            let address = addr.address.ok_or_else(|| anyhow!("No address in response"))?;
+            validate_binance_address(&address)
+                .map_err(|e| anyhow!("Device returned an unexpected address: {}", e))?;
+            Ok(address)
+        }
+        crate::messages::Message::Failure(f) => Err(anyhow!("Failure: {}", f.message())),
+        _ => Err(anyhow!("Unexpected response type")),
+    }
+}
@@ -1 +1,38 @@
-//! binance chain support stub
+//! Binance Chain (BNB Beacon Chain) support for KeepKey
+//!
+//! Transfers only: BinanceSignTx, then a BinanceTransferMsg with the inputs
+//! and outputs once the device asks for it, then BinanceSignedTx with the
+//! signature and public key the broadcast transaction is assembled from.
+
+use anyhow::Result;
+
+pub mod address;
+pub mod transaction;
+
+pub use address::{get_binance_address, validate_binance_address, BINANCE_HRP};
+pub use transaction::{
+    parse_binance_amount, sign_binance_transfer, BinanceTransfer, SignedBinanceTransfer, BINANCE_CHAIN_ID,
+    BINANCE_DECIMALS,
+};
+
+/// Main Binance Chain support structure
+pub struct BinanceSupport;
+
+impl BinanceSupport {
+    /// Get a Binance Chain address for the given path
+    pub async fn get_address(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        path: &[u32],
+        display: bool,
+    ) -> Result<String> {
+        address::get_binance_address(device_queue, path, display).await
+    }
+
+    /// Sign a transfer
+    pub async fn sign_transfer(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        transfer: BinanceTransfer,
+    ) -> Result<SignedBinanceTransfer> {
+        transaction::sign_binance_transfer(device_queue, transfer).await
+    }
+}
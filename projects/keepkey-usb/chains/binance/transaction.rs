@@ -0,0 +1,234 @@
+//! Binance Chain transfer signing
+//!
+//! The device answers BinanceSignTx with BinanceTxRequest, takes the transfer
+//! as a BinanceTransferMsg of one input and one output, and returns
+//! BinanceSignedTx.
+
+use std::future::Future;
+use anyhow::{Result, anyhow, bail};
+use crate::device_queue::DeviceQueueHandle;
+use crate::messages::{self, Message};
+use crate::messages::binance_transfer_msg::{BinanceCoin, BinanceInputOutput};
+use super::address::validate_binance_address;
+
+/// Amounts on Binance Chain are fixed-point with 8 decimals
+pub const BINANCE_DECIMALS: u32 = 8;
+
+/// Binance Chain mainnet
+pub const BINANCE_CHAIN_ID: &str = "Binance-Chain-Tigris";
+
+/// A transfer to sign
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinanceTransfer {
+    /// Derivation path of the sending account
+    pub address_n: Vec<u32>,
+    pub chain_id: String,
+    pub account_number: i64,
+    pub sequence: i64,
+    /// Source id of the client; 0 when not registered
+    pub source: i64,
+    pub from: String,
+    pub to: String,
+    /// Decimal amount, e.g. "1.5"
+    pub amount: String,
+    /// Token symbol, e.g. "BNB"
+    pub denom: String,
+    pub memo: String,
+}
+
+/// What the broadcast transaction is assembled from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedBinanceTransfer {
+    pub signature: Vec<u8>,
+    /// Compressed secp256k1 public key of the signer
+    pub public_key: Vec<u8>,
+}
+
+/// Parse a decimal amount into 8-decimal base units. More than 8 decimals
+/// are refused unless the extra digits are zeros.
+pub fn parse_binance_amount(amount: &str) -> Result<i64> {
+    let amount = amount.trim();
+    let (whole, frac) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty() && frac.is_empty() {
+        bail!("Empty amount");
+    }
+    if !whole.bytes().all(|b| b.is_ascii_digit()) || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        bail!("Invalid amount: {}", amount);
+    }
+    let decimals = BINANCE_DECIMALS as usize;
+    if frac.len() > decimals && frac[decimals..].bytes().any(|b| b != b'0') {
+        bail!("Amount {} has more than {} decimals", amount, BINANCE_DECIMALS);
+    }
+
+    let whole: i64 = match whole {
+        "" => 0,
+        digits => digits.parse().map_err(|_| anyhow!("Amount too large: {}", amount))?,
+    };
+    let frac = &frac[..frac.len().min(decimals)];
+    let frac: i64 = format!("{:0<width$}", frac, width = decimals)
+        .parse()
+        .map_err(|_| anyhow!("Invalid amount: {}", amount))?;
+    whole
+        .checked_mul(10i64.pow(BINANCE_DECIMALS))
+        .and_then(|w| w.checked_add(frac))
+        .ok_or_else(|| anyhow!("Amount too large: {}", amount))
+}
+
+fn transfer_msg(transfer: &BinanceTransfer, amount: i64) -> messages::BinanceTransferMsg {
+    let side = |address: &str| BinanceInputOutput {
+        address: Some(address.to_string()),
+        coins: vec![BinanceCoin { amount: Some(amount), denom: Some(transfer.denom.clone()) }],
+        ..Default::default()
+    };
+    messages::BinanceTransferMsg {
+        inputs: vec![side(&transfer.from)],
+        outputs: vec![side(&transfer.to)],
+    }
+}
+
+/// Run the BinanceSignTx exchange, sending with `call`
+pub async fn sign_transfer_with<F, Fut>(mut call: F, transfer: &BinanceTransfer) -> Result<SignedBinanceTransfer>
+where
+    F: FnMut(Message) -> Fut,
+    Fut: Future<Output = Result<Message>>,
+{
+    validate_binance_address(&transfer.from)?;
+    validate_binance_address(&transfer.to)?;
+    let amount = parse_binance_amount(&transfer.amount)?;
+    if amount == 0 {
+        bail!("Cannot send zero {}", transfer.denom);
+    }
+    if transfer.denom.is_empty() {
+        bail!("A transfer needs a denom");
+    }
+
+    let mut message = Message::BinanceSignTx(messages::BinanceSignTx {
+        address_n: transfer.address_n.clone(),
+        msg_count: Some(1),
+        account_number: Some(transfer.account_number),
+        chain_id: Some(transfer.chain_id.clone()),
+        memo: Some(transfer.memo.clone()),
+        sequence: Some(transfer.sequence),
+        source: Some(transfer.source),
+        ..Default::default()
+    });
+    let mut pending = Some(transfer_msg(transfer, amount));
+
+    loop {
+        message = match call(message).await? {
+            Message::BinanceTxRequest(_) => {
+                let msg = pending.take().ok_or_else(|| anyhow!("Device asked for more than one message"))?;
+                Message::BinanceTransferMsg(msg)
+            }
+            Message::BinanceSignedTx(signed) => {
+                if pending.is_some() {
+                    bail!("Device signed before receiving the transfer");
+                }
+                return Ok(SignedBinanceTransfer {
+                    signature: signed.signature.ok_or_else(|| anyhow!("No signature in response"))?,
+                    public_key: signed.public_key.ok_or_else(|| anyhow!("No public key in response"))?,
+                });
+            }
+            Message::Failure(f) => match f.code.and_then(messages::FailureType::from_i32) {
+                Some(messages::FailureType::FailureActionCancelled) => {
+                    bail!("Transaction cancelled on the device")
+                }
+                _ => bail!("Device refused the transaction: {}", f.message()),
+            },
+            Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+                bail!("Device must be unlocked before signing")
+            }
+            other => bail!("Unexpected response while signing: {:?}", other.message_type()),
+        };
+    }
+}
+
+/// Sign a Binance Chain transfer
+pub async fn sign_binance_transfer(
+    device_queue: &DeviceQueueHandle,
+    transfer: BinanceTransfer,
+) -> Result<SignedBinanceTransfer> {
+    sign_transfer_with(move |message| device_queue.send_raw(message, true), &transfer).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::scripted_device::ScriptedDevice;
+
+    fn transfer(amount: &str) -> BinanceTransfer {
+        BinanceTransfer {
+            address_n: vec![0x8000_002c, 0x8000_02ca, 0x8000_0000, 0, 0],
+            chain_id: BINANCE_CHAIN_ID.to_string(),
+            account_number: 34,
+            sequence: 31,
+            source: 0,
+            from: "bnb1qyqszqgpqyqszqgpqyqszqgpqyqszqgpsh7jvj".to_string(),
+            to: "bnb1qgpqyqszqgpqyqszqgpqyqszqgpqyqszpnch8y".to_string(),
+            amount: amount.to_string(),
+            denom: "BNB".to_string(),
+            memo: "test".to_string(),
+        }
+    }
+
+    async fn sign_scripted(responses: Vec<Message>, transfer: &BinanceTransfer) -> (Result<SignedBinanceTransfer>, Vec<Message>) {
+        let device = ScriptedDevice::new(responses);
+        let result = sign_transfer_with(|message| device.call(message), transfer).await;
+        (result, device.into_sent())
+    }
+
+    #[test]
+    fn test_parse_binance_amount() {
+        assert_eq!(parse_binance_amount("1.5").unwrap(), 150_000_000);
+        assert_eq!(parse_binance_amount("0.00000001").unwrap(), 1);
+        assert_eq!(parse_binance_amount(".5").unwrap(), 50_000_000);
+        assert_eq!(parse_binance_amount("2.").unwrap(), 200_000_000);
+        assert_eq!(parse_binance_amount("1.5000000000").unwrap(), 150_000_000);
+        assert_eq!(parse_binance_amount("92233720368.54775807").unwrap(), i64::MAX);
+
+        // Too many decimals, overflow, and malformed amounts
+        for amount in [
+            "0.000000001",
+            "92233720368.54775808",
+            "100000000000",
+            "99999999999999999999",
+            "",
+            ".",
+            "-1",
+            "1e8",
+            "1.2.3",
+            "1,5",
+        ] {
+            assert!(parse_binance_amount(amount).is_err(), "{:?}", amount);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sign_transfer() {
+        let signed = Message::BinanceSignedTx(messages::BinanceSignedTx {
+            signature: Some(vec![9; 64]),
+            public_key: Some(vec![2; 33]),
+        });
+        let (result, sent) = sign_scripted(vec![Message::BinanceTxRequest(Default::default()), signed], &transfer("1.5")).await;
+        assert_eq!(result.unwrap().public_key.len(), 33);
+
+        match &sent[1] {
+            Message::BinanceTransferMsg(msg) => {
+                assert_eq!(msg.inputs[0].address.as_deref(), Some("bnb1qyqszqgpqyqszqgpqyqszqgpqyqszqgpsh7jvj"));
+                assert_eq!(msg.outputs[0].coins[0].amount, Some(150_000_000));
+                assert_eq!(msg.outputs[0].coins[0].denom.as_deref(), Some("BNB"));
+            }
+            other => panic!("sent {:?}", other.message_type()),
+        }
+
+        for invalid in [
+            transfer("0"),
+            transfer("0.000000001"),
+            BinanceTransfer { to: "cosmos1qgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyq".to_string(), ..transfer("1") },
+        ] {
+            let (result, sent) = sign_scripted(vec![], &invalid).await;
+            assert!(result.is_err());
+            assert!(sent.is_empty());
+        }
+    }
+}
@@ -6,12 +6,13 @@ use crate::device_queue::DeviceQueueHandle;
 use super::ScriptType;
 use std::str::FromStr;
 
-/// Get a Bitcoin address from the device
+/// Get a Bitcoin address from the device, shown on its screen with `display`
 pub async fn get_bitcoin_address(
     device_queue: &DeviceQueueHandle,
     path: &[u32],
     script_type: ScriptType,
     network: Network,
+    display: bool,
 ) -> Result<Address> {
     // Create the GetAddress message
     let msg = crate::messages::GetAddress {
@@ -23,14 +24,15 @@ pub async fn get_bitcoin_address(
             Network::Regtest => "Testnet".to_string(),
             _ => return Err(anyhow!("Unsupported network")),
         }),
-        show_display: Some(false),
+        show_display: Some(display),
         multisig: None,
-        script_type: Some(script_type.to_proto_output()),
+        // GetAddress takes the input script type of the address
+        script_type: Some(script_type.to_proto_input()),
     };
     
     // Send request through device queue
     let response = device_queue
-        .send_raw(crate::messages::Message::GetAddress(msg), false)
+        .send_raw(crate::messages::Message::GetAddress(msg), display)
         .await?;
     
     // Extract address from response
@@ -13,7 +13,7 @@ pub mod transaction;
 pub mod message;
 
 pub use address::get_bitcoin_address;
-pub use transaction::{sign_bitcoin_transaction, BitcoinTxInput, BitcoinTxOutput};
+pub use transaction::{sign_bitcoin_transaction, BitcoinTxInput, BitcoinTxOutput, PrevTransactions};
 pub use message::{sign_message, verify_message};
 
 /// Main Bitcoin support structure
@@ -26,8 +26,9 @@ impl BitcoinSupport {
         path: &[u32],
         script_type: ScriptType,
         network: Network,
+        display: bool,
     ) -> Result<Address> {
-        address::get_bitcoin_address(device_queue, path, script_type, network).await
+        address::get_bitcoin_address(device_queue, path, script_type, network, display).await
     }
     
     /// Sign a Bitcoin transaction
@@ -35,9 +36,10 @@ impl BitcoinSupport {
         device_queue: &crate::device_queue::DeviceQueueHandle,
         inputs: Vec<BitcoinTxInput>,
         outputs: Vec<BitcoinTxOutput>,
+        prev_txs: &PrevTransactions,
         network: Network,
     ) -> Result<Transaction> {
-        transaction::sign_bitcoin_transaction(device_queue, inputs, outputs, network).await
+        transaction::sign_bitcoin_transaction(device_queue, inputs, outputs, prev_txs, network).await
     }
     
     /// Sign a message with a Bitcoin address
@@ -50,6 +52,44 @@ impl BitcoinSupport {
     }
 }
 
+/// A transaction to sign through `ChainSupport`
+pub struct BitcoinSignRequest {
+    pub inputs: Vec<BitcoinTxInput>,
+    pub outputs: Vec<BitcoinTxOutput>,
+    pub prev_txs: PrevTransactions,
+    pub network: Network,
+}
+
+#[async_trait::async_trait]
+impl super::ChainSupport for BitcoinSupport {
+    type Params = (ScriptType, Network);
+    type Transaction = BitcoinSignRequest;
+    type SignedTransaction = Transaction;
+
+    async fn get_address(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        path: &[u32],
+        (script_type, network): &(ScriptType, Network),
+        display: bool,
+    ) -> Result<String> {
+        let address = address::get_bitcoin_address(device_queue, path, *script_type, *network, display).await?;
+        Ok(address.to_string())
+    }
+
+    async fn sign_transaction(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        request: BitcoinSignRequest,
+    ) -> Result<Transaction> {
+        transaction::sign_bitcoin_transaction(
+            device_queue,
+            request.inputs,
+            request.outputs,
+            &request.prev_txs,
+            request.network,
+        ).await
+    }
+}
+
 /// Bitcoin script types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ScriptType {
@@ -59,32 +99,53 @@ pub enum ScriptType {
     P2SH,
     /// Pay to Witness Public Key Hash (SegWit)
     P2WPKH,
+    /// Pay to Witness Public Key Hash nested in P2SH (wrapped SegWit)
+    P2SHP2WPKH,
     /// Pay to Witness Script Hash (SegWit)
     P2WSH,
     /// Pay to Taproot
     P2TR,
 }
 
+impl std::str::FromStr for ScriptType {
+    type Err = anyhow::Error;
+
+    /// Parse a script type name such as `p2wpkh` or `p2sh-p2wpkh`
+    fn from_str(name: &str) -> Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "p2pkh" => Ok(ScriptType::P2PKH),
+            "p2sh" => Ok(ScriptType::P2SH),
+            "p2wpkh" => Ok(ScriptType::P2WPKH),
+            "p2sh-p2wpkh" | "p2shp2wpkh" => Ok(ScriptType::P2SHP2WPKH),
+            "p2wsh" => Ok(ScriptType::P2WSH),
+            "p2tr" => Ok(ScriptType::P2TR),
+            other => Err(anyhow::anyhow!("Unknown script type: {}", other)),
+        }
+    }
+}
+
 impl ScriptType {
     /// Convert to protobuf input script type
     pub fn to_proto_input(&self) -> i32 {
         match self {
-            ScriptType::P2PKH => 0,  // SPENDADDRESS
-            ScriptType::P2SH => 1,   // SPENDMULTISIG
-            ScriptType::P2WPKH => 4, // SPENDWITNESS
-            ScriptType::P2WSH => 4,  // SPENDWITNESS
-            ScriptType::P2TR => 5,   // SPENDTAPROOT
+            ScriptType::P2PKH => 0,      // SPENDADDRESS
+            ScriptType::P2SH => 1,       // SPENDMULTISIG
+            ScriptType::P2WPKH => 3,     // SPENDWITNESS
+            ScriptType::P2SHP2WPKH => 4, // SPENDP2SHWITNESS
+            ScriptType::P2WSH => 3,      // SPENDWITNESS
+            ScriptType::P2TR => 5,       // SPENDTAPROOT
         }
     }
     
     /// Convert to protobuf output script type
     pub fn to_proto_output(&self) -> i32 {
         match self {
-            ScriptType::P2PKH => 0,  // PAYTOADDRESS
-            ScriptType::P2SH => 1,   // PAYTOSCRIPTHASH
-            ScriptType::P2WPKH => 4, // PAYTOWITNESS
-            ScriptType::P2WSH => 4,  // PAYTOWITNESS
-            ScriptType::P2TR => 5,   // PAYTOTAPROOT
+            ScriptType::P2PKH => 0,      // PAYTOADDRESS
+            ScriptType::P2SH => 1,       // PAYTOSCRIPTHASH
+            ScriptType::P2WPKH => 4,     // PAYTOWITNESS
+            ScriptType::P2SHP2WPKH => 5, // PAYTOP2SHWITNESS
+            ScriptType::P2WSH => 4,      // PAYTOWITNESS
+            ScriptType::P2TR => 6,       // PAYTOTAPROOT
         }
     }
 } 
\ No newline at end of file
@@ -1,13 +1,30 @@
 //! Bitcoin transaction building and signing
+//!
+//! Signing follows the device's SignTx protocol: after SignTx the device asks
+//! for the transaction piece by piece with TxRequest messages, each of which
+//! may also carry the next chunk of the signed transaction. Legacy inputs make
+//! the device ask for the transactions they spend as well, since it checks
+//! their amounts against the outputs those transactions actually contain.
 
-use bitcoin::{Transaction, Network, TxIn, TxOut};
-use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::future::Future;
+use bitcoin::{Transaction, Network};
+use bitcoin::hashes::Hash;
+use anyhow::{Result, anyhow, bail};
 use crate::device_queue::DeviceQueueHandle;
+use crate::messages::{self, Message};
+use super::ScriptType;
+
+/// Default input sequence (final, no RBF)
+const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+
+/// Output script type the device expects for an external address
+const PAYTOADDRESS: i32 = 0;
 
 /// Bitcoin transaction input
 #[derive(Debug, Clone)]
 pub struct BitcoinTxInput {
-    /// Previous transaction hash
+    /// Previous transaction hash, in the byte order block explorers show
     pub prev_hash: Vec<u8>,
     /// Previous output index
     pub prev_index: u32,
@@ -32,22 +49,253 @@ pub struct BitcoinTxOutput {
     pub script_type: super::ScriptType,
 }
 
-/// Sign a Bitcoin transaction
+/// Transactions spent by legacy inputs, which the device asks for while signing
+#[derive(Debug, Clone, Default)]
+pub struct PrevTransactions {
+    /// Keyed by txid in the byte order block explorers show
+    by_hash: HashMap<Vec<u8>, Transaction>,
+}
+
+impl PrevTransactions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode raw serialized transactions, as returned by a node or indexer
+    pub fn from_raw<T: AsRef<[u8]>>(raw_txs: &[T]) -> Result<Self> {
+        let mut prev_txs = Self::new();
+        for raw in raw_txs {
+            let tx: Transaction = bitcoin::consensus::deserialize(raw.as_ref())
+                .map_err(|e| anyhow!("Invalid previous transaction: {}", e))?;
+            prev_txs.insert(tx);
+        }
+        Ok(prev_txs)
+    }
+
+    pub fn insert(&mut self, tx: Transaction) {
+        self.by_hash.insert(display_hash(&tx), tx);
+    }
+
+    fn get(&self, hash: &[u8]) -> Result<&Transaction> {
+        self.by_hash
+            .get(hash)
+            .ok_or_else(|| anyhow!("Device requested previous transaction {} which was not supplied", hex::encode(hash)))
+    }
+}
+
+/// Txid bytes in display order, as the device refers to transactions
+fn display_hash(tx: &Transaction) -> Vec<u8> {
+    let mut hash = tx.txid().to_byte_array().to_vec();
+    hash.reverse();
+    hash
+}
+
+fn coin_name(network: Network) -> Result<&'static str> {
+    match network {
+        Network::Bitcoin => Ok("Bitcoin"),
+        Network::Testnet | Network::Signet | Network::Regtest => Ok("Testnet"),
+        _ => Err(anyhow!("Unsupported network")),
+    }
+}
+
+/// Script types the device can sign for without extra data
+fn check_input_script_type(input: &BitcoinTxInput) -> Result<()> {
+    match input.script_type {
+        ScriptType::P2PKH | ScriptType::P2WPKH | ScriptType::P2SHP2WPKH => Ok(()),
+        other => bail!("Signing {:?} inputs is not supported", other),
+    }
+}
+
+fn input_message(input: &BitcoinTxInput) -> messages::TxInputType {
+    messages::TxInputType {
+        address_n: input.address_n.clone(),
+        prev_hash: input.prev_hash.clone(),
+        prev_index: input.prev_index,
+        sequence: Some(SEQUENCE_FINAL),
+        script_type: Some(input.script_type.to_proto_input()),
+        amount: Some(input.amount),
+        ..Default::default()
+    }
+}
+
+fn output_message(output: &BitcoinTxOutput) -> Result<messages::TxOutputType> {
+    let script_type = match (&output.address, output.address_n.is_empty()) {
+        (Some(_), true) => PAYTOADDRESS,
+        // Change back to the wallet, checked by the device against its own keys
+        (None, false) => output.script_type.to_proto_output(),
+        (Some(_), false) => bail!("Output has both an address and a derivation path"),
+        (None, true) => bail!("Output needs an address or a derivation path"),
+    };
+    Ok(messages::TxOutputType {
+        address: output.address.clone(),
+        address_n: output.address_n.clone(),
+        amount: output.amount,
+        script_type,
+        ..Default::default()
+    })
+}
+
+fn transaction_ack(tx: messages::TransactionType) -> Message {
+    Message::TxAck(messages::TxAck { tx: Some(tx) })
+}
+
+/// Answer a request about one of the transactions spent by a legacy input
+fn prev_tx_ack(
+    prev_tx: &Transaction,
+    request_type: messages::RequestType,
+    index: usize,
+) -> Result<Message> {
+    let tx = match request_type {
+        messages::RequestType::Txmeta => messages::TransactionType {
+            version: Some(prev_tx.version as u32),
+            lock_time: Some(prev_tx.lock_time.to_consensus_u32()),
+            inputs_cnt: Some(prev_tx.input.len() as u32),
+            outputs_cnt: Some(prev_tx.output.len() as u32),
+            ..Default::default()
+        },
+        messages::RequestType::Txinput => {
+            let input = prev_tx.input.get(index)
+                .ok_or_else(|| anyhow!("Device requested missing previous input {}", index))?;
+            let mut prev_hash = input.previous_output.txid.to_byte_array().to_vec();
+            prev_hash.reverse();
+            messages::TransactionType {
+                inputs: vec![messages::TxInputType {
+                    prev_hash,
+                    prev_index: input.previous_output.vout,
+                    script_sig: Some(input.script_sig.to_bytes()),
+                    sequence: Some(input.sequence.0),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }
+        }
+        messages::RequestType::Txoutput => {
+            let output = prev_tx.output.get(index)
+                .ok_or_else(|| anyhow!("Device requested missing previous output {}", index))?;
+            messages::TransactionType {
+                bin_outputs: vec![messages::TxOutputBinType {
+                    amount: output.value,
+                    script_pubkey: output.script_pubkey.to_bytes(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }
+        }
+        other => bail!("Unexpected request {:?} for a previous transaction", other),
+    };
+    Ok(transaction_ack(tx))
+}
+
+/// Run the SignTx exchange, sending each message with `call` and answering
+/// the device's requests until it reports the transaction finished
+pub async fn sign_transaction_with<F, Fut>(
+    mut call: F,
+    inputs: &[BitcoinTxInput],
+    outputs: &[BitcoinTxOutput],
+    prev_txs: &PrevTransactions,
+    network: Network,
+) -> Result<Transaction>
+where
+    F: FnMut(Message) -> Fut,
+    Fut: Future<Output = Result<Message>>,
+{
+    if inputs.is_empty() || outputs.is_empty() {
+        bail!("A transaction needs at least one input and one output");
+    }
+    for input in inputs {
+        check_input_script_type(input)?;
+    }
+    let output_messages = outputs.iter().map(output_message).collect::<Result<Vec<_>>>()?;
+
+    let mut serialized = Vec::new();
+    let mut message = Message::SignTx(messages::SignTx {
+        outputs_count: outputs.len() as u32,
+        inputs_count: inputs.len() as u32,
+        coin_name: Some(coin_name(network)?.to_string()),
+        version: Some(1),
+        lock_time: Some(0),
+        ..Default::default()
+    });
+
+    loop {
+        let request = match call(message).await? {
+            Message::TxRequest(request) => request,
+            Message::Failure(f) => match f.code.and_then(messages::FailureType::from_i32) {
+                Some(messages::FailureType::FailureActionCancelled) => {
+                    bail!("Transaction cancelled on the device")
+                }
+                _ => bail!("Device refused the transaction: {}", f.message()),
+            },
+            Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+                bail!("Device must be unlocked before signing")
+            }
+            other => bail!("Unexpected response while signing: {:?}", other.message_type()),
+        };
+
+        if let Some(chunk) = request.serialized.as_ref().and_then(|s| s.serialized_tx.as_ref()) {
+            serialized.extend_from_slice(chunk);
+        }
+
+        let request_type = request.request_type
+            .and_then(messages::RequestType::from_i32)
+            .ok_or_else(|| anyhow!("Device sent a TxRequest without a request type"))?;
+        if request_type == messages::RequestType::Txfinished {
+            break;
+        }
+
+        let details = request.details.unwrap_or_default();
+        let index = details.request_index.unwrap_or(0) as usize;
+        message = match details.tx_hash.as_deref() {
+            Some(hash) => prev_tx_ack(prev_txs.get(hash)?, request_type, index)?,
+            None => match request_type {
+                messages::RequestType::Txinput => {
+                    let input = inputs.get(index)
+                        .ok_or_else(|| anyhow!("Device requested missing input {}", index))?;
+                    transaction_ack(messages::TransactionType {
+                        inputs: vec![input_message(input)],
+                        ..Default::default()
+                    })
+                }
+                messages::RequestType::Txoutput => {
+                    let output = output_messages.get(index)
+                        .ok_or_else(|| anyhow!("Device requested missing output {}", index))?;
+                    transaction_ack(messages::TransactionType {
+                        outputs: vec![output.clone()],
+                        ..Default::default()
+                    })
+                }
+                messages::RequestType::Txmeta => transaction_ack(messages::TransactionType {
+                    version: Some(1),
+                    lock_time: Some(0),
+                    inputs_cnt: Some(inputs.len() as u32),
+                    outputs_cnt: Some(outputs.len() as u32),
+                    ..Default::default()
+                }),
+                other => bail!("Unexpected request {:?} while signing", other),
+            },
+        };
+    }
+
+    bitcoin::consensus::deserialize(&serialized)
+        .map_err(|e| anyhow!("Device returned an invalid signed transaction: {}", e))
+}
+
+/// Sign a Bitcoin transaction. `prev_txs` must hold the transactions the
+/// device asks for, which always includes those spent by P2PKH inputs.
 pub async fn sign_bitcoin_transaction(
     device_queue: &DeviceQueueHandle,
     inputs: Vec<BitcoinTxInput>,
     outputs: Vec<BitcoinTxOutput>,
+    prev_txs: &PrevTransactions,
     network: Network,
 ) -> Result<Transaction> {
-    // TODO: Implement full transaction signing flow
-    // This involves:
-    // 1. Sending SignTx message
-    // 2. Handling TxRequest messages
-    // 3. Providing transaction details as requested
-    // 4. Collecting signatures
-    // 5. Building final transaction
-    
-    Err(anyhow!("Bitcoin transaction signing not yet implemented"))
+    sign_transaction_with(
+        move |message| device_queue.send_raw(message, true),
+        &inputs,
+        &outputs,
+        prev_txs,
+        network,
+    ).await
 }
 
 /// Build a PSBT (Partially Signed Bitcoin Transaction)
@@ -58,4 +306,124 @@ pub async fn build_psbt(
 ) -> Result<Vec<u8>> {
     // TODO: Implement PSBT building
     Err(anyhow!("PSBT building not yet implemented"))
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::scripted_device::ScriptedDevice;
+    use bitcoin::{absolute::LockTime, OutPoint, ScriptBuf, Sequence, TxIn, TxOut, Witness};
+
+    fn tx(prev_vout: u32, values: &[u64]) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint { txid: bitcoin::Txid::all_zeros(), vout: prev_vout },
+                script_sig: ScriptBuf::from_bytes(vec![0x51]),
+                sequence: Sequence(0xffff_fffe),
+                witness: Witness::new(),
+            }],
+            output: values.iter().map(|&value| TxOut {
+                value,
+                script_pubkey: ScriptBuf::from_bytes(vec![0x76, 0xa9]),
+            }).collect(),
+        }
+    }
+
+    fn request(request_type: messages::RequestType, index: u32, tx_hash: Option<Vec<u8>>, chunk: Option<Vec<u8>>) -> Message {
+        Message::TxRequest(messages::TxRequest {
+            request_type: Some(request_type as i32),
+            details: Some(messages::TxRequestDetailsType {
+                request_index: Some(index),
+                tx_hash,
+                ..Default::default()
+            }),
+            serialized: chunk.map(|serialized_tx| messages::TxRequestSerializedType {
+                serialized_tx: Some(serialized_tx),
+                ..Default::default()
+            }),
+        })
+    }
+
+    fn input(prev_hash: Vec<u8>, script_type: ScriptType) -> BitcoinTxInput {
+        BitcoinTxInput { prev_hash, prev_index: 1, address_n: vec![0x8000_002c, 0x8000_0000, 0x8000_0000, 0, 0], amount: 20_000, script_type }
+    }
+
+    fn outputs() -> Vec<BitcoinTxOutput> {
+        vec![
+            BitcoinTxOutput { address: Some("1BoatSLRHtKNngkdXEeobR76b53LETtpyT".to_string()), address_n: vec![], amount: 25_000, script_type: ScriptType::P2PKH },
+            // Change
+            BitcoinTxOutput { address: None, address_n: vec![0x8000_0054, 0x8000_0000, 0x8000_0000, 1, 0], amount: 14_000, script_type: ScriptType::P2WPKH },
+        ]
+    }
+
+    async fn sign_scripted(
+        responses: Vec<Message>,
+        inputs: &[BitcoinTxInput],
+        prev_txs: &PrevTransactions,
+    ) -> (Result<Transaction>, Vec<Message>) {
+        let device = ScriptedDevice::new(responses);
+        let result = sign_transaction_with(|message| device.call(message), inputs, &outputs(), prev_txs, Network::Bitcoin).await;
+        (result, device.into_sent())
+    }
+
+    #[tokio::test]
+    async fn test_sign_mixed_inputs_with_previous_transaction() {
+        use messages::RequestType::*;
+        let prev = tx(3, &[5_000, 20_000]);
+        let prev_hash = display_hash(&prev);
+        let prev_txs = PrevTransactions::from_raw(&[bitcoin::consensus::serialize(&prev)]).unwrap();
+        let inputs = [input(prev_hash.clone(), ScriptType::P2PKH), input(vec![7; 32], ScriptType::P2SHP2WPKH)];
+
+        let signed = tx(1, &[25_000, 14_000]);
+        let raw = bitcoin::consensus::serialize(&signed);
+        let (first, rest) = raw.split_at(40);
+        let (result, sent) = sign_scripted(vec![
+            request(Txinput, 0, None, None),
+            request(Txmeta, 0, Some(prev_hash.clone()), None),
+            request(Txinput, 0, Some(prev_hash.clone()), None),
+            request(Txoutput, 1, Some(prev_hash.clone()), None),
+            request(Txinput, 1, None, None),
+            request(Txoutput, 0, None, None),
+            request(Txoutput, 1, None, Some(first.to_vec())),
+            request(Txfinished, 0, None, Some(rest.to_vec())),
+        ], &inputs, &prev_txs).await;
+        assert_eq!(result.unwrap(), signed);
+
+        let acks: Vec<messages::TransactionType> = sent.into_iter().skip(1).map(|m| match m {
+            Message::TxAck(ack) => ack.tx.unwrap(),
+            other => panic!("expected TxAck, sent {:?}", other.message_type()),
+        }).collect();
+        assert_eq!(acks.len(), 7);
+        assert_eq!(acks[0].inputs[0].script_type, Some(0));
+        assert_eq!((acks[1].version, acks[1].inputs_cnt, acks[1].outputs_cnt), (Some(2), Some(1), Some(2)));
+        assert_eq!((acks[2].inputs[0].prev_index, acks[2].inputs[0].sequence), (3, Some(0xffff_fffe)));
+        assert_eq!(acks[3].bin_outputs[0].amount, 20_000);
+        assert_eq!((acks[4].inputs[0].script_type, acks[4].inputs[0].amount), (Some(4), Some(20_000)));
+        assert_eq!((acks[5].outputs[0].script_type, acks[5].outputs[0].address.is_some()), (PAYTOADDRESS, true));
+        assert_eq!((acks[6].outputs[0].script_type, acks[6].outputs[0].address_n.len()), (4, 5));
+    }
+
+    #[tokio::test]
+    async fn test_sign_errors() {
+        use messages::RequestType::*;
+        let inputs = [input(vec![1; 32], ScriptType::P2PKH)];
+
+        // Output declined on the device
+        let cancelled = Message::Failure(messages::Failure {
+            code: Some(messages::FailureType::FailureActionCancelled as i32),
+            message: Some("Signing cancelled by user".to_string()),
+        });
+        let (result, _) = sign_scripted(vec![request(Txoutput, 0, None, None), cancelled], &inputs, &PrevTransactions::new()).await;
+        assert!(result.unwrap_err().to_string().contains("cancelled on the device"));
+
+        // Previous transaction not supplied
+        let (result, _) = sign_scripted(vec![request(Txmeta, 0, Some(vec![1; 32]), None)], &inputs, &PrevTransactions::new()).await;
+        assert!(result.unwrap_err().to_string().contains("not supplied"));
+
+        let p2tr = [input(vec![1; 32], ScriptType::P2TR)];
+        let (result, sent) = sign_scripted(vec![], &p2tr, &PrevTransactions::new()).await;
+        assert!(result.is_err() && sent.is_empty());
+    }
+}
@@ -1,20 +1,40 @@
 //! Cosmos address generation
 
+use std::str::FromStr;
 use cosmrs::AccountId;
 use anyhow::{Result, anyhow};
 use crate::device_queue::DeviceQueueHandle;
 
-/// Get a Cosmos address from the device
+/// Get a Cosmos address from the device, shown on its screen with `display`.
+/// The device encodes with the `cosmos` prefix; the address is re-encoded
+/// with `hrp` for other chains.
 pub async fn get_cosmos_address(
     device_queue: &DeviceQueueHandle,
     path: &[u32],
     hrp: &str,
+    display: bool,
 ) -> Result<AccountId> {
-    // TODO: Implement Cosmos address generation
-    // This involves:
-    // 1. Getting public key from device
-    // 2. Deriving Cosmos address with proper HRP
-    // 3. Returning AccountId
-    
-    Err(anyhow!("Cosmos address generation not yet implemented"))
+    let msg = crate::messages::CosmosGetAddress {
+        address_n: path.to_vec(),
+        show_display: Some(display),
+    };
+
+    let response = device_queue
+        .send_raw(crate::messages::Message::CosmosGetAddress(msg), display)
+        .await?;
+
+    match response {
+        crate::messages::Message::CosmosAddress(addr) => {
+            let address = addr.address.ok_or_else(|| anyhow!("No address in response"))?;
+            let account = AccountId::from_str(&address)
+                .map_err(|e| anyhow!("Failed to parse address: {}", e))?;
+            if account.prefix() == hrp {
+                return Ok(account);
+            }
+            AccountId::new(hrp, &account.to_bytes())
+                .map_err(|e| anyhow!("Failed to encode address for {}: {}", hrp, e))
+        }
+        crate::messages::Message::Failure(f) => Err(anyhow!("Failure: {}", f.message())),
+        _ => Err(anyhow!("Unexpected response type")),
+    }
 }
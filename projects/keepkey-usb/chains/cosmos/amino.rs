@@ -1,11 +1,224 @@
-//! Amino encoding for Cosmos transactions
+//! Amino JSON encoding for Cosmos transactions
+//!
+//! Legacy (Amino) signing signs the canonical JSON of a StdSignDoc: object
+//! keys sorted, no whitespace, integers as strings, and `<`, `>` and `&`
+//! escaped the way Go's encoding/json writes them. The bytes produced here
+//! must match the SDK's byte for byte or the signature will not verify.
 
 use anyhow::{Result, anyhow};
+use serde_json::{json, Value};
+use super::{Coin, CosmosMessageType};
+
+/// IBC transfers go out through the standard transfer port
+pub const IBC_TRANSFER_PORT: &str = "transfer";
+
+pub(crate) fn coin_json(coin: &Coin) -> Result<Value> {
+    if coin.denom.is_empty() {
+        return Err(anyhow!("Coin amount {} has no denom", coin.amount));
+    }
+    if coin.amount.is_empty() || !coin.amount.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(anyhow!("Invalid {} amount {:?}", coin.denom, coin.amount));
+    }
+    Ok(json!({ "amount": coin.amount, "denom": coin.denom }))
+}
+
+/// The `{"type", "value"}` object of a message
+pub fn amino_message_json(message: &CosmosMessageType) -> Result<Value> {
+    let (kind, value) = match message {
+        CosmosMessageType::Send { from_address, to_address, amount } => (
+            "cosmos-sdk/MsgSend",
+            json!({
+                "amount": amount.iter().map(coin_json).collect::<Result<Vec<_>>>()?,
+                "from_address": from_address,
+                "to_address": to_address,
+            }),
+        ),
+        CosmosMessageType::Delegate { delegator_address, validator_address, amount } => (
+            "cosmos-sdk/MsgDelegate",
+            json!({
+                "amount": coin_json(amount)?,
+                "delegator_address": delegator_address,
+                "validator_address": validator_address,
+            }),
+        ),
+        CosmosMessageType::Undelegate { delegator_address, validator_address, amount } => (
+            "cosmos-sdk/MsgUndelegate",
+            json!({
+                "amount": coin_json(amount)?,
+                "delegator_address": delegator_address,
+                "validator_address": validator_address,
+            }),
+        ),
+        // A zero timeout height is omitted field by field, leaving {}
+        CosmosMessageType::IbcTransfer { sender, receiver, amount, source_channel, timeout_timestamp } => (
+            "cosmos-sdk/MsgTransfer",
+            json!({
+                "receiver": receiver,
+                "sender": sender,
+                "source_channel": source_channel,
+                "source_port": IBC_TRANSFER_PORT,
+                "timeout_height": {},
+                "timeout_timestamp": timeout_timestamp.to_string(),
+                "token": coin_json(amount)?,
+            }),
+        ),
+    };
+    Ok(json!({ "type": kind, "value": value }))
+}
 
 /// Encode a Cosmos message in Amino format
 pub fn encode_amino_message(message: &super::CosmosMessageType) -> Result<Vec<u8>> {
-    // TODO: Implement Amino encoding for each message type
-    // This is needed for legacy Cosmos transaction signing
-    
-    Err(anyhow!("Amino encoding not yet implemented"))
+    Ok(canonical_json(&amino_message_json(message)?).into_bytes())
+}
+
+/// StdSignDoc fields
+pub struct SignDoc<'a> {
+    pub chain_id: &'a str,
+    pub account_number: u64,
+    pub sequence: u64,
+    pub fee: &'a Coin,
+    pub gas: u64,
+    pub memo: &'a str,
+    pub messages: &'a [CosmosMessageType],
+}
+
+/// Canonical JSON of a StdSignDoc, the bytes that are signed
+pub fn encode_sign_doc(doc: &SignDoc) -> Result<Vec<u8>> {
+    if doc.messages.is_empty() {
+        return Err(anyhow!("A transaction needs at least one message"));
+    }
+    let value = json!({
+        "account_number": doc.account_number.to_string(),
+        "chain_id": doc.chain_id,
+        "fee": {
+            "amount": [coin_json(doc.fee)?],
+            "gas": doc.gas.to_string(),
+        },
+        "memo": doc.memo,
+        "msgs": doc.messages.iter().map(amino_message_json).collect::<Result<Vec<_>>>()?,
+        "sequence": doc.sequence.to_string(),
+    });
+    Ok(canonical_json(&value).into_bytes())
+}
+
+/// Serialize with sorted keys and no whitespace, independent of whether
+/// serde_json was built to preserve insertion order
+pub fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(key, out);
+                out.push(':');
+                write_canonical(&map[key], out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::String(s) => write_string(s, out),
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+/// A JSON string with Go's HTML-safe escapes
+fn write_string(s: &str, out: &mut String) {
+    let quoted = Value::String(s.to_string()).to_string();
+    for c in quoted.chars() {
+        match c {
+            '<' => out.push_str("\\u003c"),
+            '>' => out.push_str("\\u003e"),
+            '&' => out.push_str("\\u0026"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEND: &str = include_str!("../../tests/fixtures/cosmos/send_sign_doc.json");
+    const MULTI: &str = include_str!("../../tests/fixtures/cosmos/multi_message_sign_doc.json");
+
+    fn uatom(amount: &str) -> Coin {
+        Coin { denom: "uatom".to_string(), amount: amount.to_string() }
+    }
+
+    fn doc<'a>(fee: &'a Coin, memo: &'a str, messages: &'a [CosmosMessageType]) -> SignDoc<'a> {
+        SignDoc { chain_id: "cosmoshub-4", account_number: 12345, sequence: 7, fee, gas: 200_000, memo, messages }
+    }
+
+    #[test]
+    fn test_send_sign_doc_matches_golden_file() {
+        let messages = [CosmosMessageType::Send {
+            from_address: "cosmos1sender".to_string(),
+            to_address: "cosmos1recipient".to_string(),
+            amount: vec![uatom("1000000")],
+        }];
+        let fee = uatom("5000");
+        let encoded = String::from_utf8(encode_sign_doc(&doc(&fee, "", &messages)).unwrap()).unwrap();
+        assert_eq!(encoded, SEND.trim_end());
+    }
+
+    #[test]
+    fn test_multi_message_sign_doc_matches_golden_file() {
+        let ibc_denom = "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2";
+        let messages = [
+            CosmosMessageType::Delegate {
+                delegator_address: "cosmos1delegator".to_string(),
+                validator_address: "cosmosvaloper1validator".to_string(),
+                amount: uatom("250000"),
+            },
+            CosmosMessageType::Undelegate {
+                delegator_address: "cosmos1delegator".to_string(),
+                validator_address: "cosmosvaloper1other".to_string(),
+                amount: uatom("100"),
+            },
+            CosmosMessageType::IbcTransfer {
+                sender: "cosmos1delegator".to_string(),
+                receiver: "osmo1receiver".to_string(),
+                amount: Coin { denom: ibc_denom.to_string(), amount: "42".to_string() },
+                source_channel: "channel-141".to_string(),
+                timeout_timestamp: 1_700_000_600_000_000_000,
+            },
+        ];
+        let fee = uatom("7500");
+        let encoded = String::from_utf8(encode_sign_doc(&doc(&fee, "<stake> & move", &messages)).unwrap()).unwrap();
+        assert_eq!(encoded, MULTI.trim_end());
+        assert!(encoded.contains(ibc_denom));
+    }
+
+    #[test]
+    fn test_invalid_sign_docs() {
+        let fee = uatom("5000");
+        assert!(encode_sign_doc(&doc(&fee, "", &[])).is_err());
+        for amount in ["", "1.5", "-3", "1e6"] {
+            let messages = [CosmosMessageType::Delegate {
+                delegator_address: "cosmos1delegator".to_string(),
+                validator_address: "cosmosvaloper1validator".to_string(),
+                amount: uatom(amount),
+            }];
+            assert!(encode_sign_doc(&doc(&fee, "", &messages)).is_err(), "{:?}", amount);
+        }
+    }
 }
@@ -0,0 +1,97 @@
+//! Cosmos message types
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Supported Cosmos message types
+#[derive(Debug, Clone)]
+pub enum CosmosMessageType {
+    /// Send tokens
+    Send {
+        from_address: String,
+        to_address: String,
+        amount: Vec<Coin>,
+    },
+    /// Delegate to validator
+    Delegate {
+        delegator_address: String,
+        validator_address: String,
+        amount: Coin,
+    },
+    /// Undelegate from validator
+    Undelegate {
+        delegator_address: String,
+        validator_address: String,
+        amount: Coin,
+    },
+    /// IBC transfer
+    IbcTransfer {
+        sender: String,
+        receiver: String,
+        amount: Coin,
+        source_channel: String,
+        timeout_timestamp: u64,
+    },
+}
+
+/// Cosmos coin representation
+#[derive(Debug, Clone)]
+pub struct Coin {
+    pub denom: String,
+    pub amount: String,
+}
+
+impl CosmosMessageType {
+    /// IBC transfer that times out `timeout_after` past `now`.
+    ///
+    /// `timeout_timestamp` is compared against the destination chain's block
+    /// time, so `now` should be the clock-corrected time rather than the raw
+    /// host clock.
+    pub fn ibc_transfer(
+        sender: String,
+        receiver: String,
+        amount: Coin,
+        source_channel: String,
+        now: SystemTime,
+        timeout_after: Duration,
+    ) -> Self {
+        CosmosMessageType::IbcTransfer {
+            sender,
+            receiver,
+            amount,
+            source_channel,
+            timeout_timestamp: timeout_timestamp_nanos(now, timeout_after),
+        }
+    }
+}
+
+/// IBC timeout timestamp: nanoseconds since the Unix epoch
+pub fn timeout_timestamp_nanos(now: SystemTime, timeout_after: Duration) -> u64 {
+    (now + timeout_after)
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ibc_timeout_in_nanoseconds() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let msg = CosmosMessageType::ibc_transfer(
+            "cosmos1sender".to_string(),
+            "osmo1receiver".to_string(),
+            Coin { denom: "uatom".to_string(), amount: "1000".to_string() },
+            "channel-141".to_string(),
+            now,
+            Duration::from_secs(600),
+        );
+        match msg {
+            CosmosMessageType::IbcTransfer { timeout_timestamp, .. } => {
+                assert_eq!(timeout_timestamp, 1_700_000_600_000_000_000)
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+}
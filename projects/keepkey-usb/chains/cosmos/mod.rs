@@ -12,9 +12,11 @@ use anyhow::Result;
 pub mod address;
 pub mod transaction;
 pub mod amino;
+pub mod messages;
 
 pub use address::get_cosmos_address;
-pub use transaction::{sign_cosmos_transaction, CosmosTransaction};
+pub use transaction::{sign_cosmos_transaction, CosmosTransaction, SignedCosmosTransaction};
+pub use messages::{timeout_timestamp_nanos, Coin, CosmosMessageType};
 
 /// Main Cosmos support structure
 pub struct CosmosSupport;
@@ -25,53 +27,41 @@ impl CosmosSupport {
         device_queue: &crate::device_queue::DeviceQueueHandle,
         path: &[u32],
         hrp: &str,
+        display: bool,
     ) -> Result<AccountId> {
-        address::get_cosmos_address(device_queue, path, hrp).await
+        address::get_cosmos_address(device_queue, path, hrp, display).await
     }
     
     /// Sign a Cosmos transaction
     pub async fn sign_transaction(
         device_queue: &crate::device_queue::DeviceQueueHandle,
         transaction: CosmosTransaction,
-    ) -> Result<Vec<u8>> {
+    ) -> Result<SignedCosmosTransaction> {
         transaction::sign_cosmos_transaction(device_queue, transaction).await
     }
 }
 
-/// Supported Cosmos message types
-#[derive(Debug, Clone)]
-pub enum CosmosMessageType {
-    /// Send tokens
-    Send {
-        from_address: String,
-        to_address: String,
-        amount: Vec<Coin>,
-    },
-    /// Delegate to validator
-    Delegate {
-        delegator_address: String,
-        validator_address: String,
-        amount: Coin,
-    },
-    /// Undelegate from validator
-    Undelegate {
-        delegator_address: String,
-        validator_address: String,
-        amount: Coin,
-    },
-    /// IBC transfer
-    IbcTransfer {
-        sender: String,
-        receiver: String,
-        amount: Coin,
-        source_channel: String,
-        timeout_timestamp: u64,
-    },
-}
+#[async_trait::async_trait]
+impl super::ChainSupport for CosmosSupport {
+    /// Bech32 prefix of the chain
+    type Params = String;
+    type Transaction = CosmosTransaction;
+    type SignedTransaction = SignedCosmosTransaction;
+
+    async fn get_address(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        path: &[u32],
+        hrp: &String,
+        display: bool,
+    ) -> Result<String> {
+        let account = address::get_cosmos_address(device_queue, path, hrp, display).await?;
+        Ok(account.to_string())
+    }
 
-/// Cosmos coin representation
-#[derive(Debug, Clone)]
-pub struct Coin {
-    pub denom: String,
-    pub amount: String,
-} 
\ No newline at end of file
+    async fn sign_transaction(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        transaction: CosmosTransaction,
+    ) -> Result<SignedCosmosTransaction> {
+        transaction::sign_cosmos_transaction(device_queue, transaction).await
+    }
+}
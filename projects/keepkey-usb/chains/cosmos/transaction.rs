@@ -1,11 +1,22 @@
 //! Cosmos transaction signing
+//!
+//! After CosmosSignTx the device asks for each message in turn with
+//! CosmosMsgRequest, rebuilds the Amino sign doc from them and returns the
+//! signature in CosmosSignedTx. The sign doc is also encoded here so callers
+//! get the exact bytes that were signed.
 
-use anyhow::{Result, anyhow};
+use std::future::Future;
+use anyhow::{Result, anyhow, bail};
 use crate::device_queue::DeviceQueueHandle;
+use crate::messages::{self, Message};
+use super::amino::{encode_sign_doc, SignDoc, IBC_TRANSFER_PORT};
+use super::{Coin, CosmosMessageType};
 
 /// Cosmos transaction structure
 #[derive(Debug, Clone)]
 pub struct CosmosTransaction {
+    /// Derivation path of the signing key
+    pub address_n: Vec<u32>,
     /// Chain ID
     pub chain_id: String,
     /// Account number
@@ -16,21 +27,261 @@ pub struct CosmosTransaction {
     pub messages: Vec<super::CosmosMessageType>,
     /// Transaction fee
     pub fee: super::Coin,
+    /// Gas limit
+    pub gas: u64,
     /// Memo
     pub memo: String,
 }
 
+impl CosmosTransaction {
+    /// Canonical Amino JSON sign doc
+    pub fn sign_doc(&self) -> Result<Vec<u8>> {
+        encode_sign_doc(&SignDoc {
+            chain_id: &self.chain_id,
+            account_number: self.account_number,
+            sequence: self.sequence,
+            fee: &self.fee,
+            gas: self.gas,
+            memo: &self.memo,
+            messages: &self.messages,
+        })
+    }
+}
+
+/// Signature from the device with the sign doc it covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedCosmosTransaction {
+    /// Compressed secp256k1 public key of the signer
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+    /// The signed bytes: the canonical Amino JSON sign doc
+    pub sign_doc: Vec<u8>,
+}
+
+fn device_amount(coin: &Coin) -> Result<u64> {
+    coin.amount
+        .parse()
+        .map_err(|_| anyhow!("{} amount {} does not fit the device's 64-bit amounts", coin.denom, coin.amount))
+}
+
+/// Amount in the fee's denom, the only one the device shows for sends and staking
+fn native_amount(coin: &Coin, native_denom: &str) -> Result<u64> {
+    if coin.denom != native_denom {
+        bail!("The device can only sign {} amounts here, not {}", native_denom, coin.denom);
+    }
+    device_amount(coin)
+}
+
+fn msg_ack(message: &CosmosMessageType, native_denom: &str) -> Result<messages::CosmosMsgAck> {
+    let mut ack = messages::CosmosMsgAck::default();
+    match message {
+        CosmosMessageType::Send { from_address, to_address, amount } => {
+            let [coin] = amount.as_slice() else {
+                bail!("The device signs sends of exactly one coin, not {}", amount.len());
+            };
+            ack.send = Some(messages::CosmosMsgSend {
+                from_address: Some(from_address.clone()),
+                to_address: Some(to_address.clone()),
+                amount: Some(native_amount(coin, native_denom)?),
+                ..Default::default()
+            });
+        }
+        CosmosMessageType::Delegate { delegator_address, validator_address, amount } => {
+            ack.delegate = Some(messages::CosmosMsgDelegate {
+                delegator_address: Some(delegator_address.clone()),
+                validator_address: Some(validator_address.clone()),
+                amount: Some(native_amount(amount, native_denom)?),
+                ..Default::default()
+            });
+        }
+        CosmosMessageType::Undelegate { delegator_address, validator_address, amount } => {
+            ack.undelegate = Some(messages::CosmosMsgUndelegate {
+                delegator_address: Some(delegator_address.clone()),
+                validator_address: Some(validator_address.clone()),
+                amount: Some(native_amount(amount, native_denom)?),
+                ..Default::default()
+            });
+        }
+        CosmosMessageType::IbcTransfer { sender, receiver, amount, source_channel, .. } => {
+            ack.ibc_transfer = Some(messages::CosmosMsgIbcTransfer {
+                source_port: Some(IBC_TRANSFER_PORT.to_string()),
+                source_channel: Some(source_channel.clone()),
+                denom: Some(amount.denom.clone()),
+                amount: Some(device_amount(amount)?),
+                sender: Some(sender.clone()),
+                receiver: Some(receiver.clone()),
+                ..Default::default()
+            });
+        }
+    }
+    Ok(ack)
+}
+
+/// Run the CosmosSignTx exchange, sending each message with `call` and
+/// answering the device's message requests until it returns the signature
+pub async fn sign_transaction_with<F, Fut>(
+    mut call: F,
+    transaction: &CosmosTransaction,
+) -> Result<SignedCosmosTransaction>
+where
+    F: FnMut(Message) -> Fut,
+    Fut: Future<Output = Result<Message>>,
+{
+    let sign_doc = transaction.sign_doc()?;
+    let acks = transaction.messages
+        .iter()
+        .map(|m| msg_ack(m, &transaction.fee.denom))
+        .collect::<Result<Vec<_>>>()?;
+    let fee_amount = u32::try_from(device_amount(&transaction.fee)?)
+        .map_err(|_| anyhow!("Fee {} is too large for the device", transaction.fee.amount))?;
+    let gas = u32::try_from(transaction.gas)
+        .map_err(|_| anyhow!("Gas {} is too large for the device", transaction.gas))?;
+
+    let mut message = Message::CosmosSignTx(messages::CosmosSignTx {
+        address_n: transaction.address_n.clone(),
+        account_number: Some(transaction.account_number),
+        chain_id: Some(transaction.chain_id.clone()),
+        fee_amount: Some(fee_amount),
+        gas: Some(gas),
+        memo: Some(transaction.memo.clone()),
+        sequence: Some(transaction.sequence),
+        msg_count: Some(acks.len() as u32),
+        ..Default::default()
+    });
+    let mut acks = acks.into_iter();
+
+    loop {
+        message = match call(message).await? {
+            Message::CosmosMsgRequest(_) => {
+                let ack = acks.next()
+                    .ok_or_else(|| anyhow!("Device asked for more than {} messages", transaction.messages.len()))?;
+                Message::CosmosMsgAck(ack)
+            }
+            Message::CosmosSignedTx(signed) => {
+                if acks.next().is_some() {
+                    bail!("Device signed before receiving every message");
+                }
+                return Ok(SignedCosmosTransaction {
+                    public_key: signed.public_key.ok_or_else(|| anyhow!("No public key in response"))?,
+                    signature: signed.signature.ok_or_else(|| anyhow!("No signature in response"))?,
+                    sign_doc,
+                });
+            }
+            Message::Failure(f) => match f.code.and_then(messages::FailureType::from_i32) {
+                Some(messages::FailureType::FailureActionCancelled) => {
+                    bail!("Transaction cancelled on the device")
+                }
+                _ => bail!("Device refused the transaction: {}", f.message()),
+            },
+            Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+                bail!("Device must be unlocked before signing")
+            }
+            other => bail!("Unexpected response while signing: {:?}", other.message_type()),
+        };
+    }
+}
+
 /// Sign a Cosmos transaction
 pub async fn sign_cosmos_transaction(
     device_queue: &DeviceQueueHandle,
     transaction: CosmosTransaction,
-) -> Result<Vec<u8>> {
-    // TODO: Implement Cosmos transaction signing
-    // This involves:
-    // 1. Encoding messages in Amino format
-    // 2. Sending CosmosSignTx message
-    // 3. Handling CosmosMsgRequest/Ack flow
-    // 4. Returning signed transaction
-    
-    Err(anyhow!("Cosmos transaction signing not yet implemented"))
+) -> Result<SignedCosmosTransaction> {
+    sign_transaction_with(move |message| device_queue.send_raw(message, true), &transaction).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::scripted_device::ScriptedDevice;
+
+    fn uatom(amount: &str) -> Coin {
+        Coin { denom: "uatom".to_string(), amount: amount.to_string() }
+    }
+
+    fn transaction(messages: Vec<CosmosMessageType>) -> CosmosTransaction {
+        CosmosTransaction {
+            address_n: vec![0x8000_002c, 0x8000_0076, 0x8000_0000, 0, 0],
+            chain_id: "cosmoshub-4".to_string(),
+            account_number: 12345,
+            sequence: 7,
+            messages,
+            fee: uatom("5000"),
+            gas: 200_000,
+            memo: String::new(),
+        }
+    }
+
+    fn delegate(amount: Coin) -> CosmosMessageType {
+        CosmosMessageType::Delegate {
+            delegator_address: "cosmos1delegator".to_string(),
+            validator_address: "cosmosvaloper1validator".to_string(),
+            amount,
+        }
+    }
+
+    async fn sign_scripted(responses: Vec<Message>, transaction: &CosmosTransaction) -> (Result<SignedCosmosTransaction>, Vec<Message>) {
+        let device = ScriptedDevice::new(responses);
+        let result = sign_transaction_with(|message| device.call(message), transaction).await;
+        (result, device.into_sent())
+    }
+
+    fn signed() -> Message {
+        Message::CosmosSignedTx(messages::CosmosSignedTx {
+            public_key: Some(vec![2; 33]),
+            signature: Some(vec![9; 64]),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_sign_multiple_messages() {
+        let tx = transaction(vec![
+            CosmosMessageType::Send {
+                from_address: "cosmos1delegator".to_string(),
+                to_address: "cosmos1recipient".to_string(),
+                amount: vec![uatom("1000000")],
+            },
+            delegate(uatom("250000")),
+        ]);
+        let request = || Message::CosmosMsgRequest(Default::default());
+        let (result, sent) = sign_scripted(vec![request(), request(), signed()], &tx).await;
+        let result = result.unwrap();
+        assert_eq!((result.signature.len(), result.sign_doc.clone()), (64, tx.sign_doc().unwrap()));
+
+        match &sent[0] {
+            Message::CosmosSignTx(sign_tx) => {
+                assert_eq!((sign_tx.msg_count, sign_tx.fee_amount, sign_tx.gas), (Some(2), Some(5000), Some(200_000)));
+                assert_eq!(sign_tx.memo.as_deref(), Some(""));
+            }
+            other => panic!("sent {:?}", other.message_type()),
+        }
+        match (&sent[1], &sent[2]) {
+            (Message::CosmosMsgAck(send), Message::CosmosMsgAck(delegate)) => {
+                assert_eq!(send.send.as_ref().and_then(|s| s.amount), Some(1_000_000));
+                assert_eq!(delegate.delegate.as_ref().and_then(|d| d.amount), Some(250_000));
+            }
+            _ => panic!("expected two CosmosMsgAck"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sign_errors() {
+        // Signed before being sent every message
+        let tx = transaction(vec![delegate(uatom("1")), delegate(uatom("2"))]);
+        let (result, _) = sign_scripted(vec![Message::CosmosMsgRequest(Default::default()), signed()], &tx).await;
+        assert!(result.is_err());
+
+        // Not the chain's own denom, nothing sent
+        let ibc_denom = "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2";
+        let tx = transaction(vec![delegate(Coin { denom: ibc_denom.to_string(), amount: "1".to_string() })]);
+        let (result, sent) = sign_scripted(vec![], &tx).await;
+        assert!(result.unwrap_err().to_string().contains(ibc_denom));
+        assert!(sent.is_empty());
+
+        let cancelled = Message::Failure(messages::Failure {
+            code: Some(messages::FailureType::FailureActionCancelled as i32),
+            message: Some("Signing cancelled by user".to_string()),
+        });
+        let (result, _) = sign_scripted(vec![cancelled], &transaction(vec![delegate(uatom("1"))])).await;
+        assert!(result.unwrap_err().to_string().contains("cancelled on the device"));
+    }
 }
@@ -1 +1,40 @@
-//! eos chain support stub
+//! EOS support for KeepKey
+//!
+//! Public keys come back from the device as compressed secp256k1 keys and
+//! are shown in both the legacy `EOS...` and the `PUB_K1_...` forms. Signing
+//! covers eosio.token transfers: the device walks the actions one at a time
+//! and returns the signature over the chain id and packed transaction.
+
+use anyhow::Result;
+
+pub mod public_key;
+pub mod serialize;
+pub mod transaction;
+
+pub use public_key::{get_eos_public_key, EosPublicKey};
+pub use serialize::{EosAsset, EosName, EosTxHeader};
+pub use transaction::{
+    sign_eos_transaction, EosTransaction, EosTransfer, SignedEosTransaction, EOS_MAINNET_CHAIN_ID, EOS_TOKEN_CONTRACT,
+};
+
+/// Main EOS support structure
+pub struct EosSupport;
+
+impl EosSupport {
+    /// Get the EOS public key for the given path
+    pub async fn get_public_key(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        path: &[u32],
+        display: bool,
+    ) -> Result<EosPublicKey> {
+        public_key::get_eos_public_key(device_queue, path, display).await
+    }
+
+    /// Sign a transaction of eosio.token transfers
+    pub async fn sign_transaction(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        transaction: EosTransaction,
+    ) -> Result<SignedEosTransaction> {
+        transaction::sign_eos_transaction(device_queue, transaction).await
+    }
+}
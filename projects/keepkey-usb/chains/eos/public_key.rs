@@ -0,0 +1,87 @@
+//! EOS public keys
+//!
+//! The device returns the compressed secp256k1 key; both text forms are
+//! derived from it. The legacy form is "EOS" and base58 of the key with the
+//! first four bytes of its RIPEMD-160; PUB_K1_ checksums the key with "K1"
+//! appended.
+
+use anyhow::{Result, anyhow, bail};
+use bitcoin::hashes::{ripemd160, Hash};
+use crate::device_queue::DeviceQueueHandle;
+
+/// Length of a compressed secp256k1 public key
+const COMPRESSED_KEY_BYTES: usize = 33;
+
+/// An EOS public key in both of its text forms
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EosPublicKey {
+    /// Compressed secp256k1 key
+    pub raw: Vec<u8>,
+    /// "EOS..." form
+    pub legacy: String,
+    /// "PUB_K1_..." form
+    pub k1: String,
+}
+
+/// Base58 of `data` followed by the first four bytes of RIPEMD-160 over
+/// `data` and `suffix`
+pub(crate) fn base58_with_checksum(data: &[u8], suffix: &[u8]) -> String {
+    let checksum = ripemd160::Hash::hash(&[data, suffix].concat());
+    bitcoin::base58::encode(&[data, &checksum[..4]].concat())
+}
+
+impl EosPublicKey {
+    pub fn from_compressed(raw: &[u8]) -> Result<Self> {
+        if raw.len() != COMPRESSED_KEY_BYTES || !matches!(raw[0], 0x02 | 0x03) {
+            bail!("Not a compressed secp256k1 public key");
+        }
+        Ok(EosPublicKey {
+            raw: raw.to_vec(),
+            legacy: format!("EOS{}", base58_with_checksum(raw, b"")),
+            k1: format!("PUB_K1_{}", base58_with_checksum(raw, b"K1")),
+        })
+    }
+}
+
+/// Get the EOS public key at `path`, shown on the device's screen with
+/// `show_display`
+pub async fn get_eos_public_key(
+    device_queue: &DeviceQueueHandle,
+    path: &[u32],
+    show_display: bool,
+) -> Result<EosPublicKey> {
+    let msg = crate::messages::EosGetPublicKey {
+        address_n: path.to_vec(),
+        show_display: Some(show_display),
+        ..Default::default()
+    };
+
+    let response = device_queue
+        .send_raw(crate::messages::Message::EosGetPublicKey(msg), show_display)
+        .await?;
+
+    match response {
+        crate::messages::Message::EosPublicKey(key) => {
+            let raw = key.raw_public_key.ok_or_else(|| anyhow!("No public key in response"))?;
+            EosPublicKey::from_compressed(&raw)
+        }
+        crate::messages::Message::Failure(f) => Err(anyhow!("Failure: {}", f.message())),
+        _ => Err(anyhow!("Unexpected response type")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_key_formats() {
+        let raw = hex::decode("02c0ded2bc1f1305fb0faac5e6c03ee3a1924234985427b6167ca569d13df435cf").unwrap();
+        let key = EosPublicKey::from_compressed(&raw).unwrap();
+        assert_eq!(key.legacy, "EOS6MRyAjQq8ud7hVNYcfnVPJqcVpscN5So8BhtHuGYqET5GDW5CV");
+        assert_eq!(key.k1, "PUB_K1_6MRyAjQq8ud7hVNYcfnVPJqcVpscN5So8BhtHuGYqET5BoDq63");
+
+        assert!(EosPublicKey::from_compressed(&raw[1..]).is_err());
+        assert!(EosPublicKey::from_compressed(&[&[0x04], &raw[1..]].concat()).is_err());
+    }
+}
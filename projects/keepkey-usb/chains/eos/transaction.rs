@@ -0,0 +1,323 @@
+//! EOS transfer signing
+//!
+//! The device takes the header in EosSignTx, asks for each action with
+//! EosTxActionRequest and gets it back decoded in an EosTxActionAck, then
+//! returns the signature in EosSignedTx. The same transaction is packed here
+//! so the signed bytes can be broadcast and the device's digest checked.
+
+use std::future::Future;
+use anyhow::{Result, anyhow, bail};
+use sha2::{Digest, Sha256};
+use crate::device_queue::DeviceQueueHandle;
+use crate::messages::{self, Message};
+use crate::messages::eos_sign_tx;
+use crate::messages::eos_tx_action_ack::{EosActionCommon, EosActionTransfer, EosPermissionLevel};
+use super::public_key::base58_with_checksum;
+use super::serialize::{pack_transaction, pack_transfer, signing_digest, EosAsset, EosName, EosTxHeader, PackedAction};
+
+/// Contract of the system token
+pub const EOS_TOKEN_CONTRACT: &str = "eosio.token";
+
+/// EOS mainnet
+pub const EOS_MAINNET_CHAIN_ID: &str = "aca376f206b8fc25a6ed44dbdc66547c36c6c33e3a119ffbeaef943642f0e906";
+
+/// eosio.token refuses longer memos
+pub const MAX_MEMO_BYTES: usize = 256;
+
+/// One eosio.token transfer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EosTransfer {
+    pub from: String,
+    pub to: String,
+    /// Amount with its symbol, e.g. "1.0000 EOS"
+    pub quantity: String,
+    pub memo: String,
+    /// Permission of `from` that authorizes the transfer, usually "active"
+    pub permission: String,
+}
+
+/// A transaction of transfers to sign
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EosTransaction {
+    /// Derivation path of the signing key
+    pub address_n: Vec<u32>,
+    /// Chain id as hex
+    pub chain_id: String,
+    pub header: EosTxHeader,
+    pub transfers: Vec<EosTransfer>,
+}
+
+/// What push_transaction needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedEosTransaction {
+    /// "SIG_K1_..." signature
+    pub signature: String,
+    pub packed_trx: Vec<u8>,
+}
+
+impl SignedEosTransaction {
+    /// The transaction id, the SHA-256 of the packed transaction
+    pub fn transaction_id(&self) -> String {
+        hex::encode(Sha256::digest(&self.packed_trx))
+    }
+}
+
+/// The device's answer to an action request
+fn action_ack(transfer: &EosTransfer) -> Result<(PackedAction, messages::EosTxActionAck)> {
+    let name = |text: &str| text.parse::<EosName>();
+    let account = name(EOS_TOKEN_CONTRACT)?;
+    let action = name("transfer")?;
+    let from = name(&transfer.from)?;
+    let to = name(&transfer.to)?;
+    let permission = name(&transfer.permission)?;
+    let quantity: EosAsset = transfer.quantity.parse()?;
+    if quantity.amount <= 0 {
+        bail!("Cannot transfer {}", transfer.quantity);
+    }
+    if from == to {
+        bail!("Cannot transfer to the sending account");
+    }
+    if transfer.memo.len() > MAX_MEMO_BYTES {
+        bail!("Memo is {} bytes, the limit is {}", transfer.memo.len(), MAX_MEMO_BYTES);
+    }
+
+    let packed = PackedAction {
+        account,
+        name: action,
+        authorization: vec![(from, permission)],
+        data: pack_transfer(from, to, quantity, &transfer.memo),
+    };
+    let ack = messages::EosTxActionAck {
+        common: Some(EosActionCommon {
+            account: Some(account.0),
+            name: Some(action.0),
+            authorization: vec![EosPermissionLevel { actor: Some(from.0), permission: Some(permission.0) }],
+        }),
+        transfer: Some(EosActionTransfer {
+            sender: Some(from.0),
+            receiver: Some(to.0),
+            quantity: Some(messages::eos_tx_action_ack::EosAsset {
+                amount: Some(quantity.amount),
+                symbol: Some(quantity.symbol),
+            }),
+            memo: Some(transfer.memo.clone()),
+        }),
+        ..Default::default()
+    };
+    Ok((packed, ack))
+}
+
+/// Encode a 65-byte recoverable signature as "SIG_K1_..."
+fn k1_signature(signed: &messages::EosSignedTx) -> Result<String> {
+    let v = signed.signature_v.ok_or_else(|| anyhow!("No signature in response"))?;
+    let r = signed.signature_r.as_deref().ok_or_else(|| anyhow!("No signature in response"))?;
+    let s = signed.signature_s.as_deref().ok_or_else(|| anyhow!("No signature in response"))?;
+    if r.len() != 32 || s.len() != 32 {
+        bail!("Malformed signature in response");
+    }
+    // EOS writes the recovery id offset by 27 + 4 (compressed key)
+    let header = match v {
+        0..=3 => v + 31,
+        27..=30 => v + 4,
+        31..=34 => v,
+        _ => bail!("Invalid recovery id {} in response", v),
+    };
+    let signature = [&[header as u8], r, s].concat();
+    Ok(format!("SIG_K1_{}", base58_with_checksum(&signature, b"K1")))
+}
+
+/// Run the EosSignTx exchange, sending with `call`
+pub async fn sign_transaction_with<F, Fut>(mut call: F, transaction: &EosTransaction) -> Result<SignedEosTransaction>
+where
+    F: FnMut(Message) -> Fut,
+    Fut: Future<Output = Result<Message>>,
+{
+    let chain_id = hex::decode(&transaction.chain_id).map_err(|_| anyhow!("Chain id is not hex"))?;
+    if chain_id.len() != 32 {
+        bail!("Chain id must be 32 bytes");
+    }
+    if transaction.transfers.is_empty() {
+        bail!("A transaction needs at least one transfer");
+    }
+    let (actions, mut acks): (Vec<_>, Vec<_>) = transaction
+        .transfers
+        .iter()
+        .map(action_ack)
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    let packed_trx = pack_transaction(&transaction.header, &actions);
+    let digest = signing_digest(&chain_id, &packed_trx);
+    acks.reverse();
+
+    let header = &transaction.header;
+    let mut message = Message::EosSignTx(messages::EosSignTx {
+        address_n: transaction.address_n.clone(),
+        chain_id: Some(chain_id),
+        header: Some(eos_sign_tx::EosTxHeader {
+            expiration: header.expiration,
+            ref_block_num: header.ref_block_num as u32,
+            ref_block_prefix: header.ref_block_prefix,
+            max_net_usage_words: header.max_net_usage_words,
+            max_cpu_usage_ms: header.max_cpu_usage_ms as u32,
+            delay_sec: header.delay_sec,
+        }),
+        num_actions: Some(actions.len() as u32),
+    });
+
+    loop {
+        message = match call(message).await? {
+            Message::EosTxActionRequest(_) => {
+                let ack = acks.pop().ok_or_else(|| anyhow!("Device asked for more actions than the transaction has"))?;
+                Message::EosTxActionAck(ack)
+            }
+            Message::EosSignedTx(signed) => {
+                if !acks.is_empty() {
+                    bail!("Device signed before receiving every action");
+                }
+                if signed.hash.as_deref().is_some_and(|hash| hash != digest) {
+                    bail!("Device signed a different transaction");
+                }
+                return Ok(SignedEosTransaction { signature: k1_signature(&signed)?, packed_trx });
+            }
+            Message::Failure(f) => match f.code.and_then(messages::FailureType::from_i32) {
+                Some(messages::FailureType::FailureActionCancelled) => {
+                    bail!("Transaction cancelled on the device")
+                }
+                _ => bail!("Device refused the transaction: {}", f.message()),
+            },
+            Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+                bail!("Device must be unlocked before signing")
+            }
+            other => bail!("Unexpected response while signing: {:?}", other.message_type()),
+        };
+    }
+}
+
+/// Sign an EOS transaction of eosio.token transfers
+pub async fn sign_eos_transaction(
+    device_queue: &DeviceQueueHandle,
+    transaction: EosTransaction,
+) -> Result<SignedEosTransaction> {
+    sign_transaction_with(move |message| device_queue.send_raw(message, true), &transaction).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::scripted_device::ScriptedDevice;
+
+    fn transfer(quantity: &str) -> EosTransfer {
+        EosTransfer {
+            from: "alice".to_string(),
+            to: "bob".to_string(),
+            quantity: quantity.to_string(),
+            memo: "hi".to_string(),
+            permission: "active".to_string(),
+        }
+    }
+
+    fn transaction(transfers: Vec<EosTransfer>) -> EosTransaction {
+        EosTransaction {
+            address_n: vec![0x8000_002c, 0x8000_00c2, 0x8000_0000, 0, 0],
+            chain_id: EOS_MAINNET_CHAIN_ID.to_string(),
+            header: EosTxHeader {
+                expiration: 1_700_000_000,
+                ref_block_num: 0x1234,
+                ref_block_prefix: 0xdead_beef,
+                max_net_usage_words: 0,
+                max_cpu_usage_ms: 0,
+                delay_sec: 0,
+            },
+            transfers,
+        }
+    }
+
+    fn signed_tx(v: u32, hash: Option<Vec<u8>>) -> Message {
+        Message::EosSignedTx(messages::EosSignedTx {
+            signature_v: Some(v),
+            signature_r: Some(vec![1; 32]),
+            signature_s: Some(vec![2; 32]),
+            hash,
+        })
+    }
+
+    async fn sign_scripted(responses: Vec<Message>, transaction: &EosTransaction) -> (Result<SignedEosTransaction>, Vec<Message>) {
+        let device = ScriptedDevice::new(responses);
+        let result = sign_transaction_with(|message| device.call(message), transaction).await;
+        (result, device.into_sent())
+    }
+
+    #[tokio::test]
+    async fn test_sign_transfers() {
+        let tx = transaction(vec![transfer("1.2345 EOS"), EosTransfer { to: "carol".to_string(), ..transfer("0.0001 EOS") }]);
+        let chain_id = hex::decode(EOS_MAINNET_CHAIN_ID).unwrap();
+        let request = || Message::EosTxActionRequest(Default::default());
+        let responses = vec![request(), request(), signed_tx(31, None)];
+        let (result, sent) = sign_scripted(responses, &tx).await;
+        let signed = result.unwrap();
+        assert!(signed.signature.starts_with("SIG_K1_"));
+        assert_eq!(signed.transaction_id().len(), 64);
+
+        match &sent[0] {
+            Message::EosSignTx(msg) => {
+                assert_eq!(msg.num_actions, Some(2));
+                assert_eq!(msg.chain_id.as_deref(), Some(&chain_id[..]));
+                assert_eq!(msg.header.as_ref().unwrap().ref_block_num, 0x1234);
+            }
+            other => panic!("sent {:?}", other.message_type()),
+        }
+        // Actions go out in transaction order
+        let receivers: Vec<_> = sent[1..].iter().map(|message| match message {
+            Message::EosTxActionAck(ack) => EosName(ack.transfer.as_ref().unwrap().receiver.unwrap()).to_string(),
+            other => panic!("sent {:?}", other.message_type()),
+        }).collect();
+        assert_eq!(receivers, ["bob", "carol"]);
+
+        // The packed transfer data is the known alice -> bob serialization
+        let data = "0000000000855c340000000000000e3d393000000000000004454f5300000000026869";
+        assert!(hex::encode(&signed.packed_trx).contains(data));
+
+        // A device digest over the same bytes is accepted, any other is not
+        let digest = signing_digest(&chain_id, &signed.packed_trx).to_vec();
+        let responses = vec![request(), request(), signed_tx(0, Some(digest))];
+        assert_eq!(sign_scripted(responses, &tx).await.0.unwrap(), signed);
+        let responses = vec![request(), request(), signed_tx(31, Some(vec![0; 32]))];
+        assert!(sign_scripted(responses, &tx).await.0.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_transactions_never_reach_the_device() {
+        for invalid in [
+            transaction(vec![]),
+            transaction(vec![transfer("0.0000 EOS")]),
+            transaction(vec![transfer("1 eos")]),
+            transaction(vec![EosTransfer { to: "Bob".to_string(), ..transfer("1.0000 EOS") }]),
+            transaction(vec![EosTransfer { to: "alice".to_string(), ..transfer("1.0000 EOS") }]),
+            transaction(vec![EosTransfer { memo: "x".repeat(MAX_MEMO_BYTES + 1), ..transfer("1.0000 EOS") }]),
+            EosTransaction { chain_id: "aca376f2".to_string(), ..transaction(vec![transfer("1.0000 EOS")]) },
+        ] {
+            let (result, sent) = sign_scripted(vec![], &invalid).await;
+            assert!(result.is_err());
+            assert!(sent.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_device_responses() {
+        let tx = transaction(vec![transfer("1.0000 EOS")]);
+        let cancelled = Message::Failure(messages::Failure {
+            code: Some(messages::FailureType::FailureActionCancelled as i32),
+            message: Some("Cancelled".to_string()),
+        });
+        let (result, _) = sign_scripted(vec![Message::EosTxActionRequest(Default::default()), cancelled], &tx).await;
+        assert_eq!(result.unwrap_err().to_string(), "Transaction cancelled on the device");
+
+        // Signing before the action was sent, and asking for one too many
+        let (result, _) = sign_scripted(vec![signed_tx(31, None)], &tx).await;
+        assert!(result.is_err());
+        let request = || Message::EosTxActionRequest(Default::default());
+        let (result, _) = sign_scripted(vec![request(), request()], &tx).await;
+        assert!(result.is_err());
+    }
+}
@@ -14,7 +14,7 @@ pub mod transaction;
 pub mod message;
 
 pub use address::get_ethereum_address;
-pub use transaction::{sign_ethereum_transaction, EthereumTransaction};
+pub use transaction::{sign_ethereum_transaction, sign_eip1559_transaction, EthereumTransaction};
 pub use message::{sign_message, sign_typed_data};
 
 /// Main Ethereum support structure
@@ -48,6 +48,30 @@ impl EthereumSupport {
     }
 }
 
+#[async_trait::async_trait]
+impl super::ChainSupport for EthereumSupport {
+    type Params = ();
+    type Transaction = EthereumTransaction;
+    type SignedTransaction = Vec<u8>;
+
+    async fn get_address(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        path: &[u32],
+        _params: &(),
+        display: bool,
+    ) -> Result<String> {
+        let address = address::get_ethereum_address(device_queue, path, display).await?;
+        Ok(format!("0x{}", hex::encode(address.as_bytes())))
+    }
+
+    async fn sign_transaction(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        transaction: EthereumTransaction,
+    ) -> Result<Vec<u8>> {
+        transaction::sign_ethereum_transaction(device_queue, transaction).await
+    }
+}
+
 /// Supported Ethereum transaction types
 #[derive(Debug, Clone)]
 pub enum TransactionType {
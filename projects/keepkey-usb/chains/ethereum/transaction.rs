@@ -1,8 +1,19 @@
 //! Ethereum transaction signing
+//!
+//! EthereumSignTx carries the first chunk of calldata; the device asks for
+//! the rest with EthereumTxRequest until it has all of it, then answers with
+//! the signature. The device only returns v/r/s, so the signed transaction is
+//! RLP-encoded here: legacy with an EIP-155 v, or a type-2 envelope for
+//! EIP-1559.
 
+use std::future::Future;
 use ethereum_types::{Address, U256};
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use crate::device_queue::DeviceQueueHandle;
+use crate::messages::{self, Message};
+
+/// Largest calldata chunk sent in one message
+const DATA_CHUNK_SIZE: usize = 1024;
 
 /// Ethereum transaction structure
 #[derive(Debug, Clone)]
@@ -29,19 +40,212 @@ pub struct EthereumTransaction {
     pub max_priority_fee_per_gas: Option<U256>,
 }
 
-/// Sign an Ethereum transaction
+impl EthereumTransaction {
+    /// Whether this is an EIP-1559 (type 2) transaction
+    pub fn is_eip1559(&self) -> bool {
+        self.max_fee_per_gas.is_some()
+    }
+
+    fn max_priority_fee(&self) -> U256 {
+        self.max_priority_fee_per_gas.unwrap_or(self.gas_price)
+    }
+}
+
+/// Signature as returned by the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EthereumSignature {
+    pub v: u32,
+    pub r: Vec<u8>,
+    pub s: Vec<u8>,
+}
+
+impl EthereumSignature {
+    /// Recovery id (0 or 1). The device may report it bare, as 27/28, or
+    /// already folded into an EIP-155 v; the chain id adds an even amount,
+    /// so v's parity gives the recovery id in the last two cases.
+    pub fn recovery_id(&self) -> u64 {
+        match self.v {
+            0 | 1 => self.v as u64,
+            v => (v as u64 + 1) % 2,
+        }
+    }
+}
+
+/// Big-endian bytes without leading zeros, as RLP and the device expect
+fn be_bytes(value: U256) -> Vec<u8> {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    bytes[start..].to_vec()
+}
+
+fn u64_bytes(value: u64) -> Vec<u8> {
+    be_bytes(U256::from(value))
+}
+
+fn rlp_header(out: &mut Vec<u8>, offset: u8, len: usize) {
+    if len < 56 {
+        out.push(offset + len as u8);
+    } else {
+        let len_bytes = u64_bytes(len as u64);
+        out.push(offset + 55 + len_bytes.len() as u8);
+        out.extend_from_slice(&len_bytes);
+    }
+}
+
+fn rlp_string(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut out = Vec::with_capacity(bytes.len() + 9);
+    rlp_header(&mut out, 0x80, bytes.len());
+    out.extend_from_slice(bytes);
+    out
+}
+
+/// Integer as an RLP string: minimal big-endian, so zero is empty
+fn rlp_uint(bytes: &[u8]) -> Vec<u8> {
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    rlp_string(&bytes[start..])
+}
+
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let len = items.iter().map(Vec::len).sum();
+    let mut out = Vec::with_capacity(len + 9);
+    rlp_header(&mut out, 0xc0, len);
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+/// RLP-encode a transaction with the device's signature, ready to broadcast
+pub fn encode_signed_transaction(transaction: &EthereumTransaction, signature: &EthereumSignature) -> Vec<u8> {
+    let to = transaction.to.map(|to| to.as_bytes().to_vec()).unwrap_or_default();
+    let recovery_id = signature.recovery_id();
+
+    if let Some(max_fee) = transaction.max_fee_per_gas {
+        let fields = [
+            rlp_uint(&u64_bytes(transaction.chain_id)),
+            rlp_uint(&be_bytes(transaction.nonce)),
+            rlp_uint(&be_bytes(transaction.max_priority_fee())),
+            rlp_uint(&be_bytes(max_fee)),
+            rlp_uint(&be_bytes(transaction.gas_limit)),
+            rlp_string(&to),
+            rlp_uint(&be_bytes(transaction.value)),
+            rlp_string(&transaction.data),
+            // Empty access list
+            rlp_list(&[]),
+            rlp_uint(&u64_bytes(recovery_id)),
+            rlp_uint(&signature.r),
+            rlp_uint(&signature.s),
+        ];
+        let mut encoded = vec![0x02];
+        encoded.extend(rlp_list(&fields));
+        return encoded;
+    }
+
+    let v = if transaction.chain_id == 0 {
+        27 + recovery_id
+    } else {
+        transaction.chain_id * 2 + 35 + recovery_id
+    };
+    rlp_list(&[
+        rlp_uint(&be_bytes(transaction.nonce)),
+        rlp_uint(&be_bytes(transaction.gas_price)),
+        rlp_uint(&be_bytes(transaction.gas_limit)),
+        rlp_string(&to),
+        rlp_uint(&be_bytes(transaction.value)),
+        rlp_string(&transaction.data),
+        rlp_uint(&u64_bytes(v)),
+        rlp_uint(&signature.r),
+        rlp_uint(&signature.s),
+    ])
+}
+
+fn sign_tx_message(transaction: &EthereumTransaction) -> Result<messages::EthereumSignTx> {
+    let chain_id = match transaction.chain_id {
+        0 => None,
+        id => Some(u32::try_from(id).map_err(|_| anyhow!("Chain id {} is too large for the device", id))?),
+    };
+    let initial_chunk = &transaction.data[..transaction.data.len().min(DATA_CHUNK_SIZE)];
+    let mut message = messages::EthereumSignTx {
+        address_n: transaction.address_n.clone(),
+        nonce: Some(be_bytes(transaction.nonce)),
+        gas_limit: Some(be_bytes(transaction.gas_limit)),
+        to: transaction.to.map(|to| to.as_bytes().to_vec()),
+        value: Some(be_bytes(transaction.value)),
+        data_initial_chunk: Some(initial_chunk.to_vec()),
+        data_length: Some(transaction.data.len() as u32),
+        chain_id,
+        ..Default::default()
+    };
+    match transaction.max_fee_per_gas {
+        Some(max_fee) => {
+            if chain_id.is_none() {
+                bail!("EIP-1559 transactions need a chain id");
+            }
+            message.max_fee_per_gas = Some(be_bytes(max_fee));
+            message.max_priority_fee_per_gas = Some(be_bytes(transaction.max_priority_fee()));
+        }
+        None => message.gas_price = Some(be_bytes(transaction.gas_price)),
+    }
+    Ok(message)
+}
+
+/// Run the EthereumSignTx exchange, sending each message with `call` and
+/// feeding the device calldata until it returns the signature
+pub async fn sign_transaction_with<F, Fut>(
+    mut call: F,
+    transaction: &EthereumTransaction,
+) -> Result<Vec<u8>>
+where
+    F: FnMut(Message) -> Fut,
+    Fut: Future<Output = Result<Message>>,
+{
+    let mut message = Message::EthereumSignTx(sign_tx_message(transaction)?);
+    let mut sent = transaction.data.len().min(DATA_CHUNK_SIZE);
+
+    loop {
+        let request = match call(message).await? {
+            Message::EthereumTxRequest(request) => request,
+            Message::Failure(f) => match f.code.and_then(messages::FailureType::from_i32) {
+                Some(messages::FailureType::FailureActionCancelled) => {
+                    bail!("Transaction cancelled on the device")
+                }
+                _ => bail!("Device refused the transaction: {}", f.message()),
+            },
+            Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+                bail!("Device must be unlocked before signing")
+            }
+            other => bail!("Unexpected response while signing: {:?}", other.message_type()),
+        };
+
+        if let (Some(r), Some(s)) = (request.signature_r, request.signature_s) {
+            let v = request.signature_v.ok_or_else(|| anyhow!("Device returned a signature without v"))?;
+            return Ok(encode_signed_transaction(transaction, &EthereumSignature { v, r, s }));
+        }
+
+        let requested = request.data_length.unwrap_or(0) as usize;
+        if requested == 0 || sent >= transaction.data.len() {
+            bail!("Device asked for {} more bytes of data after all {} were sent", requested, transaction.data.len());
+        }
+        let end = (sent + requested.min(DATA_CHUNK_SIZE)).min(transaction.data.len());
+        message = Message::EthereumTxAck(messages::EthereumTxAck {
+            data_chunk: Some(transaction.data[sent..end].to_vec()),
+        });
+        sent = end;
+    }
+}
+
+/// Sign an Ethereum transaction, returning the signed transaction bytes.
+/// Setting `max_fee_per_gas` makes it an EIP-1559 transaction; otherwise a
+/// non-zero `chain_id` signs it with EIP-155 replay protection.
 pub async fn sign_ethereum_transaction(
     device_queue: &DeviceQueueHandle,
     transaction: EthereumTransaction,
 ) -> Result<Vec<u8>> {
-    // TODO: Implement Ethereum transaction signing
-    // This involves:
-    // 1. Determining transaction type (Legacy, EIP-155, EIP-1559)
-    // 2. Sending EthereumSignTx message
-    // 3. Handling EthereumTxRequest messages
-    // 4. Building signed transaction with v, r, s values
-    
-    Err(anyhow!("Ethereum transaction signing not yet implemented"))
+    sign_transaction_with(move |message| device_queue.send_raw(message, true), &transaction).await
 }
 
 /// Sign an EIP-1559 transaction
@@ -49,6 +253,145 @@ pub async fn sign_eip1559_transaction(
     device_queue: &DeviceQueueHandle,
     transaction: EthereumTransaction,
 ) -> Result<Vec<u8>> {
-    // TODO: Implement EIP-1559 specific signing
-    Err(anyhow!("EIP-1559 transaction signing not yet implemented"))
-} 
\ No newline at end of file
+    if !transaction.is_eip1559() {
+        bail!("An EIP-1559 transaction needs max_fee_per_gas");
+    }
+    sign_ethereum_transaction(device_queue, transaction).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::scripted_device::ScriptedDevice;
+
+    fn decode_hex(s: &str) -> Vec<u8> {
+        hex::decode(s.trim_start_matches("0x")).unwrap()
+    }
+
+    /// The EIP-155 specification's example transaction
+    fn eip155_example() -> EthereumTransaction {
+        EthereumTransaction {
+            address_n: vec![0x8000_002c, 0x8000_003c, 0x8000_0000, 0, 0],
+            nonce: U256::from(9),
+            gas_price: U256::from(20_000_000_000u64),
+            gas_limit: U256::from(21_000),
+            to: Some(Address::from_slice(&[0x35; 20])),
+            value: U256::from(1_000_000_000_000_000_000u64),
+            data: vec![],
+            chain_id: 1,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+        }
+    }
+
+    fn signature(v: u32, r: &str, s: &str) -> Message {
+        Message::EthereumTxRequest(messages::EthereumTxRequest {
+            signature_v: Some(v),
+            signature_r: Some(decode_hex(r)),
+            signature_s: Some(decode_hex(s)),
+            ..Default::default()
+        })
+    }
+
+    async fn sign_scripted(responses: Vec<Message>, transaction: &EthereumTransaction) -> (Result<Vec<u8>>, Vec<Message>) {
+        let device = ScriptedDevice::new(responses);
+        let result = sign_transaction_with(|message| device.call(message), transaction).await;
+        (result, device.into_sent())
+    }
+
+    const R: &str = "28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276";
+    const S: &str = "67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+
+    #[tokio::test]
+    async fn test_eip155_matches_specification_example() {
+        let expected = decode_hex(
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a0\
+             28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a0\
+             67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+        );
+        // The same signature whichever way the device reports v
+        for v in [37, 0, 27] {
+            let (result, sent) = sign_scripted(vec![signature(v, R, S)], &eip155_example()).await;
+            assert_eq!(result.unwrap(), expected, "v = {}", v);
+            match &sent[0] {
+                Message::EthereumSignTx(tx) => {
+                    assert_eq!((tx.chain_id, tx.gas_price.as_deref()), (Some(1), Some(&decode_hex("04a817c800")[..])));
+                    assert_eq!(tx.max_fee_per_gas, None);
+                }
+                other => panic!("sent {:?}", other.message_type()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_eip1559_type_2_envelope() {
+        let transaction = EthereumTransaction {
+            nonce: U256::zero(),
+            gas_price: U256::zero(),
+            value: U256::zero(),
+            max_fee_per_gas: Some(U256::from(2_000_000_000u64)),
+            max_priority_fee_per_gas: Some(U256::from(1_000_000_000u64)),
+            ..eip155_example()
+        };
+        let (result, sent) = sign_scripted(vec![signature(1, &"11".repeat(32), &"22".repeat(32))], &transaction).await;
+        let expected = decode_hex(&format!(
+            "02f86a0180843b9aca0084773594008252089435353535353535353535353535353535353535358080c001a0{}a0{}",
+            "11".repeat(32),
+            "22".repeat(32),
+        ));
+        assert_eq!(result.unwrap(), expected);
+        match &sent[0] {
+            Message::EthereumSignTx(tx) => {
+                assert_eq!(tx.gas_price, None);
+                assert_eq!(tx.max_fee_per_gas.as_deref(), Some(&decode_hex("77359400")[..]));
+                assert_eq!(tx.max_priority_fee_per_gas.as_deref(), Some(&decode_hex("3b9aca00")[..]));
+            }
+            other => panic!("sent {:?}", other.message_type()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_large_calldata_is_chunked() {
+        let transaction = EthereumTransaction {
+            data: (0..2500u32).map(|i| i as u8).collect(),
+            ..eip155_example()
+        };
+        let more = |n: u32| Message::EthereumTxRequest(messages::EthereumTxRequest {
+            data_length: Some(n),
+            ..Default::default()
+        });
+        let (result, sent) = sign_scripted(vec![more(1024), more(452), signature(38, R, S)], &transaction).await;
+        let signed = result.unwrap();
+        // Long list header (0xf9 + two length bytes) and v = 38
+        assert_eq!(signed[0], 0xf9);
+        assert!(signed.windows(2).any(|w| w == [0x26, 0xa0]));
+
+        let mut data = Vec::new();
+        for message in &sent {
+            match message {
+                Message::EthereumSignTx(tx) => {
+                    assert_eq!(tx.data_length, Some(2500));
+                    data.extend(tx.data_initial_chunk.clone().unwrap());
+                }
+                Message::EthereumTxAck(ack) => data.extend(ack.data_chunk.clone().unwrap()),
+                other => panic!("sent {:?}", other.message_type()),
+            }
+        }
+        assert_eq!(data, transaction.data);
+        assert_eq!(sent.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_on_device() {
+        let cancelled = Message::Failure(messages::Failure {
+            code: Some(messages::FailureType::FailureActionCancelled as i32),
+            message: Some("Signing cancelled by user".to_string()),
+        });
+        let (result, _) = sign_scripted(vec![cancelled], &eip155_example()).await;
+        assert!(result.unwrap_err().to_string().contains("cancelled on the device"));
+
+        let too_large = EthereumTransaction { chain_id: u64::from(u32::MAX) + 1, ..eip155_example() };
+        let (result, sent) = sign_scripted(vec![], &too_large).await;
+        assert!(result.is_err() && sent.is_empty());
+    }
+}
@@ -12,25 +12,45 @@ pub mod nano;
 pub mod binance;
 pub mod thorchain;
 pub mod osmosis;
+pub mod registry;
+
+#[cfg(test)]
+mod scripted_device;
 
 // Re-export common types and traits
 pub use bitcoin::BitcoinSupport;
 pub use ethereum::EthereumSupport;
 pub use cosmos::CosmosSupport;
+pub use ripple::RippleSupport;
+pub use thorchain::ThorchainSupport;
+pub use osmosis::OsmosisSupport;
+pub use binance::BinanceSupport;
+pub use eos::EosSupport;
+pub use nano::NanoSupport;
+pub use registry::{ChainKind, ChainRegistry, RegisteredChain};
 
-// Common chain traits
+/// Address derivation and signing common to the chains the registry serves.
+///
+/// Implemented by the `*Support` types; `Params` is whatever besides the
+/// path decides the address, such as the script type or bech32 prefix.
+#[async_trait::async_trait]
 pub trait ChainSupport {
-    type Address;
-    type Transaction;
+    type Params: Sync;
+    type Transaction: Send;
     type SignedTransaction;
-    type Error;
-    
-    /// Generate an address for the given derivation path
-    fn get_address(path: &[u32]) -> Result<Self::Address, Self::Error>;
-    
-    /// Sign a transaction
-    fn sign_transaction(
+
+    /// Get the address at `path` as text, shown on the device's screen with
+    /// `display`
+    async fn get_address(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
         path: &[u32],
+        params: &Self::Params,
+        display: bool,
+    ) -> anyhow::Result<String>;
+
+    /// Sign a transaction
+    async fn sign_transaction(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
         transaction: Self::Transaction,
-    ) -> Result<Self::SignedTransaction, Self::Error>;
-} 
\ No newline at end of file
+    ) -> anyhow::Result<Self::SignedTransaction>;
+}
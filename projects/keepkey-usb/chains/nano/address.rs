@@ -0,0 +1,166 @@
+//! Nano address encoding and validation
+//!
+//! An address is `nano_`, the 256-bit ed25519 public key in Nano's base32
+//! (52 characters, padded with 4 zero bits at the front) and a 40-bit
+//! checksum (8 characters): the 5-byte BLAKE2b of the key, byte-reversed.
+
+use anyhow::{Result, anyhow, bail};
+use blake2::digest::consts::U5;
+use blake2::{Blake2b, Digest};
+use crate::device_queue::DeviceQueueHandle;
+
+/// Base32 alphabet of Nano addresses; no 0, 2, l or v
+const NANO_ALPHABET: &[u8; 32] = b"13456789abcdefghijkmnopqrstuwxyz";
+
+pub const NANO_PREFIX: &str = "nano_";
+
+/// Prefix of addresses created before the rename; still valid
+const LEGACY_PREFIX: &str = "xrb_";
+
+/// Characters for the public key and for the checksum
+const KEY_CHARS: usize = 52;
+const CHECKSUM_CHARS: usize = 8;
+
+fn checksum(public_key: &[u8; 32]) -> [u8; 5] {
+    let mut checksum: [u8; 5] = Blake2b::<U5>::digest(public_key).into();
+    checksum.reverse();
+    checksum
+}
+
+/// Big-endian bytes as `chars` base32 characters
+fn encode_base32(bytes: &[u8], chars: usize) -> String {
+    (0..chars)
+        .rev()
+        .map(|i| {
+            // Bit offset of this character, counted from the last byte
+            let bit = i * 5;
+            let mut value = 0u16;
+            for shift in 0..5 {
+                let bit = bit + shift;
+                let byte = bytes.len().checked_sub(bit / 8 + 1).map_or(0, |index| bytes[index]);
+                value |= (((byte >> (bit % 8)) & 1) as u16) << shift;
+            }
+            NANO_ALPHABET[value as usize] as char
+        })
+        .collect()
+}
+
+/// `text` as `N` big-endian bytes, refusing set bits above them
+fn decode_base32<const N: usize>(text: &str) -> Option<[u8; N]> {
+    let mut bytes = [0u8; N];
+    let mut overflow = 0u8;
+    for c in text.bytes() {
+        let mut carry = NANO_ALPHABET.iter().position(|&a| a == c)? as u16;
+        for byte in bytes.iter_mut().rev() {
+            carry |= (*byte as u16) << 5;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        overflow |= carry as u8;
+    }
+    (overflow == 0).then_some(bytes)
+}
+
+/// The `nano_` address of an ed25519 public key
+pub fn encode_nano_address(public_key: &[u8; 32]) -> String {
+    format!(
+        "{}{}{}",
+        NANO_PREFIX,
+        encode_base32(public_key, KEY_CHARS),
+        encode_base32(&checksum(public_key), CHECKSUM_CHARS)
+    )
+}
+
+/// The public key of a `nano_` (or legacy `xrb_`) address, checking its
+/// checksum
+pub fn decode_nano_address(address: &str) -> Result<[u8; 32]> {
+    let encoded = address
+        .strip_prefix(NANO_PREFIX)
+        .or_else(|| address.strip_prefix(LEGACY_PREFIX))
+        .ok_or_else(|| anyhow!("{} is not a Nano address: it must start with {}", address, NANO_PREFIX))?;
+    if encoded.len() != KEY_CHARS + CHECKSUM_CHARS || !encoded.is_ascii() {
+        bail!("{} is not a Nano address: wrong length", address);
+    }
+    let (key, check) = encoded.split_at(KEY_CHARS);
+    let invalid = || anyhow!("{} is not a Nano address: invalid character", address);
+    let public_key: [u8; 32] = decode_base32(key).ok_or_else(invalid)?;
+    let check: [u8; 5] = decode_base32(check).ok_or_else(invalid)?;
+    if check != checksum(&public_key) {
+        bail!("{} is not a Nano address: bad checksum", address);
+    }
+    Ok(public_key)
+}
+
+/// Check that `address` is a valid Nano address
+pub fn validate_nano_address(address: &str) -> Result<()> {
+    decode_nano_address(address).map(|_| ())
+}
+
+/// Get a Nano address from the device, shown on its screen with `display`
+pub async fn get_nano_address(
+    device_queue: &DeviceQueueHandle,
+    path: &[u32],
+    display: bool,
+) -> Result<String> {
+    let msg = crate::messages::NanoGetAddress {
+        address_n: path.to_vec(),
+        show_display: Some(display),
+        ..Default::default()
+    };
+
+    let response = device_queue
+        .send_raw(crate::messages::Message::NanoGetAddress(msg), display)
+        .await?;
+
+    match response {
+        crate::messages::Message::NanoAddress(addr) => {
+            let address = addr.address.ok_or_else(|| anyhow!("No address in response"))?;
+            validate_nano_address(&address)
+                .map_err(|e| anyhow!("Device returned an invalid address: {}", e))?;
+            Ok(address)
+        }
+        crate::messages::Message::Failure(f) => Err(anyhow!("Failure: {}", f.message())),
+        _ => Err(anyhow!("Unexpected response type")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Genesis account of the live network
+    const GENESIS: &str = "nano_3t6k35gi95xu6tergt6p69ck76ogmitsa8mnijtpxm9fkcm736xtoncuohr3";
+    const GENESIS_KEY: &str = "e89208dd038fbb269987689621d52292ae9c35941a7484756ecced92a65093ba";
+
+    #[test]
+    fn test_address_encoding() {
+        let key: [u8; 32] = hex::decode(GENESIS_KEY).unwrap().try_into().unwrap();
+        assert_eq!(encode_nano_address(&key), GENESIS);
+        assert_eq!(decode_nano_address(GENESIS).unwrap(), key);
+        assert_eq!(decode_nano_address(&GENESIS.replace("nano_", "xrb_")).unwrap(), key);
+
+        // The burn address, the all-zero key
+        let burn = "nano_1111111111111111111111111111111111111111111111111111hifc8npp";
+        assert_eq!(encode_nano_address(&[0; 32]), burn);
+        assert_eq!(decode_nano_address(burn).unwrap(), [0; 32]);
+    }
+
+    #[test]
+    fn test_invalid_addresses() {
+        let checksum = decode_nano_address(&GENESIS.replace("ohr3", "ohr4")).unwrap_err();
+        assert!(checksum.to_string().contains("checksum"));
+        for address in [
+            "",
+            &GENESIS[5..],
+            &GENESIS[..GENESIS.len() - 1],
+            // '2' and 'l' are not in the alphabet
+            &GENESIS.replace("3t6k", "2t6k"),
+            &GENESIS.replace("3t6k", "lt6k"),
+            // The first character only carries 1 bit of the key
+            &GENESIS.replace("nano_3", "nano_5"),
+            "ban_3t6k35gi95xu6tergt6p69ck76ogmitsa8mnijtpxm9fkcm736xtoncuohr3",
+        ] {
+            assert!(validate_nano_address(address).is_err(), "{}", address);
+        }
+    }
+}
@@ -1 +1,38 @@
-//! nano chain support stub
+//! Nano (XNO) support for KeepKey
+//!
+//! Accounts are ed25519 keys with BLAKE2b hashing; the device returns the
+//! `nano_` address and signs state blocks. Each block records the account's
+//! full balance, so a send is a lower balance linked to the recipient and a
+//! receive a higher one linked to the send block's hash.
+
+use anyhow::Result;
+
+pub mod address;
+pub mod transaction;
+
+pub use address::{decode_nano_address, encode_nano_address, get_nano_address, validate_nano_address, NANO_PREFIX};
+pub use transaction::{
+    parse_nano_raw, sign_nano_block, state_block_hash, NanoBlock, NanoLink, NanoParentBlock, SignedNanoBlock,
+};
+
+/// Main Nano support structure
+pub struct NanoSupport;
+
+impl NanoSupport {
+    /// Get a Nano address for the given path
+    pub async fn get_address(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        path: &[u32],
+        display: bool,
+    ) -> Result<String> {
+        address::get_nano_address(device_queue, path, display).await
+    }
+
+    /// Sign a state block
+    pub async fn sign_block(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        block: NanoBlock,
+    ) -> Result<SignedNanoBlock> {
+        transaction::sign_nano_block(device_queue, block).await
+    }
+}
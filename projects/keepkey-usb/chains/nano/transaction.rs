@@ -0,0 +1,393 @@
+//! Nano state block signing
+//!
+//! NanoSignTx carries the new block and, unless it opens the account, the
+//! block before it; the device hashes the parent itself to get `previous`,
+//! shows the amount sent or received and answers with NanoSignedTx holding
+//! the ed25519 signature and the block hash. The account key is read first
+//! with NanoGetAddress so the hash can be computed here as well; a device
+//! that signed a different block is refused.
+
+use std::future::Future;
+use anyhow::{Result, anyhow, bail};
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use crate::device_queue::DeviceQueueHandle;
+use crate::messages::{self, Message};
+use super::address::{decode_nano_address, validate_nano_address};
+
+/// The first field of every state block hash: 31 zero bytes and type 6
+const STATE_BLOCK_PREAMBLE: [u8; 32] = {
+    let mut preamble = [0u8; 32];
+    preamble[31] = 6;
+    preamble
+};
+
+/// The block before the one being signed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NanoParentBlock {
+    /// `previous` of the parent block; zero if it opened the account
+    pub parent_hash: [u8; 32],
+    pub link: [u8; 32],
+    pub representative: String,
+    /// Raw balance as a decimal string
+    pub balance: String,
+}
+
+/// What the block's `link` field points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NanoLink {
+    /// Send to an account; the link is its public key
+    Send { recipient: String },
+    /// Receive the funds of a send block; the link is its hash
+    Receive { source_hash: [u8; 32] },
+}
+
+/// A state block to sign
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NanoBlock {
+    /// Derivation path of the account
+    pub address_n: Vec<u32>,
+    /// None for the block that opens the account
+    pub parent: Option<NanoParentBlock>,
+    pub link: NanoLink,
+    pub representative: String,
+    /// Raw balance after this block as a decimal string
+    /// (1 XNO = 10^30 raw)
+    pub balance: String,
+}
+
+/// Signature and hash of a signed block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedNanoBlock {
+    pub signature: Vec<u8>,
+    pub block_hash: [u8; 32],
+}
+
+/// Parse a raw amount. Balances are 128-bit; anything larger is refused.
+pub fn parse_nano_raw(amount: &str) -> Result<u128> {
+    if amount.is_empty() || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        bail!("Invalid raw amount: {:?}", amount);
+    }
+    amount.parse().map_err(|_| anyhow!("Raw amount {} does not fit in 128 bits", amount))
+}
+
+/// Hash of a state block
+pub fn state_block_hash(
+    account: &[u8; 32],
+    previous: &[u8; 32],
+    representative: &[u8; 32],
+    balance: u128,
+    link: &[u8; 32],
+) -> [u8; 32] {
+    Blake2b::<U32>::new()
+        .chain_update(STATE_BLOCK_PREAMBLE)
+        .chain_update(account)
+        .chain_update(previous)
+        .chain_update(representative)
+        .chain_update(balance.to_be_bytes())
+        .chain_update(link)
+        .finalize()
+        .into()
+}
+
+/// The `link` field as hashed: the recipient's public key or the source hash
+pub fn link_bytes(link: &NanoLink) -> Result<[u8; 32]> {
+    match link {
+        NanoLink::Send { recipient } => decode_nano_address(recipient),
+        NanoLink::Receive { source_hash } => Ok(*source_hash),
+    }
+}
+
+impl NanoBlock {
+    /// Hash of this block on the account with public key `account`
+    pub fn hash(&self, account: &[u8; 32]) -> Result<[u8; 32]> {
+        let balance = self.validate()?;
+        let previous = match &self.parent {
+            Some(parent) => state_block_hash(
+                account,
+                &parent.parent_hash,
+                &decode_nano_address(&parent.representative)?,
+                parse_nano_raw(&parent.balance)?,
+                &parent.link,
+            ),
+            None => [0; 32],
+        };
+        Ok(state_block_hash(account, &previous, &decode_nano_address(&self.representative)?, balance, &link_bytes(&self.link)?))
+    }
+
+    fn validate(&self) -> Result<u128> {
+        validate_nano_address(&self.representative)?;
+        let balance = parse_nano_raw(&self.balance)?;
+        let previous = match &self.parent {
+            Some(parent) => {
+                validate_nano_address(&parent.representative)?;
+                Some(parse_nano_raw(&parent.balance)?)
+            }
+            None => None,
+        };
+        match (&self.link, previous) {
+            (NanoLink::Send { .. }, None) => bail!("An account is opened by receiving, not sending"),
+            (NanoLink::Send { recipient }, Some(previous)) => {
+                validate_nano_address(recipient)?;
+                if balance >= previous {
+                    bail!("A send block must lower the balance");
+                }
+            }
+            (NanoLink::Receive { source_hash }, previous) => {
+                if *source_hash == [0; 32] {
+                    bail!("A receive block needs the hash of the send block");
+                }
+                if balance <= previous.unwrap_or(0) {
+                    bail!("A receive block must raise the balance");
+                }
+            }
+        }
+        Ok(balance)
+    }
+}
+
+/// Run the NanoSignTx exchange, sending with `call`
+pub async fn sign_block_with<F, Fut>(mut call: F, block: &NanoBlock) -> Result<SignedNanoBlock>
+where
+    F: FnMut(Message) -> Fut,
+    Fut: Future<Output = Result<Message>>,
+{
+    let balance = block.validate()?;
+    let account = match call(Message::NanoGetAddress(messages::NanoGetAddress {
+        address_n: block.address_n.clone(),
+        show_display: Some(false),
+        ..Default::default()
+    })).await? {
+        Message::NanoAddress(address) => {
+            decode_nano_address(&address.address.ok_or_else(|| anyhow!("No address in response"))?)
+                .map_err(|e| anyhow!("Device returned an invalid address: {}", e))?
+        }
+        Message::Failure(f) => bail!("Device refused the address request: {}", f.message()),
+        Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+            bail!("Device must be unlocked before signing")
+        }
+        other => bail!("Unexpected response to the address request: {:?}", other.message_type()),
+    };
+    let expected_hash = block.hash(&account)?;
+
+    let parent_block = block
+        .parent
+        .as_ref()
+        .map(|parent| -> Result<_> {
+            Ok(messages::nano_sign_tx::ParentBlock {
+                parent_hash: Some(parent.parent_hash.to_vec()),
+                link: Some(parent.link.to_vec()),
+                representative: Some(parent.representative.clone()),
+                balance: Some(parse_nano_raw(&parent.balance)?.to_be_bytes().to_vec()),
+            })
+        })
+        .transpose()?;
+    let (link_recipient, link_hash) = match &block.link {
+        NanoLink::Send { recipient } => (Some(recipient.clone()), None),
+        NanoLink::Receive { source_hash } => (None, Some(source_hash.to_vec())),
+    };
+
+    let request = Message::NanoSignTx(messages::NanoSignTx {
+        address_n: block.address_n.clone(),
+        parent_block,
+        link_recipient,
+        link_hash,
+        representative: Some(block.representative.clone()),
+        balance: Some(balance.to_be_bytes().to_vec()),
+        ..Default::default()
+    });
+
+    match call(request).await? {
+        Message::NanoSignedTx(signed) => {
+            let signature = signed.signature.ok_or_else(|| anyhow!("No signature in response"))?;
+            if signature.len() != 64 {
+                bail!("Malformed signature in response");
+            }
+            let block_hash = signed
+                .block_hash
+                .ok_or_else(|| anyhow!("No block hash in response"))?
+                .try_into()
+                .map_err(|_| anyhow!("Malformed block hash in response"))?;
+            if block_hash != expected_hash {
+                bail!(
+                    "Device signed block {} but the block sent hashes to {}",
+                    hex::encode_upper(block_hash),
+                    hex::encode_upper(expected_hash)
+                );
+            }
+            Ok(SignedNanoBlock { signature, block_hash })
+        }
+        Message::Failure(f) => match f.code.and_then(messages::FailureType::from_i32) {
+            Some(messages::FailureType::FailureActionCancelled) => bail!("Transaction cancelled on the device"),
+            _ => bail!("Device refused the transaction: {}", f.message()),
+        },
+        Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+            bail!("Device must be unlocked before signing")
+        }
+        other => bail!("Unexpected response while signing: {:?}", other.message_type()),
+    }
+}
+
+/// Sign a Nano state block
+pub async fn sign_nano_block(device_queue: &DeviceQueueHandle, block: NanoBlock) -> Result<SignedNanoBlock> {
+    sign_block_with(move |message| device_queue.send_raw(message, true), &block).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::scripted_device::ScriptedDevice;
+
+    const GENESIS: &str = "nano_3t6k35gi95xu6tergt6p69ck76ogmitsa8mnijtpxm9fkcm736xtoncuohr3";
+    const BURN: &str = "nano_1111111111111111111111111111111111111111111111111111hifc8npp";
+
+    fn send(balance: &str) -> NanoBlock {
+        NanoBlock {
+            address_n: vec![0x8000_002c, 0x8000_00a5, 0x8000_0000],
+            parent: Some(NanoParentBlock {
+                parent_hash: [3; 32],
+                link: [4; 32],
+                representative: GENESIS.to_string(),
+                balance: "1000000000000000000000000000000".to_string(),
+            }),
+            link: NanoLink::Send { recipient: BURN.to_string() },
+            representative: GENESIS.to_string(),
+            balance: balance.to_string(),
+        }
+    }
+
+    fn receive(balance: &str) -> NanoBlock {
+        NanoBlock { parent: None, link: NanoLink::Receive { source_hash: [7; 32] }, ..send(balance) }
+    }
+
+    /// A device that answers the requests with `responses`, in order
+    async fn sign_scripted(responses: Vec<Message>, block: &NanoBlock) -> (Result<SignedNanoBlock>, Vec<Message>) {
+        let device = ScriptedDevice::new(responses);
+        let result = sign_block_with(|message| device.call(message), block).await;
+        (result, device.into_sent())
+    }
+
+    /// The account is GENESIS
+    fn address() -> Message {
+        Message::NanoAddress(messages::NanoAddress { address: Some(GENESIS.to_string()) })
+    }
+
+    fn signed(block_hash: [u8; 32]) -> Message {
+        Message::NanoSignedTx(messages::NanoSignedTx { signature: Some(vec![5; 64]), block_hash: Some(block_hash.to_vec()) })
+    }
+
+    /// The address and a signature over the right hash
+    fn device_signing(block: &NanoBlock) -> Vec<Message> {
+        let hash = block.hash(&decode_nano_address(GENESIS).unwrap()).unwrap();
+        vec![address(), signed(hash)]
+    }
+
+    #[test]
+    fn test_parse_nano_raw() {
+        assert_eq!(parse_nano_raw("0").unwrap(), 0);
+        assert_eq!(parse_nano_raw("1000000000000000000000000000000").unwrap(), 10u128.pow(30));
+        assert_eq!(parse_nano_raw("340282366920938463463374607431768211455").unwrap(), u128::MAX);
+        for amount in ["", "340282366920938463463374607431768211456", "+1", "-1", "1.5", "1e30", " 1"] {
+            assert!(parse_nano_raw(amount).is_err(), "{:?}", amount);
+        }
+    }
+
+    #[test]
+    fn test_state_block_hash() {
+        // Cross-checked with Python's hashlib.blake2b(digest_size=32)
+        let genesis = decode_nano_address(GENESIS).unwrap();
+        let hash = state_block_hash(&genesis, &[0; 32], &genesis, u128::MAX, &[0; 32]);
+        assert_eq!(hex::encode_upper(hash), "D18F1554683AD90AC4264C584490586CE58FF1BA94E03B5F580F19FF95917579");
+        assert_eq!(link_bytes(&NanoLink::Send { recipient: BURN.to_string() }).unwrap(), [0; 32]);
+    }
+
+    #[tokio::test]
+    async fn test_sign_send_and_receive() {
+        let block = send("400000000000000000000000000000");
+        let (result, sent) = sign_scripted(device_signing(&block), &block).await;
+        let genesis = decode_nano_address(GENESIS).unwrap();
+        let previous = state_block_hash(&genesis, &[3; 32], &genesis, 10u128.pow(30), &[4; 32]);
+        let expected = state_block_hash(&genesis, &previous, &genesis, 4 * 10u128.pow(29), &[0; 32]);
+        assert_eq!(result.unwrap().block_hash, expected);
+        match &sent[0] {
+            Message::NanoGetAddress(msg) => {
+                assert_eq!(msg.address_n, block.address_n);
+                assert_eq!(msg.show_display, Some(false));
+            }
+            other => panic!("sent {:?}", other.message_type()),
+        }
+        match &sent[1] {
+            Message::NanoSignTx(msg) => {
+                assert_eq!(msg.link_recipient.as_deref(), Some(BURN));
+                assert_eq!(msg.link_hash, None);
+                assert_eq!(msg.balance, Some((4 * 10u128.pow(29)).to_be_bytes().to_vec()));
+                let parent = msg.parent_block.as_ref().unwrap();
+                assert_eq!(parent.balance, Some(10u128.pow(30).to_be_bytes().to_vec()));
+            }
+            other => panic!("sent {:?}", other.message_type()),
+        }
+
+        // Opening the account by receiving: `previous` is zero
+        let block = receive("1");
+        let (result, sent) = sign_scripted(device_signing(&block), &block).await;
+        assert_eq!(result.unwrap().block_hash, state_block_hash(&genesis, &[0; 32], &genesis, 1, &[7; 32]));
+        match &sent[1] {
+            Message::NanoSignTx(msg) => {
+                assert_eq!(msg.link_recipient, None);
+                assert_eq!(msg.link_hash, Some(vec![7; 32]));
+                assert!(msg.parent_block.is_none());
+                assert_eq!(msg.balance.as_ref().unwrap().len(), 16);
+            }
+            other => panic!("sent {:?}", other.message_type()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_blocks_never_reach_the_device() {
+        for invalid in [
+            // Sends must lower the balance, receives raise it
+            send("1000000000000000000000000000000"),
+            send("2000000000000000000000000000000"),
+            receive("0"),
+            NanoBlock { parent: None, ..send("1") },
+            NanoBlock { link: NanoLink::Receive { source_hash: [0; 32] }, ..send("2000000000000000000000000000000") },
+            NanoBlock { link: NanoLink::Send { recipient: GENESIS.replace("ohr3", "ohr4") }, ..send("1") },
+            NanoBlock { representative: "nano_".to_string(), ..send("1") },
+            send("340282366920938463463374607431768211456"),
+        ] {
+            let (result, sent) = sign_scripted(vec![address(), signed([6; 32])], &invalid).await;
+            assert!(result.is_err());
+            assert!(sent.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_device_responses() {
+        let cancelled = Message::Failure(messages::Failure {
+            code: Some(messages::FailureType::FailureActionCancelled as i32),
+            message: Some("Cancelled".to_string()),
+        });
+        let (result, _) = sign_scripted(vec![address(), cancelled], &send("1")).await;
+        assert_eq!(result.unwrap_err().to_string(), "Transaction cancelled on the device");
+
+        let short_hash = Message::NanoSignedTx(messages::NanoSignedTx {
+            signature: Some(vec![5; 64]),
+            block_hash: Some(vec![6; 31]),
+        });
+        assert!(sign_scripted(vec![address(), short_hash], &send("1")).await.0.is_err());
+
+        // A hash other than the block's means the device signed something else
+        let (result, _) = sign_scripted(vec![address(), signed([6; 32])], &send("1")).await;
+        assert!(result.unwrap_err().to_string().starts_with("Device signed block 0606"));
+
+        // So does the right hash under another account
+        let other = NanoBlock { representative: BURN.to_string(), ..send("1") };
+        let other_hash = other.hash(&decode_nano_address(BURN).unwrap()).unwrap();
+        assert!(sign_scripted(vec![address(), signed(other_hash)], &other).await.0.is_err());
+
+        // A locked device answers the address request with its PIN matrix
+        let (result, sent) = sign_scripted(vec![Message::PinMatrixRequest(Default::default())], &send("1")).await;
+        assert_eq!(result.unwrap_err().to_string(), "Device must be unlocked before signing");
+        assert_eq!(sent.len(), 1);
+    }
+}
@@ -0,0 +1,39 @@
+//! Osmosis address generation
+
+use std::str::FromStr;
+use cosmrs::AccountId;
+use anyhow::{Result, anyhow};
+use crate::device_queue::DeviceQueueHandle;
+
+/// Bech32 prefix of Osmosis accounts
+pub const OSMOSIS_HRP: &str = "osmo";
+
+/// Get an Osmosis address from the device, shown on its screen with `display`
+pub async fn get_osmosis_address(
+    device_queue: &DeviceQueueHandle,
+    path: &[u32],
+    display: bool,
+) -> Result<String> {
+    let msg = crate::messages::OsmosisGetAddress {
+        address_n: path.to_vec(),
+        show_display: Some(display),
+    };
+
+    let response = device_queue
+        .send_raw(crate::messages::Message::OsmosisGetAddress(msg), display)
+        .await?;
+
+    match response {
+        crate::messages::Message::OsmosisAddress(addr) => {
+            let address = addr.address.ok_or_else(|| anyhow!("No address in response"))?;
+            let account = AccountId::from_str(&address)
+                .map_err(|e| anyhow!("Failed to parse address: {}", e))?;
+            if account.prefix() != OSMOSIS_HRP {
+                return Err(anyhow!("Device returned a {} address, not {}", account.prefix(), OSMOSIS_HRP));
+            }
+            Ok(address)
+        }
+        crate::messages::Message::Failure(f) => Err(anyhow!("Failure: {}", f.message())),
+        _ => Err(anyhow!("Unexpected response type")),
+    }
+}
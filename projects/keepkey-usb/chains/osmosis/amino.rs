@@ -0,0 +1,257 @@
+//! Amino JSON for Osmosis transactions
+//!
+//! Pool messages use the gamm types the device builds its sign doc with
+//! (`osmosis/gamm/...`); everything else is the Cosmos encoding. Sign docs
+//! can also be parsed, so a wallet can sign the Amino JSON a dApp hands it.
+
+use anyhow::{Result, anyhow, bail};
+use serde_json::{json, Value};
+use crate::chains::cosmos::amino::{amino_message_json, canonical_json, coin_json};
+use crate::chains::cosmos::{Coin, CosmosMessageType};
+use super::{OsmosisMessageType, SwapRoute};
+
+pub const SWAP_EXACT_AMOUNT_IN_TYPE: &str = "osmosis/gamm/swap-exact-amount-in";
+pub const JOIN_POOL_TYPE: &str = "osmosis/gamm/join-pool";
+pub const EXIT_POOL_TYPE: &str = "osmosis/gamm/exit-pool";
+
+fn coins_json(coins: &[Coin]) -> Result<Vec<Value>> {
+    coins.iter().map(coin_json).collect()
+}
+
+/// The `{"type", "value"}` object of a message
+pub fn osmosis_message_json(message: &OsmosisMessageType) -> Result<Value> {
+    let (kind, value) = match message {
+        OsmosisMessageType::Cosmos(message) => return amino_message_json(message),
+        OsmosisMessageType::SwapExactAmountIn { sender, routes, token_in, token_out_min_amount } => (
+            SWAP_EXACT_AMOUNT_IN_TYPE,
+            json!({
+                "routes": routes.iter().map(|route| json!({
+                    "pool_id": route.pool_id.to_string(),
+                    "token_out_denom": route.token_out_denom,
+                })).collect::<Vec<_>>(),
+                "sender": sender,
+                "token_in": coin_json(token_in)?,
+                "token_out_min_amount": token_out_min_amount,
+            }),
+        ),
+        OsmosisMessageType::JoinPool { sender, pool_id, share_out_amount, token_in_maxs } => (
+            JOIN_POOL_TYPE,
+            json!({
+                "pool_id": pool_id.to_string(),
+                "sender": sender,
+                "share_out_amount": share_out_amount,
+                "token_in_maxs": coins_json(token_in_maxs)?,
+            }),
+        ),
+        OsmosisMessageType::ExitPool { sender, pool_id, share_in_amount, token_out_mins } => (
+            EXIT_POOL_TYPE,
+            json!({
+                "pool_id": pool_id.to_string(),
+                "sender": sender,
+                "share_in_amount": share_in_amount,
+                "token_out_mins": coins_json(token_out_mins)?,
+            }),
+        ),
+    };
+    Ok(json!({ "type": kind, "value": value }))
+}
+
+/// StdSignDoc fields
+pub struct SignDoc<'a> {
+    pub chain_id: &'a str,
+    pub account_number: u64,
+    pub sequence: u64,
+    pub fee: &'a Coin,
+    pub gas: u64,
+    pub memo: &'a str,
+    pub messages: &'a [OsmosisMessageType],
+}
+
+/// Canonical JSON of a StdSignDoc, the bytes that are signed
+pub fn encode_sign_doc(doc: &SignDoc) -> Result<Vec<u8>> {
+    if doc.messages.is_empty() {
+        bail!("A transaction needs at least one message");
+    }
+    let value = json!({
+        "account_number": doc.account_number.to_string(),
+        "chain_id": doc.chain_id,
+        "fee": {
+            "amount": [coin_json(doc.fee)?],
+            "gas": doc.gas.to_string(),
+        },
+        "memo": doc.memo,
+        "msgs": doc.messages.iter().map(osmosis_message_json).collect::<Result<Vec<_>>>()?,
+        "sequence": doc.sequence.to_string(),
+    });
+    Ok(canonical_json(&value).into_bytes())
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value> {
+    value.get(name).ok_or_else(|| anyhow!("Missing field {}", name))
+}
+
+fn string_field(value: &Value, name: &str) -> Result<String> {
+    field(value, name)?
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Field {} is not a string", name))
+}
+
+/// Amino writes 64-bit integers as strings
+fn u64_field(value: &Value, name: &str) -> Result<u64> {
+    let text = string_field(value, name)?;
+    text.parse().map_err(|_| anyhow!("Field {} is not an integer: {:?}", name, text))
+}
+
+fn parse_coin(value: &Value) -> Result<Coin> {
+    Ok(Coin { denom: string_field(value, "denom")?, amount: string_field(value, "amount")? })
+}
+
+fn coins_field(value: &Value, name: &str) -> Result<Vec<Coin>> {
+    field(value, name)?
+        .as_array()
+        .ok_or_else(|| anyhow!("Field {} is not a list", name))?
+        .iter()
+        .map(parse_coin)
+        .collect()
+}
+
+/// Read one `{"type", "value"}` message of a sign doc
+pub fn parse_message(message: &Value) -> Result<OsmosisMessageType> {
+    let kind = string_field(message, "type")?;
+    let value = field(message, "value")?;
+    Ok(match kind.as_str() {
+        "cosmos-sdk/MsgSend" => OsmosisMessageType::Cosmos(CosmosMessageType::Send {
+            from_address: string_field(value, "from_address")?,
+            to_address: string_field(value, "to_address")?,
+            amount: coins_field(value, "amount")?,
+        }),
+        "cosmos-sdk/MsgDelegate" => OsmosisMessageType::Cosmos(CosmosMessageType::Delegate {
+            delegator_address: string_field(value, "delegator_address")?,
+            validator_address: string_field(value, "validator_address")?,
+            amount: parse_coin(field(value, "amount")?)?,
+        }),
+        "cosmos-sdk/MsgUndelegate" => OsmosisMessageType::Cosmos(CosmosMessageType::Undelegate {
+            delegator_address: string_field(value, "delegator_address")?,
+            validator_address: string_field(value, "validator_address")?,
+            amount: parse_coin(field(value, "amount")?)?,
+        }),
+        "cosmos-sdk/MsgTransfer" => OsmosisMessageType::Cosmos(CosmosMessageType::IbcTransfer {
+            sender: string_field(value, "sender")?,
+            receiver: string_field(value, "receiver")?,
+            amount: parse_coin(field(value, "token")?)?,
+            source_channel: string_field(value, "source_channel")?,
+            timeout_timestamp: u64_field(value, "timeout_timestamp")?,
+        }),
+        SWAP_EXACT_AMOUNT_IN_TYPE => OsmosisMessageType::SwapExactAmountIn {
+            sender: string_field(value, "sender")?,
+            routes: field(value, "routes")?
+                .as_array()
+                .ok_or_else(|| anyhow!("Field routes is not a list"))?
+                .iter()
+                .map(|route| Ok(SwapRoute {
+                    pool_id: u64_field(route, "pool_id")?,
+                    token_out_denom: string_field(route, "token_out_denom")?,
+                }))
+                .collect::<Result<Vec<_>>>()?,
+            token_in: parse_coin(field(value, "token_in")?)?,
+            token_out_min_amount: string_field(value, "token_out_min_amount")?,
+        },
+        JOIN_POOL_TYPE => OsmosisMessageType::JoinPool {
+            sender: string_field(value, "sender")?,
+            pool_id: u64_field(value, "pool_id")?,
+            share_out_amount: string_field(value, "share_out_amount")?,
+            token_in_maxs: coins_field(value, "token_in_maxs")?,
+        },
+        EXIT_POOL_TYPE => OsmosisMessageType::ExitPool {
+            sender: string_field(value, "sender")?,
+            pool_id: u64_field(value, "pool_id")?,
+            share_in_amount: string_field(value, "share_in_amount")?,
+            token_out_mins: coins_field(value, "token_out_mins")?,
+        },
+        other => bail!("The device cannot sign {} messages", other),
+    })
+}
+
+/// A parsed StdSignDoc
+#[derive(Debug, Clone)]
+pub struct ParsedSignDoc {
+    pub chain_id: String,
+    pub account_number: u64,
+    pub sequence: u64,
+    pub fee: Coin,
+    pub gas: u64,
+    pub memo: String,
+    pub messages: Vec<OsmosisMessageType>,
+}
+
+/// Parse an Amino JSON StdSignDoc
+pub fn parse_sign_doc(doc: &Value) -> Result<ParsedSignDoc> {
+    let fee = field(doc, "fee")?;
+    let [fee_coin] = coins_field(fee, "amount")?.try_into().map_err(|coins: Vec<Coin>| {
+        anyhow!("The device signs a fee of exactly one coin, not {}", coins.len())
+    })?;
+    Ok(ParsedSignDoc {
+        chain_id: string_field(doc, "chain_id")?,
+        account_number: u64_field(doc, "account_number")?,
+        sequence: u64_field(doc, "sequence")?,
+        fee: fee_coin,
+        gas: u64_field(fee, "gas")?,
+        memo: match doc.get("memo") {
+            None | Some(Value::Null) => String::new(),
+            Some(_) => string_field(doc, "memo")?,
+        },
+        messages: field(doc, "msgs")?
+            .as_array()
+            .ok_or_else(|| anyhow!("Field msgs is not a list"))?
+            .iter()
+            .map(parse_message)
+            .collect::<Result<Vec<_>>>()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SWAP: &str = include_str!("../../tests/fixtures/osmosis/swap_sign_doc.json");
+
+    #[test]
+    fn test_swap_sign_doc_round_trips() {
+        let value: Value = serde_json::from_str(SWAP).unwrap();
+        let parsed = parse_sign_doc(&value).unwrap();
+        match &parsed.messages[0] {
+            OsmosisMessageType::SwapExactAmountIn { routes, token_out_min_amount, .. } => {
+                assert_eq!(routes, &[SwapRoute { pool_id: 1, token_out_denom: "uosmo".to_string() }]);
+                assert_eq!(token_out_min_amount, "995000");
+            }
+            other => panic!("parsed {:?}", other),
+        }
+
+        let encoded = encode_sign_doc(&SignDoc {
+            chain_id: &parsed.chain_id,
+            account_number: parsed.account_number,
+            sequence: parsed.sequence,
+            fee: &parsed.fee,
+            gas: parsed.gas,
+            memo: &parsed.memo,
+            messages: &parsed.messages,
+        }).unwrap();
+        assert_eq!(String::from_utf8(encoded).unwrap(), SWAP.trim_end());
+    }
+
+    #[test]
+    fn test_unsupported_sign_docs() {
+        let mut value: Value = serde_json::from_str(SWAP).unwrap();
+        value["msgs"][0]["type"] = json!("osmosis/poolmanager/swap-exact-amount-in");
+        assert!(parse_sign_doc(&value).unwrap_err().to_string().contains("poolmanager"));
+
+        let mut value: Value = serde_json::from_str(SWAP).unwrap();
+        value["fee"]["amount"] = json!([]);
+        assert!(parse_sign_doc(&value).is_err());
+
+        let mut value: Value = serde_json::from_str(SWAP).unwrap();
+        value["msgs"][0]["value"]["routes"][0]["pool_id"] = json!(1);
+        assert!(parse_sign_doc(&value).is_err());
+    }
+}
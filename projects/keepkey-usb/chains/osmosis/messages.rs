@@ -0,0 +1,67 @@
+//! Osmosis message types
+
+use crate::chains::cosmos::{Coin, CosmosMessageType};
+
+/// Basis points in one whole
+const BPS: u128 = 10_000;
+
+/// One hop of a swap route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapRoute {
+    pub pool_id: u64,
+    pub token_out_denom: String,
+}
+
+/// Messages the device signs on Osmosis
+#[derive(Debug, Clone)]
+pub enum OsmosisMessageType {
+    /// Send, staking and IBC messages shared with the Cosmos Hub
+    Cosmos(CosmosMessageType),
+    /// gamm MsgSwapExactAmountIn: swap all of `token_in` through `routes`,
+    /// failing if less than `token_out_min_amount` comes out
+    SwapExactAmountIn {
+        sender: String,
+        routes: Vec<SwapRoute>,
+        token_in: Coin,
+        token_out_min_amount: String,
+    },
+    /// gamm MsgJoinPool: buy `share_out_amount` pool shares, spending at
+    /// most `token_in_maxs`
+    JoinPool {
+        sender: String,
+        pool_id: u64,
+        share_out_amount: String,
+        token_in_maxs: Vec<Coin>,
+    },
+    /// gamm MsgExitPool: redeem `share_in_amount` pool shares for at least
+    /// `token_out_mins`
+    ExitPool {
+        sender: String,
+        pool_id: u64,
+        share_in_amount: String,
+        token_out_mins: Vec<Coin>,
+    },
+}
+
+/// The least a swap may return: `expected_out` less `slippage_bps` basis
+/// points, rounded down
+pub fn token_out_min_amount(expected_out: u128, slippage_bps: u32) -> u128 {
+    let keep = BPS.saturating_sub(slippage_bps as u128);
+    expected_out / BPS * keep + expected_out % BPS * keep / BPS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_out_min_amount() {
+        assert_eq!(token_out_min_amount(1_000_000, 50), 995_000);
+        assert_eq!(token_out_min_amount(1_000_000, 0), 1_000_000);
+        // Rounds down so the bound never exceeds the allowed slippage
+        assert_eq!(token_out_min_amount(999, 100), 989);
+        assert_eq!(token_out_min_amount(1_000, 20_000), 0);
+        // No overflow on 18-decimal amounts
+        assert_eq!(token_out_min_amount(u128::MAX, 0), u128::MAX);
+    }
+}
@@ -1 +1,39 @@
-//! osmosis chain support stub
+//! Osmosis support for KeepKey
+//!
+//! Osmosis is signed with its own message set (OsmosisSignTx and friends).
+//! Sends, staking and IBC transfers reuse the Cosmos message types, wrapped
+//! in `OsmosisMessageType::Cosmos`; swaps and pool joins and exits are
+//! Osmosis variants alongside them.
+
+use anyhow::Result;
+
+pub mod address;
+pub mod amino;
+pub mod messages;
+pub mod transaction;
+
+pub use address::{get_osmosis_address, OSMOSIS_HRP};
+pub use messages::{token_out_min_amount, OsmosisMessageType, SwapRoute};
+pub use transaction::{sign_osmosis_transaction, OsmosisTransaction, SignedOsmosisTransaction};
+
+/// Main Osmosis support structure
+pub struct OsmosisSupport;
+
+impl OsmosisSupport {
+    /// Get an Osmosis address for the given path
+    pub async fn get_address(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        path: &[u32],
+        display: bool,
+    ) -> Result<String> {
+        address::get_osmosis_address(device_queue, path, display).await
+    }
+
+    /// Sign an Osmosis transaction
+    pub async fn sign_transaction(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        transaction: OsmosisTransaction,
+    ) -> Result<SignedOsmosisTransaction> {
+        transaction::sign_osmosis_transaction(device_queue, transaction).await
+    }
+}
@@ -0,0 +1,349 @@
+//! Osmosis transaction signing
+//!
+//! The OsmosisSignTx exchange mirrors Cosmos: the device asks for each
+//! message with OsmosisMsgRequest, rebuilds the Amino sign doc and returns
+//! the signature in OsmosisSignedTx. Swaps take a single pool, and pool
+//! joins and exits exactly two coins, as that is what the device shows.
+
+use std::future::Future;
+use anyhow::{Result, anyhow, bail};
+use serde_json::Value;
+use crate::chains::cosmos::amino::{canonical_json, IBC_TRANSFER_PORT};
+use crate::chains::cosmos::{Coin, CosmosMessageType};
+use crate::device_queue::DeviceQueueHandle;
+use crate::messages::{self, Message};
+use super::amino::{encode_sign_doc, parse_sign_doc, SignDoc};
+use super::OsmosisMessageType;
+
+/// Osmosis transaction structure
+#[derive(Debug, Clone)]
+pub struct OsmosisTransaction {
+    /// Derivation path of the signing key
+    pub address_n: Vec<u32>,
+    pub chain_id: String,
+    pub account_number: u64,
+    pub sequence: u64,
+    pub messages: Vec<OsmosisMessageType>,
+    pub fee: Coin,
+    pub gas: u64,
+    pub memo: String,
+}
+
+impl OsmosisTransaction {
+    /// Canonical Amino JSON sign doc
+    pub fn sign_doc(&self) -> Result<Vec<u8>> {
+        encode_sign_doc(&SignDoc {
+            chain_id: &self.chain_id,
+            account_number: self.account_number,
+            sequence: self.sequence,
+            fee: &self.fee,
+            gas: self.gas,
+            memo: &self.memo,
+            messages: &self.messages,
+        })
+    }
+
+    /// Read an Amino JSON StdSignDoc to sign with the key at `address_n`.
+    /// Refused unless it encodes back to the same canonical bytes, so the
+    /// device never signs a different document than the one given.
+    pub fn from_amino_json(address_n: Vec<u32>, sign_doc: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(sign_doc)
+            .map_err(|e| anyhow!("Invalid sign doc: {}", e))?;
+        let parsed = parse_sign_doc(&value)?;
+        let transaction = OsmosisTransaction {
+            address_n,
+            chain_id: parsed.chain_id,
+            account_number: parsed.account_number,
+            sequence: parsed.sequence,
+            messages: parsed.messages,
+            fee: parsed.fee,
+            gas: parsed.gas,
+            memo: parsed.memo,
+        };
+        if transaction.sign_doc()? != canonical_json(&value).into_bytes() {
+            bail!("The sign doc has fields the device cannot sign");
+        }
+        Ok(transaction)
+    }
+}
+
+/// Signature from the device with the sign doc it covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedOsmosisTransaction {
+    /// Compressed secp256k1 public key of the signer
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+    /// The signed bytes: the canonical Amino JSON sign doc
+    pub sign_doc: Vec<u8>,
+}
+
+fn device_amount(denom: &str, amount: &str) -> Result<u64> {
+    amount
+        .parse()
+        .map_err(|_| anyhow!("{} amount {} does not fit the device's 64-bit amounts", denom, amount))
+}
+
+/// Amount in the fee's denom, the only one the device shows for sends and staking
+fn native_amount(coin: &Coin, native_denom: &str) -> Result<u64> {
+    if coin.denom != native_denom {
+        bail!("The device can only sign {} amounts here, not {}", native_denom, coin.denom);
+    }
+    device_amount(&coin.denom, &coin.amount)
+}
+
+/// The two coins of a pool join or exit
+fn coin_pair<'a>(coins: &'a [Coin], what: &str) -> Result<(&'a Coin, &'a Coin)> {
+    match coins {
+        [a, b] => Ok((a, b)),
+        _ => bail!("The device signs {} of exactly two coins, not {}", what, coins.len()),
+    }
+}
+
+fn msg_ack(message: &OsmosisMessageType, native_denom: &str) -> Result<messages::OsmosisMsgAck> {
+    let mut ack = messages::OsmosisMsgAck::default();
+    match message {
+        OsmosisMessageType::Cosmos(CosmosMessageType::Send { from_address, to_address, amount }) => {
+            let [coin] = amount.as_slice() else {
+                bail!("The device signs sends of exactly one coin, not {}", amount.len());
+            };
+            ack.send = Some(messages::OsmosisMsgSend {
+                from_address: Some(from_address.clone()),
+                to_address: Some(to_address.clone()),
+                amount: Some(native_amount(coin, native_denom)?),
+                ..Default::default()
+            });
+        }
+        OsmosisMessageType::Cosmos(CosmosMessageType::Delegate { delegator_address, validator_address, amount }) => {
+            ack.delegate = Some(messages::OsmosisMsgDelegate {
+                delegator_address: Some(delegator_address.clone()),
+                validator_address: Some(validator_address.clone()),
+                amount: Some(native_amount(amount, native_denom)?),
+                ..Default::default()
+            });
+        }
+        OsmosisMessageType::Cosmos(CosmosMessageType::Undelegate { delegator_address, validator_address, amount }) => {
+            ack.undelegate = Some(messages::OsmosisMsgUndelegate {
+                delegator_address: Some(delegator_address.clone()),
+                validator_address: Some(validator_address.clone()),
+                amount: Some(native_amount(amount, native_denom)?),
+                ..Default::default()
+            });
+        }
+        OsmosisMessageType::Cosmos(CosmosMessageType::IbcTransfer { sender, receiver, amount, source_channel, .. }) => {
+            ack.ibc_transfer = Some(messages::OsmosisMsgIbcTransfer {
+                source_port: Some(IBC_TRANSFER_PORT.to_string()),
+                source_channel: Some(source_channel.clone()),
+                denom: Some(amount.denom.clone()),
+                amount: Some(device_amount(&amount.denom, &amount.amount)?),
+                sender: Some(sender.clone()),
+                receiver: Some(receiver.clone()),
+                ..Default::default()
+            });
+        }
+        OsmosisMessageType::SwapExactAmountIn { sender, routes, token_in, token_out_min_amount } => {
+            let [route] = routes.as_slice() else {
+                bail!("The device signs swaps through exactly one pool, not {}", routes.len());
+            };
+            ack.swap = Some(messages::OsmosisMsgSwap {
+                sender: Some(sender.clone()),
+                pool_id: Some(route.pool_id),
+                token_out_denom: Some(route.token_out_denom.clone()),
+                token_in_denom: Some(token_in.denom.clone()),
+                token_in_amount: Some(device_amount(&token_in.denom, &token_in.amount)?),
+                token_out_min_amount: Some(device_amount(&route.token_out_denom, token_out_min_amount)?),
+            });
+        }
+        OsmosisMessageType::JoinPool { sender, pool_id, share_out_amount, token_in_maxs } => {
+            let (a, b) = coin_pair(token_in_maxs, "pool joins")?;
+            ack.lp_add = Some(messages::OsmosisMsgLpAdd {
+                sender: Some(sender.clone()),
+                pool_id: Some(*pool_id),
+                share_out_amount: Some(share_out_amount.clone()),
+                denom_in_max_a: Some(a.denom.clone()),
+                amount_in_max_a: Some(device_amount(&a.denom, &a.amount)?),
+                denom_in_max_b: Some(b.denom.clone()),
+                amount_in_max_b: Some(device_amount(&b.denom, &b.amount)?),
+            });
+        }
+        OsmosisMessageType::ExitPool { sender, pool_id, share_in_amount, token_out_mins } => {
+            let (a, b) = coin_pair(token_out_mins, "pool exits")?;
+            ack.lp_remove = Some(messages::OsmosisMsgLpRemove {
+                sender: Some(sender.clone()),
+                pool_id: Some(*pool_id),
+                share_out_amount: Some(share_in_amount.clone()),
+                denom_out_min_a: Some(a.denom.clone()),
+                amount_out_min_a: Some(device_amount(&a.denom, &a.amount)?),
+                denom_out_min_b: Some(b.denom.clone()),
+                amount_out_min_b: Some(device_amount(&b.denom, &b.amount)?),
+            });
+        }
+    }
+    Ok(ack)
+}
+
+/// Run the OsmosisSignTx exchange, sending each message with `call` and
+/// answering the device's message requests until it returns the signature
+pub async fn sign_transaction_with<F, Fut>(
+    mut call: F,
+    transaction: &OsmosisTransaction,
+) -> Result<SignedOsmosisTransaction>
+where
+    F: FnMut(Message) -> Fut,
+    Fut: Future<Output = Result<Message>>,
+{
+    let sign_doc = transaction.sign_doc()?;
+    let acks = transaction.messages
+        .iter()
+        .map(|m| msg_ack(m, &transaction.fee.denom))
+        .collect::<Result<Vec<_>>>()?;
+    let fee_amount = u32::try_from(device_amount(&transaction.fee.denom, &transaction.fee.amount)?)
+        .map_err(|_| anyhow!("Fee {} is too large for the device", transaction.fee.amount))?;
+    let gas = u32::try_from(transaction.gas)
+        .map_err(|_| anyhow!("Gas {} is too large for the device", transaction.gas))?;
+
+    let mut message = Message::OsmosisSignTx(messages::OsmosisSignTx {
+        address_n: transaction.address_n.clone(),
+        account_number: Some(transaction.account_number),
+        chain_id: Some(transaction.chain_id.clone()),
+        fee_amount: Some(fee_amount),
+        gas: Some(gas),
+        memo: Some(transaction.memo.clone()),
+        sequence: Some(transaction.sequence),
+        msg_count: Some(acks.len() as u32),
+        ..Default::default()
+    });
+    let mut acks = acks.into_iter();
+
+    loop {
+        message = match call(message).await? {
+            Message::OsmosisMsgRequest(_) => {
+                let ack = acks.next()
+                    .ok_or_else(|| anyhow!("Device asked for more than {} messages", transaction.messages.len()))?;
+                Message::OsmosisMsgAck(ack)
+            }
+            Message::OsmosisSignedTx(signed) => {
+                if acks.next().is_some() {
+                    bail!("Device signed before receiving every message");
+                }
+                return Ok(SignedOsmosisTransaction {
+                    public_key: signed.public_key.ok_or_else(|| anyhow!("No public key in response"))?,
+                    signature: signed.signature.ok_or_else(|| anyhow!("No signature in response"))?,
+                    sign_doc,
+                });
+            }
+            Message::Failure(f) => match f.code.and_then(messages::FailureType::from_i32) {
+                Some(messages::FailureType::FailureActionCancelled) => {
+                    bail!("Transaction cancelled on the device")
+                }
+                _ => bail!("Device refused the transaction: {}", f.message()),
+            },
+            Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+                bail!("Device must be unlocked before signing")
+            }
+            other => bail!("Unexpected response while signing: {:?}", other.message_type()),
+        };
+    }
+}
+
+/// Sign an Osmosis transaction
+pub async fn sign_osmosis_transaction(
+    device_queue: &DeviceQueueHandle,
+    transaction: OsmosisTransaction,
+) -> Result<SignedOsmosisTransaction> {
+    sign_transaction_with(move |message| device_queue.send_raw(message, true), &transaction).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::scripted_device::ScriptedDevice;
+
+    const SWAP: &str = include_str!("../../tests/fixtures/osmosis/swap_sign_doc.json");
+    const ATOM: &str = "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2";
+
+    fn coin(denom: &str, amount: &str) -> Coin {
+        Coin { denom: denom.to_string(), amount: amount.to_string() }
+    }
+
+    fn transaction(messages: Vec<OsmosisMessageType>) -> OsmosisTransaction {
+        OsmosisTransaction {
+            address_n: vec![0x8000_002c, 0x8000_0076, 0x8000_0000, 0, 0],
+            chain_id: "osmosis-1".to_string(),
+            account_number: 12345,
+            sequence: 7,
+            messages,
+            fee: coin("uosmo", "2500"),
+            gas: 250_000,
+            memo: String::new(),
+        }
+    }
+
+    async fn sign_scripted(responses: Vec<Message>, transaction: &OsmosisTransaction) -> (Result<SignedOsmosisTransaction>, Vec<Message>) {
+        let device = ScriptedDevice::new(responses);
+        let result = sign_transaction_with(|message| device.call(message), transaction).await;
+        (result, device.into_sent())
+    }
+
+    fn signed() -> Message {
+        Message::OsmosisSignedTx(messages::OsmosisSignedTx {
+            public_key: Some(vec![2; 33]),
+            signature: Some(vec![9; 64]),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_sign_swap_and_pool_messages() {
+        let swap = OsmosisTransaction::from_amino_json(vec![0x8000_002c, 0x8000_0076, 0x8000_0000, 0, 0], SWAP).unwrap();
+        let mut messages = swap.messages.clone();
+        messages.push(OsmosisMessageType::JoinPool {
+            sender: "osmo1sender".to_string(),
+            pool_id: 1,
+            share_out_amount: "100000000000000000000".to_string(),
+            token_in_maxs: vec![coin(ATOM, "1000000"), coin("uosmo", "8000000")],
+        });
+        let tx = transaction(messages);
+        let request = || Message::OsmosisMsgRequest(Default::default());
+        let (result, sent) = sign_scripted(vec![request(), request(), signed()], &tx).await;
+        assert_eq!(result.unwrap().sign_doc, tx.sign_doc().unwrap());
+
+        match (&sent[1], &sent[2]) {
+            (Message::OsmosisMsgAck(swap), Message::OsmosisMsgAck(join)) => {
+                let swap = swap.swap.as_ref().unwrap();
+                assert_eq!((swap.pool_id, swap.token_out_min_amount), (Some(1), Some(995_000)));
+                assert_eq!(swap.token_in_denom.as_deref(), Some(ATOM));
+                let join = join.lp_add.as_ref().unwrap();
+                assert_eq!(join.share_out_amount.as_deref(), Some("100000000000000000000"));
+                assert_eq!((join.amount_in_max_a, join.amount_in_max_b), (Some(1_000_000), Some(8_000_000)));
+            }
+            _ => panic!("expected two OsmosisMsgAck"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unsignable_transactions_are_not_sent() {
+        let route = |pool_id| super::super::SwapRoute { pool_id, token_out_denom: "uosmo".to_string() };
+        for message in [
+            // Multi-hop routes
+            OsmosisMessageType::SwapExactAmountIn {
+                sender: "osmo1sender".to_string(),
+                routes: vec![route(1), route(678)],
+                token_in: coin(ATOM, "1000000"),
+                token_out_min_amount: "1".to_string(),
+            },
+            OsmosisMessageType::ExitPool {
+                sender: "osmo1sender".to_string(),
+                pool_id: 1,
+                share_in_amount: "5".to_string(),
+                token_out_mins: vec![coin("uosmo", "1")],
+            },
+        ] {
+            let (result, sent) = sign_scripted(vec![], &transaction(vec![message])).await;
+            assert!(result.is_err());
+            assert!(sent.is_empty());
+        }
+
+        // Fields the device would not sign
+        let extra = SWAP.replacen("\"memo\":\"\"", "\"memo\":\"\",\"timeout_height\":\"5\"", 1);
+        assert!(OsmosisTransaction::from_amino_json(Vec::new(), &extra).is_err());
+    }
+}
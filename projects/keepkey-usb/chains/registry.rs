@@ -0,0 +1,165 @@
+//! CAIP network identifiers mapped to the chain that serves them
+//!
+//! Each registered network knows which `ChainSupport` implementation derives
+//! its addresses, with what parameters, and the path of account 0's first
+//! address. Lookups take a CAIP-2 network id (`eip155:1`) or a CAIP-19 asset
+//! id on it (`eip155:1/slip44:60`).
+
+use std::collections::HashMap;
+use anyhow::{Result, anyhow};
+use bitcoin::Network;
+use crate::device_queue::DeviceQueueHandle;
+use super::bitcoin::ScriptType;
+use super::{BitcoinSupport, ChainSupport, CosmosSupport, EthereumSupport};
+
+const HARDENED: u32 = 0x8000_0000;
+
+pub const BITCOIN_NETWORK_ID: &str = "bip122:000000000019d6689c085ae165831e93";
+pub const ETHEREUM_NETWORK_ID: &str = "eip155:1";
+pub const COSMOS_HUB_NETWORK_ID: &str = "cosmos:cosmoshub-4";
+pub const OSMOSIS_NETWORK_ID: &str = "cosmos:osmosis-1";
+pub const THORCHAIN_NETWORK_ID: &str = "cosmos:thorchain-mainnet-v1";
+
+/// The chain module of a network and what its addresses depend on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainKind {
+    Bitcoin { network: Network, script_type: ScriptType },
+    Ethereum,
+    Cosmos { hrp: String },
+}
+
+/// A network in the registry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisteredChain {
+    /// CAIP-2 network id
+    pub network_id: String,
+    pub kind: ChainKind,
+    /// Used when no path is given
+    pub default_path: Vec<u32>,
+}
+
+/// Networks by CAIP-2 id
+#[derive(Debug, Clone)]
+pub struct ChainRegistry {
+    chains: HashMap<String, RegisteredChain>,
+}
+
+/// m/purpose'/coin_type'/0'/0/0
+fn first_address(purpose: u32, coin_type: u32) -> Vec<u32> {
+    vec![purpose | HARDENED, coin_type | HARDENED, HARDENED, 0, 0]
+}
+
+impl Default for ChainRegistry {
+    /// The networks the vault seeds its database with
+    fn default() -> Self {
+        let cosmos = |hrp: &str| ChainKind::Cosmos { hrp: hrp.to_string() };
+        let mut registry = Self::new();
+        registry.register(
+            BITCOIN_NETWORK_ID,
+            ChainKind::Bitcoin { network: Network::Bitcoin, script_type: ScriptType::P2WPKH },
+            first_address(84, 0),
+        );
+        registry.register(ETHEREUM_NETWORK_ID, ChainKind::Ethereum, first_address(44, 60));
+        registry.register(COSMOS_HUB_NETWORK_ID, cosmos("cosmos"), first_address(44, 118));
+        registry.register(OSMOSIS_NETWORK_ID, cosmos("osmo"), first_address(44, 118));
+        registry.register(THORCHAIN_NETWORK_ID, cosmos("thor"), first_address(44, 931));
+        registry
+    }
+}
+
+impl ChainRegistry {
+    /// An empty registry
+    pub fn new() -> Self {
+        ChainRegistry { chains: HashMap::new() }
+    }
+
+    /// Add a network, replacing any earlier entry for it
+    pub fn register(&mut self, network_id: &str, kind: ChainKind, default_path: Vec<u32>) {
+        self.chains.insert(
+            network_id.to_string(),
+            RegisteredChain { network_id: network_id.to_string(), kind, default_path },
+        );
+    }
+
+    /// The network of a CAIP-2 or CAIP-19 id. EVM networks without an entry
+    /// of their own share Ethereum's, since their addresses are the same.
+    pub fn resolve(&self, caip: &str) -> Option<&RegisteredChain> {
+        let network_id = caip.split('/').next().unwrap_or(caip);
+        self.chains.get(network_id).or_else(|| {
+            network_id
+                .strip_prefix("eip155:")
+                .filter(|chain_id| chain_id.parse::<u64>().is_ok())
+                .and_then(|_| self.chains.get(ETHEREUM_NETWORK_ID))
+        })
+    }
+
+    /// Get the address at `path` (the network's default when None) on the
+    /// network of `caip`
+    pub async fn get_address(
+        &self,
+        device_queue: &DeviceQueueHandle,
+        caip: &str,
+        path: Option<&[u32]>,
+        display: bool,
+    ) -> Result<String> {
+        let chain = self.resolve(caip).ok_or_else(|| anyhow!("No chain support for {}", caip))?;
+        let path = path.unwrap_or(&chain.default_path);
+        match &chain.kind {
+            ChainKind::Bitcoin { network, script_type } => {
+                <BitcoinSupport as ChainSupport>::get_address(device_queue, path, &(*script_type, *network), display).await
+            }
+            ChainKind::Ethereum => {
+                <EthereumSupport as ChainSupport>::get_address(device_queue, path, &(), display).await
+            }
+            ChainKind::Cosmos { hrp } => {
+                <CosmosSupport as ChainSupport>::get_address(device_queue, path, hrp, display).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_networks_resolve() {
+        let registry = ChainRegistry::default();
+        let cosmos = |hrp: &str| ChainKind::Cosmos { hrp: hrp.to_string() };
+        for (network_id, kind, coin_type) in [
+            (BITCOIN_NETWORK_ID, ChainKind::Bitcoin { network: Network::Bitcoin, script_type: ScriptType::P2WPKH }, 0),
+            (ETHEREUM_NETWORK_ID, ChainKind::Ethereum, 60),
+            (COSMOS_HUB_NETWORK_ID, cosmos("cosmos"), 118),
+            (OSMOSIS_NETWORK_ID, cosmos("osmo"), 118),
+            (THORCHAIN_NETWORK_ID, cosmos("thor"), 931),
+        ] {
+            let chain = registry.resolve(network_id).unwrap();
+            assert_eq!(chain.network_id, network_id);
+            assert_eq!(chain.kind, kind, "{}", network_id);
+            assert_eq!(chain.default_path[1], coin_type | HARDENED, "{}", network_id);
+        }
+    }
+
+    #[test]
+    fn test_resolve_ids() {
+        let registry = ChainRegistry::default();
+        // Asset ids resolve to their network
+        let chain = registry.resolve("cosmos:osmosis-1/slip44:118").unwrap();
+        assert_eq!(chain.network_id, OSMOSIS_NETWORK_ID);
+        let chain = registry.resolve("eip155:1/erc20:0xdac17f958d2ee523a2206206994597c13d831ec7").unwrap();
+        assert_eq!(chain.kind, ChainKind::Ethereum);
+
+        // Other EVM chains share Ethereum's entry
+        assert_eq!(registry.resolve("eip155:137").unwrap().network_id, ETHEREUM_NETWORK_ID);
+        for unknown in ["eip155:polygon", "cosmos:juno-1", "bip122:000000000933ea01ad0ee984209779ba", "", "ripple"] {
+            assert!(registry.resolve(unknown).is_none(), "{}", unknown);
+        }
+
+        // Registering a network adds it without touching the others
+        let mut registry = registry;
+        registry.register("cosmos:juno-1", ChainKind::Cosmos { hrp: "juno".to_string() }, first_address(44, 118));
+        assert!(registry.resolve("cosmos:juno-1").is_some());
+        assert!(registry.resolve(BITCOIN_NETWORK_ID).is_some());
+        assert!(ChainRegistry::new().resolve(ETHEREUM_NETWORK_ID).is_none());
+    }
+}
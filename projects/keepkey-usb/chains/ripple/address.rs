@@ -0,0 +1,111 @@
+//! XRP address generation and validation
+
+use anyhow::{Result, anyhow, bail};
+use sha2::{Digest, Sha256};
+use crate::device_queue::DeviceQueueHandle;
+
+/// Base58 alphabet of the XRP Ledger; it differs from Bitcoin's
+const RIPPLE_ALPHABET: &[u8; 58] = b"rpshnaf39wBUDNEGHJKLM4PQRST7VWXYZ2bcdeCg65jkm8oFqi1tuvAxyz";
+
+/// Type prefix of a classic account address
+const ACCOUNT_ID_PREFIX: u8 = 0x00;
+
+/// Prefix byte, 20-byte account id and 4-byte checksum
+const ADDRESS_BYTES: usize = 25;
+
+fn decode_base58(encoded: &str) -> Option<Vec<u8>> {
+    // Little-endian big number, multiplied by 58 for each digit
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in encoded.bytes() {
+        let mut carry = RIPPLE_ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    // Each leading zero digit stands for a zero byte
+    let zeros = encoded.bytes().take_while(|&c| c == RIPPLE_ALPHABET[0]).count();
+    bytes.extend(std::iter::repeat(0).take(zeros));
+    bytes.reverse();
+    Some(bytes)
+}
+
+/// Check that `address` is a classic XRP address: Ripple base58 with the
+/// account prefix and a valid double-SHA256 checksum. X-addresses are refused;
+/// use the classic address with a destination tag instead.
+pub fn validate_ripple_address(address: &str) -> Result<()> {
+    if address.starts_with('X') || address.starts_with('T') {
+        bail!("X-addresses are not supported; use the classic r-address and a destination tag");
+    }
+    let bytes = decode_base58(address)
+        .ok_or_else(|| anyhow!("{} is not a valid XRP address: not Ripple base58", address))?;
+    if bytes.len() != ADDRESS_BYTES || bytes[0] != ACCOUNT_ID_PREFIX {
+        bail!("{} is not a valid XRP address", address);
+    }
+    let (payload, checksum) = bytes.split_at(ADDRESS_BYTES - 4);
+    if Sha256::digest(Sha256::digest(payload))[..4] != *checksum {
+        bail!("{} is not a valid XRP address: bad checksum", address);
+    }
+    Ok(())
+}
+
+/// Get an XRP address from the device, shown on its screen with `display`
+pub async fn get_ripple_address(
+    device_queue: &DeviceQueueHandle,
+    path: &[u32],
+    display: bool,
+) -> Result<String> {
+    let msg = crate::messages::RippleGetAddress {
+        address_n: path.to_vec(),
+        show_display: Some(display),
+    };
+
+    let response = device_queue
+        .send_raw(crate::messages::Message::RippleGetAddress(msg), display)
+        .await?;
+
+    match response {
+        crate::messages::Message::RippleAddress(addr) => {
+            let address = addr.address.ok_or_else(|| anyhow!("No address in response"))?;
+            validate_ripple_address(&address)
+                .map_err(|e| anyhow!("Device returned an invalid address: {}", e))?;
+            Ok(address)
+        }
+        crate::messages::Message::Failure(f) => Err(anyhow!("Failure: {}", f.message())),
+        _ => Err(anyhow!("Unexpected response type")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_ripple_address() {
+        for address in [
+            "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh",
+            "rPT1Sjq2YGrBMTttX4GZHjKu9dyfzbpAYe",
+            // Account zero: every byte of the id is a leading zero digit
+            "rrrrrrrrrrrrrrrrrrrrrhoLvTp",
+        ] {
+            assert!(validate_ripple_address(address).is_ok(), "{}", address);
+        }
+
+        let checksum = validate_ripple_address("rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTi").unwrap_err();
+        assert!(checksum.to_string().contains("checksum"));
+        for address in [
+            "",
+            // Bitcoin alphabet and address
+            "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2",
+            "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyT0",
+            "X7AcgcsBL6XDcUb289X4mJ8djcdyKaB5hJDWMArnXr61cqZ",
+        ] {
+            assert!(validate_ripple_address(address).is_err(), "{}", address);
+        }
+    }
+}
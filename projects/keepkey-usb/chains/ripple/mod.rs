@@ -1 +1,35 @@
-//! ripple chain support stub
+//! Ripple (XRP Ledger) support for KeepKey
+//!
+//! The firmware derives classic `r...` addresses and signs Payment
+//! transactions in XRP; it returns the serialized transaction, ready to
+//! submit to a rippled node.
+
+use anyhow::Result;
+
+pub mod address;
+pub mod transaction;
+
+pub use address::{get_ripple_address, validate_ripple_address};
+pub use transaction::{sign_ripple_payment, RipplePayment, SignedRipplePayment};
+
+/// Main Ripple support structure
+pub struct RippleSupport;
+
+impl RippleSupport {
+    /// Get an XRP address for the given path
+    pub async fn get_address(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        path: &[u32],
+        display: bool,
+    ) -> Result<String> {
+        address::get_ripple_address(device_queue, path, display).await
+    }
+
+    /// Sign an XRP payment
+    pub async fn sign_payment(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        payment: RipplePayment,
+    ) -> Result<SignedRipplePayment> {
+        transaction::sign_ripple_payment(device_queue, payment).await
+    }
+}
@@ -0,0 +1,191 @@
+//! XRP payment signing
+//!
+//! RippleSignTx carries the whole Payment; the device shows the amount and
+//! destination (with its tag), then answers with RippleSignedTx holding the
+//! signature and the signed transaction in the ledger's binary format.
+
+use std::future::Future;
+use anyhow::{Result, anyhow, bail};
+use sha2::{Digest, Sha512};
+use crate::device_queue::DeviceQueueHandle;
+use crate::messages::{self, Message};
+use super::address::validate_ripple_address;
+
+/// Prefix hashed with a signed transaction to give its id ("TXN\0")
+const TRANSACTION_ID_PREFIX: [u8; 4] = *b"TXN\0";
+
+/// Largest amount in drops: the 100 billion XRP that exist
+pub const MAX_DROPS: u64 = 100_000_000_000 * 1_000_000;
+
+/// An XRP Payment to sign
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RipplePayment {
+    /// Derivation path of the sending account
+    pub address_n: Vec<u32>,
+    /// Amount in drops (1 XRP = 1,000,000 drops)
+    pub amount_drops: u64,
+    /// Classic r-address of the recipient
+    pub destination: String,
+    /// Identifies the recipient at a shared address; exchanges require one
+    pub destination_tag: Option<u32>,
+    /// Fee in drops
+    pub fee: u64,
+    /// Account sequence number
+    pub sequence: u32,
+    /// Transaction flags
+    pub flags: u32,
+    /// Last ledger the transaction may be included in
+    pub last_ledger_sequence: Option<u32>,
+}
+
+impl RipplePayment {
+    fn validate(&self) -> Result<()> {
+        validate_ripple_address(&self.destination)?;
+        if self.amount_drops == 0 || self.amount_drops > MAX_DROPS {
+            bail!("Amount of {} drops is out of range", self.amount_drops);
+        }
+        if self.fee == 0 {
+            bail!("The fee must be at least one drop");
+        }
+        Ok(())
+    }
+}
+
+/// Signature from the device and the transaction it signed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedRipplePayment {
+    pub signature: Vec<u8>,
+    /// The signed transaction in the ledger's binary format
+    pub serialized_tx: Vec<u8>,
+}
+
+impl SignedRipplePayment {
+    /// Upper-case hex `tx_blob` for rippled's `submit`
+    pub fn tx_blob(&self) -> String {
+        hex::encode_upper(&self.serialized_tx)
+    }
+
+    /// Transaction id as the ledger reports it: the first half of the
+    /// SHA-512 of the prefixed signed transaction, upper-case hex
+    pub fn tx_hash(&self) -> String {
+        let digest = Sha512::new()
+            .chain_update(TRANSACTION_ID_PREFIX)
+            .chain_update(&self.serialized_tx)
+            .finalize();
+        hex::encode_upper(&digest[..32])
+    }
+}
+
+/// Run the RippleSignTx exchange, sending with `call`
+pub async fn sign_payment_with<F, Fut>(mut call: F, payment: &RipplePayment) -> Result<SignedRipplePayment>
+where
+    F: FnMut(Message) -> Fut,
+    Fut: Future<Output = Result<Message>>,
+{
+    payment.validate()?;
+
+    let request = Message::RippleSignTx(messages::RippleSignTx {
+        address_n: payment.address_n.clone(),
+        fee: Some(payment.fee),
+        flags: Some(payment.flags),
+        sequence: Some(payment.sequence),
+        last_ledger_sequence: payment.last_ledger_sequence,
+        payment: Some(messages::RipplePayment {
+            amount: Some(payment.amount_drops),
+            destination: Some(payment.destination.clone()),
+            destination_tag: payment.destination_tag,
+        }),
+    });
+
+    match call(request).await? {
+        Message::RippleSignedTx(signed) => Ok(SignedRipplePayment {
+            signature: signed.signature.ok_or_else(|| anyhow!("No signature in response"))?,
+            serialized_tx: signed.serialized_tx.ok_or_else(|| anyhow!("No serialized transaction in response"))?,
+        }),
+        Message::Failure(f) => match f.code.and_then(messages::FailureType::from_i32) {
+            Some(messages::FailureType::FailureActionCancelled) => bail!("Transaction cancelled on the device"),
+            _ => bail!("Device refused the transaction: {}", f.message()),
+        },
+        Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+            bail!("Device must be unlocked before signing")
+        }
+        other => bail!("Unexpected response while signing: {:?}", other.message_type()),
+    }
+}
+
+/// Sign an XRP payment
+pub async fn sign_ripple_payment(
+    device_queue: &DeviceQueueHandle,
+    payment: RipplePayment,
+) -> Result<SignedRipplePayment> {
+    sign_payment_with(move |message| device_queue.send_raw(message, true), &payment).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::scripted_device::ScriptedDevice;
+
+    fn payment() -> RipplePayment {
+        RipplePayment {
+            address_n: vec![0x8000_002c, 0x8000_0090, 0x8000_0000, 0, 0],
+            amount_drops: 25_000_000,
+            destination: "rPT1Sjq2YGrBMTttX4GZHjKu9dyfzbpAYe".to_string(),
+            destination_tag: Some(12345),
+            fee: 12,
+            sequence: 7,
+            flags: 0x8000_0000,
+            last_ledger_sequence: Some(80_000_000),
+        }
+    }
+
+    /// A device that answers the request with `response`
+    async fn sign_scripted(response: Option<Message>, payment: &RipplePayment) -> (Result<SignedRipplePayment>, Vec<Message>) {
+        let device = ScriptedDevice::new(response);
+        let result = sign_payment_with(|message| device.call(message), payment).await;
+        (result, device.into_sent())
+    }
+
+    #[tokio::test]
+    async fn test_sign_payment() {
+        let signed = Message::RippleSignedTx(messages::RippleSignedTx {
+            signature: Some(vec![0x30; 70]),
+            serialized_tx: Some(vec![0x12, 0x00, 0x00, 0xab]),
+        });
+        let (result, sent) = sign_scripted(Some(signed), &payment()).await;
+        let signed = result.unwrap();
+        assert_eq!(signed.tx_blob(), "120000AB");
+        assert_eq!(signed.tx_hash().len(), 64);
+
+        match &sent[0] {
+            Message::RippleSignTx(sign_tx) => {
+                assert_eq!((sign_tx.fee, sign_tx.sequence, sign_tx.flags), (Some(12), Some(7), Some(0x8000_0000)));
+                let sent_payment = sign_tx.payment.as_ref().unwrap();
+                assert_eq!((sent_payment.amount, sent_payment.destination_tag), (Some(25_000_000), Some(12345)));
+            }
+            other => panic!("sent {:?}", other.message_type()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_payments_are_not_sent() {
+        let invalid = [
+            RipplePayment { destination: "rPT1Sjq2YGrBMTttX4GZHjKu9dyfzbpAYf".to_string(), ..payment() },
+            RipplePayment { amount_drops: 0, ..payment() },
+            RipplePayment { amount_drops: MAX_DROPS + 1, ..payment() },
+            RipplePayment { fee: 0, ..payment() },
+        ];
+        for payment in invalid {
+            let (result, sent) = sign_scripted(None, &payment).await;
+            assert!(result.is_err(), "{:?}", payment);
+            assert!(sent.is_empty());
+        }
+
+        let cancelled = Message::Failure(messages::Failure {
+            code: Some(messages::FailureType::FailureActionCancelled as i32),
+            message: Some("Signing cancelled by user".to_string()),
+        });
+        let (result, _) = sign_scripted(Some(cancelled), &payment()).await;
+        assert!(result.unwrap_err().to_string().contains("cancelled on the device"));
+    }
+}
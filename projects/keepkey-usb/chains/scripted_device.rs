@@ -0,0 +1,38 @@
+//! Scripted device for the chain signers' tests
+//!
+//! Each signer's `sign_*_with` takes the call that sends a message and awaits
+//! the device's answer; tests pass `ScriptedDevice::call` to replay canned
+//! responses and then inspect what the signer sent.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use anyhow::{anyhow, Result};
+use crate::messages::Message;
+
+/// A device that answers each message with the next scripted response
+pub struct ScriptedDevice {
+    responses: RefCell<VecDeque<Message>>,
+    sent: RefCell<Vec<Message>>,
+}
+
+impl ScriptedDevice {
+    pub fn new(responses: impl IntoIterator<Item = Message>) -> Self {
+        Self {
+            responses: RefCell::new(responses.into_iter().collect()),
+            sent: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Record `message` and answer it; an error once the script runs out
+    pub fn call(&self, message: Message) -> impl Future<Output = Result<Message>> {
+        self.sent.borrow_mut().push(message);
+        let response = self.responses.borrow_mut().pop_front();
+        async move { response.ok_or_else(|| anyhow!("No more responses")) }
+    }
+
+    /// Every message the signer sent, in order
+    pub fn into_sent(self) -> Vec<Message> {
+        self.sent.into_inner()
+    }
+}
@@ -0,0 +1,74 @@
+//! THORChain and Mayachain address generation and validation
+
+use std::str::FromStr;
+use cosmrs::AccountId;
+use anyhow::{Result, anyhow, bail};
+use crate::device_queue::DeviceQueueHandle;
+use crate::messages::{self, Message};
+use super::ThorNetwork;
+
+/// Check that `address` is a bech32 account address with the prefix of
+/// `network` (mainnet or testnet)
+pub fn validate_thor_address(address: &str, network: ThorNetwork, testnet: bool) -> Result<()> {
+    let account = AccountId::from_str(address)
+        .map_err(|e| anyhow!("{} is not a valid address: {}", address, e))?;
+    let hrp = network.hrp(testnet);
+    if account.prefix() != hrp {
+        bail!("{} is not a {} address", address, hrp);
+    }
+    Ok(())
+}
+
+/// Get an address on `network` from the device, shown on its screen with
+/// `display`
+pub async fn get_thor_address(
+    device_queue: &DeviceQueueHandle,
+    network: ThorNetwork,
+    path: &[u32],
+    testnet: bool,
+    display: bool,
+) -> Result<String> {
+    let request = match network {
+        ThorNetwork::Thorchain => Message::ThorchainGetAddress(messages::ThorchainGetAddress {
+            address_n: path.to_vec(),
+            show_display: Some(display),
+            testnet: Some(testnet),
+        }),
+        ThorNetwork::Mayachain => Message::MayachainGetAddress(messages::MayachainGetAddress {
+            address_n: path.to_vec(),
+            show_display: Some(display),
+            testnet: Some(testnet),
+        }),
+    };
+
+    let address = match device_queue.send_raw(request, display).await? {
+        Message::ThorchainAddress(addr) => addr.address,
+        Message::MayachainAddress(addr) => addr.address,
+        Message::Failure(f) => return Err(anyhow!("Failure: {}", f.message())),
+        _ => return Err(anyhow!("Unexpected response type")),
+    };
+    let address = address.ok_or_else(|| anyhow!("No address in response"))?;
+    validate_thor_address(&address, network, testnet)
+        .map_err(|e| anyhow!("Device returned an unexpected address: {}", e))?;
+    Ok(address)
+}
+
+/// Get a THORChain address (`thor`, or `tthor` on testnet)
+pub async fn get_thorchain_address(
+    device_queue: &DeviceQueueHandle,
+    path: &[u32],
+    testnet: bool,
+    display: bool,
+) -> Result<String> {
+    get_thor_address(device_queue, ThorNetwork::Thorchain, path, testnet, display).await
+}
+
+/// Get a Mayachain address (`maya`, or `tmaya` on testnet)
+pub async fn get_mayachain_address(
+    device_queue: &DeviceQueueHandle,
+    path: &[u32],
+    testnet: bool,
+    display: bool,
+) -> Result<String> {
+    get_thor_address(device_queue, ThorNetwork::Mayachain, path, testnet, display).await
+}
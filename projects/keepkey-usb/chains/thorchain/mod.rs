@@ -1 +1,85 @@
-//! thorchain chain support stub
+//! THORChain and Mayachain support for KeepKey
+//!
+//! Both are Cosmos SDK chains signed with the same device flow: a SignTx,
+//! one MsgRequest/MsgAck round per message, then SignedTx. Besides sends
+//! they take MsgDeposit, whose memo is the entry point for swaps and
+//! liquidity ("=:ETH.ETH:0x...", "+:BTC.BTC", ...). Memos go to the device
+//! and into the sign doc exactly as given.
+
+use anyhow::Result;
+
+pub mod address;
+pub mod transaction;
+
+pub use address::{get_mayachain_address, get_thorchain_address, get_thor_address, validate_thor_address};
+pub use transaction::{
+    sign_mayachain_tx, sign_thorchain_tx, ThorMessage, ThorTransaction, SignedThorTransaction, MAX_MEMO_BYTES,
+};
+
+/// Which of the two chains a request is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThorNetwork {
+    Thorchain,
+    Mayachain,
+}
+
+impl ThorNetwork {
+    /// Bech32 prefix of account addresses
+    pub fn hrp(self, testnet: bool) -> &'static str {
+        match (self, testnet) {
+            (ThorNetwork::Thorchain, false) => "thor",
+            (ThorNetwork::Thorchain, true) => "tthor",
+            (ThorNetwork::Mayachain, false) => "maya",
+            (ThorNetwork::Mayachain, true) => "tmaya",
+        }
+    }
+
+    /// Denom of the native coin, used for sends and the fee
+    pub fn native_denom(self) -> &'static str {
+        match self {
+            ThorNetwork::Thorchain => "rune",
+            ThorNetwork::Mayachain => "cacao",
+        }
+    }
+
+    /// The native coin in the chain's asset notation, as used by MsgDeposit
+    pub fn native_asset(self) -> &'static str {
+        match self {
+            ThorNetwork::Thorchain => "THOR.RUNE",
+            ThorNetwork::Mayachain => "MAYA.CACAO",
+        }
+    }
+
+    /// Prefix of the Amino message types
+    fn amino_prefix(self) -> &'static str {
+        match self {
+            ThorNetwork::Thorchain => "thorchain",
+            ThorNetwork::Mayachain => "mayachain",
+        }
+    }
+}
+
+/// Main THORChain/Mayachain support structure
+pub struct ThorchainSupport;
+
+impl ThorchainSupport {
+    /// Get an address for the given path on `network`
+    pub async fn get_address(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        network: ThorNetwork,
+        path: &[u32],
+        testnet: bool,
+        display: bool,
+    ) -> Result<String> {
+        address::get_thor_address(device_queue, network, path, testnet, display).await
+    }
+
+    /// Sign a transaction on `network`
+    pub async fn sign_transaction(
+        device_queue: &crate::device_queue::DeviceQueueHandle,
+        network: ThorNetwork,
+        transaction: ThorTransaction,
+    ) -> Result<SignedThorTransaction> {
+        transaction::sign_thor_transaction(device_queue, network, transaction).await
+    }
+}
@@ -0,0 +1,425 @@
+//! THORChain and Mayachain transaction signing
+//!
+//! After ThorchainSignTx (MayachainSignTx) the device asks for each message
+//! with a MsgRequest, rebuilds the Amino sign doc and returns the signature.
+//! The sign doc is also encoded here so callers get the bytes that were
+//! signed.
+
+use std::future::Future;
+use anyhow::{Result, anyhow, bail};
+use serde_json::{json, Value};
+use crate::chains::cosmos::amino::canonical_json;
+use crate::device_queue::DeviceQueueHandle;
+use crate::messages::{self, Message};
+use super::address::validate_thor_address;
+use super::ThorNetwork;
+
+/// Longest memo the chains accept
+pub const MAX_MEMO_BYTES: usize = 250;
+
+/// A THORChain/Mayachain message; amounts are in the chain's base units
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThorMessage {
+    /// Send the native coin
+    Send {
+        from_address: String,
+        to_address: String,
+        amount: u64,
+    },
+    /// Deposit into the protocol; `memo` says what to do with it
+    Deposit {
+        signer: String,
+        /// Asset notation, e.g. "THOR.RUNE"
+        asset: String,
+        amount: u64,
+        memo: String,
+    },
+}
+
+/// A transaction to sign
+#[derive(Debug, Clone)]
+pub struct ThorTransaction {
+    /// Derivation path of the signing key
+    pub address_n: Vec<u32>,
+    pub chain_id: String,
+    pub account_number: u64,
+    pub sequence: u64,
+    pub messages: Vec<ThorMessage>,
+    /// Fee in the native denom
+    pub fee: u64,
+    pub gas: u64,
+    pub memo: String,
+    pub testnet: bool,
+}
+
+/// Signature from the device with the sign doc it covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedThorTransaction {
+    /// Compressed secp256k1 public key of the signer
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+    /// The signed bytes: the canonical Amino JSON sign doc
+    pub sign_doc: Vec<u8>,
+}
+
+fn check_memo(memo: &str) -> Result<()> {
+    if memo.len() > MAX_MEMO_BYTES {
+        bail!("Memo is {} bytes; the limit is {}", memo.len(), MAX_MEMO_BYTES);
+    }
+    Ok(())
+}
+
+impl ThorTransaction {
+    fn validate(&self, network: ThorNetwork) -> Result<()> {
+        if self.messages.is_empty() {
+            bail!("A transaction needs at least one message");
+        }
+        check_memo(&self.memo)?;
+        for message in &self.messages {
+            match message {
+                ThorMessage::Send { from_address, to_address, amount } => {
+                    validate_thor_address(from_address, network, self.testnet)?;
+                    validate_thor_address(to_address, network, self.testnet)?;
+                    if *amount == 0 {
+                        bail!("Cannot send zero {}", network.native_denom());
+                    }
+                }
+                ThorMessage::Deposit { signer, asset, amount, memo } => {
+                    validate_thor_address(signer, network, self.testnet)?;
+                    if asset.is_empty() {
+                        bail!("A deposit needs an asset");
+                    }
+                    if *amount == 0 {
+                        bail!("Cannot deposit zero {}", asset);
+                    }
+                    check_memo(memo)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Canonical Amino JSON sign doc
+    pub fn sign_doc(&self, network: ThorNetwork) -> Vec<u8> {
+        let denom = network.native_denom();
+        let msgs: Vec<Value> = self.messages.iter().map(|message| match message {
+            ThorMessage::Send { from_address, to_address, amount } => json!({
+                "type": format!("{}/MsgSend", network.amino_prefix()),
+                "value": {
+                    "amount": [{ "amount": amount.to_string(), "denom": denom }],
+                    "from_address": from_address,
+                    "to_address": to_address,
+                },
+            }),
+            ThorMessage::Deposit { signer, asset, amount, memo } => json!({
+                "type": format!("{}/MsgDeposit", network.amino_prefix()),
+                "value": {
+                    "coins": [{ "amount": amount.to_string(), "asset": asset }],
+                    "memo": memo,
+                    "signer": signer,
+                },
+            }),
+        }).collect();
+        let doc = json!({
+            "account_number": self.account_number.to_string(),
+            "chain_id": self.chain_id,
+            "fee": {
+                "amount": [{ "amount": self.fee.to_string(), "denom": denom }],
+                "gas": self.gas.to_string(),
+            },
+            "memo": self.memo,
+            "msgs": msgs,
+            "sequence": self.sequence.to_string(),
+        });
+        canonical_json(&doc).into_bytes()
+    }
+}
+
+fn sign_tx_message(network: ThorNetwork, transaction: &ThorTransaction) -> Result<Message> {
+    let fee_amount = u32::try_from(transaction.fee)
+        .map_err(|_| anyhow!("Fee {} is too large for the device", transaction.fee))?;
+    let gas = u32::try_from(transaction.gas)
+        .map_err(|_| anyhow!("Gas {} is too large for the device", transaction.gas))?;
+    let msg_count = transaction.messages.len() as u32;
+    Ok(match network {
+        ThorNetwork::Thorchain => Message::ThorchainSignTx(messages::ThorchainSignTx {
+            address_n: transaction.address_n.clone(),
+            account_number: Some(transaction.account_number),
+            chain_id: Some(transaction.chain_id.clone()),
+            fee_amount: Some(fee_amount),
+            gas: Some(gas),
+            memo: Some(transaction.memo.clone()),
+            sequence: Some(transaction.sequence),
+            msg_count: Some(msg_count),
+            testnet: Some(transaction.testnet),
+            ..Default::default()
+        }),
+        ThorNetwork::Mayachain => Message::MayachainSignTx(messages::MayachainSignTx {
+            address_n: transaction.address_n.clone(),
+            account_number: Some(transaction.account_number),
+            chain_id: Some(transaction.chain_id.clone()),
+            fee_amount: Some(fee_amount),
+            gas: Some(gas),
+            memo: Some(transaction.memo.clone()),
+            sequence: Some(transaction.sequence),
+            msg_count: Some(msg_count),
+            testnet: Some(transaction.testnet),
+            ..Default::default()
+        }),
+    })
+}
+
+fn msg_ack(network: ThorNetwork, message: &ThorMessage) -> Message {
+    match (network, message) {
+        (ThorNetwork::Thorchain, ThorMessage::Send { from_address, to_address, amount }) => {
+            Message::ThorchainMsgAck(messages::ThorchainMsgAck {
+                send: Some(messages::ThorchainMsgSend {
+                    from_address: Some(from_address.clone()),
+                    to_address: Some(to_address.clone()),
+                    amount: Some(*amount),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        }
+        (ThorNetwork::Thorchain, ThorMessage::Deposit { signer, asset, amount, memo }) => {
+            Message::ThorchainMsgAck(messages::ThorchainMsgAck {
+                deposit: Some(messages::ThorchainMsgDeposit {
+                    asset: Some(asset.clone()),
+                    amount: Some(*amount),
+                    memo: Some(memo.clone()),
+                    signer: Some(signer.clone()),
+                }),
+                ..Default::default()
+            })
+        }
+        (ThorNetwork::Mayachain, ThorMessage::Send { from_address, to_address, amount }) => {
+            Message::MayachainMsgAck(messages::MayachainMsgAck {
+                send: Some(messages::MayachainMsgSend {
+                    from_address: Some(from_address.clone()),
+                    to_address: Some(to_address.clone()),
+                    amount: Some(*amount),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        }
+        (ThorNetwork::Mayachain, ThorMessage::Deposit { signer, asset, amount, memo }) => {
+            Message::MayachainMsgAck(messages::MayachainMsgAck {
+                deposit: Some(messages::MayachainMsgDeposit {
+                    asset: Some(asset.clone()),
+                    amount: Some(*amount),
+                    memo: Some(memo.clone()),
+                    signer: Some(signer.clone()),
+                }),
+                ..Default::default()
+            })
+        }
+    }
+}
+
+/// Run the SignTx exchange on `network`, sending each message with `call`
+/// and answering the device's message requests until it returns the signature
+pub async fn sign_transaction_with<F, Fut>(
+    mut call: F,
+    network: ThorNetwork,
+    transaction: &ThorTransaction,
+) -> Result<SignedThorTransaction>
+where
+    F: FnMut(Message) -> Fut,
+    Fut: Future<Output = Result<Message>>,
+{
+    transaction.validate(network)?;
+    let sign_doc = transaction.sign_doc(network);
+    let mut message = sign_tx_message(network, transaction)?;
+    let mut acks = transaction.messages.iter().map(|m| msg_ack(network, m));
+
+    loop {
+        message = match call(message).await? {
+            Message::ThorchainMsgRequest(_) | Message::MayachainMsgRequest(_) => acks.next()
+                .ok_or_else(|| anyhow!("Device asked for more than {} messages", transaction.messages.len()))?,
+            Message::ThorchainSignedTx(messages::ThorchainSignedTx { public_key, signature })
+            | Message::MayachainSignedTx(messages::MayachainSignedTx { public_key, signature }) => {
+                if acks.next().is_some() {
+                    bail!("Device signed before receiving every message");
+                }
+                return Ok(SignedThorTransaction {
+                    public_key: public_key.ok_or_else(|| anyhow!("No public key in response"))?,
+                    signature: signature.ok_or_else(|| anyhow!("No signature in response"))?,
+                    sign_doc,
+                });
+            }
+            Message::Failure(f) => match f.code.and_then(messages::FailureType::from_i32) {
+                Some(messages::FailureType::FailureActionCancelled) => {
+                    bail!("Transaction cancelled on the device")
+                }
+                _ => bail!("Device refused the transaction: {}", f.message()),
+            },
+            Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+                bail!("Device must be unlocked before signing")
+            }
+            other => bail!("Unexpected response while signing: {:?}", other.message_type()),
+        };
+    }
+}
+
+/// Sign a transaction on `network`
+pub async fn sign_thor_transaction(
+    device_queue: &DeviceQueueHandle,
+    network: ThorNetwork,
+    transaction: ThorTransaction,
+) -> Result<SignedThorTransaction> {
+    sign_transaction_with(move |message| device_queue.send_raw(message, true), network, &transaction).await
+}
+
+/// Sign a THORChain transaction
+pub async fn sign_thorchain_tx(
+    device_queue: &DeviceQueueHandle,
+    transaction: ThorTransaction,
+) -> Result<SignedThorTransaction> {
+    sign_thor_transaction(device_queue, ThorNetwork::Thorchain, transaction).await
+}
+
+/// Sign a Mayachain transaction
+pub async fn sign_mayachain_tx(
+    device_queue: &DeviceQueueHandle,
+    transaction: ThorTransaction,
+) -> Result<SignedThorTransaction> {
+    sign_thor_transaction(device_queue, ThorNetwork::Mayachain, transaction).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::scripted_device::ScriptedDevice;
+
+    const THOR_SENDER: &str = "thor1qyqszqgpqyqszqgpqyqszqgpqyqszqgp55c9cr";
+    const THOR_RECIPIENT: &str = "thor1qgpqyqszqgpqyqszqgpqyqszqgpqyqsz9s7qn4";
+    const MAYA_SENDER: &str = "maya1qyqszqgpqyqszqgpqyqszqgpqyqszqgp5rxfwn";
+    const MAYA_RECIPIENT: &str = "maya1qgpqyqszqgpqyqszqgpqyqszqgpqyqsz98qv99";
+    const SWAP_MEMO: &str = "=:ETH.ETH:0x2c7536E3605D9C16a7a3D7b1898e529396a65c23:1234567/1/0:kk:15";
+
+    fn transaction(chain_id: &str, messages: Vec<ThorMessage>) -> ThorTransaction {
+        ThorTransaction {
+            address_n: vec![0x8000_002c, 0x8000_03a3, 0x8000_0000, 0, 0],
+            chain_id: chain_id.to_string(),
+            account_number: 4242,
+            sequence: 9,
+            messages,
+            fee: 0,
+            gas: 500_000_000,
+            memo: String::new(),
+            testnet: false,
+        }
+    }
+
+    fn swap(memo: &str) -> ThorMessage {
+        ThorMessage::Deposit {
+            signer: THOR_SENDER.to_string(),
+            asset: "THOR.RUNE".to_string(),
+            amount: 150_000_000,
+            memo: memo.to_string(),
+        }
+    }
+
+    async fn sign_scripted(
+        responses: Vec<Message>,
+        network: ThorNetwork,
+        transaction: &ThorTransaction,
+    ) -> (Result<SignedThorTransaction>, Vec<Message>) {
+        let device = ScriptedDevice::new(responses);
+        let result = sign_transaction_with(|message| device.call(message), network, transaction).await;
+        (result, device.into_sent())
+    }
+
+    #[tokio::test]
+    async fn test_deposit_memo_round_trips() {
+        let tx = transaction("thorchain-1", vec![swap(SWAP_MEMO)]);
+        let signed = Message::ThorchainSignedTx(messages::ThorchainSignedTx {
+            public_key: Some(vec![2; 33]),
+            signature: Some(vec![9; 64]),
+        });
+        let (result, sent) = sign_scripted(
+            vec![Message::ThorchainMsgRequest(Default::default()), signed],
+            ThorNetwork::Thorchain,
+            &tx,
+        ).await;
+        let result = result.unwrap();
+
+        match &sent[1] {
+            Message::ThorchainMsgAck(ack) => {
+                let deposit = ack.deposit.as_ref().unwrap();
+                assert_eq!(deposit.memo.as_deref(), Some(SWAP_MEMO));
+                assert_eq!((deposit.asset.as_deref(), deposit.amount), (Some("THOR.RUNE"), Some(150_000_000)));
+            }
+            other => panic!("sent {:?}", other.message_type()),
+        }
+        let sign_doc = String::from_utf8(result.sign_doc).unwrap();
+        assert!(sign_doc.contains(&format!("\"memo\":\"{}\"", SWAP_MEMO)));
+        assert!(sign_doc.contains("\"type\":\"thorchain/MsgDeposit\""));
+    }
+
+    #[tokio::test]
+    async fn test_mayachain_send_uses_maya_messages() {
+        let tx = transaction("mayachain-mainnet-v1", vec![ThorMessage::Send {
+            from_address: MAYA_SENDER.to_string(),
+            to_address: MAYA_RECIPIENT.to_string(),
+            amount: 10_000_000_000,
+        }]);
+        let signed = Message::MayachainSignedTx(messages::MayachainSignedTx {
+            public_key: Some(vec![3; 33]),
+            signature: Some(vec![7; 64]),
+        });
+        let (result, sent) = sign_scripted(
+            vec![Message::MayachainMsgRequest(Default::default()), signed],
+            ThorNetwork::Mayachain,
+            &tx,
+        ).await;
+        let sign_doc = String::from_utf8(result.unwrap().sign_doc).unwrap();
+        assert!(sign_doc.contains("\"type\":\"mayachain/MsgSend\""));
+        assert!(sign_doc.contains("\"denom\":\"cacao\""));
+
+        assert!(matches!(&sent[0], Message::MayachainSignTx(sign_tx) if sign_tx.msg_count == Some(1)));
+        match &sent[1] {
+            Message::MayachainMsgAck(ack) => assert_eq!(ack.send.as_ref().and_then(|s| s.amount), Some(10_000_000_000)),
+            other => panic!("sent {:?}", other.message_type()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_transactions_are_not_sent() {
+        let longest = "x".repeat(MAX_MEMO_BYTES);
+        assert!(transaction("thorchain-1", vec![swap(&longest)]).validate(ThorNetwork::Thorchain).is_ok());
+
+        let wrong_chain = ThorMessage::Send {
+            from_address: THOR_SENDER.to_string(),
+            to_address: MAYA_RECIPIENT.to_string(),
+            amount: 1,
+        };
+        let too_long = format!("{}x", longest);
+        for tx in [
+            transaction("thorchain-1", vec![swap(&too_long)]),
+            ThorTransaction { memo: too_long.clone(), ..transaction("thorchain-1", vec![swap("")]) },
+            transaction("thorchain-1", vec![wrong_chain]),
+            transaction("thorchain-1", vec![ThorMessage::Send {
+                from_address: THOR_SENDER.to_string(),
+                to_address: THOR_RECIPIENT.to_string(),
+                amount: 0,
+            }]),
+            transaction("thorchain-1", vec![]),
+            ThorTransaction { testnet: true, ..transaction("thorchain-testnet", vec![swap(SWAP_MEMO)]) },
+        ] {
+            let (result, sent) = sign_scripted(vec![], ThorNetwork::Thorchain, &tx).await;
+            assert!(result.is_err(), "{:?}", tx);
+            assert!(sent.is_empty());
+        }
+
+        let cancelled = Message::Failure(messages::Failure {
+            code: Some(messages::FailureType::FailureActionCancelled as i32),
+            message: Some("Signing cancelled by user".to_string()),
+        });
+        let tx = transaction("thorchain-1", vec![swap(SWAP_MEMO)]);
+        let (result, _) = sign_scripted(vec![cancelled], ThorNetwork::Thorchain, &tx).await;
+        assert!(result.unwrap_err().to_string().contains("cancelled on the device"));
+    }
+}
@@ -0,0 +1,151 @@
+//! Claim-conflict detection
+//!
+//! When the vault cannot open a KeepKey it is either because the device went
+//! away or because another process (kkcli, a second vault, a browser using
+//! WebUSB) has claimed the interface. The OS error text for the second case
+//! differs per platform, so the classification lives here in one table.
+
+use serde::Serialize;
+use thiserror::Error;
+
+/// Host platform, for platform-specific error text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Linux,
+    MacOs,
+    Windows,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        if cfg!(target_os = "windows") {
+            Platform::Windows
+        } else if cfg!(target_os = "macos") {
+            Platform::MacOs
+        } else {
+            Platform::Linux
+        }
+    }
+}
+
+/// Why a transport could not be claimed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimFailure {
+    /// The device is no longer enumerated
+    NotPresent,
+    /// The device is present but another process holds its interface
+    HeldElsewhere,
+    /// Anything else (permissions, protocol errors, timeouts)
+    Other,
+}
+
+/// Result of probing whether the device can be claimed right now
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ClaimStatus {
+    Available,
+    NotPresent,
+    HeldElsewhere { detail: String },
+    Failed { detail: String },
+}
+
+/// Returned by the queue worker instead of retrying forever when another
+/// process holds the device
+#[derive(Debug, Clone, Error)]
+#[error("Device {device_id} is in use by another application: {detail}")]
+pub struct DeviceClaimedElsewhere {
+    pub device_id: String,
+    pub detail: String,
+}
+
+const HELD_EVERYWHERE: &[&str] = &[
+    "resource busy",
+    "exclusive access",
+    "already claimed",
+    "in use",
+];
+
+const HELD_WINDOWS: &[&str] = &["access denied", "access is denied", "sharing violation"];
+
+// IOKit reports kIOReturnExclusiveAccess as "exclusive access" (covered above);
+// hidapi on macOS surfaces a held device as an open failure with access denied.
+const HELD_MACOS: &[&str] = &["access denied", "kioreturnexclusiveaccess"];
+
+const NOT_PRESENT: &[&str] = &[
+    "no such device",
+    "entity not found",
+    "device not found",
+    "disconnected",
+    "not found in device list",
+];
+
+/// Classify a transport creation error. On Linux "access denied" means a
+/// missing udev rule rather than another holder, so it stays `Other`.
+pub fn classify_claim_error(message: &str, platform: Platform) -> ClaimFailure {
+    let msg = message.to_ascii_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|n| msg.contains(n));
+
+    if has(NOT_PRESENT) {
+        return ClaimFailure::NotPresent;
+    }
+    if has(HELD_EVERYWHERE) {
+        return ClaimFailure::HeldElsewhere;
+    }
+    let platform_held = match platform {
+        Platform::Windows => has(HELD_WINDOWS),
+        Platform::MacOs => has(HELD_MACOS),
+        Platform::Linux => false,
+    };
+    if platform_held {
+        ClaimFailure::HeldElsewhere
+    } else {
+        ClaimFailure::Other
+    }
+}
+
+/// Turn a transport creation result into a claim status. `still_enumerated`
+/// guards against misreading the errors of a device unplugged mid-open.
+pub fn claim_status_from_error(message: &str, still_enumerated: bool, platform: Platform) -> ClaimStatus {
+    if !still_enumerated {
+        return ClaimStatus::NotPresent;
+    }
+    match classify_claim_error(message, platform) {
+        ClaimFailure::NotPresent => ClaimStatus::NotPresent,
+        ClaimFailure::HeldElsewhere => ClaimStatus::HeldElsewhere { detail: message.to_string() },
+        ClaimFailure::Other => ClaimStatus::Failed { detail: message.to_string() },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_held_elsewhere_on_every_platform() {
+        for platform in [Platform::Linux, Platform::MacOs, Platform::Windows] {
+            assert_eq!(classify_claim_error("Failed to claim interface: Resource busy", platform), ClaimFailure::HeldElsewhere);
+            assert_eq!(classify_claim_error("USB error: Entity not found", platform), ClaimFailure::NotPresent);
+            assert_eq!(classify_claim_error("No such device (it may have been disconnected)", platform), ClaimFailure::NotPresent);
+            assert_eq!(classify_claim_error("Communication Timeout", platform), ClaimFailure::Other);
+        }
+        assert_eq!(classify_claim_error("IOKit: exclusive access", Platform::MacOs), ClaimFailure::HeldElsewhere);
+    }
+
+    #[test]
+    fn test_access_denied_depends_on_platform() {
+        let msg = "HID open failed: Access denied (insufficient permissions)";
+        assert_eq!(classify_claim_error(msg, Platform::Windows), ClaimFailure::HeldElsewhere);
+        assert_eq!(classify_claim_error(msg, Platform::MacOs), ClaimFailure::HeldElsewhere);
+        assert_eq!(classify_claim_error(msg, Platform::Linux), ClaimFailure::Other);
+        assert_eq!(classify_claim_error("The process cannot access the file: sharing violation", Platform::Windows), ClaimFailure::HeldElsewhere);
+    }
+
+    #[test]
+    fn test_unplugged_mid_open_is_not_present() {
+        assert_eq!(claim_status_from_error("Resource busy", false, Platform::Linux), ClaimStatus::NotPresent);
+        assert!(matches!(
+            claim_status_from_error("Resource busy", true, Platform::Linux),
+            ClaimStatus::HeldElsewhere { .. }
+        ));
+    }
+}
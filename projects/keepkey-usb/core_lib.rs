@@ -4,6 +4,10 @@ pub mod friendly_usb;
 pub mod messages;
 pub mod transport;
 pub mod features;
+pub mod device_watcher;
 pub mod device_queue;
 pub mod chains;
 pub mod device_update;
+pub mod version;
+pub mod fault_log;
+pub mod claim;
@@ -1,14 +1,19 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, oneshot};
 use tokio::time::{timeout, sleep};
 use anyhow::{anyhow, Result};
 use tracing::{info, warn, error, debug, instrument};
+use zeroize::Zeroizing;
 
 use crate::messages::{Message, GetFeatures, GetAddress, Features};
 use crate::transport::ProtocolAdapter;
 use crate::friendly_usb::FriendlyUsbDevice;
+use crate::claim::{claim_status_from_error, ClaimStatus, DeviceClaimedElsewhere, Platform};
 
 /// Transport type detection for different KeepKey device modes
 #[derive(Debug, Clone, Copy)]
@@ -27,6 +32,48 @@ const QUEUE_CHANNEL_SIZE: usize = 100;
 const CACHE_MAX_ENTRIES: usize = 256;
 const CACHE_TTL: Duration = Duration::from_secs(30);
 
+/// Asked for the passphrase of a device that answered with PassphraseRequest
+pub type PassphraseProvider =
+    Arc<dyn Fn(String) -> Pin<Box<dyn Future<Output = Result<Zeroizing<String>>> + Send>> + Send + Sync>;
+
+static PASSPHRASE_PROVIDER: OnceLock<PassphraseProvider> = OnceLock::new();
+
+/// Have `send_raw` answer a PassphraseRequest itself, with the passphrase
+/// from `provider`, and return what the device says next. Without a provider
+/// the request is returned to the caller. Returns false if one was already set.
+pub fn set_passphrase_provider(provider: PassphraseProvider) -> bool {
+    PASSPHRASE_PROVIDER.set(provider).is_ok()
+}
+
+/// Cancels the device's passphrase prompt when `send_raw` is dropped while
+/// the provider still waits, so the device does not sit waiting for an ack
+struct PendingPassphrase(Option<DeviceQueueHandle>);
+
+impl Drop for PendingPassphrase {
+    fn drop(&mut self) {
+        let (Some(handle), Ok(runtime)) = (self.0.take(), tokio::runtime::Handle::try_current()) else {
+            return;
+        };
+        runtime.spawn(async move {
+            if let Err(e) = handle.send_raw_once(crate::messages::Cancel::default().into(), true).await {
+                warn!("Could not cancel the abandoned passphrase prompt on {}: {}", handle.device_id, e);
+            }
+        });
+    }
+}
+
+/// One request sent through a handle and what came back
+pub struct DeviceExchange<'a> {
+    pub device_id: &'a str,
+    pub request: &'a Message,
+    pub response: &'a Result<Message>,
+    pub duration: Duration,
+}
+
+/// Told about every exchange on a handle it is attached to; called on the
+/// caller's task, so it must not block
+pub type RequestObserver = Arc<dyn Fn(DeviceExchange<'_>) + Send + Sync>;
+
 /// Unique key for caching device responses
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CacheKey {
@@ -184,6 +231,37 @@ impl DeviceQueueMetrics {
     }
 }
 
+/// What a worker is doing, shared with its handles
+#[derive(Debug, Default)]
+struct ActivityState {
+    current: Option<(&'static str, Instant)>,
+    last_success: Option<Instant>,
+    completed: u64,
+    failed: u64,
+}
+
+type SharedActivity = Arc<Mutex<ActivityState>>;
+
+fn lock_activity(activity: &SharedActivity) -> std::sync::MutexGuard<'_, ActivityState> {
+    activity.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Snapshot of a device queue, to tell a busy device from a dead queue
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct QueueActivity {
+    /// The worker task is still receiving requests
+    pub worker_alive: bool,
+    /// Requests waiting behind the current one
+    pub pending_requests: u32,
+    pub current_operation: Option<String>,
+    pub current_operation_ms: Option<u64>,
+    /// Since a request last succeeded; None if none has
+    pub last_success_ms_ago: Option<u64>,
+    pub completed_requests: u64,
+    pub failed_requests: u64,
+}
+
 /// Worker task that processes device commands sequentially
 pub struct DeviceWorker {
     device_id: String,
@@ -194,6 +272,7 @@ pub struct DeviceWorker {
     cmd_rx: mpsc::Receiver<DeviceCmd>,
     /// Track if device is in PIN flow mode (ResetDevice, PIN setup, etc)
     is_pin_flow: bool,
+    activity: SharedActivity,
 }
 
 impl DeviceWorker {
@@ -201,6 +280,7 @@ impl DeviceWorker {
         device_id: String,
         device_info: FriendlyUsbDevice,
         cmd_rx: mpsc::Receiver<DeviceCmd>,
+        activity: SharedActivity,
     ) -> Self {
         Self {
             device_id,
@@ -210,6 +290,7 @@ impl DeviceWorker {
             metrics: DeviceQueueMetrics::default(),
             cmd_rx,
             is_pin_flow: false,
+            activity,
         }
     }
     
@@ -226,41 +307,58 @@ impl DeviceWorker {
             self.metrics.queue_depth = self.cmd_rx.len();
             
             debug!("📝 Processing {} command (queue wait: {:?})", cmd.operation_name(), queue_wait);
+            lock_activity(&self.activity).current = Some((cmd.operation_name(), start_time));
             
             let result = self.process_command(cmd).await;
             
-            if let Err(ref e) = result {
-                error!("❌ Command failed: {}", e);
+            let mut activity = lock_activity(&self.activity);
+            activity.current = None;
+            match result {
+                Ok(()) => {
+                    activity.completed += 1;
+                    activity.last_success = Some(Instant::now());
+                }
+                Err(ref e) => {
+                    activity.failed += 1;
+                    error!("❌ Command failed: {}", e);
+                }
             }
         }
         
         info!("🛑 DeviceWorker shutting down for device {}", self.device_id);
     }
     
-    /// Process a single command
+    /// Process a single command; the result says whether the device answered
+    /// it, the response itself goes to the requester
     async fn process_command(&mut self, cmd: DeviceCmd) -> Result<()> {
         let device_start = Instant::now();
         let enqueued_at = cmd.enqueued_at();
+        let outcome;
         
         match cmd {
             DeviceCmd::GetFeatures { respond_to, .. } => {
                 let result = self.handle_get_features().await;
+                outcome = result.as_ref().map(|_| ()).map_err(|e| anyhow!("{}", e));
                 let _ = respond_to.send(result);
             }
             DeviceCmd::GetAddress { path, coin_name, script_type, show_display, respond_to, .. } => {
                 let result = self.handle_get_address(path, coin_name, script_type, show_display).await;
+                outcome = result.as_ref().map(|_| ()).map_err(|e| anyhow!("{}", e));
                 let _ = respond_to.send(result);
             }
             DeviceCmd::SendRaw { message, respond_to, bypass_cache, .. } => {
                 let result = self.handle_send_raw(message, bypass_cache).await;
+                outcome = result.as_ref().map(|_| ()).map_err(|e| anyhow!("{}", e));
                 let _ = respond_to.send(result);
             }
             DeviceCmd::UpdateBootloader { target_version, bootloader_bytes, respond_to, enqueued_at: _ } => {
                 let result = self.handle_update_bootloader(target_version, bootloader_bytes).await;
+                outcome = result.as_ref().map(|_| ()).map_err(|e| anyhow!("{}", e));
                 let _ = respond_to.send(result);
             }
             DeviceCmd::UpdateFirmware { target_version, firmware_bytes, respond_to, enqueued_at: _ } => {
                 let result = self.handle_update_firmware(target_version, firmware_bytes).await;
+                outcome = result.as_ref().map(|_| ()).map_err(|e| anyhow!("{}", e));
                 let _ = respond_to.send(result);
             }
             DeviceCmd::Shutdown { respond_to } => {
@@ -282,7 +380,7 @@ impl DeviceWorker {
         
         // Transport is kept alive across commands for performance
         // It will only be recreated on error in ensure_transport()
-        Ok(())
+        outcome
     }
     
     /// Ensure transport is available, creating if necessary
@@ -308,6 +406,13 @@ impl DeviceWorker {
                         
                         // Drop any stale transport reference just in case
                         self.transport = None;
+
+                        // Another process holds the interface: fail fast so the caller
+                        // can switch the device to observer mode instead of waiting here
+                        if let ClaimStatus::HeldElsewhere { detail } = DeviceQueueFactory::claim_status_for_error(&self.device_info, &error_msg) {
+                            warn!("🔒 Device {} is held by another application: {}", self.device_id, detail);
+                            return Err(DeviceClaimedElsewhere { device_id: self.device_id.clone(), detail }.into());
+                        }
                         // Wait a bit before retrying.  This keeps the queue worker alive
                         // and effectively makes the queue "just wait" for the device to return.
                         sleep(Duration::from_secs(2)).await;
@@ -440,10 +545,16 @@ impl DeviceWorker {
     }
     
     /// Handle raw message sending 
-    async fn handle_send_raw(&mut self, message: Message, bypass_cache: bool) -> Result<Message> {
+    async fn handle_send_raw(&mut self, mut message: Message, bypass_cache: bool) -> Result<Message> {
+        let response = self.send_raw_to_device(&message, bypass_cache).await;
+        message.wipe_secrets();
+        response
+    }
+
+    async fn send_raw_to_device(&mut self, message: &Message, bypass_cache: bool) -> Result<Message> {
         // Detect if this is a PIN flow related message
         let is_pin_flow_message = matches!(
-            &message,
+            message,
             Message::ResetDevice(_) | 
             Message::PinMatrixAck(_) | 
             Message::ChangePin(_) |
@@ -454,7 +565,7 @@ impl DeviceWorker {
         );
         
         // Update PIN flow state based on message type
-        if matches!(&message, Message::ResetDevice(_) | Message::ChangePin(_) | Message::RecoveryDevice(_)) {
+        if matches!(message, Message::ResetDevice(_) | Message::ChangePin(_) | Message::RecoveryDevice(_)) {
             info!("🔐 Entering PIN flow mode for device {} due to {:?}", self.device_id, message.message_type());
             self.is_pin_flow = true;
         }
@@ -490,9 +601,9 @@ impl DeviceWorker {
                     
                     // Retry the operation once
                     if use_pin_flow_handler {
-                        transport.with_pin_flow_handler().handle(message)?
+                        transport.with_pin_flow_handler().handle(message.clone())?
                     } else {
-                        transport.with_standard_handler().handle(message)?
+                        transport.with_standard_handler().handle(message.clone())?
                     }
                 } else {
                     // Not a transport error, propagate it
@@ -683,20 +794,71 @@ impl DeviceWorker {
 }
 
 /// Handle for communicating with a device worker
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct DeviceQueueHandle {
     device_id: String,
     cmd_tx: mpsc::Sender<DeviceCmd>,
+    activity: SharedActivity,
+    observer: Option<RequestObserver>,
+}
+
+impl std::fmt::Debug for DeviceQueueHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceQueueHandle")
+            .field("device_id", &self.device_id)
+            .field("observed", &self.observer.is_some())
+            .finish_non_exhaustive()
+    }
 }
 
 impl DeviceQueueHandle {
     pub fn new(device_id: String, cmd_tx: mpsc::Sender<DeviceCmd>) -> Self {
-        Self { device_id, cmd_tx }
+        Self { device_id, cmd_tx, activity: SharedActivity::default(), observer: None }
+    }
+
+    /// Report every `get_features` and `send_raw` exchange to `observer`
+    pub fn with_observer(mut self, observer: RequestObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    fn observe(&self, request: &Message, started: Instant, response: &Result<Message>) {
+        if let Some(observer) = &self.observer {
+            observer(DeviceExchange { device_id: &self.device_id, request, response, duration: started.elapsed() });
+        }
+    }
+
+    /// What the worker is doing right now
+    pub fn activity(&self) -> QueueActivity {
+        let activity = lock_activity(&self.activity);
+        let ms = |since: Instant| since.elapsed().as_millis() as u64;
+        QueueActivity {
+            worker_alive: !self.cmd_tx.is_closed(),
+            pending_requests: (self.cmd_tx.max_capacity() - self.cmd_tx.capacity()) as u32,
+            current_operation: activity.current.map(|(name, _)| name.to_string()),
+            current_operation_ms: activity.current.map(|(_, started)| ms(started)),
+            last_success_ms_ago: activity.last_success.map(ms),
+            completed_requests: activity.completed,
+            failed_requests: activity.failed,
+        }
     }
     
     /// Get device features
     #[instrument(level = "debug", skip(self))]
     pub async fn get_features(&self) -> Result<Features> {
+        let started = Instant::now();
+        let features = self.get_features_once().await;
+        if self.observer.is_some() {
+            let response = match &features {
+                Ok(features) => Ok(Message::Features(features.clone())),
+                Err(e) => Err(anyhow!("{}", e)),
+            };
+            self.observe(&GetFeatures {}.into(), started, &response);
+        }
+        features
+    }
+
+    async fn get_features_once(&self) -> Result<Features> {
         let (tx, rx) = oneshot::channel();
         let cmd = DeviceCmd::GetFeatures {
             respond_to: tx,
@@ -732,9 +894,47 @@ impl DeviceQueueHandle {
             .map_err(|_| anyhow!("Device worker channel closed"))?
     }
     
-    /// Send raw message to device
+    /// Send raw message to device; a PassphraseRequest is answered by the
+    /// passphrase provider, if one is set, and the operation continues
     #[instrument(level = "debug", skip(self, message))]
     pub async fn send_raw(&self, message: Message, bypass_cache: bool) -> Result<Message> {
+        let mut response = self.send_raw_once(message, bypass_cache).await?;
+        while let (Message::PassphraseRequest(_), Some(provider)) = (&response, PASSPHRASE_PROVIDER.get()) {
+            info!("🔑 Device {} asked for a passphrase", self.device_id);
+            let mut pending = PendingPassphrase(Some(self.clone()));
+            let provided = provider(self.device_id.clone()).await;
+            pending.0 = None;
+            let passphrase = match provided {
+                Ok(passphrase) => passphrase,
+                Err(e) => {
+                    // Leave the device idle instead of waiting for an ack
+                    if let Err(cancel_error) = self.send_raw_once(crate::messages::Cancel::default().into(), true).await {
+                        warn!("Could not cancel the passphrase prompt on {}: {}", self.device_id, cancel_error);
+                    }
+                    return Err(e);
+                }
+            };
+            let ack = crate::messages::PassphraseAck { passphrase: passphrase.to_string() };
+            response = self.send_raw_once(ack.into(), true).await?;
+        }
+        Ok(response)
+    }
+
+    async fn send_raw_once(&self, message: Message, bypass_cache: bool) -> Result<Message> {
+        let started = Instant::now();
+        let request = self.observer.is_some().then(|| {
+            let mut request = message.clone();
+            request.wipe_secrets();
+            request
+        });
+        let response = self.send_raw_unobserved(message, bypass_cache).await;
+        if let Some(request) = &request {
+            self.observe(request, started, &response);
+        }
+        response
+    }
+
+    async fn send_raw_unobserved(&self, message: Message, bypass_cache: bool) -> Result<Message> {
         let (tx, rx) = oneshot::channel();
         let cmd = DeviceCmd::SendRaw {
             message,
@@ -743,8 +943,14 @@ impl DeviceQueueHandle {
             bypass_cache,
         };
         
-        self.cmd_tx.send(cmd).await
-            .map_err(|_| anyhow!("Device worker unavailable"))?;
+        // The worker wipes secrets once the message is written; one it never
+        // received is wiped here
+        if let Err(mpsc::error::SendError(cmd)) = self.cmd_tx.send(cmd).await {
+            if let DeviceCmd::SendRaw { mut message, .. } = cmd {
+                message.wipe_secrets();
+            }
+            return Err(anyhow!("Device worker unavailable"));
+        }
             
         timeout(DEVICE_OPERATION_TIMEOUT, rx).await
             .map_err(|_| anyhow!("Device operation timed out"))?
@@ -817,14 +1023,51 @@ impl DeviceQueueFactory {
     pub fn spawn_worker(device_id: String, device_info: FriendlyUsbDevice) -> DeviceQueueHandle {
         let (cmd_tx, cmd_rx) = mpsc::channel(QUEUE_CHANNEL_SIZE);
         
-        let worker = DeviceWorker::new(device_id.clone(), device_info, cmd_rx);
+        let handle = DeviceQueueHandle::new(device_id.clone(), cmd_tx);
+        let worker = DeviceWorker::new(device_id, device_info, cmd_rx, handle.activity.clone());
         
         // Spawn the worker task
         tokio::spawn(worker.run());
         
-        DeviceQueueHandle::new(device_id, cmd_tx)
+        handle
+    }
+
+    /// Spawn a worker that talks through `transport` instead of opening the
+    /// device's USB interface, e.g. to a [`crate::transport::mock::MockTransport`]
+    pub fn spawn_worker_with_transport(
+        device_id: String,
+        device_info: FriendlyUsbDevice,
+        transport: Box<dyn ProtocolAdapter + Send>,
+    ) -> DeviceQueueHandle {
+        let (cmd_tx, cmd_rx) = mpsc::channel(QUEUE_CHANNEL_SIZE);
+
+        let handle = DeviceQueueHandle::new(device_id.clone(), cmd_tx);
+        let mut worker = DeviceWorker::new(device_id, device_info, cmd_rx, handle.activity.clone());
+        worker.transport = Some(transport);
+
+        tokio::spawn(worker.run());
+
+        handle
     }
     
+    /// Check whether the device could be claimed right now. Opens and
+    /// immediately drops a transport, so only call it while no worker holds one.
+    pub fn probe_claim(device_info: &FriendlyUsbDevice) -> ClaimStatus {
+        match Self::create_transport_for_device(device_info) {
+            Ok(_transport) => ClaimStatus::Available,
+            Err(e) => Self::claim_status_for_error(device_info, &e.to_string()),
+        }
+    }
+
+    /// Classify a transport creation error, re-enumerating to tell an
+    /// unplugged device apart from one held by another process
+    pub fn claim_status_for_error(device_info: &FriendlyUsbDevice, error_msg: &str) -> ClaimStatus {
+        let still_enumerated = crate::features::list_connected_devices()
+            .iter()
+            .any(|d| d.unique_id == device_info.unique_id);
+        claim_status_from_error(error_msg, still_enumerated, Platform::current())
+    }
+
     /// Create transport with WebUSB/USB/HID auto-detection
     pub fn create_transport_for_device(device_info: &FriendlyUsbDevice) -> Result<Box<dyn ProtocolAdapter + Send>> {
         // Find physical device for transport
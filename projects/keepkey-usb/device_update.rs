@@ -1,22 +1,18 @@
 use serde::{Serialize, Deserialize};
 use crate::features::DeviceFeatures;
+pub use crate::version::{compare_versions, VersionComparison};
 
 /// Bootloader check result
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
 pub struct BootloaderCheck {
     pub needs_update: bool,
     pub current_version: String,
     pub latest_version: String,
     pub is_critical: bool,
     pub bootloader_mode: bool,
-}
-
-/// Version comparison result
-#[derive(Debug, Clone, PartialEq)]
-pub enum VersionComparison {
-    Less,
-    Equal,
-    Greater,
+    /// The reported version could not be parsed and should be looked at
+    pub needs_investigation: bool,
 }
 
 /// Check bootloader status against minimum required version
@@ -35,53 +31,14 @@ pub fn check_bootloader_status(features: &DeviceFeatures) -> BootloaderCheck {
         latest_version,
         is_critical,
         bootloader_mode,
+        needs_investigation: comparison == VersionComparison::Unparseable,
     }
 }
 
-/// Compare two semantic version strings
-pub fn compare_versions(version1: &str, version2: &str) -> VersionComparison {
-    let v1_parts: Vec<u32> = version1
-        .split('.')
-        .filter_map(|s| s.parse().ok())
-        .collect();
-    let v2_parts: Vec<u32> = version2
-        .split('.')
-        .filter_map(|s| s.parse().ok())
-        .collect();
-    
-    // Pad with zeros if needed
-    let max_len = v1_parts.len().max(v2_parts.len());
-    let mut v1_padded = v1_parts.clone();
-    let mut v2_padded = v2_parts.clone();
-    
-    v1_padded.resize(max_len, 0);
-    v2_padded.resize(max_len, 0);
-    
-    for (a, b) in v1_padded.iter().zip(v2_padded.iter()) {
-        if a < b {
-            return VersionComparison::Less;
-        } else if a > b {
-            return VersionComparison::Greater;
-        }
-    }
-    
-    VersionComparison::Equal
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_version_comparison() {
-        assert_eq!(compare_versions("1.0.0", "1.0.0"), VersionComparison::Equal);
-        assert_eq!(compare_versions("1.0.0", "1.0.1"), VersionComparison::Less);
-        assert_eq!(compare_versions("1.0.1", "1.0.0"), VersionComparison::Greater);
-        assert_eq!(compare_versions("2.1.3", "2.1.4"), VersionComparison::Less);
-        assert_eq!(compare_versions("2.1.4", "2.1.4"), VersionComparison::Equal);
-        assert_eq!(compare_versions("2.1.5", "2.1.4"), VersionComparison::Greater);
-    }
-
     #[test]
     fn test_bootloader_check() {
         let mut features = DeviceFeatures::default();
@@ -105,4 +62,14 @@ mod tests {
         assert!(!check.needs_update);
         assert!(!check.is_critical);
     }
+
+    #[test]
+    fn test_bootloader_check_unparseable() {
+        let mut features = DeviceFeatures::default();
+        features.bootloader_version = Some("f13ce228c0bb2bdbc56bdcb5f4569367aca3011f".to_string());
+        
+        let check = check_bootloader_status(&features);
+        assert!(!check.needs_update);
+        assert!(check.needs_investigation);
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,267 @@
+//! Connect/disconnect events for KeepKey devices
+//!
+//! Where libusb supports hotplug (Linux, macOS) the watcher registers a
+//! hotplug callback and reports changes as they happen. Elsewhere (Windows)
+//! it falls back to listing the bus on an interval and diffing the result.
+//! Either way consumers get the same stream of [`DeviceEvent`]s, each device
+//! reported connected once and disconnected once.
+
+use std::collections::HashMap;
+use std::sync::mpsc as std_mpsc;
+use std::time::Duration;
+use rusb::{Device, GlobalContext, UsbContext};
+use tokio::sync::mpsc;
+
+use crate::features;
+use crate::friendly_usb::{FriendlyUsbDevice, KEEPKEY_VID};
+
+/// How long the hotplug thread blocks in libusb before checking whether the
+/// watcher was dropped. Events are delivered as soon as libusb sees them.
+const HOTPLUG_WAKE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Pause before reading a new device's descriptors, as list_connected_devices
+/// does, so its string descriptors are ready
+const ENUMERATION_SETTLE: Duration = Duration::from_millis(50);
+
+/// A KeepKey arriving or leaving the bus
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceEvent {
+    Connected(FriendlyUsbDevice),
+    /// The device's unique_id
+    Disconnected(String),
+}
+
+/// How the watcher learns about changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchMode {
+    Hotplug,
+    Polling,
+}
+
+/// Devices already reported, so every arrival and departure is reported once
+#[derive(Debug, Default)]
+struct ConnectedSet {
+    devices: HashMap<String, FriendlyUsbDevice>,
+}
+
+impl ConnectedSet {
+    fn connect(&mut self, device: FriendlyUsbDevice) -> Option<DeviceEvent> {
+        if !device.is_keepkey || self.devices.contains_key(&device.unique_id) {
+            return None;
+        }
+        self.devices.insert(device.unique_id.clone(), device.clone());
+        Some(DeviceEvent::Connected(device))
+    }
+
+    fn disconnect(&mut self, unique_id: &str) -> Option<DeviceEvent> {
+        self.devices.remove(unique_id).map(|_| DeviceEvent::Disconnected(unique_id.to_string()))
+    }
+
+    /// Events that turn the known set into `current`. Departures come first
+    /// so a device that re-enumerated under a new id is seen leaving before
+    /// it arrives.
+    fn sync(&mut self, current: Vec<FriendlyUsbDevice>) -> Vec<DeviceEvent> {
+        let gone: Vec<String> = self
+            .devices
+            .keys()
+            .filter(|id| !current.iter().any(|d| &d.unique_id == *id))
+            .cloned()
+            .collect();
+        let mut events: Vec<DeviceEvent> = gone.iter().filter_map(|id| self.disconnect(id)).collect();
+        events.extend(current.into_iter().filter_map(|d| self.connect(d)));
+        events
+    }
+}
+
+/// Stream of [`DeviceEvent`]s. Dropping it stops the background thread.
+pub struct DeviceWatcher {
+    events: mpsc::UnboundedReceiver<DeviceEvent>,
+    mode: WatchMode,
+}
+
+impl DeviceWatcher {
+    /// Watch with hotplug callbacks, or by listing the bus every
+    /// `poll_interval()` where hotplug is unsupported. Devices already
+    /// connected are reported first.
+    pub fn start(poll_interval: impl Fn() -> Duration + Send + 'static) -> Self {
+        if rusb::has_hotplug() {
+            match Self::hotplug() {
+                Ok(watcher) => return watcher,
+                Err(e) => log::warn!("USB hotplug unavailable ({}), polling instead", e),
+            }
+        }
+        Self::polling(features::list_connected_devices, poll_interval)
+    }
+
+    /// Watch by calling `list` every `interval()` and diffing the result
+    pub fn polling(
+        mut list: impl FnMut() -> Vec<FriendlyUsbDevice> + Send + 'static,
+        interval: impl Fn() -> Duration + Send + 'static,
+    ) -> Self {
+        let (tx, events) = mpsc::unbounded_channel();
+        std::thread::Builder::new()
+            .name("usb-poll".to_string())
+            .spawn(move || {
+                let mut connected = ConnectedSet::default();
+                while !tx.is_closed() {
+                    for event in connected.sync(list()) {
+                        if tx.send(event).is_err() {
+                            return;
+                        }
+                    }
+                    std::thread::sleep(interval());
+                }
+            })
+            .expect("failed to spawn USB polling thread");
+        log::info!("🔌 Watching USB devices by polling");
+        DeviceWatcher { events, mode: WatchMode::Polling }
+    }
+
+    fn hotplug() -> rusb::Result<Self> {
+        let (tx, events) = mpsc::unbounded_channel();
+        // The callback is registered on the thread that handles its events
+        let (registered_tx, registered) = std_mpsc::channel();
+        std::thread::Builder::new()
+            .name("usb-hotplug".to_string())
+            .spawn(move || {
+                let context = GlobalContext::default();
+                let (changes_tx, changes) = std_mpsc::channel();
+                let registration = rusb::HotplugBuilder::new()
+                    .vendor_id(KEEPKEY_VID)
+                    .enumerate(true)
+                    .register(context, Box::new(HotplugCallback { changes: changes_tx }));
+                // Unregistered when the thread ends
+                let _registration = match registration {
+                    Ok(registration) => {
+                        let _ = registered_tx.send(Ok(()));
+                        registration
+                    }
+                    Err(e) => {
+                        let _ = registered_tx.send(Err(e));
+                        return;
+                    }
+                };
+                let mut connected = ConnectedSet::default();
+                // unique_id by bus:address; a departed device can no longer be read
+                let mut locations: HashMap<(u8, u8), String> = HashMap::new();
+
+                while !tx.is_closed() {
+                    if let Err(e) = context.handle_events(Some(HOTPLUG_WAKE_INTERVAL)) {
+                        log::error!("USB hotplug event handling failed: {}", e);
+                        std::thread::sleep(HOTPLUG_WAKE_INTERVAL);
+                    }
+                    // Descriptors are read here rather than in the callback,
+                    // where libusb does not allow device I/O
+                    for change in changes.try_iter() {
+                        let event = match change {
+                            HotplugChange::Arrived(device) => {
+                                std::thread::sleep(ENUMERATION_SETTLE);
+                                let friendly = features::device_to_friendly_with_cache(&device);
+                                locations.insert((device.bus_number(), device.address()), friendly.unique_id.clone());
+                                connected.connect(friendly)
+                            }
+                            HotplugChange::Left { bus, address } => {
+                                features::forget_cached_device(bus, address);
+                                locations
+                                    .remove(&(bus, address))
+                                    .filter(|id| !locations.values().any(|other| other == id))
+                                    .and_then(|id| connected.disconnect(&id))
+                            }
+                        };
+                        if let Some(event) = event {
+                            if tx.send(event).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn USB hotplug thread");
+
+        registered.recv().unwrap_or(Err(rusb::Error::Other))?;
+        log::info!("🔌 Watching USB devices with hotplug callbacks");
+        Ok(DeviceWatcher { events, mode: WatchMode::Hotplug })
+    }
+
+    /// The next connect or disconnect; None if the watcher thread stopped
+    pub async fn next(&mut self) -> Option<DeviceEvent> {
+        self.events.recv().await
+    }
+
+    pub fn mode(&self) -> WatchMode {
+        self.mode
+    }
+}
+
+enum HotplugChange {
+    Arrived(Device<GlobalContext>),
+    Left { bus: u8, address: u8 },
+}
+
+struct HotplugCallback {
+    changes: std_mpsc::Sender<HotplugChange>,
+}
+
+impl rusb::Hotplug<GlobalContext> for HotplugCallback {
+    fn device_arrived(&mut self, device: Device<GlobalContext>) {
+        let _ = self.changes.send(HotplugChange::Arrived(device));
+    }
+
+    fn device_left(&mut self, device: Device<GlobalContext>) {
+        let _ = self.changes.send(HotplugChange::Left { bus: device.bus_number(), address: device.address() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn keepkey(id: &str) -> FriendlyUsbDevice {
+        FriendlyUsbDevice::new(id.to_string(), KEEPKEY_VID, 0x0002, None, Some("KeepKey".to_string()), None)
+    }
+
+    #[test]
+    fn test_connected_set_reports_each_change_once() {
+        let mut set = ConnectedSet::default();
+        assert_eq!(set.connect(keepkey("kk1")), Some(DeviceEvent::Connected(keepkey("kk1"))));
+        assert_eq!(set.connect(keepkey("kk1")), None);
+        assert_eq!(set.connect(FriendlyUsbDevice::new("mouse".into(), 0x046d, 1, None, None, None)), None);
+
+        // kk1 re-enumerated as kk1-boot, kk2 plugged in
+        let events = set.sync(vec![keepkey("kk1-boot"), keepkey("kk2")]);
+        assert_eq!(events, vec![
+            DeviceEvent::Disconnected("kk1".to_string()),
+            DeviceEvent::Connected(keepkey("kk1-boot")),
+            DeviceEvent::Connected(keepkey("kk2")),
+        ]);
+        assert!(set.sync(vec![keepkey("kk2"), keepkey("kk1-boot")]).is_empty());
+        assert_eq!(set.disconnect("kk2"), Some(DeviceEvent::Disconnected("kk2".to_string())));
+        assert_eq!(set.disconnect("kk2"), None);
+    }
+
+    async fn next(watcher: &mut DeviceWatcher) -> Option<DeviceEvent> {
+        tokio::time::timeout(Duration::from_secs(1), watcher.next()).await.unwrap()
+    }
+
+    /// The fallback used where libusb has no hotplug support (Windows)
+    #[tokio::test]
+    async fn test_polling_fallback_streams_changes() {
+        let bus = Arc::new(Mutex::new(vec![keepkey("kk1")]));
+        let source = bus.clone();
+        let mut watcher = DeviceWatcher::polling(
+            move || source.lock().unwrap().clone(),
+            || Duration::from_millis(5),
+        );
+        assert_eq!(watcher.mode(), WatchMode::Polling);
+
+        assert_eq!(next(&mut watcher).await, Some(DeviceEvent::Connected(keepkey("kk1"))));
+
+        *bus.lock().unwrap() = vec![keepkey("kk2")];
+        assert_eq!(next(&mut watcher).await, Some(DeviceEvent::Disconnected("kk1".to_string())));
+        assert_eq!(next(&mut watcher).await, Some(DeviceEvent::Connected(keepkey("kk2"))));
+
+        *bus.lock().unwrap() = Vec::new();
+        assert_eq!(next(&mut watcher).await, Some(DeviceEvent::Disconnected("kk2".to_string())));
+    }
+}
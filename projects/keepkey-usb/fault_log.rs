@@ -0,0 +1,235 @@
+//! Firmware fault record parsing
+//!
+//! This is the record format proposed for the firmware's fault handler, which
+//! would write it into a fault sector before resetting so it can be read back
+//! over DebugLink with a flash dump. No released firmware writes it yet, so
+//! `Capability::FaultLog` is unsupported everywhere and `read_fault_record`
+//! is never reached; the address and layout must be checked against the
+//! firmware source before the capability is enabled. Layout (little-endian,
+//! 76 bytes):
+//!
+//! | offset | size | field                                   |
+//! |--------|------|-----------------------------------------|
+//! | 0      | 4    | magic `KKFR`                            |
+//! | 4      | 1    | record format version (1)               |
+//! | 5      | 1    | fault type                              |
+//! | 6      | 2    | reserved                                |
+//! | 8      | 4    | uptime in ms at fault                   |
+//! | 12     | 32   | stacked r0, r1, r2, r3, r12, lr, pc, xpsr |
+//! | 44     | 16   | CFSR, HFSR, MMFAR, BFAR                 |
+//! | 60     | 16   | active task name, NUL padded            |
+//!
+//! Erased flash (all 0xFF) or a zeroed sector means no fault was recorded.
+//! Only the fields above are ever surfaced; the raw dump is discarded so no
+//! other flash contents can leak into reports.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::device_queue::DeviceQueueHandle;
+use crate::messages::{DebugLinkFlashDump, Message};
+
+/// Flash address of the fault record sector (proposed, see above)
+pub const FAULT_RECORD_ADDRESS: u32 = 0x0801_0000;
+/// Size of a fault record in bytes
+pub const FAULT_RECORD_LEN: usize = 76;
+
+const MAGIC: &[u8; 4] = b"KKFR";
+const FORMAT_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum FaultType {
+    HardFault,
+    MemManage,
+    BusFault,
+    UsageFault,
+    StackOverflow,
+    Watchdog,
+    Unknown(u8),
+}
+
+impl From<u8> for FaultType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => FaultType::HardFault,
+            2 => FaultType::MemManage,
+            3 => FaultType::BusFault,
+            4 => FaultType::UsageFault,
+            5 => FaultType::StackOverflow,
+            6 => FaultType::Watchdog,
+            other => FaultType::Unknown(other),
+        }
+    }
+}
+
+/// Stacked exception frame registers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct FaultRegisters {
+    pub r0: u32,
+    pub r1: u32,
+    pub r2: u32,
+    pub r3: u32,
+    pub r12: u32,
+    pub lr: u32,
+    pub pc: u32,
+    pub xpsr: u32,
+}
+
+/// Cortex-M fault status registers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct FaultStatus {
+    pub cfsr: u32,
+    pub hfsr: u32,
+    pub mmfar: u32,
+    pub bfar: u32,
+}
+
+/// A parsed firmware fault record
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct FaultRecord {
+    pub fault_type: FaultType,
+    pub uptime_ms: u32,
+    pub registers: FaultRegisters,
+    pub status: FaultStatus,
+    pub task: String,
+}
+
+impl FaultRecord {
+    /// Short human-readable summary, e.g. "hard_fault in usb_task at pc=0x08012345"
+    pub fn summary(&self) -> String {
+        let kind = serde_json::to_value(self.fault_type)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_else(|| format!("{:?}", self.fault_type));
+        format!("{} in {} at pc=0x{:08x}", kind, self.task, self.registers.pc)
+    }
+}
+
+/// Parse a raw fault sector dump. Returns `Ok(None)` when no fault is recorded.
+pub fn parse_fault_record(data: &[u8]) -> Result<Option<FaultRecord>> {
+    if data.len() < FAULT_RECORD_LEN {
+        return Err(anyhow!("Fault record too short: {} bytes", data.len()));
+    }
+    let data = &data[..FAULT_RECORD_LEN];
+
+    if data.iter().all(|b| *b == 0xFF) || data.iter().all(|b| *b == 0x00) {
+        return Ok(None);
+    }
+    if &data[0..4] != MAGIC {
+        return Err(anyhow!("Fault record has invalid magic {:02x?}", &data[0..4]));
+    }
+    if data[4] != FORMAT_VERSION {
+        return Err(anyhow!("Unsupported fault record version {}", data[4]));
+    }
+
+    let word = |offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+
+    // Task names are firmware identifiers; keep printable ASCII only
+    let task: String = data[60..76]
+        .iter()
+        .take_while(|b| **b != 0)
+        .filter(|b| b.is_ascii_graphic() || **b == b' ')
+        .map(|b| *b as char)
+        .collect();
+
+    Ok(Some(FaultRecord {
+        fault_type: FaultType::from(data[5]),
+        uptime_ms: word(8),
+        registers: FaultRegisters {
+            r0: word(12),
+            r1: word(16),
+            r2: word(20),
+            r3: word(24),
+            r12: word(28),
+            lr: word(32),
+            pc: word(36),
+            xpsr: word(40),
+        },
+        status: FaultStatus {
+            cfsr: word(44),
+            hfsr: word(48),
+            mmfar: word(52),
+            bfar: word(56),
+        },
+        task: if task.is_empty() { "unknown".to_string() } else { task },
+    }))
+}
+
+/// Read and parse the fault record through the device queue.
+///
+/// Callers must check `Capability::FaultLog` first, which no released
+/// firmware supports yet.
+pub async fn read_fault_record(queue: &DeviceQueueHandle) -> Result<Option<FaultRecord>> {
+    let request = DebugLinkFlashDump {
+        address: Some(FAULT_RECORD_ADDRESS),
+        length: Some(FAULT_RECORD_LEN as u32),
+    };
+
+    match queue.send_raw(Message::DebugLinkFlashDump(request), true).await? {
+        Message::DebugLinkFlashDumpResponse(response) => {
+            parse_fault_record(&response.data.unwrap_or_default())
+        }
+        other => Err(anyhow!("Unexpected response to fault record request: {:?}", other.message_type())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Built from the proposed layout above: a hard fault in the USB task
+    const HARD_FAULT_FIXTURE: &str = concat!(
+        "4b4b4652", "01", "01", "0000", "10270000",
+        "00000000", "01000000", "00100020", "ffffffff",
+        "00000000", "a9200108", "45230108", "00000061",
+        "00040000", "00000040", "00000000", "00000000",
+        "7573625f7461736b0000000000000000",
+    );
+
+    /// Built from the proposed layout above: a watchdog reset in the main loop
+    const WATCHDOG_FIXTURE: &str = concat!(
+        "4b4b4652", "01", "06", "0000", "c0d40100",
+        "00000000", "00000000", "00000000", "00000000",
+        "00000000", "ffffffff", "10000008", "00000001",
+        "00000000", "00000000", "00000000", "00000000",
+        "6d61696e000000000000000000000000",
+    );
+
+    fn fixture(hex_str: &str) -> Vec<u8> {
+        hex::decode(hex_str).unwrap()
+    }
+
+    #[test]
+    fn test_parse_hard_fault() {
+        let record = parse_fault_record(&fixture(HARD_FAULT_FIXTURE)).unwrap().unwrap();
+        assert_eq!(record.fault_type, FaultType::HardFault);
+        assert_eq!(record.uptime_ms, 10_000);
+        assert_eq!(record.registers.pc, 0x0801_2345);
+        assert_eq!(record.registers.lr, 0x0801_20a9);
+        assert_eq!(record.status.cfsr, 0x0000_0400);
+        assert_eq!(record.status.hfsr, 0x4000_0000);
+        assert_eq!(record.task, "usb_task");
+        assert_eq!(record.summary(), "hard_fault in usb_task at pc=0x08012345");
+    }
+
+    #[test]
+    fn test_parse_watchdog() {
+        let record = parse_fault_record(&fixture(WATCHDOG_FIXTURE)).unwrap().unwrap();
+        assert_eq!(record.fault_type, FaultType::Watchdog);
+        assert_eq!(record.uptime_ms, 120_000);
+        assert_eq!(record.task, "main");
+    }
+
+    #[test]
+    fn test_empty_and_invalid_records() {
+        assert_eq!(parse_fault_record(&[0xFF; FAULT_RECORD_LEN]).unwrap(), None);
+        assert_eq!(parse_fault_record(&[0x00; FAULT_RECORD_LEN]).unwrap(), None);
+        assert!(parse_fault_record(&[0x01; FAULT_RECORD_LEN]).is_err());
+        assert!(parse_fault_record(&[0xFF; 10]).is_err());
+    }
+}
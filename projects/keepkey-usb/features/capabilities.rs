@@ -0,0 +1,156 @@
+//! Firmware capability table
+//!
+//! Maps firmware version/variant to the optional protocol features it supports
+//! so callers can answer "not supported on this firmware" up front instead of
+//! sending a message the device will reject with a Failure.
+
+use serde::{Deserialize, Serialize};
+
+use super::DeviceFeatures;
+use crate::version::version_triple;
+
+/// Optional firmware features that are gated on version or variant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Crash/fault record readable through DebugLink flash dump; no released
+    /// firmware writes one yet (see `fault_log`)
+    FaultLog,
+    /// UTXO transaction signing as driven by the vault's Bitcoin signer
+    UtxoSigning,
+    /// Ethereum/EVM transaction signing (EIP-155 and EIP-1559)
+    EthereumSigning,
+    /// Composite hardware self-test (display confirmation, RNG, storage readback)
+    SelfTest,
+    /// Symmetric encryption with a key derived on the device (CipherKeyValue)
+    CipherKeyValue,
+}
+
+impl Capability {
+    /// Every known capability, for reporting
+    pub const ALL: &'static [Capability] = &[
+        Capability::FaultLog,
+        Capability::UtxoSigning,
+        Capability::EthereumSigning,
+        Capability::SelfTest,
+        Capability::CipherKeyValue,
+    ];
+
+    /// Whether any released firmware provides this capability
+    pub fn is_released(&self) -> bool {
+        !matches!(self, Capability::FaultLog)
+    }
+
+    /// Minimum firmware version that can provide this capability; None when
+    /// every firmware the vault supports has it
+    pub fn min_version(&self) -> Option<(u32, u32, u32)> {
+        match self {
+            // The signing messages and the self-test's Ping, GetEntropy and
+            // GetPublicKey predate any firmware the vault supports
+            Capability::FaultLog | Capability::UtxoSigning | Capability::EthereumSigning | Capability::SelfTest => None,
+            Capability::CipherKeyValue => Some((6, 0, 0)),
+        }
+    }
+
+    /// Whether the capability also requires a DebugLink-enabled firmware build
+    pub fn requires_debug_link(&self) -> bool {
+        matches!(self, Capability::FaultLog)
+    }
+}
+
+/// Capabilities of a specific device's firmware
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirmwareCapabilities {
+    pub version: (u32, u32, u32),
+    pub bootloader_mode: bool,
+    pub debug_link: bool,
+}
+
+impl FirmwareCapabilities {
+    pub fn from_features(features: &DeviceFeatures) -> Self {
+        let debug_link = features
+            .firmware_variant
+            .as_deref()
+            .map(|v| {
+                let v = v.to_ascii_lowercase();
+                v.contains("debug") || v.contains("emulator")
+            })
+            .unwrap_or(false);
+
+        Self {
+            version: version_triple(&features.version).unwrap_or((0, 0, 0)),
+            bootloader_mode: features.bootloader_mode,
+            debug_link,
+        }
+    }
+
+    /// Whether the firmware supports a capability (never in bootloader mode)
+    pub fn supports(&self, capability: Capability) -> bool {
+        if !capability.is_released() || self.bootloader_mode {
+            return false;
+        }
+        if capability.requires_debug_link() && !self.debug_link {
+            return false;
+        }
+        match capability.min_version() {
+            Some(min) => self.version >= min,
+            None => true,
+        }
+    }
+
+    /// Human-readable reason a capability is unavailable
+    pub fn unsupported_reason(&self, capability: Capability) -> Option<String> {
+        if self.supports(capability) {
+            return None;
+        }
+        Some(if !capability.is_released() {
+            "No released firmware supports this yet".to_string()
+        } else if self.bootloader_mode {
+            "Device is in bootloader mode".to_string()
+        } else if capability.requires_debug_link() && !self.debug_link {
+            "Requires a DebugLink-enabled firmware build".to_string()
+        } else {
+            let (major, minor, patch) = capability.min_version().unwrap_or_default();
+            format!("Requires firmware {}.{}.{} or newer", major, minor, patch)
+        })
+    }
+
+    pub fn supported(&self) -> Vec<Capability> {
+        Capability::ALL.iter().copied().filter(|c| self.supports(*c)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caps(version: (u32, u32, u32), debug_link: bool) -> FirmwareCapabilities {
+        FirmwareCapabilities { version, bootloader_mode: false, debug_link }
+    }
+
+    #[test]
+    fn test_fault_log_is_not_supported_by_any_firmware() {
+        for debug_link in [false, true] {
+            assert!(!caps((7, 10, 0), debug_link).supports(Capability::FaultLog));
+            assert_eq!(
+                caps((7, 10, 0), debug_link).unsupported_reason(Capability::FaultLog).as_deref(),
+                Some("No released firmware supports this yet")
+            );
+        }
+    }
+
+    #[test]
+    fn test_capabilities_gate_on_version() {
+        for capability in [Capability::UtxoSigning, Capability::EthereumSigning, Capability::SelfTest] {
+            assert!(caps((6, 4, 0), false).supports(capability));
+        }
+        assert!(caps((6, 4, 0), false).supports(Capability::CipherKeyValue));
+        assert!(!caps((5, 11, 0), false).supports(Capability::CipherKeyValue));
+        assert_eq!(
+            caps((5, 11, 0), false).unsupported_reason(Capability::CipherKeyValue).as_deref(),
+            Some("Requires firmware 6.0.0 or newer")
+        );
+        let bootloader = FirmwareCapabilities { version: (7, 10, 0), bootloader_mode: true, debug_link: false };
+        assert!(bootloader.supported().is_empty());
+    }
+}
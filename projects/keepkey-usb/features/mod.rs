@@ -7,13 +7,17 @@ use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
 use once_cell::sync::Lazy;
 
-use crate::messages::{Initialize, Message};
+use crate::messages::{Features, Initialize, Message};
 use crate::transport::{ProtocolAdapter, UsbTransport, HidTransport};
 use crate::friendly_usb::FriendlyUsbDevice;
 
+pub mod capabilities;
+
 
 const TAG: &str = " | features | ";
 const DEVICE_IDS: &[(u16, u16)] = &[(0x2b24, 0x0001), (0x2b24, 0x0002)];
+/// The HID product id legacy firmware and bootloaders enumerate with
+const LEGACY_PID: u16 = 0x0001;
 
 /// Device cache to maintain stable device identities across inconsistent USB enumeration
 #[derive(Debug, Clone)]
@@ -41,6 +45,14 @@ fn clean_device_cache() {
     }
 }
 
+/// Drop the cached identity for a bus:address a device has just left, so a
+/// different device enumerated there is not mistaken for it
+pub(crate) fn forget_cached_device(bus: u8, address: u8) {
+    if let Ok(mut cache) = DEVICE_CACHE.lock() {
+        cache.retain(|_, info| (info.bus, info.address) != (bus, address));
+    }
+}
+
 // List usb devices
 // This is kept internal to this module for now.
 //
@@ -62,6 +74,7 @@ pub fn list_devices() -> Box<[Device<GlobalContext>]> {
 /// Structure representing device features returned by the KeepKey
 /// This is a simplified version that includes the most commonly used fields
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
 #[serde(rename_all = "camelCase")]
 pub struct DeviceFeatures {
     /// Device label or name
@@ -205,66 +218,8 @@ pub fn get_device_features_for_device(target_device: &FriendlyUsbDevice) -> Resu
     let mut transport = crate::device_queue::DeviceQueueFactory::create_transport_for_device(target_device)
         .map_err(|e| anyhow!("Failed to initialize transport for device {}: {}", target_device.unique_id, e))?;
 
-    // Reset the device to clear any stuck state
-    log::info!("{TAG} Resetting device {} before communication...", target_device.unique_id);
-    transport.reset()
-        .map_err(|e| anyhow!("Failed to reset device {}: {}", target_device.unique_id, e))?;
-    
-    // Add a small delay after reset
-    std::thread::sleep(std::time::Duration::from_millis(100));
-
-    let features_msg = transport
-        .handle(Initialize::default().into())
-        .map_err(|e| anyhow!("Failed to communicate with device {}: {}", target_device.unique_id, e))?;
-
-    // Extract features from response
-    let features = match features_msg {
-        Message::Features(f) => f,
-        _ => return Err(anyhow!("Unexpected response from device {}", target_device.unique_id)),
-    };
-
-    // Convert to our DeviceFeatures struct
-    let device_features = DeviceFeatures {
-        label: features.label,
-        vendor: features.vendor,
-        model: features.model,
-        firmware_variant: features.firmware_variant,
-        device_id: features.device_id,
-        language: features.language,
-        bootloader_mode: features.bootloader_mode.unwrap_or(false),
-        version: format!(
-            "{}.{}.{}",
-            features.major_version.unwrap_or(0),
-            features.minor_version.unwrap_or(0),
-            features.patch_version.unwrap_or(0)
-        ),
-        firmware_hash: features.firmware_hash.map(hex::encode),
-        bootloader_hash: features.bootloader_hash.clone().map(hex::encode),
-        bootloader_version: features.bootloader_hash
-            .map(hex::encode)
-            // Bootloader version mapping removed (was device_update::bootloader_version_from_hash)
-            // .and_then(|hash| bootloader_version_from_hash(&hash)),
-            // Optionally just pass through the hash or leave as None
-            .and_then(|hash| Some(hash)),
-
-        initialized: features.initialized.unwrap_or(false),
-        imported: features.imported,
-        no_backup: features.no_backup.unwrap_or(false),
-        pin_protection: features.pin_protection.unwrap_or(false),
-        pin_cached: features.pin_cached.unwrap_or(false),
-        passphrase_protection: features.passphrase_protection.unwrap_or(false),
-        passphrase_cached: features.passphrase_cached.unwrap_or(false),
-        wipe_code_protection: features.wipe_code_protection.unwrap_or(false),
-        auto_lock_delay_ms: features.auto_lock_delay_ms.map(|ms| ms as u64),
-        policies: features
-            .policies
-            .into_iter()
-            .filter(|p| p.enabled())
-            .map(|p| p.policy_name().to_string())
-            .collect(),
-    };
-    log::info!("{TAG} Successfully got features for device {}: firmware v{}", target_device.unique_id, device_features.version);
-    Ok(device_features)
+    probe_device_features(transport.as_mut_dyn(), target_device.pid)
+        .map_err(|e| anyhow!("Failed to communicate with device {}: {}", target_device.unique_id, e))
 }
 
 /// Get device features from a connected KeepKey
@@ -429,155 +384,127 @@ pub fn get_device_features_with_fallback(target_device: &FriendlyUsbDevice) -> R
 
 /// Get device features from a specific KeepKey device via HID
 ///
-/// This function connects to the specific KeepKey device using HID API,
-/// sends a GetFeatures request, and returns a structured representation
-/// of the device's features and settings.
+/// The device is opened by serial number and probed with
+/// `probe_device_features`. Without a serial number it can only be told apart
+/// when it is the one KeepKey on HID; otherwise another device's features
+/// would be reported for it.
 ///
 /// # Arguments
 /// * `target_device` - The specific device to get features for
 ///
 /// # Returns
-/// - `Ok(DeviceFeatures)` if successful with all device information
-/// - `Err` if device connection fails or the device doesn't respond properly
+/// - `Ok(DeviceFeatures)` if the device answered with features that can be trusted
+/// - `Err` if the device could not be reached or its answer was not meaningful
 pub fn get_device_features_via_hid(target_device: &FriendlyUsbDevice) -> Result<DeviceFeatures> {
     use hidapi::HidApi;
     log::info!("{TAG} Getting features for device via HID: {} ({})", target_device.name, target_device.unique_id);
 
-    // If serial is present, use it; otherwise, enumerate all HID KeepKey devices and try each
-    let mut errors = vec![];
-    if let Some(serial) = &target_device.serial_number {
-        match HidTransport::new_for_device(Some(serial.as_str())) {
-            Ok(mut transport) => {
-                let adapter = &mut transport as &mut dyn ProtocolAdapter;
-                
-                // Reset device before communication
-                log::info!("{TAG} Resetting HID device via serial {} before communication...", serial);
-                let _ = adapter.reset(); // Ignore reset errors for HID
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                
-                let init_msg = Initialize::default().into();
-                match adapter.handle(init_msg) {
-                    Ok(features_msg) => {
-                        let features = match features_msg {
-                            Message::Features(f) => f,
-                            _ => return Err(anyhow!("Unexpected response from device {} via HID", target_device.unique_id)),
-                        };
-                        let device_features = DeviceFeatures {
-                            label: features.label,
-                            vendor: features.vendor,
-                            model: features.model,
-                            firmware_variant: features.firmware_variant,
-                            device_id: features.device_id,
-                            language: features.language,
-                            bootloader_mode: features.bootloader_mode.unwrap_or(false),
-                            version: format!(
-                                "{}.{}.{}",
-                                features.major_version.unwrap_or(0),
-                                features.minor_version.unwrap_or(0),
-                                features.patch_version.unwrap_or(0)
-                            ),
-                            firmware_hash: features.firmware_hash.map(hex::encode),
-                            bootloader_hash: features.bootloader_hash.clone().map(hex::encode),
-                            bootloader_version: features.bootloader_hash
-                                .map(hex::encode)
-                                .and_then(|hash| Some(hash)),
-                            initialized: features.initialized.unwrap_or(false),
-                            imported: features.imported,
-                            no_backup: features.no_backup.unwrap_or(false),
-                            pin_protection: features.pin_protection.unwrap_or(false),
-                            pin_cached: features.pin_cached.unwrap_or(false),
-                            passphrase_protection: features.passphrase_protection.unwrap_or(false),
-                            passphrase_cached: features.passphrase_cached.unwrap_or(false),
-                            wipe_code_protection: features.wipe_code_protection.unwrap_or(false),
-                            auto_lock_delay_ms: features.auto_lock_delay_ms.map(|ms| ms as u64),
-                            policies: features
-                                .policies
-                                .into_iter()
-                                .filter(|p| p.enabled())
-                                .map(|p| p.policy_name().to_string())
-                                .collect(),
-                        };
-                        log::info!("{TAG} Successfully got features via HID for device {}: firmware v{}", target_device.unique_id, device_features.version);
-                        return Ok(device_features);
-                    }
-                    Err(e) => errors.push(format!("HID (serial match) error: {}", e)),
+    let serial = match &target_device.serial_number {
+        Some(serial) => Some(serial.clone()),
+        None => {
+            let api = HidApi::new().map_err(|e| anyhow!("Failed to initialize HID API: {}", e))?;
+            let serials: HashSet<Option<String>> = api
+                .device_list()
+                .filter(|d| DEVICE_IDS.contains(&(d.vendor_id(), d.product_id())))
+                .map(|d| d.serial_number().map(str::to_string))
+                .collect();
+            match serials.len() {
+                0 => return Err(anyhow!("No HID KeepKey devices found for {}", target_device.unique_id)),
+                1 => serials.into_iter().next().flatten(),
+                n => {
+                    return Err(anyhow!(
+                        "{} KeepKeys are on HID and {} has no serial number to tell them apart",
+                        n,
+                        target_device.unique_id
+                    ))
                 }
             }
-            Err(e) => errors.push(format!("HID (serial match) transport error: {}", e)),
-        }
-    } else {
-        // No serial: enumerate all HID KeepKey devices and try each
-        let api = HidApi::new().map_err(|e| anyhow!("Failed to initialize HID API: {}", e))?;
-        let mut found_any = false;
-        for device_info in api.device_list() {
-            if device_info.vendor_id() == 0x2b24 && (device_info.product_id() == 0x0001 || device_info.product_id() == 0x0002) {
-                found_any = true;
-                match HidTransport::new_for_device(device_info.serial_number()) {
-                    Ok(mut transport) => {
-                        let adapter = &mut transport as &mut dyn ProtocolAdapter;
-                        
-                        // Reset device before communication
-                        log::info!("{TAG} Resetting HID device (enumerate) before communication...");
-                        let _ = adapter.reset(); // Ignore reset errors for HID
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                        
-                        let init_msg = Initialize::default().into();
-                        match adapter.handle(init_msg) {
-                            Ok(features_msg) => {
-                                let features = match features_msg {
-                                    Message::Features(f) => f,
-                                    _ => continue, // try next
-                                };
-                                let device_features = DeviceFeatures {
-                                    label: features.label,
-                                    vendor: features.vendor,
-                                    model: features.model,
-                                    firmware_variant: features.firmware_variant,
-                                    device_id: features.device_id,
-                                    language: features.language,
-                                    bootloader_mode: features.bootloader_mode.unwrap_or(false),
-                                    version: format!(
-                                        "{}.{}.{}",
-                                        features.major_version.unwrap_or(0),
-                                        features.minor_version.unwrap_or(0),
-                                        features.patch_version.unwrap_or(0)
-                                    ),
-                                    firmware_hash: features.firmware_hash.map(hex::encode),
-                                    bootloader_hash: features.bootloader_hash.clone().map(hex::encode),
-                                    bootloader_version: features.bootloader_hash
-                                        .map(hex::encode)
-                                        .and_then(|hash| Some(hash)),
-                                    initialized: features.initialized.unwrap_or(false),
-                                    imported: features.imported,
-                                    no_backup: features.no_backup.unwrap_or(false),
-                                    pin_protection: features.pin_protection.unwrap_or(false),
-                                    pin_cached: features.pin_cached.unwrap_or(false),
-                                    passphrase_protection: features.passphrase_protection.unwrap_or(false),
-                                    passphrase_cached: features.passphrase_cached.unwrap_or(false),
-                                    wipe_code_protection: features.wipe_code_protection.unwrap_or(false),
-                                    auto_lock_delay_ms: features.auto_lock_delay_ms.map(|ms| ms as u64),
-                                    policies: features
-                                        .policies
-                                        .into_iter()
-                                        .filter(|p| p.enabled())
-                                        .map(|p| p.policy_name().to_string())
-                                        .collect(),
-                                };
-                                log::info!("{TAG} Successfully got features via HID (enumerate) for device: firmware v{}", device_features.version);
-                                return Ok(device_features);
-                            }
-                            Err(e) => errors.push(format!("HID (enumerate) comm error for serial {:?}: {}", device_info.serial_number(), e)),
-                        }
-                    }
-                    Err(e) => errors.push(format!("HID (enumerate) transport error for serial {:?}: {}", device_info.serial_number(), e)),
-                }
-            }
-        }
-        if !found_any {
-            errors.push("No HID KeepKey devices found".to_string());
         }
+    };
+
+    let mut transport = HidTransport::new_for_device(serial.as_deref())
+        .map_err(|e| anyhow!("HID transport error for device {}: {}", target_device.unique_id, e))?;
+    let device_features = probe_device_features(&mut transport, target_device.pid)
+        .map_err(|e| anyhow!("HID communication with device {} failed: {}", target_device.unique_id, e))?;
+    log::info!(
+        "{TAG} Got features via HID for device {}: v{} (bootloader_mode: {})",
+        target_device.unique_id,
+        device_features.version,
+        device_features.bootloader_mode
+    );
+    Ok(device_features)
+}
+
+/// Ask a device for its features with the message every bootloader understands
+///
+/// Legacy bootloaders reject GetFeatures, so this sends Initialize. The answer
+/// is only trusted when it is a Features message that identifies the device,
+/// and bootloader mode is only reported when the device itself claims it; a
+/// legacy (1.x) bootloader must also have enumerated with the HID PID, as it
+/// predates WebUSB. Anything else is an error, never a guess.
+pub fn probe_device_features(adapter: &mut dyn ProtocolAdapter, pid: u16) -> Result<DeviceFeatures> {
+    // A wedged device may refuse the reset and still answer Initialize
+    if let Err(e) = adapter.reset() {
+        log::debug!("{TAG} Reset before Initialize failed: {}", e);
+    }
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let features = match adapter.handle(Initialize::default().into())? {
+        Message::Features(features) => features,
+        Message::Failure(failure) => return Err(anyhow!("Device rejected Initialize: {}", failure.message())),
+        other => return Err(anyhow!("Unexpected response to Initialize: {:?}", other.message_type())),
+    };
+    if features.vendor.is_none() && features.device_id.is_none() && features.major_version.is_none() {
+        return Err(anyhow!("Device answered Initialize with empty features"));
+    }
+    if features.bootloader_mode == Some(true) && features.major_version.unwrap_or(0) < 2 && pid != LEGACY_PID {
+        return Err(anyhow!(
+            "Device claims a legacy bootloader but enumerated with PID {:#06x}",
+            pid
+        ));
+    }
+    Ok(device_features_from(features))
+}
+
+/// Convert a Features message. Only a device running its bootloader reports
+/// the bootloader's version; otherwise it is left for the caller to look up
+/// from the bootloader hash.
+fn device_features_from(features: Features) -> DeviceFeatures {
+    let bootloader_mode = features.bootloader_mode.unwrap_or(false);
+    let version = format!(
+        "{}.{}.{}",
+        features.major_version.unwrap_or(0),
+        features.minor_version.unwrap_or(0),
+        features.patch_version.unwrap_or(0)
+    );
+    DeviceFeatures {
+        label: features.label,
+        vendor: features.vendor,
+        model: features.model,
+        firmware_variant: features.firmware_variant,
+        device_id: features.device_id,
+        language: features.language,
+        bootloader_mode,
+        bootloader_version: bootloader_mode.then(|| version.clone()),
+        version,
+        firmware_hash: features.firmware_hash.map(hex::encode),
+        bootloader_hash: features.bootloader_hash.map(hex::encode),
+        initialized: features.initialized.unwrap_or(false),
+        imported: features.imported,
+        no_backup: features.no_backup.unwrap_or(false),
+        pin_protection: features.pin_protection.unwrap_or(false),
+        pin_cached: features.pin_cached.unwrap_or(false),
+        passphrase_protection: features.passphrase_protection.unwrap_or(false),
+        passphrase_cached: features.passphrase_cached.unwrap_or(false),
+        wipe_code_protection: features.wipe_code_protection.unwrap_or(false),
+        auto_lock_delay_ms: features.auto_lock_delay_ms.map(|ms| ms as u64),
+        policies: features
+            .policies
+            .into_iter()
+            .filter(|p| p.enabled())
+            .map(|p| p.policy_name().to_string())
+            .collect(),
     }
-    Err(anyhow!("All HID attempts failed for device {}. Errors: {}", target_device.unique_id, errors.join(" | ")))
 }
 
 /// Convert a low-level USB device to a FriendlyUsbDevice
@@ -705,7 +632,7 @@ pub fn list_connected_devices() -> Vec<FriendlyUsbDevice> {
 }
 
 /// Convert a USB device to FriendlyUsbDevice with caching for stability
-fn device_to_friendly_with_cache(device: &rusb::Device<rusb::GlobalContext>) -> FriendlyUsbDevice {
+pub(crate) fn device_to_friendly_with_cache(device: &rusb::Device<rusb::GlobalContext>) -> FriendlyUsbDevice {
     let desc = device.device_descriptor().unwrap();
     let vid = desc.vendor_id();
     let pid = desc.product_id();
@@ -828,3 +755,94 @@ pub fn get_device_features_by_id(device_id: &str) -> Result<DeviceFeatures> {
     get_device_features_with_fallback(device)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages;
+
+    /// Answers Initialize from a script; an `Err` stands for a USB error
+    struct ScriptedAdapter {
+        response: Option<std::result::Result<Message, String>>,
+    }
+
+    impl ProtocolAdapter for ScriptedAdapter {
+        fn reset(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn send(&mut self, _msg: Message) -> Result<()> {
+            Ok(())
+        }
+
+        fn handle(&mut self, msg: Message) -> Result<Message> {
+            assert!(matches!(msg, Message::Initialize(_)));
+            self.response
+                .take()
+                .expect("device asked twice")
+                .map_err(anyhow::Error::msg)
+        }
+
+        fn as_mut_dyn(&mut self) -> &mut dyn ProtocolAdapter {
+            self
+        }
+    }
+
+    fn probe(response: std::result::Result<Message, String>, pid: u16) -> Result<DeviceFeatures> {
+        probe_device_features(&mut ScriptedAdapter { response: Some(response) }, pid)
+    }
+
+    fn legacy_bootloader() -> Message {
+        Message::Features(Features {
+            vendor: Some("keepkey.com".to_string()),
+            major_version: Some(1),
+            minor_version: Some(0),
+            patch_version: Some(3),
+            bootloader_mode: Some(true),
+            bootloader_hash: Some(vec![0xab; 32]),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_genuine_legacy_bootloader_is_reported() {
+        let features = probe(Ok(legacy_bootloader()), LEGACY_PID).unwrap();
+        assert!(features.bootloader_mode);
+        assert_eq!(features.version, "1.0.3");
+        assert_eq!(features.bootloader_version.as_deref(), Some("1.0.3"));
+        assert_eq!(features.bootloader_hash, Some("ab".repeat(32)));
+
+        // Legacy bootloaders only speak HID; one on the WebUSB PID is not believed
+        assert!(probe(Ok(legacy_bootloader()), 0x0002).is_err());
+    }
+
+    #[test]
+    fn test_firmware_device_is_never_reported_as_bootloader() {
+        let firmware = Message::Features(Features {
+            vendor: Some("keepkey.com".to_string()),
+            major_version: Some(7),
+            minor_version: Some(10),
+            patch_version: Some(0),
+            bootloader_hash: Some(vec![0xcd; 32]),
+            initialized: Some(true),
+            ..Default::default()
+        });
+        let features = probe(Ok(firmware), 0x0002).unwrap();
+        assert!(!features.bootloader_mode);
+        assert_eq!(features.version, "7.10.0");
+        // The hash is not a version; the caller looks it up in the release catalog
+        assert_eq!(features.bootloader_version, None);
+    }
+
+    #[test]
+    fn test_transient_failures_are_errors_not_features() {
+        assert!(probe(Err("HID write failed: timed out".to_string()), LEGACY_PID).is_err());
+        assert!(probe(Ok(Message::Features(Features::default())), LEGACY_PID).is_err());
+        let refused = Message::Failure(messages::Failure {
+            code: None,
+            message: Some("Unknown message".to_string()),
+        });
+        assert!(probe(Ok(refused), LEGACY_PID).is_err());
+        assert!(probe(Ok(Message::Success(messages::Success::default())), LEGACY_PID).is_err());
+    }
+}
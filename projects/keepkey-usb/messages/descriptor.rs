@@ -0,0 +1,180 @@
+//! Message schema of the compiled device-protocol revision
+//!
+//! build.rs keeps protoc's descriptor set and turns it into
+//! `protocol_descriptor.json` next to the generated Rust, so the schema
+//! reported here always matches the messages compiled into this build. Each
+//! message lists its fields with proto types and the camelCase JSON names the
+//! generated serde attributes use.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::Message;
+
+const DESCRIPTOR_JSON: &str = include_str!(concat!(env!("OUT_DIR"), "/protocol_descriptor.json"));
+
+/// Nested messages deeper than this are left as `{}` in examples
+const EXAMPLE_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct FieldSchema {
+    pub name: String,
+    /// Key used in the message's JSON form
+    pub json_name: String,
+    pub number: i32,
+    /// Scalar proto type, or the message/enum type name
+    #[serde(rename = "type")]
+    pub field_type: String,
+    /// "scalar", "message" or "enum"
+    pub kind: String,
+    /// "optional", "required" or "repeated"
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct MessageSchema {
+    /// Nested messages are named `Parent.Child`
+    pub name: String,
+    /// .proto file the message is defined in
+    pub file: String,
+    /// MessageType value, for messages that can be sent on the wire
+    pub wire_id: Option<i32>,
+    pub fields: Vec<FieldSchema>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ProtocolDescriptor {
+    /// device-protocol package version, when its package.json was present
+    pub version: Option<String>,
+    /// device-protocol commit, when built from a git checkout
+    pub commit: Option<String>,
+    pub messages: Vec<MessageSchema>,
+}
+
+static DESCRIPTOR: Lazy<ProtocolDescriptor> = Lazy::new(|| {
+    serde_json::from_str(DESCRIPTOR_JSON).expect("build.rs writes a valid protocol descriptor")
+});
+
+/// The schema embedded at build time
+pub fn protocol_descriptor() -> &'static ProtocolDescriptor {
+    &DESCRIPTOR
+}
+
+impl ProtocolDescriptor {
+    pub fn message(&self, name: &str) -> Option<&MessageSchema> {
+        self.messages.iter().find(|m| m.name == name)
+    }
+
+    /// Example JSON for a message: its default instance, with every field
+    /// the default leaves out filled with a placeholder of the right shape
+    pub fn example_json(&self, name: &str) -> Option<serde_json::Value> {
+        self.example_at_depth(name, 0)
+    }
+
+    fn example_at_depth(&self, name: &str, depth: usize) -> Option<serde_json::Value> {
+        let schema = self.message(name)?;
+        let mut example = match Message::default_json(name) {
+            Some(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        for field in &schema.fields {
+            if example.contains_key(&field.json_name) {
+                continue;
+            }
+            let value = if field.label == "repeated" {
+                serde_json::Value::Array(Vec::new())
+            } else {
+                self.placeholder(field, depth)
+            };
+            example.insert(field.json_name.clone(), value);
+        }
+        Some(serde_json::Value::Object(example))
+    }
+
+    fn placeholder(&self, field: &FieldSchema, depth: usize) -> serde_json::Value {
+        match (field.kind.as_str(), field.field_type.as_str()) {
+            ("message", type_name) if depth < EXAMPLE_DEPTH => self
+                .example_at_depth(type_name, depth + 1)
+                .unwrap_or_else(|| serde_json::json!({})),
+            ("message", _) => serde_json::json!({}),
+            // Enums are i32 fields in the generated code
+            ("enum", _) => serde_json::json!(0),
+            (_, "bool") => serde_json::json!(false),
+            (_, "string") => serde_json::json!(""),
+            (_, "bytes") => serde_json::json!([]),
+            (_, "double" | "float") => serde_json::json!(0.0),
+            _ => serde_json::json!(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message as _;
+
+    fn field<'a>(message: &'a MessageSchema, name: &str) -> &'a FieldSchema {
+        message.fields.iter().find(|f| f.name == name)
+            .unwrap_or_else(|| panic!("{} has no field {}", message.name, name))
+    }
+
+    #[test]
+    fn test_descriptor_covers_generated_messages() {
+        let descriptor = protocol_descriptor();
+        for name in Message::NAMES {
+            let message = descriptor.message(name).unwrap_or_else(|| panic!("{} missing from descriptor", name));
+            assert!(message.wire_id.is_some(), "{} has no MessageType value", name);
+        }
+        assert_eq!(descriptor.message("Initialize").unwrap().wire_id, Some(0));
+        assert_eq!(descriptor.message("Features").unwrap().wire_id, Some(17));
+    }
+
+    #[test]
+    fn test_known_fields() {
+        let descriptor = protocol_descriptor();
+
+        let get_address = descriptor.message("GetAddress").unwrap();
+        let address_n = field(get_address, "address_n");
+        assert_eq!(address_n.field_type, "uint32");
+        assert_eq!(address_n.label, "repeated");
+        assert_eq!(address_n.json_name, "addressN");
+        assert_eq!(field(get_address, "show_display").field_type, "bool");
+
+        let features = descriptor.message("Features").unwrap();
+        assert_eq!(field(features, "vendor").field_type, "string");
+        assert_eq!(field(features, "major_version").field_type, "uint32");
+
+        let sign_tx = descriptor.message("EthereumSignTx").unwrap();
+        assert_eq!(field(sign_tx, "nonce").field_type, "bytes");
+
+        let public_key = descriptor.message("PublicKey").unwrap();
+        let node = field(public_key, "node");
+        assert_eq!(node.kind, "message");
+        assert_eq!(node.field_type, "HDNodeType");
+    }
+
+    #[test]
+    fn test_examples_round_trip() {
+        let descriptor = protocol_descriptor();
+        for name in Message::NAMES {
+            let example = descriptor.example_json(name).unwrap();
+            let schema = descriptor.message(name).unwrap();
+            for field in &schema.fields {
+                assert!(example.get(&field.json_name).is_some(), "{} example lacks {}", name, field.json_name);
+            }
+        }
+
+        // Default instances survive an encode/decode round trip unchanged
+        let ping = crate::messages::Ping::default();
+        let decoded = crate::messages::Ping::decode(ping.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), Message::default_json("Ping").unwrap());
+
+        let example = descriptor.example_json("GetAddress").unwrap();
+        assert_eq!(example["addressN"], serde_json::json!([]));
+        assert_eq!(example["showDisplay"], serde_json::json!(false));
+        assert!(descriptor.example_json("NotAMessage").is_none());
+    }
+}
@@ -45,6 +45,25 @@ macro_rules! kk_message {
         }
 
         impl Message {
+            /// Every message type this build can send or receive
+            pub const NAMES: &'static [&'static str] = &[$(stringify!($x)),*];
+
+            /// JSON of the named message's default instance, in the shape
+            /// the serde attributes from build.rs produce
+            pub fn default_json(name: &str) -> Option<::serde_json::Value> {
+                match name {
+                    $(stringify!($x) => ::serde_json::to_value(protos::$x::default()).ok(),)*
+                    _ => None,
+                }
+            }
+
+            /// This message as JSON, in the shape `default_json` describes
+            pub fn to_json(&self) -> Option<::serde_json::Value> {
+                match self {
+                    $(Message::$x(x) => ::serde_json::to_value(x).ok()),*
+                }
+            }
+
             pub const fn message_type(&self) -> protos::MessageType {
                 match self {
                     $(Message::$x(_) => protos::MessageType::$x),*
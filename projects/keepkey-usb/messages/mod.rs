@@ -1,8 +1,10 @@
+mod descriptor;
 mod encoding;
 mod macros;
 mod protos;
 mod timeouts;
 
+pub use descriptor::{protocol_descriptor, FieldSchema, MessageSchema, ProtocolDescriptor};
 pub use protos::*;
 
 use macros::kk_message;
@@ -161,3 +163,15 @@ kk_message!(
     MayachainMsgAck,
     MayachainSignedTx
 );
+
+impl Message {
+    /// Zero the PIN or passphrase an ack carries, once it has been sent
+    pub fn wipe_secrets(&mut self) {
+        use zeroize::Zeroize;
+        match self {
+            Message::PassphraseAck(ack) => ack.passphrase.zeroize(),
+            Message::PinMatrixAck(ack) => ack.pin.zeroize(),
+            _ => {}
+        }
+    }
+}
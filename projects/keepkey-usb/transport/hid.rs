@@ -25,6 +25,14 @@ pub struct HidTransport {
     device: HidDevice,
 }
 
+/// Initialize the HID API and count the HID devices it can enumerate.
+/// Fails on hosts where HID access is blocked (some antivirus products on
+/// Windows), which also rules out the HID transport fallback.
+pub fn probe_hid_api() -> Result<usize> {
+    let api = HidApi::new().map_err(|e| anyhow!("Failed to initialize HID API: {}", e))?;
+    Ok(api.device_list().count())
+}
+
 impl HidTransport {
     /// Handle specific device open errors with helpful messages
     fn handle_device_open_error(error: &hidapi::HidError, serial: &str) -> Result<()> {
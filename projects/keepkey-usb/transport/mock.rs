@@ -0,0 +1,217 @@
+//! A scripted KeepKey for tests and CI
+//!
+//! Answers from a JSON fixture instead of USB: features, and the addresses
+//! and xpubs it lists per derivation path. Pings are confirmed as if the
+//! button was pressed, unless the fixture cancels confirmations, and entropy
+//! requests get random bytes. Every other request, and any path the fixture
+//! does not list, gets a Failure, so an unscripted request is never mistaken
+//! for a device answer.
+
+use std::collections::HashMap;
+use std::path::Path;
+use anyhow::{anyhow, Context, Result};
+use rand::RngCore;
+use serde::Deserialize;
+use crate::friendly_usb::{FriendlyUsbDevice, KEEPKEY_VID};
+use crate::messages::{self, Message};
+use super::ProtocolAdapter;
+
+/// Product id of a KeepKey running WebUSB firmware
+const MOCK_PID: u16 = 0x0002;
+
+const HARDENED: u32 = 0x8000_0000;
+
+/// The device a fixture describes
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockDevice {
+    pub device_id: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Firmware version as major.minor.patch
+    pub version: String,
+    #[serde(default)]
+    pub bootloader_mode: bool,
+    #[serde(default)]
+    pub initialized: bool,
+    /// Addresses by path, e.g. "m/84'/0'/0'/0/0"; EVM addresses as 0x hex
+    #[serde(default)]
+    pub addresses: HashMap<String, String>,
+    /// Extended public keys by path
+    #[serde(default)]
+    pub xpubs: HashMap<String, String>,
+    /// Answer button-protected requests as if the user pressed cancel
+    #[serde(default)]
+    pub cancel_confirmations: bool,
+}
+
+impl MockDevice {
+    /// Read a fixture file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read mock device {:?}", path))?;
+        serde_json::from_str(&text).with_context(|| format!("Invalid mock device {:?}", path))
+    }
+
+    /// How the device shows up in a device listing
+    pub fn usb_device(&self) -> FriendlyUsbDevice {
+        FriendlyUsbDevice::new(
+            self.device_id.clone(),
+            KEEPKEY_VID,
+            MOCK_PID,
+            Some("KeepKey".to_string()),
+            Some("KeepKey (mock)".to_string()),
+            Some(self.device_id.clone()),
+        )
+    }
+
+    fn features(&self) -> Result<messages::Features> {
+        let mut parts = self.version.split('.').map(|part| part.parse::<u32>());
+        let mut next = || parts.next().transpose().map(|v| v.unwrap_or(0));
+        let invalid = |_| anyhow!("Invalid mock firmware version {:?}", self.version);
+        Ok(messages::Features {
+            vendor: Some("keepkey.com".to_string()),
+            major_version: Some(next().map_err(invalid)?),
+            minor_version: Some(next().map_err(invalid)?),
+            patch_version: Some(next().map_err(invalid)?),
+            device_id: Some(self.device_id.clone()),
+            label: self.label.clone(),
+            initialized: Some(self.initialized),
+            bootloader_mode: Some(self.bootloader_mode),
+            ..Default::default()
+        })
+    }
+
+    /// The device's answer to `request`
+    pub fn answer(&self, request: &Message) -> Result<Message> {
+        let unscripted = |what: String| {
+            Message::Failure(messages::Failure { code: None, message: Some(format!("The mock device has no {}", what)) })
+        };
+        Ok(match request {
+            Message::GetFeatures(_) | Message::Initialize(_) => Message::Features(self.features()?),
+            Message::Ping(req) if req.button_protection == Some(true) && self.cancel_confirmations => {
+                Message::Failure(messages::Failure {
+                    code: Some(messages::FailureType::FailureActionCancelled as i32),
+                    message: Some("Action cancelled by user".to_string()),
+                })
+            }
+            Message::Ping(req) => Message::Success(messages::Success { message: req.message.clone() }),
+            Message::GetEntropy(req) => {
+                let mut entropy = vec![0u8; req.size as usize];
+                rand::thread_rng().fill_bytes(&mut entropy);
+                Message::Entropy(messages::Entropy { entropy })
+            }
+            Message::GetAddress(req) => match self.addresses.get(&path_key(&req.address_n)) {
+                Some(address) => Message::Address(messages::Address { address: address.clone() }),
+                None => unscripted(format!("address at {}", path_key(&req.address_n))),
+            },
+            Message::EthereumGetAddress(req) => match self.addresses.get(&path_key(&req.address_n)) {
+                Some(address) => Message::EthereumAddress(messages::EthereumAddress {
+                    address: hex::decode(address.trim_start_matches("0x"))
+                        .with_context(|| format!("Invalid mock EVM address {}", address))?,
+                    ..Default::default()
+                }),
+                None => unscripted(format!("address at {}", path_key(&req.address_n))),
+            },
+            Message::GetPublicKey(req) => match self.xpubs.get(&path_key(&req.address_n)) {
+                Some(xpub) => Message::PublicKey(messages::PublicKey { xpub: Some(xpub.clone()), ..Default::default() }),
+                None => unscripted(format!("xpub at {}", path_key(&req.address_n))),
+            },
+            other => unscripted(format!("answer to {:?}", other.message_type())),
+        })
+    }
+}
+
+/// Path as the fixture writes it: m/84'/0'/0'/0/0
+fn path_key(address_n: &[u32]) -> String {
+    std::iter::once("m".to_string())
+        .chain(address_n.iter().map(|&n| match n & HARDENED {
+            0 => n.to_string(),
+            _ => format!("{}'", n & !HARDENED),
+        }))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Transport to a [`MockDevice`]
+pub struct MockTransport {
+    device: MockDevice,
+}
+
+impl MockTransport {
+    pub fn new(device: MockDevice) -> Self {
+        Self { device }
+    }
+}
+
+impl ProtocolAdapter for MockTransport {
+    fn reset(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn send(&mut self, _msg: Message) -> Result<()> {
+        Ok(())
+    }
+
+    fn handle(&mut self, msg: Message) -> Result<Message> {
+        self.device.answer(&msg)
+    }
+
+    fn as_mut_dyn(&mut self) -> &mut dyn ProtocolAdapter {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device() -> MockDevice {
+        serde_json::from_value(serde_json::json!({
+            "device_id": "MOCK0001",
+            "version": "7.9.1",
+            "initialized": true,
+            "addresses": { "m/44'/60'/0'/0/0": "0x73d0385f4d8e00c5e6504c6030f47bf6212736a8" },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_answers_from_the_fixture() {
+        let device = device();
+        match device.answer(&Message::GetFeatures(Default::default())).unwrap() {
+            Message::Features(features) => {
+                assert_eq!((features.major_version, features.minor_version, features.patch_version), (Some(7), Some(9), Some(1)));
+                assert_eq!(features.device_id.as_deref(), Some("MOCK0001"));
+            }
+            other => panic!("answered {:?}", other.message_type()),
+        }
+
+        let request = messages::EthereumGetAddress { address_n: vec![44 | HARDENED, 60 | HARDENED, HARDENED, 0, 0], show_display: Some(false) };
+        match device.answer(&Message::EthereumGetAddress(request)).unwrap() {
+            Message::EthereumAddress(address) => assert_eq!(hex::encode(address.address), "73d0385f4d8e00c5e6504c6030f47bf6212736a8"),
+            other => panic!("answered {:?}", other.message_type()),
+        }
+
+        // Paths and requests the fixture does not script are refused
+        let request = messages::EthereumGetAddress { address_n: vec![44 | HARDENED, 60 | HARDENED, HARDENED, 0, 1], show_display: Some(false) };
+        assert!(matches!(device.answer(&Message::EthereumGetAddress(request)).unwrap(), Message::Failure(_)));
+        assert!(matches!(device.answer(&Message::GetPublicKey(Default::default())).unwrap(), Message::Failure(_)));
+        assert!(matches!(device.answer(&Message::SignMessage(Default::default())).unwrap(), Message::Failure(_)));
+        assert!(device.usb_device().is_keepkey);
+    }
+
+    #[test]
+    fn test_confirmations_follow_the_fixture() {
+        let ping = || Message::Ping(messages::Ping { message: Some("hello".to_string()), button_protection: Some(true), ..Default::default() });
+        match device().answer(&ping()).unwrap() {
+            Message::Success(success) => assert_eq!(success.message.as_deref(), Some("hello")),
+            other => panic!("answered {:?}", other.message_type()),
+        }
+        let cancelling = MockDevice { cancel_confirmations: true, ..device() };
+        assert!(matches!(cancelling.answer(&ping()).unwrap(), Message::Failure(_)));
+
+        match device().answer(&Message::GetEntropy(messages::GetEntropy { size: 32 })).unwrap() {
+            Message::Entropy(entropy) => assert_eq!(entropy.entropy.len(), 32),
+            other => panic!("answered {:?}", other.message_type()),
+        }
+    }
+}
@@ -2,6 +2,7 @@ pub mod protocol_adapter;
 pub mod usb;
 pub mod webusb;
 pub mod hid;
+pub mod mock;
 
 pub use protocol_adapter::*;
 pub use usb::*;
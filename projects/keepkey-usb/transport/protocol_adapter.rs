@@ -1,6 +1,7 @@
 use super::{ProtocolAdapter, Transport};
 use crate::messages::Message;
 use anyhow::{anyhow, Result};
+use zeroize::Zeroize;
 
 use log::{info, debug};
 
@@ -15,7 +16,7 @@ where
         Ok(<T as Transport>::reset(self)?)
     }
 
-    fn send(&mut self, msg: Message) -> Result<()> {
+    fn send(&mut self, mut msg: Message) -> Result<()> {
         info!("ProtocolAdapter::send: Sending message type: {:?}", msg.message_type());
         
         println!("-> {:?}", msg.message_type());
@@ -24,7 +25,10 @@ where
         
         debug!("ProtocolAdapter::send: Encoded message size: {} bytes", out_buf.len());
         
-        self.write(&out_buf, msg.write_timeout())?;
+        let written = self.write(&out_buf, msg.write_timeout());
+        msg.wipe_secrets();
+        out_buf.zeroize();
+        written?;
 
         Ok(())
     }
@@ -0,0 +1,144 @@
+//! Firmware and bootloader version parsing shared by every version check
+//!
+//! Devices report versions in several shapes: "1.0.3", "v7.10.0",
+//! "7.10.0-rc2", "2.1.4+debug", and occasionally a raw bootloader hash. All of
+//! them go through [`parse_version`] so comparisons agree everywhere, and
+//! anything that is not a version comes back as [`ParsedVersion::Unparseable`]
+//! instead of being half-parsed.
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Result of parsing a device-reported version string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsedVersion {
+    Parsed(Version),
+    Unparseable(String),
+}
+
+/// How a current version relates to a target version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionComparison {
+    Less,
+    Equal,
+    Greater,
+    /// One side is not a version; needs investigation, never "up to date"
+    Unparseable,
+}
+
+impl VersionComparison {
+    /// Whether the current version must be looked at: older than the target, or unknown
+    pub fn needs_attention(self) -> bool {
+        matches!(self, VersionComparison::Less | VersionComparison::Unparseable)
+    }
+}
+
+/// Parse a version, accepting an optional `v` prefix and missing minor/patch
+/// components ("7" and "7.10" mean 7.0.0 and 7.10.0)
+pub fn parse_version(raw: &str) -> ParsedVersion {
+    let trimmed = raw.trim();
+    let unprefixed = trimmed
+        .strip_prefix('v')
+        .or_else(|| trimmed.strip_prefix('V'))
+        .unwrap_or(trimmed);
+
+    // Pad the numeric core before any pre-release/build suffix
+    let core_end = unprefixed.find(['-', '+']).unwrap_or(unprefixed.len());
+    let (core, suffix) = unprefixed.split_at(core_end);
+    let padded = match core.split('.').count() {
+        1 => format!("{}.0.0{}", core, suffix),
+        2 => format!("{}.0{}", core, suffix),
+        _ => unprefixed.to_string(),
+    };
+
+    match Version::parse(&padded) {
+        Ok(version) => ParsedVersion::Parsed(version),
+        Err(_) => ParsedVersion::Unparseable(raw.to_string()),
+    }
+}
+
+/// Semver precedence: build metadata is ignored and a pre-release sorts
+/// before its release (7.10.0-rc1 < 7.10.0)
+fn precedence(a: &Version, b: &Version) -> Ordering {
+    (a.major, a.minor, a.patch)
+        .cmp(&(b.major, b.minor, b.patch))
+        .then_with(|| a.pre.cmp(&b.pre))
+}
+
+/// Compare `current` against `target`
+pub fn compare_versions(current: &str, target: &str) -> VersionComparison {
+    match (parse_version(current), parse_version(target)) {
+        (ParsedVersion::Parsed(a), ParsedVersion::Parsed(b)) => match precedence(&a, &b) {
+            Ordering::Less => VersionComparison::Less,
+            Ordering::Equal => VersionComparison::Equal,
+            Ordering::Greater => VersionComparison::Greater,
+        },
+        _ => VersionComparison::Unparseable,
+    }
+}
+
+/// Parse into a (major, minor, patch) tuple for feature gating; None if unparseable
+pub fn version_triple(raw: &str) -> Option<(u32, u32, u32)> {
+    match parse_version(raw) {
+        ParsedVersion::Parsed(v) => Some((
+            u32::try_from(v.major).ok()?,
+            u32::try_from(v.minor).ok()?,
+            u32::try_from(v.patch).ok()?,
+        )),
+        ParsedVersion::Unparseable(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VersionComparison::*;
+
+    #[test]
+    fn test_field_versions() {
+        // (current, target, expected) - strings seen from real devices and releases
+        let cases = [
+            ("1.0.3", "2.1.4", Less),
+            ("2.1.4", "2.1.4", Equal),
+            ("2.1.5", "2.1.4", Greater),
+            ("v7.10.0", "7.10.0", Equal),
+            ("V7.10.0", "7.9.3", Greater),
+            ("7.10.0", "7.9.0", Greater),
+            ("7.10.0-rc2", "7.10.0", Less),
+            ("7.10.0-rc1", "7.10.0-rc2", Less),
+            ("7.10.0-rc2", "7.9.9", Greater),
+            ("v2.1.4+debug", "2.1.4", Equal),
+            ("7.10.0+build.5", "7.10.0+build.6", Equal),
+            ("7", "7.0.0", Equal),
+            ("1.0", "1.0.0", Equal),
+            ("7.10-rc1", "7.10.0", Less),
+            (" 6.4.0 ", "7.10.0", Less),
+            ("f13ce228c0bb2bdbc56bdcb5f4569367aca3011f", "2.1.4", Unparseable),
+            ("Unknown bootloader", "2.1.4", Unparseable),
+            ("", "2.1.4", Unparseable),
+            ("7.10.0", "latest", Unparseable),
+            ("7.x.0", "7.10.0", Unparseable),
+        ];
+
+        for (current, target, expected) in cases {
+            assert_eq!(compare_versions(current, target), expected, "{} vs {}", current, target);
+        }
+    }
+
+    #[test]
+    fn test_unparseable_needs_attention() {
+        assert!(compare_versions("deadbeef", "2.1.4").needs_attention());
+        assert!(compare_versions("2.1.3", "2.1.4").needs_attention());
+        assert!(!compare_versions("2.1.4", "2.1.4").needs_attention());
+        assert!(!compare_versions("7.10.0", "7.9.0").needs_attention());
+    }
+
+    #[test]
+    fn test_parse_keeps_original_when_unparseable() {
+        assert_eq!(parse_version("abc123"), ParsedVersion::Unparseable("abc123".to_string()));
+        assert_eq!(version_triple("v7.10.0-rc2"), Some((7, 10, 0)));
+        assert_eq!(version_triple("garbage"), None);
+    }
+}
@@ -0,0 +1,281 @@
+// bindings.rs - Typed command surface and the generated TypeScript bindings
+//
+// Every command the frontend can invoke is listed here once. The same builder
+// produces the invoke handler and src/bindings.ts, so a command cannot be
+// registered without also being exported. The bindings are regenerated
+// explicitly (`npm run bindings`), never as a side effect of running the app.
+
+use tauri_specta::{collect_commands, Builder};
+
+pub fn builder() -> Builder<tauri::Wry> {
+    Builder::<tauri::Wry>::new().commands(collect_commands![
+        crate::greet,
+        // Device commands
+        crate::commands::device::get_features::get_features,
+        crate::commands::device::get_connected_devices::get_connected_devices,
+        crate::commands::device::get_connected_devices::set_device_id_override,
+        crate::commands::device::get_device_status::get_device_status,
+        crate::commands::device::check_device_bootloader::check_device_bootloader,
+        crate::commands::device::get_devices_needing_setup::get_devices_needing_setup,
+        crate::commands::device::wipe_device::wipe_device,
+        crate::commands::device::wipe_device::request_wipe_confirmation,
+        crate::commands::device::forget_device::forget_device,
+        crate::commands::device::migrate_device::start_device_migration,
+        crate::commands::device::get_device_info_by_id::get_device_info_by_id,
+        crate::commands::device::device_note::get_device_note,
+        crate::commands::device::device_note::set_device_note,
+        crate::commands::device::device_export::export_device_data,
+        crate::commands::device::device_export::validate_device_export,
+        crate::commands::pin::change_device_pin,
+        crate::commands::pin::remove_device_pin,
+        crate::commands::pin::trigger_pin_request,
+        crate::commands::pin::send_pin_matrix_ack,
+        crate::commands::pin::cancel_pin_entry,
+        crate::commands::recovery::start_device_recovery,
+        crate::commands::recovery::send_recovery_character,
+        crate::commands::recovery::cancel_recovery,
+        // High-risk operation confirmations
+        crate::commands::confirmation::request_confirmation,
+        // Update commands
+        crate::device::updates::update_device_bootloader,
+        crate::device::updates::update_device_firmware,
+        crate::device::updates::get_firmware_update_history,
+        crate::device::updates::get_firmware_catalog,
+        crate::device::observer::get_device_management_mode,
+        // Event and config commands
+        crate::commands::events::frontend_ready,
+        crate::commands::events::frontend_reset,
+        crate::commands::events::subscribe_events,
+        crate::commands::events::get_event_subscriptions,
+        crate::commands::events::get_event_queue_stats,
+        crate::commands::events::get_event_history,
+        crate::commands::events::re_emit_event,
+        crate::commands::events::ack_event,
+        crate::commands::events::get_unacked_events,
+        crate::commands::config::is_first_time_install,
+        crate::commands::config::is_onboarded,
+        crate::commands::config::set_onboarding_completed,
+        crate::commands::config::debug_onboarding_state,
+        crate::commands::config::get_preference,
+        crate::commands::config::set_preference,
+        crate::commands::config::get_all_preferences,
+        // Storage management commands
+        crate::commands::storage::get_database_stats,
+        crate::commands::storage::get_storage_policy,
+        crate::commands::storage::set_storage_policy,
+        crate::commands::storage::compact_database,
+        crate::commands::storage::get_data_directory,
+        crate::commands::storage::relocate_data_directory,
+        crate::commands::storage::get_pending_migrations,
+        crate::commands::storage::apply_migrations_now,
+        // Backup commands
+        crate::commands::backups::list_backups,
+        crate::commands::backups::get_backup_policy,
+        crate::commands::backups::set_backup_policy,
+        crate::commands::backups::restore_from_backup,
+        crate::commands::backups::get_activity_log,
+        // Asset capability commands
+        crate::commands::assets::get_asset_capabilities,
+        crate::commands::assets::get_portfolio_capabilities,
+        crate::commands::portfolio::get_portfolio,
+        crate::commands::portfolio::set_portfolio_balances,
+        crate::commands::portfolio::get_dashboard,
+        crate::commands::portfolio::get_portfolio_history,
+        crate::commands::portfolio::prune_portfolio_history,
+        // Network management
+        crate::commands::networks::list_available_networks,
+        crate::commands::networks::get_networks,
+        crate::commands::networks::set_network_enabled,
+        crate::commands::networks::search_assets,
+        crate::commands::networks::get_asset,
+        crate::commands::networks::get_derivation_paths,
+        // Portfolio cache maintenance
+        crate::commands::cache::audit_portfolio_integrity,
+        crate::commands::cache::cleanup_orphaned_portfolio_rows,
+        crate::commands::cache::get_cache_status,
+        // Send flow commands
+        crate::commands::send::get_spendable_balance,
+        crate::commands::send::resolve_send_amount,
+        crate::commands::send::calculate_utxo_max_send,
+        crate::commands::fees::get_fee_suggestions,
+        crate::commands::fees::get_fee_rates,
+        crate::commands::bitcoin::preview_bitcoin_tx,
+        crate::commands::bitcoin::build_and_sign_bitcoin_tx,
+        crate::commands::bitcoin::compose_bitcoin_transaction,
+        crate::commands::bitcoin::sign_composed_transaction,
+        crate::commands::ripple::get_xrp_address,
+        crate::commands::ripple::sign_xrp_payment,
+        crate::commands::thorchain::get_thorchain_address,
+        crate::commands::thorchain::get_mayachain_address,
+        crate::commands::thorchain::sign_thorchain_tx,
+        crate::commands::thorchain::sign_mayachain_tx,
+        crate::commands::osmosis::sign_osmosis_tx,
+        crate::commands::eos::sign_eos_tx,
+        // Token approval audit and revocation
+        crate::commands::approvals::get_token_approvals,
+        crate::commands::approvals::preview_token_approval_revocation,
+        crate::commands::approvals::revoke_token_approval,
+        // User-added tokens
+        crate::commands::tokens::add_custom_token,
+        crate::commands::tokens::remove_custom_token,
+        crate::commands::tokens::list_custom_tokens,
+        crate::commands::tokens::build_erc20_transfer_data,
+        // Device-encrypted secure notes
+        crate::commands::secure_notes::add_secure_note,
+        crate::commands::secure_notes::read_secure_note,
+        crate::commands::secure_notes::list_secure_notes,
+        crate::commands::secure_notes::delete_secure_note,
+        // Confirmation and reorg tracking
+        crate::commands::transactions::recheck_transactions,
+        crate::commands::transactions::get_recent_activity,
+        crate::commands::broadcast::broadcast_transaction,
+        // Cost-basis lots and transaction export
+        crate::commands::tax::get_tax_lot_settings,
+        crate::commands::tax::set_tax_lot_tracking,
+        crate::commands::tax::set_tax_lot_method,
+        crate::commands::tax::rebuild_tax_lots,
+        crate::commands::tax::get_realized_gains,
+        crate::commands::tax::export_transactions_csv,
+        // API pairing and clients
+        crate::commands::api::generate_pairing_code,
+        crate::commands::api::cancel_pairing_code,
+        crate::commands::api::list_api_clients,
+        crate::commands::api::revoke_api_client,
+        crate::commands::api::set_api_client_scope,
+        // Signing origin commands
+        crate::commands::signing::confirm_signing_limit,
+        crate::commands::signing::get_spending_by_origin,
+        crate::commands::signing::get_origin_spending_limits,
+        crate::commands::signing::set_origin_spending_limit,
+        crate::commands::signing::set_external_spending_limit,
+        // Signed message export and verification
+        crate::commands::messages::export_signed_message,
+        crate::commands::messages::verify_signed_message_file,
+        // Address verification on the device screen
+        crate::commands::verification::verify_address,
+        // Addresses by CAIP network id
+        crate::commands::addresses::get_address_for_caip,
+        // Wallet session commands
+        crate::commands::wallets::list_known_wallets,
+        crate::commands::wallets::set_wallet_nickname,
+        crate::commands::wallets::get_active_wallet,
+        crate::commands::wallets::switch_wallet_session,
+        crate::commands::wallets::import_external_wallet_export,
+        crate::commands::wallets::list_imported_wallets,
+        crate::commands::wallets::remove_imported_wallet,
+        // UTXO address discovery windows
+        crate::commands::address_scan::get_account_scan_state,
+        crate::commands::address_scan::record_address_activity,
+        crate::commands::address_scan::extend_scan,
+        // Tray commands
+        crate::commands::tray::get_tray_summary,
+        crate::commands::tray::lock_device,
+        crate::commands::tray::sync_now,
+        crate::commands::tray::set_show_tray,
+        // Command palette
+        crate::commands::quick_actions::list_quick_actions,
+        crate::commands::quick_actions::execute_quick_action,
+        // Accessibility commands
+        crate::commands::accessibility::get_accessibility_summary,
+        // App lock commands
+        crate::commands::app_lock::report_activity,
+        crate::commands::app_lock::get_app_lock_state,
+        crate::commands::app_lock::lock_app,
+        crate::commands::app_lock::unlock_app,
+        crate::commands::app_lock::set_app_lock_timeout,
+        crate::commands::app_lock::set_app_pin,
+        // Maintenance mode
+        crate::commands::maintenance::get_maintenance_state,
+        crate::commands::maintenance::enter_maintenance,
+        crate::commands::maintenance::exit_maintenance,
+        // Power profile commands
+        crate::commands::power::get_power_profile,
+        crate::commands::power::set_power_saver,
+        // Clock sanity
+        crate::commands::clock::check_time_sanity,
+        crate::commands::environment::run_environment_probe,
+        crate::commands::environment::get_environment_report,
+        // Diagnostics commands
+        crate::commands::diagnostics::get_device_fault_log,
+        crate::commands::diagnostics::run_device_self_test,
+        crate::commands::diagnostics::get_system_diagnostics,
+        crate::commands::diagnostics::get_protocol_info,
+        crate::commands::diagnostics::verify_catalog_integrity,
+        crate::commands::diagnostics::reset_endpoint_circuit,
+        crate::commands::diagnostics::get_startup_report,
+        crate::commands::logging::get_recent_device_logs,
+        crate::commands::logging::clear_device_logs,
+        // Device registry commands
+        crate::commands::device::register_device,
+        crate::commands::device::get_device_registry,
+        crate::commands::device::get_device_from_registry,
+        crate::commands::device::update_device_setup_step,
+        crate::commands::device::mark_device_setup_complete,
+        crate::commands::device::device_needs_setup,
+        crate::commands::device::get_incomplete_setup_devices,
+        crate::commands::device::reset_device_setup,
+        crate::commands::device::get_device_eth_address,
+        crate::commands::device::get_xpub,
+        crate::commands::device::set_device_label,
+        crate::commands::device::set_passphrase_protection,
+        crate::commands::device::send_passphrase,
+        crate::commands::device::cancel_passphrase,
+        crate::commands::device::set_auto_lock_delay,
+        crate::commands::device::lock_device_now,
+        crate::commands::device::get_queue_status,
+        crate::commands::device::all_queue_statuses,
+        crate::commands::device::get_connection_history,
+        crate::commands::device::frontload_device,
+        crate::commands::device::get_frontload_progress,
+        crate::commands::device::get_blocking_actions,
+        crate::commands::device::get_all_blocking_actions,
+        crate::commands::device::sign_btc_message,
+        crate::commands::device::verify_btc_message,
+    ])
+    // Payloads of events emitted from typed values; commands do not reference them
+    .typ::<vault_core::tx_tracker::TxStatusChange>()
+    .typ::<vault_core::power::PowerStatus>()
+    .typ::<vault_core::clock::ClockMeasurement>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specta_typescript::{BigIntExportBehavior, Typescript};
+
+    /// Generated bindings, committed so type drift shows up in review
+    const BINDINGS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../src/bindings.ts");
+
+    const BINDINGS_HEADER: &str = "// Generated from the Rust command signatures by tauri-specta. Do not edit;\n\
+// run `npm run bindings` to regenerate.\n";
+
+    fn typescript() -> Typescript {
+        // Amounts, sizes and timestamps are u64/i64 on the Rust side but stay well
+        // below 2^53, and the frontend already treats them as numbers
+        Typescript::default()
+            .bigint(BigIntExportBehavior::Number)
+            .header(BINDINGS_HEADER)
+    }
+
+    /// Regenerates the bindings and fails if the committed file differs.
+    /// Set UPDATE_BINDINGS=1 (`npm run bindings`) to overwrite it instead.
+    #[test]
+    fn bindings_are_up_to_date() {
+        let generated_path = std::env::temp_dir().join(format!("keepkey-vault-bindings-{}.ts", std::process::id()));
+        let generated_path = generated_path.to_str().unwrap();
+        builder().export(typescript(), generated_path).expect("Failed to export TypeScript bindings");
+        let generated = std::fs::read_to_string(generated_path).unwrap();
+        let _ = std::fs::remove_file(generated_path);
+
+        if std::env::var_os("UPDATE_BINDINGS").is_some() {
+            std::fs::write(BINDINGS_PATH, &generated).unwrap();
+            return;
+        }
+        let committed = std::fs::read_to_string(BINDINGS_PATH)
+            .unwrap_or_else(|e| panic!("Cannot read {}: {}; run `npm run bindings`", BINDINGS_PATH, e));
+        assert!(
+            committed == generated,
+            "src/bindings.ts is out of date; run `npm run bindings` and commit the result"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+// commands/accessibility.rs - Plain-state summary for screen readers
+//
+// Rebuilt from the registry after the same events that drive the tray, and
+// announced with `accessibility:summary-changed` at most once per
+// SUMMARY_MIN_INTERVAL, only when a sentence changed.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+use keepkey_db::Database;
+use tauri::{AppHandle, State};
+use vault_core::accessibility::{self, OperationPhase, SummarySentence, SummaryThrottle, SUMMARY_MIN_INTERVAL};
+
+/// Events after which the summary is rebuilt
+const REFRESH_EVENTS: [&str; 7] = [
+    "device:connected",
+    "device:disconnected",
+    "device:status-changed",
+    "device:setup-required",
+    "wallet:session-changed",
+    "device:migration-progress",
+    "device:migration-complete",
+];
+
+/// Long-running operations per device, fed from progress events
+fn operations() -> &'static Mutex<HashMap<String, OperationPhase>> {
+    static OPERATIONS: OnceLock<Mutex<HashMap<String, OperationPhase>>> = OnceLock::new();
+    OPERATIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn rebuild_wakeup() -> &'static tokio::sync::Notify {
+    static WAKEUP: OnceLock<tokio::sync::Notify> = OnceLock::new();
+    WAKEUP.get_or_init(tokio::sync::Notify::new)
+}
+
+/// Track operation phases carried by progress events
+fn note_operation_event(event: &str, payload: &str) {
+    let Ok(payload) = serde_json::from_str::<serde_json::Value>(payload) else { return };
+    let Some(device_id) = payload.get("new_device_id").and_then(|v| v.as_str()) else { return };
+    let mut operations = operations().lock().unwrap();
+    match event {
+        "device:migration-progress" => {
+            let step = payload.get("step").and_then(|v| v.as_u64()).map(|v| v as usize);
+            let total = payload.get("total").and_then(|v| v.as_u64()).map(|v| v as usize);
+            operations.insert(device_id.to_string(), OperationPhase { operation: "migrating_data".to_string(), step, total });
+        }
+        "device:migration-complete" => {
+            operations.remove(device_id);
+        }
+        _ => {}
+    }
+}
+
+/// Current summary sentences, blocking actions first
+pub async fn build_accessibility_summary(database: &Database) -> Result<Vec<SummarySentence>, String> {
+    let registry = database.get_device_registry().await.map_err(|e| format!("Database error: {}", e))?;
+    let connected = super::tray::connected_device_ids();
+    let registry_observer = vault_core::observer::observer_registry();
+    let observed: HashSet<String> = connected.iter().filter(|id| registry_observer.is_observed(id)).cloned().collect();
+    let operations = operations().lock().unwrap().clone();
+    let devices = accessibility::summarize_devices(&registry, &connected, &observed, &operations);
+    Ok(accessibility::build_summary(&devices))
+}
+
+/// Listen for state changes and emit coalesced `accessibility:summary-changed` events
+pub fn init_accessibility_events(app: &AppHandle, database: Arc<Database>) {
+    use tauri::Listener;
+
+    for event in REFRESH_EVENTS {
+        app.listen_any(event, move |e| {
+            note_operation_event(event, e.payload());
+            rebuild_wakeup().notify_one();
+        });
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut throttle = SummaryThrottle::new(SUMMARY_MIN_INTERVAL);
+        loop {
+            rebuild_wakeup().notified().await;
+            // Events arriving while waiting out the interval collapse into this rebuild
+            let wait_until = throttle.next_allowed(std::time::Instant::now());
+            tokio::time::sleep_until(wait_until.into()).await;
+
+            let summary = match build_accessibility_summary(&database).await {
+                Ok(summary) => summary,
+                Err(e) => {
+                    log::warn!("Failed to build accessibility summary: {}", e);
+                    continue;
+                }
+            };
+            if let Some(summary) = throttle.offer(summary, std::time::Instant::now()) {
+                let payload = serde_json::json!({ "sentences": summary });
+                if let Err(e) = super::emit_or_queue_event(&app, "accessibility:summary-changed", payload).await {
+                    log::warn!("Failed to emit accessibility:summary-changed: {}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Plain sentences describing every connected device, blocking actions first.
+/// Each carries a message code and parameters for localization.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_accessibility_summary(database: State<'_, Arc<Database>>) -> Result<Vec<SummarySentence>, String> {
+    build_accessibility_summary(&database).await
+}
@@ -0,0 +1,84 @@
+// commands/address_scan.rs - Address gap-limit windows of UTXO accounts
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::{AccountScanState, Database};
+use vault_core::address_scan::{self, ScanConfig, ScanOutcome, CEILING_REACHED_EVENT};
+
+/// Scanned address range and high-water mark of each UTXO account on a device
+#[tauri::command]
+#[specta::specta]
+pub async fn get_account_scan_state(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<AccountScanState>, String> {
+    database.get_account_scan_states(&device_id).await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Report the active address indexes the UTXO sync found in an account's
+/// current window.
+///
+/// Activity in the last few slots grows the window by another gap-limit
+/// chunk (`extensions` is 1 and the sync should scan the new range and report
+/// again). When the window is already at the ceiling,
+/// `accounts:scan-ceiling-reached` tells the user funds may lie beyond it.
+#[tauri::command]
+#[specta::specta]
+pub async fn record_address_activity(
+    app: AppHandle,
+    account_id: i64,
+    used_indexes: Vec<u32>,
+    database: State<'_, Arc<Database>>,
+) -> Result<ScanOutcome, String> {
+    let config = ScanConfig::load(&database).await;
+    let outcome = address_scan::record_activity(&database, account_id, &used_indexes, &config).await?;
+    if outcome.ceiling_newly_reached {
+        notify_ceiling_reached(&app, &database, &outcome.state).await;
+    }
+    Ok(outcome)
+}
+
+/// Scan more addresses of an account than the automatic extension reached,
+/// up to the ceiling. The next sync covers the larger window.
+#[tauri::command]
+#[specta::specta]
+pub async fn extend_scan(
+    account_id: i64,
+    additional: u32,
+    database: State<'_, Arc<Database>>,
+) -> Result<AccountScanState, String> {
+    let config = ScanConfig::load(&database).await;
+    let state = address_scan::extend_scan(&database, account_id, additional, &config).await?;
+    log::info!("🔭 Account {} ({}) now scans {} addresses", account_id, state.path, state.window_size);
+    Ok(state)
+}
+
+async fn notify_ceiling_reached(app: &AppHandle, database: &Database, state: &AccountScanState) {
+    log::warn!(
+        "🔭 Account {} ({}) still has activity at its {}-address scan ceiling",
+        state.account_id, state.path, state.window_size
+    );
+    let message = format!(
+        "Account {} has activity near address {}, the most the vault scans. Funds may exist beyond it.",
+        state.path, state.window_size
+    );
+    let details = serde_json::json!({ "account_id": state.account_id, "device_id": state.device_id, "window_size": state.window_size });
+    if let Err(e) = database.log_activity("accounts", &message, Some(&details)).await {
+        log::warn!("Failed to log scan ceiling: {}", e);
+    }
+    if let Err(e) = super::emit_or_queue_event(
+        app,
+        CEILING_REACHED_EVENT,
+        serde_json::json!({
+            "account_id": state.account_id,
+            "device_id": state.device_id,
+            "path": state.path,
+            "caip": state.caip,
+            "window_size": state.window_size,
+            "high_water": state.high_water,
+            "message": message,
+        }),
+    ).await {
+        log::error!("Failed to emit scan ceiling event: {}", e);
+    }
+}
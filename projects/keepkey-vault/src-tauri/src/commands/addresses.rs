@@ -0,0 +1,50 @@
+// commands/addresses.rs - Addresses for any network in the chain registry
+//
+// The CAIP id picks the chain module (and, without a path, the first address
+// of account 0), so the UI needs no per-chain address command for Bitcoin,
+// EVM and Cosmos networks. Served addresses are cached per wallet, so
+// `verify_address` can hold the device's answer against them.
+
+use std::sync::Arc;
+use tauri::State;
+use keepkey_db::Database;
+use keepkey_rust::chains::ChainRegistry;
+use vault_core::paths::parse_derivation_path;
+use vault_core::request_queue::request_queue;
+use vault_core::wallet_session::WalletSessions;
+use super::device::get_or_create_device_queue;
+use super::DeviceQueueManager;
+
+/// Get the address at `path` on the network of `caip` (a CAIP-2 network or
+/// CAIP-19 asset id); the network's default path when omitted
+#[tauri::command]
+#[specta::specta]
+pub async fn get_address_for_caip(
+    device_id: String,
+    caip: String,
+    path: Option<String>,
+    show_display: bool,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<String, String> {
+    let registry = ChainRegistry::default();
+    let Some(chain) = registry.resolve(&caip) else {
+        return Err(format!("Addresses are not supported for {}", caip));
+    };
+    let address_n = match path.as_deref() {
+        Some(path) => parse_derivation_path(path)?,
+        None => chain.default_path.clone(),
+    };
+    let address = request_queue().run(&device_id, "get_address_for_caip", || async {
+        let queue = get_or_create_device_queue(&device_id, &queue_manager).await?;
+        registry
+            .get_address(&queue, &caip, Some(&address_n), show_display)
+            .await
+            .map_err(|e| format!("Failed to get address for {}: {}", caip, e))
+    }).await?;
+    super::verification::remember_served_address(
+        &database, &wallet_sessions, &device_id, &chain.kind, &address_n, &address,
+    ).await;
+    Ok(address)
+}
@@ -1,2 +1,97 @@
-// commands/api.rs - API control commands
-pub fn _placeholder() {} 
\ No newline at end of file
+// commands/api.rs - Pairing codes and client management for the local API
+//
+// The code state machine and the scope checks live in vault_core::api_pairing.
+// These commands are the in-app side: showing a code, listing and revoking
+// clients, and granting signing after a typed-phrase confirmation.
+
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::State;
+use keepkey_db::{ApiClient, ApiScope, Database};
+use vault_core::api_pairing::{ApiPairing, PairingCode};
+use vault_core::confirmation::{Confirmations, HighRiskOperation};
+use super::confirmation::require_confirmation;
+
+pub fn new_api_pairing() -> Arc<ApiPairing> {
+    Arc::new(ApiPairing::new())
+}
+
+async fn log_api_activity(database: &Database, message: &str, details: serde_json::Value) {
+    if let Err(e) = database.log_activity("api", message, Some(&details)).await {
+        log::warn!("Failed to record API activity: {}", e);
+    }
+}
+
+/// Show a new pairing code; any previous code stops working
+#[tauri::command]
+#[specta::specta]
+pub async fn generate_pairing_code(
+    database: State<'_, Arc<Database>>,
+    pairing: State<'_, Arc<ApiPairing>>,
+) -> Result<PairingCode, String> {
+    let code = pairing.generate_code(Instant::now())?;
+    log::info!("🔐 Generated API pairing code (valid {}s)", code.expires_in_secs);
+    log_api_activity(&database, "Generated API pairing code", serde_json::json!({ "expires_in_secs": code.expires_in_secs })).await;
+    Ok(code)
+}
+
+/// Invalidate the displayed pairing code, e.g. when the dialog is closed
+#[tauri::command]
+#[specta::specta]
+pub async fn cancel_pairing_code(pairing: State<'_, Arc<ApiPairing>>) -> Result<(), String> {
+    pairing.cancel();
+    Ok(())
+}
+
+/// Paired clients, including revoked and expired ones
+#[tauri::command]
+#[specta::specta]
+pub async fn list_api_clients(database: State<'_, Arc<Database>>) -> Result<Vec<ApiClient>, String> {
+    database.list_api_clients().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Revoke a client; its token gets 401 `RevokedToken` from then on
+#[tauri::command]
+#[specta::specta]
+pub async fn revoke_api_client(id: i64, database: State<'_, Arc<Database>>) -> Result<(), String> {
+    if !database.revoke_api_client(id).await.map_err(|e| format!("Database error: {}", e))? {
+        return Err(format!("No active API client {}", id));
+    }
+    log::info!("🔐 Revoked API client {}", id);
+    log_api_activity(&database, "Revoked API client", serde_json::json!({ "client_id": id })).await;
+    Ok(())
+}
+
+/// Change what a client may do. Granting `sign` requires a confirmation from
+/// `request_confirmation("elevate_api_scope")`; dropping back to `read` does not.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_api_client_scope(
+    id: i64,
+    scope: ApiScope,
+    confirmation_token: Option<String>,
+    confirmation_phrase: Option<String>,
+    database: State<'_, Arc<Database>>,
+    confirmations: State<'_, Confirmations>,
+) -> Result<ApiClient, String> {
+    if scope == ApiScope::Sign {
+        require_confirmation(
+            &database,
+            &confirmations,
+            HighRiskOperation::ElevateApiScope,
+            None,
+            confirmation_token.as_deref().unwrap_or_default(),
+            confirmation_phrase.as_deref().unwrap_or_default(),
+        ).await?;
+    }
+
+    if !database.set_api_client_scope(id, scope).await.map_err(|e| format!("Database error: {}", e))? {
+        return Err(format!("No active API client {}", id));
+    }
+    log::info!("🔐 API client {} scope set to {}", id, scope.as_str());
+    log_api_activity(&database, "Changed API client scope", serde_json::json!({ "client_id": id, "scope": scope })).await;
+
+    database.get_api_client(id).await
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("No active API client {}", id))
+}
@@ -0,0 +1,184 @@
+// commands/app_lock.rs - Inactivity lock for the app window
+//
+// The lock state itself lives in vault_core::app_lock; the invoke handler in
+// lib.rs consults it before every command. This module loads the policy from
+// the database, hashes the app PIN and runs the idle ticker.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use tauri::{AppHandle, Manager, State};
+use keepkey_db::Database;
+use vault_core::app_lock::{AppLock, AppLockStatus, APP_LOCK_MINUTES_PREF, IDLE_CHECK_INTERVAL};
+use vault_core::signing_origin::LimitConfirmations;
+use vault_core::wallet_session::{self, WalletSessions};
+
+const MIN_PIN_LENGTH: usize = 4;
+
+pub fn new_app_lock() -> Arc<AppLock> {
+    Arc::new(AppLock::new(Instant::now()))
+}
+
+fn hash_pin(pin: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(pin.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Failed to hash PIN: {}", e))
+}
+
+fn pin_matches(pin: &str, stored_hash: &str) -> bool {
+    match PasswordHash::new(stored_hash) {
+        Ok(hash) => Argon2::default().verify_password(pin.as_bytes(), &hash).is_ok(),
+        Err(e) => {
+            log::error!("❌ Stored app PIN hash is unreadable: {}", e);
+            false
+        }
+    }
+}
+
+/// Re-read the timeout preference and whether a PIN is configured
+async fn apply_policy(database: &Database, lock: &AppLock) -> Result<(), String> {
+    let minutes = database.get_preference(APP_LOCK_MINUTES_PREF).await
+        .map_err(|e| format!("Database error: {}", e))?
+        .and_then(|value| value.trim().parse::<u64>().ok());
+    let has_pin = database.get_app_pin_hash().await
+        .map_err(|e| format!("Database error: {}", e))?
+        .is_some();
+    lock.set_policy(minutes.map(|m| Duration::from_secs(m * 60)), has_pin);
+    Ok(())
+}
+
+/// Drop in-memory secrets and tell every window the app is locked
+async fn on_locked(app: &AppHandle, reason: &str) {
+    log::info!("🔒 App locked ({})", reason);
+
+    // Passphrase wallets need the passphrase again after unlocking
+    let sessions = app.state::<WalletSessions>().inner().clone();
+    let hidden = wallet_session::end_hidden_sessions(&sessions).await;
+    if !hidden.is_empty() {
+        log::info!("🔒 Closed passphrase sessions on {} device(s)", hidden.len());
+    }
+
+    // Over-limit signing requests waiting on the UI are declined
+    let confirmations = app.state::<LimitConfirmations>().inner().clone();
+    for (_, pending) in confirmations.lock().await.drain() {
+        let _ = pending.send(false);
+    }
+
+    let payload = serde_json::json!({ "reason": reason, "closed_sessions": hidden });
+    if let Err(e) = super::emit_or_queue_event(app, "app:locked", payload).await {
+        log::warn!("Failed to emit app:locked: {}", e);
+    }
+}
+
+/// Lock the app once it has been idle past the configured timeout
+pub async fn run_app_lock_monitor(app: AppHandle, database: Arc<Database>) {
+    let lock = app.state::<Arc<AppLock>>().inner().clone();
+    if let Err(e) = apply_policy(&database, &lock).await {
+        log::warn!("⚠️ Failed to load app lock policy: {}", e);
+    }
+    let mut interval = tokio::time::interval(IDLE_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if lock.check_idle(Instant::now()) {
+            on_locked(&app, "inactivity").await;
+        }
+    }
+}
+
+/// User input in the window; the frontend calls this on input events
+#[tauri::command]
+#[specta::specta]
+pub async fn report_activity(lock: State<'_, Arc<AppLock>>) -> Result<(), String> {
+    lock.record_activity(Instant::now());
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_app_lock_state(lock: State<'_, Arc<AppLock>>) -> Result<AppLockStatus, String> {
+    Ok(lock.status())
+}
+
+/// Lock now; fails without an app PIN or while a firmware update is running
+#[tauri::command]
+#[specta::specta]
+pub async fn lock_app(app: AppHandle, lock: State<'_, Arc<AppLock>>) -> Result<AppLockStatus, String> {
+    if lock.lock().map_err(|e| e.to_json_string())? {
+        on_locked(&app, "manual").await;
+    }
+    Ok(lock.status())
+}
+
+/// Unlock with the app PIN
+#[tauri::command]
+#[specta::specta]
+pub async fn unlock_app(
+    app: AppHandle,
+    credential: String,
+    database: State<'_, Arc<Database>>,
+    lock: State<'_, Arc<AppLock>>,
+) -> Result<AppLockStatus, String> {
+    let stored_hash = database.get_app_pin_hash().await
+        .map_err(|e| format!("Database error: {}", e))?;
+    let verify = || stored_hash.as_deref().is_some_and(|hash| pin_matches(&credential, hash));
+    lock.unlock(verify, Instant::now()).map_err(|e| {
+        log::warn!("🔒 Unlock refused: {}", e);
+        e.to_json_string()
+    })?;
+
+    log::info!("🔓 App unlocked");
+    if let Err(e) = super::emit_or_queue_event(&app, "app:unlocked", serde_json::json!({})).await {
+        log::warn!("Failed to emit app:unlocked: {}", e);
+    }
+    Ok(lock.status())
+}
+
+/// Set the inactivity timeout in minutes; None or 0 turns the lock off
+#[tauri::command]
+#[specta::specta]
+pub async fn set_app_lock_timeout(
+    minutes: Option<u32>,
+    database: State<'_, Arc<Database>>,
+    lock: State<'_, Arc<AppLock>>,
+) -> Result<AppLockStatus, String> {
+    database.set_preference(APP_LOCK_MINUTES_PREF, &minutes.unwrap_or(0).to_string()).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    apply_policy(&database, &lock).await?;
+    Ok(lock.status())
+}
+
+/// Set, change or (with `new_pin` None) remove the app PIN. Changing or
+/// removing an existing PIN requires it as `current_pin`.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_app_pin(
+    current_pin: Option<String>,
+    new_pin: Option<String>,
+    database: State<'_, Arc<Database>>,
+    lock: State<'_, Arc<AppLock>>,
+) -> Result<AppLockStatus, String> {
+    if let Some(stored_hash) = database.get_app_pin_hash().await.map_err(|e| format!("Database error: {}", e))? {
+        let current = current_pin.ok_or_else(|| "The current app PIN is required".to_string())?;
+        if !pin_matches(&current, &stored_hash) {
+            return Err("The current app PIN is incorrect".to_string());
+        }
+    }
+
+    let new_hash = match new_pin {
+        Some(pin) if pin.chars().count() < MIN_PIN_LENGTH => {
+            return Err(format!("The app PIN must be at least {} characters", MIN_PIN_LENGTH));
+        }
+        Some(pin) => Some(hash_pin(&pin)?),
+        None => None,
+    };
+    database.set_app_pin_hash(new_hash.as_deref()).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    log::info!("🔒 App PIN {}", if new_hash.is_some() { "set" } else { "removed" });
+
+    apply_policy(&database, &lock).await?;
+    Ok(lock.status())
+}
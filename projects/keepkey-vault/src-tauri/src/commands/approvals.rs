@@ -0,0 +1,414 @@
+// commands/approvals.rs - ERC-20 allowance audit and revocation for EVM accounts
+
+use std::sync::Arc;
+use std::time::Duration;
+use ethereum_types::{Address, U256};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, NetworkEndpoints, SigningAuditInput, TokenApproval};
+use keepkey_db::types::TransactionCache;
+use keepkey_rust::chains::ethereum::{sign_ethereum_transaction, EthereumTransaction};
+use keepkey_rust::features::capabilities::{Capability, FirmwareCapabilities};
+use vault_core::endpoints::{endpoint_manager, EndpointError, Priority};
+use vault_core::maintenance::MaintenanceController;
+use vault_core::paths::{network_family, parse_derivation_path, NetworkFamily};
+use vault_core::signing_origin::{self, LimitConfirmations, SigningOrigin};
+use vault_core::token_approvals::{self, ApprovalScan, EvmFees, LogSource, PlannedEvmTx, RevocationPlan};
+use vault_core::units::format_units;
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use super::device::get_or_create_device_queue;
+use super::DeviceQueueManager;
+
+/// Cached allowances younger than this are returned without rescanning
+const APPROVAL_CACHE_TTL_SECS: i64 = 600;
+const RPC_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// An allowance with its amount in token units
+#[derive(Debug, Serialize, specta::Type)]
+pub struct TokenApprovalEntry {
+    #[serde(flatten)]
+    pub approval: TokenApproval,
+    /// Token units, "unlimited", or base units when decimals are unknown
+    pub allowance_display: String,
+}
+
+/// Allowances granted by a device's accounts on one network, riskiest first
+#[derive(Debug, Serialize, specta::Type)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TokenApprovalsStatus {
+    Available {
+        network_id: String,
+        approvals: Vec<TokenApprovalEntry>,
+        scanned_at: i64,
+        /// None when served from the cache
+        source: Option<LogSource>,
+    },
+    NotSupported { reason: String },
+}
+
+/// A signed revocation ready to broadcast
+#[derive(Debug, Serialize, specta::Type)]
+pub struct SignedEvmTx {
+    pub txid: String,
+    /// Raw signed transaction as 0x hex
+    pub serialized: String,
+    pub from: String,
+    pub nonce: u64,
+}
+
+/// An EVM network's chain id and endpoints
+pub(crate) struct EvmNetwork {
+    pub(crate) network_id: String,
+    pub(crate) chain_id: u64,
+    pub(crate) endpoints: NetworkEndpoints,
+}
+
+impl EvmNetwork {
+    pub(crate) async fn load(database: &Database, network_id: &str) -> Result<Self, String> {
+        let NetworkFamily::Evm { chain_id } = network_family(network_id)? else {
+            return Err(format!("{} is not an EVM network", network_id));
+        };
+        let endpoints = database.get_network_endpoints(network_id).await
+            .map_err(|e| format!("Database error: {}", e))?
+            .ok_or_else(|| format!("Unknown network {}", network_id))?;
+        Ok(Self { network_id: network_id.to_string(), chain_id, endpoints })
+    }
+
+    pub(crate) fn rpc_url(&self) -> Result<&str, String> {
+        self.endpoints.rpc_urls.first()
+            .map(String::as_str)
+            .ok_or_else(|| format!("No RPC endpoint configured for {}", self.network_id))
+    }
+}
+
+pub(crate) fn http_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(RPC_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// One JSON-RPC request; node errors come back as "RPC error <code>: <message>"
+pub(crate) async fn rpc_call(
+    client: reqwest::Client,
+    url: String,
+    method: &'static str,
+    params: Value,
+    priority: Priority,
+) -> Result<Value, String> {
+    let request = client
+        .post(&url)
+        .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }));
+    let response: Value = endpoint_manager()
+        .send(request, priority)
+        .await
+        .and_then(|r| r.error_for_status().map_err(EndpointError::Request))
+        .map_err(|e| format!("{} request to {} failed: {}", method, url, e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid {} response from {}: {}", method, url, e))?;
+    if let Some(error) = response.get("error") {
+        return Err(format!(
+            "RPC error {}: {}",
+            error.get("code").and_then(Value::as_i64).unwrap_or(0),
+            error.get("message").and_then(Value::as_str).unwrap_or("unknown error")
+        ));
+    }
+    Ok(response.get("result").cloned().unwrap_or(Value::Null))
+}
+
+/// Approval events for one owner from an Etherscan-style API
+async fn indexer_logs(client: reqwest::Client, api_url: String, owner: String) -> Result<Vec<Value>, String> {
+    let owner_topic = token_approvals::address_topic(&owner)?;
+    let request = client
+        .get(&api_url)
+        .query(&[
+            ("module", "logs"),
+            ("action", "getLogs"),
+            ("fromBlock", "0"),
+            ("toBlock", "latest"),
+            ("topic0", token_approvals::APPROVAL_TOPIC),
+            ("topic0_1_opr", "and"),
+            ("topic1", owner_topic.as_str()),
+        ]);
+    let response: Value = endpoint_manager()
+        .send(request, Priority::Interactive)
+        .await
+        .and_then(|r| r.error_for_status().map_err(EndpointError::Request))
+        .map_err(|e| format!("Indexer request to {} failed: {}", api_url, e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid indexer response from {}: {}", api_url, e))?;
+    token_approvals::indexer_logs_from_response(&response)
+}
+
+/// EVM accounts of the device's active wallet with their derivation paths
+async fn evm_accounts(database: &Database, device_id: &str, wallet_fingerprint: &str) -> Result<Vec<(String, String)>, String> {
+    let xpubs = database.get_wallet_xpubs(device_id, wallet_fingerprint).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    let mut accounts: Vec<(String, String)> = Vec::new();
+    // EVM "pubkeys" are account addresses, shared by every EVM network
+    for xpub in xpubs.iter().filter(|x| x.caip.starts_with("eip155:")) {
+        let Ok(address) = token_approvals::normalize_address(&xpub.pubkey) else { continue };
+        if !accounts.iter().any(|(a, _)| *a == address) {
+            accounts.push((address, xpub.path.clone()));
+        }
+    }
+    Ok(accounts)
+}
+
+fn entries(approvals: Vec<TokenApproval>) -> Vec<TokenApprovalEntry> {
+    approvals
+        .into_iter()
+        .map(|approval| TokenApprovalEntry { allowance_display: token_approvals::format_allowance(&approval), approval })
+        .collect()
+}
+
+/// Current ERC-20 allowances granted by the device's accounts on an EVM network.
+///
+/// Results are cached; pass `refresh` to rescan. Unlimited approvals come first.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_token_approvals(
+    device_id: String,
+    network_id: String,
+    refresh: Option<bool>,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<TokenApprovalsStatus, String> {
+    let network = EvmNetwork::load(&database, &network_id).await?;
+    let wallet = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let owners: Vec<String> = evm_accounts(&database, &device_id, &wallet).await?
+        .into_iter()
+        .map(|(address, _)| address)
+        .collect();
+    if owners.is_empty() {
+        return Err(format!("No EVM accounts known for device {}; sync the portfolio first", device_id));
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    if !refresh.unwrap_or(false) {
+        let scanned_at = database.token_approvals_scanned_at(&network_id, &owners).await.unwrap_or(None);
+        if let Some(scanned_at) = scanned_at.filter(|at| now - at < APPROVAL_CACHE_TTL_SECS) {
+            let mut approvals = database.get_token_approvals(&network_id, &owners).await
+                .map_err(|e| format!("Database error: {}", e))?;
+            token_approvals::sort_by_risk(&mut approvals);
+            return Ok(TokenApprovalsStatus::Available { network_id, approvals: entries(approvals), scanned_at, source: None });
+        }
+    }
+
+    let client = http_client()?;
+    let url = network.rpc_url()?.to_string();
+    let api_url = network.endpoints.explorer_api_url.clone().filter(|u| !u.trim().is_empty());
+    log::info!("🔎 Scanning {} token approvals for {} accounts of {}", network_id, owners.len(), device_id);
+    let scan = token_approvals::scan_approvals(
+        |method, params| rpc_call(client.clone(), url.clone(), method, params, Priority::Interactive),
+        |owner| {
+            let client = client.clone();
+            let api_url = api_url.clone();
+            async move {
+                match api_url {
+                    Some(api_url) => indexer_logs(client, api_url, owner).await.map(Some),
+                    None => Ok(None),
+                }
+            }
+        },
+        &network_id,
+        &owners,
+        now,
+    ).await?;
+
+    match scan {
+        ApprovalScan::Found { source, approvals } => {
+            database.replace_token_approvals(&network_id, &owners, &approvals, now).await
+                .map_err(|e| format!("Database error: {}", e))?;
+            let unlimited = approvals.iter().filter(|a| a.is_unlimited).count();
+            log::info!("🔎 Found {} token approvals on {} ({} unlimited)", approvals.len(), network_id, unlimited);
+            Ok(TokenApprovalsStatus::Available { network_id, approvals: entries(approvals), scanned_at: now, source: Some(source) })
+        }
+        ApprovalScan::NotSupported { reason } => Ok(TokenApprovalsStatus::NotSupported { reason }),
+    }
+}
+
+/// Revocation plans for every account of the device holding the allowance
+async fn plan_revocations(
+    database: &Database,
+    device_id: &str,
+    wallet_fingerprint: &str,
+    network: &EvmNetwork,
+    token_contract: &str,
+    spender: &str,
+) -> Result<Vec<(RevocationPlan, String)>, String> {
+    let client = http_client()?;
+    let url = network.rpc_url()?.to_string();
+    let rpc = |method, params| rpc_call(client.clone(), url.clone(), method, params, Priority::Interactive);
+
+    let mut plans = Vec::new();
+    for (owner, path) in evm_accounts(database, device_id, wallet_fingerprint).await? {
+        let plan = token_approvals::plan_revocation(
+            &rpc,
+            &network.network_id,
+            network.chain_id,
+            network.endpoints.supports_eip1559,
+            &owner,
+            token_contract,
+            spender,
+        ).await?;
+        if let Some(plan) = plan {
+            plans.push((plan, path));
+        }
+    }
+    if plans.is_empty() {
+        return Err(format!("No account of device {} has an allowance for {} on {}", device_id, spender, token_contract));
+    }
+    Ok(plans)
+}
+
+/// Preview approve(spender, 0) for each account holding the allowance: the
+/// transactions, the allowance being removed and the worst-case network fee
+#[tauri::command]
+#[specta::specta]
+pub async fn preview_token_approval_revocation(
+    device_id: String,
+    token_contract: String,
+    spender: String,
+    network_id: String,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<Vec<RevocationPlan>, String> {
+    let network = EvmNetwork::load(&database, &network_id).await?;
+    let wallet = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let plans = plan_revocations(&database, &device_id, &wallet, &network, &token_contract, &spender).await?;
+    Ok(plans.into_iter().map(|(plan, _)| plan).collect())
+}
+
+fn to_device_transaction(tx: &PlannedEvmTx, path: &str) -> Result<EthereumTransaction, String> {
+    let to = hex::decode(tx.to.trim_start_matches("0x")).map_err(|e| format!("Invalid contract address: {}", e))?;
+    let data = hex::decode(tx.data.trim_start_matches("0x")).map_err(|e| format!("Invalid calldata: {}", e))?;
+    let (gas_price, max_fee_per_gas, max_priority_fee_per_gas) = match tx.fees {
+        EvmFees::Legacy { gas_price } => (U256::from(gas_price), None, None),
+        EvmFees::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => (
+            U256::from(max_priority_fee_per_gas),
+            Some(U256::from(max_fee_per_gas)),
+            Some(U256::from(max_priority_fee_per_gas)),
+        ),
+    };
+    Ok(EthereumTransaction {
+        address_n: parse_derivation_path(path)?,
+        nonce: U256::from(tx.nonce),
+        gas_price,
+        gas_limit: U256::from(tx.gas_limit),
+        to: Some(Address::from_slice(&to)),
+        value: U256::zero(),
+        data,
+        chain_id: tx.chain_id,
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+    })
+}
+
+/// Revoke an ERC-20 allowance by signing approve(spender, 0) from every account
+/// of the device that holds it. The device shows each transaction for
+/// confirmation; the signed transactions are returned for broadcast.
+#[tauri::command]
+#[specta::specta]
+pub async fn revoke_token_approval(
+    app: AppHandle,
+    device_id: String,
+    token_contract: String,
+    spender: String,
+    network_id: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    limit_confirmations: State<'_, LimitConfirmations>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<Vec<SignedEvmTx>, String> {
+    let _operation = maintenance.device_operation("revoke_token_approval").map_err(|e| e.to_json_string())?;
+    let network = EvmNetwork::load(&database, &network_id).await?;
+    let features = vault_core::get_device_features(&device_id, &queue_manager).await?;
+    vault_core::authenticity::require_verified_device(&device_id)?;
+    if let Some(reason) = FirmwareCapabilities::from_features(&features).unsupported_reason(Capability::EthereumSigning) {
+        return Err(reason);
+    }
+
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let plans = plan_revocations(&database, &device_id, &wallet_fingerprint, &network, &token_contract, &spender).await?;
+    let caip = format!("{}/erc20:{}", network_id, token_approvals::normalize_address(&token_contract)?);
+    let origin = SigningOrigin::MainWindow;
+    let queue = get_or_create_device_queue(&device_id, &queue_manager).await?;
+
+    let mut signed = Vec::new();
+    for (plan, path) in plans {
+        for tx in &plan.transactions {
+            let device_tx = to_device_transaction(tx, &path)?;
+            let max_fee_wei = tx.max_fee_wei()?;
+            // A revocation moves no value, so it never counts against a spending limit
+            let ticket = signing_origin::authorize_signing(
+                &database,
+                &limit_confirmations,
+                &origin,
+                SigningAuditInput {
+                    origin: origin.key(),
+                    device_id: device_id.clone(),
+                    wallet_fingerprint: wallet_fingerprint.clone(),
+                    caip: caip.clone(),
+                    value_usd: Some(0.0),
+                },
+                super::signing::LIMIT_CONFIRMATION_TIMEOUT,
+                |audit_id, decision| super::signing::notify_limit_confirmation(&app, audit_id, &origin, &caip, decision),
+            ).await?;
+
+            log::info!(
+                "🧹 Revoking {} allowance for {} from {} on {} (nonce {})",
+                plan.token_contract, plan.spender, plan.owner, network_id, tx.nonce
+            );
+            let raw = match sign_ethereum_transaction(&queue, device_tx).await {
+                Ok(raw) => raw,
+                Err(e) => {
+                    let error = format!("Signing failed: {}", e);
+                    signing_origin::complete_signing(&database, &ticket, Err(&error)).await;
+                    return Err(error);
+                }
+            };
+            let txid = format!("0x{}", hex::encode(Keccak256::digest(&raw)));
+            signing_origin::complete_signing(&database, &ticket, Ok(Some(&txid))).await;
+
+            // Not broadcast yet, so not pending; broadcast_transaction moves it on
+            let cached = database
+                .upsert_transaction(&TransactionCache {
+                    id: 0,
+                    device_id: device_id.clone(),
+                    txid: txid.clone(),
+                    caip: caip.clone(),
+                    transaction_type: "approve".to_string(),
+                    amount: "0".to_string(),
+                    amount_usd: None,
+                    fee: Some(format_units(max_fee_wei, 18)),
+                    fee_usd: None,
+                    from_address: Some(plan.owner.clone()),
+                    to_address: Some(plan.token_contract.clone()),
+                    timestamp: chrono::Utc::now().timestamp(),
+                    block_height: None,
+                    status: Some("signed".to_string()),
+                    metadata_json: Some(json!({ "spender": plan.spender, "spender_label": plan.spender_label }).to_string()),
+                    wallet_fingerprint: wallet_fingerprint.clone(),
+                    origin: Some(origin.key()),
+                })
+                .await;
+            if let Err(e) = cached {
+                log::warn!("Failed to cache signed revocation {}: {}", txid, e);
+            }
+
+            signed.push(SignedEvmTx {
+                txid,
+                serialized: format!("0x{}", hex::encode(&raw)),
+                from: plan.owner.clone(),
+                nonce: tx.nonce,
+            });
+        }
+    }
+    Ok(signed)
+}
@@ -0,0 +1,30 @@
+// commands/assets.rs - Per-asset send/receive capability flags for the UI
+
+use std::sync::Arc;
+use tauri::State;
+use keepkey_db::Database;
+use vault_core::asset_capabilities::{self, AssetCapabilities};
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+
+/// Whether an asset can be received, sent or used for message signing on a device
+#[tauri::command]
+#[specta::specta]
+pub async fn get_asset_capabilities(
+    caip: String,
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+) -> Result<AssetCapabilities, String> {
+    asset_capabilities::get_asset_capabilities(&database, &caip, &device_id).await
+}
+
+/// Capabilities for every asset in the device's active wallet
+#[tauri::command]
+#[specta::specta]
+pub async fn get_portfolio_capabilities(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<Vec<AssetCapabilities>, String> {
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    asset_capabilities::get_portfolio_capabilities(&database, &device_id, &wallet_fingerprint).await
+}
@@ -0,0 +1,81 @@
+// commands/backups.rs - Automatic backup management and restore
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::{ActivityEntry, Database};
+use keepkey_db::backup::{self, BackupInfo, BackupPolicy};
+use vault_core::maintenance::MaintenanceController;
+
+/// Backups in ~/.keepkey/backups, newest first
+#[tauri::command]
+#[specta::specta]
+pub async fn list_backups() -> Result<Vec<BackupInfo>, String> {
+    backup::list_backups(&backup::default_backup_dir()).map_err(|e| format!("Failed to list backups: {}", e))
+}
+
+/// Get the schedule and retention used for automatic backups
+#[tauri::command]
+#[specta::specta]
+pub async fn get_backup_policy(
+    database: State<'_, Arc<Database>>,
+) -> Result<BackupPolicy, String> {
+    database.get_backup_policy().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Update the schedule and retention used for automatic backups
+#[tauri::command]
+#[specta::specta]
+pub async fn set_backup_policy(
+    config: BackupPolicy,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    database.set_backup_policy(&config).await.map_err(|e| e.to_string())?;
+    log::info!("💾 Backup policy updated: {:?}", config);
+    Ok(())
+}
+
+/// Replace the database with one of the files returned by `list_backups`.
+///
+/// The backup must pass the integrity and schema checks first, and the current
+/// data is kept as a pre-restore snapshot. Runs in maintenance mode and emits
+/// `database:restored` so views reload.
+#[tauri::command]
+#[specta::specta]
+pub async fn restore_from_backup(
+    app: AppHandle,
+    filename: String,
+    database: State<'_, Arc<Database>>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<String, String> {
+    if filename.contains(['/', '\\']) || filename.starts_with('.') {
+        return Err(format!("Invalid backup name: {}", filename));
+    }
+
+    let dir = backup::default_backup_dir();
+    let session = super::maintenance::begin(&maintenance, "Restoring backup").await?;
+    log::info!("♻️ Restoring database from backup {}", filename);
+    let pre_restore = database.restore_from_backup(&dir.join(&filename), &dir).await.map_err(|e| {
+        log::error!("❌ Restore from {} failed: {}", filename, e);
+        format!("Restore failed: {}", e)
+    })?;
+    drop(session);
+
+    super::emit_or_queue_event(&app, "database:restored", serde_json::json!({
+        "filename": filename,
+        "pre_restore_snapshot": pre_restore,
+    })).await?;
+
+    Ok(pre_restore)
+}
+
+/// Recent background activity (backups, restores), newest first
+#[tauri::command]
+#[specta::specta]
+pub async fn get_activity_log(
+    limit: Option<usize>,
+    category: Option<String>,
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<ActivityEntry>, String> {
+    database.get_activity_log(limit.unwrap_or(100), category.as_deref()).await
+        .map_err(|e| format!("Database error: {}", e))
+}
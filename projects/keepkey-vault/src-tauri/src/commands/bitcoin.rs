@@ -0,0 +1,626 @@
+// commands/bitcoin.rs - Bitcoin build/preview/sign pipeline (single and batch payments)
+
+use std::sync::Arc;
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::Network;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, SigningAuditInput};
+use keepkey_db::types::TransactionCache;
+use keepkey_rust::chains::bitcoin::{
+    sign_bitcoin_transaction, BitcoinTxInput, BitcoinTxOutput, PrevTransactions, ScriptType,
+};
+use vault_core::asset_capabilities::{self, AssetOperation};
+use vault_core::bitcoin_tx::{self, BatchPlan, BatchPreview, Recipient, SpendableUtxo};
+use vault_core::coin_select::{self, ComposeRecipient, SelectionMethod};
+use vault_core::fees::{self, FeeChoice};
+use vault_core::maintenance::MaintenanceController;
+use vault_core::paths::{format_derivation_path, network_family, parse_derivation_path, NetworkFamily};
+use vault_core::signing_origin::{self, LimitConfirmations, SigningOrigin};
+use vault_core::spendable;
+use vault_core::units::format_units;
+use vault_core::utxo::{check_fee_rate, total_sats, UtxoScriptType};
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use super::device::get_or_create_device_queue;
+use super::device::get_xpub::account_xpub;
+use super::DeviceQueueManager;
+
+/// Preference holding the next change index of a wallet's account, keyed
+/// `btc_change_index:<wallet fingerprint>:<account path>`
+const CHANGE_INDEX_PREF: &str = "btc_change_index";
+
+fn to_device_script_type(script_type: UtxoScriptType) -> ScriptType {
+    match script_type {
+        UtxoScriptType::P2pkh => ScriptType::P2PKH,
+        UtxoScriptType::P2shP2wpkh => ScriptType::P2SHP2WPKH,
+        UtxoScriptType::P2wpkh => ScriptType::P2WPKH,
+        UtxoScriptType::P2tr => ScriptType::P2TR,
+    }
+}
+
+fn bitcoin_network(caip: &str) -> Result<Network, String> {
+    match network_family(caip)? {
+        NetworkFamily::Utxo { coin_name } if coin_name == "Bitcoin" => Ok(Network::Bitcoin),
+        NetworkFamily::Utxo { coin_name } if coin_name == "Testnet" => Ok(Network::Testnet),
+        _ => Err(format!("Not a Bitcoin network: {}", caip)),
+    }
+}
+
+/// Coin name the device knows the network by
+fn coin_name(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "Bitcoin",
+        _ => "Testnet",
+    }
+}
+
+fn change_index_key(wallet_fingerprint: &str, account_path: &[u32]) -> String {
+    format!("{}:{}:{}", CHANGE_INDEX_PREF, wallet_fingerprint, format_derivation_path(account_path))
+}
+
+fn device_input(utxo: &SpendableUtxo, script_type: UtxoScriptType) -> Result<BitcoinTxInput, String> {
+    Ok(BitcoinTxInput {
+        prev_hash: hex::decode(&utxo.txid).map_err(|e| format!("Invalid txid {}: {}", utxo.txid, e))?,
+        prev_index: utxo.vout,
+        address_n: parse_derivation_path(&utxo.path)?,
+        amount: utxo.amount_sats,
+        script_type: to_device_script_type(script_type),
+    })
+}
+
+fn recipient_outputs(recipients: &[Recipient]) -> Vec<BitcoinTxOutput> {
+    recipients
+        .iter()
+        .map(|r| BitcoinTxOutput {
+            address: Some(r.address.clone()),
+            address_n: Vec::new(),
+            amount: r.amount_sats,
+            script_type: ScriptType::P2PKH,
+        })
+        .collect()
+}
+
+fn plan_transaction(
+    utxos: &[SpendableUtxo],
+    recipients: &[Recipient],
+    script_type: &str,
+    fee_rate_sat_vb: u64,
+    allow_duplicate_outputs: Option<bool>,
+) -> Result<(BatchPlan, UtxoScriptType), String> {
+    let input_type = UtxoScriptType::parse(script_type)?;
+    let plan = bitcoin_tx::plan_batch(
+        utxos,
+        input_type,
+        recipients,
+        input_type,
+        fee_rate_sat_vb,
+        allow_duplicate_outputs.unwrap_or(false),
+    )?;
+    Ok((plan, input_type))
+}
+
+/// Refuse a plan that would spend into the network's reserve. The error is a
+/// JSON encoded `SpendError` so the UI can show the reserve breakdown.
+async fn check_reserve(database: &Database, caip: &str, utxos: &[SpendableUtxo], plan: &BatchPlan) -> Result<(), String> {
+    let available_sats = total_sats(utxos.iter().map(|u| u.amount_sats))?;
+    check_reserve_sats(database, caip, available_sats, plan).await
+}
+
+async fn check_reserve_sats(database: &Database, caip: &str, available_sats: u64, plan: &BatchPlan) -> Result<(), String> {
+    let (rules, decimals) = spendable::load_reserve_rules(database, caip).await?;
+    spendable::check_utxo_send(&rules, caip, decimals, available_sats, plan.total_sent(), plan.fee_sats)
+        .map_err(|e| e.to_json_string())
+}
+
+/// Decode the raw (hex) transactions spent by legacy inputs
+fn decode_prev_txs(prev_txs: Option<Vec<String>>) -> Result<PrevTransactions, String> {
+    let raw_txs = prev_txs
+        .unwrap_or_default()
+        .iter()
+        .map(|raw| hex::decode(raw).map_err(|e| format!("Invalid previous transaction hex: {}", e)))
+        .collect::<Result<Vec<_>, String>>()?;
+    PrevTransactions::from_raw(&raw_txs).map_err(|e| e.to_string())
+}
+
+/// Preview plus the fee rate it was built with
+#[derive(Debug, Serialize, specta::Type)]
+pub struct BitcoinTxPreview {
+    #[serde(flatten)]
+    pub preview: BatchPreview,
+    pub fee_choice: FeeChoice,
+}
+
+/// Select coins and preview every output with its fiat value. The fee is either
+/// an explicit `fee_rate_sat_vb` or a `fee_target` ("next_block", "30_min",
+/// "1_hour", "6_hours") resolved from current fee suggestions.
+#[tauri::command]
+#[specta::specta]
+pub async fn preview_bitcoin_tx(
+    caip: String,
+    utxos: Vec<SpendableUtxo>,
+    recipients: Vec<Recipient>,
+    script_type: String,
+    fee_rate_sat_vb: Option<u64>,
+    fee_target: Option<String>,
+    allow_duplicate_outputs: Option<bool>,
+    database: State<'_, Arc<Database>>,
+) -> Result<BitcoinTxPreview, String> {
+    bitcoin_network(&caip)?;
+    let source = match fee_target {
+        Some(_) => Some(super::fees::load_fee_source(&database, &caip).await?.0),
+        None => None,
+    };
+    let fee_choice = fees::choose_fee(source.as_ref(), fee_rate_sat_vb, fee_target.as_deref())?;
+    log::info!("₿ Previewing Bitcoin tx at {:?}", fee_choice);
+
+    let (plan, _) = plan_transaction(&utxos, &recipients, &script_type, fee_choice.sat_per_vb(), allow_duplicate_outputs)?;
+    check_reserve(&database, &caip, &utxos, &plan).await?;
+    let price = database.get_asset_price_usd(&caip).await.unwrap_or(None);
+    Ok(BitcoinTxPreview { preview: plan.preview(price), fee_choice })
+}
+
+/// Build, sign and cache a Bitcoin transaction paying one or more recipients.
+///
+/// The device confirms each recipient output in turn. The full output list is
+/// stored in the transaction's metadata so history can expand a batch payment.
+/// `prev_txs` are the raw (hex) transactions spent by legacy inputs.
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+pub async fn build_and_sign_bitcoin_tx(
+    app: AppHandle,
+    device_id: String,
+    caip: String,
+    utxos: Vec<SpendableUtxo>,
+    recipients: Vec<Recipient>,
+    script_type: String,
+    change_path: String,
+    fee_rate_sat_vb: u64,
+    allow_duplicate_outputs: Option<bool>,
+    prev_txs: Option<Vec<String>>,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    limit_confirmations: State<'_, LimitConfirmations>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<SignedBitcoinTx, String> {
+    let _operation = maintenance.device_operation("build_and_sign_bitcoin_tx").map_err(|e| e.to_json_string())?;
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let request = BitcoinSignRequest {
+        device_id,
+        wallet_fingerprint,
+        caip,
+        utxos,
+        recipients,
+        script_type,
+        change_path,
+        fee_rate_sat_vb,
+        allow_duplicate_outputs,
+        prev_txs,
+    };
+    sign_bitcoin_for_origin(&app, &database, &queue_manager, &limit_confirmations, &SigningOrigin::MainWindow, request).await
+}
+
+/// A signed transaction ready to broadcast
+#[derive(Debug, Serialize, specta::Type)]
+pub struct SignedBitcoinTx {
+    pub txid: String,
+    /// Raw transaction as hex
+    pub serialized: String,
+    pub fee_sats: u64,
+    pub change_sats: Option<u64>,
+    pub recipients: Vec<Recipient>,
+}
+
+/// Everything needed to build and sign a Bitcoin transaction
+pub struct BitcoinSignRequest {
+    pub device_id: String,
+    pub wallet_fingerprint: String,
+    pub caip: String,
+    pub utxos: Vec<SpendableUtxo>,
+    pub recipients: Vec<Recipient>,
+    pub script_type: String,
+    pub change_path: String,
+    pub fee_rate_sat_vb: u64,
+    pub allow_duplicate_outputs: Option<bool>,
+    /// Raw (hex) transactions spent by legacy inputs
+    pub prev_txs: Option<Vec<String>>,
+}
+
+/// Shared signing path for every entry point; `origin` comes from the caller's
+/// transport and is recorded in the signing audit and transaction cache.
+pub async fn sign_bitcoin_for_origin(
+    app: &AppHandle,
+    database: &Database,
+    queue_manager: &DeviceQueueManager,
+    limit_confirmations: &LimitConfirmations,
+    origin: &SigningOrigin,
+    request: BitcoinSignRequest,
+) -> Result<SignedBitcoinTx, String> {
+    let BitcoinSignRequest {
+        device_id,
+        wallet_fingerprint,
+        caip,
+        utxos,
+        recipients,
+        script_type,
+        change_path,
+        fee_rate_sat_vb,
+        allow_duplicate_outputs,
+        prev_txs,
+    } = request;
+
+    let network = bitcoin_network(&caip)?;
+    let (plan, input_type) = plan_transaction(&utxos, &recipients, &script_type, fee_rate_sat_vb, allow_duplicate_outputs)?;
+    check_reserve(database, &caip, &utxos, &plan).await?;
+
+    let inputs = plan
+        .inputs
+        .iter()
+        .map(|utxo| device_input(utxo, input_type))
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut outputs = recipient_outputs(&plan.recipients);
+    if let Some(change) = plan.change_sats {
+        outputs.push(BitcoinTxOutput {
+            address: None,
+            address_n: parse_derivation_path(&change_path)?,
+            amount: change,
+            script_type: to_device_script_type(input_type),
+        });
+    }
+
+    let tx = DeviceTx {
+        device_id,
+        wallet_fingerprint,
+        caip,
+        network,
+        plan,
+        inputs,
+        outputs,
+        prev_txs: decode_prev_txs(prev_txs)?,
+    };
+    sign_and_record(app, database, queue_manager, limit_confirmations, origin, tx).await
+}
+
+/// A planned transaction and its device inputs and outputs
+struct DeviceTx {
+    device_id: String,
+    wallet_fingerprint: String,
+    caip: String,
+    network: Network,
+    plan: BatchPlan,
+    inputs: Vec<BitcoinTxInput>,
+    outputs: Vec<BitcoinTxOutput>,
+    prev_txs: PrevTransactions,
+}
+
+/// Authorize, sign and cache a planned transaction
+async fn sign_and_record(
+    app: &AppHandle,
+    database: &Database,
+    queue_manager: &DeviceQueueManager,
+    limit_confirmations: &LimitConfirmations,
+    origin: &SigningOrigin,
+    tx: DeviceTx,
+) -> Result<SignedBitcoinTx, String> {
+    let DeviceTx { device_id, wallet_fingerprint, caip, network, plan, inputs, outputs, prev_txs } = tx;
+    vault_core::authenticity::require_verified_device(&device_id)?;
+    asset_capabilities::require_capability(database, &caip, &device_id, AssetOperation::Send).await?;
+    let queue = get_or_create_device_queue(&device_id, queue_manager).await?;
+
+    let price = database.get_asset_price_usd(&caip).await.unwrap_or(None);
+    let value_usd = price.map(|p| plan.total_sent() as f64 / 100_000_000.0 * p);
+    let ticket = signing_origin::authorize_signing(
+        database,
+        limit_confirmations,
+        origin,
+        SigningAuditInput {
+            origin: origin.key(),
+            device_id: device_id.clone(),
+            wallet_fingerprint: wallet_fingerprint.clone(),
+            caip: caip.clone(),
+            value_usd,
+        },
+        super::signing::LIMIT_CONFIRMATION_TIMEOUT,
+        |audit_id, decision| super::signing::notify_limit_confirmation(app, audit_id, origin, &caip, decision),
+    ).await?;
+
+    log::info!(
+        "₿ Signing Bitcoin tx on {} for {}: {} inputs, {} recipients, fee {} sats",
+        device_id, origin, plan.inputs.len(), plan.recipients.len(), plan.fee_sats
+    );
+
+    let tx = match sign_bitcoin_transaction(&queue, inputs, outputs, &prev_txs, network).await {
+        Ok(tx) => tx,
+        Err(e) => {
+            let error = format!("Signing failed: {}", e);
+            signing_origin::complete_signing(database, &ticket, Err(&error)).await;
+            return Err(error);
+        }
+    };
+    let txid = tx.txid().to_string();
+    signing_origin::complete_signing(database, &ticket, Ok(Some(&txid))).await;
+
+    // Not broadcast yet, so not pending; broadcast_transaction moves it on
+    let to_address = match plan.recipients.as_slice() {
+        [single] => Some(single.address.clone()),
+        _ => None,
+    };
+    let cached = database
+        .upsert_transaction(&TransactionCache {
+            id: 0,
+            device_id: device_id.clone(),
+            txid: txid.clone(),
+            caip: caip.clone(),
+            transaction_type: "send".to_string(),
+            amount: format_units(plan.total_sent() as u128, 8),
+            amount_usd: value_usd.map(|v| format!("{:.2}", v)),
+            fee: Some(format_units(plan.fee_sats as u128, 8)),
+            fee_usd: None,
+            from_address: None,
+            to_address,
+            timestamp: chrono::Utc::now().timestamp(),
+            block_height: None,
+            status: Some("signed".to_string()),
+            metadata_json: Some(plan.metadata_json().to_string()),
+            wallet_fingerprint,
+            origin: Some(origin.key()),
+        })
+        .await;
+    if let Err(e) = cached {
+        log::warn!("Failed to cache signed Bitcoin tx {}: {}", txid, e);
+    }
+
+    Ok(SignedBitcoinTx {
+        txid,
+        serialized: serialize_hex(&tx),
+        fee_sats: plan.fee_sats,
+        change_sats: plan.change_sats,
+        recipients: plan.recipients,
+    })
+}
+
+/// Change output of a composed transaction, at a fresh address of the device
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
+pub struct ComposedChange {
+    pub address: String,
+    pub path: String,
+    /// derivation_paths name, e.g. "p2wpkh"
+    pub script_type: String,
+    pub amount_sats: u64,
+}
+
+/// An unsigned transaction for the UI to show, then hand back to
+/// `sign_composed_transaction` once the user confirms it
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
+pub struct ComposedTransaction {
+    pub caip: String,
+    pub inputs: Vec<SpendableUtxo>,
+    pub outputs: Vec<Recipient>,
+    pub change: Option<ComposedChange>,
+    pub fee_sats: u64,
+    pub vsize: u64,
+    pub method: SelectionMethod,
+    /// Balance of the UTXOs offered when composing, which the reserve is
+    /// checked against again at signing
+    pub available_sats: u64,
+}
+
+/// A composed transaction with its preview
+#[derive(Debug, Serialize, specta::Type)]
+pub struct ComposedBitcoinTx {
+    pub transaction: ComposedTransaction,
+    pub preview: BatchPreview,
+}
+
+/// Choose inputs for `recipients` and plan the transaction without signing it.
+///
+/// Inputs are chosen by branch-and-bound, falling back to largest first; a
+/// single recipient with amount "max" gets everything less the fee. Change
+/// goes to the next unused change address of the first input's account,
+/// derived from the account xpub as `change_script_type`.
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+pub async fn compose_bitcoin_transaction(
+    app: AppHandle,
+    device_id: String,
+    caip: String,
+    recipients: Vec<ComposeRecipient>,
+    fee_rate_sat_vb: u64,
+    utxos: Vec<SpendableUtxo>,
+    change_script_type: String,
+    allow_duplicate_outputs: Option<bool>,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<ComposedBitcoinTx, String> {
+    let network = bitcoin_network(&caip)?;
+    let change_type = UtxoScriptType::parse(&change_script_type)?;
+    let composed = coin_select::compose(
+        &utxos,
+        &recipients,
+        change_type,
+        fee_rate_sat_vb,
+        allow_duplicate_outputs.unwrap_or(false),
+    )?;
+    let available_sats = total_sats(utxos.iter().map(|u| u.amount_sats))?;
+    check_reserve_sats(&database, &caip, available_sats, &composed.plan).await?;
+    let plan = composed.plan;
+    log::info!(
+        "₿ Composed Bitcoin tx by {:?}: {} inputs, fee {} sats",
+        composed.method, plan.inputs.len(), plan.fee_sats
+    );
+
+    let change = match (plan.change_sats, plan.inputs.first()) {
+        (Some(amount_sats), Some(first)) => {
+            let _operation = maintenance.device_operation("compose_bitcoin_transaction").map_err(|e| e.to_json_string())?;
+            let fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+            let account = coin_select::change_account_path(&first.path, change_type)?;
+            let xpub = account_xpub(
+                &app,
+                &database,
+                &queue_manager,
+                &device_id,
+                &fingerprint,
+                &account,
+                &change_script_type,
+                coin_name(network),
+            ).await?;
+            let stored = database.get_preference(&change_index_key(&fingerprint, &account)).await
+                .ok()
+                .flatten()
+                .and_then(|v| v.parse::<u32>().ok());
+            let index = coin_select::next_change_index(&utxos, &account, stored);
+            let address = coin_select::change_address(&xpub, index, change_type, network)?;
+            let path = format_derivation_path(&[account.as_slice(), &[1, index]].concat());
+            Some(ComposedChange { address, path, script_type: change_script_type, amount_sats })
+        }
+        _ => None,
+    };
+
+    let price = database.get_asset_price_usd(&caip).await.unwrap_or(None);
+    let preview = plan.preview(price);
+    Ok(ComposedBitcoinTx {
+        transaction: ComposedTransaction {
+            caip,
+            inputs: plan.inputs,
+            outputs: plan.recipients,
+            change,
+            fee_sats: plan.fee_sats,
+            vsize: plan.vsize,
+            method: composed.method,
+            available_sats,
+        },
+        preview,
+    })
+}
+
+/// Check that a composed transaction adds up and pays a sane fee rate, and
+/// turn it back into a plan
+fn composed_plan(transaction: &ComposedTransaction) -> Result<BatchPlan, String> {
+    if transaction.inputs.is_empty() {
+        return Err("The transaction has no inputs".to_string());
+    }
+    let fee_rate = transaction
+        .fee_sats
+        .checked_div(transaction.vsize)
+        .ok_or_else(|| "The transaction has no size".to_string())?;
+    check_fee_rate(fee_rate)?;
+    let sent = bitcoin_tx::validate_recipients(&transaction.outputs, true)?;
+    let change_sats = transaction.change.as_ref().map(|c| c.amount_sats);
+    let spent = total_sats(transaction.inputs.iter().map(|u| u.amount_sats))?;
+    let paid = [sent, transaction.fee_sats, change_sats.unwrap_or(0)]
+        .iter()
+        .try_fold(0u64, |total, amount| total.checked_add(*amount));
+    if paid != Some(spent) {
+        return Err(format!(
+            "The transaction does not add up: inputs {} sats, outputs {} + fee {} + change {} sats",
+            spent, sent, transaction.fee_sats, change_sats.unwrap_or(0)
+        ));
+    }
+    if spent > transaction.available_sats {
+        return Err(format!(
+            "The transaction spends {} sats but was composed from {} sats",
+            spent, transaction.available_sats
+        ));
+    }
+    Ok(BatchPlan {
+        inputs: transaction.inputs.clone(),
+        recipients: transaction.outputs.clone(),
+        change_sats,
+        fee_sats: transaction.fee_sats,
+        vsize: transaction.vsize,
+    })
+}
+
+/// Sign a transaction from `compose_bitcoin_transaction` as the user confirmed it.
+///
+/// The fee rate and the network's reserve are checked again, since the
+/// transaction comes back from the UI. The device derives the change output
+/// from its path, so it shows only the recipient outputs and the fee. `prev_txs` are the raw (hex) transactions
+/// spent by legacy inputs, which the device asks for.
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+pub async fn sign_composed_transaction(
+    app: AppHandle,
+    device_id: String,
+    transaction: ComposedTransaction,
+    prev_txs: Option<Vec<String>>,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    limit_confirmations: State<'_, LimitConfirmations>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<SignedBitcoinTx, String> {
+    let _operation = maintenance.device_operation("sign_composed_transaction").map_err(|e| e.to_json_string())?;
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let network = bitcoin_network(&transaction.caip)?;
+    let plan = composed_plan(&transaction)?;
+    check_reserve_sats(&database, &transaction.caip, transaction.available_sats, &plan).await?;
+
+    let inputs = plan
+        .inputs
+        .iter()
+        .map(|utxo| device_input(utxo, coin_select::input_type_for_path(&utxo.path)?))
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut outputs = recipient_outputs(&plan.recipients);
+    if let Some(change) = &transaction.change {
+        outputs.push(BitcoinTxOutput {
+            address: None,
+            address_n: parse_derivation_path(&change.path)?,
+            amount: change.amount_sats,
+            script_type: to_device_script_type(UtxoScriptType::parse(&change.script_type)?),
+        });
+    }
+    let prev_txs = decode_prev_txs(prev_txs)?;
+
+    let tx = DeviceTx {
+        device_id,
+        wallet_fingerprint: wallet_fingerprint.clone(),
+        caip: transaction.caip,
+        network,
+        plan,
+        inputs,
+        outputs,
+        prev_txs,
+    };
+    let signed = sign_and_record(&app, &database, &queue_manager, &limit_confirmations, &SigningOrigin::MainWindow, tx).await?;
+
+    // The change address is used now; later compositions start after it
+    if let Some(change) = &transaction.change {
+        let address_n = parse_derivation_path(&change.path)?;
+        if let [account @ .., 1, index] = address_n.as_slice() {
+            let key = change_index_key(&wallet_fingerprint, account);
+            let stored = database.get_preference(&key).await.ok().flatten().and_then(|v| v.parse::<u32>().ok());
+            if stored.map_or(true, |next| next <= *index) {
+                if let Err(e) = database.set_preference(&key, &(index + 1).to_string()).await {
+                    log::warn!("Could not store the next change index for {}: {}", key, e);
+                }
+            }
+        }
+    }
+    Ok(signed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_utxo_script_type_maps_to_its_device_type() {
+        let cases = [
+            (UtxoScriptType::P2pkh, ScriptType::P2PKH),
+            (UtxoScriptType::P2shP2wpkh, ScriptType::P2SHP2WPKH),
+            (UtxoScriptType::P2wpkh, ScriptType::P2WPKH),
+            (UtxoScriptType::P2tr, ScriptType::P2TR),
+        ];
+        for (utxo_type, device_type) in cases {
+            assert_eq!(to_device_script_type(utxo_type), device_type, "{:?}", utxo_type);
+        }
+        // Wrapped segwit is SPENDP2SHWITNESS on the wire, not SPENDMULTISIG
+        assert_eq!(to_device_script_type(UtxoScriptType::P2shP2wpkh).to_proto_input(), 4);
+    }
+}
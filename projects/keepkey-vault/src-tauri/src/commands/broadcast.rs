@@ -0,0 +1,286 @@
+// commands/broadcast.rs - Broadcasting signed transactions and watching them confirm
+//
+// The raw transaction goes to the network's rpc_urls in order (a UTXO network
+// without any falls back to its explorer_api_url), moving on only when an
+// endpoint cannot be reached. A node that already has the transaction counts
+// as a successful broadcast. The endpoint that took it is then polled in the
+// background until the transaction confirms, reverts or is dropped.
+
+use std::sync::Arc;
+use std::time::Duration;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+use keepkey_db::types::TransactionCache;
+use vault_core::broadcast::{
+    classify_rejection, decode_raw_tx, watch_transaction, BroadcastError, InclusionStatus, Rejection, WatchOutcome,
+    CONFIRMED_EVENT, FAILED_EVENT,
+};
+use vault_core::endpoints::{endpoint_manager, Priority};
+use vault_core::paths::{network_family, NetworkFamily};
+use vault_core::token_approvals::parse_quantity;
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use super::approvals::{http_client, rpc_call};
+
+/// Time between confirmation checks, and how many are made (about an hour)
+/// before the periodic tracker is left to it
+const WATCH_INTERVAL: Duration = Duration::from_secs(20);
+const WATCH_POLLS: u32 = 180;
+
+/// Consecutive checks the node may not know the transaction before it is
+/// reported dropped
+const DROP_AFTER: u32 = 10;
+
+/// A broadcast transaction
+#[derive(Debug, Serialize, specta::Type)]
+pub struct BroadcastResult {
+    pub txid: String,
+    /// Asset id the transaction is cached under
+    pub caip: String,
+    /// Endpoint that took the transaction
+    pub endpoint: String,
+    /// The endpoint already had the transaction
+    pub already_known: bool,
+}
+
+/// How a network takes raw transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Api {
+    /// JSON-RPC eth_sendRawTransaction
+    JsonRpc,
+    /// Esplora-style POST /tx
+    Esplora,
+}
+
+/// Why one endpoint did not take the transaction
+enum Attempt {
+    Unreachable(String),
+    Refused(String),
+}
+
+/// The network's API and endpoints to try, in order
+async fn endpoints(database: &Database, network_id: &str) -> Result<(Api, Vec<String>), String> {
+    let no_endpoint = || BroadcastError::NoEndpoint { network_id: network_id.to_string() }.to_json_string();
+    let api = match network_family(network_id) {
+        Ok(NetworkFamily::Evm { .. }) => Api::JsonRpc,
+        Ok(NetworkFamily::Utxo { .. }) => Api::Esplora,
+        Err(_) => return Err(no_endpoint()),
+    };
+    let Some(endpoints) = database.get_network_endpoints(network_id).await
+        .map_err(|e| format!("Database error: {}", e))? else {
+        return Err(no_endpoint());
+    };
+    let mut urls = endpoints.rpc_urls;
+    if api == Api::Esplora && urls.is_empty() {
+        urls.extend(endpoints.explorer_api_url);
+    }
+    let urls: Vec<String> = urls.iter().map(|url| url.trim_end_matches('/').to_string()).collect();
+    if urls.is_empty() {
+        return Err(no_endpoint());
+    }
+    Ok((api, urls))
+}
+
+/// Post the transaction to one endpoint
+async fn submit(client: &reqwest::Client, api: Api, url: &str, raw_hex: &str) -> Result<(), Attempt> {
+    let request = match api {
+        Api::JsonRpc => client.post(url).json(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendRawTransaction",
+            "params": [format!("0x{}", raw_hex)],
+        })),
+        Api::Esplora => client.post(format!("{}/tx", url)).body(raw_hex.to_string()),
+    };
+    let response = endpoint_manager().send(request, Priority::Interactive).await
+        .map_err(|e| Attempt::Unreachable(e.to_string()))?;
+    let status = response.status();
+    let text = response.text().await.map_err(|e| Attempt::Unreachable(e.to_string()))?;
+
+    match api {
+        // Nodes report errors in the body, sometimes with an HTTP error status
+        Api::JsonRpc => match serde_json::from_str::<Value>(&text) {
+            Ok(body) => match body.get("error") {
+                Some(error) => Err(Attempt::Refused(
+                    error.get("message").and_then(Value::as_str).unwrap_or("unknown error").to_string(),
+                )),
+                None if status.is_success() => Ok(()),
+                None => Err(Attempt::Unreachable(format!("HTTP {}", status))),
+            },
+            Err(_) => Err(Attempt::Unreachable(format!("HTTP {}: {}", status, text.trim()))),
+        },
+        // Esplora answers a refused transaction with 400 and the node's message
+        Api::Esplora if status.is_success() => Ok(()),
+        Api::Esplora if status == reqwest::StatusCode::BAD_REQUEST => Err(Attempt::Refused(text.trim().to_string())),
+        Api::Esplora => Err(Attempt::Unreachable(format!("HTTP {}: {}", status, text.trim()))),
+    }
+}
+
+/// Try each endpoint until one takes the transaction. Returns the endpoint
+/// and whether it already had the transaction.
+async fn send_raw(client: &reqwest::Client, api: Api, urls: &[String], raw_hex: &str) -> Result<(String, bool), BroadcastError> {
+    let mut failures = Vec::new();
+    for url in urls {
+        match submit(client, api, url, raw_hex).await {
+            Ok(()) => return Ok((url.clone(), false)),
+            Err(Attempt::Refused(message)) => {
+                return match classify_rejection(&message) {
+                    Rejection::AlreadyKnown => Ok((url.clone(), true)),
+                    Rejection::Underpriced => Err(BroadcastError::Underpriced { endpoint: url.clone(), message }),
+                    Rejection::Conflict => Err(BroadcastError::Conflict { endpoint: url.clone(), message }),
+                    Rejection::Invalid => Err(BroadcastError::Rejected { endpoint: url.clone(), message }),
+                };
+            }
+            Err(Attempt::Unreachable(e)) => {
+                log::warn!("📡 Broadcast to {} failed: {}", url, e);
+                failures.push(format!("{}: {}", url, e));
+            }
+        }
+    }
+    Err(BroadcastError::Unreachable { endpoints: urls.to_vec(), message: failures.join("; ") })
+}
+
+/// Where the transaction is according to the endpoint that took it
+async fn inclusion(client: reqwest::Client, api: Api, url: String, txid: String) -> Result<InclusionStatus, String> {
+    match api {
+        Api::JsonRpc => {
+            let receipt = rpc_call(client.clone(), url.clone(), "eth_getTransactionReceipt", json!([txid]), Priority::Background).await?;
+            if let Some(block) = receipt.get("blockNumber").filter(|b| !b.is_null()) {
+                // Receipts from before Byzantium have no status
+                let succeeded = receipt.get("status").map_or(true, |s| s.is_null() || parse_quantity(s).ok() == Some(1));
+                return Ok(InclusionStatus::Included { height: parse_quantity(block)? as i64, succeeded });
+            }
+            let tx = rpc_call(client, url, "eth_getTransactionByHash", json!([txid]), Priority::Background).await?;
+            Ok(if tx.is_null() { InclusionStatus::NotFound } else { InclusionStatus::InMempool })
+        }
+        Api::Esplora => {
+            let url = format!("{}/tx/{}/status", url, txid);
+            let response = endpoint_manager().send(client.get(&url), Priority::Background).await
+                .map_err(|e| format!("Request to {} failed: {}", url, e))?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(InclusionStatus::NotFound);
+            }
+            let status: Value = response.error_for_status()
+                .map_err(|e| format!("Request to {} failed: {}", url, e))?
+                .json().await
+                .map_err(|e| format!("Invalid response from {}: {}", url, e))?;
+            match (status.get("confirmed").and_then(Value::as_bool), status.get("block_height").and_then(Value::as_i64)) {
+                (Some(true), Some(height)) => Ok(InclusionStatus::Included { height, succeeded: true }),
+                (Some(false), _) => Ok(InclusionStatus::InMempool),
+                _ => Err(format!("Unexpected transaction status from {}: {}", url, status)),
+            }
+        }
+    }
+}
+
+/// Poll the transaction in the background, then record and announce how it ended
+fn spawn_watch(app: AppHandle, database: Arc<Database>, client: reqwest::Client, api: Api, url: String, row: TransactionCache) {
+    tauri::async_runtime::spawn(async move {
+        let lookup = || inclusion(client.clone(), api, url.clone(), row.txid.clone());
+        let (status, event, block_height, reason) = match watch_transaction(lookup, WATCH_INTERVAL, WATCH_POLLS, DROP_AFTER).await {
+            WatchOutcome::Confirmed { height } => ("confirmed", CONFIRMED_EVENT, Some(height), None),
+            WatchOutcome::Failed { reason, height } => ("failed", FAILED_EVENT, height, Some(reason)),
+            WatchOutcome::StillPending => {
+                log::info!("📡 {} still pending after watching; leaving it to the tracker", row.txid);
+                return;
+            }
+        };
+        log::info!("📡 {} on {} is {} ({:?})", row.txid, row.caip, status, reason);
+
+        if let Err(e) = database
+            .update_transaction_status(&row.device_id, &row.wallet_fingerprint, &row.txid, status, block_height)
+            .await
+        {
+            log::warn!("Failed to record {} as {}: {}", row.txid, status, e);
+        }
+        let payload = json!({
+            "device_id": row.device_id,
+            "wallet_fingerprint": row.wallet_fingerprint,
+            "txid": row.txid,
+            "caip": row.caip,
+            "block_height": block_height,
+            "reason": reason,
+        });
+        if let Err(e) = super::emit_or_queue_event(&app, event, payload).await {
+            log::warn!("Failed to emit {}: {}", event, e);
+        }
+    });
+}
+
+/// Broadcast a signed transaction, cache it as pending and watch it confirm.
+/// Errors are BroadcastError JSON, telling unreachable endpoints (worth a
+/// retry) apart from transactions a node refused.
+#[tauri::command]
+#[specta::specta]
+pub async fn broadcast_transaction(
+    app: AppHandle,
+    device_id: String,
+    caip: String,
+    raw_tx_hex: String,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<BroadcastResult, String> {
+    let network_id = caip.split('/').next().unwrap_or(&caip).to_string();
+    let (raw, txid) = decode_raw_tx(&network_id, &raw_tx_hex).map_err(|e| e.to_json_string())?;
+    let (api, urls) = endpoints(&database, &network_id).await?;
+
+    let client = http_client()?;
+    let (endpoint, already_known) = send_raw(&client, api, &urls, &hex::encode(&raw)).await
+        .map_err(|e| {
+            log::warn!("📡 Broadcast of {} on {} failed: {}", txid, network_id, e);
+            e.to_json_string()
+        })?;
+    log::info!("📡 Broadcast {} on {} via {}{}", txid, network_id, endpoint, if already_known { " (already known)" } else { "" });
+    if let Err(e) = database.mark_signing_broadcast(&txid).await {
+        log::warn!("Failed to mark the signing audit of {} as broadcast: {}", txid, e);
+    }
+
+    // A signing command has usually cached the transaction already; only
+    // its status changes then. The network has the transaction now, so
+    // cache failures from here on are logged, not returned.
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let asset_caip = if caip.contains('/') {
+        caip.clone()
+    } else {
+        match database.get_network(&network_id).await {
+            Ok(network) => network.map(|network| network.native_asset_caip).unwrap_or_else(|| caip.clone()),
+            Err(e) => {
+                log::warn!("Failed to look up the native asset of {}: {}", network_id, e);
+                caip.clone()
+            }
+        }
+    };
+    let row = TransactionCache {
+        id: 0,
+        device_id,
+        txid: txid.clone(),
+        caip: asset_caip.clone(),
+        transaction_type: "send".to_string(),
+        amount: "0".to_string(),
+        amount_usd: None,
+        fee: None,
+        fee_usd: None,
+        from_address: None,
+        to_address: None,
+        timestamp: chrono::Utc::now().timestamp(),
+        block_height: None,
+        status: Some("pending".to_string()),
+        metadata_json: None,
+        wallet_fingerprint,
+        origin: None,
+    };
+    match database.update_transaction_status(&row.device_id, &row.wallet_fingerprint, &txid, "pending", None).await {
+        Ok(0) => {
+            if let Err(e) = database.upsert_transaction(&row).await {
+                log::warn!("Failed to cache broadcast transaction {}: {}", txid, e);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => log::warn!("Failed to mark {} as pending: {}", txid, e),
+    }
+
+    spawn_watch(app, database.inner().clone(), client, api, endpoint.clone(), row);
+
+    Ok(BroadcastResult { txid, caip: asset_caip, endpoint, already_known })
+}
@@ -1,2 +1,38 @@
-// commands/cache.rs - Cache commands
-pub fn _placeholder() {} 
\ No newline at end of file
+// commands/cache.rs - Portfolio cache maintenance and cache status
+
+use std::sync::Arc;
+use tauri::State;
+use keepkey_db::{CacheStats, Database, IntegrityReport};
+
+/// Run the portfolio integrity audit now (read-only)
+#[tauri::command]
+#[specta::specta]
+pub async fn audit_portfolio_integrity(
+    database: State<'_, Arc<Database>>,
+) -> Result<IntegrityReport, String> {
+    database.audit_portfolio_integrity().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Delete balance rows whose pubkey is no longer in wallet_xpubs; returns the number removed
+#[tauri::command]
+#[specta::specta]
+pub async fn cleanup_orphaned_portfolio_rows(
+    database: State<'_, Arc<Database>>,
+) -> Result<usize, String> {
+    let removed = database.cleanup_orphaned_portfolio_rows().await
+        .map_err(|e| format!("Database error: {}", e))?;
+    if removed > 0 {
+        log::info!("🧹 Removed {} orphaned portfolio balance rows", removed);
+        let _ = database.log_activity("integrity", &format!("Removed {} orphaned portfolio rows", removed), None).await;
+    }
+    Ok(removed)
+}
+
+/// Row counts of the cache tables and each device's frontload state
+#[tauri::command]
+#[specta::specta]
+pub async fn get_cache_status(
+    database: State<'_, Arc<Database>>,
+) -> Result<CacheStats, String> {
+    database.get_cache_stats().await.map_err(|e| format!("Database error: {}", e))
+}
@@ -0,0 +1,206 @@
+// commands/clock.rs - Host clock offset checks for timestamped transactions
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Manager, State};
+use keepkey_db::Database;
+use vault_core::clock::{self, ClockMeasurement, ClockState, ClockStatus, OffsetSource, CLOCK_OFFSET_PREF};
+use vault_core::endpoints::{endpoint_manager, outcome_of, Priority};
+use super::fees::FEE_ENDPOINT_PREF;
+
+const NTP_TIMEOUT: Duration = Duration::from_secs(3);
+const HTTP_DATE_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_TIME_ENDPOINT: &str = "https://mempool.space";
+
+pub fn new_clock_state() -> Arc<ClockState> {
+    Arc::new(ClockState::new())
+}
+
+/// With a proxy configured, UDP to an NTP server would bypass it (and is
+/// usually blocked), so only the proxied HTTP Date check is used. Looks where
+/// reqwest looks: the environment, and the system settings on Windows and
+/// macOS. Blocking on macOS; run off the async runtime.
+fn proxy_configured() -> bool {
+    let from_env = ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy", "HTTP_PROXY", "http_proxy"]
+        .iter()
+        .any(|var| std::env::var(var).map(|v| !v.trim().is_empty()).unwrap_or(false));
+    from_env || system_proxy_configured()
+}
+
+/// Internet Options proxy of the current user, set by hand or as a PAC URL
+#[cfg(windows)]
+fn system_proxy_configured() -> bool {
+    use windows_sys::Win32::Foundation::GlobalFree;
+    use windows_sys::Win32::Networking::WinHttp::{
+        WinHttpGetIEProxyConfigForCurrentUser, WINHTTP_CURRENT_USER_IE_PROXY_CONFIG,
+    };
+
+    // SAFETY: an all-zero config is valid (null strings, no auto-detect)
+    let mut config: WINHTTP_CURRENT_USER_IE_PROXY_CONFIG = unsafe { std::mem::zeroed() };
+    // SAFETY: `config` is a valid out pointer for the duration of the call
+    if unsafe { WinHttpGetIEProxyConfigForCurrentUser(&mut config) } == 0 {
+        return false;
+    }
+    let configured = !config.lpszProxy.is_null() || !config.lpszAutoConfigUrl.is_null();
+    for string in [config.lpszProxy, config.lpszAutoConfigUrl, config.lpszProxyBypass] {
+        if !string.is_null() {
+            // SAFETY: WinHTTP allocated these with GlobalAlloc; the caller frees them
+            unsafe { GlobalFree(string.cast()) };
+        }
+    }
+    configured
+}
+
+/// Proxies enabled in the network settings, as `scutil --proxy` reports them
+#[cfg(target_os = "macos")]
+fn system_proxy_configured() -> bool {
+    const ENABLE_KEYS: [&str; 4] = ["HTTPEnable", "HTTPSEnable", "SOCKSEnable", "ProxyAutoConfigEnable"];
+    std::process::Command::new("scutil")
+        .arg("--proxy")
+        .output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout).lines().any(|line| {
+                let mut fields = line.split(':').map(str::trim);
+                matches!((fields.next(), fields.next()), (Some(key), Some("1")) if ENABLE_KEYS.contains(&key))
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// Elsewhere proxies are configured through the environment
+#[cfg(not(any(windows, target_os = "macos")))]
+fn system_proxy_configured() -> bool {
+    false
+}
+
+/// Endpoints the vault already talks to, whose Date header serves as a fallback reference
+async fn time_endpoints(database: &Database) -> Vec<String> {
+    let mut endpoints = Vec::new();
+    if let Some(url) = database.get_preference(FEE_ENDPOINT_PREF).await.ok().flatten() {
+        if !url.trim().is_empty() {
+            endpoints.push(url.trim().trim_end_matches('/').to_string());
+        }
+    }
+    endpoints.push(DEFAULT_TIME_ENDPOINT.to_string());
+    endpoints.dedup();
+    endpoints
+}
+
+/// Offset from an endpoint's HTTP Date header (1s resolution, which is plenty
+/// for timeouts measured in minutes)
+async fn measure_http_date(client: &reqwest::Client, url: &str, priority: Priority) -> Result<ClockMeasurement, String> {
+    // Waiting for the permit must not count towards the round trip
+    let permit = endpoint_manager().acquire(url, priority).await.map_err(|e| format!("{}: {}", url, e))?;
+    let started = Instant::now();
+    let sent = SystemTime::now();
+    let result = client.head(url).send().await;
+    let received = SystemTime::now();
+    permit.finish(outcome_of(&result));
+    let response = result.map_err(|e| format!("{}: {}", url, e))?;
+
+    let date = response
+        .headers()
+        .get(reqwest::header::DATE)
+        .and_then(|d| d.to_str().ok())
+        .ok_or_else(|| format!("{}: no Date header", url))?;
+    let reference: SystemTime = chrono::DateTime::parse_from_rfc2822(date)
+        .map_err(|e| format!("{}: invalid Date header {:?}: {}", url, date, e))?
+        .with_timezone(&chrono::Utc)
+        .into();
+
+    Ok(ClockMeasurement {
+        offset_ms: clock::offset_from_exchange(sent, received, reference),
+        source: OffsetSource::HttpDate,
+        measured_at: Database::current_timestamp(),
+        round_trip_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+async fn measure_offset(database: &Database, priority: Priority) -> Result<ClockMeasurement, String> {
+    let mut errors = Vec::new();
+
+    if tokio::task::spawn_blocking(proxy_configured).await.unwrap_or(true) {
+        log::info!("🕰️ Proxy configured, checking the clock over HTTP only");
+    } else {
+        match tokio::task::spawn_blocking(|| clock::query_sntp(clock::DEFAULT_NTP_SERVER, NTP_TIMEOUT)).await {
+            Ok(Ok(measurement)) => return Ok(measurement),
+            Ok(Err(e)) => errors.push(format!("NTP: {}", e)),
+            Err(e) => errors.push(format!("NTP: {}", e)),
+        }
+    }
+
+    // reqwest picks up the proxy from the environment and the system settings
+    let client = reqwest::Client::builder()
+        .timeout(HTTP_DATE_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    for endpoint in time_endpoints(database).await {
+        match measure_http_date(&client, &endpoint, priority).await {
+            Ok(measurement) => return Ok(measurement),
+            Err(e) => errors.push(e),
+        }
+    }
+    Err(format!("Could not check the system clock: {}", errors.join("; ")))
+}
+
+/// Seed the clock state from the measurement stored by the last run
+pub async fn load_stored_offset(database: &Database, state: &ClockState) {
+    let stored = database
+        .get_preference(CLOCK_OFFSET_PREF)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str::<ClockMeasurement>(&json).ok());
+    if let Some(measurement) = stored {
+        state.record(measurement);
+    }
+}
+
+/// Measure, store and announce the clock offset
+pub async fn run_clock_check(
+    app: &AppHandle,
+    database: &Database,
+    state: &ClockState,
+    priority: Priority,
+) -> Result<ClockStatus, String> {
+    let measurement = measure_offset(database, priority).await?;
+    log::info!("🕰️ Clock offset {}ms via {:?}", measurement.offset_ms, measurement.source);
+
+    if let Ok(json) = serde_json::to_string(&measurement) {
+        if let Err(e) = database.set_preference(CLOCK_OFFSET_PREF, &json).await {
+            log::warn!("Failed to store clock offset: {}", e);
+        }
+    }
+    state.record(measurement.clone());
+
+    if measurement.exceeds_threshold() {
+        log::warn!("⚠️ System clock is {}s off", measurement.offset_ms / 1000);
+        let payload = serde_json::to_value(&measurement).unwrap_or_default();
+        if let Err(e) = super::emit_or_queue_event(app, "system:clock-offset", payload).await {
+            log::warn!("Failed to emit system:clock-offset: {}", e);
+        }
+    }
+    Ok(state.status(SystemTime::now()))
+}
+
+/// Background check at launch; never blocks startup
+pub fn spawn_startup_clock_check(app: AppHandle, database: Arc<Database>) {
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<Arc<ClockState>>().inner().clone();
+        load_stored_offset(&database, &state).await;
+        if let Err(e) = run_clock_check(&app, &database, &state, Priority::Background).await {
+            log::warn!("🕰️ {}", e);
+        }
+    });
+}
+
+/// Compare the system clock against NTP (or HTTP Date headers) and store the offset
+#[tauri::command]
+#[specta::specta]
+pub async fn check_time_sanity(
+    app: AppHandle,
+    database: State<'_, Arc<Database>>,
+    clock_state: State<'_, Arc<ClockState>>,
+) -> Result<ClockStatus, String> {
+    run_clock_check(&app, &database, &clock_state, Priority::Interactive).await
+}
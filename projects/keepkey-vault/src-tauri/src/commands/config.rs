@@ -1,11 +1,22 @@
 // commands/config.rs - Configuration and onboarding commands
 
 use std::sync::Arc;
-use tauri::State;
-use keepkey_db::Database;
+use tauri::{AppHandle, State};
+use serde::Serialize;
+use keepkey_db::{Database, Preference};
+
+/// Onboarding flags as seen by the backend, for debugging the setup flow
+#[derive(Debug, Serialize, specta::Type)]
+pub struct OnboardingDebugState {
+    pub is_first_time_install: bool,
+    pub is_onboarded: bool,
+    /// RFC 3339 time the state was read
+    pub timestamp: String,
+}
 
 /// Check if this is the first time the app is being installed/run
 #[tauri::command]
+#[specta::specta]
 pub async fn is_first_time_install(
     database: State<'_, Arc<Database>>,
 ) -> Result<bool, String> {
@@ -20,6 +31,7 @@ pub async fn is_first_time_install(
 
 /// Check if the user has completed onboarding
 #[tauri::command]
+#[specta::specta]
 pub async fn is_onboarded(
     database: State<'_, Arc<Database>>,
 ) -> Result<bool, String> {
@@ -34,6 +46,7 @@ pub async fn is_onboarded(
 
 /// Mark onboarding as completed
 #[tauri::command]
+#[specta::specta]
 pub async fn set_onboarding_completed(
     database: State<'_, Arc<Database>>,
 ) -> Result<(), String> {
@@ -51,6 +64,7 @@ pub async fn set_onboarding_completed(
 
 /// Get a user preference value
 #[tauri::command]
+#[specta::specta]
 pub async fn get_preference(
     key: String,
     database: State<'_, Arc<Database>>,
@@ -64,8 +78,10 @@ pub async fn get_preference(
     }
 }
 
-/// Set a user preference value
+/// Set a user preference value; known keys (theme, currency, language, ...)
+/// are validated. Emits `preferences:changed`.
 #[tauri::command]
+#[specta::specta]
 pub async fn set_preference(
     key: String,
     value: String,
@@ -83,17 +99,46 @@ pub async fn set_preference(
     }
 }
 
+/// Every stored preference, with whether the app knows the key
+#[tauri::command]
+#[specta::specta]
+pub async fn get_all_preferences(
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<Preference>, String> {
+    database.get_all_preferences().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Announce every preference written to the database as `preferences:changed`
+pub fn init_preference_events(app: &AppHandle, database: &Database) {
+    let mut changes = database.subscribe_preference_changes();
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let change = match changes.recv().await {
+                Ok(change) => change,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                    log::warn!("Missed {} preference changes", missed);
+                    continue;
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+            let payload = serde_json::json!({ "key": change.key, "value": change.value, "known": change.known });
+            if let Err(e) = super::emit_or_queue_event(&app, "preferences:changed", payload).await {
+                log::warn!("Could not announce the change to preference {}: {}", change.key, e);
+            }
+        }
+    });
+}
+
 /// Debug command to get current onboarding state
 #[tauri::command]
+#[specta::specta]
 pub async fn debug_onboarding_state(
     database: State<'_, Arc<Database>>,
-) -> Result<serde_json::Value, String> {
-    let first_time = database.is_first_time_install().await.unwrap_or(true);
-    let onboarded = database.is_onboarded().await.unwrap_or(false);
-    
-    Ok(serde_json::json!({
-        "is_first_time_install": first_time,
-        "is_onboarded": onboarded,
-        "timestamp": chrono::Utc::now().to_rfc3339()
-    }))
+) -> Result<OnboardingDebugState, String> {
+    Ok(OnboardingDebugState {
+        is_first_time_install: database.is_first_time_install().await.unwrap_or(true),
+        is_onboarded: database.is_onboarded().await.unwrap_or(false),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+    })
 } 
\ No newline at end of file
@@ -0,0 +1,75 @@
+// commands/confirmation.rs - Typed-phrase confirmations for high-risk commands
+
+use std::sync::Arc;
+use tauri::State;
+use keepkey_db::Database;
+use vault_core::confirmation::{self, ConfirmationChallenge, ConfirmationContext, Confirmations, HighRiskOperation};
+
+/// Issue a confirmation token and the phrase the user must type to run `operation`
+#[tauri::command]
+#[specta::specta]
+pub async fn request_confirmation(
+    operation: HighRiskOperation,
+    context: Option<ConfirmationContext>,
+    database: State<'_, Arc<Database>>,
+    confirmations: State<'_, Confirmations>,
+) -> Result<ConfirmationChallenge, String> {
+    issue_confirmation(&database, &confirmations, operation, context.unwrap_or_default().device_id).await
+}
+
+/// Issue a challenge for `operation`, phrased with the device's label when
+/// it targets a registered device
+pub async fn issue_confirmation(
+    database: &Database,
+    confirmations: &Confirmations,
+    operation: HighRiskOperation,
+    device_id: Option<String>,
+) -> Result<ConfirmationChallenge, String> {
+    let label = match &device_id {
+        Some(device_id) => database.get_device_by_id(device_id).await
+            .map_err(|e| format!("Database error: {}", e))?
+            .ok_or_else(|| format!("Unknown device {}", device_id))?
+            .get("label")
+            .and_then(|l| l.as_str())
+            .map(str::to_string),
+        None => None,
+    };
+
+    let phrase = confirmation::confirmation_phrase(operation, label.as_deref());
+    let challenge = confirmations.issue(operation, device_id.as_deref(), phrase)?;
+    log::info!("⚠️ Confirmation requested for {} ({:?})", operation.key(), device_id);
+    Ok(challenge)
+}
+
+/// Validate a submitted confirmation before a high-risk command runs.
+///
+/// Every attempt, accepted or not, is written to the activity log.
+pub async fn require_confirmation(
+    database: &Database,
+    confirmations: &Confirmations,
+    operation: HighRiskOperation,
+    device_id: Option<&str>,
+    token: &str,
+    typed_phrase: &str,
+) -> Result<(), String> {
+    let result = confirmations.verify(token, operation, device_id, typed_phrase);
+
+    let (message, details) = match &result {
+        Ok(()) => (
+            format!("Confirmed {}", operation.key()),
+            serde_json::json!({ "operation": operation, "device_id": device_id, "accepted": true }),
+        ),
+        Err(e) => (
+            format!("Rejected confirmation for {}: {}", operation.key(), e),
+            serde_json::json!({ "operation": operation, "device_id": device_id, "accepted": false, "reason": e.to_string() }),
+        ),
+    };
+    if let Err(e) = database.log_activity("confirmation", &message, Some(&details)).await {
+        log::warn!("Failed to record confirmation in activity log: {}", e);
+    }
+
+    result.map_err(|e| {
+        log::warn!("🚫 {}", message);
+        e.to_string()
+    })
+}
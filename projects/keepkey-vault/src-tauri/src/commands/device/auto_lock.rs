@@ -0,0 +1,90 @@
+// commands/device/auto_lock.rs - Device auto-lock delay and idle locking
+//
+// The idle clocks live in vault_core::device_session and are fed by the
+// device queues; this module applies the delay to the device and runs the
+// ticker that locks devices once their delay has passed.
+
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::{AppHandle, Manager, State};
+use keepkey_db::Database;
+use vault_core::device_flow::DeviceFlowState;
+use vault_core::device_session::{device_sessions, SESSION_CHECK_INTERVAL};
+use vault_core::request_queue::request_queue;
+use vault_core::wallet_session::WalletSessions;
+use crate::commands::DeviceQueueManager;
+
+/// Set how long the device stays unlocked without use (0 turns auto-lock
+/// off); the user confirms on the device
+#[tauri::command]
+#[specta::specta]
+pub async fn set_auto_lock_delay(
+    app: AppHandle,
+    device_id: String,
+    delay_ms: u32,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<(), String> {
+    request_queue().run(&device_id, "set_auto_lock_delay", || {
+        vault_core::device_session::apply_auto_lock_delay(&queue_manager, &device_id, delay_ms)
+    }).await?;
+    log::info!("🔒 Auto-lock delay of device {} set to {}ms", device_id, delay_ms);
+
+    if let Err(e) = vault_core::refresh_stored_features(&database, &device_id, &queue_manager).await {
+        log::warn!("Could not refresh features after changing the auto-lock delay on {}: {}", device_id, e);
+    }
+    crate::commands::emit_or_queue_event(&app, "device:status-changed", serde_json::json!({
+        "device_id": device_id,
+        "auto_lock_delay_ms": delay_ms,
+    })).await
+}
+
+/// Clear the PIN/passphrase session on a device right away
+#[tauri::command]
+#[specta::specta]
+pub async fn lock_device_now(
+    app: AppHandle,
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<(), String> {
+    crate::commands::tray::lock_and_refresh(&app, &database, &queue_manager, &wallet_sessions, &device_id, "user").await
+}
+
+/// Lock devices whose session has been idle past their auto-lock delay
+pub async fn run_device_auto_lock(app: AppHandle, database: Arc<Database>) {
+    let queue_manager = app.state::<DeviceQueueManager>().inner().clone();
+    let wallet_sessions = app.state::<WalletSessions>().inner().clone();
+    let device_flows = app.state::<Arc<DeviceFlowState>>().inner().clone();
+
+    let mut ticker = tokio::time::interval(SESSION_CHECK_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let due = device_sessions().due_for_lock(Instant::now());
+        if due.is_empty() {
+            continue;
+        }
+
+        let connected = crate::commands::tray::connected_device_ids();
+        for device_id in due {
+            // A device re-enumerating mid-flow is locked once it is back
+            if device_flows.is_in_flow(&device_id) {
+                continue;
+            }
+            // Unplugging a KeepKey ends its session
+            if !connected.contains(&device_id) {
+                device_sessions().mark_locked(&device_id);
+                continue;
+            }
+            log::info!("🔒 Device {} idle past its auto-lock delay", device_id);
+            if let Err(e) = crate::commands::tray::lock_and_refresh(
+                &app, &database, &queue_manager, &wallet_sessions, &device_id, "idle",
+            ).await {
+                // The device's own timer has run out too, so there is no point retrying
+                log::warn!("Could not lock idle device {}: {}", device_id, e);
+                device_sessions().mark_locked(&device_id);
+            }
+        }
+    }
+}
@@ -7,10 +7,12 @@ use crate::commands::DeviceQueueManager;
 use super::get_or_create_device_queue;
 use super::get_features::convert_features_to_device_features;
 use keepkey_rust::device_update::{check_bootloader_status, BootloaderCheck};
+use vault_core::request_queue::request_queue;
 
 /// Check device bootloader status and determine if update is needed
 /// SECURITY: This function MUST fail safe - if bootloader version cannot be determined, it MUST return an error
 #[tauri::command]
+#[specta::specta]
 pub async fn check_device_bootloader(
     device_id: String,
     queue_manager: State<'_, DeviceQueueManager>,
@@ -18,10 +20,15 @@ pub async fn check_device_bootloader(
 ) -> Result<BootloaderCheck, String> {
     log::info!("🔍 Checking bootloader status for device: {}", device_id);
     
-    // Get device features first
+    // Get device features first, in turn with other operations on the device
     let queue_handle = get_or_create_device_queue(&device_id, &queue_manager).await?;
-    
-    match queue_handle.get_features().await {
+    let features = request_queue()
+        .run(&device_id, "check_bootloader", || async {
+            queue_handle.get_features().await.map_err(|e| e.to_string())
+        })
+        .await;
+
+    match features {
         Ok(features) => {
             log::info!("✅ Got features for device {}: bootloader_mode={}", device_id, features.bootloader_mode.unwrap_or(false));
             
@@ -0,0 +1,55 @@
+// commands/device/device_export.rs
+
+use std::sync::Arc;
+use serde::Serialize;
+use tauri::State;
+use keepkey_db::Database;
+use vault_core::device_export::{self, ExportValidation, ManifestEntry, EXPORT_SCHEMA_VERSION};
+
+#[derive(Debug, Serialize, specta::Type)]
+pub struct DeviceExportSummary {
+    pub path: String,
+    pub schema_version: u32,
+    pub sections: Vec<ManifestEntry>,
+}
+
+/// Write everything non-secret about a device (xpubs and descriptors,
+/// addresses, labels, transactions) to `path` as one versioned JSON document.
+///
+/// `sections` picks among "xpubs", "addresses", "labels" and "transactions";
+/// None exports all of them.
+#[tauri::command]
+#[specta::specta]
+pub async fn export_device_data(
+    device_id: String,
+    path: String,
+    sections: Option<Vec<String>>,
+    database: State<'_, Arc<Database>>,
+) -> Result<DeviceExportSummary, String> {
+    let sections = device_export::parse_sections(sections.as_deref())?;
+    let data = database.get_device_export_data(&device_id).await
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
+
+    let document = device_export::build_device_export(&data, &sections, Database::current_timestamp())?;
+    let manifest: Vec<ManifestEntry> = serde_json::from_value(document["manifest"]["sections"].clone())
+        .map_err(|e| e.to_string())?;
+    let text = serde_json::to_string_pretty(&document).map_err(|e| e.to_string())?;
+    std::fs::write(&path, text + "\n").map_err(|e| format!("Could not write {}: {}", path, e))?;
+
+    let sections: Vec<&str> = manifest.iter().map(|m| m.section.as_str()).collect();
+    log::info!("📤 Exported {} for device {} to {}", sections.join(", "), device_id, path);
+    if let Err(e) = database.log_activity("export", &format!("Exported device data ({})", sections.join(", ")), None).await {
+        log::warn!("Failed to log export: {}", e);
+    }
+
+    Ok(DeviceExportSummary { path, schema_version: EXPORT_SCHEMA_VERSION, sections: manifest })
+}
+
+/// Check a device export file: its schema version and every section checksum
+#[tauri::command]
+#[specta::specta]
+pub async fn validate_device_export(path: String) -> Result<ExportValidation, String> {
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    device_export::validate_device_export(&text)
+}
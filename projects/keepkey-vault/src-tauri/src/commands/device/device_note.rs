@@ -0,0 +1,38 @@
+// commands/device/device_note.rs
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, DeviceNote};
+
+/// Freeform note attached to a device; None if it has none
+#[tauri::command]
+#[specta::specta]
+pub async fn get_device_note(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+) -> Result<Option<DeviceNote>, String> {
+    database.get_device_note(&device_id).await
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+/// Replace a device's note (markdown, up to 16 KiB). An empty note removes it.
+///
+/// Emits `device:note-changed` so other open windows refresh the note.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_device_note(
+    app: AppHandle,
+    device_id: String,
+    note: String,
+    database: State<'_, Arc<Database>>,
+) -> Result<Option<DeviceNote>, String> {
+    let saved = database.set_device_note(&device_id, &note).await
+        .map_err(|e| e.to_string())?;
+    log::info!("📝 Updated note for device {}", device_id);
+
+    crate::commands::emit_or_queue_event(&app, "device:note-changed", serde_json::json!({
+        "device_id": device_id,
+        "note": saved,
+    })).await?;
+    Ok(saved)
+}
@@ -0,0 +1,46 @@
+// commands/device/forget_device.rs
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+use vault_core::confirmation::{Confirmations, HighRiskOperation};
+use vault_core::wallet_session::{self, WalletSessions};
+use crate::commands::confirmation::require_confirmation;
+
+/// Remove a device and its cached xpubs, balances and history from the vault.
+///
+/// Requires a confirmation from `request_confirmation("forget_device", ...)`.
+/// Nothing is sent to the device; it is registered again if reconnected.
+#[tauri::command]
+#[specta::specta]
+pub async fn forget_device(
+    app: AppHandle,
+    device_id: String,
+    confirmation_token: String,
+    confirmation_phrase: String,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+    confirmations: State<'_, Confirmations>,
+) -> Result<(), String> {
+    require_confirmation(
+        &database,
+        &confirmations,
+        HighRiskOperation::ForgetDevice,
+        Some(&device_id),
+        &confirmation_token,
+        &confirmation_phrase,
+    ).await?;
+
+    let removed = database.forget_device(&device_id).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    if !removed {
+        return Err(format!("Unknown device {}", device_id));
+    }
+    wallet_session::end_wallet_session(&wallet_sessions, &device_id).await;
+    log::info!("🧹 Forgot device {}", device_id);
+
+    crate::commands::emit_or_queue_event(&app, "device:status-changed", serde_json::json!({
+        "device_id": device_id,
+        "forgotten": true,
+    })).await
+}
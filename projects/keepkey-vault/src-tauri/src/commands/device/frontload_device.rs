@@ -0,0 +1,77 @@
+// commands/device/frontload_device.rs
+//
+// The standard account xpubs are read into wallet_xpubs once setup completes,
+// and again on reconnect if a device was unplugged part way. Progress is
+// emitted as device:frontload-progress events and kept in frontload_progress.
+
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+use keepkey_db::{Database, FrontloadProgress};
+use vault_core::frontload::FRONTLOAD_PROGRESS_EVENT;
+use vault_core::wallet_session::WalletSessions;
+use crate::commands::DeviceQueueManager;
+
+async fn run_frontload(
+    app: &AppHandle,
+    database: &Database,
+    wallet_sessions: &WalletSessions,
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+) -> Result<Vec<FrontloadProgress>, String> {
+    // Opened here so a locked device's PIN matrix reaches the PIN flow
+    if vault_core::wallet_session::active_wallet_fingerprint(wallet_sessions, device_id).await.is_empty()
+        && crate::commands::wallets::identify_standard_wallet(app, device_id).await?.is_none()
+    {
+        return Err(format!("Device {} is locked; the frontload resumes once its PIN is entered", device_id));
+    }
+    vault_core::frontload::frontload_device(database, wallet_sessions, queue_manager, device_id, |progress| {
+        match serde_json::to_value(progress) {
+            Ok(payload) => {
+                if let Err(e) = crate::commands::events::emit_event(app, FRONTLOAD_PROGRESS_EVENT, payload) {
+                    log::warn!("Failed to emit frontload progress: {}", e);
+                }
+            }
+            Err(e) => log::warn!("Could not serialize frontload progress: {}", e),
+        }
+    }).await
+}
+
+/// Frontload a device in the background, e.g. once its setup completes
+pub(crate) fn spawn_frontload(app: &AppHandle, device_id: &str) {
+    let app = app.clone();
+    let device_id = device_id.to_string();
+    tokio::spawn(async move {
+        let database = app.state::<Arc<Database>>().inner().clone();
+        let wallet_sessions = app.state::<WalletSessions>().inner().clone();
+        let queue_manager = app.state::<DeviceQueueManager>().inner().clone();
+        if let Err(e) = run_frontload(&app, &database, &wallet_sessions, &queue_manager, &device_id).await {
+            log::warn!("Frontload of device {} stopped: {}", device_id, e);
+        }
+    });
+}
+
+/// Read the standard Bitcoin and Ethereum account xpubs of the device's
+/// active wallet, skipping those already stored. Returns each network's
+/// final progress.
+#[tauri::command]
+#[specta::specta]
+pub async fn frontload_device(
+    device_id: String,
+    app: AppHandle,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<Vec<FrontloadProgress>, String> {
+    run_frontload(&app, &database, &wallet_sessions, &queue_manager, &device_id).await
+}
+
+/// Frontload progress of each network of a device
+#[tauri::command]
+#[specta::specta]
+pub async fn get_frontload_progress(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<FrontloadProgress>, String> {
+    database.get_frontload_progress(&device_id).await
+        .map_err(|e| format!("Database error: {}", e))
+}
@@ -1,2 +1,266 @@
 // commands/device/get_blocking_actions.rs
-pub fn _placeholder() {} 
\ No newline at end of file
+//
+// What the user must do before a device can be used, in the order it has to
+// happen: bootloader, then firmware, then initialization, then the PIN. Built
+// from the same evaluation as get_device_status plus the vault's setup
+// tracking, so the UI does not stitch the checks together itself.
+
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use keepkey_db::Database;
+use keepkey_rust::version::version_triple;
+use crate::commands::DeviceQueueManager;
+use super::get_device_status::{read_device_status, DeviceStatus};
+
+/// A step the user has to take; variants are in the order they must be done
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockingActionKind {
+    BootloaderUpdate,
+    FirmwareUpdate,
+    InitializeDevice,
+    EnterPin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockingSeverity {
+    Critical,
+    High,
+    Medium,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct BlockingAction {
+    pub device_id: String,
+    pub action: BlockingActionKind,
+    pub severity: BlockingSeverity,
+    /// Installed version, for updates
+    pub current: Option<String>,
+    /// Version the update installs
+    pub required: Option<String>,
+    pub message: String,
+}
+
+/// How far behind a bootloader is: a major version is critical, a minor one
+/// high and a patch medium. An unparseable version is treated as critical.
+fn bootloader_severity(current: &str, required: &str) -> BlockingSeverity {
+    match (version_triple(current), version_triple(required)) {
+        (Some((major, minor, _)), Some((req_major, req_minor, _))) => {
+            if major < req_major {
+                BlockingSeverity::Critical
+            } else if major == req_major && minor < req_minor {
+                BlockingSeverity::High
+            } else {
+                BlockingSeverity::Medium
+            }
+        }
+        _ => BlockingSeverity::Critical,
+    }
+}
+
+/// Ordered actions for an evaluated device. `needs_setup` is the vault's own
+/// setup tracking: an initialized device that has not been through the setup
+/// wizard still needs initializing here. A device in bootloader mode does not
+/// report its wallet state, so only updates are listed for it.
+pub fn blocking_actions_for(status: &DeviceStatus, needs_setup: bool) -> Vec<BlockingAction> {
+    let blocking = |action, severity, current: Option<&str>, required: Option<&str>, message: String| BlockingAction {
+        device_id: status.device_id.clone(),
+        action,
+        severity,
+        current: current.map(str::to_string),
+        required: required.map(str::to_string),
+        message,
+    };
+    let mut actions = Vec::new();
+    let Some(features) = status.features.as_ref() else {
+        return actions;
+    };
+
+    if let Some(check) = status.bootloader_check.as_ref().filter(|_| status.needs_bootloader_update) {
+        actions.push(blocking(
+            BlockingActionKind::BootloaderUpdate,
+            bootloader_severity(&check.current_version, &check.latest_version),
+            Some(&check.current_version),
+            Some(&check.latest_version),
+            format!("Bootloader {} must be updated to {}", check.current_version, check.latest_version),
+        ));
+    }
+    if let Some(check) = status.firmware_check.as_ref().filter(|_| status.needs_firmware_update) {
+        // In bootloader mode the installed firmware version is not reported
+        let current = (!features.bootloader_mode).then_some(check.current_version.as_str());
+        actions.push(blocking(
+            BlockingActionKind::FirmwareUpdate,
+            BlockingSeverity::High,
+            current,
+            Some(&check.latest_version),
+            format!("Firmware must be updated to {}", check.latest_version),
+        ));
+    }
+    if features.bootloader_mode {
+        return actions;
+    }
+
+    if status.needs_initialization || needs_setup {
+        let message = if status.needs_initialization {
+            "Device has no wallet; create or recover one"
+        } else {
+            "Device setup has not been completed"
+        };
+        actions.push(blocking(BlockingActionKind::InitializeDevice, BlockingSeverity::High, None, None, message.to_string()));
+    }
+    if status.needs_pin_unlock && !status.needs_initialization {
+        actions.push(blocking(
+            BlockingActionKind::EnterPin,
+            BlockingSeverity::Medium,
+            None,
+            None,
+            "Enter the PIN to unlock the device".to_string(),
+        ));
+    }
+    actions
+}
+
+async fn device_blocking_actions(
+    device_id: String,
+    database: &Database,
+    queue_manager: &DeviceQueueManager,
+) -> Result<Vec<BlockingAction>, String> {
+    let Some(status) = read_device_status(device_id.clone(), queue_manager).await? else {
+        return Ok(Vec::new());
+    };
+    // A device the vault has not registered has not been set up either
+    let needs_setup = database.device_needs_setup(&device_id).await.unwrap_or(true);
+    Ok(blocking_actions_for(&status, needs_setup))
+}
+
+/// Actions blocking a connected device, in the order they must be done;
+/// empty if the device is not connected or ready to use
+#[tauri::command]
+#[specta::specta]
+pub async fn get_blocking_actions(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<Vec<BlockingAction>, String> {
+    device_blocking_actions(device_id, &database, &queue_manager).await
+}
+
+/// Blocking actions of every connected KeepKey, device by device
+#[tauri::command]
+#[specta::specta]
+pub async fn get_all_blocking_actions(
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<Vec<BlockingAction>, String> {
+    let mut actions = Vec::new();
+    for device in keepkey_rust::features::list_connected_devices() {
+        match device_blocking_actions(device.unique_id.clone(), &database, &queue_manager).await {
+            Ok(device_actions) => actions.extend(device_actions),
+            Err(e) => log::warn!("Could not check blocking actions of {}: {}", device.unique_id, e),
+        }
+    }
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::get_device_status::evaluate_device_status;
+    use keepkey_rust::features::DeviceFeatures;
+    use vault_core::firmware_catalog::bundled_releases;
+    use BlockingActionKind::*;
+    use BlockingSeverity::*;
+
+    /// Same features as the keepkey-db bootloader test scenarios
+    fn features(device_id: &str, version: &str, bootloader_mode: bool, initialized: bool) -> DeviceFeatures {
+        serde_json::from_value(serde_json::json!({
+            "vendor": "KeepKey",
+            "model": "KeepKey",
+            "label": format!("Test Device {}", device_id),
+            "firmwareVariant": None::<String>,
+            "deviceId": device_id,
+            "language": "english",
+            "bootloaderMode": bootloader_mode,
+            "version": version,
+            "firmwareHash": "abc123def456",
+            "bootloaderHash": "789xyz012",
+            "bootloaderVersion": "2.1.4",
+            "initialized": initialized,
+            "imported": false,
+            "noBackup": false,
+            "pinProtection": true,
+            "pinCached": false,
+            "passphraseProtection": false,
+            "passphraseCached": false,
+            "wipeCodeProtection": false,
+            "autoLockDelayMs": 600000,
+            "policies": []
+        }))
+        .unwrap()
+    }
+
+    fn actions(features: &DeviceFeatures, needs_setup: bool) -> Vec<(BlockingActionKind, BlockingSeverity)> {
+        let status = evaluate_device_status("kk1".to_string(), Some(features), bundled_releases().unwrap());
+        blocking_actions_for(&status, needs_setup).iter().map(|a| (a.action, a.severity)).collect()
+    }
+
+    #[test]
+    fn test_bootloader_scenarios() {
+        let scenarios = [
+            ("1.9.0", true, false, vec![(BootloaderUpdate, Critical)]),
+            ("2.0.5", true, false, vec![(BootloaderUpdate, High)]),
+            ("2.1.3", true, false, vec![(BootloaderUpdate, Medium)]),
+            ("2.1.4", false, true, vec![(EnterPin, Medium)]),
+            ("7.6.0", false, true, vec![(EnterPin, Medium)]),
+            ("7.5.0", false, true, vec![(EnterPin, Medium)]),
+        ];
+        for (version, bootloader_mode, initialized, expected) in scenarios {
+            assert_eq!(actions(&features("kk1", version, bootloader_mode, initialized), false), expected, "{}", version);
+        }
+
+        let status = evaluate_device_status("kk1".to_string(), Some(&features("kk1", "1.9.0", true, false)), bundled_releases().unwrap());
+        let bootloader = &blocking_actions_for(&status, true)[0];
+        assert_eq!((bootloader.current.as_deref(), bootloader.required.as_deref()), (Some("1.9.0"), Some("2.1.4")));
+        let json = serde_json::to_value(bootloader).unwrap();
+        assert_eq!((json["action"].as_str(), json["severity"].as_str()), (Some("bootloader_update"), Some("critical")));
+    }
+
+    #[test]
+    fn test_actions_are_ordered() {
+        // An up to date bootloader in bootloader mode is waiting for firmware
+        assert_eq!(actions(&features("kk1", "2.1.4", true, false), true), [(FirmwareUpdate, High)]);
+
+        // Normal mode with an old bootloader, no wallet yet
+        let mut old = features("kk1", "7.5.0", false, false);
+        old.bootloader_version = Some("1.0.3".to_string());
+        assert_eq!(actions(&old, true), [(BootloaderUpdate, Critical), (InitializeDevice, High)]);
+
+        // Wallet on the device but the vault's setup is unfinished
+        let locked = features("kk1", "7.6.0", false, true);
+        assert_eq!(actions(&locked, true), [(InitializeDevice, High), (EnterPin, Medium)]);
+
+        let mut unlocked = locked.clone();
+        unlocked.pin_cached = true;
+        assert!(actions(&unlocked, false).is_empty());
+        assert!(blocking_actions_for(&evaluate_device_status("kk1".to_string(), None, bundled_releases().unwrap()), true).is_empty());
+
+        for (version, bootloader_mode, initialized) in [("1.9.0", true, false), ("7.5.0", false, false)] {
+            let kinds: Vec<_> = actions(&features("kk1", version, bootloader_mode, initialized), true)
+                .into_iter().map(|(kind, _)| kind).collect();
+            assert!(kinds.windows(2).all(|w| w[0] < w[1]), "{:?}", kinds);
+        }
+    }
+
+    #[test]
+    fn test_latest_versions_come_from_the_catalog() {
+        let mut unknown = features("kk1", "7.5.0", false, true);
+        unknown.bootloader_version = None;
+        let status = evaluate_device_status("kk1".to_string(), Some(&unknown), bundled_releases().unwrap());
+        // A bootloader releases.json does not know is not reported as up to date
+        let bootloader = status.bootloader_check.unwrap();
+        assert_eq!((bootloader.latest_version.as_str(), bootloader.needs_investigation), ("2.1.4", true));
+        assert_eq!(status.firmware_check.unwrap().latest_version, "7.10.0");
+    }
+}
@@ -1,8 +1,16 @@
 // commands/device/get_connected_devices.rs
 
+use std::sync::Arc;
+use tauri::State;
+use keepkey_db::Database;
 use serde::{Serialize, Deserialize};
+use crate::commands::DeviceQueueManager;
+use super::get_or_create_device_queue;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Preference holding the developer device id override; empty when unset
+const DEVICE_ID_OVERRIDE_KEY: &str = "device_id_override";
+
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
 pub struct ConnectedDevice {
     pub device_id: String,
     pub name: String,
@@ -12,13 +20,45 @@ pub struct ConnectedDevice {
     pub is_keepkey: bool,
 }
 
+async fn developer_mode(database: &Database) -> bool {
+    database.get_preference("developer_mode").await.ok().flatten()
+        .map(|v| v == "true")
+        .unwrap_or(false)
+}
+
+/// The device id override, if developer mode is on and one is set
+async fn active_device_id_override(database: &Database) -> Option<String> {
+    if !developer_mode(database).await {
+        return None;
+    }
+    database.get_preference(DEVICE_ID_OVERRIDE_KEY).await.ok().flatten()
+        .filter(|id| !id.is_empty())
+}
+
+/// Report a lone device under `override_id`. With several devices connected
+/// the real ids are kept, so commands still reach the right unit.
+fn apply_device_id_override(mut devices: Vec<ConnectedDevice>, override_id: Option<&str>) -> Vec<ConnectedDevice> {
+    match (override_id, devices.as_mut_slice()) {
+        (Some(override_id), [device]) => device.device_id = override_id.to_string(),
+        (Some(_), devices) if devices.len() > 1 => {
+            log::warn!("⚠️ Device id override ignored: {} devices connected", devices.len());
+        }
+        _ => {}
+    }
+    devices
+}
+
 /// Get connected devices
 #[tauri::command]
-pub async fn get_connected_devices() -> Result<Vec<ConnectedDevice>, String> {
+#[specta::specta]
+pub async fn get_connected_devices(
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<Vec<ConnectedDevice>, String> {
     println!("🔍 Getting connected devices");
-    
+
     let devices = keepkey_rust::features::list_connected_devices();
-    
+
     let connected_devices: Vec<ConnectedDevice> = devices
         .into_iter()
         .filter(|device| device.is_keepkey)
@@ -33,7 +73,101 @@ pub async fn get_connected_devices() -> Result<Vec<ConnectedDevice>, String> {
             }
         })
         .collect();
-    
+
+    let override_id = active_device_id_override(&database).await;
+    let real_id = match connected_devices.as_slice() {
+        [device] => Some(device.device_id.clone()),
+        _ => None,
+    };
+    let connected_devices = apply_device_id_override(connected_devices, override_id.as_deref());
+
+    // Route commands sent to the override id to the real device's queue
+    if let (Some(override_id), Some(real_id)) = (&override_id, &real_id) {
+        match get_or_create_device_queue(real_id, &queue_manager).await {
+            Ok(handle) => {
+                queue_manager.lock().await.insert(override_id.clone(), handle);
+            }
+            Err(e) => log::warn!("Device id override not routed to {}: {}", real_id, e),
+        }
+    }
+
     println!("✅ Found {} connected KeepKey devices", connected_devices.len());
     Ok(connected_devices)
-} 
\ No newline at end of file
+}
+
+/// Report a single connected device under a fixed id, so development data
+/// follows whichever KeepKey is plugged in. None clears it. Developer mode only.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_device_id_override(
+    device_id: Option<String>,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    if !developer_mode(&database).await {
+        return Err("Overriding the device id requires developer mode".to_string());
+    }
+    let device_id = device_id.map(|id| id.trim().to_string()).unwrap_or_default();
+    database.set_preference(DEVICE_ID_OVERRIDE_KEY, &device_id).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    if device_id.is_empty() {
+        log::info!("🧪 Device id override cleared");
+    } else {
+        log::info!("🧪 Device id override set to {}", device_id);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::Manager;
+
+    fn device(id: &str) -> ConnectedDevice {
+        ConnectedDevice {
+            device_id: id.to_string(),
+            name: "KeepKey".to_string(),
+            manufacturer: Some("KeyHodlers, LLC".to_string()),
+            vid: 0x2b24,
+            pid: 0x0002,
+            is_keepkey: true,
+        }
+    }
+
+    fn ids(devices: &[ConnectedDevice]) -> Vec<&str> {
+        devices.iter().map(|d| d.device_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_override_only_applies_to_a_single_device() {
+        let two = vec![device("kk1"), device("kk2")];
+        assert_eq!(ids(&apply_device_id_override(two.clone(), None)), ["kk1", "kk2"]);
+        // Two units must never share an id
+        assert_eq!(ids(&apply_device_id_override(two, Some("dev-kk"))), ["kk1", "kk2"]);
+
+        assert_eq!(ids(&apply_device_id_override(vec![device("kk1")], None)), ["kk1"]);
+        assert_eq!(ids(&apply_device_id_override(vec![device("kk1")], Some("dev-kk"))), ["dev-kk"]);
+        assert!(apply_device_id_override(Vec::new(), Some("dev-kk")).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_override_requires_developer_mode() {
+        let app = tauri::test::mock_app();
+        app.manage(Arc::new(Database::new_in_memory().await.unwrap()));
+        let database = app.state::<Arc<Database>>();
+
+        assert!(set_device_id_override(Some("dev-kk".into()), app.state()).await.is_err());
+        assert_eq!(active_device_id_override(&database).await, None);
+
+        database.set_preference("developer_mode", "true").await.unwrap();
+        set_device_id_override(Some(" dev-kk ".into()), app.state()).await.unwrap();
+        assert_eq!(active_device_id_override(&database).await.as_deref(), Some("dev-kk"));
+
+        // Leaving developer mode turns the override off without clearing it
+        database.set_preference("developer_mode", "false").await.unwrap();
+        assert_eq!(active_device_id_override(&database).await, None);
+
+        database.set_preference("developer_mode", "true").await.unwrap();
+        set_device_id_override(None, app.state()).await.unwrap();
+        assert_eq!(active_device_id_override(&database).await, None);
+    }
+}
@@ -0,0 +1,23 @@
+// commands/device/get_connection_history.rs
+
+use std::sync::Arc;
+use tauri::State;
+use keepkey_db::{Database, DeviceConnection};
+
+/// Connections returned when no limit is given
+const HISTORY_PAGE: u32 = 50;
+const MAX_HISTORY: u32 = 500;
+
+/// When a device was plugged in and for how long, newest first; the
+/// connection still open has no duration
+#[tauri::command]
+#[specta::specta]
+pub async fn get_connection_history(
+    device_id: String,
+    limit: Option<u32>,
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<DeviceConnection>, String> {
+    let limit = limit.unwrap_or(HISTORY_PAGE).min(MAX_HISTORY);
+    database.get_connection_history(&device_id, limit).await
+        .map_err(|e| format!("Database error: {}", e))
+}
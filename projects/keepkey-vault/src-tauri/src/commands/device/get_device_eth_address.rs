@@ -0,0 +1,52 @@
+// commands/device/get_device_eth_address.rs
+//
+// The first Ethereum address (m/44'/60'/0'/0/0) is stored with the device's
+// registry entry when setup completes, so later launches can show it without
+// asking the device.
+
+use std::sync::Arc;
+use tauri::State;
+use keepkey_db::Database;
+use vault_core::paths::HARDENED;
+use crate::commands::DeviceQueueManager;
+use super::get_or_create_device_queue;
+
+/// m/44'/60'/0'/0/0
+const FIRST_ETH_ADDRESS_PATH: [u32; 5] = [44 | HARDENED, 60 | HARDENED, HARDENED, 0, 0];
+
+/// The device's first Ethereum address, from the registry unless
+/// `force_refresh` is set (e.g. the cached one may predate a wipe). A freshly
+/// read address is saved and marks the device's setup complete. None if the
+/// device returned no address.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_device_eth_address(
+    device_id: String,
+    force_refresh: bool,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<Option<String>, String> {
+    if !force_refresh {
+        let cached = database.get_device_eth_address(&device_id).await
+            .map_err(|e| format!("Database error: {}", e))?;
+        if let Some(address) = cached.filter(|a| !a.is_empty()) {
+            return Ok(Some(address));
+        }
+    }
+
+    let queue_handle = get_or_create_device_queue(&device_id, &queue_manager).await?;
+    let address = queue_handle
+        .get_address(FIRST_ETH_ADDRESS_PATH.to_vec(), "Ethereum".to_string(), None, Some(false))
+        .await
+        .map_err(|e| format!("Failed to get ETH address from device: {}", e))?;
+    if address.is_empty() {
+        return Ok(None);
+    }
+
+    if let Err(e) = database.mark_device_setup_complete(&device_id, Some(&address)).await {
+        log::warn!("Could not cache ETH address for {}: {}", device_id, e);
+    } else {
+        log::info!("📝 Cached ETH address for device {}", device_id);
+    }
+    Ok(Some(address))
+}
@@ -1,2 +1,106 @@
 // commands/device/get_device_info_by_id.rs
-pub fn _placeholder() {} 
\ No newline at end of file
+
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use keepkey_db::{Database, DeviceHealthCheck, DeviceNote};
+use keepkey_rust::features::DeviceFeatures;
+use vault_core::authenticity::{self, DeviceAuthenticity};
+
+/// Registry columns read back from `get_device_by_id`
+#[derive(Deserialize)]
+struct DeviceRecord {
+    device_id: String,
+    vendor: Option<String>,
+    model: Option<String>,
+    label: Option<String>,
+    firmware_variant: Option<String>,
+    firmware_version: Option<String>,
+    bootloader_mode: bool,
+    initialized: bool,
+    pin_protection: bool,
+    passphrase_protection: bool,
+    first_seen: i64,
+    last_seen: i64,
+    features: Option<String>,
+    serial_number: Option<String>,
+    setup_complete: bool,
+}
+
+/// A registered device as last seen, with its latest self-test result
+#[derive(Debug, Serialize, specta::Type)]
+pub struct DeviceInfo {
+    pub device_id: String,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub label: Option<String>,
+    pub firmware_variant: Option<String>,
+    pub firmware_version: Option<String>,
+    /// Same as `firmware_version`, for callers that read `version`
+    pub version: Option<String>,
+    pub bootloader_mode: bool,
+    pub initialized: bool,
+    pub pin_protection: bool,
+    pub passphrase_protection: bool,
+    pub first_seen: i64,
+    pub last_seen: i64,
+    pub serial_number: Option<String>,
+    pub setup_complete: bool,
+    /// Features stored at the last connection
+    pub features: Option<DeviceFeatures>,
+    /// None if the device was never self-tested
+    pub health_check: Option<DeviceHealthCheck>,
+    /// Vendor and firmware attestation of the stored features; see
+    /// `vault_core::authenticity`
+    pub authenticity: Option<DeviceAuthenticity>,
+    /// User note from the device detail page
+    pub note: Option<DeviceNote>,
+}
+
+/// Registry record of a device with its stored features, the latest
+/// self-test result and its note.
+///
+/// Works for disconnected devices; nothing is sent to the device.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_device_info_by_id(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+) -> Result<DeviceInfo, String> {
+    let device = database.get_device_by_id(&device_id).await
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("Unknown device {}", device_id))?;
+    let record: DeviceRecord = serde_json::from_value(device)
+        .map_err(|e| format!("Invalid device record: {}", e))?;
+
+    let health_check = database.latest_health_check(&device_id).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    let note = database.get_device_note(&device_id).await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    // Features are stored as a JSON string
+    let features: Option<DeviceFeatures> = record.features.as_deref().and_then(|raw| serde_json::from_str(raw).ok());
+    let authenticity = features.as_ref().map(authenticity::assess_features);
+
+    Ok(DeviceInfo {
+        device_id: record.device_id,
+        vendor: record.vendor,
+        model: record.model,
+        label: record.label,
+        firmware_variant: record.firmware_variant,
+        version: record.firmware_version.clone(),
+        firmware_version: record.firmware_version,
+        bootloader_mode: record.bootloader_mode,
+        initialized: record.initialized,
+        pin_protection: record.pin_protection,
+        passphrase_protection: record.passphrase_protection,
+        first_seen: record.first_seen,
+        last_seen: record.last_seen,
+        serial_number: record.serial_number,
+        setup_complete: record.setup_complete,
+        features,
+        health_check,
+        authenticity,
+        note,
+    })
+}
@@ -2,9 +2,11 @@ use serde::{Deserialize, Serialize};
 use crate::commands::DeviceQueueManager;
 use super::get_or_create_device_queue;
 use tauri::State;
+use keepkey_rust::version::{compare_versions, parse_version, ParsedVersion, VersionComparison};
+use vault_core::firmware_catalog::{bundled_releases, ImageKind, ReleasesCatalog};
 
 // DeviceStatus and related structs
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
 #[serde(rename_all = "camelCase")]
 pub struct DeviceStatus {
     pub device_id: String,
@@ -14,28 +16,36 @@ pub struct DeviceStatus {
     pub needs_firmware_update: bool,
     pub needs_initialization: bool,
     pub needs_pin_unlock: bool,
+    /// Vendor/model and firmware hash both failed; signing and xpub export are refused
+    pub vendor_mismatch: bool,
     pub bootloader_check: Option<BootloaderCheck>,
     pub firmware_check: Option<FirmwareCheck>,
     pub initialization_check: Option<InitializationCheck>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
 #[serde(rename_all = "camelCase")]
+// keepkey_rust::device_update::BootloaderCheck is exported under the plain name
+#[specta(rename = "DeviceStatusBootloaderCheck")]
 pub struct BootloaderCheck {
     pub current_version: String,
     pub latest_version: String,
     pub needs_update: bool,
+    /// The reported version could not be parsed (e.g. a raw hash); not treated as up to date
+    pub needs_investigation: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
 #[serde(rename_all = "camelCase")]
 pub struct FirmwareCheck {
     pub current_version: String,
     pub latest_version: String,
     pub needs_update: bool,
+    /// The reported version could not be parsed (e.g. a raw hash); not treated as up to date
+    pub needs_investigation: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
 #[serde(rename_all = "camelCase")]
 pub struct InitializationCheck {
     pub initialized: bool,
@@ -44,8 +54,13 @@ pub struct InitializationCheck {
     pub needs_setup: bool,
 }
 
-/// Evaluate device status to determine what actions are needed
-pub fn evaluate_device_status(device_id: String, features: Option<&keepkey_rust::features::DeviceFeatures>) -> DeviceStatus {
+/// Evaluate device status against the latest releases in `releases` to
+/// determine what actions are needed
+pub fn evaluate_device_status(
+    device_id: String,
+    features: Option<&keepkey_rust::features::DeviceFeatures>,
+    releases: &ReleasesCatalog,
+) -> DeviceStatus {
     let mut status = DeviceStatus {
         device_id: device_id.clone(),
         connected: true,
@@ -54,41 +69,43 @@ pub fn evaluate_device_status(device_id: String, features: Option<&keepkey_rust:
         needs_firmware_update: false,
         needs_initialization: false,
         needs_pin_unlock: false,
+        vendor_mismatch: false,
         bootloader_check: None,
         firmware_check: None,
         initialization_check: None,
     };
     
     if let Some(features) = features {
-        let latest_bootloader_version = "2.1.4".to_string();
-        
-        // Get current bootloader version
-        let current_bootloader_version = features.bootloader_version.clone().unwrap_or_else(|| {
-            if features.bootloader_mode {
-                features.version.clone()
-            } else if features.version.starts_with("1.0.") {
-                features.version.clone()
-            } else {
-                "2.1.4".to_string()
-            }
-        });
+        let latest_bootloader_version = releases.latest_version(ImageKind::Bootloader);
         
-        // Check if bootloader needs update
-        let needs_bootloader_update = if features.bootloader_mode {
-            current_bootloader_version.starts_with("1.")
-        } else if current_bootloader_version == "Unknown bootloader" {
-            false
+        // Get current bootloader version; in bootloader mode `version` is the
+        // running bootloader's own, which beats a version derived from a hash.
+        // A hash releases.json does not know is left unknown, not assumed current.
+        let current_bootloader_version = if features.bootloader_mode {
+            features.version.clone()
         } else {
-            // Simple version comparison for now
-            current_bootloader_version != latest_bootloader_version && 
-            !current_bootloader_version.starts_with("2.1.")
+            features.bootloader_version.clone().unwrap_or_else(|| {
+                if features.version.starts_with("1.0.") {
+                    features.version.clone()
+                } else {
+                    "unknown".to_string()
+                }
+            })
         };
         
+        // Check if bootloader needs update
+        let bootloader_comparison = compare_versions(&current_bootloader_version, &latest_bootloader_version);
+        let needs_bootloader_update = bootloader_comparison == VersionComparison::Less;
+        if bootloader_comparison == VersionComparison::Unparseable {
+            log::warn!("⚠️ Device {} reports unparseable bootloader version '{}'", device_id, current_bootloader_version);
+        }
+        
         status.needs_bootloader_update = needs_bootloader_update;
         status.bootloader_check = Some(BootloaderCheck {
             current_version: current_bootloader_version.clone(),
             latest_version: latest_bootloader_version,
             needs_update: needs_bootloader_update,
+            needs_investigation: bootloader_comparison == VersionComparison::Unparseable,
         });
         
         // Check firmware status
@@ -96,8 +113,11 @@ pub fn evaluate_device_status(device_id: String, features: Option<&keepkey_rust:
         status.needs_firmware_update = needs_firmware_update;
         status.firmware_check = Some(FirmwareCheck {
             current_version: features.version.clone(),
-            latest_version: "4.0.0".to_string(), // Current latest firmware
+            latest_version: releases.latest_version(ImageKind::Firmware),
             needs_update: needs_firmware_update,
+            // In bootloader mode `version` is the bootloader's, checked above
+            needs_investigation: !features.bootloader_mode
+                && matches!(parse_version(&features.version), ParsedVersion::Unparseable(_)),
         });
         
         // Check initialization status
@@ -113,6 +133,8 @@ pub fn evaluate_device_status(device_id: String, features: Option<&keepkey_rust:
         
         // Check PIN status
         status.needs_pin_unlock = features.pin_protection && !features.pin_cached;
+
+        status.vendor_mismatch = vault_core::authenticity::assess_features(features).vendor_mismatch();
     }
     
     status
@@ -120,9 +142,19 @@ pub fn evaluate_device_status(device_id: String, features: Option<&keepkey_rust:
 
 /// Get device status command
 #[tauri::command]
+#[specta::specta]
 pub async fn get_device_status(
     device_id: String,
     queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<Option<DeviceStatus>, String> {
+    read_device_status(device_id, &queue_manager).await
+}
+
+/// Status of a connected device from freshly read features; None if the
+/// device is not connected
+pub(crate) async fn read_device_status(
+    device_id: String,
+    queue_manager: &DeviceQueueManager,
 ) -> Result<Option<DeviceStatus>, String> {
     log::info!("Getting device status for: {}", device_id);
     
@@ -138,29 +170,30 @@ pub async fn get_device_status(
         log::info!("🔍 Found device for status check: {}", device_info.unique_id);
         
         // Get or create device queue handle
-        let queue_handle = get_or_create_device_queue(&device_id, &queue_manager).await?;
+        let queue_handle = get_or_create_device_queue(&device_id, queue_manager).await?;
         
-        // Fetch device features through the queue
-        let features = match tokio::time::timeout(
-            std::time::Duration::from_secs(10),
-            queue_handle.get_features()
-        ).await {
-            Ok(Ok(raw_features)) => {
+        // Fetch device features through the queue, in turn with other operations
+        let features = match vault_core::request_queue::request_queue()
+            .run_with_timeout(&device_id, "get_device_status", std::time::Duration::from_secs(10), || async {
+                queue_handle.get_features().await.map_err(|e| e.to_string())
+            })
+            .await
+        {
+            Ok(raw_features) => {
                 // Convert features to our format
-                Some(crate::commands::device::get_features::convert_features_to_device_features(raw_features))
+                let features = crate::commands::device::get_features::convert_features_to_device_features(raw_features);
+                vault_core::authenticity::authenticity_registry()
+                    .record(&device_id, vault_core::authenticity::assess_features(&features));
+                Some(features)
             }
-            Ok(Err(e)) => {
+            Err(e) => {
                 log::error!("Failed to get features for device {}: {}", device_id, e);
                 None
             }
-            Err(_) => {
-                log::error!("Timeout getting features for device {}", device_id);
-                None
-            }
         };
         
         // Evaluate device status
-        let status = evaluate_device_status(device_id.clone(), features.as_ref());
+        let status = evaluate_device_status(device_id.clone(), features.as_ref(), bundled_releases()?);
         
         Ok(Some(status))
     } else {
@@ -4,7 +4,7 @@ use keepkey_db::Database;
 use crate::commands::DeviceQueueManager;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, specta::Type)]
 pub struct DeviceNeedingSetup {
     pub device_id: String,
     pub device_name: String,
@@ -12,6 +12,7 @@ pub struct DeviceNeedingSetup {
 }
 
 #[tauri::command]
+#[specta::specta]
 pub async fn get_devices_needing_setup(
     database: tauri::State<'_, Arc<Database>>,
 ) -> Result<Vec<DeviceNeedingSetup>, String> {
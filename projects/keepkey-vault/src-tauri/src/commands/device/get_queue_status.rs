@@ -1,2 +1,31 @@
 // commands/device/get_queue_status.rs
-pub fn _placeholder() {} 
\ No newline at end of file
+
+use std::sync::Arc;
+use tauri::State;
+use vault_core::device_flow::DeviceFlowState;
+use vault_core::queue::DeviceQueueStatus;
+use crate::commands::DeviceQueueManager;
+
+/// Whether a device's queue has a worker, what it is running, how many
+/// requests and serialized operations wait behind it and when the device
+/// last answered. Lets the UI tell a busy or re-enumerating device from a
+/// stuck queue.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_queue_status(
+    device_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+    device_flows: State<'_, Arc<DeviceFlowState>>,
+) -> Result<DeviceQueueStatus, String> {
+    Ok(vault_core::queue::queue_status(&queue_manager, &device_flows, &device_id).await)
+}
+
+/// Queue status of every device with a worker
+#[tauri::command]
+#[specta::specta]
+pub async fn all_queue_statuses(
+    queue_manager: State<'_, DeviceQueueManager>,
+    device_flows: State<'_, Arc<DeviceFlowState>>,
+) -> Result<Vec<DeviceQueueStatus>, String> {
+    Ok(vault_core::queue::all_queue_statuses(&queue_manager, &device_flows).await)
+}
@@ -0,0 +1,143 @@
+// commands/device/get_xpub.rs
+//
+// Extended public keys are cached in cached_pubkeys per wallet, so a
+// passphrase wallet never sees the standard wallet's keys. Without an active
+// wallet session the key is read from the device and not cached.
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+use keepkey_rust::messages::{self, Message};
+use vault_core::paths::{format_derivation_path, parse_derivation_path};
+use vault_core::utxo::UtxoScriptType;
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use crate::commands::DeviceQueueManager;
+use super::get_or_create_device_queue;
+
+/// Extended public key for `path` (e.g. `m/84'/0'/0'`) on the device's active
+/// wallet. `script_type` is a derivation_paths name such as `p2wpkh`.
+///
+/// `show_display` shows the key on the device for verification and always
+/// asks the device; `bypass_cache` re-reads it without showing it. Either way
+/// the fresh key replaces the cached one.
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_xpub(
+    app: AppHandle,
+    device_id: String,
+    path: String,
+    script_type: Option<String>,
+    coin_name: String,
+    show_display: Option<bool>,
+    bypass_cache: Option<bool>,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<String, String> {
+    let address_n = parse_derivation_path(&path)?;
+    // `84h/0h/0h` and `m/84'/0'/0'` share a cache entry
+    let path = format_derivation_path(&address_n);
+    let show_display = show_display.unwrap_or(false);
+    let fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+
+    if !show_display && !bypass_cache.unwrap_or(false) {
+        if let Some(xpub) = lookup_cached(&database, &device_id, &fingerprint, &path, script_type.as_deref(), &coin_name).await? {
+            return Ok(xpub);
+        }
+    }
+    let xpub = read_from_device(&app, &queue_manager, &device_id, address_n, script_type.as_deref(), &coin_name, show_display).await?;
+    store_cached(&database, &device_id, &fingerprint, &path, script_type.as_deref(), &coin_name, &xpub).await;
+    Ok(xpub)
+}
+
+/// Extended public key for `path` on the wallet `fingerprint`, read from the
+/// cache when it is there and from the device (without display) otherwise
+#[allow(clippy::too_many_arguments)]
+pub async fn account_xpub(
+    app: &AppHandle,
+    database: &Database,
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+    fingerprint: &str,
+    address_n: &[u32],
+    script_type: &str,
+    coin_name: &str,
+) -> Result<String, String> {
+    let path = format_derivation_path(address_n);
+    if let Some(xpub) = lookup_cached(database, device_id, fingerprint, &path, Some(script_type), coin_name).await? {
+        return Ok(xpub);
+    }
+    let xpub = read_from_device(app, queue_manager, device_id, address_n.to_vec(), Some(script_type), coin_name, false).await?;
+    store_cached(database, device_id, fingerprint, &path, Some(script_type), coin_name, &xpub).await;
+    Ok(xpub)
+}
+
+async fn lookup_cached(
+    database: &Database,
+    device_id: &str,
+    fingerprint: &str,
+    path: &str,
+    script_type: Option<&str>,
+    coin_name: &str,
+) -> Result<Option<String>, String> {
+    if fingerprint.is_empty() {
+        return Ok(None);
+    }
+    database
+        .get_cached_pubkey(device_id, fingerprint, path, coin_name, script_type)
+        .await
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+async fn store_cached(
+    database: &Database,
+    device_id: &str,
+    fingerprint: &str,
+    path: &str,
+    script_type: Option<&str>,
+    coin_name: &str,
+    xpub: &str,
+) {
+    if fingerprint.is_empty() {
+        return;
+    }
+    if let Err(e) = database.insert_cached_pubkey(device_id, fingerprint, path, coin_name, script_type, xpub).await {
+        log::warn!("Could not cache xpub for {} {}: {}", device_id, path, e);
+    }
+}
+
+/// Ask a verified device for the key. A locked device's PIN matrix goes to
+/// the PIN flow and the read fails until the PIN is entered.
+async fn read_from_device(
+    app: &AppHandle,
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+    address_n: Vec<u32>,
+    script_type: Option<&str>,
+    coin_name: &str,
+    show_display: bool,
+) -> Result<String, String> {
+    vault_core::authenticity::require_verified_device(device_id)?;
+    let script = script_type.map(UtxoScriptType::parse).transpose()?;
+    let queue_handle = get_or_create_device_queue(device_id, queue_manager).await?;
+    let request = Message::GetPublicKey(messages::GetPublicKey {
+        address_n,
+        ecdsa_curve_name: Some("secp256k1".to_string()),
+        show_display: Some(show_display),
+        coin_name: Some(coin_name.to_string()),
+        script_type: script.map(|s| s.input_script_type()),
+    });
+    match queue_handle.send_raw(request, true).await.map_err(|e| e.to_string())? {
+        Message::PublicKey(public_key) => public_key.xpub.ok_or_else(|| "No xpub in response".to_string()),
+        Message::PinMatrixRequest(request) => {
+            crate::commands::pin::request_pin_entry(app, device_id, request).await?;
+            Err(format!("Device {} is locked; enter its PIN and try again", device_id))
+        }
+        Message::PassphraseRequest(_) => {
+            Err("The device is waiting for a passphrase; open the wallet first".to_string())
+        }
+        Message::Failure(f) => Err(format!("Device refused: {}", f.message())),
+        other => Err(format!("Unexpected response: {:?}", other.message_type())),
+    }
+}
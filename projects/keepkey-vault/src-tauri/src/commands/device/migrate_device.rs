@@ -0,0 +1,89 @@
+// commands/device/migrate_device.rs
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, DeviceMigrationSummary};
+use vault_core::wallet_session::{self, WalletSessions};
+
+fn is_connected(device_id: &str) -> bool {
+    keepkey_rust::features::list_connected_devices()
+        .iter()
+        .any(|d| d.unique_id == device_id)
+}
+
+/// Move labels, xpubs, portfolio rows and history from a replaced device to
+/// its replacement, then archive the old device.
+///
+/// The new device must be connected and unlocked: its standard wallet
+/// fingerprint is read live and has to match the one recorded for the old
+/// device (i.e. the same recovery phrase was restored). Emits
+/// `device:migration-progress` per table and `device:migration-complete`.
+#[tauri::command]
+#[specta::specta]
+pub async fn start_device_migration(
+    app: AppHandle,
+    old_device_id: String,
+    new_device_id: String,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<DeviceMigrationSummary, String> {
+    log::info!("📦 Migrating device {} to {}", old_device_id, new_device_id);
+
+    // The old device may be gone; its fingerprint is only re-read if it is
+    // connected and none was recorded
+    let recorded = database.get_standard_wallet_fingerprint(&old_device_id).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    if recorded.is_none() && is_connected(&old_device_id)
+        && crate::commands::wallets::identify_standard_wallet(&app, &old_device_id).await?.is_none()
+    {
+        return Err(format!("Device {} is locked; enter its PIN and start the migration again", old_device_id));
+    }
+
+    let new_wallet = match crate::commands::wallets::identify_standard_wallet(&app, &new_device_id).await {
+        Ok(Some(wallet)) => wallet,
+        Ok(None) => return Err(format!("Device {} is locked; enter its PIN and start the migration again", new_device_id)),
+        Err(e) => return Err(format!("Could not read the wallet fingerprint of the new device: {}", e)),
+    };
+
+    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+    let forwarder = {
+        let app = app.clone();
+        let new_device_id = new_device_id.clone();
+        tokio::spawn(async move {
+            while let Some(payload) = progress_rx.recv().await {
+                if let Err(e) = crate::commands::emit_or_queue_event(&app, "device:migration-progress", payload).await {
+                    log::warn!("Failed to emit migration progress for {}: {}", new_device_id, e);
+                }
+            }
+        })
+    };
+
+    let result = database
+        .migrate_device_data(&old_device_id, &new_device_id, &new_wallet.wallet_fingerprint, |step, total, table| {
+            let _ = progress_tx.send(serde_json::json!({
+                "old_device_id": old_device_id,
+                "new_device_id": new_device_id,
+                "step": step,
+                "total": total,
+                "table": table,
+            }));
+        })
+        .await;
+    drop(progress_tx);
+    let _ = forwarder.await;
+
+    let summary = result.map_err(|e| e.to_string())?;
+    wallet_session::end_wallet_session(&wallet_sessions, &old_device_id).await;
+
+    let moved: usize = summary.tables.iter().map(|t| t.moved + t.merged).sum();
+    log::info!("✅ Migrated {} rows from {} to {}", moved, old_device_id, new_device_id);
+    let details = serde_json::to_value(&summary).ok();
+    let _ = database.log_activity(
+        "device",
+        &format!("Migrated data from {} to replacement device {}", old_device_id, new_device_id),
+        details.as_ref(),
+    ).await;
+
+    crate::commands::emit_or_queue_event(&app, "device:migration-complete", serde_json::to_value(&summary).unwrap_or_default()).await?;
+    Ok(summary)
+}
@@ -4,61 +4,50 @@ pub mod get_connected_devices;
 pub mod get_features;
 pub mod get_device_status;
 pub mod wipe_device;
+pub mod forget_device;
+pub mod migrate_device;
 pub mod set_device_label;
+pub mod passphrase;
+pub mod auto_lock;
+pub mod device_note;
+pub mod device_export;
 pub mod get_device_info_by_id;
 pub mod get_queue_status;
+pub mod get_connection_history;
 pub mod get_blocking_actions;
 pub mod check_device_bootloader;
 pub mod register_device;
 pub mod get_devices_needing_setup;
+pub mod get_device_eth_address;
+pub mod get_xpub;
+pub mod frontload_device;
+pub mod sign_btc_message;
 
 // Re-export command functions
-pub use get_connected_devices::get_connected_devices;
+pub use get_connected_devices::{get_connected_devices, set_device_id_override};
 pub use get_features::get_features;
 pub use get_device_status::get_device_status;
 pub use check_device_bootloader::check_device_bootloader;
 pub use get_devices_needing_setup::get_devices_needing_setup;
+pub use wipe_device::{wipe_device, request_wipe_confirmation};
+pub use forget_device::forget_device;
+pub use migrate_device::start_device_migration;
+pub use get_device_info_by_id::get_device_info_by_id;
+pub use device_note::{get_device_note, set_device_note};
+pub use device_export::{export_device_data, validate_device_export};
+pub use register_device::{register_device, get_device_registry, get_device_from_registry,
+                         update_device_setup_step, mark_device_setup_complete,
+                         device_needs_setup, get_incomplete_setup_devices, reset_device_setup};
+pub use get_device_eth_address::get_device_eth_address;
+pub use get_xpub::get_xpub;
+pub use set_device_label::set_device_label;
+pub use passphrase::{set_passphrase_protection, send_passphrase, cancel_passphrase};
+pub use auto_lock::{set_auto_lock_delay, lock_device_now};
+pub use get_queue_status::{get_queue_status, all_queue_statuses};
+pub use get_connection_history::get_connection_history;
+pub use frontload_device::{frontload_device, get_frontload_progress};
+pub use get_blocking_actions::{get_blocking_actions, get_all_blocking_actions};
+pub use sign_btc_message::{sign_btc_message, verify_btc_message};
 
-// TODO: Add re-exports for other device commands as they are implemented
-// pub use wipe_device::wipe_device;
-// pub use set_device_label::set_device_label;
-// pub use get_device_info_by_id::get_device_info_by_id;
-// pub use get_queue_status::get_queue_status;
-// pub use get_blocking_actions::get_blocking_actions;
-// pub use register_device::{register_device, get_device_registry, get_device_from_registry, 
-//                          update_device_setup_step, mark_device_setup_complete, 
-//                          device_needs_setup, get_incomplete_setup_devices, reset_device_setup};
-
-// Shared utilities for device commands
-use crate::commands::DeviceQueueManager;
-use keepkey_rust::device_queue::{DeviceQueueFactory, DeviceQueueHandle};
-
-/// Get or create a device queue handle for the given device ID
-pub async fn get_or_create_device_queue(
-    device_id: &str,
-    queue_manager: &DeviceQueueManager,
-) -> Result<DeviceQueueHandle, String> {
-    let mut manager = queue_manager.lock().await;
-    
-    // Check if we already have a queue for the requested deviceId
-    if let Some(existing_handle) = manager.get(device_id) {
-        return Ok(existing_handle.clone());
-    }
-    
-    // Get list of connected devices
-    let devices = keepkey_rust::features::list_connected_devices();
-    
-    // Find device by exact ID match
-    let device = devices.iter()
-        .find(|d| d.unique_id == device_id)
-        .ok_or_else(|| format!("Device {} not found in connected devices", device_id))?;
-    
-    // Create a new queue handle
-    println!("🚀 Creating new device worker for device: {}", device_id);
-    let handle = DeviceQueueFactory::spawn_worker(device_id.to_string(), device.clone());
-    
-    // Insert the queue under the device ID
-    manager.insert(device_id.to_string(), handle.clone());
-    
-    Ok(handle)
-} 
\ No newline at end of file
+// Shared utilities for device commands (implemented in vault-core so the CLI uses the same queue wiring)
+pub use vault_core::get_or_create_device_queue;
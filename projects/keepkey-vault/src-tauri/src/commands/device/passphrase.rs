@@ -0,0 +1,95 @@
+// commands/device/passphrase.rs
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+use vault_core::passphrase::{passphrase_prompts, Zeroizing};
+use vault_core::request_queue::request_queue;
+use crate::commands::DeviceQueueManager;
+
+/// Turn passphrase protection on or off; the user confirms on the device
+#[tauri::command]
+#[specta::specta]
+pub async fn set_passphrase_protection(
+    app: AppHandle,
+    device_id: String,
+    enabled: bool,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<(), String> {
+    request_queue().run(&device_id, "set_passphrase_protection", || {
+        vault_core::passphrase::apply_passphrase_protection(&queue_manager, &device_id, enabled)
+    }).await?;
+    log::info!("🔑 Passphrase protection {} on device {}", if enabled { "enabled" } else { "disabled" }, device_id);
+
+    if let Err(e) = vault_core::refresh_stored_features(&database, &device_id, &queue_manager).await {
+        log::warn!("Could not refresh features after changing passphrase protection on {}: {}", device_id, e);
+    }
+    crate::commands::emit_or_queue_event(&app, "device:status-changed", serde_json::json!({
+        "device_id": device_id,
+        "passphrase_protection": enabled,
+    })).await
+}
+
+/// Supply the passphrase an operation on `device_id` is paused for, after a
+/// `device:passphrase-request`. The passphrase is never stored or logged.
+#[tauri::command]
+#[specta::specta]
+pub async fn send_passphrase(
+    app: AppHandle,
+    device_id: String,
+    passphrase: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<(), String> {
+    passphrase_prompts().supply(&device_id, Zeroizing::new(passphrase))?;
+    log::info!("🔑 Passphrase supplied for device {}", device_id);
+
+    // The paused operation finishes first; the features read after it show
+    // whether the device now has the passphrase cached
+    let database = database.inner().clone();
+    let queue_manager = queue_manager.inner().clone();
+    tauri::async_runtime::spawn(async move {
+        match vault_core::refresh_stored_features(&database, &device_id, &queue_manager).await {
+            Ok(features) => {
+                let status = serde_json::json!({
+                    "device_id": device_id,
+                    "passphrase_cached": features.passphrase_cached,
+                });
+                if let Err(e) = crate::commands::emit_or_queue_event(&app, "device:status-changed", status).await {
+                    log::warn!("Could not announce the passphrase state of {}: {}", device_id, e);
+                }
+            }
+            Err(e) => log::warn!("Could not refresh features after the passphrase on {}: {}", device_id, e),
+        }
+    });
+    Ok(())
+}
+
+/// Abandon the operation paused for a passphrase on `device_id`
+#[tauri::command]
+#[specta::specta]
+pub async fn cancel_passphrase(device_id: String) -> Result<bool, String> {
+    Ok(passphrase_prompts().cancel(&device_id))
+}
+
+/// Pause device operations on a PassphraseRequest and announce each one as
+/// `device:passphrase-request`
+pub fn init_passphrase_prompts(app: &AppHandle) {
+    vault_core::passphrase::install_passphrase_provider();
+    let mut requests = passphrase_prompts().subscribe();
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let device_id = match requests.recv().await {
+                Ok(device_id) => device_id,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+            let payload = serde_json::json!({ "device_id": device_id });
+            if let Err(e) = crate::commands::emit_or_queue_event(&app, "device:passphrase-request", payload).await {
+                log::warn!("Could not ask for the passphrase of {}: {}", device_id, e);
+            }
+        }
+    });
+}
@@ -1,4 +1,165 @@
-// commands/device/register_device.rs
+// commands/device/register_device.rs - Device registry and setup progress
+//
+// The setup wizard records each device it sees and how far its onboarding
+// got, so a device that finished setup is not sent through it again on the
+// next launch. Registry rows are returned as the JSON keepkey-db produces.
 
-// Device registration commands will be implemented here
-pub fn _placeholder() {} 
\ No newline at end of file
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+
+fn db_error(e: keepkey_db::DatabaseError) -> String {
+    format!("Database error: {}", e)
+}
+
+/// Record a device, or refresh its features; setup progress is kept
+#[tauri::command]
+#[specta::specta]
+pub async fn register_device(
+    device_id: String,
+    serial_number: Option<String>,
+    features: Option<String>,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    database
+        .register_device(&device_id, serial_number.as_deref(), features.as_deref())
+        .await
+        .map_err(db_error)
+}
+
+/// Every registered device with its setup progress
+#[tauri::command]
+#[specta::specta]
+pub async fn get_device_registry(
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<serde_json::Value>, String> {
+    database.get_device_registry().await.map_err(db_error)
+}
+
+/// One registered device, or null if it was never registered
+#[tauri::command]
+#[specta::specta]
+pub async fn get_device_from_registry(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+) -> Result<Option<serde_json::Value>, String> {
+    database.get_device_by_id(&device_id).await.map_err(db_error)
+}
+
+/// Record the last setup wizard step the device completed (1-based)
+#[tauri::command]
+#[specta::specta]
+pub async fn update_device_setup_step(
+    device_id: String,
+    step: u8,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    database.update_device_setup_step(&device_id, step).await.map_err(db_error)
+}
+
+/// Finish setup for a device, storing its first ETH address if known, and
+/// start frontloading its account xpubs
+#[tauri::command]
+#[specta::specta]
+pub async fn mark_device_setup_complete(
+    app: AppHandle,
+    device_id: String,
+    eth_address: Option<String>,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    database
+        .mark_device_setup_complete(&device_id, eth_address.as_deref())
+        .await
+        .map_err(db_error)?;
+    log::info!("🎉 Setup complete for device {}", device_id);
+    super::frontload_device::spawn_frontload(&app, &device_id);
+    Ok(())
+}
+
+/// Whether a registered device still has to go through setup
+#[tauri::command]
+#[specta::specta]
+pub async fn device_needs_setup(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+) -> Result<bool, String> {
+    database.device_needs_setup(&device_id).await.map_err(db_error)
+}
+
+/// Registered devices that have not finished setup, newest first
+#[tauri::command]
+#[specta::specta]
+pub async fn get_incomplete_setup_devices(
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<serde_json::Value>, String> {
+    database.get_incomplete_setup_devices().await.map_err(db_error)
+}
+
+/// Send a device through setup again
+#[tauri::command]
+#[specta::specta]
+pub async fn reset_device_setup(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    database.reset_device_setup(&device_id).await.map_err(db_error)?;
+    log::info!("🔄 Setup reset for device {}", device_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::Manager;
+
+    async fn mock_app() -> tauri::App<tauri::test::MockRuntime> {
+        let app = tauri::test::mock_app();
+        app.manage(Arc::new(Database::new_in_memory().await.unwrap()));
+        app
+    }
+
+    #[tokio::test]
+    async fn test_registry_commands() {
+        let app = mock_app().await;
+        let features = serde_json::json!({ "label": "Cold", "version": "7.10.0", "initialized": true }).to_string();
+
+        register_device("kk1".into(), Some("SN1".into()), Some(features), app.state()).await.unwrap();
+        register_device("kk2".into(), None, None, app.state()).await.unwrap();
+
+        let registry = get_device_registry(app.state()).await.unwrap();
+        assert_eq!(registry.len(), 2);
+        let device = get_device_from_registry("kk1".into(), app.state()).await.unwrap().unwrap();
+        assert_eq!(device["serial_number"], "SN1");
+        assert_eq!(device["label"], "Cold");
+        assert_eq!(device["setup_complete"], false);
+        assert!(get_device_from_registry("missing".into(), app.state()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_setup_progress_commands() {
+        let app = mock_app().await;
+        register_device("kk1".into(), None, None, app.state()).await.unwrap();
+        assert!(device_needs_setup("kk1".into(), app.state()).await.unwrap());
+
+        update_device_setup_step("kk1".into(), 2, app.state()).await.unwrap();
+        let incomplete = get_incomplete_setup_devices(app.state()).await.unwrap();
+        assert_eq!(incomplete.len(), 1);
+        assert_eq!(incomplete[0]["setup_step_completed"], 2);
+
+        // The command also starts a frontload, which needs a running app
+        let database = app.state::<Arc<Database>>();
+        database.mark_device_setup_complete("kk1", Some("0xabc")).await.unwrap();
+        assert!(!device_needs_setup("kk1".into(), app.state()).await.unwrap());
+        assert!(get_incomplete_setup_devices(app.state()).await.unwrap().is_empty());
+        let device = get_device_from_registry("kk1".into(), app.state()).await.unwrap().unwrap();
+        assert_eq!(device["eth_address"], "0xabc");
+
+        reset_device_setup("kk1".into(), app.state()).await.unwrap();
+        assert!(device_needs_setup("kk1".into(), app.state()).await.unwrap());
+
+        // Unknown devices are reported rather than silently ignored
+        assert!(update_device_setup_step("missing".into(), 1, app.state()).await.is_err());
+        assert!(database.mark_device_setup_complete("missing", None).await.is_err());
+        assert!(reset_device_setup("missing".into(), app.state()).await.is_err());
+    }
+}
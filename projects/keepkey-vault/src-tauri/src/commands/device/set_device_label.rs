@@ -1,2 +1,36 @@
 // commands/device/set_device_label.rs
-pub fn _placeholder() {} 
\ No newline at end of file
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+use crate::commands::DeviceQueueManager;
+
+/// Rename a device. The label is trimmed and must be at most 32 printable
+/// ASCII characters; the user confirms it on the device. Errors are JSON
+/// with a `kind`, `Cancelled` when the user declined on the device.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_device_label(
+    app: AppHandle,
+    device_id: String,
+    label: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<String, String> {
+    let label = vault_core::device_label::apply_device_label(&queue_manager, &device_id, &label)
+        .await
+        .map_err(|e| {
+            log::warn!("Could not rename device {}: {}", device_id, e);
+            e.to_json_string()
+        })?;
+    log::info!("🏷️ Device {} renamed to '{}'", device_id, label);
+
+    database.set_device_label(&device_id, &label).await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    crate::commands::emit_or_queue_event(&app, "device:label-changed", serde_json::json!({
+        "device_id": device_id,
+        "label": label,
+    })).await?;
+    Ok(label)
+}
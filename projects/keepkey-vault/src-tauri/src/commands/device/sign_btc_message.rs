@@ -0,0 +1,91 @@
+// commands/device/sign_btc_message.rs
+//
+// Sign and verify Bitcoin messages on the device. Both show the message on
+// the device and wait for the button, which the queue acknowledges without
+// telling us, so `device:awaiting-confirmation` is raised around the whole
+// request for the UI to prompt the user. Errors are JSON with a `kind`,
+// `Cancelled` when the user declined on the device. Signing passes the same
+// checks and audit as every other message signature
+// (`messages::begin_message_signing`).
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+use vault_core::btc_message;
+use vault_core::maintenance::MaintenanceController;
+use vault_core::signing_origin::LimitConfirmations;
+use vault_core::wallet_session::WalletSessions;
+use crate::commands::DeviceQueueManager;
+
+/// Bitcoin mainnet, the only network these commands sign for
+const BITCOIN_CAIP: &str = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+
+fn emit_awaiting_confirmation(app: &AppHandle, device_id: &str, operation: &str, awaiting: bool) {
+    let payload = serde_json::json!({
+        "device_id": device_id,
+        "operation": operation,
+        "awaiting": awaiting,
+    });
+    if let Err(e) = crate::commands::events::emit_event(app, "device:awaiting-confirmation", payload) {
+        log::warn!("Failed to emit awaiting confirmation for {}: {}", device_id, e);
+    }
+}
+
+/// Sign `message` with the key at `path`, e.g. m/84'/0'/0'/0/0; returns the
+/// base64 signature. `script_type` (p2pkh, p2sh-p2wpkh or p2wpkh) picks the
+/// address the signature is for, legacy when omitted.
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+pub async fn sign_btc_message(
+    app: AppHandle,
+    device_id: String,
+    path: String,
+    message: String,
+    script_type: Option<String>,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    limit_confirmations: State<'_, LimitConfirmations>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<String, String> {
+    let signing = crate::commands::messages::begin_message_signing(
+        &app, &database, &wallet_sessions, &limit_confirmations, &maintenance, &device_id, BITCOIN_CAIP, "sign_btc_message",
+    ).await?;
+
+    emit_awaiting_confirmation(&app, &device_id, "sign_message", true);
+    let result = btc_message::sign_btc_message(&queue_manager, &device_id, &path, &message, script_type.as_deref()).await;
+    emit_awaiting_confirmation(&app, &device_id, "sign_message", false);
+    signing.finish(&database, &result).await;
+
+    let signature = result.map_err(|e| {
+        log::warn!("Could not sign a message on {}: {}", device_id, e);
+        e.to_json_string()
+    })?;
+    log::info!("✍️ Signed a Bitcoin message with {} on {}", path, device_id);
+    Ok(signature)
+}
+
+/// Check a base64 `signature` of `message` by `address` on the device;
+/// false when the signature does not match
+#[tauri::command]
+#[specta::specta]
+pub async fn verify_btc_message(
+    app: AppHandle,
+    device_id: String,
+    address: String,
+    signature: String,
+    message: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<bool, String> {
+    emit_awaiting_confirmation(&app, &device_id, "verify_message", true);
+    let result = btc_message::verify_btc_message(&queue_manager, &device_id, &address, &signature, &message).await;
+    emit_awaiting_confirmation(&app, &device_id, "verify_message", false);
+
+    let valid = result.map_err(|e| {
+        log::warn!("Could not verify a message on {}: {}", device_id, e);
+        e.to_json_string()
+    })?;
+    log::info!("🔏 Message from {} on {}: {}", address, device_id, if valid { "valid" } else { "invalid" });
+    Ok(valid)
+}
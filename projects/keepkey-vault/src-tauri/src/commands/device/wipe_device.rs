@@ -1,2 +1,149 @@
 // commands/device/wipe_device.rs
-pub fn _placeholder() {} 
\ No newline at end of file
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+use keepkey_rust::messages::{self, Message};
+use vault_core::confirmation::{ConfirmationChallenge, Confirmations, HighRiskOperation};
+use vault_core::maintenance::MaintenanceController;
+use vault_core::wallet_session::{self, WalletSessions};
+use crate::commands::DeviceQueueManager;
+use crate::commands::confirmation::{issue_confirmation, require_confirmation};
+use super::get_or_create_device_queue;
+
+/// Issue the confirmation `wipe_device` requires: a single-use token and the
+/// phrase (the device label) the user must type
+#[tauri::command]
+#[specta::specta]
+pub async fn request_wipe_confirmation(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+    confirmations: State<'_, Confirmations>,
+) -> Result<ConfirmationChallenge, String> {
+    issue_confirmation(&database, &confirmations, HighRiskOperation::WipeDevice, Some(device_id)).await
+}
+
+/// Forget the xpubs, keys and balances of the wiped seed and send the
+/// device through setup again
+async fn clear_wiped_device(database: &Database, device_id: &str) -> Result<(), String> {
+    database.clear_wiped_device(device_id).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    database.reset_device_setup(device_id).await
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+/// Wipe a device back to factory state.
+///
+/// Requires a confirmation from `request_wipe_confirmation`; the device asks
+/// for a button press before erasing. Once it has, everything the vault
+/// cached from the old seed is cleared and `device:wiped` is emitted.
+#[tauri::command]
+#[specta::specta]
+pub async fn wipe_device(
+    app: AppHandle,
+    device_id: String,
+    confirmation_token: String,
+    confirmation_phrase: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    confirmations: State<'_, Confirmations>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<(), String> {
+    let _operation = maintenance.device_operation("wipe_device").map_err(|e| e.to_json_string())?;
+    require_confirmation(
+        &database,
+        &confirmations,
+        HighRiskOperation::WipeDevice,
+        Some(&device_id),
+        &confirmation_token,
+        &confirmation_phrase,
+    ).await?;
+
+    log::info!("🗑️ Wiping device {}", device_id);
+    let queue = get_or_create_device_queue(&device_id, &queue_manager).await?;
+    match queue.send_raw(Message::WipeDevice(messages::WipeDevice::default()), true).await {
+        Ok(Message::Success(_)) => {}
+        Ok(Message::Failure(f)) => return Err(format!("Device refused to wipe: {}", f.message())),
+        Ok(other) => return Err(format!("Unexpected response: {:?}", other.message_type())),
+        Err(e) => return Err(format!("Failed to wipe device: {}", e)),
+    }
+    log::info!("✅ Device {} wiped", device_id);
+
+    wallet_session::end_wallet_session(&wallet_sessions, &device_id).await;
+    clear_wiped_device(&database, &device_id).await?;
+    if let Err(e) = vault_core::refresh_stored_features(&database, &device_id, &queue_manager).await {
+        log::warn!("Could not refresh features after wiping {}: {}", device_id, e);
+    }
+
+    crate::commands::emit_or_queue_event(&app, "device:status-changed", serde_json::json!({
+        "device_id": device_id,
+        "wiped": true,
+    })).await?;
+    crate::commands::emit_or_queue_event(&app, "device:wiped", serde_json::json!({
+        "device_id": device_id,
+    })).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::Manager;
+    use keepkey_db::WalletXpubInput;
+
+    async fn mock_app() -> tauri::App<tauri::test::MockRuntime> {
+        let app = tauri::test::mock_app();
+        let database = Database::new_in_memory().await.unwrap();
+        database.register_device("kk1", None, Some(r#"{"label":"Cold"}"#)).await.unwrap();
+        database.register_device("kk2", None, None).await.unwrap();
+        app.manage(Arc::new(database));
+        app.manage(vault_core::confirmation::new_confirmations());
+        app
+    }
+
+    /// The check wipe_device runs before touching the device
+    async fn verify(app: &tauri::App<tauri::test::MockRuntime>, token: &str, device_id: &str) -> Result<(), String> {
+        let database = app.state::<Arc<Database>>();
+        let confirmations = app.state::<Confirmations>();
+        require_confirmation(&database, &confirmations, HighRiskOperation::WipeDevice, Some(device_id), token, "Cold").await
+    }
+
+    #[tokio::test]
+    async fn test_wipe_requires_a_matching_confirmation() {
+        let app = mock_app().await;
+        assert!(request_wipe_confirmation("missing".into(), app.state(), app.state()).await.is_err());
+        assert!(verify(&app, "made-up", "kk1").await.is_err());
+
+        let challenge = request_wipe_confirmation("kk1".into(), app.state(), app.state()).await.unwrap();
+        assert_eq!(challenge.phrase, "Cold");
+        assert!(verify(&app, &challenge.token, "kk2").await.is_err());
+        // Tokens are single use, even after a failed attempt
+        assert!(verify(&app, &challenge.token, "kk1").await.is_err());
+
+        let challenge = request_wipe_confirmation("kk1".into(), app.state(), app.state()).await.unwrap();
+        verify(&app, &challenge.token, "kk1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wiped_device_data_is_cleared() {
+        let app = mock_app().await;
+        let database = app.state::<Arc<Database>>();
+        database.mark_device_setup_complete("kk1", Some("0xabc")).await.unwrap();
+        database.save_wallet_xpub(&WalletXpubInput {
+            device_id: "kk1".to_string(),
+            wallet_fingerprint: "aaaa0001".to_string(),
+            path: "m/44'/60'/0'".to_string(),
+            label: "Ethereum".to_string(),
+            caip: "eip155:1/slip44:60".to_string(),
+            pubkey: "xpub".to_string(),
+        }).await.unwrap();
+        database.insert_cached_pubkey("kk1", "aaaa0001", "m/44'/60'/0'", "Ethereum", None, "xpub").await.unwrap();
+
+        clear_wiped_device(&database, "kk1").await.unwrap();
+        assert!(database.get_wallet_xpubs("kk1", "aaaa0001").await.unwrap().is_empty());
+        assert_eq!(database.get_cached_pubkey("kk1", "aaaa0001", "m/44'/60'/0'", "Ethereum", None).await.unwrap(), None);
+        assert!(database.device_needs_setup("kk1").await.unwrap());
+        assert_eq!(database.get_device_eth_address("kk1").await.unwrap(), None);
+        assert!(clear_wiped_device(&database, "missing").await.is_err());
+    }
+}
@@ -0,0 +1,271 @@
+// commands/diagnostics.rs - Device fault logs, self-test and the support diagnostics bundle
+
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, DeviceHealthCheck, DeviceNote};
+use keepkey_db::data_dir::DataDirLocation;
+use keepkey_db::storage::DatabaseStats;
+use vault_core::endpoints::{endpoint_manager, EndpointStatus};
+use vault_core::event_history::EventHistorySummary;
+use vault_core::fault_log::{self, FaultLogStatus};
+use vault_core::maintenance::{MaintenanceController, MaintenanceStatus};
+use vault_core::firmware_catalog::CatalogIntegrityReport;
+use vault_core::power::{PowerMonitor, PowerStatus};
+use vault_core::protocol::ProtocolInfo;
+use vault_core::self_test::{self, SelfTestOutcome, STORAGE_CHECK_PATH};
+use vault_core::startup::StartupReport;
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use vault_core::warmup::{queue_warmup, WarmupMetrics};
+use super::DeviceQueueManager;
+use crate::scheduler::IntegrityMetrics;
+use crate::startup::StartupReportState;
+
+/// Number of fault records included in the diagnostics bundle
+const DIAGNOSTICS_FAULT_LIMIT: usize = 20;
+/// Number of self-test results included in the diagnostics bundle
+const DIAGNOSTICS_HEALTH_CHECK_LIMIT: usize = 20;
+
+/// Read the device's firmware fault record, storing it if present.
+///
+/// Firmware without fault log support returns `not_supported` rather than an error.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_device_fault_log(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<FaultLogStatus, String> {
+    log::info!("🩺 Checking fault log for device {}", device_id);
+    fault_log::check_device_fault_log(&database, &device_id, &queue_manager).await
+}
+
+/// Run the hardware self-test (display, RNG, storage) and store the result.
+///
+/// The display and RNG steps wait for a button press; `device:button-request`
+/// is emitted before each step so the UI can prompt the user. A failed
+/// storage check raises a high-severity `device:health-warning`. Firmware
+/// without self-test support returns `not_supported` rather than an error.
+#[tauri::command]
+#[specta::specta]
+pub async fn run_device_self_test(
+    app: AppHandle,
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<SelfTestOutcome, String> {
+    log::info!("🩺 Running self-test on device {}", device_id);
+
+    // Compare the storage readback against the xpub stored for the open wallet.
+    // Without a session the device may be on a different passphrase wallet, so
+    // a mismatch would be a false alarm; only the two reads are compared then.
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let reference_xpub = if wallet_fingerprint.is_empty() {
+        None
+    } else {
+        database.get_wallet_xpubs(&device_id, &wallet_fingerprint).await
+            .map_err(|e| format!("Database error: {}", e))?
+            .into_iter()
+            .find(|x| x.path == STORAGE_CHECK_PATH)
+            .map(|x| x.pubkey)
+    };
+
+    let outcome = self_test::run_device_self_test(
+        &database,
+        &device_id,
+        &queue_manager,
+        reference_xpub.as_deref(),
+        |step| {
+            let payload = serde_json::json!({
+                "device_id": device_id,
+                "operation": "self_test",
+                "step": step,
+                "awaiting_button": step.awaits_button(),
+            });
+            if let Err(e) = super::events::emit_event(&app, "device:button-request", payload) {
+                log::warn!("Failed to emit self-test step: {}", e);
+            }
+        },
+    ).await?;
+
+    log::info!("🩺 Self-test on device {}: {:?}", device_id, outcome.db_outcome());
+
+    if outcome.storage_failed() {
+        log::error!("❌ Device {} failed the storage self-test", device_id);
+        let message = format!(
+            "Device {} failed its storage self-test. Move funds to a new wallet as soon as possible.",
+            device_id
+        );
+        let details = serde_json::json!({ "severity": "high", "outcome": outcome });
+        if let Err(e) = database.log_activity("device_health", &message, Some(&details)).await {
+            log::warn!("Failed to log self-test failure: {}", e);
+        }
+        if let Err(e) = super::emit_or_queue_event(
+            &app,
+            "device:health-warning",
+            serde_json::json!({
+                "device_id": device_id,
+                "severity": "high",
+                "check": "storage",
+                "message": message,
+            }),
+        ).await {
+            log::error!("Failed to emit health warning: {}", e);
+        }
+    }
+
+    Ok(outcome)
+}
+
+/// Registry fields included in the diagnostics bundle (no labels or serials)
+#[derive(Debug, Serialize, Deserialize, specta::Type)]
+pub struct DiagnosticsDevice {
+    pub device_id: String,
+    pub model: Option<String>,
+    pub firmware_variant: Option<String>,
+    pub firmware_version: Option<String>,
+    pub bootloader_mode: bool,
+    pub initialized: bool,
+    pub last_seen: i64,
+}
+
+/// Support diagnostics bundle
+#[derive(Debug, Serialize, specta::Type)]
+pub struct SystemDiagnostics {
+    pub app_version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub database: DatabaseStats,
+    pub devices: Vec<DiagnosticsDevice>,
+    /// Parsed fault records; see `fault_log::recent_fault_reports`
+    pub fault_logs: Vec<serde_json::Value>,
+    pub health_checks: Vec<DeviceHealthCheck>,
+    pub events: Option<EventHistorySummary>,
+    pub startup: Option<StartupReport>,
+    pub power: PowerStatus,
+    /// Whether a heavy operation holds the vault in maintenance mode, and why
+    pub maintenance: MaintenanceStatus,
+    pub data_dir: DataDirLocation,
+    pub integrity: IntegrityMetrics,
+    /// First-operation latency after idle, warmed vs cold
+    pub warmup: WarmupMetrics,
+    /// Rate limiter and circuit state of each external endpoint contacted
+    pub endpoints: Vec<EndpointStatus>,
+    /// Device notes; only present when the user opted in for this submission
+    pub device_notes: Option<Vec<DeviceNote>>,
+}
+
+/// Collect a diagnostics bundle for support requests.
+///
+/// Contains app/platform info, database stats, a device summary without
+/// labels, serials or addresses, recent fault records, self-test results and
+/// event counts. Device notes are left out unless `include_device_notes` is
+/// set for this submission.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_system_diagnostics(
+    include_device_notes: Option<bool>,
+    database: State<'_, Arc<Database>>,
+    startup_report: State<'_, StartupReportState>,
+    power_monitor: State<'_, Arc<PowerMonitor>>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<SystemDiagnostics, String> {
+    let database_stats = database.get_database_stats().await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let devices = database.get_device_registry().await
+        .map_err(|e| format!("Database error: {}", e))?
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<Result<Vec<DiagnosticsDevice>, _>>()
+        .map_err(|e| format!("Invalid device record: {}", e))?;
+
+    let fault_logs = fault_log::recent_fault_reports(&database, None, DIAGNOSTICS_FAULT_LIMIT).await?;
+    let health_checks = database.get_health_checks(None, DIAGNOSTICS_HEALTH_CHECK_LIMIT).await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let device_notes = if include_device_notes.unwrap_or(false) {
+        Some(database.get_device_notes().await.map_err(|e| format!("Database error: {}", e))?)
+    } else {
+        None
+    };
+
+    Ok(SystemDiagnostics {
+        app_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        database: database_stats,
+        devices,
+        fault_logs,
+        health_checks,
+        events: super::events::event_history_summary(),
+        startup: startup_report.lock().ok().and_then(|r| r.clone()),
+        power: power_monitor.status(),
+        maintenance: maintenance.status(),
+        data_dir: keepkey_db::data_dir::resolve_data_dir(),
+        integrity: crate::scheduler::integrity_metrics(),
+        warmup: queue_warmup().metrics(),
+        endpoints: endpoint_manager().snapshot(),
+        device_notes,
+    })
+}
+
+/// Close the circuit and clear any 429 cooldown of the endpoint serving
+/// `url`, so requests to it go out again right away. Returns false when the
+/// endpoint has not been contacted this session.
+#[tauri::command]
+#[specta::specta]
+pub async fn reset_endpoint_circuit(url: String) -> Result<bool, String> {
+    let reset = endpoint_manager().reset_circuit(&url).map_err(|e| e.to_string())?;
+    if reset {
+        log::info!("🔌 Reset rate limiter and circuit for {}", url);
+    }
+    Ok(reset)
+}
+
+/// Duration and outcome of each startup task from this launch
+#[tauri::command]
+#[specta::specta]
+pub async fn get_startup_report(
+    startup_report: State<'_, StartupReportState>,
+) -> Result<Option<StartupReport>, String> {
+    startup_report
+        .lock()
+        .map(|r| r.clone())
+        .map_err(|_| "Startup report unavailable".to_string())
+}
+
+/// Messages and fields of the compiled device-protocol revision, which of
+/// them the raw passthrough allows, and an example JSON body for each
+#[tauri::command]
+#[specta::specta]
+pub async fn get_protocol_info() -> Result<ProtocolInfo, String> {
+    Ok(vault_core::protocol::protocol_info())
+}
+
+/// Recompute the hashes of the bundled firmware and bootloader images and
+/// cross-check them against releases.json. Developer mode only.
+#[tauri::command]
+#[specta::specta]
+pub async fn verify_catalog_integrity(
+    database: State<'_, Arc<Database>>,
+) -> Result<CatalogIntegrityReport, String> {
+    let developer_mode = database.get_preference("developer_mode").await.ok().flatten()
+        .map(|v| v == "true")
+        .unwrap_or(false);
+    if !developer_mode {
+        return Err("Catalog verification requires developer mode".to_string());
+    }
+
+    // Hashing every bundled image reads tens of megabytes
+    let report = tokio::task::spawn_blocking(vault_core::firmware_catalog::verify_bundled_catalog)
+        .await
+        .map_err(|e| format!("Catalog verification failed: {}", e))??;
+    if report.is_consistent() {
+        log::info!("🔏 releases.json matches {} bundled images", report.verified.len());
+    } else {
+        log::warn!("🔏 releases.json disagrees with bundled images: {:?}", report.mismatches);
+    }
+    Ok(report)
+}
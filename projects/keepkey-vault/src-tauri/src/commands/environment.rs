@@ -0,0 +1,89 @@
+// commands/environment.rs - Host environment probe for onboarding and settings
+
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State};
+use keepkey_db::Database;
+use vault_core::environment::{self, EnvironmentReport, SystemProbeHost, ISSUES_FOUND_EVENT};
+use super::fees::{DEFAULT_FEE_ENDPOINT, FEE_ENDPOINT_PREF};
+
+/// Network whose RPC endpoint is checked for reachability
+const PROBE_EVM_NETWORK: &str = "eip155:1";
+
+/// Latest probe result, read by onboarding
+pub type EnvironmentReportState = Arc<Mutex<Option<EnvironmentReport>>>;
+
+pub fn new_environment_report() -> EnvironmentReportState {
+    Arc::new(Mutex::new(None))
+}
+
+/// The fee/explorer endpoint and the Ethereum RPC the vault talks to by default
+async fn probe_endpoints(database: &Database) -> Vec<String> {
+    let mut endpoints = vec![database
+        .get_preference(FEE_ENDPOINT_PREF).await
+        .ok()
+        .flatten()
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_FEE_ENDPOINT.to_string())];
+    if let Ok(Some(network)) = database.get_network_endpoints(PROBE_EVM_NETWORK).await {
+        endpoints.extend(network.rpc_urls.into_iter().take(1));
+    }
+    endpoints.dedup();
+    endpoints
+}
+
+/// Probe the host, keep the report for onboarding and announce any issues
+async fn probe_and_report(app: &AppHandle, database: &Database) -> EnvironmentReport {
+    let host = Arc::new(SystemProbeHost::new(keepkey_db::data_dir::resolve_data_dir().path));
+    let report = environment::run_environment_probe(host, &probe_endpoints(database).await).await;
+    log::info!(
+        "🧪 Environment probe finished in {}ms, {} issue(s)",
+        report.duration_ms,
+        report.issues().len()
+    );
+
+    if let Ok(mut slot) = app.state::<EnvironmentReportState>().lock() {
+        *slot = Some(report.clone());
+    }
+    if report.issues_found {
+        let payload = serde_json::json!({ "issues": report.issues(), "probed_at": report.probed_at });
+        if let Err(e) = super::emit_or_queue_event(app, ISSUES_FOUND_EVENT, payload).await {
+            log::error!("Failed to emit {}: {}", ISSUES_FOUND_EVENT, e);
+        }
+    }
+    report
+}
+
+/// Run the probe in the background until onboarding is completed, so the
+/// first screens can point out problems before a device is plugged in
+pub fn spawn_first_run_probe(app: AppHandle, database: Arc<Database>) {
+    tauri::async_runtime::spawn(async move {
+        if database.is_onboarded().await.unwrap_or(false) {
+            return;
+        }
+        probe_and_report(&app, &database).await;
+    });
+}
+
+/// Check USB permissions, conflicting software, the HID API, the data
+/// directory and endpoint reachability. Emits `environment:issues-found`
+/// when any check fails or times out.
+#[tauri::command]
+#[specta::specta]
+pub async fn run_environment_probe(
+    app: AppHandle,
+    database: State<'_, Arc<Database>>,
+) -> Result<EnvironmentReport, String> {
+    Ok(probe_and_report(&app, &database).await)
+}
+
+/// Report of the last probe this session, if one ran
+#[tauri::command]
+#[specta::specta]
+pub async fn get_environment_report(
+    report: State<'_, EnvironmentReportState>,
+) -> Result<Option<EnvironmentReport>, String> {
+    report
+        .lock()
+        .map(|r| r.clone())
+        .map_err(|_| "Environment report unavailable".to_string())
+}
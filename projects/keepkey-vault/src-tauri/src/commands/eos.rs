@@ -0,0 +1,145 @@
+// commands/eos.rs - Signing EOS token transfers
+//
+// The UI supplies the TaPoS fields (ref block num/prefix) and expiration it
+// got from the node; the signature and packed transaction come back ready
+// for push_transaction.
+
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, SigningAuditInput};
+use keepkey_rust::chains::eos::{
+    sign_eos_transaction, EosAsset, EosTransaction, EosTransfer, EosTxHeader, EOS_MAINNET_CHAIN_ID,
+};
+use vault_core::maintenance::MaintenanceController;
+use vault_core::paths::parse_derivation_path;
+use vault_core::signing_origin::{self, LimitConfirmations, SigningOrigin};
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use super::device::get_or_create_device_queue;
+use super::DeviceQueueManager;
+
+/// Native EOS on mainnet
+const EOS_CAIP: &str = "eos:aca376f206b8fc25a6ed44dbdc66547c/slip44:194";
+
+/// Account 0, first address
+const DEFAULT_EOS_PATH: &str = "m/44'/194'/0'/0/0";
+
+/// One transfer as sent by the UI
+#[derive(Debug, Deserialize, specta::Type)]
+pub struct EosTransferRequest {
+    pub from: String,
+    pub to: String,
+    /// Amount with its symbol, e.g. "1.0000 EOS"
+    pub quantity: String,
+    pub memo: Option<String>,
+    /// "active" when omitted
+    pub permission: Option<String>,
+}
+
+/// An EOS transaction as sent by the UI
+#[derive(Debug, Deserialize, specta::Type)]
+pub struct EosTransactionRequest {
+    pub path: Option<String>,
+    /// Mainnet when omitted
+    pub chain_id: Option<String>,
+    /// Seconds since the Unix epoch
+    pub expiration: u32,
+    pub ref_block_num: u16,
+    pub ref_block_prefix: u32,
+    pub transfers: Vec<EosTransferRequest>,
+}
+
+/// A signed transaction for push_transaction
+#[derive(Debug, Serialize, specta::Type)]
+pub struct SignedEosTx {
+    pub transaction_id: String,
+    /// "SIG_K1_..." signature
+    pub signature: String,
+    /// Packed transaction as hex
+    pub packed_trx: String,
+}
+
+/// EOS leaving the wallet, for the signing limits; other tokens are not priced
+fn eos_spent(transaction: &EosTransaction) -> f64 {
+    transaction.transfers.iter().filter_map(|transfer| {
+        let asset: EosAsset = transfer.quantity.parse().ok()?;
+        let precision = (asset.symbol & 0xff) as i32;
+        transfer.quantity.trim().ends_with(" EOS").then(|| asset.amount as f64 / 10f64.powi(precision))
+    }).sum()
+}
+
+/// Sign a transaction of eosio.token transfers
+#[tauri::command]
+#[specta::specta]
+pub async fn sign_eos_tx(
+    app: AppHandle,
+    device_id: String,
+    request: EosTransactionRequest,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    limit_confirmations: State<'_, LimitConfirmations>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<SignedEosTx, String> {
+    let _operation = maintenance.device_operation("sign_eos_tx").map_err(|e| e.to_json_string())?;
+    vault_core::authenticity::require_verified_device(&device_id)?;
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let origin = SigningOrigin::MainWindow;
+
+    let transaction = EosTransaction {
+        address_n: parse_derivation_path(request.path.as_deref().unwrap_or(DEFAULT_EOS_PATH))?,
+        chain_id: request.chain_id.unwrap_or_else(|| EOS_MAINNET_CHAIN_ID.to_string()),
+        header: EosTxHeader {
+            expiration: request.expiration,
+            ref_block_num: request.ref_block_num,
+            ref_block_prefix: request.ref_block_prefix,
+            max_net_usage_words: 0,
+            max_cpu_usage_ms: 0,
+            delay_sec: 0,
+        },
+        transfers: request.transfers.into_iter().map(|transfer| EosTransfer {
+            from: transfer.from,
+            to: transfer.to,
+            quantity: transfer.quantity,
+            memo: transfer.memo.unwrap_or_default(),
+            permission: transfer.permission.unwrap_or_else(|| "active".to_string()),
+        }).collect(),
+    };
+
+    let queue = get_or_create_device_queue(&device_id, &queue_manager).await?;
+    let price = database.get_asset_price_usd(EOS_CAIP).await.unwrap_or(None);
+    let value_usd = price.map(|p| eos_spent(&transaction) * p);
+    let ticket = signing_origin::authorize_signing(
+        &database,
+        &limit_confirmations,
+        &origin,
+        SigningAuditInput {
+            origin: origin.key(),
+            device_id: device_id.clone(),
+            wallet_fingerprint,
+            caip: EOS_CAIP.to_string(),
+            value_usd,
+        },
+        super::signing::LIMIT_CONFIRMATION_TIMEOUT,
+        |audit_id, decision| super::signing::notify_limit_confirmation(&app, audit_id, &origin, EOS_CAIP, decision),
+    ).await?;
+
+    log::info!("Signing EOS tx on {}: {} transfers", device_id, transaction.transfers.len());
+
+    let signed = match sign_eos_transaction(&queue, transaction).await {
+        Ok(signed) => signed,
+        Err(e) => {
+            let error = format!("Signing failed: {}", e);
+            signing_origin::complete_signing(&database, &ticket, Err(&error)).await;
+            return Err(error);
+        }
+    };
+    let transaction_id = signed.transaction_id();
+    signing_origin::complete_signing(&database, &ticket, Ok(Some(&transaction_id))).await;
+
+    Ok(SignedEosTx {
+        transaction_id,
+        signature: signed.signature,
+        packed_trx: hex::encode(&signed.packed_trx),
+    })
+}
@@ -1,109 +1,275 @@
 // commands/events.rs - Event handling utilities
 
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use serde::{Serialize, Deserialize};
-use tauri::{AppHandle, Emitter};
-
-#[derive(Debug, Clone)]
-pub struct FrontendReadyState {
-    pub is_ready: bool,
-    pub queued_events: Vec<QueuedEvent>,
-}
-
-impl Default for FrontendReadyState {
-    fn default() -> Self {
-        Self {
-            is_ready: false,
-            queued_events: Vec::new(),
-        }
+use std::sync::{Arc, Mutex, MutexGuard};
+use tauri::{AppHandle, Emitter, Manager, State, WebviewWindow};
+use keepkey_db::Database;
+use vault_core::event_history::{EventHistory, EventHistorySummary, EventRecord, DEFAULT_EVENT_HISTORY_SIZE};
+use vault_core::event_router::{EventQueueStats, EventRouter, WindowSubscription};
+
+lazy_static::lazy_static! {
+    // Per-window readiness, category subscriptions and queued events
+    static ref EVENT_ROUTER: Mutex<EventRouter> = Mutex::new(EventRouter::new());
+    // Every emitted or queued event, for get_event_history / re_emit_event
+    static ref EVENT_HISTORY: std::sync::Mutex<EventHistory> = std::sync::Mutex::new(EventHistory::default());
+}
+
+fn router() -> MutexGuard<'static, EventRouter> {
+    EVENT_ROUTER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Emit to each labelled window; stops at the first failure
+fn emit_to_windows(app: &AppHandle, labels: &[String], event_name: &str, payload: &serde_json::Value) -> Result<(), String> {
+    for label in labels {
+        app.emit_to(label.as_str(), event_name, payload)
+            .map_err(|e| format!("Failed to emit event to {}: {}", label, e))?;
+    }
+    Ok(())
+}
+
+fn record_event(event_name: &str, payload: &serde_json::Value, queued: bool, replay_of: Option<u64>) {
+    if let Ok(mut history) = EVENT_HISTORY.lock() {
+        history.record(event_name, payload, queued, replay_of);
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct QueuedEvent {
-    pub event_name: String,
-    pub payload: serde_json::Value,
-    pub timestamp: u64,
+/// Load event history size and developer mode from preferences
+pub async fn configure_event_history(database: Arc<Database>) {
+    let size = database.get_preference("event_history_size").await.ok().flatten()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_EVENT_HISTORY_SIZE);
+    let developer_mode = database.get_preference("developer_mode").await.ok().flatten()
+        .map(|v| v == "true")
+        .unwrap_or(false);
+
+    if let Ok(mut history) = EVENT_HISTORY.lock() {
+        history.configure(size, developer_mode);
+    }
+    log::info!("📼 Event history: {} events, payloads {}", size, if developer_mode { "retained" } else { "not retained" });
 }
 
-// Add frontend readiness state and queued events
-lazy_static::lazy_static! {
-    static ref FRONTEND_READY_STATE: Arc<RwLock<FrontendReadyState>> = Arc::new(RwLock::new(FrontendReadyState::default()));
-    // One-time initialization flag to prevent duplicate ready signals
-    static ref FRONTEND_READY_ONCE: Arc<tokio::sync::Mutex<bool>> = Arc::new(tokio::sync::Mutex::new(false));
+/// Event counts for the diagnostics bundle
+pub fn event_history_summary() -> Option<EventHistorySummary> {
+    EVENT_HISTORY.lock().ok().map(|history| history.summary())
 }
 
-/// Signal that the frontend is ready to receive events
+/// Signal that the calling window is ready to receive events. It then gets
+/// what was queued for it, the latest sticky events (setup required, blocking
+/// actions) and any unacknowledged critical events it subscribes to.
 #[tauri::command]
-pub async fn frontend_ready(app: AppHandle) -> Result<(), String> {
-    log::info!("🎯 Frontend ready signal received - enabling event emission");
-    
-    // Check if we've already processed frontend ready to avoid duplicates
-    let mut ready_once = FRONTEND_READY_ONCE.lock().await;
-    if *ready_once {
-        log::warn!("⚠️ Frontend ready signal already processed - ignoring duplicate");
+#[specta::specta]
+pub async fn frontend_ready(app: AppHandle, window: WebviewWindow) -> Result<(), String> {
+    let label = window.label().to_string();
+    log::info!("🎯 Frontend ready signal received from window '{}' - enabling event emission", label);
+
+    // Only a reload (see `reset_window`) re-arms readiness
+    let Some(missed) = router().mark_ready(&label, now_secs()) else {
+        log::warn!("⚠️ Window '{}' already signalled ready - ignoring duplicate", label);
         return Ok(());
-    }
-    *ready_once = true;
-    drop(ready_once);
-
-    // Mark frontend as ready and process queued events
-    let mut state = FRONTEND_READY_STATE.write().await;
-    state.is_ready = true;
-    
-    if !state.queued_events.is_empty() {
-        log::info!("📦 Flushing {} queued events to frontend", state.queued_events.len());
-        
-        // Process all queued events
-        for event in state.queued_events.drain(..) {
-            if let Err(e) = app.emit(&event.event_name, &event.payload) {
-                log::error!("❌ Failed to emit queued event {}: {}", event.event_name, e);
+    };
+
+    if !missed.is_empty() {
+        log::info!("📦 Flushing {} queued events to window '{}'", missed.len(), label);
+        for (event_name, payload) in &missed {
+            if let Err(e) = app.emit_to(label.as_str(), event_name, payload) {
+                log::error!("❌ Failed to emit queued event {}: {}", event_name, e);
             } else {
-                log::debug!("📡 Emitted queued event: {}", event.event_name);
+                log::debug!("📡 Emitted queued event: {}", event_name);
             }
         }
-        
-        log::info!("✅ All queued events have been sent to frontend");
     }
-    
+
+    // Critical events from before this window was ready, or from a previous
+    // run that ended before they were acknowledged
+    if let Some(database) = app.try_state::<Arc<Database>>() {
+        let emitted = vault_core::outbox::redeliver_unacked(
+            &database,
+            |name| router().wants(&label, name),
+            |name, payload| {
+                record_event(name, &payload, false, None);
+                app.emit_to(label.as_str(), name, &payload).map_err(|e| e.to_string())
+            },
+        ).await?;
+        if emitted > 0 {
+            log::info!("📮 Delivered {} unacknowledged critical events to '{}'", emitted, label);
+        }
+    }
+
+    Ok(())
+}
+
+/// Limit the events a window receives to `categories`, the part of an event
+/// name before ':' (e.g. "portfolio", "wallet"); empty or "*" means all.
+/// Queued events outside the subscription are dropped.
+#[tauri::command]
+#[specta::specta]
+pub async fn subscribe_events(window_label: String, categories: Vec<String>) -> Result<WindowSubscription, String> {
+    let mut router = router();
+    router.subscribe(&window_label, &categories);
+    log::info!("📡 Window '{}' subscribed to {:?}", window_label, categories);
+    router.windows().into_iter()
+        .find(|w| w.label == window_label)
+        .ok_or_else(|| format!("Window {} is not registered", window_label))
+}
+
+/// Debug command: every window the event router knows
+#[tauri::command]
+#[specta::specta]
+pub async fn get_event_subscriptions() -> Result<Vec<WindowSubscription>, String> {
+    Ok(router().windows())
+}
+
+/// Debug command: events waiting for windows that are not ready, and how
+/// many the queue caps have dropped, coalesced or expired
+#[tauri::command]
+#[specta::specta]
+pub async fn get_event_queue_stats() -> Result<EventQueueStats, String> {
+    Ok(router().queue_stats())
+}
+
+/// Queue a window's events until it signals ready again; called when its
+/// page starts loading, so a reload does not lose what is emitted meanwhile
+pub fn reset_window(label: &str) {
+    if router().reset_window(label) {
+        log::info!("🔄 Window '{}' is reloading - queueing its events until it is ready", label);
+    }
+}
+
+/// Called by a page about to reload itself, ahead of the page load hook
+#[tauri::command]
+#[specta::specta]
+pub async fn frontend_reset(window: WebviewWindow) -> Result<(), String> {
+    reset_window(window.label());
     Ok(())
 }
 
-/// Emit an event to frontend or queue it if frontend isn't ready
+/// Drop a closed window's subscription and queued events
+pub fn forget_window(label: &str) {
+    let dropped = router().close_window(label);
+    log::info!("🪟 Window '{}' closed ({} queued events dropped)", label, dropped);
+}
+
+/// Emit an event to the windows subscribed to it, queueing it for those not ready yet
 pub async fn emit_or_queue_event(
     app: &AppHandle,
     event_name: &str,
     payload: serde_json::Value,
 ) -> Result<(), String> {
-    let state = FRONTEND_READY_STATE.read().await;
-    
-    if state.is_ready {
-        // Frontend is ready - emit immediately
-        if let Err(e) = app.emit(event_name, &payload) {
-            log::error!("❌ Failed to emit event {}: {}", event_name, e);
-            return Err(format!("Failed to emit event: {}", e));
+    if vault_core::outbox::is_critical(event_name) {
+        if let Some(database) = app.try_state::<Arc<Database>>() {
+            return emit_critical_event(app, &database, event_name, payload).await;
         }
-        log::debug!("📡 Emitted event: {}", event_name);
-    } else {
-        // Frontend not ready - queue the event
-        drop(state); // Release read lock
-        let mut state = FRONTEND_READY_STATE.write().await;
-        
-        let queued_event = QueuedEvent {
-            event_name: event_name.to_string(),
-            payload,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs(),
-        };
-        
-        state.queued_events.push(queued_event);
-        let queue_size = state.queued_events.len();
-        
-        println!("📋 Queued event: {} (total queued: {})", event_name, queue_size);
+        log::warn!("⚠️ Database not ready, {} is not persisted to the outbox", event_name);
+    }
+    route_in_memory(app, event_name, payload)
+}
+
+/// Emit to the ready subscribed windows and queue in memory for the others
+fn route_in_memory(app: &AppHandle, event_name: &str, payload: serde_json::Value) -> Result<(), String> {
+    let routing = router().route(event_name, &payload, now_secs());
+    record_event(event_name, &payload, routing.emit_to.is_empty(), None);
+
+    if !routing.queued_for.is_empty() {
+        log::debug!("📋 Queued event {} for {:?}", event_name, routing.queued_for);
+    }
+    if let Err(e) = emit_to_windows(app, &routing.emit_to, event_name, &payload) {
+        log::error!("❌ Failed to emit event {}: {}", event_name, e);
+        return Err(e);
+    }
+    if !routing.emit_to.is_empty() {
+        log::debug!("📡 Emitted event: {} to {:?}", event_name, routing.emit_to);
+    }
+
+    Ok(())
+}
+
+/// Persist a critical event, then emit it to the ready windows subscribed to
+/// it. Windows not ready yet get it from the outbox in `frontend_ready`. If
+/// the outbox cannot be written the event is still delivered, queued in
+/// memory like any other event.
+async fn emit_critical_event(
+    app: &AppHandle,
+    database: &Database,
+    event_name: &str,
+    payload: serde_json::Value,
+) -> Result<(), String> {
+    let (sequence, payload) = match vault_core::outbox::persist(database, event_name, payload.clone()).await {
+        Ok(persisted) => persisted,
+        Err(e) => {
+            log::error!("❌ Failed to persist critical event {} to the outbox, queueing it in memory: {}", event_name, e);
+            return route_in_memory(app, event_name, payload);
+        }
+    };
+    let targets = router().ready_targets(event_name);
+    record_event(event_name, &payload, targets.is_empty(), None);
+    if targets.is_empty() {
+        log::info!("📮 Critical event {} held in outbox as #{}", event_name, sequence);
+        return Ok(());
     }
-    
+
+    if let Err(e) = database.mark_outbox_attempt(sequence).await {
+        log::warn!("Failed to count outbox delivery of #{}: {}", sequence, e);
+    }
+    emit_to_windows(app, &targets, event_name, &payload)?;
+    log::debug!("📡 Emitted critical event: {} (#{})", event_name, sequence);
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// Acknowledge a critical event (its `outbox_sequence`) once the frontend has handled it
+#[tauri::command]
+#[specta::specta]
+pub async fn ack_event(sequence: i64, database: State<'_, Arc<Database>>) -> Result<bool, String> {
+    database.ack_outbox_event(sequence).await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Debug command: critical events still waiting for acknowledgement
+#[tauri::command]
+#[specta::specta]
+pub async fn get_unacked_events(database: State<'_, Arc<Database>>) -> Result<Vec<keepkey_db::OutboxEvent>, String> {
+    database.get_unacked_outbox_events().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Emit immediately from synchronous callbacks (e.g. progress reporting during a
+/// command) to the ready windows subscribed to the event; nothing is queued
+pub fn emit_event(app: &AppHandle, event_name: &str, payload: serde_json::Value) -> Result<(), String> {
+    let targets = router().ready_targets(event_name);
+    record_event(event_name, &payload, false, None);
+    emit_to_windows(app, &targets, event_name, &payload)
+}
+
+/// Recent events, optionally after a sequence number and filtered by name substring
+#[tauri::command]
+#[specta::specta]
+pub async fn get_event_history(
+    since_sequence: Option<u64>,
+    name_filter: Option<String>,
+) -> Result<Vec<EventRecord>, String> {
+    let history = EVENT_HISTORY.lock().map_err(|_| "Event history unavailable".to_string())?;
+    Ok(history.since(since_sequence, name_filter.as_deref()))
+}
+
+/// Developer command: re-send a recorded event to the windows subscribed to it
+#[tauri::command]
+#[specta::specta]
+pub async fn re_emit_event(app: AppHandle, sequence: u64) -> Result<(), String> {
+    let (name, payload) = {
+        let history = EVENT_HISTORY.lock().map_err(|_| "Event history unavailable".to_string())?;
+        if !history.developer_mode() {
+            return Err("Re-emitting events requires developer mode".to_string());
+        }
+        let record = history.get(sequence).ok_or_else(|| format!("Event {} is no longer in history", sequence))?;
+        let payload = record.payload.clone().ok_or_else(|| format!("Event {} has no retained payload", sequence))?;
+        (record.name.clone(), payload)
+    };
+
+    log::info!("🔁 Re-emitting event #{} ({})", sequence, name);
+    record_event(&name, &payload, false, Some(sequence));
+    let targets = router().ready_targets(&name);
+    emit_to_windows(&app, &targets, &name, &payload)
+}
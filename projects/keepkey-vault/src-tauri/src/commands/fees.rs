@@ -0,0 +1,245 @@
+// commands/fees.rs - Bitcoin fee suggestions for confirmation targets
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use serde::Serialize;
+use tauri::State;
+use keepkey_db::{Database, FeeRateCache};
+use vault_core::endpoints::{endpoint_manager, EndpointError, Priority};
+use vault_core::fees::{self, FeeHistogram, FeeSource, FeeSuggestion, FeeTiers};
+use vault_core::paths::{network_family, NetworkFamily};
+use vault_core::power::{BackgroundWork, PowerMonitor};
+
+/// Preference key for a self-hosted mempool.space-compatible endpoint
+pub const FEE_ENDPOINT_PREF: &str = "fee_endpoint";
+pub const DEFAULT_FEE_ENDPOINT: &str = "https://mempool.space";
+
+/// Cached estimates younger than this are used without refetching
+const FEE_CACHE_TTL_SECS: i64 = 60;
+/// `get_fee_rates` serves cached rates up to this age, refreshing them in
+/// the background once they are past FEE_CACHE_TTL_SECS (twice that on the
+/// reduced power profile)
+const FEE_RATES_MAX_AGE_SECS: i64 = 5 * 60;
+const FEE_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fee suggestions for every target and where they came from
+#[derive(Debug, Serialize, specta::Type)]
+pub struct FeeSuggestions {
+    pub caip: String,
+    /// "histogram" or "tiers"
+    pub source: &'static str,
+    pub updated_at: i64,
+    pub suggestions: Vec<FeeSuggestion>,
+}
+
+/// Fee rate tiers (sat/vB) for a network
+#[derive(Debug, Serialize, specta::Type)]
+pub struct FeeRates {
+    pub caip: String,
+    pub fastest: u64,
+    pub fast: u64,
+    pub average: u64,
+    pub updated_at: i64,
+    /// The endpoint could not be reached and these are older cached rates
+    pub stale: bool,
+}
+
+impl FeeRates {
+    fn new(caip: &str, tiers: FeeTiers, updated_at: i64, stale: bool) -> Self {
+        let tiers = tiers.floored();
+        FeeRates {
+            caip: caip.to_string(),
+            fastest: tiers.fastest,
+            fast: tiers.fast,
+            average: tiers.average,
+            updated_at,
+            stale,
+        }
+    }
+}
+
+/// Base URL for a Bitcoin network's mempool API
+async fn fee_endpoint(database: &Database, caip: &str) -> Result<String, String> {
+    let base = database
+        .get_preference(FEE_ENDPOINT_PREF)
+        .await
+        .ok()
+        .flatten()
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_FEE_ENDPOINT.to_string());
+    let base = base.trim_end_matches('/');
+    match network_family(caip)? {
+        NetworkFamily::Utxo { coin_name } if coin_name == "Bitcoin" => Ok(base.to_string()),
+        NetworkFamily::Utxo { coin_name } if coin_name == "Testnet" => Ok(format!("{}/testnet", base)),
+        _ => Err(format!("Fee suggestions are only available for Bitcoin, not {}", caip)),
+    }
+}
+
+async fn fetch_json(client: &reqwest::Client, url: &str) -> Result<serde_json::Value, String> {
+    endpoint_manager()
+        .send(client.get(url), Priority::Interactive)
+        .await
+        .and_then(|r| r.error_for_status().map_err(EndpointError::Request))
+        .map_err(|e| format!("Fee request to {} failed: {}", url, e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid fee response from {}: {}", url, e))
+}
+
+/// Fetch the histogram, falling back to the three-tier endpoint when the
+/// server does not provide one
+async fn fetch_fee_source(base: &str) -> Result<FeeSource, String> {
+    let client = reqwest::Client::builder()
+        .timeout(FEE_REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    // An empty histogram is an empty mempool, not missing data
+    match fetch_json(&client, &format!("{}/api/mempool", base))
+        .await
+        .and_then(|json| FeeHistogram::from_mempool_json(&json))
+    {
+        Ok(histogram) => return Ok(FeeSource::Histogram(histogram)),
+        Err(e) => log::info!("⛽ No fee histogram from {} ({}), using fee tiers", base, e),
+    }
+
+    let json = fetch_json(&client, &format!("{}/api/v1/fees/recommended", base)).await?;
+    Ok(FeeSource::Tiers(FeeTiers::from_recommended_json(&json)?))
+}
+
+fn source_from_cache(cached: &FeeRateCache) -> FeeSource {
+    match cached.histogram.as_deref().and_then(|h| serde_json::from_str::<FeeHistogram>(h).ok()) {
+        Some(histogram) => FeeSource::Histogram(histogram),
+        None => FeeSource::Tiers(FeeTiers {
+            fastest: cached.fastest.max(0) as u64,
+            fast: cached.fast.max(0) as u64,
+            average: cached.average.max(0) as u64,
+        }),
+    }
+}
+
+fn tiers_of(source: &FeeSource) -> FeeTiers {
+    match source {
+        FeeSource::Histogram(histogram) => histogram.tiers(),
+        FeeSource::Tiers(tiers) => *tiers,
+    }
+}
+
+/// Fetch estimates for a Bitcoin network and store them in fee_rate_cache
+async fn fetch_and_cache(database: &Database, caip: &str) -> Result<(FeeSource, i64), String> {
+    let network = caip.split('/').next().unwrap_or(caip);
+    let base = fee_endpoint(database, caip).await?;
+    let source = fetch_fee_source(&base).await?;
+    let now = chrono::Utc::now().timestamp();
+
+    let tiers = tiers_of(&source);
+    let histogram = match &source {
+        FeeSource::Histogram(histogram) => serde_json::to_string(histogram).ok(),
+        FeeSource::Tiers(_) => None,
+    };
+    if let Err(e) = database.cache_fee_rates(&FeeRateCache {
+        caip: network.to_string(),
+        fastest: tiers.fastest as i64,
+        fast: tiers.fast as i64,
+        average: tiers.average as i64,
+        histogram,
+        last_updated: now,
+    }).await {
+        log::warn!("Failed to cache fee estimates: {}", e);
+    }
+    Ok((source, now))
+}
+
+/// Networks with a background refresh running, so repeated calls start only one
+fn refreshing() -> &'static Mutex<HashSet<String>> {
+    static REFRESHING: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    REFRESHING.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Refresh a network's cached estimates off the caller's path
+fn spawn_refresh(database: Arc<Database>, caip: String) {
+    if !refreshing().lock().unwrap().insert(caip.clone()) {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = fetch_and_cache(&database, &caip).await {
+            log::warn!("⛽ Background fee refresh for {} failed: {}", caip, e);
+        }
+        refreshing().lock().unwrap().remove(&caip);
+    });
+}
+
+/// Current fee source for a Bitcoin network, from cache when fresh. A failed
+/// fetch falls back to stale cached data before giving up.
+pub async fn load_fee_source(database: &Database, caip: &str) -> Result<(FeeSource, i64), String> {
+    let network = caip.split('/').next().unwrap_or(caip);
+    let cached = database.get_fee_rates(network).await.unwrap_or(None);
+    let now = chrono::Utc::now().timestamp();
+    if let Some(cached) = cached.as_ref().filter(|c| now - c.last_updated < FEE_CACHE_TTL_SECS) {
+        return Ok((source_from_cache(cached), cached.last_updated));
+    }
+
+    match fetch_and_cache(database, caip).await {
+        Ok(fetched) => Ok(fetched),
+        Err(e) => {
+            let Some(cached) = cached else { return Err(e) };
+            log::warn!("⛽ Using cached fee estimates from {}: {}", cached.last_updated, e);
+            Ok((source_from_cache(&cached), cached.last_updated))
+        }
+    }
+}
+
+/// Fastest/fast/average fee rates for a Bitcoin network. Rates cached in the
+/// last five minutes are returned at once (and refreshed in the background
+/// once over a minute old); older ones are refetched, and if that fails the
+/// last cached rates come back marked `stale`.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_fee_rates(
+    caip: String,
+    database: State<'_, Arc<Database>>,
+    power_monitor: State<'_, Arc<PowerMonitor>>,
+) -> Result<FeeRates, String> {
+    let network = caip.split('/').next().unwrap_or(&caip);
+    let cached = database.get_fee_rates(network).await.unwrap_or(None);
+    let now = chrono::Utc::now().timestamp();
+    let ttl = Duration::from_secs(FEE_CACHE_TTL_SECS as u64);
+    let refresh_after = power_monitor.interval(BackgroundWork::PeriodicRefresh, ttl).unwrap_or(ttl).as_secs() as i64;
+    if let Some(cached) = cached.as_ref().filter(|c| now - c.last_updated < FEE_RATES_MAX_AGE_SECS) {
+        if now - cached.last_updated >= refresh_after {
+            spawn_refresh(Arc::clone(&database), caip.clone());
+        }
+        return Ok(FeeRates::new(&caip, tiers_of(&source_from_cache(cached)), cached.last_updated, false));
+    }
+
+    match fetch_and_cache(&database, &caip).await {
+        Ok((source, updated_at)) => Ok(FeeRates::new(&caip, tiers_of(&source), updated_at, false)),
+        Err(e) => {
+            let Some(cached) = cached else { return Err(e) };
+            log::warn!("⛽ Serving stale fee rates from {}: {}", cached.last_updated, e);
+            Ok(FeeRates::new(&caip, tiers_of(&source_from_cache(&cached)), cached.last_updated, true))
+        }
+    }
+}
+
+/// Suggested fee rate, total fee and expected wait for each confirmation target
+#[tauri::command]
+#[specta::specta]
+pub async fn get_fee_suggestions(
+    caip: String,
+    tx_vsize_estimate: u64,
+    database: State<'_, Arc<Database>>,
+) -> Result<FeeSuggestions, String> {
+    if tx_vsize_estimate == 0 {
+        return Err("Transaction size estimate must be greater than zero".to_string());
+    }
+    let (source, updated_at) = load_fee_source(&database, &caip).await?;
+    let price = database.get_asset_price_usd(&caip).await.unwrap_or(None);
+    Ok(FeeSuggestions {
+        caip: caip.clone(),
+        source: source.name(),
+        updated_at,
+        suggestions: fees::suggest_fees(&source, tx_vsize_estimate, price)?,
+    })
+}
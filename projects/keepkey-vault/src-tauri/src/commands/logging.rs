@@ -1,27 +1,49 @@
-// commands/logging.rs - Logging commands
+// commands/logging.rs - Device request log
+//
+// Records are written by vault_core::device_log: every exchange on a device
+// queue is logged on its own, and operations spanning many messages (firmware
+// updates) add their own request/response pair through these hooks.
 use serde_json::Value;
+use vault_core::device_log::{device_log, DeviceLogRecord};
 
-/// Log a device request (stub implementation)
+/// Largest page `get_recent_device_logs` returns
+const MAX_RECENT_LOGS: u32 = 1000;
+
+/// Log the start of a device operation
 pub async fn log_device_request(
-    _device_id: &str,
-    _request_id: &str,
-    _command: &str,
-    _data: &Value,
+    device_id: &str,
+    request_id: &str,
+    command: &str,
+    data: &Value,
 ) -> Result<(), String> {
-    // TODO: Implement proper logging like v5
+    device_log().log_request(device_id, request_id, command, data);
     Ok(())
 }
 
-/// Log a device response (stub implementation) 
+/// Log how a device operation from `log_device_request` ended
 pub async fn log_device_response(
-    _device_id: &str,
-    _request_id: &str,
-    _success: bool,
-    _data: &Value,
-    _error: Option<&str>,
+    device_id: &str,
+    request_id: &str,
+    success: bool,
+    data: &Value,
+    error: Option<&str>,
 ) -> Result<(), String> {
-    // TODO: Implement proper logging like v5
+    device_log().log_response(device_id, request_id, success, data, error);
     Ok(())
 }
 
-pub fn _placeholder() {} 
\ No newline at end of file
+/// The latest device log records, newest first, for the support panel
+#[tauri::command]
+#[specta::specta]
+pub async fn get_recent_device_logs(limit: u32) -> Result<Vec<DeviceLogRecord>, String> {
+    device_log().recent(limit.min(MAX_RECENT_LOGS) as usize).await
+}
+
+/// Delete the device request log and its rotated files
+#[tauri::command]
+#[specta::specta]
+pub async fn clear_device_logs() -> Result<(), String> {
+    device_log().clear().await?;
+    log::info!("🧹 Cleared the device request log");
+    Ok(())
+}
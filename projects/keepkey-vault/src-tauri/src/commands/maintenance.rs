@@ -0,0 +1,77 @@
+// commands/maintenance.rs - Maintenance mode for heavy database operations
+//
+// The session state lives in vault_core::maintenance; the invoke handler in
+// lib.rs refuses non-exempt commands while a session is active and the
+// scheduler skips its jobs. Heavy commands call `begin` and keep the guard
+// for the whole operation. The frontend can also hold a session open across
+// several steps with enter_maintenance / exit_maintenance.
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+use vault_core::maintenance::{
+    MaintenanceController, MaintenanceGuard, MaintenanceStatus, MaintenanceTransition,
+    DEFAULT_DRAIN_TIMEOUT, MAINTENANCE_ENDED_EVENT, MAINTENANCE_STARTED_EVENT,
+};
+
+/// Session opened by `enter_maintenance`, ended by `exit_maintenance`
+pub type ManualMaintenance = Arc<Mutex<Option<MaintenanceGuard>>>;
+
+/// Controller that announces every session start and end to the windows
+pub fn new_maintenance_controller(app: AppHandle) -> Arc<MaintenanceController> {
+    Arc::new(MaintenanceController::new(move |transition, reason| {
+        let (event, verb) = match transition {
+            MaintenanceTransition::Started => (MAINTENANCE_STARTED_EVENT, "started"),
+            MaintenanceTransition::Ended => (MAINTENANCE_ENDED_EVENT, "ended"),
+        };
+        log::info!("🛠️ Maintenance {}: {}", verb, reason);
+        // Sync emit: the end event also fires from a guard dropped during a panic
+        if let Err(e) = super::events::emit_event(&app, event, serde_json::json!({ "reason": reason })) {
+            log::warn!("Failed to emit {}: {}", event, e);
+        }
+    }))
+}
+
+pub fn new_manual_maintenance() -> ManualMaintenance {
+    Arc::new(Mutex::new(None))
+}
+
+/// Enter maintenance for a heavy operation; it lasts until the guard drops
+pub(crate) async fn begin(maintenance: &Arc<MaintenanceController>, reason: &str) -> Result<MaintenanceGuard, String> {
+    maintenance.enter(reason, DEFAULT_DRAIN_TIMEOUT).await.map_err(|e| e.to_json_string())
+}
+
+/// Whether maintenance is active, why, and what is waiting on it
+#[tauri::command]
+#[specta::specta]
+pub async fn get_maintenance_state(
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<MaintenanceStatus, String> {
+    Ok(maintenance.status())
+}
+
+/// Hold maintenance open until `exit_maintenance`, e.g. around a multi-step
+/// import. Waits for any running session first.
+#[tauri::command]
+#[specta::specta]
+pub async fn enter_maintenance(
+    reason: String,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+    manual: State<'_, ManualMaintenance>,
+) -> Result<MaintenanceStatus, String> {
+    let mut held = manual.lock().await;
+    if held.is_some() {
+        return Err("Maintenance was already entered; exit it first".to_string());
+    }
+    *held = Some(begin(&maintenance, reason.trim()).await?);
+    Ok(maintenance.status())
+}
+
+/// End the session opened by `enter_maintenance`; false if none was open
+#[tauri::command]
+#[specta::specta]
+pub async fn exit_maintenance(
+    manual: State<'_, ManualMaintenance>,
+) -> Result<bool, String> {
+    Ok(manual.lock().await.take().is_some())
+}
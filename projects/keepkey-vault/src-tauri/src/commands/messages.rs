@@ -0,0 +1,180 @@
+// commands/messages.rs - Signed message export and offline verification
+//
+// Formats and verification live in vault_core::signed_message; this module
+// gets the signature from the device and moves text in and out of files.
+// Every command that has the device sign a message goes through
+// `begin_message_signing` first.
+
+use bitcoin::Network;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, SigningAuditInput};
+use keepkey_rust::chains::{bitcoin as btc_chain, ethereum as eth_chain};
+use vault_core::asset_capabilities::{self, AssetOperation};
+use vault_core::maintenance::{DeviceOperationGuard, MaintenanceController};
+use vault_core::paths::{network_family, parse_derivation_path, NetworkFamily};
+use vault_core::signed_message::{self, MessageVerification, SignatureScheme, SignedMessage, SignedMessageFormat};
+use vault_core::signing_origin::{self, LimitConfirmations, SigningOrigin, SigningTicket};
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use super::device::get_or_create_device_queue;
+use super::DeviceQueueManager;
+
+/// A message signature cleared to go to the device. Maintenance waits for it
+/// until it is dropped; `finish` records the outcome in the signing audit.
+pub(crate) struct MessageSigning {
+    _operation: DeviceOperationGuard,
+    ticket: SigningTicket,
+}
+
+impl MessageSigning {
+    pub(crate) async fn finish<T, E: std::fmt::Display>(self, database: &Database, result: &Result<T, E>) {
+        match result {
+            Ok(_) => signing_origin::complete_signing(database, &self.ticket, Ok(None)).await,
+            Err(e) => signing_origin::complete_signing(database, &self.ticket, Err(&e.to_string())).await,
+        }
+    }
+}
+
+/// Checks a message signature passes before the device is asked: no
+/// maintenance, a verified device, firmware that signs messages for `caip`,
+/// and an audit entry for the main window. A message moves no value, so it
+/// never counts against a spending limit.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn begin_message_signing(
+    app: &AppHandle,
+    database: &Database,
+    wallet_sessions: &WalletSessions,
+    limit_confirmations: &LimitConfirmations,
+    maintenance: &Arc<MaintenanceController>,
+    device_id: &str,
+    caip: &str,
+    operation: &str,
+) -> Result<MessageSigning, String> {
+    let guard = maintenance.device_operation(operation).map_err(|e| e.to_json_string())?;
+    vault_core::authenticity::require_verified_device(device_id)?;
+    asset_capabilities::require_capability(database, caip, device_id, AssetOperation::SignMessage).await?;
+
+    let origin = SigningOrigin::MainWindow;
+    let ticket = signing_origin::authorize_signing(
+        database,
+        limit_confirmations,
+        &origin,
+        SigningAuditInput {
+            origin: origin.key(),
+            device_id: device_id.to_string(),
+            wallet_fingerprint: active_wallet_fingerprint(wallet_sessions, device_id).await,
+            caip: caip.to_string(),
+            value_usd: Some(0.0),
+        },
+        super::signing::LIMIT_CONFIRMATION_TIMEOUT,
+        |audit_id, decision| super::signing::notify_limit_confirmation(app, audit_id, &origin, caip, decision),
+    ).await?;
+    Ok(MessageSigning { _operation: guard, ticket })
+}
+
+/// Account that signs the message
+#[derive(Debug, Deserialize, specta::Type)]
+pub struct MessagePathParams {
+    /// CAIP-2 network id; Bitcoin mainnet or an EVM chain
+    pub network_id: String,
+    /// BIP-32 derivation path, e.g. m/84'/0'/0'/0/0
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, specta::Type)]
+pub struct ExportedSignedMessage {
+    pub address: String,
+    pub signature: String,
+    /// The signed message in the requested format
+    pub text: String,
+    /// File the text was written to, when one was given
+    pub output_path: Option<String>,
+}
+
+/// Sign `message` on the device and render it in `format`. The device shows
+/// the message for confirmation. With `output_path` the text is also written
+/// to that file.
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_signed_message(
+    app: AppHandle,
+    device_id: String,
+    path_params: MessagePathParams,
+    message: String,
+    format: SignedMessageFormat,
+    output_path: Option<String>,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    limit_confirmations: State<'_, LimitConfirmations>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<ExportedSignedMessage, String> {
+    let address_n = parse_derivation_path(&path_params.path)?;
+    let (scheme, slip44) = match network_family(&path_params.network_id)? {
+        NetworkFamily::Utxo { .. } => (SignatureScheme::Bitcoin, 0),
+        NetworkFamily::Evm { .. } => (SignatureScheme::Eip191, 60),
+    };
+    if scheme == SignatureScheme::Eip191 && format == SignedMessageFormat::BitcoinArmor {
+        return Err("The signed message armor format only carries Bitcoin signatures".to_string());
+    }
+    let caip = format!("{}/slip44:{}", path_params.network_id, slip44);
+    let queue = get_or_create_device_queue(&device_id, &queue_manager).await?;
+    let signing = begin_message_signing(
+        &app, &database, &wallet_sessions, &limit_confirmations, &maintenance, &device_id, &caip, "export_signed_message",
+    ).await?;
+
+    let signed = match scheme {
+        SignatureScheme::Bitcoin => btc_chain::sign_message(&queue, &address_n, &message).await
+            .map_err(|e| format!("Device failed to sign the message: {}", e)),
+        SignatureScheme::Eip191 => eth_chain::message::sign_message(&queue, &address_n, message.as_bytes()).await
+            .map(|bytes| format!("0x{}", hex::encode(bytes)))
+            .map_err(|e| format!("Device failed to sign the message: {}", e)),
+    };
+    signing.finish(&database, &signed).await;
+    let signature = signed?;
+    let address = match scheme {
+        SignatureScheme::Bitcoin => signed_message::bitcoin_signer_address(&message, &signature, Network::Bitcoin)?,
+        SignatureScheme::Eip191 => signed_message::eip191_signer_address(message.as_bytes(), &signature)?,
+    };
+
+    let signed = SignedMessage { scheme, address, message, signature };
+    let text = signed.to_text(format)?;
+    if let Some(path) = &output_path {
+        std::fs::write(path, &text).map_err(|e| format!("Could not write {}: {}", path, e))?;
+    }
+    log::info!("✍️ Signed a message with {} on {} ({:?})", signed.address, device_id, format);
+
+    Ok(ExportedSignedMessage {
+        address: signed.address,
+        signature: signed.signature,
+        text,
+        output_path,
+    })
+}
+
+/// Verify a signed message without the device.
+///
+/// `path_or_text` is either a path to the file or its contents; the format
+/// (Bitcoin armor, EIP-191 JSON, or address/signature/message lines) is detected.
+#[tauri::command]
+#[specta::specta]
+pub async fn verify_signed_message_file(path_or_text: String) -> Result<MessageVerification, String> {
+    let trimmed = path_or_text.trim();
+    let looks_inline = trimmed.starts_with('{') || trimmed.contains('\n');
+    let text = match std::path::Path::new(trimmed) {
+        path if !looks_inline && path.is_file() => {
+            std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?
+        }
+        _ => path_or_text.clone(),
+    };
+
+    let verification = signed_message::verify_signed_message_text(&text)?;
+    log::info!(
+        "🔏 Signed message from {}: {}",
+        verification.address,
+        if verification.valid { "valid" } else { "invalid" }
+    );
+    Ok(verification)
+}
@@ -1,22 +1,51 @@
 // commands/mod.rs - Organized command modules
 
-// Core types used across commands
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use std::collections::HashMap;
-use keepkey_rust::device_queue::DeviceQueueHandle;
-
-pub type DeviceQueueManager = Arc<Mutex<HashMap<String, DeviceQueueHandle>>>;
+// Core types used across commands (shared with kkvault-cli through vault-core)
+pub use vault_core::DeviceQueueManager;
 
 // Command modules organized by functionality
 pub mod device;
 pub mod pin;
 pub mod recovery; 
 pub mod verification;
+pub mod addresses;
 pub mod logging;
 pub mod config;
 pub mod api;
 pub mod cache;
+pub mod storage;
+pub mod backups;
+pub mod assets;
+pub mod portfolio;
+pub mod networks;
+pub mod send;
+pub mod fees;
+pub mod bitcoin;
+pub mod ripple;
+pub mod thorchain;
+pub mod osmosis;
+pub mod eos;
+pub mod approvals;
+pub mod tokens;
+pub mod secure_notes;
+pub mod transactions;
+pub mod broadcast;
+pub mod tax;
+pub mod signing;
+pub mod messages;
+pub mod confirmation;
+pub mod wallets;
+pub mod address_scan;
+pub mod diagnostics;
+pub mod power;
+pub mod clock;
+pub mod environment;
+pub mod tray;
+pub mod quick_actions;
+pub mod accessibility;
+pub mod app_lock;
+pub mod maintenance;
+pub mod warmup;
 pub mod test;
 
 // Event handling utilities
@@ -0,0 +1,119 @@
+// commands/networks.rs - Network enable/disable and the queries it scopes
+//
+// The enabled set lives in keepkey-db (networks.rs); disabled networks drop
+// out of portfolio listings, derivation paths, asset search, transaction
+// tracking, xpub frontloading and sync requests, and asset capabilities
+// report them disabled.
+
+use std::sync::Arc;
+use serde_json::json;
+use tauri::{AppHandle, State};
+use keepkey_db::{Asset, AssetSearchResult, Database, DerivationPathInfo, Network, NetworkStatus};
+
+/// Most results returned by `search_assets`
+const SEARCH_LIMIT: u32 = 50;
+
+/// Write the first-run enabled set once the devices have frontloaded xpubs
+pub async fn apply_network_defaults(database: &Database) -> Result<(), String> {
+    if let Some(enabled) = database.initialize_network_defaults().await
+        .map_err(|e| format!("Database error: {}", e))? {
+        log::info!("🌐 Enabled {} network(s) with frontloaded xpubs: {:?}", enabled.len(), enabled);
+    }
+    Ok(())
+}
+
+/// Networks from the registry with their enabled flags
+#[tauri::command]
+#[specta::specta]
+pub async fn list_available_networks(database: State<'_, Arc<Database>>) -> Result<Vec<NetworkStatus>, String> {
+    apply_network_defaults(&database).await?;
+    database.list_network_statuses().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Full registry records of the known networks, testnets only if
+/// `include_testnets`
+#[tauri::command]
+#[specta::specta]
+pub async fn get_networks(
+    include_testnets: Option<bool>,
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<Network>, String> {
+    database.list_networks(include_testnets.unwrap_or(false)).await
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+/// Switch a network on or off. Nothing is deleted; re-enabling a network
+/// requests a sync of just that network.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_network_enabled(
+    app: AppHandle,
+    network_id: String,
+    enabled: bool,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    if !database.set_network_enabled(&network_id, enabled).await.map_err(|e| format!("Database error: {}", e))? {
+        return Err(format!("Unknown network: {}", network_id));
+    }
+    log::info!("🌐 Network {} {}", network_id, if enabled { "enabled" } else { "disabled" });
+
+    let disabled = database.get_disabled_networks().await.map_err(|e| format!("Database error: {}", e))?;
+    super::emit_or_queue_event(&app, "networks:changed", json!({
+        "network_id": network_id,
+        "enabled": enabled,
+        "disabled_network_ids": disabled,
+    })).await?;
+
+    if enabled {
+        let device_ids: Vec<String> = super::tray::connected_device_ids().into_iter().collect();
+        let imported_device_ids: Vec<String> = database.list_imported_wallets().await
+            .map_err(|e| format!("Database error: {}", e))?
+            .into_iter()
+            .map(|wallet| wallet.device_id)
+            .collect();
+        super::emit_or_queue_event(&app, "portfolio:sync-requested", json!({
+            "device_ids": device_ids,
+            "imported_device_ids": imported_device_ids,
+            "network_ids": [network_id],
+            "reason": "network_enabled",
+        })).await?;
+    }
+    Ok(())
+}
+
+/// Assets matching `query` by symbol or name, on `network_id` if given;
+/// disabled networks are left out unless `include_disabled`. At most `limit`
+/// results, capped at SEARCH_LIMIT.
+#[tauri::command]
+#[specta::specta]
+pub async fn search_assets(
+    query: String,
+    network_id: Option<String>,
+    include_disabled: Option<bool>,
+    limit: Option<u32>,
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<AssetSearchResult>, String> {
+    let limit = limit.unwrap_or(SEARCH_LIMIT).min(SEARCH_LIMIT);
+    database.search_assets(&query, network_id.as_deref(), include_disabled.unwrap_or(false), limit).await
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+/// A registry asset by CAIP, None if it is not known
+#[tauri::command]
+#[specta::specta]
+pub async fn get_asset(caip: String, database: State<'_, Arc<Database>>) -> Result<Option<Asset>, String> {
+    database.get_asset_by_caip(&caip).await
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+/// Catalog derivation paths; paths only used by disabled networks are left
+/// out unless `include_disabled`
+#[tauri::command]
+#[specta::specta]
+pub async fn get_derivation_paths(
+    include_disabled: Option<bool>,
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<DerivationPathInfo>, String> {
+    database.get_derivation_paths(include_disabled.unwrap_or(false)).await
+        .map_err(|e| format!("Database error: {}", e))
+}
@@ -0,0 +1,126 @@
+// commands/osmosis.rs - Signing Osmosis Amino sign docs
+//
+// Takes the StdSignDoc JSON an Osmosis frontend produces (sends, staking,
+// IBC, gamm swaps and pool joins/exits) and returns the signature with the
+// canonical doc it covers. The caller assembles and broadcasts the tx.
+
+use std::sync::Arc;
+use std::time::SystemTime;
+use serde::Serialize;
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, SigningAuditInput};
+use keepkey_rust::chains::cosmos::CosmosMessageType;
+use keepkey_rust::chains::osmosis::{sign_osmosis_transaction, OsmosisMessageType, OsmosisTransaction};
+use vault_core::clock::ClockState;
+use vault_core::maintenance::MaintenanceController;
+use vault_core::paths::parse_derivation_path;
+use vault_core::signing_origin::{self, LimitConfirmations, SigningOrigin};
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use super::device::get_or_create_device_queue;
+use super::DeviceQueueManager;
+
+const OSMO_CAIP: &str = "cosmos:osmosis-1/slip44:118";
+const OSMO_DENOM: &str = "uosmo";
+
+/// Account 0, first address
+const DEFAULT_OSMOSIS_PATH: &str = "m/44'/118'/0'/0/0";
+
+/// Signature and the sign doc it covers
+#[derive(Debug, Serialize, specta::Type)]
+pub struct SignedOsmosisTx {
+    /// Compressed public key as hex
+    pub public_key: String,
+    /// Signature as hex
+    pub signature: String,
+    /// The canonical Amino JSON that was signed
+    pub signed_doc: String,
+}
+
+/// OSMO leaving the wallet, for the signing limits; other denoms are not priced
+fn uosmo_spent(transaction: &OsmosisTransaction) -> u128 {
+    let uosmo = |denom: &str, amount: &str| -> u128 {
+        if denom == OSMO_DENOM { amount.parse().unwrap_or(0) } else { 0 }
+    };
+    transaction.messages.iter().map(|message| match message {
+        OsmosisMessageType::Cosmos(CosmosMessageType::Send { amount, .. }) => {
+            amount.iter().map(|c| uosmo(&c.denom, &c.amount)).sum()
+        }
+        OsmosisMessageType::Cosmos(CosmosMessageType::IbcTransfer { amount, .. }) => uosmo(&amount.denom, &amount.amount),
+        OsmosisMessageType::SwapExactAmountIn { token_in, .. } => uosmo(&token_in.denom, &token_in.amount),
+        OsmosisMessageType::JoinPool { token_in_maxs, .. } => {
+            token_in_maxs.iter().map(|c| uosmo(&c.denom, &c.amount)).sum()
+        }
+        _ => 0,
+    }).sum()
+}
+
+/// Refuse IBC transfers whose timeout has passed by the corrected clock;
+/// the chain would reject them anyway, after the user confirmed on the device
+fn check_ibc_timeouts(clock: &ClockState, transaction: &OsmosisTransaction) -> Result<(), String> {
+    for message in &transaction.messages {
+        if let OsmosisMessageType::Cosmos(CosmosMessageType::IbcTransfer { timeout_timestamp, .. }) = message {
+            clock.check_ibc_timeout(*timeout_timestamp, SystemTime::now()).map_err(|e| e.to_json_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Sign an Osmosis Amino JSON sign doc with the key at `path`
+/// (m/44'/118'/0'/0/0 when omitted)
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+pub async fn sign_osmosis_tx(
+    app: AppHandle,
+    device_id: String,
+    tx_json: String,
+    path: Option<String>,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    limit_confirmations: State<'_, LimitConfirmations>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+    clock_state: State<'_, Arc<ClockState>>,
+) -> Result<SignedOsmosisTx, String> {
+    let _operation = maintenance.device_operation("sign_osmosis_tx").map_err(|e| e.to_json_string())?;
+    vault_core::authenticity::require_verified_device(&device_id)?;
+    let address_n = parse_derivation_path(path.as_deref().unwrap_or(DEFAULT_OSMOSIS_PATH))?;
+    let transaction = OsmosisTransaction::from_amino_json(address_n, &tx_json).map_err(|e| e.to_string())?;
+    check_ibc_timeouts(&clock_state, &transaction)?;
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let origin = SigningOrigin::MainWindow;
+
+    let queue = get_or_create_device_queue(&device_id, &queue_manager).await?;
+    let price = database.get_asset_price_usd(OSMO_CAIP).await.unwrap_or(None);
+    let value_usd = price.map(|p| uosmo_spent(&transaction) as f64 / 1_000_000.0 * p);
+    let ticket = signing_origin::authorize_signing(
+        &database,
+        &limit_confirmations,
+        &origin,
+        SigningAuditInput {
+            origin: origin.key(),
+            device_id: device_id.clone(),
+            wallet_fingerprint,
+            caip: OSMO_CAIP.to_string(),
+            value_usd,
+        },
+        super::signing::LIMIT_CONFIRMATION_TIMEOUT,
+        |audit_id, decision| super::signing::notify_limit_confirmation(&app, audit_id, &origin, OSMO_CAIP, decision),
+    ).await?;
+
+    log::info!("Signing Osmosis tx on {}: {} messages", device_id, transaction.messages.len());
+
+    // The sign doc is checked before the audit records the outcome
+    let signed = sign_osmosis_transaction(&queue, transaction).await
+        .map_err(|e| format!("Signing failed: {}", e))
+        .and_then(|signed| Ok(SignedOsmosisTx {
+            public_key: hex::encode(&signed.public_key),
+            signature: hex::encode(&signed.signature),
+            signed_doc: String::from_utf8(signed.sign_doc).map_err(|e| format!("Invalid sign doc: {}", e))?,
+        }));
+    match &signed {
+        Ok(_) => signing_origin::complete_signing(&database, &ticket, Ok(None)).await,
+        Err(error) => signing_origin::complete_signing(&database, &ticket, Err(error)).await,
+    }
+    signed
+}
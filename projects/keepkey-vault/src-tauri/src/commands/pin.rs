@@ -1,8 +1,529 @@
 // commands/pin.rs - PIN operation commands
+//
+// PIN positions are entered against the scrambled matrix on the device
+// screen; the digits sent are positions, not the PIN, but they are still
+// never logged or put into error messages.
+//
+// Unlocking and changing a PIN both answer the device's matrices with
+// `send_pin_matrix_ack`; a per-device session remembers which matrix is up,
+// whether a change is in progress and how many wrong PINs were entered.
 
-// PIN commands will be implemented here
-// For now, this is a stub to allow compilation
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use serde::Serialize;
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+use keepkey_rust::messages::{self, Message};
+use vault_core::confirmation::{Confirmations, HighRiskOperation};
+use vault_core::device_flow::{DeviceFlow, DeviceFlowState};
+use vault_core::request_queue::request_queue;
+use vault_core::wallet_session::WalletSessions;
+use super::DeviceQueueManager;
+use super::confirmation::require_confirmation;
 
-pub fn _placeholder() {
-    // This prevents the "unused module" warning
-} 
\ No newline at end of file
+/// Where PIN removal stands after the device answered
+#[derive(Debug, Clone, Copy, Serialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum PinRemovalStatus {
+    Removed,
+    /// The device wants the current PIN first (PIN matrix on screen)
+    AwaitingPin,
+}
+
+/// Which PIN the device matrix is asking for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum PinMatrixType {
+    Current,
+    NewFirst,
+    NewSecond,
+}
+
+impl PinMatrixType {
+    fn from_request(request: &messages::PinMatrixRequest) -> Option<Self> {
+        match messages::PinMatrixRequestType::from_i32(request.r#type?)? {
+            messages::PinMatrixRequestType::Current => Some(Self::Current),
+            messages::PinMatrixRequestType::NewFirst => Some(Self::NewFirst),
+            messages::PinMatrixRequestType::NewSecond => Some(Self::NewSecond),
+        }
+    }
+
+    /// Event announcing this matrix during a PIN change
+    fn change_event(self) -> &'static str {
+        match self {
+            Self::Current => "pin:request-current",
+            Self::NewFirst => "pin:request-new",
+            Self::NewSecond => "pin:request-confirm",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum PinEntryStatus {
+    Unlocked,
+    /// A PIN change or removal finished
+    Changed,
+    /// A PIN matrix is on the device screen
+    AwaitingPin,
+    /// The device wants a button press to finish
+    AwaitingButton,
+    /// A recovery moved on to the phrase; see `recovery:character-request`
+    AwaitingCharacter,
+    Failed,
+}
+
+/// Where PIN entry stands after the device answered
+#[derive(Debug, Clone, Serialize, specta::Type)]
+pub struct PinEntryResult {
+    pub status: PinEntryStatus,
+    /// Set while awaiting a PIN
+    pub matrix_type: Option<PinMatrixType>,
+    /// Wrong PINs entered on this device since it was last unlocked. The
+    /// KeepKey has no attempt limit to count down from: it wipes nothing and
+    /// instead doubles its wait before each further try.
+    pub failed_attempts: u32,
+    /// Whether the device has a PIN, once a change finished
+    pub pin_protection: Option<bool>,
+    /// The device's reason when entry failed
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct PinSession {
+    /// The matrix on screen, answered by the next ack
+    matrix: Option<PinMatrixType>,
+    failed_attempts: u32,
+    /// Set during a change: whether the PIN is being removed
+    removing: Option<bool>,
+    /// Set when a wallet identification is paused on the PIN: whether it
+    /// opens a hidden wallet
+    identifying: Option<bool>,
+}
+
+fn pin_sessions() -> &'static Mutex<HashMap<String, PinSession>> {
+    static SESSIONS: OnceLock<Mutex<HashMap<String, PinSession>>> = OnceLock::new();
+    SESSIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Matrix positions are 1 to 9 digits, each 1-9
+fn validate_pin_positions(positions: &str) -> Result<(), String> {
+    if positions.is_empty() || positions.len() > 9 || !positions.bytes().all(|b| (b'1'..=b'9').contains(&b)) {
+        return Err("PIN positions must be 1 to 9 digits between 1 and 9".to_string());
+    }
+    Ok(())
+}
+
+/// Turn a device answer during PIN entry into a result, updating the session
+pub(super) fn pin_entry_result(device_id: &str, response: Result<Message, String>) -> Result<PinEntryResult, String> {
+    // The worker reports some device failures as errors
+    let response = match response {
+        Err(e) => match e.strip_prefix("Failure: ") {
+            Some(reason) => Message::Failure(messages::Failure { message: Some(reason.to_string()), ..Default::default() }),
+            None => return Err(e),
+        },
+        Ok(message) => message,
+    };
+
+    let mut sessions = pin_sessions().lock().unwrap();
+    let session = sessions.entry(device_id.to_string()).or_default();
+    let mut result = PinEntryResult {
+        status: PinEntryStatus::Failed,
+        matrix_type: None,
+        failed_attempts: session.failed_attempts,
+        pin_protection: None,
+        message: None,
+    };
+    match response {
+        // A paused request carries on once the PIN is in
+        Message::Success(_) | Message::Address(_) | Message::PublicKey(_) => {
+            let session = sessions.remove(device_id).unwrap_or_default();
+            result.failed_attempts = 0;
+            match session.removing {
+                Some(removing) => {
+                    result.status = PinEntryStatus::Changed;
+                    result.pin_protection = Some(!removing);
+                }
+                None => result.status = PinEntryStatus::Unlocked,
+            }
+        }
+        Message::PinMatrixRequest(request) => {
+            session.matrix = PinMatrixType::from_request(&request);
+            result.status = PinEntryStatus::AwaitingPin;
+            result.matrix_type = session.matrix;
+        }
+        Message::ButtonRequest(_) => result.status = PinEntryStatus::AwaitingButton,
+        Message::CharacterRequest(_) => {
+            session.matrix = None;
+            result.status = PinEntryStatus::AwaitingCharacter;
+        }
+        Message::Failure(f) => {
+            let reason = f.message().to_string();
+            // A wrong current PIN counts; a mismatched new PIN or a cancel does not
+            let wrong_pin = matches!(session.matrix, None | Some(PinMatrixType::Current))
+                && !reason.to_ascii_lowercase().contains("cancel");
+            if wrong_pin {
+                session.failed_attempts += 1;
+            }
+            // The device abandons the operation on any failure
+            session.matrix = None;
+            session.removing = None;
+            session.identifying = None;
+            result.failed_attempts = session.failed_attempts;
+            result.message = Some(reason);
+        }
+        other => return Err(format!("Unexpected response: {:?}", other.message_type())),
+    }
+    Ok(result)
+}
+
+/// Tell the frontend to show the PIN matrix, naming the stage during a change
+pub(super) async fn emit_pin_request(app: &AppHandle, device_id: &str, entry: &PinEntryResult, changing: bool) -> Result<(), String> {
+    let payload = serde_json::json!({
+        "device_id": device_id,
+        "matrix_type": entry.matrix_type,
+    });
+    if let (true, Some(matrix_type)) = (changing, entry.matrix_type) {
+        super::emit_or_queue_event(app, matrix_type.change_event(), payload.clone()).await?;
+    }
+    super::emit_or_queue_event(app, "device:pin-request", payload).await
+}
+
+/// Hand a PIN matrix the device put up unasked to the PIN flow. The request
+/// that ran into it has to be retried once the PIN is entered.
+pub(crate) async fn request_pin_entry(
+    app: &AppHandle,
+    device_id: &str,
+    request: messages::PinMatrixRequest,
+) -> Result<(), String> {
+    let entry = pin_entry_result(device_id, Ok(Message::PinMatrixRequest(request)))?;
+    emit_pin_request(app, device_id, &entry, false).await
+}
+
+/// Hand a PIN matrix the device put up during a wallet identification to
+/// the PIN flow. Entering the PIN then finishes the identification.
+pub(crate) async fn announce_pin_request(
+    app: &AppHandle,
+    device_id: &str,
+    request: messages::PinMatrixRequest,
+    is_hidden: bool,
+) -> Result<(), String> {
+    let entry = pin_entry_result(device_id, Ok(Message::PinMatrixRequest(request)))?;
+    if let Some(session) = pin_sessions().lock().unwrap().get_mut(device_id) {
+        session.identifying = Some(is_hidden);
+    }
+    emit_pin_request(app, device_id, &entry, false).await
+}
+
+/// Follow up on a device answer: flows, stored state and events
+async fn settle_pin_entry(
+    app: &AppHandle,
+    device_id: &str,
+    entry: &PinEntryResult,
+    database: &Database,
+    queue_manager: &DeviceQueueManager,
+    device_flows: &DeviceFlowState,
+) -> Result<(), String> {
+    match entry.status {
+        PinEntryStatus::AwaitingPin => {
+            let changing = device_flows.active_flow(device_id) == Some(DeviceFlow::PinChange);
+            emit_pin_request(app, device_id, entry, changing).await?;
+        }
+        PinEntryStatus::AwaitingButton => {}
+        PinEntryStatus::AwaitingCharacter => super::recovery::emit_character_request(app, device_flows, device_id).await?,
+        PinEntryStatus::Failed => {
+            let canonical_id = device_flows.resolve(device_id);
+            if device_flows.end_flow(device_id) == Some(DeviceFlow::Recovery) {
+                super::recovery::forget_recovery(&canonical_id);
+            }
+            log::warn!("🔐 PIN entry failed on device {}: {}", device_id, entry.message.as_deref().unwrap_or(""));
+        }
+        PinEntryStatus::Unlocked | PinEntryStatus::Changed => {
+            device_flows.end_flow(device_id);
+            if let Some(pin_protection) = entry.pin_protection {
+                database.set_device_pin_protection(device_id, pin_protection).await
+                    .map_err(|e| format!("Database error: {}", e))?;
+            }
+            if let Err(e) = vault_core::refresh_stored_features(database, device_id, queue_manager).await {
+                log::warn!("Could not refresh features after PIN entry on {}: {}", device_id, e);
+            }
+            let status = match entry.pin_protection {
+                Some(pin_protection) => serde_json::json!({ "device_id": device_id, "pin_protection": pin_protection }),
+                None => serde_json::json!({ "device_id": device_id, "pin_cached": true }),
+            };
+            super::emit_or_queue_event(app, "device:status-changed", status).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Change, set or remove (`remove`) the device PIN.
+///
+/// The device asks for the current PIN, then the new one twice; each matrix
+/// emits `pin:request-current`, `pin:request-new` or `pin:request-confirm`
+/// and is answered with `send_pin_matrix_ack`. Removing requires a
+/// confirmation from `request_confirmation("remove_device_pin", ...)`.
+/// A mismatched confirmation ends the change with the device's reason and
+/// leaves the old PIN in place.
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+pub async fn change_device_pin(
+    app: AppHandle,
+    device_id: String,
+    remove: bool,
+    confirmation_token: Option<String>,
+    confirmation_phrase: Option<String>,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    confirmations: State<'_, Confirmations>,
+    device_flows: State<'_, Arc<DeviceFlowState>>,
+) -> Result<PinEntryResult, String> {
+    if remove {
+        require_confirmation(
+            &database,
+            &confirmations,
+            HighRiskOperation::RemoveDevicePin,
+            Some(&device_id),
+            confirmation_token.as_deref().unwrap_or_default(),
+            confirmation_phrase.as_deref().unwrap_or_default(),
+        ).await?;
+    }
+
+    log::info!("🔐 {} PIN on device {}", if remove { "Removing" } else { "Changing" }, device_id);
+    pin_sessions().lock().unwrap().entry(device_id.clone()).or_default().removing = Some(remove);
+    let entry = request_queue().run(&device_id, "change_device_pin", || async {
+        let queue = vault_core::get_or_create_device_queue(&device_id, &queue_manager).await?;
+        let request = Message::ChangePin(messages::ChangePin { remove: Some(remove) });
+        let response = queue.send_raw(request, true).await.map_err(|e| e.to_string());
+        pin_entry_result(&device_id, response)
+    }).await;
+    let entry = match entry {
+        Ok(entry) => entry,
+        Err(e) => {
+            if let Some(session) = pin_sessions().lock().unwrap().get_mut(&device_id) {
+                session.removing = None;
+            }
+            return Err(format!("Failed to change PIN: {}", e));
+        }
+    };
+
+    if entry.status == PinEntryStatus::AwaitingPin {
+        // The device may re-enumerate while the PIN matrix is up
+        device_flows.begin_flow(&device_id, DeviceFlow::PinChange);
+    }
+    settle_pin_entry(&app, &device_id, &entry, &database, &queue_manager, &device_flows).await?;
+    Ok(entry)
+}
+
+/// Remove PIN protection from a device.
+///
+/// Requires a confirmation from `request_confirmation("remove_device_pin", ...)`;
+/// the device asks for the current PIN and a button press.
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+pub async fn remove_device_pin(
+    app: AppHandle,
+    device_id: String,
+    confirmation_token: String,
+    confirmation_phrase: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    confirmations: State<'_, Confirmations>,
+    device_flows: State<'_, Arc<DeviceFlowState>>,
+) -> Result<PinRemovalStatus, String> {
+    let entry = change_device_pin(
+        app,
+        device_id,
+        true,
+        Some(confirmation_token),
+        Some(confirmation_phrase),
+        database,
+        queue_manager,
+        confirmations,
+        device_flows,
+    ).await?;
+    match entry.status {
+        PinEntryStatus::Changed => Ok(PinRemovalStatus::Removed),
+        PinEntryStatus::AwaitingPin => Ok(PinRemovalStatus::AwaitingPin),
+        PinEntryStatus::Failed => Err(format!("Device refused to remove PIN: {}", entry.message.unwrap_or_default())),
+        other => Err(format!("Unexpected PIN removal state: {:?}", other)),
+    }
+}
+
+/// Ask a locked device for its PIN.
+///
+/// Requests the first Bitcoin address without showing it, which needs the
+/// PIN, so a locked device puts its matrix up and `device:pin-request` is
+/// emitted. An unlocked device answers with the address instead.
+#[tauri::command]
+#[specta::specta]
+pub async fn trigger_pin_request(
+    app: AppHandle,
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    device_flows: State<'_, Arc<DeviceFlowState>>,
+) -> Result<PinEntryResult, String> {
+    const HARDENED: u32 = 0x8000_0000;
+    let entry = request_queue().run(&device_id, "trigger_pin_request", || async {
+        let queue = vault_core::get_or_create_device_queue(&device_id, &queue_manager).await?;
+        let request = Message::GetAddress(messages::GetAddress {
+            address_n: vec![HARDENED | 44, HARDENED, HARDENED, 0, 0],
+            coin_name: Some("Bitcoin".to_string()),
+            show_display: Some(false),
+            ..Default::default()
+        });
+        let response = queue.send_raw(request, true).await.map_err(|e| e.to_string());
+        pin_entry_result(&device_id, response)
+    }).await?;
+
+    log::info!("🔐 PIN request on device {}: {:?}", device_id, entry.status);
+    settle_pin_entry(&app, &device_id, &entry, &database, &queue_manager, &device_flows).await?;
+    Ok(entry)
+}
+
+/// Answer the PIN matrix on screen with the positions clicked, e.g. "3719".
+///
+/// Another matrix (a new PIN asked twice) emits `device:pin-request` again;
+/// unlocking or finishing a change refreshes the stored features.
+#[tauri::command]
+#[specta::specta]
+pub async fn send_pin_matrix_ack(
+    app: AppHandle,
+    device_id: String,
+    positions: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    device_flows: State<'_, Arc<DeviceFlowState>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<PinEntryResult, String> {
+    validate_pin_positions(&positions)?;
+
+    let identifying = pin_sessions().lock().unwrap().get(&device_id).and_then(|session| session.identifying);
+    let (entry, identified) = request_queue().run(&device_id, "send_pin_matrix_ack", || async {
+        let queue = vault_core::get_or_create_device_queue(&device_id, &queue_manager).await?;
+        let response = queue
+            .send_raw(Message::PinMatrixAck(messages::PinMatrixAck { pin: positions }), true)
+            .await
+            .map_err(|e| e.to_string());
+        // The new PIN of a recovery is followed by the first character request
+        if let Ok(Message::CharacterRequest(request)) = &response {
+            super::recovery::note_character_request(&device_flows.resolve(&device_id), request);
+        }
+        // An identification paused on the PIN answers with the wallet's key
+        let identified = match (&response, identifying) {
+            (Ok(Message::PublicKey(public_key)), Some(is_hidden)) => Some((public_key.clone(), is_hidden)),
+            _ => None,
+        };
+        Ok::<_, String>((pin_entry_result(&device_id, response)?, identified))
+    }).await?;
+
+    log::info!("🔐 PIN entry on device {}: {:?} ({} wrong)", device_id, entry.status, entry.failed_attempts);
+    settle_pin_entry(&app, &device_id, &entry, &database, &queue_manager, &device_flows).await?;
+    if let Some((public_key, is_hidden)) = identified {
+        let wallet = vault_core::wallet_session::complete_identification(
+            &database, &wallet_sessions, &device_id, public_key, is_hidden,
+        ).await?;
+        super::wallets::wallet_opened(&app, &database, &device_id, &wallet).await?;
+    }
+    Ok(entry)
+}
+
+/// Dismiss the PIN matrix on the device
+#[tauri::command]
+#[specta::specta]
+pub async fn cancel_pin_entry(
+    device_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+    device_flows: State<'_, Arc<DeviceFlowState>>,
+) -> Result<(), String> {
+    request_queue().run(&device_id, "cancel_pin_entry", || async {
+        let queue = vault_core::get_or_create_device_queue(&device_id, &queue_manager).await?;
+        match queue.send_raw(Message::Cancel(messages::Cancel::default()), true).await {
+            // The device answers a cancelled prompt with a Failure
+            Ok(_) => Ok(()),
+            Err(e) if e.to_string().starts_with("Failure: ") => Ok(()),
+            Err(e) => Err(format!("Failed to cancel PIN entry: {}", e)),
+        }
+    }).await?;
+
+    if let Some(session) = pin_sessions().lock().unwrap().get_mut(&device_id) {
+        session.matrix = None;
+        session.removing = None;
+        session.identifying = None;
+    }
+    device_flows.end_flow(&device_id);
+    log::info!("🔐 PIN entry cancelled on device {}", device_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(reason: &str) -> Result<Message, String> {
+        Ok(Message::Failure(messages::Failure { message: Some(reason.to_string()), ..Default::default() }))
+    }
+
+    fn matrix(matrix_type: i32) -> Result<Message, String> {
+        Ok(Message::PinMatrixRequest(messages::PinMatrixRequest { r#type: Some(matrix_type), ..Default::default() }))
+    }
+
+    #[test]
+    fn test_pin_positions_are_validated_without_echoing_them() {
+        for valid in ["1", "3719", "123456789"] {
+            assert!(validate_pin_positions(valid).is_ok(), "{}", valid);
+        }
+        for invalid in ["", "1234567891", "1230", "12a4", " 123", "１２"] {
+            let error = validate_pin_positions(invalid).unwrap_err();
+            assert!(invalid.is_empty() || !error.contains(invalid));
+        }
+    }
+
+    #[test]
+    fn test_wrong_pins_are_counted_until_unlock() {
+        assert_eq!(pin_entry_result("kk1", failure("Invalid PIN")).unwrap().failed_attempts, 1);
+        let second = pin_entry_result("kk1", Err("Failure: Invalid PIN".to_string())).unwrap();
+        assert_eq!((second.status, second.failed_attempts), (PinEntryStatus::Failed, 2));
+        assert_eq!(pin_entry_result("kk1", failure("PIN Cancelled")).unwrap().failed_attempts, 2);
+
+        let awaiting = pin_entry_result("kk1", matrix(1)).unwrap();
+        assert_eq!((awaiting.matrix_type, awaiting.failed_attempts), (Some(PinMatrixType::Current), 2));
+
+        let unlocked = pin_entry_result("kk1", Ok(Message::Success(Default::default()))).unwrap();
+        assert_eq!((unlocked.status, unlocked.failed_attempts), (PinEntryStatus::Unlocked, 0));
+        assert!(pin_entry_result("kk1", Err("device disconnected".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_identification_carries_on_after_the_pin() {
+        pin_entry_result("kk3", matrix(1)).unwrap();
+        pin_sessions().lock().unwrap().get_mut("kk3").unwrap().identifying = Some(false);
+        let unlocked = pin_entry_result("kk3", Ok(Message::PublicKey(Default::default()))).unwrap();
+        assert_eq!(unlocked.status, PinEntryStatus::Unlocked);
+        assert!(!pin_sessions().lock().unwrap().contains_key("kk3"));
+    }
+
+    #[test]
+    fn test_pin_change_walks_the_matrices_and_a_mismatch_is_not_a_wrong_pin() {
+        pin_sessions().lock().unwrap().entry("kk2".to_string()).or_default().removing = Some(false);
+        let stages: Vec<_> = [1, 2, 3]
+            .into_iter()
+            .map(|t| pin_entry_result("kk2", matrix(t)).unwrap().matrix_type.unwrap().change_event())
+            .collect();
+        assert_eq!(stages, ["pin:request-current", "pin:request-new", "pin:request-confirm"]);
+
+        let mismatch = pin_entry_result("kk2", failure("PIN mismatch")).unwrap();
+        assert_eq!((mismatch.status, mismatch.failed_attempts), (PinEntryStatus::Failed, 0));
+        assert_eq!(mismatch.message.as_deref(), Some("PIN mismatch"));
+        // The failed change is over: a later Success is an unlock
+        assert_eq!(pin_entry_result("kk2", Ok(Message::Success(Default::default()))).unwrap().status, PinEntryStatus::Unlocked);
+
+        pin_sessions().lock().unwrap().entry("kk2".to_string()).or_default().removing = Some(true);
+        pin_entry_result("kk2", matrix(1)).unwrap();
+        let removed = pin_entry_result("kk2", Ok(Message::Success(Default::default()))).unwrap();
+        assert_eq!((removed.status, removed.pin_protection), (PinEntryStatus::Changed, Some(false)));
+    }
+}
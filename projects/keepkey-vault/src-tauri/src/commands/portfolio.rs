@@ -0,0 +1,124 @@
+// commands/portfolio.rs - Persisting balances fetched by the frontend
+//
+// Balances come from pioneer-sdk in the frontend; these commands store them
+// against the device's active wallet and read them back with the totals
+// across every device. Dashboards are cached aggregates of those balances,
+// rebuilt on read once a newer balance has been written. Each refresh also
+// records the wallet's total in portfolio_history for the value chart.
+
+use std::sync::Arc;
+use serde::Serialize;
+use tauri::State;
+use keepkey_db::{CombinedPortfolioEntry, Database, PortfolioBalance, PortfolioBalanceInput, PortfolioDashboard, PortfolioHistoryPoint};
+use vault_core::portfolio_sync::portfolio_sync;
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+
+#[derive(Debug, Serialize, specta::Type)]
+pub struct Portfolio {
+    /// Balance rows of the device's active wallet
+    pub balances: Vec<PortfolioBalance>,
+    /// Spendable balances of each asset summed across all devices
+    pub combined: Vec<CombinedPortfolioEntry>,
+}
+
+/// Cached balances of a device's active wallet and the combined totals
+#[tauri::command]
+#[specta::specta]
+pub async fn get_portfolio(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<Portfolio, String> {
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let balances = database.get_portfolio_balances(&device_id, &wallet_fingerprint).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    let combined = database.get_combined_portfolio().await
+        .map_err(|e| format!("Database error: {}", e))?;
+    Ok(Portfolio { balances, combined })
+}
+
+/// Store balances for a device's active wallet; rows for the same address,
+/// type and validator are replaced. The wallet's new total is recorded as a
+/// history snapshot. Returns the number written.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_portfolio_balances(
+    device_id: String,
+    balances: Vec<PortfolioBalanceInput>,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<usize, String> {
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    // The integrity audit skips while balances and dashboard disagree
+    let _writing = portfolio_sync().write().await;
+    let written = database.upsert_portfolio_balances(&device_id, &wallet_fingerprint, balances).await
+        .map_err(|e| format!("Could not store balances: {}", e))?;
+    log::info!("💰 Stored {} balances for device {}", written, device_id);
+
+    // The balances are stored either way; a missed snapshot is only a gap in the chart
+    match database.compute_dashboard(&device_id, &wallet_fingerprint).await {
+        Ok(dashboard) => {
+            if let Err(e) = database.record_portfolio_snapshot(
+                &device_id,
+                &wallet_fingerprint,
+                &dashboard.total_value_usd,
+                Some(&dashboard.assets_json),
+            ).await {
+                log::warn!("Could not record a portfolio snapshot for {}: {}", device_id, e);
+            }
+        }
+        Err(e) => log::warn!("Could not compute the dashboard for {}: {}", device_id, e),
+    }
+    Ok(written)
+}
+
+/// Dashboard of a device's active wallet, or the combined dashboard of all
+/// devices without one; recomputed if balances changed since it was cached
+#[tauri::command]
+#[specta::specta]
+pub async fn get_dashboard(
+    device_id: Option<String>,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<PortfolioDashboard, String> {
+    let wallet_fingerprint = match &device_id {
+        Some(device_id) => active_wallet_fingerprint(&wallet_sessions, device_id).await,
+        None => String::new(),
+    };
+    let cached = database.get_fresh_dashboard(device_id.as_deref(), &wallet_fingerprint).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    if let Some(dashboard) = cached {
+        return Ok(dashboard);
+    }
+
+    let dashboard = match &device_id {
+        Some(device_id) => database.compute_dashboard(device_id, &wallet_fingerprint).await,
+        None => database.compute_combined_dashboard().await,
+    };
+    dashboard.map_err(|e| format!("Could not compute the dashboard: {}", e))
+}
+
+/// Value of a device's active wallet between `from_ts` and `to_ts` (unix
+/// seconds), downsampled to at most `max_points` for charting
+#[tauri::command]
+#[specta::specta]
+pub async fn get_portfolio_history(
+    device_id: String,
+    from_ts: i64,
+    to_ts: i64,
+    max_points: u32,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<Vec<PortfolioHistoryPoint>, String> {
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    database.get_portfolio_history(&device_id, &wallet_fingerprint, from_ts, to_ts, max_points).await
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+/// Thin old portfolio snapshots to one per day; returns the number removed
+#[tauri::command]
+#[specta::specta]
+pub async fn prune_portfolio_history(database: State<'_, Arc<Database>>) -> Result<usize, String> {
+    database.prune_portfolio_history().await
+        .map_err(|e| format!("Database error: {}", e))
+}
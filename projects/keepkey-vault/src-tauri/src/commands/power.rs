@@ -0,0 +1,86 @@
+// commands/power.rs - Battery-aware power profile for background work
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+use keepkey_db::Database;
+use vault_core::power::{self, PowerMonitor, PowerSaverMode, PowerStatus, POWER_SAVER_PREF};
+
+/// How often the power source and preference are re-read
+const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+pub fn new_power_monitor() -> Arc<PowerMonitor> {
+    Arc::new(PowerMonitor::new())
+}
+
+async fn power_saver_mode(database: &Database) -> PowerSaverMode {
+    PowerSaverMode::from_pref(database.get_preference(POWER_SAVER_PREF).await.ok().flatten().as_deref())
+}
+
+async fn announce(app: &AppHandle, status: PowerStatus) {
+    log::info!("🔋 Power profile: {:?} (source {:?}, mode {})", status.profile, status.source, status.mode.as_str());
+    let payload = serde_json::to_value(&status).unwrap_or_default();
+    if let Err(e) = super::emit_or_queue_event(app, "power:profile-changed", payload).await {
+        log::warn!("Failed to emit power:profile-changed: {}", e);
+    }
+}
+
+/// Re-read the power source and preference, announcing profile changes
+async fn check_power(app: &AppHandle, database: &Database, monitor: &PowerMonitor) {
+    let mode = power_saver_mode(database).await;
+    let source = tokio::task::spawn_blocking(power::detect_power_source)
+        .await
+        .unwrap_or(power::PowerSource::Unknown);
+    if let Some(status) = monitor.update(mode, source, None, Instant::now()) {
+        announce(app, status).await;
+    }
+}
+
+/// Track AC/battery state for the lifetime of the app
+pub async fn run_power_monitor(app: AppHandle, database: Arc<Database>) {
+    let monitor = app.state::<Arc<PowerMonitor>>().inner().clone();
+    let mut interval = tokio::time::interval(POWER_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        check_power(&app, &database, &monitor).await;
+    }
+}
+
+/// Window focus counts as user interaction and may restore the normal profile
+pub fn note_window_focus(app: &AppHandle, focused: bool) {
+    // Focus events can arrive before setup has managed the monitor
+    let Some(monitor) = app.try_state::<Arc<PowerMonitor>>() else {
+        return;
+    };
+    if let Some(status) = monitor.note_interaction(focused, Instant::now()) {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move { announce(&app, status).await });
+    }
+}
+
+/// Active power profile and what it throttles
+#[tauri::command]
+#[specta::specta]
+pub async fn get_power_profile(
+    monitor: State<'_, Arc<PowerMonitor>>,
+) -> Result<PowerStatus, String> {
+    Ok(monitor.status())
+}
+
+/// Set the power saver override: "always", "never" or "auto"
+#[tauri::command]
+#[specta::specta]
+pub async fn set_power_saver(
+    app: AppHandle,
+    mode: String,
+    database: State<'_, Arc<Database>>,
+    monitor: State<'_, Arc<PowerMonitor>>,
+) -> Result<PowerStatus, String> {
+    if !matches!(mode.as_str(), "always" | "never" | "auto") {
+        return Err(format!("Invalid power saver mode '{}', expected always, never or auto", mode));
+    }
+    database.set_preference(POWER_SAVER_PREF, &mode).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    check_power(&app, &database, &monitor).await;
+    Ok(monitor.status())
+}
@@ -0,0 +1,123 @@
+// commands/quick_actions.rs - Command palette actions, shared with the tray menu
+//
+// The registry and availability rules live in vault_core::quick_actions; this
+// module builds the cache-backed context and routes each action to the code
+// behind the equivalent command.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde::Serialize;
+use serde_json::json;
+use tauri::{AppHandle, Manager, State};
+use keepkey_db::Database;
+use vault_core::quick_actions::{self, ActionArgs, ActionContext, QuickAction};
+use vault_core::tray::TraySummary;
+use vault_core::wallet_session::WalletSessions;
+use super::device::get_device_status::DeviceStatus;
+use super::DeviceQueueManager;
+
+/// What a quick action did
+#[derive(Debug, Serialize, specta::Type)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QuickActionOutcome {
+    Done,
+    DevicesLocked { locked: u32 },
+    /// The frontend was asked to show `view` (ui:open-view)
+    ViewOpened { view: String, args: ActionArgs },
+    DeviceStatus { status: Option<DeviceStatus> },
+}
+
+/// Context for availability checks, from the tray summary plus cached portfolio assets
+pub async fn build_action_context(
+    database: &Database,
+    wallet_sessions: &WalletSessions,
+    summary: &TraySummary,
+) -> Result<ActionContext, String> {
+    let imported_wallets = database.list_imported_wallets().await
+        .map_err(|e| format!("Database error: {}", e))?
+        .len();
+
+    let active_wallets: HashMap<String, String> = wallet_sessions
+        .lock()
+        .await
+        .iter()
+        .map(|(device_id, wallet)| (device_id.clone(), wallet.wallet_fingerprint.clone()))
+        .collect();
+    let mut portfolio_caips = HashMap::new();
+    for device in &summary.devices {
+        let fingerprint = active_wallets.get(&device.device_id).map(String::as_str).unwrap_or("");
+        let caips = database.get_portfolio_caips(&device.device_id, fingerprint).await
+            .map_err(|e| format!("Database error: {}", e))?;
+        portfolio_caips.insert(device.device_id.clone(), caips);
+    }
+
+    Ok(ActionContext { devices: summary.devices.clone(), imported_wallets, portfolio_caips })
+}
+
+async fn current_context(app: &AppHandle) -> Result<ActionContext, String> {
+    let database = app.state::<Arc<Database>>().inner().clone();
+    let wallet_sessions = app.state::<WalletSessions>().inner().clone();
+    let summary = super::tray::build_tray_summary(&database, &wallet_sessions).await?;
+    build_action_context(&database, &wallet_sessions, &summary).await
+}
+
+/// Ask the frontend to show one of its views
+async fn open_view(app: &AppHandle, view: &str, args: &ActionArgs) -> Result<QuickActionOutcome, String> {
+    super::emit_or_queue_event(app, "ui:open-view", json!({ "view": view, "args": args })).await?;
+    Ok(QuickActionOutcome::ViewOpened { view: view.to_string(), args: args.clone() })
+}
+
+/// Validate an action against the current state and run it
+pub async fn run_quick_action(app: &AppHandle, id: &str, args: ActionArgs) -> Result<QuickActionOutcome, String> {
+    let context = current_context(app).await?;
+    let spec = quick_actions::validate_invocation(&context, id, &args).map_err(|e| e.to_json_string())?;
+    log::info!("⚡ Quick action {} {:?}", spec.id, args);
+
+    let database = app.state::<Arc<Database>>().inner().clone();
+    let queue_manager = app.state::<DeviceQueueManager>().inner().clone();
+    let wallet_sessions = app.state::<WalletSessions>().inner().clone();
+    let device_id = args.get("device_id").cloned().unwrap_or_default();
+
+    match spec.id {
+        "sync_now" => {
+            super::tray::sync_now(app.clone()).await?;
+            Ok(QuickActionOutcome::Done)
+        }
+        "lock_device" => {
+            super::tray::lock_and_refresh(app, &database, &queue_manager, &wallet_sessions, &device_id, "user").await?;
+            Ok(QuickActionOutcome::Done)
+        }
+        "lock_all_devices" => {
+            let locked = super::tray::lock_all_devices(app, &database, &queue_manager, &wallet_sessions).await;
+            Ok(QuickActionOutcome::DevicesLocked { locked: locked as u32 })
+        }
+        "show_receive_address" => open_view(app, "receive", &args).await,
+        "open_diagnostics" => open_view(app, "diagnostics", &args).await,
+        "check_for_updates" => {
+            let status = super::device::get_device_status(device_id, app.state::<DeviceQueueManager>()).await?;
+            Ok(QuickActionOutcome::DeviceStatus { status })
+        }
+        other => Err(format!("Quick action '{}' has no handler", other)),
+    }
+}
+
+/// Actions that can run right now, one entry per argument set; with
+/// `device_id`, actions about other devices are left out
+#[tauri::command]
+#[specta::specta]
+pub async fn list_quick_actions(
+    device_id: Option<String>,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<Vec<QuickAction>, String> {
+    let summary = super::tray::build_tray_summary(&database, &wallet_sessions).await?;
+    let context = build_action_context(&database, &wallet_sessions, &summary).await?;
+    Ok(quick_actions::list_quick_actions(&context, device_id.as_deref()))
+}
+
+/// Run an action listed by `list_quick_actions`
+#[tauri::command]
+#[specta::specta]
+pub async fn execute_quick_action(app: AppHandle, id: String, args: Option<ActionArgs>) -> Result<QuickActionOutcome, String> {
+    run_quick_action(&app, &id, args.unwrap_or_default()).await
+}
@@ -1,4 +1,340 @@
 // commands/recovery.rs - Recovery operation commands
+//
+// Restoring a wallet from its recovery phrase: the device asks for a new PIN
+// (answered with `send_pin_matrix_ack`), then for the phrase one character at
+// a time. With the character cipher on, the device shows a scrambled keyboard
+// and the user types the letters it maps to, so what is sent here is never
+// the phrase itself; characters are still never logged.
+//
+// The recovery runs as a DeviceFlow::Recovery flow, so the USB monitor keeps
+// it alive if the device re-enumerates. Sessions are keyed by the flow's
+// canonical device id and survive the device coming back under a new id.
 
-// Recovery commands will be implemented here
-pub fn _placeholder() {} 
\ No newline at end of file
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use serde::Serialize;
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+use keepkey_rust::messages::{self, Message};
+use vault_core::device_flow::{DeviceFlow, DeviceFlowState};
+use vault_core::request_queue::request_queue;
+use super::DeviceQueueManager;
+
+/// Phrase lengths the firmware accepts
+const WORD_COUNTS: [u32; 3] = [12, 18, 24];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryStatus {
+    /// A PIN matrix is up; answer it with `send_pin_matrix_ack`
+    AwaitingPin,
+    AwaitingButton,
+    /// The device wants the next character of the phrase
+    AwaitingCharacter,
+    Completed,
+    Failed,
+}
+
+/// Where a recovery stands after the device answered
+#[derive(Debug, Clone, Serialize, specta::Type)]
+pub struct RecoveryProgress {
+    pub status: RecoveryStatus,
+    pub word_count: u32,
+    /// Word being entered, from 0
+    pub word_pos: u32,
+    /// Character of that word being entered, from 0
+    pub character_pos: u32,
+    /// The device's reason when recovery failed
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RecoverySession {
+    word_count: u32,
+    word_pos: u32,
+    character_pos: u32,
+}
+
+fn recovery_sessions() -> &'static Mutex<HashMap<String, RecoverySession>> {
+    static SESSIONS: OnceLock<Mutex<HashMap<String, RecoverySession>>> = OnceLock::new();
+    SESSIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Remember where the device is in the phrase; `canonical_id` as resolved by
+/// the flow state
+pub(crate) fn note_character_request(canonical_id: &str, request: &messages::CharacterRequest) {
+    if let Some(session) = recovery_sessions().lock().unwrap().get_mut(canonical_id) {
+        session.word_pos = request.word_pos;
+        session.character_pos = request.character_pos;
+    }
+}
+
+/// Drop the session of a recovery that was abandoned
+pub(crate) fn forget_recovery(canonical_id: &str) {
+    recovery_sessions().lock().unwrap().remove(canonical_id);
+}
+
+/// Ask the frontend for the next character, at the position the device is at
+pub(crate) async fn emit_character_request(
+    app: &AppHandle,
+    device_flows: &DeviceFlowState,
+    device_id: &str,
+) -> Result<(), String> {
+    let session = recovery_sessions().lock().unwrap().get(&device_flows.resolve(device_id)).copied();
+    let Some(session) = session else { return Ok(()) };
+    super::emit_or_queue_event(app, "recovery:character-request", serde_json::json!({
+        "device_id": device_id,
+        "word_count": session.word_count,
+        "word_pos": session.word_pos,
+        "character_pos": session.character_pos,
+    })).await
+}
+
+/// One CharacterAck: a letter or space, a delete, or done
+fn character_ack(character: Option<String>, delete: bool, done: bool) -> Result<messages::CharacterAck, String> {
+    let ack = |character| messages::CharacterAck { character, delete: Some(delete), done: Some(done) };
+    match (character, delete, done) {
+        (Some(character), false, false) => {
+            let valid = character.len() == 1 && character.bytes().all(|b| b.is_ascii_lowercase() || b == b' ');
+            if !valid {
+                return Err("A recovery character must be a single letter a-z or a space".to_string());
+            }
+            Ok(ack(Some(character)))
+        }
+        (None, true, false) | (None, false, true) => Ok(ack(None)),
+        _ => Err("Send exactly one of a character, delete or done".to_string()),
+    }
+}
+
+/// Turn a device answer during recovery into progress, updating the session
+fn recovery_progress(canonical_id: &str, response: Result<Message, String>) -> Result<RecoveryProgress, String> {
+    let mut sessions = recovery_sessions().lock().unwrap();
+    let session = *sessions
+        .get(canonical_id)
+        .ok_or_else(|| format!("No recovery in progress on {}", canonical_id))?;
+    let mut progress = RecoveryProgress {
+        status: RecoveryStatus::Failed,
+        word_count: session.word_count,
+        word_pos: session.word_pos,
+        character_pos: session.character_pos,
+        message: None,
+    };
+    match response {
+        Ok(Message::CharacterRequest(request)) => {
+            progress.status = RecoveryStatus::AwaitingCharacter;
+            progress.word_pos = request.word_pos;
+            progress.character_pos = request.character_pos;
+            if let Some(session) = sessions.get_mut(canonical_id) {
+                session.word_pos = request.word_pos;
+                session.character_pos = request.character_pos;
+            }
+        }
+        Ok(Message::PinMatrixRequest(_)) => progress.status = RecoveryStatus::AwaitingPin,
+        Ok(Message::ButtonRequest(_)) => progress.status = RecoveryStatus::AwaitingButton,
+        Ok(Message::Success(_)) => {
+            sessions.remove(canonical_id);
+            progress.status = RecoveryStatus::Completed;
+        }
+        Ok(Message::Failure(f)) => {
+            sessions.remove(canonical_id);
+            progress.message = Some(f.message().to_string());
+        }
+        Ok(other) => return Err(format!("Unexpected response: {:?}", other.message_type())),
+        // The worker reports some device failures as errors
+        Err(e) => match e.strip_prefix("Failure: ") {
+            Some(reason) => {
+                sessions.remove(canonical_id);
+                progress.message = Some(reason.to_string());
+            }
+            None => return Err(e),
+        },
+    }
+    Ok(progress)
+}
+
+/// Follow up on a device answer: flows, stored state and events
+async fn settle_recovery(
+    app: &AppHandle,
+    device_id: &str,
+    response: Result<Message, String>,
+    database: &Database,
+    queue_manager: &DeviceQueueManager,
+    device_flows: &DeviceFlowState,
+) -> Result<RecoveryProgress, String> {
+    // A PIN matrix is tracked by the PIN commands, which answer it
+    if let Ok(Message::PinMatrixRequest(_)) = &response {
+        let entry = super::pin::pin_entry_result(device_id, response.clone())?;
+        super::pin::emit_pin_request(app, device_id, &entry, false).await?;
+    }
+    let progress = recovery_progress(&device_flows.resolve(device_id), response)?;
+    match progress.status {
+        RecoveryStatus::AwaitingCharacter => emit_character_request(app, device_flows, device_id).await?,
+        RecoveryStatus::AwaitingPin | RecoveryStatus::AwaitingButton => {}
+        RecoveryStatus::Failed => {
+            device_flows.end_flow(device_id);
+            log::warn!("♻️ Recovery failed on device {}: {}", device_id, progress.message.as_deref().unwrap_or(""));
+        }
+        RecoveryStatus::Completed => {
+            device_flows.end_flow(device_id);
+            log::info!("♻️ Recovery completed on device {}", device_id);
+            if let Err(e) = vault_core::refresh_stored_features(database, device_id, queue_manager).await {
+                log::warn!("Could not refresh features after recovery on {}: {}", device_id, e);
+            }
+            super::emit_or_queue_event(app, "device:status-changed", serde_json::json!({
+                "device_id": device_id,
+                "initialized": true,
+                "pin_protection": true,
+            })).await?;
+        }
+    }
+    Ok(progress)
+}
+
+/// Restore a wallet on an uninitialized device from a `word_count`-word phrase.
+///
+/// The device first asks for a new PIN, then emits a
+/// `recovery:character-request` for each character, answered with
+/// `send_recovery_character`.
+#[tauri::command]
+#[specta::specta]
+pub async fn start_device_recovery(
+    app: AppHandle,
+    device_id: String,
+    word_count: u32,
+    use_passphrase: bool,
+    label: Option<String>,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    device_flows: State<'_, Arc<DeviceFlowState>>,
+) -> Result<RecoveryProgress, String> {
+    if !WORD_COUNTS.contains(&word_count) {
+        return Err(format!("A recovery phrase has 12, 18 or 24 words, not {}", word_count));
+    }
+    if let Some(flow) = device_flows.active_flow(&device_id) {
+        return Err(format!("Device {} is busy with {:?}", device_id, flow));
+    }
+
+    log::info!("♻️ Starting {}-word recovery on device {}", word_count, device_id);
+    // The device may re-enumerate during the recovery
+    device_flows.begin_flow(&device_id, DeviceFlow::Recovery);
+    recovery_sessions().lock().unwrap().insert(
+        device_flows.resolve(&device_id),
+        RecoverySession { word_count, word_pos: 0, character_pos: 0 },
+    );
+
+    let response = request_queue().run(&device_id, "start_device_recovery", || async {
+        let queue = vault_core::get_or_create_device_queue(&device_id, &queue_manager).await?;
+        let request = Message::RecoveryDevice(messages::RecoveryDevice {
+            word_count: Some(word_count),
+            passphrase_protection: Some(use_passphrase),
+            pin_protection: Some(true),
+            language: Some("english".to_string()),
+            label,
+            enforce_wordlist: Some(true),
+            use_character_cipher: Some(true),
+            dry_run: Some(false),
+            ..Default::default()
+        });
+        queue.send_raw(request, true).await.map_err(|e| e.to_string())
+    }).await;
+    if let Err(e) = &response {
+        if !e.starts_with("Failure: ") {
+            forget_recovery(&device_flows.resolve(&device_id));
+            device_flows.end_flow(&device_id);
+            return Err(format!("Failed to start recovery: {}", e));
+        }
+    }
+    settle_recovery(&app, &device_id, response, &database, &queue_manager, &device_flows).await
+}
+
+/// Enter one character of the phrase as shown on the device's scrambled
+/// keyboard, a space to finish a word, or `delete` / `done`.
+#[tauri::command]
+#[specta::specta]
+pub async fn send_recovery_character(
+    app: AppHandle,
+    device_id: String,
+    character: Option<String>,
+    delete: bool,
+    done: bool,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    device_flows: State<'_, Arc<DeviceFlowState>>,
+) -> Result<RecoveryProgress, String> {
+    let ack = character_ack(character, delete, done)?;
+    if !recovery_sessions().lock().unwrap().contains_key(&device_flows.resolve(&device_id)) {
+        return Err(format!("No recovery in progress on {}", device_id));
+    }
+
+    let response = request_queue().run(&device_id, "send_recovery_character", || async {
+        let queue = vault_core::get_or_create_device_queue(&device_id, &queue_manager).await?;
+        queue.send_raw(Message::CharacterAck(ack), true).await.map_err(|e| e.to_string())
+    }).await;
+    settle_recovery(&app, &device_id, response, &database, &queue_manager, &device_flows).await
+}
+
+/// Abandon a recovery; the device keeps no partial phrase
+#[tauri::command]
+#[specta::specta]
+pub async fn cancel_recovery(
+    device_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+    device_flows: State<'_, Arc<DeviceFlowState>>,
+) -> Result<(), String> {
+    request_queue().run(&device_id, "cancel_recovery", || async {
+        let queue = vault_core::get_or_create_device_queue(&device_id, &queue_manager).await?;
+        match queue.send_raw(Message::Cancel(messages::Cancel::default()), true).await {
+            // The device answers a cancelled recovery with a Failure
+            Ok(_) => Ok(()),
+            Err(e) if e.to_string().starts_with("Failure: ") => Ok(()),
+            Err(e) => Err(format!("Failed to cancel recovery: {}", e)),
+        }
+    }).await?;
+
+    forget_recovery(&device_flows.resolve(&device_id));
+    device_flows.end_flow(&device_id);
+    log::info!("♻️ Recovery cancelled on device {}", device_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_character_acks_are_validated_without_echoing_input() {
+        assert_eq!(character_ack(Some("q".into()), false, false).unwrap().character.as_deref(), Some("q"));
+        assert_eq!(character_ack(Some(" ".into()), false, false).unwrap().character.as_deref(), Some(" "));
+        assert_eq!(character_ack(None, true, false).unwrap().delete, Some(true));
+        assert_eq!(character_ack(None, false, true).unwrap().done, Some(true));
+
+        for invalid in ["Q", "ab", "1", "é"] {
+            let error = character_ack(Some(invalid.into()), false, false).unwrap_err();
+            assert!(!error.contains(invalid), "{}", invalid);
+        }
+        assert!(character_ack(None, false, false).is_err());
+        assert!(character_ack(Some("a".into()), true, false).is_err());
+        assert!(character_ack(None, true, true).is_err());
+    }
+
+    #[test]
+    fn test_recovery_progress_follows_the_device() {
+        let flows = DeviceFlowState::new();
+        flows.begin_flow("kk1", DeviceFlow::Recovery);
+        recovery_sessions().lock().unwrap().insert("kk1".into(), RecoverySession { word_count: 12, word_pos: 0, character_pos: 0 });
+
+        let request = messages::CharacterRequest { word_pos: 3, character_pos: 2, ..Default::default() };
+        let progress = recovery_progress("kk1", Ok(Message::CharacterRequest(request))).unwrap();
+        assert_eq!((progress.status, progress.word_pos, progress.character_pos), (RecoveryStatus::AwaitingCharacter, 3, 2));
+
+        // The device comes back under a new id mid-recovery
+        flows.mark_disconnected("kk1", std::time::Instant::now());
+        assert!(flows.add_alias("kk1-new", "kk1"));
+        let progress = recovery_progress(&flows.resolve("kk1-new"), Ok(Message::ButtonRequest(Default::default()))).unwrap();
+        assert_eq!((progress.status, progress.word_count, progress.word_pos), (RecoveryStatus::AwaitingButton, 12, 3));
+
+        let failed = recovery_progress("kk1", Err("Failure: Invalid mnemonic".to_string())).unwrap();
+        assert_eq!((failed.status, failed.message.as_deref()), (RecoveryStatus::Failed, Some("Invalid mnemonic")));
+        assert!(recovery_progress("kk1", Ok(Message::Success(Default::default()))).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+// commands/ripple.rs - XRP addresses and payment signing
+
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, SigningAuditInput};
+use keepkey_db::types::TransactionCache;
+use keepkey_rust::chains::ripple::{get_ripple_address, sign_ripple_payment, RipplePayment};
+use vault_core::maintenance::MaintenanceController;
+use vault_core::paths::parse_derivation_path;
+use vault_core::request_queue::request_queue;
+use vault_core::signing_origin::{self, LimitConfirmations, SigningOrigin};
+use vault_core::units::format_units;
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use super::device::get_or_create_device_queue;
+use super::DeviceQueueManager;
+
+/// Native XRP on the XRP Ledger mainnet
+const XRP_CAIP: &str = "ripple:4109c6f2045fc7eff4cde8f9905d19c2/slip44:144";
+
+/// Account 0, first address
+const DEFAULT_XRP_PATH: &str = "m/44'/144'/0'/0/0";
+
+/// Drops per XRP
+const XRP_DECIMALS: u32 = 6;
+
+/// Get the XRP address at `path` (m/44'/144'/0'/0/0 when omitted)
+#[tauri::command]
+#[specta::specta]
+pub async fn get_xrp_address(
+    device_id: String,
+    path: Option<String>,
+    show_display: bool,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<String, String> {
+    let address_n = parse_derivation_path(path.as_deref().unwrap_or(DEFAULT_XRP_PATH))?;
+    request_queue().run(&device_id, "get_xrp_address", || async {
+        let queue = get_or_create_device_queue(&device_id, &queue_manager).await?;
+        get_ripple_address(&queue, &address_n, show_display)
+            .await
+            .map_err(|e| format!("Failed to get XRP address: {}", e))
+    }).await
+}
+
+/// An XRP payment as sent by the UI; amounts and fee are in drops
+#[derive(Debug, Deserialize, specta::Type)]
+pub struct XrpPaymentRequest {
+    pub path: Option<String>,
+    pub amount_drops: u64,
+    pub destination: String,
+    /// Exchanges credit deposits by tag; leaving it out there loses the funds
+    pub destination_tag: Option<u32>,
+    pub fee_drops: u64,
+    pub sequence: u32,
+    pub flags: Option<u32>,
+    pub last_ledger_sequence: Option<u32>,
+}
+
+/// A signed payment ready for rippled's `submit`
+#[derive(Debug, Serialize, specta::Type)]
+pub struct SignedXrpPayment {
+    pub tx_hash: String,
+    /// Signed transaction as upper-case hex
+    pub tx_blob: String,
+    pub fee_drops: u64,
+}
+
+/// Sign an XRP payment on the device and cache it as a signed send
+#[tauri::command]
+#[specta::specta]
+pub async fn sign_xrp_payment(
+    app: AppHandle,
+    device_id: String,
+    payment: XrpPaymentRequest,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    limit_confirmations: State<'_, LimitConfirmations>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<SignedXrpPayment, String> {
+    let _operation = maintenance.device_operation("sign_xrp_payment").map_err(|e| e.to_json_string())?;
+    vault_core::authenticity::require_verified_device(&device_id)?;
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let origin = SigningOrigin::MainWindow;
+
+    let payment = RipplePayment {
+        address_n: parse_derivation_path(payment.path.as_deref().unwrap_or(DEFAULT_XRP_PATH))?,
+        amount_drops: payment.amount_drops,
+        destination: payment.destination,
+        destination_tag: payment.destination_tag,
+        fee: payment.fee_drops,
+        sequence: payment.sequence,
+        flags: payment.flags.unwrap_or(0),
+        last_ledger_sequence: payment.last_ledger_sequence,
+    };
+
+    let queue = get_or_create_device_queue(&device_id, &queue_manager).await?;
+    let price = database.get_asset_price_usd(XRP_CAIP).await.unwrap_or(None);
+    let value_usd = price.map(|p| payment.amount_drops as f64 / 1_000_000.0 * p);
+    let ticket = signing_origin::authorize_signing(
+        &database,
+        &limit_confirmations,
+        &origin,
+        SigningAuditInput {
+            origin: origin.key(),
+            device_id: device_id.clone(),
+            wallet_fingerprint: wallet_fingerprint.clone(),
+            caip: XRP_CAIP.to_string(),
+            value_usd,
+        },
+        super::signing::LIMIT_CONFIRMATION_TIMEOUT,
+        |audit_id, decision| super::signing::notify_limit_confirmation(&app, audit_id, &origin, XRP_CAIP, decision),
+    ).await?;
+
+    log::info!(
+        "Signing XRP payment on {}: {} drops to {} (tag {:?}), fee {} drops",
+        device_id, payment.amount_drops, payment.destination, payment.destination_tag, payment.fee
+    );
+
+    let signed = request_queue().run_with_timeout(&device_id, "sign_xrp_payment", super::signing::DEVICE_SIGNING_TIMEOUT, || async {
+        sign_ripple_payment(&queue, payment.clone()).await.map_err(|e| format!("Signing failed: {}", e))
+    }).await;
+    let signed = match signed {
+        Ok(signed) => signed,
+        Err(error) => {
+            signing_origin::complete_signing(&database, &ticket, Err(&error)).await;
+            return Err(error);
+        }
+    };
+    let tx_hash = signed.tx_hash();
+    signing_origin::complete_signing(&database, &ticket, Ok(Some(&tx_hash))).await;
+
+    // Cached as signed; broadcast_transaction moves it to pending. The
+    // payment is signed either way, so a cache failure is only logged.
+    let metadata = payment.destination_tag.map(|tag| serde_json::json!({ "destination_tag": tag }).to_string());
+    let cached = database
+        .upsert_transaction(&TransactionCache {
+            id: 0,
+            device_id: device_id.clone(),
+            txid: tx_hash.clone(),
+            caip: XRP_CAIP.to_string(),
+            transaction_type: "send".to_string(),
+            amount: format_units(payment.amount_drops as u128, XRP_DECIMALS),
+            amount_usd: value_usd.map(|v| format!("{:.2}", v)),
+            fee: Some(format_units(payment.fee as u128, XRP_DECIMALS)),
+            fee_usd: None,
+            from_address: None,
+            to_address: Some(payment.destination.clone()),
+            timestamp: chrono::Utc::now().timestamp(),
+            block_height: None,
+            status: Some("signed".to_string()),
+            metadata_json: metadata,
+            wallet_fingerprint,
+            origin: Some(origin.key()),
+        })
+        .await;
+    if let Err(e) = cached {
+        log::warn!("Failed to cache signed XRP payment {}: {}", tx_hash, e);
+    }
+
+    Ok(SignedXrpPayment {
+        tx_hash,
+        tx_blob: signed.tx_blob(),
+        fee_drops: payment.fee,
+    })
+}
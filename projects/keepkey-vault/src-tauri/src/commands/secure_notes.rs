@@ -0,0 +1,77 @@
+// commands/secure_notes.rs - Device-encrypted secure notes
+//
+// Bodies are encrypted and decrypted by the device (vault_core::secure_notes)
+// and never logged; list and delete only touch metadata.
+
+use std::sync::Arc;
+use tauri::State;
+use keepkey_db::{Database, SecureNote};
+use vault_core::secure_notes::{self, SecureNoteError};
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use super::DeviceQueueManager;
+
+/// Encrypt `body` on the device and store it for the device's active wallet.
+/// Errors are JSON `SecureNoteError`s.
+#[tauri::command]
+#[specta::specta]
+pub async fn add_secure_note(
+    device_id: String,
+    title: String,
+    body: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<SecureNote, String> {
+    let fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    if fingerprint.is_empty() {
+        return Err(SecureNoteError::NoActiveWallet.to_json_string());
+    }
+    let note = secure_notes::add_secure_note(&database, &device_id, &queue_manager, &fingerprint, &title, &body)
+        .await
+        .map_err(|e| e.to_json_string())?;
+    log::info!("🔐 Stored secure note {} for wallet {}", note.id, note.wallet_fingerprint);
+    Ok(note)
+}
+
+/// Decrypt a note on a connected, unlocked device holding the note's wallet.
+/// The device asks the user to confirm before it decrypts.
+#[tauri::command]
+#[specta::specta]
+pub async fn read_secure_note(
+    device_id: String,
+    note_id: u32,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<String, String> {
+    let fingerprint = Some(active_wallet_fingerprint(&wallet_sessions, &device_id).await).filter(|f| !f.is_empty());
+    let body = secure_notes::read_secure_note(&database, &device_id, &queue_manager, fingerprint.as_deref(), note_id)
+        .await
+        .map_err(|e| e.to_json_string())?;
+    log::info!("🔐 Secure note {} decrypted on {}", note_id, device_id);
+    Ok(body)
+}
+
+/// Titles and sizes of the stored notes, optionally for one wallet
+#[tauri::command]
+#[specta::specta]
+pub async fn list_secure_notes(
+    wallet_fingerprint: Option<String>,
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<SecureNote>, String> {
+    database.list_secure_notes(wallet_fingerprint.as_deref()).await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Delete a note; returns false if it did not exist
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_secure_note(
+    note_id: u32,
+    database: State<'_, Arc<Database>>,
+) -> Result<bool, String> {
+    let deleted = database.delete_secure_note(note_id).await.map_err(|e| format!("Database error: {}", e))?;
+    if deleted {
+        log::info!("🔐 Deleted secure note {}", note_id);
+    }
+    Ok(deleted)
+}
@@ -0,0 +1,86 @@
+// commands/send.rs - Send flow helpers shared by every chain's build/preview steps
+
+use std::sync::Arc;
+use tauri::State;
+use keepkey_db::Database;
+use vault_core::spendable::{self, ReserveBreakdown};
+use vault_core::units::{format_units, parse_units};
+use vault_core::utxo::{self, Sweep, UtxoScriptType};
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+
+/// Spendable balance for an asset after reserves, gas buffer and pending sends
+#[tauri::command]
+#[specta::specta]
+pub async fn get_spendable_balance(
+    device_id: String,
+    caip: String,
+    owned_items: Option<u32>,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<ReserveBreakdown, String> {
+    let wallet = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let spendable = spendable::load_spendable(&database, &device_id, &wallet, &caip, owned_items.unwrap_or(0)).await?;
+    Ok(spendable.breakdown(&caip))
+}
+
+/// Validate a send amount against the spendable balance.
+///
+/// Build and preview steps call this before touching the device. With
+/// `send_max` the exact fee-inclusive maximum is returned. Failures are a JSON
+/// encoded `SpendError` so the UI can show the reserve breakdown.
+#[tauri::command]
+#[specta::specta]
+pub async fn resolve_send_amount(
+    device_id: String,
+    caip: String,
+    amount: Option<String>,
+    fee: String,
+    send_max: bool,
+    owned_items: Option<u32>,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<String, String> {
+    let wallet = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let spendable = spendable::load_spendable(&database, &device_id, &wallet, &caip, owned_items.unwrap_or(0)).await?;
+
+    let requested = amount
+        .as_deref()
+        .map(|a| parse_units(a, spendable.decimals))
+        .transpose()?;
+    let fee = parse_units(&fee, spendable.decimals)?;
+
+    let resolved = spendable::resolve_send_amount(&spendable, &caip, requested, fee, send_max)
+        .map_err(|e| {
+            log::warn!("🚫 Send rejected for {} on {}: {}", caip, device_id, e);
+            e.to_json_string()
+        })?;
+
+    Ok(format_units(resolved, spendable.decimals))
+}
+
+/// Fee-inclusive max send for UTXO chains: sweep the selected UTXOs into one
+/// output. A sweep that would spend a reserve configured for the network fails
+/// with a JSON encoded `SpendError`, like `resolve_send_amount`.
+#[tauri::command]
+#[specta::specta]
+pub async fn calculate_utxo_max_send(
+    caip: String,
+    utxo_values: Vec<u64>,
+    script_type: String,
+    recipient_script_type: Option<String>,
+    fee_rate_sat_vb: u64,
+    database: State<'_, Arc<Database>>,
+) -> Result<Sweep, String> {
+    let input_type = UtxoScriptType::parse(&script_type)?;
+    let output_type = match recipient_script_type {
+        Some(t) => UtxoScriptType::parse(&t)?,
+        None => input_type,
+    };
+    let sweep = utxo::sweep_all(&utxo_values, input_type, output_type, fee_rate_sat_vb)?;
+
+    let (rules, decimals) = spendable::load_reserve_rules(&database, &caip).await?;
+    let available = utxo::total_sats(utxo_values.iter().copied())?;
+    spendable::check_utxo_send(&rules, &caip, decimals, available, sweep.amount_sats, sweep.fee_sats)
+        .map_err(|e| e.to_json_string())?;
+    Ok(sweep)
+}
@@ -0,0 +1,93 @@
+// commands/signing.rs - Signing origin audit and per-origin soft spending limits
+
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, OriginSpending, OriginSpendingLimit};
+use vault_core::signing_origin::{self, LimitConfirmations, LimitDecision, SigningOrigin, EXTERNAL_DAILY_LIMIT_PREF};
+
+/// How long an over-limit request waits for the user before it is rejected
+pub const LIMIT_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long the user has to review a transaction on the device
+pub const DEVICE_SIGNING_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Ask the UI to confirm an over-limit request before the device is prompted
+pub fn notify_limit_confirmation(app: &AppHandle, audit_id: i64, origin: &SigningOrigin, caip: &str, decision: &LimitDecision) {
+    let payload = serde_json::json!({
+        "request_id": audit_id,
+        "origin": origin,
+        "caip": caip,
+        "limit": decision,
+    });
+    if let Err(e) = super::events::emit_event(app, "signing:limit-confirmation-required", payload) {
+        log::warn!("Failed to emit limit confirmation request: {}", e);
+    }
+}
+
+/// Approve or decline a request that exceeded its origin's daily limit
+#[tauri::command]
+#[specta::specta]
+pub async fn confirm_signing_limit(
+    request_id: i64,
+    approved: bool,
+    limit_confirmations: State<'_, LimitConfirmations>,
+) -> Result<(), String> {
+    if !signing_origin::resolve_limit_confirmation(&limit_confirmations, request_id, approved).await {
+        return Err(format!("No pending confirmation for request {}", request_id));
+    }
+    log::info!("🛑 Limit confirmation for request {}: {}", request_id, if approved { "approved" } else { "declined" });
+    Ok(())
+}
+
+/// Signed and broadcast value per origin over the last `window_days`
+#[tauri::command]
+#[specta::specta]
+pub async fn get_spending_by_origin(
+    window_days: i64,
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<OriginSpending>, String> {
+    if window_days < 1 {
+        return Err("Window must be at least 1 day".to_string());
+    }
+    database.get_spending_by_origin(window_days).await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Configured per-origin limits
+#[tauri::command]
+#[specta::specta]
+pub async fn get_origin_spending_limits(
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<OriginSpendingLimit>, String> {
+    database.list_origin_spending_limits().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Set or clear the daily limit (USD) for one origin key, e.g. `rest_api:<token id>`
+#[tauri::command]
+#[specta::specta]
+pub async fn set_origin_spending_limit(
+    origin: String,
+    daily_limit_usd: Option<f64>,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    database.set_origin_spending_limit(&origin, daily_limit_usd).await.map_err(|e| e.to_string())?;
+    log::info!("🛑 Daily limit for {}: {:?}", origin, daily_limit_usd);
+    Ok(())
+}
+
+/// Set or clear the default daily limit for external origins without their own limit
+#[tauri::command]
+#[specta::specta]
+pub async fn set_external_spending_limit(
+    daily_limit_usd: Option<f64>,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    let value = match daily_limit_usd {
+        Some(limit) if !limit.is_finite() || limit < 0.0 => {
+            return Err("Daily limit must be a non-negative amount".to_string());
+        }
+        Some(limit) => limit.to_string(),
+        None => String::new(),
+    };
+    database.set_preference(EXTERNAL_DAILY_LIMIT_PREF, &value).await.map_err(|e| format!("Database error: {}", e))
+}
@@ -0,0 +1,322 @@
+// commands/storage.rs - Database size management and data directory commands
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use serde::Serialize;
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, DatabaseError, MigrationReport, MigrationStatus, PendingMigration};
+use keepkey_db::data_dir::{self, DataDirLocation, DataDirSource, DATABASE_FILE};
+use keepkey_db::storage::{CompactionReport, DatabaseStats, StoragePolicy};
+use vault_core::maintenance::{MaintenanceController, DEFAULT_DRAIN_TIMEOUT};
+use vault_core::request_queue::request_queue;
+
+/// Report per-table row counts and approximate sizes
+#[tauri::command]
+#[specta::specta]
+pub async fn get_database_stats(
+    database: State<'_, Arc<Database>>,
+) -> Result<DatabaseStats, String> {
+    database.get_database_stats().await.map_err(|e| {
+        log::error!("Failed to collect database stats: {}", e);
+        format!("Database error: {}", e)
+    })
+}
+
+/// Get the retention policy used by pruning and compaction
+#[tauri::command]
+#[specta::specta]
+pub async fn get_storage_policy(
+    database: State<'_, Arc<Database>>,
+) -> Result<StoragePolicy, String> {
+    database.get_storage_policy().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Update the retention policy
+#[tauri::command]
+#[specta::specta]
+pub async fn set_storage_policy(
+    policy: StoragePolicy,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    if policy.history_retention_days < 1 || policy.max_transactions_per_device < 1 {
+        return Err("Retention values must be at least 1".to_string());
+    }
+
+    database.set_preference("history_retention_days", &policy.history_retention_days.to_string()).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    database.set_preference("max_transactions_per_device", &policy.max_transactions_per_device.to_string()).await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    log::info!("💾 Storage policy updated: {:?}", policy);
+    Ok(())
+}
+
+/// Prune, checkpoint and VACUUM the database, emitting storage:compact-progress events
+#[tauri::command]
+#[specta::specta]
+pub async fn compact_database(
+    app: AppHandle,
+    database: State<'_, Arc<Database>>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<CompactionReport, String> {
+    // The scheduler holds a maintenance device_operation guard for each tick,
+    // so entering maintenance waits out a running tick and skips the next
+    // ones until compaction is done
+    let _maintenance = super::maintenance::begin(&maintenance, "Compacting database").await?;
+    log::info!("🗜️ Starting database compaction...");
+
+    let policy = database.get_storage_policy().await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let report = database.compact_database(&policy, |phase| {
+        if let Err(e) = super::events::emit_event(&app, "storage:compact-progress", serde_json::json!({ "phase": phase })) {
+            log::warn!("Failed to emit compaction progress: {}", e);
+        }
+    }).await.map_err(|e| {
+        log::error!("❌ Database compaction failed: {}", e);
+        format!("Compaction failed: {}", e)
+    })?;
+
+    log::info!("✅ Database compacted: {} -> {} bytes", report.size_before_bytes, report.size_after_bytes);
+    Ok(report)
+}
+
+/// Schema changes this build has not yet applied to the database. Non-empty
+/// when a migration was blocked by its dry run or deferred with the
+/// `defer_schema_migrations` preference; the database is read-only until then.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_pending_migrations(
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<PendingMigration>, String> {
+    database.get_pending_migrations().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Apply pending schema migrations now. They are dry-run on a copy first
+/// unless `force` is set, in which case a pre-migration backup is taken and the
+/// file is migrated directly. Emits database:migration-blocked if they fail.
+#[tauri::command]
+#[specta::specta]
+pub async fn apply_migrations_now(
+    app: AppHandle,
+    force: bool,
+    database: State<'_, Arc<Database>>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<MigrationReport, String> {
+    let _maintenance = super::maintenance::begin(&maintenance, "Applying schema migrations").await?;
+    log::info!("🗄️ Applying schema migrations (force: {})", force);
+    let report = database.apply_migrations_now(force).await.map_err(|e| {
+        log::error!("❌ Schema migration failed: {}", e);
+        format!("Migration failed: {}", e)
+    })?;
+    report_migration(&app, &database, &report).await;
+    Ok(report)
+}
+
+/// Log the outcome of a schema migration and tell the UI when it was blocked
+pub async fn report_migration(app: &AppHandle, database: &Database, report: &MigrationReport) {
+    match report.status {
+        MigrationStatus::UpToDate => {}
+        MigrationStatus::Applied | MigrationStatus::AppliedWithoutDryRun => {
+            log::info!("✅ Applied {} schema migration(s) in {}ms", report.pending.len(), report.duration_ms);
+            let _ = database.log_activity(
+                "storage",
+                &format!("Applied {} schema migration(s)", report.pending.len()),
+                Some(&serde_json::json!({ "report": report })),
+            ).await;
+        }
+        MigrationStatus::Deferred => {
+            log::warn!("⏸️ {} schema migration(s) deferred; database is read-only", report.pending.len());
+        }
+        MigrationStatus::Blocked => {
+            // Read-only, so the activity log cannot record it; the event carries the details
+            log::error!("❌ Schema migration blocked: {}", report.failures.join("; "));
+            if let Err(e) = super::emit_or_queue_event(app, "database:migration-blocked", serde_json::json!({ "report": report })).await {
+                log::warn!("Failed to emit database:migration-blocked: {}", e);
+            }
+        }
+    }
+}
+
+/// Apply retention policies once at startup
+pub async fn apply_startup_storage_policy(database: Arc<Database>) -> Result<(), String> {
+    let policy = database.get_storage_policy().await
+        .map_err(|e| format!("Failed to load storage policy: {}", e))?;
+
+    let report = database.apply_storage_policy(&policy).await
+        .map_err(|e| format!("Startup storage pruning failed: {}", e))?;
+    log::info!(
+        "💾 Startup pruning removed {} history rows and {} cached transactions",
+        report.history_rows_pruned, report.transactions_pruned
+    );
+    Ok(())
+}
+
+/// Result of `relocate_data_directory`
+#[derive(Debug, Serialize, specta::Type)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RelocationOutcome {
+    /// The vault now runs from `data_dir`; `previous_dir` is left intact for the user to remove
+    Moved { data_dir: String, previous_dir: String, bytes_copied: u64 },
+    /// A file was in use; the move happens on the next launch
+    ScheduledForRestart { data_dir: String, reason: String },
+}
+
+enum RelocationError {
+    Busy(String),
+    Failed(String),
+    /// The database switched to the target and could not be switched back;
+    /// the target is in use and must be kept
+    Stranded(String),
+}
+
+fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+fn emit_relocate_progress(app: &AppHandle, phase: &str, bytes_copied: u64, total_bytes: u64) {
+    if let Err(e) = super::events::emit_event(app, "storage:relocate-progress", serde_json::json!({
+        "phase": phase,
+        "bytes_copied": bytes_copied,
+        "total_bytes": total_bytes,
+    })) {
+        log::warn!("Failed to emit relocation progress: {}", e);
+    }
+}
+
+/// Copy `files` and then switch the database from `previous_db`; `target` has
+/// already been validated. Nothing in `current` is modified.
+async fn copy_and_switch(
+    app: &AppHandle,
+    database: &Database,
+    current: &Path,
+    previous_db: &Path,
+    target: &Path,
+    files: &[(PathBuf, u64)],
+    total: u64,
+) -> Result<u64, RelocationError> {
+    emit_relocate_progress(app, "copying", 0, total);
+    let copied = data_dir::copy_files(current, target, files, |copied, _| {
+        emit_relocate_progress(app, "copying", copied, total);
+    })
+    .map_err(|e| if data_dir::is_file_busy(&e) {
+        RelocationError::Busy(e.to_string())
+    } else {
+        RelocationError::Failed(format!("Copy failed: {}", e))
+    })?;
+
+    // The pointer is written after the copied database verifies and before the
+    // live connection switches, so a failure at any step leaves the old location in use
+    emit_relocate_progress(app, "database", copied, total);
+    let default_dir = data_dir::default_data_dir();
+    database
+        .relocate_to(&target.join(DATABASE_FILE), || {
+            data_dir::write_pointer(&default_dir, target).map_err(DatabaseError::from)
+        })
+        .await
+        .map_err(|e| match e {
+            DatabaseError::Io(ref io) if data_dir::is_file_busy(io) => RelocationError::Busy(e.to_string()),
+            e => RelocationError::Failed(format!("Database move failed: {}", e)),
+        })?;
+    if let Err(e) = database.health_check().await {
+        // The live connection is on the copy now; go back to the original
+        // file (which the move left untouched) before the copy is discarded
+        let error = format!("Relocated database failed its health check: {}", e);
+        return match database
+            .return_to(previous_db, || data_dir::write_pointer(&default_dir, current).map_err(DatabaseError::from))
+            .await
+        {
+            Ok(()) => Err(RelocationError::Failed(error)),
+            Err(e) => Err(RelocationError::Stranded(format!("{}; switching back failed: {}", error, e))),
+        };
+    }
+
+    emit_relocate_progress(app, "done", total, total);
+    Ok(total)
+}
+
+/// Where the vault keeps its database and backups, and what chose it
+#[tauri::command]
+#[specta::specta]
+pub async fn get_data_directory() -> Result<DataDirLocation, String> {
+    Ok(data_dir::resolve_data_dir())
+}
+
+/// Move the data directory (database, backups, caches) to `new_path`, emitting
+/// storage:relocate-progress events. It runs in maintenance mode and the
+/// database connection is held while it moves, so the app keeps running on the new
+/// location without a restart. If a file is in use the move is scheduled for
+/// the next launch instead. The original directory is never modified.
+#[tauri::command]
+#[specta::specta]
+pub async fn relocate_data_directory(
+    app: AppHandle,
+    new_path: String,
+    database: State<'_, Arc<Database>>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<RelocationOutcome, String> {
+    let location = data_dir::resolve_data_dir();
+    if location.source == DataDirSource::Env {
+        return Err(format!(
+            "The data directory is set by {}; change the variable to move it",
+            data_dir::DATA_DIR_ENV
+        ));
+    }
+    let current = location.path;
+    let target = PathBuf::from(new_path.trim());
+    log::info!("📦 Relocating data directory {:?} -> {:?}", current, target);
+
+    let files = data_dir::relocatable_files(&current, false)
+        .map_err(|e| format!("Failed to read {}: {}", current.display(), e))?;
+    let db_path = database.path();
+    let db_bytes = file_len(&db_path) + file_len(&PathBuf::from(format!("{}-wal", db_path.display())));
+    let total = files.iter().map(|(_, size)| size).sum::<u64>() + db_bytes;
+    data_dir::validate_relocation_target(&current, &target, total).map_err(|e| e.to_string())?;
+
+    let target_existed = target.exists();
+    let session = super::maintenance::begin(&maintenance, "Moving data directory").await?;
+    // Maintenance refuses new device commands and waits for the running
+    // backup; what is already queued on a device is let finish before the move
+    if let Err(queued) = request_queue().wait_idle(DEFAULT_DRAIN_TIMEOUT).await {
+        return Err(format!("{} device operation(s) are still running; try again when they finish", queued));
+    }
+    let result = copy_and_switch(&app, &database, &current, &db_path, &target, &files, total).await;
+    drop(session);
+
+    match result {
+        Ok(bytes_copied) => {
+            log::info!("✅ Data directory moved to {:?} ({} bytes)", target, bytes_copied);
+            let _ = database.log_activity(
+                "storage",
+                &format!("Data directory moved from {} to {}", current.display(), target.display()),
+                None,
+            ).await;
+            Ok(RelocationOutcome::Moved {
+                data_dir: target.display().to_string(),
+                previous_dir: current.display().to_string(),
+                bytes_copied,
+            })
+        }
+        Err(RelocationError::Busy(reason)) => {
+            data_dir::discard_partial_copy(&target, target_existed);
+            data_dir::schedule_relocation(&data_dir::default_data_dir(), &target)
+                .map_err(|e| format!("A file is in use ({}) and the move could not be scheduled: {}", reason, e))?;
+            log::warn!("⏳ Data directory move deferred to next launch: {}", reason);
+            let _ = database.log_activity(
+                "storage",
+                &format!("Data directory move to {} scheduled for next launch", target.display()),
+                None,
+            ).await;
+            Ok(RelocationOutcome::ScheduledForRestart { data_dir: target.display().to_string(), reason })
+        }
+        Err(RelocationError::Failed(error)) => {
+            log::error!("❌ Data directory relocation failed: {}", error);
+            data_dir::discard_partial_copy(&target, target_existed);
+            Err(error)
+        }
+        Err(RelocationError::Stranded(error)) => {
+            log::error!("❌ Data directory relocation failed and the database stays in {:?}: {}", target, error);
+            Err(format!("{}. The vault keeps running from {}; nothing was removed", error, target.display()))
+        }
+    }
+}
@@ -0,0 +1,157 @@
+// commands/tax.rs - Cost-basis lots, realized gains and the transaction CSV
+//
+// Lot bookkeeping lives in keepkey-db (tax_lots.rs). Every figure here is
+// bookkeeping derived from the cached history and the USD values recorded
+// with it; none of it is tax advice.
+
+use std::sync::Arc;
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+use tauri::State;
+use keepkey_db::{Database, Disposal, LotMethod, RealizedGains, TaxLotRebuild, TransactionCache};
+
+#[derive(Debug, Serialize, specta::Type)]
+pub struct TaxLotSettings {
+    pub enabled: bool,
+    pub method: LotMethod,
+}
+
+#[derive(Debug, Serialize, specta::Type)]
+pub struct TransactionCsvExport {
+    pub row_count: u32,
+    pub csv: String,
+    /// File the CSV was written to, when one was given
+    pub output_path: Option<String>,
+}
+
+const CSV_HEADER: &str = "date,txid,device_id,wallet_fingerprint,asset,type,amount,amount_usd,fee,fee_usd,status,\
+lot_method,disposed_amount,acquired_date,proceeds_usd,cost_basis_usd,gain_usd";
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_date(timestamp: i64) -> String {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .unwrap_or_default()
+}
+
+fn csv_usd(value: Option<f64>) -> String {
+    value.map(|v| format!("{:.2}", v)).unwrap_or_default()
+}
+
+fn csv_row(tx: &TransactionCache, disposal: Option<&Disposal>) -> String {
+    let mut fields = vec![
+        csv_date(tx.timestamp),
+        tx.txid.clone(),
+        tx.device_id.clone(),
+        tx.wallet_fingerprint.clone(),
+        tx.caip.clone(),
+        tx.transaction_type.clone(),
+        tx.amount.clone(),
+        tx.amount_usd.clone().unwrap_or_default(),
+        tx.fee.clone().unwrap_or_default(),
+        tx.fee_usd.clone().unwrap_or_default(),
+        tx.status.clone().unwrap_or_default(),
+    ];
+    match disposal {
+        Some(d) => fields.extend([
+            d.method.as_str().to_string(),
+            d.amount.to_string(),
+            d.acquired_at.map(csv_date).unwrap_or_default(),
+            csv_usd(d.proceeds_usd),
+            csv_usd(d.cost_basis_usd),
+            csv_usd(d.gain_usd),
+        ]),
+        None => fields.extend(std::iter::repeat_n(String::new(), 6)),
+    }
+    fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",")
+}
+
+/// Whether lot tracking is on and which consumption order it uses
+#[tauri::command]
+#[specta::specta]
+pub async fn get_tax_lot_settings(database: State<'_, Arc<Database>>) -> Result<TaxLotSettings, String> {
+    Ok(TaxLotSettings {
+        enabled: database.is_tax_lot_tracking_enabled().await.map_err(|e| format!("Database error: {}", e))?,
+        method: database.get_tax_lot_method().await.map_err(|e| format!("Database error: {}", e))?,
+    })
+}
+
+/// Turn lot tracking on (replaying the cached history) or off (dropping the
+/// lots). While on, cached transactions are not pruned by the storage policy.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_tax_lot_tracking(
+    enabled: bool,
+    database: State<'_, Arc<Database>>,
+) -> Result<Option<TaxLotRebuild>, String> {
+    let rebuild = database.set_tax_lot_tracking(enabled).await.map_err(|e| format!("Database error: {}", e))?;
+    log::info!("🧾 Tax lot tracking {}", if enabled { "enabled" } else { "disabled" });
+    Ok(rebuild)
+}
+
+/// Switch between FIFO and HIFO; every disposal is recomputed
+#[tauri::command]
+#[specta::specta]
+pub async fn set_tax_lot_method(
+    method: LotMethod,
+    database: State<'_, Arc<Database>>,
+) -> Result<TaxLotRebuild, String> {
+    let rebuild = database.set_tax_lot_method(method).await.map_err(|e| format!("Database error: {}", e))?;
+    log::info!("🧾 Rebuilt {} lots and {} disposals with {}", rebuild.lots, rebuild.disposals, method.as_str());
+    Ok(rebuild)
+}
+
+/// Recompute all lots and disposals from the cached history
+#[tauri::command]
+#[specta::specta]
+pub async fn rebuild_tax_lots(database: State<'_, Arc<Database>>) -> Result<TaxLotRebuild, String> {
+    database.rebuild_tax_lots().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Disposals dated in `year` (UTC) with proceeds, cost basis and gain.
+///
+/// Bookkeeping figures from the cached history, not tax advice. Disposals
+/// whose proceeds or cost basis are unknown are listed but left out of the
+/// totals.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_realized_gains(year: i32, database: State<'_, Arc<Database>>) -> Result<RealizedGains, String> {
+    database.get_realized_gains(year).await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Cached transactions as CSV, optionally for one year (UTC). With lot
+/// tracking on, sends have one row per lot they consumed, with that lot's
+/// acquisition date, cost basis and gain. With `output_path` the CSV is also
+/// written to that file.
+#[tauri::command]
+#[specta::specta]
+pub async fn export_transactions_csv(
+    year: Option<i32>,
+    output_path: Option<String>,
+    database: State<'_, Arc<Database>>,
+) -> Result<TransactionCsvExport, String> {
+    let rows = database.get_transaction_export_rows(year).await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+    for (tx, disposal) in &rows {
+        csv.push_str(&csv_row(tx, disposal.as_ref()));
+        csv.push('\n');
+    }
+
+    if let Some(path) = &output_path {
+        std::fs::write(path, &csv).map_err(|e| format!("Could not write {}: {}", path, e))?;
+    }
+    log::info!("🧾 Exported {} transaction rows", rows.len());
+
+    Ok(TransactionCsvExport { row_count: rows.len() as u32, csv, output_path })
+}
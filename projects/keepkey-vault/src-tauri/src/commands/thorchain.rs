@@ -0,0 +1,229 @@
+// commands/thorchain.rs - THORChain and Mayachain addresses and signing
+//
+// Sends and MsgDeposit (swaps, liquidity) on both chains. The caller builds
+// the broadcast transaction from the returned signature and sign doc, so no
+// txid is known here and nothing is written to the transaction cache.
+
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, SigningAuditInput};
+use keepkey_rust::chains::thorchain::{get_thor_address, ThorMessage, ThorNetwork, ThorTransaction};
+use keepkey_rust::chains::thorchain::transaction::sign_thor_transaction;
+use vault_core::maintenance::MaintenanceController;
+use vault_core::paths::parse_derivation_path;
+use vault_core::request_queue::request_queue;
+use vault_core::signing_origin::{self, LimitConfirmations, SigningOrigin};
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use super::device::get_or_create_device_queue;
+use super::DeviceQueueManager;
+
+/// Account 0, first address; both chains use coin type 931
+const DEFAULT_THOR_PATH: &str = "m/44'/931'/0'/0/0";
+
+/// Native asset and decimals of each chain
+fn native_asset(network: ThorNetwork) -> (&'static str, i32) {
+    match network {
+        ThorNetwork::Thorchain => ("cosmos:thorchain-mainnet-v1/slip44:931", 8),
+        ThorNetwork::Mayachain => ("cosmos:mayachain-mainnet-v1/slip44:931", 10),
+    }
+}
+
+async fn thor_address(
+    queue_manager: &DeviceQueueManager,
+    network: ThorNetwork,
+    device_id: String,
+    path: Option<String>,
+    testnet: bool,
+    show_display: bool,
+) -> Result<String, String> {
+    let address_n = parse_derivation_path(path.as_deref().unwrap_or(DEFAULT_THOR_PATH))?;
+    request_queue().run(&device_id, "get_thor_address", || async {
+        let queue = get_or_create_device_queue(&device_id, queue_manager).await?;
+        get_thor_address(&queue, network, &address_n, testnet, show_display)
+            .await
+            .map_err(|e| format!("Failed to get address: {}", e))
+    }).await
+}
+
+/// Get a THORChain address (m/44'/931'/0'/0/0 when `path` is omitted)
+#[tauri::command]
+#[specta::specta]
+pub async fn get_thorchain_address(
+    device_id: String,
+    path: Option<String>,
+    testnet: bool,
+    show_display: bool,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<String, String> {
+    thor_address(&queue_manager, ThorNetwork::Thorchain, device_id, path, testnet, show_display).await
+}
+
+/// Get a Mayachain address (m/44'/931'/0'/0/0 when `path` is omitted)
+#[tauri::command]
+#[specta::specta]
+pub async fn get_mayachain_address(
+    device_id: String,
+    path: Option<String>,
+    testnet: bool,
+    show_display: bool,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<String, String> {
+    thor_address(&queue_manager, ThorNetwork::Mayachain, device_id, path, testnet, show_display).await
+}
+
+/// One message of a THORChain/Mayachain transaction; amounts in base units
+#[derive(Debug, Deserialize, specta::Type)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ThorMessageRequest {
+    Send {
+        from_address: String,
+        to_address: String,
+        amount: u64,
+    },
+    /// `memo` is passed to the chain unchanged, e.g. "=:ETH.ETH:0x..."
+    Deposit {
+        signer: String,
+        asset: String,
+        amount: u64,
+        memo: String,
+    },
+}
+
+/// A transaction as sent by the UI
+#[derive(Debug, Deserialize, specta::Type)]
+pub struct ThorTxRequest {
+    pub path: Option<String>,
+    pub chain_id: String,
+    pub account_number: u64,
+    pub sequence: u64,
+    pub messages: Vec<ThorMessageRequest>,
+    pub fee: u64,
+    pub gas: u64,
+    #[serde(default)]
+    pub memo: String,
+    #[serde(default)]
+    pub testnet: bool,
+}
+
+/// Signature and the sign doc it covers, for building the broadcast tx
+#[derive(Debug, Serialize, specta::Type)]
+pub struct SignedThorTx {
+    /// Compressed public key as hex
+    pub public_key: String,
+    /// Signature as hex
+    pub signature: String,
+    /// The canonical Amino JSON that was signed
+    pub sign_doc: String,
+}
+
+/// Sign a THORChain send or deposit
+#[tauri::command]
+#[specta::specta]
+pub async fn sign_thorchain_tx(
+    app: AppHandle,
+    device_id: String,
+    transaction: ThorTxRequest,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    limit_confirmations: State<'_, LimitConfirmations>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<SignedThorTx, String> {
+    let _operation = maintenance.device_operation("sign_thorchain_tx").map_err(|e| e.to_json_string())?;
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    sign_thor(&app, &database, &queue_manager, &limit_confirmations, ThorNetwork::Thorchain, device_id, wallet_fingerprint, transaction).await
+}
+
+/// Sign a Mayachain send or deposit
+#[tauri::command]
+#[specta::specta]
+pub async fn sign_mayachain_tx(
+    app: AppHandle,
+    device_id: String,
+    transaction: ThorTxRequest,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+    limit_confirmations: State<'_, LimitConfirmations>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
+) -> Result<SignedThorTx, String> {
+    let _operation = maintenance.device_operation("sign_mayachain_tx").map_err(|e| e.to_json_string())?;
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    sign_thor(&app, &database, &queue_manager, &limit_confirmations, ThorNetwork::Mayachain, device_id, wallet_fingerprint, transaction).await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn sign_thor(
+    app: &AppHandle,
+    database: &Database,
+    queue_manager: &DeviceQueueManager,
+    limit_confirmations: &LimitConfirmations,
+    network: ThorNetwork,
+    device_id: String,
+    wallet_fingerprint: String,
+    request: ThorTxRequest,
+) -> Result<SignedThorTx, String> {
+    vault_core::authenticity::require_verified_device(&device_id)?;
+    let origin = SigningOrigin::MainWindow;
+    let (caip, decimals) = native_asset(network);
+
+    let messages: Vec<ThorMessage> = request.messages.into_iter().map(|message| match message {
+        ThorMessageRequest::Send { from_address, to_address, amount } => ThorMessage::Send { from_address, to_address, amount },
+        ThorMessageRequest::Deposit { signer, asset, amount, memo } => ThorMessage::Deposit { signer, asset, amount, memo },
+    }).collect();
+    // Deposits of other assets (synths, trade assets) are not priced here
+    let native_total: u64 = messages.iter().map(|message| match message {
+        ThorMessage::Send { amount, .. } => *amount,
+        ThorMessage::Deposit { asset, amount, .. } if asset == network.native_asset() => *amount,
+        ThorMessage::Deposit { .. } => 0,
+    }).sum();
+    let transaction = ThorTransaction {
+        address_n: parse_derivation_path(request.path.as_deref().unwrap_or(DEFAULT_THOR_PATH))?,
+        chain_id: request.chain_id,
+        account_number: request.account_number,
+        sequence: request.sequence,
+        messages,
+        fee: request.fee,
+        gas: request.gas,
+        memo: request.memo,
+        testnet: request.testnet,
+    };
+
+    let queue = get_or_create_device_queue(&device_id, queue_manager).await?;
+    let price = database.get_asset_price_usd(caip).await.unwrap_or(None);
+    let value_usd = price.map(|p| native_total as f64 / 10f64.powi(decimals) * p);
+    let ticket = signing_origin::authorize_signing(
+        database,
+        limit_confirmations,
+        &origin,
+        SigningAuditInput {
+            origin: origin.key(),
+            device_id: device_id.clone(),
+            wallet_fingerprint,
+            caip: caip.to_string(),
+            value_usd,
+        },
+        super::signing::LIMIT_CONFIRMATION_TIMEOUT,
+        |audit_id, decision| super::signing::notify_limit_confirmation(app, audit_id, &origin, caip, decision),
+    ).await?;
+
+    log::info!(
+        "Signing {:?} tx on {}: {} messages, fee {}",
+        network, device_id, transaction.messages.len(), transaction.fee
+    );
+
+    // The sign doc is checked before the audit records the outcome
+    let signed = sign_thor_transaction(&queue, network, transaction).await
+        .map_err(|e| format!("Signing failed: {}", e))
+        .and_then(|signed| Ok(SignedThorTx {
+            public_key: hex::encode(&signed.public_key),
+            signature: hex::encode(&signed.signature),
+            sign_doc: String::from_utf8(signed.sign_doc).map_err(|e| format!("Invalid sign doc: {}", e))?,
+        }));
+    match &signed {
+        Ok(_) => signing_origin::complete_signing(database, &ticket, Ok(None)).await,
+        Err(error) => signing_origin::complete_signing(database, &ticket, Err(error)).await,
+    }
+    signed
+}
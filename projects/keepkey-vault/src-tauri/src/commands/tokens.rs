@@ -0,0 +1,115 @@
+// commands/tokens.rs - User-added ERC-20 tokens and token transfer calldata
+
+use std::sync::Arc;
+use serde::Serialize;
+use tauri::{AppHandle, State};
+use keepkey_db::{CustomToken, Database};
+use vault_core::endpoints::Priority;
+use vault_core::erc20::{self, MetadataMismatch};
+use super::approvals::{http_client, rpc_call, EvmNetwork};
+
+/// A stored custom token and where the user's input disagreed with the contract
+#[derive(Debug, Serialize, specta::Type)]
+pub struct AddedCustomToken {
+    pub token: CustomToken,
+    pub warnings: Vec<MetadataMismatch>,
+}
+
+/// Add an ERC-20 token by contract address.
+///
+/// The contract is checked over the network's RPC first; name, symbol or
+/// decimals that differ from the input are returned as warnings and the
+/// contract's decimals are stored. The token is mapped to the network's
+/// derivation paths and a `portfolio:sync-requested` event asks the frontend
+/// to fetch its balances right away.
+#[tauri::command]
+#[specta::specta]
+pub async fn add_custom_token(
+    app: AppHandle,
+    network_id: String,
+    contract_address: String,
+    symbol: String,
+    name: String,
+    decimals: u32,
+    database: State<'_, Arc<Database>>,
+) -> Result<AddedCustomToken, String> {
+    let network = EvmNetwork::load(&database, &network_id).await?;
+    let client = http_client()?;
+    let url = network.rpc_url()?.to_string();
+    let checked = erc20::validate_token_contract(
+        &|method, params| rpc_call(client.clone(), url.clone(), method, params, Priority::Interactive),
+        &contract_address,
+        &symbol,
+        &name,
+        decimals,
+    ).await?;
+
+    let token = database
+        .add_custom_token(&network_id, &checked.contract_address, &checked.symbol, &checked.name, checked.decimals)
+        .await
+        .map_err(|e| e.to_string())?;
+    if checked.warnings.is_empty() {
+        log::info!("🪙 Added custom token {} ({})", token.symbol, token.caip);
+    } else {
+        log::warn!("🪙 Added custom token {} despite metadata mismatches: {:?}", token.caip, checked.warnings);
+    }
+
+    let details = serde_json::json!({ "caip": token.caip, "warnings": checked.warnings });
+    if let Err(e) = database.log_activity("assets", &format!("Added custom token {}", token.symbol), Some(&details)).await {
+        log::warn!("Failed to log custom token: {}", e);
+    }
+    if let Err(e) = super::emit_or_queue_event(
+        &app,
+        "portfolio:sync-requested",
+        serde_json::json!({ "network_id": network_id, "caips": [token.caip], "reason": "custom_token_added" }),
+    ).await {
+        log::warn!("Failed to request balance sync for {}: {}", token.caip, e);
+    }
+
+    Ok(AddedCustomToken { token, warnings: checked.warnings })
+}
+
+/// Remove a custom token. Refused while a wallet holds a balance of it unless
+/// `force`; returns false if the token was not found.
+#[tauri::command]
+#[specta::specta]
+pub async fn remove_custom_token(
+    caip: String,
+    force: Option<bool>,
+    database: State<'_, Arc<Database>>,
+) -> Result<bool, String> {
+    let removed = database.remove_custom_token(&caip, force.unwrap_or(false)).await
+        .map_err(|e| e.to_string())?;
+    if removed {
+        log::info!("🪙 Removed custom token {}", caip);
+    }
+    Ok(removed)
+}
+
+/// Every token the user added
+#[tauri::command]
+#[specta::specta]
+pub async fn list_custom_tokens(
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<CustomToken>, String> {
+    database.list_custom_tokens().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Calldata for transferring `amount` (token units) of an ERC-20 asset,
+/// scaled by the decimals stored in the asset registry
+#[tauri::command]
+#[specta::specta]
+pub async fn build_erc20_transfer_data(
+    caip: String,
+    recipient: String,
+    amount: String,
+    database: State<'_, Arc<Database>>,
+) -> Result<String, String> {
+    if !caip.contains("/erc20:") {
+        return Err(format!("{} is not an ERC-20 token", caip));
+    }
+    let decimals = database.get_asset_decimals(&caip).await
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("Decimals for {} are unknown", caip))?;
+    erc20::transfer_calldata(&recipient, &amount, decimals)
+}
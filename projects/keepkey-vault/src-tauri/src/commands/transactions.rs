@@ -0,0 +1,280 @@
+// commands/transactions.rs - Confirmation and reorg tracking for the activity cache
+//
+// The cache also backs the activity feed: get_recent_activity pages through a
+// device wallet's transactions with the asset details the feed shows.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+use keepkey_db::{Asset, Database};
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use vault_core::endpoints::{endpoint_manager, EndpointError, Priority};
+use vault_core::paths::{network_family, NetworkFamily};
+use vault_core::power::{BackgroundWork, PowerMonitor};
+use vault_core::token_approvals::parse_quantity;
+use vault_core::tx_tracker::{self, ChainTxStatus, RecheckOutcome, REORG_EVENT};
+use super::approvals::{http_client, rpc_call};
+
+/// How often tracked transactions are rechecked (stretched on battery power)
+const RECHECK_INTERVAL: Duration = Duration::from_secs(120);
+
+/// Feed page size when none is given, and the most one page returns
+const ACTIVITY_PAGE: u32 = 50;
+const MAX_ACTIVITY_PAGE: u32 = 200;
+
+/// One transaction in the activity feed
+#[derive(Debug, Serialize, specta::Type)]
+pub struct ActivityItem {
+    pub txid: String,
+    pub caip: String,
+    /// From the asset registry; None for assets it does not know
+    pub symbol: Option<String>,
+    pub asset_name: Option<String>,
+    pub icon: Option<String>,
+    /// 'send', 'receive', 'swap', 'stake', 'unstake' or 'approve'
+    pub transaction_type: String,
+    pub amount: String,
+    pub amount_usd: Option<String>,
+    pub fee: Option<String>,
+    pub from_address: Option<String>,
+    pub to_address: Option<String>,
+    pub timestamp: i64,
+    pub block_height: Option<i64>,
+    /// 'signed' (not broadcast yet), 'pending', 'confirmed', 'failed' or 'reorged'
+    pub status: Option<String>,
+    /// Explorer page for the transaction, when the asset has a link pattern
+    pub explorer_url: Option<String>,
+}
+
+/// How a network's chain state is read
+enum ChainSource {
+    /// JSON-RPC node
+    Evm { rpc_url: String },
+    /// Esplora-compatible REST API (the network's explorer_api_url)
+    Esplora { api_url: String },
+}
+
+impl ChainSource {
+    async fn load(database: &Database, network_id: &str) -> Result<Option<Self>, String> {
+        let Some(endpoints) = database.get_network_endpoints(network_id).await
+            .map_err(|e| format!("Database error: {}", e))? else {
+            return Ok(None);
+        };
+        Ok(match network_family(network_id) {
+            Ok(NetworkFamily::Evm { .. }) => endpoints.rpc_urls.first().map(|url| Self::Evm { rpc_url: url.clone() }),
+            Ok(NetworkFamily::Utxo { .. }) => endpoints.explorer_api_url.map(|url| Self::Esplora {
+                api_url: url.trim_end_matches('/').to_string(),
+            }),
+            Err(_) => None,
+        })
+    }
+
+    fn url(&self) -> &str {
+        match self {
+            Self::Evm { rpc_url } => rpc_url,
+            Self::Esplora { api_url } => api_url,
+        }
+    }
+
+    async fn tip_height(&self, client: &reqwest::Client, priority: Priority) -> Result<i64, String> {
+        match self {
+            Self::Evm { rpc_url } => {
+                let tip = rpc_call(client.clone(), rpc_url.clone(), "eth_blockNumber", json!([]), priority).await?;
+                Ok(parse_quantity(&tip)? as i64)
+            }
+            Self::Esplora { api_url } => {
+                let url = format!("{}/blocks/tip/height", api_url);
+                let text = endpoint_manager().send(client.get(&url), priority).await
+                    .and_then(|r| r.error_for_status().map_err(EndpointError::Request))
+                    .map_err(|e| format!("Request to {} failed: {}", url, e))?
+                    .text().await
+                    .map_err(|e| format!("Invalid response from {}: {}", url, e))?;
+                text.trim().parse().map_err(|_| format!("Invalid tip height from {}: {}", url, text))
+            }
+        }
+    }
+
+    async fn lookup(&self, client: reqwest::Client, txid: String, priority: Priority) -> Result<ChainTxStatus, String> {
+        match self {
+            Self::Evm { rpc_url } => {
+                let receipt = rpc_call(client.clone(), rpc_url.clone(), "eth_getTransactionReceipt", json!([txid]), priority).await?;
+                if let Some(block) = receipt.get("blockNumber").filter(|b| !b.is_null()) {
+                    return Ok(ChainTxStatus::Confirmed { height: parse_quantity(block)? as i64 });
+                }
+                let tx = rpc_call(client, rpc_url.clone(), "eth_getTransactionByHash", json!([txid]), priority).await?;
+                Ok(if tx.is_null() { ChainTxStatus::NotFound } else { ChainTxStatus::InMempool })
+            }
+            Self::Esplora { api_url } => {
+                let url = format!("{}/tx/{}/status", api_url, txid);
+                let response = endpoint_manager().send(client.get(&url), priority).await
+                    .map_err(|e| format!("Request to {} failed: {}", url, e))?;
+                if response.status() == reqwest::StatusCode::NOT_FOUND {
+                    return Ok(ChainTxStatus::NotFound);
+                }
+                let status: Value = response.error_for_status()
+                    .map_err(|e| format!("Request to {} failed: {}", url, e))?
+                    .json().await
+                    .map_err(|e| format!("Invalid response from {}: {}", url, e))?;
+                match (status.get("confirmed").and_then(Value::as_bool), status.get("block_height").and_then(Value::as_i64)) {
+                    (Some(true), Some(height)) => Ok(ChainTxStatus::Confirmed { height }),
+                    (Some(false), _) => Ok(ChainTxStatus::InMempool),
+                    _ => Err(format!("Unexpected transaction status from {}: {}", url, status)),
+                }
+            }
+        }
+    }
+}
+
+/// Recheck one network; None when it has no finality depth, no usable
+/// endpoint, or background work against the endpoint is being deferred
+async fn recheck_network(
+    database: &Database,
+    client: &reqwest::Client,
+    network_id: &str,
+    priority: Priority,
+) -> Result<Option<RecheckOutcome>, String> {
+    let finality = database.get_network_finality(network_id).await
+        .map_err(|e| format!("Database error: {}", e))?
+        .unwrap_or_default();
+    let Some(depth) = tx_tracker::finality_depth(network_id, finality.network_type.as_deref(), finality.finality_depth) else {
+        return Ok(None);
+    };
+    let Some(source) = ChainSource::load(database, network_id).await? else {
+        return Ok(None);
+    };
+    if priority == Priority::Background {
+        if let Some(retry_in) = endpoint_manager().background_deferral(source.url()) {
+            log::debug!("Deferring transaction recheck on {} for {}s", network_id, retry_in.as_secs());
+            return Ok(None);
+        }
+    }
+    let tip = source.tip_height(client, priority).await?;
+    let outcome = tx_tracker::recheck_network(
+        database,
+        network_id,
+        depth,
+        tip,
+        |txid| source.lookup(client.clone(), txid, priority),
+        Database::current_timestamp(),
+    ).await?;
+    Ok(Some(outcome))
+}
+
+/// Recheck every network with tracked transactions and announce reorgs
+async fn recheck_all(app: &AppHandle, database: &Database, priority: Priority) -> Result<RecheckOutcome, String> {
+    let client = http_client()?;
+    let mut total = RecheckOutcome::default();
+    let networks = database.get_tracked_networks().await.map_err(|e| format!("Database error: {}", e))?;
+    for network_id in networks {
+        match recheck_network(database, &client, &network_id, priority).await {
+            Ok(Some(outcome)) => {
+                total.confirmed.extend(outcome.confirmed);
+                total.reorged.extend(outcome.reorged);
+                total.finalized += outcome.finalized;
+                total.lookup_failures += outcome.lookup_failures;
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("⚠️ Could not recheck transactions on {}: {}", network_id, e),
+        }
+    }
+
+    for change in &total.reorged {
+        log::warn!(
+            "⛓️ Reorg: {} on {} went from {} at {:?} to {} at {:?}",
+            change.txid, change.caip, change.old_status, change.old_block_height, change.new_status, change.new_block_height
+        );
+        let payload = serde_json::to_value(change).unwrap_or_default();
+        if let Err(e) = super::emit_or_queue_event(app, REORG_EVENT, payload).await {
+            log::warn!("Failed to emit {}: {}", REORG_EVENT, e);
+        }
+    }
+
+    // Balances of the affected wallets may have changed with the reorg
+    if !total.reorged.is_empty() {
+        let device_ids: BTreeSet<&str> = total.reorged.iter().map(|c| c.device_id.as_str()).collect();
+        let caips: BTreeSet<&str> = total.reorged.iter().map(|c| c.caip.as_str()).collect();
+        super::emit_or_queue_event(app, "portfolio:sync-requested", json!({
+            "device_ids": device_ids,
+            "imported_device_ids": [],
+            "caips": caips,
+            "reason": "reorg",
+        })).await?;
+    }
+
+    Ok(total)
+}
+
+/// Recheck tracked transactions for the lifetime of the app
+pub async fn run_transaction_tracker(app: AppHandle, database: Arc<Database>) {
+    let power_monitor = app.state::<Arc<PowerMonitor>>().inner().clone();
+    loop {
+        let interval = power_monitor
+            .interval(BackgroundWork::PeriodicRefresh, RECHECK_INTERVAL)
+            .unwrap_or(RECHECK_INTERVAL);
+        tokio::time::sleep(interval).await;
+        if let Err(e) = recheck_all(&app, &database, Priority::Background).await {
+            log::warn!("⚠️ Transaction recheck failed: {}", e);
+        }
+    }
+}
+
+/// Recheck pending and recently confirmed transactions now; the frontend calls this after a sync
+#[tauri::command]
+#[specta::specta]
+pub async fn recheck_transactions(
+    app: AppHandle,
+    database: State<'_, Arc<Database>>,
+) -> Result<RecheckOutcome, String> {
+    recheck_all(&app, &database, Priority::Interactive).await
+}
+
+/// A page of the activity feed of a device's active wallet, newest first
+#[tauri::command]
+#[specta::specta]
+pub async fn get_recent_activity(
+    device_id: String,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<Vec<ActivityItem>, String> {
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let limit = limit.unwrap_or(ACTIVITY_PAGE).min(MAX_ACTIVITY_PAGE);
+    let txs = database.get_recent_transactions(&device_id, &wallet_fingerprint, limit, offset.unwrap_or(0)).await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let mut assets: HashMap<String, Option<Asset>> = HashMap::new();
+    for tx in &txs {
+        if !assets.contains_key(&tx.caip) {
+            let asset = database.get_asset_by_caip(&tx.caip).await
+                .map_err(|e| format!("Database error: {}", e))?;
+            assets.insert(tx.caip.clone(), asset);
+        }
+    }
+
+    Ok(txs.into_iter().map(|tx| {
+        let asset = assets.get(&tx.caip).and_then(Option::as_ref);
+        ActivityItem {
+            symbol: asset.map(|a| a.symbol.clone()),
+            asset_name: asset.map(|a| a.name.clone()),
+            icon: asset.and_then(|a| a.icon.clone()),
+            explorer_url: asset
+                .and_then(|a| a.explorer_tx_link.as_deref())
+                .map(|link| link.replace("{txid}", &tx.txid)),
+            txid: tx.txid,
+            caip: tx.caip,
+            transaction_type: tx.transaction_type,
+            amount: tx.amount,
+            amount_usd: tx.amount_usd,
+            fee: tx.fee,
+            from_address: tx.from_address,
+            to_address: tx.to_address,
+            timestamp: tx.timestamp,
+            block_height: tx.block_height,
+            status: tx.status,
+        }
+    }).collect())
+}
@@ -0,0 +1,155 @@
+// commands/tray.rs - Tray (menubar) summary and its quick actions
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+use vault_core::tray::TraySummary;
+use vault_core::wallet_session::{self, WalletSessions};
+use super::DeviceQueueManager;
+
+/// Preference key; the tray is shown unless this is "false"
+pub const SHOW_TRAY_PREF: &str = "show_tray";
+
+/// KeepKeys currently on the bus (enumeration only, nothing is sent to them)
+pub fn connected_device_ids() -> HashSet<String> {
+    keepkey_rust::features::list_connected_devices()
+        .into_iter()
+        .filter(|d| d.is_keepkey)
+        .map(|d| d.unique_id)
+        .collect()
+}
+
+/// Summary for the tray, built from the registry and dashboard caches
+pub async fn build_tray_summary(database: &Database, wallet_sessions: &WalletSessions) -> Result<TraySummary, String> {
+    let connected = connected_device_ids();
+    let active_wallets: HashMap<String, String> = wallet_sessions
+        .lock()
+        .await
+        .iter()
+        .map(|(device_id, wallet)| (device_id.clone(), wallet.wallet_fingerprint.clone()))
+        .collect();
+    vault_core::tray::get_tray_summary(database, &connected, &active_wallets).await
+}
+
+/// Lock a device, store its refreshed features and emit `device:locked` and
+/// `device:status-changed`. `reason` is "user" or "idle".
+pub async fn lock_and_refresh(
+    app: &AppHandle,
+    database: &Database,
+    queue_manager: &DeviceQueueManager,
+    wallet_sessions: &WalletSessions,
+    device_id: &str,
+    reason: &str,
+) -> Result<(), String> {
+    wallet_session::lock_device(wallet_sessions, queue_manager, device_id).await?;
+
+    // Re-read features so the registry reflects pinCached = false
+    if let Err(e) = vault_core::refresh_stored_features(database, device_id, queue_manager).await {
+        log::warn!("Could not refresh features after locking {}: {}", device_id, e);
+    }
+
+    super::emit_or_queue_event(app, "device:locked", serde_json::json!({
+        "device_id": device_id,
+        "reason": reason,
+    })).await?;
+
+    super::emit_or_queue_event(app, "device:status-changed", serde_json::json!({
+        "device_id": device_id,
+        "locked": true,
+    })).await
+}
+
+/// Lock every connected device; returns how many were locked
+pub async fn lock_all_devices(
+    app: &AppHandle,
+    database: &Database,
+    queue_manager: &DeviceQueueManager,
+    wallet_sessions: &WalletSessions,
+) -> usize {
+    let mut locked = 0;
+    for device_id in connected_device_ids() {
+        match lock_and_refresh(app, database, queue_manager, wallet_sessions, &device_id, "user").await {
+            Ok(()) => locked += 1,
+            Err(e) => log::warn!("Failed to lock {}: {}", device_id, e),
+        }
+    }
+    locked
+}
+
+/// Connected devices with lock/setup state and the cached portfolio total
+#[tauri::command]
+#[specta::specta]
+pub async fn get_tray_summary(
+    database: State<'_, Arc<Database>>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<TraySummary, String> {
+    build_tray_summary(&database, &wallet_sessions).await
+}
+
+/// Clear the PIN/passphrase session on a device
+#[tauri::command]
+#[specta::specta]
+pub async fn lock_device(
+    app: AppHandle,
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<(), String> {
+    lock_and_refresh(&app, &database, &queue_manager, &wallet_sessions, &device_id, "user").await
+}
+
+/// Ask the frontend to refresh portfolio data for the connected devices and
+/// watch-only imported wallets
+#[tauri::command]
+#[specta::specta]
+pub async fn sync_now(app: AppHandle) -> Result<(), String> {
+    use tauri::Manager;
+
+    let device_ids: Vec<String> = connected_device_ids().into_iter().collect();
+    let imported_device_ids: Vec<String> = match app.try_state::<Arc<Database>>() {
+        Some(database) => database
+            .list_imported_wallets()
+            .await
+            .map_err(|e| format!("Database error: {}", e))?
+            .into_iter()
+            .map(|wallet| wallet.device_id)
+            .collect(),
+        None => Vec::new(),
+    };
+    // Networks the user switched off are left out of the sync
+    let disabled_network_ids: Vec<String> = match app.try_state::<Arc<Database>>() {
+        Some(database) => database
+            .get_disabled_networks()
+            .await
+            .map_err(|e| format!("Database error: {}", e))?,
+        None => Vec::new(),
+    };
+    log::info!(
+        "🔄 Portfolio sync requested for {} device(s) and {} imported wallet(s)",
+        device_ids.len(),
+        imported_device_ids.len()
+    );
+    super::emit_or_queue_event(&app, "portfolio:sync-requested", serde_json::json!({
+        "device_ids": device_ids,
+        "imported_device_ids": imported_device_ids,
+        "disabled_network_ids": disabled_network_ids,
+    })).await
+}
+
+/// Show or hide the tray icon
+#[tauri::command]
+#[specta::specta]
+pub async fn set_show_tray(
+    app: AppHandle,
+    enabled: bool,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    database
+        .set_preference(SHOW_TRAY_PREF, if enabled { "true" } else { "false" })
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+    crate::tray::refresh_tray(&app).await;
+    Ok(())
+}
@@ -1,2 +1,234 @@
-// commands/verification.rs - Seed verification commands
-pub fn _placeholder() {} 
\ No newline at end of file
+// commands/verification.rs - Verifying addresses on the device screen
+//
+// An address shown in the app is only as trustworthy as the machine showing
+// it. `verify_address` derives it again with the device displaying it, so the
+// user can compare the two, and checks the result against what the app
+// expects for that path: the address it served before (cached by
+// `get_address_for_caip` and earlier verifications), or for Bitcoin the
+// address derived from the cached account xpub. A mismatch means the cache,
+// or whatever filled it, is wrong.
+
+use std::sync::Arc;
+use std::time::Duration;
+use bitcoin::Network;
+use serde::Serialize;
+use tauri::{AppHandle, State};
+use keepkey_db::Database;
+use keepkey_rust::chains::{bitcoin as btc_chain, cosmos as cosmos_chain, ethereum as eth_chain};
+use keepkey_rust::chains::bitcoin::ScriptType;
+use keepkey_rust::chains::ChainKind;
+use vault_core::coin_select::account_address;
+use vault_core::paths::{format_derivation_path, parse_derivation_path, HARDENED};
+use vault_core::utxo::UtxoScriptType;
+use vault_core::request_queue::request_queue;
+use vault_core::wallet_session::{active_wallet_fingerprint, WalletSessions};
+use super::DeviceQueueManager;
+
+/// How long the user has to look at the address and press the button
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Serialize, specta::Type)]
+pub struct VerifiedAddress {
+    /// The address the device derived and displayed
+    pub address: String,
+    /// The address the app expected for this path, if it knew one: the one
+    /// it served before, or one derived from the cached account xpub
+    pub cached_address: Option<String>,
+    /// The device disagreed with the cached address
+    pub mismatch: bool,
+}
+
+/// Coins `verify_address` can derive, by the name stored in cached_pubkeys
+enum VerifyCoin {
+    Bitcoin(Network),
+    Ethereum,
+    Cosmos,
+}
+
+impl VerifyCoin {
+    fn parse(coin: &str) -> Result<Self, String> {
+        match coin.to_ascii_lowercase().as_str() {
+            "bitcoin" | "btc" => Ok(VerifyCoin::Bitcoin(Network::Bitcoin)),
+            "testnet" | "tbtc" => Ok(VerifyCoin::Bitcoin(Network::Testnet)),
+            "ethereum" | "eth" => Ok(VerifyCoin::Ethereum),
+            "cosmos" | "atom" => Ok(VerifyCoin::Cosmos),
+            other => Err(format!("Address verification is not supported for {}", other)),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            VerifyCoin::Bitcoin(Network::Bitcoin) => "Bitcoin",
+            VerifyCoin::Bitcoin(_) => "Testnet",
+            VerifyCoin::Ethereum => "Ethereum",
+            VerifyCoin::Cosmos => "Cosmos",
+        }
+    }
+}
+
+/// Script type name the address cache is keyed by
+fn script_name(script: ScriptType) -> &'static str {
+    match script {
+        ScriptType::P2PKH => "p2pkh",
+        ScriptType::P2SH => "p2sh",
+        ScriptType::P2WPKH => "p2wpkh",
+        ScriptType::P2SHP2WPKH => "p2sh-p2wpkh",
+        ScriptType::P2WSH => "p2wsh",
+        ScriptType::P2TR => "p2tr",
+    }
+}
+
+/// Address cache key (coin name, script type) of an address served for a
+/// registry chain; None for chains `verify_address` does not cover
+fn cache_key(kind: &ChainKind) -> Option<(&'static str, Option<&'static str>)> {
+    match kind {
+        ChainKind::Bitcoin { network, script_type } => {
+            Some((VerifyCoin::Bitcoin(*network).name(), Some(script_name(*script_type))))
+        }
+        ChainKind::Ethereum => Some((VerifyCoin::Ethereum.name(), None)),
+        ChainKind::Cosmos { hrp } if hrp == "cosmos" => Some((VerifyCoin::Cosmos.name(), None)),
+        ChainKind::Cosmos { .. } => None,
+    }
+}
+
+/// Remember an address served to the UI, so verifying it on the device later
+/// compares against what the user was shown
+pub(crate) async fn remember_served_address(
+    database: &Database,
+    wallet_sessions: &WalletSessions,
+    device_id: &str,
+    kind: &ChainKind,
+    address_n: &[u32],
+    address: &str,
+) {
+    let Some((coin, script)) = cache_key(kind) else {
+        return;
+    };
+    let wallet_fingerprint = active_wallet_fingerprint(wallet_sessions, device_id).await;
+    if wallet_fingerprint.is_empty() {
+        return;
+    }
+    let path = format_derivation_path(address_n);
+    if let Err(e) = database.cache_address(device_id, &wallet_fingerprint, &path, coin, script, address).await {
+        log::warn!("Could not cache the address served for {} {}: {}", device_id, path, e);
+    }
+}
+
+/// Bitcoin address at `address_n` (account/chain/index) derived from the
+/// account xpub cached for the wallet, if there is one
+async fn address_from_cached_xpub(
+    database: &Database,
+    device_id: &str,
+    wallet_fingerprint: &str,
+    network: Network,
+    address_n: &[u32],
+    script: ScriptType,
+) -> Option<String> {
+    let [account @ .., chain, index] = address_n else {
+        return None;
+    };
+    if account.len() != 3 || chain & HARDENED != 0 || index & HARDENED != 0 {
+        return None;
+    }
+    let script_type = UtxoScriptType::parse(script_name(script)).ok()?;
+    let coin = VerifyCoin::Bitcoin(network);
+    let xpub = database
+        .get_cached_pubkey(device_id, wallet_fingerprint, &format_derivation_path(account), coin.name(), Some(script_name(script)))
+        .await
+        .ok()
+        .flatten()?;
+    account_address(&xpub, *chain, *index, script_type, network)
+        .map_err(|e| log::warn!("Could not derive {} from the cached xpub: {}", format_derivation_path(address_n), e))
+        .ok()
+}
+
+/// Derive the address at `path` with the device showing it on screen.
+///
+/// Bitcoin takes a `script_type` (p2pkh, p2sh-p2wpkh, p2wpkh, ...), p2wpkh
+/// by default. Emits `verification:awaiting-device` before asking the device;
+/// the command returns once the user has dismissed the address.
+#[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
+pub async fn verify_address(
+    app: AppHandle,
+    device_id: String,
+    coin: String,
+    path: String,
+    script_type: Option<String>,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<VerifiedAddress, String> {
+    let coin = VerifyCoin::parse(&coin)?;
+    let address_n = parse_derivation_path(&path)?;
+    let path = format_derivation_path(&address_n);
+    let bitcoin_script = match (&coin, script_type) {
+        (VerifyCoin::Bitcoin(_), Some(name)) => name.parse::<ScriptType>().map_err(|e| e.to_string())?,
+        (_, None) => ScriptType::P2WPKH,
+        (_, Some(_)) => return Err(format!("{} addresses have no script type", coin.name())),
+    };
+    let script_name = matches!(coin, VerifyCoin::Bitcoin(_)).then(|| script_name(bitcoin_script));
+
+    super::emit_or_queue_event(&app, "verification:awaiting-device", serde_json::json!({
+        "deviceId": device_id,
+        "coin": coin.name(),
+        "path": path,
+    })).await?;
+
+    let address = request_queue().run_with_timeout(&device_id, "verify_address", VERIFY_TIMEOUT, || async {
+        let queue = vault_core::get_or_create_device_queue(&device_id, &queue_manager).await?;
+        let derived = match &coin {
+            VerifyCoin::Bitcoin(network) => {
+                btc_chain::get_bitcoin_address(&queue, &address_n, bitcoin_script, *network, true).await
+                    .map(|address| address.to_string())
+            }
+            VerifyCoin::Ethereum => {
+                eth_chain::get_ethereum_address(&queue, &address_n, true).await
+                    .map(|address| format!("0x{}", hex::encode(address.as_bytes())))
+            }
+            VerifyCoin::Cosmos => {
+                cosmos_chain::get_cosmos_address(&queue, &address_n, "cosmos", true).await
+                    .map(|account| account.to_string())
+            }
+        };
+        derived.map_err(|e| e.to_string())
+    }).await.map_err(|e| format!("Failed to verify the address: {}", e))?;
+
+    // Without a known wallet there is no cache entry to hold the address against
+    let wallet_fingerprint = active_wallet_fingerprint(&wallet_sessions, &device_id).await;
+    let mut cached_address = None;
+    if !wallet_fingerprint.is_empty() {
+        cached_address = database
+            .get_cached_address(&device_id, &wallet_fingerprint, &path, coin.name(), script_name)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        if let (None, VerifyCoin::Bitcoin(network)) = (&cached_address, &coin) {
+            cached_address = address_from_cached_xpub(
+                &database, &device_id, &wallet_fingerprint, *network, &address_n, bitcoin_script,
+            ).await;
+        }
+        if let Err(e) = database
+            .cache_address(&device_id, &wallet_fingerprint, &path, coin.name(), script_name, &address)
+            .await
+        {
+            log::warn!("Could not cache the verified address for {}: {}", device_id, e);
+        }
+    }
+
+    // EIP-55 checksums only change the case of an Ethereum address
+    let mismatch = cached_address.as_deref().is_some_and(|cached| match coin {
+        VerifyCoin::Ethereum => !cached.eq_ignore_ascii_case(&address),
+        _ => cached != address,
+    });
+    if mismatch {
+        log::warn!(
+            "🚨 Device {} derived {} at {} but {} was cached",
+            device_id, address, path, cached_address.as_deref().unwrap_or_default()
+        );
+    } else {
+        log::info!("✅ Verified {} address {} at {} on {}", coin.name(), address, path, device_id);
+    }
+
+    Ok(VerifiedAddress { address, cached_address, mismatch })
+}
@@ -0,0 +1,181 @@
+// commands/wallets.rs - Standard and passphrase (hidden) wallet sessions
+
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+use keepkey_db::{Database, ImportedWallet, KnownWallet};
+use vault_core::wallet_import::{self, ExportFormat, ImportResult};
+use vault_core::passphrase::Zeroizing;
+use vault_core::wallet_session::{self, ActiveWallet, WalletIdentification, WalletSessions};
+use super::DeviceQueueManager;
+
+/// Wallets seen on a device: fingerprint and nickname only, never passphrases
+#[tauri::command]
+#[specta::specta]
+pub async fn list_known_wallets(
+    device_id: String,
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<KnownWallet>, String> {
+    database.list_known_wallets(&device_id).await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Name a wallet so hidden wallets are recognisable in the switcher
+#[tauri::command]
+#[specta::specta]
+pub async fn set_wallet_nickname(
+    device_id: String,
+    wallet_fingerprint: String,
+    nickname: Option<String>,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    let nickname = nickname.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    let updated = database.set_wallet_nickname(&device_id, &wallet_fingerprint, nickname).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    if !updated {
+        return Err(format!("Unknown wallet {} on device {}", wallet_fingerprint, device_id));
+    }
+    Ok(())
+}
+
+/// The wallet currently open on a device, if it has been identified
+#[tauri::command]
+#[specta::specta]
+pub async fn get_active_wallet(
+    device_id: String,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<Option<ActiveWallet>, String> {
+    Ok(wallet_sessions.lock().await.get(&device_id).cloned())
+}
+
+/// Switch to the standard wallet (no passphrase) or a hidden wallet.
+///
+/// Emits `wallet:session-changed` so views reload portfolio data for the new scope.
+#[tauri::command]
+#[specta::specta]
+pub async fn switch_wallet_session(
+    app: AppHandle,
+    device_id: String,
+    passphrase: Option<String>,
+    database: State<'_, Arc<Database>>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    wallet_sessions: State<'_, WalletSessions>,
+) -> Result<ActiveWallet, String> {
+    let passphrase = Zeroizing::new(passphrase.unwrap_or_default());
+    let is_hidden = !passphrase.is_empty();
+    let identification = wallet_session::start_wallet_session(
+        &database,
+        &wallet_sessions,
+        &queue_manager,
+        &device_id,
+        passphrase,
+    ).await?;
+    match identification {
+        WalletIdentification::Opened(wallet) => {
+            wallet_opened(&app, &database, &device_id, &wallet).await?;
+            Ok(wallet)
+        }
+        // Clearing the session dropped the cached PIN
+        WalletIdentification::PinRequired(request) => {
+            super::pin::announce_pin_request(&app, &device_id, request, is_hidden).await?;
+            Err(format!("Device {} is locked; the wallet opens once its PIN is entered", device_id))
+        }
+    }
+}
+
+/// Announce a newly opened wallet and resume a frontload the device was
+/// unplugged during
+pub(crate) async fn wallet_opened(app: &AppHandle, database: &Database, device_id: &str, wallet: &ActiveWallet) -> Result<(), String> {
+    super::emit_or_queue_event(app, "wallet:session-changed", serde_json::json!({
+        "device_id": device_id,
+        "wallet_fingerprint": wallet.wallet_fingerprint,
+        "is_hidden": wallet.is_hidden,
+    })).await?;
+
+    if database.has_unfinished_frontload(device_id).await.unwrap_or(false) {
+        log::info!("📥 Resuming frontload of device {}", device_id);
+        super::device::frontload_device::spawn_frontload(app, device_id);
+    }
+    Ok(())
+}
+
+/// Identify the standard wallet of a device without clearing its session.
+/// A locked device's PIN matrix goes to the PIN flow, which opens the wallet
+/// once the PIN is entered; None until then.
+pub(crate) async fn identify_standard_wallet(app: &AppHandle, device_id: &str) -> Result<Option<ActiveWallet>, String> {
+    let database = app.state::<Arc<Database>>();
+    let wallet_sessions = app.state::<WalletSessions>();
+    let queue_manager = app.state::<DeviceQueueManager>();
+    match wallet_session::identify_standard_wallet(&database, &wallet_sessions, &queue_manager, device_id).await? {
+        WalletIdentification::Opened(wallet) => {
+            wallet_opened(app, &database, device_id, &wallet).await?;
+            Ok(Some(wallet))
+        }
+        WalletIdentification::PinRequired(request) => {
+            log::info!("🔐 Device {} is locked; asking for its PIN to identify its wallet", device_id);
+            super::pin::announce_pin_request(app, device_id, request, false).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Import watch-only accounts from another hardware wallet's export.
+///
+/// `path_or_json` is either a path to the export file or its contents.
+/// `source_format` is `trezor_suite`, `ledger_live`, `descriptors` or None to
+/// detect. Accounts that cannot be parsed are skipped and returned in `errors`.
+#[tauri::command]
+#[specta::specta]
+pub async fn import_external_wallet_export(
+    app: AppHandle,
+    path_or_json: String,
+    source_format: Option<String>,
+    database: State<'_, Arc<Database>>,
+) -> Result<ImportResult, String> {
+    let format = ExportFormat::parse(source_format.as_deref())?;
+    let trimmed = path_or_json.trim();
+    let looks_inline = trimmed.starts_with('{') || trimmed.starts_with('[') || trimmed.contains('\n');
+    let (text, source_name) = match std::path::Path::new(trimmed) {
+        path if !looks_inline && path.is_file() => {
+            let text = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+            (text, path.file_name().map(|n| n.to_string_lossy().into_owned()))
+        }
+        _ => (path_or_json.clone(), None),
+    };
+
+    let result = wallet_import::import_export(&database, &text, format, source_name).await?;
+    log::info!(
+        "📥 Imported {} watch-only accounts as {} ({} skipped)",
+        result.wallet.accounts.len(),
+        result.wallet.device_id,
+        result.errors.len()
+    );
+    let _ = database.log_activity(
+        "wallet",
+        &format!("Imported {} watch-only accounts from a {} export", result.wallet.accounts.len(), result.wallet.source_format),
+        serde_json::to_value(&result.errors).ok().as_ref(),
+    ).await;
+
+    super::emit_or_queue_event(&app, "portfolio:sync-requested", serde_json::json!({
+        "device_ids": [],
+        "imported_device_ids": [result.wallet.device_id],
+    })).await?;
+    Ok(result)
+}
+
+/// Watch-only wallets imported from other hardware wallets, with their accounts
+#[tauri::command]
+#[specta::specta]
+pub async fn list_imported_wallets(database: State<'_, Arc<Database>>) -> Result<Vec<ImportedWallet>, String> {
+    database.list_imported_wallets().await.map_err(|e| format!("Database error: {}", e))
+}
+
+/// Remove an imported wallet with its accounts and cached portfolio data
+#[tauri::command]
+#[specta::specta]
+pub async fn remove_imported_wallet(id: i64, database: State<'_, Arc<Database>>) -> Result<(), String> {
+    let removed = database.remove_imported_wallet(id).await.map_err(|e| format!("Database error: {}", e))?;
+    if !removed {
+        return Err(format!("Unknown imported wallet {}", id));
+    }
+    log::info!("🗑️ Removed imported wallet {}", id);
+    Ok(())
+}
@@ -0,0 +1,75 @@
+// commands/warmup.rs - Warm idle device queues when the user comes back
+//
+// Planning and metrics live in vault_core::warmup. The queue has a single
+// sequential worker, so a warm-up is just two cheap requests queued ahead of
+// whatever the user does next: a Ping without button protection and a
+// features read. Neither shows anything on the device.
+
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+use keepkey_db::Database;
+use keepkey_rust::messages::{self, Message};
+use vault_core::power::PowerMonitor;
+use vault_core::warmup::{queue_warmup, WarmupDecision};
+use super::device::get_or_create_device_queue;
+use super::DeviceQueueManager;
+
+/// Warm every connected device that has been idle; called when the main
+/// window gains focus or the tray icon is clicked
+pub fn warm_up_devices(app: &AppHandle) {
+    // Focus events can arrive before setup has managed state
+    let (Some(monitor), Some(_), Some(_)) = (
+        app.try_state::<Arc<PowerMonitor>>(),
+        app.try_state::<Arc<Database>>(),
+        app.try_state::<DeviceQueueManager>(),
+    ) else {
+        return;
+    };
+    let power = monitor.status();
+    let warmup = queue_warmup();
+    let focus = warmup.begin_focus();
+
+    for device_id in super::tray::connected_device_ids() {
+        if vault_core::observer::observer_registry().check_managed(&device_id).is_err() {
+            continue;
+        }
+        match warmup.plan(&device_id, focus, &power, Instant::now()) {
+            WarmupDecision::Warm => {}
+            WarmupDecision::PowerSaver => {
+                log::debug!("🔋 Skipping queue warm-up for {}: power saver", device_id);
+                continue;
+            }
+            _ => continue,
+        }
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            match warm_up_device(&app, &device_id).await {
+                Ok(()) => queue_warmup().finish_warmup(&device_id, Instant::now()),
+                Err(e) => log::debug!("Queue warm-up for {} failed: {}", device_id, e),
+            }
+        });
+    }
+}
+
+async fn warm_up_device(app: &AppHandle, device_id: &str) -> Result<(), String> {
+    let database = app.state::<Arc<Database>>();
+    let queue_manager = app.state::<DeviceQueueManager>();
+    let started = Instant::now();
+
+    let queue = get_or_create_device_queue(device_id, &queue_manager).await?;
+    let ping = Message::Ping(messages::Ping {
+        button_protection: Some(false),
+        ..Default::default()
+    });
+    queue.send_raw(ping, true).await.map_err(|e| format!("Ping failed: {}", e))?;
+
+    let features = queue.get_features().await.map_err(|e| format!("Features failed: {}", e))?;
+    let features = vault_core::convert_features_to_device_features(features);
+    let features_json = serde_json::to_string(&features).map_err(|e| e.to_string())?;
+    database.update_device_features(device_id, &features_json).await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    log::info!("🔥 Warmed device queue for {} in {}ms", device_id, started.elapsed().as_millis());
+    Ok(())
+}
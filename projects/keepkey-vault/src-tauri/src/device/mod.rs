@@ -1,4 +1,5 @@
 // device/mod.rs - Device-related operations module
 
+pub mod observer;
 pub mod queue;
 pub mod updates; 
\ No newline at end of file
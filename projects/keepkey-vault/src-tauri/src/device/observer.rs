@@ -0,0 +1,119 @@
+// observer.rs - Observer mode wiring: status events and the re-claim loop
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{AppHandle, Manager};
+use keepkey_db::Database;
+use keepkey_rust::claim::ClaimStatus;
+use keepkey_rust::device_queue::DeviceQueueFactory;
+use keepkey_rust::friendly_usb::FriendlyUsbDevice;
+use vault_core::observer::{self, ManagementMode, ReclaimOutcome, RECLAIM_INTERVAL};
+use crate::commands::{self, DeviceQueueManager};
+
+/// Devices with a re-claim loop running, so a repeated conflict does not start a second one
+fn watchers() -> &'static Mutex<HashSet<String>> {
+    static WATCHERS: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    WATCHERS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Probe whether the device can be claimed. A device this vault already has
+/// a worker for is claimed by that worker, and opening a second transport
+/// would fight it, so it is reported available without a probe. The queue
+/// manager stays locked during the probe so no worker starts meanwhile.
+async fn probe_claim(queue_manager: &DeviceQueueManager, device: &FriendlyUsbDevice) -> ClaimStatus {
+    let workers = queue_manager.lock().await;
+    if workers.contains_key(&device.unique_id) {
+        return ClaimStatus::Available;
+    }
+    let device = device.clone();
+    let status = tokio::task::spawn_blocking(move || DeviceQueueFactory::probe_claim(&device))
+        .await
+        .unwrap_or_else(|e| ClaimStatus::Failed { detail: e.to_string() });
+    drop(workers);
+    status
+}
+
+/// Probe a freshly connected device; returns true if another process holds it
+/// and it was put in observer mode
+pub async fn check_claim_on_connect(app: &AppHandle, device: &FriendlyUsbDevice) -> bool {
+    let queue_manager = app.state::<DeviceQueueManager>().inner().clone();
+    match probe_claim(&queue_manager, device).await {
+        ClaimStatus::HeldElsewhere { detail } => {
+            observer::observer_registry().mark_observed(&device.unique_id, &detail, observer::unix_now());
+            enter_observer_mode(app, device.clone()).await;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Announce observer mode for a device already marked in the registry and
+/// start polling for the other holder to release it
+pub async fn enter_observer_mode(app: &AppHandle, device: FriendlyUsbDevice) {
+    let device_id = device.unique_id.clone();
+    let mode = observer::observer_registry().mode(&device_id);
+    let ManagementMode::Observed { detail, .. } = &mode else {
+        return;
+    };
+    log::warn!("👀 Device {} is held by another application, observing only: {}", device_id, detail);
+
+    if let Err(e) = commands::emit_or_queue_event(app, "device:status-changed", serde_json::json!({
+        "device_id": device_id,
+        "management": mode,
+    })).await {
+        log::error!("Failed to emit observer status for {}: {}", device_id, e);
+    }
+
+    if !watchers().lock().unwrap().insert(device_id.clone()) {
+        return;
+    }
+    let app = app.clone();
+    let queue_manager = app.state::<DeviceQueueManager>().inner().clone();
+    tokio::spawn(async move {
+        let outcome = observer::reclaim_until_released(
+            observer::observer_registry(),
+            &device_id,
+            RECLAIM_INTERVAL,
+            || {
+                let (queue_manager, device) = (queue_manager.clone(), device.clone());
+                async move { probe_claim(&queue_manager, &device).await }
+            },
+        )
+        .await;
+        watchers().lock().unwrap().remove(&device_id);
+
+        if outcome == ReclaimOutcome::Restored {
+            log::info!("✅ Device {} was released by the other application, managing it again", device_id);
+            restore_management(&app, &device_id).await;
+        }
+    });
+}
+
+/// Refresh the cached features and tell the UI the device is fully managed again
+async fn restore_management(app: &AppHandle, device_id: &str) {
+    let database = app.state::<Arc<Database>>().inner().clone();
+    let queue_manager = app.state::<DeviceQueueManager>().inner().clone();
+    if let Err(e) = vault_core::refresh_stored_features(&database, device_id, &queue_manager).await {
+        log::warn!("Could not refresh features for {} after re-claim: {}", device_id, e);
+    }
+
+    let _ = database.log_activity("device", &format!("Device {} released by another application", device_id), None).await;
+    if let Err(e) = commands::emit_or_queue_event(app, "device:management-restored", serde_json::json!({
+        "device_id": device_id,
+    })).await {
+        log::error!("Failed to emit management-restored for {}: {}", device_id, e);
+    }
+    if let Err(e) = commands::emit_or_queue_event(app, "device:status-changed", serde_json::json!({
+        "device_id": device_id,
+        "management": ManagementMode::Managed,
+    })).await {
+        log::error!("Failed to emit managed status for {}: {}", device_id, e);
+    }
+}
+
+/// Whether the vault manages a device or only shows its cached state
+#[tauri::command]
+#[specta::specta]
+pub async fn get_device_management_mode(device_id: String) -> Result<ManagementMode, String> {
+    Ok(observer::observer_registry().mode(&device_id))
+}
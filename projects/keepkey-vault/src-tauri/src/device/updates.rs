@@ -1,19 +1,41 @@
-use tauri::State;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use keepkey_db::{Database, FirmwareUpdateRecord};
+use vault_core::app_lock::AppLock;
+use vault_core::confirmation::{Confirmations, HighRiskOperation};
+use vault_core::firmware_catalog::{self, ImageKind, ReleasesCatalog};
+use vault_core::device_flow::{DeviceFlow, DeviceFlowState};
+use vault_core::firmware_downgrade::{self, UpdateDirection};
+use vault_core::firmware_flash::{self, FlashError, FlashPhase, FLASH_PROGRESS_EVENT};
+use vault_core::maintenance::MaintenanceController;
 use crate::commands::DeviceQueueManager;
+use crate::commands::confirmation::require_confirmation;
 use std::fs;
 use std::path::PathBuf;
-use semver::Version;
+use keepkey_rust::version::{parse_version, ParsedVersion};
 use crate::commands::logging::{log_device_request, log_device_response};
 use serde_json;
 
 /// Update device bootloader using the device queue (like v5)
+///
+/// A target older than the installed bootloader is always refused with a
+/// `BootloaderDowngradeBlocked` error; there is no override.
 #[tauri::command]
+#[specta::specta]
 pub async fn update_device_bootloader(
+    app: AppHandle,
     device_id: String,
     target_version: String,
+    database: State<'_, Arc<Database>>,
     queue_manager: State<'_, DeviceQueueManager>,
+    app_lock: State<'_, Arc<AppLock>>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
 ) -> Result<bool, String> {
     log::info!("🔄 Starting bootloader update for device {}: target version {}", device_id, target_version);
+    // The app lock must not engage while the device is being flashed
+    let _flash_guard = app_lock.flash_guard();
+    // Maintenance waits for the flash rather than starting underneath it
+    let _operation = maintenance.device_operation("update_device_bootloader").map_err(|e| e.to_json_string())?;
     
     let request_id = format!("bootloader_update_{}", std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -32,8 +54,31 @@ pub async fn update_device_bootloader(
     }
     
     // Validate target version
-    let _target_semver = Version::parse(&target_version)
-        .map_err(|e| format!("Invalid target bootloader version: {}", e))?;
+    if let ParsedVersion::Unparseable(version) = parse_version(&target_version) {
+        return Err(format!("Invalid target bootloader version: {}", version));
+    }
+
+    // Old bootloaders have issues no firmware can fix, so they are never downgraded
+    let installed_version = vault_core::features::get_device_features(&device_id, &queue_manager).await
+        .map_err(|e| format!("Could not read the installed bootloader version: {}", e))?
+        .bootloader_version;
+    if let Err(e) = firmware_downgrade::check_update(
+        &firmware_downgrade::bundled_catalog(),
+        ImageKind::Bootloader,
+        installed_version.as_deref(),
+        &target_version,
+        false,
+    ) {
+        log::warn!("🚫 {}", e);
+        let response_data = serde_json::json!({
+            "error": e,
+            "operation": "update_device_bootloader"
+        });
+        if let Err(log_err) = log_device_response(&device_id, &request_id, false, &response_data, Some(&e.to_string())).await {
+            eprintln!("Failed to log bootloader update error response: {}", log_err);
+        }
+        return Err(e.to_json_string());
+    }
     
     // Load the bootloader binary from the firmware directory (bundled with app)
     let bootloader_filename = format!("bl_v{}", target_version);
@@ -113,56 +158,27 @@ pub async fn update_device_bootloader(
     
     println!("📦 Loaded bootloader binary: {} bytes", bootloader_bytes.len());
     
-    // Get or create device queue handle
-    // Always remove any existing handle for this device to ensure we get a fresh one
-    // This is important for bootloader updates where the device may have disconnected/reconnected
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        
-        // Remove existing handle to force recreation with fresh transport
-        if manager.get(&device_id).is_some() {
-            println!("🔄 [DEBUG] Removing existing device queue handle to force recreation after reconnection");
-            manager.remove(&device_id);
-        }
-        
-        // Find the device by ID
-            let devices = keepkey_rust::features::list_connected_devices();
+    // Drop any existing handle first: the device may have disconnected and
+    // reconnected on its way into the bootloader, so the queue needs a fresh
+    // transport
+    if queue_manager.lock().await.remove(&device_id).is_some() {
+        log::info!("🔄 Recreating the device queue for {} after reconnection", device_id);
+    }
+    let queue_handle = match vault_core::get_or_create_device_queue(&device_id, &queue_manager).await {
+        Ok(handle) => handle,
+        Err(error) => {
+            // Log the error response
+            let response_data = serde_json::json!({
+                "error": error,
+                "operation": "update_device_bootloader"
+            });
             
-            // Debug: Log all available devices and the device we're looking for
-            println!("🔍 [DEBUG] Looking for device ID: '{}'", device_id);
-            println!("🔍 [DEBUG] Available devices:");
-            for (i, device) in devices.iter().enumerate() {
-                println!("  {}. device_id: '{}', name: '{}', vid: 0x{:04x}, pid: 0x{:04x}", 
-                         i + 1, device.unique_id, device.name, device.vid, device.pid);
+            if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
+                eprintln!("Failed to log bootloader update error response: {}", e);
             }
             
-            let device_info = devices
-                .iter()
-                .find(|d| d.unique_id == device_id);
-                
-            match device_info {
-                Some(device_info) => {
-                    // Spawn a new device worker
-                    let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(device_id.clone(), device_info.clone());
-                    manager.insert(device_id.clone(), handle.clone());
-                    handle
-                }
-                None => {
-                    let error = format!("Device {} not found", device_id);
-                    
-                    // Log the error response
-                    let response_data = serde_json::json!({
-                        "error": error,
-                        "operation": "update_device_bootloader"
-                    });
-                    
-                    if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
-                        eprintln!("Failed to log bootloader update error response: {}", e);
-                    }
-                    
-                    return Err(error);
-                }
-            }
+            return Err(error);
+        }
     };
     
     println!("⚠️  IMPORTANT: Check your KeepKey device screen!");
@@ -185,7 +201,8 @@ pub async fn update_device_bootloader(
             if let Err(e) = log_device_response(&device_id, &request_id, true, &response_data, None).await {
                 eprintln!("Failed to log bootloader update success response: {}", e);
             }
-            
+
+            record_update(&app, &database, &device_id, ImageKind::Bootloader, installed_version.as_deref(), &target_version, false, None).await;
             Ok(success)
         }
         Err(e) => {
@@ -201,20 +218,52 @@ pub async fn update_device_bootloader(
             if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error_msg)).await {
                 eprintln!("Failed to log bootloader update error response: {}", e);
             }
-            
+
+            record_update(&app, &database, &device_id, ImageKind::Bootloader, installed_version.as_deref(), &target_version, false, Some(&error_msg)).await;
             Err(format!("Bootloader update failed: {}", error_msg))
         }
     }
 }
 
 /// Update device firmware using the device queue (like v5)
+///
+/// The device must be in bootloader mode, and the image for `target_version`
+/// must hash to that version under `hashes.firmware` in releases.json. The
+/// flash reports `firmware:update-progress` events. On success the device is
+/// tracked in a `FirmwareUpdate` flow while it reboots.
+///
+/// Errors are JSON `FlashError`s with `retryable` and `do_not_unplug` flags.
+/// `do_not_unplug` means the transfer failed after the erase and the device
+/// is waiting in its bootloader for another attempt.
+///
+/// A target older than the installed firmware is refused with a
+/// `DowngradeBlocked` error listing the security releases it would roll back.
+/// Downgrading anyway needs `allow_downgrade` and a confirmation from
+/// `request_confirmation("downgrade_firmware", ...)`; the downgrade is then
+/// recorded in the update history and activity log and raises
+/// `device:firmware-downgraded`.
 #[tauri::command]
+#[specta::specta]
+#[allow(clippy::too_many_arguments)]
 pub async fn update_device_firmware(
+    app: AppHandle,
     device_id: String,
     target_version: String,
+    allow_downgrade: Option<bool>,
+    confirmation_token: Option<String>,
+    confirmation_phrase: Option<String>,
+    database: State<'_, Arc<Database>>,
     queue_manager: State<'_, DeviceQueueManager>,
+    confirmations: State<'_, Confirmations>,
+    device_flows: State<'_, Arc<DeviceFlowState>>,
+    app_lock: State<'_, Arc<AppLock>>,
+    maintenance: State<'_, Arc<MaintenanceController>>,
 ) -> Result<bool, String> {
     log::info!("🔄 Starting firmware update for device {}: target version {}", device_id, target_version);
+    // The app lock must not engage while the device is being flashed
+    let _flash_guard = app_lock.flash_guard();
+    // Maintenance waits for the flash rather than starting underneath it
+    let _operation = maintenance.device_operation("update_device_firmware").map_err(|e| e.to_json_string())?;
     
     let request_id = format!("firmware_update_{}", std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -233,11 +282,61 @@ pub async fn update_device_firmware(
     }
     
     // Validate target version
-    let _target_semver = Version::parse(&target_version)
-        .map_err(|e| format!("Invalid target firmware version: {}", e))?;
+    if let ParsedVersion::Unparseable(version) = parse_version(&target_version) {
+        return Err(format!("Invalid target firmware version: {}", version));
+    }
+
+    let features = match vault_core::features::get_device_features(&device_id, &queue_manager).await {
+        Ok(features) => features,
+        Err(e) => {
+            let error = FlashError::DeviceUnavailable { message: format!("Could not read the installed firmware version: {}", e) };
+            return Err(firmware_update_failed(&device_id, &request_id, error).await);
+        }
+    };
+    // Checked before the downgrade confirmation so a token is not spent on a device that cannot be flashed
+    if !features.bootloader_mode {
+        return Err(firmware_update_failed(&device_id, &request_id, FlashError::NotInBootloader).await);
+    }
+    let installed_version = firmware_downgrade::installed_firmware_version(&features);
+    let catalog = firmware_downgrade::bundled_catalog();
+    let direction = match firmware_downgrade::check_update(
+        &catalog,
+        ImageKind::Firmware,
+        installed_version.as_deref(),
+        &target_version,
+        allow_downgrade.unwrap_or(false),
+    ) {
+        Ok(direction) => direction,
+        Err(e) => {
+            log::warn!("🚫 {}", e);
+            let response_data = serde_json::json!({
+                "error": e,
+                "operation": "update_device_firmware"
+            });
+            if let Err(log_err) = log_device_response(&device_id, &request_id, false, &response_data, Some(&e.to_string())).await {
+                eprintln!("Failed to log firmware update error response: {}", log_err);
+            }
+            return Err(e.to_json_string());
+        }
+    };
+    let downgrade = direction == UpdateDirection::Downgrade;
+    if downgrade {
+        let (Some(token), Some(phrase)) = (&confirmation_token, &confirmation_phrase) else {
+            return Err("Downgrading firmware requires a confirmation from request_confirmation".to_string());
+        };
+        require_confirmation(
+            &database,
+            &confirmations,
+            HighRiskOperation::DowngradeFirmware,
+            Some(&device_id),
+            token,
+            phrase,
+        ).await?;
+        log::warn!("⚠️ Downgrading firmware on {} from {:?} to {}", device_id, installed_version, target_version);
+    }
     
     // Load the firmware binary from the firmware directory (bundled with app)
-    let firmware_filename = format!("v{}", target_version);
+    let firmware_filename = format!("v{}", target_version.trim_start_matches('v'));
     
     // Debug: Log current working directory and environment
     let cwd = std::env::current_dir().unwrap_or_default();
@@ -282,72 +381,45 @@ pub async fn update_device_firmware(
     
     let firmware_path = possible_firmware_paths.iter().find(|path| path.exists()).cloned();
     
-    let firmware_bytes = if let Some(path) = firmware_path {
-        println!("📂 Loading firmware from: {}", path.display());
-        fs::read(&path)
-            .map_err(|e| format!("Failed to read firmware file {}: {}", path.display(), e))?
-    } else {
-        let error_msg = format!(
-            "Firmware file not found: v{}/firmware.keepkey.bin in any firmware directory. Target version was: {}",
-            target_version,
-            target_version
-        );
-        
-        // Log the error response
-        let response_data = serde_json::json!({
-            "error": error_msg,
-            "operation": "update_device_firmware"
-        });
-        
-        if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error_msg)).await {
-            eprintln!("Failed to log firmware update error response: {}", e);
+    let loaded = match firmware_path {
+        Some(path) => {
+            println!("📂 Loading firmware from: {}", path.display());
+            fs::read(&path).map_err(|e| format!("Failed to read firmware file {}: {}", path.display(), e))
         }
-        
-        return Err(error_msg);
+        None => Err(format!(
+            "Firmware file not found: {}/firmware.keepkey.bin in any firmware directory",
+            firmware_filename
+        )),
+    };
+    let firmware_bytes = match loaded {
+        Ok(bytes) => bytes,
+        Err(message) => return Err(firmware_update_failed(&device_id, &request_id, FlashError::ImageUnavailable { message }).await),
     };
     
     println!("📦 Loaded firmware binary: {} bytes", firmware_bytes.len());
+    let total_bytes = firmware_bytes.len();
+
+    // Only a published release reaches the device
+    emit_flash_progress(&app, &device_id, FlashPhase::Verifying, total_bytes);
+    match firmware_flash::verify_release_image(&catalog, ImageKind::Firmware, &target_version, &firmware_bytes) {
+        Ok(hash) => log::info!("🔏 Firmware image for {} matches releases.json ({})", target_version, hash),
+        Err(e) => {
+            log::error!("❌ Refusing to flash {}: {}", target_version, e);
+            return Err(firmware_update_failed(&device_id, &request_id, e).await);
+        }
+    }
     
     // Get or create device queue handle
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        
-        if let Some(handle) = manager.get(&device_id) {
-            handle.clone()
-        } else {
-            // Find the device by ID
-            let devices = keepkey_rust::features::list_connected_devices();
-            let device_info = devices
-                .iter()
-                .find(|d| d.unique_id == device_id);
-                
-            match device_info {
-                Some(device_info) => {
-                    // Spawn a new device worker
-                    let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(device_id.clone(), device_info.clone());
-                    manager.insert(device_id.clone(), handle.clone());
-                    handle
-                }
-                None => {
-                    let error = format!("Device {} not found", device_id);
-                    
-                    // Log the error response
-                    let response_data = serde_json::json!({
-                        "error": error,
-                        "operation": "update_device_firmware"
-                    });
-                    
-                    if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
-                        eprintln!("Failed to log firmware update error response: {}", e);
-                    }
-                    
-                    return Err(error);
-                }
-            }
+    let queue_handle = match vault_core::get_or_create_device_queue(&device_id, &queue_manager).await {
+        Ok(handle) => handle,
+        Err(message) => {
+            let error = FlashError::DeviceUnavailable { message };
+            return Err(firmware_update_failed(&device_id, &request_id, error).await);
         }
     };
     
     // Perform the firmware update through the queue
+    emit_flash_progress(&app, &device_id, FlashPhase::Writing, total_bytes);
     match queue_handle.update_firmware(target_version.clone(), firmware_bytes).await {
         Ok(success) => {
             println!("✅ Firmware update successful for device {}", device_id);
@@ -362,30 +434,116 @@ pub async fn update_device_firmware(
             if let Err(e) = log_device_response(&device_id, &request_id, true, &response_data, None).await {
                 eprintln!("Failed to log firmware update success response: {}", e);
             }
-            
+
+            // The device reboots into the new firmware and re-enumerates
+            device_flows.begin_flow(&device_id, DeviceFlow::FirmwareUpdate);
+            emit_flash_progress(&app, &device_id, FlashPhase::Complete, total_bytes);
+            record_update(&app, &database, &device_id, ImageKind::Firmware, installed_version.as_deref(), &target_version, downgrade, None).await;
             Ok(success)
         }
         Err(e) => {
             let error_msg = e.to_string();
             println!("❌ Firmware update failed for device {}: {}", device_id, error_msg);
-            
-            // Log the error response
-            let response_data = serde_json::json!({
-                "error": error_msg,
-                "operation": "update_device_firmware"
-            });
-            
-            if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error_msg)).await {
-                eprintln!("Failed to log firmware update error response: {}", e);
+            record_update(&app, &database, &device_id, ImageKind::Firmware, installed_version.as_deref(), &target_version, downgrade, Some(&error_msg)).await;
+            let error = firmware_flash::classify_transfer_error(&error_msg);
+            if !error.retryable() {
+                log::error!("⚠️ Device {} was erased but not rewritten; it must stay connected for a retry", device_id);
             }
-            
-            Err(format!("Firmware update failed: {}", error_msg))
+            Err(firmware_update_failed(&device_id, &request_id, error).await)
+        }
+    }
+}
+
+/// Log a failed firmware update and encode its error for the frontend
+async fn firmware_update_failed(device_id: &str, request_id: &str, error: FlashError) -> String {
+    let message = error.to_string();
+    let response_data = serde_json::json!({
+        "error": error,
+        "retryable": error.retryable(),
+        "operation": "update_device_firmware"
+    });
+    if let Err(e) = log_device_response(device_id, request_id, false, &response_data, Some(&message)).await {
+        eprintln!("Failed to log firmware update error response: {}", e);
+    }
+    error.to_json_string()
+}
+
+fn emit_flash_progress(app: &AppHandle, device_id: &str, phase: FlashPhase, total_bytes: usize) {
+    if let Err(e) = crate::commands::events::emit_event(app, FLASH_PROGRESS_EVENT, serde_json::json!({
+        "device_id": device_id,
+        "phase": phase,
+        "percent": phase.percent(),
+        "total_bytes": total_bytes,
+    })) {
+        log::warn!("Failed to emit firmware update progress: {}", e);
+    }
+}
+
+/// Firmware and bootloader flashes, newest first, optionally for one device
+#[tauri::command]
+#[specta::specta]
+pub async fn get_firmware_update_history(
+    device_id: Option<String>,
+    limit: Option<u32>,
+    database: State<'_, Arc<Database>>,
+) -> Result<Vec<FirmwareUpdateRecord>, String> {
+    database.get_firmware_updates(device_id.as_deref(), limit.unwrap_or(50) as usize).await
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+/// Latest firmware and bootloader releases with their hashes, the known
+/// release hashes and the security notes, from the bundled releases.json
+#[tauri::command]
+#[specta::specta]
+pub async fn get_firmware_catalog() -> Result<ReleasesCatalog, String> {
+    firmware_catalog::bundled_releases().cloned()
+}
+
+/// Store a finished flash in the update history. Downgrades are also written
+/// to the activity log, and a completed one raises a warning notification.
+#[allow(clippy::too_many_arguments)]
+async fn record_update(
+    app: &AppHandle,
+    database: &Database,
+    device_id: &str,
+    kind: ImageKind,
+    from_version: Option<&str>,
+    to_version: &str,
+    downgrade: bool,
+    error: Option<&str>,
+) {
+    if let Err(e) = database.record_firmware_update(device_id, kind.key(), from_version, to_version, downgrade, error).await {
+        log::warn!("Failed to record {} update in history: {}", kind.key(), e);
+    }
+    if !downgrade {
+        return;
+    }
+
+    let from = from_version.unwrap_or("unknown");
+    let message = match error {
+        None => format!("Downgraded firmware on {} from {} to {}", device_id, from, to_version),
+        Some(e) => format!("Firmware downgrade on {} from {} to {} failed: {}", device_id, from, to_version, e),
+    };
+    let details = serde_json::json!({
+        "severity": "warning",
+        "device_id": device_id,
+        "from_version": from_version,
+        "to_version": to_version,
+        "succeeded": error.is_none(),
+    });
+    if let Err(e) = database.log_activity("firmware", &message, Some(&details)).await {
+        log::warn!("Failed to log firmware downgrade: {}", e);
+    }
+    if error.is_none() {
+        if let Err(e) = crate::commands::emit_or_queue_event(app, "device:firmware-downgraded", details).await {
+            log::error!("Failed to emit firmware downgrade warning: {}", e);
         }
     }
 }
 
 /// Resolve a blocking action (placeholder - v5 doesn't have this concept)
 #[tauri::command]
+#[specta::specta]
 pub async fn resolve_blocking_action(
     device_id: String,
     action_type: String, // Change from BlockingActionType to String
@@ -1,16 +1,22 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
+#[specta::specta]
 fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+mod bindings;
 mod commands;
 mod device;
+mod scheduler;
+mod startup;
+mod tray;
 
 use std::sync::Arc;
 use tauri::{Manager};
 use keepkey_db::Database;
 use keepkey_rust;
+use keepkey_rust::device_watcher::DeviceEvent;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -21,135 +27,159 @@ pub fn run() {
     
     log::info!("🚀 KeepKey Vault starting up...");
 
+    let invoke_handler = bindings::builder().invoke_handler();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_sql::Builder::default().build())
         .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
             log::info!("🔧 Setting up KeepKey Vault application...");
-            
-            // Initialize database
-            log::info!("🗄️ Initializing database...");
-            let database = tauri::async_runtime::block_on(async {
-                Database::new().await
-            }).map_err(|e| {
-                log::error!("Failed to initialize database: {}", e);
-                e
-            })?;
-            
-            app.manage(Arc::new(database));
-            
+
+            // Claim the single-instance lock so kkvault-cli never fights us over USB
+            let instance_lock = vault_core::InstanceLock::acquire(vault_core::instance_lock::OWNER_VAULT)
+                .map_err(|holder| {
+                    log::error!("❌ Another process ({} pid {}) holds the vault lock", holder.owner, holder.pid);
+                    format!("KeepKey devices are in use by {} (pid {})", holder.owner, holder.pid)
+                })?;
+            app.manage(instance_lock);
+
             // Initialize device queue manager (like v5)
-            let device_queue_manager = Arc::new(tokio::sync::Mutex::new(
-                std::collections::HashMap::<String, keepkey_rust::device_queue::DeviceQueueHandle>::new()
-            ));
+            let device_queue_manager: commands::DeviceQueueManager = vault_core::queue::new_queue_manager();
             app.manage(device_queue_manager);
 
-            // Initialize USB management system for connect/disconnect events
-            log::info!("🔌 Initializing USB device management...");
-            
-            // Use the USB manager from keepkey_rust to get proper event handling
-            let app_handle = app.handle().clone();
-            
-            // Get device queue manager and database to pass to USB monitoring
-            let device_queue_manager = app.state::<commands::DeviceQueueManager>().inner().clone();
-            let database = app.state::<Arc<Database>>().inner().clone();
-            
-            // Start USB monitoring in background
-            tauri::async_runtime::spawn(async move {
-                // Initialize the USB monitoring with proper event emission
-                if let Err(e) = start_usb_monitoring(app_handle, device_queue_manager, database).await {
-                    log::error!("❌ Failed to start USB monitoring: {}", e);
-                } else {
-                    log::info!("✅ USB monitoring started successfully");
-                }
-            });
+            // Active passphrase wallet per device; portfolio queries are scoped to it
+            app.manage(vault_core::wallet_session::new_wallet_sessions());
+
+            // Over-limit signing requests waiting on an in-app confirmation
+            app.manage(vault_core::signing_origin::new_limit_confirmations());
+
+            // Typed-phrase confirmation tokens for wipe, PIN removal and forget
+            app.manage(vault_core::confirmation::new_confirmations());
+
+            // Devices mid PIN change/recovery; they may re-enumerate before finishing
+            app.manage(Arc::new(vault_core::device_flow::DeviceFlowState::new()));
+
+            // AC/battery state; background loops stretch their intervals on battery
+            app.manage(commands::power::new_power_monitor());
+            // Measured host clock offset for timestamped transactions
+            app.manage(commands::clock::new_clock_state());
+            // Last host environment probe, shown during onboarding
+            app.manage(commands::environment::new_environment_report());
+            // Inactivity lock; checked by the invoke handler below before every command
+            app.manage(commands::app_lock::new_app_lock());
+            // Maintenance mode for heavy database operations; also checked by the invoke handler
+            app.manage(commands::maintenance::new_maintenance_controller(app.handle().clone()));
+            app.manage(commands::maintenance::new_manual_maintenance());
+            // Active API pairing code
+            app.manage(commands::api::new_api_pairing());
+
+            // Database, USB monitoring and background jobs, in dependency order
+            app.manage(startup::new_startup_report());
+            startup::run_startup(app.handle())?;
 
             log::info!("✅ KeepKey Vault setup completed");
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
-            greet,
-            // Device commands
-            commands::device::get_features::get_features,
-            commands::device::get_connected_devices::get_connected_devices,
-            commands::device::get_device_status::get_device_status,
-            commands::device::check_device_bootloader::check_device_bootloader,
-            commands::device::get_devices_needing_setup::get_devices_needing_setup,
-            // Update commands  
-            device::updates::update_device_bootloader,
-            device::updates::update_device_firmware,
-            // Event and config commands
-            commands::events::frontend_ready,
-            commands::config::is_first_time_install,
-            commands::config::is_onboarded,
-            commands::config::set_onboarding_completed,
-            commands::config::debug_onboarding_state,
-            commands::config::get_preference,
-            commands::config::set_preference,
-            // Legacy commands (TODO: move to appropriate modules)
-            register_device,
-            get_device_registry,
-            get_device_from_registry,
-            update_device_setup_step,
-            mark_device_setup_complete,
-            device_needs_setup,
-            get_incomplete_setup_devices,
-            reset_device_setup,
-            get_device_eth_address,
-        ])
+        // A (re)loading page has no listeners until it signals frontend_ready
+        .on_page_load(|webview, payload| {
+            if payload.event() == tauri::webview::PageLoadEvent::Started {
+                commands::events::reset_window(webview.label());
+            }
+        })
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::Focused(focused) => {
+                commands::power::note_window_focus(window.app_handle(), *focused);
+                if *focused {
+                    commands::warmup::warm_up_devices(window.app_handle());
+                }
+            }
+            // Closed windows stop receiving (and queueing) events
+            tauri::WindowEvent::Destroyed => commands::events::forget_window(window.label()),
+            _ => {}
+        })
+        .invoke_handler(move |invoke| {
+            // While the app is locked only the exempt commands reach their handlers
+            if let Some(lock) = invoke.message.webview().try_state::<Arc<vault_core::app_lock::AppLock>>() {
+                if let Err(e) = lock.check_command(invoke.message.command()) {
+                    invoke.resolver.reject(e.to_json_string());
+                    return true;
+                }
+            }
+            // During maintenance only the exempt commands run; heavy operations queue
+            if let Some(maintenance) = invoke.message.webview().try_state::<Arc<vault_core::maintenance::MaintenanceController>>() {
+                if let Err(e) = maintenance.check_command(invoke.message.command()) {
+                    invoke.resolver.reject(e.to_json_string());
+                    return true;
+                }
+            }
+            invoke_handler(invoke)
+        })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
-// Legacy command stubs that need to be moved to proper modules
-#[tauri::command]
-async fn register_device() -> Result<(), String> { Ok(()) }
-#[tauri::command]
-async fn get_device_registry() -> Result<Vec<String>, String> { Ok(vec![]) }
-#[tauri::command]
-async fn get_device_from_registry() -> Result<Option<String>, String> { Ok(None) }
-#[tauri::command]
-async fn update_device_setup_step() -> Result<(), String> { Ok(()) }
-#[tauri::command]
-async fn mark_device_setup_complete() -> Result<(), String> { Ok(()) }
-#[tauri::command]
-async fn device_needs_setup() -> Result<bool, String> { Ok(false) }
-#[tauri::command]
-async fn get_incomplete_setup_devices() -> Result<Vec<String>, String> { Ok(vec![]) }
-#[tauri::command]
-async fn reset_device_setup() -> Result<(), String> { Ok(()) }
-#[tauri::command]
-async fn get_device_eth_address() -> Result<String, String> { Ok("0x".to_string()) }
+/// USB poll interval on AC power, where hotplug is unavailable
+const USB_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+/// How often device flows waiting on a reconnect are checked for expiry
+const FLOW_EXPIRY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 
 /// Start USB monitoring with proper event emission
-async fn start_usb_monitoring(
+pub(crate) async fn start_usb_monitoring(
     app_handle: tauri::AppHandle, 
-    device_queue_manager: Arc<tokio::sync::Mutex<std::collections::HashMap<String, keepkey_rust::device_queue::DeviceQueueHandle>>>,
-    database: Arc<Database>
+    device_queue_manager: commands::DeviceQueueManager,
+    database: Arc<Database>,
+    wallet_sessions: vault_core::wallet_session::WalletSessions,
 ) -> Result<(), String> {
     log::info!("🔍 Starting USB device monitoring for connect/disconnect events...");
     
-    // Monitor device connections in a loop
+    let power_monitor = app_handle.state::<Arc<vault_core::power::PowerMonitor>>().inner().clone();
+    let device_flows = app_handle.state::<Arc<vault_core::device_flow::DeviceFlowState>>().inner().clone();
+
+    // Hotplug callbacks where libusb supports them; elsewhere the bus is
+    // polled, stretched on battery power
+    let mut watcher = keepkey_rust::device_watcher::DeviceWatcher::start(move || {
+        power_monitor
+            .interval(vault_core::power::BackgroundWork::UsbPoll, USB_POLL_INTERVAL)
+            .unwrap_or(USB_POLL_INTERVAL)
+    });
+
     tokio::spawn(async move {
-        let mut last_devices = std::collections::HashSet::new();
-        
+        let mut flow_expiry = tokio::time::interval(FLOW_EXPIRY_CHECK_INTERVAL);
+
         loop {
-            // Get current devices with full device info
-            let current_device_list = keepkey_rust::features::list_connected_devices();
-            let current_devices: std::collections::HashSet<String> = current_device_list
-                .iter()
-                .filter(|d| d.is_keepkey)
-                .map(|d| d.unique_id.clone())
-                .collect();
-            
-            // Check for new connections
-            for device_id in &current_devices {
-                if !last_devices.contains(device_id) {
-                    log::info!("🔌 Device connected: {}", device_id);
-                    
-                    // Find the full device info for this connected device
-                    if let Some(device) = current_device_list.iter().find(|d| &d.unique_id == device_id) {
+            tokio::select! {
+                event = watcher.next() => match event {
+                    Some(DeviceEvent::Connected(device)) => {
+                        let device = &device;
+                        let device_id = &device.unique_id;
+                        log::info!("🔌 Device connected: {}", device_id);
+
+                        // A device that dropped off mid-flow may come back under a new id
+                        if device_flows.mark_reconnected(device_id) {
+                            log::info!("🔁 Device {} reconnected during {:?}", device_id, device_flows.active_flow(device_id));
+                        } else if let [(original_id, flow)] = device_flows.disconnected_devices().as_slice() {
+                            if device_flows.add_alias(device_id, original_id) && device_flows.mark_reconnected(device_id) {
+                                log::info!("🔁 Device {} is {} back from {:?}", device_id, original_id, flow);
+                                // Its history continues under the new id
+                                if let Err(e) = database.record_disconnection(original_id).await {
+                                    log::warn!("Could not record the disconnection of {}: {}", original_id, e);
+                                }
+                            }
+                        }
+                        // A freshly flashed device is done once it is back on its new firmware
+                        if device_flows.active_flow(device_id) == Some(vault_core::device_flow::DeviceFlow::FirmwareUpdate) {
+                            device_flows.end_flow(device_id);
+                            log::info!("✅ Device {} came back after its firmware update", device_id);
+                        }
+                        // A recovery picks up at the character the device is waiting for
+                        if device_flows.active_flow(device_id) == Some(vault_core::device_flow::DeviceFlow::Recovery) {
+                            if let Err(e) = commands::recovery::emit_character_request(&app_handle, &device_flows, device_id).await {
+                                log::warn!("Could not resume the recovery on {}: {}", device_id, e);
+                            }
+                        }
+
                         // Register device in the database
                         let serial_number = device.serial_number.as_deref();
                         let features_json = serde_json::to_string(&device).ok();
@@ -159,6 +189,10 @@ async fn start_usb_monitoring(
                         } else {
                             log::info!("📝 Registered device in registry: {}", device_id);
                         }
+                        // A device back within a flow's grace period keeps its open connection
+                        if let Err(e) = database.record_connection(device_id).await {
+                            log::warn!("Could not record the connection of {}: {}", device_id, e);
+                        }
                         
                         // Check if device needs setup
                         match database.device_needs_setup(device_id).await {
@@ -187,6 +221,78 @@ async fn start_usb_monitoring(
                             }
                         }
                         
+                        // Another vault or kkcli may hold the device; observe it read-only until released
+                        let observed = device::observer::check_claim_on_connect(&app_handle, device).await;
+
+                        // Pull any firmware fault record in the background; only new faults are surfaced
+                        if !observed {
+                            let app_handle = app_handle.clone();
+                            let database = database.clone();
+                            let device_queue_manager = device_queue_manager.clone();
+                            let device_id = device_id.clone();
+                            tokio::spawn(async move {
+                                match vault_core::fault_log::check_device_fault_log(&database, &device_id, &device_queue_manager).await {
+                                    Ok(vault_core::fault_log::FaultLogStatus::Fault { summary, record, is_new: true }) => {
+                                        log::warn!("💥 Device {} reported a firmware fault: {}", device_id, summary);
+                                        if let Err(e) = commands::emit_or_queue_event(
+                                            &app_handle,
+                                            "device:fault-detected",
+                                            serde_json::json!({
+                                                "device_id": device_id,
+                                                "summary": summary,
+                                                "fault_type": record.fault_type,
+                                            })
+                                        ).await {
+                                            log::error!("Failed to emit fault-detected event: {}", e);
+                                        }
+                                    }
+                                    Ok(_) => {}
+                                    Err(e) => log::warn!("Could not check fault log for {}: {}", device_id, e),
+                                }
+                            });
+                        }
+
+                        // Identify the standard wallet right away unless a passphrase or
+                        // PIN prompt would be needed
+                        if !observed {
+                            let app_handle = app_handle.clone();
+                            let device = device.clone();
+                            let device_queue_manager = device_queue_manager.clone();
+                            let device_id = device_id.clone();
+                            tokio::spawn(async move {
+                                match vault_core::get_device_features(&device_id, &device_queue_manager).await {
+                                    Ok(_) if vault_core::authenticity::authenticity_registry().require_verified(&device_id).is_err() => {
+                                        let authenticity = vault_core::authenticity::authenticity_registry().assessment(&device_id);
+                                        if let Err(e) = commands::emit_or_queue_event(
+                                            &app_handle,
+                                            vault_core::authenticity::UNRECOGNIZED_VENDOR_EVENT,
+                                            serde_json::json!({
+                                                "device_id": device_id,
+                                                "authenticity": authenticity,
+                                            })
+                                        ).await {
+                                            log::error!("Failed to emit unrecognized-vendor event: {}", e);
+                                        }
+                                    }
+                                    Ok(features) if features.initialized
+                                        && !features.passphrase_protection
+                                        && !features.bootloader_mode
+                                        && (!features.pin_protection || features.pin_cached) =>
+                                    {
+                                        // Resumes an interrupted frontload once identified
+                                        if let Err(e) = commands::wallets::identify_standard_wallet(&app_handle, &device_id).await {
+                                            log::warn!("Could not identify wallet for {}: {}", device_id, e);
+                                        }
+                                    }
+                                    Ok(_) => {}
+                                    Err(_) if vault_core::observer::observer_registry().is_observed(&device_id) => {
+                                        device::observer::enter_observer_mode(&app_handle, device).await;
+                                    }
+                                    Err(e) => log::warn!("Could not read features for {}: {}", device_id, e),
+                                }
+                            });
+                        }
+
                         // Emit device:connected event with full device info using emit_or_queue_event
                         let device_payload = serde_json::json!({
                             "unique_id": device.unique_id,
@@ -212,40 +318,65 @@ async fn start_usb_monitoring(
                             log::error!("❌ Failed to emit/queue status update: {}", e);
                         }
                     }
-                }
-            }
-            
-            // Check for disconnections
-            for device_id in &last_devices {
-                if !current_devices.contains(device_id) {
-                    log::info!("🔌 Device disconnected: {}", device_id);
-                    
-                    // Emit device:disconnected event using emit_or_queue_event
-                    let disconnect_payload = serde_json::json!({
-                        "device_id": device_id
-                    });
-                    
-                    if let Err(e) = commands::emit_or_queue_event(&app_handle, "device:disconnected", disconnect_payload).await {
-                        log::error!("❌ Failed to emit/queue device:disconnected event: {}", e);
-                    } else {
-                        log::info!("📡 Successfully emitted/queued device:disconnected event for {}", device_id);
+                    Some(DeviceEvent::Disconnected(device_id)) => {
+                        let device_id = &device_id;
+                        log::info!("🔌 Device disconnected: {}", device_id);
+                        // Keep the session of a device mid-flow; it is expected back shortly
+                        let flow = device_flows.mark_disconnected(device_id, std::time::Instant::now());
+                        if let Some(flow) = flow {
+                            log::info!("⏳ Device {} left during {:?}, waiting for it to reconnect", device_id, flow);
+                        } else {
+                            vault_core::wallet_session::end_wallet_session(&wallet_sessions, device_id).await;
+                            if let Err(e) = database.record_disconnection(device_id).await {
+                                log::warn!("Could not record the disconnection of {}: {}", device_id, e);
+                            }
+                        }
+                        vault_core::observer::observer_registry().restore(device_id);
+                        let cancelled = vault_core::request_queue::request_queue().cancel_pending(device_id);
+                        if cancelled > 0 {
+                            log::info!("🚫 Cancelled {} operations queued for {}", cancelled, device_id);
+                        }
+
+                        // Emit device:disconnected event using emit_or_queue_event
+                        let disconnect_payload = serde_json::json!({
+                            "device_id": device_id,
+                            "flow": flow,
+                        });
+
+                        if let Err(e) = commands::emit_or_queue_event(&app_handle, "device:disconnected", disconnect_payload).await {
+                            log::error!("❌ Failed to emit/queue device:disconnected event: {}", e);
+                        } else {
+                            log::info!("📡 Successfully emitted/queued device:disconnected event for {}", device_id);
+                        }
+
+                        // Also emit a status update
+                        let status_payload = serde_json::json!({
+                            "status": format!("Device disconnected: {}", device_id)
+                        });
+
+                        if let Err(e) = commands::emit_or_queue_event(&app_handle, "status:update", status_payload).await {
+                            log::error!("❌ Failed to emit/queue status update: {}", e);
+                        }
+                    }
+                    None => {
+                        log::error!("❌ USB device watcher stopped");
+                        break;
                     }
-                    
-                    // Also emit a status update
-                    let status_payload = serde_json::json!({
-                        "status": format!("Device disconnected: {}", device_id)
-                    });
-                    
-                    if let Err(e) = commands::emit_or_queue_event(&app_handle, "status:update", status_payload).await {
-                        log::error!("❌ Failed to emit/queue status update: {}", e);
+                },
+                _ = flow_expiry.tick() => {
+                    // Give up on flows whose device never came back
+                    for (device_id, flow) in device_flows.expire_disconnected(std::time::Instant::now(), vault_core::device_flow::RECONNECT_GRACE) {
+                        log::warn!("⌛ Device {} did not reconnect, abandoning {:?}", device_id, flow);
+                        if flow == vault_core::device_flow::DeviceFlow::Recovery {
+                            commands::recovery::forget_recovery(&device_id);
+                        }
+                        vault_core::wallet_session::end_wallet_session(&wallet_sessions, &device_id).await;
+                        if let Err(e) = database.record_disconnection(&device_id).await {
+                            log::warn!("Could not record the disconnection of {}: {}", device_id, e);
+                        }
                     }
                 }
             }
-            
-            last_devices = current_devices;
-            
-            // Poll every 500ms for device changes
-            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
         }
     });
     
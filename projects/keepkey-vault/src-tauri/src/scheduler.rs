@@ -0,0 +1,145 @@
+// scheduler.rs - Background jobs that run on a wall-clock schedule
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use keepkey_db::Database;
+use keepkey_db::backup::{self, BackupOutcome, BackupScheduler};
+use vault_core::maintenance::MaintenanceController;
+use vault_core::portfolio_sync::portfolio_sync;
+use vault_core::power::{BackgroundWork, PowerMonitor};
+
+/// How often scheduled jobs check whether they are due
+const TICK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the portfolio integrity audit runs
+const INTEGRITY_AUDIT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+static AUDITS_RUN: AtomicU64 = AtomicU64::new(0);
+static AUDITS_SKIPPED: AtomicU64 = AtomicU64::new(0);
+static DRIFT_DETECTED: AtomicU64 = AtomicU64::new(0);
+
+/// Counters from the portfolio integrity audit, reported in diagnostics
+#[derive(Debug, Clone, Serialize, specta::Type)]
+pub struct IntegrityMetrics {
+    pub audits_run: u64,
+    pub audits_skipped: u64,
+    pub drift_detected: u64,
+}
+
+pub fn integrity_metrics() -> IntegrityMetrics {
+    IntegrityMetrics {
+        audits_run: AUDITS_RUN.load(Ordering::Relaxed),
+        audits_skipped: AUDITS_SKIPPED.load(Ordering::Relaxed),
+        drift_detected: DRIFT_DETECTED.load(Ordering::Relaxed),
+    }
+}
+
+/// Run scheduled jobs for the lifetime of the app. Ticks during maintenance
+/// mode are skipped, and maintenance waits for a tick in progress. On the
+/// reduced power profile ticks come half as often.
+pub async fn run_scheduler(app: AppHandle, database: Arc<Database>) {
+    let maintenance = app.state::<Arc<MaintenanceController>>().inner().clone();
+    let power_monitor = app.state::<Arc<PowerMonitor>>().inner().clone();
+    let mut backups = BackupScheduler::new(backup::default_backup_dir());
+
+    let mut last_audit: Option<Instant> = None;
+
+    log::info!("⏰ Scheduler started (backups in {:?})", backups.dir());
+    loop {
+        let interval = power_monitor
+            .interval(BackgroundWork::PeriodicRefresh, TICK_INTERVAL)
+            .unwrap_or(TICK_INTERVAL);
+        tokio::time::sleep(interval).await;
+        // Held for the whole tick, so entering maintenance waits for a
+        // backup that is being written instead of moving the file under it
+        let Ok(_job) = maintenance.device_operation("scheduled_jobs") else {
+            continue;
+        };
+        // Follow the data directory if it was relocated
+        let backup_dir = backup::default_backup_dir();
+        if backups.dir() != backup_dir {
+            log::info!("⏰ Backups now written to {:?}", backup_dir);
+            backups = BackupScheduler::new(backup_dir);
+        }
+        run_backup_job(&app, &database, &mut backups).await;
+
+        if last_audit.map_or(true, |t| t.elapsed() >= INTEGRITY_AUDIT_INTERVAL) && run_integrity_audit(&database).await {
+            last_audit = Some(Instant::now());
+        }
+    }
+}
+
+/// Low-priority consistency check of the portfolio cache. Dashboards that
+/// drifted from their balances are rebuilt; orphaned balance rows are only
+/// reported (see `cleanup_orphaned_portfolio_rows`). Returns false when the
+/// audit was skipped and should be retried on the next tick.
+async fn run_integrity_audit(database: &Database) -> bool {
+    // Auditing mid-sync would compare half-written data and report false drift
+    let Some(_check) = portfolio_sync().try_check() else {
+        log::debug!("Portfolio sync in progress, skipping integrity audit");
+        AUDITS_SKIPPED.fetch_add(1, Ordering::Relaxed);
+        return false;
+    };
+
+    let report = match database.audit_portfolio_integrity().await {
+        Ok(report) => report,
+        Err(e) => {
+            log::warn!("Integrity audit failed: {}", e);
+            return false;
+        }
+    };
+    AUDITS_RUN.fetch_add(1, Ordering::Relaxed);
+
+    for drift in &report.drifted {
+        DRIFT_DETECTED.fetch_add(1, Ordering::Relaxed);
+        log::warn!(
+            "🧮 Dashboard for {} ({}) drifted by ${:.2}, recomputing",
+            drift.device_id, drift.wallet_fingerprint, drift.delta_usd
+        );
+        let details = serde_json::to_value(drift).ok();
+        let _ = database.log_activity(
+            "integrity",
+            &format!("Dashboard total off by ${:.2}; recomputed from balances", drift.delta_usd),
+            details.as_ref(),
+        ).await;
+        if let Err(e) = database.compute_dashboard(&drift.device_id, &drift.wallet_fingerprint).await {
+            log::warn!("Failed to recompute dashboard for {}: {}", drift.device_id, e);
+        }
+    }
+
+    if report.orphaned_rows > 0 {
+        log::warn!("🧮 {} portfolio balance rows reference pubkeys missing from wallet_xpubs", report.orphaned_rows);
+    }
+    true
+}
+
+/// Nightly backup; failures (e.g. disk full) are surfaced as a `backup:failed` warning
+async fn run_backup_job(app: &AppHandle, database: &Database, backups: &mut BackupScheduler) {
+    let policy = match database.get_backup_policy().await {
+        Ok(policy) => policy,
+        Err(e) => {
+            log::warn!("Failed to load backup policy: {}", e);
+            return;
+        }
+    };
+
+    match backups.tick(database, &policy, chrono::Local::now().naive_local()).await {
+        Ok(Some(BackupOutcome::Created { filename, pruned, .. })) => {
+            log::info!("💾 Scheduled backup written: {} ({} old backups pruned)", filename, pruned.len());
+        }
+        Ok(Some(BackupOutcome::Unchanged)) | Ok(None) => {}
+        Err(e) => {
+            log::warn!("⚠️ Scheduled backup failed: {}", e);
+            let _ = database.log_activity("backup", &format!("Backup failed: {}", e), None).await;
+            if let Err(emit_err) = crate::commands::emit_or_queue_event(app, "backup:failed", serde_json::json!({
+                "error": e.to_string(),
+                "backup_dir": backups.dir().display().to_string(),
+            })).await {
+                log::warn!("Failed to emit backup failure: {}", emit_err);
+            }
+        }
+    }
+}
@@ -0,0 +1,275 @@
+// startup.rs - Launch steps, run through vault-core's StartupOrchestrator
+//
+// Critical steps (database, USB monitoring) abort launch with an error dialog.
+// Optional steps that fail are kept in the startup report, written to the
+// activity log and announced with a `startup:task-failed` event.
+
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+use keepkey_db::Database;
+use vault_core::endpoints::{endpoint_manager, ENDPOINT_LIMITS_PREF};
+use vault_core::startup::{Criticality, StartupOrchestrator, StartupReport, TaskOutcome};
+use crate::commands;
+
+/// How many startup tasks may run at once
+const STARTUP_CONCURRENCY: usize = 4;
+
+/// Report from the last launch, for `get_startup_report` and diagnostics
+pub type StartupReportState = Arc<Mutex<Option<StartupReport>>>;
+
+pub fn new_startup_report() -> StartupReportState {
+    Arc::new(Mutex::new(None))
+}
+
+fn database(app: &AppHandle) -> Arc<Database> {
+    app.state::<Arc<Database>>().inner().clone()
+}
+
+/// Declare the launch steps. Tasks that need the database depend on it, which
+/// guarantees `Arc<Database>` is managed before they run.
+fn build_startup(app: &AppHandle) -> StartupOrchestrator {
+    let mut startup = StartupOrchestrator::new(STARTUP_CONCURRENCY);
+
+    let handle = app.clone();
+    startup.add("database", &[], Criticality::Critical, move || async move {
+        log::info!("🗄️ Initializing database...");
+        // A move deferred because a file was busy runs before anything opens the data directory
+        let default_dir = keepkey_db::data_dir::default_data_dir();
+        if let Some(moved_to) = tokio::task::spawn_blocking(move || keepkey_db::data_dir::apply_pending_relocation(&default_dir))
+            .await
+            .ok()
+            .flatten()
+        {
+            log::info!("📦 Data directory moved to {:?}", moved_to);
+        }
+        let database = Database::new().await.map_err(|e| format!("Failed to open the vault database: {}", e))?;
+        handle.manage(Arc::new(database));
+        Ok(())
+    });
+
+    // Size the event replay log before any events are emitted
+    let handle = app.clone();
+    startup.add("event_history", &["database"], Criticality::Optional, move || async move {
+        commands::events::configure_event_history(database(&handle)).await;
+        Ok(())
+    });
+
+    // Startup schema migration outcome; a blocked one leaves the database read-only
+    let handle = app.clone();
+    startup.add("migration_status", &["database", "event_history"], Criticality::Optional, move || async move {
+        let database = database(&handle);
+        commands::storage::report_migration(&handle, &database, &database.migration_report()).await;
+        Ok(())
+    });
+
+    // Prune history/transaction cache beyond the configured retention
+    let handle = app.clone();
+    startup.add("storage_policy", &["database"], Criticality::Optional, move || async move {
+        commands::storage::apply_startup_storage_policy(database(&handle)).await
+    });
+
+    // Old portfolio snapshots are thinned to one per day
+    let handle = app.clone();
+    startup.add("portfolio_history", &["database"], Criticality::Optional, move || async move {
+        database(&handle).prune_portfolio_history().await
+            .map(|_| ())
+            .map_err(|e| format!("Could not prune portfolio history: {}", e))
+    });
+
+    // Cached pubkeys not read in PUBKEY_CACHE_MAX_IDLE_DAYS are dropped
+    let handle = app.clone();
+    startup.add("pubkey_cache", &["database"], Criticality::Optional, move || async move {
+        database(&handle).evict_unused_pubkeys(keepkey_db::cache::PUBKEY_CACHE_MAX_IDLE_DAYS).await
+            .map(|_| ())
+            .map_err(|e| format!("Could not evict unused pubkeys: {}", e))
+    });
+
+    // Native assets, mainnets and frontload paths bundled with the app.
+    // Networks and paths reference native assets, so assets go first.
+    let handle = app.clone();
+    startup.add("asset_registry", &["database"], Criticality::Optional, move || async move {
+        let database = database(&handle);
+        database.seed_default_assets().await
+            .map_err(|e| format!("Could not seed default assets: {}", e))?;
+        database.seed_default_networks().await
+            .map_err(|e| format!("Could not seed default networks: {}", e))?;
+        database.load_default_derivation_paths().await
+            .map(|_| ())
+            .map_err(|e| format!("Could not load derivation paths: {}", e))
+    });
+
+    // Nightly backups and other scheduled jobs
+    let handle = app.clone();
+    startup.add("scheduler", &["database"], Criticality::Optional, move || async move {
+        tauri::async_runtime::spawn(crate::scheduler::run_scheduler(handle.clone(), database(&handle)));
+        Ok(())
+    });
+
+    // AC/battery detection for the reduced-activity power profile
+    let handle = app.clone();
+    startup.add("power_monitor", &["database"], Criticality::Optional, move || async move {
+        tauri::async_runtime::spawn(commands::power::run_power_monitor(handle.clone(), database(&handle)));
+        Ok(())
+    });
+
+    // First-run network selection, from the chains the devices have xpubs for
+    let handle = app.clone();
+    startup.add("network_defaults", &["database", "asset_registry"], Criticality::Optional, move || async move {
+        commands::networks::apply_network_defaults(&database(&handle)).await
+    });
+
+    // Per-endpoint request budgets from the preferences, before anything calls out
+    let handle = app.clone();
+    startup.add("endpoint_limits", &["database"], Criticality::Optional, move || async move {
+        let limits = database(&handle).get_preference(ENDPOINT_LIMITS_PREF).await.ok().flatten();
+        match limits {
+            Some(json) => endpoint_manager().configure_from_json(&json),
+            None => Ok(()),
+        }
+    });
+
+    // Pending and recently confirmed transactions are rechecked for confirmations and reorgs
+    let handle = app.clone();
+    startup.add("transaction_tracker", &["database", "endpoint_limits"], Criticality::Optional, move || async move {
+        tauri::async_runtime::spawn(commands::transactions::run_transaction_tracker(handle.clone(), database(&handle)));
+        Ok(())
+    });
+
+    // Idle timer for the app lock
+    let handle = app.clone();
+    startup.add("app_lock", &["database"], Criticality::Optional, move || async move {
+        tauri::async_runtime::spawn(commands::app_lock::run_app_lock_monitor(handle.clone(), database(&handle)));
+        Ok(())
+    });
+
+    // Host clock offset for IBC timeouts and quote expiries; measured in the background
+    let handle = app.clone();
+    startup.add("clock_check", &["database", "endpoint_limits"], Criticality::Optional, move || async move {
+        commands::clock::spawn_startup_clock_check(handle.clone(), database(&handle));
+        Ok(())
+    });
+
+    // USB permissions, conflicting software and connectivity, until onboarding is done
+    let handle = app.clone();
+    startup.add("environment_probe", &["database"], Criticality::Optional, move || async move {
+        commands::environment::spawn_first_run_probe(handle.clone(), database(&handle));
+        Ok(())
+    });
+
+    // preferences:changed for every preference written
+    let handle = app.clone();
+    startup.add("preference_events", &["database", "event_history"], Criticality::Optional, move || async move {
+        commands::config::init_preference_events(&handle, &database(&handle));
+        Ok(())
+    });
+
+    // Locks devices left idle past their auto-lock delay
+    let handle = app.clone();
+    startup.add("device_auto_lock", &["database"], Criticality::Optional, move || async move {
+        tauri::async_runtime::spawn(commands::device::auto_lock::run_device_auto_lock(handle.clone(), database(&handle)));
+        Ok(())
+    });
+
+    // Operations that hit a PassphraseRequest wait for send_passphrase
+    let handle = app.clone();
+    startup.add("passphrase_prompts", &["event_history"], Criticality::Optional, move || async move {
+        commands::device::passphrase::init_passphrase_prompts(&handle);
+        Ok(())
+    });
+
+    // Connect/disconnect monitoring; without it no device can be used
+    let handle = app.clone();
+    startup.add("usb_monitor", &["database", "event_history"], Criticality::Critical, move || async move {
+        log::info!("🔌 Initializing USB device management...");
+        // Connections still open were cut short by the app being killed
+        let launched_at = chrono::Utc::now().timestamp();
+        if let Err(e) = database(&handle).close_open_connections(launched_at).await {
+            log::warn!("Could not close stale device connections: {}", e);
+        }
+        let device_queue_manager = handle.state::<commands::DeviceQueueManager>().inner().clone();
+        let wallet_sessions = handle.state::<vault_core::wallet_session::WalletSessions>().inner().clone();
+        crate::start_usb_monitoring(handle.clone(), device_queue_manager, database(&handle), wallet_sessions).await
+    });
+
+    // Tray icon with connected devices and portfolio total (no-op without tray support)
+    let handle = app.clone();
+    startup.add("tray", &["database"], Criticality::Optional, move || async move {
+        crate::tray::init_tray(&handle);
+        Ok(())
+    });
+
+    // Coalesced plain-state summaries for screen readers
+    let handle = app.clone();
+    startup.add("accessibility", &["database"], Criticality::Optional, move || async move {
+        commands::accessibility::init_accessibility_events(&handle, database(&handle));
+        Ok(())
+    });
+
+    startup
+}
+
+/// Run the launch steps.
+///
+/// If a critical step fails the window is hidden and an error dialog quits the
+/// app once dismissed; Err is only returned for an invalid task graph.
+pub fn run_startup(app: &AppHandle) -> Result<(), String> {
+    let report = tauri::async_runtime::block_on(build_startup(app).run())?;
+    log::info!("🚦 Startup finished in {}ms", report.total_ms);
+
+    if let Some(failed) = report.critical_failure() {
+        let error = match &failed.outcome {
+            TaskOutcome::Failed { error } => error.clone(),
+            _ => "unknown error".to_string(),
+        };
+        show_startup_error(app, &failed.name, &error);
+        store_report(app, report);
+        return Ok(());
+    }
+
+    let failures: Vec<(String, String)> = report
+        .optional_failures()
+        .filter_map(|t| match &t.outcome {
+            TaskOutcome::Failed { error } => Some((t.name.clone(), error.clone())),
+            _ => None,
+        })
+        .collect();
+    store_report(app, report);
+
+    if !failures.is_empty() {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let database = database(&app);
+            for (task, error) in failures {
+                let details = serde_json::json!({ "task": task, "error": error });
+                if let Err(e) = database.log_activity("startup", &format!("Startup task '{}' failed", task), Some(&details)).await {
+                    log::warn!("Failed to record startup failure: {}", e);
+                }
+                if let Err(e) = commands::emit_or_queue_event(&app, "startup:task-failed", details).await {
+                    log::error!("❌ Failed to emit/queue startup:task-failed event: {}", e);
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+fn store_report(app: &AppHandle, report: StartupReport) {
+    if let Ok(mut slot) = app.state::<StartupReportState>().lock() {
+        *slot = Some(report);
+    }
+}
+
+/// Hide the window and explain why the vault cannot start; quits once dismissed
+fn show_startup_error(app: &AppHandle, task: &str, error: &str) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.hide();
+    }
+    let handle = app.clone();
+    app.dialog()
+        .message(format!("KeepKey Vault could not start ({}).\n\n{}", task, error))
+        .title("KeepKey Vault")
+        .kind(MessageDialogKind::Error)
+        .show(move |_| handle.exit(1));
+}
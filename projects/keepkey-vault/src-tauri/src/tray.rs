@@ -0,0 +1,180 @@
+// tray.rs - System tray (menubar) icon with connected devices and portfolio total
+//
+// The menu is rebuilt from the registry and dashboard caches whenever device or
+// cache state changes; it never polls devices itself. Platforms without tray
+// support (or a disabled `show_tray` preference) simply get no tray.
+
+use tauri::AppHandle;
+
+/// Events after which the tray menu is rebuilt
+#[cfg(desktop)]
+const REFRESH_EVENTS: [&str; 6] = [
+    "device:connected",
+    "device:disconnected",
+    "device:status-changed",
+    "device:label-changed",
+    "cache:refreshed",
+    "wallet:session-changed",
+];
+
+/// Rebuild the tray on state changes and create it for the first time
+#[cfg(desktop)]
+pub fn init_tray(app: &AppHandle) {
+    use tauri::Listener;
+
+    for event in REFRESH_EVENTS {
+        let app_handle = app.clone();
+        app.listen_any(event, move |_| {
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move { refresh_tray(&app_handle).await });
+        });
+    }
+
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move { refresh_tray(&app_handle).await });
+}
+
+#[cfg(not(desktop))]
+pub fn init_tray(_app: &AppHandle) {}
+
+/// Held for a whole refresh. Refreshes fire from several events at once, and
+/// two that both find no tray would each create one.
+#[cfg(desktop)]
+fn refresh_lock() -> &'static tokio::sync::Mutex<()> {
+    static REFRESH: std::sync::OnceLock<tokio::sync::Mutex<()>> = std::sync::OnceLock::new();
+    REFRESH.get_or_init(|| tokio::sync::Mutex::new(()))
+}
+
+/// Create, update or remove the tray to match the preference and current state
+#[cfg(desktop)]
+pub async fn refresh_tray(app: &AppHandle) {
+    use std::sync::Arc;
+    use tauri::Manager;
+    use keepkey_db::Database;
+    use crate::commands::tray::{build_tray_summary, SHOW_TRAY_PREF};
+
+    let _refreshing = refresh_lock().lock().await;
+    let database = app.state::<Arc<Database>>().inner().clone();
+    let show_tray = !matches!(database.get_preference(SHOW_TRAY_PREF).await, Ok(Some(ref v)) if v == "false");
+    if !show_tray {
+        if app.remove_tray_by_id(desktop::TRAY_ID).is_some() {
+            log::info!("🖥️ Tray icon hidden");
+        }
+        return;
+    }
+
+    let wallet_sessions = app.state::<vault_core::wallet_session::WalletSessions>().inner().clone();
+    let summary = match build_tray_summary(&database, &wallet_sessions).await {
+        Ok(summary) => summary,
+        Err(e) => {
+            log::warn!("Failed to build tray summary: {}", e);
+            return;
+        }
+    };
+    // Tray actions come from the quick-action registry so they match the palette
+    let actions = match crate::commands::quick_actions::build_action_context(&database, &wallet_sessions, &summary).await {
+        Ok(context) => vault_core::quick_actions::list_quick_actions(&context, None),
+        Err(e) => {
+            log::warn!("Failed to evaluate tray actions: {}", e);
+            Vec::new()
+        }
+    };
+
+    if let Err(e) = desktop::apply_summary(app, &summary, &actions) {
+        log::warn!("Tray unavailable: {}", e);
+    }
+}
+
+#[cfg(not(desktop))]
+pub async fn refresh_tray(_app: &AppHandle) {}
+
+#[cfg(desktop)]
+mod desktop {
+    use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+    use tauri::tray::{TrayIconBuilder, TrayIconEvent};
+    use tauri::{AppHandle, Manager};
+    use vault_core::quick_actions::{self, QuickAction};
+    use vault_core::tray::TraySummary;
+    use crate::commands;
+
+    pub const TRAY_ID: &str = "main";
+
+    const SHOW_WINDOW: &str = "tray:show-window";
+    /// Menu ids of registry actions are this prefix plus the action id
+    const QUICK_ACTION_PREFIX: &str = "tray:action:";
+    /// Argument-free registry actions offered in the tray, in menu order
+    const TRAY_ACTIONS: [&str; 2] = ["lock_all_devices", "sync_now"];
+
+    fn build_menu(app: &AppHandle, summary: &TraySummary, actions: &[QuickAction]) -> tauri::Result<Menu<tauri::Wry>> {
+        let menu = Menu::new(app)?;
+
+        if summary.devices.is_empty() {
+            menu.append(&MenuItem::with_id(app, "tray:no-devices", "No KeepKey connected", false, None::<&str>)?)?;
+        }
+        for device in &summary.devices {
+            let text = format!("{} — {}", device.label, device.state.label());
+            menu.append(&MenuItem::with_id(app, format!("tray:device:{}", device.device_id), text, false, None::<&str>)?)?;
+        }
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+        menu.append(&MenuItem::with_id(app, "tray:portfolio", summary.portfolio_line(), false, None::<&str>)?)?;
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+
+        menu.append(&MenuItem::with_id(app, SHOW_WINDOW, "Show window", true, None::<&str>)?)?;
+        for id in TRAY_ACTIONS {
+            let Some(spec) = quick_actions::find_action(id) else { continue };
+            let available = actions.iter().any(|action| action.id == id);
+            let menu_id = format!("{}{}", QUICK_ACTION_PREFIX, id);
+            menu.append(&MenuItem::with_id(app, menu_id, spec.title, available, None::<&str>)?)?;
+        }
+        Ok(menu)
+    }
+
+    pub fn apply_summary(app: &AppHandle, summary: &TraySummary, actions: &[QuickAction]) -> tauri::Result<()> {
+        let menu = build_menu(app, summary, actions)?;
+
+        if let Some(tray) = app.tray_by_id(TRAY_ID) {
+            tray.set_menu(Some(menu))?;
+            tray.set_tooltip(Some(summary.tooltip()))?;
+            return Ok(());
+        }
+
+        let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+            .menu(&menu)
+            .tooltip(summary.tooltip())
+            .on_menu_event(handle_menu_event)
+            .on_tray_icon_event(|tray, event| {
+                if let TrayIconEvent::Click { .. } = event {
+                    commands::warmup::warm_up_devices(tray.app_handle());
+                }
+            });
+        if let Some(icon) = app.default_window_icon() {
+            builder = builder.icon(icon.clone());
+        }
+        builder.build(app)?;
+        log::info!("🖥️ Tray icon created");
+        Ok(())
+    }
+
+    fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+        match event.id().as_ref() {
+            SHOW_WINDOW => {
+                if let Some(window) = app.get_webview_window("main") {
+                    let _ = window.unminimize();
+                    let _ = window.show();
+                    let _ = window.set_focus();
+                }
+            }
+            id => {
+                let Some(action_id) = id.strip_prefix(QUICK_ACTION_PREFIX).map(str::to_string) else {
+                    return;
+                };
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = commands::quick_actions::run_quick_action(&app, &action_id, Default::default()).await {
+                        log::warn!("Tray action {} failed: {}", action_id, e);
+                    }
+                });
+            }
+        }
+    }
+}
@@ -188,7 +188,7 @@ export function SetupWizard({ onClose, onComplete, initialDeviceId }: SetupWizar
         // Get ETH address if the device is set up
         let ethAddress: string | null = null;
         try {
-          ethAddress = await invoke<string | null>('get_device_eth_address', { deviceId });
+          ethAddress = await invoke<string | null>('get_device_eth_address', { deviceId, forceRefresh: false });
         } catch (error) {
           console.warn('Could not get ETH address:', error);
         }
@@ -1,24 +1,24 @@
 import React, { createContext, useContext, useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
-import { useBootloaderUpdateWizard, useFirmwareUpdateWizard, useTroubleshootingWizard } from './DialogContext';
+import { useBootloaderUpdateWizard, useFirmwareUpdateWizard } from './DialogContext';
 
 // Types that mirror the Rust backend types
+// Listed in the order they must be done
 export enum BlockingActionType {
-  MandatoryBootloaderUpdate = "mandatory_bootloader_update",
+  MandatoryBootloaderUpdate = "bootloader_update",
   FirmwareUpdate = "firmware_update",
-  DeviceInitialization = "device_initialization",
-  DeviceCommunicationFailure = "device_communication_failure"
-  // Add more types here as they're added in the Rust backend
+  DeviceInitialization = "initialize_device",
+  EnterPin = "enter_pin"
 }
 
 export interface BlockingAction {
   device_id: string;
-  action_type: BlockingActionType;
+  action: BlockingActionType;
+  severity: "critical" | "high" | "medium";
+  current: string | null;
+  required: string | null;
   message: string;
-  priority: number;
-  current_version?: string;
-  required_version?: string;
 }
 
 interface BlockingActionsContextType {
@@ -44,12 +44,11 @@ export const BlockingActionsProvider: React.FC<{ children: React.ReactNode }> =
   // Get dialog hooks
   const bootloaderUpdateWizard = useBootloaderUpdateWizard();
   const firmwareUpdateWizard = useFirmwareUpdateWizard();
-  const troubleshootingWizard = useTroubleshootingWizard();
 
   // Function to fetch actions from backend
   const fetchActions = async () => {
     try {
-      const allActions = await invoke<BlockingAction[]>('get_blocking_actions', { deviceId: null });
+      const allActions = await invoke<BlockingAction[]>('get_all_blocking_actions');
       console.log('Fetched blocking actions:', allActions);
       setActions(allActions);
       setPendingActionsCount(allActions.length);
@@ -82,30 +81,27 @@ export const BlockingActionsProvider: React.FC<{ children: React.ReactNode }> =
   useEffect(() => {
     // Count actions by type
     const bootloaderCount = actions.filter(
-      a => a.action_type === BlockingActionType.MandatoryBootloaderUpdate
+      a => a.action === BlockingActionType.MandatoryBootloaderUpdate
     ).length;
     
     const firmwareCount = actions.filter(
-      a => a.action_type === BlockingActionType.FirmwareUpdate
+      a => a.action === BlockingActionType.FirmwareUpdate
     ).length;
     
     const initCount = actions.filter(
-      a => a.action_type === BlockingActionType.DeviceInitialization
+      a => a.action === BlockingActionType.DeviceInitialization
     ).length;
     
     setBootloaderUpdateCount(bootloaderCount);
     setFirmwareUpdateCount(firmwareCount);
     setDeviceInitializationCount(initCount);
     
-    // Find highest priority action
-    const highest = actions.length > 0 
-      ? actions.reduce((prev, current) => 
-          (current.priority > (prev?.priority || 0)) ? current : prev, null as BlockingAction | null)
-      : null;
+    // The backend lists each device's actions in the order they must be done
+    const highest = actions.length > 0 ? actions[0] : null;
     
     setHighestPriorityAction(highest);
     
-    console.log(`Actions updated: bootloader=${bootloaderCount}, firmware=${firmwareCount}, init=${initCount}, highest=${highest?.action_type || 'none'}`);
+    console.log(`Actions updated: bootloader=${bootloaderCount}, firmware=${firmwareCount}, init=${initCount}, highest=${highest?.action || 'none'}`);
   }, [actions]);
 
   // Function to resolve an action
@@ -119,8 +115,8 @@ export const BlockingActionsProvider: React.FC<{ children: React.ReactNode }> =
       if (wasResolved) {
         // Remove the resolved action from local state
         setActions(prev => prev.filter(a => {
-          console.debug('[BlockingActionsContext] Checking action with deviceId:', deviceId, 'against', a.device_id, a.action_type);
-          return !(a.device_id === deviceId && a.action_type === actionType);
+          console.debug('[BlockingActionsContext] Checking action with deviceId:', deviceId, 'against', a.device_id, a.action);
+          return !(a.device_id === deviceId && a.action === actionType);
         }));
 
         // Update the count
@@ -138,43 +134,20 @@ export const BlockingActionsProvider: React.FC<{ children: React.ReactNode }> =
   useEffect(() => {
     // Only process if we have a highest priority action
     if (highestPriorityAction) {
-      console.log(`Processing highest priority action: ${highestPriorityAction.action_type}`);
+      console.log(`Processing highest priority action: ${highestPriorityAction.action}`);
       
-      switch (highestPriorityAction.action_type) {
-        case BlockingActionType.DeviceCommunicationFailure:
-          // Check if wizard is already shown to prevent duplicate dialogs
-          const isTroubleshootingWizardShown = troubleshootingWizard.isShowing(highestPriorityAction.device_id);
-          
-          if (!isTroubleshootingWizardShown) {
-            console.log('Showing troubleshooting wizard for communication failure');
-            troubleshootingWizard.show({
-              deviceId: highestPriorityAction.device_id,
-              errorDetails: highestPriorityAction.message,
-              onResolved: async () => {
-                // When communication is restored, resolve the action
-                await resolveAction(highestPriorityAction.device_id, BlockingActionType.DeviceCommunicationFailure);
-                // Re-fetch actions to get the next highest priority
-                fetchActions();
-              },
-              onContactSupport: (diagnostics) => {
-                console.log('User contacted support for communication issue:', diagnostics);
-                // Handle support contact - could open email client, send to support system, etc.
-              }
-            });
-          }
-          break;
-          
+      switch (highestPriorityAction.action) {
         case BlockingActionType.MandatoryBootloaderUpdate:
           // Check if wizard is already shown to prevent duplicate dialogs
           const isBootloaderWizardShown = bootloaderUpdateWizard.isShowing(highestPriorityAction.device_id);
           
           // Only show if we have both version information and wizard is not already shown
-          if (!isBootloaderWizardShown && highestPriorityAction.current_version && highestPriorityAction.required_version) {
+          if (!isBootloaderWizardShown && highestPriorityAction.current && highestPriorityAction.required) {
             console.log('Showing bootloader update wizard for blocking action');
             bootloaderUpdateWizard.show({
               deviceId: highestPriorityAction.device_id,
-              currentVersion: highestPriorityAction.current_version,
-              requiredVersion: highestPriorityAction.required_version,
+              currentVersion: highestPriorityAction.current,
+              requiredVersion: highestPriorityAction.required,
               onWizardComplete: async (success: boolean, deviceId: string) => {
                 // When the bootloader update is complete, resolve the action
                 if (success) {
@@ -191,18 +164,18 @@ export const BlockingActionsProvider: React.FC<{ children: React.ReactNode }> =
           // Don't show firmware update wizard if there's also a bootloader update needed
           const hasBootloaderAction = actions.some(
             a => a.device_id === highestPriorityAction.device_id && 
-                 a.action_type === BlockingActionType.MandatoryBootloaderUpdate
+                 a.action === BlockingActionType.MandatoryBootloaderUpdate
           );
           
           // Check if wizard is already shown to prevent duplicate dialogs
           const isWizardShown = firmwareUpdateWizard.isShowing(highestPriorityAction.device_id);
           
-          if (!hasBootloaderAction && !isWizardShown && highestPriorityAction.current_version && highestPriorityAction.required_version) {
+          if (!hasBootloaderAction && !isWizardShown && highestPriorityAction.current && highestPriorityAction.required) {
             console.log('Showing firmware update wizard for blocking action');
             firmwareUpdateWizard.show({
               deviceId: highestPriorityAction.device_id,
-              currentVersion: highestPriorityAction.current_version,
-              targetVersion: highestPriorityAction.required_version,
+              currentVersion: highestPriorityAction.current,
+              targetVersion: highestPriorityAction.required,
               onComplete: async (success: boolean, deviceId: string) => {
                 // When the firmware update is complete, resolve the action
                 if (success) {
@@ -218,7 +191,7 @@ export const BlockingActionsProvider: React.FC<{ children: React.ReactNode }> =
         // Handle other action types as needed
       }
     }
-  }, [highestPriorityAction, actions, bootloaderUpdateWizard, firmwareUpdateWizard, troubleshootingWizard, fetchActions]);
+  }, [highestPriorityAction, actions, bootloaderUpdateWizard, firmwareUpdateWizard, fetchActions]);
 
   return (
     <BlockingActionsContext.Provider value={{
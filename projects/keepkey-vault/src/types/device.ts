@@ -29,6 +29,7 @@ export interface DeviceStatus {
   needsFirmwareUpdate: boolean
   needsInitialization: boolean
   needsPinUnlock: boolean
+  vendorMismatch: boolean
   bootloaderCheck?: BootloaderCheck
   firmwareCheck?: FirmwareCheck
   initializationCheck?: InitializationCheck
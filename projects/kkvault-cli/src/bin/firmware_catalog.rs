@@ -0,0 +1,153 @@
+//! firmware-catalog - rebuild releases.json from KeepKey release binaries
+//!
+//! Hashes each release image the way the device reports it, regenerates the
+//! hash tables of releases.json and diffs them against the bundled catalog.
+//! Exits non-zero on any mismatch so CI can run it on every change to the
+//! firmware directory.
+
+// Shared with kkvault-cli; this tool only prints tables and errors
+#[path = "../output.rs"]
+#[allow(dead_code)]
+mod output;
+
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+use output::Output;
+use vault_core::firmware_catalog::{
+    diff_catalogs, image_hash, regenerate_catalog, scan_release_dir, unverified_entries, ComputedImage, ImageKind,
+    SkippedImage,
+};
+
+#[derive(Parser)]
+#[command(name = "firmware-catalog", version, about = "Verify releases.json against KeepKey release binaries")]
+struct Cli {
+    /// Directory of release binaries laid out like the bundled firmware directory
+    #[arg(long, conflicts_with = "manifest", required_unless_present = "manifest")]
+    dir: Option<PathBuf>,
+    /// JSON list of {"kind", "version", "url"} entries to download and hash
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+    /// Catalog to diff against (defaults to releases.json in --dir)
+    #[arg(long)]
+    catalog: Option<PathBuf>,
+    /// Write the regenerated catalog here
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Print machine-readable JSON instead of tables
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Deserialize)]
+struct ManifestEntry {
+    kind: String,
+    version: String,
+    /// http(s) URL, or a path relative to the manifest
+    url: String,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let out = Output::new(cli.json);
+
+    match run(cli, &out).await {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            out.error(&e);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Returns whether the catalog matched the release images
+async fn run(cli: Cli, out: &Output) -> Result<bool> {
+    let (images, skipped) = match (&cli.dir, &cli.manifest) {
+        (Some(dir), _) => scan_release_dir(dir).map_err(|e| anyhow!(e))?,
+        (None, Some(manifest)) => hash_manifest(manifest).await?,
+        (None, None) => unreachable!("clap requires --dir or --manifest"),
+    };
+
+    let catalog_path = cli
+        .catalog
+        .or_else(|| cli.dir.as_ref().map(|dir| dir.join("releases.json")))
+        .ok_or_else(|| anyhow!("pass --catalog with --manifest"))?;
+    let catalog: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(&catalog_path).with_context(|| format!("Failed to read {:?}", catalog_path))?,
+    )
+    .context("Invalid releases.json")?;
+
+    let regenerated = regenerate_catalog(&catalog, &images);
+    if let Some(path) = &cli.output {
+        std::fs::write(path, serde_json::to_string_pretty(&regenerated)? + "\n")
+            .with_context(|| format!("Failed to write {:?}", path))?;
+    }
+
+    for image in &skipped {
+        eprintln!("skipped {} {}: {}", image.kind.key(), image.version, image.reason);
+    }
+    let changes = diff_catalogs(&catalog, &regenerated);
+    out.table(
+        &["Location", "Version", "Bundled", "Computed"],
+        changes
+            .iter()
+            .map(|c| {
+                vec![
+                    c.location.clone(),
+                    c.version.clone(),
+                    c.bundled.clone().unwrap_or_else(|| "-".to_string()),
+                    c.computed.clone().unwrap_or_else(|| "-".to_string()),
+                ]
+            })
+            .collect(),
+        &json!({
+            "verified": images,
+            "skipped": skipped,
+            "mismatches": changes,
+            "unverified": unverified_entries(&catalog, &images),
+        }),
+    );
+    if !cli.json {
+        eprintln!("{} images hashed, {} mismatches", images.len(), changes.len());
+    }
+    Ok(changes.is_empty())
+}
+
+/// Download or read every image listed in a manifest and hash it
+async fn hash_manifest(manifest: &Path) -> Result<(Vec<ComputedImage>, Vec<SkippedImage>)> {
+    let entries: Vec<ManifestEntry> = serde_json::from_str(
+        &std::fs::read_to_string(manifest).with_context(|| format!("Failed to read {:?}", manifest))?,
+    )
+    .context("Invalid manifest")?;
+    let base = manifest.parent().unwrap_or(Path::new("."));
+    let client = reqwest::Client::new();
+
+    let mut images = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in entries {
+        let kind = match entry.kind.as_str() {
+            "firmware" => ImageKind::Firmware,
+            "bootloader" => ImageKind::Bootloader,
+            other => return Err(anyhow!("Unknown image kind in manifest: {}", other)),
+        };
+        let version = format!("v{}", entry.version.trim_start_matches('v'));
+
+        let bytes = if entry.url.starts_with("http://") || entry.url.starts_with("https://") {
+            let response = client.get(&entry.url).send().await?.error_for_status()?;
+            response.bytes().await?.to_vec()
+        } else {
+            std::fs::read(base.join(&entry.url)).with_context(|| format!("Failed to read {}", entry.url))?
+        };
+
+        match image_hash(kind, &bytes) {
+            Ok(hash) => images.push(ComputedImage { kind, version, source: entry.url, hash }),
+            Err(reason) => skipped.push(SkippedImage { kind, version, source: entry.url, reason }),
+        }
+    }
+    Ok((images, skipped))
+}
@@ -0,0 +1,491 @@
+//! kkvault-cli - script KeepKey devices without the GUI
+//!
+//! Shares the device queue and database code with the Tauri vault through
+//! `vault-core`, and refuses to start while the vault holds the USB lock.
+
+mod output;
+mod tx;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use serde_json::json;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use keepkey_rust::chains::bitcoin::ScriptType;
+use keepkey_rust::device_queue::{DeviceQueueFactory, DeviceQueueHandle};
+use keepkey_rust::friendly_usb::FriendlyUsbDevice;
+use keepkey_rust::transport::mock::{MockDevice, MockTransport};
+use keepkey_rust::version::compare_versions;
+use output::Output;
+use vault_core::firmware_catalog::{ImageKind, ReleasesCatalog};
+use vault_core::instance_lock::{InstanceLock, OWNER_CLI};
+use vault_core::paths::{format_derivation_path, network_family, parse_derivation_path, NetworkFamily};
+use vault_core::queue::new_queue_manager;
+use vault_core::signing_origin::{self, new_limit_confirmations, SigningOrigin};
+use vault_core::DeviceQueueManager;
+
+#[derive(Parser)]
+#[command(name = "kkvault-cli", version, about = "Script KeepKey devices without the vault GUI")]
+struct Cli {
+    /// Print machine-readable JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Talk to a scripted device described by this JSON file instead of USB
+    #[arg(long, global = true, hide = true, value_name = "FILE")]
+    mock_device: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Connected device operations
+    Devices {
+        #[command(subcommand)]
+        action: DevicesAction,
+    },
+    /// Single device information
+    Device {
+        #[command(subcommand)]
+        action: DeviceAction,
+    },
+    /// Address derivation
+    Address {
+        #[command(subcommand)]
+        action: AddressAction,
+    },
+    /// Extended public keys
+    Xpub {
+        #[command(subcommand)]
+        action: XpubAction,
+    },
+    /// Transaction signing
+    Tx {
+        #[command(subcommand)]
+        action: TxAction,
+    },
+    /// Firmware status and updates
+    Firmware {
+        #[command(subcommand)]
+        action: FirmwareAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum DevicesAction {
+    /// List connected KeepKey devices
+    List,
+}
+
+#[derive(Subcommand)]
+enum DeviceAction {
+    /// Show features reported by a device
+    Features { device_id: String },
+}
+
+#[derive(Subcommand)]
+enum AddressAction {
+    /// Derive an address on the device
+    Get {
+        /// CAIP-2 network id, e.g. eip155:1 or bip122:000000000019d6689c085ae165831e93
+        #[arg(long)]
+        caip: String,
+        /// BIP32 derivation path, e.g. m/84'/0'/0'/0/0
+        #[arg(long)]
+        path: String,
+        /// Show the address on the device screen for verification
+        #[arg(long)]
+        display: bool,
+        /// Device to use (defaults to the only connected device)
+        #[arg(long)]
+        device: Option<String>,
+        /// UTXO script type: p2pkh, p2sh-p2wpkh or p2wpkh
+        #[arg(long, default_value = "p2pkh")]
+        script_type: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum XpubAction {
+    /// Export an extended public key
+    Get {
+        #[arg(long)]
+        path: String,
+        /// Firmware coin name
+        #[arg(long, default_value = "Bitcoin")]
+        coin: String,
+        #[arg(long, default_value = "p2pkh")]
+        script_type: String,
+        #[arg(long)]
+        device: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TxAction {
+    /// Sign a transaction described by a JSON request file
+    Sign {
+        #[arg(long)]
+        file: PathBuf,
+        #[arg(long)]
+        device: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum FirmwareAction {
+    /// Compare device firmware against the bundled release catalog
+    Check {
+        device_id: Option<String>,
+        #[arg(long)]
+        firmware_dir: Option<PathBuf>,
+    },
+    /// Flash the latest bundled firmware (device must be in bootloader mode)
+    Update {
+        device_id: Option<String>,
+        #[arg(long)]
+        firmware_dir: Option<PathBuf>,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let cli = Cli::parse();
+    let out = Output::new(cli.json);
+
+    if let Err(e) = run(cli.command, cli.mock_device, &out).await {
+        out.error(&e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(command: Command, mock_device: Option<PathBuf>, out: &Output) -> Result<()> {
+    // Never touch USB while the GUI vault (or another CLI) owns the devices
+    let _lock = InstanceLock::acquire(OWNER_CLI).map_err(|holder| {
+        anyhow!(
+            "KeepKey devices are in use by {} (pid {}); close it before using kkvault-cli",
+            holder.owner,
+            holder.pid
+        )
+    })?;
+
+    let queue_manager = new_queue_manager();
+    let devices = match mock_device {
+        Some(path) => Devices::mock(MockDevice::load(&path)?, &queue_manager).await,
+        None => Devices::Usb,
+    };
+
+    let result = match command {
+        Command::Devices { action: DevicesAction::List } => devices_list(&devices, out),
+        Command::Device { action: DeviceAction::Features { device_id } } => {
+            device_features(&device_id, &queue_manager, out).await
+        }
+        Command::Address { action: AddressAction::Get { caip, path, display, device, script_type } } => {
+            let device_id = devices.resolve(device)?;
+            address_get(&device_id, &caip, &path, display, &script_type, &queue_manager, out).await
+        }
+        Command::Xpub { action: XpubAction::Get { path, coin, script_type, device } } => {
+            let device_id = devices.resolve(device)?;
+            xpub_get(&device_id, &path, &coin, &script_type, &queue_manager, out).await
+        }
+        Command::Tx { action: TxAction::Sign { file, device } } => {
+            let device_id = devices.resolve(device)?;
+            let queue = vault_core::get_or_create_device_queue(&device_id, &queue_manager)
+                .await
+                .map_err(|e| anyhow!(e))?;
+            let request = tx::load_request(&file)?;
+            sign_with_audit(&device_id, &queue, request).await.map(|signed| out.record(&signed))
+        }
+        Command::Firmware { action: FirmwareAction::Check { device_id, firmware_dir } } => {
+            let device_id = devices.resolve(device_id)?;
+            firmware_check(&device_id, firmware_dir, &queue_manager, out).await
+        }
+        Command::Firmware { action: FirmwareAction::Update { device_id, firmware_dir } } => {
+            let device_id = devices.resolve(device_id)?;
+            firmware_update(&device_id, firmware_dir, &queue_manager, out).await
+        }
+    };
+
+    // Release device handles before the lock goes away
+    for (_, handle) in queue_manager.lock().await.drain() {
+        let _ = handle.shutdown().await;
+    }
+
+    result
+}
+
+/// Sign a request, recording it in the vault's signing audit as a CLI request.
+///
+/// The CLI has no way to show the vault's limit confirmation, so a request
+/// over a limit configured for `cli` is refused outright.
+async fn sign_with_audit(device_id: &str, queue: &DeviceQueueHandle, request: tx::SignRequest) -> Result<serde_json::Value> {
+    let db = keepkey_db::Database::new().await.context("Failed to open vault database")?;
+    let confirmations = new_limit_confirmations();
+    let caip = request.caip();
+    let price = db.get_asset_price_usd(&caip).await.unwrap_or(None);
+    let value_usd = price.zip(request.amount_sent()).map(|(price, amount)| price * amount);
+    let audit = keepkey_db::SigningAuditInput {
+        origin: SigningOrigin::Cli.key(),
+        device_id: device_id.to_string(),
+        wallet_fingerprint: String::new(),
+        caip,
+        value_usd,
+    };
+    let ticket = signing_origin::authorize_signing(&db, &confirmations, &SigningOrigin::Cli, audit, Duration::ZERO, |_, _| {
+        log::warn!("CLI signing limit reached; raise or clear the 'cli' limit in the vault");
+    })
+    .await
+    .map_err(|e| anyhow!(e))?;
+
+    let result = tx::sign_request(queue, request).await;
+    let outcome = match &result {
+        Ok(signed) => Ok(signed.get("txid").and_then(|t| t.as_str())),
+        Err(_) => Err("signing failed"),
+    };
+    signing_origin::complete_signing(&db, &ticket, outcome).await;
+    result
+}
+
+/// Where the CLI finds KeepKeys
+enum Devices {
+    Usb,
+    /// A scripted device from --mock-device, already in the queue manager
+    Mock(FriendlyUsbDevice),
+}
+
+impl Devices {
+    async fn mock(device: MockDevice, queue_manager: &DeviceQueueManager) -> Self {
+        let usb_device = device.usb_device();
+        let handle = DeviceQueueFactory::spawn_worker_with_transport(
+            device.device_id.clone(),
+            usb_device.clone(),
+            Box::new(MockTransport::new(device)),
+        );
+        queue_manager.lock().await.insert(usb_device.unique_id.clone(), handle);
+        Devices::Mock(usb_device)
+    }
+
+    fn connected(&self) -> Vec<FriendlyUsbDevice> {
+        match self {
+            Devices::Usb => keepkey_rust::features::list_connected_devices()
+                .into_iter()
+                .filter(|d| d.is_keepkey)
+                .collect(),
+            Devices::Mock(device) => vec![device.clone()],
+        }
+    }
+
+    /// Use the given device id, or the only connected KeepKey when none is given
+    fn resolve(&self, device: Option<String>) -> Result<String> {
+        if let Some(id) = device {
+            return Ok(id);
+        }
+        let devices = self.connected();
+        match devices.as_slice() {
+            [only] => Ok(only.unique_id.clone()),
+            [] => Err(anyhow!("No KeepKey connected")),
+            _ => Err(anyhow!("{} KeepKeys connected; pass --device <id>", devices.len())),
+        }
+    }
+}
+
+/// InputScriptType of the key GetAddress and GetPublicKey derive
+fn parse_script_type(script_type: &str) -> Result<i32> {
+    match ScriptType::from_str(script_type)? {
+        script_type @ (ScriptType::P2PKH | ScriptType::P2SHP2WPKH | ScriptType::P2WPKH) => {
+            Ok(script_type.to_proto_input())
+        }
+        other => Err(anyhow!("Unsupported script type for addresses: {:?}", other)),
+    }
+}
+
+fn devices_list(devices: &Devices, out: &Output) -> Result<()> {
+    let devices = devices.connected();
+
+    out.table(
+        &["Device ID", "Name", "Serial", "VID:PID"],
+        devices
+            .iter()
+            .map(|d| {
+                vec![
+                    d.unique_id.clone(),
+                    d.name.clone(),
+                    d.serial_number.clone().unwrap_or_default(),
+                    format!("{:04x}:{:04x}", d.vid, d.pid),
+                ]
+            })
+            .collect(),
+        &serde_json::to_value(&devices)?,
+    );
+    Ok(())
+}
+
+async fn device_features(device_id: &str, queue_manager: &DeviceQueueManager, out: &Output) -> Result<()> {
+    let features = vault_core::get_device_features(device_id, queue_manager)
+        .await
+        .map_err(|e| anyhow!(e))?;
+    out.record(&serde_json::to_value(&features)?);
+    Ok(())
+}
+
+async fn address_get(
+    device_id: &str,
+    caip: &str,
+    path: &str,
+    display: bool,
+    script_type: &str,
+    queue_manager: &DeviceQueueManager,
+    out: &Output,
+) -> Result<()> {
+    let address_n = parse_derivation_path(path).map_err(|e| anyhow!(e))?;
+    let queue = vault_core::get_or_create_device_queue(device_id, queue_manager)
+        .await
+        .map_err(|e| anyhow!(e))?;
+
+    let address = match network_family(caip).map_err(|e| anyhow!(e))? {
+        NetworkFamily::Utxo { coin_name } => {
+            queue
+                .get_address(address_n.clone(), coin_name, Some(parse_script_type(script_type)?), Some(display))
+                .await?
+        }
+        NetworkFamily::Evm { .. } => {
+            let address =
+                keepkey_rust::chains::ethereum::address::get_ethereum_address(&queue, &address_n, display).await?;
+            format!("{:?}", address)
+        }
+    };
+
+    out.record(&json!({
+        "caip": caip,
+        "path": format_derivation_path(&address_n),
+        "address": address,
+    }));
+    Ok(())
+}
+
+async fn xpub_get(
+    device_id: &str,
+    path: &str,
+    coin: &str,
+    script_type: &str,
+    queue_manager: &DeviceQueueManager,
+    out: &Output,
+) -> Result<()> {
+    let address_n = parse_derivation_path(path).map_err(|e| anyhow!(e))?;
+    let queue = vault_core::get_or_create_device_queue(device_id, queue_manager)
+        .await
+        .map_err(|e| anyhow!(e))?;
+
+    let msg = keepkey_rust::messages::GetPublicKey {
+        address_n: address_n.clone(),
+        ecdsa_curve_name: Some("secp256k1".to_string()),
+        show_display: Some(false),
+        coin_name: Some(coin.to_string()),
+        script_type: Some(parse_script_type(script_type)?),
+    };
+
+    let xpub = match queue.send_raw(keepkey_rust::messages::Message::GetPublicKey(msg), false).await? {
+        keepkey_rust::messages::Message::PublicKey(pubkey) => {
+            pubkey.xpub.ok_or_else(|| anyhow!("No xpub in response"))?
+        }
+        other => return Err(anyhow!("Unexpected response: {:?}", other.message_type())),
+    };
+
+    out.record(&json!({
+        "path": format_derivation_path(&address_n),
+        "coin": coin,
+        "xpub": xpub,
+    }));
+    Ok(())
+}
+
+/// Locate the bundled firmware directory containing releases.json
+fn firmware_dir(explicit: Option<PathBuf>) -> Result<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Some(dir) = explicit {
+        candidates.push(dir);
+    }
+    candidates.push(PathBuf::from("firmware"));
+    if let Ok(exe) = std::env::current_exe() {
+        if let Some(dir) = exe.parent() {
+            candidates.push(dir.join("firmware"));
+            candidates.push(dir.join("../Resources/firmware"));
+        }
+    }
+
+    candidates
+        .into_iter()
+        .find(|dir| dir.join("releases.json").exists())
+        .ok_or_else(|| anyhow!("releases.json not found; pass --firmware-dir"))
+}
+
+fn latest_firmware(dir: &std::path::Path) -> Result<(String, PathBuf)> {
+    let releases = ReleasesCatalog::load(&dir.join("releases.json")).map_err(|e| anyhow!(e))?;
+    let latest = releases.latest_image(ImageKind::Firmware);
+    Ok((releases.latest_version(ImageKind::Firmware), dir.join(&latest.url)))
+}
+
+async fn firmware_check(
+    device_id: &str,
+    explicit_dir: Option<PathBuf>,
+    queue_manager: &DeviceQueueManager,
+    out: &Output,
+) -> Result<()> {
+    let (latest, _) = latest_firmware(&firmware_dir(explicit_dir)?)?;
+    let features = vault_core::get_device_features(device_id, queue_manager)
+        .await
+        .map_err(|e| anyhow!(e))?;
+    // A version that cannot be compared is reported as needing an update
+    let comparison = compare_versions(&features.version, &latest);
+
+    out.record(&json!({
+        "device_id": device_id,
+        "current_version": features.version,
+        "latest_version": latest,
+        "bootloader_mode": features.bootloader_mode,
+        "version_comparison": comparison,
+        "update_available": comparison.needs_attention(),
+    }));
+    Ok(())
+}
+
+async fn firmware_update(
+    device_id: &str,
+    explicit_dir: Option<PathBuf>,
+    queue_manager: &DeviceQueueManager,
+    out: &Output,
+) -> Result<()> {
+    let (latest, firmware_path) = latest_firmware(&firmware_dir(explicit_dir)?)?;
+    let features = vault_core::get_device_features(device_id, queue_manager)
+        .await
+        .map_err(|e| anyhow!(e))?;
+
+    if !features.bootloader_mode {
+        return Err(anyhow!("Device must be in bootloader mode to update firmware"));
+    }
+
+    let firmware_bytes = std::fs::read(&firmware_path)
+        .with_context(|| format!("Failed to read firmware {:?}", firmware_path))?;
+
+    eprintln!("Flashing firmware {} to {} - confirm on the device", latest, device_id);
+    let queue = vault_core::get_or_create_device_queue(device_id, queue_manager)
+        .await
+        .map_err(|e| anyhow!(e))?;
+    let success = queue.update_firmware(latest.clone(), firmware_bytes).await?;
+
+    out.record(&json!({
+        "device_id": device_id,
+        "version": latest,
+        "success": success,
+    }));
+    Ok(())
+}
@@ -0,0 +1,63 @@
+// output.rs - Human-readable tables by default, JSON with --json
+
+use comfy_table::{presets::UTF8_FULL, Table};
+use serde_json::Value;
+
+pub struct Output {
+    json: bool,
+}
+
+impl Output {
+    pub fn new(json: bool) -> Self {
+        Self { json }
+    }
+
+    /// Print a list; `value` is what JSON mode emits
+    pub fn table(&self, header: &[&str], rows: Vec<Vec<String>>, value: &Value) {
+        if self.json {
+            println!("{}", value);
+            return;
+        }
+
+        let mut table = Table::new();
+        table.load_preset(UTF8_FULL);
+        table.set_header(header.to_vec());
+        for row in rows {
+            table.add_row(row);
+        }
+        println!("{table}");
+    }
+
+    /// Print a single object as a two-column field/value table
+    pub fn record(&self, value: &Value) {
+        if self.json {
+            println!("{}", value);
+            return;
+        }
+
+        let mut table = Table::new();
+        table.load_preset(UTF8_FULL);
+        table.set_header(vec!["Field", "Value"]);
+        if let Some(map) = value.as_object() {
+            for (key, field) in map {
+                let rendered = match field {
+                    Value::String(s) => s.clone(),
+                    Value::Null => "-".to_string(),
+                    other => other.to_string(),
+                };
+                table.add_row(vec![key.clone(), rendered]);
+            }
+        } else {
+            table.add_row(vec!["value".to_string(), value.to_string()]);
+        }
+        println!("{table}");
+    }
+
+    pub fn error(&self, err: &anyhow::Error) {
+        if self.json {
+            println!("{}", serde_json::json!({ "error": format!("{:#}", err) }));
+        } else {
+            eprintln!("error: {:#}", err);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// tx.rs - `tx sign --file request.json`
+//
+// Request files are tagged by chain:
+//   { "chain": "bitcoin", "network": "mainnet", "inputs": [...], "outputs": [...], "prev_txs": [...] }
+//   { "chain": "ethereum", "path": "m/44'/60'/0'/0/0", "nonce": "0x0", ... }
+//
+// `prev_txs` holds the raw hex of every transaction spent by a p2pkh input;
+// the device asks for them to check the input amounts.
+
+use anyhow::{anyhow, Context, Result};
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::Network;
+use ethereum_types::{Address, U256};
+use keepkey_rust::chains::bitcoin::{sign_bitcoin_transaction, BitcoinTxInput, BitcoinTxOutput, PrevTransactions, ScriptType};
+use keepkey_rust::chains::ethereum::transaction::{sign_eip1559_transaction, sign_ethereum_transaction, EthereumTransaction};
+use keepkey_rust::device_queue::DeviceQueueHandle;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::str::FromStr;
+use vault_core::paths::parse_derivation_path;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "chain", rename_all = "lowercase")]
+pub enum SignRequest {
+    Bitcoin {
+        #[serde(default = "default_network")]
+        network: String,
+        inputs: Vec<UtxoInput>,
+        outputs: Vec<UtxoOutput>,
+        #[serde(default)]
+        prev_txs: Vec<String>,
+    },
+    Ethereum {
+        path: String,
+        nonce: String,
+        gas_price: String,
+        gas_limit: String,
+        to: Option<String>,
+        value: String,
+        #[serde(default)]
+        data: String,
+        chain_id: u64,
+        max_fee_per_gas: Option<String>,
+        max_priority_fee_per_gas: Option<String>,
+    },
+}
+
+fn default_network() -> String {
+    "mainnet".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UtxoInput {
+    pub txid: String,
+    pub vout: u32,
+    pub path: String,
+    pub amount: u64,
+    #[serde(default = "default_script_type")]
+    pub script_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UtxoOutput {
+    pub address: Option<String>,
+    pub path: Option<String>,
+    pub amount: u64,
+    #[serde(default = "default_script_type")]
+    pub script_type: String,
+}
+
+fn default_script_type() -> String {
+    "p2pkh".to_string()
+}
+
+fn path(p: &str) -> Result<Vec<u32>> {
+    parse_derivation_path(p).map_err(|e| anyhow!(e))
+}
+
+fn u256(field: &str, value: &str) -> Result<U256> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16).ok(),
+        None => U256::from_dec_str(value).ok(),
+    };
+    parsed.ok_or_else(|| anyhow!("Invalid {}: {}", field, value))
+}
+
+impl SignRequest {
+    /// CAIP of the asset being spent, for the signing audit
+    pub fn caip(&self) -> String {
+        match self {
+            SignRequest::Bitcoin { network, .. } if network == "testnet" => {
+                "bip122:000000000933ea01ad0ee984209779ba/slip44:1".to_string()
+            }
+            SignRequest::Bitcoin { .. } => "bip122:000000000019d6689c085ae165831e93/slip44:0".to_string(),
+            SignRequest::Ethereum { chain_id, .. } => format!("eip155:{}/slip44:60", chain_id),
+        }
+    }
+
+    /// Amount sent to others in whole coins (change outputs excluded), for
+    /// pricing the request against the CLI's spending limit
+    pub fn amount_sent(&self) -> Option<f64> {
+        match self {
+            SignRequest::Bitcoin { outputs, .. } => {
+                let sats: u128 = outputs.iter().filter(|o| o.path.is_none()).map(|o| o.amount as u128).sum();
+                Some(sats as f64 / 100_000_000.0)
+            }
+            SignRequest::Ethereum { value, .. } => {
+                let wei = u256("value", value).ok()?;
+                wei.to_string().parse::<f64>().ok().map(|wei| wei / 1e18)
+            }
+        }
+    }
+}
+
+/// Parse a sign request file
+pub fn load_request(file: &Path) -> Result<SignRequest> {
+    let contents = std::fs::read_to_string(file).with_context(|| format!("Failed to read {:?}", file))?;
+    serde_json::from_str(&contents).context("Invalid sign request")
+}
+
+/// Sign a parsed request on the device
+pub async fn sign_request(queue: &DeviceQueueHandle, request: SignRequest) -> Result<Value> {
+    match request {
+        SignRequest::Bitcoin { network, inputs, outputs, prev_txs } => {
+            let network = match network.as_str() {
+                "mainnet" => Network::Bitcoin,
+                "testnet" => Network::Testnet,
+                other => return Err(anyhow!("Unsupported bitcoin network: {}", other)),
+            };
+            let inputs = inputs
+                .into_iter()
+                .map(|i| {
+                    Ok(BitcoinTxInput {
+                        prev_hash: hex::decode(&i.txid).context("Invalid txid")?,
+                        prev_index: i.vout,
+                        address_n: path(&i.path)?,
+                        amount: i.amount,
+                        script_type: ScriptType::from_str(&i.script_type)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let outputs = outputs
+                .into_iter()
+                .map(|o| {
+                    Ok(BitcoinTxOutput {
+                        address: o.address,
+                        address_n: o.path.as_deref().map(path).transpose()?.unwrap_or_default(),
+                        amount: o.amount,
+                        script_type: ScriptType::from_str(&o.script_type)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let prev_txs = prev_txs
+                .iter()
+                .map(|raw| hex::decode(raw).context("Invalid previous transaction hex"))
+                .collect::<Result<Vec<_>>>()?;
+            let prev_txs = PrevTransactions::from_raw(&prev_txs)?;
+
+            let tx = sign_bitcoin_transaction(queue, inputs, outputs, &prev_txs, network).await?;
+            Ok(json!({
+                "chain": "bitcoin",
+                "txid": tx.txid().to_string(),
+                "serialized": serialize_hex(&tx),
+            }))
+        }
+        SignRequest::Ethereum {
+            path: p,
+            nonce,
+            gas_price,
+            gas_limit,
+            to,
+            value,
+            data,
+            chain_id,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        } => {
+            let tx = EthereumTransaction {
+                address_n: path(&p)?,
+                nonce: u256("nonce", &nonce)?,
+                gas_price: u256("gas_price", &gas_price)?,
+                gas_limit: u256("gas_limit", &gas_limit)?,
+                to: to
+                    .map(|a| Address::from_str(a.trim_start_matches("0x")).map_err(|_| anyhow!("Invalid to address: {}", a)))
+                    .transpose()?,
+                value: u256("value", &value)?,
+                data: hex::decode(data.trim_start_matches("0x")).context("Invalid data")?,
+                chain_id,
+                max_fee_per_gas: max_fee_per_gas.map(|v| u256("max_fee_per_gas", &v)).transpose()?,
+                max_priority_fee_per_gas: max_priority_fee_per_gas
+                    .map(|v| u256("max_priority_fee_per_gas", &v))
+                    .transpose()?,
+            };
+
+            let signed = if tx.max_fee_per_gas.is_some() {
+                sign_eip1559_transaction(queue, tx).await?
+            } else {
+                sign_ethereum_transaction(queue, tx).await?
+            };
+            Ok(json!({
+                "chain": "ethereum",
+                "serialized": format!("0x{}", hex::encode(signed)),
+            }))
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// CLI integration tests - run the built binary with HOME pointed at a temp dir
+// so the instance lock and database never touch the developer's real vault.
+// Device commands run against a scripted device given with --mock-device.
+
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn cli(home: &TempDir) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_kkvault-cli"));
+    cmd.env("HOME", home.path()).env("USERPROFILE", home.path());
+    cmd
+}
+
+/// Run the CLI in JSON mode against a scripted device running `version`
+fn mock_cli(home: &TempDir, version: &str, args: &[&str]) -> Output {
+    let fixture = home.path().join("mock-device.json");
+    let device = serde_json::json!({
+        "device_id": "MOCK0001",
+        "label": "CI KeepKey",
+        "version": version,
+        "initialized": true,
+        "addresses": {
+            "m/44'/60'/0'/0/0": "0x73d0385f4d8e00c5e6504c6030f47bf6212736a8",
+            "m/84'/0'/0'/0/0": "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu",
+        },
+        "xpubs": {
+            "m/84'/0'/0'": "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+        },
+    });
+    std::fs::write(&fixture, device.to_string()).unwrap();
+    cli(home).arg("--json").arg("--mock-device").arg(&fixture).args(args).output().unwrap()
+}
+
+/// The JSON the CLI printed last
+fn json_output(output: &Output) -> serde_json::Value {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    serde_json::from_str(stdout.lines().last().unwrap_or_default()).unwrap()
+}
+
+#[test]
+fn test_help_lists_subcommands() {
+    let home = TempDir::new().unwrap();
+    let output = cli(&home).arg("--help").output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for sub in ["devices", "device", "address", "xpub", "tx", "firmware"] {
+        assert!(stdout.contains(sub), "missing subcommand {} in help:\n{}", sub, stdout);
+    }
+}
+
+#[test]
+fn test_refuses_while_vault_holds_lock() {
+    let home = TempDir::new().unwrap();
+    let keepkey_dir = home.path().join(".keepkey");
+    std::fs::create_dir_all(&keepkey_dir).unwrap();
+
+    // This test process stands in for a running GUI vault
+    let holder = serde_json::json!({
+        "pid": std::process::id(),
+        "owner": "keepkey-vault",
+        "acquired_at": 0,
+    });
+    std::fs::write(keepkey_dir.join("vault.lock"), holder.to_string()).unwrap();
+
+    let output = cli(&home).args(["--json", "devices", "list"]).output().unwrap();
+    assert!(!output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let error: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert!(error["error"].as_str().unwrap().contains("keepkey-vault"));
+    // The GUI's lock must survive the refused run
+    assert!(keepkey_dir.join("vault.lock").exists());
+}
+
+#[test]
+fn test_invalid_path_is_reported() {
+    let home = TempDir::new().unwrap();
+    let output = cli(&home)
+        .args(["--json", "address", "get", "--caip", "eip155:1", "--path", "m/44'/x", "--device", "none"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Invalid path segment"));
+    // The CLI's own lock is released on exit
+    assert!(!home.path().join(".keepkey").join("vault.lock").exists());
+}
+
+#[test]
+fn test_mock_device_is_listed_and_answers() {
+    let home = TempDir::new().unwrap();
+    let listed = mock_cli(&home, "7.9.1", &["devices", "list"]);
+    assert!(listed.status.success());
+    assert_eq!(json_output(&listed)[0]["unique_id"], "MOCK0001");
+
+    let features = mock_cli(&home, "7.9.1", &["device", "features", "MOCK0001"]);
+    assert!(features.status.success());
+    let features = json_output(&features);
+    assert_eq!(features["version"], "7.9.1");
+    assert_eq!(features["label"], "CI KeepKey");
+
+    // With one device connected, --device can be left out
+    let evm = mock_cli(&home, "7.9.1", &["address", "get", "--caip", "eip155:1", "--path", "m/44'/60'/0'/0/0"]);
+    assert!(evm.status.success());
+    assert_eq!(json_output(&evm)["address"], "0x73d0385f4d8e00c5e6504c6030f47bf6212736a8");
+
+    let btc = mock_cli(&home, "7.9.1", &[
+        "address", "get", "--caip", "bip122:000000000019d6689c085ae165831e93",
+        "--path", "m/84'/0'/0'/0/0", "--script-type", "p2wpkh",
+    ]);
+    assert!(btc.status.success());
+    assert_eq!(json_output(&btc)["address"], "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+
+    let xpub = mock_cli(&home, "7.9.1", &["xpub", "get", "--path", "m/84'/0'/0'", "--script-type", "p2wpkh"]);
+    assert!(xpub.status.success());
+    assert!(json_output(&xpub)["xpub"].as_str().unwrap().starts_with("xpub68Gmy5Ed"));
+
+    // A path the device was not asked to script fails instead of inventing an answer
+    let unknown = mock_cli(&home, "7.9.1", &["address", "get", "--caip", "eip155:1", "--path", "m/44'/60'/0'/0/1"]);
+    assert!(!unknown.status.success());
+    assert!(json_output(&unknown)["error"].is_string());
+}
+
+#[test]
+fn test_bitcoin_sign_request_carries_prev_txs() {
+    let home = TempDir::new().unwrap();
+    // The genesis coinbase, spent by the request's p2pkh input
+    let genesis = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+    let sign = |prev_txs: &[&str]| {
+        let request = serde_json::json!({
+            "chain": "bitcoin",
+            "inputs": [{
+                "txid": "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+                "vout": 0,
+                "path": "m/44'/0'/0'/0/0",
+                "amount": 5_000_000_000u64,
+            }],
+            "outputs": [{ "address": "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu", "amount": 4_999_990_000u64 }],
+            "prev_txs": prev_txs,
+        });
+        let file = home.path().join("request.json");
+        std::fs::write(&file, request.to_string()).unwrap();
+        let output = mock_cli(&home, "7.9.1", &["tx", "sign", "--file", file.to_str().unwrap()]);
+        assert!(!output.status.success());
+        json_output(&output)["error"].as_str().unwrap().to_string()
+    };
+
+    // A malformed previous transaction is refused before the device is asked
+    assert!(sign(&["00"]).contains("Invalid previous transaction"));
+
+    // A well-formed request reaches the device, which has no scripted SignTx
+    let error = sign(&[genesis]);
+    assert!(error.contains("The mock device has no"), "{}", error);
+}
+
+#[test]
+fn test_firmware_check_compares_versions() {
+    let home = TempDir::new().unwrap();
+    let firmware_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../keepkey-usb/firmware");
+    let check = |version: &str| {
+        let output = mock_cli(&home, version, &["firmware", "check", "--firmware-dir", firmware_dir]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+        json_output(&output)
+    };
+
+    // 7.9.1 against the catalog's 7.10.0 is older by semver, not by string
+    let outdated = check("7.9.1");
+    assert_eq!(outdated["latest_version"], "7.10.0");
+    assert_eq!(outdated["version_comparison"], "less");
+    assert_eq!(outdated["update_available"], true);
+
+    let current = check("7.10.0");
+    assert_eq!(current["version_comparison"], "equal");
+    assert_eq!(current["update_available"], false);
+}
+
+#[test]
+fn test_firmware_catalog_fails_on_hash_mismatch() {
+    let dir = TempDir::new().unwrap();
+    let release = dir.path().join("v6.0.0");
+    std::fs::create_dir_all(&release).unwrap();
+    std::fs::copy(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../keepkey-usb/firmware/v6.0.0/firmware.keepkey.bin"),
+        release.join("firmware.keepkey.bin"),
+    )
+    .unwrap();
+    let published = "d380357b7403064d7b1ea963dc56032239541a21ef0b7e08082fb36ed470de82";
+    let catalog = |hash: &str| serde_json::json!({ "hashes": { "firmware": { hash: "v6.0.0" }, "bootloader": {} } });
+
+    let run = || {
+        Command::new(env!("CARGO_BIN_EXE_firmware-catalog"))
+            .args(["--json", "--dir"])
+            .arg(dir.path())
+            .output()
+            .unwrap()
+    };
+
+    std::fs::write(dir.path().join("releases.json"), catalog(published).to_string()).unwrap();
+    assert!(run().status.success());
+
+    std::fs::write(dir.path().join("releases.json"), catalog("00".repeat(32).as_str()).to_string()).unwrap();
+    let output = run();
+    assert_eq!(output.status.code(), Some(1));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["mismatches"][0]["computed"], published);
+}
@@ -0,0 +1,332 @@
+// accessibility.rs - Plain-state device summaries for screen readers
+//
+// Toast events arrive too quickly to be useful through a screen reader, so
+// this describes the current state instead: one sentence per fact, blocking
+// actions first. Each sentence carries a message code and parameters for the
+// frontend to localize, plus an English rendering as a fallback. Built from
+// the same registry records the tray uses, so it never talks to a device.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use crate::tray::{assemble_tray_summary, TrayDeviceState};
+
+/// Minimum spacing of `accessibility:summary-changed` events
+pub const SUMMARY_MIN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How urgent a sentence is; lower sorts first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// The user must act before the device can be used
+    Blocking,
+    /// Something is running
+    Activity,
+    Informational,
+}
+
+/// Something the user has to do on a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingAction {
+    EnterPin,
+    CompleteSetup,
+    LeaveBootloader,
+    /// Another application holds the device (observer mode)
+    CloseOtherApplication,
+}
+
+/// A long-running operation and how far along it is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OperationPhase {
+    /// Message code of the operation, e.g. "migrating_data"
+    pub operation: String,
+    pub step: Option<usize>,
+    pub total: Option<usize>,
+}
+
+/// Current state of one connected device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceSummary {
+    pub device_id: String,
+    pub name: String,
+    pub connected: bool,
+    pub locked: bool,
+    pub pending_action: Option<PendingAction>,
+    pub operation: Option<OperationPhase>,
+}
+
+/// Summaries for every connected device
+pub type DeviceSummaries = Vec<DeviceSummary>;
+
+/// Localizable message codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum MessageCode {
+    NoDeviceConnected,
+    DeviceRequiresPin,
+    DeviceRequiresSetup,
+    DeviceInBootloader,
+    DeviceInUseElsewhere,
+    OperationInProgress,
+    OperationProgress,
+    DeviceReady,
+}
+
+impl MessageCode {
+    /// English template; `{name}` placeholders are filled from the parameters
+    fn template(self) -> &'static str {
+        match self {
+            MessageCode::NoDeviceConnected => "No KeepKey is connected.",
+            MessageCode::DeviceRequiresPin => "{device} requires PIN entry.",
+            MessageCode::DeviceRequiresSetup => "{device} needs to be set up.",
+            MessageCode::DeviceInBootloader => "{device} is in bootloader mode.",
+            MessageCode::DeviceInUseElsewhere => "{device} is in use by another application and is read-only.",
+            MessageCode::OperationInProgress => "{device}: {operation} in progress.",
+            MessageCode::OperationProgress => "{device}: {operation}, step {step} of {total}.",
+            MessageCode::DeviceReady => "{device} is connected and unlocked.",
+        }
+    }
+}
+
+/// English names of operation codes
+fn operation_name(operation: &str) -> &str {
+    match operation {
+        "migrating_data" => "migrating data from the previous device",
+        "updating_firmware" => "firmware update",
+        other => other,
+    }
+}
+
+/// One sentence of the summary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SummarySentence {
+    pub priority: Priority,
+    pub code: MessageCode,
+    pub device_id: Option<String>,
+    pub params: BTreeMap<String, String>,
+    /// English rendering
+    pub text: String,
+}
+
+impl SummarySentence {
+    fn new(priority: Priority, code: MessageCode, device: Option<&DeviceSummary>, extra: &[(&str, String)]) -> Self {
+        let mut params = BTreeMap::new();
+        if let Some(device) = device {
+            params.insert("device".to_string(), device.name.clone());
+        }
+        for (key, value) in extra {
+            params.insert(key.to_string(), value.clone());
+        }
+        let mut text = code.template().to_string();
+        for (key, value) in &params {
+            let value = if key == "operation" { operation_name(value) } else { value.as_str() };
+            text = text.replace(&format!("{{{}}}", key), value);
+        }
+        Self { priority, code, device_id: device.map(|d| d.device_id.clone()), params, text }
+    }
+}
+
+/// Device summaries from registry records, the connected set, observed
+/// devices and running operations
+pub fn summarize_devices(
+    registry: &[serde_json::Value],
+    connected: &HashSet<String>,
+    observed: &HashSet<String>,
+    operations: &HashMap<String, OperationPhase>,
+) -> DeviceSummaries {
+    assemble_tray_summary(registry, connected, &HashMap::new())
+        .devices
+        .into_iter()
+        .map(|device| {
+            let pending_action = if observed.contains(&device.device_id) {
+                Some(PendingAction::CloseOtherApplication)
+            } else {
+                match device.state {
+                    TrayDeviceState::Bootloader => Some(PendingAction::LeaveBootloader),
+                    TrayDeviceState::NeedsSetup => Some(PendingAction::CompleteSetup),
+                    TrayDeviceState::Locked => Some(PendingAction::EnterPin),
+                    TrayDeviceState::Ready => None,
+                }
+            };
+            DeviceSummary {
+                operation: operations.get(&device.device_id).cloned(),
+                locked: device.state == TrayDeviceState::Locked,
+                connected: true,
+                name: device.label,
+                device_id: device.device_id,
+                pending_action,
+            }
+        })
+        .collect()
+}
+
+/// Ordered sentences: blocking actions, then running operations, then
+/// informational state; devices by name within each group
+pub fn build_summary(devices: &DeviceSummaries) -> Vec<SummarySentence> {
+    if devices.is_empty() {
+        return vec![SummarySentence::new(Priority::Informational, MessageCode::NoDeviceConnected, None, &[])];
+    }
+
+    let mut sentences = Vec::new();
+    for device in devices {
+        let pending = device.pending_action.map(|action| match action {
+            PendingAction::EnterPin => MessageCode::DeviceRequiresPin,
+            PendingAction::CompleteSetup => MessageCode::DeviceRequiresSetup,
+            PendingAction::LeaveBootloader => MessageCode::DeviceInBootloader,
+            PendingAction::CloseOtherApplication => MessageCode::DeviceInUseElsewhere,
+        });
+        if let Some(code) = pending {
+            sentences.push(SummarySentence::new(Priority::Blocking, code, Some(device), &[]));
+        }
+
+        if let Some(op) = &device.operation {
+            let sentence = match (op.step, op.total) {
+                (Some(step), Some(total)) => SummarySentence::new(
+                    Priority::Activity,
+                    MessageCode::OperationProgress,
+                    Some(device),
+                    &[("operation", op.operation.clone()), ("step", step.to_string()), ("total", total.to_string())],
+                ),
+                _ => SummarySentence::new(
+                    Priority::Activity,
+                    MessageCode::OperationInProgress,
+                    Some(device),
+                    &[("operation", op.operation.clone())],
+                ),
+            };
+            sentences.push(sentence);
+        }
+
+        if pending.is_none() && device.operation.is_none() {
+            sentences.push(SummarySentence::new(Priority::Informational, MessageCode::DeviceReady, Some(device), &[]));
+        }
+    }
+
+    let name = |s: &SummarySentence| s.params.get("device").cloned().unwrap_or_default();
+    sentences.sort_by(|a, b| {
+        a.priority
+            .cmp(&b.priority)
+            .then_with(|| name(a).cmp(&name(b)))
+            .then_with(|| a.device_id.cmp(&b.device_id))
+    });
+    sentences
+}
+
+/// Coalesces rebuilds so summary events go out at most once per interval and
+/// only when the sentences changed
+#[derive(Debug)]
+pub struct SummaryThrottle {
+    min_interval: Duration,
+    last_emit: Option<Instant>,
+    last_summary: Option<Vec<SummarySentence>>,
+}
+
+impl SummaryThrottle {
+    pub fn new(min_interval: Duration) -> Self {
+        Self { min_interval, last_emit: None, last_summary: None }
+    }
+
+    /// Earliest time the next event may be emitted
+    pub fn next_allowed(&self, now: Instant) -> Instant {
+        self.last_emit.map(|t| t + self.min_interval).filter(|t| *t > now).unwrap_or(now)
+    }
+
+    /// Record a rebuilt summary; returns it if it should be emitted
+    pub fn offer(&mut self, summary: Vec<SummarySentence>, now: Instant) -> Option<Vec<SummarySentence>> {
+        if self.last_summary.as_ref() == Some(&summary) || self.next_allowed(now) > now {
+            return None;
+        }
+        self.last_emit = Some(now);
+        self.last_summary = Some(summary.clone());
+        Some(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(device_id: &str, label: &str, pin_cached: bool, setup_complete: bool) -> serde_json::Value {
+        json!({
+            "device_id": device_id,
+            "label": label,
+            "bootloader_mode": false,
+            "initialized": true,
+            "setup_complete": setup_complete,
+            "pin_protection": true,
+            "features": json!({ "pinCached": pin_cached }).to_string(),
+        })
+    }
+
+    fn fixed_state() -> DeviceSummaries {
+        let registry = vec![
+            record("dev1", "Travel", true, true),
+            record("dev2", "KeepKey Office", false, true),
+            record("dev3", "Spare", true, false),
+            record("dev4", "Backup", true, true),
+            record("dev5", "Archive", true, true),
+        ];
+        let connected: HashSet<String> = ["dev1", "dev2", "dev3", "dev4", "dev5"].iter().map(|s| s.to_string()).collect();
+        let observed: HashSet<String> = ["dev5".to_string()].into_iter().collect();
+        let operations: HashMap<String, OperationPhase> = [(
+            "dev1".to_string(),
+            OperationPhase { operation: "migrating_data".to_string(), step: Some(3), total: Some(7) },
+        )].into_iter().collect();
+        summarize_devices(&registry, &connected, &observed, &operations)
+    }
+
+    #[test]
+    fn test_summary_snapshot() {
+        let texts: Vec<String> = build_summary(&fixed_state()).into_iter().map(|s| s.text).collect();
+        assert_eq!(texts, vec![
+            "Archive is in use by another application and is read-only.",
+            "KeepKey Office requires PIN entry.",
+            "Spare needs to be set up.",
+            "Travel: migrating data from the previous device, step 3 of 7.",
+            "Backup is connected and unlocked.",
+        ]);
+    }
+
+    #[test]
+    fn test_summary_codes_are_stable() {
+        let summary = build_summary(&fixed_state());
+        let codes: Vec<String> = summary
+            .iter()
+            .map(|s| serde_json::to_value(s.code).unwrap().as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(codes, vec![
+            "device_in_use_elsewhere",
+            "device_requires_pin",
+            "device_requires_setup",
+            "operation_progress",
+            "device_ready",
+        ]);
+        assert_eq!(summary[1].params.get("device").map(String::as_str), Some("KeepKey Office"));
+        assert_eq!(summary[3].params.get("operation").map(String::as_str), Some("migrating_data"));
+
+        let empty = build_summary(&Vec::new());
+        assert_eq!(empty[0].text, "No KeepKey is connected.");
+    }
+
+    #[test]
+    fn test_throttle_coalesces_and_skips_unchanged() {
+        let start = Instant::now();
+        let mut throttle = SummaryThrottle::new(SUMMARY_MIN_INTERVAL);
+        let summary = build_summary(&fixed_state());
+
+        assert!(throttle.offer(summary.clone(), start).is_some());
+        // Unchanged state never re-emits
+        assert!(throttle.offer(summary, start + Duration::from_secs(5)).is_none());
+
+        let changed = build_summary(&Vec::new());
+        let soon = start + Duration::from_millis(500);
+        assert_eq!(throttle.next_allowed(soon), start + SUMMARY_MIN_INTERVAL);
+        assert!(throttle.offer(changed.clone(), soon).is_none());
+        assert!(throttle.offer(changed, start + SUMMARY_MIN_INTERVAL).is_some());
+    }
+}
@@ -0,0 +1,296 @@
+// address_scan.rs - Progressive gap-limit extension for UTXO address discovery
+//
+// A sync checks address indexes 0..window for activity. The window starts at
+// the standard gap limit of 20, or at the size the account's last sync
+// reached. Whenever an address in the last EDGE_SLOTS of the window has
+// activity, the window grows by another gap-limit chunk, up to a hard ceiling
+// (200 by default, `address_scan_ceiling` preference). Activity at the edge of
+// a window that has reached the ceiling means funds may exist beyond what was
+// scanned; the sync reports it so the user can be told.
+//
+// Address activity is looked up through a closure over index ranges, so the
+// extension logic runs the same against a real indexer and canned data.
+
+use std::future::Future;
+use std::ops::Range;
+use serde::Serialize;
+use keepkey_db::{AccountScanState, Database};
+
+/// Addresses scanned per chunk; the standard BIP44 gap limit
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+/// Activity this close to the end of the window triggers an extension
+pub const EDGE_SLOTS: u32 = 5;
+/// Default hard limit on an account's window
+pub const DEFAULT_SCAN_CEILING: u32 = 200;
+/// Preference overriding the scan ceiling
+pub const SCAN_CEILING_PREFERENCE: &str = "address_scan_ceiling";
+/// Emitted when a sync stops at the ceiling with activity still near the edge
+pub const CEILING_REACHED_EVENT: &str = "accounts:scan-ceiling-reached";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanConfig {
+    pub gap_limit: u32,
+    pub ceiling: u32,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self { gap_limit: DEFAULT_GAP_LIMIT, ceiling: DEFAULT_SCAN_CEILING }
+    }
+}
+
+impl ScanConfig {
+    /// Default gap limit with the ceiling from preferences. The ceiling is
+    /// never below one gap-limit chunk.
+    pub async fn load(database: &Database) -> Self {
+        let ceiling = database.get_preference(SCAN_CEILING_PREFERENCE).await.ok().flatten()
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .unwrap_or(DEFAULT_SCAN_CEILING)
+            .max(DEFAULT_GAP_LIMIT);
+        Self { ceiling, ..Self::default() }
+    }
+
+    /// Window a sync starts with: the stored size, or one chunk for an
+    /// account never synced. A ceiling lowered since then does not shrink it.
+    pub fn starting_window(&self, state: &AccountScanState) -> u32 {
+        state.window_size.max(self.gap_limit.min(self.ceiling))
+    }
+}
+
+/// Result of a sync or manual extension of one account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ScanOutcome {
+    pub state: AccountScanState,
+    /// Automatic gap-limit extensions made by this sync
+    pub extensions: u32,
+    /// This sync newly stopped at the ceiling with activity near the edge
+    pub ceiling_newly_reached: bool,
+}
+
+/// Outcome of checking one window's activity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WindowCheck {
+    next_window: Option<u32>,
+    high_water: Option<u32>,
+    at_ceiling_with_edge_activity: bool,
+}
+
+fn check_window(window: u32, high_water: Option<u32>, used: &[u32], config: &ScanConfig) -> WindowCheck {
+    let high_water = used.iter().copied().max().max(high_water);
+    let edge_activity = used.iter().any(|i| *i >= window.saturating_sub(EDGE_SLOTS));
+    let (next_window, at_ceiling) = match edge_activity {
+        false => (None, false),
+        true if window >= config.ceiling => (None, true),
+        true => (Some((window + config.gap_limit).min(config.ceiling)), false),
+    };
+    WindowCheck { next_window, high_water, at_ceiling_with_edge_activity: at_ceiling }
+}
+
+async fn load_state(database: &Database, account_id: i64) -> Result<AccountScanState, String> {
+    database.get_account_scan_state(account_id).await
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("No UTXO account with id {}", account_id))
+}
+
+/// Sync an account's address window.
+///
+/// `activity` returns the indexes in a range that have activity. The whole
+/// starting window is checked, then each extension chunk as it is added.
+/// The resulting window and high-water mark are stored.
+pub async fn scan_account<A, Fut>(
+    database: &Database,
+    account_id: i64,
+    config: &ScanConfig,
+    activity: A,
+) -> Result<ScanOutcome, String>
+where
+    A: Fn(Range<u32>) -> Fut,
+    Fut: Future<Output = Result<Vec<u32>, String>>,
+{
+    let previous = load_state(database, account_id).await?;
+    let mut window = config.starting_window(&previous);
+    let mut scanned = 0;
+    let mut high_water = previous.high_water;
+    let mut extensions = 0;
+
+    let at_ceiling = loop {
+        let used = activity(scanned..window).await?;
+        let check = check_window(window, high_water, &used, config);
+        high_water = check.high_water;
+        match check.next_window {
+            Some(next) => {
+                log::info!("🔭 Account {} has activity near index {}; scanning to {}", account_id, window, next);
+                scanned = window;
+                window = next;
+                extensions += 1;
+            }
+            None => break check.at_ceiling_with_edge_activity,
+        }
+    };
+
+    let state = database.save_account_scan_state(account_id, window, high_water, at_ceiling).await
+        .map_err(|e| format!("Database error: {}", e))?;
+    Ok(ScanOutcome { state, extensions, ceiling_newly_reached: at_ceiling && !previous.ceiling_reached })
+}
+
+/// Record activity found by a sync that walks the window itself.
+///
+/// `used` holds the active indexes of the window the caller just scanned
+/// (`state.window_size`, or the starting window for a new account). Returns
+/// the new state; when `extensions` is 1 the caller scans the added chunk
+/// and reports again.
+pub async fn record_activity(
+    database: &Database,
+    account_id: i64,
+    used: &[u32],
+    config: &ScanConfig,
+) -> Result<ScanOutcome, String> {
+    let previous = load_state(database, account_id).await?;
+    let window = config.starting_window(&previous);
+    let check = check_window(window, previous.high_water, used, config);
+    let state = database.save_account_scan_state(
+        account_id,
+        check.next_window.unwrap_or(window),
+        check.high_water,
+        check.at_ceiling_with_edge_activity,
+    ).await.map_err(|e| format!("Database error: {}", e))?;
+    Ok(ScanOutcome {
+        state,
+        extensions: u32::from(check.next_window.is_some()),
+        ceiling_newly_reached: check.at_ceiling_with_edge_activity && !previous.ceiling_reached,
+    })
+}
+
+/// Grow an account's window by `additional` addresses on the user's request,
+/// capped at the ceiling. The next sync scans the larger window.
+pub async fn extend_scan(
+    database: &Database,
+    account_id: i64,
+    additional: u32,
+    config: &ScanConfig,
+) -> Result<AccountScanState, String> {
+    if additional == 0 {
+        return Err("Extend the scan by at least one address".to_string());
+    }
+    let previous = load_state(database, account_id).await?;
+    let start = config.starting_window(&previous);
+    if start >= config.ceiling {
+        return Err(format!(
+            "Account {} already scans {} addresses, the ceiling; raise {} to scan further",
+            account_id, start, SCAN_CEILING_PREFERENCE
+        ));
+    }
+    let window = start.saturating_add(additional).min(config.ceiling);
+    // A larger window has not been checked yet, so the ceiling flag is reset
+    database.save_account_scan_state(account_id, window, previous.high_water, false).await
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::{ready, Ready};
+    use std::sync::Mutex;
+    use keepkey_db::WalletXpubInput;
+
+    async fn seed_account(database: &Database) -> i64 {
+        database.with_connection(|conn| {
+            conn.execute("INSERT OR IGNORE INTO devices (device_id, first_seen, last_seen) VALUES ('dev1', 1, 1)", [])?;
+            Ok(())
+        }).await.unwrap();
+        database.save_wallet_xpub(&WalletXpubInput {
+            device_id: "dev1".to_string(),
+            wallet_fingerprint: "fp1".to_string(),
+            path: "m/84'/0'/0'".to_string(),
+            label: "Bitcoin".to_string(),
+            caip: "bip122:000000000019d6689c085ae165831e93/slip44:0".to_string(),
+            pubkey: "zpub".to_string(),
+        }).await.unwrap();
+        database.get_wallet_xpubs("dev1", "fp1").await.unwrap()[0].id
+    }
+
+    /// Every address up to `last_used` has activity; records the ranges asked for
+    fn used_through(
+        last_used: u32,
+        queried: &Mutex<Vec<Range<u32>>>,
+    ) -> impl Fn(Range<u32>) -> Ready<Result<Vec<u32>, String>> + '_ {
+        move |range| {
+            queried.lock().unwrap().push(range.clone());
+            ready(Ok(range.filter(|i| *i <= last_used).collect()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_activity_at_95_extends_three_times_and_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.db");
+        let config = ScanConfig::default();
+        let queried = Mutex::new(Vec::new());
+
+        // First sync: addresses used through index 45 grow the window to 60
+        let account_id = {
+            let database = Database::open_at_path(path.clone()).await.unwrap();
+            let account_id = seed_account(&database).await;
+            let outcome = scan_account(&database, account_id, &config, used_through(45, &queried)).await.unwrap();
+            assert_eq!((outcome.extensions, outcome.state.window_size, outcome.state.high_water), (2, 60, Some(45)));
+            account_id
+        };
+
+        // After a restart, other software has used addresses through 95. The
+        // sync starts at the stored 60 and extends 60 -> 80 -> 100 -> 120.
+        queried.lock().unwrap().clear();
+        {
+            let database = Database::open_at_path(path.clone()).await.unwrap();
+            let outcome = scan_account(&database, account_id, &config, used_through(95, &queried)).await.unwrap();
+            assert_eq!(outcome.extensions, 3);
+            assert_eq!((outcome.state.window_size, outcome.state.high_water), (120, Some(95)));
+            assert!(!outcome.ceiling_newly_reached);
+            assert_eq!(*queried.lock().unwrap(), vec![0..60, 60..80, 80..100, 100..120]);
+        }
+
+        let database = Database::open_at_path(path).await.unwrap();
+        let state = database.get_account_scan_state(account_id).await.unwrap().unwrap();
+        assert_eq!((state.window_size, state.high_water), (120, Some(95)));
+        assert_eq!(config.starting_window(&state), 120);
+    }
+
+    #[tokio::test]
+    async fn test_ceiling_reached_with_edge_activity() {
+        let database = Database::new_in_memory().await.unwrap();
+        let account_id = seed_account(&database).await;
+        let config = ScanConfig { gap_limit: 20, ceiling: 50 };
+        let queried = Mutex::new(Vec::new());
+
+        let outcome = scan_account(&database, account_id, &config, used_through(300, &queried)).await.unwrap();
+        // 20 -> 40 -> 50 (capped), activity still at the edge
+        assert_eq!((outcome.extensions, outcome.state.window_size), (2, 50));
+        assert!(outcome.ceiling_newly_reached && outcome.state.ceiling_reached);
+
+        // Reported once; the next sync finds the same thing
+        let again = scan_account(&database, account_id, &config, used_through(300, &queried)).await.unwrap();
+        assert!(again.state.ceiling_reached && !again.ceiling_newly_reached);
+
+        assert!(extend_scan(&database, account_id, 20, &config).await.is_err());
+        let raised = ScanConfig { ceiling: 200, ..config };
+        let state = extend_scan(&database, account_id, 500, &raised).await.unwrap();
+        assert_eq!((state.window_size, state.ceiling_reached), (200, false));
+    }
+
+    #[tokio::test]
+    async fn test_record_activity_steps_the_window() {
+        let database = Database::new_in_memory().await.unwrap();
+        let account_id = seed_account(&database).await;
+        let config = ScanConfig::default();
+
+        // Nothing near the edge of the first 20
+        let outcome = record_activity(&database, account_id, &[0, 3, 9], &config).await.unwrap();
+        assert_eq!((outcome.extensions, outcome.state.window_size, outcome.state.high_water), (0, 20, Some(9)));
+
+        let outcome = record_activity(&database, account_id, &[16], &config).await.unwrap();
+        assert_eq!((outcome.extensions, outcome.state.window_size, outcome.state.high_water), (1, 40, Some(16)));
+
+        let state = extend_scan(&database, account_id, 15, &config).await.unwrap();
+        assert_eq!(state.window_size, 55);
+    }
+}
@@ -0,0 +1,324 @@
+// api_pairing.rs - Pairing codes and client tokens for the local REST API
+//
+// There is no long-lived shared bearer token. The user generates a short
+// numeric code in the vault UI; an external client exchanges it (POST /pair)
+// for its own token, bound to a client name and a scope, read-only unless
+// the user later grants signing in the app. A code is single use, expires
+// after PAIRING_CODE_TTL, and is burned after MAX_FAILED_PAIRING_ATTEMPTS
+// wrong guesses; pairing then stays locked out until a new code is generated
+// in the app, so a six-digit code cannot be brute forced over localhost.
+//
+// Token hashing and storage are left to the caller (the vault hashes tokens
+// with SHA-256 into keepkey-db's api_clients table); this module keeps the
+// code state machine and decides whether a looked-up client may proceed.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use keepkey_db::{ApiClient, ApiScope};
+use crate::signing_origin::SigningOrigin;
+
+/// How long a pairing code can be exchanged for a token
+pub const PAIRING_CODE_TTL: Duration = Duration::from_secs(120);
+
+/// Digits in a pairing code
+pub const PAIRING_CODE_DIGITS: usize = 6;
+
+/// Wrong codes accepted before the current code is burned
+pub const MAX_FAILED_PAIRING_ATTEMPTS: u32 = 5;
+
+/// Lifetime of a client token; the client pairs again afterwards
+pub const API_TOKEN_TTL_SECS: i64 = 90 * 86_400;
+
+/// Structured error for pairing and authenticated API requests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum ApiAuthError {
+    NoPairingCode,
+    ExpiredCode,
+    WrongCode { attempts_remaining: u32 },
+    LockedOut,
+    MissingToken,
+    InvalidToken,
+    ExpiredToken,
+    RevokedToken,
+    InsufficientScope { required: ApiScope, granted: ApiScope },
+    /// The vault could not check the request (database unavailable, ...)
+    Internal { message: String },
+}
+
+impl ApiAuthError {
+    /// Encode for a `Result<_, String>` command error or an HTTP response body
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+
+    /// Status the REST server answers with
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ApiAuthError::InsufficientScope { .. } => 403,
+            ApiAuthError::LockedOut => 429,
+            ApiAuthError::Internal { .. } => 500,
+            _ => 401,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiAuthError::NoPairingCode => write!(f, "No pairing code is active; generate one in the vault"),
+            ApiAuthError::ExpiredCode => write!(f, "Pairing code expired; generate a new one in the vault"),
+            ApiAuthError::WrongCode { attempts_remaining } => {
+                write!(f, "Incorrect pairing code ({} attempts left)", attempts_remaining)
+            }
+            ApiAuthError::LockedOut => write!(f, "Too many incorrect pairing codes; generate a new one in the vault"),
+            ApiAuthError::MissingToken => write!(f, "Missing bearer token"),
+            ApiAuthError::InvalidToken => write!(f, "Unknown API token"),
+            ApiAuthError::ExpiredToken => write!(f, "API token expired; pair the client again"),
+            ApiAuthError::RevokedToken => write!(f, "API token was revoked"),
+            ApiAuthError::InsufficientScope { required, granted } => write!(
+                f,
+                "Client has {} access; this request needs {}",
+                granted.as_str(),
+                required.as_str()
+            ),
+            ApiAuthError::Internal { message } => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ApiAuthError {}
+
+/// A freshly generated code, shown in the vault UI
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct PairingCode {
+    pub code: String,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Debug)]
+struct PendingCode {
+    code: String,
+    issued_at: Instant,
+    failed_attempts: u32,
+}
+
+#[derive(Debug, Default)]
+struct PairingState {
+    pending: Option<PendingCode>,
+    locked_out: bool,
+}
+
+/// The active pairing code, held as Tauri app state
+#[derive(Debug, Default)]
+pub struct ApiPairing {
+    state: Mutex<PairingState>,
+}
+
+fn random_code() -> Result<String, String> {
+    let modulus = 10u32.pow(PAIRING_CODE_DIGITS as u32);
+    // Reject the top sliver of the range so every code is equally likely
+    let limit = u32::MAX - u32::MAX % modulus;
+    loop {
+        let mut bytes = [0u8; 4];
+        getrandom::fill(&mut bytes).map_err(|e| format!("Failed to generate pairing code: {}", e))?;
+        let value = u32::from_le_bytes(bytes);
+        if value < limit {
+            return Ok(format!("{:0width$}", value % modulus, width = PAIRING_CODE_DIGITS));
+        }
+    }
+}
+
+/// Compare without an early exit so timing does not leak matching digits
+fn codes_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected.bytes().zip(given.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+impl ApiPairing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, PairingState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replace any active code with a new one and lift a lockout
+    pub fn generate_code(&self, now: Instant) -> Result<PairingCode, String> {
+        let code = random_code()?;
+        let mut state = self.state();
+        state.pending = Some(PendingCode { code: code.clone(), issued_at: now, failed_attempts: 0 });
+        state.locked_out = false;
+        Ok(PairingCode { code, expires_in_secs: PAIRING_CODE_TTL.as_secs() })
+    }
+
+    /// Consume the active code. A match uses it up; the fifth wrong guess
+    /// burns it and locks pairing until the next `generate_code`.
+    pub fn redeem(&self, code: &str, now: Instant) -> Result<(), ApiAuthError> {
+        let mut state = self.state();
+        if state.locked_out {
+            return Err(ApiAuthError::LockedOut);
+        }
+        let pending = state.pending.as_mut().ok_or(ApiAuthError::NoPairingCode)?;
+        if now.saturating_duration_since(pending.issued_at) >= PAIRING_CODE_TTL {
+            state.pending = None;
+            return Err(ApiAuthError::ExpiredCode);
+        }
+        if codes_match(&pending.code, code.trim()) {
+            state.pending = None;
+            return Ok(());
+        }
+
+        pending.failed_attempts += 1;
+        let attempts_remaining = MAX_FAILED_PAIRING_ATTEMPTS.saturating_sub(pending.failed_attempts);
+        if attempts_remaining == 0 {
+            state.pending = None;
+            state.locked_out = true;
+            log::warn!("🔐 Pairing code burned after {} wrong attempts", MAX_FAILED_PAIRING_ATTEMPTS);
+            return Err(ApiAuthError::LockedOut);
+        }
+        Err(ApiAuthError::WrongCode { attempts_remaining })
+    }
+
+    /// Drop the active code, e.g. when the pairing dialog is closed
+    pub fn cancel(&self) {
+        self.state().pending = None;
+    }
+}
+
+/// A new random client token, returned once to the paired client
+pub fn new_api_token() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to generate API token: {}", e))?;
+    Ok(hex::encode(bytes))
+}
+
+/// The token from an `Authorization: Bearer <token>` header value
+pub fn bearer_token(header: Option<&str>) -> Result<&str, ApiAuthError> {
+    header
+        .and_then(|value| value.trim().strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .ok_or(ApiAuthError::MissingToken)
+}
+
+/// Decide whether the client found for a token (None when the token is
+/// unknown) may make a request needing `required` at `now` (epoch seconds).
+/// The returned origin attributes the request to the client for signing
+/// audits and spending limits.
+pub fn authorize_client(
+    client: Option<&ApiClient>,
+    required: ApiScope,
+    now: i64,
+) -> Result<SigningOrigin, ApiAuthError> {
+    let client = client.ok_or(ApiAuthError::InvalidToken)?;
+    if client.revoked_at.is_some() {
+        return Err(ApiAuthError::RevokedToken);
+    }
+    if client.expires_at <= now {
+        return Err(ApiAuthError::ExpiredToken);
+    }
+    if !client.scope.allows(required) {
+        return Err(ApiAuthError::InsufficientScope { required, granted: client.scope });
+    }
+    Ok(SigningOrigin::RestApi { token_id: client.id.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(scope: ApiScope) -> ApiClient {
+        ApiClient {
+            id: 7,
+            name: "script".to_string(),
+            scope,
+            created_at: 1_000,
+            last_used_at: None,
+            expires_at: 1_000 + API_TOKEN_TTL_SECS,
+            revoked_at: None,
+        }
+    }
+
+    #[test]
+    fn test_code_is_single_use_and_expires() {
+        let pairing = ApiPairing::new();
+        let start = Instant::now();
+        assert_eq!(pairing.redeem("123456", start), Err(ApiAuthError::NoPairingCode));
+
+        let code = pairing.generate_code(start).unwrap();
+        assert_eq!(code.code.len(), PAIRING_CODE_DIGITS);
+        assert!(code.code.bytes().all(|b| b.is_ascii_digit()));
+        assert_eq!(pairing.redeem(&format!(" {} ", code.code), start + Duration::from_secs(30)), Ok(()));
+        assert_eq!(pairing.redeem(&code.code, start + Duration::from_secs(31)), Err(ApiAuthError::NoPairingCode));
+
+        let code = pairing.generate_code(start).unwrap();
+        assert_eq!(pairing.redeem(&code.code, start + PAIRING_CODE_TTL), Err(ApiAuthError::ExpiredCode));
+        // An expired code is gone, not merely refused
+        assert_eq!(pairing.redeem(&code.code, start), Err(ApiAuthError::NoPairingCode));
+    }
+
+    #[test]
+    fn test_lockout_after_five_wrong_codes() {
+        let pairing = ApiPairing::new();
+        let now = Instant::now();
+        let code = pairing.generate_code(now).unwrap();
+        let wrong = if code.code == "000000" { "000001" } else { "000000" };
+
+        for remaining in (1..MAX_FAILED_PAIRING_ATTEMPTS).rev() {
+            assert_eq!(pairing.redeem(wrong, now), Err(ApiAuthError::WrongCode { attempts_remaining: remaining }));
+        }
+        let err = pairing.redeem(wrong, now).unwrap_err();
+        assert_eq!(err, ApiAuthError::LockedOut);
+        assert_eq!(err.http_status(), 429);
+
+        // The right code no longer works once burned
+        assert_eq!(pairing.redeem(&code.code, now), Err(ApiAuthError::LockedOut));
+
+        // Only a new code from the app lifts the lockout
+        let code = pairing.generate_code(now).unwrap();
+        assert_eq!(pairing.redeem(&code.code, now), Ok(()));
+    }
+
+    #[test]
+    fn test_scope_enforcement() {
+        let now = 2_000;
+        let reader = client(ApiScope::Read);
+        assert_eq!(
+            authorize_client(Some(&reader), ApiScope::Read, now),
+            Ok(SigningOrigin::RestApi { token_id: "7".to_string() })
+        );
+        let err = authorize_client(Some(&reader), ApiScope::Sign, now).unwrap_err();
+        assert_eq!(err, ApiAuthError::InsufficientScope { required: ApiScope::Sign, granted: ApiScope::Read });
+        assert_eq!(err.http_status(), 403);
+        let json: serde_json::Value = serde_json::from_str(&err.to_json_string()).unwrap();
+        assert_eq!(json["kind"], "InsufficientScope");
+        assert_eq!(json["granted"], "read");
+
+        let signer = client(ApiScope::Sign);
+        assert!(authorize_client(Some(&signer), ApiScope::Sign, now).is_ok());
+        assert!(authorize_client(Some(&signer), ApiScope::Read, now).is_ok());
+    }
+
+    #[test]
+    fn test_unusable_tokens_get_401() {
+        let now = 2_000;
+        assert_eq!(authorize_client(None, ApiScope::Read, now), Err(ApiAuthError::InvalidToken));
+
+        let mut revoked = client(ApiScope::Sign);
+        revoked.revoked_at = Some(1_500);
+        assert_eq!(authorize_client(Some(&revoked), ApiScope::Read, now), Err(ApiAuthError::RevokedToken));
+
+        let expired = client(ApiScope::Read);
+        let err = authorize_client(Some(&expired), ApiScope::Read, expired.expires_at).unwrap_err();
+        assert_eq!(err, ApiAuthError::ExpiredToken);
+        assert_eq!(err.http_status(), 401);
+
+        assert_eq!(bearer_token(Some("Bearer abc")), Ok("abc"));
+        assert_eq!(bearer_token(Some("Basic abc")), Err(ApiAuthError::MissingToken));
+        assert_eq!(bearer_token(None), Err(ApiAuthError::MissingToken));
+    }
+}
@@ -0,0 +1,402 @@
+// app_lock.rs - Inactivity lock for the vault UI
+//
+// The frontend reports user input through `report_activity`; once the
+// configured number of minutes passes without any, the app locks. While locked
+// every command outside EXEMPT_COMMANDS is refused with a structured AppLocked
+// error, so a locked window cannot read balances or start a signing request
+// even if the frontend ignores the lock screen. Unlocking needs the app PIN.
+//
+// A lock never lands in the middle of a firmware or bootloader flash: the
+// update commands hold a FlashGuard for the whole transfer, idle checks are
+// skipped while one is alive, and the idle clock restarts when it is dropped.
+//
+// Credential checks are left to the caller (the vault hashes the PIN with
+// argon2); this module only keeps the state machine and the retry throttle.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+/// Preference holding the inactivity timeout in minutes; unset or 0 disables the lock
+pub const APP_LOCK_MINUTES_PREF: &str = "app_lock_minutes";
+
+/// Activity reports closer together than this are coalesced
+pub const ACTIVITY_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the backend checks for an idle timeout
+pub const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Wrong credentials allowed before unlock attempts are throttled
+const FREE_UNLOCK_ATTEMPTS: u32 = 3;
+const MAX_UNLOCK_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Commands that stay available while the app is locked
+pub const EXEMPT_COMMANDS: &[&str] = &[
+    "unlock_app",
+    "report_activity",
+    "get_app_lock_state",
+    "frontend_ready",
+    "frontend_reset",
+    "subscribe_events",
+    "ack_event",
+    "get_system_diagnostics",
+    "get_startup_report",
+    "get_device_fault_log",
+];
+
+/// Structured error for commands refused or failed by the app lock
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum AppLockError {
+    AppLocked { command: String },
+    InvalidCredential { attempts_before_throttle: u32 },
+    Throttled { retry_after_secs: u64 },
+    FlashInProgress,
+    NoCredential,
+}
+
+impl AppLockError {
+    /// Encode for a `Result<_, String>` command error
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl std::fmt::Display for AppLockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppLockError::AppLocked { command } => write!(f, "The vault is locked; unlock it to use {}", command),
+            AppLockError::InvalidCredential { .. } => write!(f, "Incorrect PIN"),
+            AppLockError::Throttled { retry_after_secs } => {
+                write!(f, "Too many incorrect attempts; try again in {}s", retry_after_secs)
+            }
+            AppLockError::FlashInProgress => write!(f, "A firmware update is in progress"),
+            AppLockError::NoCredential => write!(f, "No app PIN is configured"),
+        }
+    }
+}
+
+impl std::error::Error for AppLockError {}
+
+/// Lock state as shown to the frontend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct AppLockStatus {
+    pub locked: bool,
+    /// Inactivity timeout in minutes, None when the lock is disabled
+    pub timeout_minutes: Option<u32>,
+    pub has_credential: bool,
+    pub flash_in_progress: bool,
+}
+
+#[derive(Debug)]
+struct LockState {
+    timeout: Option<Duration>,
+    has_credential: bool,
+    last_activity: Instant,
+    last_report: Option<Instant>,
+    locked: bool,
+    flashes_in_flight: u32,
+    failed_attempts: u32,
+    throttled_until: Option<Instant>,
+}
+
+impl LockState {
+    fn enabled(&self) -> bool {
+        self.has_credential && self.timeout.is_some()
+    }
+}
+
+/// Backend state of the app lock, held as Tauri app state
+#[derive(Debug)]
+pub struct AppLock {
+    state: Mutex<LockState>,
+}
+
+impl AppLock {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            state: Mutex::new(LockState {
+                timeout: None,
+                has_credential: false,
+                last_activity: now,
+                last_report: None,
+                locked: false,
+                flashes_in_flight: 0,
+                failed_attempts: 0,
+                throttled_until: None,
+            }),
+        }
+    }
+
+    /// Lock the state, taking it back from a poisoned lock like DeviceFlowState
+    fn state(&self) -> MutexGuard<'_, LockState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Apply the configured timeout and whether an unlock credential exists.
+    /// Without a credential the app never locks, since it could not be unlocked.
+    pub fn set_policy(&self, timeout: Option<Duration>, has_credential: bool) {
+        let mut state = self.state();
+        state.timeout = timeout.filter(|t| !t.is_zero());
+        state.has_credential = has_credential;
+        if !has_credential {
+            state.locked = false;
+        }
+    }
+
+    /// Record user input; returns false when the report was coalesced or the app is locked
+    pub fn record_activity(&self, now: Instant) -> bool {
+        let mut state = self.state();
+        if state.locked {
+            return false;
+        }
+        if let Some(last) = state.last_report {
+            if now.saturating_duration_since(last) < ACTIVITY_REPORT_INTERVAL {
+                return false;
+            }
+        }
+        state.last_report = Some(now);
+        state.last_activity = now;
+        true
+    }
+
+    /// Lock if the app has been idle past the timeout; returns true on the transition
+    pub fn check_idle(&self, now: Instant) -> bool {
+        let mut state = self.state();
+        if state.locked || !state.enabled() || state.flashes_in_flight > 0 {
+            return false;
+        }
+        let timeout = state.timeout.unwrap_or_default();
+        if now.saturating_duration_since(state.last_activity) < timeout {
+            return false;
+        }
+        state.locked = true;
+        true
+    }
+
+    /// Lock right away; returns true on the transition
+    pub fn lock(&self) -> Result<bool, AppLockError> {
+        let mut state = self.state();
+        if !state.has_credential {
+            return Err(AppLockError::NoCredential);
+        }
+        if state.flashes_in_flight > 0 {
+            return Err(AppLockError::FlashInProgress);
+        }
+        let changed = !state.locked;
+        state.locked = true;
+        Ok(changed)
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.state().locked
+    }
+
+    /// Refuse `command` while locked unless it is exempt
+    pub fn check_command(&self, command: &str) -> Result<(), AppLockError> {
+        if self.is_locked() && !EXEMPT_COMMANDS.contains(&command) {
+            return Err(AppLockError::AppLocked { command: command.to_string() });
+        }
+        Ok(())
+    }
+
+    /// Unlock with a credential checked by `verify`. Repeated failures back
+    /// off exponentially; the verifier is not called while throttled.
+    pub fn unlock<F>(&self, verify: F, now: Instant) -> Result<(), AppLockError>
+    where
+        F: FnOnce() -> bool,
+    {
+        let mut state = self.state();
+        if !state.locked {
+            return Ok(());
+        }
+        if let Some(until) = state.throttled_until {
+            if now < until {
+                let remaining = until.saturating_duration_since(now);
+                return Err(AppLockError::Throttled { retry_after_secs: remaining.as_secs().max(1) });
+            }
+        }
+
+        if verify() {
+            state.locked = false;
+            state.failed_attempts = 0;
+            state.throttled_until = None;
+            state.last_activity = now;
+            state.last_report = Some(now);
+            return Ok(());
+        }
+
+        state.failed_attempts += 1;
+        if state.failed_attempts >= FREE_UNLOCK_ATTEMPTS {
+            let exponent = (state.failed_attempts - FREE_UNLOCK_ATTEMPTS).min(6);
+            let backoff = (Duration::from_secs(5) * 2u32.pow(exponent)).min(MAX_UNLOCK_BACKOFF);
+            state.throttled_until = Some(now + backoff);
+        }
+        Err(AppLockError::InvalidCredential {
+            attempts_before_throttle: FREE_UNLOCK_ATTEMPTS.saturating_sub(state.failed_attempts),
+        })
+    }
+
+    /// Hold off idle locking until the returned guard is dropped
+    pub fn flash_guard(self: &Arc<Self>) -> FlashGuard {
+        self.state().flashes_in_flight += 1;
+        FlashGuard { lock: self.clone() }
+    }
+
+    pub fn status(&self) -> AppLockStatus {
+        let state = self.state();
+        AppLockStatus {
+            locked: state.locked,
+            timeout_minutes: state.timeout.map(|t| (t.as_secs() / 60) as u32),
+            has_credential: state.has_credential,
+            flash_in_progress: state.flashes_in_flight > 0,
+        }
+    }
+}
+
+/// Keeps the app unlocked while a device flash is in flight
+#[derive(Debug)]
+pub struct FlashGuard {
+    lock: Arc<AppLock>,
+}
+
+impl Drop for FlashGuard {
+    fn drop(&mut self) {
+        let mut state = self.lock.state();
+        state.flashes_in_flight = state.flashes_in_flight.saturating_sub(1);
+        // The user was waiting on the device, so restart the idle clock
+        state.last_activity = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn configured(now: Instant) -> AppLock {
+        let lock = AppLock::new(now);
+        lock.set_policy(Some(5 * MINUTE), true);
+        lock
+    }
+
+    #[test]
+    fn test_locks_after_timeout() {
+        let start = Instant::now();
+        let lock = configured(start);
+        assert!(!lock.check_idle(start + 4 * MINUTE));
+
+        // Activity pushes the deadline out
+        assert!(lock.record_activity(start + 4 * MINUTE));
+        assert!(!lock.check_idle(start + 8 * MINUTE));
+
+        assert!(lock.check_idle(start + 9 * MINUTE));
+        assert!(lock.is_locked());
+        // Only the transition reports true
+        assert!(!lock.check_idle(start + 10 * MINUTE));
+        // Activity while locked does not unlock
+        assert!(!lock.record_activity(start + 10 * MINUTE));
+        assert!(lock.is_locked());
+    }
+
+    #[test]
+    fn test_disabled_without_timeout_or_credential() {
+        let start = Instant::now();
+        let lock = AppLock::new(start);
+        lock.set_policy(Some(MINUTE), false);
+        assert!(!lock.check_idle(start + 60 * MINUTE));
+        assert_eq!(lock.lock(), Err(AppLockError::NoCredential));
+
+        lock.set_policy(Some(Duration::ZERO), true);
+        assert!(!lock.check_idle(start + 60 * MINUTE));
+        assert_eq!(lock.status().timeout_minutes, None);
+    }
+
+    #[test]
+    fn test_rejects_signing_while_locked() {
+        let start = Instant::now();
+        let lock = configured(start);
+        assert!(lock.check_command("build_and_sign_bitcoin_tx").is_ok());
+
+        assert_eq!(lock.lock(), Ok(true));
+        assert_eq!(
+            lock.check_command("build_and_sign_bitcoin_tx"),
+            Err(AppLockError::AppLocked { command: "build_and_sign_bitcoin_tx".to_string() })
+        );
+        for command in ["unlock_app", "frontend_ready", "get_system_diagnostics"] {
+            assert!(lock.check_command(command).is_ok(), "{} should be exempt", command);
+        }
+
+        let json: serde_json::Value =
+            serde_json::from_str(&lock.check_command("revoke_token_approval").unwrap_err().to_json_string()).unwrap();
+        assert_eq!(json["kind"], "AppLocked");
+        assert_eq!(json["command"], "revoke_token_approval");
+    }
+
+    #[test]
+    fn test_unlock_with_wrong_and_right_credential() {
+        let start = Instant::now();
+        let lock = configured(start);
+        lock.lock().unwrap();
+        let check = |entered: &'static str| move || entered == "2468";
+
+        assert_eq!(
+            lock.unlock(check("1111"), start),
+            Err(AppLockError::InvalidCredential { attempts_before_throttle: 2 })
+        );
+        assert!(lock.is_locked());
+
+        lock.unlock(check("2468"), start).unwrap();
+        assert!(!lock.is_locked());
+        assert!(lock.check_command("build_and_sign_bitcoin_tx").is_ok());
+        // The idle clock restarts at the unlock
+        assert!(!lock.check_idle(start + 4 * MINUTE));
+    }
+
+    #[test]
+    fn test_repeated_failures_are_throttled() {
+        let start = Instant::now();
+        let lock = configured(start);
+        lock.lock().unwrap();
+        for _ in 0..3 {
+            assert!(matches!(lock.unlock(|| false, start), Err(AppLockError::InvalidCredential { .. })));
+        }
+
+        let mut verified = false;
+        assert_eq!(
+            lock.unlock(|| { verified = true; true }, start + Duration::from_secs(1)),
+            Err(AppLockError::Throttled { retry_after_secs: 4 })
+        );
+        assert!(!verified);
+
+        lock.unlock(|| true, start + Duration::from_secs(5)).unwrap();
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn test_flash_blocks_lock() {
+        let start = Instant::now();
+        let lock = Arc::new(configured(start));
+        let guard = lock.flash_guard();
+        assert!(lock.status().flash_in_progress);
+        assert!(!lock.check_idle(start + 30 * MINUTE));
+        assert_eq!(lock.lock(), Err(AppLockError::FlashInProgress));
+
+        drop(guard);
+        assert!(!lock.status().flash_in_progress);
+        // The idle clock restarted when the flash finished
+        assert!(!lock.check_idle(Instant::now() + MINUTE));
+        assert!(lock.check_idle(Instant::now() + 6 * MINUTE));
+    }
+
+    #[test]
+    fn test_activity_reports_are_coalesced() {
+        let start = Instant::now();
+        let lock = configured(start);
+        assert!(lock.record_activity(start));
+        assert!(!lock.record_activity(start + Duration::from_secs(1)));
+        assert!(lock.record_activity(start + ACTIVITY_REPORT_INTERVAL));
+    }
+}
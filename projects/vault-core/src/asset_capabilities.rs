@@ -0,0 +1,294 @@
+// asset_capabilities.rs - What the vault can actually do with an asset on a given device
+//
+// Combines three sources: the chain module registry (is a signer implemented
+// for this network?), the firmware capability table, and whether the user has
+// the network enabled (keepkey_db::networks). The UI uses the result to grey
+// out actions; the signing paths call `require_capability` so the same rules
+// hold server-side.
+
+use std::collections::HashSet;
+use serde::Serialize;
+use keepkey_db::Database;
+use keepkey_rust::features::capabilities::{Capability, FirmwareCapabilities};
+use keepkey_rust::features::DeviceFeatures;
+use crate::paths::{network_family, NetworkFamily};
+
+/// Operations gated by capability resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetOperation {
+    Receive,
+    Send,
+    SignMessage,
+}
+
+impl AssetOperation {
+    fn describe(&self) -> &'static str {
+        match self {
+            AssetOperation::Receive => "Receiving",
+            AssetOperation::Send => "Sending",
+            AssetOperation::SignMessage => "Message signing",
+        }
+    }
+}
+
+/// Resolved capabilities for one asset; `reasons` explains every `false`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct AssetCapabilities {
+    pub caip: String,
+    pub can_receive: bool,
+    pub can_send: bool,
+    pub can_sign_messages: bool,
+    pub reasons: Vec<String>,
+}
+
+impl AssetCapabilities {
+    pub fn allows(&self, operation: AssetOperation) -> bool {
+        match operation {
+            AssetOperation::Receive => self.can_receive,
+            AssetOperation::Send => self.can_send,
+            AssetOperation::SignMessage => self.can_sign_messages,
+        }
+    }
+}
+
+/// What the vault implements for a network. Update `chain_module` as chain
+/// modules land; nothing else needs to change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainModule {
+    pub name: String,
+    /// Firmware capability the signer relies on
+    pub firmware: Capability,
+    /// Address derivation is implemented
+    pub receive: bool,
+    /// A transaction signer is implemented for the native asset
+    pub send: bool,
+    /// Token transfers are implemented
+    pub send_tokens: bool,
+    pub sign_messages: bool,
+}
+
+/// Chain module for an asset's network, or None if the vault has no module for it
+pub fn chain_module(caip: &str) -> Option<ChainModule> {
+    match network_family(caip).ok()? {
+        NetworkFamily::Utxo { coin_name } => {
+            // Only the Bitcoin signer (commands/bitcoin.rs) is wired up so far
+            let send = matches!(coin_name.as_str(), "Bitcoin" | "Testnet");
+            // The device signs messages with the Bitcoin coin (commands/messages.rs)
+            let sign_messages = coin_name == "Bitcoin";
+            Some(ChainModule {
+                name: coin_name,
+                firmware: Capability::UtxoSigning,
+                receive: true,
+                send,
+                send_tokens: false,
+                sign_messages,
+            })
+        }
+        NetworkFamily::Evm { chain_id } => Some(ChainModule {
+            name: format!("EVM chain {}", chain_id),
+            firmware: Capability::EthereumSigning,
+            receive: true,
+            send: false,
+            send_tokens: false,
+            sign_messages: true,
+        }),
+    }
+}
+
+/// Network part of a CAIP-19 asset id (`eip155:1/slip44:60` -> `eip155:1`)
+fn network_id(caip: &str) -> &str {
+    caip.split('/').next().unwrap_or(caip)
+}
+
+/// Native assets use the slip44 namespace; anything else is a token
+fn is_native(caip: &str) -> bool {
+    caip.split('/')
+        .nth(1)
+        .map(|asset| asset.starts_with("slip44:"))
+        .unwrap_or(true)
+}
+
+/// Record why an operation is unavailable; always returns false
+fn block(reasons: &mut Vec<String>, reason: String) -> bool {
+    if !reasons.contains(&reason) {
+        reasons.push(reason);
+    }
+    false
+}
+
+/// Resolve capabilities for an asset. `firmware` is None when the device's
+/// features have not been read yet.
+pub fn resolve_capabilities(
+    caip: &str,
+    network_active: bool,
+    firmware: Option<&FirmwareCapabilities>,
+) -> AssetCapabilities {
+    let Some(module) = chain_module(caip) else {
+        return AssetCapabilities {
+            caip: caip.to_string(),
+            can_receive: false,
+            can_send: false,
+            can_sign_messages: false,
+            reasons: vec![format!("Network {} is not supported by this version of the vault", network_id(caip))],
+        };
+    };
+
+    // Conditions shared by every operation
+    let mut reasons = Vec::new();
+    let mut usable = true;
+    if !network_active {
+        usable = block(&mut reasons, format!("Network {} is disabled", network_id(caip)));
+    }
+    match firmware {
+        None => usable = block(&mut reasons, "Device features are not known yet; connect and unlock the device".to_string()),
+        Some(fw) if fw.bootloader_mode => usable = block(&mut reasons, "Device is in bootloader mode".to_string()),
+        Some(_) => {}
+    }
+    let firmware_reason = firmware.and_then(|fw| fw.unsupported_reason(module.firmware));
+
+    let can_receive = usable
+        && (module.receive || block(&mut reasons, format!("Receiving is not implemented for {}", module.name)));
+
+    let (signer, what) = if is_native(caip) {
+        (module.send, module.name.clone())
+    } else {
+        (module.send_tokens, format!("tokens on {}", module.name))
+    };
+    let can_send = usable
+        && (signer || block(&mut reasons, format!("Sending {} is not implemented yet", what)))
+        && firmware_reason.clone().map_or(true, |r| block(&mut reasons, format!("{} for {}", r, module.name)));
+
+    let can_sign_messages = usable
+        && (module.sign_messages || block(&mut reasons, format!("Message signing is not implemented for {}", module.name)))
+        && firmware_reason.map_or(true, |r| block(&mut reasons, format!("{} for {}", r, module.name)));
+
+    AssetCapabilities {
+        caip: caip.to_string(),
+        can_receive,
+        can_send,
+        can_sign_messages,
+        reasons,
+    }
+}
+
+/// Firmware capabilities from the features last stored for a device
+async fn stored_firmware(db: &Database, device_id: &str) -> Result<Option<FirmwareCapabilities>, String> {
+    let record = db
+        .get_device_by_id(device_id)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+    Ok(record
+        .as_ref()
+        .and_then(|r| r.get("features"))
+        .and_then(|f| f.as_str())
+        .and_then(|f| serde_json::from_str::<DeviceFeatures>(f).ok())
+        .map(|features| FirmwareCapabilities::from_features(&features)))
+}
+
+async fn disabled_networks(db: &Database) -> Result<HashSet<String>, String> {
+    Ok(db
+        .get_disabled_networks()
+        .await
+        .map_err(|e| format!("Database error: {}", e))?
+        .into_iter()
+        .collect())
+}
+
+/// Capabilities of one asset on a device
+pub async fn get_asset_capabilities(db: &Database, caip: &str, device_id: &str) -> Result<AssetCapabilities, String> {
+    let firmware = stored_firmware(db, device_id).await?;
+    let disabled = disabled_networks(db).await?;
+    Ok(resolve_capabilities(caip, !disabled.contains(network_id(caip)), firmware.as_ref()))
+}
+
+/// Capabilities of every asset with a cached balance in a device wallet
+pub async fn get_portfolio_capabilities(
+    db: &Database,
+    device_id: &str,
+    wallet_fingerprint: &str,
+) -> Result<Vec<AssetCapabilities>, String> {
+    let firmware = stored_firmware(db, device_id).await?;
+    let disabled = disabled_networks(db).await?;
+    let caips = db
+        .get_portfolio_caips(device_id, wallet_fingerprint)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+    Ok(caips
+        .iter()
+        .map(|caip| resolve_capabilities(caip, !disabled.contains(network_id(caip)), firmware.as_ref()))
+        .collect())
+}
+
+/// Refuse an operation the resolution does not allow; called by the signing paths
+pub async fn require_capability(
+    db: &Database,
+    caip: &str,
+    device_id: &str,
+    operation: AssetOperation,
+) -> Result<(), String> {
+    let capabilities = get_asset_capabilities(db, caip, device_id).await?;
+    if capabilities.allows(operation) {
+        return Ok(());
+    }
+    Err(format!(
+        "{} {} is not available: {}",
+        operation.describe(),
+        caip,
+        capabilities.reasons.join("; ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+    const ETH: &str = "eip155:1/slip44:60";
+
+    fn firmware(version: (u32, u32, u32)) -> FirmwareCapabilities {
+        FirmwareCapabilities { version, bootloader_mode: false, debug_link: false }
+    }
+
+    #[test]
+    fn test_unimplemented_chain() {
+        let caps = resolve_capabilities("cosmos:cosmoshub-4/slip44:118", true, Some(&firmware((7, 10, 0))));
+        assert!(!caps.can_receive && !caps.can_send && !caps.can_sign_messages);
+        assert!(caps.reasons[0].contains("cosmos:cosmoshub-4"));
+
+        // EVM addresses derive, but there is no signer yet
+        let caps = resolve_capabilities(ETH, true, Some(&firmware((7, 10, 0))));
+        assert!(caps.can_receive);
+        assert!(!caps.can_send);
+        assert!(caps.reasons.iter().any(|r| r.contains("not implemented")));
+    }
+
+    #[test]
+    fn test_supported_asset_without_usable_firmware() {
+        // Signing has no firmware minimum, so an old release is not blocked
+        let caps = resolve_capabilities(BTC, true, Some(&firmware((6, 4, 0))));
+        assert!(caps.can_receive && caps.can_send);
+
+        let bootloader = FirmwareCapabilities { version: (7, 10, 0), bootloader_mode: true, debug_link: false };
+        let caps = resolve_capabilities(BTC, true, Some(&bootloader));
+        assert!(!caps.can_receive && !caps.can_send);
+        assert!(caps.reasons.iter().any(|r| r.contains("bootloader mode")));
+        assert!(!resolve_capabilities(BTC, true, None).can_send);
+    }
+
+    #[test]
+    fn test_fully_supported_asset() {
+        let caps = resolve_capabilities(BTC, true, Some(&firmware((7, 10, 0))));
+        assert!(caps.can_receive);
+        assert!(caps.allows(AssetOperation::Send));
+        assert!(caps.can_sign_messages);
+        assert!(caps.reasons.is_empty());
+
+        // Message signing is wired for the Bitcoin coin only
+        let ltc = resolve_capabilities("bip122:12a765e31ffd4059bada1e25190f6e98/slip44:2", true, Some(&firmware((7, 10, 0))));
+        assert!(!ltc.can_sign_messages);
+
+        let disabled = resolve_capabilities(BTC, false, Some(&firmware((7, 10, 0))));
+        assert!(!disabled.can_send && !disabled.can_receive);
+    }
+}
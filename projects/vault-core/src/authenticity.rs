@@ -0,0 +1,268 @@
+// authenticity.rs - Vendor and firmware attestation of connected devices
+//
+// Clones enumerate with the KeepKey VID/PID, so the USB filter alone lets
+// them through. Features are checked when they are read: the vendor and model
+// strings against the ones genuine devices report, and the firmware hash
+// against the signed releases. A device is only refused when both checks
+// fail, so a genuine device with custom firmware metadata or an unreleased
+// build is not blocked. Refused devices can still run firmware verification
+// and diagnostics; signing and xpub export return `UnverifiedDevice`.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use serde::Serialize;
+use keepkey_rust::features::DeviceFeatures;
+
+/// Emitted when a connected device fails the vendor and firmware checks
+pub const UNRECOGNIZED_VENDOR_EVENT: &str = "security:unrecognized-vendor";
+
+/// Vendor strings reported by genuine firmware and bootloaders
+pub const KNOWN_VENDORS: &[&str] = &["keepkey.com", "KeepKey", "KeyHodlers, LLC"];
+
+/// Model strings start with this prefix (K1-14AM, K1-14WL-S, ...)
+const MODEL_PREFIX: &str = "K1-14";
+
+/// Model reported by early firmware
+const LEGACY_MODEL: &str = "KeepKey";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum AuthenticityStatus {
+    /// Known vendor and model, released firmware
+    Genuine,
+    /// Known vendor and model, firmware hash missing or not in the releases
+    HashUnknown,
+    /// Released firmware but an odd vendor or model string; allowed
+    IdentityUnrecognized,
+    /// Neither the identity nor the firmware checks out; signing is blocked
+    VendorMismatch,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct DeviceAuthenticity {
+    pub status: AuthenticityStatus,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    /// None when the device reported no firmware hash
+    pub firmware_hash_known: Option<bool>,
+    pub reasons: Vec<String>,
+}
+
+impl DeviceAuthenticity {
+    pub fn vendor_mismatch(&self) -> bool {
+        self.status == AuthenticityStatus::VendorMismatch
+    }
+}
+
+/// Structured error for signing and xpub commands on an unverified device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum AuthenticityError {
+    UnverifiedDevice {
+        device_id: String,
+        vendor: Option<String>,
+        model: Option<String>,
+        reasons: Vec<String>,
+    },
+}
+
+impl AuthenticityError {
+    /// Encode for a `Result<_, String>` command error
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl std::fmt::Display for AuthenticityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthenticityError::UnverifiedDevice { device_id, reasons, .. } => write!(
+                f,
+                "Device {} could not be verified as a genuine KeepKey ({})",
+                device_id,
+                reasons.join("; ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AuthenticityError {}
+
+/// Empty strings count as not reported; conversion fills in defaults
+fn reported(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Decide authenticity from the reported strings and whether the firmware
+/// hash is a released one (None when no hash was reported)
+pub fn assess_authenticity(
+    vendor: Option<&str>,
+    model: Option<&str>,
+    firmware_hash_known: Option<bool>,
+) -> DeviceAuthenticity {
+    let vendor = reported(vendor);
+    let model = reported(model);
+    let mut reasons = Vec::new();
+
+    if let Some(vendor) = vendor.filter(|v| !KNOWN_VENDORS.iter().any(|known| known.eq_ignore_ascii_case(v))) {
+        reasons.push(format!("unrecognized vendor \"{}\"", vendor));
+    }
+    if let Some(model) = model.filter(|m| !m.starts_with(MODEL_PREFIX) && !m.eq_ignore_ascii_case(LEGACY_MODEL)) {
+        reasons.push(format!("unexpected model \"{}\"", model));
+    }
+    let identity_ok = reasons.is_empty();
+
+    match firmware_hash_known {
+        Some(true) => {}
+        Some(false) => reasons.push("firmware hash is not a released build".to_string()),
+        None => reasons.push("no firmware hash reported".to_string()),
+    }
+    let hash_ok = firmware_hash_known == Some(true);
+
+    let status = match (identity_ok, hash_ok) {
+        (true, true) => AuthenticityStatus::Genuine,
+        (true, false) => AuthenticityStatus::HashUnknown,
+        (false, true) => AuthenticityStatus::IdentityUnrecognized,
+        (false, false) => AuthenticityStatus::VendorMismatch,
+    };
+
+    DeviceAuthenticity {
+        status,
+        vendor: vendor.map(str::to_string),
+        model: model.map(str::to_string),
+        firmware_hash_known,
+        reasons,
+    }
+}
+
+/// Assess converted features, looking the firmware hash up in releases.json
+pub fn assess_features(features: &DeviceFeatures) -> DeviceAuthenticity {
+    let firmware_hash_known = features
+        .firmware_hash
+        .as_deref()
+        .filter(|hash| !hash.is_empty())
+        .map(|hash| crate::features::firmware_version_from_hash(hash).is_some());
+    assess_authenticity(features.vendor.as_deref(), features.model.as_deref(), firmware_hash_known)
+}
+
+/// Latest assessment per device_id, filled in whenever features are read
+pub struct AuthenticityRegistry {
+    devices: Mutex<HashMap<String, DeviceAuthenticity>>,
+}
+
+impl Default for AuthenticityRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthenticityRegistry {
+    pub fn new() -> Self {
+        Self { devices: Mutex::new(HashMap::new()) }
+    }
+
+    fn with_devices<R>(&self, f: impl FnOnce(&mut HashMap<String, DeviceAuthenticity>) -> R) -> R {
+        let mut devices = self.devices.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut devices)
+    }
+
+    pub fn record(&self, device_id: &str, authenticity: DeviceAuthenticity) {
+        if authenticity.vendor_mismatch() {
+            log::warn!("🚩 Device {} failed vendor and firmware checks: {}", device_id, authenticity.reasons.join("; "));
+        }
+        self.with_devices(|devices| devices.insert(device_id.to_string(), authenticity));
+    }
+
+    pub fn assessment(&self, device_id: &str) -> Option<DeviceAuthenticity> {
+        self.with_devices(|devices| devices.get(device_id).cloned())
+    }
+
+    /// Refuse signing and xpub export for a device whose last features failed
+    /// both checks. Devices not assessed yet are allowed; features are read
+    /// on every connect before a wallet is opened.
+    pub fn require_verified(&self, device_id: &str) -> Result<(), AuthenticityError> {
+        match self.assessment(device_id) {
+            Some(authenticity) if authenticity.vendor_mismatch() => Err(AuthenticityError::UnverifiedDevice {
+                device_id: device_id.to_string(),
+                vendor: authenticity.vendor,
+                model: authenticity.model,
+                reasons: authenticity.reasons,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn authenticity_registry() -> &'static AuthenticityRegistry {
+    static REGISTRY: OnceLock<AuthenticityRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(AuthenticityRegistry::new)
+}
+
+/// `require_verified` on the process-wide registry, encoded for a command error
+pub fn require_verified_device(device_id: &str) -> Result<(), String> {
+    authenticity_registry()
+        .require_verified(device_id)
+        .map_err(|e| e.to_json_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genuine_device() {
+        let registry = AuthenticityRegistry::new();
+        let authenticity = assess_authenticity(Some("keepkey.com"), Some("K1-14AM"), Some(true));
+        assert_eq!(authenticity.status, AuthenticityStatus::Genuine);
+        assert!(authenticity.reasons.is_empty());
+
+        registry.record("kk1", authenticity);
+        assert!(registry.require_verified("kk1").is_ok());
+        // Not assessed yet
+        assert!(registry.require_verified("kk2").is_ok());
+    }
+
+    #[test]
+    fn test_unknown_hash_with_known_vendor_is_allowed() {
+        let registry = AuthenticityRegistry::new();
+        let authenticity = assess_authenticity(Some("KeyHodlers, LLC"), Some("KeepKey"), Some(false));
+        assert_eq!(authenticity.status, AuthenticityStatus::HashUnknown);
+        registry.record("kk1", authenticity);
+        assert!(registry.require_verified("kk1").is_ok());
+
+        // Empty strings from conversion defaults are not a mismatch
+        let authenticity = assess_authenticity(Some(""), Some(""), None);
+        assert_eq!(authenticity.status, AuthenticityStatus::HashUnknown);
+
+        // Released firmware with odd metadata is not blocked either
+        let authenticity = assess_authenticity(Some("Custom"), Some("K1-14WL-S"), Some(true));
+        assert_eq!(authenticity.status, AuthenticityStatus::IdentityUnrecognized);
+        registry.record("kk2", authenticity);
+        assert!(registry.require_verified("kk2").is_ok());
+    }
+
+    #[test]
+    fn test_full_mismatch_blocks_signing() {
+        let registry = AuthenticityRegistry::new();
+        let authenticity = assess_authenticity(Some("Trezor"), Some("K1-14AM"), Some(false));
+        assert_eq!(authenticity.status, AuthenticityStatus::VendorMismatch);
+        assert!(authenticity.reasons.iter().any(|r| r.contains("Trezor")));
+
+        registry.record("clone", authenticity);
+        let err = registry.require_verified("clone").unwrap_err();
+        let json: serde_json::Value = serde_json::from_str(&err.to_json_string()).unwrap();
+        assert_eq!(json["kind"], "UnverifiedDevice");
+        assert_eq!(json["device_id"], "clone");
+        assert_eq!(json["vendor"], "Trezor");
+
+        // A missing hash counts as a failed hash check
+        let authenticity = assess_authenticity(Some("keepkey.com"), Some("X9"), None);
+        assert!(authenticity.vendor_mismatch());
+
+        // Re-reading features from a genuine device clears the block
+        registry.record("clone", assess_authenticity(Some("keepkey.com"), Some("K1-14AM"), Some(true)));
+        assert!(registry.require_verified("clone").is_ok());
+    }
+}
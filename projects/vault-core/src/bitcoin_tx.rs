@@ -0,0 +1,300 @@
+// bitcoin_tx.rs - Multi-recipient Bitcoin transaction planning (batch payments)
+
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use crate::units::format_units;
+use crate::utxo::{check_fee_rate, estimate_vsize, fee_for_vsize, total_sats, UtxoScriptType, DUST_LIMIT_SATS};
+
+/// Maximum number of recipient outputs in one transaction
+pub const MAX_RECIPIENTS: usize = 100;
+
+const BTC_DECIMALS: u32 = 8;
+
+/// A payment output requested by the user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct Recipient {
+    pub address: String,
+    pub amount_sats: u64,
+}
+
+/// A UTXO available for coin selection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SpendableUtxo {
+    pub txid: String,
+    pub vout: u32,
+    pub amount_sats: u64,
+    pub path: String,
+}
+
+/// Selected inputs, recipient outputs, change and fee for a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BatchPlan {
+    pub inputs: Vec<SpendableUtxo>,
+    pub recipients: Vec<Recipient>,
+    /// None when the leftover was below dust and went to the fee
+    pub change_sats: Option<u64>,
+    pub fee_sats: u64,
+    pub vsize: u64,
+}
+
+/// One preview line per recipient output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct PreviewOutput {
+    pub address: String,
+    pub amount_sats: u64,
+    pub amount: String,
+    pub value_usd: Option<String>,
+}
+
+/// Preview shown before the device walks through each output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct BatchPreview {
+    pub outputs: Vec<PreviewOutput>,
+    pub change_sats: Option<u64>,
+    pub fee_sats: u64,
+    pub fee_usd: Option<String>,
+    pub total_sats: u64,
+    pub total_usd: Option<String>,
+}
+
+impl BatchPlan {
+    /// Sum of all recipient outputs
+    pub fn total_sent(&self) -> u64 {
+        self.recipients.iter().map(|r| r.amount_sats).sum()
+    }
+
+    pub fn preview(&self, price_usd: Option<f64>) -> BatchPreview {
+        let usd = |sats: u64| price_usd.map(|p| format!("{:.2}", sats as f64 / 1e8 * p));
+        BatchPreview {
+            outputs: self
+                .recipients
+                .iter()
+                .map(|r| PreviewOutput {
+                    address: r.address.clone(),
+                    amount_sats: r.amount_sats,
+                    amount: format_units(r.amount_sats as u128, BTC_DECIMALS),
+                    value_usd: usd(r.amount_sats),
+                })
+                .collect(),
+            change_sats: self.change_sats,
+            fee_sats: self.fee_sats,
+            fee_usd: usd(self.fee_sats),
+            total_sats: self.total_sent() + self.fee_sats,
+            total_usd: usd(self.total_sent() + self.fee_sats),
+        }
+    }
+
+    /// Output list stored in transaction_cache.metadata_json for history
+    pub fn metadata_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "batch": self.recipients.len() > 1,
+            "outputs": self.recipients,
+            "change_sats": self.change_sats,
+            "vsize": self.vsize,
+        })
+    }
+}
+
+/// Output size class for a recipient address, falling back to the largest standard output
+pub fn output_type_for_address(address: &str) -> UtxoScriptType {
+    let lower = address.to_ascii_lowercase();
+    if lower.starts_with("bc1q") || lower.starts_with("tb1q") || lower.starts_with("bcrt1q") {
+        // 42 chars is a P2WPKH program; longer ones are P2WSH, sized like P2TR
+        if lower.len() <= 44 { UtxoScriptType::P2wpkh } else { UtxoScriptType::P2tr }
+    } else if lower.starts_with("bc1p") || lower.starts_with("tb1p") || lower.starts_with("bcrt1p") {
+        UtxoScriptType::P2tr
+    } else if address.starts_with('3') || address.starts_with('2') {
+        UtxoScriptType::P2shP2wpkh
+    } else if address.starts_with('1') || address.starts_with('m') || address.starts_with('n') {
+        UtxoScriptType::P2pkh
+    } else {
+        UtxoScriptType::P2tr
+    }
+}
+
+/// Validate recipient outputs and return their combined amount.
+///
+/// Identical address+amount pairs are usually a UI mistake, so they are
+/// rejected unless `allow_duplicate_outputs` is set.
+pub fn validate_recipients(recipients: &[Recipient], allow_duplicate_outputs: bool) -> Result<u64, String> {
+    if recipients.is_empty() {
+        return Err("At least one recipient is required".to_string());
+    }
+    if recipients.len() > MAX_RECIPIENTS {
+        return Err(format!("Too many recipients: {} (max {})", recipients.len(), MAX_RECIPIENTS));
+    }
+
+    let mut seen = HashSet::new();
+    let mut total: u64 = 0;
+    for (index, recipient) in recipients.iter().enumerate() {
+        if recipient.address.trim().is_empty() {
+            return Err(format!("Output {} has no address", index + 1));
+        }
+        if recipient.amount_sats < DUST_LIMIT_SATS {
+            return Err(format!(
+                "Output {} to {} is below the dust limit ({} < {} sats)",
+                index + 1, recipient.address, recipient.amount_sats, DUST_LIMIT_SATS
+            ));
+        }
+        if !allow_duplicate_outputs && !seen.insert((recipient.address.as_str(), recipient.amount_sats)) {
+            return Err(format!(
+                "Output {} duplicates an earlier output to {} for {} sats",
+                index + 1, recipient.address, recipient.amount_sats
+            ));
+        }
+        total = total
+            .checked_add(recipient.amount_sats)
+            .ok_or_else(|| "Total output amount overflows".to_string())?;
+    }
+    Ok(total)
+}
+
+/// Select inputs (largest first) to pay every recipient plus the fee.
+///
+/// A change output is added only when the leftover after its own cost is at
+/// least dust; otherwise the leftover is absorbed into the fee.
+pub fn plan_batch(
+    utxos: &[SpendableUtxo],
+    input_type: UtxoScriptType,
+    recipients: &[Recipient],
+    change_type: UtxoScriptType,
+    fee_rate_sat_vb: u64,
+    allow_duplicate_outputs: bool,
+) -> Result<BatchPlan, String> {
+    let target = validate_recipients(recipients, allow_duplicate_outputs)?;
+    check_fee_rate(fee_rate_sat_vb)?;
+
+    let mut outputs: Vec<UtxoScriptType> = recipients.iter().map(|r| output_type_for_address(&r.address)).collect();
+    let mut sorted = utxos.to_vec();
+    sorted.sort_by_key(|u| std::cmp::Reverse(u.amount_sats));
+
+    let mut total: u64 = 0;
+    for count in 1..=sorted.len() {
+        total = total_sats([total, sorted[count - 1].amount_sats])?;
+
+        let vsize = estimate_vsize(input_type, count, &outputs);
+        let fee_no_change = fee_for_vsize(vsize, fee_rate_sat_vb)?;
+        match target.checked_add(fee_no_change) {
+            Some(needed) if total >= needed => {}
+            _ => continue,
+        }
+
+        outputs.push(change_type);
+        let vsize_with_change = estimate_vsize(input_type, count, &outputs);
+        outputs.pop();
+        let fee_with_change = fee_for_vsize(vsize_with_change, fee_rate_sat_vb)?;
+
+        let inputs = sorted[..count].to_vec();
+        let recipients = recipients.to_vec();
+        let change = target.checked_add(fee_with_change).and_then(|needed| total.checked_sub(needed));
+        return Ok(match change {
+            Some(change) if change >= DUST_LIMIT_SATS => BatchPlan {
+                inputs,
+                recipients,
+                change_sats: Some(change),
+                fee_sats: fee_with_change,
+                vsize: vsize_with_change,
+            },
+            _ => BatchPlan {
+                inputs,
+                recipients,
+                change_sats: None,
+                fee_sats: total - target,
+                vsize,
+            },
+        });
+    }
+
+    Err(format!(
+        "Insufficient funds: outputs total {} sats plus fee, available {} sats",
+        target,
+        total_sats(utxos.iter().map(|u| u.amount_sats))?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEGWIT_ADDR: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+    fn utxo(n: u32, amount_sats: u64) -> SpendableUtxo {
+        SpendableUtxo { txid: format!("{:064x}", n), vout: 0, amount_sats, path: "m/84'/0'/0'/0/0".to_string() }
+    }
+
+    fn recipients(count: usize, amount_sats: u64) -> Vec<Recipient> {
+        (0..count)
+            .map(|i| Recipient { address: format!("{}{}", &SEGWIT_ADDR[..SEGWIT_ADDR.len() - 2], i % 100), amount_sats })
+            .collect()
+    }
+
+    #[test]
+    fn test_single_output_with_change() {
+        let plan = plan_batch(&[utxo(1, 100_000)], UtxoScriptType::P2wpkh, &recipients(1, 40_000), UtxoScriptType::P2wpkh, 10, false).unwrap();
+        // 11 + 68 + 31 recipient + 31 change
+        assert_eq!(plan.vsize, 141);
+        assert_eq!(plan.fee_sats, 1_410);
+        assert_eq!(plan.change_sats, Some(58_590));
+        assert_eq!(plan.total_sent() + plan.fee_sats + plan.change_sats.unwrap(), 100_000);
+    }
+
+    #[test]
+    fn test_three_outputs_select_enough_inputs() {
+        let utxos = [utxo(1, 20_000), utxo(2, 50_000), utxo(3, 30_000)];
+        let plan = plan_batch(&utxos, UtxoScriptType::P2wpkh, &recipients(3, 20_000), UtxoScriptType::P2wpkh, 5, false).unwrap();
+        assert_eq!(plan.inputs.len(), 2);
+        assert_eq!(plan.inputs[0].amount_sats, 50_000);
+        let change = plan.change_sats.unwrap();
+        assert_eq!(plan.total_sent() + plan.fee_sats + change, 80_000);
+
+        let preview = plan.preview(Some(50_000.0));
+        assert_eq!(preview.outputs.len(), 3);
+        assert_eq!(preview.outputs[0].amount, "0.0002");
+        assert_eq!(preview.outputs[0].value_usd.as_deref(), Some("10.00"));
+        assert_eq!(plan.metadata_json()["outputs"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_fifty_outputs_without_change() {
+        let outs = recipients(50, 1_000);
+        // 11 + 68 + 50 * 31 = 1629 vbytes at 1 sat/vB; leftover below dust goes to fee
+        let plan = plan_batch(&[utxo(1, 50_000 + 1_629 + 100)], UtxoScriptType::P2wpkh, &outs, UtxoScriptType::P2wpkh, 1, false).unwrap();
+        assert_eq!(plan.recipients.len(), 50);
+        assert_eq!(plan.change_sats, None);
+        assert_eq!(plan.fee_sats, 1_729);
+        assert_eq!(plan.vsize, 1_629);
+    }
+
+    #[test]
+    fn test_rejects_dust_duplicates_and_too_many() {
+        let mut outs = recipients(3, 10_000);
+        outs[1].amount_sats = 545;
+        let err = validate_recipients(&outs, false).unwrap_err();
+        assert!(err.contains("Output 2") && err.contains("dust"));
+
+        let dup = vec![recipients(1, 10_000)[0].clone(), recipients(1, 10_000)[0].clone()];
+        assert!(validate_recipients(&dup, false).is_err());
+        assert_eq!(validate_recipients(&dup, true), Ok(20_000));
+
+        assert!(validate_recipients(&recipients(MAX_RECIPIENTS + 1, 10_000), true).is_err());
+        assert!(plan_batch(&[utxo(1, 5_000)], UtxoScriptType::P2wpkh, &recipients(1, 10_000), UtxoScriptType::P2wpkh, 1, false).is_err());
+    }
+
+    #[test]
+    fn test_amount_overflow_is_an_error() {
+        let utxos = [utxo(1, u64::MAX - 10), utxo(2, 20)];
+        let err = plan_batch(&utxos, UtxoScriptType::P2wpkh, &recipients(1, u64::MAX - 5), UtxoScriptType::P2wpkh, 1, false).unwrap_err();
+        assert!(err.contains("overflows"));
+    }
+
+    #[test]
+    fn test_output_type_for_address() {
+        assert_eq!(output_type_for_address(SEGWIT_ADDR), UtxoScriptType::P2wpkh);
+        assert_eq!(output_type_for_address("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"), UtxoScriptType::P2pkh);
+        assert_eq!(output_type_for_address("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"), UtxoScriptType::P2shP2wpkh);
+    }
+}
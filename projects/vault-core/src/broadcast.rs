@@ -0,0 +1,304 @@
+// broadcast.rs - Broadcast outcomes and watching a broadcast transaction
+//
+// A node refuses a raw transaction for one of three reasons that the UI has
+// to tell apart: it already has it (nothing went wrong, the transaction is
+// out), it conflicts with one the chain already has (a nonce too low, inputs
+// already spent) so it can never confirm, or it is invalid. None of these
+// are worth retrying on another endpoint, unlike a node that cannot be
+// reached at all.
+//
+// After a broadcast the transaction is polled until it is included, and
+// reported failed when it reverts or the node stops knowing it. A watch that
+// runs out of polls leaves the transaction pending for the periodic tracker.
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+use bitcoin::Transaction;
+use serde::Serialize;
+use sha3::{Digest, Keccak256};
+use crate::paths::{network_family, NetworkFamily};
+
+/// Emitted when a broadcast transaction is included
+pub const CONFIRMED_EVENT: &str = "transaction:confirmed";
+/// Emitted when a broadcast transaction reverts or is dropped
+pub const FAILED_EVENT: &str = "transaction:failed";
+
+/// Node messages meaning it already has the transaction
+const ALREADY_KNOWN: &[&str] = &[
+    "already known",
+    "known transaction",
+    "already imported",
+    "already exists",
+    "txn-already-known",
+    "txn-already-in-mempool",
+    "already in block chain",
+];
+
+/// Node messages meaning a pending transaction uses the same nonce or inputs
+/// and this one does not pay enough more to replace it
+const UNDERPRICED: &[&str] = &[
+    "replacement transaction underpriced",
+    "rejecting replacement",
+];
+
+/// Node messages meaning the transaction conflicts with the chain's state
+const CONFLICT: &[&str] = &[
+    "nonce too low",
+    "nonce is too low",
+    "txn-mempool-conflict",
+    "bad-txns-inputs-missingorspent",
+    "missing inputs",
+    "missingorspent",
+];
+
+/// Why a node refused a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    AlreadyKnown,
+    Underpriced,
+    Conflict,
+    Invalid,
+}
+
+/// Classify a node's refusal by its message
+pub fn classify_rejection(message: &str) -> Rejection {
+    let message = message.to_ascii_lowercase();
+    if ALREADY_KNOWN.iter().any(|m| message.contains(m)) {
+        Rejection::AlreadyKnown
+    } else if UNDERPRICED.iter().any(|m| message.contains(m)) {
+        Rejection::Underpriced
+    } else if CONFLICT.iter().any(|m| message.contains(m)) {
+        Rejection::Conflict
+    } else {
+        Rejection::Invalid
+    }
+}
+
+/// Structured error for a failed broadcast
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum BroadcastError {
+    /// The raw transaction could not be decoded
+    InvalidTransaction { message: String },
+    /// The network has no endpoint to broadcast through
+    NoEndpoint { network_id: String },
+    /// No endpoint could be reached; the transaction may be retried
+    Unreachable { endpoints: Vec<String>, message: String },
+    /// Replaces a pending transaction without paying enough more fee
+    Underpriced { endpoint: String, message: String },
+    /// Spends a nonce or inputs the chain has already used
+    Conflict { endpoint: String, message: String },
+    /// The node refused the transaction
+    Rejected { endpoint: String, message: String },
+}
+
+impl BroadcastError {
+    /// Encode for a `Result<_, String>` command error
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl fmt::Display for BroadcastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BroadcastError::InvalidTransaction { message } => write!(f, "Invalid transaction: {}", message),
+            BroadcastError::NoEndpoint { network_id } => write!(f, "No RPC endpoint configured for {}", network_id),
+            BroadcastError::Unreachable { endpoints, message } => {
+                write!(f, "Could not reach {}: {}", endpoints.join(", "), message)
+            }
+            BroadcastError::Underpriced { endpoint, message } => {
+                write!(f, "{} refused the replacement of a pending transaction as underpriced: {}", endpoint, message)
+            }
+            BroadcastError::Conflict { endpoint, message } => {
+                write!(f, "{} refused the transaction as already spent: {}", endpoint, message)
+            }
+            BroadcastError::Rejected { endpoint, message } => write!(f, "{} rejected the transaction: {}", endpoint, message),
+        }
+    }
+}
+
+/// Decode a raw transaction and return its id the way the network reports it:
+/// the txid for UTXO chains, the 0x-prefixed Keccak-256 hash for EVM chains
+pub fn decode_raw_tx(network_id: &str, raw_tx_hex: &str) -> Result<(Vec<u8>, String), BroadcastError> {
+    let invalid = |message: String| BroadcastError::InvalidTransaction { message };
+    let hex_str = raw_tx_hex.trim();
+    let hex_str = hex_str.strip_prefix("0x").unwrap_or(hex_str);
+    let raw = hex::decode(hex_str).map_err(|e| invalid(format!("not hex: {}", e)))?;
+    if raw.is_empty() {
+        return Err(invalid("empty".to_string()));
+    }
+
+    let txid = match network_family(network_id).map_err(invalid)? {
+        NetworkFamily::Utxo { .. } => {
+            let tx: Transaction = bitcoin::consensus::deserialize(&raw).map_err(|e| invalid(e.to_string()))?;
+            tx.txid().to_string()
+        }
+        NetworkFamily::Evm { .. } => format!("0x{}", hex::encode(Keccak256::digest(&raw))),
+    };
+    Ok((raw, txid))
+}
+
+/// Where a broadcast transaction is, as far as a node can tell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InclusionStatus {
+    /// In a block; `succeeded` is false for a reverted EVM transaction
+    Included { height: i64, succeeded: bool },
+    InMempool,
+    NotFound,
+}
+
+/// How a watch ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchOutcome {
+    Confirmed { height: i64 },
+    Failed { reason: String, height: Option<i64> },
+    /// Out of polls while still pending
+    StillPending,
+}
+
+/// Poll `lookup` every `interval`, up to `polls` times, until the transaction
+/// is included. It fails when it reverts or goes unseen for `drop_after`
+/// polls in a row; failed lookups count toward neither.
+pub async fn watch_transaction<L, Fut>(lookup: L, interval: Duration, polls: u32, drop_after: u32) -> WatchOutcome
+where
+    L: Fn() -> Fut,
+    Fut: Future<Output = Result<InclusionStatus, String>>,
+{
+    let mut unseen = 0;
+    for _ in 0..polls {
+        tokio::time::sleep(interval).await;
+        match lookup().await {
+            Ok(InclusionStatus::Included { height, succeeded: true }) => return WatchOutcome::Confirmed { height },
+            Ok(InclusionStatus::Included { height, succeeded: false }) => {
+                return WatchOutcome::Failed { reason: format!("Reverted in block {}", height), height: Some(height) };
+            }
+            Ok(InclusionStatus::InMempool) => unseen = 0,
+            Ok(InclusionStatus::NotFound) => {
+                unseen += 1;
+                if unseen >= drop_after {
+                    return WatchOutcome::Failed {
+                        reason: format!("Dropped: the node has not seen it in {} checks", unseen),
+                        height: None,
+                    };
+                }
+            }
+            Err(e) => log::debug!("Broadcast watch lookup failed: {}", e),
+        }
+    }
+    WatchOutcome::StillPending
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::{ready, Ready};
+    use std::sync::Mutex;
+    use bitcoin::consensus::encode::serialize_hex;
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93";
+
+    /// Answers `lookup` calls in order, repeating the last one
+    fn scripted(answers: Vec<Result<InclusionStatus, String>>) -> impl Fn() -> Ready<Result<InclusionStatus, String>> {
+        let answers = Mutex::new(answers);
+        move || {
+            let mut answers = answers.lock().unwrap();
+            let answer = if answers.len() > 1 { answers.remove(0) } else { answers[0].clone() };
+            ready(answer)
+        }
+    }
+
+    #[test]
+    fn test_classify_node_messages() {
+        for message in [
+            "RPC error -32000: already known",
+            "known transaction: 0xabc",
+            "Transaction with the same hash was already imported.",
+            "sendrawtransaction RPC error: {\"code\":-27,\"message\":\"Transaction already in block chain\"}",
+            "sendrawtransaction RPC error: {\"code\":-26,\"message\":\"txn-already-in-mempool\"}",
+        ] {
+            assert_eq!(classify_rejection(message), Rejection::AlreadyKnown, "{}", message);
+        }
+        for message in [
+            "nonce too low: address 0x1, tx: 5 state: 7",
+            "sendrawtransaction RPC error: {\"code\":-25,\"message\":\"bad-txns-inputs-missingorspent\"}",
+            "sendrawtransaction RPC error: {\"code\":-26,\"message\":\"txn-mempool-conflict\"}",
+        ] {
+            assert_eq!(classify_rejection(message), Rejection::Conflict, "{}", message);
+        }
+        for message in [
+            "replacement transaction underpriced",
+            "sendrawtransaction RPC error: {\"code\":-26,\"message\":\"insufficient fee, rejecting replacement 1a2b, not enough additional fees to relay\"}",
+        ] {
+            assert_eq!(classify_rejection(message), Rejection::Underpriced, "{}", message);
+        }
+        for message in ["insufficient funds for gas * price + value", "min relay fee not met", "TX decode failed"] {
+            assert_eq!(classify_rejection(message), Rejection::Invalid, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_decode_raw_tx() {
+        let genesis = bitcoin::blockdata::constants::genesis_block(bitcoin::Network::Bitcoin);
+        let raw = serialize_hex(&genesis.txdata[0]);
+        let (bytes, txid) = decode_raw_tx(BTC, &raw).unwrap();
+        assert_eq!(bytes.len(), raw.len() / 2);
+        assert_eq!(txid, "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b");
+
+        // EVM ids are the Keccak-256 of the raw bytes
+        let (_, hash) = decode_raw_tx("eip155:1", "0x616263").unwrap();
+        assert_eq!(hash, "0x4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45");
+
+        for (network, raw) in [(BTC, "zz"), (BTC, ""), (BTC, "0100"), ("cosmos:cosmoshub-4", "00")] {
+            let err = decode_raw_tx(network, raw).unwrap_err();
+            assert!(matches!(err, BroadcastError::InvalidTransaction { .. }), "{} {}", network, raw);
+        }
+    }
+
+    #[test]
+    fn test_error_json_has_kind() {
+        let err = BroadcastError::Conflict { endpoint: "https://rpc".to_string(), message: "nonce too low".to_string() };
+        let json: serde_json::Value = serde_json::from_str(&err.to_json_string()).unwrap();
+        assert_eq!(json["kind"], "Conflict");
+        assert_eq!(json["message"], "nonce too low");
+    }
+
+    #[tokio::test]
+    async fn test_watch_confirms_after_mempool() {
+        let lookup = scripted(vec![
+            Err("timeout".to_string()),
+            Ok(InclusionStatus::NotFound),
+            Ok(InclusionStatus::InMempool),
+            Ok(InclusionStatus::NotFound),
+            Ok(InclusionStatus::Included { height: 812_000, succeeded: true }),
+        ]);
+        let outcome = watch_transaction(lookup, Duration::ZERO, 10, 2).await;
+        assert_eq!(outcome, WatchOutcome::Confirmed { height: 812_000 });
+    }
+
+    #[tokio::test]
+    async fn test_watch_reports_reverts_and_drops() {
+        let reverted = scripted(vec![Ok(InclusionStatus::Included { height: 19_000_000, succeeded: false })]);
+        let outcome = watch_transaction(reverted, Duration::ZERO, 10, 3).await;
+        assert!(matches!(outcome, WatchOutcome::Failed { height: Some(19_000_000), .. }));
+
+        // Lookup failures between misses do not reset or advance the count
+        let dropped = scripted(vec![
+            Ok(InclusionStatus::NotFound),
+            Err("502".to_string()),
+            Ok(InclusionStatus::NotFound),
+            Ok(InclusionStatus::NotFound),
+        ]);
+        let outcome = watch_transaction(dropped, Duration::ZERO, 10, 3).await;
+        assert!(matches!(outcome, WatchOutcome::Failed { height: None, ref reason } if reason.starts_with("Dropped")));
+    }
+
+    #[tokio::test]
+    async fn test_watch_runs_out_of_polls() {
+        let pending = scripted(vec![Ok(InclusionStatus::InMempool)]);
+        assert_eq!(watch_transaction(pending, Duration::ZERO, 5, 3).await, WatchOutcome::StillPending);
+        let unreachable = scripted(vec![Err("connection refused".to_string())]);
+        assert_eq!(watch_transaction(unreachable, Duration::ZERO, 5, 1).await, WatchOutcome::StillPending);
+    }
+}
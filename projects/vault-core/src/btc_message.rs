@@ -0,0 +1,182 @@
+// btc_message.rs - Signing and verifying Bitcoin messages on the device
+//
+// The device shows the message and waits for the button before answering;
+// declining there is reported as Cancelled, apart from a device that could
+// not be reached. The queue's standard handler turns a Failure answer into
+// an error reading "Failure: <message>", so both forms are classified here.
+// Signatures go in and out as base64, the form Bitcoin wallets exchange.
+
+use base64::Engine;
+use serde::Serialize;
+use keepkey_rust::messages::{self, Message};
+use crate::paths::parse_derivation_path;
+use crate::queue::{get_or_create_device_queue, DeviceQueueManager};
+use crate::utxo::UtxoScriptType;
+
+/// Length of a compact recoverable signature
+const SIGNATURE_LEN: usize = 65;
+
+/// What the device answers when a signature does not match
+const INVALID_SIGNATURE: &str = "Invalid signature";
+
+/// Why a message could not be signed or verified
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum MessageError {
+    Invalid { message: String },
+    /// Declined on the device
+    Cancelled,
+    DeviceUnavailable { message: String },
+    Rejected { message: String },
+}
+
+impl MessageError {
+    /// Encode for a `Result<_, String>` command error
+    pub fn to_json_string(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(fields) = value.as_object_mut() {
+            fields.insert("summary".to_string(), self.to_string().into());
+        }
+        value.to_string()
+    }
+}
+
+impl std::fmt::Display for MessageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageError::Invalid { message } => write!(f, "{}", message),
+            MessageError::Cancelled => write!(f, "Cancelled on the device"),
+            MessageError::DeviceUnavailable { message } => write!(f, "Device unavailable: {}", message),
+            MessageError::Rejected { message } => write!(f, "Device refused the message: {}", message),
+        }
+    }
+}
+
+fn failure_error(failure: &messages::Failure) -> MessageError {
+    match failure.code() {
+        messages::FailureType::FailureActionCancelled | messages::FailureType::FailurePinCancelled => MessageError::Cancelled,
+        _ => MessageError::Rejected { message: failure.message().to_string() },
+    }
+}
+
+/// Classify an error from the queue. Only the text of a Failure survives the
+/// standard handler, so a cancel is recognised by its wording.
+fn queue_error(error: &str) -> MessageError {
+    match error.strip_prefix("Failure: ") {
+        Some(reason) if reason.to_lowercase().contains("cancel") => MessageError::Cancelled,
+        Some(reason) => MessageError::Rejected { message: reason.to_string() },
+        None => MessageError::DeviceUnavailable { message: error.to_string() },
+    }
+}
+
+fn decode_signature(signature: &str) -> Result<Vec<u8>, MessageError> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(signature.trim())
+        .map_err(|e| MessageError::Invalid { message: format!("Signature is not base64: {}", e) })?;
+    if bytes.len() != SIGNATURE_LEN {
+        return Err(MessageError::Invalid {
+            message: format!("Signature must be {} bytes, not {}", SIGNATURE_LEN, bytes.len()),
+        });
+    }
+    Ok(bytes)
+}
+
+async fn send(queue_manager: &DeviceQueueManager, device_id: &str, request: Message) -> Result<Message, MessageError> {
+    let queue = get_or_create_device_queue(device_id, queue_manager)
+        .await
+        .map_err(|message| MessageError::DeviceUnavailable { message })?;
+    match queue.send_raw(request, false).await {
+        Ok(Message::Failure(f)) => Err(failure_error(&f)),
+        Ok(response) => Ok(response),
+        Err(e) => Err(queue_error(&e.to_string())),
+    }
+}
+
+/// Sign `message` with the key at `path`; returns the base64 signature.
+/// `script_type` is a derivation_paths name and selects the address the
+/// signature commits to, the legacy address when omitted.
+pub async fn sign_btc_message(
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+    path: &str,
+    message: &str,
+    script_type: Option<&str>,
+) -> Result<String, MessageError> {
+    let invalid = |message: String| MessageError::Invalid { message };
+    let address_n = parse_derivation_path(path).map_err(invalid)?;
+    let script_type = script_type.map(UtxoScriptType::parse).transpose().map_err(invalid)?;
+    let request = Message::SignMessage(messages::SignMessage {
+        address_n,
+        message: message.as_bytes().to_vec(),
+        coin_name: Some("Bitcoin".to_string()),
+        script_type: script_type.map(|t| t.input_script_type()),
+    });
+    match send(queue_manager, device_id, request).await? {
+        Message::MessageSignature(sig) => {
+            let signature = sig.signature.ok_or_else(|| MessageError::Rejected { message: "No signature in response".to_string() })?;
+            Ok(base64::engine::general_purpose::STANDARD.encode(signature))
+        }
+        other => Err(MessageError::Rejected { message: format!("Unexpected response: {:?}", other.message_type()) }),
+    }
+}
+
+/// Have the device check a base64 `signature` of `message` by `address`.
+/// A signature that does not match is `Ok(false)`, not an error.
+pub async fn verify_btc_message(
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+    address: &str,
+    signature: &str,
+    message: &str,
+) -> Result<bool, MessageError> {
+    let address = address.trim();
+    if address.is_empty() {
+        return Err(MessageError::Invalid { message: "Address cannot be empty".to_string() });
+    }
+    let request = Message::VerifyMessage(messages::VerifyMessage {
+        address: Some(address.to_string()),
+        signature: Some(decode_signature(signature)?),
+        message: Some(message.as_bytes().to_vec()),
+        coin_name: Some("Bitcoin".to_string()),
+    });
+    match send(queue_manager, device_id, request).await {
+        Ok(Message::Success(_)) => Ok(true),
+        Err(MessageError::Rejected { message }) if message == INVALID_SIGNATURE => Ok(false),
+        Ok(other) => Err(MessageError::Rejected { message: format!("Unexpected response: {:?}", other.message_type()) }),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_is_distinct_from_transport_errors() {
+        assert_eq!(queue_error("Failure: Sign message cancelled"), MessageError::Cancelled);
+        assert_eq!(
+            queue_error("Failure: Invalid signature"),
+            MessageError::Rejected { message: INVALID_SIGNATURE.to_string() }
+        );
+        assert!(matches!(queue_error("Device operation timed out"), MessageError::DeviceUnavailable { .. }));
+
+        let cancelled = messages::Failure {
+            code: Some(messages::FailureType::FailureActionCancelled as i32),
+            message: Some("Action cancelled by user".to_string()),
+        };
+        assert_eq!(failure_error(&cancelled), MessageError::Cancelled);
+        let json: serde_json::Value = serde_json::from_str(&MessageError::Cancelled.to_json_string()).unwrap();
+        assert_eq!((json["kind"].as_str(), json["summary"].as_str()), (Some("Cancelled"), Some("Cancelled on the device")));
+    }
+
+    #[test]
+    fn test_decode_signature() {
+        let signature = base64::engine::general_purpose::STANDARD.encode([7u8; SIGNATURE_LEN]);
+        assert_eq!(decode_signature(&format!(" {}\n", signature)).unwrap(), vec![7u8; SIGNATURE_LEN]);
+
+        let short = base64::engine::general_purpose::STANDARD.encode([7u8; 64]);
+        for bad in ["not base64!", short.as_str(), ""] {
+            assert!(matches!(decode_signature(bad), Err(MessageError::Invalid { .. })), "{:?}", bad);
+        }
+    }
+}
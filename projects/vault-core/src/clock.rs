@@ -0,0 +1,337 @@
+// clock.rs - Host clock sanity for protocols that embed timestamps
+//
+// IBC transfers carry a timeout_timestamp checked against the destination
+// chain's block time, and swap quotes carry expiries. A host clock that is a
+// few minutes off turns those into transfers that time out immediately (or
+// never). The offset against an SNTP server, or the HTTP Date header of a
+// configured endpoint, is measured in the background and applied by the
+// builders, and to the timeouts of transfers built by the frontend before
+// they are signed; when it is unknown and the last known offset was large
+// they refuse instead.
+
+use std::net::UdpSocket;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use keepkey_rust::chains::cosmos::{Coin, CosmosMessageType};
+
+/// Preference key holding the last measurement as JSON
+pub const CLOCK_OFFSET_PREF: &str = "clock_offset";
+
+/// Offsets beyond this are announced with `system:clock-offset`
+pub const CLOCK_OFFSET_WARN_MS: i64 = 30_000;
+
+/// A measurement older than this no longer counts as known
+pub const OFFSET_STALE_AFTER_SECS: i64 = 6 * 60 * 60;
+
+/// Default SNTP server
+pub const DEFAULT_NTP_SERVER: &str = "pool.ntp.org:123";
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch
+const NTP_UNIX_OFFSET_SECS: u64 = 2_208_988_800;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum OffsetSource {
+    Ntp,
+    HttpDate,
+}
+
+/// Reference time minus host time, from one exchange
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ClockMeasurement {
+    pub offset_ms: i64,
+    pub source: OffsetSource,
+    /// Host time of the measurement (Unix seconds)
+    pub measured_at: i64,
+    pub round_trip_ms: u64,
+}
+
+impl ClockMeasurement {
+    pub fn exceeds_threshold(&self) -> bool {
+        self.offset_ms.abs() > CLOCK_OFFSET_WARN_MS
+    }
+}
+
+/// Current knowledge of the host clock offset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ClockStatus {
+    /// Offset to apply, if a fresh measurement exists
+    pub offset_ms: Option<i64>,
+    pub last_measurement: Option<ClockMeasurement>,
+    pub stale: bool,
+}
+
+/// Structured error for builders refusing to produce timestamps
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum ClockError {
+    OffsetUnknown { last_offset_ms: i64, measured_at: i64 },
+    /// An IBC transfer whose timeout has already passed by the corrected clock
+    TimeoutPassed { timeout_timestamp: u64 },
+}
+
+impl ClockError {
+    /// Encode for a `Result<_, String>` command error
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl std::fmt::Display for ClockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClockError::OffsetUnknown { last_offset_ms, .. } => write!(
+                f,
+                "The system clock was last measured {}s off and could not be re-checked; fix the clock or retry when online",
+                last_offset_ms / 1000
+            ),
+            ClockError::TimeoutPassed { .. } => {
+                write!(f, "The transfer's timeout has already passed; build it again")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClockError {}
+
+fn unix_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+fn shift(time: SystemTime, offset_ms: i64) -> SystemTime {
+    if offset_ms >= 0 {
+        time + Duration::from_millis(offset_ms as u64)
+    } else {
+        time - Duration::from_millis(offset_ms.unsigned_abs())
+    }
+}
+
+/// Offset from one request/response exchange, assuming the reference time
+/// was taken halfway through the round trip
+pub fn offset_from_exchange(sent: SystemTime, received: SystemTime, reference: SystemTime) -> i64 {
+    let (sent, received) = (unix_millis(sent), unix_millis(received));
+    unix_millis(reference) - (sent + (received - sent) / 2)
+}
+
+/// Measured host clock offset, shared by the checker and the builders
+#[derive(Debug, Default)]
+pub struct ClockState {
+    last: Mutex<Option<ClockMeasurement>>,
+}
+
+impl ClockState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seed from a measurement persisted by a previous run
+    pub fn with_measurement(measurement: Option<ClockMeasurement>) -> Self {
+        Self { last: Mutex::new(measurement) }
+    }
+
+    pub fn record(&self, measurement: ClockMeasurement) {
+        *self.last.lock().unwrap() = Some(measurement);
+    }
+
+    pub fn status(&self, host_now: SystemTime) -> ClockStatus {
+        let last = self.last.lock().unwrap().clone();
+        let stale = last
+            .as_ref()
+            .map_or(true, |m| unix_secs(host_now) - m.measured_at > OFFSET_STALE_AFTER_SECS);
+        ClockStatus {
+            offset_ms: if stale { None } else { last.as_ref().map(|m| m.offset_ms) },
+            last_measurement: last,
+            stale,
+        }
+    }
+
+    /// Host time corrected by the measured offset. With no fresh measurement
+    /// the host clock is trusted unless it was last seen beyond the threshold.
+    pub fn corrected_now(&self, host_now: SystemTime) -> Result<SystemTime, ClockError> {
+        let status = self.status(host_now);
+        if let Some(offset_ms) = status.offset_ms {
+            return Ok(shift(host_now, offset_ms));
+        }
+        match status.last_measurement {
+            Some(m) if m.exceeds_threshold() => Err(ClockError::OffsetUnknown {
+                last_offset_ms: m.offset_ms,
+                measured_at: m.measured_at,
+            }),
+            _ => Ok(host_now),
+        }
+    }
+
+    /// Whether a quote or offer expiring at `expires_at` (Unix seconds) has expired
+    pub fn is_expired(&self, expires_at: i64, host_now: SystemTime) -> Result<bool, ClockError> {
+        Ok(unix_secs(self.corrected_now(host_now)?) >= expires_at)
+    }
+
+    /// Refuse an IBC transfer built elsewhere whose `timeout_timestamp`
+    /// (Unix nanoseconds; 0 for none) has passed by the corrected clock
+    pub fn check_ibc_timeout(&self, timeout_timestamp: u64, host_now: SystemTime) -> Result<(), ClockError> {
+        if timeout_timestamp == 0 {
+            return Ok(());
+        }
+        let expires_at = (timeout_timestamp / 1_000_000_000) as i64;
+        if self.is_expired(expires_at, host_now)? {
+            return Err(ClockError::TimeoutPassed { timeout_timestamp });
+        }
+        Ok(())
+    }
+}
+
+/// Build an IBC transfer whose timeout is measured from the corrected clock
+pub fn build_ibc_transfer(
+    clock: &ClockState,
+    host_now: SystemTime,
+    sender: String,
+    receiver: String,
+    amount: Coin,
+    source_channel: String,
+    timeout_after: Duration,
+) -> Result<CosmosMessageType, ClockError> {
+    let now = clock.corrected_now(host_now)?;
+    Ok(CosmosMessageType::ibc_transfer(sender, receiver, amount, source_channel, now, timeout_after))
+}
+
+/// SNTP (RFC 4330) client request: LI 0, version 4, mode 3
+pub fn sntp_request() -> [u8; 48] {
+    let mut packet = [0u8; 48];
+    packet[0] = 0x23;
+    packet
+}
+
+/// Server transmit time from an SNTP response
+pub fn parse_sntp_response(packet: &[u8]) -> Option<SystemTime> {
+    if packet.len() < 48 {
+        return None;
+    }
+    // Mode 4 (server); stratum 0 is a kiss-of-death
+    if packet[0] & 0x07 != 4 || packet[1] == 0 {
+        return None;
+    }
+    let secs = u32::from_be_bytes(packet[40..44].try_into().ok()?) as u64;
+    let fraction = u32::from_be_bytes(packet[44..48].try_into().ok()?) as u64;
+    let unix_secs = secs.checked_sub(NTP_UNIX_OFFSET_SECS)?;
+    let nanos = (fraction * 1_000_000_000) >> 32;
+    Some(UNIX_EPOCH + Duration::from_secs(unix_secs) + Duration::from_nanos(nanos))
+}
+
+/// Measure the offset against an SNTP server (blocking; run off the async runtime)
+pub fn query_sntp(server: &str, timeout: Duration) -> std::io::Result<ClockMeasurement> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(server)?;
+
+    let started = Instant::now();
+    let sent = SystemTime::now();
+    socket.send(&sntp_request())?;
+    let mut buf = [0u8; 48];
+    let len = socket.recv(&mut buf)?;
+    let received = SystemTime::now();
+
+    let reference = parse_sntp_response(&buf[..len])
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid SNTP response"))?;
+    Ok(ClockMeasurement {
+        offset_ms: offset_from_exchange(sent, received, reference),
+        source: OffsetSource::Ntp,
+        measured_at: unix_secs(received),
+        round_trip_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST_NOW: u64 = 1_700_000_000;
+
+    fn host_now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(HOST_NOW)
+    }
+
+    fn measurement(offset_ms: i64, measured_at: i64) -> ClockMeasurement {
+        ClockMeasurement { offset_ms, source: OffsetSource::HttpDate, measured_at, round_trip_ms: 80 }
+    }
+
+    #[test]
+    fn test_ibc_timeout_corrected_for_fast_host_clock() {
+        // Host clock is 3 minutes ahead of the reference
+        let sent = host_now();
+        let received = sent + Duration::from_millis(200);
+        let reference = UNIX_EPOCH + Duration::from_secs(HOST_NOW - 180) + Duration::from_millis(100);
+        let offset_ms = offset_from_exchange(sent, received, reference);
+        assert_eq!(offset_ms, -180_000);
+
+        let clock = ClockState::new();
+        clock.record(measurement(offset_ms, HOST_NOW as i64));
+        let msg = build_ibc_transfer(
+            &clock,
+            host_now(),
+            "cosmos1sender".to_string(),
+            "osmo1receiver".to_string(),
+            Coin { denom: "uatom".to_string(), amount: "1000".to_string() },
+            "channel-141".to_string(),
+            Duration::from_secs(600),
+        )
+        .unwrap();
+
+        let CosmosMessageType::IbcTransfer { timeout_timestamp, .. } = msg else {
+            panic!("expected an IBC transfer");
+        };
+        // Ten minutes after the true time, not the host's
+        assert_eq!(timeout_timestamp, (HOST_NOW - 180 + 600) * 1_000_000_000);
+    }
+
+    #[test]
+    fn test_refuses_when_large_offset_is_stale() {
+        let stale_at = HOST_NOW as i64 - OFFSET_STALE_AFTER_SECS - 1;
+
+        let clock = ClockState::with_measurement(Some(measurement(180_000, stale_at)));
+        let err = clock.corrected_now(host_now()).unwrap_err();
+        let json: serde_json::Value = serde_json::from_str(&err.to_json_string()).unwrap();
+        assert_eq!(json["kind"], "OffsetUnknown");
+        assert!(clock.is_expired(HOST_NOW as i64 + 60, host_now()).is_err());
+
+        // A small stale offset, or none at all, falls back to the host clock
+        let clock = ClockState::with_measurement(Some(measurement(2_000, stale_at)));
+        assert_eq!(clock.corrected_now(host_now()).unwrap(), host_now());
+        assert_eq!(ClockState::new().corrected_now(host_now()).unwrap(), host_now());
+        assert!(ClockState::new().is_expired(HOST_NOW as i64, host_now()).unwrap());
+    }
+
+    #[test]
+    fn test_ibc_timeout_checked_against_corrected_clock() {
+        // Host clock 3 minutes behind: a timeout 2 minutes past the host's
+        // time has already passed on the destination chain
+        let clock = ClockState::with_measurement(Some(measurement(180_000, HOST_NOW as i64)));
+        let timeout = (HOST_NOW + 120) * 1_000_000_000;
+        assert!(ClockState::new().check_ibc_timeout(timeout, host_now()).is_ok());
+        let err = clock.check_ibc_timeout(timeout, host_now()).unwrap_err();
+        assert_eq!(err, ClockError::TimeoutPassed { timeout_timestamp: timeout });
+        assert!(clock.check_ibc_timeout((HOST_NOW + 600) * 1_000_000_000, host_now()).is_ok());
+        assert!(clock.check_ibc_timeout(0, host_now()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_sntp_response() {
+        let mut packet = [0u8; 48];
+        packet[0] = 0x24; // version 4, server
+        packet[1] = 2;
+        packet[40..44].copy_from_slice(&((HOST_NOW + NTP_UNIX_OFFSET_SECS) as u32).to_be_bytes());
+        packet[44..48].copy_from_slice(&(1u32 << 31).to_be_bytes());
+        assert_eq!(parse_sntp_response(&packet), Some(host_now() + Duration::from_millis(500)));
+
+        packet[1] = 0;
+        assert_eq!(parse_sntp_response(&packet), None);
+        assert_eq!(sntp_request()[0], 0x23);
+    }
+}
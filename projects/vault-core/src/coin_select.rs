@@ -0,0 +1,646 @@
+// coin_select.rs - Coin selection for composed Bitcoin transactions
+//
+// Selection works on effective values: each UTXO's amount minus the fee its
+// own input adds at the chosen rate. UTXOs worth less than that are never
+// spent. Branch-and-bound looks for an input set that pays the recipients
+// and fee without a change output, overshooting by no more than a change
+// output would have cost to create and later spend; the overshoot goes to
+// the fee. When no such set exists inputs are taken largest first and the
+// rest comes back as change, or goes to the fee when it would be dust.
+
+use bitcoin::bip32::{ChildNumber, ExtendedPubKey};
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{Address, Network};
+use serde::{Deserialize, Serialize};
+use crate::bitcoin_tx::{output_type_for_address, validate_recipients, BatchPlan, Recipient, SpendableUtxo};
+use crate::paths::{parse_derivation_path, HARDENED};
+use crate::utxo::{check_fee_rate, estimate_mixed_vsize, fee_for_vsize, total_sats, UtxoScriptType, DUST_LIMIT_SATS};
+
+/// Branch-and-bound gives up after visiting this many nodes
+pub const BNB_MAX_TRIES: usize = 100_000;
+
+/// The "max" amount keyword
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "lowercase")]
+pub enum MaxAmount {
+    Max,
+}
+
+/// Amount of a recipient output: sats, or "max" for everything the inputs
+/// hold after the fee
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(untagged)]
+pub enum SendAmount {
+    Sats(u64),
+    Max(MaxAmount),
+}
+
+/// A recipient whose amount may be "max"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ComposeRecipient {
+    pub address: String,
+    pub amount_sats: SendAmount,
+}
+
+/// How the inputs of a composition were chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "kebab-case")]
+pub enum SelectionMethod {
+    BranchAndBound,
+    LargestFirst,
+    SendMax,
+}
+
+/// Selected inputs, outputs, change and fee, and how they were found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Composition {
+    pub plan: BatchPlan,
+    pub method: SelectionMethod,
+}
+
+/// BIP32 purpose of each script type's accounts
+const PURPOSES: [(u32, UtxoScriptType); 4] = [
+    (44, UtxoScriptType::P2pkh),
+    (49, UtxoScriptType::P2shP2wpkh),
+    (84, UtxoScriptType::P2wpkh),
+    (86, UtxoScriptType::P2tr),
+];
+
+/// Version bytes of mainnet and testnet xpubs
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const TPUB_VERSION: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+
+/// Script type of a UTXO from the purpose of its path (44', 49', 84', 86')
+pub fn input_type_for_path(path: &str) -> Result<UtxoScriptType, String> {
+    let address_n = parse_derivation_path(path)?;
+    let purpose = address_n.first().map(|purpose| purpose & !HARDENED);
+    PURPOSES
+        .iter()
+        .find(|(p, _)| Some(*p) == purpose)
+        .map(|(_, script_type)| *script_type)
+        .ok_or_else(|| format!("Cannot tell the script type of UTXO path {}", path))
+}
+
+/// Account path (m/purpose'/coin'/account') of `change_type` in the coin and
+/// account of a UTXO path
+pub fn change_account_path(utxo_path: &str, change_type: UtxoScriptType) -> Result<Vec<u32>, String> {
+    let address_n = parse_derivation_path(utxo_path)?;
+    if address_n.len() < 3 {
+        return Err(format!("UTXO path {} has no account", utxo_path));
+    }
+    let purpose = PURPOSES.iter().find(|(_, t)| *t == change_type).map(|(p, _)| *p).unwrap_or(84);
+    Ok(vec![purpose | HARDENED, address_n[1], address_n[2]])
+}
+
+/// First change index (/1/index) past those in use: above every change UTXO
+/// of the account and at least `stored`, the index after the last one signed
+pub fn next_change_index(utxos: &[SpendableUtxo], account_path: &[u32], stored: Option<u32>) -> u32 {
+    utxos
+        .iter()
+        .filter_map(|utxo| parse_derivation_path(&utxo.path).ok())
+        .filter(|address_n| address_n.len() == 5 && address_n[..3] == *account_path && address_n[3] == 1)
+        .map(|address_n| address_n[4] + 1)
+        .chain(stored)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Address at /1/`index` below an account's extended public key
+pub fn change_address(account_xpub: &str, index: u32, script_type: UtxoScriptType, network: Network) -> Result<String, String> {
+    account_address(account_xpub, 1, index, script_type, network)
+}
+
+/// Address at /`chain`/`index` below an account's extended public key (0 for
+/// receive addresses, 1 for change). ypub/zpub style prefixes are accepted;
+/// the address type comes from `script_type`.
+pub fn account_address(
+    account_xpub: &str,
+    chain: u32,
+    index: u32,
+    script_type: UtxoScriptType,
+    network: Network,
+) -> Result<String, String> {
+    let mut data = bitcoin::base58::decode_check(account_xpub.trim())
+        .map_err(|e| format!("Invalid extended public key: {}", e))?;
+    if data.len() != 78 {
+        return Err(format!("Invalid extended public key: {} bytes", data.len()));
+    }
+    let version = if network == Network::Bitcoin { XPUB_VERSION } else { TPUB_VERSION };
+    data[..4].copy_from_slice(&version);
+    let account = ExtendedPubKey::decode(&data).map_err(|e| format!("Invalid extended public key: {}", e))?;
+
+    let secp = Secp256k1::verification_only();
+    let normal = |n: u32| ChildNumber::from_normal_idx(n).map_err(|e| format!("Invalid address index {}: {}", n, e));
+    let key = account
+        .derive_pub(&secp, &[normal(chain)?, normal(index)?])
+        .map_err(|e| format!("Cannot derive address key: {}", e))?;
+
+    let public_key = key.to_pub();
+    let address = match script_type {
+        UtxoScriptType::P2pkh => Address::p2pkh(&public_key, network),
+        UtxoScriptType::P2shP2wpkh => Address::p2shwpkh(&public_key, network).map_err(|e| e.to_string())?,
+        UtxoScriptType::P2wpkh => Address::p2wpkh(&public_key, network).map_err(|e| e.to_string())?,
+        UtxoScriptType::P2tr => Address::p2tr(&secp, key.public_key.into(), None, network),
+    };
+    Ok(address.to_string())
+}
+
+/// A UTXO worth spending at the fee rate
+#[derive(Debug, Clone)]
+struct Candidate<'a> {
+    utxo: &'a SpendableUtxo,
+    script_type: UtxoScriptType,
+    effective: u64,
+}
+
+/// UTXOs with a positive effective value, largest first. Taproot UTXOs are
+/// left out since the device cannot sign for them.
+fn candidates(utxos: &[SpendableUtxo], fee_rate_sat_vb: u64) -> Result<Vec<Candidate<'_>>, String> {
+    let mut candidates = Vec::new();
+    for utxo in utxos {
+        let script_type = input_type_for_path(&utxo.path)?;
+        if script_type == UtxoScriptType::P2tr {
+            continue;
+        }
+        let input_fee = fee_for_vsize(script_type.input_vbytes(), fee_rate_sat_vb)?;
+        if let Some(effective) = utxo.amount_sats.checked_sub(input_fee).filter(|v| *v > 0) {
+            candidates.push(Candidate { utxo, script_type, effective });
+        }
+    }
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.effective));
+    Ok(candidates)
+}
+
+/// Depth-first search for the subset of `pool` (sorted largest first) whose
+/// sum lands in `target..=target + cost_of_change` with the least excess.
+/// Returns the indexes of the subset.
+pub fn branch_and_bound(pool: &[u64], target: u64, cost_of_change: u64) -> Option<Vec<usize>> {
+    let mut available: u64 = pool.iter().sum();
+    let mut value: u64 = 0;
+    let mut selected: Vec<usize> = Vec::new();
+    let mut best: Option<(u64, Vec<usize>)> = None;
+    let mut index = 0;
+
+    for _ in 0..BNB_MAX_TRIES {
+        let backtrack = if value + available < target || value > target.saturating_add(cost_of_change) {
+            true
+        } else if value >= target {
+            let excess = value - target;
+            if best.as_ref().map_or(true, |(best_excess, _)| excess < *best_excess) {
+                best = Some((excess, selected.clone()));
+            }
+            if excess == 0 {
+                break;
+            }
+            true
+        } else {
+            false
+        };
+
+        if backtrack {
+            // Undo the exclusions after the last included UTXO, then exclude it
+            let Some(last) = selected.pop() else { break };
+            available += pool[last + 1..index].iter().sum::<u64>();
+            value -= pool[last];
+            index = last + 1;
+        } else {
+            let amount = pool[index];
+            available -= amount;
+            // Including a UTXO equal to the one just excluded repeats a branch already searched
+            let repeats_branch = index > 0 && selected.last() != Some(&(index - 1)) && pool[index - 1] == amount;
+            if !repeats_branch {
+                selected.push(index);
+                value += amount;
+            }
+            index += 1;
+        }
+    }
+    best.map(|(_, selected)| selected)
+}
+
+fn spendable_total(candidates: &[Candidate]) -> u64 {
+    candidates.iter().map(|c| c.utxo.amount_sats).sum()
+}
+
+/// Compose a transaction paying `recipients` at `fee_rate_sat_vb`.
+///
+/// A single recipient with a "max" amount receives every UTXO worth spending,
+/// less the fee. Otherwise inputs are chosen by branch-and-bound, falling back
+/// to largest first with a `change_type` change output.
+pub fn compose(
+    utxos: &[SpendableUtxo],
+    recipients: &[ComposeRecipient],
+    change_type: UtxoScriptType,
+    fee_rate_sat_vb: u64,
+    allow_duplicate_outputs: bool,
+) -> Result<Composition, String> {
+    check_fee_rate(fee_rate_sat_vb)?;
+    // Any sum of these amounts fits in a u64 once their total does
+    total_sats(utxos.iter().map(|u| u.amount_sats))?;
+    let candidates = candidates(utxos, fee_rate_sat_vb)?;
+
+    let fixed: Option<Vec<Recipient>> = recipients
+        .iter()
+        .map(|r| match r.amount_sats {
+            SendAmount::Sats(amount_sats) => Some(Recipient { address: r.address.clone(), amount_sats }),
+            SendAmount::Max(_) => None,
+        })
+        .collect();
+    match (fixed, recipients) {
+        (Some(recipients), _) => compose_fixed(&candidates, &recipients, change_type, fee_rate_sat_vb, allow_duplicate_outputs),
+        (None, [recipient]) => compose_max(&candidates, &recipient.address, fee_rate_sat_vb, utxos.len()),
+        (None, _) => Err("A \"max\" amount is only allowed with a single recipient".to_string()),
+    }
+}
+
+fn compose_fixed(
+    candidates: &[Candidate],
+    recipients: &[Recipient],
+    change_type: UtxoScriptType,
+    fee_rate_sat_vb: u64,
+    allow_duplicate_outputs: bool,
+) -> Result<Composition, String> {
+    let sent = validate_recipients(recipients, allow_duplicate_outputs)?;
+    let outputs: Vec<UtxoScriptType> = recipients.iter().map(|r| output_type_for_address(&r.address)).collect();
+
+    // Everything but the inputs; the overhead is the segwit one if any candidate could make it so
+    let overhead = if candidates.iter().any(|c| c.script_type.is_segwit()) { 11 } else { 10 };
+    let base_fee = fee_for_vsize(overhead + outputs.iter().map(|o| o.output_vbytes()).sum::<u64>(), fee_rate_sat_vb)?;
+    let target = sent.checked_add(base_fee).ok_or_else(|| "Outputs plus fee overflow".to_string())?;
+    let cost_of_change = fee_for_vsize(change_type.output_vbytes() + change_type.input_vbytes(), fee_rate_sat_vb)?;
+
+    let pool: Vec<u64> = candidates.iter().map(|c| c.effective).collect();
+    let build = |selected: &[&Candidate], method, with_change: bool| {
+        let input_types: Vec<UtxoScriptType> = selected.iter().map(|c| c.script_type).collect();
+        let total: u64 = selected.iter().map(|c| c.utxo.amount_sats).sum();
+        let inputs: Vec<SpendableUtxo> = selected.iter().map(|c| c.utxo.clone()).collect();
+        let recipients = recipients.to_vec();
+
+        let mut outputs = outputs.clone();
+        outputs.push(change_type);
+        let vsize_with_change = estimate_mixed_vsize(&input_types, &outputs);
+        outputs.pop();
+        let fee_with_change = fee_for_vsize(vsize_with_change, fee_rate_sat_vb)?;
+
+        let plan = match sent.checked_add(fee_with_change).and_then(|needed| total.checked_sub(needed)) {
+            Some(change) if with_change && change >= DUST_LIMIT_SATS => BatchPlan {
+                inputs,
+                recipients,
+                change_sats: Some(change),
+                fee_sats: fee_with_change,
+                vsize: vsize_with_change,
+            },
+            // Leftover below dust, or within the cost of change, goes to the fee
+            _ => BatchPlan {
+                inputs,
+                recipients,
+                change_sats: None,
+                fee_sats: total - sent,
+                vsize: estimate_mixed_vsize(&input_types, &outputs),
+            },
+        };
+        Ok(Composition { plan, method })
+    };
+
+    if let Some(indexes) = branch_and_bound(&pool, target, cost_of_change) {
+        let selected: Vec<&Candidate> = indexes.iter().map(|i| &candidates[*i]).collect();
+        return build(&selected, SelectionMethod::BranchAndBound, false);
+    }
+
+    let mut effective_total = 0;
+    for (count, candidate) in candidates.iter().enumerate() {
+        effective_total += candidate.effective;
+        if effective_total >= target {
+            let selected: Vec<&Candidate> = candidates[..=count].iter().collect();
+            return build(&selected, SelectionMethod::LargestFirst, true);
+        }
+    }
+
+    Err(format!(
+        "Insufficient funds: outputs total {} sats plus fee, spendable {} sats",
+        sent,
+        spendable_total(candidates)
+    ))
+}
+
+fn compose_max(
+    candidates: &[Candidate],
+    address: &str,
+    fee_rate_sat_vb: u64,
+    utxo_count: usize,
+) -> Result<Composition, String> {
+    if address.trim().is_empty() {
+        return Err("Output 1 has no address".to_string());
+    }
+    if candidates.is_empty() {
+        return Err(match utxo_count {
+            0 => "No UTXOs to spend".to_string(),
+            _ => format!("No UTXO is worth spending at {} sat/vB", fee_rate_sat_vb),
+        });
+    }
+
+    let input_types: Vec<UtxoScriptType> = candidates.iter().map(|c| c.script_type).collect();
+    let vsize = estimate_mixed_vsize(&input_types, &[output_type_for_address(address)]);
+    let fee_sats = fee_for_vsize(vsize, fee_rate_sat_vb)?;
+    let total = spendable_total(candidates);
+    let amount_sats = total
+        .checked_sub(fee_sats)
+        .filter(|amount| *amount >= DUST_LIMIT_SATS)
+        .ok_or_else(|| format!("Spendable UTXOs ({} sats) cannot cover a {} sat fee", total, fee_sats))?;
+
+    Ok(Composition {
+        plan: BatchPlan {
+            inputs: candidates.iter().map(|c| c.utxo.clone()).collect(),
+            recipients: vec![Recipient { address: address.to_string(), amount_sats }],
+            change_sats: None,
+            fee_sats,
+            vsize,
+        },
+        method: SelectionMethod::SendMax,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEGWIT_ADDR: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+    const SEGWIT_PATH: &str = "m/84'/0'/0'/0/0";
+    const LEGACY_PATH: &str = "m/44'/0'/0'/0/0";
+    const TAPROOT_PATH: &str = "m/86'/0'/0'/0/0";
+
+    fn utxo_at(n: u32, amount_sats: u64, path: &str) -> SpendableUtxo {
+        SpendableUtxo { txid: format!("{:064x}", n), vout: 0, amount_sats, path: path.to_string() }
+    }
+
+    fn utxo(n: u32, amount_sats: u64) -> SpendableUtxo {
+        utxo_at(n, amount_sats, SEGWIT_PATH)
+    }
+
+    fn pay(amount_sats: u64) -> Vec<ComposeRecipient> {
+        vec![ComposeRecipient { address: SEGWIT_ADDR.to_string(), amount_sats: SendAmount::Sats(amount_sats) }]
+    }
+
+    fn pay_max() -> Vec<ComposeRecipient> {
+        vec![ComposeRecipient { address: SEGWIT_ADDR.to_string(), amount_sats: SendAmount::Max(MaxAmount::Max) }]
+    }
+
+    fn compose_segwit(utxos: &[SpendableUtxo], recipients: &[ComposeRecipient], fee_rate: u64) -> Result<Composition, String> {
+        compose(utxos, recipients, UtxoScriptType::P2wpkh, fee_rate, false)
+    }
+
+    fn amounts(plan: &BatchPlan) -> Vec<u64> {
+        plan.inputs.iter().map(|u| u.amount_sats).collect()
+    }
+
+    fn assert_balanced(plan: &BatchPlan, fee_rate: u64) {
+        let inputs: u64 = plan.inputs.iter().map(|u| u.amount_sats).sum();
+        assert_eq!(inputs, plan.total_sent() + plan.fee_sats + plan.change_sats.unwrap_or(0));
+        assert!(plan.fee_sats >= plan.vsize * fee_rate, "fee {} below {} vB at {}", plan.fee_sats, plan.vsize, fee_rate);
+        assert!(plan.change_sats.map_or(true, |change| change >= DUST_LIMIT_SATS));
+    }
+
+    #[test]
+    fn test_bnb_finds_exact_and_near_matches() {
+        let found = branch_and_bound(&[7, 5, 3, 3, 2], 10, 0).unwrap();
+        assert_eq!(found.iter().map(|i| [7, 5, 3, 3, 2][*i]).sum::<u64>(), 10);
+
+        // The least excess within the window wins
+        assert_eq!(branch_and_bound(&[9, 6, 5], 10, 2), Some(vec![1, 2]));
+        assert_eq!(branch_and_bound(&[7, 5], 11, 2), Some(vec![0, 1]));
+        assert_eq!(branch_and_bound(&[7, 5], 11, 0), None);
+
+        // Runs of equal values still reach every distinct sum
+        let found = branch_and_bound(&[4, 4, 4, 4], 12, 0).unwrap();
+        assert_eq!(found.len(), 3);
+
+        assert_eq!(branch_and_bound(&[7, 5, 3], 100, 10), None);
+        assert_eq!(branch_and_bound(&[50, 30], 10, 5), None);
+        assert_eq!(branch_and_bound(&[], 1, 5), None);
+    }
+
+    #[test]
+    fn test_bnb_without_a_match_ends() {
+        // Two of these odd values fall short of the odd target and three overshoot it
+        let pool: Vec<u64> = (0..40).map(|i| 1_000_001 - 2 * i).collect();
+        assert_eq!(branch_and_bound(&pool, 2_000_001, 0), None);
+    }
+
+    #[test]
+    fn test_exact_match_spends_no_change() {
+        // At 1 sat/vB: target 10_000 + (11 + 31) = 10_042; effective values
+        // 49_932, 6_000 and 4_042 make it exactly with the two small ones
+        let utxos = [utxo(1, 50_000), utxo(2, 6_068), utxo(3, 4_110)];
+        let composed = compose_segwit(&utxos, &pay(10_000), 1).unwrap();
+        assert_eq!(composed.method, SelectionMethod::BranchAndBound);
+        assert_eq!(amounts(&composed.plan), vec![6_068, 4_110]);
+        assert_eq!(composed.plan.change_sats, None);
+        // 11 + 2 * 68 + 31
+        assert_eq!(composed.plan.vsize, 178);
+        assert_eq!(composed.plan.fee_sats, 178);
+        assert_balanced(&composed.plan, 1);
+    }
+
+    #[test]
+    fn test_excess_within_cost_of_change_goes_to_fee() {
+        // 58 sats over the target, less than the 99 a change output would cost
+        let utxos = [utxo(1, 50_000), utxo(2, 6_068), utxo(3, 4_168)];
+        let composed = compose_segwit(&utxos, &pay(10_000), 1).unwrap();
+        assert_eq!(composed.method, SelectionMethod::BranchAndBound);
+        assert_eq!(composed.plan.change_sats, None);
+        assert_eq!(composed.plan.fee_sats, 236);
+        assert_eq!(composed.plan.vsize, 178);
+        assert_balanced(&composed.plan, 1);
+    }
+
+    #[test]
+    fn test_falls_back_to_largest_first_with_change() {
+        let utxos = [utxo(1, 30_000), utxo(2, 50_000)];
+        let composed = compose_segwit(&utxos, &pay(10_000), 10).unwrap();
+        assert_eq!(composed.method, SelectionMethod::LargestFirst);
+        assert_eq!(amounts(&composed.plan), vec![50_000]);
+        // 11 + 68 + 31 recipient + 31 change at 10 sat/vB
+        assert_eq!(composed.plan.vsize, 141);
+        assert_eq!(composed.plan.fee_sats, 1_410);
+        assert_eq!(composed.plan.change_sats, Some(38_590));
+        assert_balanced(&composed.plan, 10);
+    }
+
+    #[test]
+    fn test_dust_change_is_folded_into_fee() {
+        // 490 sats over the target: too much for branch-and-bound, and the
+        // 459 left after paying for a change output is dust
+        let composed = compose_segwit(&[utxo(1, 10_600)], &pay(10_000), 1).unwrap();
+        assert_eq!(composed.method, SelectionMethod::LargestFirst);
+        assert_eq!(composed.plan.change_sats, None);
+        assert_eq!(composed.plan.fee_sats, 600);
+        assert_eq!(composed.plan.vsize, 110);
+        assert_balanced(&composed.plan, 1);
+
+        // A little more and the change is kept
+        let composed = compose_segwit(&[utxo(1, 10_710)], &pay(10_000), 1).unwrap();
+        assert_eq!(composed.plan.change_sats, Some(569));
+        assert_eq!(composed.plan.fee_sats, 141);
+    }
+
+    #[test]
+    fn test_mixed_input_types() {
+        // Legacy input 148 vB, segwit 68 vB; neither alone covers 22_000
+        let utxos = [utxo_at(1, 20_000, LEGACY_PATH), utxo(2, 5_000)];
+        let composed = compose_segwit(&utxos, &pay(22_000), 2).unwrap();
+        assert_eq!(composed.method, SelectionMethod::LargestFirst);
+        assert_eq!(amounts(&composed.plan), vec![20_000, 5_000]);
+        // 11 + 148 + 68 + 31 + 31
+        assert_eq!(composed.plan.vsize, 289);
+        assert_eq!(composed.plan.fee_sats, 578);
+        assert_eq!(composed.plan.change_sats, Some(2_422));
+        assert_balanced(&composed.plan, 2);
+    }
+
+    #[test]
+    fn test_uneconomic_utxos_are_not_spent() {
+        // 600 sats cost 680 to spend at 10 sat/vB
+        let utxos = [utxo(1, 600), utxo(2, 30_000)];
+        let composed = compose_segwit(&utxos, &pay(10_000), 10).unwrap();
+        assert_eq!(amounts(&composed.plan), vec![30_000]);
+
+        let err = compose_segwit(&[utxo(1, 600), utxo(2, 9_000)], &pay(10_000), 10).unwrap_err();
+        assert!(err.contains("Insufficient funds") && err.contains("spendable 9000 sats"), "{}", err);
+    }
+
+    #[test]
+    fn test_taproot_utxos_are_not_spent() {
+        let utxos = [utxo_at(1, 90_000, TAPROOT_PATH), utxo(2, 30_000)];
+        let composed = compose_segwit(&utxos, &pay(10_000), 1).unwrap();
+        assert_eq!(amounts(&composed.plan), vec![30_000]);
+        assert_eq!(amounts(&compose_segwit(&utxos, &pay_max(), 1).unwrap().plan), vec![30_000]);
+        assert!(compose_segwit(&utxos, &pay(50_000), 1).is_err());
+    }
+
+    #[test]
+    fn test_insufficient_funds() {
+        let err = compose_segwit(&[utxo(1, 5_000)], &pay(10_000), 1).unwrap_err();
+        assert!(err.starts_with("Insufficient funds"), "{}", err);
+        // Enough for the outputs but not the fee
+        assert!(compose_segwit(&[utxo(1, 10_050)], &pay(10_000), 1).is_err());
+        assert!(compose_segwit(&[], &pay(10_000), 1).is_err());
+    }
+
+    #[test]
+    fn test_send_max() {
+        let utxos = [utxo(1, 50_000), utxo(2, 30_000), utxo(3, 100)];
+        let composed = compose_segwit(&utxos, &pay_max(), 10).unwrap();
+        assert_eq!(composed.method, SelectionMethod::SendMax);
+        // The 100 sat UTXO is skipped; 11 + 2 * 68 + 31 = 178 vB
+        assert_eq!(amounts(&composed.plan), vec![50_000, 30_000]);
+        assert_eq!(composed.plan.vsize, 178);
+        assert_eq!(composed.plan.fee_sats, 1_780);
+        assert_eq!(composed.plan.recipients[0].amount_sats, 78_220);
+        assert_eq!(composed.plan.change_sats, None);
+        assert_balanced(&composed.plan, 10);
+    }
+
+    #[test]
+    fn test_send_max_errors() {
+        // 450 sats would be left, which is dust
+        let err = compose_segwit(&[utxo(1, 1_000)], &pay_max(), 5).unwrap_err();
+        assert!(err.contains("cannot cover"), "{}", err);
+        assert_eq!(compose_segwit(&[], &pay_max(), 5).unwrap_err(), "No UTXOs to spend");
+        assert!(compose_segwit(&[utxo(1, 100)], &pay_max(), 5).unwrap_err().contains("worth spending"));
+
+        let mut two = pay_max();
+        two.extend(pay(10_000));
+        assert!(compose_segwit(&[utxo(1, 50_000)], &two, 1).unwrap_err().contains("single recipient"));
+        let mut no_address = pay_max();
+        no_address[0].address.clear();
+        assert!(compose_segwit(&[utxo(1, 50_000)], &no_address, 1).is_err());
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        assert!(compose_segwit(&[utxo(1, 50_000)], &pay(10_000), 0).is_err());
+        assert!(compose_segwit(&[utxo(1, 50_000)], &pay(10_000), u64::MAX).unwrap_err().contains("maximum"));
+        assert!(compose_segwit(&[utxo(1, u64::MAX), utxo(2, 1)], &pay_max(), 1).unwrap_err().contains("overflows"));
+        assert!(compose_segwit(&[utxo(1, 50_000)], &pay(500), 1).unwrap_err().contains("dust"));
+        assert!(compose_segwit(&[utxo_at(1, 50_000, "m/0/1")], &pay(10_000), 1).is_err());
+
+        assert_eq!(input_type_for_path("m/49'/0'/0'/1/3"), Ok(UtxoScriptType::P2shP2wpkh));
+        assert_eq!(input_type_for_path("m/86'/0'/0'/0/0"), Ok(UtxoScriptType::P2tr));
+        assert_eq!(input_type_for_path(LEGACY_PATH), Ok(UtxoScriptType::P2pkh));
+    }
+
+    #[test]
+    fn test_compositions_always_balance() {
+        let utxos: Vec<SpendableUtxo> = [120_000, 75_000, 33_333, 20_000, 9_999, 4_500, 1_200, 700]
+            .iter()
+            .enumerate()
+            .map(|(i, amount)| utxo(i as u32, *amount))
+            .collect();
+        for fee_rate in [1, 3, 12, 55] {
+            for amount in [1_000, 4_321, 20_000, 52_000, 150_000, 250_000] {
+                if let Ok(composed) = compose_segwit(&utxos, &pay(amount), fee_rate) {
+                    assert_eq!(composed.plan.total_sent(), amount);
+                    assert_balanced(&composed.plan, fee_rate);
+                }
+            }
+            assert_balanced(&compose_segwit(&utxos, &pay_max(), fee_rate).unwrap().plan, fee_rate);
+        }
+    }
+
+    #[test]
+    fn test_change_addresses() {
+        // BIP84 test vector account of "abandon abandon ... about"
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        assert_eq!(
+            change_address(zpub, 0, UtxoScriptType::P2wpkh, Network::Bitcoin).unwrap(),
+            "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"
+        );
+        let legacy = change_address(zpub, 0, UtxoScriptType::P2pkh, Network::Bitcoin).unwrap();
+        assert!(legacy.starts_with('1'), "{}", legacy);
+        assert!(change_address(zpub, 0, UtxoScriptType::P2tr, Network::Bitcoin).unwrap().starts_with("bc1p"));
+        assert!(change_address(zpub, 0, UtxoScriptType::P2shP2wpkh, Network::Testnet).unwrap().starts_with('2'));
+        assert!(change_address(zpub, HARDENED, UtxoScriptType::P2wpkh, Network::Bitcoin).is_err());
+        assert!(change_address(&zpub[..zpub.len() - 1], 0, UtxoScriptType::P2wpkh, Network::Bitcoin).is_err());
+        assert_eq!(
+            account_address(zpub, 0, 0, UtxoScriptType::P2wpkh, Network::Bitcoin).unwrap(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+    }
+
+    #[test]
+    fn test_next_change_index() {
+        let account = change_account_path(SEGWIT_PATH, UtxoScriptType::P2wpkh).unwrap();
+        assert_eq!(account, vec![84 | HARDENED, HARDENED, HARDENED]);
+        assert_eq!(
+            change_account_path("m/84'/1'/3'/0/7", UtxoScriptType::P2pkh).unwrap(),
+            vec![44 | HARDENED, 1 | HARDENED, 3 | HARDENED]
+        );
+        assert!(change_account_path("m/84'/0'", UtxoScriptType::P2wpkh).is_err());
+
+        let utxos = [
+            utxo_at(1, 1_000, "m/84'/0'/0'/1/4"),
+            utxo_at(2, 1_000, "m/84'/0'/0'/0/30"),
+            // Another account and another script type have their own chains
+            utxo_at(3, 1_000, "m/84'/0'/1'/1/50"),
+            utxo_at(4, 1_000, "m/44'/0'/0'/1/60"),
+        ];
+        assert_eq!(next_change_index(&utxos, &account, None), 5);
+        assert_eq!(next_change_index(&utxos, &account, Some(9)), 9);
+        assert_eq!(next_change_index(&utxos[1..2], &account, None), 0);
+    }
+
+    #[test]
+    fn test_amount_json() {
+        let recipients: Vec<ComposeRecipient> = serde_json::from_str(
+            r#"[{"address": "a", "amount_sats": 1500}, {"address": "b", "amount_sats": "max"}]"#,
+        ).unwrap();
+        assert_eq!(recipients[0].amount_sats, SendAmount::Sats(1_500));
+        assert_eq!(recipients[1].amount_sats, SendAmount::Max(MaxAmount::Max));
+        assert_eq!(serde_json::to_string(&recipients[1].amount_sats).unwrap(), "\"max\"");
+        assert!(serde_json::from_str::<SendAmount>("\"all\"").is_err());
+    }
+}
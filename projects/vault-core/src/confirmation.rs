@@ -0,0 +1,295 @@
+// confirmation.rs - Typed-phrase confirmation for destructive operations
+//
+// A high-risk command only runs with a token from `ConfirmationStore::issue`
+// and the phrase the user typed. Tokens are single use, expire after
+// CONFIRMATION_TTL and are bound to one operation on one device, so a
+// confirmation for one action can never authorize another.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+/// How long a confirmation token stays valid
+pub const CONFIRMATION_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum HighRiskOperation {
+    WipeDevice,
+    RemoveDevicePin,
+    ForgetDevice,
+    DisableDatabaseEncryption,
+    /// Grant a paired REST API client permission to request signatures
+    ElevateApiScope,
+    /// Flash firmware older than the installed version
+    DowngradeFirmware,
+}
+
+impl HighRiskOperation {
+    pub fn key(self) -> &'static str {
+        match self {
+            HighRiskOperation::WipeDevice => "wipe_device",
+            HighRiskOperation::RemoveDevicePin => "remove_device_pin",
+            HighRiskOperation::ForgetDevice => "forget_device",
+            HighRiskOperation::DisableDatabaseEncryption => "disable_database_encryption",
+            HighRiskOperation::ElevateApiScope => "elevate_api_scope",
+            HighRiskOperation::DowngradeFirmware => "downgrade_firmware",
+        }
+    }
+
+    /// Whether the operation targets a single device
+    pub fn requires_device(self) -> bool {
+        !matches!(self, HighRiskOperation::DisableDatabaseEncryption | HighRiskOperation::ElevateApiScope)
+    }
+
+    /// Phrase to type when there is no device label to use
+    fn fallback_phrase(self) -> &'static str {
+        match self {
+            HighRiskOperation::WipeDevice => "WIPE DEVICE",
+            HighRiskOperation::RemoveDevicePin => "REMOVE PIN",
+            HighRiskOperation::ForgetDevice => "FORGET DEVICE",
+            HighRiskOperation::DisableDatabaseEncryption => "DISABLE ENCRYPTION",
+            HighRiskOperation::ElevateApiScope => "ALLOW SIGNING",
+            HighRiskOperation::DowngradeFirmware => "DOWNGRADE FIRMWARE",
+        }
+    }
+}
+
+/// What the confirmation is for, as sent by the frontend
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ConfirmationContext {
+    pub device_id: Option<String>,
+}
+
+/// The phrase the user must type: the device label for device operations, so
+/// the user sees which device is affected, otherwise a fixed phrase
+pub fn confirmation_phrase(operation: HighRiskOperation, device_label: Option<&str>) -> String {
+    match device_label.map(str::trim).filter(|l| !l.is_empty()) {
+        Some(label) if operation.requires_device() => label.to_string(),
+        _ => operation.fallback_phrase().to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ConfirmationChallenge {
+    pub token: String,
+    pub operation: HighRiskOperation,
+    pub device_id: Option<String>,
+    pub phrase: String,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfirmationError {
+    UnknownToken,
+    Expired,
+    WrongOperation,
+    WrongDevice,
+    PhraseMismatch,
+}
+
+impl fmt::Display for ConfirmationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ConfirmationError::UnknownToken => "Confirmation not found or already used",
+            ConfirmationError::Expired => "Confirmation expired, please confirm again",
+            ConfirmationError::WrongOperation => "Confirmation was issued for a different operation",
+            ConfirmationError::WrongDevice => "Confirmation was issued for a different device",
+            ConfirmationError::PhraseMismatch => "Typed confirmation does not match",
+        };
+        f.write_str(message)
+    }
+}
+
+struct PendingConfirmation {
+    operation: HighRiskOperation,
+    device_id: Option<String>,
+    phrase: String,
+    issued_at: Instant,
+}
+
+/// Outstanding confirmation tokens
+pub struct ConfirmationStore {
+    ttl: Duration,
+    pending: Mutex<HashMap<String, PendingConfirmation>>,
+}
+
+pub type Confirmations = Arc<ConfirmationStore>;
+
+pub fn new_confirmations() -> Confirmations {
+    Arc::new(ConfirmationStore::new(CONFIRMATION_TTL))
+}
+
+fn new_token() -> Result<String, String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to generate confirmation token: {}", e))?;
+    Ok(hex::encode(bytes))
+}
+
+impl ConfirmationStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, pending: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn issue(
+        &self,
+        operation: HighRiskOperation,
+        device_id: Option<&str>,
+        phrase: String,
+    ) -> Result<ConfirmationChallenge, String> {
+        self.issue_at(operation, device_id, phrase, Instant::now())
+    }
+
+    fn issue_at(
+        &self,
+        operation: HighRiskOperation,
+        device_id: Option<&str>,
+        phrase: String,
+        now: Instant,
+    ) -> Result<ConfirmationChallenge, String> {
+        if operation.requires_device() && device_id.is_none() {
+            return Err(format!("{} requires a device_id", operation.key()));
+        }
+
+        let token = new_token()?;
+        let mut pending = self.pending.lock().map_err(|_| "Confirmation store unavailable".to_string())?;
+        pending.retain(|_, p| now.duration_since(p.issued_at) < self.ttl);
+        pending.insert(token.clone(), PendingConfirmation {
+            operation,
+            device_id: device_id.map(str::to_string),
+            phrase: phrase.clone(),
+            issued_at: now,
+        });
+
+        Ok(ConfirmationChallenge {
+            token,
+            operation,
+            device_id: device_id.map(str::to_string),
+            phrase,
+            expires_in_secs: self.ttl.as_secs(),
+        })
+    }
+
+    /// Check a submitted confirmation. The token is consumed by any attempt,
+    /// so a mistyped phrase needs a fresh confirmation.
+    pub fn verify(
+        &self,
+        token: &str,
+        operation: HighRiskOperation,
+        device_id: Option<&str>,
+        typed_phrase: &str,
+    ) -> Result<(), ConfirmationError> {
+        self.verify_at(token, operation, device_id, typed_phrase, Instant::now())
+    }
+
+    fn verify_at(
+        &self,
+        token: &str,
+        operation: HighRiskOperation,
+        device_id: Option<&str>,
+        typed_phrase: &str,
+        now: Instant,
+    ) -> Result<(), ConfirmationError> {
+        let pending = self
+            .pending
+            .lock()
+            .map_err(|_| ConfirmationError::UnknownToken)?
+            .remove(token)
+            .ok_or(ConfirmationError::UnknownToken)?;
+
+        if now.duration_since(pending.issued_at) >= self.ttl {
+            return Err(ConfirmationError::Expired);
+        }
+        if pending.operation != operation {
+            return Err(ConfirmationError::WrongOperation);
+        }
+        if pending.device_id.as_deref() != device_id {
+            return Err(ConfirmationError::WrongDevice);
+        }
+        if typed_phrase.trim() != pending.phrase {
+            return Err(ConfirmationError::PhraseMismatch);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wipe_challenge(store: &ConfirmationStore, now: Instant) -> ConfirmationChallenge {
+        store.issue_at(HighRiskOperation::WipeDevice, Some("dev1"), "Main KeepKey".to_string(), now).unwrap()
+    }
+
+    #[test]
+    fn test_valid_confirmation_is_single_use() {
+        let store = ConfirmationStore::new(CONFIRMATION_TTL);
+        let now = Instant::now();
+        let challenge = wipe_challenge(&store, now);
+        assert_eq!(challenge.token.len(), 32);
+
+        assert_eq!(store.verify_at(&challenge.token, HighRiskOperation::WipeDevice, Some("dev1"), " Main KeepKey ", now), Ok(()));
+        assert_eq!(
+            store.verify_at(&challenge.token, HighRiskOperation::WipeDevice, Some("dev1"), "Main KeepKey", now),
+            Err(ConfirmationError::UnknownToken)
+        );
+    }
+
+    #[test]
+    fn test_expired_token_is_rejected() {
+        let store = ConfirmationStore::new(CONFIRMATION_TTL);
+        let issued = Instant::now();
+        let challenge = wipe_challenge(&store, issued);
+        let later = issued + CONFIRMATION_TTL + Duration::from_secs(1);
+        assert_eq!(
+            store.verify_at(&challenge.token, HighRiskOperation::WipeDevice, Some("dev1"), "Main KeepKey", later),
+            Err(ConfirmationError::Expired)
+        );
+    }
+
+    #[test]
+    fn test_mismatched_phrase_burns_token() {
+        let store = ConfirmationStore::new(CONFIRMATION_TTL);
+        let now = Instant::now();
+        let challenge = wipe_challenge(&store, now);
+        assert_eq!(
+            store.verify_at(&challenge.token, HighRiskOperation::WipeDevice, Some("dev1"), "main keepkey", now),
+            Err(ConfirmationError::PhraseMismatch)
+        );
+        assert_eq!(
+            store.verify_at(&challenge.token, HighRiskOperation::WipeDevice, Some("dev1"), "Main KeepKey", now),
+            Err(ConfirmationError::UnknownToken)
+        );
+    }
+
+    #[test]
+    fn test_token_bound_to_operation_and_device() {
+        let store = ConfirmationStore::new(CONFIRMATION_TTL);
+        let now = Instant::now();
+        let challenge = wipe_challenge(&store, now);
+        assert_eq!(
+            store.verify_at(&challenge.token, HighRiskOperation::ForgetDevice, Some("dev1"), "Main KeepKey", now),
+            Err(ConfirmationError::WrongOperation)
+        );
+
+        let challenge = wipe_challenge(&store, now);
+        assert_eq!(
+            store.verify_at(&challenge.token, HighRiskOperation::WipeDevice, Some("dev2"), "Main KeepKey", now),
+            Err(ConfirmationError::WrongDevice)
+        );
+
+        assert!(store.issue_at(HighRiskOperation::RemoveDevicePin, None, "x".to_string(), now).is_err());
+    }
+
+    #[test]
+    fn test_phrase_uses_label_for_device_operations() {
+        assert_eq!(confirmation_phrase(HighRiskOperation::WipeDevice, Some(" Main ")), "Main");
+        assert_eq!(confirmation_phrase(HighRiskOperation::ForgetDevice, Some("")), "FORGET DEVICE");
+        assert_eq!(confirmation_phrase(HighRiskOperation::DisableDatabaseEncryption, Some("Main")), "DISABLE ENCRYPTION");
+    }
+}
@@ -0,0 +1,727 @@
+// device_export.rs - Portable export of one device's non-secret data
+//
+// Users moving to other wallet software (and auditors) get a single
+// versioned JSON document per device: account xpubs with their paths, script
+// types and output descriptors, cached addresses with whether they were used,
+// labels, and the transaction history. Each section is listed in the
+// manifest with a SHA-256 checksum over its canonical JSON, so recipients can
+// check a file with `validate_device_export`. The vault never stores keys,
+// seeds, PINs or passphrases, but the finished document is still scanned for
+// anything shaped like them before it is handed out.
+
+use std::collections::{BTreeMap, HashSet};
+use bitcoin::hashes::{sha256, Hash};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use keepkey_db::{DeviceExportData, WalletXpub};
+use crate::paths::{parse_derivation_path, HARDENED};
+
+/// `format` field of every export document
+pub const EXPORT_FORMAT: &str = "keepkey-vault-device-export";
+
+/// Bumped whenever a field is renamed, removed or changes meaning
+pub const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Extended private key prefixes (BIP32 and SLIP-132)
+const PRIVATE_KEY_PREFIXES: &[&str] = &[
+    "xprv", "yprv", "zprv", "Yprv", "Zprv", "tprv", "uprv", "vprv", "Uprv", "Vprv", "Ltpv", "Mtpv", "dgpv",
+];
+
+/// Object keys that never belong in an export
+const SECRET_FIELD_NAMES: &[&str] = &["mnemonic", "seed", "passphrase", "pin", "private_key", "xprv", "wif"];
+
+/// Shortest BIP39 phrase; runs of this many word-like tokens are refused
+const MIN_PHRASE_WORDS: usize = 12;
+
+const BTC_MAINNET: &str = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+const BTC_TESTNET: &str = "bip122:000000000933ea01ad0ee984209779ba/slip44:1";
+
+/// Parts of the device's data a user can choose to export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum ExportSection {
+    /// Account xpubs with paths, script types and descriptors
+    Xpubs,
+    /// Cached addresses and whether they appear in the history
+    Addresses,
+    /// Device label, wallet nicknames, account labels and the device note
+    Labels,
+    Transactions,
+}
+
+impl ExportSection {
+    pub const ALL: [ExportSection; 4] = [Self::Xpubs, Self::Addresses, Self::Labels, Self::Transactions];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Xpubs => "xpubs",
+            Self::Addresses => "addresses",
+            Self::Labels => "labels",
+            Self::Transactions => "transactions",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|s| s.key() == name.trim().to_ascii_lowercase())
+            .ok_or_else(|| format!("Unknown export section '{}'", name))
+    }
+}
+
+/// One section as listed in the manifest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ManifestEntry {
+    pub section: String,
+    pub entries: u32,
+    /// Hex SHA-256 of the section's canonical JSON
+    pub sha256: String,
+}
+
+/// Result of checking an export file
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ExportValidation {
+    pub schema_version: u32,
+    /// Whether this build knows the schema version
+    pub schema_supported: bool,
+    pub device_id: Option<String>,
+    pub exported_at: Option<i64>,
+    pub sections: Vec<SectionCheck>,
+    /// Sections present in the document but not in the manifest
+    pub unlisted_sections: Vec<String>,
+    pub valid: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SectionCheck {
+    pub section: String,
+    pub expected_sha256: String,
+    /// None when the section is missing from the document
+    pub actual_sha256: Option<String>,
+    pub valid: bool,
+}
+
+/// Parse the `sections` argument of `export_device_data`; None means all
+pub fn parse_sections(names: Option<&[String]>) -> Result<Vec<ExportSection>, String> {
+    let Some(names) = names else {
+        return Ok(ExportSection::ALL.to_vec());
+    };
+    let mut sections = names.iter().map(|n| ExportSection::parse(n)).collect::<Result<Vec<_>, _>>()?;
+    sections.sort();
+    sections.dedup();
+    if sections.is_empty() {
+        return Err("Choose at least one section to export".to_string());
+    }
+    Ok(sections)
+}
+
+/// Serialize with object keys sorted and no whitespace, so checksums do not
+/// depend on how a file was pretty-printed or which JSON library wrote it
+pub fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            out.push('{');
+            let sorted: BTreeMap<&String, &Value> = map.iter().collect();
+            for (i, (key, item)) in sorted.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(item, out);
+            }
+            out.push('}');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+pub fn section_checksum(section: &Value) -> String {
+    hex::encode(sha256::Hash::hash(canonical_json(section).as_bytes()).to_byte_array())
+}
+
+/// BIP380 descriptor checksum
+pub fn descriptor_checksum(descriptor: &str) -> Option<String> {
+    const INPUT_CHARSET: &str =
+        "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+    const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+    fn polymod(c: u64, val: u64) -> u64 {
+        let c0 = c >> 35;
+        let mut c = ((c & 0x7_ffff_ffff) << 5) ^ val;
+        for (bit, generator) in [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd].into_iter().enumerate() {
+            if c0 & (1 << bit) != 0 {
+                c ^= generator;
+            }
+        }
+        c
+    }
+
+    let mut c = 1u64;
+    let mut class = 0u64;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let pos = INPUT_CHARSET.find(ch)? as u64;
+        c = polymod(c, pos & 31);
+        class = class * 3 + (pos >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+    Some((0..8).map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char).collect())
+}
+
+/// Script type implied by a UTXO account path's BIP43 purpose
+fn script_type(caip: &str, path: &[u32]) -> Option<&'static str> {
+    if !caip.starts_with("bip122:") {
+        return None;
+    }
+    match path.first().map(|p| p & !HARDENED) {
+        Some(44) => Some("p2pkh"),
+        Some(49) => Some("p2sh-p2wpkh"),
+        Some(84) => Some("p2wpkh"),
+        Some(86) => Some("p2tr"),
+        _ => None,
+    }
+}
+
+/// Re-encode a SLIP-132 key (ypub, zpub, upub, vpub) under the xpub/tpub
+/// prefix descriptors expect
+fn descriptor_key(pubkey: &str, testnet: bool) -> Option<String> {
+    let mut data = bitcoin::base58::decode_check(pubkey).ok()?;
+    if data.len() != 78 {
+        return None;
+    }
+    let version: [u8; 4] = if testnet { [0x04, 0x35, 0x87, 0xcf] } else { [0x04, 0x88, 0xb2, 0x1e] };
+    data[..4].copy_from_slice(&version);
+    Some(bitcoin::base58::encode_check(&data))
+}
+
+/// Receive and change descriptors for a Bitcoin account, e.g.
+/// `wpkh([aabbccdd/84'/0'/0']xpub.../0/*)#checksum`
+fn account_descriptors(xpub: &WalletXpub, path: &[u32]) -> Option<Value> {
+    let testnet = match xpub.caip.as_str() {
+        BTC_MAINNET => false,
+        BTC_TESTNET => true,
+        _ => return None,
+    };
+    let (open, close) = match script_type(&xpub.caip, path)? {
+        "p2pkh" => ("pkh(", ")"),
+        "p2sh-p2wpkh" => ("sh(wpkh(", "))"),
+        "p2wpkh" => ("wpkh(", ")"),
+        "p2tr" => ("tr(", ")"),
+        _ => return None,
+    };
+    let key = descriptor_key(&xpub.pubkey, testnet)?;
+    let origin_path = xpub.path.trim_start_matches("m/");
+    let origin = if xpub.wallet_fingerprint.len() == 8 {
+        format!("[{}/{}]", xpub.wallet_fingerprint, origin_path)
+    } else {
+        String::new()
+    };
+    let descriptor = |chain: u32| {
+        let body = format!("{}{}{}/{}/*{}", open, origin, key, chain, close);
+        descriptor_checksum(&body).map(|checksum| format!("{}#{}", body, checksum))
+    };
+    Some(json!({ "receive": descriptor(0)?, "change": descriptor(1)? }))
+}
+
+fn xpubs_section(data: &DeviceExportData) -> Value {
+    Value::Array(
+        data.xpubs
+            .iter()
+            .map(|xpub| {
+                let path = parse_derivation_path(&xpub.path).unwrap_or_default();
+                json!({
+                    "wallet_fingerprint": xpub.wallet_fingerprint,
+                    "path": xpub.path,
+                    "caip": xpub.caip,
+                    "label": xpub.label,
+                    "xpub": xpub.pubkey,
+                    "script_type": script_type(&xpub.caip, &path),
+                    "descriptors": account_descriptors(xpub, &path),
+                })
+            })
+            .collect(),
+    )
+}
+
+fn addresses_section(data: &DeviceExportData) -> Value {
+    let used: HashSet<&str> = data
+        .transactions
+        .iter()
+        .flat_map(|tx| [tx.from_address.as_deref(), tx.to_address.as_deref()])
+        .flatten()
+        .collect();
+    Value::Array(
+        data.addresses
+            .iter()
+            .map(|a| {
+                json!({
+                    "wallet_fingerprint": a.wallet_fingerprint,
+                    "path": a.derivation_path,
+                    "coin_name": a.coin_name,
+                    "script_type": a.script_type,
+                    "address": a.address,
+                    "used": used.contains(a.address.as_str()),
+                })
+            })
+            .collect(),
+    )
+}
+
+fn labels_section(data: &DeviceExportData) -> Value {
+    json!({
+        "device_label": data.label,
+        "wallets": data.wallets.iter().map(|w| json!({
+            "wallet_fingerprint": w.wallet_fingerprint,
+            "hidden": w.is_hidden,
+            "nickname": w.nickname,
+        })).collect::<Vec<_>>(),
+        "accounts": data.xpubs.iter().map(|x| json!({
+            "wallet_fingerprint": x.wallet_fingerprint,
+            "path": x.path,
+            "caip": x.caip,
+            "label": x.label,
+        })).collect::<Vec<_>>(),
+        "device_note": data.note.as_ref().map(|n| json!({
+            "markdown": n.note_markdown,
+            "updated_at": n.updated_at,
+        })),
+    })
+}
+
+/// History without internal ids, signing origins or free-form metadata
+fn transactions_section(data: &DeviceExportData) -> Value {
+    Value::Array(
+        data.transactions
+            .iter()
+            .map(|tx| {
+                json!({
+                    "wallet_fingerprint": tx.wallet_fingerprint,
+                    "txid": tx.txid,
+                    "caip": tx.caip,
+                    "type": tx.transaction_type,
+                    "amount": tx.amount,
+                    "amount_usd": tx.amount_usd,
+                    "fee": tx.fee,
+                    "fee_usd": tx.fee_usd,
+                    "from_address": tx.from_address,
+                    "to_address": tx.to_address,
+                    "timestamp": tx.timestamp,
+                    "block_height": tx.block_height,
+                    "status": tx.status,
+                })
+            })
+            .collect(),
+    )
+}
+
+fn entry_count(section: &Value) -> u32 {
+    match section {
+        Value::Array(items) => items.len() as u32,
+        Value::Object(_) => 1,
+        _ => 0,
+    }
+}
+
+/// Build the export document for the chosen sections. Fails if anything in
+/// it looks like a private key or recovery phrase.
+pub fn build_device_export(data: &DeviceExportData, sections: &[ExportSection], exported_at: i64) -> Result<Value, String> {
+    let mut section_values = Map::new();
+    let mut manifest = Vec::new();
+    for section in ExportSection::ALL.into_iter().filter(|s| sections.contains(s)) {
+        let value = match section {
+            ExportSection::Xpubs => xpubs_section(data),
+            ExportSection::Addresses => addresses_section(data),
+            ExportSection::Labels => labels_section(data),
+            ExportSection::Transactions => transactions_section(data),
+        };
+        manifest.push(ManifestEntry {
+            section: section.key().to_string(),
+            entries: entry_count(&value),
+            sha256: section_checksum(&value),
+        });
+        section_values.insert(section.key().to_string(), value);
+    }
+    let omitted: Vec<&str> = ExportSection::ALL
+        .into_iter()
+        .filter(|s| !sections.contains(s))
+        .map(ExportSection::key)
+        .collect();
+
+    let document = json!({
+        "format": EXPORT_FORMAT,
+        "schema_version": EXPORT_SCHEMA_VERSION,
+        "exported_at": exported_at,
+        "device": {
+            "device_id": data.device_id,
+            "label": data.label,
+            "model": data.model,
+            "firmware_version": data.firmware_version,
+        },
+        "manifest": {
+            "sections": manifest,
+            "omitted": omitted,
+        },
+        "sections": section_values,
+    });
+
+    if let Some(finding) = scan_for_secrets(&document).into_iter().next() {
+        return Err(format!("Refusing to export: {}", finding));
+    }
+    Ok(document)
+}
+
+/// Whether `token` is a BIP39 English word, ignoring case and surrounding punctuation
+fn is_phrase_word(token: &str) -> bool {
+    let word = token.trim_matches(|c: char| c.is_ascii_punctuation()).to_ascii_lowercase();
+    bip39::Language::English.find_word(&word).is_some()
+}
+
+fn secret_in_text(text: &str) -> Option<&'static str> {
+    let has_private_key = PRIVATE_KEY_PREFIXES.iter().any(|prefix| {
+        text.match_indices(prefix).any(|(i, _)| {
+            let rest = &text[i..];
+            rest.chars().take_while(|c| c.is_ascii_alphanumeric() && !"0OIl".contains(*c)).count() >= 100
+        })
+    });
+    if has_private_key {
+        return Some("extended private key");
+    }
+
+    let mut run = 0;
+    for token in text.split_whitespace() {
+        run = if is_phrase_word(token) { run + 1 } else { 0 };
+        if run >= MIN_PHRASE_WORDS {
+            return Some("recovery phrase");
+        }
+    }
+    None
+}
+
+fn scan_value(value: &Value, pointer: &str, findings: &mut Vec<String>) {
+    match value {
+        Value::String(text) => {
+            if let Some(kind) = secret_in_text(text) {
+                findings.push(format!("{} looks like a {}", pointer, kind));
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                scan_value(item, &format!("{}/{}", pointer, i), findings);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                let pointer = format!("{}/{}", pointer, key);
+                if SECRET_FIELD_NAMES.contains(&key.to_ascii_lowercase().as_str()) {
+                    findings.push(format!("{} is a secret field", pointer));
+                }
+                scan_value(item, &pointer, findings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// JSON pointers of everything in `document` shaped like a private key,
+/// a recovery phrase or a secret field
+pub fn scan_for_secrets(document: &Value) -> Vec<String> {
+    let mut findings = Vec::new();
+    scan_value(document, "", &mut findings);
+    findings
+}
+
+/// Re-verify an export file's section checksums and report its schema version
+pub fn validate_device_export(text: &str) -> Result<ExportValidation, String> {
+    let document: Value = serde_json::from_str(text).map_err(|e| format!("Not a JSON document: {}", e))?;
+    if document.get("format").and_then(Value::as_str) != Some(EXPORT_FORMAT) {
+        return Err("Not a KeepKey Vault device export".to_string());
+    }
+    let schema_version = document
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .ok_or("Export has no schema_version")? as u32;
+    let manifest: Vec<ManifestEntry> = document
+        .pointer("/manifest/sections")
+        .cloned()
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| format!("Invalid manifest: {}", e))?
+        .ok_or("Export has no manifest")?;
+    let empty = Map::new();
+    let sections = document.get("sections").and_then(Value::as_object).unwrap_or(&empty);
+
+    let checks: Vec<SectionCheck> = manifest
+        .iter()
+        .map(|entry| {
+            let actual = sections.get(&entry.section).map(section_checksum);
+            SectionCheck {
+                section: entry.section.clone(),
+                expected_sha256: entry.sha256.clone(),
+                valid: actual.as_deref() == Some(entry.sha256.as_str()),
+                actual_sha256: actual,
+            }
+        })
+        .collect();
+    let listed: HashSet<&str> = manifest.iter().map(|e| e.section.as_str()).collect();
+    let unlisted_sections: Vec<String> = sections.keys().filter(|k| !listed.contains(k.as_str())).cloned().collect();
+    let schema_supported = schema_version <= EXPORT_SCHEMA_VERSION;
+
+    Ok(ExportValidation {
+        schema_version,
+        schema_supported,
+        device_id: document.pointer("/device/device_id").and_then(Value::as_str).map(str::to_string),
+        exported_at: document.get("exported_at").and_then(Value::as_i64),
+        valid: schema_supported && unlisted_sections.is_empty() && checks.iter().all(|c| c.valid),
+        sections: checks,
+        unlisted_sections,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepkey_db::{CachedAddress, DeviceNote, KnownWallet, TransactionCache};
+
+    const GOLDEN: &str = include_str!("../tests/fixtures/device_export/golden_v1.json");
+
+    /// BIP84 test vector account key (abandon ... about), as a zpub
+    const ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+
+    fn sample_data() -> DeviceExportData {
+        let tx = |txid: &str, kind: &str, to: &str, timestamp: i64| TransactionCache {
+            id: 7,
+            device_id: "343737340F4736331F003B00".to_string(),
+            txid: txid.to_string(),
+            caip: BTC_MAINNET.to_string(),
+            transaction_type: kind.to_string(),
+            amount: "0.015".to_string(),
+            amount_usd: Some("950.10".to_string()),
+            fee: Some("0.00001".to_string()),
+            fee_usd: None,
+            from_address: None,
+            to_address: Some(to.to_string()),
+            timestamp,
+            block_height: Some(840_000),
+            status: Some("confirmed".to_string()),
+            metadata_json: Some(r#"{"memo":"internal"}"#.to_string()),
+            wallet_fingerprint: "73c5da0a".to_string(),
+            origin: Some("rest_api:client-1".to_string()),
+        };
+        DeviceExportData {
+            device_id: "343737340F4736331F003B00".to_string(),
+            label: Some("Office".to_string()),
+            model: Some("KeepKey".to_string()),
+            firmware_version: Some("7.10.0".to_string()),
+            wallets: vec![KnownWallet {
+                device_id: "343737340F4736331F003B00".to_string(),
+                wallet_fingerprint: "73c5da0a".to_string(),
+                is_hidden: false,
+                nickname: Some("Savings".to_string()),
+                first_seen: 1_700_000_000,
+                last_used: 1_700_000_500,
+            }],
+            xpubs: vec![
+                WalletXpub {
+                    id: 1,
+                    device_id: "343737340F4736331F003B00".to_string(),
+                    wallet_fingerprint: "73c5da0a".to_string(),
+                    path: "m/84'/0'/0'".to_string(),
+                    label: "Bitcoin Native Segwit".to_string(),
+                    caip: BTC_MAINNET.to_string(),
+                    pubkey: ZPUB.to_string(),
+                    created_at: 1_700_000_000,
+                },
+                WalletXpub {
+                    id: 2,
+                    device_id: "343737340F4736331F003B00".to_string(),
+                    wallet_fingerprint: "73c5da0a".to_string(),
+                    path: "m/44'/60'/0'".to_string(),
+                    label: "Ethereum".to_string(),
+                    caip: "eip155:1/slip44:60".to_string(),
+                    pubkey: "xpub-ethereum-account".to_string(),
+                    created_at: 1_700_000_000,
+                },
+            ],
+            addresses: vec![
+                CachedAddress {
+                    wallet_fingerprint: "73c5da0a".to_string(),
+                    derivation_path: "m/84'/0'/0'/0/0".to_string(),
+                    coin_name: "Bitcoin".to_string(),
+                    script_type: Some("p2wpkh".to_string()),
+                    address: "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu".to_string(),
+                },
+                CachedAddress {
+                    wallet_fingerprint: "73c5da0a".to_string(),
+                    derivation_path: "m/84'/0'/0'/0/1".to_string(),
+                    coin_name: "Bitcoin".to_string(),
+                    script_type: Some("p2wpkh".to_string()),
+                    address: "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g".to_string(),
+                },
+            ],
+            transactions: vec![tx(
+                "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+                "receive",
+                "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu",
+                1_700_000_100,
+            )],
+            note: Some(DeviceNote {
+                device_id: "343737340F4736331F003B00".to_string(),
+                note_markdown: "Office unit, bought 2021".to_string(),
+                updated_at: 1_700_000_200,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_descriptor_checksum_vectors() {
+        // BIP380 test vectors
+        assert_eq!(descriptor_checksum("raw(deadbeef)").as_deref(), Some("89f8spxm"));
+        assert_eq!(
+            descriptor_checksum("addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)").as_deref(),
+            Some("02wpgw69")
+        );
+        assert_eq!(descriptor_checksum("raw(déadbeef)"), None);
+    }
+
+    #[test]
+    fn test_export_matches_golden_file() {
+        let document = build_device_export(&sample_data(), &ExportSection::ALL, 1_700_001_000).unwrap();
+        let golden: Value = serde_json::from_str(GOLDEN).unwrap();
+        assert_eq!(
+            document, golden,
+            "Export schema changed; bump EXPORT_SCHEMA_VERSION if this is intended and regenerate the golden file:\n{}",
+            serde_json::to_string_pretty(&document).unwrap()
+        );
+        assert!(validate_device_export(GOLDEN).unwrap().valid);
+    }
+
+    #[test]
+    fn test_bitcoin_accounts_get_descriptors() {
+        let document = build_device_export(&sample_data(), &[ExportSection::Xpubs], 0).unwrap();
+        let btc = &document["sections"]["xpubs"][0];
+        assert_eq!(btc["script_type"], "p2wpkh");
+        let receive = btc["descriptors"]["receive"].as_str().unwrap();
+        assert!(receive.starts_with("wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)#"));
+        assert!(btc["descriptors"]["change"].as_str().unwrap().contains("/1/*)#"));
+        // Accounts outside Bitcoin have no descriptor form
+        assert!(document["sections"]["xpubs"][1]["descriptors"].is_null());
+        assert!(document["sections"]["xpubs"][1]["script_type"].is_null());
+    }
+
+    #[test]
+    fn test_selected_sections_only() {
+        let document = build_device_export(&sample_data(), &[ExportSection::Labels], 0).unwrap();
+        let sections = document["sections"].as_object().unwrap();
+        assert_eq!(sections.keys().collect::<Vec<_>>(), vec!["labels"]);
+        assert_eq!(document["manifest"]["omitted"], json!(["xpubs", "addresses", "transactions"]));
+
+        assert_eq!(parse_sections(Some(&["Labels".to_string(), "labels".to_string()])).unwrap(), vec![ExportSection::Labels]);
+        assert!(parse_sections(Some(&["seeds".to_string()])).is_err());
+        assert!(parse_sections(Some(&[])).is_err());
+    }
+
+    #[test]
+    fn test_used_addresses_come_from_history() {
+        let document = build_device_export(&sample_data(), &[ExportSection::Addresses], 0).unwrap();
+        let used: Vec<bool> = document["sections"]["addresses"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|a| a["used"].as_bool().unwrap())
+            .collect();
+        assert_eq!(used, vec![true, false]);
+    }
+
+    #[test]
+    fn test_validation_detects_tampering() {
+        let document = build_device_export(&sample_data(), &ExportSection::ALL, 0).unwrap();
+        // Reformatting does not change the checksums
+        let pretty = serde_json::to_string_pretty(&document).unwrap();
+        assert!(validate_device_export(&pretty).unwrap().valid);
+
+        let mut tampered = document.clone();
+        tampered["sections"]["transactions"][0]["amount"] = json!("1.5");
+        let report = validate_device_export(&tampered.to_string()).unwrap();
+        assert!(!report.valid);
+        let failed: Vec<&str> = report.sections.iter().filter(|c| !c.valid).map(|c| c.section.as_str()).collect();
+        assert_eq!(failed, vec!["transactions"]);
+
+        let mut extra = document.clone();
+        extra["sections"]["notes"] = json!([]);
+        assert_eq!(validate_device_export(&extra.to_string()).unwrap().unlisted_sections, vec!["notes"]);
+
+        let mut future = document;
+        future["schema_version"] = json!(EXPORT_SCHEMA_VERSION + 1);
+        let report = validate_device_export(&future.to_string()).unwrap();
+        assert!(!report.schema_supported && !report.valid);
+
+        assert!(validate_device_export("{\"format\":\"something-else\"}").is_err());
+    }
+
+    #[test]
+    fn test_export_contains_no_secret_material() {
+        let document = build_device_export(&sample_data(), &ExportSection::ALL, 0).unwrap();
+        assert!(scan_for_secrets(&document).is_empty());
+        let text = document.to_string();
+        for forbidden in ["xprv", "tprv", "zprv", "mnemonic", "passphrase", "rest_api:", "internal"] {
+            assert!(!text.contains(forbidden), "export contains {}", forbidden);
+        }
+    }
+
+    #[test]
+    fn test_secret_scan_refuses_keys_and_phrases() {
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        let mut data = sample_data();
+        data.note.as_mut().unwrap().note_markdown = format!("backup: {}", phrase);
+        let err = build_device_export(&data, &[ExportSection::Labels], 0).unwrap_err();
+        assert!(err.contains("/sections/labels/device_note/markdown") && err.contains("recovery phrase"), "{}", err);
+        // Left out of the export, the note is not scanned
+        assert!(build_device_export(&data, &[ExportSection::Xpubs], 0).is_ok());
+
+        let mut data = sample_data();
+        data.xpubs[1].pubkey = xprv.to_string();
+        assert!(build_device_export(&data, &[ExportSection::Xpubs], 0).unwrap_err().contains("extended private key"));
+
+        assert_eq!(scan_for_secrets(&json!({ "wallet": { "Mnemonic": null } })), vec!["/wallet/Mnemonic is a secret field"]);
+        // Ordinary prose and short word runs pass
+        assert!(scan_for_secrets(&json!({ "note": "office unit, bought in 2021; the left button is a bit flaky" })).is_empty());
+        assert!(scan_for_secrets(&json!({ "note": "keep this spare unit with the other hardware until the office moves next month please" })).is_empty());
+        let numbered = format!("Words: {}.", phrase.replace("abandon", "Abandon,"));
+        assert_eq!(scan_for_secrets(&json!({ "note": numbered })), vec!["/note looks like a recovery phrase"]);
+    }
+}
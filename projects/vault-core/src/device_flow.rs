@@ -0,0 +1,284 @@
+// device_flow.rs - Tracking for devices in the middle of a multi-step flow
+//
+// PIN changes, seed recovery and seed verification drive the device through
+// several round trips, and the device may drop off the bus and come back
+// (sometimes under a new id) while one is in progress. The USB monitor needs
+// to know about those devices so it keeps the flow alive instead of tearing
+// the session down.
+//
+// All of that state lives in one DeviceFlowState held as Tauri app state. A
+// panic while the lock is held must not switch tracking off for the rest of
+// the session, so every accessor recovers the guard from a poisoned lock and
+// none of them can fail. Each update leaves the tables consistent before it
+// returns, so the recovered data is always safe to read.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+/// How long a device in a flow may stay off the bus before its flow is dropped
+pub const RECONNECT_GRACE: Duration = Duration::from_secs(120);
+
+/// Multi-step device operation that survives a USB re-enumeration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceFlow {
+    PinChange,
+    Recovery,
+    SeedVerification,
+    /// Freshly flashed firmware rebooting out of the bootloader
+    FirmwareUpdate,
+}
+
+#[derive(Debug, Default)]
+struct FlowTables {
+    /// Canonical device id -> flow in progress
+    flows: HashMap<String, DeviceFlow>,
+    /// Id the device re-enumerated under -> canonical id
+    aliases: HashMap<String, String>,
+    /// Canonical device id -> when it dropped off the bus mid-flow
+    disconnected: HashMap<String, Instant>,
+}
+
+impl FlowTables {
+    fn canonical<'a>(&'a self, device_id: &'a str) -> &'a str {
+        self.aliases.get(device_id).map(String::as_str).unwrap_or(device_id)
+    }
+
+    fn remove_flow(&mut self, canonical: &str) -> Option<DeviceFlow> {
+        self.aliases.retain(|_, target| target != canonical);
+        self.disconnected.remove(canonical);
+        self.flows.remove(canonical)
+    }
+}
+
+/// Devices with a flow in progress, their aliases and disconnect marks
+#[derive(Debug, Default)]
+pub struct DeviceFlowState {
+    tables: Mutex<FlowTables>,
+}
+
+impl DeviceFlowState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lock the tables, taking them back from a poisoned lock
+    fn tables(&self) -> MutexGuard<'_, FlowTables> {
+        self.tables.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Start tracking a flow; a flow already in progress on the device is replaced
+    pub fn begin_flow(&self, device_id: &str, flow: DeviceFlow) -> Option<DeviceFlow> {
+        let mut tables = self.tables();
+        let canonical = tables.canonical(device_id).to_string();
+        tables.disconnected.remove(&canonical);
+        tables.flows.insert(canonical, flow)
+    }
+
+    /// Stop tracking the device's flow along with its aliases and disconnect mark
+    pub fn end_flow(&self, device_id: &str) -> Option<DeviceFlow> {
+        let mut tables = self.tables();
+        let canonical = tables.canonical(device_id).to_string();
+        tables.remove_flow(&canonical)
+    }
+
+    /// Flow in progress on the device, following aliases
+    pub fn active_flow(&self, device_id: &str) -> Option<DeviceFlow> {
+        let tables = self.tables();
+        tables.flows.get(tables.canonical(device_id)).copied()
+    }
+
+    pub fn is_in_flow(&self, device_id: &str) -> bool {
+        self.active_flow(device_id).is_some()
+    }
+
+    /// Id the flow was started under, following aliases
+    pub fn resolve(&self, device_id: &str) -> String {
+        self.tables().canonical(device_id).to_string()
+    }
+
+    /// Record that a device in a flow came back as `alias`.
+    ///
+    /// Returns false, recording nothing, if `device_id` has no flow in progress.
+    pub fn add_alias(&self, alias: &str, device_id: &str) -> bool {
+        let mut tables = self.tables();
+        let canonical = tables.canonical(device_id).to_string();
+        if alias == canonical || !tables.flows.contains_key(&canonical) {
+            return false;
+        }
+        tables.aliases.insert(alias.to_string(), canonical);
+        true
+    }
+
+    /// Note that a device in a flow left the bus; returns its flow, or None
+    /// (recording nothing) if it had no flow in progress
+    pub fn mark_disconnected(&self, device_id: &str, now: Instant) -> Option<DeviceFlow> {
+        let mut tables = self.tables();
+        let canonical = tables.canonical(device_id).to_string();
+        let flow = tables.flows.get(&canonical).copied()?;
+        tables.disconnected.entry(canonical).or_insert(now);
+        Some(flow)
+    }
+
+    /// Note that the device is back; returns true if it was marked disconnected
+    pub fn mark_reconnected(&self, device_id: &str) -> bool {
+        let mut tables = self.tables();
+        let canonical = tables.canonical(device_id).to_string();
+        tables.disconnected.remove(&canonical).is_some()
+    }
+
+    pub fn is_temporarily_disconnected(&self, device_id: &str) -> bool {
+        let tables = self.tables();
+        tables.disconnected.contains_key(tables.canonical(device_id))
+    }
+
+    /// Devices in a flow that are currently off the bus
+    pub fn disconnected_devices(&self) -> Vec<(String, DeviceFlow)> {
+        let tables = self.tables();
+        tables.disconnected.keys()
+            .filter_map(|id| tables.flows.get(id).map(|flow| (id.clone(), *flow)))
+            .collect()
+    }
+
+    /// Drop the flows of devices that stayed off the bus longer than `grace`
+    pub fn expire_disconnected(&self, now: Instant, grace: Duration) -> Vec<(String, DeviceFlow)> {
+        let mut tables = self.tables();
+        let expired: Vec<String> = tables.disconnected.iter()
+            .filter(|(_, since)| now.saturating_duration_since(**since) > grace)
+            .map(|(id, _)| id.clone())
+            .collect();
+        expired.into_iter()
+            .filter_map(|id| tables.remove_flow(&id).map(|flow| (id, flow)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_begin_and_end_flow() {
+        let state = DeviceFlowState::new();
+        assert!(!state.is_in_flow("kk1"));
+
+        assert_eq!(state.begin_flow("kk1", DeviceFlow::Recovery), None);
+        assert_eq!(state.active_flow("kk1"), Some(DeviceFlow::Recovery));
+        assert!(!state.is_in_flow("kk2"));
+
+        // A new flow replaces the old one
+        assert_eq!(state.begin_flow("kk1", DeviceFlow::PinChange), Some(DeviceFlow::Recovery));
+        assert_eq!(state.end_flow("kk1"), Some(DeviceFlow::PinChange));
+        assert!(!state.is_in_flow("kk1"));
+        assert_eq!(state.end_flow("kk1"), None);
+    }
+
+    #[test]
+    fn test_alias_follows_flow() {
+        let state = DeviceFlowState::new();
+        assert!(!state.add_alias("kk1-boot", "kk1"));
+
+        state.begin_flow("kk1", DeviceFlow::Recovery);
+        assert!(state.add_alias("kk1-boot", "kk1"));
+        assert!(!state.add_alias("kk1", "kk1"));
+        assert_eq!(state.resolve("kk1-boot"), "kk1");
+        assert_eq!(state.active_flow("kk1-boot"), Some(DeviceFlow::Recovery));
+
+        // Ending through the alias clears the flow and the alias
+        assert_eq!(state.end_flow("kk1-boot"), Some(DeviceFlow::Recovery));
+        assert_eq!(state.resolve("kk1-boot"), "kk1-boot");
+        assert!(!state.is_in_flow("kk1"));
+    }
+
+    #[test]
+    fn test_disconnect_and_reconnect() {
+        let state = DeviceFlowState::new();
+        let now = Instant::now();
+        assert_eq!(state.mark_disconnected("kk1", now), None);
+        assert!(!state.is_temporarily_disconnected("kk1"));
+
+        state.begin_flow("kk1", DeviceFlow::SeedVerification);
+        assert_eq!(state.mark_disconnected("kk1", now), Some(DeviceFlow::SeedVerification));
+        assert!(state.is_temporarily_disconnected("kk1"));
+        assert_eq!(state.disconnected_devices(), vec![("kk1".to_string(), DeviceFlow::SeedVerification)]);
+
+        // Coming back under a new id reconnects the original device
+        state.add_alias("kk1-new", "kk1");
+        assert!(state.mark_reconnected("kk1-new"));
+        assert!(!state.is_temporarily_disconnected("kk1"));
+        assert!(!state.mark_reconnected("kk1"));
+        assert!(state.is_in_flow("kk1"));
+    }
+
+    #[test]
+    fn test_expire_disconnected() {
+        let state = DeviceFlowState::new();
+        let start = Instant::now();
+        state.begin_flow("kk1", DeviceFlow::Recovery);
+        state.begin_flow("kk2", DeviceFlow::PinChange);
+        state.add_alias("kk1-boot", "kk1");
+        state.mark_disconnected("kk1", start);
+        state.mark_disconnected("kk2", start + Duration::from_secs(60));
+
+        let later = start + Duration::from_secs(90);
+        assert_eq!(
+            state.expire_disconnected(later, Duration::from_secs(60)),
+            vec![("kk1".to_string(), DeviceFlow::Recovery)]
+        );
+        assert!(!state.is_in_flow("kk1"));
+        assert_eq!(state.resolve("kk1-boot"), "kk1-boot");
+        assert!(state.is_temporarily_disconnected("kk2"));
+    }
+
+    #[test]
+    fn test_begin_flow_clears_stale_disconnect() {
+        let state = DeviceFlowState::new();
+        state.begin_flow("kk1", DeviceFlow::Recovery);
+        state.mark_disconnected("kk1", Instant::now());
+        state.begin_flow("kk1", DeviceFlow::PinChange);
+        assert!(!state.is_temporarily_disconnected("kk1"));
+    }
+
+    #[test]
+    fn test_panic_while_locked_keeps_tracking() {
+        let state = Arc::new(DeviceFlowState::new());
+        state.begin_flow("kk1", DeviceFlow::Recovery);
+        state.add_alias("kk1-boot", "kk1");
+
+        let poisoner = state.clone();
+        let result = std::thread::spawn(move || {
+            let _guard = poisoner.tables();
+            panic!("unrelated failure while holding the flow lock");
+        }).join();
+        assert!(result.is_err());
+        assert!(state.tables.is_poisoned());
+
+        // Reads and writes carry on with the state from before the panic
+        assert_eq!(state.active_flow("kk1-boot"), Some(DeviceFlow::Recovery));
+        assert_eq!(state.mark_disconnected("kk1", Instant::now()), Some(DeviceFlow::Recovery));
+        assert!(state.mark_reconnected("kk1-boot"));
+        assert_eq!(state.end_flow("kk1"), Some(DeviceFlow::Recovery));
+        assert!(!state.is_in_flow("kk1"));
+    }
+
+    #[test]
+    fn test_panic_in_caller_closure_does_not_corrupt_state() {
+        let state = DeviceFlowState::new();
+        state.begin_flow("kk1", DeviceFlow::PinChange);
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            if state.is_in_flow("kk1") {
+                panic!("caller failed mid-flow");
+            }
+        }));
+        assert!(result.is_err());
+        assert_eq!(state.active_flow("kk1"), Some(DeviceFlow::PinChange));
+        assert_eq!(state.begin_flow("kk2", DeviceFlow::Recovery), None);
+        assert!(state.is_in_flow("kk2"));
+    }
+}
@@ -0,0 +1,127 @@
+// device_label.rs - Renaming a device with ApplySettings
+//
+// Labels are checked before the device is asked, since the device only
+// reports a bare Failure for a label it cannot show. The user confirms the
+// new label on the device; declining there is reported as Cancelled so the
+// UI can simply leave the old name in place.
+
+use serde::Serialize;
+use keepkey_rust::messages::{self, Message};
+use crate::queue::{get_or_create_device_queue, DeviceQueueManager};
+
+/// Longest label the device accepts
+pub const MAX_LABEL_LEN: usize = 32;
+
+/// Why a device could not be renamed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum LabelError {
+    Invalid { message: String },
+    /// Declined on the device
+    Cancelled,
+    DeviceUnavailable { message: String },
+    Rejected { message: String },
+}
+
+impl LabelError {
+    /// Encode for a `Result<_, String>` command error
+    pub fn to_json_string(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(fields) = value.as_object_mut() {
+            fields.insert("summary".to_string(), self.to_string().into());
+        }
+        value.to_string()
+    }
+}
+
+impl std::fmt::Display for LabelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LabelError::Invalid { message } => write!(f, "{}", message),
+            LabelError::Cancelled => write!(f, "Renaming was cancelled on the device"),
+            LabelError::DeviceUnavailable { message } => write!(f, "Device unavailable: {}", message),
+            LabelError::Rejected { message } => write!(f, "Device refused the label: {}", message),
+        }
+    }
+}
+
+/// The label to send: surrounding spaces trimmed, at most MAX_LABEL_LEN
+/// printable ASCII characters
+pub fn validate_label(label: &str) -> Result<String, LabelError> {
+    let label = label.trim();
+    let invalid = |message: String| Err(LabelError::Invalid { message });
+    if label.is_empty() {
+        return invalid("Label cannot be empty".to_string());
+    }
+    if let Some(c) = label.chars().find(|c| !(' '..='~').contains(c)) {
+        return invalid(format!("Label can only contain printable ASCII characters, not {:?}", c));
+    }
+    if label.len() > MAX_LABEL_LEN {
+        return invalid(format!("Label must be {} characters or less", MAX_LABEL_LEN));
+    }
+    Ok(label.to_string())
+}
+
+fn failure_error(failure: &messages::Failure) -> LabelError {
+    match failure.code() {
+        messages::FailureType::FailureActionCancelled | messages::FailureType::FailurePinCancelled => LabelError::Cancelled,
+        _ => LabelError::Rejected { message: failure.message().to_string() },
+    }
+}
+
+/// Validate `label` and set it on the device; returns the label as applied
+pub async fn apply_device_label(
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+    label: &str,
+) -> Result<String, LabelError> {
+    let label = validate_label(label)?;
+    let queue = get_or_create_device_queue(device_id, queue_manager)
+        .await
+        .map_err(|message| LabelError::DeviceUnavailable { message })?;
+    let request = Message::ApplySettings(messages::ApplySettings {
+        language: None,
+        label: Some(label.clone()),
+        use_passphrase: None,
+        auto_lock_delay_ms: None,
+        u2f_counter: None,
+    });
+    match queue.send_raw(request, true).await {
+        Ok(Message::Success(_)) => Ok(label),
+        Ok(Message::Failure(f)) => Err(failure_error(&f)),
+        Ok(other) => Err(LabelError::Rejected { message: format!("Unexpected response: {:?}", other.message_type()) }),
+        Err(e) => Err(LabelError::DeviceUnavailable { message: e.to_string() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_label() {
+        assert_eq!(validate_label("  Savings KeepKey ").unwrap(), "Savings KeepKey");
+        assert_eq!(validate_label(&"k".repeat(MAX_LABEL_LEN)).unwrap().len(), MAX_LABEL_LEN);
+
+        for label in ["", "   ", "tab\there", "new\nline", "bell\u{7}", "Café", "🔑", &"k".repeat(MAX_LABEL_LEN + 1)] {
+            assert!(matches!(validate_label(label), Err(LabelError::Invalid { .. })), "{:?}", label);
+        }
+    }
+
+    #[test]
+    fn test_cancel_on_device_is_distinguishable() {
+        let cancelled = messages::Failure {
+            code: Some(messages::FailureType::FailureActionCancelled as i32),
+            message: Some("Action cancelled by user".to_string()),
+        };
+        assert_eq!(failure_error(&cancelled), LabelError::Cancelled);
+        let json: serde_json::Value = serde_json::from_str(&LabelError::Cancelled.to_json_string()).unwrap();
+        assert_eq!(json["kind"], "Cancelled");
+
+        let other = messages::Failure {
+            code: Some(messages::FailureType::FailureSyntaxError as i32),
+            message: Some("Invalid label".to_string()),
+        };
+        assert_eq!(failure_error(&other), LabelError::Rejected { message: "Invalid label".to_string() });
+    }
+}
@@ -0,0 +1,426 @@
+// device_log.rs - Structured log of device requests and responses
+//
+// Every message exchanged through a queue from `get_or_create_device_queue`
+// is appended as one JSON line to logs/device-requests.log in the data
+// directory, as are the firmware updates reported through `log_request` and
+// `log_response`. A background thread does the writing and rotates the file
+// at 10MB, keeping five files. Payload fields that could reveal secrets (PIN
+// positions, passphrases, entropy, seed words) are redacted by message type
+// before a record is queued for writing.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::oneshot;
+use keepkey_rust::device_queue::{DeviceExchange, RequestObserver};
+use keepkey_rust::messages::Message;
+
+pub const LOG_FILE_NAME: &str = "device-requests.log";
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// The live file plus rotated ones
+const KEPT_FILES: usize = 5;
+/// Longer strings (firmware payloads, raw transactions) are cut to this
+const MAX_STRING_CHARS: usize = 512;
+const REDACTED: &str = "[redacted]";
+
+/// Payload fields that could reveal secrets, by message type; `*` drops the
+/// whole payload. Names are the camelCase of the message JSON.
+const SENSITIVE_FIELDS: &[(&str, &[&str])] = &[
+    ("PinMatrixAck", &["pin"]),
+    ("PassphraseAck", &["passphrase"]),
+    ("CharacterAck", &["character"]),
+    ("WordAck", &["word"]),
+    ("EntropyAck", &["entropy"]),
+    ("Entropy", &["entropy"]),
+    ("LoadDevice", &["mnemonic", "node", "pin"]),
+    ("CipherKeyValue", &["value"]),
+    ("CipheredKeyValue", &["value"]),
+    ("DebugLinkState", &["*"]),
+];
+
+/// One line of the device request log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct DeviceLogRecord {
+    /// Unix milliseconds
+    pub timestamp: i64,
+    pub device_id: String,
+    pub request_id: String,
+    /// Message sent, or the operation of a logged request
+    pub message_type: String,
+    /// Message the device answered with
+    pub response_type: Option<String>,
+    /// Absent on a request whose response is logged separately
+    pub success: Option<bool>,
+    pub duration_ms: Option<u64>,
+    pub error: Option<String>,
+    /// The payload, redacted
+    pub data: Option<Value>,
+}
+
+/// `data` with the sensitive fields of `message_type` replaced and long
+/// strings shortened
+pub fn redact(message_type: &str, mut data: Value) -> Value {
+    let sensitive = SENSITIVE_FIELDS
+        .iter()
+        .find(|(name, _)| *name == message_type)
+        .map_or(&[][..], |(_, fields)| *fields);
+    if sensitive.contains(&"*") {
+        return Value::String(REDACTED.to_string());
+    }
+    if let Some(object) = data.as_object_mut() {
+        for field in sensitive {
+            if let Some(value) = object.get_mut(*field) {
+                *value = Value::String(REDACTED.to_string());
+            }
+        }
+    }
+    shorten_strings(&mut data);
+    data
+}
+
+fn shorten_strings(value: &mut Value) {
+    match value {
+        Value::String(s) if s.chars().count() > MAX_STRING_CHARS => {
+            let kept: String = s.chars().take(MAX_STRING_CHARS).collect();
+            *s = format!("{}... ({} chars)", kept, s.chars().count());
+        }
+        Value::Array(items) => items.iter_mut().for_each(shorten_strings),
+        Value::Object(fields) => fields.values_mut().for_each(shorten_strings),
+        _ => {}
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64)
+}
+
+fn message_name(message: &Message) -> String {
+    format!("{:?}", message.message_type())
+}
+
+enum Command {
+    Write(Box<DeviceLogRecord>),
+    /// Answered once every earlier record is written
+    Barrier(oneshot::Sender<()>),
+    Clear(oneshot::Sender<io::Result<()>>),
+}
+
+/// The log files in `dir`, live file first
+fn log_files(dir: &Path, kept: usize) -> Vec<PathBuf> {
+    (0..kept)
+        .map(|i| match i {
+            0 => dir.join(LOG_FILE_NAME),
+            i => dir.join(format!("{}.{}", LOG_FILE_NAME, i)),
+        })
+        .collect()
+}
+
+struct Writer {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    kept_files: usize,
+    file: Option<File>,
+}
+
+impl Writer {
+    fn run(mut self, commands: mpsc::Receiver<Command>) {
+        for command in commands {
+            match command {
+                Command::Write(record) => {
+                    if let Err(e) = self.append(&record) {
+                        log::warn!("Could not write the device request log: {}", e);
+                        self.file = None;
+                    }
+                }
+                Command::Barrier(done) => {
+                    let _ = done.send(());
+                }
+                Command::Clear(done) => {
+                    self.file = None;
+                    let _ = done.send(self.remove_all());
+                }
+            }
+        }
+    }
+
+    fn append(&mut self, record: &DeviceLogRecord) -> io::Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => self.open()?,
+        };
+        let size = file.metadata()?.len();
+        let mut file = if size > 0 && size + line.len() as u64 > self.max_file_bytes {
+            drop(file);
+            self.rotate()?;
+            self.open()?
+        } else {
+            file
+        };
+        file.write_all(line.as_bytes())?;
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn open(&self) -> io::Result<File> {
+        fs::create_dir_all(&self.dir)?;
+        OpenOptions::new().create(true).append(true).open(self.dir.join(LOG_FILE_NAME))
+    }
+
+    /// Shift every file one place older, dropping the oldest
+    fn rotate(&self) -> io::Result<()> {
+        let files = log_files(&self.dir, self.kept_files);
+        for pair in files.windows(2).rev() {
+            if pair[0].exists() {
+                fs::rename(&pair[0], &pair[1])?;
+            }
+        }
+        if self.kept_files == 1 {
+            fs::remove_file(&files[0])?;
+        }
+        Ok(())
+    }
+
+    fn remove_all(&self) -> io::Result<()> {
+        for path in log_files(&self.dir, self.kept_files) {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Appends records to the log files on a background thread
+pub struct DeviceLog {
+    dir: PathBuf,
+    kept_files: usize,
+    commands: Mutex<mpsc::Sender<Command>>,
+    /// Start and operation of requests logged with `log_request`
+    pending: Mutex<HashMap<String, (Instant, String)>>,
+    next_request: AtomicU64,
+}
+
+impl DeviceLog {
+    /// Log to `dir`, rotating at 10MB and keeping five files
+    pub fn new(dir: PathBuf) -> Self {
+        Self::with_limits(dir, MAX_FILE_BYTES, KEPT_FILES)
+    }
+
+    pub fn with_limits(dir: PathBuf, max_file_bytes: u64, kept_files: usize) -> Self {
+        let kept_files = kept_files.max(1);
+        let (sender, receiver) = mpsc::channel();
+        let writer = Writer { dir: dir.clone(), max_file_bytes, kept_files, file: None };
+        std::thread::Builder::new()
+            .name("device-log".to_string())
+            .spawn(move || writer.run(receiver))
+            .expect("failed to start the device log writer");
+        Self {
+            dir,
+            kept_files,
+            commands: Mutex::new(sender),
+            pending: Mutex::new(HashMap::new()),
+            next_request: AtomicU64::new(1),
+        }
+    }
+
+    fn send(&self, command: Command) -> Result<(), String> {
+        self.commands
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .send(command)
+            .map_err(|_| "The device log writer stopped".to_string())
+    }
+
+    fn write(&self, record: DeviceLogRecord) {
+        // A record that cannot be logged must never fail the device operation
+        let _ = self.send(Command::Write(Box::new(record)));
+    }
+
+    /// Record a request; its response follows through `log_response` with
+    /// the same `request_id`
+    pub fn log_request(&self, device_id: &str, request_id: &str, operation: &str, data: &Value) {
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(request_id.to_string(), (Instant::now(), operation.to_string()));
+        self.write(DeviceLogRecord {
+            timestamp: now_ms(),
+            device_id: device_id.to_string(),
+            request_id: request_id.to_string(),
+            message_type: operation.to_string(),
+            response_type: None,
+            success: None,
+            duration_ms: None,
+            error: None,
+            data: Some(redact(operation, data.clone())),
+        });
+    }
+
+    /// Record the response to a request from `log_request`
+    pub fn log_response(&self, device_id: &str, request_id: &str, success: bool, data: &Value, error: Option<&str>) {
+        let started = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(request_id);
+        let operation = started.as_ref().map_or_else(|| "response".to_string(), |(_, operation)| operation.clone());
+        self.write(DeviceLogRecord {
+            timestamp: now_ms(),
+            device_id: device_id.to_string(),
+            request_id: request_id.to_string(),
+            response_type: None,
+            success: Some(success),
+            duration_ms: started.map(|(at, _)| at.elapsed().as_millis() as u64),
+            error: error.map(String::from),
+            data: Some(redact(&operation, data.clone())),
+            message_type: operation,
+        });
+    }
+
+    /// Record a message exchanged through a device queue
+    pub fn log_exchange(&self, exchange: &DeviceExchange<'_>) {
+        let message_type = message_name(exchange.request);
+        let (response_type, success, error) = match exchange.response {
+            Ok(Message::Failure(failure)) => {
+                (Some("Failure".to_string()), false, Some(failure.message().to_string()))
+            }
+            Ok(response) => (Some(message_name(response)), true, None),
+            Err(e) => (None, false, Some(e.to_string())),
+        };
+        let request_id = format!("dq-{}", self.next_request.fetch_add(1, Ordering::Relaxed));
+        self.write(DeviceLogRecord {
+            timestamp: now_ms(),
+            device_id: exchange.device_id.to_string(),
+            request_id,
+            response_type,
+            success: Some(success),
+            duration_ms: Some(exchange.duration.as_millis() as u64),
+            error,
+            data: exchange.request.to_json().map(|data| redact(&message_type, data)),
+            message_type,
+        });
+    }
+
+    /// Up to `limit` records, newest first
+    pub async fn recent(&self, limit: usize) -> Result<Vec<DeviceLogRecord>, String> {
+        let (done, written) = oneshot::channel();
+        self.send(Command::Barrier(done))?;
+        written.await.map_err(|_| "The device log writer stopped".to_string())?;
+
+        let files = log_files(&self.dir, self.kept_files);
+        tokio::task::spawn_blocking(move || {
+            let mut records = Vec::new();
+            for path in files {
+                if records.len() >= limit {
+                    break;
+                }
+                let file = match File::open(&path) {
+                    Ok(file) => file,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+                };
+                // A line cut short by a crash is skipped
+                let lines: Vec<DeviceLogRecord> = BufReader::new(file)
+                    .lines()
+                    .map_while(Result::ok)
+                    .filter_map(|line| serde_json::from_str(&line).ok())
+                    .collect();
+                records.extend(lines.into_iter().rev().take(limit - records.len()));
+            }
+            Ok(records)
+        })
+        .await
+        .map_err(|e| format!("Reading the device log failed: {}", e))?
+    }
+
+    /// Delete every log file, after the records already queued are written
+    pub async fn clear(&self) -> Result<(), String> {
+        let (done, cleared) = oneshot::channel();
+        self.send(Command::Clear(done))?;
+        cleared
+            .await
+            .map_err(|_| "The device log writer stopped".to_string())?
+            .map_err(|e| format!("Could not delete the device log: {}", e))
+    }
+}
+
+/// The log in the data directory's logs folder
+pub fn device_log() -> &'static DeviceLog {
+    static LOG: OnceLock<DeviceLog> = OnceLock::new();
+    LOG.get_or_init(|| DeviceLog::new(keepkey_db::data_dir::resolve_data_dir().path.join("logs")))
+}
+
+/// Observer that logs every exchange on a device queue to `device_log()`
+pub fn request_observer() -> RequestObserver {
+    Arc::new(|exchange| device_log().log_exchange(&exchange))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepkey_rust::messages;
+    use std::time::Duration;
+
+    fn exchange<'a>(request: &'a Message, response: &'a anyhow::Result<Message>) -> DeviceExchange<'a> {
+        DeviceExchange { device_id: "kk1", request, response, duration: Duration::from_millis(12) }
+    }
+
+    #[tokio::test]
+    async fn test_exchanges_are_logged_with_secrets_redacted() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = DeviceLog::new(dir.path().to_path_buf());
+
+        let pin = Message::PinMatrixAck(messages::PinMatrixAck { pin: "7913".to_string() });
+        log.log_exchange(&exchange(&pin, &Ok(Message::Success(Default::default()))));
+        let passphrase = Message::PassphraseAck(messages::PassphraseAck { passphrase: "hunter2".to_string() });
+        let refused = Ok(Message::Failure(messages::Failure { code: None, message: Some("Cancelled".to_string()) }));
+        log.log_exchange(&exchange(&passphrase, &refused));
+        log.log_request("kk1", "fw-1", "UpdateFirmware", &serde_json::json!({ "payload": "ab".repeat(1000) }));
+        log.log_response("kk1", "fw-1", true, &serde_json::json!({ "version": "7.10.0" }), None);
+
+        let records = log.recent(10).await.unwrap();
+        let text = fs::read_to_string(dir.path().join(LOG_FILE_NAME)).unwrap();
+        assert!(!text.contains("7913") && !text.contains("hunter2"));
+        assert_eq!(records.len(), 4);
+
+        let (response, request) = (&records[0], &records[1]);
+        assert_eq!((response.message_type.as_str(), response.success), ("UpdateFirmware", Some(true)));
+        assert!(response.duration_ms.is_some());
+        assert_eq!(request.success, None);
+        assert!(request.data.as_ref().unwrap()["payload"].as_str().unwrap().ends_with("(2000 chars)"));
+
+        assert_eq!(records[2].message_type, "PassphraseAck");
+        assert_eq!((records[2].success, records[2].error.as_deref()), (Some(false), Some("Cancelled")));
+        assert_eq!(records[3].data.as_ref().unwrap()["pin"], REDACTED);
+        assert_eq!((records[3].response_type.as_deref(), records[3].duration_ms), (Some("Success"), Some(12)));
+        assert_eq!(log.recent(1).await.unwrap(), records[..1]);
+    }
+
+    #[tokio::test]
+    async fn test_log_rotates_and_clears() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = DeviceLog::with_limits(dir.path().to_path_buf(), 600, 3);
+        for i in 0..20 {
+            log.log_request("kk1", &format!("req-{}", i), "GetFeatures", &serde_json::json!({}));
+        }
+        let records = log.recent(100).await.unwrap();
+        assert_eq!(records[0].request_id, "req-19");
+        assert!(records.len() < 20);
+        assert!(dir.path().join(format!("{}.2", LOG_FILE_NAME)).exists());
+        assert!(!dir.path().join(format!("{}.3", LOG_FILE_NAME)).exists());
+        for path in log_files(dir.path(), 3) {
+            assert!(fs::metadata(path).unwrap().len() <= 600);
+        }
+
+        log.clear().await.unwrap();
+        assert!(log.recent(100).await.unwrap().is_empty());
+        log.log_request("kk1", "after", "GetFeatures", &serde_json::json!({}));
+        assert_eq!(log.recent(100).await.unwrap()[0].request_id, "after");
+    }
+}
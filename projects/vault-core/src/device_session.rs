@@ -0,0 +1,236 @@
+// device_session.rs - Idle tracking and auto-lock of device sessions
+//
+// A KeepKey keeps its PIN and passphrase cached until it is unplugged, sent
+// ClearSession, or its own `auto_lock_delay_ms` passes without use. The vault
+// runs the same idle clock per device so it can lock the device itself and
+// tell the UI, instead of finding out at the next PIN prompt.
+//
+// The clock is fed by the queue observer and keyed by device_id, not by
+// worker, so it carries over when a worker is recreated after a temporary
+// disconnect. Status reads (GetFeatures, Initialize, Ping) are not activity:
+// the app polls them, and polling must not keep a device unlocked. Nor are
+// PIN, passphrase and button prompts, which only ask for the user. Features
+// responses still say whether a session is open and what the delay is.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+use keepkey_rust::device_queue::DeviceExchange;
+use keepkey_rust::messages::{self, Message, MessageType};
+use crate::queue::{get_or_create_device_queue, DeviceQueueManager};
+
+/// How often the app looks for sessions past their auto-lock delay
+pub const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+struct DeviceSession {
+    last_activity: Instant,
+    /// The device's auto-lock delay; None until known, or when it is off
+    auto_lock: Option<Duration>,
+    /// No PIN or passphrase is cached, so there is nothing to lock
+    locked: bool,
+}
+
+/// Idle clocks of every device seen since launch
+#[derive(Debug, Default)]
+pub struct DeviceSessions {
+    sessions: Mutex<HashMap<String, DeviceSession>>,
+}
+
+impl DeviceSessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn sessions(&self) -> MutexGuard<'_, HashMap<String, DeviceSession>> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn with_session<R>(&self, device_id: &str, now: Instant, f: impl FnOnce(&mut DeviceSession) -> R) -> R {
+        let mut sessions = self.sessions();
+        let session = sessions
+            .entry(device_id.to_string())
+            .or_insert(DeviceSession { last_activity: now, auto_lock: None, locked: true });
+        f(session)
+    }
+
+    /// The device was used: restart its idle clock
+    pub fn record_activity(&self, device_id: &str, now: Instant) {
+        self.with_session(device_id, now, |session| {
+            session.last_activity = now;
+            session.locked = false;
+        });
+    }
+
+    /// Take the auto-lock delay and session state from a Features response
+    pub fn record_features(&self, device_id: &str, features: &messages::Features, now: Instant) {
+        let open = features.pin_cached.unwrap_or(false) || features.passphrase_cached.unwrap_or(false);
+        self.with_session(device_id, now, |session| {
+            if let Some(ms) = features.auto_lock_delay_ms {
+                session.auto_lock = (ms > 0).then(|| Duration::from_millis(ms as u64));
+            }
+            if !open {
+                session.locked = true;
+            } else if session.locked {
+                // Unlocked since we last looked; the clock starts now
+                session.locked = false;
+                session.last_activity = now;
+            }
+        });
+    }
+
+    /// Set the auto-lock delay after it was applied to the device
+    pub fn set_auto_lock(&self, device_id: &str, delay: Option<Duration>, now: Instant) {
+        self.with_session(device_id, now, |session| session.auto_lock = delay);
+    }
+
+    /// The device's session was cleared, or ended by other means
+    pub fn mark_locked(&self, device_id: &str) {
+        if let Some(session) = self.sessions().get_mut(device_id) {
+            session.locked = true;
+        }
+    }
+
+    /// Feed one queue exchange into the device's clock
+    pub fn observe(&self, exchange: &DeviceExchange<'_>, now: Instant) {
+        let response = match exchange.response {
+            Ok(Message::Failure(_)) | Err(_) => return,
+            Ok(response) => response,
+        };
+        match (exchange.request.message_type(), response) {
+            (_, Message::Features(features)) => self.record_features(exchange.device_id, features, now),
+            (MessageType::ClearSession, _) => self.mark_locked(exchange.device_id),
+            (MessageType::GetFeatures | MessageType::Initialize | MessageType::Ping, _) => {}
+            // Prompts mid-request: the device is waiting on the user, not used yet
+            (_, Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) | Message::ButtonRequest(_)) => {}
+            _ => self.record_activity(exchange.device_id, now),
+        }
+    }
+
+    /// Devices with an open session idle for longer than their auto-lock delay
+    pub fn due_for_lock(&self, now: Instant) -> Vec<String> {
+        let mut due: Vec<String> = self
+            .sessions()
+            .iter()
+            .filter(|(_, session)| {
+                !session.locked
+                    && session.auto_lock.is_some_and(|delay| now.saturating_duration_since(session.last_activity) >= delay)
+            })
+            .map(|(device_id, _)| device_id.clone())
+            .collect();
+        due.sort();
+        due
+    }
+}
+
+/// The idle clocks shared by the device queues and the auto-lock monitor
+pub fn device_sessions() -> &'static DeviceSessions {
+    static SESSIONS: OnceLock<DeviceSessions> = OnceLock::new();
+    SESSIONS.get_or_init(DeviceSessions::new)
+}
+
+/// Set how long the device stays unlocked without use; the user confirms on
+/// the device. The vault locks it after the same delay.
+pub async fn apply_auto_lock_delay(
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+    delay_ms: u32,
+) -> Result<(), String> {
+    let queue = get_or_create_device_queue(device_id, queue_manager).await?;
+    let request = Message::ApplySettings(messages::ApplySettings {
+        language: None,
+        label: None,
+        use_passphrase: None,
+        auto_lock_delay_ms: Some(delay_ms),
+        u2f_counter: None,
+    });
+    match queue.send_raw(request, true).await {
+        Ok(Message::Success(_)) => {
+            let delay = (delay_ms > 0).then(|| Duration::from_millis(delay_ms as u64));
+            device_sessions().set_auto_lock(device_id, delay, Instant::now());
+            Ok(())
+        }
+        Ok(Message::Failure(f)) => Err(format!("Device refused the auto-lock delay: {}", f.message())),
+        Ok(other) => Err(format!("Unexpected response: {:?}", other.message_type())),
+        Err(e) => Err(format!("Failed to set the auto-lock delay: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange<'a>(request: &'a Message, response: &'a anyhow::Result<Message>) -> DeviceExchange<'a> {
+        DeviceExchange { device_id: "kk1", request, response, duration: Duration::from_millis(5) }
+    }
+
+    fn features(pin_cached: bool, auto_lock_delay_ms: Option<u32>) -> anyhow::Result<Message> {
+        Ok(Message::Features(messages::Features {
+            pin_cached: Some(pin_cached),
+            auto_lock_delay_ms,
+            ..Default::default()
+        }))
+    }
+
+    #[test]
+    fn test_idle_session_is_due_after_its_delay() {
+        let sessions = DeviceSessions::new();
+        let start = Instant::now();
+        let get_features = Message::GetFeatures(Default::default());
+
+        // Unlocked with a 60s delay
+        sessions.observe(&exchange(&get_features, &features(true, Some(60_000))), start);
+        assert!(sessions.due_for_lock(start + Duration::from_secs(59)).is_empty());
+
+        // Signing restarts the clock; status polling does not
+        let get_address = Message::GetAddress(Default::default());
+        let address = Ok(Message::Address(Default::default()));
+        sessions.observe(&exchange(&get_address, &address), start + Duration::from_secs(30));
+        sessions.observe(&exchange(&get_features, &features(true, Some(60_000))), start + Duration::from_secs(80));
+        assert!(sessions.due_for_lock(start + Duration::from_secs(89)).is_empty());
+        assert_eq!(sessions.due_for_lock(start + Duration::from_secs(90)), ["kk1"]);
+
+        // Failed requests are not activity
+        let refused = Ok(Message::Failure(messages::Failure { code: None, message: Some("busy".to_string()) }));
+        sessions.observe(&exchange(&get_address, &refused), start + Duration::from_secs(90));
+        assert_eq!(sessions.due_for_lock(start + Duration::from_secs(90)), ["kk1"]);
+
+        // Clearing the session locks it until the device is used again
+        let clear = Message::ClearSession(Default::default());
+        sessions.observe(&exchange(&clear, &Ok(Message::Success(Default::default()))), start + Duration::from_secs(91));
+        assert!(sessions.due_for_lock(start + Duration::from_secs(500)).is_empty());
+
+        // Prompts are not use: a PIN matrix does not reopen a locked session
+        for prompt in [
+            Message::PinMatrixRequest(Default::default()),
+            Message::PassphraseRequest(Default::default()),
+            Message::ButtonRequest(Default::default()),
+        ] {
+            sessions.observe(&exchange(&get_address, &Ok(prompt)), start + Duration::from_secs(100));
+        }
+        assert!(sessions.due_for_lock(start + Duration::from_secs(500)).is_empty());
+        sessions.observe(&exchange(&get_address, &address), start + Duration::from_secs(500));
+        assert_eq!(sessions.due_for_lock(start + Duration::from_secs(560)), ["kk1"]);
+    }
+
+    #[test]
+    fn test_sessions_without_a_delay_or_a_cached_pin_never_lock() {
+        let sessions = DeviceSessions::new();
+        let start = Instant::now();
+        let get_features = Message::GetFeatures(Default::default());
+        let later = start + Duration::from_secs(3600);
+
+        sessions.record_activity("kk1", start);
+        assert!(sessions.due_for_lock(later).is_empty());
+
+        // A device reporting nothing cached has no session to lock
+        sessions.observe(&exchange(&get_features, &features(false, Some(10_000))), start);
+        assert!(sessions.due_for_lock(later).is_empty());
+
+        // Turning auto-lock off on the device
+        sessions.observe(&exchange(&get_features, &features(true, Some(10_000))), start);
+        assert_eq!(sessions.due_for_lock(later), ["kk1"]);
+        sessions.set_auto_lock("kk1", None, later);
+        assert!(sessions.due_for_lock(later).is_empty());
+    }
+}
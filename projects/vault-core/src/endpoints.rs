@@ -0,0 +1,820 @@
+// endpoints.rs - Shared rate limiting and circuit breaking for external APIs
+//
+// Public esplora, RPC and indexer endpoints ban clients that hammer them.
+// Every outbound request takes a permit from the EndpointManager first: each
+// endpoint (scheme, host and port) has a requests-per-minute token bucket, all
+// endpoints share a concurrency cap, a 429 starts a cooldown taken from its
+// Retry-After header, and consecutive 5xx responses or timeouts open a circuit
+// that later half-opens to let a single probe through. Interactive requests
+// wait briefly for a token; background requests are deferred instead so a
+// sync loop never competes with the user.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Preference key holding per-endpoint requests-per-minute overrides as a
+/// JSON object, e.g. `{"https://mempool.space": 30}`
+pub const ENDPOINT_LIMITS_PREF: &str = "endpoint_rate_limits";
+
+/// Outbound requests in flight across all endpoints
+pub const MAX_CONCURRENT_REQUESTS: usize = 6;
+
+/// Longest an interactive request waits for a token before failing
+pub const MAX_INTERACTIVE_WAIT: Duration = Duration::from_secs(20);
+
+/// Cooldown after a 429 without a usable Retry-After header
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Retry-After values beyond this are clamped
+pub const MAX_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
+/// How long to come back later while a half-open probe is in flight
+const PROBE_WAIT: Duration = Duration::from_secs(1);
+
+/// Budget and breaker settings for one endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct EndpointPolicy {
+    pub requests_per_minute: u32,
+    /// Tokens that can be saved up for a burst of requests
+    pub burst: u32,
+    /// Consecutive 5xx responses or timeouts that open the circuit
+    pub failure_threshold: u32,
+    /// How long an open circuit rejects requests before half-opening
+    pub open_for_ms: u64,
+}
+
+impl EndpointPolicy {
+    /// Policy allowing `requests_per_minute`, with ten seconds' worth of burst
+    pub fn per_minute(requests_per_minute: u32) -> Self {
+        let requests_per_minute = requests_per_minute.max(1);
+        Self {
+            requests_per_minute,
+            burst: (requests_per_minute / 6).max(1),
+            ..Self::default()
+        }
+    }
+
+    fn tokens_per_sec(&self) -> f64 {
+        self.requests_per_minute as f64 / 60.0
+    }
+
+    fn open_for(&self) -> Duration {
+        Duration::from_millis(self.open_for_ms)
+    }
+}
+
+impl Default for EndpointPolicy {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 60,
+            burst: 10,
+            failure_threshold: 5,
+            open_for_ms: 60_000,
+        }
+    }
+}
+
+/// Who is waiting on a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// The user is waiting; worth a short wait for a token
+    Interactive,
+    /// Sync loops and trackers; deferred whenever the endpoint is busy
+    Background,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    /// Rejecting requests after repeated failures
+    Open,
+    /// The open period is over; the next request is a probe
+    HalfOpen,
+}
+
+/// What came of a request made under a permit
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Success,
+    /// 429 Too Many Requests, with the parsed Retry-After if any
+    RateLimited { retry_after: Option<Duration> },
+    /// 5xx response, timeout or connection failure
+    Failure(String),
+}
+
+#[derive(Debug)]
+pub enum EndpointError {
+    /// A background request would have had to wait; try again later
+    Deferred { endpoint: String, retry_in: Duration },
+    /// The endpoint answered 429 and its Retry-After has not passed
+    CoolingDown { endpoint: String, retry_in: Duration },
+    /// The endpoint kept failing and is not being contacted
+    CircuitOpen { endpoint: String, retry_in: Duration },
+    /// The requests-per-minute budget is spent for longer than an interactive wait
+    BudgetExhausted { endpoint: String, retry_in: Duration },
+    InvalidUrl(String),
+    Request(reqwest::Error),
+}
+
+impl EndpointError {
+    pub fn is_deferred(&self) -> bool {
+        matches!(self, Self::Deferred { .. })
+    }
+}
+
+impl fmt::Display for EndpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Deferred { endpoint, retry_in } => {
+                write!(f, "Deferred request to {} for {}s", endpoint, retry_in.as_secs().max(1))
+            }
+            Self::CoolingDown { endpoint, retry_in } => {
+                write!(f, "{} is rate limiting us, retry in {}s", endpoint, retry_in.as_secs().max(1))
+            }
+            Self::CircuitOpen { endpoint, retry_in } => {
+                write!(f, "{} is failing, retry in {}s", endpoint, retry_in.as_secs().max(1))
+            }
+            Self::BudgetExhausted { endpoint, retry_in } => {
+                write!(f, "Request budget for {} is spent, retry in {}s", endpoint, retry_in.as_secs().max(1))
+            }
+            Self::InvalidUrl(url) => write!(f, "Invalid endpoint URL: {}", url),
+            Self::Request(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for EndpointError {}
+
+/// Limiter and breaker state of one endpoint, for diagnostics
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct EndpointStatus {
+    pub endpoint: String,
+    pub policy: EndpointPolicy,
+    pub available_tokens: f64,
+    pub circuit: CircuitState,
+    pub consecutive_failures: u32,
+    /// Time left on a 429 cooldown
+    pub cooldown_remaining_ms: Option<u64>,
+    /// Time until an open circuit half-opens
+    pub reopens_in_ms: Option<u64>,
+    pub requests: u64,
+    pub rate_limited: u32,
+    pub deferred: u32,
+    pub last_error: Option<String>,
+}
+
+/// Why a request could not be admitted right now
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Refusal {
+    CoolingDown(Duration),
+    CircuitOpen(Duration),
+    Starved(Duration),
+}
+
+impl Refusal {
+    fn retry_in(self) -> Duration {
+        match self {
+            Self::CoolingDown(d) | Self::CircuitOpen(d) | Self::Starved(d) => d,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Admission {
+    /// Wait this long for the token before sending
+    wait: Duration,
+    /// This request decides whether a half-open circuit closes
+    probe: bool,
+}
+
+#[derive(Debug)]
+struct EndpointState {
+    tokens: f64,
+    refilled_at: Instant,
+    cooldown_until: Option<Instant>,
+    circuit: CircuitState,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+    consecutive_failures: u32,
+    requests: u64,
+    rate_limited: u32,
+    deferred: u32,
+    last_error: Option<String>,
+}
+
+impl EndpointState {
+    fn new(policy: &EndpointPolicy, now: Instant) -> Self {
+        Self {
+            tokens: policy.burst as f64,
+            refilled_at: now,
+            cooldown_until: None,
+            circuit: CircuitState::Closed,
+            opened_at: None,
+            probe_in_flight: false,
+            consecutive_failures: 0,
+            requests: 0,
+            rate_limited: 0,
+            deferred: 0,
+            last_error: None,
+        }
+    }
+
+    fn refill(&mut self, policy: &EndpointPolicy, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * policy.tokens_per_sec()).min(policy.burst as f64);
+        self.refilled_at = now;
+    }
+
+    /// Open circuits whose open period is over read as half-open
+    fn circuit_at(&self, policy: &EndpointPolicy, now: Instant) -> CircuitState {
+        match (self.circuit, self.opened_at) {
+            (CircuitState::Open, Some(opened)) if now.saturating_duration_since(opened) >= policy.open_for() => {
+                CircuitState::HalfOpen
+            }
+            (circuit, _) => circuit,
+        }
+    }
+
+    fn cooldown_remaining(&self, now: Instant) -> Option<Duration> {
+        self.cooldown_until.filter(|until| *until > now).map(|until| until - now)
+    }
+
+    fn admit(&mut self, policy: &EndpointPolicy, priority: Priority, now: Instant) -> Result<Admission, Refusal> {
+        if let Some(remaining) = self.cooldown_remaining(now) {
+            return Err(Refusal::CoolingDown(remaining));
+        }
+
+        let probe = match self.circuit_at(policy, now) {
+            CircuitState::Closed => false,
+            CircuitState::Open => {
+                let opened = self.opened_at.unwrap_or(now);
+                return Err(Refusal::CircuitOpen(policy.open_for().saturating_sub(now.saturating_duration_since(opened))));
+            }
+            CircuitState::HalfOpen if self.probe_in_flight => return Err(Refusal::CircuitOpen(PROBE_WAIT)),
+            CircuitState::HalfOpen => true,
+        };
+
+        self.refill(policy, now);
+        let wait = if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / policy.tokens_per_sec())
+        };
+        if !wait.is_zero() && (priority == Priority::Background || wait > MAX_INTERACTIVE_WAIT) {
+            return Err(Refusal::Starved(wait));
+        }
+
+        // Interactive requests take the token now and sleep off the deficit
+        self.tokens -= 1.0;
+        self.requests += 1;
+        if probe {
+            self.circuit = CircuitState::HalfOpen;
+            self.probe_in_flight = true;
+        }
+        Ok(Admission { wait, probe })
+    }
+
+    fn record(&mut self, policy: &EndpointPolicy, outcome: Outcome, probe: bool, now: Instant) {
+        if probe {
+            self.probe_in_flight = false;
+        }
+        match outcome {
+            Outcome::Success => {
+                self.consecutive_failures = 0;
+                self.circuit = CircuitState::Closed;
+                self.opened_at = None;
+            }
+            Outcome::RateLimited { retry_after } => {
+                self.rate_limited += 1;
+                self.cooldown_until = Some(now + retry_after.unwrap_or(DEFAULT_COOLDOWN).min(MAX_COOLDOWN));
+                self.tokens = self.tokens.min(0.0);
+                self.last_error = Some("429 Too Many Requests".to_string());
+            }
+            Outcome::Failure(reason) => {
+                self.consecutive_failures += 1;
+                self.last_error = Some(reason);
+                if probe || self.consecutive_failures >= policy.failure_threshold {
+                    self.circuit = CircuitState::Open;
+                    self.opened_at = Some(now);
+                }
+            }
+        }
+    }
+
+    fn status(&self, endpoint: &str, policy: &EndpointPolicy, now: Instant) -> EndpointStatus {
+        let circuit = self.circuit_at(policy, now);
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        EndpointStatus {
+            endpoint: endpoint.to_string(),
+            policy: *policy,
+            available_tokens: (self.tokens + elapsed * policy.tokens_per_sec()).min(policy.burst as f64),
+            circuit,
+            consecutive_failures: self.consecutive_failures,
+            cooldown_remaining_ms: self.cooldown_remaining(now).map(|d| d.as_millis() as u64),
+            reopens_in_ms: match (circuit, self.opened_at) {
+                (CircuitState::Open, Some(opened)) => {
+                    Some(policy.open_for().saturating_sub(now.saturating_duration_since(opened)).as_millis() as u64)
+                }
+                _ => None,
+            },
+            requests: self.requests,
+            rate_limited: self.rate_limited,
+            deferred: self.deferred,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ManagerState {
+    default_policy: EndpointPolicy,
+    policies: HashMap<String, EndpointPolicy>,
+    endpoints: HashMap<String, EndpointState>,
+}
+
+impl ManagerState {
+    fn policy(&self, endpoint: &str) -> EndpointPolicy {
+        self.policies.get(endpoint).copied().unwrap_or(self.default_policy)
+    }
+}
+
+/// Rate limiter and circuit breaker shared by every outbound HTTP caller
+pub struct EndpointManager {
+    state: Mutex<ManagerState>,
+    slots: Semaphore,
+}
+
+/// Permission to send one request. Report how it went with `finish`; a permit
+/// dropped unfinished (a cancelled request) only frees its slot.
+pub struct EndpointPermit<'a> {
+    manager: &'a EndpointManager,
+    endpoint: String,
+    probe: bool,
+    finished: bool,
+    /// Taken once any token wait is over
+    slot: Option<SemaphorePermit<'a>>,
+}
+
+impl EndpointPermit<'_> {
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn finish(mut self, outcome: Outcome) {
+        self.finished = true;
+        let now = Instant::now();
+        self.manager.with_state(|state| {
+            let policy = state.policy(&self.endpoint);
+            if let Some(endpoint) = state.endpoints.get_mut(&self.endpoint) {
+                endpoint.record(&policy, outcome, self.probe, now);
+            }
+        });
+    }
+}
+
+impl Drop for EndpointPermit<'_> {
+    fn drop(&mut self) {
+        if self.finished || !self.probe {
+            return;
+        }
+        // A cancelled probe must not leave the circuit waiting on it forever
+        self.manager.with_state(|state| {
+            if let Some(endpoint) = state.endpoints.get_mut(&self.endpoint) {
+                endpoint.probe_in_flight = false;
+            }
+        });
+    }
+}
+
+/// The endpoint a URL is limited under: its scheme, host and port
+pub fn endpoint_key(url: &str) -> Result<String, EndpointError> {
+    let parsed = reqwest::Url::parse(url).map_err(|_| EndpointError::InvalidUrl(url.to_string()))?;
+    let origin = parsed.origin();
+    if !origin.is_tuple() {
+        return Err(EndpointError::InvalidUrl(url.to_string()));
+    }
+    Ok(origin.ascii_serialization())
+}
+
+/// Parse a Retry-After header: delay-seconds or an HTTP date
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+/// Classify the result of a request for the limiter
+pub fn outcome_of(result: &Result<reqwest::Response, reqwest::Error>) -> Outcome {
+    match result {
+        Ok(response) if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => Outcome::RateLimited {
+            retry_after: response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| parse_retry_after(v, SystemTime::now())),
+        },
+        Ok(response) if response.status().is_server_error() => Outcome::Failure(response.status().to_string()),
+        Ok(_) => Outcome::Success,
+        Err(e) if e.is_timeout() => Outcome::Failure("timed out".to_string()),
+        Err(e) => Outcome::Failure(e.to_string()),
+    }
+}
+
+impl EndpointManager {
+    pub fn new() -> Self {
+        Self::with_concurrency(MAX_CONCURRENT_REQUESTS)
+    }
+
+    pub fn with_concurrency(max_concurrent: usize) -> Self {
+        Self {
+            state: Mutex::new(ManagerState::default()),
+            slots: Semaphore::new(max_concurrent.max(1)),
+        }
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut ManagerState) -> R) -> R {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut state)
+    }
+
+    /// Policy for endpoints without their own
+    pub fn set_default_policy(&self, policy: EndpointPolicy) {
+        self.with_state(|state| state.default_policy = policy);
+    }
+
+    /// Policy for the endpoint serving `url`
+    pub fn set_policy(&self, url: &str, policy: EndpointPolicy) -> Result<(), EndpointError> {
+        let endpoint = endpoint_key(url)?;
+        self.with_state(|state| state.policies.insert(endpoint, policy));
+        Ok(())
+    }
+
+    /// Apply the `ENDPOINT_LIMITS_PREF` preference; bad entries are skipped
+    pub fn configure_from_json(&self, json: &str) -> Result<(), String> {
+        let limits: HashMap<String, u32> = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid {}: {}", ENDPOINT_LIMITS_PREF, e))?;
+        for (url, requests_per_minute) in limits {
+            if let Err(e) = self.set_policy(&url, EndpointPolicy::per_minute(requests_per_minute)) {
+                log::warn!("Ignoring rate limit for {}: {}", url, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait for permission to send a request to `url`. Background requests
+    /// that would have to wait come back as `EndpointError::Deferred`.
+    pub async fn acquire(&self, url: &str, priority: Priority) -> Result<EndpointPermit<'_>, EndpointError> {
+        let endpoint = endpoint_key(url)?;
+
+        let early_slot = match priority {
+            Priority::Interactive => None,
+            Priority::Background => match self.slots.try_acquire() {
+                Ok(slot) => Some(slot),
+                Err(_) => {
+                    self.count_deferral(&endpoint);
+                    return Err(EndpointError::Deferred { endpoint, retry_in: PROBE_WAIT });
+                }
+            },
+        };
+
+        let now = Instant::now();
+        let admitted = self.with_state(|state| {
+            let policy = state.policy(&endpoint);
+            let entry = state
+                .endpoints
+                .entry(endpoint.clone())
+                .or_insert_with(|| EndpointState::new(&policy, now));
+            let admitted = entry.admit(&policy, priority, now);
+            if admitted.is_err() && priority == Priority::Background {
+                entry.deferred += 1;
+            }
+            admitted
+        });
+        let admission = match admitted {
+            Ok(admission) => admission,
+            Err(refusal) => {
+                let retry_in = refusal.retry_in();
+                return Err(match (priority, refusal) {
+                    (Priority::Background, _) => EndpointError::Deferred { endpoint, retry_in },
+                    (_, Refusal::CoolingDown(_)) => EndpointError::CoolingDown { endpoint, retry_in },
+                    (_, Refusal::CircuitOpen(_)) => EndpointError::CircuitOpen { endpoint, retry_in },
+                    (_, Refusal::Starved(_)) => EndpointError::BudgetExhausted { endpoint, retry_in },
+                });
+            }
+        };
+
+        // Build the permit before waiting so a cancelled wait releases a probe
+        let mut permit = EndpointPermit { manager: self, endpoint, probe: admission.probe, finished: false, slot: early_slot };
+        if permit.slot.is_none() {
+            if !admission.wait.is_zero() {
+                tokio::time::sleep(admission.wait).await;
+            }
+            permit.slot = Some(self.slots.acquire().await.expect("endpoint semaphore is never closed"));
+        }
+        Ok(permit)
+    }
+
+    fn count_deferral(&self, endpoint: &str) {
+        self.with_state(|state| {
+            if let Some(entry) = state.endpoints.get_mut(endpoint) {
+                entry.deferred += 1;
+            }
+        });
+    }
+
+    /// Send a request under a permit and record how it went. 429 and 5xx
+    /// responses are returned like any other; callers still check the status.
+    pub async fn send(&self, request: reqwest::RequestBuilder, priority: Priority) -> Result<reqwest::Response, EndpointError> {
+        let (client, request) = request.build_split();
+        let request = request.map_err(EndpointError::Request)?;
+        let permit = self.acquire(request.url().as_str(), priority).await?;
+        let result = client.execute(request).await;
+        permit.finish(outcome_of(&result));
+        result.map_err(EndpointError::Request)
+    }
+
+    /// How long background work against `url` would be deferred right now,
+    /// without taking a token. None when it could go ahead.
+    pub fn background_deferral(&self, url: &str) -> Option<Duration> {
+        let endpoint = endpoint_key(url).ok()?;
+        let now = Instant::now();
+        self.with_state(|state| {
+            let policy = state.policy(&endpoint);
+            let entry = state.endpoints.get(&endpoint)?;
+            if let Some(remaining) = entry.cooldown_remaining(now) {
+                return Some(remaining);
+            }
+            match entry.circuit_at(&policy, now) {
+                CircuitState::Open => {
+                    let opened = entry.opened_at.unwrap_or(now);
+                    Some(policy.open_for().saturating_sub(now.saturating_duration_since(opened)))
+                }
+                CircuitState::HalfOpen if entry.probe_in_flight => Some(PROBE_WAIT),
+                _ => None,
+            }
+        })
+    }
+
+    /// Close the circuit and clear the cooldown of the endpoint serving `url`.
+    /// Returns false when nothing has been sent to it yet.
+    pub fn reset_circuit(&self, url: &str) -> Result<bool, EndpointError> {
+        let endpoint = endpoint_key(url)?;
+        let now = Instant::now();
+        Ok(self.with_state(|state| {
+            let policy = state.policy(&endpoint);
+            let Some(entry) = state.endpoints.get_mut(&endpoint) else {
+                return false;
+            };
+            entry.circuit = CircuitState::Closed;
+            entry.opened_at = None;
+            entry.probe_in_flight = false;
+            entry.consecutive_failures = 0;
+            entry.cooldown_until = None;
+            entry.tokens = entry.tokens.max(policy.burst as f64);
+            entry.refilled_at = now;
+            true
+        }))
+    }
+
+    /// Limiter state of every endpoint contacted so far
+    pub fn snapshot(&self) -> Vec<EndpointStatus> {
+        let now = Instant::now();
+        self.with_state(|state| {
+            let mut statuses: Vec<EndpointStatus> = state
+                .endpoints
+                .iter()
+                .map(|(endpoint, entry)| entry.status(endpoint, &state.policy(endpoint), now))
+                .collect();
+            statuses.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+            statuses
+        })
+    }
+}
+
+impl Default for EndpointManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The process-wide endpoint manager
+pub fn endpoint_manager() -> &'static EndpointManager {
+    static MANAGER: OnceLock<EndpointManager> = OnceLock::new();
+    MANAGER.get_or_init(EndpointManager::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// HTTP server answering with scripted statuses (200 once the script runs
+    /// out) and counting the requests it saw
+    async fn mock_server(script: Vec<(u16, Option<&'static str>)>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let script = Arc::new(Mutex::new(VecDeque::from(script)));
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else { return };
+                counter.fetch_add(1, Ordering::SeqCst);
+                let (status, retry_after) = script.lock().unwrap().pop_front().unwrap_or((200, None));
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let _ = socket.read(&mut buf).await;
+                    let retry_after = retry_after.map(|v| format!("Retry-After: {}\r\n", v)).unwrap_or_default();
+                    let response = format!(
+                        "HTTP/1.1 {} Mock\r\n{}Content-Length: 2\r\nConnection: close\r\n\r\nok",
+                        status, retry_after
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        (url, hits)
+    }
+
+    #[test]
+    fn test_token_bucket_defers_background_and_waits_interactive() {
+        let policy = EndpointPolicy { requests_per_minute: 60, burst: 2, ..EndpointPolicy::default() };
+        let t0 = Instant::now();
+        let mut state = EndpointState::new(&policy, t0);
+
+        assert_eq!(state.admit(&policy, Priority::Background, t0).unwrap().wait, Duration::ZERO);
+        assert_eq!(state.admit(&policy, Priority::Background, t0).unwrap().wait, Duration::ZERO);
+        assert_eq!(state.admit(&policy, Priority::Background, t0), Err(Refusal::Starved(Duration::from_secs(1))));
+
+        // One token a second: an interactive request takes it ahead of time
+        let admission = state.admit(&policy, Priority::Interactive, t0).unwrap();
+        assert_eq!(admission.wait, Duration::from_secs(1));
+        assert!(state.admit(&policy, Priority::Background, t0 + Duration::from_secs(1)).is_err());
+        assert!(state.admit(&policy, Priority::Background, t0 + Duration::from_secs(2)).is_ok());
+    }
+
+    #[test]
+    fn test_interactive_wait_is_bounded() {
+        let policy = EndpointPolicy { requests_per_minute: 1, burst: 1, ..EndpointPolicy::default() };
+        let t0 = Instant::now();
+        let mut state = EndpointState::new(&policy, t0);
+        assert!(state.admit(&policy, Priority::Interactive, t0).is_ok());
+        assert!(matches!(state.admit(&policy, Priority::Interactive, t0), Err(Refusal::Starved(_))));
+    }
+
+    #[test]
+    fn test_circuit_half_opens_with_one_probe() {
+        let policy = EndpointPolicy { failure_threshold: 2, open_for_ms: 1_000, ..EndpointPolicy::default() };
+        let t0 = Instant::now();
+        let mut state = EndpointState::new(&policy, t0);
+
+        state.record(&policy, Outcome::Failure("500".into()), false, t0);
+        assert_eq!(state.circuit_at(&policy, t0), CircuitState::Closed);
+        state.record(&policy, Outcome::Failure("500".into()), false, t0);
+        assert_eq!(state.circuit_at(&policy, t0), CircuitState::Open);
+        assert!(matches!(state.admit(&policy, Priority::Interactive, t0), Err(Refusal::CircuitOpen(_))));
+
+        let t1 = t0 + Duration::from_secs(1);
+        let probe = state.admit(&policy, Priority::Interactive, t1).unwrap();
+        assert!(probe.probe);
+        assert_eq!(state.admit(&policy, Priority::Interactive, t1), Err(Refusal::CircuitOpen(PROBE_WAIT)));
+
+        // A failed probe reopens straight away
+        state.record(&policy, Outcome::Failure("timed out".into()), true, t1);
+        assert_eq!(state.circuit_at(&policy, t1), CircuitState::Open);
+
+        let t2 = t1 + Duration::from_secs(1);
+        assert!(state.admit(&policy, Priority::Interactive, t2).unwrap().probe);
+        state.record(&policy, Outcome::Success, true, t2);
+        assert_eq!(state.circuit_at(&policy, t2), CircuitState::Closed);
+        assert_eq!(state.consecutive_failures, 0);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_endpoint_key_ignores_path() {
+        assert_eq!(endpoint_key("https://mempool.space/api/mempool").unwrap(), "https://mempool.space");
+        assert_eq!(endpoint_key("http://127.0.0.1:8545").unwrap(), "http://127.0.0.1:8545");
+        assert!(endpoint_key("not a url").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_429_starts_cooldown_until_reset() {
+        let (url, hits) = mock_server(vec![(429, Some("120"))]).await;
+        let manager = EndpointManager::new();
+        let client = reqwest::Client::new();
+
+        let response = manager.send(client.get(&url), Priority::Interactive).await.unwrap();
+        assert_eq!(response.status(), 429);
+
+        let err = manager.send(client.get(&url), Priority::Interactive).await.unwrap_err();
+        assert!(matches!(err, EndpointError::CoolingDown { retry_in, .. } if retry_in > Duration::from_secs(100)));
+        let err = manager.send(client.get(&url), Priority::Background).await.unwrap_err();
+        assert!(err.is_deferred());
+        assert!(manager.background_deferral(&url).is_some());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let status = manager.snapshot().remove(0);
+        assert_eq!(status.rate_limited, 1);
+        assert_eq!(status.deferred, 1);
+        assert!(status.cooldown_remaining_ms.unwrap() > 100_000);
+
+        assert!(manager.reset_circuit(&url).unwrap());
+        let response = manager.send(client.get(&url), Priority::Background).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_5xx_opens_circuit_and_probe_recovers() {
+        let (url, hits) = mock_server(vec![(500, None), (503, None), (500, None)]).await;
+        let manager = EndpointManager::new();
+        manager
+            .set_policy(&url, EndpointPolicy { failure_threshold: 2, open_for_ms: 100, ..EndpointPolicy::default() })
+            .unwrap();
+        let client = reqwest::Client::new();
+
+        for _ in 0..2 {
+            let response = manager.send(client.get(&url), Priority::Interactive).await.unwrap();
+            assert!(response.status().is_server_error());
+        }
+        let err = manager.send(client.get(&url), Priority::Interactive).await.unwrap_err();
+        assert!(matches!(err, EndpointError::CircuitOpen { .. }));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert_eq!(manager.snapshot()[0].circuit, CircuitState::Open);
+
+        // The first probe fails and reopens the circuit
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert_eq!(manager.snapshot()[0].circuit, CircuitState::HalfOpen);
+        let response = manager.send(client.get(&url), Priority::Interactive).await.unwrap();
+        assert_eq!(response.status(), 500);
+        assert!(manager.send(client.get(&url), Priority::Background).await.unwrap_err().is_deferred());
+
+        // The second probe succeeds and closes it
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        let response = manager.send(client.get(&url), Priority::Interactive).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let status = manager.snapshot().remove(0);
+        assert_eq!(status.circuit, CircuitState::Closed);
+        assert_eq!(status.consecutive_failures, 0);
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_timeouts_count_as_failures() {
+        // Accepts connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        let manager = EndpointManager::new();
+        manager.set_policy(&url, EndpointPolicy { failure_threshold: 1, ..EndpointPolicy::default() }).unwrap();
+        let client = reqwest::Client::builder().timeout(Duration::from_millis(100)).build().unwrap();
+
+        assert!(matches!(
+            manager.send(client.get(&url), Priority::Interactive).await,
+            Err(EndpointError::Request(_))
+        ));
+        let status = manager.snapshot().remove(0);
+        assert_eq!(status.circuit, CircuitState::Open);
+        assert_eq!(status.last_error.as_deref(), Some("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_background_defers_when_all_slots_are_busy() {
+        let (url, _) = mock_server(Vec::new()).await;
+        let manager = EndpointManager::with_concurrency(1);
+        let held = manager.acquire(&url, Priority::Interactive).await.unwrap();
+
+        assert!(matches!(manager.acquire(&url, Priority::Background).await, Err(e) if e.is_deferred()));
+        held.finish(Outcome::Success);
+        assert!(manager.acquire(&url, Priority::Background).await.is_ok());
+    }
+}
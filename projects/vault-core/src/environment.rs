@@ -0,0 +1,603 @@
+// environment.rs - Host environment probe for first launch and support
+//
+// Most "my KeepKey is not detected" reports are not about the device: a
+// missing udev rule, KeepKey Desktop v5 or a bridge service still holding the
+// USB interface, antivirus blocking HID, a read-only data directory or no
+// route to the default endpoints. The probe checks each of these before a
+// device is plugged in and reports a status and remediation code per check,
+// which onboarding turns into instructions.
+//
+// Every check runs on its own blocking thread under CHECK_TIMEOUT and all of
+// them run at once, so a check stuck on a port or a DNS lookup only costs its
+// own deadline and the whole probe stays under PROBE_BUDGET. A timed-out
+// thread is abandoned, not joined.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use keepkey_rust::claim::Platform;
+
+/// Deadline of a single check
+pub const CHECK_TIMEOUT: Duration = Duration::from_millis(2000);
+/// Upper bound for the whole probe
+pub const PROBE_BUDGET: Duration = Duration::from_secs(3);
+/// Connect timeout of each local port and endpoint connection
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(1500);
+/// Local ports are only asked for a handshake, which is immediate when open
+const PORT_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Event emitted when any check fails or times out
+pub const ISSUES_FOUND_EVENT: &str = "environment:issues-found";
+
+/// KeepKey USB vendor id as it appears in udev rules
+const KEEPKEY_VENDOR_ID: &str = "2b24";
+
+const UDEV_RULE_DIRS: &[&str] = &["/etc/udev/rules.d", "/lib/udev/rules.d", "/usr/lib/udev/rules.d"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum CheckKind {
+    UsbPermissions,
+    ConflictingApps,
+    HidApi,
+    DataDir,
+    Network,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    /// Works, but something is likely to cause trouble
+    Warn,
+    Fail,
+    /// Does not apply on this platform
+    Skipped,
+    TimedOut,
+}
+
+impl CheckStatus {
+    /// Whether the user should act before plugging in a device
+    pub fn is_issue(self) -> bool {
+        matches!(self, CheckStatus::Fail | CheckStatus::TimedOut)
+    }
+}
+
+/// What the user should do about a check; onboarding maps each code to
+/// platform-specific instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum Remediation {
+    InstallUdevRules,
+    QuitKeepKeyDesktop,
+    StopKeepKeyBridge,
+    StopTrezorBridge,
+    /// Allow the vault through antivirus or endpoint protection
+    AllowHidAccess,
+    FixDataDirPermissions,
+    CheckNetwork,
+}
+
+/// Software known to claim the KeepKey interface while running
+#[derive(Debug, Clone, Copy)]
+pub struct KnownConflict {
+    pub name: &'static str,
+    /// Executable names, lowercase and without ".exe"
+    pub process_names: &'static [&'static str],
+    /// Local ports the software listens on
+    pub ports: &'static [u16],
+    pub remediation: Remediation,
+}
+
+/// KeepKey Desktop listens on 1646 like the vault's own REST API, so it is
+/// only recognized by process name
+pub const KNOWN_CONFLICTS: &[KnownConflict] = &[
+    KnownConflict {
+        name: "KeepKey Desktop",
+        process_names: &["keepkey desktop", "keepkey-desktop"],
+        ports: &[],
+        remediation: Remediation::QuitKeepKeyDesktop,
+    },
+    KnownConflict {
+        name: "KeepKey Bridge",
+        process_names: &["keepkeyd", "keepkey-bridge", "keepkey bridge"],
+        ports: &[],
+        remediation: Remediation::StopKeepKeyBridge,
+    },
+    KnownConflict {
+        name: "Trezor Bridge",
+        process_names: &["trezord", "trezord-go"],
+        ports: &[21325],
+        remediation: Remediation::StopTrezorBridge,
+    },
+];
+
+/// Result of one check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CheckOutcome {
+    pub status: CheckStatus,
+    pub detail: String,
+    pub remediation: Option<Remediation>,
+}
+
+impl CheckOutcome {
+    fn pass(detail: impl Into<String>) -> Self {
+        Self { status: CheckStatus::Pass, detail: detail.into(), remediation: None }
+    }
+
+    fn skipped(detail: impl Into<String>) -> Self {
+        Self { status: CheckStatus::Skipped, detail: detail.into(), remediation: None }
+    }
+
+    fn issue(status: CheckStatus, detail: impl Into<String>, remediation: Remediation) -> Self {
+        Self { status, detail: detail.into(), remediation: Some(remediation) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct EnvironmentCheck {
+    pub check: CheckKind,
+    /// Endpoint for network checks
+    pub target: Option<String>,
+    #[serde(flatten)]
+    pub outcome: CheckOutcome,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct EnvironmentReport {
+    pub checks: Vec<EnvironmentCheck>,
+    pub issues_found: bool,
+    pub duration_ms: u64,
+    /// Epoch seconds the probe ran
+    pub probed_at: i64,
+}
+
+impl EnvironmentReport {
+    /// Checks the user should act on, for the `environment:issues-found` payload
+    pub fn issues(&self) -> Vec<&EnvironmentCheck> {
+        self.checks.iter().filter(|c| c.outcome.status.is_issue()).collect()
+    }
+}
+
+/// What the probe asks of the host; tests substitute each answer
+pub trait ProbeHost: Send + Sync + 'static {
+    fn platform(&self) -> Platform;
+    /// Contents of every udev rules file
+    fn udev_rules(&self) -> Vec<String>;
+    /// Names of running executables
+    fn running_processes(&self) -> Result<Vec<String>, String>;
+    /// Whether something accepts connections on this local port
+    fn port_open(&self, port: u16) -> bool;
+    /// Number of HID devices the HID API can enumerate
+    fn hid_api(&self) -> Result<usize, String>;
+    fn data_dir_writable(&self) -> Result<(), String>;
+    /// Open a TCP connection to the host serving `url`
+    fn reachable(&self, url: &str) -> Result<(), String>;
+}
+
+/// Whether a udev rule line matches KeepKey and grants access to it
+fn rule_grants_access(line: &str) -> bool {
+    let line = line.to_ascii_lowercase();
+    line.contains("uaccess") || line.contains("mode=\"0666\"") || line.contains("mode=\"0660\"") || line.contains("group=")
+}
+
+/// Linux needs a udev rule for the vault to open the device without root
+pub fn check_usb_permissions(platform: Platform, rules: &[String]) -> CheckOutcome {
+    if platform != Platform::Linux {
+        return CheckOutcome::skipped("USB access needs no extra rules on this platform");
+    }
+    let keepkey_rules: Vec<&str> = rules
+        .iter()
+        .flat_map(|contents| contents.lines())
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#') && line.to_ascii_lowercase().contains(KEEPKEY_VENDOR_ID))
+        .collect();
+
+    if keepkey_rules.is_empty() {
+        CheckOutcome::issue(CheckStatus::Fail, "No udev rule for KeepKey devices is installed", Remediation::InstallUdevRules)
+    } else if keepkey_rules.iter().any(|line| rule_grants_access(line)) {
+        CheckOutcome::pass("udev rules for KeepKey devices are installed")
+    } else {
+        CheckOutcome::issue(
+            CheckStatus::Warn,
+            "A udev rule matches KeepKey devices but does not grant user access",
+            Remediation::InstallUdevRules,
+        )
+    }
+}
+
+/// "C:\\Program Files\\KeepKey Desktop.exe" -> "keepkey desktop"
+fn normalize_process_name(name: &str) -> String {
+    let base = name.trim().rsplit(['/', '\\']).next().unwrap_or_default().to_ascii_lowercase();
+    base.strip_suffix(".exe").map(str::to_string).unwrap_or(base)
+}
+
+/// Known conflicting software found running, by process name or open port
+pub fn check_conflicts(processes: Result<Vec<String>, String>, open_ports: &[u16]) -> CheckOutcome {
+    let (names, list_error) = match processes {
+        Ok(names) => (names.iter().map(|n| normalize_process_name(n)).collect::<Vec<_>>(), None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+
+    let found: Vec<&KnownConflict> = KNOWN_CONFLICTS
+        .iter()
+        .filter(|conflict| {
+            conflict.process_names.iter().any(|p| names.iter().any(|n| n == p))
+                || conflict.ports.iter().any(|p| open_ports.contains(p))
+        })
+        .collect();
+
+    if let Some(first) = found.first() {
+        let found_names: Vec<&str> = found.iter().map(|c| c.name).collect();
+        return CheckOutcome::issue(
+            CheckStatus::Fail,
+            format!("Running and may hold the device: {}", found_names.join(", ")),
+            first.remediation,
+        );
+    }
+    match list_error {
+        Some(e) => CheckOutcome {
+            status: CheckStatus::Warn,
+            detail: format!("Could not list running processes: {}", e),
+            remediation: None,
+        },
+        None => CheckOutcome::pass("No conflicting KeepKey or bridge software is running"),
+    }
+}
+
+pub fn check_hid_api(result: Result<usize, String>) -> CheckOutcome {
+    match result {
+        Ok(count) => CheckOutcome::pass(format!("HID API available ({} HID devices visible)", count)),
+        Err(e) => CheckOutcome::issue(CheckStatus::Fail, e, Remediation::AllowHidAccess),
+    }
+}
+
+pub fn check_data_dir(result: Result<(), String>) -> CheckOutcome {
+    match result {
+        Ok(()) => CheckOutcome::pass("Data directory is writable"),
+        Err(e) => CheckOutcome::issue(CheckStatus::Fail, e, Remediation::FixDataDirPermissions),
+    }
+}
+
+pub fn check_network(url: &str, result: Result<(), String>) -> CheckOutcome {
+    match result {
+        Ok(()) => CheckOutcome::pass(format!("{} is reachable", url)),
+        Err(e) => CheckOutcome::issue(CheckStatus::Fail, format!("{} is unreachable: {}", url, e), Remediation::CheckNetwork),
+    }
+}
+
+/// Run `check` on a blocking thread, giving up after `timeout`
+async fn time_boxed<F>(check: CheckKind, target: Option<String>, timeout: Duration, run: F) -> EnvironmentCheck
+where
+    F: FnOnce() -> CheckOutcome + Send + 'static,
+{
+    let started = Instant::now();
+    let outcome = match tokio::time::timeout(timeout, tokio::task::spawn_blocking(run)).await {
+        Ok(Ok(outcome)) => outcome,
+        Ok(Err(e)) => CheckOutcome {
+            status: CheckStatus::Fail,
+            detail: format!("Check failed to run: {}", e),
+            remediation: None,
+        },
+        Err(_) => CheckOutcome {
+            status: CheckStatus::TimedOut,
+            detail: format!("No answer within {}ms", timeout.as_millis()),
+            remediation: (check == CheckKind::Network).then_some(Remediation::CheckNetwork),
+        },
+    };
+    EnvironmentCheck { check, target, outcome, duration_ms: started.elapsed().as_millis() as u64 }
+}
+
+/// Probe the host, one network check per endpoint
+pub async fn run_environment_probe<H: ProbeHost>(host: Arc<H>, endpoints: &[String]) -> EnvironmentReport {
+    run_probe_with_timeout(host, endpoints, CHECK_TIMEOUT).await
+}
+
+async fn run_probe_with_timeout<H: ProbeHost>(host: Arc<H>, endpoints: &[String], timeout: Duration) -> EnvironmentReport {
+    let started = Instant::now();
+    let mut pending = Vec::new();
+
+    let h = host.clone();
+    pending.push(tokio::spawn(time_boxed(CheckKind::UsbPermissions, None, timeout, move || {
+        check_usb_permissions(h.platform(), &h.udev_rules())
+    })));
+
+    let h = host.clone();
+    pending.push(tokio::spawn(time_boxed(CheckKind::ConflictingApps, None, timeout, move || {
+        let ports: Vec<u16> = KNOWN_CONFLICTS
+            .iter()
+            .flat_map(|c| c.ports.iter().copied())
+            .filter(|port| h.port_open(*port))
+            .collect();
+        check_conflicts(h.running_processes(), &ports)
+    })));
+
+    let h = host.clone();
+    pending.push(tokio::spawn(time_boxed(CheckKind::HidApi, None, timeout, move || check_hid_api(h.hid_api()))));
+
+    let h = host.clone();
+    pending.push(tokio::spawn(time_boxed(CheckKind::DataDir, None, timeout, move || {
+        check_data_dir(h.data_dir_writable())
+    })));
+
+    for url in endpoints {
+        let h = host.clone();
+        let target = url.clone();
+        pending.push(tokio::spawn(time_boxed(CheckKind::Network, Some(url.clone()), timeout, move || {
+            let result = h.reachable(&target);
+            check_network(&target, result)
+        })));
+    }
+
+    // Everything is already running; awaiting in order keeps the report stable
+    let mut checks = Vec::with_capacity(pending.len());
+    for handle in pending {
+        if let Ok(check) = handle.await {
+            checks.push(check);
+        }
+    }
+
+    let issues_found = checks.iter().any(|c| c.outcome.status.is_issue());
+    EnvironmentReport {
+        checks,
+        issues_found,
+        duration_ms: started.elapsed().as_millis() as u64,
+        probed_at: keepkey_db::Database::current_timestamp(),
+    }
+}
+
+/// The real host
+pub struct SystemProbeHost {
+    pub data_dir: PathBuf,
+}
+
+impl SystemProbeHost {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self { data_dir }
+    }
+}
+
+impl ProbeHost for SystemProbeHost {
+    fn platform(&self) -> Platform {
+        Platform::current()
+    }
+
+    fn udev_rules(&self) -> Vec<String> {
+        UDEV_RULE_DIRS
+            .iter()
+            .filter_map(|dir| std::fs::read_dir(dir).ok())
+            .flat_map(|entries| entries.filter_map(|e| e.ok()))
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "rules"))
+            .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+            .collect()
+    }
+
+    fn running_processes(&self) -> Result<Vec<String>, String> {
+        if cfg!(target_os = "linux") {
+            let entries = std::fs::read_dir("/proc").map_err(|e| e.to_string())?;
+            return Ok(entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| std::fs::read_to_string(e.path().join("comm")).ok())
+                .map(|name| name.trim().to_string())
+                .collect());
+        }
+
+        let output = if cfg!(target_os = "windows") {
+            std::process::Command::new("tasklist").args(["/fo", "csv", "/nh"]).output()
+        } else {
+            std::process::Command::new("ps").args(["-A", "-o", "comm="]).output()
+        }
+        .map_err(|e| e.to_string())?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout
+            .lines()
+            // tasklist CSV: "image.exe","pid",...
+            .filter_map(|line| line.split(',').next())
+            .map(|name| name.trim_matches('"').to_string())
+            .filter(|name| !name.is_empty())
+            .collect())
+    }
+
+    fn port_open(&self, port: u16) -> bool {
+        std::net::TcpStream::connect_timeout(&std::net::SocketAddr::from(([127, 0, 0, 1], port)), PORT_TIMEOUT).is_ok()
+    }
+
+    fn hid_api(&self) -> Result<usize, String> {
+        keepkey_rust::transport::probe_hid_api().map_err(|e| e.to_string())
+    }
+
+    fn data_dir_writable(&self) -> Result<(), String> {
+        std::fs::create_dir_all(&self.data_dir)
+            .map_err(|e| format!("Cannot create {}: {}", self.data_dir.display(), e))?;
+        let probe = self.data_dir.join(format!(".write-probe-{}", std::process::id()));
+        std::fs::write(&probe, b"probe").map_err(|e| format!("Cannot write to {}: {}", self.data_dir.display(), e))?;
+        std::fs::remove_file(&probe).map_err(|e| format!("Cannot remove {}: {}", probe.display(), e))
+    }
+
+    fn reachable(&self, url: &str) -> Result<(), String> {
+        use std::net::ToSocketAddrs;
+        let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid URL: {}", e))?;
+        let host = parsed.host_str().ok_or("URL has no host")?;
+        let port = parsed.port_or_known_default().ok_or("URL has no port")?;
+        let addr = (host, port)
+            .to_socket_addrs()
+            .map_err(|e| format!("DNS lookup failed: {}", e))?
+            .next()
+            .ok_or("DNS lookup returned no addresses")?;
+        std::net::TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Host that answers every check from fields, optionally stalling one
+    struct MockHost {
+        platform: Platform,
+        rules: Vec<String>,
+        processes: Result<Vec<String>, String>,
+        open_ports: Vec<u16>,
+        hid: Result<usize, String>,
+        data_dir: Result<(), String>,
+        unreachable: Vec<&'static str>,
+        stall: Option<CheckKind>,
+    }
+
+    impl Default for MockHost {
+        fn default() -> Self {
+            Self {
+                platform: Platform::Linux,
+                rules: vec![r#"SUBSYSTEM=="usb", ATTR{idVendor}=="2b24", MODE="0666""#.to_string()],
+                processes: Ok(vec!["systemd".to_string(), "keepkey-vault".to_string()]),
+                open_ports: Vec::new(),
+                hid: Ok(3),
+                data_dir: Ok(()),
+                unreachable: Vec::new(),
+                stall: None,
+            }
+        }
+    }
+
+    impl MockHost {
+        fn stall_if(&self, kind: CheckKind) {
+            if self.stall == Some(kind) {
+                std::thread::sleep(Duration::from_millis(800));
+            }
+        }
+    }
+
+    impl ProbeHost for MockHost {
+        fn platform(&self) -> Platform {
+            self.platform
+        }
+        fn udev_rules(&self) -> Vec<String> {
+            self.rules.clone()
+        }
+        fn running_processes(&self) -> Result<Vec<String>, String> {
+            self.stall_if(CheckKind::ConflictingApps);
+            self.processes.clone()
+        }
+        fn port_open(&self, port: u16) -> bool {
+            self.open_ports.contains(&port)
+        }
+        fn hid_api(&self) -> Result<usize, String> {
+            self.hid.clone()
+        }
+        fn data_dir_writable(&self) -> Result<(), String> {
+            self.data_dir.clone()
+        }
+        fn reachable(&self, url: &str) -> Result<(), String> {
+            self.stall_if(CheckKind::Network);
+            if self.unreachable.contains(&url) {
+                Err("connection refused".to_string())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn endpoints() -> Vec<String> {
+        vec!["https://mempool.space".to_string(), "https://eth.llamarpc.com".to_string()]
+    }
+
+    async fn probe(host: MockHost) -> EnvironmentReport {
+        run_environment_probe(Arc::new(host), &endpoints()).await
+    }
+
+    fn find(report: &EnvironmentReport, kind: CheckKind) -> &EnvironmentCheck {
+        report.checks.iter().find(|c| c.check == kind).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_healthy_host_passes() {
+        let report = probe(MockHost::default()).await;
+        assert_eq!(report.checks.len(), 6);
+        assert!(!report.issues_found);
+        assert!(report.checks.iter().all(|c| c.outcome.status == CheckStatus::Pass), "{:?}", report.checks);
+
+        let report = probe(MockHost { platform: Platform::Windows, rules: Vec::new(), ..Default::default() }).await;
+        assert_eq!(find(&report, CheckKind::UsbPermissions).outcome.status, CheckStatus::Skipped);
+        assert!(!report.issues_found);
+    }
+
+    #[tokio::test]
+    async fn test_missing_or_incomplete_udev_rules() {
+        let report = probe(MockHost { rules: vec!["# 2b24 commented out".to_string()], ..Default::default() }).await;
+        let usb = find(&report, CheckKind::UsbPermissions);
+        assert_eq!(usb.outcome.status, CheckStatus::Fail);
+        assert_eq!(usb.outcome.remediation, Some(Remediation::InstallUdevRules));
+        assert!(report.issues_found);
+
+        let rules = vec![r#"SUBSYSTEM=="usb", ATTR{idVendor}=="2B24""#.to_string()];
+        let outcome = check_usb_permissions(Platform::Linux, &rules);
+        assert_eq!(outcome.status, CheckStatus::Warn);
+        assert!(!outcome.status.is_issue());
+    }
+
+    #[tokio::test]
+    async fn test_conflicting_software_by_process_and_port() {
+        let processes = Ok(vec!["C:\\Program Files\\KeepKey\\KeepKey Desktop.exe".to_string()]);
+        let report = probe(MockHost { processes, ..Default::default() }).await;
+        let conflicts = find(&report, CheckKind::ConflictingApps);
+        assert_eq!(conflicts.outcome.status, CheckStatus::Fail);
+        assert_eq!(conflicts.outcome.remediation, Some(Remediation::QuitKeepKeyDesktop));
+
+        let report = probe(MockHost { open_ports: vec![21325], ..Default::default() }).await;
+        let conflicts = find(&report, CheckKind::ConflictingApps);
+        assert_eq!(conflicts.outcome.remediation, Some(Remediation::StopTrezorBridge));
+        assert!(conflicts.outcome.detail.contains("Trezor Bridge"));
+
+        let outcome = check_conflicts(Ok(vec!["keepkeyd".to_string(), "trezord".to_string()]), &[]);
+        assert_eq!(outcome.remediation, Some(Remediation::StopKeepKeyBridge));
+        assert!(outcome.detail.contains("KeepKey Bridge, Trezor Bridge"));
+
+        let outcome = check_conflicts(Err("ps not found".to_string()), &[]);
+        assert_eq!(outcome.status, CheckStatus::Warn);
+    }
+
+    #[tokio::test]
+    async fn test_hid_data_dir_and_network_failures() {
+        let report = probe(MockHost {
+            hid: Err("Failed to initialize HID API: access denied".to_string()),
+            data_dir: Err("Cannot write to /data: read-only file system".to_string()),
+            unreachable: vec!["https://eth.llamarpc.com"],
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(find(&report, CheckKind::HidApi).outcome.remediation, Some(Remediation::AllowHidAccess));
+        assert_eq!(find(&report, CheckKind::DataDir).outcome.remediation, Some(Remediation::FixDataDirPermissions));
+
+        let network: Vec<_> = report.checks.iter().filter(|c| c.check == CheckKind::Network).collect();
+        assert_eq!(network[0].outcome.status, CheckStatus::Pass);
+        assert_eq!(network[1].target.as_deref(), Some("https://eth.llamarpc.com"));
+        assert_eq!(network[1].outcome.remediation, Some(Remediation::CheckNetwork));
+        assert_eq!(report.issues().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_stuck_checks_are_time_boxed() {
+        let timeout = Duration::from_millis(100);
+        for stall in [CheckKind::ConflictingApps, CheckKind::Network] {
+            let host = Arc::new(MockHost { stall: Some(stall), ..Default::default() });
+            let started = Instant::now();
+            let report = run_probe_with_timeout(host, &endpoints(), timeout).await;
+            assert!(started.elapsed() < Duration::from_secs(1), "{:?} held up the probe", stall);
+
+            let stuck = find(&report, stall);
+            assert_eq!(stuck.outcome.status, CheckStatus::TimedOut);
+            assert!(report.issues_found);
+            // The other checks still report
+            assert_eq!(find(&report, CheckKind::HidApi).outcome.status, CheckStatus::Pass);
+        }
+        assert!(CHECK_TIMEOUT < PROBE_BUDGET);
+    }
+}
@@ -0,0 +1,219 @@
+// erc20.rs - Custom token contract checks and ERC-20 transfer calldata
+//
+// A token added by contract address is checked against the chain before it
+// is stored: the address must hold code, and name(), symbol() and decimals()
+// are read and compared with what the user typed. Differences come back as
+// warnings rather than errors since plenty of tokens have odd metadata, but
+// the on-chain decimals always win because a wrong value would scale every
+// amount sent. Chain access goes through the same JSON-RPC closure as
+// token_approvals.rs.
+
+use std::future::Future;
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::token_approvals::{address_word, decode_abi_string, eth_call, normalize_address, parse_word, word_to_u128};
+use crate::units::parse_units;
+
+const NAME_SELECTOR: &str = "06fdde03";
+const SYMBOL_SELECTOR: &str = "95d89b41";
+const DECIMALS_SELECTOR: &str = "313ce567";
+const TRANSFER_SELECTOR: &str = "a9059cbb";
+
+/// Largest decimals value treated as genuine
+const MAX_DECIMALS: u32 = 36;
+
+/// name(), symbol() and decimals() as reported by the contract; None when the
+/// call reverted or returned something undecodable
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct OnChainMetadata {
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u32>,
+}
+
+/// A field where the user's input and the contract disagree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct MetadataMismatch {
+    /// "name", "symbol" or "decimals"
+    pub field: String,
+    pub entered: String,
+    pub on_chain: String,
+}
+
+/// Outcome of checking a token contract against user input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct TokenValidation {
+    /// Lowercase 0x address
+    pub contract_address: String,
+    pub on_chain: OnChainMetadata,
+    /// Values to store: the user's name and symbol, the contract's decimals
+    /// when it reports them
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u32,
+    pub warnings: Vec<MetadataMismatch>,
+}
+
+/// Read name(), symbol() and decimals() from a contract
+pub async fn read_metadata<R, Fut>(rpc: &R, contract: &str) -> OnChainMetadata
+where
+    R: Fn(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    let name = eth_call(rpc, contract, format!("0x{}", NAME_SELECTOR)).await.ok()
+        .and_then(|data| decode_abi_string(&data));
+    let symbol = eth_call(rpc, contract, format!("0x{}", SYMBOL_SELECTOR)).await.ok()
+        .and_then(|data| decode_abi_string(&data));
+    let decimals = eth_call(rpc, contract, format!("0x{}", DECIMALS_SELECTOR)).await.ok()
+        .and_then(|data| parse_word(&data).ok())
+        .and_then(|word| word_to_u128(&word))
+        .and_then(|d| u32::try_from(d).ok())
+        .filter(|d| *d <= MAX_DECIMALS);
+    OnChainMetadata { name, symbol, decimals }
+}
+
+/// Check a token contract before adding it.
+///
+/// Fails when the address is malformed, holds no code, or implements none of
+/// name(), symbol() and decimals(). Name and symbol are compared without
+/// regard to case or surrounding whitespace.
+pub async fn validate_token_contract<R, Fut>(
+    rpc: &R,
+    contract_address: &str,
+    symbol: &str,
+    name: &str,
+    decimals: u32,
+) -> Result<TokenValidation, String>
+where
+    R: Fn(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    let contract_address = normalize_address(contract_address)?;
+    let code = rpc("eth_getCode", json!([contract_address, "latest"])).await?;
+    if matches!(code.as_str(), None | Some("") | Some("0x") | Some("0x0")) {
+        return Err(format!("No contract is deployed at {}", contract_address));
+    }
+
+    let on_chain = read_metadata(rpc, &contract_address).await;
+    if on_chain == OnChainMetadata::default() {
+        return Err(format!("{} does not implement the ERC-20 metadata calls", contract_address));
+    }
+
+    let (symbol, name) = (symbol.trim(), name.trim());
+    let mut warnings = Vec::new();
+    let mut compare = |field: &str, entered: &str, on_chain: Option<&str>| {
+        if let Some(on_chain) = on_chain.filter(|v| !v.eq_ignore_ascii_case(entered)) {
+            warnings.push(MetadataMismatch {
+                field: field.to_string(),
+                entered: entered.to_string(),
+                on_chain: on_chain.to_string(),
+            });
+        }
+    };
+    compare("name", name, on_chain.name.as_deref());
+    compare("symbol", symbol, on_chain.symbol.as_deref());
+    compare("decimals", &decimals.to_string(), on_chain.decimals.map(|d| d.to_string()).as_deref());
+
+    Ok(TokenValidation {
+        contract_address,
+        name: name.to_string(),
+        symbol: symbol.to_string(),
+        decimals: on_chain.decimals.unwrap_or(decimals),
+        on_chain,
+        warnings,
+    })
+}
+
+/// transfer(recipient, amount) calldata, with `amount` in token units scaled
+/// by the token's stored decimals
+pub fn transfer_calldata(recipient: &str, amount: &str, decimals: u32) -> Result<String, String> {
+    let base_units = parse_units(amount, decimals)?;
+    Ok(format!("0x{}{}{:064x}", TRANSFER_SELECTOR, address_word(recipient)?, base_units))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::{ready, Ready};
+
+    const DAI: &str = "0x6B175474E89094C44Da98b954EedeAC495271d0F";
+    const RECIPIENT: &str = "0x1111111111111111111111111111111111111111";
+
+    fn abi_string(text: &str) -> String {
+        format!("0x{:064x}{:064x}{:0<64}", 32, text.len(), hex::encode(text))
+    }
+
+    /// Canned node for a contract with the given metadata; None fields revert
+    fn mock_rpc(
+        code: &'static str,
+        name: Option<&'static str>,
+        symbol: Option<&'static str>,
+        decimals: Option<u32>,
+    ) -> impl Fn(&'static str, Value) -> Ready<Result<Value, String>> {
+        move |method, params| {
+            let result = match method {
+                "eth_getCode" => Ok(json!(code)),
+                "eth_call" => {
+                    let data = params[0]["data"].as_str().unwrap().to_string();
+                    let value = match &data[2..] {
+                        NAME_SELECTOR => name.map(abi_string),
+                        SYMBOL_SELECTOR => symbol.map(abi_string),
+                        DECIMALS_SELECTOR => decimals.map(|d| format!("0x{:064x}", d)),
+                        other => panic!("unexpected call {}", other),
+                    };
+                    value.map(|v| json!(v)).ok_or_else(|| "RPC error 3: execution reverted".to_string())
+                }
+                other => Err(format!("unexpected method {}", other)),
+            };
+            ready(result)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_input_warns_and_uses_chain_decimals() {
+        let rpc = mock_rpc("0x6080", Some("Dai Stablecoin"), Some("DAI"), Some(18));
+
+        let checked = validate_token_contract(&rpc, DAI, " dai ", "Dai Stablecoin", 18).await.unwrap();
+        assert_eq!(checked.contract_address, DAI.to_lowercase());
+        assert!(checked.warnings.is_empty());
+
+        let checked = validate_token_contract(&rpc, DAI, "DIA", "My Dai", 6).await.unwrap();
+        assert_eq!(checked.warnings, vec![
+            MetadataMismatch { field: "name".to_string(), entered: "My Dai".to_string(), on_chain: "Dai Stablecoin".to_string() },
+            MetadataMismatch { field: "symbol".to_string(), entered: "DIA".to_string(), on_chain: "DAI".to_string() },
+            MetadataMismatch { field: "decimals".to_string(), entered: "6".to_string(), on_chain: "18".to_string() },
+        ]);
+        // The user's labels are kept; the contract's decimals are not negotiable
+        assert_eq!((checked.symbol.as_str(), checked.name.as_str(), checked.decimals), ("DIA", "My Dai", 18));
+    }
+
+    #[tokio::test]
+    async fn test_contract_must_exist_and_answer() {
+        let rpc = mock_rpc("0x", Some("Dai Stablecoin"), Some("DAI"), Some(18));
+        assert!(validate_token_contract(&rpc, DAI, "DAI", "Dai", 18).await.unwrap_err().contains("No contract"));
+
+        let rpc = mock_rpc("0x6080", None, None, None);
+        assert!(validate_token_contract(&rpc, DAI, "DAI", "Dai", 18).await.unwrap_err().contains("ERC-20"));
+
+        // Missing decimals() falls back to the entered value without a warning
+        let rpc = mock_rpc("0x6080", None, Some("DAI"), None);
+        let checked = validate_token_contract(&rpc, DAI, "DAI", "Dai", 18).await.unwrap();
+        assert_eq!((checked.decimals, checked.warnings.len()), (18, 0));
+
+        assert!(validate_token_contract(&rpc, "0x1234", "DAI", "Dai", 18).await.is_err());
+    }
+
+    #[test]
+    fn test_transfer_calldata_scales_by_decimals() {
+        let data = transfer_calldata(RECIPIENT, "1.5", 6).unwrap();
+        assert_eq!(
+            data,
+            format!("0xa9059cbb{:0>64}{:064x}", &RECIPIENT[2..], 1_500_000u128)
+        );
+        assert_eq!(&transfer_calldata(RECIPIENT, "1.5", 18).unwrap()[74..], format!("{:064x}", 1_500_000_000_000_000_000u128));
+        assert!(transfer_calldata(RECIPIENT, "0.0000001", 6).is_err());
+    }
+}
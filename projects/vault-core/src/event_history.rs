@@ -0,0 +1,190 @@
+// event_history.rs - Ring buffer of emitted frontend events for debugging missed events
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use serde::Serialize;
+
+/// Number of events retained when no size is configured
+pub const DEFAULT_EVENT_HISTORY_SIZE: usize = 500;
+
+/// One emitted or queued event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct EventRecord {
+    pub sequence: u64,
+    pub name: String,
+    pub timestamp_ms: u64,
+    pub payload_size: usize,
+    /// True if the event was queued because the frontend was not ready yet
+    pub queued: bool,
+    /// Sequence of the original event when this is a developer re-emit
+    pub replay_of: Option<u64>,
+    /// Only retained in developer mode
+    pub payload: Option<serde_json::Value>,
+}
+
+/// Counts for the diagnostics bundle
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct EventHistorySummary {
+    pub total_recorded: u64,
+    pub retained: usize,
+    pub capacity: usize,
+    pub by_name: BTreeMap<String, usize>,
+}
+
+/// Counts serialized bytes without allocating the JSON string
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct EventHistory {
+    capacity: usize,
+    developer_mode: bool,
+    next_sequence: u64,
+    records: VecDeque<EventRecord>,
+}
+
+impl Default for EventHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_HISTORY_SIZE)
+    }
+}
+
+impl EventHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            developer_mode: false,
+            next_sequence: 1,
+            records: VecDeque::new(),
+        }
+    }
+
+    /// Change the buffer size and payload retention; oldest records are dropped to fit
+    pub fn configure(&mut self, capacity: usize, developer_mode: bool) {
+        self.capacity = capacity.max(1);
+        self.developer_mode = developer_mode;
+        while self.records.len() > self.capacity {
+            self.records.pop_front();
+        }
+        if !developer_mode {
+            for record in self.records.iter_mut() {
+                record.payload = None;
+            }
+        }
+    }
+
+    pub fn developer_mode(&self) -> bool {
+        self.developer_mode
+    }
+
+    /// Record an event and return its sequence number.
+    ///
+    /// The payload is only cloned in developer mode; otherwise just its size is kept.
+    pub fn record(&mut self, name: &str, payload: &serde_json::Value, queued: bool, replay_of: Option<u64>) -> u64 {
+        let mut counter = ByteCounter(0);
+        let payload_size = serde_json::to_writer(&mut counter, payload).map(|_| counter.0).unwrap_or(0);
+
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(EventRecord {
+            sequence,
+            name: name.to_string(),
+            timestamp_ms: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            payload_size,
+            queued,
+            replay_of,
+            payload: self.developer_mode.then(|| payload.clone()),
+        });
+        sequence
+    }
+
+    /// Records after `since_sequence` whose name contains `name_filter`
+    pub fn since(&self, since_sequence: Option<u64>, name_filter: Option<&str>) -> Vec<EventRecord> {
+        let since = since_sequence.unwrap_or(0);
+        self.records
+            .iter()
+            .filter(|r| r.sequence > since)
+            .filter(|r| name_filter.map(|f| r.name.contains(f)).unwrap_or(true))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, sequence: u64) -> Option<&EventRecord> {
+        self.records.iter().find(|r| r.sequence == sequence)
+    }
+
+    pub fn summary(&self) -> EventHistorySummary {
+        let mut by_name = BTreeMap::new();
+        for record in &self.records {
+            *by_name.entry(record.name.clone()).or_insert(0) += 1;
+        }
+        EventHistorySummary {
+            total_recorded: self.next_sequence - 1,
+            retained: self.records.len(),
+            capacity: self.capacity,
+            by_name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_ring_buffer_drops_oldest() {
+        let mut history = EventHistory::new(3);
+        for i in 0..5 {
+            history.record("device:connected", &json!({ "i": i }), false, None);
+        }
+        let records = history.since(None, None);
+        assert_eq!(records.iter().map(|r| r.sequence).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!(history.summary().total_recorded, 5);
+        assert_eq!(history.summary().retained, 3);
+    }
+
+    #[test]
+    fn test_payload_only_kept_in_developer_mode() {
+        let mut history = EventHistory::default();
+        let payload = json!({ "device_id": "abc" });
+        let first = history.record("device:connected", &payload, true, None);
+        assert_eq!(history.get(first).unwrap().payload, None);
+        assert_eq!(history.get(first).unwrap().payload_size, payload.to_string().len());
+
+        history.configure(DEFAULT_EVENT_HISTORY_SIZE, true);
+        let second = history.record("device:connected", &payload, false, None);
+        assert_eq!(history.get(second).unwrap().payload, Some(payload));
+    }
+
+    #[test]
+    fn test_filters_by_sequence_and_name() {
+        let mut history = EventHistory::default();
+        history.record("device:connected", &json!({}), false, None);
+        history.record("status:update", &json!({}), false, None);
+        history.record("device:disconnected", &json!({}), false, None);
+
+        assert_eq!(history.since(Some(1), None).len(), 2);
+        assert_eq!(history.since(None, Some("device:")).len(), 2);
+        assert_eq!(history.summary().by_name.get("status:update"), Some(&1));
+    }
+}
@@ -0,0 +1,482 @@
+// event_router.rs - Per-window delivery of frontend events
+//
+// Each webview window signals readiness on its own and may subscribe to a set
+// of event categories (the part of the event name before ':' or the first
+// '-'), so a popped-out portfolio window does not receive PIN or device setup
+// events. An event goes straight to every ready window that wants it and is
+// queued for subscribed windows still loading; queues are per window and die
+// with it. A reloaded webview is reset to not ready, so what is emitted until
+// its new page signals again is queued rather than sent to torn-down
+// listeners. Sticky events keep their latest payload and are replayed to every
+// window that becomes ready later, so a second window still learns that a
+// device needs setup or has blocking actions.
+//
+// A window that never becomes ready (a crashed or reload-looping webview)
+// must not hold events forever, so each queue is capped with the oldest
+// events dropped first, high-frequency status events keep only the latest
+// per device, and status updates too old to mean anything are discarded when
+// the queue is flushed.
+//
+// The router only decides who gets what; emission stays with the Tauri side.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+
+/// Label of the window created at startup
+pub const MAIN_WINDOW: &str = "main";
+
+/// Subscribing to this category receives every event
+pub const ALL_CATEGORIES: &str = "*";
+
+/// Events whose latest payload is replayed to each newly ready window
+pub const STICKY_EVENTS: &[&str] = &[
+    "device:setup-required",
+    "blocking:actions_updated",
+    "database:migration-blocked",
+];
+
+/// Most events queued for one window; older ones are dropped first
+pub const MAX_QUEUED_EVENTS: usize = 500;
+
+/// Events a queue keeps only the latest of per device
+pub const COALESCED_EVENTS: &[&str] = &[
+    "status:update",
+    "device:status-changed",
+    "device:migration-progress",
+];
+
+/// Events discarded on flush once older than this many seconds
+pub const EXPIRING_EVENTS: &[(&str, u64)] = &[("status:update", 60)];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedEvent {
+    pub event_name: String,
+    pub payload: serde_json::Value,
+    pub timestamp: u64,
+}
+
+/// Category of an event name: "device:connected" -> "device",
+/// "wallet-sync-progress" -> "wallet"
+pub fn event_category(event_name: &str) -> &str {
+    let end = event_name.find(':').or_else(|| event_name.find('-')).unwrap_or(event_name.len());
+    &event_name[..end]
+}
+
+pub fn is_sticky(event_name: &str) -> bool {
+    STICKY_EVENTS.contains(&event_name)
+}
+
+/// The device an event is about, if its payload names one
+fn payload_device_id(payload: &serde_json::Value) -> Option<&str> {
+    payload.get("deviceId").or_else(|| payload.get("device_id"))?.as_str()
+}
+
+fn is_expired(event: &QueuedEvent, now: u64) -> bool {
+    EXPIRING_EVENTS.iter()
+        .find(|(name, _)| *name == event.event_name)
+        .is_some_and(|(_, ttl)| now.saturating_sub(event.timestamp) > *ttl)
+}
+
+/// Queue sizes and what the caps have discarded since startup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct EventQueueStats {
+    /// Events queued across all windows
+    pub queued: usize,
+    /// Per-window cap
+    pub capacity: usize,
+    /// Dropped as the oldest of a full queue
+    pub dropped_oldest: usize,
+    /// Replaced by a newer event for the same device
+    pub coalesced: usize,
+    /// Discarded on flush as too old
+    pub expired: usize,
+}
+
+/// One window's readiness, subscription and backlog
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct WindowSubscription {
+    pub label: String,
+    pub ready: bool,
+    /// None until the window subscribes; it then receives everything
+    pub categories: Option<Vec<String>>,
+    pub queued_events: usize,
+}
+
+#[derive(Default)]
+struct WindowState {
+    ready: bool,
+    /// None receives every event
+    categories: Option<BTreeSet<String>>,
+    queue: Vec<QueuedEvent>,
+}
+
+impl WindowState {
+    fn wants(&self, event_name: &str) -> bool {
+        match &self.categories {
+            None => true,
+            Some(categories) => categories.contains(event_category(event_name)),
+        }
+    }
+
+    /// Queue an event, replacing an older one it supersedes and dropping the
+    /// oldest when full
+    fn enqueue(&mut self, event: QueuedEvent, stats: &mut EventQueueStats) {
+        if COALESCED_EVENTS.contains(&event.event_name.as_str()) {
+            let device_id = payload_device_id(&event.payload);
+            let before = self.queue.len();
+            self.queue.retain(|queued| {
+                queued.event_name != event.event_name || payload_device_id(&queued.payload) != device_id
+            });
+            stats.coalesced += before - self.queue.len();
+        }
+        if self.queue.len() >= MAX_QUEUED_EVENTS {
+            let excess = self.queue.len() + 1 - MAX_QUEUED_EVENTS;
+            self.queue.drain(..excess);
+            stats.dropped_oldest += excess;
+        }
+        self.queue.push(event);
+    }
+}
+
+/// Where an event goes right now
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Routing {
+    /// Ready windows to emit to immediately
+    pub emit_to: Vec<String>,
+    /// Windows the event was queued for
+    pub queued_for: Vec<String>,
+}
+
+/// Readiness, subscriptions and queues of every open window
+pub struct EventRouter {
+    windows: HashMap<String, WindowState>,
+    sticky: BTreeMap<String, serde_json::Value>,
+    stats: EventQueueStats,
+}
+
+impl Default for EventRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventRouter {
+    /// A router knowing only the main window, which queues until it is ready
+    pub fn new() -> Self {
+        let mut windows = HashMap::new();
+        windows.insert(MAIN_WINDOW.to_string(), WindowState::default());
+        Self { windows, sticky: BTreeMap::new(), stats: EventQueueStats::default() }
+    }
+
+    fn window(&mut self, label: &str) -> &mut WindowState {
+        self.windows.entry(label.to_string()).or_default()
+    }
+
+    /// Route an event: emit to ready windows that want it, queue for the others
+    pub fn route(&mut self, event_name: &str, payload: &serde_json::Value, timestamp: u64) -> Routing {
+        if is_sticky(event_name) {
+            self.sticky.insert(event_name.to_string(), payload.clone());
+        }
+
+        let mut routing = Routing::default();
+        for (label, window) in self.windows.iter_mut() {
+            if !window.wants(event_name) {
+                continue;
+            }
+            if window.ready {
+                routing.emit_to.push(label.clone());
+            } else {
+                let event = QueuedEvent {
+                    event_name: event_name.to_string(),
+                    payload: payload.clone(),
+                    timestamp,
+                };
+                window.enqueue(event, &mut self.stats);
+                routing.queued_for.push(label.clone());
+            }
+        }
+        routing.emit_to.sort();
+        routing.queued_for.sort();
+        routing
+    }
+
+    /// Ready windows that want an event, without queueing it for the rest
+    /// (progress updates and developer re-emits are useless later)
+    pub fn ready_targets(&self, event_name: &str) -> Vec<String> {
+        let mut targets: Vec<String> = self.windows.iter()
+            .filter(|(_, window)| window.ready && window.wants(event_name))
+            .map(|(label, _)| label.clone())
+            .collect();
+        targets.sort();
+        targets
+    }
+
+    /// Mark a window ready and return what it missed: its queue less expired
+    /// events, then the latest payload of each sticky event it wants and did
+    /// not have queued. None if the window was already ready.
+    pub fn mark_ready(&mut self, label: &str, now: u64) -> Option<Vec<(String, serde_json::Value)>> {
+        let sticky = self.sticky.clone();
+        let window = self.windows.entry(label.to_string()).or_default();
+        if window.ready {
+            return None;
+        }
+        window.ready = true;
+
+        let mut queued: Vec<QueuedEvent> = window.queue.drain(..).collect();
+        let before = queued.len();
+        queued.retain(|event| !is_expired(event, now));
+        self.stats.expired += before - queued.len();
+        let mut replay: Vec<(String, serde_json::Value)> = Vec::new();
+        for (name, payload) in sticky {
+            if window.wants(&name) && !queued.iter().any(|e| e.event_name == name) {
+                replay.push((name, payload));
+            }
+        }
+        let mut missed: Vec<(String, serde_json::Value)> =
+            queued.into_iter().map(|e| (e.event_name, e.payload)).collect();
+        missed.extend(replay);
+        Some(missed)
+    }
+
+    /// Queue sizes and discard counters for the debug panel
+    pub fn queue_stats(&self) -> EventQueueStats {
+        EventQueueStats {
+            queued: self.windows.values().map(|w| w.queue.len()).sum(),
+            capacity: MAX_QUEUED_EVENTS,
+            ..self.stats.clone()
+        }
+    }
+
+    /// A window's page is reloading: queue its events until it signals ready
+    /// again. Returns whether it had been ready.
+    pub fn reset_window(&mut self, label: &str) -> bool {
+        std::mem::take(&mut self.window(label).ready)
+    }
+
+    pub fn is_ready(&self, label: &str) -> bool {
+        self.windows.get(label).is_some_and(|w| w.ready)
+    }
+
+    /// Whether `label` receives `event_name` (unknown windows receive everything)
+    pub fn wants(&self, label: &str, event_name: &str) -> bool {
+        self.windows.get(label).map_or(true, |w| w.wants(event_name))
+    }
+
+    /// Limit a window to `categories`; empty or containing "*" means everything.
+    /// Queued events outside the new subscription are dropped.
+    pub fn subscribe(&mut self, label: &str, categories: &[String]) {
+        let categories: BTreeSet<String> = categories.iter()
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .collect();
+        let window = self.window(label);
+        window.categories = if categories.is_empty() || categories.contains(ALL_CATEGORIES) {
+            None
+        } else {
+            Some(categories)
+        };
+        let queue = std::mem::take(&mut window.queue);
+        window.queue = queue.into_iter().filter(|e| window.wants(&e.event_name)).collect();
+    }
+
+    /// Forget a closed window; returns how many queued events were dropped
+    pub fn close_window(&mut self, label: &str) -> usize {
+        self.windows.remove(label).map(|w| w.queue.len()).unwrap_or(0)
+    }
+
+    pub fn windows(&self) -> Vec<WindowSubscription> {
+        let mut windows: Vec<WindowSubscription> = self.windows.iter()
+            .map(|(label, window)| WindowSubscription {
+                label: label.clone(),
+                ready: window.ready,
+                categories: window.categories.as_ref().map(|c| c.iter().cloned().collect()),
+                queued_events: window.queue.len(),
+            })
+            .collect();
+        windows.sort_by(|a, b| a.label.cmp(&b.label));
+        windows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn names(events: &[(String, serde_json::Value)]) -> Vec<&str> {
+        events.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_event_category() {
+        assert_eq!(event_category("device:connected"), "device");
+        assert_eq!(event_category("blocking:actions_updated"), "blocking");
+        assert_eq!(event_category("wallet-sync-progress"), "wallet");
+        assert_eq!(event_category("portfolio:sync-requested"), "portfolio");
+        assert_eq!(event_category("ready"), "ready");
+    }
+
+    #[test]
+    fn test_two_windows_route_by_subscription() {
+        let mut router = EventRouter::new();
+        router.subscribe("portfolio", &["portfolio".to_string(), "wallet".to_string()]);
+
+        // Neither window is ready: each queues only what it wants
+        let routing = router.route("device:button-request", &json!({ "code": "pin" }), 1);
+        assert_eq!(routing.queued_for, vec!["main"]);
+        let routing = router.route("portfolio:sync-requested", &json!({}), 2);
+        assert_eq!(routing.queued_for, vec!["main", "portfolio"]);
+
+        let main_missed = router.mark_ready(MAIN_WINDOW, 10).unwrap();
+        assert_eq!(names(&main_missed), vec!["device:button-request", "portfolio:sync-requested"]);
+        assert!(router.mark_ready(MAIN_WINDOW, 10).is_none());
+
+        // Main is ready, the portfolio window still loading: no device events leak into its queue
+        let routing = router.route("device:button-request", &json!({ "code": "pin" }), 3);
+        assert_eq!(routing, Routing { emit_to: vec!["main".to_string()], queued_for: vec![] });
+        let portfolio_missed = router.mark_ready("portfolio", 10).unwrap();
+        assert_eq!(names(&portfolio_missed), vec!["portfolio:sync-requested"]);
+
+        let routing = router.route("wallet-sync-progress", &json!({}), 4);
+        assert_eq!(routing.emit_to, vec!["main", "portfolio"]);
+        assert_eq!(router.ready_targets("device:connected"), vec!["main"]);
+        assert!(!router.wants("portfolio", "device:connected"));
+    }
+
+    #[test]
+    fn test_sticky_events_replay_per_window() {
+        let mut router = EventRouter::new();
+        router.mark_ready(MAIN_WINDOW, 10).unwrap();
+        router.route("blocking:actions_updated", &json!(1), 1);
+        router.route("blocking:actions_updated", &json!(2), 2);
+        router.route("device:setup-required", &json!({ "device_id": "kk1" }), 3);
+
+        // A window opened later gets the latest sticky payloads it subscribes to
+        router.subscribe("second", &["blocking".to_string()]);
+        let missed = router.mark_ready("second", 10).unwrap();
+        assert_eq!(missed, vec![("blocking:actions_updated".to_string(), json!(2))]);
+
+        // A queued copy is not replayed twice
+        router.route("device:setup-required", &json!({ "device_id": "kk2" }), 4);
+        let missed = router.mark_ready("third", 10).unwrap();
+        assert_eq!(names(&missed), vec!["blocking:actions_updated", "device:setup-required"]);
+    }
+
+    #[test]
+    fn test_closing_window_drops_its_queue_only() {
+        let mut router = EventRouter::new();
+        router.subscribe("portfolio", &["portfolio".to_string()]);
+        router.route("portfolio:sync-requested", &json!({}), 1);
+        router.route("device:connected", &json!({}), 2);
+
+        assert_eq!(router.close_window("portfolio"), 1);
+        assert_eq!(router.close_window("portfolio"), 0);
+        assert_eq!(router.windows().len(), 1);
+
+        let missed = router.mark_ready(MAIN_WINDOW, 10).unwrap();
+        assert_eq!(names(&missed), vec!["portfolio:sync-requested", "device:connected"]);
+    }
+
+    #[test]
+    fn test_resubscribe_prunes_queue() {
+        let mut router = EventRouter::new();
+        router.route("device:connected", &json!({}), 1);
+        router.route("portfolio:sync-requested", &json!({}), 2);
+        router.subscribe(MAIN_WINDOW, &["portfolio".to_string()]);
+        assert_eq!(router.windows()[0].queued_events, 1);
+
+        router.subscribe(MAIN_WINDOW, &[ALL_CATEGORIES.to_string()]);
+        assert_eq!(router.windows()[0].categories, None);
+        assert!(router.wants(MAIN_WINDOW, "device:connected"));
+    }
+
+    #[test]
+    fn test_events_queued_at_startup_are_delivered_after_frontend_ready() {
+        let mut router = EventRouter::new();
+        // The USB watcher reports devices before the webview has loaded
+        let routing = router.route("device:connected", &json!({ "deviceId": "kk1" }), 1);
+        assert!(routing.emit_to.is_empty());
+        router.route("device:connected", &json!({ "deviceId": "kk2" }), 2);
+        assert!(!router.is_ready(MAIN_WINDOW));
+
+        let missed = router.mark_ready(MAIN_WINDOW, 10).unwrap();
+        assert_eq!(missed, vec![
+            ("device:connected".to_string(), json!({ "deviceId": "kk1" })),
+            ("device:connected".to_string(), json!({ "deviceId": "kk2" })),
+        ]);
+        assert_eq!(router.windows()[0].queued_events, 0);
+
+        // Later events go straight out, and a repeated ready signal replays nothing
+        let routing = router.route("device:connected", &json!({ "deviceId": "kk3" }), 3);
+        assert_eq!(routing.emit_to, vec!["main"]);
+        assert!(router.mark_ready(MAIN_WINDOW, 10).is_none());
+    }
+
+    #[test]
+    fn test_queue_stays_bounded_when_the_window_never_gets_ready() {
+        let mut router = EventRouter::new();
+        for i in 0..10_000u64 {
+            router.route("portfolio:sync-requested", &json!({ "n": i }), i);
+        }
+        let stats = router.queue_stats();
+        assert_eq!((stats.queued, stats.dropped_oldest), (MAX_QUEUED_EVENTS, 10_000 - MAX_QUEUED_EVENTS));
+
+        // The newest events survive, in order
+        let missed = router.mark_ready(MAIN_WINDOW, 10_000).unwrap();
+        assert_eq!(missed.len(), MAX_QUEUED_EVENTS);
+        assert_eq!(missed[0].1, json!({ "n": 10_000 - MAX_QUEUED_EVENTS as u64 }));
+        assert_eq!(missed.last().unwrap().1, json!({ "n": 9_999 }));
+    }
+
+    #[test]
+    fn test_status_events_coalesce_per_device_and_expire() {
+        let mut router = EventRouter::new();
+        for i in 0..10_000u64 {
+            let device_id = if i % 2 == 0 { "kk1" } else { "kk2" };
+            router.route("device:status-changed", &json!({ "device_id": device_id, "n": i }), i);
+        }
+        router.route("device:connected", &json!({ "deviceId": "kk1" }), 10_000);
+        router.route("status:update", &json!({ "deviceId": "kk1", "status": "old" }), 10_000);
+        router.route("status:update", &json!({ "deviceId": "kk2", "status": "fresh" }), 10_100);
+        let stats = router.queue_stats();
+        assert_eq!((stats.queued, stats.coalesced, stats.dropped_oldest), (5, 9_998, 0));
+
+        let missed = router.mark_ready(MAIN_WINDOW, 10_120).unwrap();
+        assert_eq!(missed, vec![
+            ("device:status-changed".to_string(), json!({ "device_id": "kk1", "n": 9_998 })),
+            ("device:status-changed".to_string(), json!({ "device_id": "kk2", "n": 9_999 })),
+            ("device:connected".to_string(), json!({ "deviceId": "kk1" })),
+            ("status:update".to_string(), json!({ "deviceId": "kk2", "status": "fresh" })),
+        ]);
+        assert_eq!(router.queue_stats(), EventQueueStats {
+            queued: 0,
+            capacity: MAX_QUEUED_EVENTS,
+            dropped_oldest: 0,
+            coalesced: 9_998,
+            expired: 1,
+        });
+    }
+
+    #[test]
+    fn test_reloaded_window_queues_until_ready_again() {
+        let mut router = EventRouter::new();
+        router.mark_ready(MAIN_WINDOW, 1).unwrap();
+        router.route("blocking:actions_updated", &json!(1), 1);
+
+        // Cmd+R: the page is torn down before the new one signals ready
+        assert!(router.reset_window(MAIN_WINDOW));
+        assert!(!router.reset_window(MAIN_WINDOW));
+        let routing = router.route("device:connected", &json!({ "deviceId": "kk1" }), 2);
+        assert_eq!(routing.queued_for, vec!["main"]);
+        assert!(router.ready_targets("device:connected").is_empty());
+
+        let missed = router.mark_ready(MAIN_WINDOW, 3).unwrap();
+        assert_eq!(missed, vec![
+            ("device:connected".to_string(), json!({ "deviceId": "kk1" })),
+            ("blocking:actions_updated".to_string(), json!(1)),
+        ]);
+        assert_eq!(router.route("device:connected", &json!({}), 4).emit_to, vec!["main"]);
+    }
+}
@@ -0,0 +1,81 @@
+// fault_log.rs - Device crash record retrieval and storage
+
+use serde::Serialize;
+use keepkey_db::Database;
+use keepkey_rust::fault_log::{read_fault_record, FaultRecord};
+use keepkey_rust::features::capabilities::{Capability, FirmwareCapabilities};
+use crate::queue::{get_or_create_device_queue, DeviceQueueManager};
+
+/// Outcome of checking a device for a stored fault record
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum FaultLogStatus {
+    /// Firmware cannot report faults; not an error
+    NotSupported { reason: String },
+    /// No fault recorded since the sector was last cleared
+    Clean,
+    /// A fault record was found; `is_new` is false if it was already stored
+    Fault { summary: String, record: FaultRecord, is_new: bool },
+}
+
+/// Check a device for a fault record and store any record found
+pub async fn check_device_fault_log(
+    db: &Database,
+    device_id: &str,
+    queue_manager: &DeviceQueueManager,
+) -> Result<FaultLogStatus, String> {
+    let features = crate::features::get_device_features(device_id, queue_manager).await?;
+    let capabilities = FirmwareCapabilities::from_features(&features);
+    if let Some(reason) = capabilities.unsupported_reason(Capability::FaultLog) {
+        return Ok(FaultLogStatus::NotSupported { reason });
+    }
+
+    let queue = get_or_create_device_queue(device_id, queue_manager).await?;
+    let record = match read_fault_record(&queue).await.map_err(|e| e.to_string())? {
+        Some(record) => record,
+        None => return Ok(FaultLogStatus::Clean),
+    };
+
+    let summary = record.summary();
+    let fault_type = serde_json::to_value(record.fault_type)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_else(|| "unknown".to_string());
+    let record_json = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+
+    let is_new = db
+        .record_device_fault(device_id, &fault_type, &summary, &record_json)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    Ok(FaultLogStatus::Fault { summary, record, is_new })
+}
+
+/// Recent fault records for the diagnostics bundle.
+///
+/// Each stored record is re-parsed into `FaultRecord` so only its known
+/// fields (registers, status words, task name) can ever be exported.
+pub async fn recent_fault_reports(
+    db: &Database,
+    device_id: Option<&str>,
+    limit: usize,
+) -> Result<Vec<serde_json::Value>, String> {
+    let logs = db
+        .get_device_fault_logs(device_id, limit)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    Ok(logs
+        .into_iter()
+        .filter_map(|log| {
+            let record: FaultRecord = serde_json::from_str(&log.record_json).ok()?;
+            Some(serde_json::json!({
+                "device_id": log.device_id,
+                "recorded_at": log.recorded_at,
+                "summary": log.summary,
+                "record": record,
+            }))
+        })
+        .collect())
+}
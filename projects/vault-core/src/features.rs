@@ -0,0 +1,212 @@
+// features.rs - Device feature retrieval and conversion shared by the GUI and the CLI
+
+use keepkey_db::Database;
+use keepkey_rust::features::DeviceFeatures;
+use crate::authenticity::{assess_features, authenticity_registry};
+use crate::firmware_catalog::ImageKind;
+use crate::queue::{get_or_create_device_queue, DeviceQueueManager};
+use crate::request_queue::request_queue;
+
+/// Get features for a device through its queue, falling back to OOB bootloader detection
+pub async fn get_device_features(
+    device_id: &str,
+    queue_manager: &DeviceQueueManager,
+) -> Result<DeviceFeatures, String> {
+    request_queue()
+        .run(device_id, "get_features", || read_device_features(device_id, queue_manager))
+        .await
+}
+
+async fn read_device_features(
+    device_id: &str,
+    queue_manager: &DeviceQueueManager,
+) -> Result<DeviceFeatures, String> {
+    let started = std::time::Instant::now();
+    let queue_handle = get_or_create_device_queue(device_id, queue_manager).await?;
+
+    match queue_handle.get_features().await {
+        Ok(features) => {
+            crate::warmup::queue_warmup().record_operation(device_id, started.elapsed(), std::time::Instant::now());
+            let features = convert_features_to_device_features(features);
+            authenticity_registry().record(device_id, assess_features(&features));
+            Ok(features)
+        }
+        Err(e) => {
+            let registry = crate::observer::observer_registry();
+            if registry.note_device_error(device_id, &e, crate::observer::unix_now()) {
+                log::warn!("🔒 Device {} is held by another application, switching to observer mode", device_id);
+                return Err(registry.check_managed(device_id).unwrap_err().to_json_string());
+            }
+
+            // Check if this might be an OOB bootloader communication issue
+            let error_str = e.to_string();
+            if error_str.contains("HID write failed") || error_str.contains("Device is disconnected") {
+                log::info!("🔄 Queue-based features failed for {}: {}, attempting OOB bootloader detection", device_id, error_str);
+                let features = try_oob_bootloader_detection(device_id).await?;
+                authenticity_registry().record(device_id, assess_features(&features));
+                Ok(features)
+            } else {
+                Err(format!("Failed to get device features for {}: {}", device_id, e))
+            }
+        }
+    }
+}
+
+/// Re-read features after a state change (lock, wipe, PIN change) and store
+/// them in the registry so cached views reflect the device's new state
+pub async fn refresh_stored_features(
+    db: &Database,
+    device_id: &str,
+    queue_manager: &DeviceQueueManager,
+) -> Result<DeviceFeatures, String> {
+    let features = get_device_features(device_id, queue_manager).await?;
+    let features_json = serde_json::to_string(&features).map_err(|e| e.to_string())?;
+    db.update_device_features(device_id, &features_json)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+    Ok(features)
+}
+
+/// Read features straight off the USB/HID transport when the queue cannot
+///
+/// Legacy bootloaders do not speak the queue's protocol, so this asks the
+/// device directly. Bootloader mode is only reported when the device answers
+/// as one; a device that does not answer meaningfully is a communication
+/// problem and comes back as an error.
+pub async fn try_oob_bootloader_detection(device_id: &str) -> Result<DeviceFeatures, String> {
+    log::info!("🔧 Attempting OOB bootloader detection for device {}", device_id);
+    
+    // Get list of connected devices to find the physical device
+    let devices = keepkey_rust::features::list_connected_devices();
+    
+    // Find device by exact ID match
+    let target_device = devices.iter()
+        .find(|d| d.unique_id == device_id)
+        .ok_or_else(|| format!("Device {} not found in connected devices", device_id))?;
+    
+    let result = tokio::task::spawn_blocking({
+        let device = target_device.clone();
+        move || -> Result<DeviceFeatures, String> {
+            keepkey_rust::features::get_device_features_with_fallback(&device)
+                .map_err(|e| e.to_string())
+        }
+    }).await;
+    
+    match result {
+        Ok(Ok(mut features)) => {
+            fill_bootloader_version(&mut features);
+            log::info!("✅ Direct transport read features for device {}", device_id);
+            log::info!("   - bootloader_mode: {}", features.bootloader_mode);
+            log::info!("   - version: {}", features.version);
+            log::info!("   - initialized: {}", features.initialized);
+            Ok(features)
+        }
+        Ok(Err(e)) => {
+            let error_msg = format!("Communication problem with device {}: {}", device_id, e);
+            log::error!("❌ {}", error_msg);
+            Err(error_msg)
+        }
+        Err(e) => {
+            let error_msg = format!("Task execution error for {}: {}", device_id, e);
+            log::error!("❌ {}", error_msg);
+            Err(error_msg)
+        }
+    }
+}
+
+/// Fill in the bootloader version from what the device actually reported: in
+/// bootloader mode its version is the bootloader's own, otherwise the hash is
+/// looked up in releases.json. An unknown hash leaves the version unknown.
+fn fill_bootloader_version(features: &mut DeviceFeatures) {
+    if features.bootloader_version.is_some() {
+        return;
+    }
+    features.bootloader_version = if features.bootloader_mode {
+        Some(features.version.clone())
+    } else {
+        features.bootloader_hash.as_deref().and_then(bootloader_version_from_hash)
+    };
+}
+
+/// Look up bootloader version from hash in the bundled releases.json
+fn bootloader_version_from_hash(hash: &str) -> Option<String> {
+    let version = release_version_from_hash(ImageKind::Bootloader, hash);
+    if version.is_none() {
+        log::warn!("🔍 No bootloader version found for hash {}", hash);
+    }
+    version
+}
+
+/// Look up the released firmware version with this hash; None for builds
+/// that are not in releases.json
+pub fn firmware_version_from_hash(hash: &str) -> Option<String> {
+    release_version_from_hash(ImageKind::Firmware, hash)
+}
+
+fn release_version_from_hash(kind: ImageKind, hash: &str) -> Option<String> {
+    let releases = match crate::firmware_catalog::bundled_releases() {
+        Ok(releases) => releases,
+        Err(e) => {
+            log::error!("{}", e);
+            return None;
+        }
+    };
+    let version = releases.version_for_hash(kind, hash)?;
+    log::info!("🔍 Found {} version {} for hash {}", kind.key(), version, hash);
+    Some(version)
+}
+
+/// Convert raw Features to DeviceFeatures
+pub fn convert_features_to_device_features(features: keepkey_rust::messages::Features) -> DeviceFeatures {
+    // Log the raw features we're getting from the device
+    log::info!("🔍 Raw device features received:");
+    log::info!("   - firmware version: {}.{}.{}", 
+        features.major_version.unwrap_or(0),
+        features.minor_version.unwrap_or(0), 
+        features.patch_version.unwrap_or(0)
+    );
+    log::info!("   - bootloader_mode: {:?}", features.bootloader_mode);
+    log::info!("   - bootloader_hash (raw): {:?}", features.bootloader_hash);
+    log::info!("   - firmware_hash (raw): {:?}", features.firmware_hash);
+    
+    // First create the basic device features
+    let mut device_features = DeviceFeatures {
+        vendor: Some(features.vendor.unwrap_or_default()),
+        label: Some(features.label.unwrap_or_default()),
+        model: Some(features.model.unwrap_or_default()),
+        firmware_variant: features.firmware_variant.clone(),
+        device_id: Some(features.device_id.unwrap_or_default()),
+        language: Some(features.language.unwrap_or_default()),
+        bootloader_mode: features.bootloader_mode.unwrap_or(false),
+        version: format!("{}.{}.{}", 
+            features.major_version.unwrap_or(0),
+            features.minor_version.unwrap_or(0), 
+            features.patch_version.unwrap_or(0)
+        ),
+        firmware_hash: features.firmware_hash.clone().map(hex::encode),
+        bootloader_hash: features.bootloader_hash.clone().map(hex::encode),
+        bootloader_version: None, // Will be populated below
+        initialized: features.initialized.unwrap_or(false),
+        imported: features.imported,
+        no_backup: features.no_backup.unwrap_or(false),
+        pin_protection: features.pin_protection.unwrap_or(false),
+        pin_cached: features.pin_cached.unwrap_or(false),
+        passphrase_protection: features.passphrase_protection.unwrap_or(false),
+        passphrase_cached: features.passphrase_cached.unwrap_or(false),
+        wipe_code_protection: features.wipe_code_protection.unwrap_or(false),
+        auto_lock_delay_ms: features.auto_lock_delay_ms.map(|x| x as u64),
+        policies: features.policies.into_iter()
+            .map(|p| p.policy_name().to_string())
+            .collect(),
+    };
+
+    // Log what we've converted so far
+    log::info!("🔍 Converted device features (before bootloader version):");
+    log::info!("   - firmware_hash (hex): {:?}", device_features.firmware_hash);
+    log::info!("   - bootloader_hash (hex): {:?}", device_features.bootloader_hash);
+
+    fill_bootloader_version(&mut device_features);
+    log::info!("🔍 Final bootloader version: {:?}", device_features.bootloader_version);
+    
+    device_features
+} 
\ No newline at end of file
@@ -0,0 +1,348 @@
+// fees.rs - Bitcoin fee suggestions from the mempool fee histogram
+//
+// mempool.space's /api/mempool returns `fee_histogram`: [sat/vB, vbytes] pairs
+// sorted from the highest fee rate down. Walking it from the top tells how
+// many vbytes are queued ahead of a given rate, and so how many blocks a
+// transaction paying that rate has to wait. Endpoints that only serve the
+// three-tier /api/v1/fees/recommended data get the tiers mapped onto targets.
+
+use serde::{Deserialize, Serialize};
+
+/// Virtual size of one block
+pub const BLOCK_VSIZE: u64 = 1_000_000;
+/// Lowest rate nodes relay by default
+pub const MIN_RELAY_FEE_RATE: u64 = 1;
+/// Lowest rates ever suggested for each tier, so an endpoint answering
+/// 0 sat/vB cannot produce a fee the network would not relay
+pub const FEE_TIER_FLOORS: FeeTiers = FeeTiers { fastest: 2, fast: 1, average: 1 };
+/// Average block interval used for time estimates
+const MINUTES_PER_BLOCK: u64 = 10;
+
+/// Confirmation targets offered to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub enum FeeTarget {
+    #[serde(rename = "next_block")]
+    NextBlock,
+    #[serde(rename = "30_min")]
+    HalfHour,
+    #[serde(rename = "1_hour")]
+    Hour,
+    #[serde(rename = "6_hours")]
+    SixHours,
+}
+
+impl FeeTarget {
+    pub const ALL: [FeeTarget; 4] = [FeeTarget::NextBlock, FeeTarget::HalfHour, FeeTarget::Hour, FeeTarget::SixHours];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeeTarget::NextBlock => "next_block",
+            FeeTarget::HalfHour => "30_min",
+            FeeTarget::Hour => "1_hour",
+            FeeTarget::SixHours => "6_hours",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|t| t.as_str() == name)
+            .ok_or_else(|| format!("Unknown fee target '{}', expected next_block, 30_min, 1_hour or 6_hours", name))
+    }
+
+    /// Blocks within which the transaction should confirm
+    pub fn blocks(&self) -> u64 {
+        match self {
+            FeeTarget::NextBlock => 1,
+            FeeTarget::HalfHour => 3,
+            FeeTarget::Hour => 6,
+            FeeTarget::SixHours => 36,
+        }
+    }
+}
+
+/// Mempool contents as (sat/vB, vbytes) buckets, highest rate first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeHistogram(pub Vec<(f64, u64)>);
+
+/// The simple fastest/fast/average tiers (sat/vB), as stored in fee_rate_cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeTiers {
+    pub fastest: u64,
+    pub fast: u64,
+    pub average: u64,
+}
+
+/// What suggestions were computed from
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeeSource {
+    Histogram(FeeHistogram),
+    Tiers(FeeTiers),
+}
+
+/// Suggested fee for one confirmation target
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct FeeSuggestion {
+    pub target: FeeTarget,
+    pub sat_per_vb: u64,
+    pub total_fee_sats: u64,
+    pub total_fee_fiat: Option<String>,
+    pub expected_minutes: u64,
+    /// Vbytes queued at or above this rate; None when only tiers are known
+    pub mempool_depth_vbytes: Option<u64>,
+}
+
+/// How the fee rate for a transaction was chosen
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FeeChoice {
+    Explicit { sat_per_vb: u64 },
+    Target { target: FeeTarget, sat_per_vb: u64 },
+}
+
+impl FeeChoice {
+    pub fn sat_per_vb(&self) -> u64 {
+        match self {
+            FeeChoice::Explicit { sat_per_vb } | FeeChoice::Target { sat_per_vb, .. } => *sat_per_vb,
+        }
+    }
+}
+
+impl FeeHistogram {
+    /// Parse the `fee_histogram` field of an /api/mempool response
+    pub fn from_mempool_json(json: &serde_json::Value) -> Result<Self, String> {
+        let buckets = json
+            .get("fee_histogram")
+            .and_then(|h| h.as_array())
+            .ok_or("Response has no fee_histogram")?;
+        let mut histogram = buckets
+            .iter()
+            .map(|bucket| {
+                let rate = bucket.get(0).and_then(|r| r.as_f64());
+                let vsize = bucket.get(1).and_then(|v| v.as_u64());
+                rate.zip(vsize).ok_or_else(|| format!("Malformed fee histogram bucket: {}", bucket))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        histogram.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(FeeHistogram(histogram))
+    }
+
+    /// Vbytes paying at least `rate`
+    pub fn depth_at(&self, rate: u64) -> u64 {
+        self.0.iter().filter(|(r, _)| *r >= rate as f64).fold(0u64, |depth, (_, v)| depth.saturating_add(*v))
+    }
+
+    /// Lowest whole rate expected to confirm within `blocks`: one above the
+    /// rate at which the queue ahead fills those blocks, or the relay minimum
+    /// if the whole mempool fits
+    pub fn rate_for_blocks(&self, blocks: u64) -> u64 {
+        let capacity = blocks * BLOCK_VSIZE;
+        let mut depth = 0u64;
+        for (rate, vsize) in &self.0 {
+            depth = depth.saturating_add(*vsize);
+            if depth >= capacity {
+                return (rate.ceil() as u64).saturating_add(1).max(MIN_RELAY_FEE_RATE);
+            }
+        }
+        MIN_RELAY_FEE_RATE
+    }
+
+    /// Tiers equivalent to the histogram, for fee_rate_cache
+    pub fn tiers(&self) -> FeeTiers {
+        FeeTiers {
+            fastest: self.rate_for_blocks(FeeTarget::NextBlock.blocks()),
+            fast: self.rate_for_blocks(FeeTarget::HalfHour.blocks()),
+            average: self.rate_for_blocks(FeeTarget::Hour.blocks()),
+        }
+    }
+}
+
+impl FeeTiers {
+    /// Parse an /api/v1/fees/recommended response
+    pub fn from_recommended_json(json: &serde_json::Value) -> Result<Self, String> {
+        let field = |name: &str| {
+            json.get(name)
+                .and_then(|v| v.as_u64())
+                .ok_or_else(|| format!("Fee response is missing {}", name))
+        };
+        Ok(FeeTiers {
+            fastest: field("fastestFee")?,
+            fast: field("halfHourFee")?,
+            average: field("hourFee")?,
+        })
+    }
+
+    /// Each tier raised to its floor and to at least the tier below it
+    pub fn floored(&self) -> FeeTiers {
+        let average = self.average.max(FEE_TIER_FLOORS.average);
+        let fast = self.fast.max(FEE_TIER_FLOORS.fast).max(average);
+        FeeTiers { fastest: self.fastest.max(FEE_TIER_FLOORS.fastest).max(fast), fast, average }
+    }
+
+    /// The tier used for a target; beyond an hour the average tier is the lowest known
+    fn rate_for(&self, target: FeeTarget) -> u64 {
+        let tiers = self.floored();
+        match target {
+            FeeTarget::NextBlock => tiers.fastest,
+            FeeTarget::HalfHour => tiers.fast,
+            FeeTarget::Hour | FeeTarget::SixHours => tiers.average,
+        }
+    }
+}
+
+impl FeeSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FeeSource::Histogram(_) => "histogram",
+            FeeSource::Tiers(_) => "tiers",
+        }
+    }
+
+    pub fn rate_for(&self, target: FeeTarget) -> u64 {
+        match self {
+            FeeSource::Histogram(histogram) => histogram.rate_for_blocks(target.blocks()),
+            FeeSource::Tiers(tiers) => tiers.rate_for(target),
+        }
+    }
+}
+
+/// Suggestions for every target for a transaction of `tx_vsize` vbytes
+pub fn suggest_fees(source: &FeeSource, tx_vsize: u64, price_usd: Option<f64>) -> Result<Vec<FeeSuggestion>, String> {
+    FeeTarget::ALL
+        .into_iter()
+        .map(|target| {
+            let sat_per_vb = source.rate_for(target);
+            let total_fee_sats = crate::utxo::fee_for_vsize(tx_vsize, sat_per_vb)?;
+            let (expected_minutes, mempool_depth_vbytes) = match source {
+                FeeSource::Histogram(histogram) => {
+                    let depth = histogram.depth_at(sat_per_vb);
+                    ((depth / BLOCK_VSIZE + 1) * MINUTES_PER_BLOCK, Some(depth))
+                }
+                FeeSource::Tiers(_) => (target.blocks() * MINUTES_PER_BLOCK, None),
+            };
+            Ok(FeeSuggestion {
+                target,
+                sat_per_vb,
+                total_fee_sats,
+                total_fee_fiat: price_usd.map(|p| format!("{:.2}", total_fee_sats as f64 / 1e8 * p)),
+                expected_minutes,
+                mempool_depth_vbytes,
+            })
+        })
+        .collect()
+}
+
+/// Resolve an explicit rate or a named target into the rate to use
+pub fn choose_fee(source: Option<&FeeSource>, explicit: Option<u64>, target: Option<&str>) -> Result<FeeChoice, String> {
+    match (explicit, target) {
+        (Some(_), Some(_)) => Err("Pass either a fee rate or a fee target, not both".to_string()),
+        (Some(sat_per_vb), None) if sat_per_vb >= MIN_RELAY_FEE_RATE => Ok(FeeChoice::Explicit { sat_per_vb }),
+        (Some(_), None) => Err(format!("Fee rate must be at least {} sat/vB", MIN_RELAY_FEE_RATE)),
+        (None, Some(name)) => {
+            let target = FeeTarget::parse(name)?;
+            let source = source.ok_or("No fee estimates are available; enter a fee rate")?;
+            Ok(FeeChoice::Target { target, sat_per_vb: source.rate_for(target) })
+        }
+        (None, None) => Err("A fee rate or fee target is required".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Shaped like /api/mempool during a fee spike (~58 MvB queued)
+    const CONGESTED: &str = r#"{
+        "count": 142731, "vsize": 58233518, "total_fee": 412880331,
+        "fee_histogram": [
+            [412.5, 51233], [120.0, 98311], [85.2, 402114], [62.0, 512006],
+            [48.9, 733120], [40.1, 1012773], [35.0, 1488210], [30.2, 2011354],
+            [25.5, 3120987], [20.0, 4872310], [15.1, 7333002], [12.0, 9012331],
+            [10.0, 11230988], [8.0, 9877430], [5.0, 6477349]
+        ]
+    }"#;
+
+    // A quiet mempool: all of it fits in one block
+    const EMPTY: &str = r#"{
+        "count": 812, "vsize": 291553, "total_fee": 1022310,
+        "fee_histogram": [[6.1, 21003], [3.0, 88120], [2.0, 101338], [1.0, 81092]]
+    }"#;
+
+    fn histogram(json: &str) -> FeeHistogram {
+        FeeHistogram::from_mempool_json(&serde_json::from_str(json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_congested_mempool_targets() {
+        let source = FeeSource::Histogram(histogram(CONGESTED));
+        let suggestions = suggest_fees(&source, 141, Some(60_000.0)).unwrap();
+        let rates: Vec<u64> = suggestions.iter().map(|s| s.sat_per_vb).collect();
+        // The first 1 MvB is filled within the 62 sat/vB bucket
+        assert_eq!(rates, vec![63, 36, 32, 11]);
+        assert!(rates.windows(2).all(|w| w[0] >= w[1]));
+
+        let next = &suggestions[0];
+        assert_eq!(next.total_fee_sats, 63 * 141);
+        assert_eq!(next.total_fee_fiat.as_deref(), Some("5.33"));
+        assert_eq!(next.mempool_depth_vbytes, Some(51233 + 98311 + 402114));
+        assert_eq!(next.expected_minutes, 10);
+        assert_eq!(suggestions[3].expected_minutes, 310);
+    }
+
+    #[test]
+    fn test_empty_mempool_uses_relay_minimum() {
+        let source = FeeSource::Histogram(histogram(EMPTY));
+        for suggestion in suggest_fees(&source, 200, None).unwrap() {
+            assert_eq!(suggestion.sat_per_vb, MIN_RELAY_FEE_RATE);
+            assert_eq!(suggestion.expected_minutes, 10);
+            assert_eq!(suggestion.total_fee_fiat, None);
+        }
+        assert_eq!(histogram(EMPTY).tiers(), FeeTiers { fastest: 1, fast: 1, average: 1 });
+    }
+
+    #[test]
+    fn test_tiers_fallback_and_choice() {
+        let json = serde_json::json!({ "fastestFee": 25, "halfHourFee": 18, "hourFee": 12, "economyFee": 6, "minimumFee": 1 });
+        let source = FeeSource::Tiers(FeeTiers::from_recommended_json(&json).unwrap());
+        let suggestions = suggest_fees(&source, 100, None).unwrap();
+        assert_eq!(suggestions.iter().map(|s| s.sat_per_vb).collect::<Vec<_>>(), vec![25, 18, 12, 12]);
+        assert_eq!(suggestions[1].expected_minutes, 30);
+        assert!(suggestions.iter().all(|s| s.mempool_depth_vbytes.is_none()));
+
+        assert_eq!(
+            choose_fee(Some(&source), None, Some("30_min")).unwrap(),
+            FeeChoice::Target { target: FeeTarget::HalfHour, sat_per_vb: 18 }
+        );
+        assert_eq!(choose_fee(None, Some(7), None).unwrap(), FeeChoice::Explicit { sat_per_vb: 7 });
+        assert!(choose_fee(Some(&source), Some(7), Some("1_hour")).is_err());
+        assert!(choose_fee(None, None, Some("1_hour")).is_err());
+        assert!(choose_fee(Some(&source), None, Some("tomorrow")).is_err());
+    }
+
+    #[test]
+    fn test_tier_floors() {
+        let json = serde_json::json!({ "fastestFee": 0, "halfHourFee": 0, "hourFee": 0 });
+        let zero = FeeTiers::from_recommended_json(&json).unwrap();
+        assert_eq!(zero.floored(), FEE_TIER_FLOORS);
+        let rates: Vec<u64> = suggest_fees(&FeeSource::Tiers(zero), 100, None).unwrap().iter().map(|s| s.sat_per_vb).collect();
+        assert_eq!(rates, vec![2, 1, 1, 1]);
+
+        // A slower tier never costs more than a faster one
+        let inverted = FeeTiers { fastest: 5, fast: 9, average: 3 };
+        assert_eq!(inverted.floored(), FeeTiers { fastest: 9, fast: 9, average: 3 });
+        let busy = FeeTiers { fastest: 40, fast: 30, average: 20 };
+        assert_eq!(busy.floored(), busy);
+    }
+
+    #[test]
+    fn test_absurd_estimates_do_not_overflow() {
+        let json = serde_json::json!({ "fee_histogram": [[1e30, u64::MAX], [5.0, u64::MAX]] });
+        let histogram = histogram(&json.to_string());
+        assert_eq!(histogram.depth_at(1), u64::MAX);
+        assert_eq!(histogram.rate_for_blocks(1), u64::MAX);
+        assert!(suggest_fees(&FeeSource::Histogram(histogram), 141, None).is_err());
+    }
+}
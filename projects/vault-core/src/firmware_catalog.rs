@@ -0,0 +1,630 @@
+// firmware_catalog.rs - Release image hashing and releases.json integrity checks
+//
+// releases.json maps the hashes devices report in Features to release
+// versions. This module recomputes those hashes from release binaries the way
+// the device does, so the table can be rebuilt from published images instead
+// of being edited by hand:
+//
+// - Firmware (`v*/firmware.keepkey.bin`): a 256-byte meta header (magic
+//   "KPKY", code length, three signature indexes, flags, reserved bytes and
+//   three signatures) followed by the code. Both are flashed as-is and the
+//   device reports the SHA-256 of header plus `code_len` bytes of code.
+//   Anything after the code is never flashed and is not hashed.
+// - Bootloader (`bl_v*/blupdater.bin`): a firmware image whose code embeds
+//   the whole bootloader flash region (sectors 5 and 6, 256 KiB, padding
+//   included). The device reports SHA-256(SHA-256(region)), hashing the padded
+//   region rather than the bootloader's own length. The region is found by its
+//   Cortex-M vector table. 1.x updaters only carry the unpadded bootloader and
+//   cannot be hashed this way; they are skipped.
+//
+// The catalog the app trusts is compiled in (`bundled_releases`), so it is
+// found however the app was launched; only the integrity checks, which need
+// the release images, look for the firmware directory on disk.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use bitcoin::hashes::{sha256, sha256d, Hash};
+use keepkey_rust::version::{parse_version, ParsedVersion};
+use serde::{Deserialize, Serialize};
+
+/// Magic at the start of every signed image
+pub const IMAGE_MAGIC: &[u8; 4] = b"KPKY";
+
+/// Size of the meta header in front of the code
+pub const META_HEADER_LEN: usize = 256;
+
+/// Size of the bootloader flash region the device hashes
+pub const BOOTLOADER_REGION_LEN: usize = 0x40000;
+
+/// Bootloader flash region in the device address space
+const BOOTLOADER_FLASH_START: u32 = 0x0802_0000;
+const BOOTLOADER_FLASH_END: u32 = BOOTLOADER_FLASH_START + BOOTLOADER_REGION_LEN as u32;
+
+/// SRAM range a valid initial stack pointer falls in
+const SRAM_START: u32 = 0x2000_0000;
+const SRAM_END: u32 = 0x2003_0000;
+
+/// Vector table entries checked: initial SP, reset and the five fault handlers
+const VECTOR_ENTRIES_CHECKED: usize = 7;
+
+/// releases.json as shipped with the app
+pub const BUNDLED_RELEASES_JSON: &str = include_str!("../../keepkey-vault/src-tauri/firmware/releases.json");
+
+/// Paths the bundled release images are looked up from
+pub const BUNDLED_FIRMWARE_DIRS: &[&str] = &["firmware", "./firmware", "../firmware", "../../firmware"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum ImageKind {
+    Firmware,
+    Bootloader,
+}
+
+impl ImageKind {
+    /// Key of this kind under `hashes` in releases.json
+    pub fn key(&self) -> &'static str {
+        match self {
+            ImageKind::Firmware => "firmware",
+            ImageKind::Bootloader => "bootloader",
+        }
+    }
+
+    /// Release directory name prefix and image file name
+    fn layout(&self) -> (&'static str, &'static str) {
+        match self {
+            ImageKind::Firmware => ("v", "firmware.keepkey.bin"),
+            ImageKind::Bootloader => ("bl_v", "blupdater.bin"),
+        }
+    }
+
+    /// Path of a release image relative to the firmware directory, as used in
+    /// the `url` fields of releases.json
+    pub fn relative_path(&self, version: &str) -> String {
+        let (prefix, file) = self.layout();
+        format!("{}{}/{}", prefix, version.trim_start_matches('v'), file)
+    }
+}
+
+/// Fields of the meta header that determine what gets hashed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub code_len: usize,
+    pub signature_indexes: [u8; 3],
+    pub flags: u8,
+}
+
+impl ImageHeader {
+    /// Images with all signature indexes zero are unsigned developer builds
+    pub fn is_signed(&self) -> bool {
+        self.signature_indexes.iter().all(|i| *i != 0)
+    }
+}
+
+/// Parse and bounds-check the meta header of a firmware or updater image
+pub fn parse_image_header(image: &[u8]) -> Result<ImageHeader, String> {
+    if image.len() < META_HEADER_LEN {
+        return Err(format!("Image is {} bytes, shorter than its meta header", image.len()));
+    }
+    if &image[..4] != IMAGE_MAGIC {
+        return Err("Image does not start with the KPKY magic".to_string());
+    }
+    let code_len = u32::from_le_bytes([image[4], image[5], image[6], image[7]]) as usize;
+    if META_HEADER_LEN + code_len > image.len() {
+        return Err(format!(
+            "Header declares {} bytes of code but the image only has {}",
+            code_len,
+            image.len() - META_HEADER_LEN
+        ));
+    }
+    Ok(ImageHeader {
+        code_len,
+        signature_indexes: [image[8], image[9], image[10]],
+        flags: image[11],
+    })
+}
+
+/// Hash a firmware image as the device reports it in `firmware_hash`
+pub fn firmware_image_hash(image: &[u8]) -> Result<String, String> {
+    let header = parse_image_header(image)?;
+    let flashed = &image[..META_HEADER_LEN + header.code_len];
+    Ok(hex::encode(sha256::Hash::hash(flashed).to_byte_array()))
+}
+
+/// Offset of the bootloader region embedded in an updater's code
+fn find_bootloader_region(code: &[u8]) -> Option<usize> {
+    let word = |bytes: &[u8], i: usize| {
+        u32::from_le_bytes([bytes[i * 4], bytes[i * 4 + 1], bytes[i * 4 + 2], bytes[i * 4 + 3]])
+    };
+    // The region is linked in as raw bytes, so it need not be word aligned
+    (0..code.len().saturating_sub(VECTOR_ENTRIES_CHECKED * 4)).find(|&offset| {
+        let table = &code[offset..offset + VECTOR_ENTRIES_CHECKED * 4];
+        let stack_pointer = word(table, 0);
+        (SRAM_START + 1..=SRAM_END).contains(&stack_pointer)
+            && (1..VECTOR_ENTRIES_CHECKED).all(|i| {
+                let handler = word(table, i);
+                handler & 1 == 1 && (BOOTLOADER_FLASH_START..BOOTLOADER_FLASH_END).contains(&handler)
+            })
+    })
+}
+
+/// Hash the bootloader an updater installs, as the device reports it in
+/// `bootloader_hash`
+pub fn bootloader_image_hash(updater: &[u8]) -> Result<String, String> {
+    let header = parse_image_header(updater)?;
+    let code = &updater[META_HEADER_LEN..META_HEADER_LEN + header.code_len];
+    let offset = find_bootloader_region(code)
+        .ok_or_else(|| "No bootloader vector table found in the updater".to_string())?;
+    let region = code
+        .get(offset..offset + BOOTLOADER_REGION_LEN)
+        .ok_or_else(|| "Updater does not carry a full bootloader region (1.x updater?)".to_string())?;
+    Ok(hex::encode(sha256d::Hash::hash(region).to_byte_array()))
+}
+
+/// Hash an image of the given kind
+pub fn image_hash(kind: ImageKind, image: &[u8]) -> Result<String, String> {
+    match kind {
+        ImageKind::Firmware => firmware_image_hash(image),
+        ImageKind::Bootloader => bootloader_image_hash(image),
+    }
+}
+
+/// A release image and the hash recomputed from it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ComputedImage {
+    pub kind: ImageKind,
+    /// Release version with the `v` prefix, as releases.json writes it
+    pub version: String,
+    pub source: String,
+    pub hash: String,
+}
+
+/// A release image that could not be hashed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SkippedImage {
+    pub kind: ImageKind,
+    pub version: String,
+    pub source: String,
+    pub reason: String,
+}
+
+/// Hash every release image under `dir`, laid out like the bundled firmware
+/// directory (`v<version>/firmware.keepkey.bin`, `bl_v<version>/blupdater.bin`)
+pub fn scan_release_dir(dir: &Path) -> Result<(Vec<ComputedImage>, Vec<SkippedImage>), String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut computed = Vec::new();
+    let mut skipped = Vec::new();
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        // bl_v is checked first since firmware directories are only "v"
+        let (kind, version) = if let Some(version) = name.strip_prefix("bl_v") {
+            (ImageKind::Bootloader, version)
+        } else if let Some(version) = name.strip_prefix('v') {
+            (ImageKind::Firmware, version)
+        } else {
+            continue;
+        };
+        let version = format!("v{}", version);
+        let path: PathBuf = dir.join(kind.relative_path(&version));
+        if !path.exists() {
+            continue;
+        }
+
+        let source = path.display().to_string();
+        let hashed = std::fs::read(&path)
+            .map_err(|e| format!("Failed to read {}: {}", source, e))
+            .and_then(|image| image_hash(kind, &image));
+        match hashed {
+            Ok(hash) => computed.push(ComputedImage { kind, version, source, hash }),
+            Err(reason) => skipped.push(SkippedImage { kind, version, source, reason }),
+        }
+    }
+
+    computed.sort_by(|a, b| (a.kind, &a.version).cmp(&(b.kind, &b.version)));
+    skipped.sort_by(|a, b| (a.kind, &a.version).cmp(&(b.kind, &b.version)));
+    Ok((computed, skipped))
+}
+
+/// Rebuild a catalog from recomputed hashes.
+///
+/// Each version with a computed image gets exactly that hash under
+/// `hashes.<kind>`; versions without an image keep their entries. The `hash`
+/// next to each `url` in `latest` and `beta` is refreshed when the image it
+/// points at was computed. Everything else is copied unchanged.
+pub fn regenerate_catalog(catalog: &serde_json::Value, images: &[ComputedImage]) -> serde_json::Value {
+    let mut regenerated = catalog.clone();
+
+    for kind in [ImageKind::Firmware, ImageKind::Bootloader] {
+        let ours: Vec<&ComputedImage> = images.iter().filter(|i| i.kind == kind).collect();
+        if ours.is_empty() {
+            continue;
+        }
+        if !regenerated["hashes"][kind.key()].is_object() {
+            regenerated["hashes"][kind.key()] = serde_json::json!({});
+        }
+        let Some(table) = regenerated["hashes"][kind.key()].as_object_mut() else { continue };
+        table.retain(|_, version| !ours.iter().any(|i| Some(i.version.as_str()) == version.as_str()));
+        for image in ours {
+            table.insert(image.hash.clone(), serde_json::json!(image.version));
+        }
+    }
+
+    for channel in ["latest", "beta"] {
+        for kind in [ImageKind::Firmware, ImageKind::Bootloader] {
+            let Some(release) = regenerated.get_mut(channel).and_then(|c| c.get_mut(kind.key())) else { continue };
+            let Some(url) = release.get("url").and_then(|u| u.as_str()) else { continue };
+            if let Some(image) = images.iter().find(|i| i.kind == kind && kind.relative_path(&i.version) == url) {
+                release["hash"] = serde_json::json!(image.hash);
+            }
+        }
+    }
+
+    regenerated
+}
+
+/// One difference between the bundled and the regenerated catalog
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CatalogChange {
+    /// `hashes.firmware`, `latest.bootloader.hash`, ...
+    pub location: String,
+    pub version: String,
+    /// Hash in the bundled catalog; None when it has no entry
+    pub bundled: Option<String>,
+    /// Hash from the release image; None when the entry would be dropped
+    pub computed: Option<String>,
+}
+
+fn hash_table(catalog: &serde_json::Value, kind: ImageKind) -> BTreeMap<String, String> {
+    catalog["hashes"][kind.key()]
+        .as_object()
+        .map(|table| {
+            table
+                .iter()
+                .filter_map(|(hash, version)| Some((hash.clone(), version.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Differences between two catalogs, per version
+pub fn diff_catalogs(bundled: &serde_json::Value, regenerated: &serde_json::Value) -> Vec<CatalogChange> {
+    let mut changes = Vec::new();
+
+    for kind in [ImageKind::Firmware, ImageKind::Bootloader] {
+        let location = format!("hashes.{}", kind.key());
+        let before = hash_table(bundled, kind);
+        let after = hash_table(regenerated, kind);
+        for (hash, version) in &before {
+            if after.get(hash) != Some(version) {
+                let computed = after.iter().find(|(_, v)| *v == version).map(|(h, _)| h.clone());
+                changes.push(CatalogChange {
+                    location: location.clone(),
+                    version: version.clone(),
+                    bundled: Some(hash.clone()),
+                    computed,
+                });
+            }
+        }
+        for (hash, version) in &after {
+            if !before.contains_key(hash) && !before.values().any(|v| v == version) {
+                changes.push(CatalogChange {
+                    location: location.clone(),
+                    version: version.clone(),
+                    bundled: None,
+                    computed: Some(hash.clone()),
+                });
+            }
+        }
+    }
+
+    for channel in ["latest", "beta"] {
+        for kind in [ImageKind::Firmware, ImageKind::Bootloader] {
+            let before = bundled[channel][kind.key()]["hash"].as_str();
+            let after = regenerated[channel][kind.key()]["hash"].as_str();
+            if before != after {
+                changes.push(CatalogChange {
+                    location: format!("{}.{}.hash", channel, kind.key()),
+                    version: bundled[channel][kind.key()]["version"].as_str().unwrap_or_default().to_string(),
+                    bundled: before.map(str::to_string),
+                    computed: after.map(str::to_string),
+                });
+            }
+        }
+    }
+
+    changes
+}
+
+/// Catalog entries with no release image to check them against
+pub fn unverified_entries(catalog: &serde_json::Value, images: &[ComputedImage]) -> Vec<String> {
+    let mut unverified: Vec<String> = [ImageKind::Firmware, ImageKind::Bootloader]
+        .into_iter()
+        .flat_map(|kind| {
+            hash_table(catalog, kind)
+                .into_values()
+                .filter(move |version| !images.iter().any(|i| i.kind == kind && &i.version == version))
+                .map(move |version| format!("{} {}", kind.key(), version))
+        })
+        .collect();
+    unverified.sort();
+    unverified.dedup();
+    unverified
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CatalogIntegrityReport {
+    pub firmware_dir: String,
+    pub verified: Vec<ComputedImage>,
+    pub skipped: Vec<SkippedImage>,
+    /// Any entry here means releases.json does not match the release images
+    pub mismatches: Vec<CatalogChange>,
+    /// Catalog entries without a local image, e.g. `bootloader v1.0.3`
+    pub unverified: Vec<String>,
+}
+
+impl CatalogIntegrityReport {
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Recompute the hashes of the images in `dir` and cross-check its releases.json
+pub fn verify_catalog_dir(dir: &Path) -> Result<CatalogIntegrityReport, String> {
+    let catalog_path = dir.join("releases.json");
+    let contents = std::fs::read_to_string(&catalog_path)
+        .map_err(|e| format!("Failed to read {}: {}", catalog_path.display(), e))?;
+    let catalog: serde_json::Value =
+        serde_json::from_str(&contents).map_err(|e| format!("Invalid releases.json: {}", e))?;
+
+    let (verified, skipped) = scan_release_dir(dir)?;
+    let mismatches = diff_catalogs(&catalog, &regenerate_catalog(&catalog, &verified));
+    let unverified = unverified_entries(&catalog, &verified);
+
+    Ok(CatalogIntegrityReport {
+        firmware_dir: dir.display().to_string(),
+        verified,
+        skipped,
+        mismatches,
+        unverified,
+    })
+}
+
+/// Cross-check the bundled releases.json against the bundled release images
+pub fn verify_bundled_catalog() -> Result<CatalogIntegrityReport, String> {
+    let dir = BUNDLED_FIRMWARE_DIRS
+        .iter()
+        .map(Path::new)
+        .find(|dir| dir.join("releases.json").exists())
+        .ok_or_else(|| "Bundled firmware directory with releases.json not found".to_string())?;
+    verify_catalog_dir(dir)
+}
+
+/// A release image a channel points at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ReleaseImage {
+    /// With the `v` prefix, as releases.json writes it
+    pub version: String,
+    /// Image path relative to the firmware directory
+    pub url: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ReleaseChannel {
+    pub firmware: ReleaseImage,
+    pub bootloader: ReleaseImage,
+}
+
+/// Entries of `hashes` or `security`, by image kind
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ByKind {
+    #[serde(default)]
+    pub firmware: BTreeMap<String, String>,
+    #[serde(default)]
+    pub bootloader: BTreeMap<String, String>,
+}
+
+impl ByKind {
+    pub fn get(&self, kind: ImageKind) -> &BTreeMap<String, String> {
+        match kind {
+            ImageKind::Firmware => &self.firmware,
+            ImageKind::Bootloader => &self.bootloader,
+        }
+    }
+}
+
+/// The parts of releases.json the app reads
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ReleasesCatalog {
+    pub latest: ReleaseChannel,
+    #[serde(default)]
+    pub beta: Option<ReleaseChannel>,
+    /// Release version by the hash devices report
+    pub hashes: ByKind,
+    /// What each security release fixed, by version
+    #[serde(default)]
+    pub security: ByKind,
+}
+
+impl ReleasesCatalog {
+    /// Parse and check a catalog; one without a usable latest release or
+    /// hash table is an error rather than an empty answer
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let catalog: Self = serde_json::from_str(json).map_err(|e| format!("Invalid releases.json: {}", e))?;
+        for kind in [ImageKind::Firmware, ImageKind::Bootloader] {
+            if let ParsedVersion::Unparseable(version) = parse_version(&catalog.latest_image(kind).version) {
+                return Err(format!("Invalid releases.json: latest {} version '{}'", kind.key(), version));
+            }
+            if catalog.hashes.get(kind).is_empty() {
+                return Err(format!("Invalid releases.json: no {} hashes", kind.key()));
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// Read and parse the catalog at `path`
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&contents)
+    }
+
+    pub fn latest_image(&self, kind: ImageKind) -> &ReleaseImage {
+        match kind {
+            ImageKind::Firmware => &self.latest.firmware,
+            ImageKind::Bootloader => &self.latest.bootloader,
+        }
+    }
+
+    /// Latest release version without the `v` prefix, as devices report it
+    pub fn latest_version(&self, kind: ImageKind) -> String {
+        self.latest_image(kind).version.trim_start_matches('v').to_string()
+    }
+
+    /// Release version of the image with `hash`, without the `v` prefix;
+    /// None for builds that were never released
+    pub fn version_for_hash(&self, kind: ImageKind, hash: &str) -> Option<String> {
+        self.hashes.get(kind).get(hash).map(|version| version.trim_start_matches('v').to_string())
+    }
+}
+
+/// The catalog compiled into the app, parsed on first use
+pub fn bundled_releases() -> Result<&'static ReleasesCatalog, String> {
+    static CATALOG: OnceLock<Result<ReleasesCatalog, String>> = OnceLock::new();
+    CATALOG
+        .get_or_init(|| ReleasesCatalog::parse(BUNDLED_RELEASES_JSON))
+        .as_ref()
+        .map_err(|e| format!("Bundled firmware catalog is unusable: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundled(relative: &str) -> Vec<u8> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../keepkey-usb/firmware").join(relative);
+        std::fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+    }
+
+    fn image(kind: ImageKind, version: &str, hash: &str) -> ComputedImage {
+        ComputedImage { kind, version: version.to_string(), source: String::new(), hash: hash.to_string() }
+    }
+
+    #[test]
+    fn test_firmware_hashes_match_published_releases() {
+        for (version, expected) in [
+            ("6.0.0", "d380357b7403064d7b1ea963dc56032239541a21ef0b7e08082fb36ed470de82"),
+            ("7.1.0", "d8b2b43eada45ded399f347289750a7083081186b37158b85eab41a38cbc6e50"),
+            ("7.9.0", "387ec4c8d3dcc83df8707aa0129eeb44e824c3797fb629a493be845327669da1"),
+        ] {
+            let firmware = bundled(&ImageKind::Firmware.relative_path(version));
+            assert_eq!(firmware_image_hash(&firmware).unwrap(), expected, "v{}", version);
+        }
+    }
+
+    #[test]
+    fn test_bootloader_hash_is_double_hash_of_padded_region() {
+        for (version, expected) in [
+            ("2.0.0", "9bf1580d1b21250f922b68794cdadd6c8e166ae5b15ce160a42f8c44a2f05936"),
+            ("2.1.4", "fe98454e7ebd4aef4a6db5bd4c60f52cf3f58b974283a7c1e1fcc5fea02cf3eb"),
+        ] {
+            let updater = bundled(&ImageKind::Bootloader.relative_path(version));
+            assert_eq!(bootloader_image_hash(&updater).unwrap(), expected, "v{}", version);
+        }
+        // 1.x updaters carry the bootloader without its padding
+        assert!(bootloader_image_hash(&bundled("bl_v1.1.0/blupdater.bin")).is_err());
+    }
+
+    #[test]
+    fn test_firmware_hash_ignores_bytes_after_code() {
+        let mut firmware = bundled("v6.0.0/firmware.keepkey.bin");
+        let expected = firmware_image_hash(&firmware).unwrap();
+        firmware.extend_from_slice(&[0xff; 64]);
+        assert_eq!(firmware_image_hash(&firmware).unwrap(), expected);
+
+        firmware.truncate(1000);
+        assert!(firmware_image_hash(&firmware).unwrap_err().contains("declares"));
+        assert!(parse_image_header(b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_regenerate_replaces_entries_for_computed_versions() {
+        let catalog = serde_json::json!({
+            "latest": { "firmware": { "version": "v7.1.0", "url": "v7.1.0/firmware.keepkey.bin", "hash": "stale" } },
+            "hashes": {
+                "firmware": { "aa": "v7.0.3", "bb": "v7.1.0" },
+                "bootloader": { "cc": "v1.0.3", "dd": "v1.0.3" }
+            },
+            "links": { "app": "https://keepkey.com" }
+        });
+        let images = [image(ImageKind::Firmware, "v7.1.0", "ee"), image(ImageKind::Firmware, "v7.2.1", "ff")];
+
+        let regenerated = regenerate_catalog(&catalog, &images);
+        assert_eq!(
+            regenerated["hashes"]["firmware"],
+            serde_json::json!({ "aa": "v7.0.3", "ee": "v7.1.0", "ff": "v7.2.1" })
+        );
+        assert_eq!(regenerated["hashes"]["bootloader"], catalog["hashes"]["bootloader"]);
+        assert_eq!(regenerated["latest"]["firmware"]["hash"], "ee");
+        assert_eq!(regenerated["links"], catalog["links"]);
+
+        let changes = diff_catalogs(&catalog, &regenerated);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.contains(&CatalogChange {
+            location: "hashes.firmware".to_string(),
+            version: "v7.1.0".to_string(),
+            bundled: Some("bb".to_string()),
+            computed: Some("ee".to_string()),
+        }));
+        assert!(changes.iter().any(|c| c.version == "v7.2.1" && c.bundled.is_none()));
+        assert!(changes.iter().any(|c| c.location == "latest.firmware.hash"));
+
+        assert_eq!(unverified_entries(&catalog, &images), vec!["bootloader v1.0.3", "firmware v7.0.3"]);
+    }
+
+    #[test]
+    fn test_bundled_releases_parse() {
+        let releases = bundled_releases().unwrap();
+        assert_eq!(releases.latest_version(ImageKind::Firmware), "7.10.0");
+        assert_eq!(releases.latest_version(ImageKind::Bootloader), "2.1.4");
+        assert_eq!(
+            releases.version_for_hash(ImageKind::Bootloader, "fe98454e7ebd4aef4a6db5bd4c60f52cf3f58b974283a7c1e1fcc5fea02cf3eb").as_deref(),
+            Some("2.1.4")
+        );
+        assert_eq!(releases.version_for_hash(ImageKind::Firmware, "not-a-release"), None);
+    }
+
+    #[test]
+    fn test_missing_or_corrupt_catalog_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("releases.json");
+        assert!(ReleasesCatalog::load(&path).unwrap_err().starts_with("Failed to read"));
+        std::fs::write(&path, r#"{ "latest": "#).unwrap();
+        assert!(ReleasesCatalog::load(&path).unwrap_err().starts_with("Invalid releases.json"));
+
+        let mut catalog: serde_json::Value = serde_json::from_str(BUNDLED_RELEASES_JSON).unwrap();
+        catalog["latest"]["firmware"]["version"] = serde_json::json!("latest");
+        assert!(ReleasesCatalog::parse(&catalog.to_string()).unwrap_err().contains("latest firmware version"));
+        catalog["latest"]["firmware"]["version"] = serde_json::json!("v7.10.0");
+        catalog["hashes"]["bootloader"] = serde_json::json!({});
+        assert!(ReleasesCatalog::parse(&catalog.to_string()).unwrap_err().contains("no bootloader hashes"));
+        catalog.as_object_mut().unwrap().remove("latest");
+        assert!(ReleasesCatalog::parse(&catalog.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_matching_catalog_has_no_changes() {
+        let catalog = serde_json::json!({ "hashes": { "firmware": { "aa": "v7.0.3" }, "bootloader": {} } });
+        let images = [image(ImageKind::Firmware, "v7.0.3", "aa")];
+        assert!(diff_catalogs(&catalog, &regenerate_catalog(&catalog, &images)).is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+// firmware_downgrade.rs - Firmware and bootloader downgrade protection
+//
+// Flashing older firmware can bring back vulnerabilities a later release
+// fixed, so an update to a version older than the installed one is refused
+// unless the caller explicitly overrides it. The override itself (the
+// allow_downgrade flag plus a typed confirmation) is enforced by the update
+// command; this module only decides whether an update is a downgrade and what
+// it would undo. Bootloaders are never downgraded: old bootloaders have issues
+// no firmware can fix, and there is no override.
+//
+// Releases that fixed security issues are listed in releases.json under
+// `security.<kind>`, keyed by version with a one-line summary:
+//
+//   "security": { "firmware": { "v7.1.0": "Fixes ..." } }
+
+use std::cmp::Ordering;
+use std::fmt;
+use serde::Serialize;
+use keepkey_rust::version::{compare_versions, VersionComparison};
+use crate::firmware_catalog::ImageKind;
+
+/// How an update target relates to the installed version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateDirection {
+    Upgrade,
+    Reinstall,
+    /// Older than the installed version; only returned under override
+    Downgrade,
+    /// The installed version is not known, so no direction can be told
+    Unknown,
+}
+
+/// A release between the target and the installed version that fixed a
+/// security issue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SecurityRelease {
+    pub version: String,
+    pub summary: String,
+}
+
+/// Structured error for refused downgrades
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum DowngradeError {
+    /// Firmware downgrade without the override
+    DowngradeBlocked {
+        installed: String,
+        target: String,
+        /// Security releases the downgrade would roll back, newest first
+        skipped_security_releases: Vec<SecurityRelease>,
+    },
+    /// Bootloader downgrades are refused even with the override
+    BootloaderDowngradeBlocked { installed: String, target: String },
+}
+
+impl DowngradeError {
+    /// Encode for a `Result<_, String>` command error
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl fmt::Display for DowngradeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DowngradeError::DowngradeBlocked { installed, target, skipped_security_releases } => {
+                write!(f, "Firmware {} is older than the installed {}", target, installed)?;
+                if !skipped_security_releases.is_empty() {
+                    let versions: Vec<&str> = skipped_security_releases.iter().map(|r| r.version.as_str()).collect();
+                    write!(f, " and would undo security fixes in {}", versions.join(", "))?;
+                }
+                Ok(())
+            }
+            DowngradeError::BootloaderDowngradeBlocked { installed, target } => write!(
+                f,
+                "Bootloader {} is older than the installed {}; bootloaders are never downgraded",
+                target, installed
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DowngradeError {}
+
+/// Security releases of `kind` newer than `target` and no newer than
+/// `installed`, newest first
+pub fn skipped_security_releases(
+    catalog: &serde_json::Value,
+    kind: ImageKind,
+    installed: &str,
+    target: &str,
+) -> Vec<SecurityRelease> {
+    let Some(entries) = catalog["security"][kind.key()].as_object() else {
+        return Vec::new();
+    };
+
+    let mut skipped: Vec<SecurityRelease> = entries
+        .iter()
+        .filter(|(version, _)| {
+            compare_versions(version, target) == VersionComparison::Greater
+                && matches!(compare_versions(version, installed), VersionComparison::Less | VersionComparison::Equal)
+        })
+        .map(|(version, summary)| SecurityRelease {
+            version: version.clone(),
+            summary: summary.as_str().unwrap_or_default().to_string(),
+        })
+        .collect();
+    // Every entry left compared as a version, so this ordering is total
+    skipped.sort_by(|a, b| match compare_versions(&b.version, &a.version) {
+        VersionComparison::Less => Ordering::Less,
+        VersionComparison::Greater => Ordering::Greater,
+        VersionComparison::Equal | VersionComparison::Unparseable => Ordering::Equal,
+    });
+    skipped
+}
+
+/// Decide whether flashing `target` over `installed` may go ahead.
+///
+/// A firmware downgrade is only allowed with `allow_downgrade`, and then
+/// returns `UpdateDirection::Downgrade` so the caller can demand the
+/// confirmation and record it. A bootloader downgrade is always refused. An
+/// unknown or unparseable installed version cannot be compared and is let
+/// through as `Unknown`; the target must already be a valid version.
+pub fn check_update(
+    catalog: &serde_json::Value,
+    kind: ImageKind,
+    installed: Option<&str>,
+    target: &str,
+    allow_downgrade: bool,
+) -> Result<UpdateDirection, DowngradeError> {
+    let Some(installed) = installed else {
+        return Ok(UpdateDirection::Unknown);
+    };
+
+    match compare_versions(installed, target) {
+        VersionComparison::Less => Ok(UpdateDirection::Upgrade),
+        VersionComparison::Equal => Ok(UpdateDirection::Reinstall),
+        VersionComparison::Unparseable => Ok(UpdateDirection::Unknown),
+        VersionComparison::Greater => match kind {
+            ImageKind::Bootloader => Err(DowngradeError::BootloaderDowngradeBlocked {
+                installed: installed.to_string(),
+                target: target.to_string(),
+            }),
+            ImageKind::Firmware if allow_downgrade => Ok(UpdateDirection::Downgrade),
+            ImageKind::Firmware => Err(DowngradeError::DowngradeBlocked {
+                installed: installed.to_string(),
+                target: target.to_string(),
+                skipped_security_releases: skipped_security_releases(catalog, kind, installed, target),
+            }),
+        },
+    }
+}
+
+/// Firmware version installed on a device. In bootloader mode the device
+/// reports the bootloader's version, so the firmware is identified by its
+/// hash instead; None when that hash is not a known release.
+pub fn installed_firmware_version(features: &keepkey_rust::features::DeviceFeatures) -> Option<String> {
+    if features.bootloader_mode {
+        features.firmware_hash.as_deref().and_then(crate::features::firmware_version_from_hash)
+    } else {
+        Some(features.version.clone())
+    }
+}
+
+/// Bundled releases.json, or an empty catalog if it does not parse (no
+/// security releases are listed then, but downgrades are still refused)
+pub fn bundled_catalog() -> serde_json::Value {
+    serde_json::from_str(crate::firmware_catalog::BUNDLED_RELEASES_JSON).unwrap_or(serde_json::Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn catalog() -> serde_json::Value {
+        json!({
+            "security": {
+                "firmware": {
+                    "v7.1.0": "Fixes a passphrase cache issue",
+                    "v7.5.2": "Hardens transaction confirmation screens",
+                    "v7.9.0": "Fixes a USB descriptor overflow",
+                    "v7.10.0": "Fixes a display buffer overread"
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_firmware_downgrade_is_blocked_with_skipped_security_releases() {
+        let err = check_update(&catalog(), ImageKind::Firmware, Some("7.9.2"), "7.5.0", false).unwrap_err();
+        assert_eq!(err, DowngradeError::DowngradeBlocked {
+            installed: "7.9.2".to_string(),
+            target: "7.5.0".to_string(),
+            skipped_security_releases: vec![
+                SecurityRelease { version: "v7.9.0".to_string(), summary: "Fixes a USB descriptor overflow".to_string() },
+                SecurityRelease { version: "v7.5.2".to_string(), summary: "Hardens transaction confirmation screens".to_string() },
+            ],
+        });
+        assert!(err.to_string().contains("v7.9.0, v7.5.2"));
+
+        let json: serde_json::Value = serde_json::from_str(&err.to_json_string()).unwrap();
+        assert_eq!(json["kind"], "DowngradeBlocked");
+        assert_eq!(json["skipped_security_releases"][0]["version"], "v7.9.0");
+    }
+
+    #[test]
+    fn test_firmware_downgrade_override() {
+        assert_eq!(
+            check_update(&catalog(), ImageKind::Firmware, Some("v7.10.0"), "7.9.2", true),
+            Ok(UpdateDirection::Downgrade)
+        );
+        // Without security metadata the downgrade is still refused
+        let err = check_update(&serde_json::Value::Null, ImageKind::Firmware, Some("7.10.0"), "7.9.2", false).unwrap_err();
+        assert!(matches!(err, DowngradeError::DowngradeBlocked { ref skipped_security_releases, .. } if skipped_security_releases.is_empty()));
+    }
+
+    #[test]
+    fn test_bootloader_downgrade_is_never_allowed() {
+        for allow_downgrade in [false, true] {
+            assert_eq!(
+                check_update(&catalog(), ImageKind::Bootloader, Some("2.1.4"), "1.0.3", allow_downgrade),
+                Err(DowngradeError::BootloaderDowngradeBlocked {
+                    installed: "2.1.4".to_string(),
+                    target: "1.0.3".to_string(),
+                })
+            );
+        }
+        assert_eq!(check_update(&catalog(), ImageKind::Bootloader, Some("1.0.3"), "2.1.4", false), Ok(UpdateDirection::Upgrade));
+    }
+
+    #[test]
+    fn test_upgrades_and_unknown_versions_pass() {
+        assert_eq!(check_update(&catalog(), ImageKind::Firmware, Some("7.9.2"), "7.10.0", false), Ok(UpdateDirection::Upgrade));
+        assert_eq!(check_update(&catalog(), ImageKind::Firmware, Some("7.10.0"), "v7.10.0", false), Ok(UpdateDirection::Reinstall));
+        assert_eq!(check_update(&catalog(), ImageKind::Firmware, None, "7.0.3", false), Ok(UpdateDirection::Unknown));
+        assert_eq!(check_update(&catalog(), ImageKind::Bootloader, Some("unknown"), "2.1.4", false), Ok(UpdateDirection::Unknown));
+    }
+}
@@ -0,0 +1,214 @@
+// firmware_flash.rs - Checks around flashing a firmware image
+//
+// Before a firmware image reaches the device it must be a release the vault
+// knows: its hash, computed the way the device reports it, has to appear
+// under `hashes.firmware` in releases.json with the target version. Nothing
+// is written to the device until that holds and the device is in bootloader
+// mode.
+//
+// Failures are reported with whether the device is still intact. Anything
+// before the erase is retryable; once the erase has gone through, the device
+// has no firmware until an upload completes. It stays in the bootloader and
+// can be flashed again, but must not be unplugged while it is being written.
+
+use serde::Serialize;
+use crate::firmware_catalog::{image_hash, ImageKind};
+
+/// Progress of a flash, emitted so the frontend can show a progress bar
+pub const FLASH_PROGRESS_EVENT: &str = "firmware:update-progress";
+
+/// Stage of a firmware flash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum FlashPhase {
+    /// Image loaded and checked against releases.json
+    Verifying,
+    /// Erase and upload in progress; the device must stay connected
+    Writing,
+    /// The device accepted the image and is rebooting
+    Complete,
+}
+
+impl FlashPhase {
+    /// Overall progress at the start of the phase
+    pub fn percent(&self) -> u8 {
+        match self {
+            FlashPhase::Verifying => 0,
+            FlashPhase::Writing => 10,
+            FlashPhase::Complete => 100,
+        }
+    }
+}
+
+/// Why a firmware flash failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum FlashError {
+    /// The device is running firmware; it has to be restarted into the bootloader
+    NotInBootloader,
+    DeviceUnavailable { message: String },
+    /// No image for the version, or it could not be read
+    ImageUnavailable { message: String },
+    /// releases.json lists no hash for the version
+    UnknownRelease { version: String },
+    /// The image is not the release releases.json lists for the version
+    HashMismatch { version: String, computed: String, catalog_version: Option<String> },
+    /// The device refused the erase; its firmware is untouched
+    Rejected { message: String },
+    /// The transfer failed after the erase; the device has no usable firmware
+    TransferFailed { message: String },
+}
+
+impl FlashError {
+    /// Whether the device is as it was and the update can simply be retried
+    pub fn retryable(&self) -> bool {
+        !matches!(self, FlashError::TransferFailed { .. })
+    }
+
+    /// Encode for a `Result<_, String>` command error, with `retryable` and
+    /// `do_not_unplug` alongside the kind
+    pub fn to_json_string(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(fields) = value.as_object_mut() {
+            fields.insert("retryable".to_string(), self.retryable().into());
+            fields.insert("do_not_unplug".to_string(), (!self.retryable()).into());
+            fields.insert("summary".to_string(), self.to_string().into());
+        }
+        value.to_string()
+    }
+}
+
+impl std::fmt::Display for FlashError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlashError::NotInBootloader => write!(
+                f,
+                "The device is not in bootloader mode. Unplug it, hold down its button and plug it back in."
+            ),
+            FlashError::DeviceUnavailable { message } => write!(f, "{}", message),
+            FlashError::ImageUnavailable { message } => write!(f, "{}", message),
+            FlashError::UnknownRelease { version } => write!(f, "releases.json lists no firmware hash for {}", version),
+            FlashError::HashMismatch { version, catalog_version: Some(other), .. } => {
+                write!(f, "The image for {} is actually release {}", version, other)
+            }
+            FlashError::HashMismatch { version, .. } => {
+                write!(f, "The image for {} does not match any published release", version)
+            }
+            FlashError::Rejected { message } => write!(f, "The device refused the update: {}", message),
+            FlashError::TransferFailed { message } => write!(
+                f,
+                "Firmware transfer failed ({}). Do not unplug the device; it is in the bootloader and the update can be retried.",
+                message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FlashError {}
+
+fn normalize_version(version: &str) -> String {
+    format!("v{}", version.trim().trim_start_matches('v'))
+}
+
+/// Check `image` is the release `version` according to the `hashes` section
+/// of releases.json; returns the image hash
+pub fn verify_release_image(
+    catalog: &serde_json::Value,
+    kind: ImageKind,
+    version: &str,
+    image: &[u8],
+) -> Result<String, FlashError> {
+    let version = normalize_version(version);
+    let hashes = catalog["hashes"][kind.key()].as_object();
+    let listed = hashes
+        .map(|hashes| hashes.values().any(|v| v.as_str() == Some(version.as_str())))
+        .unwrap_or(false);
+    if !listed {
+        return Err(FlashError::UnknownRelease { version });
+    }
+
+    let computed = image_hash(kind, image).map_err(|message| FlashError::ImageUnavailable { message })?;
+    let catalog_version = hashes.and_then(|h| h.get(&computed)).and_then(|v| v.as_str());
+    if catalog_version == Some(version.as_str()) {
+        return Ok(computed);
+    }
+    Err(FlashError::HashMismatch { version, computed, catalog_version: catalog_version.map(str::to_string) })
+}
+
+/// Classify an error from the device queue's erase-and-upload. A Failure
+/// answer to the erase comes before anything is written; everything else
+/// may have left the device erased.
+pub fn classify_transfer_error(message: &str) -> FlashError {
+    match message.strip_prefix("Firmware erase failed: ") {
+        Some(reason) => FlashError::Rejected { message: reason.to_string() },
+        None => FlashError::TransferFailed { message: message.to_string() },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firmware_catalog::{firmware_image_hash, META_HEADER_LEN};
+
+    fn image(code: &[u8]) -> Vec<u8> {
+        let mut image = vec![0u8; META_HEADER_LEN];
+        image[..4].copy_from_slice(b"KPKY");
+        image[4..8].copy_from_slice(&(code.len() as u32).to_le_bytes());
+        image[8..11].copy_from_slice(&[1, 2, 3]);
+        image.extend_from_slice(code);
+        image
+    }
+
+    #[test]
+    fn test_verifies_image_against_catalog() {
+        let release = image(b"release 7.10.0 code");
+        let older = image(b"release 7.9.3 code");
+        let catalog = serde_json::json!({
+            "hashes": { "firmware": {
+                firmware_image_hash(&release).unwrap(): "v7.10.0",
+                firmware_image_hash(&older).unwrap(): "v7.9.3",
+            }}
+        });
+
+        let hash = verify_release_image(&catalog, ImageKind::Firmware, "7.10.0", &release).unwrap();
+        assert_eq!(hash, firmware_image_hash(&release).unwrap());
+
+        // A mislabeled image is caught and named
+        match verify_release_image(&catalog, ImageKind::Firmware, "v7.10.0", &older) {
+            Err(FlashError::HashMismatch { catalog_version, .. }) => assert_eq!(catalog_version.as_deref(), Some("v7.9.3")),
+            other => panic!("expected a hash mismatch, got {:?}", other),
+        }
+        let tampered = image(b"release 7.10.0 cod3");
+        assert!(matches!(
+            verify_release_image(&catalog, ImageKind::Firmware, "7.10.0", &tampered),
+            Err(FlashError::HashMismatch { catalog_version: None, .. })
+        ));
+        assert_eq!(
+            verify_release_image(&catalog, ImageKind::Firmware, "7.11.0", &release),
+            Err(FlashError::UnknownRelease { version: "v7.11.0".to_string() })
+        );
+        assert!(matches!(
+            verify_release_image(&catalog, ImageKind::Firmware, "7.10.0", b"not an image"),
+            Err(FlashError::ImageUnavailable { .. })
+        ));
+    }
+
+    #[test]
+    fn test_failures_say_whether_to_unplug() {
+        let rejected = classify_transfer_error("Firmware erase failed: Action cancelled by user");
+        assert_eq!(rejected, FlashError::Rejected { message: "Action cancelled by user".to_string() });
+        assert!(rejected.retryable());
+
+        let midway = classify_transfer_error("Error during firmware upload: timeout");
+        assert!(!midway.retryable());
+        let json: serde_json::Value = serde_json::from_str(&midway.to_json_string()).unwrap();
+        assert_eq!(json["kind"], "TransferFailed");
+        assert_eq!(json["do_not_unplug"], true);
+        assert_eq!(json["retryable"], false);
+
+        let json: serde_json::Value = serde_json::from_str(&FlashError::NotInBootloader.to_json_string()).unwrap();
+        assert_eq!((json["retryable"].as_bool(), json["do_not_unplug"].as_bool()), (Some(true), Some(false)));
+        assert!(json["summary"].as_str().unwrap().contains("bootloader"));
+    }
+}
@@ -0,0 +1,343 @@
+// frontload.rs - Reading a wallet's standard account xpubs after device setup
+//
+// Once a device is set up the vault reads the xpubs of the catalog account
+// paths (derivation_paths, loaded from default-paths.json) into wallet_xpubs,
+// so balances and receive addresses are available without asking the device
+// again. Progress is kept per network in frontload_progress, and for the
+// device as a whole in cache_metadata. Paths the wallet already has are
+// skipped, so a run cut short by an unplug continues from the first path it
+// had not stored.
+//
+// Accounts on networks the user has disabled are not read; enabling the
+// network again lets the next frontload pick them up.
+//
+// Xpubs are read through a closure, so the bookkeeping runs the same against a
+// device queue and canned keys.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use keepkey_db::{Database, FrontloadProgress, WalletXpubInput};
+use keepkey_rust::messages::{self, Message};
+use crate::paths::{format_derivation_path, network_family, parse_derivation_path, NetworkFamily};
+use crate::queue::{get_or_create_device_queue, DeviceQueueManager};
+use crate::utxo::UtxoScriptType;
+use crate::wallet_session::{active_wallet_fingerprint, identify_standard_wallet, WalletIdentification, WalletSessions};
+
+/// Emitted with a FrontloadProgress whenever a network's progress changes
+pub const FRONTLOAD_PROGRESS_EVENT: &str = "device:frontload-progress";
+
+/// An account whose xpub is read after setup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontloadAccount {
+    pub path: String,
+    pub label: String,
+    /// CAIP-19 asset id stored with the xpub
+    pub caip: String,
+    /// Firmware coin name for GetPublicKey
+    pub coin_name: String,
+    pub script_type: Option<UtxoScriptType>,
+}
+
+impl FrontloadAccount {
+    /// CAIP-2 network the account's progress is counted under
+    pub fn network_id(&self) -> &str {
+        self.caip.split('/').next().unwrap_or(&self.caip)
+    }
+}
+
+/// Accounts to read: every catalog path on an enabled, registered network
+/// the firmware can export an xpub for, keyed to the network's native asset
+pub async fn frontload_accounts(database: &Database) -> Result<Vec<FrontloadAccount>, String> {
+    let db_error = |e: keepkey_db::DatabaseError| format!("Database error: {}", e);
+    let disabled: HashSet<String> = database
+        .get_disabled_networks()
+        .await
+        .map_err(db_error)?
+        .into_iter()
+        .collect();
+
+    let mut accounts = Vec::new();
+    for path in database.get_derivation_paths(false).await.map_err(db_error)? {
+        let script_type = path.script_type.as_deref().map(UtxoScriptType::parse).transpose()?;
+        for network_id in path.networks.iter().filter(|n| !disabled.contains(*n)) {
+            let coin_name = match network_family(network_id) {
+                Ok(NetworkFamily::Utxo { coin_name }) => coin_name,
+                Ok(NetworkFamily::Evm { .. }) => "Ethereum".to_string(),
+                Err(_) => continue,
+            };
+            let Some(network) = database.get_network(network_id).await.map_err(db_error)? else {
+                continue;
+            };
+            accounts.push(FrontloadAccount {
+                path: format_derivation_path(&path.address_n),
+                label: path.note.clone().unwrap_or_else(|| path.symbol.clone()),
+                caip: network.native_asset_caip,
+                coin_name,
+                script_type,
+            });
+        }
+    }
+    Ok(accounts)
+}
+
+/// Store the xpub of every account in `accounts` the wallet does not have
+/// yet, network by network, calling `on_progress` as each network starts and
+/// each path is stored. A failed read marks its network failed and stops the
+/// run; the paths stored so far are kept.
+pub async fn frontload_wallet<R, Fut>(
+    database: &Database,
+    device_id: &str,
+    wallet_fingerprint: &str,
+    accounts: &[FrontloadAccount],
+    read_xpub: R,
+    on_progress: impl Fn(&FrontloadProgress),
+) -> Result<Vec<FrontloadProgress>, String>
+where
+    R: Fn(FrontloadAccount) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    let db_error = |e: keepkey_db::DatabaseError| format!("Database error: {}", e);
+    let stored: HashSet<(String, String)> = database
+        .get_wallet_xpubs(device_id, wallet_fingerprint)
+        .await
+        .map_err(db_error)?
+        .into_iter()
+        .map(|xpub| (xpub.path, xpub.caip))
+        .collect();
+    let is_stored = |account: &FrontloadAccount| stored.contains(&(account.path.clone(), account.caip.clone()));
+
+    let mut networks: Vec<&str> = Vec::new();
+    for account in accounts {
+        if !networks.contains(&account.network_id()) {
+            networks.push(account.network_id());
+        }
+    }
+
+    // The device-wide status in cache_metadata counts paths across networks
+    let total = accounts.len();
+    let mut stored_count = accounts.iter().filter(|a| is_stored(a)).count();
+    let percent = |stored: usize| if total == 0 { 100 } else { (stored * 100 / total) as i32 };
+    database
+        .set_frontload_status(device_id, "in_progress", percent(stored_count), None)
+        .await
+        .map_err(db_error)?;
+
+    let mut results = Vec::new();
+    for network_id in networks {
+        let network_accounts: Vec<FrontloadAccount> =
+            accounts.iter().filter(|a| a.network_id() == network_id).cloned().collect();
+        let done = network_accounts.iter().filter(|a| is_stored(a)).count() as u32;
+        let mut progress = database
+            .begin_frontload(device_id, network_id, network_accounts.len() as u32, done)
+            .await
+            .map_err(db_error)?;
+        on_progress(&progress);
+
+        for account in network_accounts.into_iter().filter(|a| !is_stored(a)) {
+            let pubkey = match read_xpub(account.clone()).await {
+                Ok(pubkey) => pubkey,
+                Err(e) => {
+                    let error = format!("Failed to read {} xpub: {}", account.path, e);
+                    database.fail_frontload(device_id, network_id, &error).await.map_err(db_error)?;
+                    database
+                        .set_frontload_status(device_id, "failed", percent(stored_count), Some(&error))
+                        .await
+                        .map_err(db_error)?;
+                    progress.status = "failed".to_string();
+                    progress.error_message = Some(error.clone());
+                    on_progress(&progress);
+                    return Err(error);
+                }
+            };
+            database
+                .save_wallet_xpub(&WalletXpubInput {
+                    device_id: device_id.to_string(),
+                    wallet_fingerprint: wallet_fingerprint.to_string(),
+                    path: account.path.clone(),
+                    label: account.label,
+                    caip: account.caip,
+                    pubkey,
+                })
+                .await
+                .map_err(db_error)?;
+            progress = database
+                .record_frontload_path(device_id, network_id, &account.path)
+                .await
+                .map_err(db_error)?;
+            stored_count += 1;
+            database
+                .set_frontload_status(device_id, "in_progress", percent(stored_count), None)
+                .await
+                .map_err(db_error)?;
+            on_progress(&progress);
+        }
+        results.push(progress);
+    }
+    database.set_frontload_status(device_id, "completed", 100, None).await.map_err(db_error)?;
+    Ok(results)
+}
+
+/// Devices with a frontload running, so setup completing and a reconnect
+/// never read the same device at once
+fn running_frontloads() -> &'static Mutex<HashSet<String>> {
+    static RUNNING: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    RUNNING.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+struct RunningFrontload(String);
+
+impl Drop for RunningFrontload {
+    fn drop(&mut self) {
+        running_frontloads().lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Frontload the standard accounts of the device's active wallet, opening
+/// the standard wallet first if no wallet session is active
+pub async fn frontload_device(
+    database: &Database,
+    sessions: &WalletSessions,
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+    on_progress: impl Fn(&FrontloadProgress),
+) -> Result<Vec<FrontloadProgress>, String> {
+    if !running_frontloads().lock().unwrap().insert(device_id.to_string()) {
+        return Err(format!("Device {} is already being frontloaded", device_id));
+    }
+    let _running = RunningFrontload(device_id.to_string());
+    crate::authenticity::require_verified_device(device_id)?;
+
+    let mut wallet_fingerprint = active_wallet_fingerprint(sessions, device_id).await;
+    if wallet_fingerprint.is_empty() {
+        wallet_fingerprint = match identify_standard_wallet(database, sessions, queue_manager, device_id).await? {
+            WalletIdentification::Opened(wallet) => wallet.wallet_fingerprint,
+            WalletIdentification::PinRequired(_) => return Err(format!("Device {} is locked; enter its PIN first", device_id)),
+        };
+    }
+    let queue = get_or_create_device_queue(device_id, queue_manager).await?;
+
+    let accounts = frontload_accounts(database).await?;
+    if accounts.is_empty() {
+        log::warn!("No frontload paths for device {}; are the derivation paths loaded?", device_id);
+    }
+
+    log::info!("📥 Frontloading xpubs of device {} wallet {}", device_id, wallet_fingerprint);
+    let read_xpub = |account: FrontloadAccount| {
+        let queue = queue.clone();
+        async move {
+            let request = Message::GetPublicKey(messages::GetPublicKey {
+                address_n: parse_derivation_path(&account.path)?,
+                ecdsa_curve_name: Some("secp256k1".to_string()),
+                show_display: Some(false),
+                coin_name: Some(account.coin_name),
+                script_type: account.script_type.map(|s| s.input_script_type()),
+            });
+            match queue.send_raw(request, true).await.map_err(|e| e.to_string())? {
+                Message::PublicKey(public_key) => public_key.xpub.ok_or_else(|| "No xpub in response".to_string()),
+                // Locked again since the wallet opened; it resumes once the PIN is entered
+                Message::PinMatrixRequest(_) => Err("the device is locked; enter its PIN first".to_string()),
+                Message::Failure(f) => Err(format!("Device refused: {}", f.message())),
+                other => Err(format!("Unexpected response: {:?}", other.message_type())),
+            }
+        }
+    };
+    let progress = frontload_wallet(
+        database,
+        device_id,
+        &wallet_fingerprint,
+        &accounts,
+        read_xpub,
+        on_progress,
+    ).await?;
+    log::info!("✅ Frontloaded xpubs of device {}", device_id);
+    Ok(progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93";
+
+    fn xpub_for(account: FrontloadAccount) -> String {
+        format!("xpub-{}", account.path)
+    }
+
+    async fn catalog_db() -> Database {
+        let db = Database::new_in_memory().await.unwrap();
+        db.seed_default_assets().await.unwrap();
+        db.seed_default_networks().await.unwrap();
+        db.load_default_derivation_paths().await.unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_accounts_come_from_the_path_catalog() {
+        let db = catalog_db().await;
+        let accounts = frontload_accounts(&db).await.unwrap();
+        let paths: Vec<_> = accounts.iter().map(|a| (a.path.as_str(), a.coin_name.as_str(), a.script_type)).collect();
+        assert_eq!(paths, [
+            ("m/44'/0'/0'", "Bitcoin", Some(UtxoScriptType::P2pkh)),
+            ("m/84'/0'/0'", "Bitcoin", Some(UtxoScriptType::P2wpkh)),
+            ("m/49'/0'/0'", "Bitcoin", Some(UtxoScriptType::P2shP2wpkh)),
+            ("m/44'/60'/0'", "Ethereum", None),
+        ]);
+
+        db.set_network_enabled(BTC, false).await.unwrap();
+        let accounts = frontload_accounts(&db).await.unwrap();
+        assert_eq!(accounts.iter().map(|a| a.network_id()).collect::<Vec<_>>(), ["eip155:1"]);
+    }
+
+    #[tokio::test]
+    async fn test_frontload_resumes_after_a_failed_read() {
+        let db = catalog_db().await;
+        db.register_device("kk1", None, None).await.unwrap();
+        let accounts = frontload_accounts(&db).await.unwrap();
+        let updates = RefCell::new(Vec::new());
+        let record = |p: &FrontloadProgress| updates.borrow_mut().push((p.network_id.clone(), p.paths_completed, p.status.clone()));
+
+        // Unplugged while reading the native segwit account
+        let result = frontload_wallet(&db, "kk1", "fp1", &accounts, |account| async move {
+            if account.path == "m/84'/0'/0'" { Err("Device disconnected".to_string()) } else { Ok(xpub_for(account)) }
+        }, record).await;
+        assert!(result.unwrap_err().contains("m/84'/0'/0'"));
+        assert!(db.has_unfinished_frontload("kk1").await.unwrap());
+        let status = db.get_frontload_status("kk1").await.unwrap().unwrap();
+        assert_eq!((status.frontload_status.as_deref(), status.frontload_progress), (Some("failed"), 25));
+        assert_eq!(db.get_wallet_xpubs("kk1", "fp1").await.unwrap().len(), 1);
+
+        // Reconnected: only the missing paths are read
+        let reads = RefCell::new(Vec::new());
+        let progress = frontload_wallet(&db, "kk1", "fp1", &accounts, |account| {
+            reads.borrow_mut().push(account.path.clone());
+            async move { Ok(xpub_for(account)) }
+        }, record).await.unwrap();
+        assert_eq!(*reads.borrow(), ["m/84'/0'/0'", "m/49'/0'/0'", "m/44'/60'/0'"]);
+        assert_eq!(progress.iter().map(|p| (p.paths_completed, p.paths_total)).collect::<Vec<_>>(), [(3, 3), (1, 1)]);
+        assert!(!db.has_unfinished_frontload("kk1").await.unwrap());
+        let status = db.get_frontload_status("kk1").await.unwrap().unwrap();
+        assert_eq!((status.frontload_status.as_deref(), status.frontload_progress), (Some("completed"), 100));
+        assert!(status.error_message.is_none() && status.last_frontload.is_some());
+
+        assert_eq!(updates.borrow()[..3], [
+            (BTC.to_string(), 0, "in_progress".to_string()),
+            (BTC.to_string(), 1, "in_progress".to_string()),
+            (BTC.to_string(), 1, "failed".to_string()),
+        ]);
+        assert_eq!(updates.borrow().last().unwrap(), &("eip155:1".to_string(), 1, "completed".to_string()));
+
+        let xpubs = db.get_wallet_xpubs("kk1", "fp1").await.unwrap();
+        let eth = xpubs.iter().find(|x| x.path == "m/44'/60'/0'").unwrap();
+        assert_eq!((eth.label.as_str(), eth.caip.as_str()), ("Ethereum account 0", "eip155:1/slip44:60"));
+
+        // Another wallet on the same device is read in full
+        let reads = RefCell::new(0);
+        frontload_wallet(&db, "kk1", "fp2", &accounts, |account| {
+            *reads.borrow_mut() += 1;
+            async move { Ok(xpub_for(account)) }
+        }, |_| {}).await.unwrap();
+        assert_eq!(*reads.borrow(), 4);
+    }
+}
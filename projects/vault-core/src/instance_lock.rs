@@ -0,0 +1,180 @@
+// instance_lock.rs - Single-instance lock so only one process talks to USB at a time
+//
+// The GUI vault and kkvault-cli both acquire the same lock file before touching
+// devices. Two processes claiming the same KeepKey interleave HID/WebUSB frames
+// and corrupt each other's sessions, so the second process must refuse to start.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use keepkey_db::data_dir::DataDirSource;
+
+/// Owner name used by the Tauri application
+pub const OWNER_VAULT: &str = "keepkey-vault";
+/// Owner name used by the command-line interface
+pub const OWNER_CLI: &str = "kkvault-cli";
+
+/// Contents of the lock file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LockHolder {
+    pub pid: u32,
+    pub owner: String,
+    pub acquired_at: i64,
+}
+
+/// RAII guard - the lock file is removed when dropped
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: PathBuf,
+    holder: LockHolder,
+}
+
+/// Default lock file location. It stays in ~/.keepkey when the data directory
+/// is relocated, so every launch checks the same file; a KEEPKEY_DATA_DIR
+/// override gets its own lock next to its database.
+pub fn default_lock_path() -> PathBuf {
+    let location = keepkey_db::data_dir::resolve_data_dir();
+    let dir = match location.source {
+        DataDirSource::Env => location.path,
+        _ => keepkey_db::data_dir::default_data_dir(),
+    };
+    dir.join("vault.lock")
+}
+
+impl InstanceLock {
+    /// Acquire the lock at the default location
+    pub fn acquire(owner: &str) -> Result<Self, LockHolder> {
+        Self::acquire_at(&default_lock_path(), owner)
+    }
+
+    /// Acquire the lock at a specific path.
+    ///
+    /// Returns the current holder if another live process owns the lock.
+    /// Locks left behind by crashed processes are reclaimed.
+    pub fn acquire_at(path: &Path, owner: &str) -> Result<Self, LockHolder> {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        let holder = LockHolder {
+            pid: std::process::id(),
+            owner: owner.to_string(),
+            acquired_at: keepkey_db::Database::current_timestamp(),
+        };
+
+        // Two attempts: the second one runs after reclaiming a stale lock
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(mut file) => {
+                    let contents = serde_json::to_string(&holder).unwrap_or_default();
+                    if let Err(e) = file.write_all(contents.as_bytes()) {
+                        log::warn!("Failed to write instance lock {:?}: {}", path, e);
+                    }
+                    log::info!("🔒 Acquired instance lock {:?} as {}", path, owner);
+                    return Ok(Self { path: path.to_path_buf(), holder });
+                }
+                Err(_) => {
+                    match Self::read_holder(path) {
+                        Some(existing) if existing.pid != holder.pid && process_alive(existing.pid) => {
+                            return Err(existing);
+                        }
+                        existing => {
+                            log::warn!("Reclaiming stale instance lock {:?} (previous holder: {:?})", path, existing);
+                            let _ = fs::remove_file(path);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Lost a race with another process that reclaimed the same stale lock
+        Err(Self::read_holder(path).unwrap_or(LockHolder {
+            pid: 0,
+            owner: "unknown".to_string(),
+            acquired_at: 0,
+        }))
+    }
+
+    /// Read the current holder of a lock file, if any
+    pub fn read_holder(path: &Path) -> Option<LockHolder> {
+        let contents = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn holder(&self) -> &LockHolder {
+        &self.holder
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Only remove the file if it still belongs to us
+        if Self::read_holder(&self.path).map(|h| h.pid) == Some(self.holder.pid) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn process_alive(pid: u32) -> bool {
+    Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(true)
+}
+
+#[cfg(windows)]
+fn process_alive(pid: u32) -> bool {
+    std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
+        .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_second_acquire_reports_holder() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("vault.lock");
+
+        let lock = InstanceLock::acquire_at(&path, OWNER_VAULT).unwrap();
+        // Same pid re-acquiring is treated as stale, so fake a live foreign holder
+        let foreign = LockHolder { pid: 1, owner: OWNER_VAULT.to_string(), acquired_at: 0 };
+        fs::write(&path, serde_json::to_string(&foreign).unwrap()).unwrap();
+
+        let err = InstanceLock::acquire_at(&path, OWNER_CLI).unwrap_err();
+        assert_eq!(err.owner, OWNER_VAULT);
+        drop(lock);
+        // Drop must not delete a lock that now belongs to someone else
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_stale_lock_is_reclaimed() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("vault.lock");
+        fs::write(&path, "not json").unwrap();
+
+        let lock = InstanceLock::acquire_at(&path, OWNER_CLI).unwrap();
+        assert_eq!(lock.holder().owner, OWNER_CLI);
+        drop(lock);
+        assert!(!path.exists());
+    }
+}
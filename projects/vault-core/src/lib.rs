@@ -0,0 +1,64 @@
+//! Shared, UI-agnostic vault backend logic.
+//!
+//! Both the Tauri application and `kkvault-cli` link against this crate so the
+//! device queue wiring and process coordination live in exactly one place.
+
+pub mod accessibility;
+pub mod address_scan;
+pub mod api_pairing;
+pub mod app_lock;
+pub mod asset_capabilities;
+pub mod authenticity;
+pub mod bitcoin_tx;
+pub mod broadcast;
+pub mod btc_message;
+pub mod clock;
+pub mod coin_select;
+pub mod confirmation;
+pub mod device_export;
+pub mod device_flow;
+pub mod device_log;
+pub mod device_label;
+pub mod device_session;
+pub mod endpoints;
+pub mod environment;
+pub mod erc20;
+pub mod event_history;
+pub mod event_router;
+pub mod fault_log;
+pub mod features;
+pub mod fees;
+pub mod firmware_catalog;
+pub mod firmware_downgrade;
+pub mod firmware_flash;
+pub mod frontload;
+pub mod instance_lock;
+pub mod maintenance;
+pub mod observer;
+pub mod outbox;
+pub mod passphrase;
+pub mod paths;
+pub mod portfolio_sync;
+pub mod power;
+pub mod protocol;
+pub mod queue;
+pub mod quick_actions;
+pub mod request_queue;
+pub mod secure_notes;
+pub mod self_test;
+pub mod signed_message;
+pub mod signing_origin;
+pub mod spendable;
+pub mod startup;
+pub mod token_approvals;
+pub mod tx_tracker;
+pub mod tray;
+pub mod units;
+pub mod utxo;
+pub mod wallet_import;
+pub mod wallet_session;
+pub mod warmup;
+
+pub use features::{convert_features_to_device_features, get_device_features, refresh_stored_features};
+pub use instance_lock::{InstanceLock, LockHolder};
+pub use queue::{get_or_create_device_queue, DeviceQueueManager};
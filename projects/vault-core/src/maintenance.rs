@@ -0,0 +1,399 @@
+// maintenance.rs - Maintenance mode for heavy, whole-vault operations
+//
+// Compaction, schema migrations, data directory moves and backup restores
+// rewrite the database underneath everything else. They run inside a
+// maintenance session: entering one stops new device operations, waits (up
+// to a timeout) for the ones in flight to finish, and from then on every
+// command outside EXEMPT_COMMANDS is refused with a structured
+// MaintenanceInProgress error carrying the reason. The scheduler skips its
+// jobs while a session is active.
+//
+// A session lasts as long as its MaintenanceGuard, so an operation that
+// fails or panics still ends maintenance when the guard unwinds. Sessions
+// never overlap: a second request waits for the first guard to drop.
+//
+// Device operations that must not be cut off (flashing, signing, wipes)
+// hold a DeviceOperationGuard, which is what entering maintenance waits on.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use tokio::sync::{Notify, OwnedMutexGuard};
+
+/// Emitted when a maintenance session begins
+pub const MAINTENANCE_STARTED_EVENT: &str = "maintenance:started";
+/// Emitted when a maintenance session ends, including by an unwinding panic
+pub const MAINTENANCE_ENDED_EVENT: &str = "maintenance:ended";
+
+/// How long entering maintenance waits for device operations in flight
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Commands that stay available during maintenance. The heavy operations
+/// themselves are listed so a second one queues behind the running session
+/// instead of being refused.
+pub const EXEMPT_COMMANDS: &[&str] = &[
+    "get_maintenance_state",
+    "enter_maintenance",
+    "exit_maintenance",
+    "compact_database",
+    "apply_migrations_now",
+    "relocate_data_directory",
+    "restore_from_backup",
+    "frontend_ready",
+    "frontend_reset",
+    "subscribe_events",
+    "ack_event",
+    "report_activity",
+    "get_app_lock_state",
+    "get_system_diagnostics",
+    "get_startup_report",
+    "get_device_fault_log",
+];
+
+/// Structured error for commands refused by maintenance mode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum MaintenanceError {
+    MaintenanceInProgress { reason: String, command: String },
+    /// Device operations were still running when the drain timeout expired
+    DrainTimeout { reason: String, in_flight: u32 },
+}
+
+impl MaintenanceError {
+    /// Encode for a `Result<_, String>` command error
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl std::fmt::Display for MaintenanceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MaintenanceError::MaintenanceInProgress { reason, command } => {
+                write!(f, "Maintenance in progress ({}); {} is unavailable until it finishes", reason, command)
+            }
+            MaintenanceError::DrainTimeout { reason, in_flight } => {
+                write!(f, "Could not start {}: {} device operation(s) still running", reason, in_flight)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MaintenanceError {}
+
+/// Maintenance state as shown to the frontend and in diagnostics
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct MaintenanceStatus {
+    pub active: bool,
+    pub reason: Option<String>,
+    /// Unix seconds the current session started
+    pub started_at: Option<i64>,
+    pub device_operations_in_flight: u32,
+    /// Maintenance requests waiting for the current session to end
+    pub queued_requests: u32,
+}
+
+/// Which way a session changed, passed to the controller's listener
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaintenanceTransition {
+    Started,
+    Ended,
+}
+
+type Listener = Box<dyn Fn(MaintenanceTransition, &str) + Send + Sync>;
+
+#[derive(Debug, Default)]
+struct SessionState {
+    reason: Option<String>,
+    started_at: Option<i64>,
+    operations_in_flight: u32,
+    queued: u32,
+}
+
+/// Backend state of maintenance mode, held as Tauri app state
+pub struct MaintenanceController {
+    state: Mutex<SessionState>,
+    /// Held by the active session; tokio's mutex hands it out in request order
+    session: Arc<tokio::sync::Mutex<()>>,
+    drained: Notify,
+    listener: Listener,
+}
+
+impl std::fmt::Debug for MaintenanceController {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MaintenanceController").field("state", &self.state).finish()
+    }
+}
+
+impl MaintenanceController {
+    /// `listener` is told about every session start and end, with its reason
+    pub fn new<F>(listener: F) -> Self
+    where
+        F: Fn(MaintenanceTransition, &str) + Send + Sync + 'static,
+    {
+        Self {
+            state: Mutex::new(SessionState::default()),
+            session: Arc::new(tokio::sync::Mutex::new(())),
+            drained: Notify::new(),
+            listener: Box::new(listener),
+        }
+    }
+
+    /// Lock the state, taking it back from a poisoned lock like AppLock
+    fn state(&self) -> MutexGuard<'_, SessionState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state().reason.is_some()
+    }
+
+    /// Refuse `command` during maintenance unless it is exempt
+    pub fn check_command(&self, command: &str) -> Result<(), MaintenanceError> {
+        match &self.state().reason {
+            Some(reason) if !EXEMPT_COMMANDS.contains(&command) => Err(MaintenanceError::MaintenanceInProgress {
+                reason: reason.clone(),
+                command: command.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Register a device operation that maintenance must wait for. Refused
+    /// once a session has started.
+    pub fn device_operation(self: &Arc<Self>, command: &str) -> Result<DeviceOperationGuard, MaintenanceError> {
+        let mut state = self.state();
+        if let Some(reason) = &state.reason {
+            return Err(MaintenanceError::MaintenanceInProgress {
+                reason: reason.clone(),
+                command: command.to_string(),
+            });
+        }
+        state.operations_in_flight += 1;
+        Ok(DeviceOperationGuard { controller: self.clone() })
+    }
+
+    /// Start a maintenance session, waiting first for any earlier session to
+    /// end and then up to `drain_timeout` for device operations in flight.
+    /// Maintenance lasts until the returned guard is dropped.
+    pub async fn enter(self: &Arc<Self>, reason: &str, drain_timeout: Duration) -> Result<MaintenanceGuard, MaintenanceError> {
+        self.state().queued += 1;
+        let session = self.session.clone().lock_owned().await;
+        {
+            let mut state = self.state();
+            state.queued -= 1;
+            state.reason = Some(reason.to_string());
+            state.started_at = Some(unix_now());
+        }
+
+        if tokio::time::timeout(drain_timeout, self.wait_for_operations()).await.is_err() {
+            let in_flight = {
+                let mut state = self.state();
+                state.reason = None;
+                state.started_at = None;
+                state.operations_in_flight
+            };
+            return Err(MaintenanceError::DrainTimeout { reason: reason.to_string(), in_flight });
+        }
+
+        (self.listener)(MaintenanceTransition::Started, reason);
+        Ok(MaintenanceGuard {
+            controller: self.clone(),
+            reason: reason.to_string(),
+            _session: session,
+        })
+    }
+
+    async fn wait_for_operations(&self) {
+        loop {
+            let drained = self.drained.notified();
+            tokio::pin!(drained);
+            // Register before checking so a guard dropped in between still wakes us
+            drained.as_mut().enable();
+            if self.state().operations_in_flight == 0 {
+                return;
+            }
+            drained.await;
+        }
+    }
+
+    pub fn status(&self) -> MaintenanceStatus {
+        let state = self.state();
+        MaintenanceStatus {
+            active: state.reason.is_some(),
+            reason: state.reason.clone(),
+            started_at: state.started_at,
+            device_operations_in_flight: state.operations_in_flight,
+            queued_requests: state.queued,
+        }
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// Keeps a maintenance session active; dropping it (or unwinding past it)
+/// ends maintenance and lets the next queued request in
+pub struct MaintenanceGuard {
+    controller: Arc<MaintenanceController>,
+    reason: String,
+    _session: OwnedMutexGuard<()>,
+}
+
+impl MaintenanceGuard {
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl std::fmt::Debug for MaintenanceGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MaintenanceGuard").field("reason", &self.reason).finish()
+    }
+}
+
+impl Drop for MaintenanceGuard {
+    fn drop(&mut self) {
+        {
+            let mut state = self.controller.state();
+            state.reason = None;
+            state.started_at = None;
+        }
+        (self.controller.listener)(MaintenanceTransition::Ended, &self.reason);
+        // The session lock is released after this, once the fields drop
+    }
+}
+
+/// A device operation that entering maintenance waits for
+#[derive(Debug)]
+pub struct DeviceOperationGuard {
+    controller: Arc<MaintenanceController>,
+}
+
+impl Drop for DeviceOperationGuard {
+    fn drop(&mut self) {
+        let mut state = self.controller.state();
+        state.operations_in_flight = state.operations_in_flight.saturating_sub(1);
+        if state.operations_in_flight == 0 {
+            self.controller.drained.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Transitions = Arc<Mutex<Vec<(MaintenanceTransition, String)>>>;
+
+    fn controller() -> (Arc<MaintenanceController>, Transitions) {
+        let transitions: Transitions = Arc::new(Mutex::new(Vec::new()));
+        let recorded = transitions.clone();
+        let controller = MaintenanceController::new(move |transition, reason| {
+            recorded.lock().unwrap().push((transition, reason.to_string()));
+        });
+        (Arc::new(controller), transitions)
+    }
+
+    #[tokio::test]
+    async fn test_blocks_commands_during_maintenance() {
+        let (controller, transitions) = controller();
+        assert!(controller.check_command("build_and_sign_bitcoin_tx").is_ok());
+
+        let guard = controller.enter("Compacting database", DEFAULT_DRAIN_TIMEOUT).await.unwrap();
+        let err = controller.check_command("build_and_sign_bitcoin_tx").unwrap_err();
+        assert_eq!(err, MaintenanceError::MaintenanceInProgress {
+            reason: "Compacting database".to_string(),
+            command: "build_and_sign_bitcoin_tx".to_string(),
+        });
+        let json: serde_json::Value = serde_json::from_str(&err.to_json_string()).unwrap();
+        assert_eq!(json["kind"], "MaintenanceInProgress");
+        assert_eq!(json["reason"], "Compacting database");
+        for command in ["get_maintenance_state", "get_system_diagnostics", "compact_database"] {
+            assert!(controller.check_command(command).is_ok(), "{} should be exempt", command);
+        }
+        assert!(controller.device_operation("wipe_device").is_err());
+        assert_eq!(controller.status().reason.as_deref(), Some("Compacting database"));
+
+        drop(guard);
+        assert!(controller.check_command("build_and_sign_bitcoin_tx").is_ok());
+        assert!(!controller.status().active);
+        assert_eq!(*transitions.lock().unwrap(), vec![
+            (MaintenanceTransition::Started, "Compacting database".to_string()),
+            (MaintenanceTransition::Ended, "Compacting database".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_device_operations() {
+        let (controller, _) = controller();
+        let operation = controller.device_operation("update_device_firmware").unwrap();
+
+        let timed_out = controller.enter("Restoring backup", Duration::from_millis(20)).await.unwrap_err();
+        assert_eq!(timed_out, MaintenanceError::DrainTimeout { reason: "Restoring backup".to_string(), in_flight: 1 });
+        assert!(!controller.is_active());
+
+        let entering = tokio::spawn({
+            let controller = controller.clone();
+            async move { controller.enter("Restoring backup", DEFAULT_DRAIN_TIMEOUT).await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!entering.is_finished());
+        drop(operation);
+        entering.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_panic_ends_maintenance() {
+        let (controller, transitions) = controller();
+        let task = tokio::spawn({
+            let controller = controller.clone();
+            async move {
+                let _guard = controller.enter("Migrating schema", DEFAULT_DRAIN_TIMEOUT).await.unwrap();
+                panic!("migration failed");
+            }
+        });
+        assert!(task.await.unwrap_err().is_panic());
+
+        assert!(!controller.is_active());
+        assert!(controller.check_command("build_and_sign_bitcoin_tx").is_ok());
+        assert_eq!(transitions.lock().unwrap().last().unwrap().0, MaintenanceTransition::Ended);
+        // The session lock was released too
+        let guard = tokio::time::timeout(Duration::from_secs(1), controller.enter("Compacting database", DEFAULT_DRAIN_TIMEOUT))
+            .await
+            .expect("session lock was left held")
+            .unwrap();
+        assert_eq!(guard.reason(), "Compacting database");
+    }
+
+    #[tokio::test]
+    async fn test_second_request_queues() {
+        let (controller, transitions) = controller();
+        let first = controller.enter("Compacting database", DEFAULT_DRAIN_TIMEOUT).await.unwrap();
+
+        let second = tokio::spawn({
+            let controller = controller.clone();
+            async move {
+                let _guard = controller.enter("Moving data directory", DEFAULT_DRAIN_TIMEOUT).await.unwrap();
+                controller.status()
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!second.is_finished());
+        let status = controller.status();
+        assert_eq!((status.reason.as_deref(), status.queued_requests), (Some("Compacting database"), 1));
+
+        drop(first);
+        let status = second.await.unwrap();
+        assert_eq!((status.reason.as_deref(), status.queued_requests), (Some("Moving data directory"), 0));
+        let order: Vec<_> = transitions.lock().unwrap().iter().map(|(t, r)| (*t, r.clone())).collect();
+        assert_eq!(order, vec![
+            (MaintenanceTransition::Started, "Compacting database".to_string()),
+            (MaintenanceTransition::Ended, "Compacting database".to_string()),
+            (MaintenanceTransition::Started, "Moving data directory".to_string()),
+            (MaintenanceTransition::Ended, "Moving data directory".to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,328 @@
+// observer.rs - Read-only observer mode for devices held by another process
+//
+// When kkcli or a second vault has claimed a KeepKey, the device stays in the
+// registry with its cached features and portfolio, but every device-interactive
+// command is refused with a structured ObserverMode error instead of waiting on
+// a transport that will not open. A cheap re-claim probe runs every 30s and
+// upgrades the device back to fully managed once the other holder lets go.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use serde::Serialize;
+use keepkey_rust::claim::{ClaimStatus, DeviceClaimedElsewhere};
+
+/// How often an observed device is probed for the conflict clearing
+pub const RECLAIM_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Whether the vault drives a device or only shows its cached state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ManagementMode {
+    Managed,
+    Observed { detail: String, since: i64 },
+}
+
+/// Structured error for device commands refused in observer mode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum DeviceAccessError {
+    ObserverMode { device_id: String, detail: String, since: i64 },
+}
+
+impl DeviceAccessError {
+    /// Encode for a `Result<_, String>` command error
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl std::fmt::Display for DeviceAccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceAccessError::ObserverMode { device_id, detail, .. } => write!(
+                f,
+                "Device {} is in use by another application and is read-only until it is released ({})",
+                device_id, detail
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DeviceAccessError {}
+
+#[derive(Debug, Clone)]
+struct ObservedDevice {
+    detail: String,
+    since: i64,
+}
+
+/// Devices currently in observer mode, keyed by device id
+#[derive(Debug, Default)]
+pub struct ObserverRegistry {
+    devices: Mutex<HashMap<String, ObservedDevice>>,
+}
+
+impl ObserverRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Put a device in observer mode; returns true if it was managed before
+    pub fn mark_observed(&self, device_id: &str, detail: &str, now: i64) -> bool {
+        let mut devices = self.devices.lock().unwrap();
+        match devices.get_mut(device_id) {
+            Some(existing) => {
+                existing.detail = detail.to_string();
+                false
+            }
+            None => {
+                devices.insert(device_id.to_string(), ObservedDevice { detail: detail.to_string(), since: now });
+                true
+            }
+        }
+    }
+
+    /// Return a device to managed mode; returns true if it was observed
+    pub fn restore(&self, device_id: &str) -> bool {
+        self.devices.lock().unwrap().remove(device_id).is_some()
+    }
+
+    pub fn mode(&self, device_id: &str) -> ManagementMode {
+        match self.devices.lock().unwrap().get(device_id) {
+            Some(observed) => ManagementMode::Observed { detail: observed.detail.clone(), since: observed.since },
+            None => ManagementMode::Managed,
+        }
+    }
+
+    pub fn is_observed(&self, device_id: &str) -> bool {
+        self.devices.lock().unwrap().contains_key(device_id)
+    }
+
+    /// Gate for device-interactive commands
+    pub fn check_managed(&self, device_id: &str) -> Result<(), DeviceAccessError> {
+        match self.mode(device_id) {
+            ManagementMode::Managed => Ok(()),
+            ManagementMode::Observed { detail, since } => Err(DeviceAccessError::ObserverMode {
+                device_id: device_id.to_string(),
+                detail,
+                since,
+            }),
+        }
+    }
+
+    /// Switch to observer mode if a device command failed because another
+    /// process holds the device; returns true if the error was such a conflict
+    pub fn note_device_error(&self, device_id: &str, error: &anyhow::Error, now: i64) -> bool {
+        match error.downcast_ref::<DeviceClaimedElsewhere>() {
+            Some(conflict) => {
+                self.mark_observed(device_id, &conflict.detail, now);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Process-wide registry consulted by `get_or_create_device_queue`
+pub fn observer_registry() -> &'static ObserverRegistry {
+    static REGISTRY: OnceLock<ObserverRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(ObserverRegistry::new)
+}
+
+/// Seconds since the Unix epoch, for `ManagementMode::Observed::since`
+pub fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// How a re-claim loop ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReclaimOutcome {
+    /// The other holder released the device; it is managed again
+    Restored,
+    /// The device was unplugged while observed
+    Disconnected,
+    /// Observer mode was cleared elsewhere (e.g. on disconnect)
+    Cancelled,
+}
+
+/// Probe an observed device every `interval` until it can be claimed, is
+/// unplugged, or leaves observer mode by other means. Failures other than a
+/// conflict keep the device observed and are retried on the next tick.
+pub async fn reclaim_until_released<P, Fut>(
+    registry: &ObserverRegistry,
+    device_id: &str,
+    interval: Duration,
+    mut probe: P,
+) -> ReclaimOutcome
+where
+    P: FnMut() -> Fut,
+    Fut: Future<Output = ClaimStatus>,
+{
+    loop {
+        tokio::time::sleep(interval).await;
+        if !registry.is_observed(device_id) {
+            return ReclaimOutcome::Cancelled;
+        }
+        match probe().await {
+            ClaimStatus::Available => {
+                registry.restore(device_id);
+                return ReclaimOutcome::Restored;
+            }
+            ClaimStatus::NotPresent => {
+                registry.restore(device_id);
+                return ReclaimOutcome::Disconnected;
+            }
+            ClaimStatus::HeldElsewhere { detail } => {
+                registry.mark_observed(device_id, &detail, unix_now());
+            }
+            ClaimStatus::Failed { detail } => {
+                log::debug!("Re-claim probe for {} failed: {}", device_id, detail);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const TICK: Duration = Duration::from_millis(1);
+
+    /// Stand-in for another process holding the transport: the device reports
+    /// as held until the holder releases it after `held_for` probes
+    struct MockHolder {
+        probes: AtomicUsize,
+        held_for: usize,
+    }
+
+    impl MockHolder {
+        fn probe(&self) -> ClaimStatus {
+            if self.probes.fetch_add(1, Ordering::SeqCst) < self.held_for {
+                ClaimStatus::HeldElsewhere { detail: "Resource busy".to_string() }
+            } else {
+                ClaimStatus::Available
+            }
+        }
+    }
+
+    #[test]
+    fn test_observed_device_refuses_commands() {
+        let registry = ObserverRegistry::new();
+        assert!(registry.check_managed("kk1").is_ok());
+
+        let conflict = anyhow::Error::new(DeviceClaimedElsewhere {
+            device_id: "kk1".to_string(),
+            detail: "Resource busy".to_string(),
+        });
+        assert!(registry.note_device_error("kk1", &conflict, 1_700_000_000));
+        assert!(!registry.note_device_error("kk2", &anyhow::anyhow!("Communication Timeout"), 0));
+        assert!(!registry.is_observed("kk2"));
+
+        let err = registry.check_managed("kk1").unwrap_err();
+        let json: serde_json::Value = serde_json::from_str(&err.to_json_string()).unwrap();
+        assert_eq!(json["kind"], "ObserverMode");
+        assert_eq!(json["since"], 1_700_000_000);
+
+        // A second conflict keeps the original start time
+        assert!(!registry.mark_observed("kk1", "in use", 1_800_000_000));
+        assert_eq!(
+            registry.mode("kk1"),
+            ManagementMode::Observed { detail: "in use".to_string(), since: 1_700_000_000 }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_restores_when_holder_releases() {
+        let registry = ObserverRegistry::new();
+        registry.mark_observed("kk1", "Resource busy", 0);
+        let holder = Arc::new(MockHolder { probes: AtomicUsize::new(0), held_for: 2 });
+
+        let h = holder.clone();
+        let outcome = reclaim_until_released(&registry, "kk1", TICK, || {
+            let h = h.clone();
+            async move { h.probe() }
+        })
+        .await;
+
+        assert_eq!(outcome, ReclaimOutcome::Restored);
+        assert_eq!(holder.probes.load(Ordering::SeqCst), 3);
+        assert_eq!(registry.mode("kk1"), ManagementMode::Managed);
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_stops_on_unplug_or_cancel() {
+        let registry = ObserverRegistry::new();
+        registry.mark_observed("kk1", "Resource busy", 0);
+        let outcome = reclaim_until_released(&registry, "kk1", TICK, || async { ClaimStatus::NotPresent }).await;
+        assert_eq!(outcome, ReclaimOutcome::Disconnected);
+        assert!(!registry.is_observed("kk1"));
+
+        let outcome = reclaim_until_released(&registry, "kk1", TICK, || async { ClaimStatus::Available }).await;
+        assert_eq!(outcome, ReclaimOutcome::Cancelled);
+    }
+
+    /// Port the holder process binds, passed through the environment
+    const HOLDER_PORT_VAR: &str = "KK_OBSERVER_TEST_HOLDER_PORT";
+
+    /// The other process in `test_reclaim_after_another_process_exits`:
+    /// holds the port for a moment and exits. Does nothing when run normally.
+    #[test]
+    fn holder_process() {
+        let Ok(port) = std::env::var(HOLDER_PORT_VAR) else {
+            return;
+        };
+        let _held = std::net::TcpListener::bind(("127.0.0.1", port.parse::<u16>().unwrap())).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+    }
+
+    /// Claim the port the way the queue claims a device's interface
+    fn probe_port(port: u16) -> ClaimStatus {
+        match std::net::TcpListener::bind(("127.0.0.1", port)) {
+            Ok(_) => ClaimStatus::Available,
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => ClaimStatus::HeldElsewhere { detail: e.to_string() },
+            Err(e) => ClaimStatus::Failed { detail: e.to_string() },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_after_another_process_exits() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let mut holder = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "observer::tests::holder_process", "--quiet"])
+            .env(HOLDER_PORT_VAR, port.to_string())
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        while probe_port(port) == ClaimStatus::Available {
+            assert!(started.elapsed() < Duration::from_secs(10), "holder process never claimed the port");
+            tokio::time::sleep(TICK).await;
+        }
+        let registry = ObserverRegistry::new();
+        registry.mark_observed("kk1", "Resource busy", unix_now());
+
+        let held_probes = AtomicUsize::new(0);
+        let outcome = reclaim_until_released(&registry, "kk1", Duration::from_millis(20), || {
+            let status = probe_port(port);
+            if matches!(status, ClaimStatus::HeldElsewhere { .. }) {
+                held_probes.fetch_add(1, Ordering::SeqCst);
+            }
+            async move { status }
+        })
+        .await;
+
+        assert!(holder.wait().unwrap().success());
+        assert_eq!(outcome, ReclaimOutcome::Restored);
+        assert!(held_probes.load(Ordering::SeqCst) > 0);
+        assert_eq!(registry.mode("kk1"), ManagementMode::Managed);
+    }
+}
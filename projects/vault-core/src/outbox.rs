@@ -0,0 +1,124 @@
+// outbox.rs - Delivery of critical events across backend crashes
+//
+// Critical events are persisted to the database outbox before emission and
+// carry their outbox sequence in the payload so the frontend can acknowledge
+// them. Anything unacknowledged is re-emitted once the frontend is ready on
+// the next start. Other events never touch the database.
+
+use keepkey_db::Database;
+
+/// Events that go through the outbox
+pub const CRITICAL_EVENTS: &[&str] = &[
+    "firmware:verification-failed",
+    "security:xpub-mismatch",
+    "security:unrecognized-vendor",
+    "device:fault-detected",
+    "backup:failed",
+    "device:health-warning",
+    "device:firmware-downgraded",
+    "startup:task-failed",
+    "app:locked",
+    "transaction:reorged",
+    "accounts:scan-ceiling-reached",
+];
+
+/// Payload field holding the outbox sequence to pass to `ack_event`
+pub const OUTBOX_SEQUENCE_FIELD: &str = "outbox_sequence";
+
+pub fn is_critical(event_name: &str) -> bool {
+    CRITICAL_EVENTS.contains(&event_name)
+}
+
+fn with_sequence(mut payload: serde_json::Value, sequence: i64) -> serde_json::Value {
+    match payload.as_object_mut() {
+        Some(object) => {
+            object.insert(OUTBOX_SEQUENCE_FIELD.to_string(), sequence.into());
+            payload
+        }
+        None => serde_json::json!({ "value": payload, OUTBOX_SEQUENCE_FIELD: sequence }),
+    }
+}
+
+/// Write a critical event to the outbox; returns the payload to emit, tagged
+/// with its sequence
+pub async fn persist(db: &Database, event_name: &str, payload: serde_json::Value) -> Result<(i64, serde_json::Value), String> {
+    let sequence = db
+        .enqueue_outbox_event(event_name, &payload.to_string())
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+    Ok((sequence, with_sequence(payload, sequence)))
+}
+
+/// Re-emit every unacknowledged event `wants` accepts, oldest first, and prune
+/// old acknowledged ones. Returns how many were emitted.
+pub async fn redeliver_unacked<W, F>(db: &Database, wants: W, mut emit: F) -> Result<usize, String>
+where
+    W: Fn(&str) -> bool,
+    F: FnMut(&str, serde_json::Value) -> Result<(), String>,
+{
+    match db.prune_outbox().await {
+        Ok(0) => {}
+        Ok(pruned) => log::info!("📮 Pruned {} acknowledged outbox events", pruned),
+        Err(e) => log::warn!("Failed to prune event outbox: {}", e),
+    }
+
+    let pending = db.get_unacked_outbox_events().await.map_err(|e| format!("Database error: {}", e))?;
+    let mut emitted = 0;
+    for event in pending.into_iter().filter(|e| wants(&e.event_name)) {
+        let payload = serde_json::from_str(&event.payload_json).unwrap_or(serde_json::Value::Null);
+        if let Err(e) = db.mark_outbox_attempt(event.sequence).await {
+            log::warn!("Failed to count outbox delivery of #{}: {}", event.sequence, e);
+        }
+        match emit(&event.event_name, with_sequence(payload, event.sequence)) {
+            Ok(()) => emitted += 1,
+            Err(e) => log::error!("❌ Failed to re-emit outbox event #{} ({}): {}", event.sequence, event.event_name, e),
+        }
+    }
+    Ok(emitted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_redelivers_after_crash_between_write_and_emit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.db");
+
+        // First backend persists the event and dies before emitting it
+        {
+            let db = Database::open_at_path(path.clone()).await.unwrap();
+            let (sequence, payload) = persist(&db, "security:xpub-mismatch", serde_json::json!({ "device_id": "dev1" }))
+                .await
+                .unwrap();
+            assert_eq!(payload[OUTBOX_SEQUENCE_FIELD], sequence);
+        }
+
+        // A fresh backend against the same database re-emits it once ready
+        let db = Database::open_at_path(path).await.unwrap();
+        let mut emitted = Vec::new();
+        let count = redeliver_unacked(&db, |_| true, |name, payload| {
+            emitted.push((name.to_string(), payload));
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(emitted[0].0, "security:xpub-mismatch");
+        assert_eq!(emitted[0].1["device_id"], "dev1");
+
+        // Until acknowledged it keeps coming back
+        let sequence = emitted[0].1[OUTBOX_SEQUENCE_FIELD].as_i64().unwrap();
+        assert_eq!(redeliver_unacked(&db, |_| true, |_, _| Ok(())).await.unwrap(), 1);
+        assert!(db.ack_outbox_event(sequence).await.unwrap());
+        assert_eq!(redeliver_unacked(&db, |_| true, |_, _| Ok(())).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_only_critical_events_use_outbox() {
+        assert!(is_critical("firmware:verification-failed"));
+        assert!(!is_critical("device:connected"));
+        assert_eq!(with_sequence(serde_json::json!("disk full"), 4), serde_json::json!({ "value": "disk full", "outbox_sequence": 4 }));
+    }
+}
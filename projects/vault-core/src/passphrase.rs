@@ -0,0 +1,178 @@
+// passphrase.rs - Passphrase protection and mid-operation passphrase entry
+//
+// With passphrase protection on, the device answers the first operation that
+// needs keys with a PassphraseRequest. The device queue hands that to the
+// provider installed here, which announces the device on `subscribe()` (the
+// app turns it into a `device:passphrase-request` event) and waits for
+// `supply`; the paused operation then continues with the ack.
+//
+// The passphrase is never stored or logged. It is handed on in a buffer
+// zeroed on drop, and the USB layer zeroes its ack once it is written. If
+// the paused operation is abandoned, the prompt is withdrawn and the device
+// is sent Cancel.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot};
+pub use zeroize::Zeroizing;
+use keepkey_rust::messages::{self, Message};
+use crate::queue::{get_or_create_device_queue, DeviceQueueManager};
+
+/// How long a paused operation waits for the passphrase
+pub const PASSPHRASE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Operations paused on a passphrase, one per device
+pub struct PassphrasePrompts {
+    waiting: Mutex<HashMap<String, oneshot::Sender<Zeroizing<String>>>>,
+    requests: broadcast::Sender<String>,
+}
+
+impl PassphrasePrompts {
+    fn new() -> Self {
+        Self { waiting: Mutex::new(HashMap::new()), requests: broadcast::channel(16).0 }
+    }
+
+    fn waiting(&self) -> std::sync::MutexGuard<'_, HashMap<String, oneshot::Sender<Zeroizing<String>>>> {
+        self.waiting.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Ids of devices asking for a passphrase, as they ask
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.requests.subscribe()
+    }
+
+    pub fn is_waiting(&self, device_id: &str) -> bool {
+        self.waiting().contains_key(device_id)
+    }
+
+    /// Hand the passphrase to the operation paused on `device_id`
+    pub fn supply(&self, device_id: &str, passphrase: Zeroizing<String>) -> Result<(), String> {
+        let sender = self
+            .waiting()
+            .remove(device_id)
+            .ok_or_else(|| format!("Device {} is not asking for a passphrase", device_id))?;
+        sender
+            .send(passphrase)
+            .map_err(|_| format!("The operation on {} stopped waiting for the passphrase", device_id))
+    }
+
+    /// Abandon the operation paused on `device_id`; returns whether one was
+    pub fn cancel(&self, device_id: &str) -> bool {
+        self.waiting().remove(device_id).is_some()
+    }
+
+    /// Announce that `device_id` wants a passphrase and wait for it
+    pub(crate) async fn wait(&self, device_id: &str, timeout: Duration) -> Result<Zeroizing<String>, String> {
+        let (sender, receiver) = oneshot::channel();
+        self.waiting().insert(device_id.to_string(), sender);
+        // Withdrawn however the wait ends, including the caller giving up
+        let _prompt = OpenPrompt { prompts: self, device_id };
+        // Nobody listening just means nobody can answer; the wait times out
+        let _ = self.requests.send(device_id.to_string());
+
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(passphrase)) => Ok(passphrase),
+            Ok(Err(_)) => Err(format!("Passphrase entry on {} was cancelled", device_id)),
+            Err(_) => Err(format!("No passphrase for {} within {}s", device_id, timeout.as_secs())),
+        }
+    }
+}
+
+struct OpenPrompt<'a> {
+    prompts: &'a PassphrasePrompts,
+    device_id: &'a str,
+}
+
+impl Drop for OpenPrompt<'_> {
+    fn drop(&mut self) {
+        self.prompts.waiting().remove(self.device_id);
+    }
+}
+
+/// The prompts shared by the device queues and the passphrase commands
+pub fn passphrase_prompts() -> &'static PassphrasePrompts {
+    static PROMPTS: OnceLock<PassphrasePrompts> = OnceLock::new();
+    PROMPTS.get_or_init(PassphrasePrompts::new)
+}
+
+/// Have device queues pause on a PassphraseRequest until `supply` is called
+pub fn install_passphrase_provider() {
+    let provider: keepkey_rust::device_queue::PassphraseProvider = Arc::new(|device_id| {
+        Box::pin(async move {
+            passphrase_prompts()
+                .wait(&device_id, PASSPHRASE_TIMEOUT)
+                .await
+                .map_err(anyhow::Error::msg)
+        })
+    });
+    if !keepkey_rust::device_queue::set_passphrase_provider(provider) {
+        log::warn!("A passphrase provider was already installed");
+    }
+}
+
+/// Turn passphrase protection on or off; the user confirms on the device
+pub async fn apply_passphrase_protection(
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+    enabled: bool,
+) -> Result<(), String> {
+    let queue = get_or_create_device_queue(device_id, queue_manager).await?;
+    let request = Message::ApplySettings(messages::ApplySettings {
+        language: None,
+        label: None,
+        use_passphrase: Some(enabled),
+        auto_lock_delay_ms: None,
+        u2f_counter: None,
+    });
+    match queue.send_raw(request, true).await {
+        Ok(Message::Success(_)) => Ok(()),
+        Ok(Message::Failure(f)) => Err(format!("Device refused the passphrase setting: {}", f.message())),
+        Ok(other) => Err(format!("Unexpected response: {:?}", other.message_type())),
+        Err(e) => Err(format!("Failed to change passphrase protection: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_paused_operation_gets_the_supplied_passphrase() {
+        let prompts = Arc::new(PassphrasePrompts::new());
+        let mut requests = prompts.subscribe();
+        assert!(prompts.supply("kk1", Zeroizing::new("nobody asked".to_string())).is_err());
+
+        let waiting = tokio::spawn({
+            let prompts = prompts.clone();
+            async move { prompts.wait("kk1", Duration::from_secs(5)).await }
+        });
+        assert_eq!(requests.recv().await.unwrap(), "kk1");
+        assert!(prompts.is_waiting("kk1"));
+        prompts.supply("kk1", Zeroizing::new("correct horse".to_string())).unwrap();
+        assert_eq!(waiting.await.unwrap().unwrap().as_str(), "correct horse");
+        assert!(!prompts.is_waiting("kk1"));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_and_unanswered_prompts_fail() {
+        let prompts = Arc::new(PassphrasePrompts::new());
+        let mut requests = prompts.subscribe();
+        let waiting = tokio::spawn({
+            let prompts = prompts.clone();
+            async move { prompts.wait("kk1", Duration::from_secs(5)).await }
+        });
+        requests.recv().await.unwrap();
+        assert!(prompts.cancel("kk1"));
+        assert!(waiting.await.unwrap().unwrap_err().contains("cancelled"));
+
+        let timed_out = prompts.wait("kk2", Duration::from_millis(20)).await;
+        assert!(timed_out.unwrap_err().contains("No passphrase"));
+        assert!(!prompts.is_waiting("kk2"));
+
+        // The operation giving up withdraws its prompt
+        let abandoned = tokio::time::timeout(Duration::from_millis(20), prompts.wait("kk3", Duration::from_secs(5))).await;
+        assert!(abandoned.is_err());
+        assert!(!prompts.is_waiting("kk3"));
+    }
+}
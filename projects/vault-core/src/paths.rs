@@ -0,0 +1,103 @@
+// paths.rs - BIP32 path and CAIP network helpers
+
+pub const HARDENED: u32 = 0x8000_0000;
+
+/// Parse a BIP32 path like `m/44'/0'/0'/0/0` (or `44h/0h/...`) into address_n
+pub fn parse_derivation_path(path: &str) -> Result<Vec<u32>, String> {
+    let trimmed = path.trim();
+    let body = trimmed
+        .strip_prefix("m/")
+        .or_else(|| trimmed.strip_prefix("M/"))
+        .unwrap_or(trimmed);
+
+    if body.is_empty() || body == "m" {
+        return Ok(Vec::new());
+    }
+
+    body.split('/')
+        .map(|segment| {
+            let (index, hardened) = match segment.strip_suffix('\'').or_else(|| segment.strip_suffix('h')) {
+                Some(index) => (index, true),
+                None => (segment, false),
+            };
+            let value: u32 = index
+                .parse()
+                .map_err(|_| format!("Invalid path segment '{}' in {}", segment, path))?;
+            if value >= HARDENED {
+                return Err(format!("Path index {} out of range in {}", value, path));
+            }
+            Ok(if hardened { value | HARDENED } else { value })
+        })
+        .collect()
+}
+
+/// Format address_n back into `m/...` notation
+pub fn format_derivation_path(address_n: &[u32]) -> String {
+    let mut out = String::from("m");
+    for &index in address_n {
+        if index & HARDENED != 0 {
+            out.push_str(&format!("/{}'", index & !HARDENED));
+        } else {
+            out.push_str(&format!("/{}", index));
+        }
+    }
+    out
+}
+
+/// Address family a CAIP-2 network belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkFamily {
+    /// UTXO chains addressed through GetAddress with a firmware coin name
+    Utxo { coin_name: String },
+    /// EVM chains addressed through EthereumGetAddress
+    Evm { chain_id: u64 },
+}
+
+/// Map a CAIP-2 network id (e.g. `bip122:000000000019d6689c085ae165831e93`, `eip155:1`) to a family
+pub fn network_family(caip: &str) -> Result<NetworkFamily, String> {
+    // Accept CAIP-19 asset ids too by trimming the asset part
+    let network = caip.split('/').next().unwrap_or(caip);
+
+    if let Some(chain_id) = network.strip_prefix("eip155:") {
+        let chain_id = chain_id
+            .parse()
+            .map_err(|_| format!("Invalid EVM chain id in {}", caip))?;
+        return Ok(NetworkFamily::Evm { chain_id });
+    }
+
+    let coin_name = match network {
+        "bip122:000000000019d6689c085ae165831e93" => "Bitcoin",
+        "bip122:000000000933ea01ad0ee984209779ba" => "Testnet",
+        "bip122:12a765e31ffd4059bada1e25190f6e98" => "Litecoin",
+        "bip122:00000000001a91e3dace36e2be3bf030" => "Dogecoin",
+        "bip122:000000000000000000651ef99cb9fcbe" => "BitcoinCash",
+        "bip122:000007d91d1254d60e2dd1ae58038307" => "Dash",
+        _ => return Err(format!("Unsupported network: {}", caip)),
+    };
+
+    Ok(NetworkFamily::Utxo { coin_name: coin_name.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_round_trip() {
+        let path = parse_derivation_path("m/84'/0'/0'/0/5").unwrap();
+        assert_eq!(path, vec![84 | HARDENED, HARDENED, HARDENED, 0, 5]);
+        assert_eq!(format_derivation_path(&path), "m/84'/0'/0'/0/5");
+        assert_eq!(parse_derivation_path("44h/60h/0h").unwrap(), vec![44 | HARDENED, 60 | HARDENED, HARDENED]);
+        assert!(parse_derivation_path("m/44'/x").is_err());
+    }
+
+    #[test]
+    fn test_network_family() {
+        assert_eq!(network_family("eip155:1").unwrap(), NetworkFamily::Evm { chain_id: 1 });
+        assert_eq!(
+            network_family("bip122:000000000019d6689c085ae165831e93/slip44:0").unwrap(),
+            NetworkFamily::Utxo { coin_name: "Bitcoin".to_string() }
+        );
+        assert!(network_family("cosmos:unknown").is_err());
+    }
+}
@@ -0,0 +1,60 @@
+// portfolio_sync.rs - Whether a portfolio sync is writing the cache
+//
+// Balances arrive from the frontend in batches; each batch is stored and the
+// wallet's dashboard rebuilt from it. Until the rebuild finishes the cache is
+// half-written, so consistency checks wait for a moment with no batch in
+// flight rather than guess from row timestamps.
+
+use std::sync::OnceLock;
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Coordinates balance writes with consistency checks of the portfolio cache
+#[derive(Default)]
+pub struct PortfolioSync {
+    lock: RwLock<()>,
+}
+
+impl PortfolioSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Held while a batch of balances is stored and its dashboard rebuilt;
+    /// batches for different wallets may overlap
+    pub async fn write(&self) -> RwLockReadGuard<'_, ()> {
+        self.lock.read().await
+    }
+
+    /// Exclusive access for a consistency check, or None while a sync is
+    /// writing. Batches arriving during the check wait for it to finish.
+    pub fn try_check(&self) -> Option<RwLockWriteGuard<'_, ()>> {
+        self.lock.try_write().ok()
+    }
+}
+
+/// Process-wide tracker shared by the balance commands and the scheduler
+pub fn portfolio_sync() -> &'static PortfolioSync {
+    static SYNC: OnceLock<PortfolioSync> = OnceLock::new();
+    SYNC.get_or_init(PortfolioSync::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checks_wait_for_writes() {
+        let sync = PortfolioSync::new();
+        let first = sync.write().await;
+        let second = sync.write().await;
+        assert!(sync.try_check().is_none());
+        drop(first);
+        assert!(sync.try_check().is_none());
+        drop(second);
+
+        let check = sync.try_check().unwrap();
+        assert!(tokio::time::timeout(std::time::Duration::from_millis(20), sync.write()).await.is_err());
+        drop(check);
+        assert!(sync.try_check().is_some());
+    }
+}
@@ -0,0 +1,367 @@
+// power.rs - Battery-aware scheduling of background work
+//
+// On battery (or when forced with the `power_saver` preference) background
+// work switches to a reduced profile: slower USB polling, doubled refresh
+// intervals and no status polling while the window is unfocused. Recent user
+// interaction restores the normal profile. Work the user is actively waiting
+// on (device operations, watched payment addresses) never asks the monitor
+// for an interval, so it is never slowed.
+
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+/// Preference key: "always", "never" or "auto" (default)
+pub const POWER_SAVER_PREF: &str = "power_saver";
+
+/// USB poll interval in the reduced profile
+pub const REDUCED_USB_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long the normal profile is kept on battery after the window loses focus
+pub const INTERACTION_GRACE: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    /// Desktop without a battery, or a platform we cannot query
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum PowerSaverMode {
+    Always,
+    Never,
+    Auto,
+}
+
+impl PowerSaverMode {
+    /// Unknown or missing values mean auto
+    pub fn from_pref(value: Option<&str>) -> Self {
+        match value.map(str::trim) {
+            Some("always") => PowerSaverMode::Always,
+            Some("never") => PowerSaverMode::Never,
+            _ => PowerSaverMode::Auto,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PowerSaverMode::Always => "always",
+            PowerSaverMode::Never => "never",
+            PowerSaverMode::Auto => "auto",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum PowerProfile {
+    Normal,
+    Reduced,
+}
+
+/// Kinds of recurring work, so each can be throttled appropriately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundWork {
+    /// Fallback USB connect/disconnect polling
+    UsbPoll,
+    /// Portfolio and fee refreshes
+    PeriodicRefresh,
+    /// Frontend status polling
+    StatusPoll,
+}
+
+/// Profile for the given mode, power source and interaction state
+pub fn select_profile(mode: PowerSaverMode, source: PowerSource, recently_active: bool) -> PowerProfile {
+    match mode {
+        PowerSaverMode::Never => PowerProfile::Normal,
+        PowerSaverMode::Always => PowerProfile::Reduced,
+        PowerSaverMode::Auto if source == PowerSource::Battery && !recently_active => PowerProfile::Reduced,
+        PowerSaverMode::Auto => PowerProfile::Normal,
+    }
+}
+
+/// Interval for `work` under `profile`; None means the work is paused
+pub fn work_interval(profile: PowerProfile, work: BackgroundWork, base: Duration, window_focused: bool) -> Option<Duration> {
+    match (profile, work) {
+        (PowerProfile::Normal, _) => Some(base),
+        (PowerProfile::Reduced, BackgroundWork::UsbPoll) => Some(base.max(REDUCED_USB_POLL_INTERVAL)),
+        (PowerProfile::Reduced, BackgroundWork::PeriodicRefresh) => Some(base * 2),
+        (PowerProfile::Reduced, BackgroundWork::StatusPoll) if !window_focused => None,
+        (PowerProfile::Reduced, BackgroundWork::StatusPoll) => Some(base),
+    }
+}
+
+/// Snapshot for diagnostics and `power:profile-changed`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct PowerStatus {
+    pub profile: PowerProfile,
+    pub mode: PowerSaverMode,
+    pub source: PowerSource,
+    pub window_focused: bool,
+    pub refresh_multiplier: u32,
+    pub status_polling_paused: bool,
+}
+
+struct PowerState {
+    mode: PowerSaverMode,
+    source: PowerSource,
+    window_focused: bool,
+    last_interaction: Option<Instant>,
+    profile: PowerProfile,
+}
+
+/// Current power state shared by the background loops
+pub struct PowerMonitor {
+    state: Mutex<PowerState>,
+}
+
+impl Default for PowerMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PowerMonitor {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(PowerState {
+                mode: PowerSaverMode::Auto,
+                source: PowerSource::Unknown,
+                window_focused: true,
+                last_interaction: Some(Instant::now()),
+                profile: PowerProfile::Normal,
+            }),
+        }
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut PowerState) -> R) -> R {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut state)
+    }
+
+    fn status_of(state: &PowerState) -> PowerStatus {
+        PowerStatus {
+            profile: state.profile,
+            mode: state.mode,
+            source: state.source,
+            window_focused: state.window_focused,
+            refresh_multiplier: if state.profile == PowerProfile::Reduced { 2 } else { 1 },
+            status_polling_paused: work_interval(state.profile, BackgroundWork::StatusPoll, Duration::ZERO, state.window_focused).is_none(),
+        }
+    }
+
+    pub fn status(&self) -> PowerStatus {
+        self.with_state(|state| Self::status_of(state))
+    }
+
+    pub fn interval(&self, work: BackgroundWork, base: Duration) -> Option<Duration> {
+        self.with_state(|state| work_interval(state.profile, work, base, state.window_focused))
+    }
+
+    /// Apply new inputs; returns the new status if anything visible changed
+    pub fn update(
+        &self,
+        mode: PowerSaverMode,
+        source: PowerSource,
+        window_focused: Option<bool>,
+        now: Instant,
+    ) -> Option<PowerStatus> {
+        self.with_state(|state| {
+            let before = Self::status_of(state);
+            state.mode = mode;
+            state.source = source;
+            if let Some(focused) = window_focused {
+                state.window_focused = focused;
+                state.last_interaction = Some(now);
+            }
+            let recently_active = state.window_focused
+                || state.last_interaction.is_some_and(|t| now.duration_since(t) < INTERACTION_GRACE);
+            state.profile = select_profile(state.mode, state.source, recently_active);
+            let after = Self::status_of(state);
+            (after != before).then_some(after)
+        })
+    }
+
+    /// Record user interaction (focus, input); may restore the normal profile
+    pub fn note_interaction(&self, window_focused: bool, now: Instant) -> Option<PowerStatus> {
+        let (mode, source) = self.with_state(|state| (state.mode, state.source));
+        self.update(mode, source, Some(window_focused), now)
+    }
+}
+
+/// Read the current power source for this platform
+pub fn detect_power_source() -> PowerSource {
+    if cfg!(windows) {
+        windows_power_source()
+    } else if cfg!(target_os = "linux") {
+        linux_power_source(Path::new("/sys/class/power_supply"))
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .ok()
+            .map(|out| parse_pmset_output(&String::from_utf8_lossy(&out.stdout)))
+            .unwrap_or(PowerSource::Unknown)
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+/// AC line status from GetSystemPowerStatus
+#[cfg(windows)]
+fn windows_power_source() -> PowerSource {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    // SAFETY: SYSTEM_POWER_STATUS is plain integers, so all-zero is valid
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    // SAFETY: `status` is a valid out pointer for the duration of the call
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return PowerSource::Unknown;
+    }
+    parse_ac_line_status(status.ACLineStatus)
+}
+
+#[cfg(not(windows))]
+fn windows_power_source() -> PowerSource {
+    PowerSource::Unknown
+}
+
+/// SYSTEM_POWER_STATUS.ACLineStatus: 0 offline, 1 online, 255 unknown
+pub fn parse_ac_line_status(ac_line_status: u8) -> PowerSource {
+    match ac_line_status {
+        0 => PowerSource::Battery,
+        1 => PowerSource::Ac,
+        _ => PowerSource::Unknown,
+    }
+}
+
+/// `pmset -g batt` starts with "Now drawing from 'AC Power'" or "'Battery Power'"
+pub fn parse_pmset_output(output: &str) -> PowerSource {
+    if output.contains("'Battery Power'") {
+        PowerSource::Battery
+    } else if output.contains("'AC Power'") {
+        PowerSource::Ac
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+/// Any online mains/USB supply means AC; otherwise a discharging battery means battery
+pub fn linux_power_source(supply_dir: &Path) -> PowerSource {
+    let Ok(entries) = std::fs::read_dir(supply_dir) else {
+        return PowerSource::Unknown;
+    };
+    let read = |dir: &Path, name: &str| std::fs::read_to_string(dir.join(name)).map(|v| v.trim().to_string()).ok();
+
+    let mut discharging = false;
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        match read(&dir, "type").as_deref() {
+            Some("Mains") | Some("USB") if read(&dir, "online").as_deref() == Some("1") => return PowerSource::Ac,
+            Some("Battery") if read(&dir, "status").as_deref() == Some("Discharging") => discharging = true,
+            _ => {}
+        }
+    }
+    if discharging { PowerSource::Battery } else { PowerSource::Unknown }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: Duration = Duration::from_millis(500);
+
+    #[test]
+    fn test_profile_for_each_mode_and_source() {
+        use PowerProfile::*;
+        use PowerSaverMode::*;
+        use PowerSource::*;
+
+        let cases = [
+            (Auto, Battery, false, Reduced),
+            (Auto, Battery, true, Normal),
+            (Auto, Ac, false, Normal),
+            (Auto, Unknown, false, Normal),
+            (Always, Ac, true, Reduced),
+            (Always, Battery, false, Reduced),
+            (Never, Battery, false, Normal),
+            (Never, Ac, false, Normal),
+        ];
+        for (mode, source, active, expected) in cases {
+            assert_eq!(select_profile(mode, source, active), expected, "{:?} {:?} active={}", mode, source, active);
+        }
+    }
+
+    #[test]
+    fn test_intervals_per_profile() {
+        let refresh = Duration::from_secs(60);
+        assert_eq!(work_interval(PowerProfile::Normal, BackgroundWork::UsbPoll, BASE, false), Some(BASE));
+        assert_eq!(work_interval(PowerProfile::Reduced, BackgroundWork::UsbPoll, BASE, true), Some(REDUCED_USB_POLL_INTERVAL));
+        assert_eq!(work_interval(PowerProfile::Reduced, BackgroundWork::PeriodicRefresh, refresh, true), Some(refresh * 2));
+        assert_eq!(work_interval(PowerProfile::Normal, BackgroundWork::PeriodicRefresh, refresh, false), Some(refresh));
+        assert_eq!(work_interval(PowerProfile::Reduced, BackgroundWork::StatusPoll, BASE, false), None);
+        assert_eq!(work_interval(PowerProfile::Reduced, BackgroundWork::StatusPoll, BASE, true), Some(BASE));
+    }
+
+    #[test]
+    fn test_monitor_reports_changes_and_interaction_restores_normal() {
+        let monitor = PowerMonitor::new();
+        let start = Instant::now();
+        assert_eq!(monitor.update(PowerSaverMode::Auto, PowerSource::Battery, None, start).unwrap().profile, PowerProfile::Normal);
+        assert!(monitor.update(PowerSaverMode::Auto, PowerSource::Battery, None, start).is_none());
+
+        // Window left in the background: normal for the grace period, then reduced
+        assert!(!monitor.update(PowerSaverMode::Auto, PowerSource::Battery, Some(false), start).unwrap().window_focused);
+        assert_eq!(monitor.status().profile, PowerProfile::Normal);
+        let idle = start + INTERACTION_GRACE + Duration::from_secs(5);
+        let status = monitor.update(PowerSaverMode::Auto, PowerSource::Battery, None, idle).unwrap();
+        assert_eq!(status.profile, PowerProfile::Reduced);
+        assert!(status.status_polling_paused);
+        assert_eq!(monitor.interval(BackgroundWork::UsbPoll, BASE), Some(REDUCED_USB_POLL_INTERVAL));
+
+        let status = monitor.note_interaction(true, idle).unwrap();
+        assert_eq!(status.profile, PowerProfile::Normal);
+
+        // Back on AC while unfocused and idle
+        monitor.note_interaction(false, idle);
+        let later = idle + INTERACTION_GRACE * 2;
+        assert_eq!(monitor.update(PowerSaverMode::Auto, PowerSource::Battery, None, later).unwrap().profile, PowerProfile::Reduced);
+        assert_eq!(monitor.update(PowerSaverMode::Auto, PowerSource::Ac, None, later).unwrap().profile, PowerProfile::Normal);
+        assert_eq!(monitor.update(PowerSaverMode::Always, PowerSource::Ac, None, later).unwrap().profile, PowerProfile::Reduced);
+    }
+
+    #[test]
+    fn test_platform_parsing() {
+        assert_eq!(parse_pmset_output("Now drawing from 'Battery Power'\n -InternalBattery-0"), PowerSource::Battery);
+        assert_eq!(parse_pmset_output("Now drawing from 'AC Power'"), PowerSource::Ac);
+        assert_eq!(PowerSaverMode::from_pref(Some("bogus")), PowerSaverMode::Auto);
+        assert_eq!(parse_ac_line_status(0), PowerSource::Battery);
+        assert_eq!(parse_ac_line_status(1), PowerSource::Ac);
+        assert_eq!(parse_ac_line_status(255), PowerSource::Unknown);
+
+        let dir = tempfile::tempdir().unwrap();
+        let supply = |name: &str, files: &[(&str, &str)]| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(&path).unwrap();
+            for (file, value) in files {
+                std::fs::write(path.join(file), format!("{}\n", value)).unwrap();
+            }
+        };
+        supply("BAT0", &[("type", "Battery"), ("status", "Discharging")]);
+        supply("AC", &[("type", "Mains"), ("online", "0")]);
+        assert_eq!(linux_power_source(dir.path()), PowerSource::Battery);
+
+        supply("AC", &[("type", "Mains"), ("online", "1")]);
+        assert_eq!(linux_power_source(dir.path()), PowerSource::Ac);
+        assert_eq!(linux_power_source(&dir.path().join("missing")), PowerSource::Unknown);
+    }
+}
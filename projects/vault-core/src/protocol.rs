@@ -0,0 +1,147 @@
+// protocol.rs - Protocol schema report and the raw-message passthrough allowlist
+//
+// keepkey_rust embeds the message schema of the device-protocol revision it
+// was compiled against. This module adds the vault's view: which messages a
+// raw-message passthrough may forward to a device on behalf of an external
+// client, and an example JSON body for each message.
+//
+// The allowlist only holds host-to-device requests and acks that cannot
+// change device state. Wipe, load, reset, recovery, settings and policy
+// changes, firmware and flash access, and DebugLink stay behind the vault's
+// own commands, which add their confirmations.
+
+use serde::Serialize;
+use keepkey_rust::messages::{protocol_descriptor, FieldSchema};
+
+/// Messages a raw passthrough may send to a device
+pub const RAW_PASSTHROUGH_ALLOWLIST: &[&str] = &[
+    // Session and flow control
+    "Initialize",
+    "GetFeatures",
+    "Ping",
+    "Cancel",
+    "ButtonAck",
+    "PinMatrixAck",
+    "PassphraseAck",
+    // Bitcoin-style coins
+    "GetAddress",
+    "GetPublicKey",
+    "SignTx",
+    "TxAck",
+    "RawTxAck",
+    "SignMessage",
+    "VerifyMessage",
+    // Ethereum
+    "EthereumGetAddress",
+    "EthereumSignTx",
+    "EthereumTxAck",
+    "EthereumSignMessage",
+    "EthereumVerifyMessage",
+    "EthereumSignTypedHash",
+    // Other chains
+    "RippleGetAddress",
+    "RippleSignTx",
+    "ThorchainGetAddress",
+    "ThorchainSignTx",
+    "ThorchainMsgAck",
+    "MayachainGetAddress",
+    "MayachainSignTx",
+    "MayachainMsgAck",
+    "CosmosGetAddress",
+    "CosmosSignTx",
+    "CosmosMsgAck",
+    "OsmosisGetAddress",
+    "OsmosisSignTx",
+    "OsmosisMsgAck",
+    "TendermintGetAddress",
+    "TendermintSignTx",
+    "TendermintMsgAck",
+    "BinanceGetAddress",
+    "BinanceGetPublicKey",
+    "BinanceSignTx",
+    "BinanceTransferMsg",
+    "BinanceOrderMsg",
+    "BinanceCancelMsg",
+    "EosGetPublicKey",
+    "EosSignTx",
+    "EosTxActionAck",
+    "NanoGetAddress",
+    "NanoSignTx",
+];
+
+/// Whether the raw passthrough may forward `message_name`
+pub fn raw_passthrough_allowed(message_name: &str) -> bool {
+    RAW_PASSTHROUGH_ALLOWLIST.contains(&message_name)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ProtocolMessageInfo {
+    pub name: String,
+    pub file: String,
+    /// MessageType value; None for types only used inside other messages
+    pub wire_id: Option<i32>,
+    pub fields: Vec<FieldSchema>,
+    pub passthrough_allowed: bool,
+    /// Default instance as JSON, every field present
+    pub example: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ProtocolInfo {
+    pub protocol_version: Option<String>,
+    pub protocol_commit: Option<String>,
+    pub messages: Vec<ProtocolMessageInfo>,
+}
+
+/// Schema of every message compiled into this build, sorted by name
+pub fn protocol_info() -> ProtocolInfo {
+    let descriptor = protocol_descriptor();
+    let mut messages: Vec<ProtocolMessageInfo> = descriptor
+        .messages
+        .iter()
+        .map(|message| ProtocolMessageInfo {
+            name: message.name.clone(),
+            file: message.file.clone(),
+            wire_id: message.wire_id,
+            fields: message.fields.clone(),
+            passthrough_allowed: raw_passthrough_allowed(&message.name),
+            example: descriptor.example_json(&message.name).unwrap_or_else(|| serde_json::json!({})),
+        })
+        .collect();
+    messages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    ProtocolInfo {
+        protocol_version: descriptor.version.clone(),
+        protocol_commit: descriptor.commit.clone(),
+        messages,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepkey_rust::messages::Message;
+
+    #[test]
+    fn test_allowlist_names_compiled_messages() {
+        for name in RAW_PASSTHROUGH_ALLOWLIST {
+            assert!(Message::NAMES.contains(name), "{} is not a compiled message", name);
+        }
+        assert!(raw_passthrough_allowed("GetAddress"));
+        assert!(!raw_passthrough_allowed("WipeDevice"));
+        assert!(!raw_passthrough_allowed("FirmwareUpload"));
+        assert!(!raw_passthrough_allowed("DebugLinkGetState"));
+    }
+
+    #[test]
+    fn test_protocol_info_flags_passthrough() {
+        let info = protocol_info();
+        let wipe = info.messages.iter().find(|m| m.name == "WipeDevice").unwrap();
+        assert!(!wipe.passthrough_allowed);
+        let ping = info.messages.iter().find(|m| m.name == "Ping").unwrap();
+        assert!(ping.passthrough_allowed);
+        assert!(ping.example.get("message").is_some());
+    }
+}
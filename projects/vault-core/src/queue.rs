@@ -0,0 +1,143 @@
+// queue.rs - Device queue manager shared by the GUI and the CLI
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use keepkey_rust::device_queue::{DeviceQueueFactory, DeviceQueueHandle, QueueActivity, RequestObserver};
+use crate::device_flow::{DeviceFlow, DeviceFlowState};
+use crate::request_queue::request_queue;
+
+pub type DeviceQueueManager = Arc<Mutex<HashMap<String, DeviceQueueHandle>>>;
+
+/// Create an empty queue manager
+pub fn new_queue_manager() -> DeviceQueueManager {
+    Arc::new(Mutex::new(HashMap::new()))
+}
+
+/// Logs every exchange and feeds the device's auto-lock idle clock
+fn queue_observer() -> RequestObserver {
+    let log_exchange = crate::device_log::request_observer();
+    Arc::new(move |exchange| {
+        crate::device_session::device_sessions().observe(&exchange, Instant::now());
+        log_exchange(exchange);
+    })
+}
+
+/// Get or create a device queue handle for the given device ID
+pub async fn get_or_create_device_queue(
+    device_id: &str,
+    queue_manager: &DeviceQueueManager,
+) -> Result<DeviceQueueHandle, String> {
+    // Watch-only imports have no device behind them and can never sign
+    if keepkey_db::imported_wallets::is_imported_device(device_id) {
+        return Err(format!("{} is a watch-only imported wallet and cannot be used for signing", device_id));
+    }
+
+    // Devices held by another process are read-only until they are released
+    crate::observer::observer_registry()
+        .check_managed(device_id)
+        .map_err(|e| e.to_json_string())?;
+
+    let mut manager = queue_manager.lock().await;
+
+    // Check if we already have a queue for the requested deviceId
+    if let Some(existing_handle) = manager.get(device_id) {
+        return Ok(existing_handle.clone());
+    }
+
+    // Get list of connected devices
+    let devices = keepkey_rust::features::list_connected_devices();
+
+    // Find device by exact ID match
+    let device = devices.iter()
+        .find(|d| d.unique_id == device_id)
+        .ok_or_else(|| format!("Device {} not found in connected devices", device_id))?;
+
+    // Create a new queue handle
+    log::info!("🚀 Creating new device worker for device: {}", device_id);
+    let handle = DeviceQueueFactory::spawn_worker(device_id.to_string(), device.clone())
+        .with_observer(queue_observer());
+
+    // Insert the queue under the device ID
+    manager.insert(device_id.to_string(), handle.clone());
+
+    Ok(handle)
+}
+
+/// A device's queue and flow state, for telling a device that is busy or
+/// re-enumerating from one whose queue is stuck
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct DeviceQueueStatus {
+    pub device_id: String,
+    /// Worker activity; None if no worker was created for the device
+    pub queue: Option<QueueActivity>,
+    /// Dropped off the bus during a flow and expected back
+    pub temporarily_disconnected: bool,
+    pub active_flow: Option<DeviceFlow>,
+    /// Operations waiting for or holding the device in the request queue
+    pub serialized_depth: u32,
+}
+
+fn device_queue_status(device_id: &str, handle: Option<&DeviceQueueHandle>, flows: &DeviceFlowState) -> DeviceQueueStatus {
+    DeviceQueueStatus {
+        device_id: device_id.to_string(),
+        queue: handle.map(DeviceQueueHandle::activity),
+        temporarily_disconnected: flows.is_temporarily_disconnected(device_id),
+        active_flow: flows.active_flow(device_id),
+        serialized_depth: request_queue().depth(device_id),
+    }
+}
+
+/// Queue status of one device; it does not create a worker
+pub async fn queue_status(queue_manager: &DeviceQueueManager, flows: &DeviceFlowState, device_id: &str) -> DeviceQueueStatus {
+    let manager = queue_manager.lock().await;
+    device_queue_status(device_id, manager.get(device_id), flows)
+}
+
+/// Queue status of every device with a worker
+pub async fn all_queue_statuses(queue_manager: &DeviceQueueManager, flows: &DeviceFlowState) -> Vec<DeviceQueueStatus> {
+    let manager = queue_manager.lock().await;
+    let mut statuses: Vec<DeviceQueueStatus> = manager
+        .iter()
+        .map(|(device_id, handle)| device_queue_status(device_id, Some(handle), flows))
+        .collect();
+    statuses.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+    statuses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queue_status_reports_workers_and_flows() {
+        let queue_manager = new_queue_manager();
+        let flows = DeviceFlowState::new();
+
+        let status = queue_status(&queue_manager, &flows, "kk1").await;
+        assert_eq!((status.queue, status.temporarily_disconnected, status.active_flow), (None, false, None));
+
+        // A worker that stopped: its channel is closed
+        let (cmd_tx, cmd_rx) = tokio::sync::mpsc::channel(4);
+        drop(cmd_rx);
+        queue_manager.lock().await.insert("kk1".to_string(), DeviceQueueHandle::new("kk1".to_string(), cmd_tx));
+        let (cmd_tx, _cmd_rx) = tokio::sync::mpsc::channel(4);
+        queue_manager.lock().await.insert("kk2".to_string(), DeviceQueueHandle::new("kk2".to_string(), cmd_tx));
+        flows.begin_flow("kk2", DeviceFlow::Recovery);
+        flows.mark_disconnected("kk2", Instant::now());
+
+        let statuses = all_queue_statuses(&queue_manager, &flows).await;
+        assert_eq!(statuses.iter().map(|s| s.device_id.as_str()).collect::<Vec<_>>(), ["kk1", "kk2"]);
+        let dead = statuses[0].queue.as_ref().unwrap();
+        assert!(!dead.worker_alive);
+        let recovering = &statuses[1];
+        assert!(recovering.queue.as_ref().unwrap().worker_alive);
+        assert_eq!(recovering.queue.as_ref().unwrap().pending_requests, 0);
+        assert!(recovering.temporarily_disconnected);
+        assert_eq!(recovering.active_flow, Some(DeviceFlow::Recovery));
+        assert_eq!(recovering.serialized_depth, 0);
+    }
+}
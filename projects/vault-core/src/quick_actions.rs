@@ -0,0 +1,391 @@
+// quick_actions.rs - Registry of actions for the command palette and tray
+//
+// Every action the palette (Cmd+K) or the tray can trigger is declared here
+// once, with the arguments it needs and a predicate that says which argument
+// sets it can run with right now. Predicates only look at an ActionContext
+// built from the registry and portfolio caches, so listing actions never
+// talks to a device. Execution re-checks availability against a fresh
+// context, so a stale palette entry cannot lock a device that has gone away.
+//
+// Actions route to the same code as the command they stand for, behind the
+// same app-lock check. High-risk operations (wipe, PIN removal, forget) need
+// a typed confirmation and are deliberately not registered.
+
+use std::collections::{BTreeMap, HashMap};
+use serde::Serialize;
+use crate::tray::{TrayDevice, TrayDeviceState};
+
+/// Arguments of an action invocation, by name
+pub type ActionArgs = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum QuickActionCategory {
+    Device,
+    Portfolio,
+    Diagnostics,
+}
+
+/// What an argument identifies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum ActionArgKind {
+    DeviceId,
+    /// CAIP-19 asset id of an account with a cached balance
+    Caip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ActionArgSpec {
+    pub name: &'static str,
+    pub kind: ActionArgKind,
+}
+
+/// Cache-backed state that availability is evaluated against
+#[derive(Debug, Clone, Default)]
+pub struct ActionContext {
+    /// Connected devices, as shown in the tray
+    pub devices: Vec<TrayDevice>,
+    pub imported_wallets: usize,
+    /// Assets with a cached balance in each connected device's active wallet
+    pub portfolio_caips: HashMap<String, Vec<String>>,
+}
+
+/// One way an action can run right now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionOffer {
+    pub args: ActionArgs,
+    /// What the arguments refer to, e.g. the device label
+    pub subject: Option<String>,
+}
+
+impl ActionOffer {
+    fn bare() -> Self {
+        Self { args: ActionArgs::new(), subject: None }
+    }
+
+    fn device(device: &TrayDevice) -> Self {
+        Self {
+            args: [("device_id".to_string(), device.device_id.clone())].into_iter().collect(),
+            subject: Some(device.label.clone()),
+        }
+    }
+}
+
+/// A registered action
+#[derive(Debug)]
+pub struct QuickActionSpec {
+    pub id: &'static str,
+    /// Localization key of the title
+    pub title_code: &'static str,
+    /// English title, used by the tray and as a fallback
+    pub title: &'static str,
+    pub category: QuickActionCategory,
+    pub required_args: &'static [ActionArgSpec],
+    /// Argument sets the action can run with in this context; empty when unavailable
+    pub availability: fn(&ActionContext) -> Vec<ActionOffer>,
+}
+
+const DEVICE_ARG: ActionArgSpec = ActionArgSpec { name: "device_id", kind: ActionArgKind::DeviceId };
+const CAIP_ARG: ActionArgSpec = ActionArgSpec { name: "caip", kind: ActionArgKind::Caip };
+
+fn ready_devices(ctx: &ActionContext) -> impl Iterator<Item = &TrayDevice> {
+    ctx.devices.iter().filter(|d| d.state == TrayDeviceState::Ready)
+}
+
+fn when(available: bool) -> Vec<ActionOffer> {
+    if available { vec![ActionOffer::bare()] } else { Vec::new() }
+}
+
+fn sync_now_offers(ctx: &ActionContext) -> Vec<ActionOffer> {
+    when(!ctx.devices.is_empty() || ctx.imported_wallets > 0)
+}
+
+fn lock_device_offers(ctx: &ActionContext) -> Vec<ActionOffer> {
+    ready_devices(ctx).map(ActionOffer::device).collect()
+}
+
+fn lock_all_offers(ctx: &ActionContext) -> Vec<ActionOffer> {
+    when(ready_devices(ctx).next().is_some())
+}
+
+fn receive_address_offers(ctx: &ActionContext) -> Vec<ActionOffer> {
+    ready_devices(ctx)
+        .flat_map(|device| {
+            ctx.portfolio_caips.get(&device.device_id).into_iter().flatten().map(move |caip| {
+                let mut offer = ActionOffer::device(device);
+                offer.args.insert(CAIP_ARG.name.to_string(), caip.clone());
+                offer.subject = Some(format!("{} — {}", device.label, caip));
+                offer
+            })
+        })
+        .collect()
+}
+
+fn diagnostics_offers(_ctx: &ActionContext) -> Vec<ActionOffer> {
+    vec![ActionOffer::bare()]
+}
+
+fn update_check_offers(ctx: &ActionContext) -> Vec<ActionOffer> {
+    ctx.devices.iter().map(ActionOffer::device).collect()
+}
+
+pub const QUICK_ACTIONS: &[QuickActionSpec] = &[
+    QuickActionSpec {
+        id: "sync_now",
+        title_code: "quick_action.sync_now",
+        title: "Sync now",
+        category: QuickActionCategory::Portfolio,
+        required_args: &[],
+        availability: sync_now_offers,
+    },
+    QuickActionSpec {
+        id: "lock_device",
+        title_code: "quick_action.lock_device",
+        title: "Lock device",
+        category: QuickActionCategory::Device,
+        required_args: &[DEVICE_ARG],
+        availability: lock_device_offers,
+    },
+    QuickActionSpec {
+        id: "lock_all_devices",
+        title_code: "quick_action.lock_all_devices",
+        title: "Lock all devices",
+        category: QuickActionCategory::Device,
+        required_args: &[],
+        availability: lock_all_offers,
+    },
+    QuickActionSpec {
+        id: "show_receive_address",
+        title_code: "quick_action.show_receive_address",
+        title: "Show receive address",
+        category: QuickActionCategory::Portfolio,
+        required_args: &[DEVICE_ARG, CAIP_ARG],
+        availability: receive_address_offers,
+    },
+    QuickActionSpec {
+        id: "open_diagnostics",
+        title_code: "quick_action.open_diagnostics",
+        title: "Open diagnostics",
+        category: QuickActionCategory::Diagnostics,
+        required_args: &[],
+        availability: diagnostics_offers,
+    },
+    QuickActionSpec {
+        id: "check_for_updates",
+        title_code: "quick_action.check_for_updates",
+        title: "Check for updates",
+        category: QuickActionCategory::Device,
+        required_args: &[DEVICE_ARG],
+        availability: update_check_offers,
+    },
+];
+
+pub fn find_action(id: &str) -> Option<&'static QuickActionSpec> {
+    QUICK_ACTIONS.iter().find(|action| action.id == id)
+}
+
+/// An action available right now, with its arguments filled in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct QuickAction {
+    pub id: String,
+    pub title_code: String,
+    pub title: String,
+    pub category: QuickActionCategory,
+    pub required_args: Vec<ActionArgSpec>,
+    pub args: ActionArgs,
+    pub subject: Option<String>,
+}
+
+/// Available actions, one entry per argument set. With `device_id`, actions
+/// about other devices are left out.
+pub fn list_quick_actions(ctx: &ActionContext, device_id: Option<&str>) -> Vec<QuickAction> {
+    QUICK_ACTIONS
+        .iter()
+        .flat_map(|spec| {
+            (spec.availability)(ctx).into_iter().map(move |offer| QuickAction {
+                id: spec.id.to_string(),
+                title_code: spec.title_code.to_string(),
+                title: spec.title.to_string(),
+                category: spec.category,
+                required_args: spec.required_args.to_vec(),
+                args: offer.args,
+                subject: offer.subject,
+            })
+        })
+        .filter(|action| match (device_id, action.args.get(DEVICE_ARG.name)) {
+            (Some(wanted), Some(device)) => device == wanted,
+            _ => true,
+        })
+        .collect()
+}
+
+/// Structured error for `execute_quick_action`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum QuickActionError {
+    UnknownAction { id: String },
+    MissingArgument { id: String, name: String },
+    UnexpectedArgument { id: String, name: String },
+    /// The arguments are well-formed but the action cannot run with them now
+    Unavailable { id: String },
+}
+
+impl QuickActionError {
+    /// Encode for a `Result<_, String>` command error
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl std::fmt::Display for QuickActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuickActionError::UnknownAction { id } => write!(f, "Unknown quick action '{}'", id),
+            QuickActionError::MissingArgument { id, name } => write!(f, "Quick action '{}' needs '{}'", id, name),
+            QuickActionError::UnexpectedArgument { id, name } => write!(f, "Quick action '{}' does not take '{}'", id, name),
+            QuickActionError::Unavailable { id } => write!(f, "Quick action '{}' is not available right now", id),
+        }
+    }
+}
+
+impl std::error::Error for QuickActionError {}
+
+/// Check `args` against the action's declared arguments and current availability
+pub fn validate_invocation(
+    ctx: &ActionContext,
+    id: &str,
+    args: &ActionArgs,
+) -> Result<&'static QuickActionSpec, QuickActionError> {
+    let spec = find_action(id).ok_or_else(|| QuickActionError::UnknownAction { id: id.to_string() })?;
+    for arg in spec.required_args {
+        if args.get(arg.name).map_or(true, |value| value.trim().is_empty()) {
+            return Err(QuickActionError::MissingArgument { id: id.to_string(), name: arg.name.to_string() });
+        }
+    }
+    if let Some(extra) = args.keys().find(|name| !spec.required_args.iter().any(|arg| arg.name == name.as_str())) {
+        return Err(QuickActionError::UnexpectedArgument { id: id.to_string(), name: extra.clone() });
+    }
+    if !(spec.availability)(ctx).iter().any(|offer| &offer.args == args) {
+        return Err(QuickActionError::Unavailable { id: id.to_string() });
+    }
+    Ok(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, label: &str, state: TrayDeviceState) -> TrayDevice {
+        TrayDevice { device_id: id.to_string(), label: label.to_string(), state }
+    }
+
+    fn args(pairs: &[(&str, &str)]) -> ActionArgs {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn ids(actions: &[QuickAction]) -> Vec<&str> {
+        actions.iter().map(|a| a.id.as_str()).collect()
+    }
+
+    fn two_devices() -> ActionContext {
+        ActionContext {
+            devices: vec![
+                device("dev1", "Main", TrayDeviceState::Ready),
+                device("dev2", "Travel", TrayDeviceState::Locked),
+            ],
+            imported_wallets: 0,
+            portfolio_caips: [("dev1".to_string(), vec!["bip122:000000000019d6689c085ae165831e93/slip44:0".to_string()])]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_nothing_connected_hides_device_actions() {
+        let actions = list_quick_actions(&ActionContext::default(), None);
+        assert_eq!(ids(&actions), vec!["open_diagnostics"]);
+
+        // Watch-only wallets can still be synced
+        let ctx = ActionContext { imported_wallets: 1, ..Default::default() };
+        assert_eq!(ids(&list_quick_actions(&ctx, None)), vec!["sync_now", "open_diagnostics"]);
+    }
+
+    #[test]
+    fn test_availability_follows_device_state() {
+        let actions = list_quick_actions(&two_devices(), None);
+        assert_eq!(ids(&actions), vec![
+            "sync_now",
+            "lock_device",
+            "lock_all_devices",
+            "show_receive_address",
+            "open_diagnostics",
+            "check_for_updates",
+            "check_for_updates",
+        ]);
+        // Only the unlocked device can be locked or show an address
+        let lock = actions.iter().find(|a| a.id == "lock_device").unwrap();
+        assert_eq!(lock.args, args(&[("device_id", "dev1")]));
+        assert_eq!(lock.subject.as_deref(), Some("Main"));
+        let receive = actions.iter().find(|a| a.id == "show_receive_address").unwrap();
+        assert_eq!(receive.args["caip"], "bip122:000000000019d6689c085ae165831e93/slip44:0");
+
+        // Scoped to the locked device, only its own and global actions remain
+        let scoped = list_quick_actions(&two_devices(), Some("dev2"));
+        assert_eq!(ids(&scoped), vec!["sync_now", "lock_all_devices", "open_diagnostics", "check_for_updates"]);
+        assert_eq!(scoped[3].args, args(&[("device_id", "dev2")]));
+    }
+
+    #[test]
+    fn test_validate_arguments() {
+        let ctx = two_devices();
+        assert_eq!(validate_invocation(&ctx, "lock_device", &args(&[("device_id", "dev1")])).unwrap().id, "lock_device");
+        assert!(validate_invocation(&ctx, "sync_now", &ActionArgs::new()).is_ok());
+
+        assert_eq!(
+            validate_invocation(&ctx, "wipe_device", &ActionArgs::new()).unwrap_err(),
+            QuickActionError::UnknownAction { id: "wipe_device".to_string() }
+        );
+        assert_eq!(
+            validate_invocation(&ctx, "lock_device", &args(&[("device_id", " ")])).unwrap_err(),
+            QuickActionError::MissingArgument { id: "lock_device".to_string(), name: "device_id".to_string() }
+        );
+        assert_eq!(
+            validate_invocation(&ctx, "show_receive_address", &args(&[("device_id", "dev1")])).unwrap_err(),
+            QuickActionError::MissingArgument { id: "show_receive_address".to_string(), name: "caip".to_string() }
+        );
+        assert_eq!(
+            validate_invocation(&ctx, "sync_now", &args(&[("device_id", "dev1")])).unwrap_err(),
+            QuickActionError::UnexpectedArgument { id: "sync_now".to_string(), name: "device_id".to_string() }
+        );
+        // Well-formed but not runnable: the device is already locked, or not connected
+        for device_id in ["dev2", "gone"] {
+            assert_eq!(
+                validate_invocation(&ctx, "lock_device", &args(&[("device_id", device_id)])).unwrap_err(),
+                QuickActionError::Unavailable { id: "lock_device".to_string() }
+            );
+        }
+        assert_eq!(
+            validate_invocation(&ctx, "show_receive_address", &args(&[("device_id", "dev1"), ("caip", "eip155:1/slip44:60")]))
+                .unwrap_err(),
+            QuickActionError::Unavailable { id: "show_receive_address".to_string() }
+        );
+
+        let json: serde_json::Value = serde_json::from_str(
+            &QuickActionError::Unavailable { id: "lock_device".to_string() }.to_json_string()
+        ).unwrap();
+        assert_eq!(json["kind"], "Unavailable");
+    }
+
+    #[test]
+    fn test_registry_ids_are_unique() {
+        for (i, action) in QUICK_ACTIONS.iter().enumerate() {
+            assert!(QUICK_ACTIONS[i + 1..].iter().all(|other| other.id != action.id), "duplicate {}", action.id);
+            assert!(action.title_code.ends_with(action.id));
+        }
+    }
+}
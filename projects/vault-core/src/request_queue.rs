@@ -0,0 +1,325 @@
+// request_queue.rs - Device operations run one at a time per device
+//
+// The device worker runs one command at a time, but an operation such as
+// reading features and then checking the bootloader spans several commands,
+// and the frontend fires a few of those at once for the same device when a
+// view opens. The device then sometimes answers the interleaved request with
+// a Failure. Operations wrapped here run strictly one at a time per device,
+// in arrival order (tokio's Mutex is fair), each with a timeout that stops
+// while the device waits for a passphrase. Operations still waiting when the
+// device disconnects are cancelled; the running one is left to its timeout.
+//
+// Not reentrant: an operation must not run another one for the same device.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::watch;
+use crate::passphrase::passphrase_prompts;
+
+/// Timeout of an operation once it has the device
+pub const DEFAULT_OPERATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often `wait_idle` looks at the lanes
+const IDLE_POLL: Duration = Duration::from_millis(50);
+
+struct DeviceLane {
+    turn: tokio::sync::Mutex<()>,
+    waiting: AtomicU32,
+    running: AtomicU32,
+    /// Bumped to cancel every operation waiting at that moment
+    cancel_epoch: watch::Sender<u64>,
+}
+
+impl DeviceLane {
+    fn new() -> Self {
+        Self {
+            turn: tokio::sync::Mutex::new(()),
+            waiting: AtomicU32::new(0),
+            running: AtomicU32::new(0),
+            cancel_epoch: watch::channel(0).0,
+        }
+    }
+}
+
+/// Decrements a lane counter when the operation ends or its caller gives up
+struct Counted<'a>(&'a AtomicU32);
+
+impl<'a> Counted<'a> {
+    fn new(counter: &'a AtomicU32) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for Counted<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Per-device serialization of device operations
+pub struct RequestQueue {
+    lanes: Mutex<HashMap<String, Arc<DeviceLane>>>,
+    timeout: Duration,
+}
+
+impl RequestQueue {
+    pub fn new(timeout: Duration) -> Self {
+        Self { lanes: Mutex::new(HashMap::new()), timeout }
+    }
+
+    fn lane(&self, device_id: &str) -> Arc<DeviceLane> {
+        let mut lanes = self.lanes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        lanes.entry(device_id.to_string()).or_insert_with(|| Arc::new(DeviceLane::new())).clone()
+    }
+
+    /// Run `operation` once every earlier operation on `device_id` finished,
+    /// with the queue's timeout
+    pub async fn run<T, F, Fut>(&self, device_id: &str, operation: &str, f: F) -> Result<T, String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, String>>,
+    {
+        self.run_with_timeout(device_id, operation, self.timeout, f).await
+    }
+
+    /// `run` with a timeout of its own, for operations slower or faster than
+    /// the default. The timeout starts when the operation gets the device.
+    pub async fn run_with_timeout<T, F, Fut>(
+        &self,
+        device_id: &str,
+        operation: &str,
+        timeout: Duration,
+        f: F,
+    ) -> Result<T, String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, String>>,
+    {
+        let lane = self.lane(device_id);
+        let turn = {
+            let _waiting = Counted::new(&lane.waiting);
+            // Only cancellations after this point concern this operation
+            let mut cancelled = lane.cancel_epoch.subscribe();
+            tokio::select! {
+                turn = lane.turn.lock() => turn,
+                _ = cancelled.changed() => {
+                    return Err(format!("{} on {} was cancelled: the device disconnected", operation, device_id));
+                }
+            }
+        };
+        let _running = Counted::new(&lane.running);
+        let mut running = std::pin::pin!(f());
+        let mut deadline = tokio::time::Instant::now() + timeout;
+        let result = loop {
+            match tokio::time::timeout_at(deadline, running.as_mut()).await {
+                Ok(result) => break Some(result),
+                // The device is waiting on the user's passphrase, which has a
+                // timeout of its own; the operation gets its time again after
+                Err(_) if passphrase_prompts().is_waiting(device_id) => {
+                    deadline = tokio::time::Instant::now() + timeout;
+                }
+                Err(_) => break None,
+            }
+        };
+        drop(turn);
+        result.unwrap_or_else(|| {
+            Err(format!("{} on {} timed out after {}s", operation, device_id, timeout.as_secs()))
+        })
+    }
+
+    /// Operations waiting for or running on `device_id`
+    pub fn depth(&self, device_id: &str) -> u32 {
+        let lanes = self.lanes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        lanes.get(device_id).map_or(0, |lane| {
+            lane.waiting.load(Ordering::SeqCst) + lane.running.load(Ordering::SeqCst)
+        })
+    }
+
+    /// Operations waiting for or running on any device
+    pub fn total_depth(&self) -> u32 {
+        let lanes = self.lanes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        lanes.values()
+            .map(|lane| lane.waiting.load(Ordering::SeqCst) + lane.running.load(Ordering::SeqCst))
+            .sum()
+    }
+
+    /// Wait up to `timeout` for every device's operations to finish. Nothing
+    /// stops new ones from being queued meanwhile; callers refuse them first
+    /// (maintenance mode). The error is how many were still queued.
+    pub async fn wait_idle(&self, timeout: Duration) -> Result<(), u32> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let depth = self.total_depth();
+            if depth == 0 {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(depth);
+            }
+            tokio::time::sleep(IDLE_POLL).await;
+        }
+    }
+
+    /// Cancel the operations waiting on `device_id`; returns how many were
+    pub fn cancel_pending(&self, device_id: &str) -> u32 {
+        let lanes = self.lanes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(lane) = lanes.get(device_id) else { return 0 };
+        lane.cancel_epoch.send_modify(|epoch| *epoch += 1);
+        lane.waiting.load(Ordering::SeqCst)
+    }
+}
+
+/// The queue shared by the GUI commands and vault-core's device operations
+pub fn request_queue() -> &'static RequestQueue {
+    static QUEUE: OnceLock<RequestQueue> = OnceLock::new();
+    QUEUE.get_or_init(|| RequestQueue::new(DEFAULT_OPERATION_TIMEOUT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepkey_rust::device_queue::{DeviceCmd, DeviceQueueHandle};
+    use keepkey_rust::messages::Features;
+    use std::sync::atomic::AtomicBool;
+    use tokio::sync::mpsc;
+
+    /// Answers GetFeatures like a device worker, failing any command that
+    /// arrives while another one is being answered
+    fn mock_device(mut cmd_rx: mpsc::Receiver<DeviceCmd>, interleaved: Arc<AtomicBool>) {
+        tokio::spawn(async move {
+            while let Some(cmd) = cmd_rx.recv().await {
+                if let DeviceCmd::GetFeatures { respond_to, .. } = cmd {
+                    tokio::time::sleep(Duration::from_millis(2)).await;
+                    // Another command already queued means two operations overlapped
+                    if !cmd_rx.is_empty() {
+                        interleaved.store(true, Ordering::SeqCst);
+                    }
+                    let _ = respond_to.send(Ok(Features { label: Some("kk".to_string()), ..Default::default() }));
+                }
+            }
+        });
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_get_features_run_one_at_a_time() {
+        let queue = Arc::new(RequestQueue::new(DEFAULT_OPERATION_TIMEOUT));
+        let (cmd_tx, cmd_rx) = mpsc::channel(100);
+        let handle = DeviceQueueHandle::new("kk1".to_string(), cmd_tx);
+        let interleaved = Arc::new(AtomicBool::new(false));
+        mock_device(cmd_rx, interleaved.clone());
+
+        let calls: Vec<_> = (0..20)
+            .map(|_| {
+                let (queue, handle) = (queue.clone(), handle.clone());
+                tokio::spawn(async move {
+                    queue.run("kk1", "get_features", || async {
+                        handle.get_features().await.map_err(|e| e.to_string())
+                    }).await
+                })
+            })
+            .collect();
+
+        let results = tokio::time::timeout(Duration::from_secs(10), async {
+            let mut results = Vec::new();
+            for call in calls {
+                results.push(call.await.unwrap());
+            }
+            results
+        }).await.expect("serialized calls deadlocked");
+
+        assert!(results.iter().all(|r| r.as_ref().unwrap().label.as_deref() == Some("kk")));
+        assert!(!interleaved.load(Ordering::SeqCst));
+        assert_eq!(queue.depth("kk1"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_cancels_waiting_operations_and_slow_ones_time_out() {
+        let queue = Arc::new(RequestQueue::new(Duration::from_millis(200)));
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+
+        let running = tokio::spawn({
+            let queue = queue.clone();
+            async move {
+                queue.run("kk1", "sign_tx", || async {
+                    released.await.map_err(|e| e.to_string())
+                }).await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let waiting: Vec<_> = (0..3)
+            .map(|_| {
+                let queue = queue.clone();
+                tokio::spawn(async move { queue.run("kk1", "get_features", || async { Ok(()) }).await })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(queue.depth("kk1"), 4);
+
+        assert_eq!(queue.cancel_pending("kk1"), 3);
+        for call in waiting {
+            assert!(call.await.unwrap().unwrap_err().contains("cancelled"));
+        }
+        // The running operation is not cancelled
+        release.send(()).unwrap();
+        assert!(running.await.unwrap().is_ok());
+        assert_eq!(queue.depth("kk1"), 0);
+
+        // Operations queued after the disconnect run normally
+        assert_eq!(queue.run("kk1", "get_features", || async { Ok(1) }).await, Ok(1));
+        let slow = queue.run("kk1", "get_features", || async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        }).await;
+        assert!(slow.unwrap_err().contains("timed out"));
+        assert_eq!(queue.run_with_timeout("kk2", "ping", Duration::from_secs(1), || async { Ok("pong") }).await, Ok("pong"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_idle_waits_for_every_device() {
+        let queue = Arc::new(RequestQueue::new(DEFAULT_OPERATION_TIMEOUT));
+        assert_eq!(queue.wait_idle(Duration::ZERO).await, Ok(()));
+
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let running = tokio::spawn({
+            let queue = queue.clone();
+            async move {
+                queue.run("kk2", "get_public_key", || async {
+                    released.await.map_err(|e| e.to_string())
+                }).await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(queue.total_depth(), 1);
+        assert_eq!(queue.wait_idle(Duration::from_millis(60)).await, Err(1));
+
+        let idle = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.wait_idle(Duration::from_secs(5)).await }
+        });
+        release.send(()).unwrap();
+        assert!(running.await.unwrap().is_ok());
+        assert_eq!(idle.await.unwrap(), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_stops_while_the_device_waits_for_a_passphrase() {
+        let queue = Arc::new(RequestQueue::new(Duration::from_millis(30)));
+        let mut requests = passphrase_prompts().subscribe();
+        let unlocking = tokio::spawn({
+            let queue = queue.clone();
+            async move {
+                queue.run("kk-passphrase", "get_public_key", || async {
+                    passphrase_prompts().wait("kk-passphrase", Duration::from_secs(5)).await
+                }).await
+            }
+        });
+        while requests.recv().await.unwrap() != "kk-passphrase" {}
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        passphrase_prompts().supply("kk-passphrase", zeroize::Zeroizing::new("hunter2".to_string())).unwrap();
+        assert_eq!(unlocking.await.unwrap().unwrap().as_str(), "hunter2");
+    }
+}
@@ -0,0 +1,394 @@
+// secure_notes.rs - Small secrets encrypted by the device
+//
+// A note body is encrypted with CipherKeyValue under a key the device derives
+// from the seed, the note's path (m/10018'/<note id>') and a key string built
+// from the title. Only the ciphertext is stored, so a note is useless without
+// the hardware; the same seed on a replacement device decrypts it again.
+//
+// Reading always asks for a button press: the ask_on_decrypt flag is part of
+// the key derivation, so a note encrypted with it set cannot be decrypted
+// with it cleared. The decrypted body goes back to the caller and is never
+// written to the database, the logs or an export.
+
+use std::future::Future;
+use serde::Serialize;
+use keepkey_db::{Database, SecureNote};
+use keepkey_rust::features::capabilities::{Capability, FirmwareCapabilities};
+use keepkey_rust::messages::{self, Message};
+use crate::paths::HARDENED;
+use crate::queue::{get_or_create_device_queue, DeviceQueueManager};
+
+/// First path component of note keys, outside any coin or SLIP-0011 subtree
+pub const NOTE_KEY_PURPOSE: u32 = 10018;
+
+/// CipherKeyValue takes at most 1024 bytes and the body is padded to a
+/// 16-byte boundary, which always adds at least one byte
+pub const MAX_NOTE_BYTES: usize = 1023;
+
+const CIPHER_BLOCK: usize = 16;
+const ASK_ON_ENCRYPT: bool = false;
+const ASK_ON_DECRYPT: bool = true;
+
+/// Errors of the secure-note commands, encoded for the frontend by `kind`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum SecureNoteError {
+    /// The firmware cannot run CipherKeyValue
+    NotSupported { reason: String },
+    /// The user rejected the request on the device
+    Cancelled,
+    DeviceLocked,
+    /// No wallet session is open on the device, so its seed is unknown
+    NoActiveWallet,
+    /// The note was written with a different seed than the device holds
+    WalletMismatch { note_wallet: String },
+    TooLarge { bytes: usize, max_bytes: usize },
+    NotFound { note_id: u32 },
+    /// The device returned something that is not a padded note body
+    Corrupt,
+    Device { message: String },
+}
+
+impl SecureNoteError {
+    /// Encode for a `Result<_, String>` command error
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl std::fmt::Display for SecureNoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecureNoteError::NotSupported { reason } => write!(f, "Secure notes are not supported: {}", reason),
+            SecureNoteError::Cancelled => write!(f, "Cancelled on the device"),
+            SecureNoteError::DeviceLocked => write!(f, "Unlock the device first"),
+            SecureNoteError::NoActiveWallet => write!(f, "Open the device's wallet first"),
+            SecureNoteError::WalletMismatch { note_wallet } => {
+                write!(f, "This note belongs to wallet {}, which is not the one on this device", note_wallet)
+            }
+            SecureNoteError::TooLarge { bytes, max_bytes } => {
+                write!(f, "Note is {} bytes; the limit is {} bytes", bytes, max_bytes)
+            }
+            SecureNoteError::NotFound { note_id } => write!(f, "No secure note with id {}", note_id),
+            SecureNoteError::Corrupt => write!(f, "The device returned an unreadable note"),
+            SecureNoteError::Device { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SecureNoteError {}
+
+/// One CipherKeyValue round-trip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CipherRequest {
+    pub address_n: Vec<u32>,
+    pub key: String,
+    pub value: Vec<u8>,
+    pub encrypt: bool,
+}
+
+/// Device answer to a `CipherRequest`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CipherReply {
+    Value(Vec<u8>),
+    Cancelled,
+    Locked,
+    /// The device answered with a Failure other than a cancellation
+    Refused(String),
+}
+
+pub fn note_address_n(note_id: u32) -> Vec<u32> {
+    vec![NOTE_KEY_PURPOSE | HARDENED, note_id | HARDENED]
+}
+
+/// Key string of a note; shown on the device when it is decrypted
+pub fn note_cipher_key(title: &str) -> String {
+    format!("Secure note: {}", title)
+}
+
+/// PKCS#7-pad a body to the cipher block size
+fn pad(body: &[u8]) -> Vec<u8> {
+    let fill = CIPHER_BLOCK - body.len() % CIPHER_BLOCK;
+    let mut padded = body.to_vec();
+    padded.resize(body.len() + fill, fill as u8);
+    padded
+}
+
+fn unpad(mut padded: Vec<u8>) -> Result<Vec<u8>, SecureNoteError> {
+    let fill = *padded.last().ok_or(SecureNoteError::Corrupt)? as usize;
+    if padded.len() % CIPHER_BLOCK != 0 || fill == 0 || fill > CIPHER_BLOCK
+        || padded[padded.len() - fill..].iter().any(|b| *b as usize != fill)
+    {
+        return Err(SecureNoteError::Corrupt);
+    }
+    padded.truncate(padded.len() - fill);
+    Ok(padded)
+}
+
+async fn cipher<E, Fut>(exchange: E, request: CipherRequest) -> Result<Vec<u8>, SecureNoteError>
+where
+    E: FnOnce(CipherRequest) -> Fut,
+    Fut: Future<Output = Result<CipherReply, String>>,
+{
+    let expected_len = request.value.len();
+    match exchange(request).await.map_err(|message| SecureNoteError::Device { message })? {
+        CipherReply::Value(value) if value.len() == expected_len => Ok(value),
+        CipherReply::Value(_) => Err(SecureNoteError::Corrupt),
+        CipherReply::Cancelled => Err(SecureNoteError::Cancelled),
+        CipherReply::Locked => Err(SecureNoteError::DeviceLocked),
+        // Firmware that predates the message rejects it outright
+        CipherReply::Refused(reason) if reason.to_ascii_lowercase().contains("unknown message") => {
+            Err(SecureNoteError::NotSupported { reason })
+        }
+        CipherReply::Refused(message) => Err(SecureNoteError::Device { message }),
+    }
+}
+
+/// Encrypt a note body on the device
+pub async fn encrypt_note<E, Fut>(exchange: E, note_id: u32, title: &str, body: &str) -> Result<Vec<u8>, SecureNoteError>
+where
+    E: FnOnce(CipherRequest) -> Fut,
+    Fut: Future<Output = Result<CipherReply, String>>,
+{
+    if body.len() > MAX_NOTE_BYTES {
+        return Err(SecureNoteError::TooLarge { bytes: body.len(), max_bytes: MAX_NOTE_BYTES });
+    }
+    cipher(exchange, CipherRequest {
+        address_n: note_address_n(note_id),
+        key: note_cipher_key(title),
+        value: pad(body.as_bytes()),
+        encrypt: true,
+    }).await
+}
+
+/// Decrypt a note body on the device; the user confirms on the screen
+pub async fn decrypt_note<E, Fut>(exchange: E, note_id: u32, title: &str, ciphertext: &[u8]) -> Result<String, SecureNoteError>
+where
+    E: FnOnce(CipherRequest) -> Fut,
+    Fut: Future<Output = Result<CipherReply, String>>,
+{
+    if ciphertext.is_empty() || ciphertext.len() % CIPHER_BLOCK != 0 {
+        return Err(SecureNoteError::Corrupt);
+    }
+    let padded = cipher(exchange, CipherRequest {
+        address_n: note_address_n(note_id),
+        key: note_cipher_key(title),
+        value: ciphertext.to_vec(),
+        encrypt: false,
+    }).await?;
+    String::from_utf8(unpad(padded)?).map_err(|_| SecureNoteError::Corrupt)
+}
+
+/// Send a request as CipherKeyValue; the queue answers button requests itself
+async fn queue_exchange(
+    queue: &keepkey_rust::device_queue::DeviceQueueHandle,
+    request: CipherRequest,
+) -> Result<CipherReply, String> {
+    let message = Message::CipherKeyValue(messages::CipherKeyValue {
+        address_n: request.address_n,
+        key: Some(request.key),
+        value: Some(request.value),
+        encrypt: Some(request.encrypt),
+        ask_on_encrypt: Some(ASK_ON_ENCRYPT),
+        ask_on_decrypt: Some(ASK_ON_DECRYPT),
+        ..Default::default()
+    });
+
+    let response = match queue.send_raw(message, true).await {
+        Ok(response) => response,
+        // The standard handler turns device Failures into errors
+        Err(e) => {
+            let error = e.to_string();
+            return match error.strip_prefix("Failure: ") {
+                Some(reason) if reason.to_ascii_lowercase().contains("cancel") => Ok(CipherReply::Cancelled),
+                Some(reason) => Ok(CipherReply::Refused(reason.to_string())),
+                None => Err(error),
+            };
+        }
+    };
+
+    match response {
+        Message::CipheredKeyValue(ciphered) => ciphered
+            .value
+            .map(CipherReply::Value)
+            .ok_or_else(|| "No value in response".to_string()),
+        Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+            if let Err(e) = queue.send_raw(Message::Cancel(messages::Cancel::default()), true).await {
+                log::warn!("Failed to cancel unlock prompt after cipher request: {}", e);
+            }
+            Ok(CipherReply::Locked)
+        }
+        other => Err(format!("Unexpected response: {:?}", other.message_type())),
+    }
+}
+
+async fn require_cipher_support(device_id: &str, queue_manager: &DeviceQueueManager) -> Result<(), SecureNoteError> {
+    let features = crate::features::get_device_features(device_id, queue_manager)
+        .await
+        .map_err(|message| SecureNoteError::Device { message })?;
+    match FirmwareCapabilities::from_features(&features).unsupported_reason(Capability::CipherKeyValue) {
+        Some(reason) => Err(SecureNoteError::NotSupported { reason }),
+        None => Ok(()),
+    }
+}
+
+fn database_error(e: keepkey_db::DatabaseError) -> SecureNoteError {
+    SecureNoteError::Device { message: format!("Database error: {}", e) }
+}
+
+/// Random note id, unused so far; it selects the note's key path
+async fn unused_note_id(db: &Database) -> Result<u32, SecureNoteError> {
+    for _ in 0..8 {
+        let mut bytes = [0u8; 4];
+        getrandom::fill(&mut bytes).map_err(|e| SecureNoteError::Device { message: format!("Failed to pick a note id: {}", e) })?;
+        let id = u32::from_le_bytes(bytes) & !HARDENED;
+        if db.get_secure_note_ciphertext(id).await.map_err(database_error)?.is_none() {
+            return Ok(id);
+        }
+    }
+    Err(SecureNoteError::Device { message: "Could not pick an unused note id".to_string() })
+}
+
+/// Encrypt `body` on the device and store the ciphertext for `wallet_fingerprint`
+pub async fn add_secure_note(
+    db: &Database,
+    device_id: &str,
+    queue_manager: &DeviceQueueManager,
+    wallet_fingerprint: &str,
+    title: &str,
+    body: &str,
+) -> Result<SecureNote, SecureNoteError> {
+    let title = keepkey_db::secure_notes::validate_title(title)
+        .map_err(|e| SecureNoteError::Device { message: e.to_string() })?;
+    require_cipher_support(device_id, queue_manager).await?;
+    let note_id = unused_note_id(db).await?;
+
+    let queue = get_or_create_device_queue(device_id, queue_manager)
+        .await
+        .map_err(|message| SecureNoteError::Device { message })?;
+    let ciphertext = encrypt_note(|request| queue_exchange(&queue, request), note_id, &title, body).await?;
+    db.add_secure_note(note_id, wallet_fingerprint, &title, &ciphertext, body.len() as u32, Some(device_id))
+        .await
+        .map_err(database_error)
+}
+
+/// Decrypt a stored note on a device holding the wallet it was written with
+pub async fn read_secure_note(
+    db: &Database,
+    device_id: &str,
+    queue_manager: &DeviceQueueManager,
+    wallet_fingerprint: Option<&str>,
+    note_id: u32,
+) -> Result<String, SecureNoteError> {
+    let (note, ciphertext) = db.get_secure_note_ciphertext(note_id)
+        .await
+        .map_err(database_error)?
+        .ok_or(SecureNoteError::NotFound { note_id })?;
+    match wallet_fingerprint {
+        None => return Err(SecureNoteError::NoActiveWallet),
+        Some(fingerprint) if fingerprint != note.wallet_fingerprint => {
+            return Err(SecureNoteError::WalletMismatch { note_wallet: note.wallet_fingerprint });
+        }
+        Some(_) => {}
+    }
+    require_cipher_support(device_id, queue_manager).await?;
+
+    let queue = get_or_create_device_queue(device_id, queue_manager)
+        .await
+        .map_err(|message| SecureNoteError::Device { message })?;
+    decrypt_note(|request| queue_exchange(&queue, request), note.id, &note.title, &ciphertext).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::ready;
+
+    /// Stand-in for the device: a keystream derived from the path and key, so
+    /// only the same path and key decrypt. `script` overrides the next replies.
+    #[derive(Default)]
+    struct MockCipher {
+        script: Vec<CipherReply>,
+        requests: Vec<CipherRequest>,
+    }
+
+    impl MockCipher {
+        fn reply(&mut self, request: CipherRequest) -> Result<CipherReply, String> {
+            self.requests.push(request.clone());
+            if !self.script.is_empty() {
+                return Ok(self.script.remove(0));
+            }
+            let seed = request.address_n.iter().fold(request.key.len() as u32, |acc, n| acc.rotate_left(5) ^ n);
+            let value = request
+                .value
+                .iter()
+                .enumerate()
+                .map(|(i, b)| b ^ (seed.wrapping_mul(i as u32 + 1) >> 7) as u8 ^ 0xa5)
+                .collect();
+            Ok(CipherReply::Value(value))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let mut device = MockCipher::default();
+        let body = "backup codes: 1234-5678 8765-4321";
+        let ciphertext = encrypt_note(|r| ready(device.reply(r)), 42, "Exchange 2FA", body).await.unwrap();
+        assert_eq!(ciphertext.len(), 48);
+        assert!(!ciphertext.windows(4).any(|w| w == b"1234"));
+
+        let decrypted = decrypt_note(|r| ready(device.reply(r)), 42, "Exchange 2FA", &ciphertext).await.unwrap();
+        assert_eq!(decrypted, body);
+
+        let [encrypt, decrypt] = device.requests.as_slice() else { panic!("expected two requests") };
+        assert_eq!(encrypt.address_n, vec![NOTE_KEY_PURPOSE | HARDENED, 42 | HARDENED]);
+        assert_eq!(encrypt.key, "Secure note: Exchange 2FA");
+        assert!(encrypt.encrypt && !decrypt.encrypt);
+        assert_eq!(decrypt.address_n, encrypt.address_n);
+
+        // A body that fills whole blocks still gets a full block of padding
+        let exact = "a".repeat(32);
+        let ciphertext = encrypt_note(|r| ready(device.reply(r)), 7, "Hint", &exact).await.unwrap();
+        assert_eq!(ciphertext.len(), 48);
+        assert_eq!(decrypt_note(|r| ready(device.reply(r)), 7, "Hint", &ciphertext).await.unwrap(), exact);
+
+        // Another note's key does not produce the body
+        let wrong = decrypt_note(|r| ready(device.reply(r)), 8, "Hint", &ciphertext).await;
+        assert_ne!(wrong, Ok(exact));
+    }
+
+    #[tokio::test]
+    async fn test_device_refusals() {
+        let ciphertext = vec![0u8; 16];
+        for (reply, expected) in [
+            (CipherReply::Cancelled, SecureNoteError::Cancelled),
+            (CipherReply::Locked, SecureNoteError::DeviceLocked),
+            (
+                CipherReply::Refused("Unknown message".to_string()),
+                SecureNoteError::NotSupported { reason: "Unknown message".to_string() },
+            ),
+            (CipherReply::Value(vec![0u8; 15]), SecureNoteError::Corrupt),
+        ] {
+            let mut device = MockCipher { script: vec![reply], ..Default::default() };
+            let result = decrypt_note(|r| ready(device.reply(r)), 1, "Hint", &ciphertext).await;
+            assert_eq!(result, Err(expected));
+        }
+
+        let json: serde_json::Value = serde_json::from_str(&SecureNoteError::Cancelled.to_json_string()).unwrap();
+        assert_eq!(json["kind"], "Cancelled");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_and_corrupt_notes() {
+        let mut device = MockCipher::default();
+        let result = encrypt_note(|r| ready(device.reply(r)), 1, "Big", &"x".repeat(MAX_NOTE_BYTES + 1)).await;
+        assert_eq!(result, Err(SecureNoteError::TooLarge { bytes: MAX_NOTE_BYTES + 1, max_bytes: MAX_NOTE_BYTES }));
+        assert!(device.requests.is_empty());
+        assert_eq!(pad(&[b'x'; MAX_NOTE_BYTES]).len(), 1024);
+
+        assert_eq!(decrypt_note(|r| ready(device.reply(r)), 1, "Big", &[1, 2, 3]).await, Err(SecureNoteError::Corrupt));
+        // The device returned the block unchanged: not valid padding
+        let mut echo = MockCipher { script: vec![CipherReply::Value(vec![0u8; 16])], ..Default::default() };
+        assert_eq!(decrypt_note(|r| ready(echo.reply(r)), 1, "Big", &[0u8; 16]).await, Err(SecureNoteError::Corrupt));
+    }
+}
@@ -0,0 +1,384 @@
+// self_test.rs - Hardware self-test run through the device queue
+//
+// The protocol has no single diagnostic message, so the self-test is composed
+// of ordinary requests that exercise one subsystem each:
+//   display - a button-protected Ping the user has to confirm on the screen
+//   rng     - two GetEntropy reads that must differ and look random
+//   storage - the account xpub read back twice, and against the stored copy
+// Display and RNG steps wait for a button press, so callers get a step
+// callback to surface the prompt before each one.
+
+use std::future::Future;
+use serde::Serialize;
+use keepkey_db::Database;
+use keepkey_rust::features::capabilities::{Capability, FirmwareCapabilities};
+use keepkey_rust::messages::{self, Message};
+use crate::paths::HARDENED;
+use crate::queue::{get_or_create_device_queue, DeviceQueueManager};
+
+/// Account whose xpub is read back for the storage check
+pub const STORAGE_CHECK_PATH: &str = "m/44'/0'/0'";
+const STORAGE_CHECK_ADDRESS_N: [u32; 3] = [44 | HARDENED, HARDENED, HARDENED];
+
+/// Bytes of entropy per RNG read
+const ENTROPY_SIZE: u32 = 32;
+/// Fewer distinct byte values than this in a read is treated as a stuck RNG
+const MIN_DISTINCT_ENTROPY_BYTES: usize = 16;
+
+const DISPLAY_TEST_TEXT: &str = "Self-test: confirm if this text is readable";
+
+/// Step about to run; steps that wait for a button press are flagged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum SelfTestStep {
+    Display,
+    Rng,
+    Storage,
+}
+
+impl SelfTestStep {
+    pub fn awaits_button(&self) -> bool {
+        matches!(self, SelfTestStep::Display | SelfTestStep::Rng)
+    }
+}
+
+/// One device round-trip the self-test needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelfTestRequest {
+    /// Show `text` and wait for the user to confirm it
+    ConfirmOnDisplay { text: String },
+    Entropy { size: u32 },
+    PublicKey { address_n: Vec<u32> },
+}
+
+/// Device answer to a `SelfTestRequest`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelfTestReply {
+    /// The display request was confirmed; carries the echoed text
+    Confirmed(String),
+    /// The user rejected the request on the device
+    Cancelled,
+    Entropy(Vec<u8>),
+    PublicKey(String),
+    /// The device answered with a Failure other than a cancellation
+    Refused(String),
+}
+
+/// Per-subsystem results; None when the check did not run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SelfTestFlags {
+    pub display_ok: Option<bool>,
+    pub storage_ok: Option<bool>,
+    pub rng_ok: Option<bool>,
+}
+
+/// Outcome of `run_device_self_test`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SelfTestOutcome {
+    /// Firmware cannot run the self-test; not an error
+    NotSupported { reason: String },
+    Passed { flags: SelfTestFlags },
+    /// At least one check failed; `failures` explains each
+    Failed { flags: SelfTestFlags, failures: Vec<String> },
+    /// The user rejected a step on the device; checks after it did not run
+    Cancelled { flags: SelfTestFlags },
+}
+
+impl SelfTestOutcome {
+    /// Value stored in `device_health_checks.outcome`; None for NotSupported
+    pub fn db_outcome(&self) -> Option<&'static str> {
+        match self {
+            SelfTestOutcome::NotSupported { .. } => None,
+            SelfTestOutcome::Passed { .. } => Some("passed"),
+            SelfTestOutcome::Failed { .. } => Some("failed"),
+            SelfTestOutcome::Cancelled { .. } => Some("cancelled"),
+        }
+    }
+
+    pub fn flags(&self) -> Option<&SelfTestFlags> {
+        match self {
+            SelfTestOutcome::NotSupported { .. } => None,
+            SelfTestOutcome::Passed { flags }
+            | SelfTestOutcome::Failed { flags, .. }
+            | SelfTestOutcome::Cancelled { flags } => Some(flags),
+        }
+    }
+
+    /// A failed storage check means key material may be unreliable
+    pub fn storage_failed(&self) -> bool {
+        self.flags().map(|f| f.storage_ok == Some(false)).unwrap_or(false)
+    }
+}
+
+fn entropy_failure(first: &[u8], second: &[u8]) -> Option<String> {
+    let expected = ENTROPY_SIZE as usize;
+    if first.len() != expected || second.len() != expected {
+        return Some(format!("RNG returned {} and {} bytes, expected {}", first.len(), second.len(), expected));
+    }
+    if first == second {
+        return Some("RNG returned the same bytes twice".to_string());
+    }
+    for sample in [first, second] {
+        let mut seen = [false; 256];
+        sample.iter().for_each(|b| seen[*b as usize] = true);
+        let distinct = seen.iter().filter(|s| **s).count();
+        if distinct < MIN_DISTINCT_ENTROPY_BYTES {
+            return Some(format!("RNG output has only {} distinct byte values", distinct));
+        }
+    }
+    None
+}
+
+/// Run the checks over `exchange`, calling `on_step` before each one.
+///
+/// `reference_xpub` is the stored xpub for `STORAGE_CHECK_PATH`, if the vault
+/// has one; without it the storage check only requires two identical reads.
+/// Transport errors abort with Err; device refusals count as failed checks.
+pub async fn run_checks<E, Fut, S>(
+    mut exchange: E,
+    reference_xpub: Option<&str>,
+    mut on_step: S,
+) -> Result<SelfTestOutcome, String>
+where
+    E: FnMut(SelfTestRequest) -> Fut,
+    Fut: Future<Output = Result<SelfTestReply, String>>,
+    S: FnMut(SelfTestStep),
+{
+    let mut flags = SelfTestFlags::default();
+    let mut failures = Vec::new();
+
+    on_step(SelfTestStep::Display);
+    match exchange(SelfTestRequest::ConfirmOnDisplay { text: DISPLAY_TEST_TEXT.to_string() }).await? {
+        SelfTestReply::Cancelled => return Ok(SelfTestOutcome::Cancelled { flags }),
+        SelfTestReply::Confirmed(echo) if echo == DISPLAY_TEST_TEXT => flags.display_ok = Some(true),
+        SelfTestReply::Confirmed(echo) => {
+            flags.display_ok = Some(false);
+            failures.push(format!("Display test echoed '{}'", echo));
+        }
+        SelfTestReply::Refused(reason) => {
+            flags.display_ok = Some(false);
+            failures.push(format!("Display test refused: {}", reason));
+        }
+        other => return Err(format!("Unexpected reply to display test: {:?}", other)),
+    }
+
+    on_step(SelfTestStep::Rng);
+    let mut samples = Vec::with_capacity(2);
+    for _ in 0..2 {
+        match exchange(SelfTestRequest::Entropy { size: ENTROPY_SIZE }).await? {
+            SelfTestReply::Cancelled => return Ok(SelfTestOutcome::Cancelled { flags }),
+            SelfTestReply::Entropy(bytes) => samples.push(bytes),
+            SelfTestReply::Refused(reason) => {
+                failures.push(format!("Entropy request refused: {}", reason));
+                break;
+            }
+            other => return Err(format!("Unexpected reply to entropy request: {:?}", other)),
+        }
+    }
+    // A refusal was already recorded as the failure
+    let rng_failure = match samples.as_slice() {
+        [first, second] => entropy_failure(first, second),
+        _ => None,
+    };
+    flags.rng_ok = Some(samples.len() == 2 && rng_failure.is_none());
+    failures.extend(rng_failure);
+
+    on_step(SelfTestStep::Storage);
+    let mut reads = Vec::with_capacity(2);
+    for _ in 0..2 {
+        match exchange(SelfTestRequest::PublicKey { address_n: STORAGE_CHECK_ADDRESS_N.to_vec() }).await? {
+            SelfTestReply::Cancelled => return Ok(SelfTestOutcome::Cancelled { flags }),
+            SelfTestReply::PublicKey(xpub) => reads.push(xpub),
+            SelfTestReply::Refused(reason) => {
+                failures.push(format!("Storage read refused: {}", reason));
+                break;
+            }
+            other => return Err(format!("Unexpected reply to storage read: {:?}", other)),
+        }
+    }
+    let storage_failure = match reads.as_slice() {
+        [first, second] if first != second => Some("Account key changed between two reads".to_string()),
+        [first, _] => reference_xpub
+            .filter(|reference| *reference != first)
+            .map(|_| format!("Account key at {} does not match the stored copy", STORAGE_CHECK_PATH)),
+        _ => None,
+    };
+    flags.storage_ok = Some(reads.len() == 2 && storage_failure.is_none());
+    failures.extend(storage_failure);
+
+    Ok(if failures.is_empty() {
+        SelfTestOutcome::Passed { flags }
+    } else {
+        SelfTestOutcome::Failed { flags, failures }
+    })
+}
+
+/// Map a request onto device messages; the queue answers button requests itself
+async fn queue_exchange(
+    queue: &keepkey_rust::device_queue::DeviceQueueHandle,
+    request: SelfTestRequest,
+) -> Result<SelfTestReply, String> {
+    let message = match request {
+        SelfTestRequest::ConfirmOnDisplay { text } => Message::Ping(messages::Ping {
+            message: Some(text),
+            button_protection: Some(true),
+            ..Default::default()
+        }),
+        SelfTestRequest::Entropy { size } => Message::GetEntropy(messages::GetEntropy { size }),
+        SelfTestRequest::PublicKey { address_n } => Message::GetPublicKey(messages::GetPublicKey {
+            address_n,
+            ecdsa_curve_name: Some("secp256k1".to_string()),
+            show_display: Some(false),
+            coin_name: Some("Bitcoin".to_string()),
+            script_type: None,
+        }),
+    };
+
+    let response = match queue.send_raw(message, true).await {
+        Ok(response) => response,
+        // The standard handler turns device Failures into errors
+        Err(e) => {
+            let error = e.to_string();
+            return match error.strip_prefix("Failure: ") {
+                Some(reason) if reason.to_ascii_lowercase().contains("cancel") => Ok(SelfTestReply::Cancelled),
+                Some(reason) => Ok(SelfTestReply::Refused(reason.to_string())),
+                None => Err(error),
+            };
+        }
+    };
+
+    match response {
+        Message::Success(success) => Ok(SelfTestReply::Confirmed(success.message.unwrap_or_default())),
+        Message::Entropy(entropy) => Ok(SelfTestReply::Entropy(entropy.entropy)),
+        Message::PublicKey(public_key) => public_key
+            .xpub
+            .map(SelfTestReply::PublicKey)
+            .ok_or_else(|| "No xpub in response".to_string()),
+        Message::PinMatrixRequest(_) | Message::PassphraseRequest(_) => {
+            if let Err(e) = queue.send_raw(Message::Cancel(messages::Cancel::default()), true).await {
+                log::warn!("Failed to cancel unlock prompt after self-test: {}", e);
+            }
+            Err("Unlock the device before running the self-test".to_string())
+        }
+        other => Err(format!("Unexpected response: {:?}", other.message_type())),
+    }
+}
+
+/// Run the self-test on a device and store the result.
+///
+/// `on_step` is called before each step so the UI can prompt the user to
+/// look at or press the button on the device.
+pub async fn run_device_self_test<S>(
+    db: &Database,
+    device_id: &str,
+    queue_manager: &DeviceQueueManager,
+    reference_xpub: Option<&str>,
+    on_step: S,
+) -> Result<SelfTestOutcome, String>
+where
+    S: FnMut(SelfTestStep),
+{
+    let features = crate::features::get_device_features(device_id, queue_manager).await?;
+    let capabilities = FirmwareCapabilities::from_features(&features);
+    if let Some(reason) = capabilities.unsupported_reason(Capability::SelfTest) {
+        return Ok(SelfTestOutcome::NotSupported { reason });
+    }
+
+    let queue = get_or_create_device_queue(device_id, queue_manager).await?;
+    let outcome = run_checks(|request| queue_exchange(&queue, request), reference_xpub, on_step).await?;
+
+    if let (Some(result), Some(flags)) = (outcome.db_outcome(), outcome.flags()) {
+        let details = match &outcome {
+            SelfTestOutcome::Failed { failures, .. } => serde_json::json!({ "failures": failures }),
+            _ => serde_json::json!({}),
+        };
+        db.record_health_check(device_id, result, flags.display_ok, flags.storage_ok, flags.rng_ok, &details.to_string())
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+    }
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepkey_rust::device_queue::{DeviceQueueFactory, DeviceQueueHandle};
+    use keepkey_rust::transport::mock::{MockDevice, MockTransport};
+
+    const XPUB: &str = "xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj";
+
+    /// A queue worker talking to a scripted device that holds XPUB at STORAGE_CHECK_PATH
+    fn mock_queue(edit: impl FnOnce(&mut MockDevice)) -> DeviceQueueHandle {
+        let mut device: MockDevice = serde_json::from_value(serde_json::json!({
+            "device_id": "MOCK0001",
+            "version": "7.9.1",
+            "initialized": true,
+            "xpubs": { STORAGE_CHECK_PATH: XPUB },
+        }))
+        .unwrap();
+        edit(&mut device);
+        DeviceQueueFactory::spawn_worker_with_transport(
+            device.device_id.clone(),
+            device.usb_device(),
+            Box::new(MockTransport::new(device)),
+        )
+    }
+
+    async fn run(queue: &DeviceQueueHandle, reference: Option<&str>) -> (SelfTestOutcome, Vec<SelfTestStep>) {
+        let mut steps = Vec::new();
+        let outcome = run_checks(|request| queue_exchange(queue, request), reference, |step| steps.push(step))
+            .await
+            .unwrap();
+        (outcome, steps)
+    }
+
+    #[tokio::test]
+    async fn test_healthy_device_passes() {
+        let (outcome, steps) = run(&mock_queue(|_| {}), Some(XPUB)).await;
+        let all_ok = SelfTestFlags { display_ok: Some(true), storage_ok: Some(true), rng_ok: Some(true) };
+        assert_eq!(outcome, SelfTestOutcome::Passed { flags: all_ok });
+        assert_eq!(steps, vec![SelfTestStep::Display, SelfTestStep::Rng, SelfTestStep::Storage]);
+        assert_eq!(outcome.db_outcome(), Some("passed"));
+    }
+
+    #[tokio::test]
+    async fn test_storage_failure_is_reported() {
+        // A consistent key that differs from the stored copy fails storage
+        let (outcome, _) = run(&mock_queue(|_| {}), Some("xpubSomethingElse")).await;
+        assert!(outcome.storage_failed());
+        match outcome {
+            SelfTestOutcome::Failed { flags, failures } => {
+                assert_eq!(flags.display_ok, Some(true));
+                assert_eq!(flags.rng_ok, Some(true));
+                assert_eq!(failures, vec![format!("Account key at {} does not match the stored copy", STORAGE_CHECK_PATH)]);
+            }
+            other => panic!("expected failure, got {:?}", other),
+        }
+
+        // A device that cannot read the key back fails storage too
+        let (outcome, _) = run(&mock_queue(|device| device.xpubs.clear()), None).await;
+        assert!(outcome.storage_failed());
+    }
+
+    #[tokio::test]
+    async fn test_user_cancel_stops_the_test() {
+        let queue = mock_queue(|device| device.cancel_confirmations = true);
+        let (outcome, steps) = run(&queue, Some(XPUB)).await;
+        assert_eq!(outcome, SelfTestOutcome::Cancelled { flags: SelfTestFlags::default() });
+        assert_eq!(steps, vec![SelfTestStep::Display]);
+        assert!(!outcome.storage_failed());
+    }
+
+    #[test]
+    fn test_stuck_rng_is_detected() {
+        let stuck = vec![0xAA; ENTROPY_SIZE as usize];
+        let mut other = stuck.clone();
+        other[0] = 0;
+        assert!(entropy_failure(&stuck, &other).unwrap().contains("distinct"));
+        assert!(entropy_failure(&stuck, &stuck).unwrap().contains("same bytes"));
+    }
+}
@@ -0,0 +1,538 @@
+// signed_message.rs - Signed message files in the formats other wallets use
+//
+// Bitcoin signatures travel in the armored text format read and written by
+// Electrum, Sparrow and most desktop wallets; EVM personal_sign (EIP-191)
+// signatures as the {address, msg, sig} JSON produced by MyEtherWallet and
+// Etherscan. Verification is local: the signer's key is recovered from the
+// signature and compared with the claimed address.
+//
+// Line endings and trailing whitespace are where these files break between
+// tools. A file saved on Windows carries CRLF although the message was signed
+// with LF (or the reverse), so verification also tries the message with the
+// other line ending and with trailing whitespace trimmed from each line, and
+// reports which form matched. Armor parsing never trims inside the message.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::sign_message::signed_msg_hash;
+use bitcoin::{Address, Network, PublicKey};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+pub const ARMOR_BEGIN: &str = "-----BEGIN BITCOIN SIGNED MESSAGE-----";
+pub const ARMOR_SIGNATURE: &str = "-----BEGIN SIGNATURE-----";
+pub const ARMOR_END: &str = "-----END BITCOIN SIGNED MESSAGE-----";
+
+/// Signature header used by some wallets instead of ARMOR_SIGNATURE
+const ARMOR_SIGNATURE_ALT: &str = "-----BEGIN BITCOIN SIGNATURE-----";
+
+/// Text representation of a signed message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum SignedMessageFormat {
+    /// -----BEGIN BITCOIN SIGNED MESSAGE----- block (Bitcoin only)
+    BitcoinArmor,
+    /// {"address", "message", "signature"}; "msg" and "sig" are accepted on import
+    Json,
+    /// Address line, base64 signature line, then the message
+    Plain,
+}
+
+/// How the signature was produced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureScheme {
+    /// Base64 compact signature over the "Bitcoin Signed Message" hash (BIP-137)
+    Bitcoin,
+    /// 0x-hex r||s||v signature over the "Ethereum Signed Message" hash
+    Eip191,
+}
+
+/// Form of the message the signature matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum MessageVariant {
+    AsWritten,
+    LfLineEndings,
+    CrlfLineEndings,
+    TrailingWhitespaceTrimmed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SignedMessage {
+    pub scheme: SignatureScheme,
+    pub address: String,
+    pub message: String,
+    pub signature: String,
+}
+
+/// Result of verifying an imported signed message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct MessageVerification {
+    pub format: SignedMessageFormat,
+    pub scheme: SignatureScheme,
+    /// Address the file claims signed the message
+    pub address: String,
+    /// Address recovered from the signature; equal to `address` when valid
+    pub signer_address: Option<String>,
+    pub valid: bool,
+    pub message: String,
+    /// Which form of the message verified, when any did
+    pub matched_variant: Option<MessageVariant>,
+}
+
+/// JSON layout on import; MyEtherWallet and Etherscan write msg/sig
+#[derive(Deserialize)]
+struct JsonSignedMessage {
+    address: String,
+    #[serde(alias = "msg")]
+    message: String,
+    #[serde(alias = "sig")]
+    signature: String,
+}
+
+impl SignedMessage {
+    /// Text of this message in `format`
+    pub fn to_text(&self, format: SignedMessageFormat) -> Result<String, String> {
+        match format {
+            SignedMessageFormat::BitcoinArmor => {
+                if self.scheme != SignatureScheme::Bitcoin {
+                    return Err("The signed message armor format only carries Bitcoin signatures".to_string());
+                }
+                Ok(format!(
+                    "{}\n{}\n{}\n{}\n{}\n{}\n",
+                    ARMOR_BEGIN, self.message, ARMOR_SIGNATURE, self.address, self.signature, ARMOR_END
+                ))
+            }
+            SignedMessageFormat::Json => {
+                let value = serde_json::json!({
+                    "address": self.address,
+                    "message": self.message,
+                    "signature": self.signature,
+                });
+                serde_json::to_string_pretty(&value).map_err(|e| e.to_string())
+            }
+            SignedMessageFormat::Plain => Ok(format!("{}\n{}\n{}", self.address, self.signature, self.message)),
+        }
+    }
+
+    /// Parse any supported format, detecting which one the text uses
+    pub fn parse(text: &str) -> Result<(SignedMessageFormat, SignedMessage), String> {
+        let trimmed = text.trim_start_matches('\u{feff}').trim_start();
+        if trimmed.starts_with(ARMOR_BEGIN) {
+            return parse_armor(trimmed).map(|m| (SignedMessageFormat::BitcoinArmor, m));
+        }
+        if trimmed.starts_with('{') {
+            let json: JsonSignedMessage = serde_json::from_str(trimmed)
+                .map_err(|e| format!("Invalid signed message JSON: {}", e))?;
+            let signature = json.signature.trim().to_string();
+            let message = SignedMessage {
+                scheme: detect_scheme(&signature)?,
+                address: json.address.trim().to_string(),
+                message: json.message,
+                signature,
+            };
+            return Ok((SignedMessageFormat::Json, message));
+        }
+        parse_plain(trimmed).map(|m| (SignedMessageFormat::Plain, m))
+    }
+
+    /// Verify locally, trying the line-ending and whitespace variants of the message
+    pub fn verify(&self) -> Result<(Option<String>, Option<MessageVariant>), String> {
+        let mut signer = None;
+        for (variant, candidate) in message_variants(&self.message) {
+            let (recovered, valid) = match self.scheme {
+                SignatureScheme::Bitcoin => verify_bitcoin(&self.address, &candidate, &self.signature)?,
+                SignatureScheme::Eip191 => verify_eip191(&self.address, candidate.as_bytes(), &self.signature)?,
+            };
+            if valid {
+                return Ok((Some(recovered), Some(variant)));
+            }
+            // Report the signer of the message as written when nothing matches
+            signer.get_or_insert(recovered);
+        }
+        Ok((signer, None))
+    }
+}
+
+/// Detect, parse and verify a signed message file's contents
+pub fn verify_signed_message_text(text: &str) -> Result<MessageVerification, String> {
+    let (format, message) = SignedMessage::parse(text)?;
+    let (signer_address, matched_variant) = message.verify()?;
+    Ok(MessageVerification {
+        format,
+        scheme: message.scheme,
+        address: message.address,
+        valid: matched_variant.is_some(),
+        signer_address,
+        message: message.message,
+        matched_variant,
+    })
+}
+
+/// Lines of `text` without their line terminator, keeping trailing whitespace
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)).collect()
+}
+
+fn parse_armor(text: &str) -> Result<SignedMessage, String> {
+    let lines = split_lines(text);
+    let begin = lines.iter().position(|l| l.trim_end() == ARMOR_BEGIN)
+        .ok_or("Missing signed message header")?;
+    let signature_start = lines.iter().skip(begin + 1)
+        .position(|l| matches!(l.trim_end(), ARMOR_SIGNATURE | ARMOR_SIGNATURE_ALT))
+        .map(|i| i + begin + 1)
+        .ok_or("Missing signature header")?;
+    let end = lines.iter().skip(signature_start + 1)
+        .position(|l| l.trim_end() == ARMOR_END)
+        .map(|i| i + signature_start + 1)
+        .ok_or("Missing signed message footer")?;
+
+    // The message is everything between the headers, byte for byte
+    let message = lines[begin + 1..signature_start].join("\n");
+
+    // Signature block: address then signature, optionally as "Key: value" lines
+    let mut address = None;
+    let mut signature = None;
+    for line in &lines[signature_start + 1..end] {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let value = match line.split_once(": ") {
+            Some((key, value)) if key.eq_ignore_ascii_case("address") => {
+                address = Some(value.trim().to_string());
+                continue;
+            }
+            Some((key, value)) if key.eq_ignore_ascii_case("signature") => value.trim(),
+            Some((key, _)) if key.eq_ignore_ascii_case("version") || key.eq_ignore_ascii_case("comment") => continue,
+            _ => line,
+        };
+        if address.is_none() {
+            address = Some(value.to_string());
+        } else if signature.is_none() {
+            signature = Some(value.to_string());
+        } else {
+            return Err("Unexpected line in signature block".to_string());
+        }
+    }
+
+    Ok(SignedMessage {
+        scheme: SignatureScheme::Bitcoin,
+        address: address.ok_or("Signature block has no address")?,
+        message,
+        signature: signature.ok_or("Signature block has no signature")?,
+    })
+}
+
+fn parse_plain(text: &str) -> Result<SignedMessage, String> {
+    let mut parts = text.splitn(3, '\n');
+    let address = parts.next().map(str::trim).filter(|s| !s.is_empty())
+        .ok_or("Expected an address, a signature and the message")?;
+    let signature = parts.next().map(str::trim).filter(|s| !s.is_empty())
+        .ok_or("Expected a signature after the address")?;
+    let message = parts.next().ok_or("Expected the message after the signature")?;
+    Ok(SignedMessage {
+        scheme: detect_scheme(signature)?,
+        address: address.to_string(),
+        message: message.to_string(),
+        signature: signature.to_string(),
+    })
+}
+
+fn detect_scheme(signature: &str) -> Result<SignatureScheme, String> {
+    if signature.starts_with("0x") || signature.starts_with("0X") {
+        return Ok(SignatureScheme::Eip191);
+    }
+    if STANDARD.decode(signature).is_ok() {
+        return Ok(SignatureScheme::Bitcoin);
+    }
+    Err("Signature is neither base64 nor 0x-prefixed hex".to_string())
+}
+
+/// The message as written, then its line-ending and whitespace variants
+fn message_variants(message: &str) -> Vec<(MessageVariant, String)> {
+    let lf = message.replace("\r\n", "\n");
+    let candidates = [
+        (MessageVariant::AsWritten, message.to_string()),
+        (MessageVariant::LfLineEndings, lf.clone()),
+        (MessageVariant::CrlfLineEndings, lf.replace('\n', "\r\n")),
+        (MessageVariant::TrailingWhitespaceTrimmed, lf.split('\n').map(str::trim_end).collect::<Vec<_>>().join("\n")),
+    ];
+    let mut variants: Vec<(MessageVariant, String)> = Vec::new();
+    for (variant, text) in candidates {
+        if !variants.iter().any(|(_, seen)| *seen == text) {
+            variants.push((variant, text));
+        }
+    }
+    variants
+}
+
+/// Address kind encoded in a BIP-137 header byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BitcoinAddressKind {
+    P2pkhUncompressed,
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+}
+
+fn decode_bitcoin_signature(signature: &str) -> Result<(RecoverableSignature, BitcoinAddressKind), String> {
+    let bytes = STANDARD.decode(signature.trim()).map_err(|e| format!("Invalid base64 signature: {}", e))?;
+    if bytes.len() != 65 {
+        return Err(format!("Signature is {} bytes, expected 65", bytes.len()));
+    }
+    let (kind, base) = match bytes[0] {
+        27..=30 => (BitcoinAddressKind::P2pkhUncompressed, 27),
+        31..=34 => (BitcoinAddressKind::P2pkh, 31),
+        35..=38 => (BitcoinAddressKind::P2shP2wpkh, 35),
+        39..=42 => (BitcoinAddressKind::P2wpkh, 39),
+        header => return Err(format!("Unknown signature header byte {}", header)),
+    };
+    let recovery_id = RecoveryId::from_i32(i32::from(bytes[0] - base)).map_err(|e| e.to_string())?;
+    let signature = RecoverableSignature::from_compact(&bytes[1..], recovery_id)
+        .map_err(|e| format!("Invalid signature: {}", e))?;
+    Ok((signature, kind))
+}
+
+fn recover_bitcoin_key(message: &str, signature: &str) -> Result<(PublicKey, BitcoinAddressKind), String> {
+    let (signature, kind) = decode_bitcoin_signature(signature)?;
+    let hash = signed_msg_hash(message);
+    let digest = Message::from_slice(&hash[..]).map_err(|e| e.to_string())?;
+    let key = Secp256k1::verification_only()
+        .recover_ecdsa(&digest, &signature)
+        .map_err(|e| format!("Could not recover the signing key: {}", e))?;
+    let compressed = kind != BitcoinAddressKind::P2pkhUncompressed;
+    Ok((PublicKey { inner: key, compressed }, kind))
+}
+
+fn bitcoin_address(key: &PublicKey, kind: BitcoinAddressKind, network: Network) -> Result<Address, String> {
+    match kind {
+        BitcoinAddressKind::P2pkhUncompressed | BitcoinAddressKind::P2pkh => Ok(Address::p2pkh(key, network)),
+        BitcoinAddressKind::P2shP2wpkh => Address::p2shwpkh(key, network).map_err(|e| e.to_string()),
+        BitcoinAddressKind::P2wpkh => Address::p2wpkh(key, network).map_err(|e| e.to_string()),
+    }
+}
+
+/// Address that produced a Bitcoin signature, in the form its header names
+pub fn bitcoin_signer_address(message: &str, signature: &str, network: Network) -> Result<String, String> {
+    let (key, kind) = recover_bitcoin_key(message, signature)?;
+    Ok(bitcoin_address(&key, kind, network)?.to_string())
+}
+
+/// Recovered signer and whether it controls `address`. Compressed keys are
+/// matched against every single-key address form, since wallets disagree on
+/// whether segwit signatures use the segwit header bytes.
+fn verify_bitcoin(address: &str, message: &str, signature: &str) -> Result<(String, bool), String> {
+    let claimed: Address<NetworkUnchecked> = address.parse().map_err(|e| format!("Invalid Bitcoin address {}: {}", address, e))?;
+    let network = [Network::Bitcoin, Network::Testnet, Network::Regtest]
+        .into_iter()
+        .find(|n| claimed.is_valid_for_network(*n))
+        .ok_or_else(|| format!("Unsupported network for address {}", address))?;
+    let claimed = claimed.assume_checked();
+
+    let (key, kind) = recover_bitcoin_key(message, signature)?;
+    let kinds: &[BitcoinAddressKind] = if key.compressed {
+        &[BitcoinAddressKind::P2pkh, BitcoinAddressKind::P2shP2wpkh, BitcoinAddressKind::P2wpkh]
+    } else {
+        &[BitcoinAddressKind::P2pkhUncompressed]
+    };
+    for candidate in kinds {
+        if bitcoin_address(&key, *candidate, network)? == claimed {
+            return Ok((claimed.to_string(), true));
+        }
+    }
+    Ok((bitcoin_address(&key, kind, network)?.to_string(), false))
+}
+
+/// keccak256("\x19Ethereum Signed Message:\n" || len || message)
+pub fn eip191_hash(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+/// Lowercase 0x address that produced an EIP-191 signature
+pub fn eip191_signer_address(message: &[u8], signature: &str) -> Result<String, String> {
+    let hex_part = signature.trim().trim_start_matches("0x").trim_start_matches("0X");
+    let bytes = hex::decode(hex_part).map_err(|e| format!("Invalid hex signature: {}", e))?;
+    if bytes.len() != 65 {
+        return Err(format!("Signature is {} bytes, expected 65", bytes.len()));
+    }
+    let v = match bytes[64] {
+        v @ (0 | 1) => v,
+        v @ (27 | 28) => v - 27,
+        v => return Err(format!("Unsupported signature v value {}", v)),
+    };
+    let recovery_id = RecoveryId::from_i32(i32::from(v)).map_err(|e| e.to_string())?;
+    let signature = RecoverableSignature::from_compact(&bytes[..64], recovery_id)
+        .map_err(|e| format!("Invalid signature: {}", e))?;
+    let digest = Message::from_slice(&eip191_hash(message)).map_err(|e| e.to_string())?;
+    let key = Secp256k1::verification_only()
+        .recover_ecdsa(&digest, &signature)
+        .map_err(|e| format!("Could not recover the signing key: {}", e))?;
+    let hash = Keccak256::digest(&key.serialize_uncompressed()[1..]);
+    Ok(format!("0x{}", hex::encode(&hash[12..])))
+}
+
+fn verify_eip191(address: &str, message: &[u8], signature: &str) -> Result<(String, bool), String> {
+    let claimed = crate::token_approvals::normalize_address(address)?;
+    let signer = eip191_signer_address(message, signature)?;
+    let valid = signer == claimed;
+    Ok((signer, valid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::SecretKey;
+
+    const KEY: [u8; 32] = [0x11; 32];
+
+    /// Sign the way Electrum does: compact signature with header 31 + recid
+    fn electrum_sign(message: &str, header_base: u8) -> String {
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&KEY).unwrap();
+        let hash = signed_msg_hash(message);
+        let signature = secp.sign_ecdsa_recoverable(&Message::from_slice(&hash[..]).unwrap(), &key);
+        let (recovery_id, compact) = signature.serialize_compact();
+        let mut bytes = vec![header_base + recovery_id.to_i32() as u8];
+        bytes.extend_from_slice(&compact);
+        STANDARD.encode(bytes)
+    }
+
+    fn electrum_address() -> String {
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&KEY).unwrap();
+        let public = PublicKey::new(bitcoin::secp256k1::PublicKey::from_secret_key(&secp, &key));
+        Address::p2pkh(&public, Network::Bitcoin).to_string()
+    }
+
+    #[test]
+    fn known_bitcoin_signature_verifies() {
+        // bitcoinjs-message reference vector (compressed P2PKH, header 31)
+        let armor = "-----BEGIN BITCOIN SIGNED MESSAGE-----\n\
+                     This is an example of a signed message.\n\
+                     -----BEGIN SIGNATURE-----\n\
+                     1F3sAm6ZtwLAUnj7d38pGFxtP3RVEvtsbV\n\
+                     H9L5yLFjti0QTHhPyFrZCT1V/MMnBtXKmoiKDZ78NDBjERki6ZTQZdSMCtkgoNmp17By9ItJr8o7ChX0XxY91nk=\n\
+                     -----END BITCOIN SIGNED MESSAGE-----\n";
+        let result = verify_signed_message_text(armor).unwrap();
+        assert_eq!(result.format, SignedMessageFormat::BitcoinArmor);
+        assert!(result.valid);
+        assert_eq!(result.matched_variant, Some(MessageVariant::AsWritten));
+        assert_eq!(result.signer_address.as_deref(), Some("1F3sAm6ZtwLAUnj7d38pGFxtP3RVEvtsbV"));
+
+        let tampered = armor.replace("example", "exemple");
+        let result = verify_signed_message_text(&tampered).unwrap();
+        assert!(!result.valid);
+        assert_ne!(result.signer_address.as_deref(), Some("1F3sAm6ZtwLAUnj7d38pGFxtP3RVEvtsbV"));
+    }
+
+    #[test]
+    fn armor_round_trip_preserves_whitespace_and_line_endings() {
+        let message = "Proof of funds  \nsecond line\t\n\nlast line ";
+        let signature = electrum_sign(message, 31);
+        let address = bitcoin_signer_address(message, &signature, Network::Bitcoin).unwrap();
+        assert_eq!(address, electrum_address());
+
+        let signed = SignedMessage { scheme: SignatureScheme::Bitcoin, address: address.clone(), message: message.to_string(), signature };
+        let armor = signed.to_text(SignedMessageFormat::BitcoinArmor).unwrap();
+
+        let (format, parsed) = SignedMessage::parse(&armor).unwrap();
+        assert_eq!(format, SignedMessageFormat::BitcoinArmor);
+        assert_eq!(parsed, signed);
+        assert_eq!(verify_signed_message_text(&armor).unwrap().matched_variant, Some(MessageVariant::AsWritten));
+
+        // Saved by a Windows editor: CRLF everywhere, whitespace inside the message kept
+        let windows = armor.replace('\n', "\r\n");
+        let result = verify_signed_message_text(&windows).unwrap();
+        assert!(result.valid);
+        assert_eq!(result.message, message);
+        assert_eq!(result.signer_address, Some(address));
+    }
+
+    #[test]
+    fn crlf_and_trimmed_messages_verify_as_variants() {
+        // Signed on Windows with CRLF, exported through a tool that wrote LF
+        let signed_text = "line one\r\nline two";
+        let signature = electrum_sign(signed_text, 31);
+        let signed = SignedMessage {
+            scheme: SignatureScheme::Bitcoin,
+            address: electrum_address(),
+            message: "line one\nline two".to_string(),
+            signature,
+        };
+        let result = verify_signed_message_text(&signed.to_text(SignedMessageFormat::BitcoinArmor).unwrap()).unwrap();
+        assert!(result.valid);
+        assert_eq!(result.matched_variant, Some(MessageVariant::CrlfLineEndings));
+
+        // Signed without trailing whitespace, which an editor then added
+        let signature = electrum_sign("hello\nworld", 31);
+        let padded = SignedMessage { message: "hello  \nworld\t".to_string(), signature, ..signed };
+        let result = verify_signed_message_text(&padded.to_text(SignedMessageFormat::Json).unwrap()).unwrap();
+        assert_eq!(result.format, SignedMessageFormat::Json);
+        assert_eq!(result.matched_variant, Some(MessageVariant::TrailingWhitespaceTrimmed));
+    }
+
+    #[test]
+    fn segwit_headers_and_plain_format() {
+        let message = "segwit";
+        let signature = electrum_sign(message, 39);
+        let address = bitcoin_signer_address(message, &signature, Network::Bitcoin).unwrap();
+        assert!(address.starts_with("bc1q"));
+
+        let plain = format!("{}\r\n{}\r\n{}", address, signature, message);
+        let result = verify_signed_message_text(&plain).unwrap();
+        assert_eq!(result.format, SignedMessageFormat::Plain);
+        assert!(result.valid);
+
+        // Electrum signs segwit addresses with the P2PKH header; the key still matches
+        let signature = electrum_sign(message, 31);
+        let json = serde_json::json!({ "address": address, "message": message, "signature": signature }).to_string();
+        assert!(verify_signed_message_text(&json).unwrap().valid);
+    }
+
+    #[test]
+    fn eip191_json_verifies() {
+        // web3.js accounts.sign reference vector
+        let json = r#"{
+            "address": "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23",
+            "msg": "Some data",
+            "sig": "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c",
+            "version": "2"
+        }"#;
+        let result = verify_signed_message_text(json).unwrap();
+        assert_eq!(result.format, SignedMessageFormat::Json);
+        assert_eq!(result.scheme, SignatureScheme::Eip191);
+        assert!(result.valid);
+        assert_eq!(result.signer_address.as_deref(), Some("0x2c7536e3605d9c16a7a3d7b1898e529396a65c23"));
+        assert_eq!(
+            hex::encode(eip191_hash(b"Some data")),
+            "1da44b586eb0729ff70a73c326926f6ed5a25f5b056e7f47fbc6e58d86871655"
+        );
+
+        let wrong = json.replace("Some data", "Other data");
+        assert!(!verify_signed_message_text(&wrong).unwrap().valid);
+    }
+
+    #[test]
+    fn armor_rejects_evm_signatures_and_broken_blocks() {
+        let signed = SignedMessage {
+            scheme: SignatureScheme::Eip191,
+            address: "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23".to_string(),
+            message: "x".to_string(),
+            signature: "0x00".to_string(),
+        };
+        assert!(signed.to_text(SignedMessageFormat::BitcoinArmor).is_err());
+        assert!(SignedMessage::parse(&format!("{}\nmessage\n{}\n", ARMOR_BEGIN, ARMOR_END)).is_err());
+    }
+}
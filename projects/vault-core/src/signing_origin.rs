@@ -0,0 +1,357 @@
+// signing_origin.rs - Who asked for a signature, and per-origin soft spending limits
+//
+// Every entry point (main window, REST API, WalletConnect, CLI) constructs its
+// SigningOrigin from the transport it was reached through: the Tauri window,
+// the authenticated API token, the WalletConnect session topic. The origin is
+// never read from the request payload, which is why SigningOrigin implements
+// Serialize (for the audit trail) but deliberately not Deserialize.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use serde::de::DeserializeOwned;
+use serde::{Serialize, Serializer};
+use tokio::sync::{oneshot, Mutex};
+use keepkey_db::{Database, SigningAuditInput};
+
+/// Preference holding the default daily limit applied to external origins without their own
+pub const EXTERNAL_DAILY_LIMIT_PREF: &str = "external_origin_daily_limit_usd";
+/// Window over which an origin's spending counts towards its daily limit
+pub const LIMIT_WINDOW_SECS: i64 = 86_400;
+
+/// The entry point a signing request came through
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SigningOrigin {
+    MainWindow,
+    Cli,
+    RestApi { token_id: String },
+    WalletConnect { topic: String },
+}
+
+impl SigningOrigin {
+    /// Stable identifier stored in signing_audit and transaction_cache
+    pub fn key(&self) -> String {
+        match self {
+            SigningOrigin::MainWindow => "main_window".to_string(),
+            SigningOrigin::Cli => "cli".to_string(),
+            SigningOrigin::RestApi { token_id } => format!("rest_api:{}", token_id),
+            SigningOrigin::WalletConnect { topic } => format!("walletconnect:{}", topic),
+        }
+    }
+
+    /// Requests from outside the vault itself; only these get the default soft limit
+    pub fn is_external(&self) -> bool {
+        matches!(self, SigningOrigin::RestApi { .. } | SigningOrigin::WalletConnect { .. })
+    }
+}
+
+impl fmt::Display for SigningOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.key())
+    }
+}
+
+impl Serialize for SigningOrigin {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.key())
+    }
+}
+
+/// A request payload paired with the origin its transport established
+#[derive(Debug, Clone)]
+pub struct AttributedRequest<T> {
+    pub origin: SigningOrigin,
+    pub request: T,
+}
+
+impl<T: DeserializeOwned> AttributedRequest<T> {
+    /// Parse `payload` for `origin`; any origin-like fields in the payload are ignored
+    pub fn from_payload(origin: SigningOrigin, payload: serde_json::Value) -> Result<Self, String> {
+        let request = serde_json::from_value(payload).map_err(|e| format!("Invalid signing request: {}", e))?;
+        Ok(Self { origin, request })
+    }
+}
+
+/// Outcome of checking a request against its origin's daily limit
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum LimitDecision {
+    Allowed,
+    ConfirmationRequired {
+        spent_usd: f64,
+        request_usd: Option<f64>,
+        limit_usd: f64,
+    },
+}
+
+/// The limit that applies to an origin: its own, else the external default.
+/// Internal origins (main window, CLI) are exempt unless given their own limit.
+pub fn effective_limit(origin: &SigningOrigin, configured: Option<f64>, external_default: Option<f64>) -> Option<f64> {
+    configured.or(if origin.is_external() { external_default } else { None })
+}
+
+/// Whether signing `request_usd` on top of `spent_usd` crosses `limit`.
+/// A request of unknown value needs confirmation once the limit is reached.
+pub fn evaluate_limit(spent_usd: f64, request_usd: Option<f64>, limit: Option<f64>) -> LimitDecision {
+    let Some(limit_usd) = limit else {
+        return LimitDecision::Allowed;
+    };
+    let crosses = match request_usd {
+        Some(value) => spent_usd + value > limit_usd,
+        None => spent_usd >= limit_usd,
+    };
+    if crosses {
+        LimitDecision::ConfirmationRequired { spent_usd, request_usd, limit_usd }
+    } else {
+        LimitDecision::Allowed
+    }
+}
+
+/// Check a request against the origin's spending over the last day. Requests
+/// still waiting on the device count as spent; `audit_id` is the request's
+/// own entry, which is left out.
+pub async fn check_origin_limit(
+    db: &Database,
+    origin: &SigningOrigin,
+    request_usd: Option<f64>,
+    audit_id: Option<i64>,
+) -> Result<LimitDecision, String> {
+    let key = origin.key();
+    let configured = db.get_origin_spending_limit(&key).await.map_err(|e| format!("Database error: {}", e))?;
+    let external_default = db
+        .get_preference(EXTERNAL_DAILY_LIMIT_PREF)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?
+        .and_then(|v| v.parse().ok());
+
+    let limit = effective_limit(origin, configured, external_default);
+    if limit.is_none() {
+        return Ok(LimitDecision::Allowed);
+    }
+
+    let since = Database::current_timestamp() - LIMIT_WINDOW_SECS;
+    let spent = db.get_origin_spent_since(&key, since, audit_id).await.map_err(|e| format!("Database error: {}", e))?;
+    Ok(evaluate_limit(spent, request_usd, limit))
+}
+
+/// In-app confirmations waiting on the user, keyed by signing audit id
+pub type LimitConfirmations = Arc<Mutex<HashMap<i64, oneshot::Sender<bool>>>>;
+
+pub fn new_limit_confirmations() -> LimitConfirmations {
+    Arc::new(Mutex::new(HashMap::new()))
+}
+
+/// Answer a pending limit confirmation; false if it already timed out or never existed
+pub async fn resolve_limit_confirmation(confirmations: &LimitConfirmations, audit_id: i64, approved: bool) -> bool {
+    match confirmations.lock().await.remove(&audit_id) {
+        Some(sender) => sender.send(approved).is_ok(),
+        None => false,
+    }
+}
+
+/// A signing request that passed the origin checks and may go to the device
+#[derive(Debug, Clone)]
+pub struct SigningTicket {
+    pub audit_id: i64,
+    pub origin: SigningOrigin,
+}
+
+/// Record a signing request and enforce its origin's soft limit.
+///
+/// The request is recorded before the limit is checked, so a concurrent
+/// request from the same origin already counts against it. When the limit
+/// would be crossed, `notify` is called with the audit id (the
+/// UI shows an extra confirmation) and this waits up to `timeout` for
+/// `resolve_limit_confirmation`. Declined or unanswered requests are audited
+/// as rejected and never reach the device.
+pub async fn authorize_signing<F>(
+    db: &Database,
+    confirmations: &LimitConfirmations,
+    origin: &SigningOrigin,
+    request: SigningAuditInput,
+    timeout: Duration,
+    notify: F,
+) -> Result<SigningTicket, String>
+where
+    F: FnOnce(i64, &LimitDecision),
+{
+    let request = SigningAuditInput { origin: origin.key(), ..request };
+    let audit_id = db.record_signing_request(&request).await.map_err(|e| format!("Database error: {}", e))?;
+    let decision = match check_origin_limit(db, origin, request.value_usd, Some(audit_id)).await {
+        Ok(decision) => decision,
+        Err(e) => {
+            if let Err(update) = db.update_signing_status(audit_id, "failed", None).await {
+                log::warn!("Failed to close signing audit entry {}: {}", audit_id, update);
+            }
+            return Err(e);
+        }
+    };
+
+    if let LimitDecision::ConfirmationRequired { .. } = decision {
+        let (sender, receiver) = oneshot::channel();
+        confirmations.lock().await.insert(audit_id, sender);
+        log::info!("🛑 Signing request {} from {} exceeds its daily limit; waiting for confirmation", audit_id, origin);
+        notify(audit_id, &decision);
+
+        let approved = matches!(tokio::time::timeout(timeout, receiver).await, Ok(Ok(true)));
+        confirmations.lock().await.remove(&audit_id);
+        if !approved {
+            db.update_signing_status(audit_id, "rejected", None).await.map_err(|e| format!("Database error: {}", e))?;
+            return Err(format!("Signing request from {} exceeds its daily limit and was not confirmed", origin));
+        }
+    }
+
+    Ok(SigningTicket { audit_id, origin: origin.clone() })
+}
+
+/// Record how a ticketed signing request ended. A failed write is only
+/// logged: once the device has signed, the signature must still reach the
+/// caller.
+pub async fn complete_signing(db: &Database, ticket: &SigningTicket, result: Result<Option<&str>, &str>) {
+    let (status, txid) = match result {
+        Ok(txid) => ("signed", txid),
+        Err(_) => ("failed", None),
+    };
+    if let Err(e) = db.update_signing_status(ticket.audit_id, status, txid).await {
+        log::error!("❌ Failed to record signing request {} as {}: {}", ticket.audit_id, status, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    fn api(token_id: &str) -> SigningOrigin {
+        SigningOrigin::RestApi { token_id: token_id.to_string() }
+    }
+
+    fn request(value_usd: Option<f64>) -> SigningAuditInput {
+        SigningAuditInput {
+            origin: String::new(),
+            device_id: "dev1".to_string(),
+            wallet_fingerprint: "aaaa0001".to_string(),
+            caip: "eip155:1/slip44:60".to_string(),
+            value_usd,
+        }
+    }
+
+    #[test]
+    fn test_internal_origins_exempt_by_default() {
+        assert_eq!(effective_limit(&SigningOrigin::MainWindow, None, Some(100.0)), None);
+        assert_eq!(effective_limit(&SigningOrigin::Cli, None, Some(100.0)), None);
+        assert_eq!(effective_limit(&SigningOrigin::MainWindow, Some(50.0), Some(100.0)), Some(50.0));
+        assert_eq!(effective_limit(&api("tok1"), None, Some(100.0)), Some(100.0));
+        assert_eq!(effective_limit(&api("tok1"), Some(20.0), Some(100.0)), Some(20.0));
+    }
+
+    #[test]
+    fn test_limit_crossing() {
+        assert_eq!(evaluate_limit(80.0, Some(20.0), Some(100.0)), LimitDecision::Allowed);
+        assert!(matches!(evaluate_limit(80.0, Some(20.01), Some(100.0)), LimitDecision::ConfirmationRequired { .. }));
+        assert_eq!(evaluate_limit(99.0, None, Some(100.0)), LimitDecision::Allowed);
+        assert!(matches!(evaluate_limit(100.0, None, Some(100.0)), LimitDecision::ConfirmationRequired { .. }));
+        assert_eq!(evaluate_limit(1e9, Some(1.0), None), LimitDecision::Allowed);
+    }
+
+    #[tokio::test]
+    async fn test_request_over_limit_needs_confirmation() {
+        let db = Database::new_in_memory().await.unwrap();
+        let confirmations = new_limit_confirmations();
+        let origin = api("tok1");
+        db.set_origin_spending_limit(&origin.key(), Some(100.0)).await.unwrap();
+
+        let ticket = authorize_signing(&db, &confirmations, &origin, request(Some(70.0)), Duration::from_secs(1), |_, _| {
+            panic!("under the limit must not ask for confirmation")
+        }).await.unwrap();
+        complete_signing(&db, &ticket, Ok(Some("0xaaa"))).await;
+
+        // 70 + 40 crosses 100: declined (timed out) requests never get a ticket
+        let mut notified = None;
+        let declined = authorize_signing(&db, &confirmations, &origin, request(Some(40.0)), Duration::from_millis(10), |id, decision| {
+            notified = Some((id, decision.clone()));
+        }).await;
+        assert!(declined.is_err());
+        let (_, decision) = notified.unwrap();
+        assert_eq!(decision, LimitDecision::ConfirmationRequired { spent_usd: 70.0, request_usd: Some(40.0), limit_usd: 100.0 });
+
+        // Approved from the app
+        let approver = confirmations.clone();
+        let approve = tokio::spawn(async move {
+            loop {
+                let pending = approver.lock().await.keys().next().copied();
+                if let Some(id) = pending {
+                    return resolve_limit_confirmation(&approver, id, true).await;
+                }
+                tokio::task::yield_now().await;
+            }
+        });
+        let ticket = authorize_signing(&db, &confirmations, &origin, request(Some(40.0)), Duration::from_secs(5), |_, _| {})
+            .await
+            .unwrap();
+        assert!(approve.await.unwrap());
+        complete_signing(&db, &ticket, Ok(Some("0xbbb"))).await;
+
+        let spending = db.get_spending_by_origin(1).await.unwrap();
+        assert_eq!(spending[0].origin, "rest_api:tok1");
+        assert_eq!(spending[0].total_usd, 110.0);
+        assert_eq!(spending[0].signed_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_requests_waiting_on_the_device_count_against_the_limit() {
+        let db = Database::new_in_memory().await.unwrap();
+        let confirmations = new_limit_confirmations();
+        let origin = api("tok1");
+        db.set_origin_spending_limit(&origin.key(), Some(100.0)).await.unwrap();
+
+        // Not completed yet: the device is still showing it
+        let _waiting = authorize_signing(&db, &confirmations, &origin, request(Some(60.0)), Duration::from_secs(1), |_, _| {
+            panic!("under the limit must not ask for confirmation")
+        }).await.unwrap();
+
+        let mut notified = false;
+        let second = authorize_signing(&db, &confirmations, &origin, request(Some(60.0)), Duration::from_millis(10), |_, _| {
+            notified = true;
+        }).await;
+        assert!(second.is_err());
+        assert!(notified);
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct SendPayload {
+        to: String,
+        value_usd: f64,
+    }
+
+    #[tokio::test]
+    async fn test_origin_not_spoofable_by_payload() {
+        let payload = serde_json::json!({
+            "to": "0x1234",
+            "value_usd": 500.0,
+            "origin": "main_window",
+            "token_id": "main_window",
+        });
+        let attributed = AttributedRequest::<SendPayload>::from_payload(api("tok1"), payload).unwrap();
+        assert_eq!(attributed.origin.key(), "rest_api:tok1");
+        assert!(attributed.origin.is_external());
+        assert_eq!(attributed.request.to, "0x1234");
+
+        // A token named like an internal origin still maps to an external key
+        assert_eq!(api("main_window").key(), "rest_api:main_window");
+        assert_ne!(api("main_window").key(), SigningOrigin::MainWindow.key());
+
+        // The audit row takes the transport origin even if the caller pre-filled one
+        let db = Database::new_in_memory().await.unwrap();
+        let confirmations = new_limit_confirmations();
+        let spoofed = SigningAuditInput { origin: "main_window".to_string(), ..request(Some(attributed.request.value_usd)) };
+        let ticket = authorize_signing(&db, &confirmations, &attributed.origin, spoofed, Duration::from_millis(10), |_, _| {})
+            .await
+            .unwrap();
+        complete_signing(&db, &ticket, Ok(Some("0xccc"))).await;
+        let spending = db.get_spending_by_origin(1).await.unwrap();
+        assert_eq!(spending.len(), 1);
+        assert_eq!(spending[0].origin, "rest_api:tok1");
+    }
+}
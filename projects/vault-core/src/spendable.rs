@@ -0,0 +1,355 @@
+// spendable.rs - Reserve-aware spendable balance and send amount validation
+//
+// Some networks make part of the balance unspendable: XRP accounts must keep a
+// base reserve plus a reserve per owned object, and Cosmos-SDK accounts need
+// gas left over to ever move funds again. Send flows must work from the
+// spendable amount, never the raw balance.
+
+use serde::Serialize;
+use keepkey_db::NetworkReserve;
+use crate::units::{format_units, parse_units};
+
+/// Reserve requirements for a network when the networks table has none
+pub fn default_reserve(network_id: &str) -> NetworkReserve {
+    let (base, per_item, gas) = match network_id {
+        "ripple:4109c6f2045fc7eff4cde8f9905d19c2" => (Some("10"), Some("2"), None),
+        "cosmos:cosmoshub-4" => (None, None, Some("0.01")),
+        "cosmos:osmosis-1" => (None, None, Some("0.01")),
+        "cosmos:thorchain-mainnet-v1" => (None, None, Some("0.02")),
+        "cosmos:mayachain-mainnet-v1" => (None, None, Some("0.5")),
+        _ => (None, None, None),
+    };
+    NetworkReserve {
+        base_reserve: base.map(String::from),
+        reserve_per_item: per_item.map(String::from),
+        recommended_gas_buffer: gas.map(String::from),
+    }
+}
+
+/// Native asset decimals when the asset registry has no entry
+pub fn default_decimals(network_id: &str) -> u32 {
+    if network_id.starts_with("eip155:") {
+        18
+    } else if network_id.starts_with("ripple:")
+        || network_id == "cosmos:cosmoshub-4"
+        || network_id == "cosmos:osmosis-1"
+    {
+        6
+    } else if network_id == "cosmos:mayachain-mainnet-v1" {
+        10
+    } else {
+        8
+    }
+}
+
+/// Reserve requirements in base units
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReserveRules {
+    pub base_reserve: u128,
+    pub reserve_per_item: u128,
+    pub gas_buffer: u128,
+}
+
+impl ReserveRules {
+    pub fn from_network(reserve: &NetworkReserve, decimals: u32) -> Result<Self, String> {
+        let parse = |v: &Option<String>| v.as_deref().map(|s| parse_units(s, decimals)).transpose().map(|v| v.unwrap_or(0));
+        Ok(Self {
+            base_reserve: parse(&reserve.base_reserve)?,
+            reserve_per_item: parse(&reserve.reserve_per_item)?,
+            gas_buffer: parse(&reserve.recommended_gas_buffer)?,
+        })
+    }
+}
+
+/// Human-readable reserve breakdown returned to the UI and inside errors
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ReserveBreakdown {
+    pub caip: String,
+    pub decimals: u32,
+    pub raw_balance: String,
+    pub base_reserve: String,
+    pub owned_items: u32,
+    pub item_reserve: String,
+    pub gas_buffer: String,
+    pub pending_outgoing: String,
+    pub spendable: String,
+}
+
+/// Spendable balance in base units
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spendable {
+    pub decimals: u32,
+    pub raw_balance: u128,
+    pub base_reserve: u128,
+    pub owned_items: u32,
+    pub item_reserve: u128,
+    pub gas_buffer: u128,
+    pub pending_outgoing: u128,
+    pub spendable: u128,
+}
+
+impl Spendable {
+    /// raw - (base + per_item * items) - gas_buffer - pending, floored at zero
+    pub fn compute(raw_balance: u128, rules: &ReserveRules, owned_items: u32, pending_outgoing: u128, decimals: u32) -> Self {
+        let item_reserve = rules.reserve_per_item.saturating_mul(owned_items as u128);
+        let locked = [rules.base_reserve, item_reserve, rules.gas_buffer, pending_outgoing]
+            .iter()
+            .fold(0u128, |total, amount| total.saturating_add(*amount));
+        Self {
+            decimals,
+            raw_balance,
+            base_reserve: rules.base_reserve,
+            owned_items,
+            item_reserve,
+            gas_buffer: rules.gas_buffer,
+            pending_outgoing,
+            spendable: raw_balance.saturating_sub(locked),
+        }
+    }
+
+    pub fn breakdown(&self, caip: &str) -> ReserveBreakdown {
+        let fmt = |v: u128| format_units(v, self.decimals);
+        ReserveBreakdown {
+            caip: caip.to_string(),
+            decimals: self.decimals,
+            raw_balance: fmt(self.raw_balance),
+            base_reserve: fmt(self.base_reserve),
+            owned_items: self.owned_items,
+            item_reserve: fmt(self.item_reserve),
+            gas_buffer: fmt(self.gas_buffer),
+            pending_outgoing: fmt(self.pending_outgoing),
+            spendable: fmt(self.spendable),
+        }
+    }
+}
+
+/// Reserve rules for an asset's network and the asset's decimals
+pub async fn load_reserve_rules(db: &keepkey_db::Database, caip: &str) -> Result<(ReserveRules, u32), String> {
+    let network_id = caip.split('/').next().unwrap_or(caip);
+    let db_err = |e: keepkey_db::DatabaseError| format!("Database error: {}", e);
+
+    let decimals = db.get_asset_decimals(caip).await.map_err(db_err)?
+        .unwrap_or_else(|| default_decimals(network_id));
+
+    // Configured values win; anything left unset falls back to the built-in table
+    let defaults = default_reserve(network_id);
+    let configured = db.get_network_reserve(network_id).await.map_err(db_err)?.unwrap_or_default();
+    let reserve = NetworkReserve {
+        base_reserve: configured.base_reserve.or(defaults.base_reserve),
+        reserve_per_item: configured.reserve_per_item.or(defaults.reserve_per_item),
+        recommended_gas_buffer: configured.recommended_gas_buffer.or(defaults.recommended_gas_buffer),
+    };
+    Ok((ReserveRules::from_network(&reserve, decimals)?, decimals))
+}
+
+/// Build the spendable balance for an asset in a device wallet from cached balances, pending sends and reserve config
+pub async fn load_spendable(
+    db: &keepkey_db::Database,
+    device_id: &str,
+    wallet_fingerprint: &str,
+    caip: &str,
+    owned_items: u32,
+) -> Result<Spendable, String> {
+    let db_err = |e: keepkey_db::DatabaseError| format!("Database error: {}", e);
+    let (rules, decimals) = load_reserve_rules(db, caip).await?;
+
+    let mut raw_balance = 0u128;
+    for balance in db.get_asset_balances(device_id, wallet_fingerprint, caip).await.map_err(db_err)? {
+        raw_balance = raw_balance.saturating_add(parse_units(&balance, decimals)?);
+    }
+
+    let mut pending_outgoing = 0u128;
+    for tx in db.get_pending_outgoing(device_id, wallet_fingerprint, caip).await.map_err(db_err)? {
+        pending_outgoing = pending_outgoing.saturating_add(parse_units(&tx.amount, decimals)?);
+        if let Some(fee) = tx.fee.as_deref() {
+            pending_outgoing = pending_outgoing.saturating_add(parse_units(fee, decimals)?);
+        }
+    }
+
+    Ok(Spendable::compute(raw_balance, &rules, owned_items, pending_outgoing, decimals))
+}
+
+/// Structured send validation errors (serialized to JSON for the frontend)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind")]
+pub enum SpendError {
+    InsufficientSpendable {
+        requested: String,
+        fee: String,
+        breakdown: Box<ReserveBreakdown>,
+    },
+    InvalidAmount {
+        message: String,
+    },
+}
+
+impl SpendError {
+    /// Encode for a `Result<_, String>` Tauri command error
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl std::fmt::Display for SpendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpendError::InsufficientSpendable { requested, fee, breakdown } => write!(
+                f,
+                "Insufficient spendable balance: requested {} + fee {} but only {} is spendable",
+                requested, fee, breakdown.spendable
+            ),
+            SpendError::InvalidAmount { message } => write!(f, "Invalid amount: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SpendError {}
+
+/// Resolve the amount to send, enforcing reserves.
+///
+/// With `send_max` the exact maximum (spendable minus fee) is returned and
+/// `requested` is ignored. `fee` is in the same asset as the balance.
+pub fn resolve_send_amount(
+    spendable: &Spendable,
+    caip: &str,
+    requested: Option<u128>,
+    fee: u128,
+    send_max: bool,
+) -> Result<u128, SpendError> {
+    let insufficient = |requested: String| SpendError::InsufficientSpendable {
+        requested,
+        fee: format_units(fee, spendable.decimals),
+        breakdown: Box::new(spendable.breakdown(caip)),
+    };
+
+    if send_max {
+        return match spendable.spendable.checked_sub(fee) {
+            Some(amount) if amount > 0 => Ok(amount),
+            _ => Err(insufficient("max".to_string())),
+        };
+    }
+
+    let amount = requested.ok_or_else(|| SpendError::InvalidAmount {
+        message: "amount is required unless send_max is set".to_string(),
+    })?;
+    if amount == 0 {
+        return Err(SpendError::InvalidAmount { message: "amount must be greater than zero".to_string() });
+    }
+
+    match amount.checked_add(fee) {
+        Some(total) if total <= spendable.spendable => Ok(amount),
+        _ => Err(insufficient(format_units(amount, spendable.decimals))),
+    }
+}
+
+/// Check a UTXO send against the network's reserve rules.
+///
+/// The offered UTXOs are the balance: the UTXO source already leaves out coins
+/// spent by pending sends, so they are not subtracted again.
+pub fn check_utxo_send(
+    rules: &ReserveRules,
+    caip: &str,
+    decimals: u32,
+    available_sats: u64,
+    sent_sats: u64,
+    fee_sats: u64,
+) -> Result<(), SpendError> {
+    let spendable = Spendable::compute(available_sats as u128, rules, 0, 0, decimals);
+    resolve_send_amount(&spendable, caip, Some(sent_sats as u128), fee_sats as u128, false).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XRP: &str = "ripple:4109c6f2045fc7eff4cde8f9905d19c2/slip44:144";
+    const ATOM: &str = "cosmos:cosmoshub-4/slip44:118";
+
+    fn rules(network_id: &str) -> ReserveRules {
+        ReserveRules::from_network(&default_reserve(network_id), default_decimals(network_id)).unwrap()
+    }
+
+    #[test]
+    fn test_xrp_reserve_math() {
+        let rules = rules("ripple:4109c6f2045fc7eff4cde8f9905d19c2");
+        // 25 XRP with two trust lines: 10 base + 2 * 2 owner reserve
+        let s = Spendable::compute(25_000_000, &rules, 2, 0, 6);
+        assert_eq!(s.item_reserve, 4_000_000);
+        assert_eq!(s.spendable, 11_000_000);
+
+        // Max send pays the 12 drop fee out of the spendable amount
+        assert_eq!(resolve_send_amount(&s, XRP, None, 12, true).unwrap(), 10_999_988);
+
+        // Sending the raw balance would delete the account
+        let err = resolve_send_amount(&s, XRP, Some(25_000_000), 12, false).unwrap_err();
+        match err {
+            SpendError::InsufficientSpendable { breakdown, .. } => {
+                assert_eq!(breakdown.base_reserve, "10");
+                assert_eq!(breakdown.item_reserve, "4");
+                assert_eq!(breakdown.spendable, "11");
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        // Below the reserve nothing is spendable
+        let s = Spendable::compute(9_000_000, &rules, 0, 0, 6);
+        assert_eq!(s.spendable, 0);
+        assert!(resolve_send_amount(&s, XRP, None, 12, true).is_err());
+    }
+
+    #[test]
+    fn test_cosmos_gas_buffer() {
+        let rules = rules("cosmos:cosmoshub-4");
+        // 1 ATOM with 0.2 ATOM pending: 1 - 0.01 buffer - 0.2 pending
+        let s = Spendable::compute(1_000_000, &rules, 0, 200_000, 6);
+        assert_eq!(s.spendable, 790_000);
+        assert_eq!(resolve_send_amount(&s, ATOM, None, 5_000, true).unwrap(), 785_000);
+        assert!(resolve_send_amount(&s, ATOM, Some(785_000), 5_000, false).is_ok());
+        assert!(resolve_send_amount(&s, ATOM, Some(785_001), 5_000, false).is_err());
+    }
+
+    #[test]
+    fn test_utxo_send_keeps_configured_reserve() {
+        const BTC: &str = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+        let none = ReserveRules::default();
+        assert!(check_utxo_send(&none, BTC, 8, 100_000, 98_000, 2_000).is_ok());
+        assert!(check_utxo_send(&none, BTC, 8, 100_000, 98_001, 2_000).is_err());
+
+        let reserve = ReserveRules { base_reserve: 10_000, ..Default::default() };
+        assert!(check_utxo_send(&reserve, BTC, 8, 100_000, 88_000, 2_000).is_ok());
+        match check_utxo_send(&reserve, BTC, 8, 100_000, 88_001, 2_000).unwrap_err() {
+            SpendError::InsufficientSpendable { breakdown, .. } => assert_eq!(breakdown.base_reserve, "0.0001"),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_no_reserve_network_and_error_json() {
+        let s = Spendable::compute(100, &rules("eip155:1"), 0, 0, 18);
+        assert_eq!(s.spendable, 100);
+
+        let err = resolve_send_amount(&s, "eip155:1/slip44:60", Some(100), 1, false).unwrap_err();
+        let json: serde_json::Value = serde_json::from_str(&err.to_json_string()).unwrap();
+        assert_eq!(json["kind"], "InsufficientSpendable");
+        assert_eq!(json["breakdown"]["spendable"], "0.0000000000000001");
+    }
+
+    #[tokio::test]
+    async fn test_load_spendable_from_database() {
+        let db = keepkey_db::Database::new_in_memory().await.unwrap();
+        db.with_connection(|conn| {
+            conn.execute_batch(
+                "INSERT INTO portfolio_balances (device_id, pubkey, caip, network_id, ticker, balance, balance_usd, price_usd, type, last_updated)
+                    VALUES ('dev1', 'cosmos1abc', 'cosmos:cosmoshub-4/slip44:118', 'cosmos:cosmoshub-4', 'ATOM', '2.5', '0', '0', 'balance', 0);
+                 INSERT INTO transaction_cache (device_id, txid, caip, type, amount, fee, timestamp, status)
+                    VALUES ('dev1', 'p1', 'cosmos:cosmoshub-4/slip44:118', 'send', '1', '0.005', 0, 'pending');"
+            )?;
+            Ok(())
+        }).await.unwrap();
+
+        let s = load_spendable(&db, "dev1", "", ATOM, 0).await.unwrap();
+        // 2.5 - 0.01 gas buffer - (1 + 0.005) pending
+        assert_eq!(s.breakdown(ATOM).spendable, "1.485");
+    }
+}
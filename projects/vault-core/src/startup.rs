@@ -0,0 +1,360 @@
+// startup.rs - Ordered, bounded startup tasks with a per-task report
+//
+// Each startup step is declared with its dependencies and criticality. Tasks
+// run as soon as their dependencies succeed, at most `max_concurrency` at a
+// time. A failed critical task stops anything new from starting; a failed
+// optional task only skips the tasks that depend on it.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
+use serde::Serialize;
+use tokio::task::JoinSet;
+
+type TaskFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+type TaskFn = Box<dyn FnOnce() -> TaskFuture + Send>;
+
+/// Whether launch can continue without the task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum Criticality {
+    Critical,
+    Optional,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TaskOutcome {
+    Succeeded,
+    Failed { error: String },
+    Skipped { reason: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct TaskReport {
+    pub name: String,
+    pub criticality: Criticality,
+    pub depends_on: Vec<String>,
+    pub outcome: TaskOutcome,
+    /// Milliseconds after startup began; None if the task never ran
+    pub started_at_ms: Option<u64>,
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct StartupReport {
+    /// In declaration order
+    pub tasks: Vec<TaskReport>,
+    pub total_ms: u64,
+    /// The critical task whose failure aborted startup
+    pub aborted_by: Option<String>,
+}
+
+impl StartupReport {
+    /// The critical task that failed, if launch has to be aborted
+    pub fn critical_failure(&self) -> Option<&TaskReport> {
+        let name = self.aborted_by.as_deref()?;
+        self.tasks.iter().find(|t| t.name == name)
+    }
+
+    /// Optional tasks that failed; the app keeps running without them
+    pub fn optional_failures(&self) -> impl Iterator<Item = &TaskReport> {
+        self.tasks.iter().filter(|t| {
+            t.criticality == Criticality::Optional && matches!(t.outcome, TaskOutcome::Failed { .. })
+        })
+    }
+}
+
+struct PendingTask {
+    name: &'static str,
+    depends_on: Vec<&'static str>,
+    criticality: Criticality,
+    run: TaskFn,
+}
+
+enum TaskState {
+    Pending(TaskFn),
+    Running,
+    Done,
+}
+
+pub struct StartupOrchestrator {
+    tasks: Vec<PendingTask>,
+    max_concurrency: usize,
+}
+
+impl StartupOrchestrator {
+    pub fn new(max_concurrency: usize) -> Self {
+        Self { tasks: Vec::new(), max_concurrency: max_concurrency.max(1) }
+    }
+
+    /// Declare a task; dependencies must be declared too (in any order)
+    pub fn add<F, Fut>(
+        &mut self,
+        name: &'static str,
+        depends_on: &[&'static str],
+        criticality: Criticality,
+        run: F,
+    ) -> &mut Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.tasks.push(PendingTask {
+            name,
+            depends_on: depends_on.to_vec(),
+            criticality,
+            run: Box::new(move || Box::pin(run())),
+        });
+        self
+    }
+
+    /// Reject duplicate names, unknown dependencies and cycles before anything runs
+    fn validate(&self) -> Result<(), String> {
+        let mut names = HashSet::new();
+        for task in &self.tasks {
+            if !names.insert(task.name) {
+                return Err(format!("Startup task '{}' is declared twice", task.name));
+            }
+        }
+        for task in &self.tasks {
+            if let Some(missing) = task.depends_on.iter().find(|d| !names.contains(*d)) {
+                return Err(format!("Startup task '{}' depends on unknown task '{}'", task.name, missing));
+            }
+        }
+
+        // Kahn's algorithm: anything left unresolved is part of a cycle
+        let mut resolved: HashSet<&str> = HashSet::new();
+        loop {
+            let ready: Vec<&str> = self.tasks.iter()
+                .filter(|t| !resolved.contains(t.name) && t.depends_on.iter().all(|d| resolved.contains(d)))
+                .map(|t| t.name)
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+            resolved.extend(ready);
+        }
+        match self.tasks.iter().find(|t| !resolved.contains(t.name)) {
+            Some(task) => Err(format!("Startup task '{}' is part of a dependency cycle", task.name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Run every task and report how each one went.
+    ///
+    /// Returns Err only for an invalid task graph; task failures are in the report.
+    pub async fn run(self) -> Result<StartupReport, String> {
+        self.validate()?;
+
+        let started = Instant::now();
+        let max_concurrency = self.max_concurrency;
+        let index: HashMap<&'static str, usize> = self.tasks.iter().enumerate().map(|(i, t)| (t.name, i)).collect();
+        let mut reports: Vec<TaskReport> = Vec::with_capacity(self.tasks.len());
+        let mut states: Vec<TaskState> = Vec::with_capacity(self.tasks.len());
+        let mut depends_on: Vec<Vec<&'static str>> = Vec::with_capacity(self.tasks.len());
+        for task in self.tasks {
+            reports.push(TaskReport {
+                name: task.name.to_string(),
+                criticality: task.criticality,
+                depends_on: task.depends_on.iter().map(|d| d.to_string()).collect(),
+                outcome: TaskOutcome::Skipped { reason: "not started".to_string() },
+                started_at_ms: None,
+                duration_ms: None,
+            });
+            states.push(TaskState::Pending(task.run));
+            depends_on.push(task.depends_on);
+        }
+
+        let mut running: JoinSet<(usize, Result<(), String>, u64)> = JoinSet::new();
+        let mut aborted_by: Option<String> = None;
+
+        loop {
+            if aborted_by.is_none() {
+                // Settle tasks whose dependencies can no longer succeed; repeat for chains
+                let mut changed = true;
+                while changed {
+                    changed = false;
+                    for i in 0..states.len() {
+                        if !matches!(states[i], TaskState::Pending(_)) {
+                            continue;
+                        }
+                        let blocked_by = depends_on[i].iter().find(|d| {
+                            let dep = index[*d];
+                            matches!(states[dep], TaskState::Done) && reports[dep].outcome != TaskOutcome::Succeeded
+                        });
+                        if let Some(dep) = blocked_by {
+                            reports[i].outcome = TaskOutcome::Skipped { reason: format!("dependency '{}' did not complete", dep) };
+                            states[i] = TaskState::Done;
+                            changed = true;
+                        }
+                    }
+                }
+
+                for i in 0..states.len() {
+                    if running.len() >= max_concurrency {
+                        break;
+                    }
+                    let ready = matches!(states[i], TaskState::Pending(_))
+                        && depends_on[i].iter().all(|d| reports[index[d]].outcome == TaskOutcome::Succeeded);
+                    if !ready {
+                        continue;
+                    }
+                    let TaskState::Pending(run) = std::mem::replace(&mut states[i], TaskState::Running) else {
+                        continue;
+                    };
+                    reports[i].started_at_ms = Some(started.elapsed().as_millis() as u64);
+                    log::info!("🚦 Startup task '{}' started", reports[i].name);
+                    running.spawn(async move {
+                        let task_started = Instant::now();
+                        // Run in its own task so a panic is reported as a failure instead of unwinding here
+                        let result = match tokio::spawn(run()).await {
+                            Ok(result) => result,
+                            Err(e) => Err(format!("task panicked: {}", e)),
+                        };
+                        (i, result, task_started.elapsed().as_millis() as u64)
+                    });
+                }
+            }
+
+            let Some(joined) = running.join_next().await else {
+                break;
+            };
+            let (i, result, duration_ms) = joined.map_err(|e| format!("Startup task runner failed: {}", e))?;
+            states[i] = TaskState::Done;
+            reports[i].duration_ms = Some(duration_ms);
+            reports[i].outcome = match result {
+                Ok(()) => {
+                    log::info!("✅ Startup task '{}' finished in {}ms", reports[i].name, duration_ms);
+                    TaskOutcome::Succeeded
+                }
+                Err(error) => {
+                    log::error!("❌ Startup task '{}' failed after {}ms: {}", reports[i].name, duration_ms, error);
+                    if reports[i].criticality == Criticality::Critical && aborted_by.is_none() {
+                        aborted_by = Some(reports[i].name.clone());
+                    }
+                    TaskOutcome::Failed { error }
+                }
+            };
+        }
+
+        // Tasks that never got to run because a critical task failed
+        if let Some(critical) = &aborted_by {
+            for (i, state) in states.iter().enumerate() {
+                if matches!(state, TaskState::Pending(_)) {
+                    reports[i].outcome = TaskOutcome::Skipped { reason: format!("startup aborted: '{}' failed", critical) };
+                }
+            }
+        }
+
+        Ok(StartupReport {
+            tasks: reports,
+            total_ms: started.elapsed().as_millis() as u64,
+            aborted_by,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn recorder() -> Arc<Mutex<Vec<&'static str>>> {
+        Arc::new(Mutex::new(Vec::new()))
+    }
+
+    fn step(
+        log: &Arc<Mutex<Vec<&'static str>>>,
+        name: &'static str,
+        delay_ms: u64,
+        result: Result<(), String>,
+    ) -> impl FnOnce() -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> + Send + 'static {
+        let log = log.clone();
+        move || Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            log.lock().unwrap().push(name);
+            result
+        })
+    }
+
+    fn outcome<'a>(report: &'a StartupReport, name: &str) -> &'a TaskOutcome {
+        &report.tasks.iter().find(|t| t.name == name).unwrap().outcome
+    }
+
+    #[tokio::test]
+    async fn test_dependencies_run_in_order() {
+        let log = recorder();
+        let mut startup = StartupOrchestrator::new(4);
+        // Declared out of order; the slow database must still finish before its dependents start
+        startup
+            .add("usb", &["database"], Criticality::Critical, step(&log, "usb", 0, Ok(())))
+            .add("tray", &["usb", "database"], Criticality::Optional, step(&log, "tray", 0, Ok(())))
+            .add("database", &[], Criticality::Critical, step(&log, "database", 30, Ok(())))
+            .add("telemetry", &[], Criticality::Optional, step(&log, "telemetry", 0, Ok(())));
+
+        let report = startup.run().await.unwrap();
+        let order = log.lock().unwrap().clone();
+        let position = |name| order.iter().position(|n| *n == name).unwrap();
+        assert!(position("database") < position("usb"));
+        assert!(position("usb") < position("tray"));
+        assert_eq!(position("telemetry"), 0);
+        assert!(report.aborted_by.is_none());
+        assert!(report.tasks.iter().all(|t| t.outcome == TaskOutcome::Succeeded && t.duration_ms.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_critical_failure_aborts_launch() {
+        let log = recorder();
+        let mut startup = StartupOrchestrator::new(1);
+        startup
+            .add("database", &[], Criticality::Critical, step(&log, "database", 0, Err("disk I/O error".to_string())))
+            .add("usb", &["database"], Criticality::Critical, step(&log, "usb", 0, Ok(())))
+            .add("catalog", &[], Criticality::Optional, step(&log, "catalog", 0, Ok(())));
+
+        let report = startup.run().await.unwrap();
+        assert_eq!(report.aborted_by.as_deref(), Some("database"));
+        assert_eq!(report.critical_failure().unwrap().outcome, TaskOutcome::Failed { error: "disk I/O error".to_string() });
+        assert!(matches!(outcome(&report, "usb"), TaskOutcome::Skipped { .. }));
+        assert!(matches!(outcome(&report, "catalog"), TaskOutcome::Skipped { reason } if reason.contains("aborted")));
+        assert_eq!(*log.lock().unwrap(), vec!["database"]);
+    }
+
+    #[tokio::test]
+    async fn test_optional_failure_skips_only_dependents() {
+        let log = recorder();
+        let mut startup = StartupOrchestrator::new(2);
+        startup
+            .add("catalog", &[], Criticality::Optional, step(&log, "catalog", 0, Err("offline".to_string())))
+            .add("seed_assets", &["catalog"], Criticality::Optional, step(&log, "seed_assets", 0, Ok(())))
+            .add("import", &["seed_assets"], Criticality::Optional, step(&log, "import", 0, Ok(())))
+            .add("usb", &[], Criticality::Critical, step(&log, "usb", 0, Ok(())))
+            .add("panics", &[], Criticality::Optional, || async { panic!("boom") });
+
+        let report = startup.run().await.unwrap();
+        assert!(report.aborted_by.is_none());
+        assert!(matches!(outcome(&report, "import"), TaskOutcome::Skipped { reason } if reason.contains("seed_assets")));
+        assert_eq!(*outcome(&report, "usb"), TaskOutcome::Succeeded);
+        let failed: Vec<&str> = report.optional_failures().map(|t| t.name.as_str()).collect();
+        assert_eq!(failed, vec!["catalog", "panics"]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_graph_is_rejected() {
+        let mut cycle = StartupOrchestrator::new(2);
+        cycle
+            .add("a", &["b"], Criticality::Optional, || async { Ok(()) })
+            .add("b", &["a"], Criticality::Optional, || async { Ok(()) });
+        assert!(cycle.run().await.unwrap_err().contains("cycle"));
+
+        let mut unknown = StartupOrchestrator::new(2);
+        unknown.add("a", &["missing"], Criticality::Optional, || async { Ok(()) });
+        assert!(unknown.run().await.unwrap_err().contains("unknown task"));
+    }
+}
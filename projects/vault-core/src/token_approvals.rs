@@ -0,0 +1,744 @@
+// token_approvals.rs - ERC-20 allowance discovery and revocation plans
+//
+// Allowances are found from the token's Approval(owner, spender, value)
+// events, then the current value of each (token, owner, spender) pair is read
+// with allowance() since later transfers and approvals change it. Event
+// discovery uses eth_getLogs when the RPC allows it and an Etherscan-style
+// indexer otherwise. All chain access goes through a JSON-RPC closure so the
+// logic can be exercised against canned responses.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use serde::Serialize;
+use serde_json::{json, Value};
+use keepkey_db::TokenApproval;
+use crate::units::format_units;
+
+/// keccak256("Approval(address,address,uint256)")
+pub const APPROVAL_TOPIC: &str = "0x8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925";
+
+const ALLOWANCE_SELECTOR: &str = "dd62ed3e";
+const APPROVE_SELECTOR: &str = "095ea7b3";
+const DECIMALS_SELECTOR: &str = "313ce567";
+const SYMBOL_SELECTOR: &str = "95d89b41";
+
+/// Gas estimates are padded by this percentage before signing
+const GAS_HEADROOM_PERCENT: u64 = 20;
+
+/// Well-known spender contracts (lowercase address, name)
+pub const SPENDER_LABELS: &[(&str, &str)] = &[
+    ("0x000000000022d473030f116ddee9f6b43ac78ba3", "Uniswap Permit2"),
+    ("0x7a250d5630b4cf539739df2c5dacb4c659f2488d", "Uniswap V2 Router"),
+    ("0xe592427a0aece92de3edee1f18e0157c05861564", "Uniswap V3 Router"),
+    ("0x68b3465833fb72a70ecdf485e0e4c7bd8665fc45", "Uniswap V3 Router 2"),
+    ("0x3fc91a3afd70395cd496c647d5a6cc9d4b2b7fad", "Uniswap Universal Router"),
+    ("0x1111111254eeb25477b68fb85ed929f73a960582", "1inch Router v5"),
+    ("0x111111125421ca6dc452d289314280a0f8842a65", "1inch Router v6"),
+    ("0xdef1c0ded9bec7f1a1670819833240f027b25eff", "0x Exchange Proxy"),
+    ("0x00000000000000adc04c56bf30ac9d3c0aaf14dc", "OpenSea Seaport 1.5"),
+    ("0xd9e1ce17f2641f24ae83637ab66a2cca9c378b9f", "SushiSwap Router"),
+    ("0x87870bca3f3fd6335c3f4ce8392d69350b4fa4e2", "Aave V3 Pool"),
+];
+
+/// Tokens that reject approve() from one non-zero allowance to another
+/// (network id, lowercase token address)
+pub const ZERO_FIRST_TOKENS: &[(&str, &str)] = &[
+    ("eip155:1", "0xdac17f958d2ee523a2206206994597c13d831ec7"), // USDT
+    ("eip155:1", "0xdd974d5c2e2928dea5f71b9825b8b646686bd200"), // KNC (legacy)
+];
+
+pub fn spender_label(spender: &str) -> Option<&'static str> {
+    let spender = spender.to_lowercase();
+    SPENDER_LABELS.iter().find(|(address, _)| *address == spender).map(|(_, label)| *label)
+}
+
+pub fn requires_zero_first(network_id: &str, token: &str) -> bool {
+    let token = token.to_lowercase();
+    ZERO_FIRST_TOKENS.iter().any(|(network, address)| *network == network_id && *address == token)
+}
+
+/// A 32-byte ABI word
+pub type Word = [u8; 32];
+
+/// Lowercase a 0x address after checking its shape
+pub fn normalize_address(address: &str) -> Result<String, String> {
+    let hex_part = address.strip_prefix("0x").or_else(|| address.strip_prefix("0X")).unwrap_or("");
+    if hex_part.len() != 40 || !hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid EVM address: {}", address));
+    }
+    Ok(format!("0x{}", hex_part.to_lowercase()))
+}
+
+/// Address left-padded to a 32-byte topic or ABI argument, without 0x
+pub(crate) fn address_word(address: &str) -> Result<String, String> {
+    Ok(format!("{:0>64}", &normalize_address(address)?[2..]))
+}
+
+/// Address as an indexed event topic
+pub fn address_topic(address: &str) -> Result<String, String> {
+    Ok(format!("0x{}", address_word(address)?))
+}
+
+fn topic_address(topic: &str) -> Option<String> {
+    let hex_part = topic.strip_prefix("0x")?;
+    (hex_part.len() == 64).then(|| format!("0x{}", hex_part[24..].to_lowercase()))
+}
+
+/// Decode hex into a word, left-padding short values
+pub fn parse_word(value: &str) -> Result<Word, String> {
+    let hex_part = value.strip_prefix("0x").unwrap_or(value);
+    if hex_part.is_empty() || hex_part.len() > 64 {
+        return Err(format!("Not a uint256: '{}'", value));
+    }
+    let bytes = hex::decode(format!("{:0>64}", hex_part)).map_err(|e| format!("Not a uint256: {}", e))?;
+    let mut word = [0u8; 32];
+    word.copy_from_slice(&bytes);
+    Ok(word)
+}
+
+pub fn word_hex(word: &Word) -> String {
+    format!("0x{}", hex::encode(word))
+}
+
+/// Value of a word that fits in 128 bits
+pub fn word_to_u128(word: &Word) -> Option<u128> {
+    if word[..16].iter().any(|b| *b != 0) {
+        return None;
+    }
+    let mut low = [0u8; 16];
+    low.copy_from_slice(&word[16..]);
+    Some(u128::from_be_bytes(low))
+}
+
+/// Wallets approve 2^256-1 and some tokens decrement it on every transfer,
+/// so anything at or above 2^128 is treated as unlimited
+pub fn is_unlimited(word: &Word) -> bool {
+    word_to_u128(word).is_none()
+}
+
+/// Allowance in token units, "unlimited", or raw base units when decimals are unknown
+pub fn format_allowance(approval: &TokenApproval) -> String {
+    if approval.is_unlimited {
+        return "unlimited".to_string();
+    }
+    let raw = parse_word(&approval.allowance).ok().and_then(|w| word_to_u128(&w)).unwrap_or(0);
+    format_units(raw, approval.token_decimals.unwrap_or(0))
+}
+
+/// Parse a JSON-RPC quantity ("0x1a") into an integer
+pub fn parse_quantity(value: &Value) -> Result<u128, String> {
+    let text = value.as_str().ok_or_else(|| format!("Expected a hex quantity, got {}", value))?;
+    let hex_part = text.strip_prefix("0x").ok_or_else(|| format!("Expected a hex quantity, got {}", text))?;
+    if hex_part.is_empty() {
+        return Ok(0);
+    }
+    u128::from_str_radix(hex_part, 16).map_err(|e| format!("Invalid quantity {}: {}", text, e))
+}
+
+/// Decode an ABI string return value, accepting bytes32 symbols (e.g. MKR)
+pub(crate) fn decode_abi_string(data: &str) -> Option<String> {
+    let bytes = hex::decode(data.strip_prefix("0x")?).ok()?;
+    let text = if bytes.len() == 32 {
+        bytes.into_iter().take_while(|b| *b != 0).collect::<Vec<_>>()
+    } else {
+        let offset = word_to_u128(&bytes.get(..32)?.try_into().ok()?)? as usize;
+        let len_word: Word = bytes.get(offset..offset + 32)?.try_into().ok()?;
+        let len = word_to_u128(&len_word)? as usize;
+        bytes.get(offset + 32..offset + 32 + len)?.to_vec()
+    };
+    String::from_utf8(text).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+/// Where the Approval events came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum LogSource {
+    Rpc,
+    Indexer,
+}
+
+/// Result of scanning a network for an account's allowances
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ApprovalScan {
+    Found { source: LogSource, approvals: Vec<TokenApproval> },
+    NotSupported { reason: String },
+}
+
+/// eth_getLogs filter for Approval events granted by any of `owners`
+pub fn approval_log_filter(owners: &[String]) -> Result<Value, String> {
+    let owner_topics = owners
+        .iter()
+        .map(|owner| address_topic(owner))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(json!({
+        "fromBlock": "earliest",
+        "toBlock": "latest",
+        "topics": [APPROVAL_TOPIC, owner_topics],
+    }))
+}
+
+/// Logs from an Etherscan-style `module=logs&action=getLogs` response
+pub fn indexer_logs_from_response(response: &Value) -> Result<Vec<Value>, String> {
+    match (response.get("status").and_then(Value::as_str), response.get("result")) {
+        (Some("1"), Some(Value::Array(logs))) => Ok(logs.clone()),
+        // "No records found" comes back as status 0 with an empty result
+        (Some("0"), Some(Value::Array(logs))) if logs.is_empty() => Ok(Vec::new()),
+        _ => Err(format!(
+            "Indexer error: {}",
+            response.get("result").or_else(|| response.get("message")).unwrap_or(response)
+        )),
+    }
+}
+
+/// Latest Approval block per (token, owner, spender) among `owners`. ERC-721
+/// Approval events share the signature but index a fourth topic and are skipped.
+fn approval_candidates(logs: &[Value], owners: &[String]) -> BTreeMap<(String, String, String), Option<i64>> {
+    let mut candidates: BTreeMap<(String, String, String), Option<i64>> = BTreeMap::new();
+    for log in logs {
+        let topics: Vec<&str> = match log.get("topics").and_then(Value::as_array) {
+            Some(topics) => topics.iter().filter_map(Value::as_str).collect(),
+            None => continue,
+        };
+        if topics.len() != 3 || !topics[0].eq_ignore_ascii_case(APPROVAL_TOPIC) {
+            continue;
+        }
+        let (Some(owner), Some(spender)) = (topic_address(topics[1]), topic_address(topics[2])) else { continue };
+        let Some(token) = log.get("address").and_then(Value::as_str).and_then(|a| normalize_address(a).ok()) else { continue };
+        if !owners.contains(&owner) {
+            continue;
+        }
+        let block = log.get("blockNumber").and_then(|b| parse_quantity(b).ok()).map(|b| b as i64);
+        let entry = candidates.entry((token, owner, spender)).or_insert(block);
+        if block > *entry {
+            *entry = block;
+        }
+    }
+    candidates
+}
+
+pub(crate) async fn eth_call<R, Fut>(rpc: &R, to: &str, data: String) -> Result<String, String>
+where
+    R: Fn(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    let result = rpc("eth_call", json!([{ "to": to, "data": data }, "latest"])).await?;
+    result.as_str().map(str::to_string).ok_or_else(|| format!("Unexpected eth_call result: {}", result))
+}
+
+/// Current allowance from `owner` to `spender`
+pub async fn read_allowance<R, Fut>(rpc: &R, token: &str, owner: &str, spender: &str) -> Result<Word, String>
+where
+    R: Fn(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    let data = format!("0x{}{}{}", ALLOWANCE_SELECTOR, address_word(owner)?, address_word(spender)?);
+    parse_word(&eth_call(rpc, token, data).await?)
+}
+
+/// Symbol and decimals; either is None if the token does not implement it
+async fn read_token_metadata<R, Fut>(rpc: &R, token: &str) -> (Option<String>, Option<u32>)
+where
+    R: Fn(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    let symbol = eth_call(rpc, token, format!("0x{}", SYMBOL_SELECTOR)).await.ok()
+        .and_then(|data| decode_abi_string(&data));
+    let decimals = eth_call(rpc, token, format!("0x{}", DECIMALS_SELECTOR)).await.ok()
+        .and_then(|data| parse_word(&data).ok())
+        .and_then(|word| word_to_u128(&word))
+        .and_then(|d| u32::try_from(d).ok())
+        .filter(|d| *d <= 36);
+    (symbol, decimals)
+}
+
+/// Riskiest first: unlimited, then unrecognized spenders, then most recently approved
+pub fn sort_by_risk(approvals: &mut [TokenApproval]) {
+    approvals.sort_by(|a, b| {
+        b.is_unlimited
+            .cmp(&a.is_unlimited)
+            .then_with(|| a.spender_label.is_some().cmp(&b.spender_label.is_some()))
+            .then_with(|| b.approved_block.cmp(&a.approved_block))
+            .then_with(|| (&a.token_contract, &a.spender).cmp(&(&b.token_contract, &b.spender)))
+    });
+}
+
+/// Current non-zero allowances behind a set of Approval logs, riskiest first
+pub async fn read_approvals<R, Fut>(
+    rpc: &R,
+    network_id: &str,
+    owners: &[String],
+    logs: &[Value],
+    now: i64,
+) -> Result<Vec<TokenApproval>, String>
+where
+    R: Fn(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    let owners = owners.iter().map(|o| normalize_address(o)).collect::<Result<Vec<_>, _>>()?;
+    let mut metadata: HashMap<String, (Option<String>, Option<u32>)> = HashMap::new();
+    let mut approvals = Vec::new();
+
+    for ((token, owner, spender), approved_block) in approval_candidates(logs, &owners) {
+        let allowance = read_allowance(rpc, &token, &owner, &spender).await?;
+        if allowance == [0u8; 32] {
+            continue;
+        }
+        if !metadata.contains_key(&token) {
+            let token_metadata = read_token_metadata(rpc, &token).await;
+            metadata.insert(token.clone(), token_metadata);
+        }
+        let (token_symbol, token_decimals) = metadata[&token].clone();
+        approvals.push(TokenApproval {
+            network_id: network_id.to_string(),
+            spender_label: spender_label(&spender).map(str::to_string),
+            owner,
+            token_contract: token,
+            spender,
+            allowance: word_hex(&allowance),
+            is_unlimited: is_unlimited(&allowance),
+            token_symbol,
+            token_decimals,
+            approved_block,
+            updated_at: now,
+        });
+    }
+
+    sort_by_risk(&mut approvals);
+    Ok(approvals)
+}
+
+/// Find the current allowances granted by `owners`.
+///
+/// Approval events come from eth_getLogs, or from `indexer` (called per owner)
+/// when the RPC refuses the query. `indexer` returning `Ok(None)` means the
+/// network has no indexer, which makes the scan unsupported.
+pub async fn scan_approvals<R, Fut, I, IFut>(
+    rpc: R,
+    indexer: I,
+    network_id: &str,
+    owners: &[String],
+    now: i64,
+) -> Result<ApprovalScan, String>
+where
+    R: Fn(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+    I: Fn(String) -> IFut,
+    IFut: Future<Output = Result<Option<Vec<Value>>, String>>,
+{
+    let owners = owners.iter().map(|o| normalize_address(o)).collect::<Result<Vec<_>, _>>()?;
+    if owners.is_empty() {
+        return Ok(ApprovalScan::Found { source: LogSource::Rpc, approvals: Vec::new() });
+    }
+
+    let rpc_logs = match rpc("eth_getLogs", json!([approval_log_filter(&owners)?])).await {
+        Ok(Value::Array(logs)) => Ok(logs),
+        Ok(other) => Err(format!("Unexpected eth_getLogs result: {}", other)),
+        Err(e) => Err(e),
+    };
+    let (source, logs) = match rpc_logs {
+        Ok(logs) => (LogSource::Rpc, logs),
+        Err(rpc_error) => {
+            log::info!("🔎 Log query unavailable on {} RPC ({}), trying the indexer", network_id, rpc_error);
+            let mut logs = Vec::new();
+            for owner in &owners {
+                match indexer(owner.clone()).await? {
+                    Some(owner_logs) => logs.extend(owner_logs),
+                    None => {
+                        return Ok(ApprovalScan::NotSupported {
+                            reason: format!(
+                                "The {} RPC does not support log queries ({}) and no indexer is configured",
+                                network_id, rpc_error
+                            ),
+                        })
+                    }
+                }
+            }
+            (LogSource::Indexer, logs)
+        }
+    };
+
+    let approvals = read_approvals(&rpc, network_id, &owners, &logs, now).await?;
+    Ok(ApprovalScan::Found { source, approvals })
+}
+
+/// Calldata for approve(spender, amount)
+pub fn approve_calldata(spender: &str, amount: &Word) -> Result<String, String> {
+    Ok(format!("0x{}{}{}", APPROVE_SELECTOR, address_word(spender)?, hex::encode(amount)))
+}
+
+/// approve() calls that move an allowance from `current` to `target`. Tokens
+/// in ZERO_FIRST_TOKENS get a reset to zero first when both are non-zero.
+pub fn approval_change_calls(
+    network_id: &str,
+    token: &str,
+    spender: &str,
+    current: &Word,
+    target: &Word,
+) -> Result<Vec<String>, String> {
+    let zero = [0u8; 32];
+    let mut calls = Vec::new();
+    if *current != zero && *target != zero && requires_zero_first(network_id, token) {
+        calls.push(approve_calldata(spender, &zero)?);
+    }
+    calls.push(approve_calldata(spender, target)?);
+    Ok(calls)
+}
+
+/// Fee parameters for an EVM transaction, in wei
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EvmFees {
+    Legacy { gas_price: u128 },
+    Eip1559 { max_fee_per_gas: u128, max_priority_fee_per_gas: u128 },
+}
+
+impl EvmFees {
+    pub fn max_fee_per_gas(&self) -> u128 {
+        match self {
+            EvmFees::Legacy { gas_price } => *gas_price,
+            EvmFees::Eip1559 { max_fee_per_gas, .. } => *max_fee_per_gas,
+        }
+    }
+}
+
+/// A contract call ready for the device to sign
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct PlannedEvmTx {
+    pub chain_id: u64,
+    pub from: String,
+    pub to: String,
+    /// 0x-prefixed calldata
+    pub data: String,
+    pub nonce: u64,
+    pub gas_limit: u64,
+    pub fees: EvmFees,
+}
+
+impl PlannedEvmTx {
+    /// Worst-case fee in wei: the whole gas limit at the maximum fee per gas
+    pub fn max_fee_wei(&self) -> Result<u128, String> {
+        (self.gas_limit as u128)
+            .checked_mul(self.fees.max_fee_per_gas())
+            .ok_or_else(|| format!("Fee of {} gas at {} wei overflows", self.gas_limit, self.fees.max_fee_per_gas()))
+    }
+}
+
+/// Gas estimate plus GAS_HEADROOM_PERCENT
+fn with_gas_headroom(estimate: u64) -> Result<u64, String> {
+    estimate
+        .checked_mul(GAS_HEADROOM_PERCENT)
+        .and_then(|headroom| estimate.checked_add(headroom / 100))
+        .ok_or_else(|| format!("Gas estimate out of range: {}", estimate))
+}
+
+/// Everything shown before a revocation is signed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct RevocationPlan {
+    pub owner: String,
+    pub token_contract: String,
+    pub spender: String,
+    pub spender_label: Option<String>,
+    /// Allowance being revoked, raw uint256 hex
+    pub current_allowance: String,
+    pub transactions: Vec<PlannedEvmTx>,
+    /// Worst-case network fee for all transactions, in native units
+    pub max_network_fee: String,
+}
+
+async fn fetch_fees<R, Fut>(rpc: &R, supports_eip1559: bool) -> Result<EvmFees, String>
+where
+    R: Fn(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    if supports_eip1559 {
+        let block = rpc("eth_getBlockByNumber", json!(["latest", false])).await?;
+        let base_fee = parse_quantity(block.get("baseFeePerGas").unwrap_or(&Value::Null))?;
+        let tip = parse_quantity(&rpc("eth_maxPriorityFeePerGas", json!([])).await?)?;
+        // Room for the base fee to double before the transaction stalls
+        return Ok(EvmFees::Eip1559 {
+            max_fee_per_gas: base_fee.saturating_mul(2).saturating_add(tip),
+            max_priority_fee_per_gas: tip,
+        });
+    }
+    Ok(EvmFees::Legacy { gas_price: parse_quantity(&rpc("eth_gasPrice", json!([])).await?)? })
+}
+
+/// Plan approve(spender, 0) from `owner`; None when there is nothing to revoke
+pub async fn plan_revocation<R, Fut>(
+    rpc: &R,
+    network_id: &str,
+    chain_id: u64,
+    supports_eip1559: bool,
+    owner: &str,
+    token: &str,
+    spender: &str,
+) -> Result<Option<RevocationPlan>, String>
+where
+    R: Fn(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    let owner = normalize_address(owner)?;
+    let token = normalize_address(token)?;
+    let spender = normalize_address(spender)?;
+
+    let current = read_allowance(rpc, &token, &owner, &spender).await?;
+    if current == [0u8; 32] {
+        return Ok(None);
+    }
+    let calls = approval_change_calls(network_id, &token, &spender, &current, &[0u8; 32])?;
+
+    let nonce = parse_quantity(&rpc("eth_getTransactionCount", json!([owner, "pending"])).await?)?;
+    let nonce = u64::try_from(nonce).map_err(|_| format!("Nonce out of range: {}", nonce))?;
+    let fees = fetch_fees(rpc, supports_eip1559).await?;
+
+    let mut transactions = Vec::new();
+    let mut max_fee_wei: u128 = 0;
+    for (i, data) in calls.into_iter().enumerate() {
+        let estimate = parse_quantity(&rpc("eth_estimateGas", json!([{ "from": owner, "to": token, "data": data }])).await?)?;
+        let estimate = u64::try_from(estimate).map_err(|_| format!("Gas estimate out of range: {}", estimate))?;
+        let tx = PlannedEvmTx {
+            chain_id,
+            from: owner.clone(),
+            to: token.clone(),
+            data,
+            nonce: nonce.checked_add(i as u64).ok_or_else(|| format!("Nonce out of range: {}", nonce))?,
+            gas_limit: with_gas_headroom(estimate)?,
+            fees,
+        };
+        max_fee_wei = max_fee_wei
+            .checked_add(tx.max_fee_wei()?)
+            .ok_or_else(|| "Total network fee overflows".to_string())?;
+        transactions.push(tx);
+    }
+
+    Ok(Some(RevocationPlan {
+        spender_label: spender_label(&spender).map(str::to_string),
+        owner,
+        token_contract: token,
+        spender,
+        current_allowance: word_hex(&current),
+        transactions,
+        max_network_fee: format_units(max_fee_wei, 18),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::{ready, Ready};
+
+    const OWNER: &str = "0x1111111111111111111111111111111111111111";
+    const OTHER: &str = "0x9999999999999999999999999999999999999999";
+    const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+    const USDT: &str = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+    const PERMIT2: &str = "0x000000000022d473030f116ddee9f6b43ac78ba3";
+    const UNKNOWN: &str = "0x2222222222222222222222222222222222222222";
+
+    fn topic(address: &str) -> String {
+        address_topic(address).unwrap()
+    }
+
+    fn approval_log(token: &str, owner: &str, spender: &str, block: u64) -> Value {
+        json!({
+            "address": token,
+            "topics": [APPROVAL_TOPIC, topic(owner), topic(spender)],
+            "data": "0x",
+            "blockNumber": format!("0x{:x}", block),
+        })
+    }
+
+    fn uint(value: u128) -> String {
+        format!("0x{:064x}", value)
+    }
+
+    fn abi_string(text: &str) -> String {
+        format!("0x{:064x}{:064x}{:0<64}", 32, text.len(), hex::encode(text))
+    }
+
+    /// Canned node: allowances keyed by (token, spender), plus token metadata and fee data
+    fn mock_rpc(
+        logs: Option<Vec<Value>>,
+        allowances: Vec<(&'static str, &'static str, String)>,
+    ) -> impl Fn(&'static str, Value) -> Ready<Result<Value, String>> {
+        move |method, params| {
+            let result = match method {
+                "eth_getLogs" => match &logs {
+                    Some(logs) => Ok(Value::Array(logs.clone())),
+                    None => Err("RPC error -32601: the method eth_getLogs does not exist".to_string()),
+                },
+                "eth_call" => {
+                    let to = params[0]["to"].as_str().unwrap().to_string();
+                    let data = params[0]["data"].as_str().unwrap().to_string();
+                    if data.starts_with(&format!("0x{}", ALLOWANCE_SELECTOR)) {
+                        let spender = format!("0x{}", &data[data.len() - 40..]);
+                        Ok(json!(allowances
+                            .iter()
+                            .find(|(t, s, _)| *t == to && *s == spender)
+                            .map(|(_, _, v)| v.clone())
+                            .unwrap_or_else(|| uint(0))))
+                    } else if data == format!("0x{}", SYMBOL_SELECTOR) {
+                        Ok(json!(if to == USDC { abi_string("USDC") } else { abi_string("USDT") }))
+                    } else if data == format!("0x{}", DECIMALS_SELECTOR) {
+                        Ok(json!(uint(6)))
+                    } else {
+                        Err(format!("unexpected call {}", data))
+                    }
+                }
+                "eth_getTransactionCount" => Ok(json!("0x7")),
+                "eth_estimateGas" => Ok(json!("0xb3b0")), // 46000
+                "eth_getBlockByNumber" => Ok(json!({ "baseFeePerGas": "0x3b9aca00" })), // 1 gwei
+                "eth_maxPriorityFeePerGas" => Ok(json!("0x77359400")), // 2 gwei
+                "eth_gasPrice" => Ok(json!("0x12a05f200")), // 5 gwei
+                other => Err(format!("unexpected method {}", other)),
+            };
+            ready(result)
+        }
+    }
+
+    fn no_indexer(_: String) -> Ready<Result<Option<Vec<Value>>, String>> {
+        ready(Ok(None))
+    }
+
+    #[tokio::test]
+    async fn test_scan_reads_current_allowances_riskiest_first() {
+        let logs = vec![
+            approval_log(USDC, OWNER, PERMIT2, 100),
+            approval_log(USDC, OWNER, PERMIT2, 150),
+            approval_log(USDC, OWNER, UNKNOWN, 120),
+            approval_log(USDT, OWNER, UNKNOWN, 130),
+            // Spent down to zero since
+            approval_log(USDT, OWNER, PERMIT2, 140),
+            // Someone else's approval and an ERC-721 approval
+            approval_log(USDC, OTHER, UNKNOWN, 160),
+            json!({ "address": USDC, "topics": [APPROVAL_TOPIC, topic(OWNER), topic(UNKNOWN), uint(5)], "blockNumber": "0x1" }),
+        ];
+        let rpc = mock_rpc(Some(logs), vec![
+            (USDC, PERMIT2, format!("0x{}", "f".repeat(64))),
+            (USDC, UNKNOWN, uint(2_500_000)),
+            (USDT, UNKNOWN, format!("0x{}", "f".repeat(62))),
+        ]);
+
+        let scan = scan_approvals(rpc, no_indexer, "eip155:1", &[OWNER.to_string()], 42)
+            .await
+            .unwrap();
+        let ApprovalScan::Found { source, approvals } = scan else { panic!("expected approvals") };
+        assert_eq!(source, LogSource::Rpc);
+        assert_eq!(approvals.len(), 3);
+
+        // Unlimited to an unknown spender, then unlimited to Permit2, then the limited one
+        assert_eq!((approvals[0].token_contract.as_str(), approvals[0].spender.as_str()), (USDT, UNKNOWN));
+        assert!(approvals[0].is_unlimited);
+        assert_eq!(approvals[1].spender_label.as_deref(), Some("Uniswap Permit2"));
+        assert_eq!(approvals[1].approved_block, Some(150));
+        assert!(!approvals[2].is_unlimited);
+        assert_eq!(approvals[2].token_symbol.as_deref(), Some("USDC"));
+        assert_eq!(format_allowance(&approvals[2]), "2.5");
+        assert_eq!(format_allowance(&approvals[1]), "unlimited");
+        assert!(approvals.iter().all(|a| a.owner == OWNER && a.updated_at == 42));
+    }
+
+    #[tokio::test]
+    async fn test_scan_falls_back_to_indexer() {
+        let rpc = mock_rpc(None, vec![(USDC, UNKNOWN, uint(1))]);
+        let indexer = |owner: String| {
+            assert_eq!(owner, OWNER);
+            ready(Ok(Some(vec![approval_log(USDC, OWNER, UNKNOWN, 10)])))
+        };
+        let scan = scan_approvals(&rpc, indexer, "eip155:1", &[OWNER.to_string()], 0).await.unwrap();
+        let ApprovalScan::Found { source, approvals } = scan else { panic!("expected approvals") };
+        assert_eq!(source, LogSource::Indexer);
+        assert_eq!(approvals.len(), 1);
+
+        let scan = scan_approvals(&rpc, no_indexer, "eip155:1", &[OWNER.to_string()], 0).await.unwrap();
+        assert!(matches!(scan, ApprovalScan::NotSupported { reason } if reason.contains("-32601")));
+    }
+
+    #[test]
+    fn test_indexer_response() {
+        let found = json!({ "status": "1", "message": "OK", "result": [approval_log(USDC, OWNER, UNKNOWN, 1)] });
+        assert_eq!(indexer_logs_from_response(&found).unwrap().len(), 1);
+        let empty = json!({ "status": "0", "message": "No records found", "result": [] });
+        assert!(indexer_logs_from_response(&empty).unwrap().is_empty());
+        let limited = json!({ "status": "0", "message": "NOTOK", "result": "Max rate limit reached" });
+        assert!(indexer_logs_from_response(&limited).unwrap_err().contains("rate limit"));
+    }
+
+    #[tokio::test]
+    async fn test_plan_revocation_eip1559() {
+        let rpc = mock_rpc(None, vec![(USDC, PERMIT2, format!("0x{}", "f".repeat(64)))]);
+        let plan = plan_revocation(&rpc, "eip155:1", 1, true, OWNER, USDC, PERMIT2).await.unwrap().unwrap();
+
+        assert_eq!(plan.spender_label.as_deref(), Some("Uniswap Permit2"));
+        let [tx] = plan.transactions.as_slice() else { panic!("expected one transaction") };
+        assert_eq!(tx.to, USDC);
+        assert_eq!(tx.nonce, 7);
+        assert_eq!(tx.gas_limit, 55_200);
+        assert_eq!(tx.data, format!("0x095ea7b3{}{}", address_word(PERMIT2).unwrap(), "0".repeat(64)));
+        assert_eq!(tx.fees, EvmFees::Eip1559 { max_fee_per_gas: 4_000_000_000, max_priority_fee_per_gas: 2_000_000_000 });
+        assert_eq!(plan.max_network_fee, "0.0002208");
+
+        // Nothing to revoke
+        assert!(plan_revocation(&rpc, "eip155:1", 1, false, OWNER, USDC, UNKNOWN).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_plan_revocation_legacy_fees() {
+        let rpc = mock_rpc(None, vec![(USDT, UNKNOWN, uint(10))]);
+        let plan = plan_revocation(&rpc, "eip155:1", 1, false, OWNER, USDT, UNKNOWN).await.unwrap().unwrap();
+        // Revoking goes straight to zero even on zero-first tokens
+        assert_eq!(plan.transactions.len(), 1);
+        assert_eq!(plan.transactions[0].fees, EvmFees::Legacy { gas_price: 5_000_000_000 });
+    }
+
+    #[test]
+    fn test_zero_first_tokens_reset_before_changing() {
+        let current = parse_word(&uint(10)).unwrap();
+        let target = parse_word(&uint(5)).unwrap();
+        let zero = [0u8; 32];
+
+        let calls = approval_change_calls("eip155:1", USDT, UNKNOWN, &current, &target).unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0], approve_calldata(UNKNOWN, &zero).unwrap());
+        assert_eq!(calls[1], approve_calldata(UNKNOWN, &target).unwrap());
+
+        assert_eq!(approval_change_calls("eip155:1", USDC, UNKNOWN, &current, &target).unwrap().len(), 1);
+        assert_eq!(approval_change_calls("eip155:137", USDT, UNKNOWN, &current, &target).unwrap().len(), 1);
+        assert_eq!(approval_change_calls("eip155:1", USDT, UNKNOWN, &current, &zero).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_fee_arithmetic_is_checked() {
+        assert_eq!(with_gas_headroom(50_000), Ok(60_000));
+        assert!(with_gas_headroom(u64::MAX / 10).is_err());
+
+        let mut tx = PlannedEvmTx {
+            chain_id: 1,
+            from: OWNER.to_string(),
+            to: USDT.to_string(),
+            data: "0x".to_string(),
+            nonce: 0,
+            gas_limit: 60_000,
+            fees: EvmFees::Legacy { gas_price: 5_000_000_000 },
+        };
+        assert_eq!(tx.max_fee_wei(), Ok(300_000_000_000_000));
+        tx.fees = EvmFees::Legacy { gas_price: u128::MAX };
+        assert!(tx.max_fee_wei().is_err());
+    }
+
+    #[test]
+    fn test_word_helpers() {
+        assert_eq!(word_to_u128(&parse_word("0x10").unwrap()), Some(16));
+        assert!(is_unlimited(&parse_word(&format!("0x1{}", "0".repeat(32))).unwrap()));
+        assert!(!is_unlimited(&parse_word(&format!("0x{}", "f".repeat(32))).unwrap()));
+        assert!(parse_word("0x").is_err());
+        assert_eq!(decode_abi_string(&abi_string("DAI")).as_deref(), Some("DAI"));
+        // bytes32 symbol
+        assert_eq!(decode_abi_string(&format!("0x{:0<64}", hex::encode("MKR"))).as_deref(), Some("MKR"));
+        assert!(normalize_address("0x123").is_err());
+    }
+}
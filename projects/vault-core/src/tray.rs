@@ -0,0 +1,242 @@
+// tray.rs - Compact device/portfolio state for the OS tray (menubar) menu
+//
+// Assembled from the device registry and cached portfolio dashboards only, so
+// refreshing the tray never sends a message to a device.
+
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use keepkey_db::Database;
+
+/// What a connected device needs, most urgent first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum TrayDeviceState {
+    Bootloader,
+    NeedsSetup,
+    Locked,
+    Ready,
+}
+
+impl TrayDeviceState {
+    pub fn label(self) -> &'static str {
+        match self {
+            TrayDeviceState::Bootloader => "Bootloader mode",
+            TrayDeviceState::NeedsSetup => "Needs setup",
+            TrayDeviceState::Locked => "Locked",
+            TrayDeviceState::Ready => "Unlocked",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct TrayDevice {
+    pub device_id: String,
+    pub label: String,
+    pub state: TrayDeviceState,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct TraySummary {
+    pub devices: Vec<TrayDevice>,
+    /// Sum of cached dashboard totals for the connected devices' active wallets
+    pub portfolio_total_usd: Option<f64>,
+}
+
+impl TraySummary {
+    /// "Portfolio: $1,234.56", or a placeholder before any dashboard is cached
+    pub fn portfolio_line(&self) -> String {
+        match self.portfolio_total_usd {
+            Some(total) => format!("Portfolio: ${}", format_usd(total)),
+            None => "Portfolio: not synced".to_string(),
+        }
+    }
+
+    pub fn tooltip(&self) -> String {
+        match self.devices.len() {
+            0 => "KeepKey Vault - no device connected".to_string(),
+            1 => format!("KeepKey Vault - {} ({})", self.devices[0].label, self.devices[0].state.label()),
+            n => format!("KeepKey Vault - {} devices", n),
+        }
+    }
+}
+
+/// Two decimals with thousands separators
+fn format_usd(value: f64) -> String {
+    let cents = (value * 100.0).round() as i64;
+    let whole = (cents / 100).abs().to_string();
+    let mut grouped = String::new();
+    for (i, c) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, grouped, (cents % 100).abs())
+}
+
+/// State of a registry entry (as returned by `Database::get_device_registry`)
+fn device_state(record: &serde_json::Value) -> TrayDeviceState {
+    let flag = |key: &str| record.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+    // pinCached is only known once features have been stored for the device
+    let pin_cached = record
+        .get("features")
+        .and_then(|f| f.as_str())
+        .and_then(|f| serde_json::from_str::<serde_json::Value>(f).ok())
+        .and_then(|f| f.get("pinCached").and_then(|v| v.as_bool()))
+        .unwrap_or(false);
+
+    if flag("bootloader_mode") {
+        TrayDeviceState::Bootloader
+    } else if !flag("initialized") || !flag("setup_complete") {
+        TrayDeviceState::NeedsSetup
+    } else if flag("pin_protection") && !pin_cached {
+        TrayDeviceState::Locked
+    } else {
+        TrayDeviceState::Ready
+    }
+}
+
+fn device_label(record: &serde_json::Value, device_id: &str) -> String {
+    record
+        .get("label")
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| {
+            let suffix: String = device_id.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+            format!("KeepKey {}", suffix)
+        })
+}
+
+/// Build the summary for the connected devices.
+///
+/// `dashboard_totals` maps device_id to its active wallet's cached total;
+/// unparseable totals are ignored rather than shown as zero.
+pub fn assemble_tray_summary(
+    registry: &[serde_json::Value],
+    connected: &HashSet<String>,
+    dashboard_totals: &HashMap<String, String>,
+) -> TraySummary {
+    let mut devices: Vec<TrayDevice> = registry
+        .iter()
+        .filter_map(|record| {
+            let device_id = record.get("device_id")?.as_str()?;
+            if !connected.contains(device_id) {
+                return None;
+            }
+            Some(TrayDevice {
+                device_id: device_id.to_string(),
+                label: device_label(record, device_id),
+                state: device_state(record),
+            })
+        })
+        .collect();
+    devices.sort_by(|a, b| a.state.cmp(&b.state).then_with(|| a.label.cmp(&b.label)));
+
+    let totals: Vec<f64> = devices
+        .iter()
+        .filter_map(|d| dashboard_totals.get(&d.device_id)?.parse::<f64>().ok())
+        .collect();
+    let portfolio_total_usd = if totals.is_empty() { None } else { Some(totals.iter().sum()) };
+
+    TraySummary { devices, portfolio_total_usd }
+}
+
+/// Read the registry and dashboard caches for the connected devices
+pub async fn get_tray_summary(
+    db: &Database,
+    connected: &HashSet<String>,
+    active_wallets: &HashMap<String, String>,
+) -> Result<TraySummary, String> {
+    let registry = db.get_device_registry().await.map_err(|e| format!("Database error: {}", e))?;
+
+    let mut totals = HashMap::new();
+    for device_id in connected {
+        let fingerprint = active_wallets.get(device_id).map(String::as_str).unwrap_or("");
+        if let Some(total) = db
+            .get_dashboard_total_usd(device_id, fingerprint)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?
+        {
+            totals.insert(device_id.clone(), total);
+        }
+    }
+
+    Ok(assemble_tray_summary(&registry, connected, &totals))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(device_id: &str, label: Option<&str>, pin_cached: Option<bool>) -> serde_json::Value {
+        json!({
+            "device_id": device_id,
+            "label": label,
+            "bootloader_mode": false,
+            "initialized": true,
+            "setup_complete": true,
+            "pin_protection": true,
+            "features": pin_cached.map(|cached| json!({ "pinCached": cached }).to_string()),
+        })
+    }
+
+    #[test]
+    fn test_summary_lists_connected_devices_by_urgency() {
+        let mut needs_setup = record("dev3", Some("Spare"), None);
+        needs_setup["setup_complete"] = json!(false);
+        let registry = vec![
+            record("dev1", Some("Main"), Some(true)),
+            record("dev2", None, Some(false)),
+            needs_setup,
+            record("offline", Some("Drawer"), Some(true)),
+        ];
+        let connected: HashSet<String> = ["dev1", "dev2", "dev3"].iter().map(|s| s.to_string()).collect();
+
+        let summary = assemble_tray_summary(&registry, &connected, &HashMap::new());
+        let states: Vec<_> = summary.devices.iter().map(|d| (d.label.as_str(), d.state)).collect();
+        assert_eq!(states, vec![
+            ("Spare", TrayDeviceState::NeedsSetup),
+            ("KeepKey dev2", TrayDeviceState::Locked),
+            ("Main", TrayDeviceState::Ready),
+        ]);
+        assert_eq!(summary.portfolio_total_usd, None);
+        assert_eq!(summary.portfolio_line(), "Portfolio: not synced");
+        assert_eq!(summary.tooltip(), "KeepKey Vault - 3 devices");
+    }
+
+    #[test]
+    fn test_portfolio_total_sums_connected_devices() {
+        let registry = vec![record("dev1", Some("Main"), Some(true)), record("dev2", Some("Travel"), None)];
+        let connected: HashSet<String> = ["dev1", "dev2"].iter().map(|s| s.to_string()).collect();
+        let totals: HashMap<String, String> = [
+            ("dev1".to_string(), "1234.5".to_string()),
+            ("dev2".to_string(), "not-a-number".to_string()),
+            ("offline".to_string(), "999".to_string()),
+        ].into_iter().collect();
+
+        let summary = assemble_tray_summary(&registry, &connected, &totals);
+        assert_eq!(summary.portfolio_total_usd, Some(1234.5));
+        assert_eq!(summary.portfolio_line(), "Portfolio: $1,234.50");
+    }
+
+    #[test]
+    fn test_single_device_tooltip_and_empty_state() {
+        let registry = vec![record("dev1", Some("Main"), Some(false))];
+        let connected: HashSet<String> = ["dev1".to_string()].into_iter().collect();
+        let summary = assemble_tray_summary(&registry, &connected, &HashMap::new());
+        assert_eq!(summary.tooltip(), "KeepKey Vault - Main (Locked)");
+
+        let empty = assemble_tray_summary(&registry, &HashSet::new(), &HashMap::new());
+        assert!(empty.devices.is_empty());
+        assert_eq!(empty.tooltip(), "KeepKey Vault - no device connected");
+        assert_eq!(format_usd(1_000_000.0), "1,000,000.00");
+        assert_eq!(format_usd(0.456), "0.46");
+    }
+}
@@ -0,0 +1,328 @@
+// tx_tracker.rs - Confirmation and reorg tracking for cached transactions
+//
+// Each pass looks up every tracked transaction on a network: pending ones
+// until they confirm, confirmed ones until they are finality_depth blocks
+// deep. Until then a reorg can undo the confirmation, so a confirmed
+// transaction that is found in a different block is moved there, one back in
+// the mempool reverts to pending, and one the chain no longer knows is marked
+// 'reorged'. Past the finality depth a transaction is finalized and never
+// looked up again, which keeps each pass bounded by recent activity.
+//
+// A reorged-out send stops counting as pending outgoing, which returns its
+// inputs to the spendable balance; the caller refreshes the portfolio of
+// every affected wallet so received funds that vanished are dropped too.
+
+use std::collections::HashMap;
+use std::future::Future;
+use serde::Serialize;
+use keepkey_db::Database;
+use crate::paths::{network_family, NetworkFamily};
+
+/// Emitted for every confirmed transaction whose status changed because of a reorg
+pub const REORG_EVENT: &str = "transaction:reorged";
+
+/// Finality depth when the network has none configured
+const UTXO_FINALITY_DEPTH: i64 = 6;
+const EVM_FINALITY_DEPTH: i64 = 64;
+
+/// Where the chain currently has a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainTxStatus {
+    Confirmed { height: i64 },
+    InMempool,
+    NotFound,
+}
+
+/// A cached transaction whose recorded status changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct TxStatusChange {
+    pub device_id: String,
+    pub wallet_fingerprint: String,
+    pub txid: String,
+    pub caip: String,
+    pub old_status: String,
+    pub new_status: String,
+    pub old_block_height: Option<i64>,
+    pub new_block_height: Option<i64>,
+}
+
+/// Result of one pass over a network
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct RecheckOutcome {
+    /// Pending transactions that made it into a block
+    pub confirmed: Vec<TxStatusChange>,
+    /// Confirmed transactions moved, sent back to the mempool or dropped by a reorg
+    pub reorged: Vec<TxStatusChange>,
+    /// Transactions that passed the finality depth and are no longer tracked
+    pub finalized: u32,
+    /// Lookups that failed; those transactions are retried on the next pass
+    pub lookup_failures: u32,
+}
+
+/// Finality depth for a network: the configured value, else a default by network type
+pub fn finality_depth(network_id: &str, network_type: Option<&str>, configured: Option<i64>) -> Option<i64> {
+    if let Some(depth) = configured.filter(|d| *d > 0) {
+        return Some(depth);
+    }
+    match network_type {
+        Some("utxo") => Some(UTXO_FINALITY_DEPTH),
+        Some("evm") => Some(EVM_FINALITY_DEPTH),
+        Some(_) => None,
+        None => match network_family(network_id) {
+            Ok(NetworkFamily::Utxo { .. }) => Some(UTXO_FINALITY_DEPTH),
+            Ok(NetworkFamily::Evm { .. }) => Some(EVM_FINALITY_DEPTH),
+            Err(_) => None,
+        },
+    }
+}
+
+fn is_final(block_height: i64, tip_height: i64, depth: i64) -> bool {
+    tip_height - block_height + 1 >= depth
+}
+
+/// New (status, block height) for a tracked transaction, or None if unchanged
+fn next_state(status: &str, block_height: Option<i64>, chain: ChainTxStatus) -> Option<(&'static str, Option<i64>)> {
+    match (status, chain) {
+        ("pending", ChainTxStatus::Confirmed { height }) => Some(("confirmed", Some(height))),
+        ("pending", _) => None,
+        (_, ChainTxStatus::Confirmed { height }) if block_height == Some(height) => None,
+        (_, ChainTxStatus::Confirmed { height }) => Some(("confirmed", Some(height))),
+        (_, ChainTxStatus::InMempool) => Some(("pending", None)),
+        (_, ChainTxStatus::NotFound) => Some(("reorged", None)),
+    }
+}
+
+/// Recheck every tracked transaction on a network against the chain.
+///
+/// `lookup` is called once per txid; transactions whose lookup fails keep
+/// their status and are counted in `lookup_failures`.
+pub async fn recheck_network<L, Fut>(
+    db: &Database,
+    network_id: &str,
+    depth: i64,
+    tip_height: i64,
+    lookup: L,
+    now: i64,
+) -> Result<RecheckOutcome, String>
+where
+    L: Fn(String) -> Fut,
+    Fut: Future<Output = Result<ChainTxStatus, String>>,
+{
+    let txs = db.get_tracked_transactions(network_id).await.map_err(|e| format!("Database error: {}", e))?;
+    let mut outcome = RecheckOutcome::default();
+    let mut answers: HashMap<String, Result<ChainTxStatus, String>> = HashMap::new();
+
+    for tx in txs {
+        let status = tx.status.clone().unwrap_or_default();
+
+        // Already past the finality depth (e.g. history cached before tracking existed)
+        if status == "confirmed" && tx.block_height.is_some_and(|h| is_final(h, tip_height, depth)) {
+            db.mark_transaction_finalized(tx.id, now).await.map_err(|e| format!("Database error: {}", e))?;
+            outcome.finalized += 1;
+            continue;
+        }
+
+        if !answers.contains_key(&tx.txid) {
+            let answer = lookup(tx.txid.clone()).await;
+            answers.insert(tx.txid.clone(), answer);
+        }
+        let chain = match &answers[&tx.txid] {
+            Ok(chain) => *chain,
+            Err(e) => {
+                log::warn!("Failed to look up {} on {}: {}", tx.txid, network_id, e);
+                outcome.lookup_failures += 1;
+                continue;
+            }
+        };
+
+        let (new_status, new_height) = match next_state(&status, tx.block_height, chain) {
+            Some((new_status, new_height)) => {
+                db.set_transaction_confirmation(tx.id, new_status, new_height).await
+                    .map_err(|e| format!("Database error: {}", e))?;
+                let change = TxStatusChange {
+                    device_id: tx.device_id.clone(),
+                    wallet_fingerprint: tx.wallet_fingerprint.clone(),
+                    txid: tx.txid.clone(),
+                    caip: tx.caip.clone(),
+                    old_status: status.clone(),
+                    new_status: new_status.to_string(),
+                    old_block_height: tx.block_height,
+                    new_block_height: new_height,
+                };
+                if status == "confirmed" {
+                    outcome.reorged.push(change);
+                } else {
+                    outcome.confirmed.push(change);
+                }
+                (new_status, new_height)
+            }
+            None => (status.as_str(), tx.block_height),
+        };
+
+        if new_status == "confirmed" && new_height.is_some_and(|h| is_final(h, tip_height, depth)) {
+            db.mark_transaction_finalized(tx.id, now).await.map_err(|e| format!("Database error: {}", e))?;
+            outcome.finalized += 1;
+        }
+    }
+
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::{ready, Ready};
+    use std::sync::Mutex;
+    use keepkey_db::types::TransactionCache;
+
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93";
+    const BTC_ASSET: &str = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+
+    fn tx(txid: &str, tx_type: &str, status: &str, block_height: Option<i64>) -> TransactionCache {
+        TransactionCache {
+            id: 0,
+            device_id: "dev1".to_string(),
+            txid: txid.to_string(),
+            caip: BTC_ASSET.to_string(),
+            transaction_type: tx_type.to_string(),
+            amount: "0.1".to_string(),
+            amount_usd: None,
+            fee: Some("0.0001".to_string()),
+            fee_usd: None,
+            from_address: None,
+            to_address: None,
+            timestamp: 1,
+            block_height,
+            status: Some(status.to_string()),
+            metadata_json: None,
+            wallet_fingerprint: String::new(),
+            origin: None,
+        }
+    }
+
+    /// Chain whose answers the test rewrites between polls; counts lookups
+    struct MockChain {
+        answers: Mutex<HashMap<String, ChainTxStatus>>,
+        lookups: Mutex<u32>,
+    }
+
+    impl MockChain {
+        fn new() -> Self {
+            Self { answers: Mutex::new(HashMap::new()), lookups: Mutex::new(0) }
+        }
+
+        fn set(&self, txid: &str, status: ChainTxStatus) {
+            self.answers.lock().unwrap().insert(txid.to_string(), status);
+        }
+
+        fn lookup(&self) -> impl Fn(String) -> Ready<Result<ChainTxStatus, String>> + '_ {
+            move |txid| {
+                *self.lookups.lock().unwrap() += 1;
+                ready(self.answers.lock().unwrap().get(&txid).copied().ok_or_else(|| "node unavailable".to_string()))
+            }
+        }
+
+        fn lookups(&self) -> u32 {
+            *self.lookups.lock().unwrap()
+        }
+    }
+
+    async fn stored(db: &Database, txid: &str) -> (Option<String>, Option<i64>) {
+        let tx = db.get_transaction("dev1", "", txid, BTC_ASSET).await.unwrap().unwrap();
+        (tx.status, tx.block_height)
+    }
+
+    #[test]
+    fn test_finality_depth_defaults() {
+        assert_eq!(finality_depth(BTC, Some("utxo"), None), Some(6));
+        assert_eq!(finality_depth("eip155:1", None, None), Some(64));
+        assert_eq!(finality_depth("eip155:1", Some("evm"), Some(12)), Some(12));
+        assert_eq!(finality_depth("cosmos:cosmoshub-4", Some("cosmos"), None), None);
+        assert_eq!(finality_depth("cosmos:cosmoshub-4", None, Some(0)), None);
+    }
+
+    #[tokio::test]
+    async fn test_confirmed_send_reorged_out() {
+        let db = Database::new_in_memory().await.unwrap();
+        let chain = MockChain::new();
+        db.upsert_transaction(&tx("send1", "send", "pending", None)).await.unwrap();
+
+        chain.set("send1", ChainTxStatus::InMempool);
+        let outcome = recheck_network(&db, BTC, 6, 100, chain.lookup(), 1).await.unwrap();
+        assert_eq!(outcome, RecheckOutcome::default());
+
+        chain.set("send1", ChainTxStatus::Confirmed { height: 101 });
+        let outcome = recheck_network(&db, BTC, 6, 101, chain.lookup(), 2).await.unwrap();
+        assert_eq!(outcome.confirmed.len(), 1);
+        assert_eq!(stored(&db, "send1").await, (Some("confirmed".to_string()), Some(101)));
+
+        // The block is orphaned and the transaction is gone from the chain
+        chain.set("send1", ChainTxStatus::NotFound);
+        let outcome = recheck_network(&db, BTC, 6, 102, chain.lookup(), 3).await.unwrap();
+        assert_eq!(outcome.reorged, vec![TxStatusChange {
+            device_id: "dev1".to_string(),
+            wallet_fingerprint: String::new(),
+            txid: "send1".to_string(),
+            caip: BTC_ASSET.to_string(),
+            old_status: "confirmed".to_string(),
+            new_status: "reorged".to_string(),
+            old_block_height: Some(101),
+            new_block_height: None,
+        }]);
+        assert_eq!(stored(&db, "send1").await, (Some("reorged".to_string()), None));
+        // Its inputs are no longer held back as pending outgoing, and it is not tracked any more
+        assert!(db.get_pending_outgoing("dev1", "", BTC_ASSET).await.unwrap().is_empty());
+        assert!(db.get_tracked_transactions(BTC).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reorg_back_to_mempool_then_reconfirmed_and_finalized() {
+        let db = Database::new_in_memory().await.unwrap();
+        let chain = MockChain::new();
+        db.upsert_transaction(&tx("recv1", "receive", "confirmed", Some(200))).await.unwrap();
+
+        chain.set("recv1", ChainTxStatus::Confirmed { height: 200 });
+        let outcome = recheck_network(&db, BTC, 6, 201, chain.lookup(), 1).await.unwrap();
+        assert_eq!(outcome, RecheckOutcome::default());
+
+        chain.set("recv1", ChainTxStatus::InMempool);
+        let outcome = recheck_network(&db, BTC, 6, 201, chain.lookup(), 2).await.unwrap();
+        assert_eq!(outcome.reorged[0].new_status, "pending");
+        assert_eq!(stored(&db, "recv1").await, (Some("pending".to_string()), None));
+
+        // Mined again in a later block, deep enough to be final
+        chain.set("recv1", ChainTxStatus::Confirmed { height: 202 });
+        let outcome = recheck_network(&db, BTC, 6, 207, chain.lookup(), 3).await.unwrap();
+        assert_eq!(outcome.confirmed[0].new_block_height, Some(202));
+        assert_eq!(outcome.finalized, 1);
+
+        // Finalized transactions are not looked up again
+        let lookups = chain.lookups();
+        chain.set("recv1", ChainTxStatus::NotFound);
+        let outcome = recheck_network(&db, BTC, 6, 208, chain.lookup(), 4).await.unwrap();
+        assert_eq!(outcome, RecheckOutcome::default());
+        assert_eq!(chain.lookups(), lookups);
+        assert_eq!(stored(&db, "recv1").await, (Some("confirmed".to_string()), Some(202)));
+    }
+
+    #[tokio::test]
+    async fn test_moved_block_and_lookup_failures() {
+        let db = Database::new_in_memory().await.unwrap();
+        let chain = MockChain::new();
+        db.upsert_transaction(&tx("moved", "send", "confirmed", Some(300))).await.unwrap();
+        db.upsert_transaction(&tx("unknown", "send", "confirmed", Some(300))).await.unwrap();
+        db.upsert_transaction(&tx("old", "receive", "confirmed", Some(10))).await.unwrap();
+
+        chain.set("moved", ChainTxStatus::Confirmed { height: 301 });
+        let outcome = recheck_network(&db, BTC, 6, 302, chain.lookup(), 1).await.unwrap();
+        assert_eq!(outcome.reorged.len(), 1);
+        assert_eq!((outcome.reorged[0].old_block_height, outcome.reorged[0].new_block_height), (Some(300), Some(301)));
+        assert_eq!(outcome.lookup_failures, 1);
+        // Deep history is finalized without a lookup
+        assert_eq!(outcome.finalized, 1);
+        assert_eq!(chain.lookups(), 2);
+        assert_eq!(stored(&db, "unknown").await, (Some("confirmed".to_string()), Some(300)));
+    }
+}
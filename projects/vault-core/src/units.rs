@@ -0,0 +1,82 @@
+// units.rs - Exact decimal <-> base unit conversion
+//
+// Balances are stored as decimal strings to preserve precision; all arithmetic
+// happens on integer base units (sats, drops, uatom, wei) to avoid float error.
+
+/// Parse a decimal string like "12.345" into base units with the given decimals
+pub fn parse_units(value: &str, decimals: u32) -> Result<u128, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err("Empty amount".to_string());
+    }
+
+    let (whole, frac) = match value.split_once('.') {
+        Some((whole, frac)) => (whole, frac),
+        None => (value, ""),
+    };
+
+    if !whole.chars().all(|c| c.is_ascii_digit()) || !frac.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid amount: {}", value));
+    }
+    if frac.len() > decimals as usize {
+        // Extra digits are only acceptable if they are zeros
+        if frac[decimals as usize..].chars().any(|c| c != '0') {
+            return Err(format!("Amount {} has more than {} decimals", value, decimals));
+        }
+    }
+
+    let scale = 10u128
+        .checked_pow(decimals)
+        .ok_or_else(|| format!("Unsupported decimals: {}", decimals))?;
+    let whole: u128 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| format!("Amount too large: {}", value))?
+    };
+
+    let frac_digits: String = frac.chars().take(decimals as usize).collect();
+    let frac_value: u128 = if frac_digits.is_empty() {
+        0
+    } else {
+        let padded = format!("{:0<width$}", frac_digits, width = decimals as usize);
+        padded.parse().map_err(|_| format!("Invalid amount: {}", value))?
+    };
+
+    whole
+        .checked_mul(scale)
+        .and_then(|w| w.checked_add(frac_value))
+        .ok_or_else(|| format!("Amount too large: {}", value))
+}
+
+/// Format base units as a decimal string without trailing zeros
+pub fn format_units(value: u128, decimals: u32) -> String {
+    if decimals == 0 {
+        return value.to_string();
+    }
+    let scale = 10u128.pow(decimals);
+    let whole = value / scale;
+    let frac = value % scale;
+    if frac == 0 {
+        return whole.to_string();
+    }
+    let frac = format!("{:0>width$}", frac, width = decimals as usize);
+    format!("{}.{}", whole, frac.trim_end_matches('0'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(parse_units("1.5", 8).unwrap(), 150_000_000);
+        assert_eq!(parse_units("0.000001", 6).unwrap(), 1);
+        assert_eq!(parse_units("10", 6).unwrap(), 10_000_000);
+        assert_eq!(parse_units("1.50000000000", 8).unwrap(), 150_000_000);
+        assert!(parse_units("1.000000001", 8).is_err());
+        assert!(parse_units("-1", 8).is_err());
+        assert_eq!(format_units(150_000_000, 8), "1.5");
+        assert_eq!(format_units(1, 6), "0.000001");
+        assert_eq!(format_units(42, 0), "42");
+    }
+}
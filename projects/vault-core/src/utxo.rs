@@ -0,0 +1,189 @@
+// utxo.rs - UTXO transaction size and fee estimation
+
+use serde::{Deserialize, Serialize};
+
+/// Outputs below this many sats are rejected by relay policy
+pub const DUST_LIMIT_SATS: u64 = 546;
+
+/// Highest fee rate accepted: Bitcoin Core's default -maxfeerate of 0.1 BTC/kvB
+pub const MAX_FEE_RATE_SAT_VB: u64 = 10_000;
+
+/// Refuse a fee rate of zero or above MAX_FEE_RATE_SAT_VB
+pub fn check_fee_rate(fee_rate_sat_vb: u64) -> Result<(), String> {
+    match fee_rate_sat_vb {
+        0 => Err("Fee rate must be at least 1 sat/vB".to_string()),
+        rate if rate > MAX_FEE_RATE_SAT_VB => {
+            Err(format!("Fee rate {} sat/vB is above the {} sat/vB maximum", rate, MAX_FEE_RATE_SAT_VB))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fee of `vsize` vbytes at `fee_rate_sat_vb`
+pub fn fee_for_vsize(vsize: u64, fee_rate_sat_vb: u64) -> Result<u64, String> {
+    vsize
+        .checked_mul(fee_rate_sat_vb)
+        .ok_or_else(|| format!("Fee of {} vB at {} sat/vB overflows", vsize, fee_rate_sat_vb))
+}
+
+/// Sum of UTXO amounts
+pub fn total_sats(amounts: impl IntoIterator<Item = u64>) -> Result<u64, String> {
+    amounts
+        .into_iter()
+        .try_fold(0u64, |total, amount| total.checked_add(amount))
+        .ok_or_else(|| "Total UTXO amount overflows".to_string())
+}
+
+/// Script types used by KeepKey UTXO accounts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UtxoScriptType {
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+    P2tr,
+}
+
+impl UtxoScriptType {
+    /// Parse the script_type names used in derivation_paths
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "p2pkh" => Ok(Self::P2pkh),
+            "p2sh-p2wpkh" => Ok(Self::P2shP2wpkh),
+            "p2wpkh" => Ok(Self::P2wpkh),
+            "p2tr" => Ok(Self::P2tr),
+            other => Err(format!("Unknown script type: {}", other)),
+        }
+    }
+
+    /// Virtual size of a signed input spending this script type
+    pub fn input_vbytes(&self) -> u64 {
+        match self {
+            Self::P2pkh => 148,
+            Self::P2shP2wpkh => 91,
+            Self::P2wpkh => 68,
+            Self::P2tr => 58,
+        }
+    }
+
+    /// Virtual size of an output paying to this script type
+    pub fn output_vbytes(&self) -> u64 {
+        match self {
+            Self::P2pkh => 34,
+            Self::P2shP2wpkh => 32,
+            Self::P2wpkh => 31,
+            Self::P2tr => 43,
+        }
+    }
+
+    pub fn is_segwit(&self) -> bool {
+        !matches!(self, Self::P2pkh)
+    }
+
+    /// KeepKey InputScriptType for GetPublicKey and transaction inputs
+    pub fn input_script_type(&self) -> i32 {
+        match self {
+            Self::P2pkh => 0,      // SPENDADDRESS
+            Self::P2shP2wpkh => 4, // SPENDP2SHWITNESS
+            Self::P2wpkh => 3,     // SPENDWITNESS
+            Self::P2tr => 5,       // SPENDTAPROOT
+        }
+    }
+}
+
+/// Estimate the virtual size of a transaction
+pub fn estimate_vsize(input_type: UtxoScriptType, inputs: usize, outputs: &[UtxoScriptType]) -> u64 {
+    // version + locktime + counts, plus marker/flag rounded up for segwit
+    let overhead = if input_type.is_segwit() { 11 } else { 10 };
+    overhead
+        + inputs as u64 * input_type.input_vbytes()
+        + outputs.iter().map(|o| o.output_vbytes()).sum::<u64>()
+}
+
+/// Estimate the virtual size of a transaction whose inputs may differ in script type
+pub fn estimate_mixed_vsize(inputs: &[UtxoScriptType], outputs: &[UtxoScriptType]) -> u64 {
+    let overhead = if inputs.iter().any(|i| i.is_segwit()) { 11 } else { 10 };
+    overhead
+        + inputs.iter().map(|i| i.input_vbytes()).sum::<u64>()
+        + outputs.iter().map(|o| o.output_vbytes()).sum::<u64>()
+}
+
+/// Result of spending every selected UTXO to a single recipient
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct Sweep {
+    pub amount_sats: u64,
+    pub fee_sats: u64,
+    pub vsize: u64,
+}
+
+/// Fee-inclusive maximum send: all selected UTXOs to one output, no change
+pub fn sweep_all(
+    utxo_values: &[u64],
+    input_type: UtxoScriptType,
+    output_type: UtxoScriptType,
+    fee_rate_sat_vb: u64,
+) -> Result<Sweep, String> {
+    if utxo_values.is_empty() {
+        return Err("No UTXOs selected".to_string());
+    }
+
+    check_fee_rate(fee_rate_sat_vb)?;
+
+    let total = total_sats(utxo_values.iter().copied())?;
+    let vsize = estimate_vsize(input_type, utxo_values.len(), &[output_type]);
+    let fee_sats = fee_for_vsize(vsize, fee_rate_sat_vb)?;
+
+    let amount_sats = total
+        .checked_sub(fee_sats)
+        .filter(|amount| *amount >= DUST_LIMIT_SATS)
+        .ok_or_else(|| format!("Selected UTXOs ({} sats) cannot cover a {} sat fee", total, fee_sats))?;
+
+    Ok(Sweep { amount_sats, fee_sats, vsize })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep_all_is_fee_inclusive() {
+        let sweep = sweep_all(&[50_000, 30_000], UtxoScriptType::P2wpkh, UtxoScriptType::P2wpkh, 10).unwrap();
+        // 11 overhead + 2 * 68 inputs + 31 output
+        assert_eq!(sweep.vsize, 178);
+        assert_eq!(sweep.fee_sats, 1_780);
+        assert_eq!(sweep.amount_sats, 78_220);
+        assert_eq!(sweep.amount_sats + sweep.fee_sats, 80_000);
+    }
+
+    #[test]
+    fn test_fee_arithmetic_is_checked() {
+        assert!(check_fee_rate(0).is_err());
+        assert!(check_fee_rate(1).is_ok());
+        assert!(check_fee_rate(MAX_FEE_RATE_SAT_VB).is_ok());
+        assert!(check_fee_rate(MAX_FEE_RATE_SAT_VB + 1).is_err());
+        assert_eq!(fee_for_vsize(178, 10), Ok(1_780));
+        assert!(fee_for_vsize(u64::MAX / 2, 3).is_err());
+        assert_eq!(total_sats([1, 2, 3]), Ok(6));
+        assert!(total_sats([u64::MAX, 1]).is_err());
+    }
+
+    #[test]
+    fn test_mixed_vsize_matches_single_type() {
+        let outputs = [UtxoScriptType::P2wpkh, UtxoScriptType::P2tr];
+        for input_type in [UtxoScriptType::P2pkh, UtxoScriptType::P2wpkh] {
+            assert_eq!(estimate_mixed_vsize(&[input_type; 3], &outputs), estimate_vsize(input_type, 3, &outputs));
+        }
+        // One segwit input makes the whole transaction segwit: 11 + 148 + 68 + 31
+        assert_eq!(estimate_mixed_vsize(&[UtxoScriptType::P2pkh, UtxoScriptType::P2wpkh], &outputs[..1]), 258);
+    }
+
+    #[test]
+    fn test_sweep_rejects_dust_result() {
+        // 10 + 148 + 34 = 192 vbytes at 5 sat/vB = 960 sats of fee
+        assert!(sweep_all(&[1_200], UtxoScriptType::P2pkh, UtxoScriptType::P2pkh, 5).is_err());
+        assert!(sweep_all(&[], UtxoScriptType::P2pkh, UtxoScriptType::P2pkh, 5).is_err());
+        assert!(sweep_all(&[u64::MAX, 1], UtxoScriptType::P2pkh, UtxoScriptType::P2pkh, 5).is_err());
+        assert!(sweep_all(&[50_000], UtxoScriptType::P2pkh, UtxoScriptType::P2pkh, MAX_FEE_RATE_SAT_VB + 1).is_err());
+    }
+}
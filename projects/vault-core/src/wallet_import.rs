@@ -0,0 +1,497 @@
+// wallet_import.rs - Watch-only accounts from other hardware wallets' exports
+//
+// Users moving to KeepKey can see their old wallet's balances while they move
+// funds. Trezor Suite account exports, Ledger Live account exports and lists
+// of output descriptors are parsed into account xpubs, validated and stored as
+// an imported wallet under a synthetic fingerprint derived from the xpubs.
+// Parsing is per account: a malformed or unsupported entry is reported and
+// skipped, the rest are still imported.
+
+use std::collections::HashSet;
+use bitcoin::bip32::ExtendedPubKey;
+use bitcoin::hashes::{sha256, Hash};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use keepkey_db::{Database, ImportedAccountInput, ImportedWallet, ImportedWalletInput};
+use crate::paths::{format_derivation_path, parse_derivation_path, HARDENED};
+
+/// Export formats `import_external_wallet_export` understands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    TrezorSuite,
+    LedgerLive,
+    Descriptors,
+}
+
+impl ExportFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExportFormat::TrezorSuite => "trezor_suite",
+            ExportFormat::LedgerLive => "ledger_live",
+            ExportFormat::Descriptors => "descriptors",
+        }
+    }
+
+    /// Parse a user-supplied format; None (or "auto") means detect
+    pub fn parse(format: Option<&str>) -> Result<Option<Self>, String> {
+        match format.map(|f| f.trim().to_ascii_lowercase().replace(['-', ' '], "_")) {
+            None => Ok(None),
+            Some(f) if f.is_empty() || f == "auto" => Ok(None),
+            Some(f) if f == "trezor_suite" || f == "trezor" => Ok(Some(ExportFormat::TrezorSuite)),
+            Some(f) if f == "ledger_live" || f == "ledger" => Ok(Some(ExportFormat::LedgerLive)),
+            Some(f) if f == "descriptors" || f == "descriptor" => Ok(Some(ExportFormat::Descriptors)),
+            Some(f) => Err(format!("Unsupported export format: {}", f)),
+        }
+    }
+
+    /// Guess the format from the file contents
+    pub fn detect(text: &str) -> Self {
+        let Ok(json) = serde_json::from_str::<Value>(text) else {
+            return ExportFormat::Descriptors;
+        };
+        let entries = account_entries(&json);
+        if json.pointer("/data/accounts").is_some()
+            || entries.iter().any(|e| ledger_fields(e).get("currencyId").is_some())
+        {
+            ExportFormat::LedgerLive
+        } else if json.get("descriptors").is_some() || entries.iter().all(|e| e.is_string() || e.get("desc").is_some()) {
+            ExportFormat::Descriptors
+        } else {
+            ExportFormat::TrezorSuite
+        }
+    }
+}
+
+/// An account that could not be imported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct AccountError {
+    /// Position of the entry in the file (line for plain descriptor lists)
+    pub index: usize,
+    pub label: Option<String>,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedExport {
+    pub format: ExportFormat,
+    pub accounts: Vec<ImportedAccountInput>,
+    pub errors: Vec<AccountError>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ImportResult {
+    pub wallet: ImportedWallet,
+    /// Entries that were skipped, with the reason
+    pub errors: Vec<AccountError>,
+}
+
+/// UTXO coins that can be watched from an account xpub: (SLIP-44 coin type,
+/// CAIP-19 id, names and tickers used by exports)
+const COINS: &[(u32, &str, &[&str])] = &[
+    (0, "bip122:000000000019d6689c085ae165831e93/slip44:0", &["btc", "bitcoin"]),
+    (1, "bip122:000000000933ea01ad0ee984209779ba/slip44:1", &["test", "tbtc", "bitcoin_testnet", "testnet"]),
+    (2, "bip122:12a765e31ffd4059bada1e25190f6e98/slip44:2", &["ltc", "litecoin"]),
+    (3, "bip122:00000000001a91e3dace36e2be3bf030/slip44:3", &["doge", "dogecoin"]),
+    (5, "bip122:000007d91d1254d60e2dd1ae58038307/slip44:5", &["dash"]),
+    (145, "bip122:000000000000000000651ef99cb9fcbe/slip44:145", &["bch", "bitcoin_cash", "bcash"]),
+];
+
+/// Extended key version prefixes and the BIP32 purpose they imply, if any
+const XPUB_VERSIONS: &[([u8; 4], Option<u32>)] = &[
+    ([0x04, 0x88, 0xb2, 0x1e], None),     // xpub
+    ([0x04, 0x9d, 0x7c, 0xb2], Some(49)), // ypub
+    ([0x04, 0xb2, 0x47, 0x46], Some(84)), // zpub
+    ([0x04, 0x35, 0x87, 0xcf], None),     // tpub
+    ([0x04, 0x4a, 0x52, 0x62], Some(49)), // upub
+    ([0x04, 0x5f, 0x1c, 0xf6], Some(84)), // vpub
+    ([0x01, 0x9d, 0xa4, 0x62], None),     // Ltub
+    ([0x01, 0xb2, 0x6e, 0xf6], Some(49)), // Mtub
+    ([0x02, 0xfa, 0xca, 0xfd], None),     // dgub
+];
+
+fn coin_by_type(coin_type: u32) -> Option<&'static str> {
+    COINS.iter().find(|(t, _, _)| *t == coin_type).map(|(_, caip, _)| *caip)
+}
+
+fn coin_by_name(name: &str) -> Option<(u32, &'static str)> {
+    let name = name.trim().to_ascii_lowercase().replace(['-', ' '], "_");
+    COINS.iter().find(|(_, _, names)| names.contains(&name.as_str())).map(|(t, caip, _)| (*t, *caip))
+}
+
+fn coin_label(caip: &str) -> &'static str {
+    match COINS.iter().find(|(_, c, _)| *c == caip).map(|(t, _, _)| *t) {
+        Some(0) => "Bitcoin",
+        Some(1) => "Bitcoin Testnet",
+        Some(2) => "Litecoin",
+        Some(3) => "Dogecoin",
+        Some(5) => "Dash",
+        Some(145) => "Bitcoin Cash",
+        _ => "Account",
+    }
+}
+
+/// Check an extended public key and return (depth, purpose implied by its prefix)
+fn validate_xpub(xpub: &str) -> Result<(u8, Option<u32>), String> {
+    let data = bitcoin::base58::decode_check(xpub).map_err(|e| format!("Invalid extended public key: {}", e))?;
+    if data.len() != 78 {
+        return Err(format!("Invalid extended public key: {} bytes", data.len()));
+    }
+    let purpose = XPUB_VERSIONS
+        .iter()
+        .find(|(version, _)| data[..4] == version[..])
+        .map(|(_, purpose)| *purpose)
+        .ok_or_else(|| "Not an extended public key (private keys are never imported)".to_string())?;
+
+    // Re-encode under the standard xpub prefix so the key itself is checked
+    let mut normalized = data.clone();
+    normalized[..4].copy_from_slice(&XPUB_VERSIONS[0].0);
+    let node = ExtendedPubKey::decode(&normalized).map_err(|e| format!("Invalid extended public key: {}", e))?;
+    Ok((node.depth, purpose))
+}
+
+/// Normalize and check one account; `path` is the account path if the export gave one
+fn build_account(
+    xpub: &str,
+    path: Option<&str>,
+    coin: Option<&str>,
+    label: Option<&str>,
+    default_purpose: Option<u32>,
+) -> Result<ImportedAccountInput, String> {
+    let xpub = xpub.trim();
+    let (depth, prefix_purpose) = validate_xpub(xpub)?;
+
+    let named_coin = match coin {
+        Some(name) => Some(coin_by_name(name).ok_or_else(|| format!("Unsupported coin: {}", name))?),
+        None => None,
+    };
+
+    let path = match path {
+        Some(path) => {
+            let mut indexes = parse_derivation_path(path)?;
+            // Exports sometimes give the first receive address path; keep the account part
+            if indexes.len() == 5 {
+                indexes.truncate(3);
+            }
+            indexes
+        }
+        None => {
+            let (coin_type, _) = named_coin.ok_or_else(|| "No derivation path or coin given".to_string())?;
+            let purpose = prefix_purpose.or(default_purpose).unwrap_or(44);
+            vec![purpose | HARDENED, coin_type | HARDENED, HARDENED]
+        }
+    };
+    if path.len() != 3 || path.iter().any(|i| i & HARDENED == 0) {
+        return Err(format!("{} is not a hardened account path", format_derivation_path(&path)));
+    }
+    if depth as usize != path.len() {
+        return Err(format!("Key depth {} does not match path {}", depth, format_derivation_path(&path)));
+    }
+
+    let coin_type = path[1] & !HARDENED;
+    let caip = match named_coin {
+        Some((named_type, caip)) if named_type == coin_type => caip,
+        Some(_) => return Err(format!("Coin does not match path {}", format_derivation_path(&path))),
+        None => coin_by_type(coin_type).ok_or_else(|| format!("Unsupported coin type {}", coin_type))?,
+    };
+
+    let label = label
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .unwrap_or_else(|| format!("{} #{}", coin_label(caip), (path[2] & !HARDENED) + 1));
+
+    Ok(ImportedAccountInput {
+        label,
+        path: format_derivation_path(&path),
+        caip: caip.to_string(),
+        xpub: xpub.to_string(),
+    })
+}
+
+/// Account key and origin path from an output descriptor, e.g.
+/// `wpkh([d34db33f/84'/0'/0']xpub.../0/*)#checksum`
+fn parse_descriptor(descriptor: &str) -> Result<(String, Option<String>, Option<u32>), String> {
+    let descriptor = descriptor.split('#').next().unwrap_or(descriptor).trim();
+    let purpose = if descriptor.starts_with("sh(wpkh(") {
+        Some(49)
+    } else if descriptor.starts_with("wpkh(") {
+        Some(84)
+    } else if descriptor.starts_with("tr(") {
+        Some(86)
+    } else if descriptor.starts_with("pkh(") {
+        Some(44)
+    } else if descriptor.contains('(') {
+        return Err("Only single-key descriptors (pkh, sh(wpkh), wpkh, tr) are supported".to_string());
+    } else {
+        None
+    };
+
+    let inner = if descriptor.contains('(') {
+        descriptor.trim_start_matches(|c: char| c.is_ascii_alphanumeric() || c == '(').trim_end_matches(')')
+    } else {
+        descriptor
+    };
+    let (origin, key) = match inner.strip_prefix('[') {
+        Some(rest) => {
+            let (origin, key) = rest.split_once(']').ok_or("Unterminated key origin")?;
+            // The first origin element is the master fingerprint
+            let path = origin.split_once('/').map(|(_, path)| format!("m/{}", path));
+            (path, key)
+        }
+        None => (None, inner),
+    };
+    let key = key.split('/').next().unwrap_or(key).trim();
+    if key.is_empty() {
+        return Err("No key in descriptor".to_string());
+    }
+    Ok((key.to_string(), origin, purpose))
+}
+
+/// The list of account entries in a JSON export, wherever it sits
+fn account_entries(json: &Value) -> Vec<Value> {
+    let list = json
+        .pointer("/data/accounts")
+        .or_else(|| json.get("accounts"))
+        .or_else(|| json.get("descriptors"))
+        .unwrap_or(json);
+    match list {
+        Value::Array(items) => items.clone(),
+        Value::Object(_) => vec![list.clone()],
+        _ => Vec::new(),
+    }
+}
+
+/// Ledger Live wraps each account's fields in `data`
+fn ledger_fields(entry: &Value) -> &Value {
+    entry.get("data").filter(|d| d.is_object()).unwrap_or(entry)
+}
+
+fn str_field<'a>(entry: &'a Value, keys: &[&str]) -> Option<&'a str> {
+    keys.iter()
+        .find_map(|key| entry.pointer(key).or_else(|| entry.get(*key)).and_then(Value::as_str))
+        .filter(|s| !s.trim().is_empty())
+}
+
+fn trezor_account(entry: &Value) -> Result<ImportedAccountInput, String> {
+    let label = str_field(entry, &["label", "accountLabel", "/metadata/accountLabel", "name"]);
+    let coin = str_field(entry, &["symbol", "coin", "networkSymbol", "network"]);
+    let key = str_field(entry, &["descriptor", "xpub", "publicKey"]).ok_or("No xpub or descriptor")?;
+
+    let default_purpose = match str_field(entry, &["accountType", "type"]) {
+        Some("segwit") => Some(49),
+        Some("normal") => Some(84),
+        Some("taproot") => Some(86),
+        Some("legacy") => Some(44),
+        _ => None,
+    };
+    if key.contains('(') || key.starts_with('[') {
+        let (xpub, origin, purpose) = parse_descriptor(key)?;
+        let path = str_field(entry, &["path", "accountPath"]).map(str::to_string).or(origin);
+        build_account(&xpub, path.as_deref(), coin, label, purpose.or(default_purpose))
+    } else {
+        build_account(key, str_field(entry, &["path", "accountPath"]), coin, label, default_purpose)
+    }
+}
+
+fn ledger_account(entry: &Value) -> Result<ImportedAccountInput, String> {
+    let fields = ledger_fields(entry);
+    let label = str_field(fields, &["name", "label"]);
+    let coin = str_field(fields, &["currencyId", "currency"]);
+    let xpub = str_field(fields, &["xpub"])
+        .ok_or("No xpub; only UTXO accounts can be imported watch-only")?;
+
+    let purpose = match str_field(fields, &["derivationMode"]).unwrap_or("") {
+        "native_segwit" => Some(84),
+        "segwit" => Some(49),
+        "taproot" => Some(86),
+        "" | "legacy" => Some(44),
+        other => return Err(format!("Unsupported derivation mode: {}", other)),
+    };
+    let path = match str_field(fields, &["freshAddressPath", "path"]) {
+        Some(path) => Some(path.to_string()),
+        None => {
+            let index = fields.get("index").and_then(Value::as_u64);
+            match (coin.and_then(coin_by_name), index) {
+                (Some((coin_type, _)), Some(index)) => {
+                    Some(format!("m/{}'/{}'/{}'", purpose.unwrap_or(44), coin_type, index))
+                }
+                _ => None,
+            }
+        }
+    };
+    build_account(xpub, path.as_deref(), coin, label, purpose)
+}
+
+fn descriptor_account(descriptor: &str) -> Result<ImportedAccountInput, String> {
+    let (xpub, origin, purpose) = parse_descriptor(descriptor)?;
+    build_account(&xpub, origin.as_deref(), None, None, purpose)
+}
+
+/// Parse an export. Returns Err only if the file itself cannot be read as the
+/// format; individual bad accounts are listed in `errors`.
+pub fn parse_export(text: &str, format: Option<ExportFormat>) -> Result<ParsedExport, String> {
+    let format = format.unwrap_or_else(|| ExportFormat::detect(text));
+    let json = serde_json::from_str::<Value>(text).ok();
+
+    let results: Vec<(usize, Option<String>, Result<ImportedAccountInput, String>)> = match (format, &json) {
+        (ExportFormat::Descriptors, None) => text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(i, line)| (i + 1, None, descriptor_account(line)))
+            .collect(),
+        (_, None) => return Err(format!("{} exports are JSON; the file could not be parsed", format.as_str())),
+        (format, Some(json)) => {
+            let entries = account_entries(json);
+            if entries.is_empty() {
+                return Err("No accounts found in the export".to_string());
+            }
+            entries
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    let label = str_field(ledger_fields(entry), &["name", "label", "accountLabel"]).map(str::to_string);
+                    let result = match format {
+                        ExportFormat::TrezorSuite => trezor_account(entry),
+                        ExportFormat::LedgerLive => ledger_account(entry),
+                        ExportFormat::Descriptors => match entry.as_str().or_else(|| str_field(entry, &["desc", "descriptor"])) {
+                            Some(descriptor) => descriptor_account(descriptor),
+                            None => Err("No descriptor".to_string()),
+                        },
+                    };
+                    (i, label, result)
+                })
+                .collect()
+        }
+    };
+
+    let mut seen = HashSet::new();
+    let mut parsed = ParsedExport { format, accounts: Vec::new(), errors: Vec::new() };
+    for (index, label, result) in results {
+        match result {
+            // Descriptor exports list receive and change descriptors of each account
+            Ok(account) if !seen.insert((account.xpub.clone(), account.caip.clone())) => {}
+            Ok(account) => parsed.accounts.push(account),
+            Err(error) => parsed.errors.push(AccountError { index, label, error }),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Synthetic wallet fingerprint of an import: the first four bytes of the
+/// SHA-256 of its sorted xpubs, so re-importing the same file is detected
+pub fn synthetic_fingerprint(accounts: &[ImportedAccountInput]) -> String {
+    let mut xpubs: Vec<&str> = accounts.iter().map(|a| a.xpub.as_str()).collect();
+    xpubs.sort_unstable();
+    xpubs.dedup();
+    let digest = sha256::Hash::hash(xpubs.join("\n").as_bytes());
+    hex::encode(&digest.to_byte_array()[..4])
+}
+
+/// Parse an export and store its valid accounts as a watch-only wallet
+pub async fn import_export(
+    db: &Database,
+    text: &str,
+    format: Option<ExportFormat>,
+    source_name: Option<String>,
+) -> Result<ImportResult, String> {
+    let parsed = parse_export(text, format)?;
+    if parsed.accounts.is_empty() {
+        let reasons: Vec<String> = parsed.errors.iter().map(|e| format!("#{}: {}", e.index, e.error)).collect();
+        return Err(format!("No importable accounts ({})", reasons.join("; ")));
+    }
+
+    let input = ImportedWalletInput {
+        wallet_fingerprint: synthetic_fingerprint(&parsed.accounts),
+        source_format: parsed.format.as_str().to_string(),
+        label: source_name.clone(),
+        source_name,
+        accounts: parsed.accounts,
+    };
+    let wallet = db.save_imported_wallet(&input).await.map_err(|e| e.to_string())?;
+    Ok(ImportResult { wallet, errors: parsed.errors })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TREZOR: &str = include_str!("../tests/fixtures/wallet_import/trezor_suite.json");
+    const LEDGER: &str = include_str!("../tests/fixtures/wallet_import/ledger_live.json");
+    const DESCRIPTORS: &str = include_str!("../tests/fixtures/wallet_import/descriptors.txt");
+    const CORE_DESCRIPTORS: &str = include_str!("../tests/fixtures/wallet_import/listdescriptors.json");
+    const BTC: &str = "bip122:000000000019d6689c085ae165831e93/slip44:0";
+
+    fn paths(parsed: &ParsedExport) -> Vec<(&str, &str)> {
+        parsed.accounts.iter().map(|a| (a.path.as_str(), a.caip.as_str())).collect()
+    }
+
+    #[test]
+    fn test_trezor_suite_export() {
+        assert_eq!(ExportFormat::detect(TREZOR), ExportFormat::TrezorSuite);
+        let parsed = parse_export(TREZOR, None).unwrap();
+        assert_eq!(paths(&parsed), vec![
+            ("m/84'/0'/0'", BTC),
+            ("m/49'/0'/0'", BTC),
+            ("m/86'/0'/0'", BTC),
+        ]);
+        assert_eq!(parsed.accounts[0].label, "Savings");
+        // ETH has no xpub, and a typo'd key fails its checksum
+        assert_eq!(parsed.errors.len(), 2);
+        assert_eq!(parsed.errors[0].index, 3);
+    }
+
+    #[test]
+    fn test_ledger_live_export() {
+        assert_eq!(ExportFormat::detect(LEDGER), ExportFormat::LedgerLive);
+        let parsed = parse_export(LEDGER, None).unwrap();
+        assert_eq!(paths(&parsed), vec![
+            ("m/84'/0'/0'", BTC),
+            ("m/44'/2'/0'", "bip122:12a765e31ffd4059bada1e25190f6e98/slip44:2"),
+        ]);
+        assert_eq!(parsed.accounts[1].label, "Litecoin 1");
+        assert_eq!(parsed.errors.len(), 1);
+        assert!(parsed.errors[0].error.contains("UTXO"));
+    }
+
+    #[test]
+    fn test_descriptor_lists() {
+        let parsed = parse_export(DESCRIPTORS, None).unwrap();
+        assert_eq!(parsed.format, ExportFormat::Descriptors);
+        // Receive and change descriptors of one account collapse into one
+        assert_eq!(paths(&parsed), vec![("m/84'/0'/0'", BTC), ("m/44'/0'/0'", BTC)]);
+        assert_eq!(parsed.errors.len(), 1);
+        assert!(parsed.errors[0].error.contains("single-key"));
+
+        let core = parse_export(CORE_DESCRIPTORS, None).unwrap();
+        assert_eq!(core.format, ExportFormat::Descriptors);
+        assert_eq!(paths(&core), vec![("m/84'/0'/0'", BTC)]);
+        assert!(core.errors.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_private_keys_and_depth_mismatch() {
+        let parsed = parse_export(TREZOR, Some(ExportFormat::TrezorSuite)).unwrap();
+        let xpub = &parsed.accounts[0].xpub;
+        let err = build_account(xpub, Some("m/84'/0'"), None, None, None).unwrap_err();
+        assert!(err.contains("account path") || err.contains("depth"));
+        assert!(build_account("xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi", None, Some("btc"), None, None).is_err());
+        assert!(ExportFormat::parse(Some("Ledger Live")).unwrap() == Some(ExportFormat::LedgerLive));
+        assert!(ExportFormat::parse(Some("electrum")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import_is_stored_once() {
+        let db = Database::new_in_memory().await.unwrap();
+        let result = import_export(&db, LEDGER, None, Some("ledger.json".to_string())).await.unwrap();
+        assert_eq!(result.wallet.accounts.len(), 2);
+        assert_eq!(result.errors.len(), 1);
+        assert!(keepkey_db::imported_wallets::is_imported_device(&result.wallet.device_id));
+        assert_eq!(result.wallet.source_format, "ledger_live");
+
+        assert!(import_export(&db, LEDGER, None, None).await.unwrap_err().contains("already imported"));
+        assert!(import_export(&db, "[]", Some(ExportFormat::TrezorSuite), None).await.is_err());
+    }
+}
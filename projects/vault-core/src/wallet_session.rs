@@ -0,0 +1,228 @@
+// wallet_session.rs - Passphrase wallet sessions scoped by root fingerprint
+//
+// Each passphrase opens a different wallet on the same device. Everything the
+// vault stores per wallet is keyed by (device_id, wallet_fingerprint); the
+// passphrase itself is only forwarded to the device and never kept.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use bitcoin::bip32::ExtendedPubKey;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use keepkey_db::Database;
+use keepkey_rust::device_queue::DeviceQueueHandle;
+use keepkey_rust::messages::{self, Message};
+use crate::passphrase::Zeroizing;
+use crate::queue::{get_or_create_device_queue, DeviceQueueManager};
+
+/// m/44' - a depth-1 node whose parent fingerprint is the wallet's root fingerprint
+const FINGERPRINT_PATH: [u32; 1] = [44 | 0x8000_0000];
+
+/// The wallet currently open on a device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ActiveWallet {
+    pub wallet_fingerprint: String,
+    pub is_hidden: bool,
+}
+
+/// Active wallet per device_id
+pub type WalletSessions = Arc<Mutex<HashMap<String, ActiveWallet>>>;
+
+pub fn new_wallet_sessions() -> WalletSessions {
+    Arc::new(Mutex::new(HashMap::new()))
+}
+
+/// Fingerprint used to scope queries; empty until the device's wallet is identified
+pub async fn active_wallet_fingerprint(sessions: &WalletSessions, device_id: &str) -> String {
+    sessions
+        .lock()
+        .await
+        .get(device_id)
+        .map(|w| w.wallet_fingerprint.clone())
+        .unwrap_or_default()
+}
+
+/// Forget the session when a device disconnects (the device drops its passphrase too)
+pub async fn end_wallet_session(sessions: &WalletSessions, device_id: &str) {
+    sessions.lock().await.remove(device_id);
+}
+
+/// Forget every passphrase wallet session; returns the devices that had one.
+/// Those devices fall back to the standard wallet until the passphrase is entered again.
+pub async fn end_hidden_sessions(sessions: &WalletSessions) -> Vec<String> {
+    let mut sessions = sessions.lock().await;
+    let hidden: Vec<String> = sessions.iter()
+        .filter(|(_, wallet)| wallet.is_hidden)
+        .map(|(device_id, _)| device_id.clone())
+        .collect();
+    for device_id in &hidden {
+        sessions.remove(device_id);
+    }
+    hidden
+}
+
+/// What the device answered when its wallet was to be identified
+#[derive(Debug, Clone)]
+pub enum WalletIdentification {
+    Opened(ActiveWallet),
+    /// The device is locked and put its PIN matrix up instead; answering it
+    /// through the PIN flow lets the identification be retried
+    PinRequired(messages::PinMatrixRequest),
+}
+
+/// Root fingerprint from the device's m/44' public key
+fn root_fingerprint(public_key: messages::PublicKey) -> Result<String, String> {
+    let xpub = public_key.xpub.ok_or_else(|| "No xpub in response".to_string())?;
+    let node = ExtendedPubKey::from_str(&xpub).map_err(|e| format!("Invalid xpub from device: {}", e))?;
+    Ok(node.parent_fingerprint.to_string())
+}
+
+/// Read the root fingerprint, answering a passphrase prompt if the device
+/// asks. The inner error is the PIN matrix of a device that wants its PIN first.
+///
+/// The queue worker wipes the PassphraseAck once it is written to the device.
+async fn read_wallet_fingerprint(
+    queue: &DeviceQueueHandle,
+    passphrase: &Zeroizing<String>,
+) -> Result<Result<String, messages::PinMatrixRequest>, String> {
+    let request = Message::GetPublicKey(messages::GetPublicKey {
+        address_n: FINGERPRINT_PATH.to_vec(),
+        ecdsa_curve_name: Some("secp256k1".to_string()),
+        show_display: Some(false),
+        coin_name: Some("Bitcoin".to_string()),
+        script_type: None,
+    });
+
+    let mut response = queue.send_raw(request, true).await.map_err(|e| e.to_string())?;
+    if let Message::PassphraseRequest(_) = response {
+        let ack = Message::PassphraseAck(messages::PassphraseAck { passphrase: passphrase.to_string() });
+        response = queue.send_raw(ack, true).await.map_err(|e| e.to_string())?;
+    }
+
+    match response {
+        Message::PublicKey(public_key) => Ok(Ok(root_fingerprint(public_key)?)),
+        Message::PinMatrixRequest(request) => Ok(Err(request)),
+        Message::Failure(f) => Err(format!("Device refused: {}", f.message())),
+        other => Err(format!("Unexpected response: {:?}", other.message_type())),
+    }
+}
+
+/// Record the wallet read from the device and make it the active session
+async fn open_session(
+    db: &Database,
+    sessions: &WalletSessions,
+    device_id: &str,
+    wallet_fingerprint: String,
+    is_hidden: bool,
+) -> Result<ActiveWallet, String> {
+    db.record_wallet_session(device_id, &wallet_fingerprint, is_hidden)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let wallet = ActiveWallet { wallet_fingerprint, is_hidden };
+    sessions.lock().await.insert(device_id.to_string(), wallet.clone());
+    log::info!("👛 Device {} wallet session: {} (hidden: {})", device_id, wallet.wallet_fingerprint, is_hidden);
+    Ok(wallet)
+}
+
+/// Open a wallet session: the standard wallet when `passphrase` is empty,
+/// otherwise the hidden wallet for that passphrase.
+///
+/// Clearing the device session also purges the queue's feature/address cache,
+/// so nothing read under the previous wallet leaks into the new one. It drops
+/// the cached PIN as well, so a PIN-protected device asks for it again.
+pub async fn start_wallet_session(
+    db: &Database,
+    sessions: &WalletSessions,
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+    passphrase: Zeroizing<String>,
+) -> Result<WalletIdentification, String> {
+    crate::authenticity::require_verified_device(device_id)?;
+    let queue = get_or_create_device_queue(device_id, queue_manager).await?;
+    let is_hidden = !passphrase.is_empty();
+
+    queue
+        .send_raw(Message::ClearSession(messages::ClearSession::default()), true)
+        .await
+        .map_err(|e| format!("Failed to clear device session: {}", e))?;
+
+    let read = read_wallet_fingerprint(&queue, &passphrase).await?;
+    drop(passphrase);
+    match read {
+        Ok(wallet_fingerprint) => Ok(WalletIdentification::Opened(
+            open_session(db, sessions, device_id, wallet_fingerprint, is_hidden).await?,
+        )),
+        Err(request) => Ok(WalletIdentification::PinRequired(request)),
+    }
+}
+
+/// Identify the standard wallet of a device without touching its session.
+///
+/// Used on connect and by background work, which must not clear the PIN the
+/// user already entered. A locked device answers with its PIN matrix.
+pub async fn identify_standard_wallet(
+    db: &Database,
+    sessions: &WalletSessions,
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+) -> Result<WalletIdentification, String> {
+    crate::authenticity::require_verified_device(device_id)?;
+    let queue = get_or_create_device_queue(device_id, queue_manager).await?;
+    match read_wallet_fingerprint(&queue, &Zeroizing::new(String::new())).await? {
+        Ok(wallet_fingerprint) => Ok(WalletIdentification::Opened(
+            open_session(db, sessions, device_id, wallet_fingerprint, false).await?,
+        )),
+        Err(request) => Ok(WalletIdentification::PinRequired(request)),
+    }
+}
+
+/// Finish an identification the device paused for its PIN. `public_key` is
+/// what the device answered the PIN with: the paused request carries on once
+/// it is unlocked.
+pub async fn complete_identification(
+    db: &Database,
+    sessions: &WalletSessions,
+    device_id: &str,
+    public_key: messages::PublicKey,
+    is_hidden: bool,
+) -> Result<ActiveWallet, String> {
+    let wallet_fingerprint = root_fingerprint(public_key)?;
+    open_session(db, sessions, device_id, wallet_fingerprint, is_hidden).await
+}
+
+/// Lock a device: clearing the session drops its cached PIN and passphrase,
+/// so the wallet has to be identified again after the next unlock.
+pub async fn lock_device(
+    sessions: &WalletSessions,
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+) -> Result<(), String> {
+    let queue = get_or_create_device_queue(device_id, queue_manager).await?;
+    queue
+        .send_raw(Message::ClearSession(messages::ClearSession::default()), true)
+        .await
+        .map_err(|e| format!("Failed to lock device: {}", e))?;
+
+    end_wallet_session(sessions, device_id).await;
+    log::info!("🔒 Device {} locked", device_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root_fingerprint_is_the_parent_of_m44() {
+        // BIP32 test vector 1, m/0': a depth-1 node under master 3442193e
+        let public_key = messages::PublicKey {
+            xpub: Some("xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(root_fingerprint(public_key).unwrap(), "3442193e");
+        assert!(root_fingerprint(messages::PublicKey::default()).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+// warmup.rs - Device queue warm-up when the user comes back to the app
+//
+// The first device operation after an idle period pays for stale-handle
+// detection, queue recreation and a features round trip. When the window
+// gains focus (or the tray menu opens) the app pings connected devices whose
+// queue has been idle so those costs are paid before the user clicks
+// anything. This module decides which devices to warm and keeps latency
+// samples for the first operation after idle, split by whether a warm-up
+// preceded it.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use crate::power::{PowerProfile, PowerSaverMode, PowerSource, PowerStatus};
+
+/// A queue that completed an operation more recently than this is still warm
+pub const WARM_AFTER_IDLE: Duration = Duration::from_secs(60);
+
+/// Latency samples kept per kind
+const LATENCY_SAMPLES: usize = 50;
+
+/// Why a device was or wasn't warmed for a focus event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[serde(rename_all = "snake_case")]
+pub enum WarmupDecision {
+    Warm,
+    /// The queue completed an operation within `WARM_AFTER_IDLE`
+    RecentlyActive,
+    /// Power saver is on; warm-up is background work
+    PowerSaver,
+    /// Already warmed for this focus event
+    AlreadyWarmed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct LatencySummary {
+    pub samples: u32,
+    pub median_ms: Option<u64>,
+    pub max_ms: Option<u64>,
+}
+
+/// First-operation latency after idle, with and without a preceding warm-up
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct WarmupMetrics {
+    pub warmups_run: u32,
+    pub warmups_skipped_power_saver: u32,
+    pub warm_first_operation: LatencySummary,
+    pub cold_first_operation: LatencySummary,
+}
+
+#[derive(Default)]
+struct DeviceWarmth {
+    last_operation: Option<Instant>,
+    last_warmup: Option<Instant>,
+    warmed_for_focus: Option<u64>,
+}
+
+#[derive(Default)]
+struct WarmupState {
+    focus_epoch: u64,
+    devices: HashMap<String, DeviceWarmth>,
+    warm_latencies: VecDeque<Duration>,
+    cold_latencies: VecDeque<Duration>,
+    warmups_run: u32,
+    warmups_skipped_power_saver: u32,
+}
+
+/// Warm-up bookkeeping shared by the focus hooks and the device operations
+#[derive(Default)]
+pub struct QueueWarmup {
+    state: Mutex<WarmupState>,
+}
+
+/// Power saver in the user's sense: forced on, or automatic while on battery.
+/// Focusing the window restores the normal profile on battery, so the profile
+/// alone would never suppress a focus-triggered warm-up.
+pub fn power_saver_active(status: &PowerStatus) -> bool {
+    status.profile == PowerProfile::Reduced
+        || status.mode == PowerSaverMode::Always
+        || (status.mode == PowerSaverMode::Auto && status.source == PowerSource::Battery)
+}
+
+impl QueueWarmup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut WarmupState) -> R) -> R {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut state)
+    }
+
+    /// Start a new focus event; each device is warmed at most once per event
+    pub fn begin_focus(&self) -> u64 {
+        self.with_state(|state| {
+            state.focus_epoch += 1;
+            state.focus_epoch
+        })
+    }
+
+    /// Decide whether to warm `device_id` for focus event `focus`. A `Warm`
+    /// decision claims the event for the device, so a second hook firing for
+    /// the same event is skipped.
+    pub fn plan(&self, device_id: &str, focus: u64, power: &PowerStatus, now: Instant) -> WarmupDecision {
+        self.with_state(|state| {
+            if power_saver_active(power) {
+                state.warmups_skipped_power_saver += 1;
+                return WarmupDecision::PowerSaver;
+            }
+            let device = state.devices.entry(device_id.to_string()).or_default();
+            if device.warmed_for_focus == Some(focus) {
+                return WarmupDecision::AlreadyWarmed;
+            }
+            let last_active = device.last_operation.max(device.last_warmup);
+            if last_active.is_some_and(|t| now.duration_since(t) < WARM_AFTER_IDLE) {
+                return WarmupDecision::RecentlyActive;
+            }
+            device.warmed_for_focus = Some(focus);
+            WarmupDecision::Warm
+        })
+    }
+
+    /// A warm-up finished; it keeps the queue warm but is not a user operation
+    pub fn finish_warmup(&self, device_id: &str, now: Instant) {
+        self.with_state(|state| {
+            state.warmups_run += 1;
+            state.devices.entry(device_id.to_string()).or_default().last_warmup = Some(now);
+        })
+    }
+
+    /// A user-initiated operation completed after `latency`. The first one
+    /// after an idle period is sampled as warm when a warm-up ran since the
+    /// previous operation and recently enough to still count.
+    pub fn record_operation(&self, device_id: &str, latency: Duration, now: Instant) {
+        self.with_state(|state| {
+            let device = state.devices.entry(device_id.to_string()).or_default();
+            let started = now.checked_sub(latency).unwrap_or(now);
+            let first_after_idle = device.last_operation
+                .map_or(true, |t| started.saturating_duration_since(t) >= WARM_AFTER_IDLE);
+            let warmed = device.last_warmup.is_some_and(|w| {
+                device.last_operation.map_or(true, |t| w > t) && started.saturating_duration_since(w) < WARM_AFTER_IDLE
+            });
+            device.last_operation = Some(now);
+
+            if first_after_idle {
+                let samples = if warmed { &mut state.warm_latencies } else { &mut state.cold_latencies };
+                if samples.len() == LATENCY_SAMPLES {
+                    samples.pop_front();
+                }
+                samples.push_back(latency);
+            }
+        })
+    }
+
+    pub fn metrics(&self) -> WarmupMetrics {
+        self.with_state(|state| WarmupMetrics {
+            warmups_run: state.warmups_run,
+            warmups_skipped_power_saver: state.warmups_skipped_power_saver,
+            warm_first_operation: summarize(&state.warm_latencies),
+            cold_first_operation: summarize(&state.cold_latencies),
+        })
+    }
+}
+
+fn summarize(samples: &VecDeque<Duration>) -> LatencySummary {
+    let mut ms: Vec<u64> = samples.iter().map(|d| d.as_millis() as u64).collect();
+    ms.sort_unstable();
+    LatencySummary {
+        samples: ms.len() as u32,
+        median_ms: ms.get(ms.len() / 2).copied(),
+        max_ms: ms.last().copied(),
+    }
+}
+
+/// Process-wide tracker; device operations record into it from vault-core
+pub fn queue_warmup() -> &'static QueueWarmup {
+    static WARMUP: OnceLock<QueueWarmup> = OnceLock::new();
+    WARMUP.get_or_init(QueueWarmup::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(profile: PowerProfile, mode: PowerSaverMode, source: PowerSource) -> PowerStatus {
+        PowerStatus {
+            profile,
+            mode,
+            source,
+            window_focused: true,
+            refresh_multiplier: 1,
+            status_polling_paused: false,
+        }
+    }
+
+    fn plugged_in() -> PowerStatus {
+        status(PowerProfile::Normal, PowerSaverMode::Auto, PowerSource::Ac)
+    }
+
+    #[test]
+    fn test_warm_once_per_focus_event() {
+        let warmup = QueueWarmup::new();
+        let now = Instant::now();
+
+        let focus = warmup.begin_focus();
+        assert_eq!(warmup.plan("kk1", focus, &plugged_in(), now), WarmupDecision::Warm);
+        assert_eq!(warmup.plan("kk1", focus, &plugged_in(), now), WarmupDecision::AlreadyWarmed);
+        assert_eq!(warmup.plan("kk2", focus, &plugged_in(), now), WarmupDecision::Warm);
+
+        // A warm-up that never finished still counts for its own event, but
+        // the next focus event may try again
+        let next = warmup.begin_focus();
+        assert_eq!(warmup.plan("kk1", next, &plugged_in(), now), WarmupDecision::Warm);
+    }
+
+    #[test]
+    fn test_recent_activity_skips_warmup() {
+        let warmup = QueueWarmup::new();
+        let start = Instant::now();
+
+        warmup.record_operation("kk1", Duration::from_millis(200), start);
+        let focus = warmup.begin_focus();
+        assert_eq!(warmup.plan("kk1", focus, &plugged_in(), start + Duration::from_secs(10)), WarmupDecision::RecentlyActive);
+
+        let later = start + WARM_AFTER_IDLE + Duration::from_secs(1);
+        let focus = warmup.begin_focus();
+        assert_eq!(warmup.plan("kk1", focus, &plugged_in(), later), WarmupDecision::Warm);
+        warmup.finish_warmup("kk1", later);
+
+        // The finished warm-up keeps the device warm across the next focus
+        let focus = warmup.begin_focus();
+        assert_eq!(warmup.plan("kk1", focus, &plugged_in(), later + Duration::from_secs(5)), WarmupDecision::RecentlyActive);
+    }
+
+    #[test]
+    fn test_power_saver_suppresses_warmup() {
+        let warmup = QueueWarmup::new();
+        let now = Instant::now();
+        let focus = warmup.begin_focus();
+
+        // Focus restores the normal profile on battery; auto mode still counts
+        let on_battery = status(PowerProfile::Normal, PowerSaverMode::Auto, PowerSource::Battery);
+        assert_eq!(warmup.plan("kk1", focus, &on_battery, now), WarmupDecision::PowerSaver);
+
+        let forced = status(PowerProfile::Reduced, PowerSaverMode::Always, PowerSource::Ac);
+        assert_eq!(warmup.plan("kk1", focus, &forced, now), WarmupDecision::PowerSaver);
+
+        let never = status(PowerProfile::Normal, PowerSaverMode::Never, PowerSource::Battery);
+        assert_eq!(warmup.plan("kk1", focus, &never, now), WarmupDecision::Warm);
+
+        assert_eq!(warmup.metrics().warmups_skipped_power_saver, 2);
+    }
+
+    #[test]
+    fn test_first_operation_latency_split_by_warmth() {
+        let warmup = QueueWarmup::new();
+        let start = Instant::now();
+
+        // Cold: first operation with no warm-up
+        warmup.record_operation("kk1", Duration::from_millis(3500), start);
+        // Follow-up operations are not first operations
+        warmup.record_operation("kk1", Duration::from_millis(80), start + Duration::from_secs(5));
+
+        // Idle, warm-up on focus, then the user acts
+        let back = start + Duration::from_secs(600);
+        warmup.finish_warmup("kk1", back);
+        warmup.record_operation("kk1", Duration::from_millis(150), back + Duration::from_secs(3));
+
+        // Idle again; a stale warm-up from long ago does not count
+        let much_later = back + Duration::from_secs(3600);
+        warmup.record_operation("kk1", Duration::from_millis(3000), much_later);
+
+        let metrics = warmup.metrics();
+        assert_eq!(metrics.warmups_run, 1);
+        assert_eq!(metrics.warm_first_operation.samples, 1);
+        assert_eq!(metrics.warm_first_operation.median_ms, Some(150));
+        assert_eq!(metrics.cold_first_operation.samples, 2);
+        assert_eq!(metrics.cold_first_operation.max_ms, Some(3500));
+    }
+}